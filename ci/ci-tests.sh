@@ -47,6 +47,9 @@ echo -e "\n\nChecking and testing lightning with features"
 cargo test -p lightning --verbose --color always --features dnssec
 cargo check -p lightning --verbose --color always --features dnssec
 cargo doc -p lightning --document-private-items --features dnssec
+cargo test -p lightning --verbose --color always --features self_test test_run_self_test
+cargo test -p lightning --verbose --color always --features ptlc_experimental ptlc
+cargo doc -p lightning --document-private-items --features ptlc_experimental
 
 echo -e "\n\nChecking and testing Block Sync Clients with features"
 
@@ -64,6 +67,7 @@ cargo check -p lightning-transaction-sync --verbose --color always --features es
 cargo check -p lightning-transaction-sync --verbose --color always --features esplora-async
 cargo check -p lightning-transaction-sync --verbose --color always --features esplora-async-https
 cargo check -p lightning-transaction-sync --verbose --color always --features electrum
+cargo check -p lightning-transaction-sync --verbose --color always --features regtest,esplora-blocking,electrum
 
 if [ -z "$CI_ENV" ] && [[ -z "$BITCOIND_EXE" || -z "$ELECTRS_EXE" ]]; then
 	echo -e "\n\nSkipping testing Transaction Sync Clients due to BITCOIND_EXE or ELECTRS_EXE being unset."
@@ -77,9 +81,9 @@ else
 fi
 
 echo -e "\n\nChecking and testing lightning-persister with features"
-cargo test -p lightning-persister --verbose --color always --features tokio
-cargo check -p lightning-persister --verbose --color always --features tokio
-cargo doc -p lightning-persister --document-private-items --features tokio
+cargo test -p lightning-persister --verbose --color always --features tokio,sqlite,vss
+cargo check -p lightning-persister --verbose --color always --features tokio,sqlite,vss
+cargo doc -p lightning-persister --document-private-items --features tokio,sqlite,vss
 
 echo -e "\n\nTest Custom Message Macros"
 cargo test -p lightning-custom-message --verbose --color always
@@ -36,7 +36,9 @@ use bitcoin::WPubkeyHash;
 use lightning::blinded_path::message::{BlindedMessagePath, MessageContext, MessageForwardNode};
 use lightning::blinded_path::payment::{BlindedPaymentPath, ReceiveTlvs};
 use lightning::chain;
-use lightning::chain::chaininterface::{BroadcasterInterface, ConfirmationTarget, FeeEstimator};
+use lightning::chain::chaininterface::{
+	BroadcastContext, BroadcasterInterface, ConfirmationTarget, FeeEstimator,
+};
 use lightning::chain::channelmonitor::{ChannelMonitor, MonitorEvent};
 use lightning::chain::transaction::OutPoint;
 use lightning::chain::{
@@ -106,9 +108,9 @@ impl FeeEstimator for FuzzEstimator {
 		// always return a HighPriority feerate here which is >= the maximum Normal feerate and a
 		// Background feerate which is <= the minimum Normal feerate.
 		match conf_target {
-			ConfirmationTarget::MaximumFeeEstimate | ConfirmationTarget::UrgentOnChainSweep => {
-				MAX_FEE
-			},
+			ConfirmationTarget::MaximumFeeEstimate
+			| ConfirmationTarget::UrgentOnChainSweep
+			| ConfirmationTarget::UrgentOnChainSweepWithDeadline { .. } => MAX_FEE,
 			ConfirmationTarget::ChannelCloseMinimum
 			| ConfirmationTarget::AnchorChannelFee
 			| ConfirmationTarget::MinAllowedAnchorChannelRemoteFee
@@ -157,7 +159,7 @@ impl MessageRouter for FuzzRouter {
 
 pub struct TestBroadcaster {}
 impl BroadcasterInterface for TestBroadcaster {
-	fn broadcast_transactions(&self, _txs: &[&Transaction]) {}
+	fn broadcast_transactions(&self, _txs: &[&Transaction], _context: BroadcastContext) {}
 }
 
 pub struct VecWriter(pub Vec<u8>);
@@ -740,7 +742,11 @@ pub fn do_test<Out: Output>(data: &[u8], underlying_out: Out, anchors: bool) {
 			}
 			let network = Network::Bitcoin;
 			let best_block_timestamp = genesis_block(network).header.time;
-			let params = ChainParameters { network, best_block: BestBlock::from_network(network) };
+			let params = ChainParameters {
+				network,
+				custom_chain_hash: None,
+				best_block: BestBlock::from_network(network),
+			};
 			(
 				ChannelManager::new(
 					$fee_estimator.clone(),
@@ -33,7 +33,9 @@ use bitcoin::WPubkeyHash;
 use lightning::blinded_path::message::{BlindedMessagePath, MessageContext, MessageForwardNode};
 use lightning::blinded_path::payment::{BlindedPaymentPath, ReceiveTlvs};
 use lightning::chain;
-use lightning::chain::chaininterface::{BroadcasterInterface, ConfirmationTarget, FeeEstimator};
+use lightning::chain::chaininterface::{
+	BroadcastContext, BroadcasterInterface, ConfirmationTarget, FeeEstimator,
+};
 use lightning::chain::chainmonitor;
 use lightning::chain::transaction::OutPoint;
 use lightning::chain::{BestBlock, ChannelMonitorUpdateStatus, Confirm, Listen};
@@ -184,7 +186,7 @@ struct TestBroadcaster {
 	txn_broadcasted: Mutex<Vec<Transaction>>,
 }
 impl BroadcasterInterface for TestBroadcaster {
-	fn broadcast_transactions(&self, txs: &[&Transaction]) {
+	fn broadcast_transactions(&self, txs: &[&Transaction], _context: BroadcastContext) {
 		let owned_txs: Vec<Transaction> = txs.iter().map(|tx| (*tx).clone()).collect();
 		self.txn_broadcasted.lock().unwrap().extend(owned_txs);
 	}
@@ -597,7 +599,11 @@ pub fn do_test(mut data: &[u8], logger: &Arc<dyn Logger>) {
 
 	let network = Network::Bitcoin;
 	let best_block_timestamp = genesis_block(network).header.time;
-	let params = ChainParameters { network, best_block: BestBlock::from_network(network) };
+	let params = ChainParameters {
+		network,
+		custom_chain_hash: None,
+		best_block: BestBlock::from_network(network),
+	};
 	let channelmanager = Arc::new(ChannelManager::new(
 		fee_est.clone(),
 		monitor.clone(),
@@ -61,7 +61,7 @@ pub fn do_test(data: &[u8]) {
 		keys_manager.get_peer_storage_key(),
 	));
 	let best_block = BestBlock::from_network(network);
-	let params = ChainParameters { network, best_block };
+	let params = ChainParameters { network, custom_chain_hash: None, best_block };
 	let manager = Arc::new(ChannelManager::new(
 		Arc::clone(&fee_estimator),
 		Arc::clone(&chain_monitor),
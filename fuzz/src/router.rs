@@ -231,6 +231,8 @@ pub fn do_test<Out: test_logger::Output>(data: &[u8], out: Out) {
 							}),
 							funding_redeem_script: None,
 							channel_type: None,
+							commitment_format: None,
+							p2a_anchor_value_satoshis: None,
 							short_channel_id: Some(scid),
 							inbound_scid_alias: None,
 							outbound_scid_alias: None,
@@ -255,6 +257,7 @@ pub fn do_test<Out: test_logger::Output>(data: &[u8], out: Out) {
 							channel_shutdown_state: Some(ChannelShutdownState::NotShuttingDown),
 							pending_inbound_htlcs: Vec::new(),
 							pending_outbound_htlcs: Vec::new(),
+							protocol_error_history: Vec::new(),
 						});
 					}
 					Some(&$first_hops_vec[..])
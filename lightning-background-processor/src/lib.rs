@@ -27,8 +27,12 @@ extern crate lightning;
 extern crate lightning_rapid_gossip_sync;
 
 mod fwd_batch;
+mod task_control;
 
 use fwd_batch::BatchDelay;
+pub use task_control::{
+	BackgroundHealth, BackgroundTask, BackgroundTaskControl, BackgroundTaskStatus,
+};
 
 use lightning::chain;
 use lightning::chain::chaininterface::{BroadcasterInterface, FeeEstimator};
@@ -126,6 +130,7 @@ use alloc::vec::Vec;
 pub struct BackgroundProcessor {
 	stop_thread: Arc<AtomicBool>,
 	thread_handle: Option<JoinHandle<Result<(), std::io::Error>>>,
+	task_control: BackgroundTaskControl,
 }
 
 #[cfg(not(test))]
@@ -951,6 +956,171 @@ pub async fn process_events_async<
 	liquidity_manager: Option<LM>, sweeper: Option<OS>, logger: L, scorer: Option<S>,
 	sleeper: Sleeper, mobile_interruptable_platform: bool, fetch_time: FetchTime,
 ) -> Result<(), lightning::io::Error>
+where
+	UL::Target: UtxoLookup,
+	CF::Target: chain::Filter,
+	T::Target: BroadcasterInterface,
+	F::Target: FeeEstimator,
+	L::Target: Logger,
+	P::Target: Persist<<CM::Target as AChannelManager>::Signer>,
+	ES::Target: EntropySource,
+	CM::Target: AChannelManager,
+	OM::Target: AOnionMessenger,
+	PM::Target: APeerManager,
+	LM::Target: ALiquidityManager,
+	O::Target: OutputSpender,
+	D::Target: ChangeDestinationSource,
+	K::Target: KVStore,
+{
+	process_events_async_with_concurrent_event_handling(
+		kv_store,
+		event_handler,
+		chain_monitor,
+		channel_manager,
+		onion_messenger,
+		gossip_sync,
+		peer_manager,
+		liquidity_manager,
+		sweeper,
+		logger,
+		scorer,
+		sleeper,
+		mobile_interruptable_platform,
+		fetch_time,
+		1,
+	)
+	.await
+}
+
+/// Async events processor that is based on [`process_events_async`] but allows for handling up to
+/// `max_concurrent_event_handlers` [`ChannelManager`] events at once.
+///
+/// This allows slow event handling (e.g., calling a webhook upon [`Event::PaymentClaimable`]) to
+/// overlap rather than delaying the handling of all subsequent events. Events are still handed to
+/// `event_handler` in the order they were generated, and events relating to a common channel or
+/// payment are only handed to `event_handler` once the handling of earlier ones has completed.
+/// See [`ChannelManager::process_pending_events_async_concurrently`] for details.
+///
+/// Events generated by the [`ChainMonitor`] and the [`OnionMessenger`] are handled as they are by
+/// [`process_events_async`].
+///
+/// [`ChannelManager`]: lightning::ln::channelmanager::ChannelManager
+/// [`ChannelManager::process_pending_events_async_concurrently`]: lightning::ln::channelmanager::ChannelManager::process_pending_events_async_concurrently
+/// [`OnionMessenger`]: lightning::onion_message::messenger::OnionMessenger
+pub async fn process_events_async_with_concurrent_event_handling<
+	'a,
+	UL: Deref,
+	CF: Deref,
+	T: Deref,
+	F: Deref,
+	G: Deref<Target = NetworkGraph<L>>,
+	L: Deref,
+	P: Deref,
+	EventHandlerFuture: core::future::Future<Output = Result<(), ReplayEvent>>,
+	EventHandler: Fn(Event) -> EventHandlerFuture,
+	ES: Deref,
+	M: Deref<Target = ChainMonitor<<CM::Target as AChannelManager>::Signer, CF, T, F, L, P, ES>>,
+	CM: Deref,
+	OM: Deref,
+	PGS: Deref<Target = P2PGossipSync<G, UL, L>>,
+	RGS: Deref<Target = RapidGossipSync<G, L>>,
+	PM: Deref,
+	LM: Deref,
+	D: Deref,
+	O: Deref,
+	K: Deref,
+	OS: Deref<Target = OutputSweeper<T, D, F, CF, K, L, O>>,
+	S: Deref<Target = SC>,
+	SC: for<'b> WriteableScore<'b>,
+	SleepFuture: core::future::Future<Output = bool> + core::marker::Unpin,
+	Sleeper: Fn(Duration) -> SleepFuture,
+	FetchTime: Fn() -> Option<Duration>,
+>(
+	kv_store: K, event_handler: EventHandler, chain_monitor: M, channel_manager: CM,
+	onion_messenger: Option<OM>, gossip_sync: GossipSync<PGS, RGS, G, UL, L>, peer_manager: PM,
+	liquidity_manager: Option<LM>, sweeper: Option<OS>, logger: L, scorer: Option<S>,
+	sleeper: Sleeper, mobile_interruptable_platform: bool, fetch_time: FetchTime,
+	max_concurrent_event_handlers: usize,
+) -> Result<(), lightning::io::Error>
+where
+	UL::Target: UtxoLookup,
+	CF::Target: chain::Filter,
+	T::Target: BroadcasterInterface,
+	F::Target: FeeEstimator,
+	L::Target: Logger,
+	P::Target: Persist<<CM::Target as AChannelManager>::Signer>,
+	ES::Target: EntropySource,
+	CM::Target: AChannelManager,
+	OM::Target: AOnionMessenger,
+	PM::Target: APeerManager,
+	LM::Target: ALiquidityManager,
+	O::Target: OutputSpender,
+	D::Target: ChangeDestinationSource,
+	K::Target: KVStore,
+{
+	let task_control = BackgroundTaskControl::new();
+	process_events_async_with_task_control(
+		kv_store,
+		event_handler,
+		chain_monitor,
+		channel_manager,
+		onion_messenger,
+		gossip_sync,
+		peer_manager,
+		liquidity_manager,
+		sweeper,
+		logger,
+		scorer,
+		sleeper,
+		mobile_interruptable_platform,
+		fetch_time,
+		max_concurrent_event_handlers,
+		&task_control,
+	)
+	.await
+}
+
+/// Async events processor that is based on [`process_events_async_with_concurrent_event_handling`]
+/// but additionally allows for individual [`BackgroundTask`]s to be paused and resumed and the
+/// health of the background processor to be monitored via the given [`BackgroundTaskControl`].
+///
+/// Run times and errors are recorded using the time returned by `fetch_time`, and thus are not
+/// tracked if `fetch_time` returns `None`.
+pub async fn process_events_async_with_task_control<
+	'a,
+	UL: Deref,
+	CF: Deref,
+	T: Deref,
+	F: Deref,
+	G: Deref<Target = NetworkGraph<L>>,
+	L: Deref,
+	P: Deref,
+	EventHandlerFuture: core::future::Future<Output = Result<(), ReplayEvent>>,
+	EventHandler: Fn(Event) -> EventHandlerFuture,
+	ES: Deref,
+	M: Deref<Target = ChainMonitor<<CM::Target as AChannelManager>::Signer, CF, T, F, L, P, ES>>,
+	CM: Deref,
+	OM: Deref,
+	PGS: Deref<Target = P2PGossipSync<G, UL, L>>,
+	RGS: Deref<Target = RapidGossipSync<G, L>>,
+	PM: Deref,
+	LM: Deref,
+	D: Deref,
+	O: Deref,
+	K: Deref,
+	OS: Deref<Target = OutputSweeper<T, D, F, CF, K, L, O>>,
+	S: Deref<Target = SC>,
+	SC: for<'b> WriteableScore<'b>,
+	SleepFuture: core::future::Future<Output = bool> + core::marker::Unpin,
+	Sleeper: Fn(Duration) -> SleepFuture,
+	FetchTime: Fn() -> Option<Duration>,
+>(
+	kv_store: K, event_handler: EventHandler, chain_monitor: M, channel_manager: CM,
+	onion_messenger: Option<OM>, gossip_sync: GossipSync<PGS, RGS, G, UL, L>, peer_manager: PM,
+	liquidity_manager: Option<LM>, sweeper: Option<OS>, logger: L, scorer: Option<S>,
+	sleeper: Sleeper, mobile_interruptable_platform: bool, fetch_time: FetchTime,
+	max_concurrent_event_handlers: usize, task_control: &BackgroundTaskControl,
+) -> Result<(), lightning::io::Error>
 where
 	UL::Target: UtxoLookup,
 	CF::Target: chain::Filter,
@@ -1024,7 +1194,17 @@ where
 	let mut last_forwards_processing_call = sleeper(batch_delay.get());
 
 	loop {
-		channel_manager.get_cm().process_pending_events_async(async_event_handler).await;
+		if max_concurrent_event_handlers > 1 {
+			channel_manager
+				.get_cm()
+				.process_pending_events_async_concurrently(
+					async_event_handler,
+					max_concurrent_event_handlers,
+				)
+				.await;
+		} else {
+			channel_manager.get_cm().process_pending_events_async(async_event_handler).await;
+		}
 		chain_monitor.process_pending_events_async(async_event_handler).await;
 		if let Some(om) = &onion_messenger {
 			om.get_om().process_pending_events_async(async_event_handler).await
@@ -1169,7 +1349,9 @@ where
 			GossipSync::Rapid(_) => !have_pruned || prune_timer_elapsed,
 			_ => prune_timer_elapsed,
 		};
-		if should_prune {
+		if should_prune && task_control.is_paused(BackgroundTask::NetworkGraphPrune) {
+			log_trace!(logger, "Not pruning network graph as the task is paused.");
+		} else if should_prune {
 			// The network graph must not be pruned while rapid sync completion is pending
 			if let Some(network_graph) = gossip_sync.prunable_network_graph() {
 				if let Some(duration_since_epoch) = fetch_time() {
@@ -1192,6 +1374,11 @@ where
 						.await
 					{
 						log_error!(logger, "Error: Failed to persist network graph, check your disk and permissions {}",e);
+						task_control.record_error(
+							BackgroundTask::NetworkGraphPrune,
+							&e,
+							fetch_time(),
+						);
 					}
 
 					Ok(())
@@ -1199,6 +1386,7 @@ where
 
 				// TODO: Once our MSRV is 1.68 we should be able to drop the Box
 				futures.set_b(Box::pin(fut));
+				task_control.record_run(BackgroundTask::NetworkGraphPrune, fetch_time());
 
 				have_pruned = true;
 			}
@@ -1215,6 +1403,9 @@ where
 		match check_and_reset_sleeper(&mut last_scorer_persist_call, || {
 			sleeper(SCORER_PERSIST_TIMER)
 		}) {
+			Some(false) if task_control.is_paused(BackgroundTask::ScorerPersist) => {
+				log_trace!(logger, "Not persisting scorer as the task is paused.");
+			},
 			Some(false) => {
 				if let Some(ref scorer) = scorer {
 					if let Some(duration_since_epoch) = fetch_time() {
@@ -1238,6 +1429,11 @@ where
 							"Error: Failed to persist scorer, check your disk and permissions {}",
 							e
 						);
+							task_control.record_error(
+								BackgroundTask::ScorerPersist,
+								&e,
+								fetch_time(),
+							);
 						}
 
 						Ok(())
@@ -1245,6 +1441,7 @@ where
 
 					// TODO: Once our MSRV is 1.68 we should be able to drop the Box
 					futures.set_c(Box::pin(fut));
+					task_control.record_run(BackgroundTask::ScorerPersist, fetch_time());
 				}
 			},
 			Some(true) => break,
@@ -1324,9 +1521,16 @@ where
 			last_ping_call = sleeper(PING_TIMER);
 		} else {
 			match check_and_reset_sleeper(&mut last_ping_call, || sleeper(PING_TIMER)) {
+				Some(false) if task_control.is_paused(BackgroundTask::PeerTimerTick) => {
+					log_trace!(
+						logger,
+						"Not calling PeerManager's timer_tick_occurred as the task is paused."
+					);
+				},
 				Some(false) => {
 					log_trace!(logger, "Calling PeerManager's timer_tick_occurred");
 					peer_manager.as_ref().timer_tick_occurred();
+					task_control.record_run(BackgroundTask::PeerTimerTick, fetch_time());
 				},
 				Some(true) => break,
 				_ => {},
@@ -1342,6 +1546,8 @@ where
 			Some(true) => break,
 			None => {},
 		}
+
+		task_control.record_iteration(fetch_time());
 	}
 	log_trace!(logger, "Terminating background processor.");
 
@@ -1562,7 +1768,12 @@ impl BackgroundProcessor {
 	{
 		let stop_thread = Arc::new(AtomicBool::new(false));
 		let stop_thread_clone = Arc::clone(&stop_thread);
+		let task_control = BackgroundTaskControl::new();
+		let task_control_clone = task_control.clone();
 		let handle = thread::spawn(move || -> Result<(), std::io::Error> {
+			let fetch_time = || {
+				std::time::SystemTime::now().duration_since(std::time::SystemTime::UNIX_EPOCH).ok()
+			};
 			let event_handler = |event| {
 				let network_graph = gossip_sync.network_graph();
 				if let Some(network_graph) = network_graph {
@@ -1703,7 +1914,9 @@ impl BackgroundProcessor {
 					GossipSync::Rapid(_) => !have_pruned || prune_timer_elapsed,
 					_ => prune_timer_elapsed,
 				};
-				if should_prune {
+				if should_prune && task_control.is_paused(BackgroundTask::NetworkGraphPrune) {
+					log_trace!(logger, "Not pruning network graph as the task is paused.");
+				} else if should_prune {
 					// The network graph must not be pruned while rapid sync completion is pending
 					if let Some(network_graph) = gossip_sync.prunable_network_graph() {
 						let duration_since_epoch = std::time::SystemTime::now()
@@ -1721,7 +1934,13 @@ impl BackgroundProcessor {
 							network_graph.encode(),
 						) {
 							log_error!(logger, "Error: Failed to persist network graph, check your disk and permissions {}", e);
+							task_control.record_error(
+								BackgroundTask::NetworkGraphPrune,
+								&e,
+								fetch_time(),
+							);
 						}
+						task_control.record_run(BackgroundTask::NetworkGraphPrune, fetch_time());
 						have_pruned = true;
 					}
 					last_prune_call = Instant::now();
@@ -1737,7 +1956,9 @@ impl BackgroundProcessor {
 					have_decayed_scorer = true;
 				}
 				if last_scorer_persist_call.elapsed() > SCORER_PERSIST_TIMER {
-					if let Some(ref scorer) = scorer {
+					if task_control.is_paused(BackgroundTask::ScorerPersist) {
+						log_trace!(logger, "Not persisting scorer as the task is paused.");
+					} else if let Some(ref scorer) = scorer {
 						let duration_since_epoch = std::time::SystemTime::now()
 							.duration_since(std::time::SystemTime::UNIX_EPOCH)
 							.expect("Time should be sometime after 1970");
@@ -1750,7 +1971,13 @@ impl BackgroundProcessor {
 							scorer.encode(),
 						) {
 							log_error!(logger, "Error: Failed to persist scorer, check your disk and permissions {}", e);
+							task_control.record_error(
+								BackgroundTask::ScorerPersist,
+								&e,
+								fetch_time(),
+							);
 						}
+						task_control.record_run(BackgroundTask::ScorerPersist, fetch_time());
 					}
 					last_scorer_persist_call = Instant::now();
 				}
@@ -1769,8 +1996,16 @@ impl BackgroundProcessor {
 					last_onion_message_handler_call = Instant::now();
 				}
 				if last_ping_call.elapsed() > PING_TIMER {
-					log_trace!(logger, "Calling PeerManager's timer_tick_occurred");
-					peer_manager.as_ref().timer_tick_occurred();
+					if task_control.is_paused(BackgroundTask::PeerTimerTick) {
+						log_trace!(
+							logger,
+							"Not calling PeerManager's timer_tick_occurred as the task is paused."
+						);
+					} else {
+						log_trace!(logger, "Calling PeerManager's timer_tick_occurred");
+						peer_manager.as_ref().timer_tick_occurred();
+						task_control.record_run(BackgroundTask::PeerTimerTick, fetch_time());
+					}
 					last_ping_call = Instant::now();
 				}
 				if last_rebroadcast_call.elapsed() > REBROADCAST_TIMER {
@@ -1778,6 +2013,8 @@ impl BackgroundProcessor {
 					chain_monitor.rebroadcast_pending_claims();
 					last_rebroadcast_call = Instant::now();
 				}
+
+				task_control.record_iteration(fetch_time());
 			}
 
 			// After we exit, ensure we persist the ChannelManager one final time - this avoids
@@ -1807,7 +2044,20 @@ impl BackgroundProcessor {
 			}
 			Ok(())
 		});
-		Self { stop_thread: stop_thread_clone, thread_handle: Some(handle) }
+		Self {
+			stop_thread: stop_thread_clone,
+			thread_handle: Some(handle),
+			task_control: task_control_clone,
+		}
+	}
+
+	/// Returns the [`BackgroundTaskControl`] for this `BackgroundProcessor`'s thread, allowing
+	/// individual [`BackgroundTask`]s to be paused and resumed and its [`BackgroundHealth`] to be
+	/// monitored.
+	///
+	/// The returned handle may be cloned and held independently of the `BackgroundProcessor`.
+	pub fn task_control(&self) -> &BackgroundTaskControl {
+		&self.task_control
 	}
 
 	/// Join `BackgroundProcessor`'s thread, returning any error that occurred while persisting
@@ -1860,7 +2110,7 @@ impl Drop for BackgroundProcessor {
 
 #[cfg(all(feature = "std", test))]
 mod tests {
-	use super::{BackgroundProcessor, GossipSync, FRESHNESS_TIMER};
+	use super::{BackgroundProcessor, BackgroundTask, GossipSync, FRESHNESS_TIMER};
 	use bitcoin::constants::{genesis_block, ChainHash};
 	use bitcoin::hashes::Hash;
 	use bitcoin::locktime::absolute::LockTime;
@@ -2400,7 +2650,7 @@ mod tests {
 				keys_manager.get_peer_storage_key(),
 			));
 			let best_block = BestBlock::from_network(network);
-			let params = ChainParameters { network, best_block };
+			let params = ChainParameters { network, custom_chain_hash: None, best_block };
 			let manager = Arc::new(ChannelManager::new(
 				Arc::clone(&fee_estimator),
 				Arc::clone(&chain_monitor),
@@ -2945,6 +3195,61 @@ mod tests {
 		}
 	}
 
+	#[test]
+	fn test_task_control() {
+		// Test that paused tasks are skipped until resumed and that task runs and errors are
+		// reflected in the health snapshot.
+		let (_, nodes) = create_nodes(1, "test_task_control");
+		let data_dir = nodes[0].kv_store.get_data_dir();
+		let persister =
+			Arc::new(Persister::new(data_dir).with_scorer_error(std::io::ErrorKind::Other, "test"));
+		let event_handler = |_: _| Ok(());
+		let bg_processor = BackgroundProcessor::start(
+			persister,
+			event_handler,
+			Arc::clone(&nodes[0].chain_monitor),
+			Arc::clone(&nodes[0].node),
+			Some(Arc::clone(&nodes[0].messenger)),
+			nodes[0].no_gossip_sync(),
+			Arc::clone(&nodes[0].peer_manager),
+			Some(Arc::clone(&nodes[0].liquidity_manager)),
+			Some(Arc::clone(&nodes[0].sweeper)),
+			Arc::clone(&nodes[0].logger),
+			Some(Arc::clone(&nodes[0].scorer)),
+		);
+		let task_control = bg_processor.task_control().clone();
+		task_control.pause(BackgroundTask::PeerTimerTick);
+
+		let desired_log =
+			"Not calling PeerManager's timer_tick_occurred as the task is paused.".to_string();
+		loop {
+			let health = task_control.health();
+			let log_entries = nodes[0].logger.lines.lock().unwrap();
+			if health.last_iteration.is_some()
+				&& health.scorer_persist.last_run.is_some()
+				&& health.scorer_persist.last_error.is_some()
+				&& log_entries
+					.get(&("lightning_background_processor", desired_log.clone()))
+					.is_some()
+			{
+				break;
+			}
+		}
+
+		let health = task_control.health();
+		assert!(health.peer_timer_tick.paused);
+		assert_eq!(health.peer_timer_tick.last_run, None);
+		assert_eq!(health.scorer_persist.last_error, Some("test".to_string()));
+		assert!(health.scorer_persist.last_error_time.is_some());
+
+		task_control.resume(BackgroundTask::PeerTimerTick);
+		while task_control.health().peer_timer_tick.last_run.is_none() {}
+		assert!(!task_control.health().peer_timer_tick.paused);
+
+		// The scorer is persisted once more on shutdown, which fails.
+		assert!(bg_processor.stop().is_err());
+	}
+
 	#[test]
 	fn test_background_event_handling() {
 		let (_, mut nodes) = create_nodes(2, "test_background_event_handling");
@@ -3641,6 +3946,115 @@ mod tests {
 		r2.unwrap()
 	}
 
+	#[tokio::test]
+	async fn test_concurrent_event_handling_async() {
+		// Test that unrelated events are handled concurrently, while events relating to a common
+		// payment are still handled one after another.
+		let handling_log = Arc::new(std::sync::Mutex::new(Vec::new()));
+		let (sender, mut receiver) = tokio::sync::mpsc::channel(3);
+		let event_handler = {
+			let handling_log = Arc::clone(&handling_log);
+			move |event: Event| {
+				let handling_log = Arc::clone(&handling_log);
+				let sender = sender.clone();
+				async move {
+					let (is_failure, payment_id) = match event {
+						Event::ProbeSuccessful { payment_id, .. } => (false, payment_id),
+						Event::ProbeFailed { payment_id, .. } => (true, payment_id),
+						_ => panic!("Unexpected event: {:?}", event),
+					};
+					handling_log.lock().unwrap().push((true, is_failure, payment_id));
+					tokio::time::sleep(Duration::from_millis(50)).await;
+					handling_log.lock().unwrap().push((false, is_failure, payment_id));
+					sender.send(()).await.unwrap();
+					Ok(())
+				}
+			}
+		};
+
+		let (_, nodes) = create_nodes(1, "test_concurrent_event_handling_async");
+		let data_dir = nodes[0].kv_store.get_data_dir();
+		let kv_store_sync = Arc::new(Persister::new(data_dir));
+		let kv_store = KVStoreSyncWrapper(kv_store_sync);
+
+		let (exit_sender, exit_receiver) = tokio::sync::watch::channel(());
+
+		// Yes, you can unsafe { turn off the borrow checker }
+		let lm_async: &'static LiquidityManager<_, _, _, _, _, _, _> = unsafe {
+			&*(nodes[0].liquidity_manager.get_lm_async()
+				as *const LiquidityManager<_, _, _, _, _, _, _>)
+				as &'static LiquidityManager<_, _, _, _, _, _, _>
+		};
+		let sweeper_async: &'static OutputSweeper<_, _, _, _, _, _, _> = unsafe {
+			&*(nodes[0].sweeper.sweeper_async() as *const OutputSweeper<_, _, _, _, _, _, _>)
+				as &'static OutputSweeper<_, _, _, _, _, _, _>
+		};
+
+		let path = Path { hops: Vec::new(), blinded_tail: None };
+		let payment_a = PaymentId([1; 32]);
+		let payment_b = PaymentId([2; 32]);
+		nodes[0].node.push_pending_event(Event::ProbeSuccessful {
+			payment_id: payment_a,
+			payment_hash: PaymentHash([1; 32]),
+			path: path.clone(),
+		});
+		nodes[0].node.push_pending_event(Event::ProbeSuccessful {
+			payment_id: payment_b,
+			payment_hash: PaymentHash([2; 32]),
+			path: path.clone(),
+		});
+		nodes[0].node.push_pending_event(Event::ProbeFailed {
+			payment_id: payment_a,
+			payment_hash: PaymentHash([1; 32]),
+			path,
+			short_channel_id: None,
+		});
+
+		let bp_future = super::process_events_async_with_concurrent_event_handling(
+			kv_store,
+			event_handler,
+			Arc::clone(&nodes[0].chain_monitor),
+			Arc::clone(&nodes[0].node),
+			Some(Arc::clone(&nodes[0].messenger)),
+			nodes[0].no_gossip_sync(),
+			Arc::clone(&nodes[0].peer_manager),
+			Some(lm_async),
+			Some(sweeper_async),
+			Arc::clone(&nodes[0].logger),
+			Some(Arc::clone(&nodes[0].scorer)),
+			move |dur: Duration| {
+				let mut exit_receiver = exit_receiver.clone();
+				Box::pin(async move {
+					tokio::select! {
+						_ = tokio::time::sleep(dur) => false,
+						_ = exit_receiver.changed() => true,
+					}
+				})
+			},
+			false,
+			|| Some(Duration::ZERO),
+			3,
+		);
+		let t1 = tokio::spawn(bp_future);
+		let t2 = tokio::spawn(async move {
+			for _ in 0..3 {
+				receiver.recv().await.unwrap();
+			}
+			exit_sender.send(()).unwrap();
+		});
+
+		let (r1, r2) = tokio::join!(t1, t2);
+		r1.unwrap().unwrap();
+		r2.unwrap();
+
+		let log = handling_log.lock().unwrap();
+		let position = |entry| log.iter().position(|e| *e == entry).unwrap();
+		// Both probes were handled concurrently...
+		assert!(position((true, false, payment_b)) < position((false, false, payment_a)));
+		// ...but the failure relating to the first probe was only handled once it completed.
+		assert!(position((false, false, payment_a)) < position((true, true, payment_a)));
+	}
+
 	#[tokio::test]
 	#[cfg(not(c_bindings))]
 	async fn test_no_consts() {
@@ -0,0 +1,171 @@
+// This file is Copyright its original authors, visible in version control
+// history.
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Utilities to pause, resume and monitor individual background tasks.
+
+use core::ops::DerefMut;
+use core::time::Duration;
+
+#[cfg(not(feature = "std"))]
+use alloc::string::{String, ToString};
+#[cfg(not(feature = "std"))]
+use alloc::sync::Arc;
+#[cfg(not(feature = "std"))]
+use core::cell::RefCell;
+#[cfg(feature = "std")]
+use std::sync::{Arc, Mutex};
+
+/// A periodic task run by the background processor which may be paused and resumed via a
+/// [`BackgroundTaskControl`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BackgroundTask {
+	/// Pruning stale channels from the [`NetworkGraph`] and persisting it.
+	///
+	/// [`NetworkGraph`]: lightning::routing::gossip::NetworkGraph
+	NetworkGraphPrune,
+	/// Decaying and persisting the scorer.
+	ScorerPersist,
+	/// Calling [`PeerManager::timer_tick_occurred`], which pings peers and disconnects those which
+	/// failed to respond.
+	///
+	/// [`PeerManager::timer_tick_occurred`]: lightning::ln::peer_handler::PeerManager::timer_tick_occurred
+	PeerTimerTick,
+}
+
+impl BackgroundTask {
+	fn idx(&self) -> usize {
+		match self {
+			BackgroundTask::NetworkGraphPrune => 0,
+			BackgroundTask::ScorerPersist => 1,
+			BackgroundTask::PeerTimerTick => 2,
+		}
+	}
+}
+
+/// The status of a single [`BackgroundTask`], as returned by [`BackgroundTaskControl::health`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BackgroundTaskStatus {
+	/// Whether the task is currently paused.
+	pub paused: bool,
+	/// The time, as a duration since the UNIX epoch, at which the task last ran, if ever.
+	///
+	/// When using `process_events_async`, this is only tracked if `fetch_time` returns a time.
+	pub last_run: Option<Duration>,
+	/// The last error the task ran into, if any. This is not cleared once the task succeeds again,
+	/// so should be compared against [`Self::last_run`] and [`Self::last_error_time`].
+	pub last_error: Option<String>,
+	/// The time, as a duration since the UNIX epoch, at which [`Self::last_error`] occurred.
+	pub last_error_time: Option<Duration>,
+}
+
+/// A snapshot of the background processor's health, as returned by
+/// [`BackgroundTaskControl::health`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BackgroundHealth {
+	/// The time, as a duration since the UNIX epoch, at which the background processor last
+	/// completed an iteration of its main loop, if ever.
+	///
+	/// If this falls too far behind the current time, the background processor is likely stalled,
+	/// e.g., on a blocked event handler or a hung persistence call.
+	pub last_iteration: Option<Duration>,
+	/// The status of the [`BackgroundTask::NetworkGraphPrune`] task.
+	pub network_graph_prune: BackgroundTaskStatus,
+	/// The status of the [`BackgroundTask::ScorerPersist`] task.
+	pub scorer_persist: BackgroundTaskStatus,
+	/// The status of the [`BackgroundTask::PeerTimerTick`] task.
+	pub peer_timer_tick: BackgroundTaskStatus,
+}
+
+#[derive(Default)]
+struct ControlState {
+	last_iteration: Option<Duration>,
+	tasks: [BackgroundTaskStatus; 3],
+}
+
+/// A handle which allows pausing and resuming individual [`BackgroundTask`]s and retrieving a
+/// [`BackgroundHealth`] snapshot of a running background processor.
+///
+/// This is cheaply cloneable, with all clones referring to the same background processor, and may
+/// be handed to an orchestrator to detect and recover from stalled background tasks without
+/// restarting the node.
+///
+/// Paused tasks are skipped when their timer fires rather than queued, i.e., resuming a task
+/// won't cause it to run until its timer fires again.
+#[derive(Clone, Default)]
+pub struct BackgroundTaskControl {
+	#[cfg(feature = "std")]
+	state: Arc<Mutex<ControlState>>,
+	// Without `std` we cannot be running on multiple threads, so a `RefCell` suffices.
+	#[cfg(not(feature = "std"))]
+	state: Arc<RefCell<ControlState>>,
+}
+
+impl BackgroundTaskControl {
+	/// Constructs a new [`BackgroundTaskControl`] with all tasks running.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Pauses the given task until [`Self::resume`] is called.
+	pub fn pause(&self, task: BackgroundTask) {
+		self.lock_state().tasks[task.idx()].paused = true;
+	}
+
+	/// Resumes the given task if it was previously paused via [`Self::pause`].
+	pub fn resume(&self, task: BackgroundTask) {
+		self.lock_state().tasks[task.idx()].paused = false;
+	}
+
+	/// Returns whether the given task is currently paused.
+	pub fn is_paused(&self, task: BackgroundTask) -> bool {
+		self.lock_state().tasks[task.idx()].paused
+	}
+
+	/// Returns a snapshot of the background processor's health.
+	pub fn health(&self) -> BackgroundHealth {
+		let state = self.lock_state();
+		BackgroundHealth {
+			last_iteration: state.last_iteration,
+			network_graph_prune: state.tasks[BackgroundTask::NetworkGraphPrune.idx()].clone(),
+			scorer_persist: state.tasks[BackgroundTask::ScorerPersist.idx()].clone(),
+			peer_timer_tick: state.tasks[BackgroundTask::PeerTimerTick.idx()].clone(),
+		}
+	}
+
+	#[cfg(feature = "std")]
+	fn lock_state(&self) -> impl DerefMut<Target = ControlState> + '_ {
+		self.state.lock().unwrap()
+	}
+
+	#[cfg(not(feature = "std"))]
+	fn lock_state(&self) -> impl DerefMut<Target = ControlState> + '_ {
+		self.state.borrow_mut()
+	}
+
+	pub(crate) fn record_iteration(&self, now: Option<Duration>) {
+		if now.is_some() {
+			self.lock_state().last_iteration = now;
+		}
+	}
+
+	pub(crate) fn record_run(&self, task: BackgroundTask, now: Option<Duration>) {
+		if now.is_some() {
+			self.lock_state().tasks[task.idx()].last_run = now;
+		}
+	}
+
+	pub(crate) fn record_error<E: core::fmt::Display>(
+		&self, task: BackgroundTask, error: &E, now: Option<Duration>,
+	) {
+		let mut state = self.lock_state();
+		let status = &mut state.tasks[task.idx()];
+		status.last_error = Some(error.to_string());
+		status.last_error_time = now;
+	}
+}
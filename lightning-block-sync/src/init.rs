@@ -36,6 +36,10 @@ where
 /// failure, each listener may be left at a different block hash than the one it was originally
 /// paired with.
 ///
+/// Consecutive full blocks are connected in batches via
+/// [`chain::Listen::filtered_blocks_connected`], allowing listeners to process many blocks at once
+/// when they have been offline for a while.
+///
 /// Useful during startup to bring the [`ChannelManager`] and each [`ChannelMonitor`] in sync before
 /// switching to [`SpvClient`]. For example:
 ///
@@ -258,6 +262,19 @@ impl<'a, L: chain::Listen + ?Sized> chain::Listen for ChainListenerSet<'a, L> {
 		}
 	}
 
+	fn filtered_blocks_connected(
+		&self, blocks: &[(&Header, &chain::transaction::TransactionData, u32)],
+	) {
+		for (starting_height, chain_listener) in self.0.iter() {
+			// Blocks are given in chain order, so only pass on those after the starting height.
+			let first_new_block =
+				blocks.iter().position(|(_, _, height)| *height > *starting_height);
+			if let Some(first_new_block) = first_new_block {
+				chain_listener.filtered_blocks_connected(&blocks[first_new_block..]);
+			}
+		}
+	}
+
 	fn blocks_disconnected(&self, _fork_point: BestBlock) {
 		unreachable!()
 	}
@@ -364,6 +381,32 @@ mod tests {
 		}
 	}
 
+	#[tokio::test]
+	async fn sync_connects_blocks_in_batches() {
+		let chain = Blockchain::default().with_height(20);
+
+		let mut listener_1 = MockChainListener::new();
+		for height in 1..=20 {
+			listener_1 = listener_1.expect_block_connected(*chain.at_height(height));
+		}
+		let mut listener_2 = MockChainListener::new();
+		for height in 11..=20 {
+			listener_2 = listener_2.expect_block_connected(*chain.at_height(height));
+		}
+
+		let listeners = vec![
+			(chain.at_height(0).block_hash, &listener_1 as &dyn chain::Listen),
+			(chain.at_height(10).block_hash, &listener_2 as &dyn chain::Listen),
+		];
+		let mut cache = chain.header_cache(0..=20);
+		match synchronize_listeners(&chain, Network::Bitcoin, &mut cache, listeners).await {
+			Ok(header) => assert_eq!(header, chain.tip()),
+			Err(e) => panic!("Unexpected error: {:?}", e),
+		}
+		assert_eq!(listener_1.connected_batch_sizes(), vec![8, 8, 4]);
+		assert_eq!(listener_2.connected_batch_sizes(), vec![6, 4]);
+	}
+
 	#[tokio::test]
 	async fn cache_connected_and_keep_disconnected_blocks() {
 		let main_chain = Blockchain::default().with_height(2);
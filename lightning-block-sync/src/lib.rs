@@ -7,6 +7,11 @@
 //! Defines a [`BlockSource`] trait, which is an asynchronous interface for retrieving block headers
 //! and data.
 //!
+//! Defines a [`ChainSourceSupervisor`] utility for failing over between several block sources as
+//! they become unhealthy.
+//!
+//! [`ChainSourceSupervisor`]: supervisor::ChainSourceSupervisor
+//!
 //! Enabling feature `rest-client` or `rpc-client` allows configuring the client to fetch blocks
 //! using Bitcoin Core's REST or RPC interface, respectively.
 //!
@@ -33,6 +38,8 @@ pub mod rest;
 #[cfg(feature = "rpc-client")]
 pub mod rpc;
 
+pub mod supervisor;
+
 #[cfg(any(feature = "rest-client", feature = "rpc-client"))]
 mod convert;
 
@@ -42,7 +49,7 @@ mod test_utils;
 #[cfg(any(feature = "rest-client", feature = "rpc-client"))]
 mod utils;
 
-use crate::poll::{ChainTip, Poll, ValidatedBlockHeader};
+use crate::poll::{ChainTip, Poll, ValidatedBlock, ValidatedBlockHeader};
 
 use bitcoin::block::{Block, Header};
 use bitcoin::hash_types::BlockHash;
@@ -290,6 +297,10 @@ where
 	}
 }
 
+/// The maximum number of full blocks passed to [`Listen::filtered_blocks_connected`] at once,
+/// bounding the number of fetched blocks held in memory.
+const MAX_BLOCKS_PER_BATCH: usize = 8;
+
 /// Notifies [listeners] of blocks that have been connected or disconnected from the chain.
 ///
 /// [listeners]: lightning::chain::Listen
@@ -407,30 +418,73 @@ where
 	}
 
 	/// Notifies the chain listeners of connected blocks.
+	///
+	/// Consecutive full blocks are connected in batches of up to [`MAX_BLOCKS_PER_BATCH`] via
+	/// [`Listen::filtered_blocks_connected`], while header-only blocks are connected individually
+	/// as their transaction data is filtered.
 	async fn connect_blocks<P: Poll>(
 		&mut self, mut new_tip: ValidatedBlockHeader,
 		mut connected_blocks: Vec<ValidatedBlockHeader>, chain_poller: &mut P,
 	) -> Result<(), (BlockSourceError, Option<ValidatedBlockHeader>)> {
+		let mut batch = Vec::with_capacity(MAX_BLOCKS_PER_BATCH);
 		for header in connected_blocks.drain(..).rev() {
-			let height = header.height;
-			let block_data =
-				chain_poller.fetch_block(&header).await.map_err(|e| (e, Some(new_tip)))?;
+			let block_data = match chain_poller.fetch_block(&header).await {
+				Ok(block_data) => block_data,
+				Err(e) => {
+					// Connect the blocks fetched so far so that the returned tip is accurate.
+					self.connect_batch(&mut batch, &mut new_tip);
+					return Err((e, Some(new_tip)));
+				},
+			};
 			debug_assert_eq!(block_data.block_hash, header.block_hash);
 
-			match block_data.deref() {
+			let is_full_block = matches!(block_data.deref(), BlockData::FullBlock(_));
+			batch.push((header, block_data));
+			if !is_full_block || batch.len() >= MAX_BLOCKS_PER_BATCH {
+				self.connect_batch(&mut batch, &mut new_tip);
+			}
+		}
+		self.connect_batch(&mut batch, &mut new_tip);
+
+		Ok(())
+	}
+
+	/// Notifies the chain listeners of the blocks in `batch`, draining it and updating `new_tip`.
+	fn connect_batch(
+		&mut self, batch: &mut Vec<(ValidatedBlockHeader, ValidatedBlock)>,
+		new_tip: &mut ValidatedBlockHeader,
+	) {
+		match &batch[..] {
+			[] => return,
+			[(header, block_data)] => match block_data.deref() {
 				BlockData::FullBlock(block) => {
-					self.chain_listener.block_connected(block, height);
+					self.chain_listener.block_connected(block, header.height);
 				},
-				BlockData::HeaderOnly(header) => {
-					self.chain_listener.filtered_block_connected(header, &[], height);
+				BlockData::HeaderOnly(block_header) => {
+					self.chain_listener.filtered_block_connected(block_header, &[], header.height);
 				},
-			}
+			},
+			_ => {
+				let txdata: Vec<Vec<_>> = batch
+					.iter()
+					.map(|(_, block_data)| match block_data.deref() {
+						BlockData::FullBlock(block) => block.txdata.iter().enumerate().collect(),
+						BlockData::HeaderOnly(_) => Vec::new(),
+					})
+					.collect();
+				let blocks: Vec<_> = batch
+					.iter()
+					.zip(txdata.iter())
+					.map(|((header, _), txdata)| (&header.header, &txdata[..], header.height))
+					.collect();
+				self.chain_listener.filtered_blocks_connected(&blocks);
+			},
+		}
 
+		for (header, _) in batch.drain(..) {
 			self.header_cache.block_connected(header.block_hash, header);
-			new_tip = header;
+			*new_tip = header;
 		}
-
-		Ok(())
 	}
 }
 
@@ -0,0 +1,588 @@
+//! A [`BlockSource`] monitoring the health of several chain sources and failing over between
+//! them.
+//!
+//! A [`ChainSourceSupervisor`] is configured with a list of sources in order of preference, e.g.
+//! a local Bitcoin Core node followed by one or more remote backends. For each source it tracks
+//! how long ago its chain tip last changed, how far its tip trails the best one seen from any
+//! source, and how many of its recent requests failed. Requests are served by the most preferred
+//! source which isn't degraded, so that a dead or stalled backend doesn't silently stall
+//! confirmations and claims. Changes in health are reported as [`ChainSourceEvent`]s.
+//!
+//! The supervisor implements [`BlockSource`] itself and may thus be polled via a
+//! [`ChainPoller`]. Sources which aren't [`BlockSource`]s, e.g. transaction-sync backends, may be
+//! supervised as well by reporting the outcome of each request via
+//! [`ChainSourceSupervisor::record_success`] and [`ChainSourceSupervisor::record_error`].
+//!
+//! [`ChainPoller`]: crate::poll::ChainPoller
+
+use crate::{BlockData, BlockHeaderData, BlockSource, BlockSourceResult};
+
+use bitcoin::hash_types::BlockHash;
+
+use std::collections::VecDeque;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Configuration for a [`ChainSourceSupervisor`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SupervisorConfig {
+	/// How long a source's chain tip may remain unchanged before the source is considered stale.
+	///
+	/// As blocks are occasionally found hours apart, this should be set generously.
+	///
+	/// Default value: 2 hours.
+	pub max_tip_age: Duration,
+	/// By how many blocks a source's chain tip may trail the best tip seen from any supervised
+	/// source before the source is considered stale.
+	///
+	/// Default value: 2.
+	pub max_tip_lag: u32,
+	/// The number of consecutive failed requests after which a source is considered degraded.
+	///
+	/// Default value: 3.
+	pub max_consecutive_errors: u32,
+	/// The number of most recent requests over which a source's error rate is calculated.
+	///
+	/// Default value: 20.
+	pub error_window: usize,
+	/// The percentage of failed requests among the last [`Self::error_window`] requests at or
+	/// above which a source is considered degraded.
+	///
+	/// Default value: 50.
+	pub max_error_rate_percent: u8,
+}
+
+impl Default for SupervisorConfig {
+	fn default() -> Self {
+		Self {
+			max_tip_age: Duration::from_secs(2 * 60 * 60),
+			max_tip_lag: 2,
+			max_consecutive_errors: 3,
+			error_window: 20,
+			max_error_rate_percent: 50,
+		}
+	}
+}
+
+/// The reason a chain source is considered degraded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DegradationReason {
+	/// The source's chain tip hasn't changed for longer than [`SupervisorConfig::max_tip_age`].
+	StaleTip {
+		/// How long ago the source's chain tip last changed.
+		tip_age: Duration,
+	},
+	/// The source's chain tip trails the best tip seen from any supervised source by more than
+	/// [`SupervisorConfig::max_tip_lag`] blocks.
+	LaggingTip {
+		/// The height of the source's chain tip.
+		height: u32,
+		/// The height of the best chain tip seen from any supervised source.
+		best_height: u32,
+	},
+	/// Too many of the source's recent requests failed, per
+	/// [`SupervisorConfig::max_consecutive_errors`] and
+	/// [`SupervisorConfig::max_error_rate_percent`].
+	Errors {
+		/// The number of requests which failed since the last successful one.
+		consecutive_errors: u32,
+		/// The number of failed requests among the `recent_requests`.
+		recent_errors: usize,
+		/// The number of recent requests considered, at most [`SupervisorConfig::error_window`].
+		recent_requests: usize,
+	},
+}
+
+/// An event about the health of the sources supervised by a [`ChainSourceSupervisor`], as
+/// returned by [`ChainSourceSupervisor::get_and_clear_pending_events`].
+///
+/// Sources are identified by their index in the list given to [`ChainSourceSupervisor::new`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChainSourceEvent {
+	/// A source became degraded and won't be used while a more healthy source is available.
+	Degraded {
+		/// The index of the degraded source.
+		source: usize,
+		/// Why the source is considered degraded.
+		reason: DegradationReason,
+	},
+	/// A previously degraded source is healthy again.
+	Recovered {
+		/// The index of the recovered source.
+		source: usize,
+	},
+	/// Requests are now served by a different source, either because the previously active one
+	/// became degraded or because a more preferred one recovered.
+	FailedOver {
+		/// The index of the previously active source.
+		from: usize,
+		/// The index of the now active source.
+		to: usize,
+	},
+}
+
+/// The health of a supervised source, as returned by [`ChainSourceSupervisor::source_health`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChainSourceHealth {
+	/// The hash of the source's chain tip, if it has been queried yet.
+	pub tip_hash: Option<BlockHash>,
+	/// The height of the source's chain tip, if known.
+	pub tip_height: Option<u32>,
+	/// How long ago the source's chain tip last changed, if it has been queried yet.
+	pub tip_age: Option<Duration>,
+	/// The number of requests which failed since the last successful one.
+	pub consecutive_errors: u32,
+	/// The number of failed requests among [`Self::recent_requests`].
+	pub recent_errors: usize,
+	/// The number of recent requests considered, at most [`SupervisorConfig::error_window`].
+	pub recent_requests: usize,
+	/// Why the source is considered degraded, if it is.
+	pub degradation: Option<DegradationReason>,
+}
+
+struct SourceState {
+	tip_hash: Option<BlockHash>,
+	tip_height: Option<u32>,
+	tip_changed_at: Option<Instant>,
+	consecutive_errors: u32,
+	/// Whether each of the last [`SupervisorConfig::error_window`] requests failed.
+	recent_errors: VecDeque<bool>,
+	degradation: Option<DegradationReason>,
+}
+
+impl SourceState {
+	fn new() -> Self {
+		Self {
+			tip_hash: None,
+			tip_height: None,
+			tip_changed_at: None,
+			consecutive_errors: 0,
+			recent_errors: VecDeque::new(),
+			degradation: None,
+		}
+	}
+
+	fn record_request(&mut self, failed: bool, config: &SupervisorConfig) {
+		if failed {
+			self.consecutive_errors = self.consecutive_errors.saturating_add(1);
+		} else {
+			self.consecutive_errors = 0;
+		}
+		self.recent_errors.push_back(failed);
+		while self.recent_errors.len() > config.error_window {
+			self.recent_errors.pop_front();
+		}
+	}
+
+	fn record_tip(&mut self, tip_hash: BlockHash, tip_height: Option<u32>, now: Instant) {
+		if self.tip_hash != Some(tip_hash) {
+			self.tip_hash = Some(tip_hash);
+			self.tip_changed_at = Some(now);
+		}
+		self.tip_height = tip_height;
+	}
+
+	fn recent_error_count(&self) -> usize {
+		self.recent_errors.iter().filter(|failed| **failed).count()
+	}
+
+	fn check_degradation(
+		&self, config: &SupervisorConfig, best_height: Option<u32>, now: Instant,
+	) -> Option<DegradationReason> {
+		let recent_errors = self.recent_error_count();
+		let recent_requests = self.recent_errors.len();
+		let error_rate_exceeded = recent_requests > 0
+			&& recent_requests >= config.error_window
+			&& recent_errors * 100 >= config.max_error_rate_percent as usize * recent_requests;
+		if self.consecutive_errors >= config.max_consecutive_errors || error_rate_exceeded {
+			return Some(DegradationReason::Errors {
+				consecutive_errors: self.consecutive_errors,
+				recent_errors,
+				recent_requests,
+			});
+		}
+
+		if let (Some(height), Some(best_height)) = (self.tip_height, best_height) {
+			if best_height > height.saturating_add(config.max_tip_lag) {
+				return Some(DegradationReason::LaggingTip { height, best_height });
+			}
+		}
+
+		if let Some(tip_changed_at) = self.tip_changed_at {
+			let tip_age = now.saturating_duration_since(tip_changed_at);
+			if tip_age > config.max_tip_age {
+				return Some(DegradationReason::StaleTip { tip_age });
+			}
+		}
+
+		None
+	}
+}
+
+struct SupervisorState {
+	sources: Vec<SourceState>,
+	active: usize,
+	pending_events: Vec<ChainSourceEvent>,
+}
+
+impl SupervisorState {
+	/// Re-evaluates the health of all sources, switching the active source if needed.
+	fn update_health(&mut self, config: &SupervisorConfig) {
+		let now = Instant::now();
+		let best_height = self.sources.iter().filter_map(|source| source.tip_height).max();
+		for (idx, source) in self.sources.iter_mut().enumerate() {
+			let degradation = source.check_degradation(config, best_height, now);
+			match (source.degradation.is_some(), degradation) {
+				(false, Some(reason)) => {
+					self.pending_events.push(ChainSourceEvent::Degraded { source: idx, reason });
+				},
+				(true, None) => {
+					self.pending_events.push(ChainSourceEvent::Recovered { source: idx });
+				},
+				_ => {},
+			}
+			source.degradation = degradation;
+		}
+
+		// If all sources are degraded we stick with the active one rather than flip-flopping.
+		if let Some(preferred) = self.sources.iter().position(|source| source.degradation.is_none())
+		{
+			if preferred != self.active {
+				let from = self.active;
+				self.active = preferred;
+				self.pending_events.push(ChainSourceEvent::FailedOver { from, to: preferred });
+			}
+		}
+	}
+}
+
+/// Supervises several chain sources, serving requests from the most preferred healthy one.
+///
+/// Sources which haven't been queried yet are assumed to be healthy. Thus, in order to detect a
+/// more preferred source recovering, or a secondary source being unusable before failing over to
+/// it, [`Self::probe_sources`] (or, for sources which aren't [`BlockSource`]s, the equivalent
+/// requests reported via [`Self::record_success`] and [`Self::record_error`]) should be called
+/// regularly. [`Self::check_health`] should be called regularly as well to detect stale chain tips
+/// even while no requests are made.
+///
+/// See the [module-level documentation] for details.
+///
+/// [module-level documentation]: crate::supervisor
+pub struct ChainSourceSupervisor<S> {
+	sources: Vec<S>,
+	config: SupervisorConfig,
+	state: Mutex<SupervisorState>,
+}
+
+impl<S> ChainSourceSupervisor<S> {
+	/// Creates a new supervisor for the given sources, in order of preference.
+	///
+	/// # Panics
+	///
+	/// Panics if `sources` is empty.
+	pub fn new(sources: Vec<S>, config: SupervisorConfig) -> Self {
+		assert!(!sources.is_empty(), "At least one chain source is required");
+		let state = SupervisorState {
+			sources: sources.iter().map(|_| SourceState::new()).collect(),
+			active: 0,
+			pending_events: Vec::new(),
+		};
+		Self { sources, config, state: Mutex::new(state) }
+	}
+
+	/// Returns the supervised sources, in order of preference.
+	pub fn sources(&self) -> &[S] {
+		&self.sources
+	}
+
+	/// Returns the index of the source requests should currently be made to.
+	pub fn active_source(&self) -> usize {
+		self.state.lock().unwrap().active
+	}
+
+	/// Records a successful request to the source with the given index, along with the chain tip
+	/// hash and height it reported, if the request returned it.
+	pub fn record_success(&self, source: usize, tip: Option<(BlockHash, Option<u32>)>) {
+		let mut state = self.state.lock().unwrap();
+		state.sources[source].record_request(false, &self.config);
+		if let Some((tip_hash, tip_height)) = tip {
+			state.sources[source].record_tip(tip_hash, tip_height, Instant::now());
+		}
+		state.update_health(&self.config);
+	}
+
+	/// Records a failed request to the source with the given index.
+	pub fn record_error(&self, source: usize) {
+		let mut state = self.state.lock().unwrap();
+		state.sources[source].record_request(true, &self.config);
+		state.update_health(&self.config);
+	}
+
+	/// Re-evaluates the health of all sources, e.g. to detect chain tips which became stale while
+	/// no requests were made.
+	pub fn check_health(&self) {
+		self.state.lock().unwrap().update_health(&self.config);
+	}
+
+	/// Returns the current health of each source, in order of preference.
+	pub fn source_health(&self) -> Vec<ChainSourceHealth> {
+		let now = Instant::now();
+		let state = self.state.lock().unwrap();
+		state
+			.sources
+			.iter()
+			.map(|source| ChainSourceHealth {
+				tip_hash: source.tip_hash,
+				tip_height: source.tip_height,
+				tip_age: source
+					.tip_changed_at
+					.map(|tip_changed_at| now.saturating_duration_since(tip_changed_at)),
+				consecutive_errors: source.consecutive_errors,
+				recent_errors: source.recent_error_count(),
+				recent_requests: source.recent_errors.len(),
+				degradation: source.degradation,
+			})
+			.collect()
+	}
+
+	/// Returns and clears the [`ChainSourceEvent`]s generated since the last call.
+	pub fn get_and_clear_pending_events(&self) -> Vec<ChainSourceEvent> {
+		std::mem::take(&mut self.state.lock().unwrap().pending_events)
+	}
+}
+
+impl<S: BlockSource> ChainSourceSupervisor<S> {
+	/// Queries the chain tip of every source other than the active one, updating their health.
+	///
+	/// This allows detecting a more preferred source recovering, at which point requests are
+	/// served by it again, as well as a secondary source failing before we need to fail over to it.
+	pub async fn probe_sources(&self) {
+		let active = self.active_source();
+		for (idx, source) in self.sources.iter().enumerate() {
+			if idx == active {
+				continue;
+			}
+			match source.get_best_block().await {
+				Ok(tip) => self.record_success(idx, Some(tip)),
+				Err(_) => self.record_error(idx),
+			}
+		}
+	}
+
+	/// Makes the given request to the active source, recording its outcome. If the request fails
+	/// and we fail over to another source as a result, the request is retried once with it.
+	async fn request<'a, T, R, Fut, Tip>(&'a self, request: R, tip: Tip) -> BlockSourceResult<T>
+	where
+		R: Fn(&'a S) -> Fut,
+		Fut: Future<Output = BlockSourceResult<T>>,
+		Tip: Fn(&T) -> Option<(BlockHash, Option<u32>)>,
+	{
+		let source = self.active_source();
+		let res = request(&self.sources[source]).await;
+		match &res {
+			Ok(value) => self.record_success(source, tip(value)),
+			Err(_) => self.record_error(source),
+		}
+
+		let failover_source = self.active_source();
+		if res.is_ok() || failover_source == source {
+			return res;
+		}
+		let res = request(&self.sources[failover_source]).await;
+		match &res {
+			Ok(value) => self.record_success(failover_source, tip(value)),
+			Err(_) => self.record_error(failover_source),
+		}
+		res
+	}
+}
+
+impl<S: BlockSource> BlockSource for ChainSourceSupervisor<S> {
+	fn get_header<'a>(
+		&'a self, header_hash: &'a BlockHash, height_hint: Option<u32>,
+	) -> impl Future<Output = BlockSourceResult<BlockHeaderData>> + Send + 'a {
+		async move {
+			let request = |source: &'a S| source.get_header(header_hash, height_hint);
+			self.request(request, |_| None).await
+		}
+	}
+
+	fn get_block<'a>(
+		&'a self, header_hash: &'a BlockHash,
+	) -> impl Future<Output = BlockSourceResult<BlockData>> + Send + 'a {
+		async move {
+			let request = |source: &'a S| source.get_block(header_hash);
+			self.request(request, |_| None).await
+		}
+	}
+
+	fn get_best_block<'a>(
+		&'a self,
+	) -> impl Future<Output = BlockSourceResult<(BlockHash, Option<u32>)>> + Send + 'a {
+		async move {
+			let request = |source: &'a S| source.get_best_block();
+			self.request(request, |tip| Some(*tip)).await
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_utils::Blockchain;
+	use crate::BlockSourceError;
+
+	use std::sync::atomic::{AtomicBool, Ordering};
+
+	/// A [`Blockchain`] whose requests can be made to fail.
+	struct FlakySource {
+		chain: Blockchain,
+		failing: AtomicBool,
+	}
+
+	impl FlakySource {
+		fn new(chain: Blockchain) -> Self {
+			Self { chain, failing: AtomicBool::new(false) }
+		}
+
+		fn check_failing(&self) -> BlockSourceResult<()> {
+			if self.failing.load(Ordering::Acquire) {
+				Err(BlockSourceError::transient("source unavailable"))
+			} else {
+				Ok(())
+			}
+		}
+	}
+
+	impl BlockSource for FlakySource {
+		fn get_header<'a>(
+			&'a self, header_hash: &'a BlockHash, height_hint: Option<u32>,
+		) -> impl Future<Output = BlockSourceResult<BlockHeaderData>> + Send + 'a {
+			async move {
+				self.check_failing()?;
+				self.chain.get_header(header_hash, height_hint).await
+			}
+		}
+
+		fn get_block<'a>(
+			&'a self, header_hash: &'a BlockHash,
+		) -> impl Future<Output = BlockSourceResult<BlockData>> + Send + 'a {
+			async move {
+				self.check_failing()?;
+				self.chain.get_block(header_hash).await
+			}
+		}
+
+		fn get_best_block<'a>(
+			&'a self,
+		) -> impl Future<Output = BlockSourceResult<(BlockHash, Option<u32>)>> + Send + 'a {
+			async move {
+				self.check_failing()?;
+				self.chain.get_best_block().await
+			}
+		}
+	}
+
+	fn degraded_errors(consecutive_errors: u32, recent_requests: usize) -> DegradationReason {
+		DegradationReason::Errors {
+			consecutive_errors,
+			recent_errors: consecutive_errors as usize,
+			recent_requests,
+		}
+	}
+
+	#[tokio::test]
+	async fn fails_over_on_errors_and_back_on_recovery() {
+		let primary = FlakySource::new(Blockchain::default().with_height(3));
+		let secondary = FlakySource::new(Blockchain::default().with_height(3));
+		let tip_hash = secondary.chain.tip().block_hash;
+		let config = SupervisorConfig { max_consecutive_errors: 2, ..Default::default() };
+		let supervisor = ChainSourceSupervisor::new(vec![primary, secondary], config);
+		supervisor.sources()[0].failing.store(true, Ordering::Release);
+
+		// The first failure is simply returned.
+		assert!(supervisor.get_best_block().await.is_err());
+		assert_eq!(supervisor.active_source(), 0);
+		assert!(supervisor.get_and_clear_pending_events().is_empty());
+
+		// The second one makes us fail over, retrying the request with the secondary.
+		assert_eq!(supervisor.get_best_block().await.unwrap(), (tip_hash, Some(3)));
+		assert_eq!(supervisor.active_source(), 1);
+		assert_eq!(
+			supervisor.get_and_clear_pending_events(),
+			vec![
+				ChainSourceEvent::Degraded { source: 0, reason: degraded_errors(2, 2) },
+				ChainSourceEvent::FailedOver { from: 0, to: 1 },
+			]
+		);
+
+		// Probes of the primary fail until it recovers, at which point we switch back to it.
+		supervisor.probe_sources().await;
+		assert_eq!(supervisor.active_source(), 1);
+		assert!(supervisor.get_and_clear_pending_events().is_empty());
+
+		supervisor.sources()[0].failing.store(false, Ordering::Release);
+		supervisor.probe_sources().await;
+		assert_eq!(supervisor.active_source(), 0);
+		assert_eq!(
+			supervisor.get_and_clear_pending_events(),
+			vec![
+				ChainSourceEvent::Recovered { source: 0 },
+				ChainSourceEvent::FailedOver { from: 1, to: 0 },
+			]
+		);
+	}
+
+	#[tokio::test]
+	async fn fails_over_from_lagging_source() {
+		let primary = Blockchain::default().with_height(1);
+		let secondary = Blockchain::default().with_height(4);
+		let supervisor =
+			ChainSourceSupervisor::new(vec![primary, secondary], SupervisorConfig::default());
+
+		assert_eq!(supervisor.get_best_block().await.unwrap().1, Some(1));
+		assert_eq!(supervisor.active_source(), 0);
+
+		// Probing the secondary reveals the primary's tip is lagging behind.
+		supervisor.probe_sources().await;
+		assert_eq!(supervisor.active_source(), 1);
+		assert_eq!(
+			supervisor.get_and_clear_pending_events(),
+			vec![
+				ChainSourceEvent::Degraded {
+					source: 0,
+					reason: DegradationReason::LaggingTip { height: 1, best_height: 4 },
+				},
+				ChainSourceEvent::FailedOver { from: 0, to: 1 },
+			]
+		);
+		assert_eq!(supervisor.get_best_block().await.unwrap().1, Some(4));
+
+		let health = supervisor.source_health();
+		assert_eq!(health[0].tip_height, Some(1));
+		assert_eq!(health[1].tip_height, Some(4));
+		assert_eq!(health[1].recent_requests, 2);
+		assert!(health[1].degradation.is_none());
+	}
+
+	#[test]
+	fn sticks_with_active_source_if_all_are_degraded() {
+		let sources = vec![Blockchain::default(), Blockchain::default()];
+		let config = SupervisorConfig { max_consecutive_errors: 1, ..Default::default() };
+		let supervisor = ChainSourceSupervisor::new(sources, config);
+
+		supervisor.record_error(0);
+		assert_eq!(supervisor.active_source(), 1);
+		supervisor.record_error(1);
+		assert_eq!(supervisor.active_source(), 1);
+		assert_eq!(
+			supervisor.get_and_clear_pending_events(),
+			vec![
+				ChainSourceEvent::Degraded { source: 0, reason: degraded_errors(1, 1) },
+				ChainSourceEvent::FailedOver { from: 0, to: 1 },
+				ChainSourceEvent::Degraded { source: 1, reason: degraded_errors(1, 1) },
+			]
+		);
+	}
+}
@@ -213,6 +213,7 @@ pub struct MockChainListener {
 	expected_blocks_connected: RefCell<VecDeque<BlockHeaderData>>,
 	expected_filtered_blocks_connected: RefCell<VecDeque<BlockHeaderData>>,
 	expected_blocks_disconnected: RefCell<VecDeque<BlockHeaderData>>,
+	connected_batch_sizes: RefCell<Vec<usize>>,
 }
 
 impl MockChainListener {
@@ -221,9 +222,14 @@ impl MockChainListener {
 			expected_blocks_connected: RefCell::new(VecDeque::new()),
 			expected_filtered_blocks_connected: RefCell::new(VecDeque::new()),
 			expected_blocks_disconnected: RefCell::new(VecDeque::new()),
+			connected_batch_sizes: RefCell::new(Vec::new()),
 		}
 	}
 
+	pub fn connected_batch_sizes(&self) -> Vec<usize> {
+		self.connected_batch_sizes.borrow().clone()
+	}
+
 	pub fn expect_block_connected(self, block: BlockHeaderData) -> Self {
 		self.expected_blocks_connected.borrow_mut().push_back(block);
 		self
@@ -267,6 +273,24 @@ impl chain::Listen for MockChainListener {
 		}
 	}
 
+	fn filtered_blocks_connected(
+		&self, blocks: &[(&Header, &chain::transaction::TransactionData, u32)],
+	) {
+		// Batches are only made of full blocks, so are checked against the expected full blocks.
+		self.connected_batch_sizes.borrow_mut().push(blocks.len());
+		for (header, _, height) in blocks.iter() {
+			match self.expected_blocks_connected.borrow_mut().pop_front() {
+				None => {
+					panic!("Unexpected block connected: {:?}", header.block_hash());
+				},
+				Some(expected_block) => {
+					assert_eq!(header.block_hash(), expected_block.header.block_hash());
+					assert_eq!(*height, expected_block.height);
+				},
+			}
+		}
+	}
+
 	fn blocks_disconnected(&self, fork_point: BestBlock) {
 		match self.expected_blocks_disconnected.borrow_mut().pop_front() {
 			None => {
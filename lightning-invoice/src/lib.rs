@@ -32,6 +32,7 @@ use std::time::SystemTime;
 
 use bech32::primitives::decode::CheckedHrpstringError;
 use bech32::{Checksum, Fe32};
+use bitcoin::constants::ChainHash;
 use bitcoin::hashes::{sha256, Hash};
 use bitcoin::{Address, Network, PubkeyHash, ScriptHash, WitnessProgram, WitnessVersion};
 use lightning_types::features::Bolt11InvoiceFeatures;
@@ -463,6 +464,32 @@ impl From<Network> for Currency {
 	}
 }
 
+impl Currency {
+	/// Returns the currency to use for invoices on the chain with the given genesis block hash.
+	///
+	/// Chains other than the well-known [`Network`]s, e.g., custom signets, map to
+	/// [`Currency::Signet`] as they generally use the signet address and invoice prefixes.
+	pub fn from_chain_hash(chain_hash: ChainHash) -> Self {
+		Network::from_chain_hash(chain_hash).map(Into::into).unwrap_or(Currency::Signet)
+	}
+
+	/// Returns whether invoices in this currency may be paid on the chain with the given genesis
+	/// block hash.
+	///
+	/// Chains other than the well-known [`Network`]s, e.g., custom signets or regtests with a
+	/// custom genesis block, are considered compatible with all test currencies other than
+	/// [`Currency::BitcoinTestnet`].
+	pub fn is_compatible_with_chain_hash(&self, chain_hash: ChainHash) -> bool {
+		match Network::from_chain_hash(chain_hash) {
+			Some(network) => match self {
+				Currency::Simnet => network == Network::Regtest,
+				_ => Currency::from(network) == *self,
+			},
+			None => matches!(self, Currency::Regtest | Currency::Simnet | Currency::Signet),
+		}
+	}
+}
+
 impl From<Currency> for Network {
 	fn from(currency: Currency) -> Self {
 		match currency {
@@ -2455,4 +2482,29 @@ mod test {
 		assert_eq!(field10.partial_cmp(&field11).unwrap(), std::cmp::Ordering::Less);
 		assert_eq!(field20.partial_cmp(&field21).unwrap(), std::cmp::Ordering::Less);
 	}
+	#[test]
+	fn currency_chain_hash_compatibility() {
+		use crate::Currency;
+		use bitcoin::constants::ChainHash;
+		use bitcoin::Network;
+
+		let mainnet = ChainHash::using_genesis_block(Network::Bitcoin);
+		let testnet4 = ChainHash::using_genesis_block(Network::Testnet4);
+		let regtest = ChainHash::using_genesis_block(Network::Regtest);
+		let custom_signet = ChainHash::from([42; 32]);
+
+		assert_eq!(Currency::from_chain_hash(mainnet), Currency::Bitcoin);
+		assert_eq!(Currency::from_chain_hash(testnet4), Currency::BitcoinTestnet);
+		assert_eq!(Currency::from_chain_hash(custom_signet), Currency::Signet);
+
+		assert!(Currency::Bitcoin.is_compatible_with_chain_hash(mainnet));
+		assert!(!Currency::Bitcoin.is_compatible_with_chain_hash(testnet4));
+		assert!(!Currency::Bitcoin.is_compatible_with_chain_hash(custom_signet));
+		assert!(Currency::BitcoinTestnet.is_compatible_with_chain_hash(testnet4));
+		assert!(!Currency::BitcoinTestnet.is_compatible_with_chain_hash(custom_signet));
+		assert!(Currency::Simnet.is_compatible_with_chain_hash(regtest));
+		assert!(!Currency::Signet.is_compatible_with_chain_hash(regtest));
+		assert!(Currency::Signet.is_compatible_with_chain_hash(custom_signet));
+		assert!(Currency::Regtest.is_compatible_with_chain_hash(custom_signet));
+	}
 }
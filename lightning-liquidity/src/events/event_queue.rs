@@ -291,6 +291,12 @@ impl Writeable for EventQueueSerWrapper<'_> {
 						// Skip persisting GetInfoRequest and BuyRequest events as we prune the pending
 						// request state currently anyways.
 						Ok(false)
+					} else if matches!(event, LSPS2ServiceEvent::ChannelOpened { .. })
+						|| matches!(event, LSPS2ServiceEvent::OpeningFeeCollected { .. })
+					{
+						// Skip persisting purely informational events, which also keeps the persisted
+						// queue readable by prior versions.
+						Ok(false)
 					} else {
 						if let Some(writer) = writer {
 							0u8.write(writer)?;
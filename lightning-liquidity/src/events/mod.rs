@@ -18,7 +18,7 @@
 mod event_queue;
 
 pub use event_queue::MAX_EVENT_QUEUE_SIZE;
-pub(crate) use event_queue::{EventQueue, EventQueueDeserWrapper};
+pub(crate) use event_queue::{EventQueue, EventQueueDeserWrapper, EventQueueNotifierGuard};
 
 use crate::lsps0;
 use crate::lsps1;
@@ -12,18 +12,23 @@
 use super::event::LSPS1ClientEvent;
 use super::msgs::{
 	LSPS1CreateOrderRequest, LSPS1CreateOrderResponse, LSPS1GetInfoRequest, LSPS1GetInfoResponse,
-	LSPS1GetOrderRequest, LSPS1Message, LSPS1OrderId, LSPS1OrderParams, LSPS1Request,
-	LSPS1Response,
+	LSPS1GetOrderRequest, LSPS1Message, LSPS1OrderId, LSPS1OrderParams, LSPS1OrderState,
+	LSPS1PaymentInfo, LSPS1PaymentState, LSPS1Request, LSPS1Response,
 };
 use crate::message_queue::MessageQueue;
 
-use crate::events::EventQueue;
+use crate::events::{EventQueue, EventQueueNotifierGuard};
 use crate::lsps0::ser::{LSPSProtocolMessageHandler, LSPSRequestId, LSPSResponseError};
 use crate::prelude::{new_hash_map, HashMap, HashSet};
 use crate::sync::{Arc, Mutex, RwLock};
 
+use lightning::chain::transaction::OutPoint;
+use lightning::ln::channelmanager::{AChannelManager, PaymentId, Retry};
 use lightning::ln::msgs::{ErrorAction, LightningError};
+use lightning::ln::types::ChannelId;
+use lightning::routing::router::RouteParametersConfig;
 use lightning::sign::EntropySource;
+use lightning::util::errors::APIError;
 use lightning::util::logger::Level;
 use lightning::util::persist::KVStore;
 
@@ -39,38 +44,152 @@ pub struct LSPS1ClientConfig {
 	pub max_channel_fees_msat: Option<u64>,
 }
 
+/// The number of times we retry paying an order's invoice via [`LSPS1ClientHandler::pay_order`].
+const ORDER_PAYMENT_RETRY_ATTEMPTS: u32 = 3;
+
+/// The client-side state of an order placed via [`LSPS1ClientHandler::create_order`].
+///
+/// The state is advanced based on the LSP's responses to [`LSPS1ClientHandler::create_order`] and
+/// [`LSPS1ClientHandler::check_order_status`], as well as on calls to
+/// [`LSPS1ClientHandler::pay_order`] and [`LSPS1ClientHandler::channel_ready`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LSPS1ClientOrderState {
+	/// The LSP created the order and expects a payment.
+	AwaitingPayment,
+	/// We initiated a payment of the order's BOLT11 invoice via
+	/// [`LSPS1ClientHandler::pay_order`], but the LSP hasn't acknowledged it yet.
+	PaymentPending {
+		/// The id of the payment we initiated.
+		payment_id: PaymentId,
+	},
+	/// The LSP considers the order paid and will open the channel.
+	Paid,
+	/// The LSP published the funding transaction of the purchased channel.
+	ChannelFunded {
+		/// The funding outpoint of the purchased channel.
+		funding_outpoint: bitcoin::OutPoint,
+	},
+	/// The purchased channel is ready to be used.
+	ChannelReady {
+		/// The id of the purchased channel.
+		channel_id: ChannelId,
+	},
+	/// The LSP failed the order. Any payment made will be refunded.
+	Failed,
+}
+
+struct ClientOrder {
+	state: LSPS1ClientOrderState,
+	payment: LSPS1PaymentInfo,
+}
+
+impl ClientOrder {
+	fn new(response: &LSPS1CreateOrderResponse) -> Self {
+		let mut order = Self {
+			state: LSPS1ClientOrderState::AwaitingPayment,
+			payment: response.payment.clone(),
+		};
+		order.update(response);
+		order
+	}
+
+	/// Advances the order's state given the latest response from the LSP.
+	fn update(&mut self, response: &LSPS1CreateOrderResponse) {
+		self.payment = response.payment.clone();
+		if matches!(self.state, LSPS1ClientOrderState::ChannelReady { .. }) {
+			return;
+		}
+
+		let is_paid = |state: &LSPS1PaymentState| *state == LSPS1PaymentState::Paid;
+		let payment = &response.payment;
+		if response.order_state == LSPS1OrderState::Failed {
+			self.state = LSPS1ClientOrderState::Failed;
+		} else if let Some(channel) = &response.channel {
+			self.state =
+				LSPS1ClientOrderState::ChannelFunded { funding_outpoint: channel.funding_outpoint };
+		} else if payment.bolt11.as_ref().map_or(false, |p| is_paid(&p.state))
+			|| payment.bolt12.as_ref().map_or(false, |p| is_paid(&p.state))
+			|| payment.onchain.as_ref().map_or(false, |p| is_paid(&p.state))
+		{
+			self.state = LSPS1ClientOrderState::Paid;
+		}
+	}
+}
+
 #[derive(Default)]
 struct PeerState {
 	pending_get_info_requests: HashSet<LSPSRequestId>,
 	pending_create_order_requests: HashSet<LSPSRequestId>,
 	pending_get_order_requests: HashSet<LSPSRequestId>,
+	orders: HashMap<LSPS1OrderId, ClientOrder>,
+	// Channels with the LSP which became ready before we learned which order they belong to.
+	ready_channels: HashMap<bitcoin::OutPoint, (ChannelId, u128)>,
+}
+
+impl PeerState {
+	/// Moves the given order into [`LSPS1ClientOrderState::ChannelReady`] and emits an
+	/// [`LSPS1ClientEvent::ChannelReady`] if its channel was already reported ready.
+	fn check_order_channel_ready<K: Deref + Clone>(
+		&mut self, order_id: &LSPS1OrderId, counterparty_node_id: &PublicKey,
+		event_queue_notifier: &EventQueueNotifierGuard<K>,
+	) where
+		K::Target: KVStore,
+	{
+		let order = match self.orders.get_mut(order_id) {
+			Some(order) => order,
+			None => return,
+		};
+		let funding_outpoint = match order.state {
+			LSPS1ClientOrderState::ChannelFunded { funding_outpoint } => funding_outpoint,
+			_ => return,
+		};
+		if let Some((channel_id, user_channel_id)) = self.ready_channels.remove(&funding_outpoint) {
+			order.state = LSPS1ClientOrderState::ChannelReady { channel_id };
+			event_queue_notifier.enqueue(LSPS1ClientEvent::ChannelReady {
+				counterparty_node_id: *counterparty_node_id,
+				order_id: order_id.clone(),
+				channel_id,
+				user_channel_id,
+				funding_outpoint,
+			});
+		}
+	}
 }
 
 /// The main object allowing to send and receive bLIP-51 / LSPS1 messages.
-pub struct LSPS1ClientHandler<ES: Deref, K: Deref + Clone>
+///
+/// Users need to forward [`Event::ChannelReady`] parameters to [`Self::channel_ready`] in order to
+/// learn when a purchased channel is ready.
+///
+/// [`Event::ChannelReady`]: lightning::events::Event::ChannelReady
+pub struct LSPS1ClientHandler<ES: Deref, CM: Deref + Clone, K: Deref + Clone>
 where
 	ES::Target: EntropySource,
+	CM::Target: AChannelManager,
 	K::Target: KVStore,
 {
 	entropy_source: ES,
+	channel_manager: CM,
 	pending_messages: Arc<MessageQueue>,
 	pending_events: Arc<EventQueue<K>>,
 	per_peer_state: RwLock<HashMap<PublicKey, Mutex<PeerState>>>,
 	config: LSPS1ClientConfig,
 }
 
-impl<ES: Deref, K: Deref + Clone> LSPS1ClientHandler<ES, K>
+impl<ES: Deref, CM: Deref + Clone, K: Deref + Clone> LSPS1ClientHandler<ES, CM, K>
 where
 	ES::Target: EntropySource,
+	CM::Target: AChannelManager,
 	K::Target: KVStore,
 {
 	/// Constructs an `LSPS1ClientHandler`.
 	pub(crate) fn new(
-		entropy_source: ES, pending_messages: Arc<MessageQueue>,
+		entropy_source: ES, channel_manager: CM, pending_messages: Arc<MessageQueue>,
 		pending_events: Arc<EventQueue<K>>, config: LSPS1ClientConfig,
 	) -> Self {
 		Self {
 			entropy_source,
+			channel_manager,
 			pending_messages,
 			pending_events,
 			per_peer_state: RwLock::new(new_hash_map()),
@@ -243,6 +362,14 @@ where
 					});
 				}
 
+				let order = ClientOrder::new(&response);
+				peer_state_lock.orders.insert(response.order_id.clone(), order);
+				peer_state_lock.check_order_channel_ready(
+					&response.order_id,
+					counterparty_node_id,
+					&event_queue_notifier,
+				);
+
 				event_queue_notifier.enqueue(LSPS1ClientEvent::OrderCreated {
 					request_id,
 					counterparty_node_id: *counterparty_node_id,
@@ -361,6 +488,19 @@ where
 					});
 				}
 
+				match peer_state_lock.orders.get_mut(&response.order_id) {
+					Some(order) => order.update(&response),
+					None => {
+						let order = ClientOrder::new(&response);
+						peer_state_lock.orders.insert(response.order_id.clone(), order);
+					},
+				}
+				peer_state_lock.check_order_channel_ready(
+					&response.order_id,
+					counterparty_node_id,
+					&event_queue_notifier,
+				);
+
 				event_queue_notifier.enqueue(LSPS1ClientEvent::OrderStatus {
 					request_id,
 					counterparty_node_id: *counterparty_node_id,
@@ -430,11 +570,139 @@ where
 			},
 		}
 	}
+
+	/// Pays the BOLT11 invoice of an order previously placed via [`Self::create_order`].
+	///
+	/// The order must be awaiting payment and the LSP must have provided a BOLT11 invoice. If a
+	/// [`LSPS1ClientConfig::max_channel_fees_msat`] limit is configured, the order's fees must not
+	/// exceed it.
+	///
+	/// On success, returns the [`PaymentId`] of the initiated payment and moves the order into
+	/// [`LSPS1ClientOrderState::PaymentPending`]. Use [`Self::check_order_status`] to learn when
+	/// the LSP considers the order paid.
+	pub fn pay_order(
+		&self, counterparty_node_id: &PublicKey, order_id: &LSPS1OrderId,
+	) -> Result<PaymentId, APIError> {
+		let outer_state_lock = self.per_peer_state.read().unwrap();
+		let inner_state_lock =
+			outer_state_lock.get(counterparty_node_id).ok_or_else(|| APIError::APIMisuseError {
+				err: format!("No state for the counterparty exists: {}", counterparty_node_id),
+			})?;
+		let mut peer_state_lock = inner_state_lock.lock().unwrap();
+
+		let order = peer_state_lock.orders.get_mut(order_id).ok_or_else(|| {
+			APIError::APIMisuseError { err: format!("Unknown order: {:?}", order_id) }
+		})?;
+
+		if order.state != LSPS1ClientOrderState::AwaitingPayment {
+			return Err(APIError::APIMisuseError {
+				err: format!("Order {:?} is not awaiting payment: {:?}", order_id, order.state),
+			});
+		}
+
+		let bolt11 = order.payment.bolt11.as_ref().ok_or_else(|| APIError::APIMisuseError {
+			err: format!("Order {:?} does not offer a BOLT11 payment option", order_id),
+		})?;
+
+		if let Some(max_channel_fees_msat) = self.config.max_channel_fees_msat {
+			let fee_total_msat = bolt11.fee_total_sat.saturating_mul(1000);
+			if fee_total_msat > max_channel_fees_msat {
+				return Err(APIError::APIMisuseError {
+					err: format!(
+						"Order {:?} fees of {}msat exceed the configured maximum of {}msat",
+						order_id, fee_total_msat, max_channel_fees_msat
+					),
+				});
+			}
+		}
+
+		let payment_id = PaymentId(self.entropy_source.get_secure_random_bytes());
+		self.channel_manager
+			.get_cm()
+			.pay_for_bolt11_invoice(
+				&bolt11.invoice,
+				payment_id,
+				None,
+				RouteParametersConfig::default(),
+				Retry::Attempts(ORDER_PAYMENT_RETRY_ATTEMPTS),
+			)
+			.map_err(|e| APIError::APIMisuseError {
+				err: format!("Failed to pay invoice of order {:?}: {:?}", order_id, e),
+			})?;
+
+		order.state = LSPS1ClientOrderState::PaymentPending { payment_id };
+		Ok(payment_id)
+	}
+
+	/// Returns the current state of an order previously placed via [`Self::create_order`], if
+	/// known.
+	pub fn order_state(
+		&self, counterparty_node_id: &PublicKey, order_id: &LSPS1OrderId,
+	) -> Option<LSPS1ClientOrderState> {
+		let outer_state_lock = self.per_peer_state.read().unwrap();
+		let peer_state_lock = outer_state_lock.get(counterparty_node_id)?.lock().unwrap();
+		peer_state_lock.orders.get(order_id).map(|order| order.state.clone())
+	}
+
+	/// Forward [`Event::ChannelReady`] event parameters into this function.
+	///
+	/// Will emit an [`LSPS1ClientEvent::ChannelReady`] event if the channel was purchased via an
+	/// order placed with the counterparty. If we have yet to learn the channel's funding outpoint
+	/// from the LSP, the event will be emitted once a subsequent call to
+	/// [`Self::check_order_status`] reports it.
+	///
+	/// [`Event::ChannelReady`]: lightning::events::Event::ChannelReady
+	/// [`LSPS1ClientEvent::ChannelReady`]: crate::lsps1::event::LSPS1ClientEvent::ChannelReady
+	pub fn channel_ready(
+		&self, user_channel_id: u128, channel_id: &ChannelId, counterparty_node_id: &PublicKey,
+		funding_txo: OutPoint,
+	) {
+		let event_queue_notifier = self.pending_events.notifier();
+
+		let outer_state_lock = self.per_peer_state.read().unwrap();
+		if let Some(inner_state_lock) = outer_state_lock.get(counterparty_node_id) {
+			let mut peer_state_lock = inner_state_lock.lock().unwrap();
+			let funding_outpoint = funding_txo.into_bitcoin_outpoint();
+			peer_state_lock.ready_channels.insert(funding_outpoint, (*channel_id, user_channel_id));
+
+			let order_ids = peer_state_lock
+				.orders
+				.iter()
+				.filter(|(_, order)| {
+					order.state == LSPS1ClientOrderState::ChannelFunded { funding_outpoint }
+				})
+				.map(|(order_id, _)| order_id.clone())
+				.collect::<Vec<_>>();
+			for order_id in order_ids {
+				peer_state_lock.check_order_channel_ready(
+					&order_id,
+					counterparty_node_id,
+					&event_queue_notifier,
+				);
+			}
+
+			// Only remember the channel if it may still belong to an order we don't know the
+			// funding outpoint of yet.
+			let has_unfunded_orders = peer_state_lock.orders.values().any(|order| {
+				matches!(
+					order.state,
+					LSPS1ClientOrderState::AwaitingPayment
+						| LSPS1ClientOrderState::PaymentPending { .. }
+						| LSPS1ClientOrderState::Paid
+				)
+			});
+			if !has_unfunded_orders {
+				peer_state_lock.ready_channels.remove(&funding_outpoint);
+			}
+		}
+	}
 }
 
-impl<ES: Deref, K: Deref + Clone> LSPSProtocolMessageHandler for LSPS1ClientHandler<ES, K>
+impl<ES: Deref, CM: Deref + Clone, K: Deref + Clone> LSPSProtocolMessageHandler
+	for LSPS1ClientHandler<ES, CM, K>
 where
 	ES::Target: EntropySource,
+	CM::Target: AChannelManager,
 	K::Target: KVStore,
 {
 	type ProtocolMessage = LSPS1Message;
@@ -480,3 +748,105 @@ where
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use crate::events::LiquidityEvent;
+	use crate::lsps1::msgs::LSPS1ChannelInfo;
+
+	use lightning::util::persist::KVStoreSyncWrapper;
+	use lightning::util::test_utils::TestStore;
+	use lightning::util::wakers::Notifier;
+
+	use bitcoin::secp256k1::{Secp256k1, SecretKey};
+
+	use alloc::collections::VecDeque;
+
+	fn order_response() -> LSPS1CreateOrderResponse {
+		let json_str = r#"{
+			"order_id": "bb4b5d0a-8334-49d8-9463-90a6d413af7c",
+			"lsp_balance_sat": "5000000",
+			"client_balance_sat": "2000000",
+			"required_channel_confirmations" : 0,
+			"funding_confirms_within_blocks": 1,
+			"channel_expiry_blocks": 12,
+			"token": "",
+			"created_at": "2012-04-23T18:25:43.511Z",
+			"announce_channel": true,
+			"order_state": "CREATED",
+			"payment": {
+				"bolt11": {
+					"state": "EXPECT_PAYMENT",
+					"expires_at": "2015-01-25T19:29:44.612Z",
+					"fee_total_sat": "8888",
+					"order_total_sat": "2008888",
+					"invoice" : "lnbc252u1p3aht9ysp580g4633gd2x9lc5al0wd8wx0mpn9748jeyz46kqjrpxn52uhfpjqpp5qgf67tcqmuqehzgjm8mzya90h73deafvr4m5705l5u5l4r05l8cqdpud3h8ymm4w3jhytnpwpczqmt0de6xsmre2pkxzm3qydmkzdjrdev9s7zhgfaqxqyjw5qcqpjrzjqt6xptnd85lpqnu2lefq4cx070v5cdwzh2xlvmdgnu7gqp4zvkus5zapryqqx9qqqyqqqqqqqqqqqcsq9q9qyysgqen77vu8xqjelum24hgjpgfdgfgx4q0nehhalcmuggt32japhjuksq9jv6eksjfnppm4hrzsgyxt8y8xacxut9qv3fpyetz8t7tsymygq8yzn05"
+				}
+			},
+			"channel": null
+		}"#;
+		serde_json::from_str(json_str).unwrap()
+	}
+
+	#[test]
+	fn client_order_state_transitions() {
+		let mut response = order_response();
+		let mut order = ClientOrder::new(&response);
+		assert_eq!(order.state, LSPS1ClientOrderState::AwaitingPayment);
+
+		response.payment.bolt11.as_mut().unwrap().state = LSPS1PaymentState::Paid;
+		order.update(&response);
+		assert_eq!(order.state, LSPS1ClientOrderState::Paid);
+
+		let json_str = r#"{
+			"funded_at": "2012-04-23T18:25:43.511Z",
+			"funding_outpoint": "0301e0480b374b32851a9462db29dc19fe830a7f7d7a88b81612b9d42099c0ae:0",
+			"expires_at": "2012-04-23T18:25:43.511Z"
+		}"#;
+		let channel: LSPS1ChannelInfo = serde_json::from_str(json_str).unwrap();
+		let funding_outpoint = channel.funding_outpoint;
+		response.order_state = LSPS1OrderState::Completed;
+		response.channel = Some(channel);
+		order.update(&response);
+		assert_eq!(order.state, LSPS1ClientOrderState::ChannelFunded { funding_outpoint });
+
+		let mut peer_state = PeerState::default();
+		let order_id = response.order_id.clone();
+		peer_state.orders.insert(order_id.clone(), order);
+		let channel_id = ChannelId([42; 32]);
+		peer_state.ready_channels.insert(funding_outpoint, (channel_id, 42));
+
+		let kv_store = Arc::new(KVStoreSyncWrapper(Arc::new(TestStore::new(false))));
+		let event_queue = EventQueue::new(VecDeque::new(), kv_store, Arc::new(Notifier::new()));
+		let secp_ctx = Secp256k1::new();
+		let counterparty_node_id =
+			PublicKey::from_secret_key(&secp_ctx, &SecretKey::from_slice(&[42; 32]).unwrap());
+		peer_state.check_order_channel_ready(
+			&order_id,
+			&counterparty_node_id,
+			&event_queue.notifier(),
+		);
+		assert_eq!(
+			peer_state.orders.get(&order_id).unwrap().state,
+			LSPS1ClientOrderState::ChannelReady { channel_id }
+		);
+		assert!(peer_state.ready_channels.is_empty());
+
+		let expected_event = LiquidityEvent::LSPS1Client(LSPS1ClientEvent::ChannelReady {
+			counterparty_node_id,
+			order_id,
+			channel_id,
+			user_channel_id: 42,
+			funding_outpoint,
+		});
+		assert_eq!(event_queue.next_event(), Some(expected_event));
+
+		// Once the channel is ready, later responses from the LSP don't alter the state anymore.
+		response.order_state = LSPS1OrderState::Failed;
+		let order = peer_state.orders.get_mut(&response.order_id).unwrap();
+		order.update(&response);
+		assert_eq!(order.state, LSPS1ClientOrderState::ChannelReady { channel_id });
+	}
+}
@@ -14,7 +14,10 @@ use super::msgs::{LSPS1ChannelInfo, LSPS1Options, LSPS1OrderParams, LSPS1Payment
 
 use crate::lsps0::ser::{LSPSRequestId, LSPSResponseError};
 
+use lightning::ln::types::ChannelId;
+
 use bitcoin::secp256k1::PublicKey;
+use bitcoin::OutPoint;
 
 /// An event which an bLIP-51 / LSPS1 client should take some action in response to.
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -139,6 +142,27 @@ pub enum LSPS1ClientEvent {
 		/// The error that was returned.
 		error: LSPSResponseError,
 	},
+	/// A channel purchased via an order previously placed via
+	/// [`LSPS1ClientHandler::create_order`] is ready to be used.
+	///
+	/// **Note: ** This event will *not* be persisted across restarts.
+	///
+	/// [`LSPS1ClientHandler::create_order`]: crate::lsps1::client::LSPS1ClientHandler::create_order
+	ChannelReady {
+		/// The node id of the LSP.
+		counterparty_node_id: PublicKey,
+		/// The id of the order the channel was purchased with.
+		order_id: LSPS1OrderId,
+		/// The id of the purchased channel.
+		channel_id: ChannelId,
+		/// The `user_channel_id` of the purchased channel, as reported by
+		/// [`Event::ChannelReady`].
+		///
+		/// [`Event::ChannelReady`]: lightning::events::Event::ChannelReady
+		user_channel_id: u128,
+		/// The funding outpoint of the purchased channel.
+		funding_outpoint: OutPoint,
+	},
 }
 
 /// An event which an LSPS1 server should take some action in response to.
@@ -17,6 +17,7 @@ use alloc::vec::Vec;
 use bitcoin::secp256k1::PublicKey;
 
 use lightning::impl_writeable_tlv_based_enum;
+use lightning::ln::types::ChannelId;
 
 /// An event which an LSPS2 client should take some action in response to.
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -179,6 +180,46 @@ pub enum LSPS2ServiceEvent {
 		/// The intercept short channel id to use in the route hint.
 		intercept_scid: u64,
 	},
+	/// The channel opened in response to an [`LSPS2ServiceEvent::OpenChannel`] is ready and the
+	/// intercepted payment which triggered its opening is being forwarded over it, less the
+	/// opening fee.
+	///
+	/// The opening fee is only collected once the client claims the payment, at which point an
+	/// [`LSPS2ServiceEvent::OpeningFeeCollected`] event will be emitted.
+	///
+	/// **Note: ** This event will *not* be persisted across restarts.
+	ChannelOpened {
+		/// The node id of the client the channel was opened to.
+		counterparty_node_id: PublicKey,
+		/// The user specified id given in the corresponding [`LSPS2ServiceEvent::OpenChannel`].
+		user_channel_id: u128,
+		/// The id of the opened channel.
+		channel_id: ChannelId,
+		/// The intercept short channel id the client's invoice was generated for.
+		intercept_scid: u64,
+		/// The opening fee being deducted from the forwarded payment.
+		opening_fee_msat: u64,
+	},
+	/// A payment forwarded over a JIT channel was claimed by the client, paying the channel's
+	/// opening fee.
+	///
+	/// Any further HTLCs to the intercept short channel id are now forwarded without deducting a
+	/// fee. If the client trusts us to broadcast the funding transaction, it will be broadcast at
+	/// this point.
+	///
+	/// **Note: ** This event will *not* be persisted across restarts.
+	OpeningFeeCollected {
+		/// The node id of the client the channel was opened to.
+		counterparty_node_id: PublicKey,
+		/// The user specified id given in the corresponding [`LSPS2ServiceEvent::OpenChannel`].
+		user_channel_id: u128,
+		/// The id of the JIT channel.
+		channel_id: ChannelId,
+		/// The intercept short channel id the client's invoice was generated for.
+		intercept_scid: u64,
+		/// The fee skimmed from the forwarded payment, which is at least the opening fee.
+		skimmed_fee_msat: u64,
+	},
 }
 
 impl_writeable_tlv_based_enum!(LSPS2ServiceEvent,
@@ -199,5 +240,19 @@ impl_writeable_tlv_based_enum!(LSPS2ServiceEvent,
 		(4, opening_fee_msat, required),
 		(6, user_channel_id, required),
 		(8, intercept_scid, required),
+	},
+	(6, ChannelOpened) => {
+		(0, counterparty_node_id, required),
+		(2, user_channel_id, required),
+		(4, channel_id, required),
+		(6, intercept_scid, required),
+		(8, opening_fee_msat, required),
+	},
+	(8, OpeningFeeCollected) => {
+		(0, counterparty_node_id, required),
+		(2, user_channel_id, required),
+		(4, channel_id, required),
+		(6, intercept_scid, required),
+		(8, skimmed_fee_msat, required),
 	}
 );
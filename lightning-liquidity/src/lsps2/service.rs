@@ -40,7 +40,7 @@ use crate::prelude::{new_hash_map, HashMap};
 use crate::sync::{Arc, Mutex, MutexGuard, RwLock};
 use crate::utils::async_poll::dummy_waker;
 
-use lightning::chain::chaininterface::BroadcasterInterface;
+use lightning::chain::chaininterface::{BroadcastContext, BroadcastType, BroadcasterInterface};
 use lightning::events::HTLCHandlingFailureType;
 use lightning::ln::channelmanager::{AChannelManager, FailureCode, InterceptId};
 use lightning::ln::msgs::{ErrorAction, LightningError};
@@ -1179,14 +1179,19 @@ where
 	/// were being held for that JIT channel are forwarded. In a `client_trusts_lsp` flow, once
 	/// the fee has been fully paid, the channel's funding transaction will be broadcasted.
 	///
+	/// Will generate a [`LSPS2ServiceEvent::OpeningFeeCollected`] event once the fee has been
+	/// fully paid.
+	///
 	/// Note that `next_channel_id` and `skimmed_fee_msat` are required to be provided.
 	/// Therefore, the corresponding [`Event::PaymentForwarded`] events need to be generated and
 	/// serialized by LDK versions greater or equal to 0.0.122.
 	///
 	/// [`Event::PaymentForwarded`]: lightning::events::Event::PaymentForwarded
+	/// [`LSPS2ServiceEvent::OpeningFeeCollected`]: crate::lsps2::event::LSPS2ServiceEvent::OpeningFeeCollected
 	pub async fn payment_forwarded(
 		&self, next_channel_id: ChannelId, skimmed_fee_msat: u64,
 	) -> Result<(), APIError> {
+		let event_queue_notifier = self.pending_events.notifier();
 		let mut should_persist = None;
 		if let Some(counterparty_node_id) =
 			self.peer_by_channel_id.read().unwrap().get(&next_channel_id)
@@ -1213,6 +1218,14 @@ where
 											htlc.expected_outbound_amount_msat,
 										)?;
 									}
+									let event = LSPS2ServiceEvent::OpeningFeeCollected {
+										counterparty_node_id: *counterparty_node_id,
+										user_channel_id: jit_channel.user_channel_id,
+										channel_id,
+										intercept_scid,
+										skimmed_fee_msat,
+									};
+									event_queue_notifier.enqueue(event);
 								},
 								Ok(None) => {},
 								Err(e) => {
@@ -1410,10 +1423,15 @@ where
 	/// Will forward the intercepted HTLC if it matches a channel
 	/// we need to forward a payment over otherwise it will be ignored.
 	///
+	/// Will generate a [`LSPS2ServiceEvent::ChannelOpened`] event once the intercepted HTLC has
+	/// been forwarded.
+	///
 	/// [`Event::ChannelReady`]: lightning::events::Event::ChannelReady
+	/// [`LSPS2ServiceEvent::ChannelOpened`]: crate::lsps2::event::LSPS2ServiceEvent::ChannelOpened
 	pub async fn channel_ready(
 		&self, user_channel_id: u128, channel_id: &ChannelId, counterparty_node_id: &PublicKey,
 	) -> Result<(), APIError> {
+		let event_queue_notifier = self.pending_events.notifier();
 		let mut should_persist = false;
 		{
 			let mut peer_by_channel_id = self.peer_by_channel_id.write().unwrap();
@@ -1448,6 +1466,14 @@ where
 										amount_to_forward_msat,
 									)?;
 								}
+								let event = LSPS2ServiceEvent::ChannelOpened {
+									counterparty_node_id: *counterparty_node_id,
+									user_channel_id,
+									channel_id,
+									intercept_scid,
+									opening_fee_msat,
+								};
+								event_queue_notifier.enqueue(event);
 							},
 							Err(e) => {
 								return Err(APIError::APIMisuseError {
@@ -2039,7 +2065,12 @@ where
 		}
 
 		if let Some(funding_tx) = jit_channel.get_funding_tx() {
-			self.tx_broadcaster.broadcast_transactions(&[funding_tx]);
+			let context = BroadcastContext {
+				broadcast_type: BroadcastType::Funding,
+				channel_id: channel_id_opt,
+				urgency: None,
+			};
+			self.tx_broadcaster.broadcast_transactions(&[funding_tx], context);
 		}
 	}
 }
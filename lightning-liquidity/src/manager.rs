@@ -291,6 +291,9 @@ where
 /// - [`Event::HTLCHandlingFailed`] to [`LSPS2ServiceHandler::htlc_handling_failed`]
 /// - [`Event::PaymentForwarded`] to [`LSPS2ServiceHandler::payment_forwarded`]
 ///
+/// If the LSPS1 client is configured, users must forward the following parameters from LDK events:
+/// - [`Event::ChannelReady`] to [`LSPS1ClientHandler::channel_ready`]
+///
 /// [`PeerManager`]: lightning::ln::peer_handler::PeerManager
 /// [`MessageHandler`]: lightning::ln::peer_handler::MessageHandler
 /// [`Event::HTLCIntercepted`]: lightning::events::Event::HTLCIntercepted
@@ -323,7 +326,7 @@ pub struct LiquidityManager<
 	lsps0_service_handler: Option<LSPS0ServiceHandler>,
 	#[cfg(lsps1_service)]
 	lsps1_service_handler: Option<LSPS1ServiceHandler<ES, CM, C, K>>,
-	lsps1_client_handler: Option<LSPS1ClientHandler<ES, K>>,
+	lsps1_client_handler: Option<LSPS1ClientHandler<ES, CM, K>>,
 	lsps2_service_handler: Option<LSPS2ServiceHandler<CM, K, T>>,
 	lsps2_client_handler: Option<LSPS2ClientHandler<ES, K>>,
 	lsps5_service_handler: Option<LSPS5ServiceHandler<CM, NS, K, TP>>,
@@ -499,6 +502,7 @@ where
 			config.lsps1_client_config.as_ref().map(|config| {
 				LSPS1ClientHandler::new(
 					entropy_source.clone(),
+					channel_manager.clone(),
 					Arc::clone(&pending_messages),
 					Arc::clone(&pending_events),
 					config.clone(),
@@ -573,7 +577,7 @@ where
 	///
 	/// The returned handler allows to initiate the LSPS1 client-side flow, i.e., allows to request
 	/// channels from the configured LSP.
-	pub fn lsps1_client_handler(&self) -> Option<&LSPS1ClientHandler<ES, K>> {
+	pub fn lsps1_client_handler(&self) -> Option<&LSPS1ClientHandler<ES, CM, K>> {
 		self.lsps1_client_handler.as_ref()
 	}
 
@@ -1205,7 +1209,9 @@ where
 	/// Returns a reference to the LSPS1 client-side handler.
 	///
 	/// Wraps [`LiquidityManager::lsps1_client_handler`].
-	pub fn lsps1_client_handler(&self) -> Option<&LSPS1ClientHandler<ES, KVStoreSyncWrapper<KS>>> {
+	pub fn lsps1_client_handler(
+		&self,
+	) -> Option<&LSPS1ClientHandler<ES, CM, KVStoreSyncWrapper<KS>>> {
 		self.inner.lsps1_client_handler()
 	}
 
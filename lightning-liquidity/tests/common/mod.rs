@@ -28,6 +28,7 @@ fn build_service_and_client_nodes<'a, 'b, 'c>(
 
 	let chain_params = ChainParameters {
 		network: Network::Testnet,
+		custom_chain_hash: None,
 		best_block: BestBlock::from_network(Network::Testnet),
 	};
 
@@ -141,7 +141,7 @@ fn create_jit_invoice(
 		log_error!(node.logger, "Invalid payment hash: {:?}", e);
 	})?;
 
-	let currency = Network::Bitcoin.into();
+	let currency = Network::Testnet.into();
 	let mut invoice_builder = InvoiceBuilder::new(currency)
 		.description(description.to_string())
 		.payment_hash(payment_hash)
@@ -1078,6 +1078,7 @@ fn lsps2_service_handler_persistence_across_restarts() {
 		// Create a new LiquidityManager with the same configuration and KV store to simulate restart
 		let chain_params = ChainParameters {
 			network: Network::Testnet,
+			custom_chain_hash: None,
 			best_block: BestBlock::from_network(Network::Testnet),
 		};
 
@@ -1285,6 +1286,23 @@ fn client_trusts_lsp_end_to_end_test() {
 
 	service_handler.channel_ready(user_channel_id, &channel_id, &client_node_id).unwrap();
 
+	match service_node.liquidity_manager.next_event().unwrap() {
+		LiquidityEvent::LSPS2Service(LSPS2ServiceEvent::ChannelOpened {
+			counterparty_node_id,
+			user_channel_id: u,
+			channel_id: c,
+			intercept_scid: sc,
+			opening_fee_msat,
+		}) => {
+			assert_eq!(counterparty_node_id, client_node_id);
+			assert_eq!(u, user_channel_id);
+			assert_eq!(c, channel_id);
+			assert_eq!(sc, intercept_scid);
+			assert_eq!(opening_fee_msat, fee_base_msat);
+		},
+		other => panic!("Expected ChannelOpened event, got: {:?}", other),
+	};
+
 	service_node.inner.node.process_pending_htlc_forwards();
 
 	let pay_event = {
@@ -1352,6 +1370,23 @@ fn client_trusts_lsp_end_to_end_test() {
 		_ => panic!("Expected PaymentForwarded event, got: {:?}", service_events[0]),
 	};
 
+	match service_node.liquidity_manager.next_event().unwrap() {
+		LiquidityEvent::LSPS2Service(LSPS2ServiceEvent::OpeningFeeCollected {
+			counterparty_node_id,
+			user_channel_id: u,
+			channel_id: c,
+			intercept_scid: sc,
+			skimmed_fee_msat,
+		}) => {
+			assert_eq!(counterparty_node_id, client_node_id);
+			assert_eq!(u, user_channel_id);
+			assert_eq!(c, channel_id);
+			assert_eq!(sc, intercept_scid);
+			assert_eq!(skimmed_fee_msat, fee_base_msat);
+		},
+		other => panic!("Expected OpeningFeeCollected event, got: {:?}", other),
+	};
+
 	let broadcasted = service_node.inner.tx_broadcaster.txn_broadcasted.lock().unwrap();
 	assert!(broadcasted.iter().any(|b| b.compute_txid() == funding_tx.compute_txid()));
 
@@ -2314,11 +2349,19 @@ fn client_trusts_lsp_partial_fee_does_not_trigger_broadcast() {
 		other => panic!("Expected PaymentClaimable, got {:?}", other),
 	};
 
-	assert!(service_node.liquidity_manager.get_and_clear_pending_events().is_empty());
+	let service_events = service_node.liquidity_manager.get_and_clear_pending_events();
+	assert_eq!(service_events.len(), 1);
+	assert!(matches!(
+		service_events[0],
+		LiquidityEvent::LSPS2Service(LSPS2ServiceEvent::ChannelOpened { .. })
+	));
 
 	let partial_skim_msat = fee_base_msat - 1; // less than promised fee
 	service_handler.payment_forwarded(channel_id, partial_skim_msat).unwrap();
 
+	// The opening fee hasn't been fully paid, so it isn't reported as collected.
+	assert!(service_node.liquidity_manager.get_and_clear_pending_events().is_empty());
+
 	let broadcasted = service_node.inner.tx_broadcaster.txn_broadcasted.lock().unwrap();
 	assert!(broadcasted.is_empty(), "There should be no broadcasted txs yet");
 	drop(broadcasted);
@@ -1604,6 +1604,7 @@ fn lsps5_service_handler_persistence_across_restarts() {
 		// Create a new LiquidityManager with the same configuration and KV store to simulate restart
 		let chain_params = ChainParameters {
 			network: Network::Testnet,
+			custom_chain_hash: None,
 			best_block: BestBlock::from_network(Network::Testnet),
 		};
 
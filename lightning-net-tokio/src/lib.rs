@@ -17,7 +17,7 @@
 //! (e.g. an [`Arc`]) and must use the [`SocketDescriptor`] provided here as the [`PeerManager`]'s
 //! `SocketDescriptor` implementation.
 //!
-//! Three methods are exposed to register a new connection for handling in [`tokio::spawn`] calls;
+//! Four methods are exposed to register a new connection for handling in [`tokio::spawn`] calls;
 //! see their individual docs for details.
 //!
 //! [`PeerManager`]: lightning::ln::peer_handler::PeerManager
@@ -469,6 +469,94 @@ where
 	}
 }
 
+/// The delay after which, if no connection attempt has completed, [`connect_outbound_any`] will
+/// start attempting to connect to the next address, as recommended by RFC 8305.
+const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+/// Reorders `addrs` such that IPv6 and IPv4 addresses alternate, starting with the family of the
+/// first address and otherwise preserving the given order, as recommended by RFC 8305.
+fn interleave_address_families(addrs: &[SocketAddr]) -> Vec<SocketAddr> {
+	let first_is_ipv6 = match addrs.first() {
+		Some(addr) => addr.is_ipv6(),
+		None => return Vec::new(),
+	};
+	let (preferred, other): (Vec<_>, Vec<_>) =
+		addrs.iter().copied().partition(|addr| addr.is_ipv6() == first_is_ipv6);
+	let mut res = Vec::with_capacity(addrs.len());
+	let (mut preferred, mut other) = (preferred.into_iter(), other.into_iter());
+	loop {
+		match (preferred.next(), other.next()) {
+			(None, None) => break,
+			(a, b) => res.extend(a.into_iter().chain(b)),
+		}
+	}
+	res
+}
+
+/// Attempts to open a TCP connection to any of `addrs` in the style of "Happy Eyeballs" (RFC
+/// 8305), returning the first one to succeed along with the address it was made to.
+async fn connect_any(addrs: &[SocketAddr]) -> Option<(SocketAddr, StdTcpStream)> {
+	let mut pending_addrs = interleave_address_families(addrs).into_iter();
+	// Dropping the `JoinSet` aborts any attempts still in flight once one has succeeded.
+	let mut attempts = tokio::task::JoinSet::new();
+	loop {
+		if let Some(addr) = pending_addrs.next() {
+			attempts.spawn(async move { (addr, TcpStream::connect(addr).await) });
+		} else if attempts.is_empty() {
+			return None;
+		}
+
+		// Wait for an in-flight attempt to complete, starting the next one if none do within
+		// `CONNECTION_ATTEMPT_DELAY` or as soon as one fails.
+		let attempt_res = if pending_addrs.len() > 0 {
+			match time::timeout(CONNECTION_ATTEMPT_DELAY, attempts.join_next()).await {
+				Ok(res) => res,
+				Err(_) => continue,
+			}
+		} else {
+			attempts.join_next().await
+		};
+		if let Some(Ok((addr, Ok(stream)))) = attempt_res {
+			if let Ok(stream) = stream.into_std() {
+				return Some((addr, stream));
+			}
+		}
+	}
+}
+
+/// Process incoming messages and feed outgoing messages on a new connection made to any of the
+/// given socket addresses, all of which are expected to belong to a peer with the given public
+/// key (by scheduling futures with tokio::spawn).
+///
+/// This is useful when a peer announces several addresses, e.g. both IPv6 and IPv4 ones.
+/// Connection attempts are made in the style of "Happy Eyeballs" (RFC 8305): addresses are tried
+/// in the given order, alternating between IPv6 and IPv4 addresses, with a new attempt being
+/// started whenever the previous one fails or hasn't completed within 250ms, without cancelling
+/// any in-flight attempts. The first connection to be established is used and all other attempts
+/// are abandoned, thus addresses should be ordered by preference, with lower-latency transports
+/// first.
+///
+/// Returns a future (as the fn is async) which needs to be polled to complete the connection and
+/// connection setup. That future then returns the address which was connected to, along with a
+/// future which will complete when the peer is disconnected and associated handling futures are
+/// freed, though, because all processing in said futures are spawned with tokio::spawn, you do not
+/// need to poll the second future in order to make progress.
+///
+/// As with [`connect_outbound`], the whole connection process times out after 10 seconds.
+pub async fn connect_outbound_any<PM: Deref + 'static + Send + Sync + Clone>(
+	peer_manager: PM, their_node_id: PublicKey, addrs: &[SocketAddr],
+) -> Option<(SocketAddr, impl std::future::Future<Output = ()>)>
+where
+	PM::Target: APeerManager<Descriptor = SocketDescriptor>,
+{
+	let connect_fut = connect_any(addrs);
+	if let Ok(Some((addr, stream))) = time::timeout(Duration::from_secs(10), connect_fut).await {
+		Some((addr, setup_outbound(peer_manager, their_node_id, stream)))
+	} else {
+		None
+	}
+}
+
 const SOCK_WAKER_VTABLE: task::RawWakerVTable = task::RawWakerVTable::new(
 	clone_socket_waker,
 	wake_socket_waker,
@@ -621,6 +709,7 @@ mod tests {
 	use tokio::sync::mpsc;
 
 	use std::mem;
+	use std::net::SocketAddr;
 	use std::sync::atomic::{AtomicBool, Ordering};
 	use std::sync::{Arc, Mutex};
 	use std::time::Duration;
@@ -743,6 +832,7 @@ mod tests {
 		fn handle_channel_reestablish(&self, _their_node_id: PublicKey, _msg: &ChannelReestablish) {
 		}
 		fn handle_error(&self, _their_node_id: PublicKey, _msg: &ErrorMessage) {}
+		fn handle_warning(&self, _their_node_id: PublicKey, _msg: &WarningMessage) {}
 		fn get_chain_hashes(&self) -> Option<Vec<ChainHash>> {
 			Some(vec![ChainHash::using_genesis_block(Network::Testnet)])
 		}
@@ -932,6 +1022,44 @@ mod tests {
 		tokio::spawn(async move { super::setup_outbound(a_manager, b_pub, conn_b).await });
 	}
 
+	#[test]
+	fn test_interleave_address_families() {
+		let v4 = |port| SocketAddr::from(([127, 0, 0, 1], port));
+		let v6 = |port| SocketAddr::from(([0, 0, 0, 0, 0, 0, 0, 1], port));
+		assert!(super::interleave_address_families(&[]).is_empty());
+		assert_eq!(
+			super::interleave_address_families(&[v6(1), v6(2), v6(3), v4(4), v4(5)]),
+			vec![v6(1), v4(4), v6(2), v4(5), v6(3)]
+		);
+		assert_eq!(
+			super::interleave_address_families(&[v4(1), v6(2), v6(3), v6(4)]),
+			vec![v4(1), v6(2), v6(3), v6(4)]
+		);
+	}
+
+	#[tokio::test]
+	async fn connect_any_fails_over() {
+		// Test that `connect_any` skips over addresses it can't connect to and reports the one it
+		// did connect to.
+		let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let listening_addr = listener.local_addr().unwrap();
+		// Bind and then immediately drop a listener to get an address connections are refused on.
+		let refused_addr = {
+			let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+			listener.local_addr().unwrap()
+		};
+
+		let accept_fut = listener.accept();
+		let connect_fut = super::connect_any(&[refused_addr, listening_addr]);
+		let (accepted, connected) = tokio::join!(accept_fut, connect_fut);
+		let (connected_addr, stream) = connected.unwrap();
+		assert_eq!(connected_addr, listening_addr);
+		assert_eq!(stream.local_addr().unwrap(), accepted.unwrap().1);
+
+		assert!(super::connect_any(&[refused_addr]).await.is_none());
+		assert!(super::connect_any(&[]).await.is_none());
+	}
+
 	#[tokio::test(flavor = "multi_thread")]
 	async fn threaded_race_disconnect_accept() {
 		race_disconnect_accept().await;
@@ -9,6 +9,10 @@
 extern crate criterion;
 
 pub mod fs_store;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_store;
+#[cfg(feature = "vss")]
+pub mod vss_store;
 
 mod utils;
 
@@ -0,0 +1,798 @@
+//! Objects related to [`SqliteStore`] live here.
+use crate::utils::check_namespace_key_validity;
+
+use lightning::util::persist::{KVStoreSync, MigratableKVStore};
+
+use rusqlite::{named_params, Connection, OptionalExtension};
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+#[cfg(feature = "tokio")]
+use core::future::Future;
+#[cfg(feature = "tokio")]
+use lightning::util::persist::KVStore;
+
+/// The default database file name.
+pub const DEFAULT_SQLITE_DB_FILE_NAME: &str = "ldk_data.sqlite";
+
+/// The default table in which we store all data.
+pub const DEFAULT_KV_TABLE_NAME: &str = "ldk_data";
+
+// The current SQLite `user_version`, which we use to track the schema version of the database.
+const SCHEMA_USER_VERSION: u16 = 1;
+
+/// A single write to be applied as part of an atomic batch via [`SqliteStore::write_batch`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SqliteBatchWrite {
+	/// The primary namespace to write to.
+	pub primary_namespace: String,
+	/// The secondary namespace to write to.
+	pub secondary_namespace: String,
+	/// The key to write to.
+	pub key: String,
+	/// The value to write.
+	pub value: Vec<u8>,
+}
+
+struct SqliteStoreInner {
+	connection: Mutex<SqliteConnection>,
+	data_dir: PathBuf,
+	kv_table_name: String,
+}
+
+struct SqliteConnection {
+	connection: Connection,
+
+	// Tracks, per key, the latest version written and the number of operations in flight. This is
+	// used in async contexts to ensure writes to the same key are applied in the order in which
+	// they were initiated. Entries are removed once no operations are in flight for the key.
+	write_versions: HashMap<String, (u64, usize)>,
+}
+
+/// A [`KVStore`] and [`KVStoreSync`] implementation that writes to and reads from an [SQLite]
+/// database.
+///
+/// All data is stored in a single table (which may be configured via
+/// [`SqliteStore::new_with_table_name`], allowing several stores to share one database file),
+/// keyed by primary namespace, secondary namespace, and key. The database is operated in
+/// write-ahead logging (WAL) mode.
+///
+/// In addition to the [`KVStoreSync`] methods, [`SqliteStore::write_batch`] allows writing
+/// several keys atomically, e.g., to persist a `ChannelManager` alongside the `ChannelMonitor`s it
+/// refers to.
+///
+/// [`KVStore`]: lightning::util::persist::KVStore
+/// [SQLite]: https://sqlite.org
+pub struct SqliteStore {
+	inner: Arc<SqliteStoreInner>,
+
+	// Version counter to ensure that writes are applied in the correct order. It is assumed that
+	// read and list operations aren't sensitive to the order of execution.
+	next_version: AtomicU64,
+}
+
+impl SqliteStore {
+	/// Constructs a new [`SqliteStore`], storing data in the file [`DEFAULT_SQLITE_DB_FILE_NAME`]
+	/// within `data_dir` and the table [`DEFAULT_KV_TABLE_NAME`].
+	///
+	/// The data directory and database are created if they don't exist yet.
+	pub fn new(data_dir: PathBuf) -> Result<Self, lightning::io::Error> {
+		Self::new_with_table_name(
+			data_dir,
+			DEFAULT_SQLITE_DB_FILE_NAME.to_string(),
+			DEFAULT_KV_TABLE_NAME.to_string(),
+		)
+	}
+
+	/// Constructs a new [`SqliteStore`], storing data in the file `db_file_name` within `data_dir`
+	/// and the table `kv_table_name`.
+	///
+	/// The table name may only contain ASCII alphanumeric characters and underscores and may not
+	/// start with a digit.
+	///
+	/// The data directory, database, and table are created if they don't exist yet.
+	pub fn new_with_table_name(
+		data_dir: PathBuf, db_file_name: String, kv_table_name: String,
+	) -> Result<Self, lightning::io::Error> {
+		if !is_valid_table_name(&kv_table_name) {
+			let msg = format!("Failed to open SqliteStore: invalid table name {}", kv_table_name);
+			return Err(lightning::io::Error::new(lightning::io::ErrorKind::InvalidInput, msg));
+		}
+
+		fs::create_dir_all(&data_dir).map_err(|e| {
+			let msg = format!(
+				"Failed to create database destination directory {}: {}",
+				data_dir.display(),
+				e
+			);
+			lightning::io::Error::new(lightning::io::ErrorKind::Other, msg)
+		})?;
+		let mut db_file_path = data_dir.clone();
+		db_file_path.push(db_file_name);
+
+		let connection = Connection::open(&db_file_path).map_err(|e| {
+			let msg = format!("Failed to open/create database {}: {}", db_file_path.display(), e);
+			lightning::io::Error::new(lightning::io::ErrorKind::Other, msg)
+		})?;
+
+		// WAL mode allows readers to proceed concurrently with a writer and generally makes writes
+		// cheaper. Together with `synchronous = FULL`, committed transactions are durable even on
+		// power loss.
+		connection
+			.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))
+			.map_err(|e| {
+				let msg = format!("Failed to enable WAL mode: {}", e);
+				lightning::io::Error::new(lightning::io::ErrorKind::Other, msg)
+			})?;
+		connection.pragma_update(None, "synchronous", "FULL").map_err(|e| {
+			let msg = format!("Failed to set synchronous mode: {}", e);
+			lightning::io::Error::new(lightning::io::ErrorKind::Other, msg)
+		})?;
+
+		let user_version: u16 =
+			connection.pragma_query_value(None, "user_version", |row| row.get(0)).map_err(|e| {
+				let msg = format!("Failed to read database user_version: {}", e);
+				lightning::io::Error::new(lightning::io::ErrorKind::Other, msg)
+			})?;
+		if user_version == 0 {
+			connection.pragma_update(None, "user_version", SCHEMA_USER_VERSION).map_err(|e| {
+				let msg = format!("Failed to set database user_version: {}", e);
+				lightning::io::Error::new(lightning::io::ErrorKind::Other, msg)
+			})?;
+		} else if user_version > SCHEMA_USER_VERSION {
+			let msg = format!(
+				"Failed to open database: unknown schema version {} (we support up to {})",
+				user_version, SCHEMA_USER_VERSION
+			);
+			return Err(lightning::io::Error::new(lightning::io::ErrorKind::Other, msg));
+		}
+
+		let sql = format!(
+			"CREATE TABLE IF NOT EXISTS {} (
+				primary_namespace TEXT NOT NULL,
+				secondary_namespace TEXT DEFAULT '' NOT NULL,
+				key TEXT NOT NULL CHECK (key <> ''),
+				value BLOB, PRIMARY KEY ( primary_namespace, secondary_namespace, key )
+			);",
+			kv_table_name
+		);
+		connection.execute(&sql, []).map_err(|e| {
+			let msg = format!("Failed to create table {}: {}", kv_table_name, e);
+			lightning::io::Error::new(lightning::io::ErrorKind::Other, msg)
+		})?;
+
+		let connection =
+			Mutex::new(SqliteConnection { connection, write_versions: HashMap::new() });
+		Ok(Self {
+			inner: Arc::new(SqliteStoreInner { connection, data_dir, kv_table_name }),
+			next_version: AtomicU64::new(1),
+		})
+	}
+
+	/// Returns the data directory.
+	pub fn get_data_dir(&self) -> PathBuf {
+		self.inner.data_dir.clone()
+	}
+
+	/// Atomically writes all of the given entries, i.e., either all or none of them will have been
+	/// persisted once this returns.
+	///
+	/// This may be used to persist a `ChannelManager` together with the `ChannelMonitor`s it refers
+	/// to without risking the two getting out of sync on crash.
+	pub fn write_batch(&self, writes: Vec<SqliteBatchWrite>) -> Result<(), lightning::io::Error> {
+		for write in writes.iter() {
+			check_namespace_key_validity(
+				&write.primary_namespace,
+				&write.secondary_namespace,
+				Some(&write.key),
+				"write",
+			)?;
+		}
+		let versioned_writes = writes
+			.into_iter()
+			.map(|write| {
+				let locking_key = get_locking_key(
+					&write.primary_namespace,
+					&write.secondary_namespace,
+					&write.key,
+				);
+				let version = self.get_new_version(&locking_key);
+				(locking_key, version, write)
+			})
+			.collect();
+		self.inner.write_batch(versioned_writes)
+	}
+
+	fn get_new_version(&self, locking_key: &str) -> u64 {
+		let version = self.next_version.fetch_add(1, Ordering::Relaxed);
+		if version == u64::MAX {
+			panic!("SqliteStore version counter overflowed");
+		}
+
+		// Register the in-flight operation early so that the entry isn't cleaned up before the
+		// operation completes.
+		self.inner.register_in_flight(locking_key);
+
+		version
+	}
+
+	#[cfg(any(all(feature = "tokio", test), fuzzing))]
+	/// Returns the size of the async state.
+	pub fn state_size(&self) -> usize {
+		self.inner.connection.lock().unwrap().write_versions.len()
+	}
+}
+
+impl KVStoreSync for SqliteStore {
+	fn read(
+		&self, primary_namespace: &str, secondary_namespace: &str, key: &str,
+	) -> Result<Vec<u8>, lightning::io::Error> {
+		check_namespace_key_validity(primary_namespace, secondary_namespace, Some(key), "read")?;
+		self.inner.read(primary_namespace, secondary_namespace, key)
+	}
+
+	fn write(
+		&self, primary_namespace: &str, secondary_namespace: &str, key: &str, buf: Vec<u8>,
+	) -> Result<(), lightning::io::Error> {
+		check_namespace_key_validity(primary_namespace, secondary_namespace, Some(key), "write")?;
+		let locking_key = get_locking_key(primary_namespace, secondary_namespace, key);
+		let version = self.get_new_version(&locking_key);
+		self.inner.write_version(
+			locking_key,
+			version,
+			primary_namespace,
+			secondary_namespace,
+			key,
+			buf,
+		)
+	}
+
+	fn remove(
+		&self, primary_namespace: &str, secondary_namespace: &str, key: &str, _lazy: bool,
+	) -> Result<(), lightning::io::Error> {
+		check_namespace_key_validity(primary_namespace, secondary_namespace, Some(key), "remove")?;
+		let locking_key = get_locking_key(primary_namespace, secondary_namespace, key);
+		let version = self.get_new_version(&locking_key);
+		self.inner.remove_version(locking_key, version, primary_namespace, secondary_namespace, key)
+	}
+
+	fn list(
+		&self, primary_namespace: &str, secondary_namespace: &str,
+	) -> Result<Vec<String>, lightning::io::Error> {
+		check_namespace_key_validity(primary_namespace, secondary_namespace, None, "list")?;
+		self.inner.list(primary_namespace, secondary_namespace)
+	}
+}
+
+impl SqliteStoreInner {
+	fn register_in_flight(&self, locking_key: &str) {
+		let mut locked_conn = self.connection.lock().unwrap();
+		locked_conn.write_versions.entry(locking_key.to_string()).or_insert((0, 0)).1 += 1;
+	}
+
+	fn read(
+		&self, primary_namespace: &str, secondary_namespace: &str, key: &str,
+	) -> lightning::io::Result<Vec<u8>> {
+		let locked_conn = self.connection.lock().unwrap();
+		let sql = format!(
+			"SELECT value FROM {} WHERE primary_namespace=:primary_namespace AND secondary_namespace=:secondary_namespace AND key=:key;",
+			self.kv_table_name
+		);
+
+		let mut stmt = locked_conn.connection.prepare_cached(&sql).map_err(|e| {
+			let msg = format!("Failed to prepare statement: {}", e);
+			lightning::io::Error::new(lightning::io::ErrorKind::Other, msg)
+		})?;
+
+		let res = stmt
+			.query_row(
+				named_params! {
+					":primary_namespace": primary_namespace,
+					":secondary_namespace": secondary_namespace,
+					":key": key,
+				},
+				|row| row.get(0),
+			)
+			.optional()
+			.map_err(|e| {
+				let msg = format!(
+					"Failed to read from key {}/{}/{}: {}",
+					primary_namespace, secondary_namespace, key, e
+				);
+				lightning::io::Error::new(lightning::io::ErrorKind::Other, msg)
+			})?;
+
+		res.ok_or_else(|| {
+			let msg = format!(
+				"Failed to read as key could not be found: {}/{}/{}",
+				primary_namespace, secondary_namespace, key
+			);
+			lightning::io::Error::new(lightning::io::ErrorKind::NotFound, msg)
+		})
+	}
+
+	/// Runs `callback` unless a newer version of the key at `locking_key` has already been
+	/// written or removed, marking the in-flight operation as complete in either case.
+	fn execute_locked_write<F: FnOnce(&mut Connection) -> Result<(), lightning::io::Error>>(
+		&self, locking_key: String, version: u64, callback: F,
+	) -> Result<(), lightning::io::Error> {
+		let mut locked_conn = self.connection.lock().unwrap();
+		let SqliteConnection { connection, write_versions } = &mut *locked_conn;
+
+		let res = {
+			let (last_written_version, _) =
+				write_versions.get(&locking_key).expect("In-flight operations are registered");
+
+			// If a newer version has already been written or removed we can and must skip writing.
+			if version <= *last_written_version {
+				Ok(())
+			} else {
+				callback(connection).map(|_| {
+					write_versions.get_mut(&locking_key).expect("Checked above").0 = version;
+				})
+			}
+		};
+
+		Self::complete_in_flight(write_versions, &locking_key);
+
+		res
+	}
+
+	fn complete_in_flight(write_versions: &mut HashMap<String, (u64, usize)>, locking_key: &str) {
+		let in_flight = {
+			let entry =
+				write_versions.get_mut(locking_key).expect("In-flight operations are registered");
+			entry.1 -= 1;
+			entry.1
+		};
+		if in_flight == 0 {
+			write_versions.remove(locking_key);
+		}
+	}
+
+	fn write_version(
+		&self, locking_key: String, version: u64, primary_namespace: &str,
+		secondary_namespace: &str, key: &str, buf: Vec<u8>,
+	) -> lightning::io::Result<()> {
+		let kv_table_name = &self.kv_table_name;
+		self.execute_locked_write(locking_key, version, |connection| {
+			write_row(connection, kv_table_name, primary_namespace, secondary_namespace, key, &buf)
+		})
+	}
+
+	fn remove_version(
+		&self, locking_key: String, version: u64, primary_namespace: &str,
+		secondary_namespace: &str, key: &str,
+	) -> lightning::io::Result<()> {
+		self.execute_locked_write(locking_key, version, |connection| {
+			let sql = format!("DELETE FROM {} WHERE primary_namespace=:primary_namespace AND secondary_namespace=:secondary_namespace AND key=:key;", self.kv_table_name);
+
+			let mut stmt = connection.prepare_cached(&sql).map_err(|e| {
+				let msg = format!("Failed to prepare statement: {}", e);
+				lightning::io::Error::new(lightning::io::ErrorKind::Other, msg)
+			})?;
+
+			stmt.execute(named_params! {
+				":primary_namespace": primary_namespace,
+				":secondary_namespace": secondary_namespace,
+				":key": key,
+			})
+			.map_err(|e| {
+				let msg = format!(
+					"Failed to delete key {}/{}/{}: {}",
+					primary_namespace, secondary_namespace, key, e
+				);
+				lightning::io::Error::new(lightning::io::ErrorKind::Other, msg)
+			})?;
+			Ok(())
+		})
+	}
+
+	fn write_batch(
+		&self, writes: Vec<(String, u64, SqliteBatchWrite)>,
+	) -> lightning::io::Result<()> {
+		let mut locked_conn = self.connection.lock().unwrap();
+		let SqliteConnection { connection, write_versions } = &mut *locked_conn;
+
+		// Only apply the writes for which no newer version was written in the meantime. As
+		// versions are handed out in order, if a later write to the same key is part of this batch
+		// it will supersede the earlier one.
+		let res = (|| {
+			let tx = connection.transaction().map_err(|e| {
+				let msg = format!("Failed to start transaction: {}", e);
+				lightning::io::Error::new(lightning::io::ErrorKind::Other, msg)
+			})?;
+			for (locking_key, version, write) in writes.iter() {
+				let (last_written_version, _) =
+					write_versions.get(locking_key).expect("In-flight operations are registered");
+				if *version <= *last_written_version {
+					continue;
+				}
+				write_row(
+					&tx,
+					&self.kv_table_name,
+					&write.primary_namespace,
+					&write.secondary_namespace,
+					&write.key,
+					&write.value,
+				)?;
+			}
+			tx.commit().map_err(|e| {
+				let msg = format!("Failed to commit transaction: {}", e);
+				lightning::io::Error::new(lightning::io::ErrorKind::Other, msg)
+			})
+		})();
+
+		for (locking_key, version, _) in writes.iter() {
+			if res.is_ok() {
+				let entry = write_versions
+					.get_mut(locking_key)
+					.expect("In-flight operations are registered");
+				entry.0 = core::cmp::max(entry.0, *version);
+			}
+			Self::complete_in_flight(write_versions, locking_key);
+		}
+
+		res
+	}
+
+	fn list(
+		&self, primary_namespace: &str, secondary_namespace: &str,
+	) -> lightning::io::Result<Vec<String>> {
+		let locked_conn = self.connection.lock().unwrap();
+
+		let sql = format!(
+			"SELECT key FROM {} WHERE primary_namespace=:primary_namespace AND secondary_namespace=:secondary_namespace",
+			self.kv_table_name
+		);
+		let mut stmt = locked_conn.connection.prepare_cached(&sql).map_err(|e| {
+			let msg = format!("Failed to prepare statement: {}", e);
+			lightning::io::Error::new(lightning::io::ErrorKind::Other, msg)
+		})?;
+
+		let mut keys = Vec::new();
+
+		let rows_iter = stmt
+			.query_map(
+				named_params! {
+					":primary_namespace": primary_namespace,
+					":secondary_namespace": secondary_namespace,
+				},
+				|row| row.get(0),
+			)
+			.map_err(|e| {
+				let msg = format!("Failed to retrieve queried rows: {}", e);
+				lightning::io::Error::new(lightning::io::ErrorKind::Other, msg)
+			})?;
+
+		for k in rows_iter {
+			keys.push(k.map_err(|e| {
+				let msg = format!("Failed to retrieve queried rows: {}", e);
+				lightning::io::Error::new(lightning::io::ErrorKind::Other, msg)
+			})?);
+		}
+
+		Ok(keys)
+	}
+}
+
+#[cfg(feature = "tokio")]
+impl KVStore for SqliteStore {
+	fn read(
+		&self, primary_namespace: &str, secondary_namespace: &str, key: &str,
+	) -> impl Future<Output = Result<Vec<u8>, lightning::io::Error>> + 'static + Send {
+		let this = Arc::clone(&self.inner);
+		let primary_namespace = primary_namespace.to_string();
+		let secondary_namespace = secondary_namespace.to_string();
+		let key = key.to_string();
+		let validity = check_namespace_key_validity(
+			&primary_namespace,
+			&secondary_namespace,
+			Some(&key),
+			"read",
+		);
+
+		async move {
+			validity?;
+			tokio::task::spawn_blocking(move || {
+				this.read(&primary_namespace, &secondary_namespace, &key)
+			})
+			.await
+			.unwrap_or_else(|e| Err(lightning::io::Error::new(lightning::io::ErrorKind::Other, e)))
+		}
+	}
+
+	fn write(
+		&self, primary_namespace: &str, secondary_namespace: &str, key: &str, buf: Vec<u8>,
+	) -> impl Future<Output = Result<(), lightning::io::Error>> + 'static + Send {
+		let this = Arc::clone(&self.inner);
+		let primary_namespace = primary_namespace.to_string();
+		let secondary_namespace = secondary_namespace.to_string();
+		let key = key.to_string();
+		let version = check_namespace_key_validity(
+			&primary_namespace,
+			&secondary_namespace,
+			Some(&key),
+			"write",
+		)
+		.map(|_| {
+			let locking_key = get_locking_key(&primary_namespace, &secondary_namespace, &key);
+			let version = self.get_new_version(&locking_key);
+			(locking_key, version)
+		});
+
+		async move {
+			let (locking_key, version) = match version {
+				Ok(res) => res,
+				Err(e) => return Err(e),
+			};
+			tokio::task::spawn_blocking(move || {
+				this.write_version(
+					locking_key,
+					version,
+					&primary_namespace,
+					&secondary_namespace,
+					&key,
+					buf,
+				)
+			})
+			.await
+			.unwrap_or_else(|e| Err(lightning::io::Error::new(lightning::io::ErrorKind::Other, e)))
+		}
+	}
+
+	fn remove(
+		&self, primary_namespace: &str, secondary_namespace: &str, key: &str, _lazy: bool,
+	) -> impl Future<Output = Result<(), lightning::io::Error>> + 'static + Send {
+		let this = Arc::clone(&self.inner);
+		let primary_namespace = primary_namespace.to_string();
+		let secondary_namespace = secondary_namespace.to_string();
+		let key = key.to_string();
+		let version = check_namespace_key_validity(
+			&primary_namespace,
+			&secondary_namespace,
+			Some(&key),
+			"remove",
+		)
+		.map(|_| {
+			let locking_key = get_locking_key(&primary_namespace, &secondary_namespace, &key);
+			let version = self.get_new_version(&locking_key);
+			(locking_key, version)
+		});
+
+		async move {
+			let (locking_key, version) = match version {
+				Ok(res) => res,
+				Err(e) => return Err(e),
+			};
+			tokio::task::spawn_blocking(move || {
+				this.remove_version(
+					locking_key,
+					version,
+					&primary_namespace,
+					&secondary_namespace,
+					&key,
+				)
+			})
+			.await
+			.unwrap_or_else(|e| Err(lightning::io::Error::new(lightning::io::ErrorKind::Other, e)))
+		}
+	}
+
+	fn list(
+		&self, primary_namespace: &str, secondary_namespace: &str,
+	) -> impl Future<Output = Result<Vec<String>, lightning::io::Error>> + 'static + Send {
+		let this = Arc::clone(&self.inner);
+		let primary_namespace = primary_namespace.to_string();
+		let secondary_namespace = secondary_namespace.to_string();
+		let validity =
+			check_namespace_key_validity(&primary_namespace, &secondary_namespace, None, "list");
+
+		async move {
+			validity?;
+			tokio::task::spawn_blocking(move || this.list(&primary_namespace, &secondary_namespace))
+				.await
+				.unwrap_or_else(|e| {
+					Err(lightning::io::Error::new(lightning::io::ErrorKind::Other, e))
+				})
+		}
+	}
+}
+
+impl MigratableKVStore for SqliteStore {
+	fn list_all_keys(&self) -> Result<Vec<(String, String, String)>, lightning::io::Error> {
+		let locked_conn = self.inner.connection.lock().unwrap();
+
+		let sql = format!(
+			"SELECT primary_namespace, secondary_namespace, key FROM {}",
+			self.inner.kv_table_name
+		);
+		let mut stmt = locked_conn.connection.prepare_cached(&sql).map_err(|e| {
+			let msg = format!("Failed to prepare statement: {}", e);
+			lightning::io::Error::new(lightning::io::ErrorKind::Other, msg)
+		})?;
+
+		let mut keys = Vec::new();
+
+		let rows_iter =
+			stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))).map_err(|e| {
+				let msg = format!("Failed to retrieve queried rows: {}", e);
+				lightning::io::Error::new(lightning::io::ErrorKind::Other, msg)
+			})?;
+
+		for k in rows_iter {
+			keys.push(k.map_err(|e| {
+				let msg = format!("Failed to retrieve queried rows: {}", e);
+				lightning::io::Error::new(lightning::io::ErrorKind::Other, msg)
+			})?);
+		}
+
+		Ok(keys)
+	}
+}
+
+fn write_row(
+	connection: &Connection, kv_table_name: &str, primary_namespace: &str,
+	secondary_namespace: &str, key: &str, buf: &[u8],
+) -> lightning::io::Result<()> {
+	let sql = format!(
+		"INSERT OR REPLACE INTO {} (primary_namespace, secondary_namespace, key, value) VALUES (:primary_namespace, :secondary_namespace, :key, :value);",
+		kv_table_name
+	);
+
+	let mut stmt = connection.prepare_cached(&sql).map_err(|e| {
+		let msg = format!("Failed to prepare statement: {}", e);
+		lightning::io::Error::new(lightning::io::ErrorKind::Other, msg)
+	})?;
+
+	stmt.execute(named_params! {
+		":primary_namespace": primary_namespace,
+		":secondary_namespace": secondary_namespace,
+		":key": key,
+		":value": buf,
+	})
+	.map(|_| ())
+	.map_err(|e| {
+		let msg = format!(
+			"Failed to write to key {}/{}/{}: {}",
+			primary_namespace, secondary_namespace, key, e
+		);
+		lightning::io::Error::new(lightning::io::ErrorKind::Other, msg)
+	})
+}
+
+fn get_locking_key(primary_namespace: &str, secondary_namespace: &str, key: &str) -> String {
+	// Namespaces and keys may not contain '/', so this is unambiguous.
+	format!("{}/{}/{}", primary_namespace, secondary_namespace, key)
+}
+
+fn is_valid_table_name(table_name: &str) -> bool {
+	!table_name.is_empty()
+		&& !table_name.starts_with(|c: char| c.is_ascii_digit())
+		&& table_name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_utils::{
+		do_read_write_remove_list_persist, do_test_data_migration, do_test_store,
+	};
+
+	impl Drop for SqliteStore {
+		fn drop(&mut self) {
+			match fs::remove_dir_all(&self.inner.data_dir) {
+				Err(e) => println!("Failed to remove test store directory: {}", e),
+				_ => {},
+			}
+		}
+	}
+
+	#[test]
+	fn read_write_remove_list_persist() {
+		let mut temp_path = std::env::temp_dir();
+		temp_path.push("test_sqlite_read_write_remove_list_persist");
+		let store = SqliteStore::new(temp_path).unwrap();
+		do_read_write_remove_list_persist(&store);
+	}
+
+	#[test]
+	fn test_data_migration() {
+		let mut source_temp_path = std::env::temp_dir();
+		source_temp_path.push("test_sqlite_data_migration_source");
+		let mut source_store = SqliteStore::new(source_temp_path).unwrap();
+
+		let mut target_temp_path = std::env::temp_dir();
+		target_temp_path.push("test_sqlite_data_migration_target");
+		let mut target_store = SqliteStore::new(target_temp_path).unwrap();
+
+		do_test_data_migration(&mut source_store, &mut target_store);
+	}
+
+	#[test]
+	fn test_sqlite_store() {
+		// Create the nodes, giving them SqliteStores for data stores.
+		let store_0 = SqliteStore::new("test_sqlite_store_0".into()).unwrap();
+		let store_1 = SqliteStore::new("test_sqlite_store_1".into()).unwrap();
+		do_test_store(&store_0, &store_1)
+	}
+
+	#[test]
+	fn test_write_batch() {
+		let mut temp_path = std::env::temp_dir();
+		temp_path.push("test_sqlite_write_batch");
+		let store = SqliteStore::new(temp_path).unwrap();
+
+		let write = |primary: &str, secondary: &str, key: &str, value: Vec<u8>| SqliteBatchWrite {
+			primary_namespace: primary.to_string(),
+			secondary_namespace: secondary.to_string(),
+			key: key.to_string(),
+			value,
+		};
+
+		store
+			.write_batch(vec![
+				write("", "", "manager", vec![1; 32]),
+				write("monitors", "", "monitor_a", vec![2; 32]),
+				write("monitors", "", "monitor_b", vec![3; 32]),
+				// A later write to the same key within a batch supersedes the earlier one.
+				write("monitors", "", "monitor_a", vec![4; 32]),
+			])
+			.unwrap();
+		assert_eq!(store.inner.connection.lock().unwrap().write_versions.len(), 0);
+
+		assert_eq!(KVStoreSync::read(&store, "", "", "manager").unwrap(), vec![1; 32]);
+		assert_eq!(KVStoreSync::read(&store, "monitors", "", "monitor_a").unwrap(), vec![4; 32]);
+		assert_eq!(KVStoreSync::read(&store, "monitors", "", "monitor_b").unwrap(), vec![3; 32]);
+		let mut keys = KVStoreSync::list(&store, "monitors", "").unwrap();
+		keys.sort();
+		assert_eq!(keys, vec!["monitor_a".to_string(), "monitor_b".to_string()]);
+
+		// If any write in the batch fails, none of them are applied. We bypass the key validity
+		// checks to provoke a failure when writing an empty key.
+		let manager_locking_key = get_locking_key("", "", "manager");
+		let bogus_locking_key = get_locking_key("", "", "");
+		let manager_version = store.get_new_version(&manager_locking_key);
+		let bogus_version = store.get_new_version(&bogus_locking_key);
+		let res = store.inner.write_batch(vec![
+			(manager_locking_key, manager_version, write("", "", "manager", vec![5; 32])),
+			(bogus_locking_key, bogus_version, write("", "", "", vec![6; 32])),
+		]);
+		assert!(res.is_err());
+		assert_eq!(store.inner.connection.lock().unwrap().write_versions.len(), 0);
+		assert_eq!(KVStoreSync::read(&store, "", "", "manager").unwrap(), vec![1; 32]);
+	}
+
+	#[test]
+	fn test_table_names() {
+		let mut temp_path = std::env::temp_dir();
+		temp_path.push("test_sqlite_table_names");
+		let store_a = SqliteStore::new_with_table_name(
+			temp_path.clone(),
+			DEFAULT_SQLITE_DB_FILE_NAME.to_string(),
+			"store_a".to_string(),
+		)
+		.unwrap();
+		let store_b = SqliteStore::new_with_table_name(
+			temp_path.clone(),
+			DEFAULT_SQLITE_DB_FILE_NAME.to_string(),
+			"store_b".to_string(),
+		)
+		.unwrap();
+
+		KVStoreSync::write(&store_a, "ns", "", "key", vec![1; 32]).unwrap();
+		assert_eq!(KVStoreSync::read(&store_a, "ns", "", "key").unwrap(), vec![1; 32]);
+		assert!(KVStoreSync::list(&store_b, "ns", "").unwrap().is_empty());
+
+		assert!(SqliteStore::new_with_table_name(
+			temp_path,
+			DEFAULT_SQLITE_DB_FILE_NAME.to_string(),
+			"bogus; DROP TABLE store_a".to_string(),
+		)
+		.is_err());
+	}
+}
@@ -0,0 +1,657 @@
+//! Objects related to [`VssStore`] live here.
+use crate::utils::check_namespace_key_validity;
+
+use bitcoin::hashes::{sha256, Hash, HashEngine, Hmac, HmacEngine};
+
+use lightning::sign::EntropySource;
+use lightning::util::persist::{KVStore, KVStoreSync, MigratableKVStore};
+
+use prost::Message;
+
+use vss_client::client::VssClient;
+use vss_client::error::VssError;
+use vss_client::headers::{FixedHeaders, VssHeaderProvider};
+use vss_client::types::{
+	DeleteObjectRequest, GetObjectRequest, KeyValue, ListKeyVersionsRequest, PutObjectRequest,
+	Storable,
+};
+use vss_client::util::key_obfuscator::KeyObfuscator;
+use vss_client::util::retry::{
+	ExponentialBackoffRetryPolicy, FilteredRetryPolicy, JitteredRetryPolicy,
+	MaxAttemptsRetryPolicy, MaxTotalDelayRetryPolicy, RetryPolicy,
+};
+use vss_client::util::storable_builder::{self, StorableBuilder};
+
+use core::future::Future;
+use core::time::Duration;
+
+use std::collections::HashMap;
+#[cfg(test)]
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+type CustomRetryPolicy = FilteredRetryPolicy<
+	JitteredRetryPolicy<
+		MaxTotalDelayRetryPolicy<MaxAttemptsRetryPolicy<ExponentialBackoffRetryPolicy<VssError>>>,
+	>,
+	Box<dyn Fn(&VssError) -> bool + 'static + Send + Sync>,
+>;
+
+// The maximum number of attempts we make for any single request to the VSS server.
+const MAX_REQUEST_ATTEMPTS: u32 = 10;
+
+// The maximum total time we spend retrying any single request to the VSS server.
+const MAX_REQUEST_RETRY_DELAY: Duration = Duration::from_secs(15);
+
+// The number of keys we request per page when listing keys.
+const LIST_KEYS_PAGE_SIZE: i32 = 100;
+
+// The separator between the namespaces and the (obfuscated) key in the keys we store on the
+// server. Namespaces and keys may not contain it.
+const KEY_SEPARATOR: char = '#';
+
+// The labels used to derive the encryption and obfuscation keys from the seed given to
+// `VssStore::new`.
+const DATA_ENCRYPTION_KEY_LABEL: &[u8] = b"LDK VSS data encryption key";
+const KEY_OBFUSCATION_KEY_LABEL: &[u8] = b"LDK VSS key obfuscation key";
+
+/// The per-key state we track to detect lost updates and order concurrent writes.
+#[derive(Default)]
+struct KeyState {
+	// The version the server currently stores for the key, if we know it. Writes are conditional
+	// on this version, causing them to fail if another client updated the key in the meantime.
+	server_version: Option<i64>,
+	// The local version of the last write or removal applied to the key. Used to skip stale
+	// writes in async contexts.
+	last_applied_version: u64,
+}
+
+struct VssStoreInner {
+	client: VssClient<CustomRetryPolicy>,
+	store_id: String,
+	storable_builder: StorableBuilder<EntropySourceAdapter>,
+	key_obfuscator: KeyObfuscator,
+
+	// Per-key state, which also serializes operations on the same key.
+	key_states: Mutex<HashMap<String, Arc<tokio::sync::Mutex<KeyState>>>>,
+
+	// Fails the next write after it was applied on the server, as if its response had been lost.
+	#[cfg(test)]
+	fail_next_write: AtomicBool,
+}
+
+/// A [`KVStore`] and [`KVStoreSync`] implementation that persists data to a remote server
+/// speaking the [Versioned Storage Service] (VSS) protocol.
+///
+/// All values are encrypted client-side with ChaCha20-Poly1305 before being sent to the server
+/// and keys are obfuscated, such that the server learns nothing but the namespaces and the size
+/// of the stored data. Both the encryption and obfuscation keys are derived from the seed given
+/// on construction, which therefore needs to be the same across restarts and devices.
+///
+/// Writes are conditional on the version of the key we last saw on the server. If another
+/// client updated a key in the meantime, the write fails with an error rather than silently
+/// overwriting the other client's update. This makes it safe-ish to point several devices at the
+/// same store, but users should still ensure only one node is running at a time.
+///
+/// The [`KVStoreSync`] methods block on an internal runtime and hence must not be called from
+/// within an async context. Use the [`KVStore`] methods instead in that case. The store may be
+/// dropped from within an async context though, in which case the internal runtime is shut down
+/// in the background rather than waiting for its worker threads to exit.
+///
+/// [Versioned Storage Service]: https://github.com/lightningdevkit/vss-server
+pub struct VssStore {
+	inner: Arc<VssStoreInner>,
+	// Only `None` while being dropped.
+	runtime: Option<tokio::runtime::Runtime>,
+
+	// Version counter to ensure that writes are applied in the correct order. It is assumed that
+	// read and list operations aren't sensitive to the order of execution.
+	next_version: AtomicU64,
+}
+
+impl VssStore {
+	/// Constructs a new [`VssStore`] talking to the VSS server at `base_url`, storing data in the
+	/// store identified by `store_id`.
+	///
+	/// The encryption and key obfuscation keys are derived from `vss_seed`, which must be kept
+	/// secret and be the same every time the store is constructed. `entropy_source` is used to
+	/// generate the nonces used for encryption.
+	pub fn new<ES: EntropySource + Send + Sync + 'static>(
+		base_url: String, store_id: String, vss_seed: [u8; 32], entropy_source: ES,
+	) -> Result<Self, lightning::io::Error> {
+		Self::new_with_header_provider(
+			base_url,
+			store_id,
+			vss_seed,
+			entropy_source,
+			Arc::new(FixedHeaders::new(HashMap::new())),
+		)
+	}
+
+	/// Constructs a new [`VssStore`] like [`VssStore::new`], attaching the headers returned by
+	/// `header_provider` to each request, e.g., to authenticate with the server.
+	pub fn new_with_header_provider<ES: EntropySource + Send + Sync + 'static>(
+		base_url: String, store_id: String, vss_seed: [u8; 32], entropy_source: ES,
+		header_provider: Arc<dyn VssHeaderProvider>,
+	) -> Result<Self, lightning::io::Error> {
+		let runtime =
+			tokio::runtime::Builder::new_multi_thread().enable_all().build().map_err(|e| {
+				let msg = format!("Failed to build VssStore runtime: {}", e);
+				lightning::io::Error::new(lightning::io::ErrorKind::Other, msg)
+			})?;
+
+		let data_encryption_key = derive_key(&vss_seed, DATA_ENCRYPTION_KEY_LABEL);
+		let obfuscation_master_key = derive_key(&vss_seed, KEY_OBFUSCATION_KEY_LABEL);
+		let storable_builder = StorableBuilder::new(
+			data_encryption_key,
+			EntropySourceAdapter(Box::new(entropy_source)),
+		);
+		let key_obfuscator = KeyObfuscator::new(obfuscation_master_key);
+
+		let retry_policy = ExponentialBackoffRetryPolicy::new(Duration::from_millis(10))
+			.with_max_attempts(MAX_REQUEST_ATTEMPTS)
+			.with_max_total_delay(MAX_REQUEST_RETRY_DELAY)
+			.with_max_jitter(Duration::from_millis(10))
+			.skip_retry_on_error(Box::new(|e: &VssError| {
+				matches!(
+					e,
+					VssError::NoSuchKeyError(..)
+						| VssError::InvalidRequestError(..)
+						| VssError::ConflictError(..)
+				)
+			}) as _);
+		let client = VssClient::new_with_headers(base_url, retry_policy, header_provider);
+
+		Ok(Self {
+			inner: Arc::new(VssStoreInner {
+				client,
+				store_id,
+				storable_builder,
+				key_obfuscator,
+				key_states: Mutex::new(HashMap::new()),
+				#[cfg(test)]
+				fail_next_write: AtomicBool::new(false),
+			}),
+			runtime: Some(runtime),
+			next_version: AtomicU64::new(1),
+		})
+	}
+
+	fn runtime(&self) -> &tokio::runtime::Runtime {
+		self.runtime.as_ref().expect("VssStore runtime is only taken on drop")
+	}
+
+	fn get_new_version_and_state_ref(
+		&self, store_key: String,
+	) -> (Arc<tokio::sync::Mutex<KeyState>>, u64) {
+		let version = self.next_version.fetch_add(1, Ordering::Relaxed);
+		if version == u64::MAX {
+			panic!("VssStore version counter overflowed");
+		}
+		(self.inner.get_key_state_ref(store_key), version)
+	}
+}
+
+impl VssStoreInner {
+	fn get_key_state_ref(&self, store_key: String) -> Arc<tokio::sync::Mutex<KeyState>> {
+		let mut key_states = self.key_states.lock().unwrap();
+		Arc::clone(&key_states.entry(store_key).or_default())
+	}
+
+	fn build_store_key(
+		&self, primary_namespace: &str, secondary_namespace: &str, key: &str,
+	) -> String {
+		let obfuscated_key = self.key_obfuscator.obfuscate(key);
+		format!(
+			"{}{}{}{}{}",
+			primary_namespace, KEY_SEPARATOR, secondary_namespace, KEY_SEPARATOR, obfuscated_key
+		)
+	}
+
+	fn extract_key(&self, store_key: &str) -> lightning::io::Result<(String, String, String)> {
+		let mut parts = store_key.splitn(3, KEY_SEPARATOR);
+		match (parts.next(), parts.next(), parts.next()) {
+			(Some(primary_namespace), Some(secondary_namespace), Some(obfuscated_key)) => {
+				let key = self.key_obfuscator.deobfuscate(obfuscated_key)?;
+				Ok((primary_namespace.to_string(), secondary_namespace.to_string(), key))
+			},
+			_ => {
+				let msg = format!("Failed to extract key from invalid store key {}", store_key);
+				Err(lightning::io::Error::new(lightning::io::ErrorKind::InvalidData, msg))
+			},
+		}
+	}
+
+	async fn fetch_server_version(&self, store_key: &str) -> lightning::io::Result<i64> {
+		let request =
+			GetObjectRequest { store_id: self.store_id.clone(), key: store_key.to_string() };
+		match self.client.get_object(&request).await {
+			Ok(response) => Ok(response.value.map(|kv| kv.version).unwrap_or(0)),
+			// Writes to keys which don't exist yet have to use version 0.
+			Err(VssError::NoSuchKeyError(_)) => Ok(0),
+			Err(e) => Err(vss_error_to_io_error("fetch version of", store_key, e)),
+		}
+	}
+
+	async fn read(
+		&self, state_ref: Arc<tokio::sync::Mutex<KeyState>>, store_key: String,
+	) -> lightning::io::Result<Vec<u8>> {
+		let mut state = state_ref.lock().await;
+
+		let request = GetObjectRequest { store_id: self.store_id.clone(), key: store_key.clone() };
+		let response = match self.client.get_object(&request).await {
+			Ok(response) => response,
+			Err(VssError::NoSuchKeyError(_)) => {
+				state.server_version = Some(0);
+				let msg = format!("Failed to read as key could not be found: {}", store_key);
+				return Err(lightning::io::Error::new(lightning::io::ErrorKind::NotFound, msg));
+			},
+			Err(e) => return Err(vss_error_to_io_error("read", &store_key, e)),
+		};
+
+		let key_value = response.value.ok_or_else(|| {
+			let msg = format!("Failed to read {}: server returned no value", store_key);
+			lightning::io::Error::new(lightning::io::ErrorKind::Other, msg)
+		})?;
+		let storable = Storable::decode(&key_value.value[..]).map_err(|e| {
+			let msg = format!("Failed to decode data read for key {}: {}", store_key, e);
+			lightning::io::Error::new(lightning::io::ErrorKind::InvalidData, msg)
+		})?;
+		let (value, _) = self.storable_builder.deconstruct(storable)?;
+
+		state.server_version = Some(key_value.version);
+		Ok(value)
+	}
+
+	async fn write_version(
+		&self, state_ref: Arc<tokio::sync::Mutex<KeyState>>, store_key: String, buf: Vec<u8>,
+		version: u64,
+	) -> lightning::io::Result<()> {
+		let mut state = state_ref.lock().await;
+
+		// If a newer version has already been written or removed we can and must skip writing.
+		if version <= state.last_applied_version {
+			return Ok(());
+		}
+
+		let server_version = match state.server_version {
+			Some(server_version) => server_version,
+			None => self.fetch_server_version(&store_key).await?,
+		};
+
+		// The version stored inside the encrypted `Storable` is unused as the server tracks the
+		// version for us.
+		let storable = self.storable_builder.build(buf, 0);
+		let request = PutObjectRequest {
+			store_id: self.store_id.clone(),
+			global_version: None,
+			transaction_items: vec![KeyValue {
+				key: store_key.clone(),
+				version: server_version,
+				value: storable.encode_to_vec(),
+			}],
+			delete_items: vec![],
+		};
+
+		let res = self.client.put_object(&request).await;
+		#[cfg(test)]
+		let res = res.and_then(|response| {
+			if self.fail_next_write.swap(false, Ordering::Relaxed) {
+				Err(VssError::InternalServerError("Injected failure".to_string()))
+			} else {
+				Ok(response)
+			}
+		});
+
+		match res {
+			Ok(_) => {
+				state.server_version = Some(server_version + 1);
+				state.last_applied_version = version;
+				Ok(())
+			},
+			Err(VssError::ConflictError(e)) => {
+				let msg = format!(
+					"Failed to write {} as it was updated by another client, refusing to overwrite the lost update: {}",
+					store_key, e
+				);
+				Err(lightning::io::Error::new(lightning::io::ErrorKind::Other, msg))
+			},
+			Err(e) => {
+				// The write may or may not have been applied, so we no longer know the server's
+				// version and have to fetch it again on the next write.
+				state.server_version = None;
+				Err(vss_error_to_io_error("write", &store_key, e))
+			},
+		}
+	}
+
+	async fn remove_version(
+		&self, state_ref: Arc<tokio::sync::Mutex<KeyState>>, store_key: String, version: u64,
+	) -> lightning::io::Result<()> {
+		let mut state = state_ref.lock().await;
+
+		if version <= state.last_applied_version {
+			return Ok(());
+		}
+
+		// Deletions are conditional if we know the server version, and unconditional (as signaled
+		// by a version of -1) otherwise.
+		let request = DeleteObjectRequest {
+			store_id: self.store_id.clone(),
+			key_value: Some(KeyValue {
+				key: store_key.clone(),
+				version: state.server_version.unwrap_or(-1),
+				value: vec![],
+			}),
+		};
+
+		match self.client.delete_object(&request).await {
+			Ok(_) => {
+				state.server_version = Some(0);
+				state.last_applied_version = version;
+				Ok(())
+			},
+			Err(VssError::ConflictError(e)) => {
+				let msg = format!(
+					"Failed to remove {} as it was updated by another client, refusing to overwrite the lost update: {}",
+					store_key, e
+				);
+				Err(lightning::io::Error::new(lightning::io::ErrorKind::Other, msg))
+			},
+			Err(e) => {
+				// As for writes, the removal may or may not have been applied.
+				state.server_version = None;
+				Err(vss_error_to_io_error("remove", &store_key, e))
+			},
+		}
+	}
+
+	async fn list_store_keys(
+		&self, key_prefix: Option<String>,
+	) -> lightning::io::Result<Vec<String>> {
+		let mut store_keys = Vec::new();
+		let mut page_token = None;
+		loop {
+			let request = ListKeyVersionsRequest {
+				store_id: self.store_id.clone(),
+				key_prefix: key_prefix.clone(),
+				page_token,
+				page_size: Some(LIST_KEYS_PAGE_SIZE),
+			};
+			let response = self.client.list_key_versions(&request).await.map_err(|e| {
+				let msg = format!("Failed to list keys: {}", e);
+				lightning::io::Error::new(lightning::io::ErrorKind::Other, msg)
+			})?;
+			store_keys.extend(response.key_versions.into_iter().map(|kv| kv.key));
+			match response.next_page_token {
+				Some(token) if !token.is_empty() => page_token = Some(token),
+				_ => break,
+			}
+		}
+		Ok(store_keys)
+	}
+
+	async fn list(
+		&self, primary_namespace: String, secondary_namespace: String,
+	) -> lightning::io::Result<Vec<String>> {
+		let key_prefix = format!(
+			"{}{}{}{}",
+			primary_namespace, KEY_SEPARATOR, secondary_namespace, KEY_SEPARATOR
+		);
+		let store_keys = self.list_store_keys(Some(key_prefix)).await?;
+
+		let mut keys = Vec::with_capacity(store_keys.len());
+		for store_key in store_keys {
+			let (found_primary_namespace, found_secondary_namespace, key) =
+				self.extract_key(&store_key)?;
+			// The prefix match may include namespaces which merely start with the requested ones.
+			if found_primary_namespace == primary_namespace
+				&& found_secondary_namespace == secondary_namespace
+			{
+				keys.push(key);
+			}
+		}
+		Ok(keys)
+	}
+}
+
+impl KVStore for VssStore {
+	fn read(
+		&self, primary_namespace: &str, secondary_namespace: &str, key: &str,
+	) -> impl Future<Output = Result<Vec<u8>, lightning::io::Error>> + 'static + Send {
+		let this = Arc::clone(&self.inner);
+		let state =
+			check_namespace_key_validity(primary_namespace, secondary_namespace, Some(key), "read")
+				.map(|_| {
+					let store_key =
+						this.build_store_key(primary_namespace, secondary_namespace, key);
+					(this.get_key_state_ref(store_key.clone()), store_key)
+				});
+
+		async move {
+			let (state_ref, store_key) = state?;
+			this.read(state_ref, store_key).await
+		}
+	}
+
+	fn write(
+		&self, primary_namespace: &str, secondary_namespace: &str, key: &str, buf: Vec<u8>,
+	) -> impl Future<Output = Result<(), lightning::io::Error>> + 'static + Send {
+		let this = Arc::clone(&self.inner);
+		let state = check_namespace_key_validity(
+			primary_namespace,
+			secondary_namespace,
+			Some(key),
+			"write",
+		)
+		.map(|_| {
+			let store_key = this.build_store_key(primary_namespace, secondary_namespace, key);
+			(self.get_new_version_and_state_ref(store_key.clone()), store_key)
+		});
+
+		async move {
+			let ((state_ref, version), store_key) = state?;
+			this.write_version(state_ref, store_key, buf, version).await
+		}
+	}
+
+	fn remove(
+		&self, primary_namespace: &str, secondary_namespace: &str, key: &str, _lazy: bool,
+	) -> impl Future<Output = Result<(), lightning::io::Error>> + 'static + Send {
+		let this = Arc::clone(&self.inner);
+		let state = check_namespace_key_validity(
+			primary_namespace,
+			secondary_namespace,
+			Some(key),
+			"remove",
+		)
+		.map(|_| {
+			let store_key = this.build_store_key(primary_namespace, secondary_namespace, key);
+			(self.get_new_version_and_state_ref(store_key.clone()), store_key)
+		});
+
+		async move {
+			let ((state_ref, version), store_key) = state?;
+			this.remove_version(state_ref, store_key, version).await
+		}
+	}
+
+	fn list(
+		&self, primary_namespace: &str, secondary_namespace: &str,
+	) -> impl Future<Output = Result<Vec<String>, lightning::io::Error>> + 'static + Send {
+		let this = Arc::clone(&self.inner);
+		let validity =
+			check_namespace_key_validity(primary_namespace, secondary_namespace, None, "list");
+		let primary_namespace = primary_namespace.to_string();
+		let secondary_namespace = secondary_namespace.to_string();
+
+		async move {
+			validity?;
+			this.list(primary_namespace, secondary_namespace).await
+		}
+	}
+}
+
+impl KVStoreSync for VssStore {
+	fn read(
+		&self, primary_namespace: &str, secondary_namespace: &str, key: &str,
+	) -> Result<Vec<u8>, lightning::io::Error> {
+		let fut = KVStore::read(self, primary_namespace, secondary_namespace, key);
+		self.runtime().block_on(fut)
+	}
+
+	fn write(
+		&self, primary_namespace: &str, secondary_namespace: &str, key: &str, buf: Vec<u8>,
+	) -> Result<(), lightning::io::Error> {
+		let fut = KVStore::write(self, primary_namespace, secondary_namespace, key, buf);
+		self.runtime().block_on(fut)
+	}
+
+	fn remove(
+		&self, primary_namespace: &str, secondary_namespace: &str, key: &str, lazy: bool,
+	) -> Result<(), lightning::io::Error> {
+		let fut = KVStore::remove(self, primary_namespace, secondary_namespace, key, lazy);
+		self.runtime().block_on(fut)
+	}
+
+	fn list(
+		&self, primary_namespace: &str, secondary_namespace: &str,
+	) -> Result<Vec<String>, lightning::io::Error> {
+		let fut = KVStore::list(self, primary_namespace, secondary_namespace);
+		self.runtime().block_on(fut)
+	}
+}
+
+impl Drop for VssStore {
+	fn drop(&mut self) {
+		// Dropping a runtime blocks until its worker threads exit, which panics from within an
+		// async context, so shut it down in the background in that case.
+		if let Some(runtime) = self.runtime.take() {
+			if tokio::runtime::Handle::try_current().is_ok() {
+				runtime.shutdown_background();
+			}
+		}
+	}
+}
+
+impl MigratableKVStore for VssStore {
+	fn list_all_keys(&self) -> Result<Vec<(String, String, String)>, lightning::io::Error> {
+		let store_keys = self.runtime().block_on(self.inner.list_store_keys(None))?;
+		store_keys.iter().map(|store_key| self.inner.extract_key(store_key)).collect()
+	}
+}
+
+/// Adapts an LDK [`EntropySource`] to the one required by [`StorableBuilder`].
+struct EntropySourceAdapter(Box<dyn EntropySource + Send + Sync>);
+
+impl storable_builder::EntropySource for EntropySourceAdapter {
+	fn fill_bytes(&self, buffer: &mut [u8]) {
+		for chunk in buffer.chunks_mut(32) {
+			let random_bytes = self.0.get_secure_random_bytes();
+			chunk.copy_from_slice(&random_bytes[..chunk.len()]);
+		}
+	}
+}
+
+fn derive_key(vss_seed: &[u8; 32], label: &[u8]) -> [u8; 32] {
+	let mut hmac = HmacEngine::<sha256::Hash>::new(vss_seed);
+	hmac.input(label);
+	Hmac::from_engine(hmac).to_byte_array()
+}
+
+fn vss_error_to_io_error(operation: &str, store_key: &str, e: VssError) -> lightning::io::Error {
+	let msg = format!("Failed to {} {}: {}", operation, store_key, e);
+	lightning::io::Error::new(lightning::io::ErrorKind::Other, msg)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use lightning::sign::RandomBytes;
+
+	fn test_store(store_id: &str) -> VssStore {
+		let base_url = std::env::var("TEST_VSS_BASE_URL")
+			.unwrap_or_else(|_| "http://localhost:8080/vss".to_string());
+		VssStore::new(base_url, store_id.to_string(), [42; 32], RandomBytes::new([43; 32])).unwrap()
+	}
+
+	#[test]
+	fn store_keys_round_trip() {
+		let store = test_store("test_store_keys_round_trip");
+		let store_key = store.inner.build_store_key("primary", "secondary", "key");
+		assert!(store_key.starts_with("primary#secondary#"));
+		assert!(!store_key.ends_with("#key"));
+		assert_eq!(
+			store.inner.extract_key(&store_key).unwrap(),
+			("primary".to_string(), "secondary".to_string(), "key".to_string())
+		);
+
+		let store_key = store.inner.build_store_key("", "", "key");
+		assert_eq!(
+			store.inner.extract_key(&store_key).unwrap(),
+			(String::new(), String::new(), "key".to_string())
+		);
+		assert!(store.inner.extract_key("no_separators").is_err());
+	}
+
+	#[test]
+	fn derived_keys_differ() {
+		let seed = [42; 32];
+		let data_encryption_key = derive_key(&seed, DATA_ENCRYPTION_KEY_LABEL);
+		let obfuscation_master_key = derive_key(&seed, KEY_OBFUSCATION_KEY_LABEL);
+		assert_ne!(data_encryption_key, obfuscation_master_key);
+		assert_eq!(data_encryption_key, derive_key(&seed, DATA_ENCRYPTION_KEY_LABEL));
+	}
+
+	#[tokio::test]
+	async fn drop_in_async_context() {
+		// Dropping the store, and with it its internal runtime, must not panic in async contexts.
+		let store = test_store("test_drop_in_async_context");
+		drop(store);
+	}
+
+	// The following tests require a VSS server running at `TEST_VSS_BASE_URL`.
+	#[cfg(vss_test)]
+	#[test]
+	fn read_write_remove_list_persist() {
+		let store = test_store("test_vss_read_write_remove_list_persist");
+		crate::test_utils::do_read_write_remove_list_persist(&store);
+	}
+
+	#[cfg(vss_test)]
+	#[test]
+	fn detects_lost_updates() {
+		let store_a = test_store("test_vss_detects_lost_updates");
+		let store_b = test_store("test_vss_detects_lost_updates");
+
+		KVStoreSync::write(&store_a, "testspace", "", "testkey", vec![1; 32]).unwrap();
+		assert_eq!(KVStoreSync::read(&store_b, "testspace", "", "testkey").unwrap(), vec![1; 32]);
+		KVStoreSync::write(&store_b, "testspace", "", "testkey", vec![2; 32]).unwrap();
+
+		// Store A's view of the key is outdated, so its write must fail rather than overwrite
+		// store B's update.
+		assert!(KVStoreSync::write(&store_a, "testspace", "", "testkey", vec![3; 32]).is_err());
+		assert_eq!(KVStoreSync::read(&store_a, "testspace", "", "testkey").unwrap(), vec![2; 32]);
+
+		KVStoreSync::remove(&store_a, "testspace", "", "testkey", false).unwrap();
+	}
+
+	#[cfg(vss_test)]
+	#[test]
+	fn recovers_from_transient_failures() {
+		let store = test_store("test_vss_recovers_from_transient_failures");
+
+		KVStoreSync::write(&store, "testspace", "", "testkey", vec![1; 32]).unwrap();
+
+		// A write which was applied on the server but whose response was lost fails, leaving us
+		// unaware of the server's version. Later writes must still succeed.
+		store.inner.fail_next_write.store(true, Ordering::Relaxed);
+		assert!(KVStoreSync::write(&store, "testspace", "", "testkey", vec![2; 32]).is_err());
+		KVStoreSync::write(&store, "testspace", "", "testkey", vec![3; 32]).unwrap();
+		assert_eq!(KVStoreSync::read(&store, "testspace", "", "testkey").unwrap(), vec![3; 32]);
+
+		KVStoreSync::remove(&store, "testspace", "", "testkey", false).unwrap();
+	}
+}
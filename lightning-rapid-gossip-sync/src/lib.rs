@@ -127,6 +127,29 @@ impl From<LightningError> for GraphSyncError {
 	}
 }
 
+/// The stages of applying rapid gossip sync data, in the order in which they occur.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyncStage {
+	/// The IDs (and details) of the nodes referenced by the data are being read.
+	NodeIds,
+	/// Channel announcements are being applied to the network graph.
+	ChannelAnnouncements,
+	/// Channel updates are being applied to the network graph.
+	ChannelUpdates,
+}
+
+/// The progress of applying rapid gossip sync data, as reported to the callback provided to
+/// [`RapidGossipSync::update_network_graph_from_reader_no_std`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SyncProgress {
+	/// The stage the sync is in.
+	pub stage: SyncStage,
+	/// The number of items of the current stage processed so far.
+	pub processed: u32,
+	/// The total number of items in the current stage.
+	pub total: u32,
+}
+
 /// The main Rapid Gossip Sync object.
 ///
 /// See [crate-level documentation] for usage.
@@ -166,7 +189,7 @@ where
 			bitcoin_error
 		})?;
 		let mut buf_reader = std::io::BufReader::new(file);
-		self.update_network_graph_from_byte_stream(&mut buf_reader)
+		self.update_network_graph_from_byte_stream(&mut buf_reader, |_| {})
 	}
 
 	/// Update network graph from binary data.
@@ -176,7 +199,7 @@ where
 	#[cfg(feature = "std")]
 	pub fn update_network_graph(&self, update_data: &[u8]) -> Result<u32, GraphSyncError> {
 		let mut read_cursor = io::Cursor::new(update_data);
-		self.update_network_graph_from_byte_stream(&mut read_cursor)
+		self.update_network_graph_from_byte_stream(&mut read_cursor, |_| {})
 	}
 
 	/// Update network graph from binary data.
@@ -188,7 +211,49 @@ where
 		&self, update_data: &[u8], current_time_unix: Option<u64>,
 	) -> Result<u32, GraphSyncError> {
 		let mut read_cursor = io::Cursor::new(update_data);
-		self.update_network_graph_from_byte_stream_no_std(&mut read_cursor, current_time_unix)
+		self.update_network_graph_from_byte_stream_no_std(
+			&mut read_cursor,
+			current_time_unix,
+			|_| {},
+		)
+	}
+
+	/// Update network graph from binary data read incrementally from `reader`, see
+	/// [`Self::update_network_graph_from_reader_no_std`] for details.
+	/// Returns the last sync timestamp to be used the next time rapid sync data is queried.
+	///
+	/// `reader`: the source of the update data
+	/// `progress_callback`: `FnMut(SyncProgress)` called to report progress
+	#[cfg(feature = "std")]
+	pub fn update_network_graph_from_reader<R: io::Read, F: FnMut(SyncProgress)>(
+		&self, reader: &mut R, progress_callback: F,
+	) -> Result<u32, GraphSyncError> {
+		self.update_network_graph_from_byte_stream(reader, progress_callback)
+	}
+
+	/// Update network graph from binary data read incrementally from `reader`, rather than
+	/// requiring the full snapshot to be held in memory.
+	/// Returns the last sync timestamp to be used the next time rapid sync data is queried.
+	///
+	/// As the data is read in many small pieces, `reader` should generally be buffered, e.g. by
+	/// wrapping it in a `std::io::BufReader`, which also bounds the amount of memory used for
+	/// reading. Note that memory proportional to the number of nodes in the snapshot is still
+	/// required while it is being applied.
+	///
+	/// `progress_callback` is called as the data is applied, at least once per [`SyncStage`] the
+	/// sync reaches, allowing callers to report progress on multi-megabyte snapshots.
+	///
+	/// `reader`: the source of the update data
+	/// `current_time_unix`: `Option<u64>` optional current timestamp to verify data age
+	/// `progress_callback`: `FnMut(SyncProgress)` called to report progress
+	pub fn update_network_graph_from_reader_no_std<R: io::Read, F: FnMut(SyncProgress)>(
+		&self, reader: &mut R, current_time_unix: Option<u64>, progress_callback: F,
+	) -> Result<u32, GraphSyncError> {
+		self.update_network_graph_from_byte_stream_no_std(
+			reader,
+			current_time_unix,
+			progress_callback,
+		)
 	}
 
 	/// Gets a reference to the underlying [`NetworkGraph`] which was provided in
@@ -14,7 +14,7 @@ use lightning::util::logger::Logger;
 use lightning::util::ser::{BigSize, FixedLengthReader, Readable};
 use lightning::{log_debug, log_given_level, log_gossip, log_trace, log_warn};
 
-use crate::{GraphSyncError, RapidGossipSync};
+use crate::{GraphSyncError, RapidGossipSync, SyncProgress, SyncStage};
 
 #[cfg(all(feature = "std", not(test)))]
 use std::time::{SystemTime, UNIX_EPOCH};
@@ -37,13 +37,27 @@ const MAX_INITIAL_NODE_ID_VECTOR_CAPACITY: u32 = 50_000;
 /// suggestion.
 const STALE_RGS_UPDATE_AGE_LIMIT_SECS: u64 = 60 * 60 * 24 * 14;
 
+/// The number of items processed between calls to the progress callback within each
+/// [`SyncStage`].
+const PROGRESS_REPORT_INTERVAL: u32 = 1000;
+
+/// Calls `progress_callback` if `processed` is at a [`PROGRESS_REPORT_INTERVAL`] boundary or the
+/// end of the current stage.
+fn report_progress<F: FnMut(SyncProgress)>(
+	progress_callback: &mut F, stage: SyncStage, processed: u32, total: u32,
+) {
+	if processed % PROGRESS_REPORT_INTERVAL == 0 || processed == total {
+		progress_callback(SyncProgress { stage, processed, total });
+	}
+}
+
 impl<NG: Deref<Target = NetworkGraph<L>>, L: Deref> RapidGossipSync<NG, L>
 where
 	L::Target: Logger,
 {
 	#[cfg(feature = "std")]
-	pub(crate) fn update_network_graph_from_byte_stream<R: io::Read>(
-		&self, read_cursor: &mut R,
+	pub(crate) fn update_network_graph_from_byte_stream<R: io::Read, F: FnMut(SyncProgress)>(
+		&self, read_cursor: &mut R, progress_callback: F,
 	) -> Result<u32, GraphSyncError> {
 		#[allow(unused_mut, unused_assignments)]
 		let mut current_time_unix = None;
@@ -58,11 +72,18 @@ where
 					.as_secs(),
 			);
 		}
-		self.update_network_graph_from_byte_stream_no_std(read_cursor, current_time_unix)
+		self.update_network_graph_from_byte_stream_no_std(
+			read_cursor,
+			current_time_unix,
+			progress_callback,
+		)
 	}
 
-	pub(crate) fn update_network_graph_from_byte_stream_no_std<R: io::Read>(
-		&self, read_cursor: &mut R, current_time_unix: Option<u64>,
+	pub(crate) fn update_network_graph_from_byte_stream_no_std<
+		R: io::Read,
+		F: FnMut(SyncProgress),
+	>(
+		&self, read_cursor: &mut R, current_time_unix: Option<u64>, mut progress_callback: F,
 	) -> Result<u32, GraphSyncError> {
 		log_trace!(self.logger, "Processing RGS data...");
 		let mut protocol_prefix = [0u8; 3];
@@ -126,7 +147,13 @@ where
 
 		if parse_node_details {
 			let read_only_network_graph = network_graph.read_only();
-			for _ in 0..node_id_count {
+			for processed in 0..node_id_count {
+				report_progress(
+					&mut progress_callback,
+					SyncStage::NodeIds,
+					processed,
+					node_id_count,
+				);
 				let mut pubkey_bytes = [0u8; 33];
 				read_cursor.read_exact(&mut pubkey_bytes)?;
 
@@ -231,15 +258,24 @@ where
 				}
 			}
 		} else {
-			for _ in 0..node_id_count {
+			for processed in 0..node_id_count {
+				report_progress(
+					&mut progress_callback,
+					SyncStage::NodeIds,
+					processed,
+					node_id_count,
+				);
 				let current_node_id = Readable::read(read_cursor)?;
 				node_ids.push(current_node_id);
 			}
 		}
+		report_progress(&mut progress_callback, SyncStage::NodeIds, node_id_count, node_id_count);
 
 		let mut previous_scid: u64 = 0;
 		let announcement_count: u32 = Readable::read(read_cursor)?;
-		for _ in 0..announcement_count {
+		for processed in 0..announcement_count {
+			let stage = SyncStage::ChannelAnnouncements;
+			report_progress(&mut progress_callback, stage, processed, announcement_count);
 			let features = Readable::read(read_cursor)?;
 
 			// handle SCID
@@ -306,6 +342,9 @@ where
 			}
 		}
 
+		let stage = SyncStage::ChannelAnnouncements;
+		report_progress(&mut progress_callback, stage, announcement_count, announcement_count);
+
 		for modification in node_modifications {
 			match network_graph.update_node_from_unsigned_announcement(&modification) {
 				Ok(_) => {},
@@ -332,6 +371,7 @@ where
 		log_debug!(self.logger, "Processing RGS update from {} with {} nodes, {} channel announcements and {} channel updates.",
 			latest_seen_timestamp, node_id_count, announcement_count, update_count);
 		if update_count == 0 {
+			report_progress(&mut progress_callback, SyncStage::ChannelUpdates, 0, 0);
 			return Ok(latest_seen_timestamp);
 		}
 
@@ -344,7 +384,13 @@ where
 
 		let mut previous_channel_direction = None;
 
-		for _ in 0..update_count {
+		for processed in 0..update_count {
+			report_progress(
+				&mut progress_callback,
+				SyncStage::ChannelUpdates,
+				processed,
+				update_count,
+			);
 			let scid_delta: BigSize = Readable::read(read_cursor)?;
 			let short_channel_id =
 				previous_scid.checked_add(scid_delta.0).ok_or(DecodeError::InvalidValue)?;
@@ -462,6 +508,13 @@ where
 			}
 		}
 
+		report_progress(
+			&mut progress_callback,
+			SyncStage::ChannelUpdates,
+			update_count,
+			update_count,
+		);
+
 		self.network_graph.set_last_rapid_gossip_sync_timestamp(latest_seen_timestamp);
 
 		if let Some(time) = current_time_unix {
@@ -485,7 +538,7 @@ mod tests {
 	use lightning::util::test_utils::TestLogger;
 
 	use crate::processing::STALE_RGS_UPDATE_AGE_LIMIT_SECS;
-	use crate::{GraphSyncError, RapidGossipSync};
+	use crate::{GraphSyncError, RapidGossipSync, SyncProgress, SyncStage};
 
 	const VALID_RGS_BINARY: [u8; 300] = [
 		76, 68, 75, 1, 111, 226, 140, 10, 182, 241, 179, 114, 193, 166, 162, 70, 174, 99, 247, 79,
@@ -948,6 +1001,36 @@ mod tests {
 		assert!(after.contains("783241506229452801"));
 	}
 
+	#[test]
+	fn update_from_reader_reports_progress() {
+		let logger = TestLogger::new();
+		let network_graph = NetworkGraph::new(Network::Bitcoin, &logger);
+		let rapid_sync = RapidGossipSync::new(&network_graph, &logger);
+
+		let mut progress = Vec::new();
+		let mut reader = &VALID_RGS_BINARY[..];
+		let update_result =
+			rapid_sync.update_network_graph_from_reader_no_std(&mut reader, Some(0), |p| {
+				progress.push(p)
+			});
+		assert!(update_result.is_ok());
+		assert_eq!(network_graph.read_only().channels().len(), 2);
+
+		let expected = [
+			(SyncStage::NodeIds, 0, 4),
+			(SyncStage::NodeIds, 4, 4),
+			(SyncStage::ChannelAnnouncements, 0, 2),
+			(SyncStage::ChannelAnnouncements, 2, 2),
+			(SyncStage::ChannelUpdates, 0, 4),
+			(SyncStage::ChannelUpdates, 4, 4),
+		];
+		let expected: Vec<SyncProgress> = expected
+			.iter()
+			.map(|&(stage, processed, total)| SyncProgress { stage, processed, total })
+			.collect();
+		assert_eq!(progress, expected);
+	}
+
 	#[test]
 	fn full_update_succeeds_at_the_beginning_of_the_unix_era() {
 		let logger = TestLogger::new();
@@ -11,6 +11,7 @@ use crate::error::{InternalError, TxSyncError};
 use electrum_client::utils::validate_merkle_proof;
 use electrum_client::Client as ElectrumClient;
 use electrum_client::ElectrumApi;
+use electrum_client::ScriptStatus;
 
 use lightning::chain::WatchedOutput;
 use lightning::chain::{Confirm, Filter};
@@ -18,9 +19,9 @@ use lightning::util::logger::Logger;
 use lightning::{log_debug, log_error, log_trace};
 
 use bitcoin::block::Header;
-use bitcoin::{BlockHash, Script, Transaction, Txid};
+use bitcoin::{BlockHash, OutPoint, Script, ScriptBuf, Txid};
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::ops::Deref;
 use std::sync::{Arc, Mutex};
 use std::time::Instant;
@@ -34,6 +35,11 @@ use std::time::Instant;
 /// Note that registration via [`Filter`] needs to happen before any calls to
 /// [`Watch::watch_channel`] to ensure we get notified of the items to monitor.
 ///
+/// To reduce the load on the Electrum server, we subscribe to the scripts of all watched
+/// transactions and outputs and keep these subscriptions across sync rounds. Script histories are
+/// only re-queried if their status changed since the last sync, and any transactions and block
+/// headers we need are retrieved via batched requests.
+///
 /// [`ChainMonitor`]: lightning::chain::chainmonitor::ChainMonitor
 /// [`Watch::watch_channel`]: lightning::chain::Watch::watch_channel
 /// [`Filter`]: lightning::chain::Filter
@@ -43,6 +49,10 @@ where
 {
 	sync_state: Mutex<SyncState>,
 	queue: Mutex<FilterQueue>,
+	// The scripts of transactions registered via `Filter::register_tx`, which are to be drained
+	// alongside `queue`.
+	queued_tx_scripts: Mutex<HashMap<Txid, ScriptBuf>>,
+	script_subscriptions: Mutex<ScriptSubscriptions>,
 	client: Arc<ElectrumClient>,
 	logger: L,
 }
@@ -67,8 +77,10 @@ where
 	pub fn from_client(client: Arc<ElectrumClient>, logger: L) -> Result<Self, TxSyncError> {
 		let sync_state = Mutex::new(SyncState::new());
 		let queue = Mutex::new(FilterQueue::new());
+		let queued_tx_scripts = Mutex::new(HashMap::new());
+		let script_subscriptions = Mutex::new(ScriptSubscriptions::new());
 
-		Ok(Self { sync_state, queue, client, logger })
+		Ok(Self { sync_state, queue, queued_tx_scripts, script_subscriptions, client, logger })
 	}
 
 	/// Synchronizes the given `confirmables` via their [`Confirm`] interface implementations. This
@@ -88,6 +100,7 @@ where
 	{
 		// This lock makes sure we're syncing once at a time.
 		let mut sync_state = self.sync_state.lock().unwrap();
+		let mut subs = self.script_subscriptions.lock().unwrap();
 
 		log_trace!(self.logger, "Starting transaction sync.");
 		#[cfg(feature = "time")]
@@ -103,12 +116,25 @@ where
 		let mut tip_height = tip_notification.height as u32;
 
 		loop {
-			let pending_registrations = self.queue.lock().unwrap().process_queues(&mut sync_state);
+			let pending_registrations = {
+				let mut locked_queue = self.queue.lock().unwrap();
+				subs.tx_scripts.extend(self.queued_tx_scripts.lock().unwrap().drain());
+				locked_queue.process_queues(&mut sync_state)
+			};
 			let tip_is_new = Some(tip_header.block_hash()) != sync_state.last_sync_hash;
+			let scripts_changed = match self.poll_script_statuses(&mut subs) {
+				Ok(scripts_changed) => scripts_changed,
+				Err(err) => {
+					log_error!(self.logger, "Failed to poll script notifications, aborting.");
+					sync_state.pending_sync = true;
+					return Err(TxSyncError::from(err));
+				},
+			};
 
 			// We loop until any registered transactions have been processed at least once, or the
-			// tip hasn't been updated during the last iteration.
-			if !sync_state.pending_sync && !pending_registrations && !tip_is_new {
+			// tip and the status of watched scripts haven't been updated during the last iteration.
+			if !sync_state.pending_sync && !pending_registrations && !tip_is_new && !scripts_changed
+			{
 				// Nothing to do.
 				break;
 			} else {
@@ -122,6 +148,11 @@ where
 							match self.check_update_tip(&mut tip_header, &mut tip_height) {
 								Ok(false) => {
 									num_unconfirmed += unconfirmed_txs.len();
+									if !unconfirmed_txs.is_empty() {
+										// Transactions might get reconfirmed without the status
+										// of their scripts changing, so re-check all histories.
+										subs.reset_synced();
+									}
 									sync_state.sync_unconfirmed_transactions(
 										&confirmables,
 										unconfirmed_txs,
@@ -166,8 +197,8 @@ where
 					sync_state.prune_output_spends(tip_height);
 				}
 
-				match self.get_confirmed_transactions(&sync_state) {
-					Ok(confirmed_txs) => {
+				match self.get_confirmed_transactions(&sync_state, &mut subs) {
+					Ok((confirmed_txs, checked_scripts)) => {
 						// Double-check the tip hash. If it changed, a reorg happened since
 						// we started syncing and we need to restart last-minute.
 						match self.check_update_tip(&mut tip_header, &mut tip_height) {
@@ -175,6 +206,8 @@ where
 								num_confirmed += confirmed_txs.len();
 								sync_state
 									.sync_confirmed_transactions(&confirmables, confirmed_txs);
+								subs.commit(checked_scripts);
+								self.prune_subscriptions(&sync_state, &mut subs);
 							},
 							Ok(true) => {
 								log_debug!(self.logger,
@@ -264,37 +297,20 @@ where
 	}
 
 	fn get_confirmed_transactions(
-		&self, sync_state: &SyncState,
-	) -> Result<Vec<ConfirmedTx>, InternalError> {
-		// First, check the confirmation status of registered transactions as well as the
-		// status of dependent transactions of registered outputs.
-		let mut confirmed_txs: Vec<ConfirmedTx> = Vec::new();
-		let mut watched_script_pubkeys = Vec::with_capacity(
-			sync_state.watched_transactions.len() + sync_state.watched_outputs.len(),
-		);
-		let mut watched_txs = Vec::with_capacity(sync_state.watched_transactions.len());
-
+		&self, sync_state: &SyncState, subs: &mut ScriptSubscriptions,
+	) -> Result<(Vec<ConfirmedTx>, CheckedScripts), InternalError> {
+		// First, make sure we know a script for each of the registered transactions. We watch an
+		// arbitrary output of the transaction of interest in order to retrieve the associated script
+		// history, before narrowing down our search through `filter`ing by `txid` below.
 		for txid in &sync_state.watched_transactions {
+			if subs.tx_scripts.contains_key(txid) {
+				continue;
+			}
+
 			match self.client.transaction_get(&txid) {
 				Ok(tx) => {
-					// Bitcoin Core's Merkle tree implementation has no way to discern between
-					// internal and leaf node entries. As a consequence it is susceptible to an
-					// attacker injecting additional transactions by crafting 64-byte
-					// transactions matching an inner Merkle node's hash (see
-					// https://web.archive.org/web/20240329003521/https://bitslog.com/2018/06/09/leaf-node-weakness-in-bitcoin-merkle-tree-design/).
-					// To protect against this (highly unlikely) attack vector, we check that the
-					// transaction is at least 65 bytes in length.
-					if tx.total_size() == 64 {
-						log_error!(self.logger, "Skipping transaction {} due to retrieving potentially invalid tx data.", txid);
-						continue;
-					}
-
-					watched_txs.push((txid, tx.clone()));
 					if let Some(tx_out) = tx.output.first() {
-						// We watch an arbitrary output of the transaction of interest in order to
-						// retrieve the associated script history, before narrowing down our search
-						// through `filter`ing by `txid` below.
-						watched_script_pubkeys.push(tx_out.script_pubkey.clone());
+						subs.tx_scripts.insert(*txid, tx_out.script_pubkey.clone());
 					} else {
 						debug_assert!(false, "Failed due to retrieving invalid tx data.");
 						log_error!(self.logger, "Failed due to retrieving invalid tx data.");
@@ -311,87 +327,162 @@ where
 			}
 		}
 
-		let num_tx_lookups = watched_script_pubkeys.len();
-		debug_assert_eq!(num_tx_lookups, watched_txs.len());
-
-		for output in sync_state.watched_outputs.values() {
-			watched_script_pubkeys.push(output.script_pubkey.clone());
-		}
+		let watched_txs = sync_state
+			.watched_transactions
+			.iter()
+			.filter_map(|txid| subs.tx_scripts.get(txid).map(|script| (*txid, script.clone())))
+			.collect::<Vec<(Txid, ScriptBuf)>>();
 
-		let num_output_spend_lookups = watched_script_pubkeys.len() - num_tx_lookups;
-		debug_assert_eq!(num_output_spend_lookups, sync_state.watched_outputs.len());
+		let mut watched_scripts = HashSet::new();
+		watched_scripts.extend(watched_txs.iter().map(|(_, script)| script.clone()));
+		watched_scripts
+			.extend(sync_state.watched_outputs.values().map(|o| o.script_pubkey.clone()));
 
-		match self.client.batch_script_get_history(watched_script_pubkeys.iter().map(|s| s.deref()))
-		{
-			Ok(results) => {
-				let (tx_results, output_results) = results.split_at(num_tx_lookups);
-				debug_assert_eq!(num_output_spend_lookups, output_results.len());
+		// Make sure we're subscribed to all scripts and know their latest status.
+		self.poll_script_statuses(subs)?;
+		self.subscribe_scripts(&watched_scripts, subs)?;
 
-				for (i, script_history) in tx_results.iter().enumerate() {
-					let (txid, tx) = &watched_txs[i];
-					if confirmed_txs.iter().any(|ctx| ctx.txid == **txid) {
-						continue;
-					}
-					let mut filtered_history =
-						script_history.iter().filter(|h| h.tx_hash == **txid);
-					if let Some(history) = filtered_history.next() {
-						let prob_conf_height = history.height as u32;
-						let confirmed_tx = self.get_confirmed_tx(tx, prob_conf_height)?;
-						confirmed_txs.push(confirmed_tx);
+		// Only query the histories of scripts whose status changed since we last checked them.
+		let mut checked_histories = HashMap::new();
+		let scripts_to_query = watched_scripts
+			.iter()
+			.filter(|script| {
+				let status = subs.statuses.get(*script).cloned().flatten();
+				subs.synced_histories.get(*script).map_or(true, |synced| synced.status != status)
+			})
+			.collect::<Vec<&ScriptBuf>>();
+
+		if !scripts_to_query.is_empty() {
+			match self
+				.client
+				.batch_script_get_history(scripts_to_query.iter().map(|s| s.as_script()))
+			{
+				Ok(results) => {
+					debug_assert_eq!(results.len(), scripts_to_query.len());
+					for (script, script_history) in scripts_to_query.into_iter().zip(results) {
+						let status = subs.statuses.get(script).cloned().flatten();
+						let entries =
+							script_history.iter().map(|h| (h.tx_hash, h.height)).collect();
+						checked_histories.insert(script.clone(), SyncedHistory { status, entries });
 					}
-					debug_assert!(filtered_history.next().is_none());
+				},
+				Err(e) => {
+					log_error!(self.logger, "Failed to look up script histories: {}.", e);
+					return Err(InternalError::Failed);
+				},
+			}
+		}
+
+		// Then, check the confirmation status of registered transactions as well as the status of
+		// dependent transactions of registered outputs. Items we already checked during the last
+		// successful sync only need to be checked against history entries we haven't seen before.
+		let mut candidates: HashMap<Txid, TxCandidate> = HashMap::new();
+		for (txid, script) in &watched_txs {
+			let is_new = !subs.synced_txs.contains(txid);
+			for (tx_hash, height) in subs.unchecked_entries(script, is_new, &checked_histories) {
+				if tx_hash != *txid || height <= 0 {
+					continue;
 				}
+				let candidate =
+					candidates.entry(tx_hash).or_insert_with(|| TxCandidate::new(height as u32));
+				candidate.is_watched = true;
+			}
+		}
 
-				for (watched_output, script_history) in
-					sync_state.watched_outputs.values().zip(output_results)
-				{
-					for possible_output_spend in script_history {
-						if possible_output_spend.height <= 0 {
-							continue;
-						}
+		for (outpoint, output) in &sync_state.watched_outputs {
+			let is_new = !subs.synced_outputs.contains(outpoint);
+			for (tx_hash, height) in
+				subs.unchecked_entries(&output.script_pubkey, is_new, &checked_histories)
+			{
+				if height <= 0 {
+					continue;
+				}
+				let candidate =
+					candidates.entry(tx_hash).or_insert_with(|| TxCandidate::new(height as u32));
+				candidate.spent_outpoints.push(*outpoint);
+			}
+		}
 
-						let txid = possible_output_spend.tx_hash;
-						if confirmed_txs.iter().any(|ctx| ctx.txid == txid) {
-							continue;
-						}
+		let candidate_txids = candidates.keys().copied().collect::<Vec<Txid>>();
+		let candidate_txs = if candidate_txids.is_empty() {
+			Vec::new()
+		} else {
+			match self.client.batch_transaction_get(candidate_txids.iter()) {
+				Ok(txs) => txs,
+				Err(e) => {
+					log_trace!(
+						self.logger,
+						"Inconsistency: Failed to retrieve transactions that were confirmed during syncing: {}",
+						e
+					);
+					return Err(InternalError::Inconsistency);
+				},
+			}
+		};
+		debug_assert_eq!(candidate_txids.len(), candidate_txs.len());
+
+		let mut proven_txs = Vec::new();
+		for (txid, tx) in candidate_txids.into_iter().zip(candidate_txs) {
+			let candidate = &candidates[&txid];
+			let is_spend = tx
+				.input
+				.iter()
+				.any(|txin| candidate.spent_outpoints.contains(&txin.previous_output));
+			if !candidate.is_watched && !is_spend {
+				continue;
+			}
 
-						match self.client.transaction_get(&txid) {
-							Ok(tx) => {
-								let mut is_spend = false;
-								for txin in &tx.input {
-									let watched_outpoint =
-										watched_output.outpoint.into_bitcoin_outpoint();
-									if txin.previous_output == watched_outpoint {
-										is_spend = true;
-										break;
-									}
-								}
+			// Bitcoin Core's Merkle tree implementation has no way to discern between
+			// internal and leaf node entries. As a consequence it is susceptible to an
+			// attacker injecting additional transactions by crafting 64-byte
+			// transactions matching an inner Merkle node's hash (see
+			// https://web.archive.org/web/20240329003521/https://bitslog.com/2018/06/09/leaf-node-weakness-in-bitcoin-merkle-tree-design/).
+			// To protect against this (highly unlikely) attack vector, we check that the
+			// transaction is at least 65 bytes in length.
+			if tx.total_size() == 64 {
+				log_error!(
+					self.logger,
+					"Skipping transaction {} due to retrieving potentially invalid tx data.",
+					txid
+				);
+				continue;
+			}
 
-								if !is_spend {
-									continue;
-								}
+			match self.client.transaction_get_merkle(&txid, candidate.height as usize) {
+				Ok(merkle_res) => {
+					debug_assert_eq!(candidate.height, merkle_res.block_height as u32);
+					proven_txs.push((tx, txid, candidate.height, merkle_res));
+				},
+				Err(e) => {
+					log_trace!(
+						self.logger,
+						"Inconsistency: Tx {} was unconfirmed during syncing: {}",
+						txid,
+						e
+					);
+					return Err(InternalError::Inconsistency);
+				},
+			}
+		}
 
-								let prob_conf_height = possible_output_spend.height as u32;
-								let confirmed_tx = self.get_confirmed_tx(&tx, prob_conf_height)?;
-								confirmed_txs.push(confirmed_tx);
-							},
-							Err(e) => {
-								log_trace!(
-									self.logger,
-									"Inconsistency: Tx {} was unconfirmed during syncing: {}",
-									txid,
-									e
-								);
-								return Err(InternalError::Inconsistency);
-							},
-						}
-					}
-				}
-			},
-			Err(e) => {
-				log_error!(self.logger, "Failed to look up script histories: {}.", e);
-				return Err(InternalError::Failed);
-			},
+		let mut heights = proven_txs.iter().map(|(_, _, height, _)| *height).collect::<Vec<u32>>();
+		heights.sort_unstable();
+		heights.dedup();
+		let block_headers = self.get_block_headers(&heights)?;
+
+		let mut confirmed_txs: Vec<ConfirmedTx> = Vec::with_capacity(proven_txs.len());
+		for (tx, txid, block_height, merkle_res) in proven_txs {
+			let block_header = block_headers[&block_height];
+			if !validate_merkle_proof(&txid, &block_header.merkle_root, &merkle_res) {
+				log_trace!(
+					self.logger,
+					"Inconsistency: Block {} was unconfirmed during syncing.",
+					block_header.block_hash()
+				);
+				return Err(InternalError::Inconsistency);
+			}
+			let pos = merkle_res.pos;
+			confirmed_txs.push(ConfirmedTx { tx, txid, block_header, block_height, pos });
 		}
 
 		// Sort all confirmed transactions first by block height, then by in-block
@@ -400,7 +491,13 @@ where
 			tx1.block_height.cmp(&tx2.block_height).then_with(|| tx1.pos.cmp(&tx2.pos))
 		});
 
-		Ok(confirmed_txs)
+		let checked_scripts = CheckedScripts {
+			histories: checked_histories,
+			txs: watched_txs.into_iter().map(|(txid, _)| txid).collect(),
+			outputs: sync_state.watched_outputs.keys().copied().collect(),
+		};
+
+		Ok((confirmed_txs, checked_scripts))
 	}
 
 	fn get_unconfirmed_transactions<C: Deref>(
@@ -416,74 +513,155 @@ where
 			.flat_map(|c| c.get_relevant_txids())
 			.collect::<HashSet<(Txid, u32, Option<BlockHash>)>>();
 
-		let mut unconfirmed_txs = Vec::new();
-
+		let mut confirmed_txids = Vec::with_capacity(relevant_txids.len());
 		for (txid, conf_height, block_hash_opt) in relevant_txids {
 			if let Some(block_hash) = block_hash_opt {
-				let block_header = self.client.block_header(conf_height as usize)?;
-				if block_header.block_hash() == block_hash {
-					// Skip if the tx is still confirmed in the block in question.
-					continue;
-				}
-
-				unconfirmed_txs.push(txid);
+				confirmed_txids.push((txid, conf_height, block_hash));
 			} else {
 				log_error!(self.logger,
 					"Untracked confirmation of funding transaction. Please ensure none of your channels had been created with LDK prior to version 0.0.113!");
 				panic!("Untracked confirmation of funding transaction. Please ensure none of your channels had been created with LDK prior to version 0.0.113!");
 			}
 		}
+
+		let mut heights =
+			confirmed_txids.iter().map(|(_, height, _)| *height).collect::<Vec<u32>>();
+		heights.sort_unstable();
+		heights.dedup();
+		let block_headers = self.get_block_headers(&heights)?;
+
+		let mut unconfirmed_txs = Vec::new();
+		for (txid, conf_height, block_hash) in confirmed_txids {
+			if block_headers[&conf_height].block_hash() == block_hash {
+				// Skip if the tx is still confirmed in the block in question.
+				continue;
+			}
+
+			unconfirmed_txs.push(txid);
+		}
 		Ok(unconfirmed_txs)
 	}
 
-	fn get_confirmed_tx(
-		&self, tx: &Transaction, prob_conf_height: u32,
-	) -> Result<ConfirmedTx, InternalError> {
-		let txid = tx.compute_txid();
-		match self.client.transaction_get_merkle(&txid, prob_conf_height as usize) {
-			Ok(merkle_res) => {
-				debug_assert_eq!(prob_conf_height, merkle_res.block_height as u32);
-				match self.client.block_header(prob_conf_height as usize) {
-					Ok(block_header) => {
-						let pos = merkle_res.pos;
-						if !validate_merkle_proof(&txid, &block_header.merkle_root, &merkle_res) {
-							log_trace!(
-								self.logger,
-								"Inconsistency: Block {} was unconfirmed during syncing.",
-								block_header.block_hash()
-							);
-							return Err(InternalError::Inconsistency);
-						}
-						let confirmed_tx = ConfirmedTx {
-							tx: tx.clone(),
-							txid,
-							block_header,
-							block_height: prob_conf_height,
-							pos,
-						};
-						Ok(confirmed_tx)
-					},
-					Err(e) => {
-						log_error!(
-							self.logger,
-							"Failed to retrieve block header for height {}: {}.",
-							prob_conf_height,
-							e
-						);
-						Err(InternalError::Failed)
+	fn get_block_headers(&self, heights: &[u32]) -> Result<HashMap<u32, Header>, InternalError> {
+		if heights.is_empty() {
+			return Ok(HashMap::new());
+		}
+
+		match self.client.batch_block_header(heights.iter()) {
+			Ok(block_headers) => {
+				debug_assert_eq!(heights.len(), block_headers.len());
+				Ok(heights.iter().copied().zip(block_headers).collect())
+			},
+			Err(e) => {
+				log_error!(self.logger, "Failed to retrieve block headers: {}.", e);
+				Err(InternalError::Failed)
+			},
+		}
+	}
+
+	// Drains any pending status notifications for the scripts we're subscribed to.
+	//
+	// Returns `true` if the status of any script changed since we last checked its history.
+	fn poll_script_statuses(&self, subs: &mut ScriptSubscriptions) -> Result<bool, InternalError> {
+		let mut status_changed = false;
+		let mut lost_subscriptions = Vec::new();
+		for (script, status) in subs.statuses.iter_mut() {
+			loop {
+				match self.client.script_pop(script) {
+					Ok(Some(new_status)) => *status = Some(new_status),
+					Ok(None) => break,
+					Err(electrum_client::Error::NotSubscribed(_)) => {
+						lost_subscriptions.push(script.clone());
+						break;
 					},
+					Err(e) => return Err(e.into()),
+				}
+			}
+
+			if let Some(synced) = subs.synced_histories.get(script) {
+				status_changed |= synced.status != *status;
+			}
+		}
+
+		for script in lost_subscriptions {
+			// We lost our subscription, e.g., as the client reconnected to the server in the
+			// meantime. Forget everything we know about the script so we resubscribe to it.
+			log_trace!(self.logger, "Lost script subscription, resubscribing.");
+			subs.statuses.remove(&script);
+			subs.synced_histories.remove(&script);
+			status_changed = true;
+		}
+
+		Ok(status_changed)
+	}
+
+	fn subscribe_scripts(
+		&self, scripts: &HashSet<ScriptBuf>, subs: &mut ScriptSubscriptions,
+	) -> Result<(), InternalError> {
+		let new_scripts =
+			scripts.iter().filter(|s| !subs.statuses.contains_key(*s)).collect::<Vec<&ScriptBuf>>();
+		if new_scripts.is_empty() {
+			return Ok(());
+		}
+
+		match self.client.batch_script_subscribe(new_scripts.iter().map(|s| s.as_script())) {
+			Ok(statuses) => {
+				debug_assert_eq!(new_scripts.len(), statuses.len());
+				for (script, status) in new_scripts.into_iter().zip(statuses) {
+					subs.statuses.insert(script.clone(), status);
 				}
 			},
 			Err(e) => {
-				log_trace!(
-					self.logger,
-					"Inconsistency: Tx {} was unconfirmed during syncing: {}",
-					txid,
-					e
-				);
-				Err(InternalError::Inconsistency)
+				// The batch might have been partially processed, so fall back to subscribing to
+				// each script individually.
+				log_trace!(self.logger, "Failed to subscribe to scripts in batch: {}", e);
+				for script in new_scripts {
+					let status = match self.client.script_subscribe(script) {
+						Ok(status) => status,
+						Err(electrum_client::Error::AlreadySubscribed(_)) => {
+							// We're still subscribed, e.g., as we previously failed to unsubscribe.
+							// As we might have missed status updates in the meantime, make sure to
+							// re-query the script's history.
+							let mut status = None;
+							while let Some(queued_status) = self.client.script_pop(script)? {
+								status = Some(queued_status);
+							}
+							subs.synced_histories.remove(script);
+							status
+						},
+						Err(e) => {
+							log_error!(self.logger, "Failed to subscribe to script: {}.", e);
+							return Err(InternalError::Failed);
+						},
+					};
+					subs.statuses.insert(script.clone(), status);
+				}
 			},
 		}
+		Ok(())
+	}
+
+	// Drops any subscriptions to scripts that are not relevant to any watched item anymore.
+	fn prune_subscriptions(&self, sync_state: &SyncState, subs: &mut ScriptSubscriptions) {
+		subs.tx_scripts.retain(|txid, _| sync_state.watched_transactions.contains(txid));
+
+		let mut relevant_scripts = subs.tx_scripts.values().collect::<HashSet<&ScriptBuf>>();
+		relevant_scripts.extend(sync_state.watched_outputs.values().map(|o| &o.script_pubkey));
+
+		let stale_scripts = subs
+			.statuses
+			.keys()
+			.filter(|s| !relevant_scripts.contains(s))
+			.cloned()
+			.collect::<Vec<ScriptBuf>>();
+
+		for script in stale_scripts {
+			subs.statuses.remove(&script);
+			subs.synced_histories.remove(&script);
+			if let Err(e) = self.client.script_unsubscribe(&script) {
+				log_trace!(self.logger, "Failed to unsubscribe from script: {}", e);
+			}
+		}
 	}
 
 	/// Returns a reference to the underlying Electrum client.
@@ -498,9 +676,10 @@ impl<L: Deref> Filter for ElectrumSyncClient<L>
 where
 	L::Target: Logger,
 {
-	fn register_tx(&self, txid: &Txid, _script_pubkey: &Script) {
+	fn register_tx(&self, txid: &Txid, script_pubkey: &Script) {
 		let mut locked_queue = self.queue.lock().unwrap();
 		locked_queue.transactions.insert(*txid);
+		self.queued_tx_scripts.lock().unwrap().insert(*txid, script_pubkey.to_owned());
 	}
 
 	fn register_output(&self, output: WatchedOutput) {
@@ -508,3 +687,89 @@ where
 		locked_queue.outputs.insert(output.outpoint.into_bitcoin_outpoint(), output);
 	}
 }
+
+// Electrum-specific state we keep across sync rounds, allowing us to reuse script subscriptions
+// and to only query the histories of scripts whose status changed since we last checked them.
+struct ScriptSubscriptions {
+	// The scripts we use to look up watched transactions, as registered via `Filter::register_tx`
+	// or as retrieved from the transaction itself.
+	tx_scripts: HashMap<Txid, ScriptBuf>,
+	// The scripts we're subscribed to, mapped to the latest status the server reported.
+	statuses: HashMap<ScriptBuf, Option<ScriptStatus>>,
+	// The status and history of scripts as of the last successful sync round.
+	synced_histories: HashMap<ScriptBuf, SyncedHistory>,
+	// The transactions and outputs we checked during the last successful sync round.
+	synced_txs: HashSet<Txid>,
+	synced_outputs: HashSet<OutPoint>,
+}
+
+impl ScriptSubscriptions {
+	fn new() -> Self {
+		Self {
+			tx_scripts: HashMap::new(),
+			statuses: HashMap::new(),
+			synced_histories: HashMap::new(),
+			synced_txs: HashSet::new(),
+			synced_outputs: HashSet::new(),
+		}
+	}
+
+	// Returns the history entries of the given script we still need to check for the watched item
+	// in question, i.e., all of them if the item is new, and otherwise only those we haven't seen
+	// during the last successful sync round.
+	fn unchecked_entries(
+		&self, script: &ScriptBuf, is_new: bool,
+		checked_histories: &HashMap<ScriptBuf, SyncedHistory>,
+	) -> Vec<(Txid, i32)> {
+		match (checked_histories.get(script), self.synced_histories.get(script)) {
+			(Some(checked), Some(synced)) if !is_new => {
+				let known_entries = synced.entries.iter().collect::<HashSet<_>>();
+				checked.entries.iter().filter(|e| !known_entries.contains(e)).copied().collect()
+			},
+			(Some(checked), _) => checked.entries.clone(),
+			(None, Some(synced)) if is_new => synced.entries.clone(),
+			(None, _) => Vec::new(),
+		}
+	}
+
+	fn commit(&mut self, checked_scripts: CheckedScripts) {
+		self.synced_histories.extend(checked_scripts.histories);
+		self.synced_txs = checked_scripts.txs;
+		self.synced_outputs = checked_scripts.outputs;
+	}
+
+	fn reset_synced(&mut self) {
+		self.synced_histories.clear();
+		self.synced_txs.clear();
+		self.synced_outputs.clear();
+	}
+}
+
+struct SyncedHistory {
+	status: Option<ScriptStatus>,
+	// The txids and heights of the script's history entries.
+	entries: Vec<(Txid, i32)>,
+}
+
+// The script histories and watched items checked during a sync round, which are committed to our
+// `ScriptSubscriptions` once the round's results have been handed to the `Confirm` implementations.
+struct CheckedScripts {
+	histories: HashMap<ScriptBuf, SyncedHistory>,
+	txs: HashSet<Txid>,
+	outputs: HashSet<OutPoint>,
+}
+
+// A transaction found in the history of a watched script that might be relevant to us.
+struct TxCandidate {
+	height: u32,
+	// Whether the transaction was registered via `Filter::register_tx`.
+	is_watched: bool,
+	// The watched outputs the transaction might spend.
+	spent_outpoints: Vec<OutPoint>,
+}
+
+impl TxCandidate {
+	fn new(height: u32) -> Self {
+		Self { height, is_watched: false, spent_outpoints: Vec::new() }
+	}
+}
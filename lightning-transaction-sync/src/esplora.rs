@@ -21,10 +21,45 @@ use bitcoin::{BlockHash, Script, Txid};
 use esplora_client::blocking::BlockingClient;
 #[cfg(feature = "async-interface")]
 use esplora_client::r#async::AsyncClient;
-use esplora_client::Builder;
+use esplora_client::{Builder, OutputStatus};
 
 use core::ops::Deref;
+use core::time::Duration;
+use std::collections::hash_map::RandomState;
 use std::collections::HashSet;
+use std::hash::{BuildHasher, Hasher};
+
+/// Configures how an [`EsploraSyncClient`] issues requests to the Esplora server.
+#[derive(Clone, Copy, Debug)]
+pub struct EsploraSyncConfig {
+	/// The maximum number of lookups we have in flight at any given time during a sync round.
+	///
+	/// Only applies if the `esplora-async` feature is enabled. The blocking client always issues
+	/// its lookups sequentially.
+	///
+	/// Default value: `4`.
+	pub max_concurrent_lookups: usize,
+	/// The maximum number of times we retry a request that failed due to a transient error, e.g.,
+	/// as the server is rate-limiting us or was temporarily unreachable.
+	///
+	/// Default value: `3`.
+	pub max_retries: u8,
+	/// The delay before the first retry of a failed request. The delay doubles for each subsequent
+	/// retry, and is randomized to avoid many clients retrying in lockstep.
+	///
+	/// Default value: 250 milliseconds.
+	pub retry_base_delay: Duration,
+}
+
+impl Default for EsploraSyncConfig {
+	fn default() -> Self {
+		Self {
+			max_concurrent_lookups: 4,
+			max_retries: 3,
+			retry_base_delay: Duration::from_millis(250),
+		}
+	}
+}
 
 /// Synchronizes LDK with a given [`Esplora`] server.
 ///
@@ -36,7 +71,9 @@ use std::collections::HashSet;
 /// [`Watch::watch_channel`] to ensure we get notified of the items to monitor.
 ///
 /// This uses and exposes either a blocking or async client variant dependent on whether the
-/// `esplora-blocking` or the `esplora-async` feature is enabled.
+/// `esplora-blocking` or the `esplora-async` feature is enabled. When using the async client,
+/// lookups of registered transactions and outputs are issued concurrently, as configured via
+/// [`EsploraSyncConfig`].
 ///
 /// [`Esplora`]: https://github.com/Blockstream/electrs
 /// [`ChainMonitor`]: lightning::chain::chainmonitor::ChainMonitor
@@ -49,6 +86,7 @@ where
 	sync_state: MutexType<SyncState>,
 	queue: std::sync::Mutex<FilterQueue>,
 	client: EsploraClientType,
+	config: EsploraSyncConfig,
 	logger: L,
 }
 
@@ -58,22 +96,37 @@ where
 {
 	/// Returns a new [`EsploraSyncClient`] object.
 	pub fn new(server_url: String, logger: L) -> Self {
+		Self::new_with_config(server_url, EsploraSyncConfig::default(), logger)
+	}
+
+	/// Returns a new [`EsploraSyncClient`] object using the given [`EsploraSyncConfig`].
+	pub fn new_with_config(server_url: String, config: EsploraSyncConfig, logger: L) -> Self {
 		let builder = Builder::new(&server_url);
 		#[cfg(not(feature = "async-interface"))]
 		let client = builder.build_blocking();
 		#[cfg(feature = "async-interface")]
 		let client = builder.build_async().unwrap();
 
-		EsploraSyncClient::from_client(client, logger)
+		EsploraSyncClient::from_client_with_config(client, config, logger)
 	}
 
 	/// Returns a new [`EsploraSyncClient`] object using the given Esplora client.
 	///
 	/// This is not exported to bindings users as the underlying client from BDK is not exported.
 	pub fn from_client(client: EsploraClientType, logger: L) -> Self {
+		Self::from_client_with_config(client, EsploraSyncConfig::default(), logger)
+	}
+
+	/// Returns a new [`EsploraSyncClient`] object using the given Esplora client and
+	/// [`EsploraSyncConfig`].
+	///
+	/// This is not exported to bindings users as the underlying client from BDK is not exported.
+	pub fn from_client_with_config(
+		client: EsploraClientType, config: EsploraSyncConfig, logger: L,
+	) -> Self {
 		let sync_state = MutexType::new(SyncState::new());
 		let queue = std::sync::Mutex::new(FilterQueue::new());
-		Self { sync_state, queue, client, logger }
+		Self { sync_state, queue, client, config, logger }
 	}
 
 	/// Synchronizes the given `confirmables` via their [`Confirm`] interface implementations. This
@@ -104,7 +157,7 @@ where
 		let mut num_confirmed = 0;
 		let mut num_unconfirmed = 0;
 
-		let mut tip_hash = maybe_await!(self.client.get_tip_hash())?;
+		let mut tip_hash = maybe_await!(self.with_retry(|| self.client.get_tip_hash()))?;
 
 		loop {
 			let pending_registrations = self.queue.lock().unwrap().process_queues(&mut sync_state);
@@ -123,7 +176,7 @@ where
 						Ok(unconfirmed_txs) => {
 							// Double-check the tip hash. If it changed, a reorg happened since
 							// we started syncing and we need to restart last-minute.
-							match maybe_await!(self.client.get_tip_hash()) {
+							match maybe_await!(self.with_retry(|| self.client.get_tip_hash())) {
 								Ok(check_tip_hash) => {
 									if check_tip_hash != tip_hash {
 										tip_hash = check_tip_hash;
@@ -194,7 +247,7 @@ where
 					Ok(confirmed_txs) => {
 						// Double-check the tip hash. If it changed, a reorg happened since
 						// we started syncing and we need to restart last-minute.
-						match maybe_await!(self.client.get_tip_hash()) {
+						match maybe_await!(self.with_retry(|| self.client.get_tip_hash())) {
 							Ok(check_tip_hash) => {
 								if check_tip_hash != tip_hash {
 									tip_hash = check_tip_hash;
@@ -272,8 +325,9 @@ where
 		C::Target: Confirm,
 	{
 		// Inform the interface of the new block.
-		let tip_header = maybe_await!(self.client.get_header_by_hash(tip_hash))?;
-		let tip_status = maybe_await!(self.client.get_block_status(&tip_hash))?;
+		let tip_header =
+			maybe_await!(self.with_retry(|| self.client.get_header_by_hash(tip_hash)))?;
+		let tip_status = maybe_await!(self.with_retry(|| self.client.get_block_status(&tip_hash)))?;
 		if tip_status.in_best_chain {
 			if let Some(tip_height) = tip_status.height {
 				for c in confirmables {
@@ -295,47 +349,72 @@ where
 	) -> Result<Vec<ConfirmedTx>, InternalError> {
 		// First, check the confirmation status of registered transactions as well as the
 		// status of dependent transactions of registered outputs.
-
-		let mut confirmed_txs: Vec<ConfirmedTx> = Vec::new();
-
-		for txid in &sync_state.watched_transactions {
-			if confirmed_txs.iter().any(|ctx| ctx.txid == *txid) {
-				continue;
-			}
-			if let Some(confirmed_tx) = maybe_await!(self.get_confirmed_tx(*txid, None, None))? {
-				confirmed_txs.push(confirmed_tx);
-			}
-		}
-
-		for (_, output) in &sync_state.watched_outputs {
-			if let Some(output_status) = maybe_await!(self
-				.client
-				.get_output_status(&output.outpoint.txid, output.outpoint.index as u64))?
-			{
-				if let Some(spending_txid) = output_status.txid {
-					if let Some(spending_tx_status) = output_status.status {
-						if confirmed_txs.iter().any(|ctx| ctx.txid == spending_txid) {
-							if spending_tx_status.confirmed {
-								// Skip inserting duplicate ConfirmedTx entry
-								continue;
-							} else {
-								log_trace!(self.logger, "Inconsistency: Detected previously-confirmed Tx {} as unconfirmed", spending_txid);
-								return Err(InternalError::Inconsistency);
-							}
+		let mut confirmed_txs: Vec<ConfirmedTx> = maybe_await!(self.lookup_all(
+			sync_state
+				.watched_transactions
+				.iter()
+				.map(|txid| self.get_confirmed_tx(*txid, None, None))
+		))?
+		.into_iter()
+		.flatten()
+		.collect();
+
+		let output_statuses = maybe_await!(self.lookup_all(
+			sync_state.watched_outputs.values().map(|output| self.get_output_status(output))
+		))?;
+
+		let mut spend_lookups: Vec<(Txid, Option<BlockHash>, Option<u32>)> = Vec::new();
+		for output_status in output_statuses.into_iter().flatten() {
+			if let Some(spending_txid) = output_status.txid {
+				if let Some(spending_tx_status) = output_status.status {
+					if confirmed_txs.iter().any(|ctx| ctx.txid == spending_txid) {
+						if spending_tx_status.confirmed {
+							// Skip inserting duplicate ConfirmedTx entry
+							continue;
+						} else {
+							log_trace!(
+								self.logger,
+								"Inconsistency: Detected previously-confirmed Tx {} as unconfirmed",
+								spending_txid
+							);
+							return Err(InternalError::Inconsistency);
 						}
+					}
 
-						if let Some(confirmed_tx) = maybe_await!(self.get_confirmed_tx(
-							spending_txid,
-							spending_tx_status.block_hash,
-							spending_tx_status.block_height,
-						))? {
-							confirmed_txs.push(confirmed_tx);
+					let block_hash = spending_tx_status.block_hash;
+					if let Some((_, prev_block_hash, _)) =
+						spend_lookups.iter().find(|(txid, _, _)| *txid == spending_txid)
+					{
+						if *prev_block_hash != block_hash {
+							log_trace!(
+								self.logger,
+								"Inconsistency: Detected Tx {} with diverging confirmation status",
+								spending_txid
+							);
+							return Err(InternalError::Inconsistency);
 						}
+						// Skip looking up the same spending transaction twice.
+						continue;
 					}
+
+					spend_lookups.push((
+						spending_txid,
+						block_hash,
+						spending_tx_status.block_height,
+					));
 				}
 			}
 		}
 
+		let confirmed_spends = maybe_await!(self.lookup_all(spend_lookups.into_iter().map(
+			|(txid, block_hash, block_height)| self.get_confirmed_tx(
+				txid,
+				block_hash,
+				block_height
+			)
+		)))?;
+		confirmed_txs.extend(confirmed_spends.into_iter().flatten());
+
 		// Sort all confirmed transactions first by block height, then by in-block
 		// position, and finally feed them to the interface in order.
 		confirmed_txs.sort_unstable_by(|tx1, tx2| {
@@ -349,7 +428,9 @@ where
 	fn get_confirmed_tx(
 		&self, txid: Txid, expected_block_hash: Option<BlockHash>, known_block_height: Option<u32>,
 	) -> Result<Option<ConfirmedTx>, InternalError> {
-		if let Some(merkle_block) = maybe_await!(self.client.get_merkle_block(&txid))? {
+		if let Some(merkle_block) =
+			maybe_await!(self.with_retry(|| self.client.get_merkle_block(&txid)))?
+		{
 			let block_header = merkle_block.header;
 			let block_hash = block_header.block_hash();
 			if let Some(expected_block_hash) = expected_block_hash {
@@ -375,7 +456,7 @@ where
 
 			// unwrap() safety: len() > 0 is checked above
 			let pos = *indexes.first().unwrap() as usize;
-			if let Some(tx) = maybe_await!(self.client.get_tx(&txid))? {
+			if let Some(tx) = maybe_await!(self.with_retry(|| self.client.get_tx(&txid)))? {
 				if tx.compute_txid() != txid {
 					log_error!(self.logger, "Retrieved transaction for txid {} doesn't match expectations. This should not happen. Please verify server integrity.", txid);
 					return Err(InternalError::Failed);
@@ -402,7 +483,8 @@ where
 					return Ok(Some(ConfirmedTx { tx, txid, block_header, pos, block_height }));
 				}
 
-				let block_status = maybe_await!(self.client.get_block_status(&block_hash))?;
+				let block_status =
+					maybe_await!(self.with_retry(|| self.client.get_block_status(&block_hash)))?;
 				if let Some(block_height) = block_status.height {
 					return Ok(Some(ConfirmedTx { tx, txid, block_header, pos, block_height }));
 				} else {
@@ -434,25 +516,120 @@ where
 			.flat_map(|c| c.get_relevant_txids())
 			.collect::<HashSet<(Txid, u32, Option<BlockHash>)>>();
 
-		let mut unconfirmed_txs = Vec::new();
-
+		let mut confirmed_txids = Vec::with_capacity(relevant_txids.len());
 		for (txid, _conf_height, block_hash_opt) in relevant_txids {
 			if let Some(block_hash) = block_hash_opt {
-				let block_status = maybe_await!(self.client.get_block_status(&block_hash))?;
-				if block_status.in_best_chain {
-					// Skip if the block in question is still confirmed.
-					continue;
-				}
-
-				unconfirmed_txs.push(txid);
+				confirmed_txids.push((txid, block_hash));
 			} else {
 				log_error!(self.logger, "Untracked confirmation of funding transaction. Please ensure none of your channels had been created with LDK prior to version 0.0.113!");
 				panic!("Untracked confirmation of funding transaction. Please ensure none of your channels had been created with LDK prior to version 0.0.113!");
 			}
 		}
+
+		let in_best_chain = maybe_await!(self.lookup_all(
+			confirmed_txids.iter().map(|(_, block_hash)| self.is_block_in_best_chain(block_hash))
+		))?;
+
+		let mut unconfirmed_txs = Vec::new();
+		for ((txid, _), in_best_chain) in confirmed_txids.into_iter().zip(in_best_chain) {
+			if in_best_chain {
+				// Skip if the block in question is still confirmed.
+				continue;
+			}
+
+			unconfirmed_txs.push(txid);
+		}
 		Ok(unconfirmed_txs)
 	}
 
+	#[maybe_async]
+	fn get_output_status(
+		&self, output: &WatchedOutput,
+	) -> Result<Option<OutputStatus>, InternalError> {
+		let txid = output.outpoint.txid;
+		let index = output.outpoint.index as u64;
+		Ok(maybe_await!(self.with_retry(|| self.client.get_output_status(&txid, index)))?)
+	}
+
+	#[maybe_async]
+	fn is_block_in_best_chain(&self, block_hash: &BlockHash) -> Result<bool, InternalError> {
+		let block_status =
+			maybe_await!(self.with_retry(|| self.client.get_block_status(block_hash)))?;
+		Ok(block_status.in_best_chain)
+	}
+
+	// Runs the given lookups, with at most `max_concurrent_lookups` of them being in flight at any
+	// given time. The results are returned in the order of the given lookups.
+	#[cfg(feature = "async-interface")]
+	async fn lookup_all<T, F>(
+		&self, lookups: impl IntoIterator<Item = F>,
+	) -> Result<Vec<T>, InternalError>
+	where
+		F: core::future::Future<Output = Result<T, InternalError>>,
+	{
+		use futures::stream::{self, StreamExt, TryStreamExt};
+		let max_concurrent_lookups = self.config.max_concurrent_lookups.max(1);
+		stream::iter(lookups).buffered(max_concurrent_lookups).try_collect().await
+	}
+
+	#[cfg(not(feature = "async-interface"))]
+	fn lookup_all<T>(
+		&self, lookups: impl IntoIterator<Item = Result<T, InternalError>>,
+	) -> Result<Vec<T>, InternalError> {
+		lookups.into_iter().collect()
+	}
+
+	// Issues the given request, retrying it up to `max_retries` times with exponential backoff if
+	// it fails due to a transient error.
+	#[cfg(feature = "async-interface")]
+	async fn with_retry<T, R, F>(&self, request: R) -> Result<T, esplora_client::Error>
+	where
+		R: Fn() -> F,
+		F: core::future::Future<Output = Result<T, esplora_client::Error>>,
+	{
+		let mut num_retries = 0;
+		loop {
+			match request().await {
+				Err(e) if num_retries < self.config.max_retries && is_transient_error(&e) => {
+					let delay = retry_delay(self.config.retry_base_delay, num_retries);
+					log_trace!(
+						self.logger,
+						"Request to Esplora server failed, retrying in {}ms: {}",
+						delay.as_millis(),
+						e
+					);
+					tokio::time::sleep(delay).await;
+					num_retries += 1;
+				},
+				res => return res,
+			}
+		}
+	}
+
+	#[cfg(not(feature = "async-interface"))]
+	fn with_retry<T, R>(&self, request: R) -> Result<T, esplora_client::Error>
+	where
+		R: Fn() -> Result<T, esplora_client::Error>,
+	{
+		let mut num_retries = 0;
+		loop {
+			match request() {
+				Err(e) if num_retries < self.config.max_retries && is_transient_error(&e) => {
+					let delay = retry_delay(self.config.retry_base_delay, num_retries);
+					log_trace!(
+						self.logger,
+						"Request to Esplora server failed, retrying in {}ms: {}",
+						delay.as_millis(),
+						e
+					);
+					std::thread::sleep(delay);
+					num_retries += 1;
+				},
+				res => return res,
+			}
+		}
+	}
+
 	/// Returns a reference to the underlying esplora client.
 	///
 	/// This is not exported to bindings users as the underlying client from BDK is not exported.
@@ -461,6 +638,30 @@ where
 	}
 }
 
+// Returns whether the given error is likely transient, i.e., whether the request should be retried.
+fn is_transient_error(e: &esplora_client::Error) -> bool {
+	match e {
+		esplora_client::Error::HttpResponse { status, .. } => {
+			*status == 408 || *status == 429 || *status >= 500
+		},
+		#[cfg(feature = "esplora-async")]
+		esplora_client::Error::Reqwest(_) => true,
+		#[cfg(feature = "esplora-blocking")]
+		esplora_client::Error::Minreq(_) => true,
+		_ => false,
+	}
+}
+
+// Returns the delay before the given retry, doubling the base delay for each previous retry and
+// picking a random value between half and the full resulting delay.
+fn retry_delay(base_delay: Duration, num_retries: u8) -> Duration {
+	let max_delay = base_delay.saturating_mul(1u32 << num_retries.min(16));
+	let min_delay = max_delay / 2;
+	let jitter_range_nanos = (max_delay - min_delay).as_nanos() as u64;
+	let random = RandomState::new().build_hasher().finish();
+	min_delay + Duration::from_nanos(random % jitter_range_nanos.saturating_add(1))
+}
+
 #[cfg(feature = "async-interface")]
 type MutexType<I> = futures::lock::Mutex<I>;
 #[cfg(not(feature = "async-interface"))]
@@ -17,6 +17,8 @@
 //!- `esplora-blocking` enables syncing against an Esplora backend based on a blocking client.
 //!- `esplora-async` enables syncing against an Esplora backend based on an async client.
 //!- `esplora-async-https` enables the async Esplora client with support for HTTPS.
+//!- `regtest` enables the `regtest` module, providing helpers to run end-to-end tests against a
+//!  regtest `bitcoind`.
 //!
 //! ## Version Compatibility
 //!
@@ -77,6 +79,9 @@ mod esplora;
 #[cfg(any(feature = "_electrum"))]
 mod electrum;
 
+#[cfg(all(feature = "regtest", not(target_os = "windows")))]
+pub mod regtest;
+
 #[cfg(any(feature = "esplora-blocking", feature = "esplora-async", feature = "_electrum"))]
 mod common;
 #[cfg(any(feature = "esplora-blocking", feature = "esplora-async", feature = "_electrum"))]
@@ -87,4 +92,4 @@ pub use error::TxSyncError;
 #[cfg(feature = "_electrum")]
 pub use electrum::ElectrumSyncClient;
 #[cfg(any(feature = "esplora-blocking", feature = "esplora-async"))]
-pub use esplora::EsploraSyncClient;
+pub use esplora::{EsploraSyncClient, EsploraSyncConfig};
@@ -0,0 +1,212 @@
+// This file is Copyright its original authors, visible in version control history.
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. You may not use this file except in
+// accordance with one or both of these licenses.
+
+//! Helpers to run end-to-end tests against a regtest `bitcoind`.
+//!
+//! [`RegtestEnv`] spawns a `bitcoind` and an `electrs` instance on regtest and allows to mine
+//! blocks and fund addresses. It further provides chain sources wired to the spawned instances,
+//! i.e., an [`RpcClient`] for use with `lightning-block-sync`, as well as the transaction-based
+//! sync clients of this crate, depending on the enabled features.
+//!
+//! The `bitcoind` and `electrs` binaries are taken from the `BITCOIND_EXE` and `ELECTRS_EXE`
+//! environment variables, or, if unset, from the binaries downloaded by the respective version
+//! features of the `corepc-node` and `electrsd` crates.
+//!
+//! [`RpcClient`]: lightning_block_sync::rpc::RpcClient
+
+#[cfg(any(feature = "esplora-blocking", feature = "esplora-async", feature = "_electrum"))]
+use lightning::util::logger::Logger;
+
+use lightning_block_sync::http::HttpEndpoint;
+use lightning_block_sync::rpc::RpcClient;
+
+#[cfg(feature = "_electrum")]
+use crate::ElectrumSyncClient;
+#[cfg(any(feature = "esplora-blocking", feature = "esplora-async"))]
+use crate::EsploraSyncClient;
+#[cfg(feature = "_electrum")]
+use crate::TxSyncError;
+
+use bitcoin::base64::engine::general_purpose::STANDARD as BASE64;
+use bitcoin::base64::Engine;
+use bitcoin::{Address, Amount, Txid};
+
+use corepc_node::Node as BitcoinD;
+use electrsd::electrum_client::ElectrumApi;
+use electrsd::ElectrsD;
+
+use std::env;
+#[cfg(any(feature = "esplora-blocking", feature = "esplora-async", feature = "_electrum"))]
+use std::ops::Deref;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// A regtest environment consisting of a `bitcoind` and an `electrs` instance.
+///
+/// Both instances are killed when this is dropped.
+pub struct RegtestEnv {
+	/// The spawned `bitcoind` instance, whose `client` may be used for further RPC calls.
+	pub bitcoind: BitcoinD,
+	/// The spawned `electrs` instance, connected to [`Self::bitcoind`].
+	pub electrsd: ElectrsD,
+}
+
+impl RegtestEnv {
+	/// Spawns a new regtest environment and mines enough blocks to have spendable coinbase
+	/// outputs in the `bitcoind` wallet.
+	///
+	/// Panics if the binaries can't be found or fail to start.
+	pub fn new() -> Self {
+		let bitcoind_exe = env::var("BITCOIND_EXE")
+			.ok()
+			.or_else(|| corepc_node::downloaded_exe_path().ok())
+			.expect(
+				"you need to provide an env var BITCOIND_EXE or specify a bitcoind version feature",
+			);
+		let mut bitcoind_conf = corepc_node::Conf::default();
+		bitcoind_conf.network = "regtest";
+		let bitcoind = BitcoinD::with_conf(bitcoind_exe, &bitcoind_conf).unwrap();
+
+		let electrs_exe =
+			env::var("ELECTRS_EXE").ok().or_else(electrsd::downloaded_exe_path).expect(
+				"you need to provide env var ELECTRS_EXE or specify an electrsd version feature",
+			);
+		let mut electrsd_conf = electrsd::Conf::default();
+		electrsd_conf.http_enabled = true;
+		electrsd_conf.network = "regtest";
+		let electrsd = ElectrsD::with_conf(electrs_exe, &bitcoind, &electrsd_conf).unwrap();
+
+		let env = Self { bitcoind, electrsd };
+		// Coinbase outputs only become spendable after 100 confirmations.
+		env.mine_blocks(101);
+		env
+	}
+
+	/// Returns the current height of the best chain known to `bitcoind`.
+	pub fn block_height(&self) -> u32 {
+		let block_count =
+			self.bitcoind.client.get_block_count().expect("failed to get block count");
+		block_count.into_model().0 as u32
+	}
+
+	/// Mines `num` blocks to a wallet address and waits for `electrs` to catch up.
+	pub fn mine_blocks(&self, num: usize) {
+		let cur_height = self.block_height();
+		let address = self.bitcoind.client.new_address().expect("failed to get new address");
+		// TODO: expect this Result once the WouldBlock issue is resolved upstream.
+		let _block_hashes_res = self.bitcoind.client.generate_to_address(num, &address);
+		self.wait_for_block(cur_height + num as u32);
+	}
+
+	/// Sends `amount` from the `bitcoind` wallet to the given address, returning the id of the
+	/// funding transaction. The transaction is not confirmed until blocks are mined via
+	/// [`Self::mine_blocks`].
+	pub fn fund_address(&self, address: &Address, amount: Amount) -> Txid {
+		let txid = self
+			.bitcoind
+			.client
+			.send_to_address(address, amount)
+			.expect("failed to send to address")
+			.0;
+		let txid = Txid::from_str(&txid).expect("bitcoind returned an invalid txid");
+		self.wait_for_tx(&txid);
+		txid
+	}
+
+	/// Waits until `electrs` has indexed a block at `min_height` or above.
+	///
+	/// Panics if that doesn't happen in a reasonable amount of time.
+	pub fn wait_for_block(&self, min_height: u32) {
+		let mut header = match self.electrsd.client.block_headers_subscribe_raw() {
+			Ok(header) => header,
+			Err(_) => {
+				// While subscribing should succeed the first time around, we ran into some cases
+				// where it didn't. Since we can't proceed without subscribing, we try again after a
+				// delay and panic if it still fails.
+				std::thread::sleep(Duration::from_secs(1));
+				self.electrsd
+					.client
+					.block_headers_subscribe_raw()
+					.expect("failed to subscribe to block headers")
+			},
+		};
+		while header.height < min_height as usize {
+			header = exponential_backoff_poll(|| {
+				self.electrsd.trigger().expect("failed to trigger electrsd");
+				self.electrsd.client.ping().expect("failed to ping electrsd");
+				self.electrsd.client.block_headers_pop_raw().expect("failed to pop block header")
+			});
+		}
+	}
+
+	/// Waits until `electrs` has seen the transaction with the given id.
+	///
+	/// Panics if that doesn't happen in a reasonable amount of time.
+	pub fn wait_for_tx(&self, txid: &Txid) {
+		exponential_backoff_poll(|| {
+			self.electrsd.trigger().expect("failed to trigger electrsd");
+			self.electrsd.client.transaction_get(txid).ok()
+		});
+	}
+
+	/// Returns an [`RpcClient`] connected to `bitcoind`, which implements `lightning-block-sync`'s
+	/// [`BlockSource`].
+	///
+	/// [`BlockSource`]: lightning_block_sync::BlockSource
+	pub fn rpc_client(&self) -> RpcClient {
+		let cookie = std::fs::read_to_string(&self.bitcoind.params.cookie_file)
+			.expect("failed to read bitcoind cookie file");
+		let credentials = BASE64.encode(cookie.trim());
+		let rpc_socket = self.bitcoind.params.rpc_socket;
+		let endpoint =
+			HttpEndpoint::for_host(rpc_socket.ip().to_string()).with_port(rpc_socket.port());
+		RpcClient::new(&credentials, endpoint)
+	}
+
+	/// Returns an [`EsploraSyncClient`] connected to `electrs`' Esplora interface.
+	#[cfg(any(feature = "esplora-blocking", feature = "esplora-async"))]
+	pub fn esplora_sync_client<L: Deref>(&self, logger: L) -> EsploraSyncClient<L>
+	where
+		L::Target: Logger,
+	{
+		let esplora_url = format!("http://{}", self.electrsd.esplora_url.as_ref().unwrap());
+		EsploraSyncClient::new(esplora_url, logger)
+	}
+
+	/// Returns an [`ElectrumSyncClient`] connected to `electrs`' Electrum interface.
+	#[cfg(feature = "_electrum")]
+	pub fn electrum_sync_client<L: Deref>(
+		&self, logger: L,
+	) -> Result<ElectrumSyncClient<L>, TxSyncError>
+	where
+		L::Target: Logger,
+	{
+		let electrum_url = format!("tcp://{}", self.electrsd.electrum_url);
+		ElectrumSyncClient::new(electrum_url, logger)
+	}
+}
+
+fn exponential_backoff_poll<T, F>(mut poll: F) -> T
+where
+	F: FnMut() -> Option<T>,
+{
+	let mut delay = Duration::from_millis(64);
+	let mut tries = 0;
+	loop {
+		match poll() {
+			Some(data) => break data,
+			None if delay.as_millis() < 512 => {
+				delay = delay.mul_f32(2.0);
+				tries += 1;
+			},
+			None if tries == 10 => panic!("Exceeded our maximum wait time."),
+			None => tries += 1,
+		}
+
+		std::thread::sleep(delay);
+	}
+}
@@ -84,6 +84,10 @@
 //! - `HtlcHold` - requires/supports holding HTLCs and forwarding on receipt of an onion message
 //!   (see [BOLT-2](https://github.com/lightning/bolts/pull/989/files) for more information).
 //!
+//! - `PtlcExperimental` - an experimental channel type using point time-locked contracts in place
+//!   of HTLCs. Only usable for research when LDK is built with the `ptlc_experimental` feature, and
+//!   not compatible with any other implementation.
+//!
 //! LDK knows about the following features, but does not support them:
 //! - `AnchorsNonzeroFeeHtlcTx` - the initial version of anchor outputs, which was later found to be
 //!   vulnerable (see this
@@ -175,6 +179,10 @@ mod sealed {
 			,
 			// Byte 19
 			HtlcHold | SplicePrototype,
+			// Byte 20 - 21
+			,,
+			// Byte 22
+			PtlcExperimental,
 		]
 	);
 	define_context!(
@@ -204,8 +212,12 @@ mod sealed {
 			,
 			// Byte 19
 			HtlcHold | SplicePrototype,
-			// Byte 20 - 31
-			,,,,,,,,,,,,
+			// Byte 20 - 21
+			,,
+			// Byte 22
+			PtlcExperimental,
+			// Byte 23 - 31
+			,,,,,,,,,
 			// Byte 32
 			DnsResolver,
 		]
@@ -271,6 +283,10 @@ mod sealed {
 		,,,,,,,,,,
 		// Byte 17
 		AnchorZeroFeeCommitmentsStaging,
+		// Byte 18 - 21
+		,,,,
+		// Byte 22
+		PtlcExperimental,
 	]);
 
 	/// Defines a feature with the given bits for the specified [`Context`]s. The generated trait is
@@ -732,6 +748,17 @@ mod sealed {
 		supports_splicing,
 		requires_splicing
 	);
+	define_feature!(
+		177, // No BOLTs PR assigns PTLCs a feature bit yet, so use one well clear of the others
+		PtlcExperimental,
+		[InitContext, NodeContext, ChannelTypeContext],
+		"Feature flags for experimental point time-locked contract (PTLC) channels.",
+		set_ptlc_experimental_optional,
+		set_ptlc_experimental_required,
+		clear_ptlc_experimental,
+		supports_ptlc_experimental,
+		requires_ptlc_experimental
+	);
 	define_feature!(
 		259,
 		DnsResolver,
@@ -15,8 +15,11 @@
 
 use core::{cmp, ops::Deref};
 
+use crate::ln::types::ChannelId;
 use crate::prelude::*;
+use crate::types::features::ChannelTypeFeatures;
 
+use bitcoin::secp256k1::PublicKey;
 use bitcoin::transaction::Transaction;
 
 // TODO: Define typed abstraction over feerates to handle their conversions.
@@ -43,9 +46,50 @@ pub trait BroadcasterInterface {
 	///
 	/// Implementations MUST NOT assume any topological order on the transactions.
 	///
+	/// Note that the version 3 (TRUC) commitment transactions of zero-fee commitment channels pay
+	/// no fee themselves, and will thus only be accepted into the mempool when broadcast as a
+	/// package together with the child transaction spending their pay-to-anchor output.
+	///
 	/// Bitcoin transaction packages are defined in BIP 331 and here:
 	/// <https://github.com/bitcoin/bitcoin/blob/master/doc/policy/packages.md>
-	fn broadcast_transactions(&self, txs: &[&Transaction]);
+	///
+	/// The given [`BroadcastContext`] describes why the transactions are being broadcast, allowing
+	/// implementations to apply different relay strategies, e.g. submitting justice transactions
+	/// directly to miners. For packages, it describes the child transaction.
+	fn broadcast_transactions(&self, txs: &[&Transaction], context: BroadcastContext);
+}
+
+/// The kind of transaction being handed to a [`BroadcasterInterface`].
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum BroadcastType {
+	/// A transaction funding (or splicing) one or more channels.
+	Funding,
+	/// A transaction closing a channel cooperatively.
+	CooperativeClose,
+	/// One of our commitment transactions, closing a channel unilaterally.
+	UnilateralClose,
+	/// A child transaction spending an anchor output to bump the fee of a commitment transaction.
+	AnchorBump,
+	/// A transaction claiming HTLC outputs of a commitment transaction, either through the
+	/// preimage or after the HTLC timed out.
+	HtlcClaim,
+	/// A transaction claiming outputs of a revoked commitment transaction our counterparty
+	/// broadcast, also known as a justice transaction.
+	Justice,
+	/// A transaction sweeping outputs which became spendable by us to our wallet.
+	Sweep,
+}
+
+/// Context about the transactions handed to [`BroadcasterInterface::broadcast_transactions`].
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub struct BroadcastContext {
+	/// The kind of transaction being broadcast.
+	pub broadcast_type: BroadcastType,
+	/// The channel the transaction relates to, if it relates to a single one.
+	pub channel_id: Option<ChannelId>,
+	/// How urgently the transaction needs to confirm, expressed as the [`ConfirmationTarget`] it
+	/// was built for, if any.
+	pub urgency: Option<ConfirmationTarget>,
 }
 
 /// An enum that represents the priority at which we want a transaction to confirm used for feerate
@@ -68,6 +112,18 @@ pub enum ConfirmationTarget {
 	/// on-chain (it doesn't have to happen in the next few blocks!), but we shouldn't risk too low
 	/// a fee - this should be a relatively high priority feerate.
 	UrgentOnChainSweep,
+	/// Similar to [`Self::UrgentOnChainSweep`], but used when we know the number of blocks left
+	/// before our counterparty may be able to steal the funds being claimed, e.g., because an HTLC
+	/// we're claiming is about to expire.
+	///
+	/// As `blocks_until_deadline` decreases, the claim becomes more urgent, so the feerate returned
+	/// should be non-increasing with it. Implementors without deadline-aware estimates may simply
+	/// return the same feerate as for [`Self::UrgentOnChainSweep`].
+	UrgentOnChainSweepWithDeadline {
+		/// The number of blocks left until the deadline by which the claim must confirm. This may
+		/// be zero if the deadline has already been reached.
+		blocks_until_deadline: u32,
+	},
 	/// This is the lowest feerate we will allow our channel counterparty to have in an anchor
 	/// channel in order to close the channel if a channel party goes away.
 	///
@@ -211,11 +267,203 @@ where
 	}
 }
 
+/// The bounds within which we accept a feerate proposed by our counterparty in an `update_fee`
+/// message, as returned by an [`UpdateFeePolicy`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RemoteFeerateLimits {
+	/// The feerate, in satoshis per 1000 weight units, below which we will force-close the channel
+	/// rather than accept the new feerate, unless it is higher than the feerate currently in use.
+	pub min_feerate_sat_per_1000_weight: u32,
+	/// The feerate, in satoshis per 1000 weight units, below which we generate an
+	/// [`Event::PeerFeerateNearLimit`] to warn that we're close to force-closing the channel.
+	///
+	/// If this is not greater than [`Self::min_feerate_sat_per_1000_weight`], no warning is ever
+	/// generated for feerates we accept.
+	///
+	/// [`Event::PeerFeerateNearLimit`]: crate::events::Event::PeerFeerateNearLimit
+	pub warn_below_sat_per_1000_weight: u32,
+	/// Once a warning has been generated for a channel, the feerate, in satoshis per 1000 weight
+	/// units, at or above which our counterparty must set the channel's feerate before another
+	/// warning may be generated.
+	///
+	/// This should be no lower than [`Self::warn_below_sat_per_1000_weight`], with any gap between
+	/// the two avoiding repeated warnings as the feerate oscillates around the warning threshold.
+	pub warn_reset_above_sat_per_1000_weight: u32,
+}
+
+/// A policy deciding which feerates proposed by our counterparty in `update_fee` messages we are
+/// willing to accept.
+///
+/// Accepting a feerate which is too low may leave us unable to get the commitment transaction
+/// confirmed (or even into the mempool) should we need to force-close the channel, while rejecting
+/// one requires force-closing the channel immediately.
+///
+/// Note that this does not apply to the initial feerate proposed in `open_channel` messages, which
+/// is checked by an [`InboundChannelFeePolicy`] instead.
+pub trait UpdateFeePolicy {
+	/// Returns the bounds within which to accept a feerate proposed by our counterparty in a
+	/// channel of the given `channel_type`.
+	///
+	/// `min_allowed_estimate_sat_per_1000_weight` is our [`FeeEstimator`]'s current estimate for
+	/// [`ConfirmationTarget::MinAllowedAnchorChannelRemoteFee`] or
+	/// [`ConfirmationTarget::MinAllowedNonAnchorChannelRemoteFee`], depending on whether the
+	/// channel uses anchor outputs.
+	fn remote_feerate_limits(
+		&self, channel_type: &ChannelTypeFeatures, min_allowed_estimate_sat_per_1000_weight: u32,
+	) -> RemoteFeerateLimits;
+}
+
+/// Thresholds, as percentages of our [`FeeEstimator`]'s estimate, used by the
+/// [`DefaultUpdateFeePolicy`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UpdateFeeThresholds {
+	/// The percentage of our estimate below which we force-close the channel.
+	///
+	/// See [`RemoteFeerateLimits::min_feerate_sat_per_1000_weight`].
+	///
+	/// Default value: `100`
+	pub min_feerate_percent: u16,
+	/// The percentage of our estimate below which we warn that we're close to force-closing the
+	/// channel.
+	///
+	/// See [`RemoteFeerateLimits::warn_below_sat_per_1000_weight`].
+	///
+	/// Default value: `100` (i.e. warnings are disabled)
+	pub warn_below_percent: u16,
+	/// The number of percentage points above [`Self::warn_below_percent`] the feerate must reach
+	/// before we warn again.
+	///
+	/// See [`RemoteFeerateLimits::warn_reset_above_sat_per_1000_weight`].
+	///
+	/// Default value: `10`
+	pub hysteresis_percent: u16,
+}
+
+impl Default for UpdateFeeThresholds {
+	fn default() -> Self {
+		Self { min_feerate_percent: 100, warn_below_percent: 100, hysteresis_percent: 10 }
+	}
+}
+
+impl UpdateFeeThresholds {
+	fn limits(&self, estimate_sat_per_1000_weight: u32) -> RemoteFeerateLimits {
+		let percent_of_estimate = |percent: u16| -> u32 {
+			(estimate_sat_per_1000_weight as u64 * percent as u64 / 100)
+				.try_into()
+				.unwrap_or(u32::max_value())
+		};
+		let warn_reset_percent = self.warn_below_percent.saturating_add(self.hysteresis_percent);
+		RemoteFeerateLimits {
+			min_feerate_sat_per_1000_weight: percent_of_estimate(self.min_feerate_percent),
+			warn_below_sat_per_1000_weight: percent_of_estimate(self.warn_below_percent),
+			warn_reset_above_sat_per_1000_weight: percent_of_estimate(warn_reset_percent),
+		}
+	}
+}
+
+/// The default [`UpdateFeePolicy`], which computes its bounds as a percentage of our
+/// [`FeeEstimator`]'s estimate, with distinct thresholds for anchor and non-anchor channels.
+///
+/// By default, this rejects any feerate below our estimate and never generates warnings.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DefaultUpdateFeePolicy {
+	/// The thresholds applied to channels using anchor outputs.
+	pub anchor_channels: UpdateFeeThresholds,
+	/// The thresholds applied to channels not using anchor outputs.
+	pub non_anchor_channels: UpdateFeeThresholds,
+}
+
+impl UpdateFeePolicy for DefaultUpdateFeePolicy {
+	fn remote_feerate_limits(
+		&self, channel_type: &ChannelTypeFeatures, min_allowed_estimate_sat_per_1000_weight: u32,
+	) -> RemoteFeerateLimits {
+		let thresholds = if channel_type.supports_anchors_zero_fee_htlc_tx() {
+			&self.anchor_channels
+		} else {
+			&self.non_anchor_channels
+		};
+		thresholds.limits(min_allowed_estimate_sat_per_1000_weight)
+	}
+}
+
+/// The decision of an [`InboundChannelFeePolicy`] on the commitment transaction feerate proposed
+/// by our counterparty when opening a channel to us.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InboundChannelFeerateDecision {
+	/// Accept the proposed feerate, subject to all other checks on the channel.
+	Accept,
+	/// Reject the channel, responding to our counterparty with an error message containing the
+	/// range of feerates we would accept.
+	///
+	/// Channel opens cannot be renegotiated, but a well-behaved counterparty may re-attempt to open
+	/// the channel with a feerate in the given range.
+	ProposeRange {
+		/// The minimum feerate, in satoshis per 1000 weight units, we would accept.
+		min_feerate_sat_per_1000_weight: u32,
+		/// The maximum feerate, in satoshis per 1000 weight units, we would accept, if any.
+		max_feerate_sat_per_1000_weight: Option<u32>,
+	},
+	/// Leave the decision to the user, generating an [`Event::OpenChannelRequest`] for the channel
+	/// even if [`UserConfig::manually_accept_inbound_channels`] is not set.
+	///
+	/// Accepting the channel via [`ChannelManager::accept_inbound_channel`] then accepts the
+	/// proposed feerate.
+	///
+	/// [`Event::OpenChannelRequest`]: crate::events::Event::OpenChannelRequest
+	/// [`UserConfig::manually_accept_inbound_channels`]: crate::util::config::UserConfig::manually_accept_inbound_channels
+	/// [`ChannelManager::accept_inbound_channel`]: crate::ln::channelmanager::ChannelManager::accept_inbound_channel
+	Defer,
+}
+
+/// A policy deciding whether we accept the commitment transaction feerate proposed by our
+/// counterparty in `open_channel` and `open_channel2` messages.
+///
+/// As with [`UpdateFeePolicy`], accepting a feerate which is too low may leave us unable to get the
+/// commitment transaction confirmed should we need to force-close the channel.
+///
+/// This is not consulted for channels using zero-fee commitments, which must never use a feerate.
+pub trait InboundChannelFeePolicy {
+	/// Decides whether to accept `feerate_sat_per_1000_weight`, as proposed by the peer with the
+	/// given `counterparty_node_id` for a new channel of the given `channel_type`.
+	///
+	/// `min_allowed_estimate_sat_per_1000_weight` is our [`FeeEstimator`]'s current estimate for
+	/// [`ConfirmationTarget::MinAllowedAnchorChannelRemoteFee`] or
+	/// [`ConfirmationTarget::MinAllowedNonAnchorChannelRemoteFee`], depending on whether the
+	/// channel uses anchor outputs.
+	fn decide_inbound_feerate(
+		&self, counterparty_node_id: &PublicKey, channel_type: &ChannelTypeFeatures,
+		feerate_sat_per_1000_weight: u32, min_allowed_estimate_sat_per_1000_weight: u32,
+	) -> InboundChannelFeerateDecision;
+}
+
+/// The default [`InboundChannelFeePolicy`], which accepts any feerate at or above our
+/// [`FeeEstimator`]'s estimate and proposes the range above it otherwise.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DefaultInboundChannelFeePolicy;
+
+impl InboundChannelFeePolicy for DefaultInboundChannelFeePolicy {
+	fn decide_inbound_feerate(
+		&self, _counterparty_node_id: &PublicKey, _channel_type: &ChannelTypeFeatures,
+		feerate_sat_per_1000_weight: u32, min_allowed_estimate_sat_per_1000_weight: u32,
+	) -> InboundChannelFeerateDecision {
+		if feerate_sat_per_1000_weight >= min_allowed_estimate_sat_per_1000_weight {
+			InboundChannelFeerateDecision::Accept
+		} else {
+			InboundChannelFeerateDecision::ProposeRange {
+				min_feerate_sat_per_1000_weight: min_allowed_estimate_sat_per_1000_weight,
+				max_feerate_sat_per_1000_weight: None,
+			}
+		}
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use super::{
-		ConfirmationTarget, FeeEstimator, LowerBoundedFeeEstimator, FEERATE_FLOOR_SATS_PER_KW,
+		ConfirmationTarget, DefaultUpdateFeePolicy, FeeEstimator, LowerBoundedFeeEstimator,
+		RemoteFeerateLimits, UpdateFeePolicy, UpdateFeeThresholds, FEERATE_FLOOR_SATS_PER_KW,
 	};
+	use crate::types::features::ChannelTypeFeatures;
 
 	struct TestFeeEstimator {
 		sat_per_kw: u32,
@@ -250,4 +498,36 @@ mod tests {
 			sat_per_kw
 		);
 	}
+
+	#[test]
+	fn test_default_update_fee_policy() {
+		let policy = DefaultUpdateFeePolicy {
+			anchor_channels: UpdateFeeThresholds {
+				min_feerate_percent: 50,
+				warn_below_percent: 80,
+				hysteresis_percent: 10,
+			},
+			non_anchor_channels: UpdateFeeThresholds::default(),
+		};
+
+		let anchors = ChannelTypeFeatures::anchors_zero_htlc_fee_and_dependencies();
+		assert_eq!(
+			policy.remote_feerate_limits(&anchors, 1000),
+			RemoteFeerateLimits {
+				min_feerate_sat_per_1000_weight: 500,
+				warn_below_sat_per_1000_weight: 800,
+				warn_reset_above_sat_per_1000_weight: 900,
+			}
+		);
+
+		let non_anchors = ChannelTypeFeatures::only_static_remote_key();
+		assert_eq!(
+			policy.remote_feerate_limits(&non_anchors, 1000),
+			RemoteFeerateLimits {
+				min_feerate_sat_per_1000_weight: 1000,
+				warn_below_sat_per_1000_weight: 1000,
+				warn_reset_above_sat_per_1000_weight: 1100,
+			}
+		);
+	}
 }
@@ -25,8 +25,10 @@
 
 use bitcoin::block::Header;
 use bitcoin::hash_types::{BlockHash, Txid};
+use bitcoin::locktime::absolute::LockTime;
 
 use bitcoin::secp256k1::PublicKey;
+use bitcoin::Amount;
 
 use crate::chain;
 use crate::chain::chaininterface::{BroadcasterInterface, FeeEstimator};
@@ -37,8 +39,13 @@ use crate::chain::channelmonitor::{
 	WithChannelMonitor,
 };
 use crate::chain::transaction::{OutPoint, TransactionData};
-use crate::chain::{BestBlock, ChannelMonitorUpdateStatus, Filter, WatchedOutput};
+use crate::chain::{
+	BestBlock, ChannelMonitorUpdateStatus, ChannelWatchItems, ClaimId, Filter, WatchRegistration,
+	WatchSnapshot, WatchedOutput,
+};
+use crate::events::bump_transaction::{BumpTransactionEvent, Utxo};
 use crate::events::{self, Event, EventHandler, ReplayEvent};
+use crate::io;
 use crate::ln::channel_state::ChannelDetails;
 #[cfg(peer_storage)]
 use crate::ln::msgs::PeerStorage;
@@ -51,20 +58,28 @@ use crate::sign::ecdsa::EcdsaChannelSigner;
 use crate::sign::{EntropySource, PeerStorageKey, SignerProvider};
 use crate::sync::{Mutex, MutexGuard, RwLock, RwLockReadGuard};
 use crate::types::features::{InitFeatures, NodeFeatures};
+use crate::util::anchor_channel_reserves::{
+	get_available_reserve, get_reserve_for_channel, AnchorChannelReserveContext,
+};
 use crate::util::async_poll::{MaybeSend, MaybeSync};
 use crate::util::errors::APIError;
 use crate::util::logger::{Logger, WithContext};
 use crate::util::native_async::FutureSpawner;
-use crate::util::persist::{KVStore, MonitorName, MonitorUpdatingPersisterAsync};
+use crate::util::persist::{
+	KVStore, KVStoreSync, MonitorName, MonitorUpdatingPersisterAsync, UpdateName,
+	CHANNEL_MONITOR_UPDATE_JOURNAL_PRIMARY_NAMESPACE,
+};
 #[cfg(peer_storage)]
-use crate::util::ser::{VecWriter, Writeable};
+use crate::util::ser::{Readable, VecWriter, Writeable};
 use crate::util::wakers::{Future, Notifier};
 
 use alloc::sync::Arc;
+use core::cell::RefCell;
+use core::cmp;
 #[cfg(peer_storage)]
 use core::iter::Cycle;
 use core::ops::Deref;
-use core::sync::atomic::{AtomicUsize, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 
 /// `Persist` defines behavior for persisting channel monitors: this could mean
 /// writing once to disk, and/or uploading to one or more backup services.
@@ -109,6 +124,9 @@ use core::sync::atomic::{AtomicUsize, Ordering};
 ///  and [`ChainMonitor::get_monitor`] (note that if a full monitor is persisted all pending
 ///  monitor updates may be marked completed).
 ///
+///  Rather than implementing this directly, users of a native Rust async runtime may wish to
+///  implement [`AsyncPersist`] instead, which handles this for them.
+///
 /// # Using remote watchtowers
 ///
 /// Watchtowers may be updated as a part of an implementation of this trait, utilizing the async
@@ -185,6 +203,28 @@ pub trait Persist<ChannelSigner: EcdsaChannelSigner> {
 		&self, monitor_name: MonitorName, monitor_update: Option<&ChannelMonitorUpdate>,
 		monitor: &ChannelMonitor<ChannelSigner>,
 	) -> ChannelMonitorUpdateStatus;
+
+	/// Persist a channel's data after it was compacted via [`ChannelMonitor::compact`], e.g. by
+	/// [`ChainMonitor::compact_monitor`].
+	///
+	/// A compacted [`ChannelMonitor`] is smaller than the one previously persisted, but is
+	/// otherwise equivalent to it, having the same [`ChannelMonitor::get_latest_update_id`]. Thus,
+	/// the full [`ChannelMonitor`] must be persisted, overwriting the stored one, after which any
+	/// previously persisted [`ChannelMonitorUpdate`]s should be discarded, exactly as when
+	/// [`Self::update_persisted_channel`] is called with no [`ChannelMonitorUpdate`]. It is never
+	/// necessary to call [`ChainMonitor::channel_monitor_updated`] in response to this call.
+	///
+	/// Because losing a compacted write simply leaves the larger, uncompacted monitor on disk,
+	/// implementations may wish to schedule this write with a lower priority than other writes.
+	///
+	/// By default, this calls [`Self::update_persisted_channel`] with no
+	/// [`ChannelMonitorUpdate`].
+	fn persist_compacted_monitor(
+		&self, monitor_name: MonitorName, monitor: &ChannelMonitor<ChannelSigner>,
+	) -> ChannelMonitorUpdateStatus {
+		self.update_persisted_channel(monitor_name, None, monitor)
+	}
+
 	/// Prevents the channel monitor from being loaded on startup.
 	///
 	/// Archiving the data in a backup location (rather than deleting it fully) is useful for
@@ -210,6 +250,52 @@ pub trait Persist<ChannelSigner: EcdsaChannelSigner> {
 	}
 }
 
+/// An asynchronous version of [`Persist`], for use with [`ChainMonitor::new_with_async_persist`].
+///
+/// Rather than returning a [`ChannelMonitorUpdateStatus`], each method returns a future which
+/// completes once the data has been durably persisted. The [`ChainMonitor`] spawns each such
+/// future in the background, tracking it while it is in flight and marking the corresponding
+/// [`ChannelMonitorUpdate`] as complete once it resolves, i.e. it handles the
+/// [`ChannelMonitorUpdateStatus::InProgress`] and [`ChainMonitor::channel_monitor_updated`]
+/// dance on your behalf.
+///
+/// Note that these are *not* `async fn`s. Much like [`KVStore::write`], the order of writes for a
+/// given channel is defined by the order of the synchronous calls, which must be retained while
+/// persisting asynchronously. The [`ChannelMonitor`] and [`ChannelMonitorUpdate`] should thus be
+/// serialized before the future is returned.
+///
+/// Futures may resolve in any order, but for each channel updates are only marked complete in the
+/// order in which the persists were started. Thus, once a later update is reported as complete, all
+/// prior updates for the same channel are known to be durably persisted as well.
+///
+/// If a returned future resolves to an `Err`, persistence is considered to have failed and the
+/// corresponding channel will be unable to make progress until the node restarts, as no later
+/// update for it will be marked complete. Implementations should therefore retry transient
+/// failures internally before giving up.
+///
+/// See [`Persist`] for more information on the semantics of each method.
+///
+/// This is not exported to bindings users as async is only supported in Rust.
+pub trait AsyncPersist<ChannelSigner: EcdsaChannelSigner> {
+	/// Persist a new channel's data. See [`Persist::persist_new_channel`].
+	fn persist_new_channel(
+		&self, monitor_name: MonitorName, monitor: &ChannelMonitor<ChannelSigner>,
+	) -> impl core::future::Future<Output = Result<(), io::Error>> + 'static + MaybeSend;
+
+	/// Update one channel's data, or persist the full [`ChannelMonitor`] if no
+	/// [`ChannelMonitorUpdate`] is provided. See [`Persist::update_persisted_channel`].
+	fn update_persisted_channel(
+		&self, monitor_name: MonitorName, monitor_update: Option<&ChannelMonitorUpdate>,
+		monitor: &ChannelMonitor<ChannelSigner>,
+	) -> impl core::future::Future<Output = Result<(), io::Error>> + 'static + MaybeSend;
+
+	/// Prevents the channel monitor from being loaded on startup. See
+	/// [`Persist::archive_persisted_channel`].
+	fn archive_persisted_channel(
+		&self, monitor_name: MonitorName,
+	) -> impl core::future::Future<Output = ()> + 'static + MaybeSend;
+}
+
 struct MonitorHolder<ChannelSigner: EcdsaChannelSigner> {
 	monitor: ChannelMonitor<ChannelSigner>,
 	/// The full set of pending monitor updates for this Channel.
@@ -343,6 +429,171 @@ where
 	}
 }
 
+struct AsyncPersistState<L: Deref>
+where
+	L::Target: Logger,
+{
+	logger: L,
+	persist_queues: Mutex<AsyncPersistQueues>,
+	completed_updates: Mutex<Vec<(ChannelId, u64)>>,
+	in_flight_persists: AtomicUsize,
+	event_notifier: Arc<Notifier>,
+}
+
+/// A persist which has been spawned by an [`AsyncPersistAdapter`] but which may not yet have been
+/// reported as complete.
+struct QueuedAsyncPersist {
+	persist_id: u64,
+	/// The [`ChannelMonitor::get_latest_update_id`] at the time the persist was started.
+	update_id: u64,
+	resolved: bool,
+}
+
+/// Tracks the in-flight persists for each channel in the order in which they were started.
+///
+/// Because futures may resolve in any order, we only report a persist as complete once all
+/// persists for the same channel which were started before it have also completed. Otherwise, an
+/// older, still-pending write may land after a newer one, leaving a stale [`ChannelMonitor`] on
+/// disk while we believe the newer state is durable.
+struct AsyncPersistQueues {
+	next_persist_id: u64,
+	queues: HashMap<ChannelId, VecDeque<QueuedAsyncPersist>>,
+}
+
+/// An unconstructable [`Persist`]er which is used under the hood when you call
+/// [`ChainMonitor::new_with_async_persist`], adapting an [`AsyncPersist`] implementation.
+///
+/// This is not exported to bindings users as async is not supported outside of Rust.
+pub struct AsyncPersistAdapter<P: Deref, S: FutureSpawner, L: Deref>
+where
+	L::Target: Logger,
+{
+	persister: P,
+	future_spawner: S,
+	state: Arc<AsyncPersistState<L>>,
+}
+
+impl<P: Deref, S: FutureSpawner, L: Deref> Deref for AsyncPersistAdapter<P, S, L>
+where
+	L::Target: Logger,
+{
+	type Target = Self;
+	fn deref(&self) -> &Self {
+		self
+	}
+}
+
+impl<P: Deref, S: FutureSpawner, L: Deref + MaybeSend + MaybeSync + 'static>
+	AsyncPersistAdapter<P, S, L>
+where
+	L::Target: Logger,
+{
+	fn spawn_persist<Fut>(&self, future: Fut, channel_id: ChannelId, update_id: u64)
+	where
+		Fut: core::future::Future<Output = Result<(), io::Error>> + 'static + MaybeSend,
+	{
+		let state = Arc::clone(&self.state);
+		let persist_id = {
+			let mut persist_queues = state.persist_queues.lock().unwrap();
+			let persist_id = persist_queues.next_persist_id;
+			persist_queues.next_persist_id += 1;
+			let queued_persist = QueuedAsyncPersist { persist_id, update_id, resolved: false };
+			persist_queues.queues.entry(channel_id).or_default().push_back(queued_persist);
+			persist_id
+		};
+		state.in_flight_persists.fetch_add(1, Ordering::AcqRel);
+		self.future_spawner.spawn(async move {
+			let res = future.await;
+			state.in_flight_persists.fetch_sub(1, Ordering::AcqRel);
+			match res {
+				Ok(()) => state.persist_resolved(channel_id, persist_id),
+				Err(e) => {
+					log_error!(
+						state.logger,
+						"Failed to persist ChannelMonitor {channel_id}: {e}. The node will now likely stall as this channel will not be able to make progress. You should restart as soon as possible.",
+					);
+				},
+			}
+		});
+	}
+}
+
+impl<L: Deref> AsyncPersistState<L>
+where
+	L::Target: Logger,
+{
+	/// Marks the given persist as resolved, reporting it and any subsequent resolved persists for
+	/// the same channel as complete once all persists started before it have also resolved.
+	fn persist_resolved(&self, channel_id: ChannelId, persist_id: u64) {
+		let mut persist_queues = self.persist_queues.lock().unwrap();
+		let queue = match persist_queues.queues.get_mut(&channel_id) {
+			Some(queue) => queue,
+			None => {
+				debug_assert!(false, "Resolved persists must be queued");
+				return;
+			},
+		};
+		if let Some(queued) = queue.iter_mut().find(|queued| queued.persist_id == persist_id) {
+			queued.resolved = true;
+		}
+		let mut completed_updates = self.completed_updates.lock().unwrap();
+		let completed_count = completed_updates.len();
+		while queue.front().map(|queued| queued.resolved).unwrap_or(false) {
+			let queued = queue.pop_front().expect("We just checked the front is resolved");
+			completed_updates.push((channel_id, queued.update_id));
+		}
+		if queue.is_empty() {
+			persist_queues.queues.remove(&channel_id);
+		}
+		if completed_updates.len() != completed_count {
+			self.event_notifier.notify();
+		}
+	}
+}
+
+impl<
+		ChannelSigner: EcdsaChannelSigner,
+		P: Deref,
+		S: FutureSpawner,
+		L: Deref + MaybeSend + MaybeSync + 'static,
+	> Persist<ChannelSigner> for AsyncPersistAdapter<P, S, L>
+where
+	P::Target: AsyncPersist<ChannelSigner>,
+	L::Target: Logger,
+{
+	fn persist_new_channel(
+		&self, monitor_name: MonitorName, monitor: &ChannelMonitor<ChannelSigner>,
+	) -> ChannelMonitorUpdateStatus {
+		let future = self.persister.persist_new_channel(monitor_name, monitor);
+		self.spawn_persist(future, monitor.channel_id(), monitor.get_latest_update_id());
+		ChannelMonitorUpdateStatus::InProgress
+	}
+
+	fn update_persisted_channel(
+		&self, monitor_name: MonitorName, monitor_update: Option<&ChannelMonitorUpdate>,
+		monitor: &ChannelMonitor<ChannelSigner>,
+	) -> ChannelMonitorUpdateStatus {
+		let future = self.persister.update_persisted_channel(monitor_name, monitor_update, monitor);
+		// Note that we always track the monitor's latest update ID, even when persisting the full
+		// monitor, as ChainMonitor may be waiting on it if applying a ChannelMonitorUpdate failed.
+		self.spawn_persist(future, monitor.channel_id(), monitor.get_latest_update_id());
+		ChannelMonitorUpdateStatus::InProgress
+	}
+
+	fn archive_persisted_channel(&self, monitor_name: MonitorName) {
+		self.future_spawner.spawn(self.persister.archive_persisted_channel(monitor_name));
+	}
+
+	fn get_and_clear_completed_updates(&self) -> Vec<(ChannelId, u64)> {
+		core::mem::take(&mut *self.state.completed_updates.lock().unwrap())
+	}
+}
+
+/// The maximum number of [`WatchRegistration`]s which will be queued for
+/// [`ChainMonitor::get_and_clear_watch_registrations`] before the queue is dropped and a new
+/// [`WatchSnapshot`] has to be taken.
+pub const MAX_QUEUED_WATCH_REGISTRATIONS: usize = 4096;
+
 /// An implementation of [`chain::Watch`] for monitoring channels.
 ///
 /// Connected and disconnected blocks must be provided to `ChainMonitor` as documented by
@@ -395,6 +646,24 @@ pub struct ChainMonitor<
 	/// Messages to send to the peer. This is currently used to distribute PeerStorage to channel partners.
 	pending_send_only_events: Mutex<Vec<MessageSendEvent>>,
 
+	/// [`Event`]s generated by the `ChainMonitor` itself rather than by a [`ChannelMonitor`].
+	pending_events: Mutex<Vec<Event>>,
+	/// Whether the last [`Self::check_anchor_channel_reserves`] call found the reserves to be
+	/// insufficient, so that we only generate [`Event::InsufficientAnchorReserves`] once.
+	anchor_reserves_insufficient: AtomicBool,
+	/// Whether [`BumpTransactionEvent::HTLCResolution`]s from different channels should be merged,
+	/// see [`Self::set_aggregate_htlc_claims`].
+	aggregate_htlc_claims: AtomicBool,
+	/// The store every [`ChannelMonitorUpdate`] is written to before being applied, if enabled.
+	/// See [`Self::enable_update_journal`].
+	update_journal: RwLock<Option<Arc<dyn KVStoreSync + Send + Sync>>>,
+	/// The [`chain::Filter`] registrations made since the last call to [`Self::watch_snapshot`], if
+	/// it has been called and no more than [`MAX_QUEUED_WATCH_REGISTRATIONS`] have been queued
+	/// since. See [`Self::get_and_clear_watch_registrations`].
+	///
+	/// This is a leaf lock - no other locks may be taken while it is held.
+	watch_registrations: Mutex<Option<Vec<WatchRegistration>>>,
+
 	#[cfg(peer_storage)]
 	our_peerstorage_encryption_key: PeerStorageKey,
 }
@@ -417,7 +686,8 @@ impl<
 		L,
 		AsyncPersister<K, S, L, ES, SP, T, F>,
 		ES,
-	> where
+	>
+where
 	K::Target: KVStore + MaybeSync,
 	SP::Target: SignerProvider + Sized,
 	C::Target: chain::Filter,
@@ -453,10 +723,88 @@ impl<
 			event_notifier: Arc::clone(&event_notifier),
 			persister: AsyncPersister { persister, event_notifier },
 			pending_send_only_events: Mutex::new(Vec::new()),
+			pending_events: Mutex::new(Vec::new()),
+			anchor_reserves_insufficient: AtomicBool::new(false),
+			aggregate_htlc_claims: AtomicBool::new(false),
+			update_journal: RwLock::new(None),
+			watch_registrations: Mutex::new(None),
+			#[cfg(peer_storage)]
+			our_peerstorage_encryption_key: _our_peerstorage_encryption_key,
+		}
+	}
+}
+
+impl<
+		ChannelSigner: EcdsaChannelSigner,
+		C: Deref,
+		T: Deref,
+		F: Deref,
+		L: Deref + Clone + MaybeSend + MaybeSync + 'static,
+		AP: Deref,
+		S: FutureSpawner,
+		ES: Deref,
+	> ChainMonitor<ChannelSigner, C, T, F, L, AsyncPersistAdapter<AP, S, L>, ES>
+where
+	C::Target: chain::Filter,
+	T::Target: BroadcasterInterface,
+	F::Target: FeeEstimator,
+	L::Target: Logger,
+	AP::Target: AsyncPersist<ChannelSigner>,
+	ES::Target: EntropySource,
+{
+	/// Creates a new `ChainMonitor` used to watch on-chain activity pertaining to channels.
+	///
+	/// This behaves the same as [`ChainMonitor::new`] except that it persists via an
+	/// [`AsyncPersist`] implementation, spawning each persistence future using `future_spawner`
+	/// and marking the corresponding [`ChannelMonitorUpdate`]s as complete once they resolve.
+	///
+	/// Note that async monitor updating is considered beta, and bugs may be triggered by its use.
+	///
+	/// This is not exported to bindings users as async is not supported outside of Rust.
+	pub fn new_with_async_persist(
+		chain_source: Option<C>, broadcaster: T, logger: L, feeest: F, persister: AP,
+		future_spawner: S, _entropy_source: ES, _our_peerstorage_encryption_key: PeerStorageKey,
+	) -> Self {
+		let event_notifier = Arc::new(Notifier::new());
+		let state = Arc::new(AsyncPersistState {
+			logger: logger.clone(),
+			persist_queues: Mutex::new(AsyncPersistQueues {
+				next_persist_id: 0,
+				queues: new_hash_map(),
+			}),
+			completed_updates: Mutex::new(Vec::new()),
+			in_flight_persists: AtomicUsize::new(0),
+			event_notifier: Arc::clone(&event_notifier),
+		});
+		Self {
+			monitors: RwLock::new(new_hash_map()),
+			chain_source,
+			broadcaster,
+			logger,
+			fee_estimator: feeest,
+			_entropy_source,
+			pending_monitor_events: Mutex::new(Vec::new()),
+			highest_chain_height: AtomicUsize::new(0),
+			event_notifier,
+			persister: AsyncPersistAdapter { persister, future_spawner, state },
+			pending_send_only_events: Mutex::new(Vec::new()),
+			pending_events: Mutex::new(Vec::new()),
+			anchor_reserves_insufficient: AtomicBool::new(false),
+			aggregate_htlc_claims: AtomicBool::new(false),
+			update_journal: RwLock::new(None),
+			watch_registrations: Mutex::new(None),
 			#[cfg(peer_storage)]
 			our_peerstorage_encryption_key: _our_peerstorage_encryption_key,
 		}
 	}
+
+	/// Returns the number of [`AsyncPersist`] futures which have been spawned but have not yet
+	/// completed.
+	///
+	/// This may be useful to wait for all in-flight persistence to complete before shutting down.
+	pub fn in_flight_async_persists(&self) -> usize {
+		self.persister.state.in_flight_persists.load(Ordering::Acquire)
+	}
 }
 
 impl<
@@ -556,11 +904,78 @@ where
 	where
 		FN: Fn(&ChannelMonitor<ChannelSigner>, &TransactionData) -> Vec<TransactionOutputs>,
 	{
-		let monitor = &monitor_state.monitor;
-		let logger = WithChannelMonitor::from(&self.logger, &monitor, None);
+		let txn_outputs = process(&monitor_state.monitor, txdata);
 
-		let mut txn_outputs = process(monitor, txdata);
+		if self.should_sync_monitor(channel_id, monitor_state, best_height, channel_count) {
+			self.sync_monitor_for_chain_data(monitor_state)?;
+		}
+
+		self.register_txn_outputs(header, &monitor_state.monitor, txn_outputs);
+		Ok(())
+	}
 
+	/// Like [`Self::process_chain_data`] for a batch of connected blocks, but only taking the
+	/// monitors lock once and persisting each [`ChannelMonitor`] at most once for the whole batch.
+	fn process_blocks_connected(&self, blocks: &[(&Header, &TransactionData, u32)]) {
+		let err_str = "ChannelMonitor[Update] persistence failed unrecoverably. This indicates we cannot continue normal operation and must shut down.";
+		let last_height = match blocks.last() {
+			Some((_, _, height)) => *height,
+			None => return,
+		};
+
+		// As we hold the monitors lock for the whole batch, no monitors can be added while we're
+		// processing it, so unlike `process_chain_data` we don't need a second pass.
+		let monitor_states = self.monitors.read().unwrap();
+		let channel_count = monitor_states.len();
+		let mut sync_failed = false;
+		for (channel_id, monitor_state) in monitor_states.iter() {
+			let monitor = &monitor_state.monitor;
+			let mut needs_sync = false;
+			for (header, txdata, height) in blocks.iter() {
+				let txn_outputs = monitor.block_connected(
+					header,
+					txdata,
+					*height,
+					&*self.broadcaster,
+					&*self.fee_estimator,
+					&self.logger,
+				);
+				needs_sync |= self.should_sync_monitor(
+					channel_id,
+					monitor_state,
+					Some(*height),
+					channel_count,
+				);
+				self.register_txn_outputs(header, monitor, txn_outputs);
+			}
+			if needs_sync && self.sync_monitor_for_chain_data(monitor_state).is_err() {
+				sync_failed = true;
+				break;
+			}
+		}
+		if sync_failed {
+			// Take the monitors lock for writing so that we poison it and any future operations
+			// going forward fail immediately.
+			core::mem::drop(monitor_states);
+			let _poison = self.monitors.write().unwrap();
+			log_error!(self.logger, "{}", err_str);
+			panic!("{}", err_str);
+		}
+
+		let old_height = self.highest_chain_height.load(Ordering::Acquire);
+		let new_height = last_height as usize;
+		if new_height > old_height {
+			self.highest_chain_height.store(new_height, Ordering::Release);
+		}
+	}
+
+	/// Returns whether a [`ChannelMonitor`] should be persisted after processing chain data at
+	/// `best_height`. To spread out the persistence load, monitors without pending claims are
+	/// only persisted for a subset of blocks.
+	fn should_sync_monitor(
+		&self, channel_id: &ChannelId, monitor_state: &MonitorHolder<ChannelSigner>,
+		best_height: Option<u32>, channel_count: usize,
+	) -> bool {
 		let get_partition_key = |channel_id: &ChannelId| {
 			let channel_id_bytes = channel_id.0;
 			let channel_id_u32 = u32::from_be_bytes([
@@ -579,43 +994,57 @@ where
 		};
 
 		let has_pending_claims = monitor_state.monitor.has_pending_claims();
-		if has_pending_claims || get_partition_key(channel_id) % partition_factor == 0 {
-			log_trace!(logger, "Syncing Channel Monitor");
-			// Even though we don't track monitor updates from chain-sync as pending, we still want
-			// updates per-channel to be well-ordered so that users don't see a
-			// `ChannelMonitorUpdate` after a channel persist for a channel with the same
-			// `latest_update_id`.
-			let _pending_monitor_updates = monitor_state.pending_monitor_updates.lock().unwrap();
-			match self.persister.update_persisted_channel(monitor.persistence_key(), None, monitor)
-			{
-				ChannelMonitorUpdateStatus::Completed => {
-					log_trace!(logger, "Finished syncing Channel Monitor for block-data")
-				},
-				ChannelMonitorUpdateStatus::InProgress => {
-					log_trace!(
-						logger,
-						"Channel Monitor sync for channel {} in progress.",
-						log_funding_info!(monitor)
-					);
-				},
-				ChannelMonitorUpdateStatus::UnrecoverableError => {
-					return Err(());
-				},
-			}
+		has_pending_claims || get_partition_key(channel_id) % partition_factor == 0
+	}
+
+	fn sync_monitor_for_chain_data(
+		&self, monitor_state: &MonitorHolder<ChannelSigner>,
+	) -> Result<(), ()> {
+		let monitor = &monitor_state.monitor;
+		let logger = WithChannelMonitor::from(&self.logger, &monitor, None);
+		log_trace!(logger, "Syncing Channel Monitor");
+		// Even though we don't track monitor updates from chain-sync as pending, we still want
+		// updates per-channel to be well-ordered so that users don't see a
+		// `ChannelMonitorUpdate` after a channel persist for a channel with the same
+		// `latest_update_id`.
+		let _pending_monitor_updates = monitor_state.pending_monitor_updates.lock().unwrap();
+		match self.persister.update_persisted_channel(monitor.persistence_key(), None, monitor) {
+			ChannelMonitorUpdateStatus::Completed => {
+				log_trace!(logger, "Finished syncing Channel Monitor for block-data");
+				self.maybe_prune_update_journal(monitor);
+			},
+			ChannelMonitorUpdateStatus::InProgress => {
+				log_trace!(
+					logger,
+					"Channel Monitor sync for channel {} in progress.",
+					log_funding_info!(monitor)
+				);
+			},
+			ChannelMonitorUpdateStatus::UnrecoverableError => {
+				return Err(());
+			},
 		}
+		Ok(())
+	}
 
-		// Register any new outputs with the chain source for filtering, storing any dependent
-		// transactions from within the block that previously had not been included in txdata.
-		if let Some(ref chain_source) = self.chain_source {
-			let block_hash = header.block_hash();
-			for (txid, mut outputs) in txn_outputs.drain(..) {
-				for (idx, output) in outputs.drain(..) {
-					// Register any new outputs with the chain source for filtering
-					let output = WatchedOutput {
-						block_hash: Some(block_hash),
-						outpoint: OutPoint { txid, index: idx as u16 },
-						script_pubkey: output.script_pubkey,
-					};
+	/// Registers any new outputs with the chain source for filtering, storing any dependent
+	/// transactions from within the block that previously had not been included in txdata.
+	fn register_txn_outputs(
+		&self, header: &Header, monitor: &ChannelMonitor<ChannelSigner>,
+		mut txn_outputs: Vec<TransactionOutputs>,
+	) {
+		let logger = WithChannelMonitor::from(&self.logger, &monitor, None);
+		let block_hash = header.block_hash();
+		for (txid, mut outputs) in txn_outputs.drain(..) {
+			for (idx, output) in outputs.drain(..) {
+				// Register any new outputs with the chain source for filtering
+				let output = WatchedOutput {
+					block_hash: Some(block_hash),
+					outpoint: OutPoint { txid, index: idx as u16 },
+					script_pubkey: output.script_pubkey,
+				};
+				self.record_watch_registration(|| WatchRegistration::Output(output.clone()));
+				if let Some(ref chain_source) = self.chain_source {
 					log_trace!(
 						logger,
 						"Adding monitoring for spends of outpoint {} to the filter",
@@ -625,7 +1054,37 @@ where
 				}
 			}
 		}
-		Ok(())
+	}
+
+	/// Notes a [`chain::Filter`] registration for [`Self::get_and_clear_watch_registrations`] if a
+	/// [`WatchSnapshot`] has been taken.
+	///
+	/// If [`MAX_QUEUED_WATCH_REGISTRATIONS`] are already queued, the queue is dropped instead.
+	fn record_watch_registration<R: FnOnce() -> WatchRegistration>(&self, registration: R) {
+		let mut registrations = self.watch_registrations.lock().unwrap();
+		if let Some(queue) = registrations.as_mut() {
+			if queue.len() >= MAX_QUEUED_WATCH_REGISTRATIONS {
+				*registrations = None;
+			} else {
+				queue.push(registration());
+			}
+		}
+	}
+
+	/// Notes the [`chain::Filter`] registrations made for a newly loaded [`ChannelMonitor`] for
+	/// [`Self::get_and_clear_watch_registrations`] if a [`WatchSnapshot`] has been taken.
+	fn record_monitor_watch_registrations(&self, monitor: &ChannelMonitor<ChannelSigner>) {
+		if self.watch_registrations.lock().unwrap().is_none() {
+			return;
+		}
+		let watch_items = monitor.get_watch_items();
+		for (txid, script_pubkey) in watch_items.transactions {
+			let registration = WatchRegistration::Transaction { txid, script_pubkey };
+			self.record_watch_registration(|| registration);
+		}
+		for output in watch_items.outputs {
+			self.record_watch_registration(|| WatchRegistration::Output(output));
+		}
 	}
 
 	/// Creates a new `ChainMonitor` used to watch on-chain activity pertaining to channels.
@@ -663,6 +1122,11 @@ where
 			highest_chain_height: AtomicUsize::new(0),
 			event_notifier: Arc::new(Notifier::new()),
 			pending_send_only_events: Mutex::new(Vec::new()),
+			pending_events: Mutex::new(Vec::new()),
+			anchor_reserves_insufficient: AtomicBool::new(false),
+			aggregate_htlc_claims: AtomicBool::new(false),
+			update_journal: RwLock::new(None),
+			watch_registrations: Mutex::new(None),
 			#[cfg(peer_storage)]
 			our_peerstorage_encryption_key: _our_peerstorage_encryption_key,
 		}
@@ -692,6 +1156,55 @@ where
 		ret
 	}
 
+	/// Checks whether `utxos`, the UTXOs available in the wallet used to fee-bump anchor channel
+	/// transactions, can cover the reserves required by all anchor channels we're monitoring,
+	/// returning `true` if so.
+	///
+	/// The reserve for each channel is calculated with [`get_reserve_for_channel`], taking the
+	/// HTLCs currently pending in the channel into account. If the reserves are insufficient, an
+	/// [`Event::InsufficientAnchorReserves`] will be generated, unless one was already generated
+	/// by a prior call without the reserves having since been replenished.
+	///
+	/// This should be called whenever the wallet's UTXO set changes, as well as periodically as
+	/// pending HTLCs change, e.g. alongside [`Self::rebroadcast_pending_claims`].
+	///
+	/// [`get_reserve_for_channel`]: crate::util::anchor_channel_reserves::get_reserve_for_channel
+	pub fn check_anchor_channel_reserves(
+		&self, context: &AnchorChannelReserveContext, utxos: &[Utxo],
+	) -> bool {
+		let mut required_reserve = Amount::ZERO;
+		let mut num_anchor_channels = 0;
+		for monitor_state in self.monitors.read().unwrap().values() {
+			let reserve = get_reserve_for_channel(context, &monitor_state.monitor);
+			if reserve > Amount::ZERO {
+				required_reserve = required_reserve.checked_add(reserve).unwrap_or(Amount::MAX);
+				num_anchor_channels += 1;
+			}
+		}
+		let available_reserve = get_available_reserve(context, utxos);
+		if available_reserve >= required_reserve {
+			self.anchor_reserves_insufficient.store(false, Ordering::Release);
+			return true;
+		}
+
+		if !self.anchor_reserves_insufficient.swap(true, Ordering::AcqRel) {
+			log_warn!(
+				self.logger,
+				"Anchor channel reserves are insufficient: {} required across {} channels, {} available",
+				required_reserve,
+				num_anchor_channels,
+				available_reserve
+			);
+			self.pending_events.lock().unwrap().push(Event::InsufficientAnchorReserves {
+				required_reserve_satoshis: required_reserve.to_sat(),
+				available_reserve_satoshis: available_reserve.to_sat(),
+				num_anchor_channels,
+			});
+			self.event_notifier.notify();
+		}
+		false
+	}
+
 	/// Gets the [`LockedChannelMonitor`] for a given funding outpoint, returning an `Err` if no
 	/// such [`ChannelMonitor`] is currently being monitored for.
 	///
@@ -708,6 +1221,202 @@ where
 		}
 	}
 
+	/// Compacts the [`ChannelMonitor`] for the given channel via [`ChannelMonitor::compact`],
+	/// retaining the HTLC data for the `retained_revoked_states` most recently revoked
+	/// counterparty commitment transactions, and re-persists it via
+	/// [`Persist::persist_compacted_monitor`] if anything was dropped.
+	///
+	/// Returns the number of revoked states which were dropped, or an `Err` if no such
+	/// [`ChannelMonitor`] is currently being monitored for or if the persister returned
+	/// [`ChannelMonitorUpdateStatus::UnrecoverableError`].
+	///
+	/// This may be called periodically for long-lived channels to bound the size of their
+	/// [`ChannelMonitor`]s.
+	pub fn compact_monitor(
+		&self, channel_id: ChannelId, retained_revoked_states: u64,
+	) -> Result<usize, ()> {
+		let monitors = self.monitors.read().unwrap();
+		let monitor_state = monitors.get(&channel_id).ok_or(())?;
+		let monitor = &monitor_state.monitor;
+		let logger = WithChannelMonitor::from(&self.logger, monitor, None);
+
+		// As with chain-sync persistence, hold the pending updates lock so that the full monitor
+		// write is well-ordered with respect to any `ChannelMonitorUpdate`s for this channel.
+		let _pending_monitor_updates = monitor_state.pending_monitor_updates.lock().unwrap();
+		let removed_states = monitor.compact(retained_revoked_states);
+		if removed_states == 0 {
+			return Ok(0);
+		}
+		log_debug!(logger, "Compacted {} revoked states from ChannelMonitor", removed_states);
+		match self.persister.persist_compacted_monitor(monitor.persistence_key(), monitor) {
+			ChannelMonitorUpdateStatus::Completed | ChannelMonitorUpdateStatus::InProgress => {
+				Ok(removed_states)
+			},
+			ChannelMonitorUpdateStatus::UnrecoverableError => Err(()),
+		}
+	}
+
+	/// Enables the write-ahead update journal, writing every [`ChannelMonitorUpdate`] passed to
+	/// [`chain::Watch::update_channel`] to `journal` before it is applied or handed to the
+	/// [`Persist`]er.
+	///
+	/// Updates are written under [`CHANNEL_MONITOR_UPDATE_JOURNAL_PRIMARY_NAMESPACE`] with the
+	/// [`MonitorName`] as the secondary namespace and the [`UpdateName`] as the key, so writing
+	/// the same update more than once is idempotent. With the journal enabled, a [`Persist`]
+	/// implementation may return [`ChannelMonitorUpdateStatus::Completed`] for a
+	/// [`ChannelMonitorUpdate`] without re-persisting the full [`ChannelMonitor`], which avoids
+	/// rewriting the full monitor on every update with backends such as object stores. The full
+	/// [`ChannelMonitor`] must still be persisted whenever no update is provided.
+	///
+	/// On startup, [`Self::replay_pending_updates`] must be called after loading all
+	/// [`ChannelMonitor`]s (e.g. via [`Self::load_existing_monitor`]) and before deserializing the
+	/// [`ChannelManager`] to bring the monitors up to date.
+	///
+	/// Journal entries are removed once a full [`ChannelMonitor`] including them has been
+	/// persisted.
+	///
+	/// [`ChannelManager`]: crate::ln::channelmanager::ChannelManager
+	pub fn enable_update_journal(&self, journal: Arc<dyn KVStoreSync + Send + Sync>) {
+		*self.update_journal.write().unwrap() = Some(journal);
+	}
+
+	/// Applies any [`ChannelMonitorUpdate`]s in the update journal which are newer than the
+	/// corresponding loaded [`ChannelMonitor`], returning the number of updates applied.
+	///
+	/// Each [`ChannelMonitor`] to which updates were applied is then persisted in full via
+	/// [`Persist::update_persisted_channel`], after which its journal entries are removed.
+	///
+	/// Does nothing if the update journal has not been enabled via
+	/// [`Self::enable_update_journal`].
+	pub fn replay_pending_updates(&self) -> Result<usize, io::Error> {
+		let journal = match self.update_journal.read().unwrap().clone() {
+			Some(journal) => journal,
+			None => return Ok(0),
+		};
+		let primary = CHANNEL_MONITOR_UPDATE_JOURNAL_PRIMARY_NAMESPACE;
+		let mut replayed_updates = 0;
+		for monitor_state in self.monitors.read().unwrap().values() {
+			let monitor = &monitor_state.monitor;
+			let logger = WithChannelMonitor::from(&self.logger, monitor, None);
+			let monitor_key = monitor.persistence_key().to_string();
+			let mut update_ids = journal
+				.list(primary, &monitor_key)?
+				.into_iter()
+				.map(|key| UpdateName::new(key).map(|update_name| update_name.0))
+				.collect::<Result<Vec<_>, _>>()?;
+			update_ids.sort_unstable();
+
+			let _pending_monitor_updates = monitor_state.pending_monitor_updates.lock().unwrap();
+			let mut applied_updates = false;
+			for update_id in update_ids {
+				if update_id <= monitor.get_latest_update_id() {
+					continue;
+				}
+				if update_id != monitor.get_latest_update_id() + 1 {
+					// There's a gap in the journal, so any later updates cannot be applied.
+					log_error!(
+						logger,
+						"Missing journaled ChannelMonitorUpdate before id {}",
+						update_id
+					);
+					break;
+				}
+				let update_name = UpdateName::from(update_id);
+				let update_bytes = journal.read(primary, &monitor_key, update_name.as_str())?;
+				let update = ChannelMonitorUpdate::read(&mut &update_bytes[..]).map_err(|e| {
+					log_error!(
+						logger,
+						"Failed to read journaled ChannelMonitorUpdate {}, reason: {}",
+						update_id,
+						e
+					);
+					io::Error::new(
+						io::ErrorKind::InvalidData,
+						"Failed to read ChannelMonitorUpdate",
+					)
+				})?;
+				log_info!(logger, "Replaying journaled ChannelMonitorUpdate id {}", update_id);
+				monitor
+					.update_monitor(&update, &self.broadcaster, &self.fee_estimator, &self.logger)
+					.map_err(|()| {
+						log_error!(
+							logger,
+							"Failed to replay ChannelMonitorUpdate id {}",
+							update_id
+						);
+						io::Error::new(io::ErrorKind::Other, "Monitor update failed")
+					})?;
+				replayed_updates += 1;
+				applied_updates = true;
+			}
+
+			if applied_updates {
+				let persist_res = self.persister.update_persisted_channel(
+					monitor.persistence_key(),
+					None,
+					monitor,
+				);
+				match persist_res {
+					ChannelMonitorUpdateStatus::Completed => {},
+					ChannelMonitorUpdateStatus::InProgress => {
+						// We can't remove the journal entries until the persist completes, so leave
+						// them to be pruned on a later full persist.
+						continue;
+					},
+					ChannelMonitorUpdateStatus::UnrecoverableError => {
+						return Err(io::Error::new(
+							io::ErrorKind::Other,
+							"Failed to persist replayed ChannelMonitor",
+						));
+					},
+				}
+			}
+			self.prune_update_journal(&*journal, monitor)?;
+		}
+		Ok(replayed_updates)
+	}
+
+	/// Writes the given update to the update journal, if enabled.
+	fn write_to_update_journal(
+		&self, monitor: &ChannelMonitor<ChannelSigner>, update: &ChannelMonitorUpdate,
+	) -> Result<(), io::Error> {
+		if let Some(journal) = &*self.update_journal.read().unwrap() {
+			let monitor_key = monitor.persistence_key().to_string();
+			let update_name = UpdateName::from(update.update_id);
+			let primary = CHANNEL_MONITOR_UPDATE_JOURNAL_PRIMARY_NAMESPACE;
+			journal.write(primary, &monitor_key, update_name.as_str(), update.encode())?;
+		}
+		Ok(())
+	}
+
+	/// Removes any update journal entries which are included in the given monitor's current state.
+	///
+	/// Must only be called once the current state of the monitor has been fully persisted.
+	fn prune_update_journal(
+		&self, journal: &dyn KVStoreSync, monitor: &ChannelMonitor<ChannelSigner>,
+	) -> Result<(), io::Error> {
+		let primary = CHANNEL_MONITOR_UPDATE_JOURNAL_PRIMARY_NAMESPACE;
+		let monitor_key = monitor.persistence_key().to_string();
+		for key in journal.list(primary, &monitor_key)? {
+			let update_name = UpdateName::new(key)?;
+			if update_name.0 <= monitor.get_latest_update_id() {
+				journal.remove(primary, &monitor_key, update_name.as_str(), true)?;
+			}
+		}
+		Ok(())
+	}
+
+	/// Prunes the update journal for the given monitor after a full persist completed, if the
+	/// journal is enabled. Failures are only logged as the entries will be pruned later.
+	fn maybe_prune_update_journal(&self, monitor: &ChannelMonitor<ChannelSigner>) {
+		if let Some(journal) = &*self.update_journal.read().unwrap() {
+			if let Err(e) = self.prune_update_journal(&**journal, monitor) {
+				let logger = WithChannelMonitor::from(&self.logger, monitor, None);
+				log_warn!(logger, "Failed to prune ChannelMonitorUpdate journal: {}", e);
+			}
+		}
+	}
+
 	/// Lists the funding outpoint and channel ID of each [`ChannelMonitor`] being monitored.
 	///
 	/// Note that [`ChannelMonitor`]s are not removed when a channel is closed as they are always
@@ -716,6 +1425,72 @@ where
 		self.monitors.read().unwrap().keys().copied().collect()
 	}
 
+	/// Lists the transactions and outputs each [`ChannelMonitor`] needs the chain source to watch.
+	///
+	/// This returns the same data as has been provided to the [`chain::Filter`] passed to
+	/// [`ChainMonitor::new`] across all monitors, allowing chain sources which lose their
+	/// registrations (e.g. on reconnection to an Electrum server) to re-subscribe in bulk. See
+	/// [`ChannelMonitor::get_watch_items`] for more details.
+	pub fn list_watch_items(&self) -> Vec<ChannelWatchItems> {
+		let monitors = self.monitors.read().unwrap();
+		monitors.values().map(|holder| holder.monitor.get_watch_items()).collect()
+	}
+
+	/// Takes a [`WatchSnapshot`] of everything the chain source needs to watch across all
+	/// [`ChannelMonitor`]s, for re-seeding a chain source after it was restarted or failed over to
+	/// another backend.
+	///
+	/// The snapshot contains the per-channel [`Self::list_watch_items`], with hints of the blocks
+	/// in which watched outputs were created where known, as well as the transactions which must
+	/// be monitored for reorganizations, allowing the chain source to limit its rescan.
+	///
+	/// Once a snapshot has been taken, further [`chain::Filter`] registrations are also queued to
+	/// be returned by [`Self::get_and_clear_watch_registrations`], so that nothing is missed
+	/// between re-seeding the chain source and it catching up. Taking another snapshot clears the
+	/// queue.
+	pub fn watch_snapshot(&self) -> WatchSnapshot {
+		// Start queueing registrations before taking the snapshot, so that those made while we
+		// take it are at worst duplicated rather than missed.
+		*self.watch_registrations.lock().unwrap() = Some(Vec::new());
+
+		let mut channels = self.list_watch_items();
+
+		let monitors = self.monitors.read().unwrap();
+		let best_block = monitors
+			.values()
+			.map(|holder| holder.monitor.current_best_block())
+			.min_by_key(|best_block| best_block.height);
+
+		let mut relevant_txids = Vec::new();
+		for holder in monitors.values() {
+			relevant_txids.append(&mut holder.monitor.get_relevant_txids());
+		}
+		relevant_txids.sort_unstable_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)));
+		relevant_txids.dedup_by_key(|(txid, _, _)| *txid);
+
+		for output in channels.iter_mut().flat_map(|channel| channel.outputs.iter_mut()) {
+			let txid = output.outpoint.txid;
+			if let Ok(idx) = relevant_txids.binary_search_by_key(&txid, |(txid, _, _)| *txid) {
+				output.block_hash = relevant_txids[idx].2;
+			}
+		}
+
+		WatchSnapshot { best_block, relevant_txids, channels }
+	}
+
+	/// Returns the [`chain::Filter`] registrations made since the last call to this method or
+	/// [`Self::watch_snapshot`], whichever happened last.
+	///
+	/// Registrations are queued whether or not a [`chain::Filter`] was provided to the
+	/// `ChainMonitor`, and may duplicate items already included in the last [`WatchSnapshot`].
+	///
+	/// Returns `None` if [`Self::watch_snapshot`] has never been called or if more than
+	/// [`MAX_QUEUED_WATCH_REGISTRATIONS`] registrations were made without being fetched, in which
+	/// case registrations may have been missed and a new [`WatchSnapshot`] must be taken.
+	pub fn get_and_clear_watch_registrations(&self) -> Option<Vec<WatchRegistration>> {
+		self.watch_registrations.lock().unwrap().as_mut().map(core::mem::take)
+	}
+
 	#[cfg(not(c_bindings))]
 	/// Lists the pending updates for each [`ChannelMonitor`] (by `ChannelId` being monitored).
 	/// Each `Vec<u64>` contains `update_id`s from [`ChannelMonitor::get_latest_update_id`] for updates
@@ -855,8 +1630,22 @@ where
 	>(
 		&self, handler: H,
 	) {
+		let pending_events = core::mem::take(&mut *self.pending_events.lock().unwrap());
+		let mut pending_events_iter = pending_events.into_iter();
+		while let Some(event) = pending_events_iter.next() {
+			if handler(event.clone()).await.is_err() {
+				let mut events = self.pending_events.lock().unwrap();
+				let remaining = core::iter::once(event).chain(pending_events_iter);
+				events.splice(0..0, remaining);
+				self.event_notifier.notify();
+				break;
+			}
+		}
+
 		// Sadly we can't hold the monitors read lock through an async call. Thus we have to do a
 		// crazy dance to process a monitor's events then only remove them once we've done so.
+		let aggregate_htlc_claims = self.aggregate_htlc_claims.load(Ordering::Acquire);
+		let mut htlc_resolutions = Vec::new();
 		let mons_to_process = self.monitors.read().unwrap().keys().cloned().collect::<Vec<_>>();
 		for channel_id in mons_to_process {
 			let mut ev;
@@ -864,7 +1653,15 @@ where
 				self.monitors.read().unwrap().get(&channel_id).map(|m| &m.monitor),
 				self.logger,
 				ev,
-				handler(ev).await
+				match ev {
+					Event::BumpTransaction(
+						bump_event @ BumpTransactionEvent::HTLCResolution { .. },
+					) if aggregate_htlc_claims => {
+						htlc_resolutions.push(bump_event);
+						Ok(())
+					},
+					ev => handler(ev).await,
+				}
 			) {
 				Ok(()) => {},
 				Err(ReplayEvent()) => {
@@ -872,6 +1669,33 @@ where
 				},
 			}
 		}
+
+		for htlc_resolutions in group_htlc_resolutions(htlc_resolutions) {
+			if htlc_resolutions.len() > 1 {
+				let merged = merge_htlc_resolutions(&htlc_resolutions);
+				let claim_id = merged.claim_id();
+				if handler(Event::BumpTransaction(merged)).await.is_ok() {
+					continue;
+				}
+				// Fall back to claiming each channel's HTLCs on their own, so that failing to claim
+				// the merged HTLCs doesn't hold back the claims of every channel involved.
+				log_warn!(
+					self.logger,
+					"Failed to handle merged HTLC claim {}, retrying per channel",
+					log_bytes!(claim_id.0)
+				);
+			}
+			for bump_event in htlc_resolutions {
+				let claim_id = bump_event.claim_id();
+				if handler(Event::BumpTransaction(bump_event)).await.is_err() {
+					log_error!(
+						self.logger,
+						"Failed to handle HTLC claim {}, awaiting its regeneration by the ChannelMonitor",
+						log_bytes!(claim_id.0)
+					);
+				}
+			}
+		}
 	}
 
 	/// Gets a [`Future`] that completes when an event is available either via
@@ -886,6 +1710,33 @@ where
 		self.event_notifier.get_future()
 	}
 
+	/// Sets whether HTLC claims from force-closed anchor channels should be aggregated across
+	/// channels. Defaults to `false`.
+	///
+	/// Each [`ChannelMonitor`] generates its own [`BumpTransactionEvent::HTLCResolution`]s for the
+	/// HTLCs it needs to claim. When enabled, those events from all channels which can be claimed
+	/// in the same transaction, i.e. channels with the same commitment format whose claims require
+	/// the same locktime, are merged into a single event before being handed to the event handler.
+	/// This allows the [`BumpTransactionEventHandler`] to claim them with a single transaction and
+	/// set of wallet inputs, substantially reducing on-chain fees when closing many channels at
+	/// once.
+	///
+	/// A merged event targets the highest feerate of the events it was built from, has a
+	/// [`ClaimId`] derived from theirs, and carries the `channel_id` and `counterparty_node_id` of
+	/// the one with the lowest [`ClaimId`]. Each HTLC's channel can be identified through
+	/// [`HTLCDescriptor::channel_derivation_parameters`]. If the event handler fails to handle a
+	/// merged event, each of the events it was built from is handed to it on its own instead.
+	///
+	/// Claims which are signed and broadcast by the [`ChannelMonitor`]s themselves, such as
+	/// justice transactions or HTLC claims on non-anchor channels, are not affected as they can
+	/// only be signed for by each channel's own signer.
+	///
+	/// [`BumpTransactionEventHandler`]: crate::events::bump_transaction::BumpTransactionEventHandler
+	/// [`HTLCDescriptor::channel_derivation_parameters`]: crate::sign::HTLCDescriptor::channel_derivation_parameters
+	pub fn set_aggregate_htlc_claims(&self, aggregate_htlc_claims: bool) {
+		self.aggregate_htlc_claims.store(aggregate_htlc_claims, Ordering::Release);
+	}
+
 	/// Triggers rebroadcasts/fee-bumps of pending claims from a force-closed channel. This is
 	/// crucial in preventing certain classes of pinning attacks, detecting substantial mempool
 	/// feerate changes between blocks, and ensuring reliability if broadcasting fails. We recommend
@@ -963,6 +1814,7 @@ where
 					log_info!(logger, "Archiving fully resolved ChannelMonitor");
 					self.persister
 						.archive_persisted_channel(monitor_holder.monitor.persistence_key());
+					self.maybe_prune_update_journal(&monitor_holder.monitor);
 					false
 				} else {
 					true
@@ -1100,6 +1952,7 @@ where
 		if let Some(ref chain_source) = self.chain_source {
 			monitor.load_outputs_to_watch(chain_source, &self.logger);
 		}
+		self.record_monitor_watch_registrations(&monitor);
 		entry.insert(MonitorHolder { monitor, pending_monitor_updates: Mutex::new(Vec::new()) });
 
 		Ok(ChannelMonitorUpdateStatus::Completed)
@@ -1209,6 +2062,30 @@ where
 		self.event_notifier.notify();
 	}
 
+	fn filtered_blocks_connected(&self, blocks: &[(&Header, &TransactionData, u32)]) {
+		let (header, height) = match blocks.last() {
+			Some((header, _, height)) => (*header, *height),
+			None => return,
+		};
+		log_debug!(
+			self.logger,
+			"{} new blocks up to best block {} at height {} provided via filtered_blocks_connected",
+			blocks.len(),
+			header.block_hash(),
+			height
+		);
+		self.process_blocks_connected(blocks);
+
+		#[cfg(peer_storage)]
+		// Send peer storage once for the whole batch.
+		for node_id in self.all_counterparty_node_ids() {
+			self.send_peer_storage(node_id);
+		}
+
+		// Assume we may have some new events and wake the event processor
+		self.event_notifier.notify();
+	}
+
 	fn blocks_disconnected(&self, fork_point: BestBlock) {
 		let monitor_states = self.monitors.read().unwrap();
 		log_debug!(
@@ -1373,6 +2250,7 @@ where
 		if let Some(ref chain_source) = self.chain_source {
 			monitor.load_outputs_to_watch(chain_source, &self.logger);
 		}
+		self.record_monitor_watch_registrations(&monitor);
 		entry.insert(MonitorHolder {
 			monitor,
 			pending_monitor_updates: Mutex::new(pending_monitor_updates),
@@ -1411,6 +2289,16 @@ where
 				// `pending_monitor_updates` docs for more.
 				let mut pending_monitor_updates =
 					monitor_state.pending_monitor_updates.lock().unwrap();
+				if let Err(e) = self.write_to_update_journal(monitor, update) {
+					// Take the monitors lock for writing so that we poison it and any future
+					// operations going forward fail immediately.
+					core::mem::drop(pending_monitor_updates);
+					core::mem::drop(monitors);
+					let _poison = self.monitors.write().unwrap();
+					let err_str = "Writing a ChannelMonitorUpdate to the update journal failed. This indicates we cannot continue normal operation and must shut down.";
+					log_error!(logger, "{} Error: {}", err_str, e);
+					panic!("{}", err_str);
+				}
 				let update_res = monitor.update_monitor(
 					update,
 					&self.broadcaster,
@@ -1453,6 +2341,10 @@ where
 							"Persistence of ChannelMonitorUpdate id {:?} completed",
 							update_id,
 						);
+						if update_res.is_err() {
+							// The full monitor was persisted, so the journal can be pruned.
+							self.maybe_prune_update_journal(monitor);
+						}
 					},
 					ChannelMonitorUpdateStatus::UnrecoverableError => {
 						// Take the monitors lock for writing so that we poison it and any future
@@ -1467,21 +2359,27 @@ where
 				}
 
 				// We may need to start monitoring for any alternative funding transactions.
-				if let Some(ref chain_source) = self.chain_source {
-					for (funding_outpoint, funding_script) in
-						update.internal_renegotiated_funding_data()
-					{
+				for (funding_outpoint, funding_script) in
+					update.internal_renegotiated_funding_data()
+				{
+					let output = WatchedOutput {
+						block_hash: None,
+						outpoint: funding_outpoint,
+						script_pubkey: funding_script.clone(),
+					};
+					self.record_watch_registration(|| WatchRegistration::Transaction {
+						txid: funding_outpoint.txid,
+						script_pubkey: funding_script.clone(),
+					});
+					self.record_watch_registration(|| WatchRegistration::Output(output.clone()));
+					if let Some(ref chain_source) = self.chain_source {
 						log_trace!(
 							logger,
 							"Registering renegotiated funding outpoint {} with the filter to monitor confirmations and spends",
 							funding_outpoint
 						);
 						chain_source.register_tx(&funding_outpoint.txid, &funding_script);
-						chain_source.register_output(WatchedOutput {
-							block_hash: None,
-							outpoint: funding_outpoint,
-							script_pubkey: funding_script,
-						});
+						chain_source.register_output(output);
 					}
 				}
 
@@ -1498,7 +2396,16 @@ where
 		&self,
 	) -> Vec<(OutPoint, ChannelId, Vec<MonitorEvent>, PublicKey)> {
 		for (channel_id, update_id) in self.persister.get_and_clear_completed_updates() {
-			let _ = self.channel_monitor_updated(channel_id, update_id);
+			// Persists of the full monitor (e.g. during chain sync) may complete an update ID which
+			// was never pending or has already completed, which we skip to avoid spurious
+			// `MonitorEvent::Completed`s.
+			let is_pending =
+				self.monitors.read().unwrap().get(&channel_id).map_or(false, |m| {
+					m.pending_monitor_updates.lock().unwrap().contains(&update_id)
+				});
+			if is_pending {
+				let _ = self.channel_monitor_updated(channel_id, update_id);
+			}
 		}
 		let mut pending_monitor_events = self.pending_monitor_events.lock().unwrap().split_off(0);
 		for monitor_state in self.monitors.read().unwrap().values() {
@@ -1517,6 +2424,11 @@ where
 		}
 		pending_monitor_events
 	}
+
+	fn get_broadcast_holder_commitment_txid(&self, channel_id: ChannelId) -> Option<Txid> {
+		let monitors = self.monitors.read().unwrap();
+		monitors.get(&channel_id).and_then(|m| m.monitor.get_broadcast_holder_commitment_txid())
+	}
 }
 
 impl<
@@ -1553,25 +2465,162 @@ where
 	where
 		H::Target: EventHandler,
 	{
+		let pending_events = core::mem::take(&mut *self.pending_events.lock().unwrap());
+		let mut pending_events_iter = pending_events.into_iter();
+		while let Some(event) = pending_events_iter.next() {
+			if handler.handle_event(event.clone()).is_err() {
+				let mut events = self.pending_events.lock().unwrap();
+				let remaining = core::iter::once(event).chain(pending_events_iter);
+				events.splice(0..0, remaining);
+				self.event_notifier.notify();
+				break;
+			}
+		}
+
+		let aggregate_htlc_claims = self.aggregate_htlc_claims.load(Ordering::Acquire);
+		let htlc_resolutions = RefCell::new(Vec::new());
+		let monitor_event_handler = |event: Event| match event {
+			Event::BumpTransaction(bump_event @ BumpTransactionEvent::HTLCResolution { .. })
+				if aggregate_htlc_claims =>
+			{
+				htlc_resolutions.borrow_mut().push(bump_event);
+				Ok(())
+			},
+			event => handler.handle_event(event),
+		};
 		for monitor_state in self.monitors.read().unwrap().values() {
-			match monitor_state.monitor.process_pending_events(&handler, &self.logger) {
+			let monitor = &monitor_state.monitor;
+			match monitor.process_pending_events(&&monitor_event_handler, &self.logger) {
 				Ok(()) => {},
 				Err(ReplayEvent()) => {
 					self.event_notifier.notify();
 				},
 			}
 		}
+
+		for htlc_resolutions in group_htlc_resolutions(htlc_resolutions.into_inner()) {
+			if htlc_resolutions.len() > 1 {
+				let merged = merge_htlc_resolutions(&htlc_resolutions);
+				let claim_id = merged.claim_id();
+				if handler.handle_event(Event::BumpTransaction(merged)).is_ok() {
+					continue;
+				}
+				// Fall back to claiming each channel's HTLCs on their own, so that failing to claim
+				// the merged HTLCs doesn't hold back the claims of every channel involved.
+				log_warn!(
+					self.logger,
+					"Failed to handle merged HTLC claim {}, retrying per channel",
+					log_bytes!(claim_id.0)
+				);
+			}
+			for bump_event in htlc_resolutions {
+				let claim_id = bump_event.claim_id();
+				if handler.handle_event(Event::BumpTransaction(bump_event)).is_err() {
+					log_error!(
+						self.logger,
+						"Failed to handle HTLC claim {}, awaiting its regeneration by the ChannelMonitor",
+						log_bytes!(claim_id.0)
+					);
+				}
+			}
+		}
 	}
 }
 
+/// Returns the properties which must match for the HTLCs of two
+/// [`BumpTransactionEvent::HTLCResolution`]s to be claimed in the same transaction.
+fn htlc_resolution_aggregation_key(event: &BumpTransactionEvent) -> Option<(LockTime, bool)> {
+	match event {
+		BumpTransactionEvent::HTLCResolution { htlc_descriptors, tx_lock_time, .. } => {
+			let channel_parameters =
+				&htlc_descriptors.first()?.channel_derivation_parameters.transaction_parameters;
+			let zero_fee_commitments =
+				channel_parameters.channel_type_features.supports_anchor_zero_fee_commitments();
+			Some((*tx_lock_time, zero_fee_commitments))
+		},
+		_ => None,
+	}
+}
+
+/// Groups the [`BumpTransactionEvent::HTLCResolution`]s in `events` whose HTLCs can be claimed in
+/// the same transaction, see [`ChainMonitor::set_aggregate_htlc_claims`].
+///
+/// Events are ordered by their [`ClaimId`] so that the same claims are always grouped, and thus
+/// merged, the same way regardless of the order in which the monitors' events were processed.
+fn group_htlc_resolutions(mut events: Vec<BumpTransactionEvent>) -> Vec<Vec<BumpTransactionEvent>> {
+	events.sort_unstable_by_key(|event| event.claim_id().0);
+	let mut groups: Vec<Vec<BumpTransactionEvent>> = Vec::with_capacity(events.len());
+	for event in events {
+		let key = htlc_resolution_aggregation_key(&event);
+		let group = key.and_then(|key| {
+			groups.iter_mut().find(|group| htlc_resolution_aggregation_key(&group[0]) == Some(key))
+		});
+		match group {
+			Some(group) => group.push(event),
+			None => groups.push(vec![event]),
+		}
+	}
+	groups
+}
+
+/// Merges a group of [`BumpTransactionEvent::HTLCResolution`]s built by [`group_htlc_resolutions`]
+/// into a single event targeting the highest feerate among them.
+///
+/// The merged event's [`ClaimId`] is derived from those of the events it was built from, which
+/// the [`ChannelMonitor`]s keep stable across regenerations of the same claim. Thus, the UTXOs
+/// previously assigned to the merged claim are reused as long as the same claims are merged.
+fn merge_htlc_resolutions(group: &[BumpTransactionEvent]) -> BumpTransactionEvent {
+	let mut merged = group[0].clone();
+	if let BumpTransactionEvent::HTLCResolution {
+		claim_id,
+		target_feerate_sat_per_1000_weight,
+		htlc_descriptors,
+		..
+	} = &mut merged
+	{
+		*claim_id = ClaimId::from_claim_ids(group.iter().map(|event| event.claim_id()));
+		for event in &group[1..] {
+			if let BumpTransactionEvent::HTLCResolution {
+				target_feerate_sat_per_1000_weight: feerate,
+				htlc_descriptors: descriptors,
+				..
+			} = event
+			{
+				*target_feerate_sat_per_1000_weight =
+					cmp::max(*target_feerate_sat_per_1000_weight, *feerate);
+				htlc_descriptors.extend_from_slice(descriptors);
+			}
+		}
+	}
+	merged
+}
+
 #[cfg(test)]
 mod tests {
+	use super::{group_htlc_resolutions, merge_htlc_resolutions, MAX_QUEUED_WATCH_REGISTRATIONS};
 	use crate::chain::channelmonitor::ANTI_REORG_DELAY;
-	use crate::chain::{ChannelMonitorUpdateStatus, Watch};
+	use crate::chain::{
+		BestBlock, ChannelMonitorUpdateStatus, ClaimId, Listen, Watch, WatchRegistration,
+		WatchedOutput,
+	};
+	use crate::events::bump_transaction::{BumpTransactionEvent, Utxo};
 	use crate::events::{ClosureReason, Event};
+	use crate::ln::chan_utils::{
+		ChannelTransactionParameters, HTLCOutputInCommitment, HolderCommitmentTransaction,
+	};
 	use crate::ln::functional_test_utils::*;
 	use crate::ln::msgs::{BaseMessageHandler, ChannelMessageHandler, MessageSendEvent};
-	use crate::{expect_payment_path_successful, get_event_msg};
+	use crate::ln::types::ChannelId;
+	use crate::sign::{ChannelDerivationParameters, HTLCDescriptor};
+	use crate::types::features::ChannelTypeFeatures;
+	use crate::types::payment::PaymentHash;
+	use crate::util::anchor_channel_reserves::{
+		get_reserve_for_channel, get_reserve_per_channel, AnchorChannelReserveContext,
+	};
+	use crate::{expect_payment_path_successful, get_event_msg, get_local_commitment_txn};
+	use bitcoin::hashes::Hash;
+	use bitcoin::locktime::absolute::LockTime;
+	use bitcoin::{OutPoint, Transaction, Txid, WPubkeyHash};
 
 	const CHAINSYNC_MONITOR_PARTITION_FACTOR: u32 = 5;
 
@@ -1799,6 +2848,307 @@ mod tests {
 		);
 	}
 
+	#[test]
+	fn test_batched_blocks_connected_persist_monitors_once() {
+		// Test that connecting a batch of blocks via `Listen::filtered_blocks_connected` moves both
+		// the `ChainMonitor` and `ChannelManager` to the end of the batch while only persisting each
+		// `ChannelMonitor` once for the whole batch.
+		let chanmon_cfgs = create_chanmon_cfgs(2);
+		let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+		let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[None, None]);
+		let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+
+		create_announced_chan_between_nodes(&nodes, 0, 1);
+		chanmon_cfgs[0].persister.chain_sync_monitor_persistences.lock().unwrap().clear();
+
+		let (mut prev_blockhash, start_height) = nodes[0].best_block_info();
+		let end_height = start_height + CHAINSYNC_MONITOR_PARTITION_FACTOR * 2;
+		let mut blocks = Vec::new();
+		for height in start_height + 1..=end_height {
+			let block = create_dummy_block(prev_blockhash, height, Vec::new());
+			prev_blockhash = block.block_hash();
+			blocks.push((block, height));
+		}
+
+		let txdata: Vec<(usize, &Transaction)> = Vec::new();
+		let batch = blocks
+			.iter()
+			.map(|(block, height)| (&block.header, &txdata[..], *height))
+			.collect::<Vec<_>>();
+		nodes[0].chain_monitor.chain_monitor.filtered_blocks_connected(&batch);
+		nodes[0].node.filtered_blocks_connected(&batch);
+		nodes[0].blocks.lock().unwrap().extend(blocks);
+
+		assert_eq!(nodes[0].node.current_best_block().height, end_height);
+		assert_eq!(nodes[0].node.current_best_block().block_hash, prev_blockhash);
+
+		// Connecting the blocks one at a time would have persisted the monitor twice.
+		assert_eq!(
+			1,
+			chanmon_cfgs[0].persister.chain_sync_monitor_persistences.lock().unwrap().len()
+		);
+	}
+
+	#[test]
+	fn test_list_watch_items_matches_filter_registrations() {
+		// Test that the watch items exported by the `ChainMonitor` cover exactly what was
+		// registered with the `chain::Filter`, even after we start watching for commitment
+		// transaction outputs.
+		let chanmon_cfgs = create_chanmon_cfgs(2);
+		let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+		let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[None, None]);
+		let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+		let node_b_id = nodes[1].node.get_our_node_id();
+		let channel_id = create_announced_chan_between_nodes(&nodes, 0, 1).2;
+
+		let check_watch_items = || {
+			let watch_items = nodes[0].chain_monitor.chain_monitor.list_watch_items();
+			assert_eq!(watch_items.len(), 1);
+			assert_eq!(watch_items[0].channel_id, channel_id);
+			assert_eq!(watch_items[0].counterparty_node_id, node_b_id);
+
+			let watched_txn = nodes[0].chain_source.watched_txn.lock().unwrap();
+			assert_eq!(watch_items[0].transactions.len(), 1);
+			for tx in watch_items[0].transactions.iter() {
+				assert!(watched_txn.contains(tx));
+			}
+			let watched_outputs = nodes[0].chain_source.watched_outputs.lock().unwrap();
+			assert_eq!(watch_items[0].outputs.len(), watched_outputs.len());
+			for output in watch_items[0].outputs.iter() {
+				assert!(output.block_hash.is_none());
+				assert!(watched_outputs.contains(&(output.outpoint, output.script_pubkey.clone())));
+			}
+		};
+		check_watch_items();
+
+		// Once the counterparty's commitment transaction confirms, we'll need to watch its outputs.
+		let remote_txn = get_local_commitment_txn!(nodes[1], channel_id);
+		mine_transaction(&nodes[0], &remote_txn[0]);
+		check_closed_broadcast(&nodes[0], 1, true);
+		check_added_monitors(&nodes[0], 1);
+		let reason = ClosureReason::CommitmentTxConfirmed;
+		check_closed_event(&nodes[0], 1, reason, &[node_b_id], 100000);
+		check_watch_items();
+	}
+
+	#[test]
+	fn test_watch_snapshot_and_registrations() {
+		// Test that a watch snapshot covers everything registered with the `chain::Filter`, and
+		// that registrations made after it was taken are queued along with their block hints.
+		let chanmon_cfgs = create_chanmon_cfgs(2);
+		let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+		let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[None, None]);
+		let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+		let node_b_id = nodes[1].node.get_our_node_id();
+		let channel_id = create_announced_chan_between_nodes(&nodes, 0, 1).2;
+		let chain_monitor = &nodes[0].chain_monitor.chain_monitor;
+
+		// Registrations are only queued once a snapshot has been taken.
+		assert!(chain_monitor.get_and_clear_watch_registrations().is_none());
+		let snapshot = chain_monitor.watch_snapshot();
+		let (best_block_hash, best_block_height) = nodes[0].best_block_info();
+		assert_eq!(snapshot.best_block, Some(BestBlock::new(best_block_hash, best_block_height)));
+		let watch_items = chain_monitor.list_watch_items();
+		assert_eq!(snapshot.channels.len(), 1);
+		assert_eq!(snapshot.channels[0].channel_id, channel_id);
+		assert_eq!(snapshot.channels[0].transactions, watch_items[0].transactions);
+		let outpoints = |outputs: &[WatchedOutput]| {
+			outputs.iter().map(|output| output.outpoint).collect::<Vec<_>>()
+		};
+		assert_eq!(outpoints(&snapshot.channels[0].outputs), outpoints(&watch_items[0].outputs));
+		assert_eq!(chain_monitor.get_and_clear_watch_registrations(), Some(Vec::new()));
+
+		// Once the counterparty's commitment transaction confirms, we'll need to watch its outputs.
+		let remote_txn = get_local_commitment_txn!(nodes[1], channel_id);
+		let commitment_txid = remote_txn[0].compute_txid();
+		mine_transaction(&nodes[0], &remote_txn[0]);
+		check_closed_broadcast(&nodes[0], 1, true);
+		check_added_monitors(&nodes[0], 1);
+		let reason = ClosureReason::CommitmentTxConfirmed;
+		check_closed_event(&nodes[0], 1, reason, &[node_b_id], 100000);
+		let (commitment_block_hash, commitment_height) = nodes[0].best_block_info();
+
+		let registrations = chain_monitor.get_and_clear_watch_registrations().unwrap();
+		assert!(!registrations.is_empty());
+		for registration in registrations.iter() {
+			match registration {
+				WatchRegistration::Output(output) => {
+					assert_eq!(output.outpoint.txid, commitment_txid);
+					assert_eq!(output.block_hash, Some(commitment_block_hash));
+				},
+				_ => panic!("Unexpected registration {:?}", registration),
+			}
+		}
+		assert_eq!(chain_monitor.get_and_clear_watch_registrations(), Some(Vec::new()));
+
+		// A new snapshot includes the new outputs, hinting the block they were created in.
+		let snapshot = chain_monitor.watch_snapshot();
+		assert!(snapshot.relevant_txids.contains(&(
+			commitment_txid,
+			commitment_height,
+			Some(commitment_block_hash)
+		)));
+		for registration in registrations {
+			if let WatchRegistration::Output(output) = registration {
+				assert!(snapshot.channels[0].outputs.contains(&output));
+			}
+		}
+
+		// If too many registrations are queued without being fetched, the queue is dropped and a
+		// new snapshot has to be taken.
+		let output = snapshot.channels[0].outputs[0].clone();
+		for _ in 0..MAX_QUEUED_WATCH_REGISTRATIONS {
+			chain_monitor.record_watch_registration(|| WatchRegistration::Output(output.clone()));
+		}
+		let registrations = chain_monitor.get_and_clear_watch_registrations().unwrap();
+		assert_eq!(registrations.len(), MAX_QUEUED_WATCH_REGISTRATIONS);
+		for _ in 0..=MAX_QUEUED_WATCH_REGISTRATIONS {
+			chain_monitor.record_watch_registration(|| WatchRegistration::Output(output.clone()));
+		}
+		assert!(chain_monitor.get_and_clear_watch_registrations().is_none());
+		chain_monitor.record_watch_registration(|| WatchRegistration::Output(output.clone()));
+		assert!(chain_monitor.get_and_clear_watch_registrations().is_none());
+		chain_monitor.watch_snapshot();
+		assert_eq!(chain_monitor.get_and_clear_watch_registrations(), Some(Vec::new()));
+	}
+
+	#[test]
+	fn test_aggregate_htlc_resolutions() {
+		// Test that `HTLCResolution`s are only grouped if their HTLCs can be claimed in the same
+		// transaction, and that merged events get a `ClaimId` derived from the events they were
+		// built from and the highest feerate.
+		fn htlc_resolution(
+			idx: u8, feerate: u32, lock_time: u32, features: &ChannelTypeFeatures,
+		) -> BumpTransactionEvent {
+			let mut channel_parameters = ChannelTransactionParameters::test_dummy(100_000);
+			channel_parameters.channel_type_features = features.clone();
+			let htlc = HTLCOutputInCommitment {
+				offered: true,
+				amount_msat: 1_000_000,
+				cltv_expiry: lock_time,
+				payment_hash: PaymentHash([idx; 32]),
+				transaction_output_index: Some(0),
+			};
+			let funding_outpoint = channel_parameters.funding_outpoint.unwrap();
+			let commitment_tx =
+				HolderCommitmentTransaction::dummy(100_000, funding_outpoint, vec![htlc.clone()]);
+			let trusted_tx = commitment_tx.trust();
+			let counterparty_node_id = channel_parameters.holder_pubkeys.funding_pubkey;
+			let htlc_descriptor = HTLCDescriptor {
+				channel_derivation_parameters: ChannelDerivationParameters {
+					value_satoshis: channel_parameters.channel_value_satoshis,
+					keys_id: [idx; 32],
+					transaction_parameters: channel_parameters,
+				},
+				commitment_txid: Txid::from_byte_array([idx; 32]),
+				per_commitment_number: trusted_tx.commitment_number(),
+				per_commitment_point: trusted_tx.per_commitment_point(),
+				feerate_per_kw: 0,
+				htlc,
+				preimage: None,
+				counterparty_sig: commitment_tx.counterparty_htlc_sigs[0],
+			};
+			BumpTransactionEvent::HTLCResolution {
+				channel_id: ChannelId([idx; 32]),
+				counterparty_node_id,
+				claim_id: ClaimId([idx; 32]),
+				target_feerate_sat_per_1000_weight: feerate,
+				htlc_descriptors: vec![htlc_descriptor],
+				tx_lock_time: LockTime::from_consensus(lock_time),
+			}
+		}
+
+		let anchors = ChannelTypeFeatures::anchors_zero_htlc_fee_and_dependencies();
+		let mut zero_fee_commitments = ChannelTypeFeatures::empty();
+		zero_fee_commitments.set_anchor_zero_fee_commitments_required();
+		let events = vec![
+			htlc_resolution(1, 253, 100, &anchors),
+			htlc_resolution(2, 500, 200, &anchors),
+			htlc_resolution(3, 1000, 100, &anchors),
+			htlc_resolution(4, 253, 100, &zero_fee_commitments),
+		];
+
+		// The grouping doesn't depend on the order in which the events were generated.
+		let groups = group_htlc_resolutions(events.iter().rev().cloned().collect());
+		assert_eq!(groups, group_htlc_resolutions(events.clone()));
+		assert_eq!(
+			groups,
+			vec![
+				vec![events[0].clone(), events[2].clone()],
+				vec![events[1].clone()],
+				vec![events[3].clone()]
+			]
+		);
+
+		match merge_htlc_resolutions(&groups[0]) {
+			BumpTransactionEvent::HTLCResolution {
+				channel_id,
+				claim_id,
+				target_feerate_sat_per_1000_weight,
+				htlc_descriptors,
+				tx_lock_time,
+				..
+			} => {
+				assert_eq!(channel_id, ChannelId([1; 32]));
+				let claim_ids = [ClaimId([1; 32]), ClaimId([3; 32])];
+				assert_eq!(claim_id, ClaimId::from_claim_ids(claim_ids.into_iter()));
+				assert_eq!(target_feerate_sat_per_1000_weight, 1000);
+				assert_eq!(htlc_descriptors.len(), 2);
+				assert_eq!(htlc_descriptors[0].channel_derivation_parameters.keys_id, [1; 32]);
+				assert_eq!(htlc_descriptors[1].channel_derivation_parameters.keys_id, [3; 32]);
+				assert_eq!(tx_lock_time, LockTime::from_consensus(100));
+			},
+			_ => panic!("Unexpected event"),
+		}
+	}
+
+	#[test]
+	fn test_check_anchor_channel_reserves() {
+		// Test that `check_anchor_channel_reserves` generates an `InsufficientAnchorReserves` event
+		// when the reserves first become insufficient, and only again once they've been
+		// replenished in between.
+		let chanmon_cfgs = create_chanmon_cfgs(2);
+		let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+		let config = test_default_anchors_channel_config();
+		let node_chanmgrs =
+			create_node_chanmgrs(2, &node_cfgs, &[Some(config.clone()), Some(config)]);
+		let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+		let channel_id = create_announced_chan_between_nodes_with_value(&nodes, 0, 1, 100_000, 0).2;
+
+		let chain_monitor = &nodes[0].chain_monitor.chain_monitor;
+		let context = AnchorChannelReserveContext::default();
+		let reserve =
+			get_reserve_for_channel(&context, &*chain_monitor.get_monitor(channel_id).unwrap());
+		// Without any pending HTLCs, we fall back to the expected number of HTLCs.
+		assert_eq!(reserve, get_reserve_per_channel(&context));
+
+		let utxo = Utxo::new_v0_p2wpkh(OutPoint::null(), reserve * 2, &WPubkeyHash::all_zeros());
+		assert!(chain_monitor.check_anchor_channel_reserves(&context, &[utxo.clone()]));
+		assert!(chain_monitor.get_and_clear_pending_events().is_empty());
+
+		assert!(!chain_monitor.check_anchor_channel_reserves(&context, &[]));
+		let events = chain_monitor.get_and_clear_pending_events();
+		match &events[..] {
+			[Event::InsufficientAnchorReserves {
+				required_reserve_satoshis,
+				available_reserve_satoshis,
+				num_anchor_channels,
+			}] => {
+				assert_eq!(*required_reserve_satoshis, reserve.to_sat());
+				assert_eq!(*available_reserve_satoshis, 0);
+				assert_eq!(*num_anchor_channels, 1);
+			},
+			_ => panic!("Unexpected events: {:?}", events),
+		}
+
+		assert!(!chain_monitor.check_anchor_channel_reserves(&context, &[]));
+		assert!(chain_monitor.get_and_clear_pending_events().is_empty());
+
+		assert!(chain_monitor.check_anchor_channel_reserves(&context, &[utxo]));
+		assert!(!chain_monitor.check_anchor_channel_reserves(&context, &[]));
+		assert_eq!(chain_monitor.get_and_clear_pending_events().len(), 1);
+	}
+
 	#[test]
 	#[cfg(feature = "std")]
 	fn update_during_chainsync_poisons_channel() {
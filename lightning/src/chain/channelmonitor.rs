@@ -34,16 +34,19 @@ use bitcoin::secp256k1::{self, ecdsa::Signature, PublicKey, Secp256k1, SecretKey
 
 use crate::chain;
 use crate::chain::chaininterface::{
-	BroadcasterInterface, ConfirmationTarget, FeeEstimator, LowerBoundedFeeEstimator,
+	fee_for_weight, BroadcasterInterface, ConfirmationTarget, FeeEstimator,
+	LowerBoundedFeeEstimator,
 };
 use crate::chain::onchaintx::{ClaimEvent, FeerateStrategy, OnchainTxHandler};
 use crate::chain::package::{
-	CounterpartyOfferedHTLCOutput, CounterpartyReceivedHTLCOutput, HolderFundingOutput,
-	HolderHTLCOutput, PackageSolvingData, PackageTemplate, RevokedHTLCOutput, RevokedOutput,
+	weight_offered_htlc, weight_received_htlc, weight_revoked_offered_htlc,
+	weight_revoked_received_htlc, CounterpartyOfferedHTLCOutput, CounterpartyReceivedHTLCOutput,
+	HolderFundingOutput, HolderHTLCOutput, PackageSolvingData, PackageTemplate, RevokedHTLCOutput,
+	RevokedOutput, WEIGHT_REVOKED_OUTPUT,
 };
 use crate::chain::transaction::{OutPoint, TransactionData};
 use crate::chain::Filter;
-use crate::chain::{BestBlock, WatchedOutput};
+use crate::chain::{BestBlock, ChannelWatchItems, WatchedOutput};
 use crate::events::bump_transaction::{AnchorDescriptor, BumpTransactionEvent};
 use crate::events::{ClosureReason, Event, EventHandler, ReplayEvent};
 use crate::ln::chan_utils::{
@@ -61,7 +64,7 @@ use crate::ln::types::ChannelId;
 use crate::sign::{
 	ecdsa::EcdsaChannelSigner, ChannelDerivationParameters, DelayedPaymentOutputDescriptor,
 	EntropySource, HTLCDescriptor, SignerProvider, SpendableOutputDescriptor,
-	StaticPaymentOutputDescriptor,
+	StaticPaymentOutputDescriptor, P2WPKH_WITNESS_WEIGHT,
 };
 use crate::types::features::ChannelTypeFeatures;
 use crate::types::payment::{PaymentHash, PaymentPreimage};
@@ -681,6 +684,9 @@ pub(crate) enum ChannelMonitorUpdateStep {
 		/// If set to false, we shouldn't broadcast the latest holder commitment transaction as we
 		/// think we've fallen behind!
 		should_broadcast: bool,
+		/// Set if our counterparty proved that our latest holder commitment transaction has been
+		/// revoked, in which case it must never be broadcast automatically, even if HTLCs time out.
+		holder_commitment_stale: bool,
 	},
 	ShutdownScript {
 		scriptpubkey: ScriptBuf,
@@ -754,6 +760,7 @@ impl_writeable_tlv_based_enum_upgradable!(ChannelMonitorUpdateStep,
 	},
 	(4, ChannelForceClosed) => {
 		(0, should_broadcast, required),
+		(1, holder_commitment_stale, (default_value, false)), // Added in 0.3
 	},
 	(5, ShutdownScript) => {
 		(0, scriptpubkey, required),
@@ -1001,6 +1008,18 @@ impl Balance {
 	}
 }
 
+/// An estimate of the on-chain cost of claiming a [`Balance`], as returned by
+/// [`ChannelMonitor::estimate_claim_cost`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClaimCostEstimate {
+	/// The expected weight, in weight units, which claiming the balance adds to the transactions
+	/// we broadcast, including any pre-signed HTLC transaction needed to do so.
+	pub weight: u64,
+	/// The expected fee, in satoshis, required to claim the balance at the feerate provided to
+	/// [`ChannelMonitor::estimate_claim_cost`].
+	pub fee_satoshis: u64,
+}
+
 /// An HTLC which has been irrevocably resolved on-chain, and has reached ANTI_REORG_DELAY.
 #[derive(Clone, PartialEq, Eq)]
 struct IrrevocablyResolvedHTLC {
@@ -1053,6 +1072,56 @@ impl Readable for IrrevocablyResolvedHTLC {
 	}
 }
 
+/// Details about a claim we're attempting to get confirmed on-chain for a closed channel, as
+/// returned by [`ChannelMonitor::get_pending_claims`].
+///
+/// Pending claims are persisted as part of the [`ChannelMonitor`], so they (and their
+/// [`ClaimId`]s) are retained across restarts, with fee-bumping resuming from the last feerate
+/// used.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PendingClaim {
+	/// The identifier of the claim, which remains the same across fee-bumps.
+	///
+	/// For claims requiring external funding, this matches the `claim_id` of the
+	/// [`BumpTransactionEvent`]s generated for it.
+	///
+	/// [`BumpTransactionEvent`]: crate::events::bump_transaction::BumpTransactionEvent
+	pub claim_id: ClaimId,
+	/// The outpoints being spent by the claim.
+	pub outpoints: Vec<BitcoinOutPoint>,
+	/// The feerate, in satoshis per 1000 weight units, used by the latest attempt to get the
+	/// claim confirmed, or 0 if no attempt has been made yet.
+	pub feerate_sat_per_1000_weight: u64,
+	/// The height at which we'll bump the claim's feerate if it has not confirmed by then.
+	pub next_bump_height: u32,
+	/// The number of confirmations of the transaction spending [`Self::outpoints`], if any.
+	///
+	/// Once this reaches [`ANTI_REORG_DELAY`], the claim is considered resolved and will no longer
+	/// be returned.
+	pub confirmations: Option<u32>,
+}
+
+/// The data required for a third party, such as an LSP, to get our holder commitment transaction
+/// confirmed by spending the counterparty's anchor output on it, as returned by
+/// [`ChannelMonitor::get_anchor_spend_delegation`].
+///
+/// This allows nodes without on-chain funds of their own to delegate fee-bumping of their
+/// commitment transaction. The anchor output can be spent at any time by the counterparty with a
+/// signature from their funding key, or by anyone once the commitment transaction has 16
+/// confirmations with an empty signature in the witness.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AnchorSpendDelegation {
+	/// Our fully-signed holder commitment transaction.
+	pub commitment_tx: Transaction,
+	/// The outpoint of the counterparty's anchor output within [`Self::commitment_tx`].
+	pub anchor_outpoint: BitcoinOutPoint,
+	/// The value of the counterparty's anchor output.
+	pub anchor_value: Amount,
+	/// The witness script of the counterparty's anchor output, which must be provided as the last
+	/// witness element when spending it.
+	pub witness_script: ScriptBuf,
+}
+
 /// A ChannelMonitor handles chain events (blocks connected and disconnected) and generates
 /// on-chain transactions to ensure no loss of funds occurs.
 ///
@@ -1315,6 +1384,11 @@ pub(crate) struct ChannelMonitorImpl<Signer: EcdsaChannelSigner> {
 	// remote monitor out-of-order with regards to the block view.
 	holder_tx_signed: bool,
 
+	// Set when the Channel[Manager] learned that our latest holder commitment transaction has been
+	// revoked, i.e. our state is stale. Broadcasting it would allow our counterparty to claim all
+	// funds in the channel, so we never do so automatically once this is set.
+	holder_commitment_stale: bool,
+
 	// If a spend of the funding output is seen, we set this to true and reject any further
 	// updates. This prevents any further changes in the offchain state no matter the order
 	// of block connection between ChannelMonitors and the ChannelManager.
@@ -1398,6 +1472,20 @@ pub(crate) struct ChannelMonitorImpl<Signer: EcdsaChannelSigner> {
 	// `FundingScope` could be one that was renegotiated.
 	alternative_funding_confirmed: Option<(Txid, u32)>,
 
+	// Upon confirmation, tracks the txid and confirmation height of a transaction spending the
+	// counterparty's anchor output on our holder commitment transaction, i.e., one which was
+	// delegated via `ChannelMonitor::get_anchor_spend_delegation`.
+	delegated_anchor_spend_confirmed: Option<(Txid, u32)>,
+
+	// Maps the txid of each counterparty commitment transaction tracked in any
+	// `FundingScope::counterparty_claimable_outpoints` to its commitment number, allowing
+	// `ChannelMonitor::compact` to identify which revoked states are old enough to be dropped.
+	// Monitors written prior to this field's introduction will not have entries for commitment
+	// transactions they already knew about, and such transactions are never compacted.
+	// Entries are dropped alongside the transactions they refer to, including when a
+	// `FundingScope` is discarded, and the map is cleared once the funding output is spent.
+	counterparty_commitment_numbers: HashMap<Txid, u64>,
+
 	/// [`ChannelMonitor`]s written by LDK prior to 0.1 need to be re-persisted after startup. To
 	/// make deciding whether to do so simple, here we track whether this monitor was last written
 	/// prior to 0.1.
@@ -1755,6 +1843,9 @@ pub(crate) fn write_chanmon_internal<Signer: EcdsaChannelSigner, W: Writer>(
 		(34, channel_monitor.alternative_funding_confirmed, option),
 		(35, channel_monitor.is_manual_broadcast, required),
 		(37, channel_monitor.funding_seen_onchain, required),
+		(39, channel_monitor.delegated_anchor_spend_confirmed, option),
+		(41, channel_monitor.counterparty_commitment_numbers, required),
+		(43, channel_monitor.holder_commitment_stale, required),
 	});
 
 	Ok(())
@@ -1972,6 +2063,7 @@ impl<Signer: EcdsaChannelSigner> ChannelMonitor<Signer> {
 			holder_pays_commitment_tx_fee: Some(holder_pays_commitment_tx_fee),
 			lockdown_from_offchain: false,
 			holder_tx_signed: false,
+			holder_commitment_stale: false,
 			funding_spend_seen: false,
 			funding_spend_confirmed: None,
 			confirmed_commitment_tx_counterparty_output: None,
@@ -1992,6 +2084,8 @@ impl<Signer: EcdsaChannelSigner> ChannelMonitor<Signer> {
 			prev_holder_htlc_data: None,
 
 			alternative_funding_confirmed: None,
+			delegated_anchor_spend_confirmed: None,
+			counterparty_commitment_numbers: new_hash_map(),
 
 			written_by_0_1_or_later: true,
 		})
@@ -2127,6 +2221,17 @@ impl<Signer: EcdsaChannelSigner> ChannelMonitor<Signer> {
 		self.inner.lock().unwrap().get_funding_txo()
 	}
 
+	/// Gets the txid of the latest holder commitment transaction, if we've broadcast it (or queued
+	/// it for broadcast) as a result of the channel being force-closed.
+	pub fn get_broadcast_holder_commitment_txid(&self) -> Option<Txid> {
+		let inner = self.inner.lock().unwrap();
+		if inner.holder_tx_signed {
+			Some(inner.funding.current_holder_commitment_tx.trust().txid())
+		} else {
+			None
+		}
+	}
+
 	pub(crate) fn written_by_0_1_or_later(&self) -> bool {
 		self.inner.lock().unwrap().written_by_0_1_or_later
 	}
@@ -2154,34 +2259,67 @@ impl<Signer: EcdsaChannelSigner> ChannelMonitor<Signer> {
 			.iter().map(|(txid, outputs)| (*txid, outputs.clone())).collect()
 	}
 
-	/// Loads the funding txo and outputs to watch into the given `chain::Filter` by repeatedly
-	/// calling `chain::Filter::register_output` and `chain::Filter::register_tx` until all outputs
-	/// have been registered.
-	#[rustfmt::skip]
-	pub fn load_outputs_to_watch<F: Deref, L: Deref>(&self, filter: &F, logger: &L)
-	where
-		F::Target: chain::Filter, L::Target: Logger,
-	{
+	/// Gets the full set of transactions and outputs this [`ChannelMonitor`] needs its chain source
+	/// to watch, i.e. everything that would be registered via [`chain::Filter`] if the monitor were
+	/// loaded with [`Self::load_outputs_to_watch`] now.
+	///
+	/// This is useful for chain sources which need to re-subscribe to all relevant scripts and
+	/// outpoints, e.g. after reconnecting to a server.
+	pub fn get_watch_items(&self) -> ChannelWatchItems {
 		let lock = self.inner.lock().unwrap();
-		let logger = WithChannelMonitor::from_impl(logger, &*lock, None);
-		for funding in core::iter::once(&lock.funding).chain(&lock.pending_funding) {
-			let funding_outpoint = funding.funding_outpoint();
-			log_trace!(&logger, "Registering funding outpoint {} with the filter to monitor confirmations", &funding_outpoint);
-			let script_pubkey = funding.channel_parameters.make_funding_redeemscript().to_p2wsh();
-			filter.register_tx(&funding_outpoint.txid, &script_pubkey);
-		}
-		for (txid, outputs) in lock.get_outputs_to_watch().iter() {
-			for (index, script_pubkey) in outputs.iter() {
+		let transactions = core::iter::once(&lock.funding)
+			.chain(&lock.pending_funding)
+			.map(|funding| {
+				let script_pubkey =
+					funding.channel_parameters.make_funding_redeemscript().to_p2wsh();
+				(funding.funding_outpoint().txid, script_pubkey)
+			})
+			.collect();
+		let mut outputs = Vec::new();
+		for (txid, tx_outputs) in lock.get_outputs_to_watch().iter() {
+			for (index, script_pubkey) in tx_outputs.iter() {
 				assert!(*index <= u16::MAX as u32);
-				let outpoint = OutPoint { txid: *txid, index: *index as u16 };
-				log_trace!(logger, "Registering outpoint {} with the filter to monitor spend", outpoint);
-				filter.register_output(WatchedOutput {
+				outputs.push(WatchedOutput {
 					block_hash: None,
-					outpoint,
+					outpoint: OutPoint { txid: *txid, index: *index as u16 },
 					script_pubkey: script_pubkey.clone(),
 				});
 			}
 		}
+		ChannelWatchItems {
+			channel_id: lock.channel_id(),
+			counterparty_node_id: lock.counterparty_node_id,
+			transactions,
+			outputs,
+		}
+	}
+
+	/// Loads the funding txo and outputs to watch into the given `chain::Filter` by repeatedly
+	/// calling `chain::Filter::register_output` and `chain::Filter::register_tx` until all outputs
+	/// have been registered.
+	pub fn load_outputs_to_watch<F: Deref, L: Deref>(&self, filter: &F, logger: &L)
+	where
+		F::Target: chain::Filter,
+		L::Target: Logger,
+	{
+		let watch_items = self.get_watch_items();
+		let logger = WithChannelMonitor::from(logger, self, None);
+		for (txid, script_pubkey) in watch_items.transactions.iter() {
+			log_trace!(
+				&logger,
+				"Registering funding transaction {} with the filter to monitor confirmations",
+				txid
+			);
+			filter.register_tx(txid, script_pubkey);
+		}
+		for output in watch_items.outputs {
+			log_trace!(
+				logger,
+				"Registering outpoint {} with the filter to monitor spend",
+				output.outpoint
+			);
+			filter.register_output(output);
+		}
 	}
 
 	/// Get the list of HTLCs who's status has been updated on chain. This should be called by
@@ -2310,6 +2448,66 @@ impl<Signer: EcdsaChannelSigner> ChannelMonitor<Signer> {
 		self.inner.lock().unwrap().sign_to_local_justice_tx(justice_tx, input_idx, value, commitment_number)
 	}
 
+	/// Builds and signs a justice transaction claiming every revokeable output of the given
+	/// counterparty commitment transaction, i.e. its `to_local` output as well as all of its
+	/// non-dust HTLC outputs, sending the funds to `destination_script`.
+	///
+	/// Unlike [`Self::sign_to_local_justice_tx`], which only signs a caller-built transaction
+	/// spending the `to_local` output, this allows watchtower integrations to pre-sign a complete
+	/// revocation punishment as soon as a counterparty state is revoked. The counterparty
+	/// commitment transactions can be retrieved via [`Self::initial_counterparty_commitment_tx`]
+	/// and [`Self::counterparty_commitment_txs_from_update`].
+	///
+	/// The built transaction signals RBF, and `feerate_per_kw` is taken as an input such that
+	/// copies of the justice transaction at different fee rates may be signed.
+	///
+	/// This method will return an `Err` if this monitor has not yet received the revocation secret
+	/// for the commitment transaction, if the commitment transaction does not belong to this
+	/// channel (or to a funding transaction which has since been spliced away), if it has no
+	/// revokeable outputs, or if the claimed value less the fee at the given feerate is below the
+	/// dust limit of `destination_script`.
+	pub fn sign_justice_tx_for_commitment(
+		&self, commitment_tx: &CommitmentTransaction, feerate_per_kw: u32,
+		destination_script: ScriptBuf,
+	) -> Result<Transaction, ()> {
+		self.inner.lock().unwrap().sign_justice_tx_for_commitment(
+			commitment_tx,
+			feerate_per_kw,
+			destination_script,
+		)
+	}
+
+	/// Compacts this monitor by dropping the HTLC data tracked for old revoked counterparty
+	/// commitment transactions, returning the number of revoked states which were dropped.
+	///
+	/// A `ChannelMonitor` stores the set of HTLCs included in every counterparty commitment
+	/// transaction it has ever seen, so that HTLC outputs can be claimed via the revocation path if
+	/// the counterparty broadcasts a revoked state. For long-lived channels with many updates this
+	/// data grows without bound. This drops it for all but the `retained_revoked_states` most
+	/// recently revoked commitment transactions. Should the counterparty broadcast one of the
+	/// dropped states, its `to_local` output will still be claimed in full but its HTLC outputs
+	/// will not be, making this a trade-off between storage and punishment completeness. Given the
+	/// counterparty would forfeit its entire balance by doing so, HTLC outputs on very old states
+	/// generally make up a small portion of the funds at stake.
+	///
+	/// Note that the data for a revoked state is needed to build a full justice transaction via
+	/// [`Self::sign_justice_tx_for_commitment`], so watchtower integrations should only compact
+	/// states for which justice transactions have already been signed.
+	///
+	/// Compaction is a no-op once a commitment transaction for this channel has been seen
+	/// on-chain, as well as for states learned by monitors written by LDK versions which did not
+	/// track commitment numbers of counterparty commitment transactions.
+	///
+	/// After a successful compaction (i.e. when a non-zero value is returned), the monitor should
+	/// be re-persisted in full, see [`Persist::persist_compacted_monitor`] and
+	/// [`ChainMonitor::compact_monitor`].
+	///
+	/// [`Persist::persist_compacted_monitor`]: crate::chain::chainmonitor::Persist::persist_compacted_monitor
+	/// [`ChainMonitor::compact_monitor`]: crate::chain::chainmonitor::ChainMonitor::compact_monitor
+	pub fn compact(&self, retained_revoked_states: u64) -> usize {
+		self.inner.lock().unwrap().compact(retained_revoked_states)
+	}
+
 	pub(crate) fn get_min_seen_secret(&self) -> u64 {
 		self.inner.lock().unwrap().get_min_seen_secret()
 	}
@@ -2559,8 +2757,8 @@ impl<Signer: EcdsaChannelSigner> ChannelMonitor<Signer> {
 		let current_height = inner.best_block.height;
 		let conf_target = inner.closure_conf_target();
 		inner.onchain_tx_handler.rebroadcast_pending_claims(
-			current_height, FeerateStrategy::HighestOfPreviousOrNew, &broadcaster, conf_target,
-			&inner.destination_script, &fee_estimator, &logger,
+			current_height, FeerateStrategy::HighestOfPreviousOrNew, &broadcaster, inner.channel_id,
+			conf_target, &inner.destination_script, &fee_estimator, &logger,
 		);
 	}
 
@@ -2569,6 +2767,38 @@ impl<Signer: EcdsaChannelSigner> ChannelMonitor<Signer> {
 		self.inner.lock().unwrap().onchain_tx_handler.has_pending_claims()
 	}
 
+	/// Returns the claims for this channel which we're currently attempting to get confirmed
+	/// on-chain, i.e., those for which [`Self::has_pending_claims`] returns true.
+	pub fn get_pending_claims(&self) -> Vec<PendingClaim> {
+		let inner = self.inner.lock().unwrap();
+		inner.onchain_tx_handler.get_pending_claims(inner.best_block.height)
+	}
+
+	/// Returns the data required for a third party to get our holder commitment transaction
+	/// confirmed by spending the counterparty's anchor output on it.
+	///
+	/// This is only available for channels with keyed anchor outputs (i.e., not
+	/// [`ChannelTypeFeatures::supports_anchor_zero_fee_commitments`]), and only once we've decided
+	/// to broadcast our holder commitment transaction, e.g., via
+	/// [`ChannelManager::force_close_broadcasting_latest_txn`], as we must never sign it otherwise.
+	///
+	/// Use [`Self::get_delegated_anchor_spend_txid`] to learn whether the anchor was spent.
+	///
+	/// [`ChannelTypeFeatures::supports_anchor_zero_fee_commitments`]: crate::types::features::ChannelTypeFeatures::supports_anchor_zero_fee_commitments
+	/// [`ChannelManager::force_close_broadcasting_latest_txn`]: crate::ln::channelmanager::ChannelManager::force_close_broadcasting_latest_txn
+	pub fn get_anchor_spend_delegation(&self) -> Option<AnchorSpendDelegation> {
+		self.inner.lock().unwrap().get_anchor_spend_delegation()
+	}
+
+	/// Returns the txid of a confirmed transaction spending the anchor output returned by
+	/// [`Self::get_anchor_spend_delegation`], if any.
+	///
+	/// Once our holder commitment transaction confirms, the anchor output is registered with the
+	/// [`chain::Filter`], if any, so that spends confirming in later blocks are detected as well.
+	pub fn get_delegated_anchor_spend_txid(&self) -> Option<Txid> {
+		self.inner.lock().unwrap().delegated_anchor_spend_confirmed.map(|(txid, _)| txid)
+	}
+
 	/// Triggers rebroadcasts of pending claims from a force-closed channel after a transaction
 	/// signature generation failure.
 	#[rustfmt::skip]
@@ -2587,8 +2817,8 @@ impl<Signer: EcdsaChannelSigner> ChannelMonitor<Signer> {
 		let current_height = inner.best_block.height;
 		let conf_target = inner.closure_conf_target();
 		inner.onchain_tx_handler.rebroadcast_pending_claims(
-			current_height, FeerateStrategy::RetryPrevious, &broadcaster, conf_target,
-			&inner.destination_script, &fee_estimator, &logger,
+			current_height, FeerateStrategy::RetryPrevious, &broadcaster, inner.channel_id,
+			conf_target, &inner.destination_script, &fee_estimator, &logger,
 		);
 	}
 
@@ -3168,6 +3398,33 @@ impl<Signer: EcdsaChannelSigner> ChannelMonitor<Signer> {
 		res
 	}
 
+	/// Estimates the on-chain cost of claiming the given [`Balance`], as returned by
+	/// [`Self::get_claimable_balances`], at the given feerate.
+	///
+	/// This allows wallets to display the net amount recoverable from a balance, i.e.
+	/// [`Balance::claimable_amount_satoshis`] less [`ClaimCostEstimate::fee_satoshis`], while a
+	/// channel is being force-closed.
+	///
+	/// The estimate is based on the expected weight of the input (and, for HTLCs on our own
+	/// commitment transaction, the second-stage HTLC transaction) required to claim the balance
+	/// given this channel's type, as well as any subsequent spend of a delayed output via
+	/// [`SpendableOutputDescriptor`]s. Note that:
+	///  * for channels which are not yet closed, this does not include the fee of the commitment
+	///    transaction itself or, for anchor channels, of bumping it,
+	///  * [`Balance::CounterpartyRevokedOutputClaimable`] does not specify which output of the
+	///    revoked commitment transaction it refers to, so the most expensive one is assumed,
+	///  * balances resulting from a cooperative close do not need to be claimed and are
+	///    estimated to cost nothing.
+	pub fn estimate_claim_cost(
+		&self, balance: &Balance, feerate_sat_per_1000_weight: u32,
+	) -> ClaimCostEstimate {
+		let weight = self.inner.lock().unwrap().claim_weight(balance);
+		ClaimCostEstimate {
+			weight,
+			fee_satoshis: fee_for_weight(feerate_sat_per_1000_weight, weight),
+		}
+	}
+
 	/// Gets the set of outbound HTLCs which can be (or have been) resolved by this
 	/// `ChannelMonitor`. This is used to determine if an HTLC was removed from the channel prior
 	/// to the `ChannelManager` having been persisted.
@@ -3440,6 +3697,82 @@ pub fn deliberately_bogus_accepted_htlc_witness() -> Vec<Vec<u8>> {
 }
 
 impl<Signer: EcdsaChannelSigner> ChannelMonitorImpl<Signer> {
+	/// Returns the expected weight of claiming `balance`, see
+	/// [`ChannelMonitor::estimate_claim_cost`].
+	fn claim_weight(&self, balance: &Balance) -> u64 {
+		let channel_type_features = self.channel_type_features();
+		let input_weight = |witness_weight: u64| {
+			chan_utils::BASE_INPUT_WEIGHT + chan_utils::EMPTY_SCRIPT_SIG_WEIGHT + witness_weight
+		};
+		let delayed_output_weight =
+			input_weight(DelayedPaymentOutputDescriptor::MAX_WITNESS_LENGTH);
+		let has_htlc_anchors = channel_type_features.supports_anchors_zero_fee_htlc_tx()
+			|| channel_type_features.supports_anchor_zero_fee_commitments();
+
+		// HTLCs are claimed directly from the counterparty's commitment transaction, but require a
+		// second-stage HTLC transaction (whose output is then delayed) on our own.
+		let confirmed_txid = self.funding_spend_confirmed.or_else(|| {
+			self.onchain_events_awaiting_threshold_conf.iter().find_map(|event| match event.event {
+				OnchainEvent::FundingSpendConfirmation { .. } => Some(event.txid),
+				_ => None,
+			})
+		});
+		let on_counterparty_commitment = confirmed_txid
+			.map(|txid| {
+				let funding = get_confirmed_funding_scope!(self);
+				txid != funding.current_holder_commitment_tx.trust().txid()
+					&& funding.prev_holder_commitment_tx.as_ref().map(|tx| tx.trust().txid())
+						!= Some(txid)
+			})
+			.unwrap_or(false);
+
+		match balance {
+			Balance::ClaimableOnChannelClose { .. } => delayed_output_weight,
+			Balance::ClaimableAwaitingConfirmations { source, .. } => match source {
+				BalanceSource::HolderForceClosed | BalanceSource::Htlc => delayed_output_weight,
+				BalanceSource::CounterpartyForceClosed => {
+					if channel_type_features.supports_anchors_zero_fee_htlc_tx() {
+						input_weight(StaticPaymentOutputDescriptor::CSV_1_MAX_WITNESS_LENGTH)
+					} else {
+						input_weight(P2WPKH_WITNESS_WEIGHT)
+					}
+				},
+				BalanceSource::CoopClose => 0,
+			},
+			Balance::ContentiousClaimable { .. } | Balance::MaybePreimageClaimableHTLC { .. } => {
+				if on_counterparty_commitment {
+					input_weight(weight_offered_htlc(channel_type_features))
+				} else if has_htlc_anchors {
+					chan_utils::aggregated_htlc_success_input_output_pair_weight(
+						channel_type_features,
+					) + delayed_output_weight
+				} else {
+					chan_utils::htlc_success_tx_weight(channel_type_features)
+						+ delayed_output_weight
+				}
+			},
+			Balance::MaybeTimeoutClaimableHTLC { .. } => {
+				if on_counterparty_commitment {
+					input_weight(weight_received_htlc(channel_type_features))
+				} else if has_htlc_anchors {
+					chan_utils::aggregated_htlc_timeout_input_output_pair_weight(
+						channel_type_features,
+					) + delayed_output_weight
+				} else {
+					chan_utils::htlc_timeout_tx_weight(channel_type_features)
+						+ delayed_output_weight
+				}
+			},
+			Balance::CounterpartyRevokedOutputClaimable { .. } => input_weight(cmp::max(
+				WEIGHT_REVOKED_OUTPUT,
+				cmp::max(
+					weight_revoked_offered_htlc(channel_type_features),
+					weight_revoked_received_htlc(channel_type_features),
+				),
+			)),
+		}
+	}
+
 	/// Gets the [`ConfirmationTarget`] we should use when selecting feerates for channel closure
 	/// transactions for this channel right now.
 	#[rustfmt::skip]
@@ -3540,6 +3873,52 @@ impl<Signer: EcdsaChannelSigner> ChannelMonitorImpl<Signer> {
 		Ok(())
 	}
 
+	fn compact(&mut self, retained_revoked_states: u64) -> usize {
+		if self.funding_spend_seen {
+			return 0;
+		}
+
+		// Commitment numbers count down, so revoked states have a commitment number at or above
+		// the minimum one we've seen a secret for, with older states having higher numbers.
+		let prune_threshold = self.get_min_seen_secret().saturating_add(retained_revoked_states);
+		let counterparty_commitment_numbers = &self.counterparty_commitment_numbers;
+		// The same commitment number is tracked once per `FundingScope` while a splice or RBF is
+		// pending, so collect the removed states rather than counting removed entries.
+		let mut removed_states = new_hash_set();
+		for funding in core::iter::once(&mut self.funding).chain(&mut self.pending_funding) {
+			let current_txid = funding.current_counterparty_commitment_txid;
+			let prev_txid = funding.prev_counterparty_commitment_txid;
+			funding.counterparty_claimable_outpoints.retain(|txid, _| {
+				if Some(*txid) == current_txid || Some(*txid) == prev_txid {
+					return true;
+				}
+				match counterparty_commitment_numbers.get(txid) {
+					Some(commitment_number) if *commitment_number >= prune_threshold => {
+						removed_states.insert(*commitment_number);
+						false
+					},
+					_ => true,
+				}
+			});
+		}
+
+		self.prune_counterparty_commitment_numbers();
+
+		removed_states.len()
+	}
+
+	/// Drops the commitment numbers of any counterparty commitment transactions which are no
+	/// longer tracked by a [`FundingScope`], e.g. after compaction or once a splice or RBF locks.
+	fn prune_counterparty_commitment_numbers(&mut self) {
+		let funding = &self.funding;
+		let pending_funding = &self.pending_funding;
+		self.counterparty_commitment_numbers.retain(|txid, _| {
+			core::iter::once(funding)
+				.chain(pending_funding)
+				.any(|funding| funding.counterparty_claimable_outpoints.contains_key(txid))
+		});
+	}
+
 	#[rustfmt::skip]
 	fn provide_initial_counterparty_commitment_tx(
 		&mut self, commitment_tx: CommitmentTransaction,
@@ -3575,6 +3954,7 @@ impl<Signer: EcdsaChannelSigner> ChannelMonitorImpl<Signer> {
 		self.funding.prev_counterparty_commitment_txid = self.funding.current_counterparty_commitment_txid.take();
 		self.funding.current_counterparty_commitment_txid = Some(txid);
 		self.funding.counterparty_claimable_outpoints.insert(txid, htlc_outputs);
+		self.counterparty_commitment_numbers.insert(txid, commitment_number);
 		self.current_counterparty_commitment_number = commitment_number;
 
 		//TODO: Merge this into the other per-counterparty-transaction output storage stuff
@@ -3644,6 +4024,8 @@ impl<Signer: EcdsaChannelSigner> ChannelMonitorImpl<Signer> {
 			pending_funding
 				.counterparty_claimable_outpoints
 				.insert(commitment_txid, htlcs_for_commitment(commitment_tx));
+			self.counterparty_commitment_numbers
+				.insert(commitment_txid, commitment_tx.commitment_number());
 		}
 
 		Ok(())
@@ -3870,7 +4252,7 @@ impl<Signer: EcdsaChannelSigner> ChannelMonitorImpl<Signer> {
 				let conf_target = self.closure_conf_target();
 				self.onchain_tx_handler.update_claims_view_from_requests(
 					htlc_claim_reqs, self.best_block.height, self.best_block.height, broadcaster,
-					conf_target, &self.destination_script, fee_estimator, logger,
+					self.channel_id, conf_target, &self.destination_script, fee_estimator, logger,
 				);
 			}
 		}
@@ -3924,7 +4306,7 @@ impl<Signer: EcdsaChannelSigner> ChannelMonitorImpl<Signer> {
 				let conf_target = self.closure_conf_target();
 				self.onchain_tx_handler.update_claims_view_from_requests(
 					claim_reqs, self.best_block.height, self.best_block.height, broadcaster,
-					conf_target, &self.destination_script, fee_estimator, logger,
+					self.channel_id, conf_target, &self.destination_script, fee_estimator, logger,
 				);
 			}
 		}
@@ -4029,7 +4411,7 @@ impl<Signer: EcdsaChannelSigner> ChannelMonitorImpl<Signer> {
 		let conf_target = self.closure_conf_target();
 		self.onchain_tx_handler.update_claims_view_from_requests(
 			claimable_outpoints, self.best_block.height, self.best_block.height, broadcaster,
-			conf_target, &self.destination_script, fee_estimator, logger,
+			self.channel_id, conf_target, &self.destination_script, fee_estimator, logger,
 		);
 	}
 
@@ -4151,6 +4533,10 @@ impl<Signer: EcdsaChannelSigner> ChannelMonitorImpl<Signer> {
 			alternative_funding_outpoint.txid,
 			vec![(alternative_funding_outpoint.index as u32, script_pubkey)],
 		);
+		self.counterparty_commitment_numbers.insert(
+			alternative_counterparty_commitment_txid,
+			alternative_counterparty_commitment_tx.commitment_number(),
+		);
 		self.pending_funding.push(alternative_funding);
 
 		Ok(())
@@ -4198,6 +4584,7 @@ impl<Signer: EcdsaChannelSigner> ChannelMonitorImpl<Signer> {
 				});
 			}
 		}
+		self.prune_counterparty_commitment_numbers();
 		if let Some((alternative_funding_txid, _)) = self.alternative_funding_confirmed.take() {
 			// In exceedingly rare cases, it's possible there was a reorg that caused a potential funding to
 			// be locked in that this `ChannelMonitor` has not yet seen. Thus, we avoid a runtime assertion
@@ -4334,10 +4721,14 @@ impl<Signer: EcdsaChannelSigner> ChannelMonitorImpl<Signer> {
 						ret = Err(());
 					}
 				},
-				ChannelMonitorUpdateStep::ChannelForceClosed { should_broadcast } => {
+				ChannelMonitorUpdateStep::ChannelForceClosed { should_broadcast, holder_commitment_stale } => {
 					log_trace!(logger, "Updating ChannelMonitor: channel force closed, should broadcast: {}", should_broadcast);
 					self.lockdown_from_offchain = true;
-					if *should_broadcast {
+					if *holder_commitment_stale {
+						log_error!(logger, "Our latest holder commitment transaction has been revoked, never broadcasting it automatically");
+						self.holder_commitment_stale = true;
+					}
+					if *should_broadcast && !self.holder_commitment_stale {
 						// There's no need to broadcast our commitment transaction if we've seen one
 						// confirmed (even with 1 confirmation) as it'll be rejected as
 						// duplicate/conflicting.
@@ -4652,6 +5043,59 @@ impl<Signer: EcdsaChannelSigner> ChannelMonitorImpl<Signer> {
 		Ok(justice_tx)
 	}
 
+	fn sign_justice_tx_for_commitment(
+		&self, commitment_tx: &CommitmentTransaction, feerate_per_kw: u32,
+		destination_script: ScriptBuf,
+	) -> Result<Transaction, ()> {
+		let secp_ctx = &self.onchain_tx_handler.secp_ctx;
+		let secret = self.get_secret(commitment_tx.commitment_number()).ok_or(())?;
+		let per_commitment_key = SecretKey::from_slice(&secret).map_err(|_| ())?;
+		if PublicKey::from_secret_key(secp_ctx, &per_commitment_key)
+			!= commitment_tx.per_commitment_point()
+		{
+			return Err(());
+		}
+
+		let trusted_tx = commitment_tx.trust();
+		let commitment_txid = trusted_tx.txid();
+		// As in `sign_to_local_justice_tx`, locate the `FundingScope` the commitment transaction
+		// spends, as there may be several due to splicing.
+		let channel_parameters = core::iter::once(&self.funding)
+			.chain(&self.pending_funding)
+			.find(|funding| funding.counterparty_claimable_outpoints.contains_key(&commitment_txid))
+			.map(|funding| &funding.channel_parameters)
+			.ok_or(())?;
+		let (mut justice_tx, inputs) =
+			trusted_tx.build_justice_tx(feerate_per_kw, destination_script)?;
+
+		let signer = &self.onchain_tx_handler.signer;
+		let revocation_key = &trusted_tx.keys().revocation_key;
+		for (input_idx, input) in inputs.iter().enumerate() {
+			let sig = if let Some(htlc) = &input.htlc {
+				signer.sign_justice_revoked_htlc(
+					channel_parameters,
+					&justice_tx,
+					input_idx,
+					input.value.to_sat(),
+					&per_commitment_key,
+					htlc,
+					secp_ctx,
+				)?
+			} else {
+				signer.sign_justice_revoked_output(
+					channel_parameters,
+					&justice_tx,
+					input_idx,
+					input.value.to_sat(),
+					&per_commitment_key,
+					secp_ctx,
+				)?
+			};
+			justice_tx.input[input_idx].witness = input.witness(&sig, revocation_key);
+		}
+		Ok(justice_tx)
+	}
+
 	/// Can only fail if idx is < get_min_seen_secret
 	fn get_secret(&self, idx: u64) -> Option<[u8; 32]> {
 		self.commitment_secrets.get_secret(idx)
@@ -4978,7 +5422,8 @@ impl<Signer: EcdsaChannelSigner> ChannelMonitorImpl<Signer> {
 		let per_commitment_point = PublicKey::from_secret_key(&self.onchain_tx_handler.secp_ctx, &per_commitment_key);
 
 		let funding_spent = get_confirmed_funding_scope!(self);
-		debug_assert!(funding_spent.counterparty_claimable_outpoints.contains_key(commitment_txid));
+		// Note that we may no longer have the HTLC data for `commitment_txid` if it was dropped by
+		// `ChannelMonitor::compact`, but that's fine as we only need the revocation key here.
 
 		let htlc_txid = tx.compute_txid();
 		let mut claimable_outpoints = vec![];
@@ -5250,6 +5695,53 @@ impl<Signer: EcdsaChannelSigner> ChannelMonitorImpl<Signer> {
 		}
 	}
 
+	/// Returns the outpoint of the counterparty's keyed anchor output on the holder commitment
+	/// transaction we'd broadcast, along with the output itself.
+	fn holder_commitment_counterparty_anchor(&self) -> Option<(BitcoinOutPoint, TxOut)> {
+		let funding = get_confirmed_funding_scope!(self);
+		if !funding.channel_type_features().supports_anchors_zero_fee_htlc_tx() {
+			return None;
+		}
+		let counterparty_funding_pubkey =
+			&funding.channel_parameters.counterparty_pubkeys()?.funding_pubkey;
+		let script_pubkey =
+			chan_utils::get_keyed_anchor_redeemscript(counterparty_funding_pubkey).to_p2wsh();
+		let trusted_tx = funding.current_holder_commitment_tx.trust();
+		let txid = trusted_tx.txid();
+		let tx = &trusted_tx.built_transaction().transaction;
+		tx.output
+			.iter()
+			.enumerate()
+			.find(|(_, txout)| txout.script_pubkey == script_pubkey)
+			.map(|(idx, txout)| (BitcoinOutPoint { txid, vout: idx as u32 }, txout.clone()))
+	}
+
+	fn get_anchor_spend_delegation(&mut self) -> Option<AnchorSpendDelegation> {
+		// Signing our holder commitment is only safe once we've decided to broadcast it.
+		if !self.holder_tx_signed {
+			return None;
+		}
+		let (anchor_outpoint, anchor_output) = self.holder_commitment_counterparty_anchor()?;
+		let funding = get_confirmed_funding_scope!(self);
+		let counterparty_funding_pubkey =
+			funding.channel_parameters.counterparty_pubkeys()?.funding_pubkey;
+		let funding_output = HolderFundingOutput::build(
+			funding.current_holder_commitment_tx.clone(),
+			funding.channel_parameters.clone(),
+		);
+		let commitment_tx =
+			funding_output.get_maybe_signed_commitment_tx(&mut self.onchain_tx_handler);
+		if !commitment_tx.is_fully_signed() {
+			return None;
+		}
+		Some(AnchorSpendDelegation {
+			commitment_tx: commitment_tx.0,
+			anchor_outpoint,
+			anchor_value: anchor_output.value,
+			witness_script: chan_utils::get_keyed_anchor_redeemscript(&counterparty_funding_pubkey),
+		})
+	}
+
 	#[cfg(any(test, feature = "_test_utils", feature = "unsafe_revoked_tx_signing"))]
 	/// Note that this includes possibly-locktimed-in-the-future transactions!
 	#[rustfmt::skip]
@@ -5346,7 +5838,8 @@ impl<Signer: EcdsaChannelSigner> ChannelMonitorImpl<Signer> {
 			self.onchain_events_awaiting_threshold_conf.retain(|ref entry| entry.height <= height);
 			let conf_target = self.closure_conf_target();
 			self.onchain_tx_handler.blocks_disconnected(
-				height, &broadcaster, conf_target, &self.destination_script, fee_estimator, logger,
+				height, &broadcaster, self.channel_id, conf_target, &self.destination_script,
+				fee_estimator, logger,
 			);
 			Vec::new()
 		} else { Vec::new() }
@@ -5407,6 +5900,11 @@ impl<Signer: EcdsaChannelSigner> ChannelMonitorImpl<Signer> {
 		{
 			should_broadcast_commitment = true;
 		}
+		let counterparty_anchor = if self.delegated_anchor_spend_confirmed.is_none() {
+			self.holder_commitment_counterparty_anchor()
+		} else {
+			None
+		};
 		'tx_iter: for tx in &txn_matched {
 			let txid = tx.compute_txid();
 			log_trace!(logger, "Transaction {} confirmed in block {}", txid , block_hash);
@@ -5430,6 +5928,12 @@ impl<Signer: EcdsaChannelSigner> ChannelMonitorImpl<Signer> {
 					continue 'tx_iter;
 				}
 			}
+			if let Some((anchor_outpoint, _)) = counterparty_anchor.as_ref() {
+				if tx.input.iter().any(|input| input.previous_output == *anchor_outpoint) {
+					log_info!(logger, "Counterparty anchor {} on our holder commitment was spent by {}", anchor_outpoint, txid);
+					self.delegated_anchor_spend_confirmed = Some((txid, height));
+				}
+			}
 			for htlc in self.htlcs_resolved_on_chain.iter() {
 				if Some(txid) == htlc.resolving_txid {
 					log_debug!(logger, "Skipping redundant processing of HTLC resolution tx {} as it was previously confirmed", txid);
@@ -5536,13 +6040,22 @@ impl<Signer: EcdsaChannelSigner> ChannelMonitorImpl<Signer> {
 						self.pending_monitor_events.push(MonitorEvent::CommitmentTxConfirmed(()));
 					}
 					self.funding_spend_seen = true;
+					// Compaction no longer applies once the funding output has been spent.
+					self.counterparty_commitment_numbers.clear();
 
 					let mut balance_spendable_csv = None;
 					let mut commitment_tx_to_counterparty_output = None;
 
 					// Is it a commitment transaction?
 					if (tx.input[0].sequence.0 >> 8*3) as u8 == 0x80 && (tx.lock_time.to_consensus_u32() >> 8*3) as u8 == 0x20 {
-						if let Some((mut new_outpoints, new_outputs)) = self.check_spend_holder_transaction(txid, &tx, height, &block_hash, &logger) {
+						if let Some((mut new_outpoints, mut new_outputs)) = self.check_spend_holder_transaction(txid, &tx, height, &block_hash, &logger) {
+							// Watch the counterparty's anchor so that we learn of a delegated spend
+							// of it even if it confirms after our holder commitment.
+							if let Some((anchor_outpoint, anchor_output)) = counterparty_anchor.as_ref() {
+								if anchor_outpoint.txid == txid {
+									new_outputs.1.push((anchor_outpoint.vout, anchor_output.clone()));
+								}
+							}
 							if !new_outputs.1.is_empty() {
 								watch_outputs.push(new_outputs);
 							}
@@ -5619,7 +6132,7 @@ impl<Signer: EcdsaChannelSigner> ChannelMonitorImpl<Signer> {
 			self.best_block = BestBlock::new(block_hash, height);
 		}
 
-		if should_broadcast_commitment {
+		if should_broadcast_commitment && !self.holder_commitment_stale {
 			let (mut claimables, mut outputs) =
 				self.generate_claimable_outpoints_and_watch_outputs(None, false);
 			claimable_outpoints.append(&mut claimables);
@@ -5755,6 +6268,7 @@ impl<Signer: EcdsaChannelSigner> ChannelMonitorImpl<Signer> {
 								},
 							});
 						}
+						self.prune_counterparty_commitment_numbers();
 					}
 				},
 				OnchainEvent::AlternativeFundingConfirmation {} => {
@@ -5834,12 +6348,12 @@ impl<Signer: EcdsaChannelSigner> ChannelMonitorImpl<Signer> {
 
 		let conf_target = self.closure_conf_target();
 		self.onchain_tx_handler.update_claims_view_from_requests(
-			claimable_outpoints, conf_height, self.best_block.height, broadcaster, conf_target,
-			&self.destination_script, fee_estimator, logger,
+			claimable_outpoints, conf_height, self.best_block.height, broadcaster, self.channel_id,
+			conf_target, &self.destination_script, fee_estimator, logger,
 		);
 		self.onchain_tx_handler.update_claims_view_from_matched_txn(
-			&txn_matched, conf_height, conf_hash, self.best_block.height, broadcaster, conf_target,
-			&self.destination_script, fee_estimator, logger,
+			&txn_matched, conf_height, conf_hash, self.best_block.height, broadcaster,
+			self.channel_id, conf_target, &self.destination_script, fee_estimator, logger,
 		);
 
 		// Determine new outputs to watch by comparing against previously known outputs to watch,
@@ -5882,6 +6396,12 @@ impl<Signer: EcdsaChannelSigner> ChannelMonitorImpl<Signer> {
 		//- maturing spendable output has transaction paying us has been disconnected
 		self.onchain_events_awaiting_threshold_conf.retain(|ref entry| entry.height <= new_height);
 
+		if let Some((_, conf_height)) = self.delegated_anchor_spend_confirmed.as_ref() {
+			if *conf_height > new_height {
+				self.delegated_anchor_spend_confirmed.take();
+			}
+		}
+
 		// TODO: Replace with `take_if` once our MSRV is >= 1.80.
 		let mut should_broadcast_commitment = false;
 		if let Some((_, conf_height)) = self.alternative_funding_confirmed.as_ref() {
@@ -5902,12 +6422,13 @@ impl<Signer: EcdsaChannelSigner> ChannelMonitorImpl<Signer> {
 		let bounded_fee_estimator = LowerBoundedFeeEstimator::new(fee_estimator);
 		let conf_target = self.closure_conf_target();
 		self.onchain_tx_handler.blocks_disconnected(
-			new_height, &broadcaster, conf_target, &self.destination_script, &bounded_fee_estimator, logger
+			new_height, &broadcaster, self.channel_id, conf_target, &self.destination_script,
+			&bounded_fee_estimator, logger
 		);
 
 		// Only attempt to broadcast the new commitment after the `block_disconnected` call above so that
 		// it doesn't get removed from the set of pending claims.
-		if should_broadcast_commitment {
+		if should_broadcast_commitment && !self.holder_commitment_stale {
 			self.queue_latest_holder_commitment_txn_for_broadcast(&broadcaster, &bounded_fee_estimator, logger, true);
 		}
 
@@ -5944,6 +6465,12 @@ impl<Signer: EcdsaChannelSigner> ChannelMonitorImpl<Signer> {
 
 		debug_assert!(!self.onchain_events_awaiting_threshold_conf.iter().any(|ref entry| entry.txid == *txid));
 
+		if let Some((spend_txid, _)) = self.delegated_anchor_spend_confirmed.as_ref() {
+			if spend_txid == txid {
+				self.delegated_anchor_spend_confirmed.take();
+			}
+		}
+
 		// TODO: Replace with `take_if` once our MSRV is >= 1.80.
 		let mut should_broadcast_commitment = false;
 		if let Some((alternative_funding_txid, _)) = self.alternative_funding_confirmed.as_ref() {
@@ -5963,12 +6490,13 @@ impl<Signer: EcdsaChannelSigner> ChannelMonitorImpl<Signer> {
 
 		let conf_target = self.closure_conf_target();
 		self.onchain_tx_handler.transaction_unconfirmed(
-			txid, &broadcaster, conf_target, &self.destination_script, fee_estimator, logger
+			txid, &broadcaster, self.channel_id, conf_target, &self.destination_script,
+			fee_estimator, logger
 		);
 
 		// Only attempt to broadcast the new commitment after the `transaction_unconfirmed` call above so
 		//  that it doesn't get removed from the set of pending claims.
-		if should_broadcast_commitment {
+		if should_broadcast_commitment && !self.holder_commitment_stale {
 			self.queue_latest_holder_commitment_txn_for_broadcast(&broadcaster, fee_estimator, logger, true);
 		}
 	}
@@ -6033,6 +6561,11 @@ impl<Signer: EcdsaChannelSigner> ChannelMonitorImpl<Signer> {
 	fn should_broadcast_holder_commitment_txn<L: Deref>(
 		&self, logger: &WithChannelMonitor<L>
 	) -> Option<PaymentHash> where L::Target: Logger {
+		// Our latest holder commitment transaction has been revoked, so broadcasting it would only
+		// allow our counterparty to claim all of the channel's funds.
+		if self.holder_commitment_stale {
+			return None;
+		}
 		// There's no need to broadcast our commitment transaction if we've seen one confirmed (even
 		// with 1 confirmation) as it'll be rejected as duplicate/conflicting.
 		if self.funding_spend_confirmed.is_some() ||
@@ -6644,6 +7177,9 @@ impl<'a, 'b, ES: EntropySource, SP: SignerProvider> ReadableArgs<(&'a ES, &'b SP
 		let mut alternative_funding_confirmed = None;
 		let mut is_manual_broadcast = RequiredWrapper(None);
 		let mut funding_seen_onchain = RequiredWrapper(None);
+		let mut delegated_anchor_spend_confirmed = None;
+		let mut counterparty_commitment_numbers = Some(new_hash_map());
+		let mut holder_commitment_stale = RequiredWrapper(None);
 		read_tlv_fields!(reader, {
 			(1, funding_spend_confirmed, option),
 			(3, htlcs_resolved_on_chain, optional_vec),
@@ -6666,6 +7202,9 @@ impl<'a, 'b, ES: EntropySource, SP: SignerProvider> ReadableArgs<(&'a ES, &'b SP
 			(34, alternative_funding_confirmed, option),
 			(35, is_manual_broadcast, (default_value, false)),
 			(37, funding_seen_onchain, (default_value, true)),
+			(39, delegated_anchor_spend_confirmed, option),
+			(41, counterparty_commitment_numbers, option),
+			(43, holder_commitment_stale, (default_value, false)),
 		});
 		// Note that `payment_preimages_with_info` was added (and is always written) in LDK 0.1, so
 		// we can use it to determine if this monitor was last written by LDK 0.1 or later.
@@ -6822,6 +7361,7 @@ impl<'a, 'b, ES: EntropySource, SP: SignerProvider> ReadableArgs<(&'a ES, &'b SP
 
 			lockdown_from_offchain,
 			holder_tx_signed,
+			holder_commitment_stale: holder_commitment_stale.0.unwrap(),
 			holder_pays_commitment_tx_fee,
 			funding_spend_seen: funding_spend_seen.unwrap(),
 			funding_spend_confirmed,
@@ -6841,6 +7381,8 @@ impl<'a, 'b, ES: EntropySource, SP: SignerProvider> ReadableArgs<(&'a ES, &'b SP
 			prev_holder_htlc_data,
 
 			alternative_funding_confirmed,
+			delegated_anchor_spend_confirmed,
+			counterparty_commitment_numbers: counterparty_commitment_numbers.unwrap(),
 
 			written_by_0_1_or_later,
 		});
@@ -80,7 +80,8 @@ impl_writeable_tlv_based!(BestBlock, {
 /// # Requirements
 ///
 /// Each block must be connected in chain order with one call to either
-/// [`Listen::block_connected`] or [`Listen::filtered_block_connected`]. If a call to the
+/// [`Listen::block_connected`] or [`Listen::filtered_block_connected`], or as part of a single
+/// call to [`Listen::filtered_blocks_connected`]. If a call to the
 /// [`Filter`] interface was made during block processing and further transaction(s) from the same
 /// block now match the filter, a second call to [`Listen::filtered_block_connected`] should be
 /// made immediately for the same block (prior to any other calls to the [`Listen`] interface).
@@ -105,6 +106,23 @@ pub trait Listen {
 		self.filtered_block_connected(&block.header, &txdata, height);
 	}
 
+	/// Notifies the listener that a batch of blocks were added, in chain order, with the
+	/// transaction data possibly filtered.
+	///
+	/// This is equivalent to calling [`Listen::filtered_block_connected`] for each block in turn,
+	/// but allows implementations to take locks and persist their state once per batch rather than
+	/// once per block, which significantly speeds up connecting many historical blocks, e.g., when
+	/// a node has been offline for a while.
+	///
+	/// Because any [`Filter`] calls made while processing the batch cannot be reflected in the
+	/// transaction data of later blocks in the same batch, blocks should only be batched if their
+	/// transaction data is unfiltered or no [`Filter`] is in use.
+	fn filtered_blocks_connected(&self, blocks: &[(&Header, &TransactionData, u32)]) {
+		for (header, txdata, height) in blocks.iter() {
+			self.filtered_block_connected(header, txdata, *height);
+		}
+	}
+
 	/// Notifies the listener that one or more blocks were removed in anticipation of a reorg.
 	///
 	/// The provided [`BestBlock`] is the new best block after disconnecting blocks in the reorg
@@ -344,6 +362,17 @@ pub trait Watch<ChannelSigner: EcdsaChannelSigner> {
 	fn release_pending_monitor_events(
 		&self,
 	) -> Vec<(OutPoint, ChannelId, Vec<MonitorEvent>, PublicKey)>;
+
+	/// Returns the txid of the latest holder commitment transaction the [`ChannelMonitor`] for the
+	/// channel identified by `channel_id` has broadcast, if any.
+	///
+	/// This is used to report the closing transaction to a [`ForceCloseObserver`] and is
+	/// defaulted to `None`.
+	///
+	/// [`ForceCloseObserver`]: crate::ln::force_close_observer::ForceCloseObserver
+	fn get_broadcast_holder_commitment_txid(&self, _channel_id: ChannelId) -> Option<Txid> {
+		None
+	}
 }
 
 /// The `Filter` trait defines behavior for indicating chain activity of interest pertaining to
@@ -402,7 +431,7 @@ pub trait Filter {
 ///
 /// [`ChannelMonitor`]: channelmonitor::ChannelMonitor
 /// [`ChannelMonitor::block_connected`]: channelmonitor::ChannelMonitor::block_connected
-#[derive(Clone, PartialEq, Eq, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct WatchedOutput {
 	/// First block where the transaction output may have been spent.
 	pub block_hash: Option<BlockHash>,
@@ -414,11 +443,102 @@ pub struct WatchedOutput {
 	pub script_pubkey: ScriptBuf,
 }
 
+/// The full set of on-chain data a [`ChannelMonitor`] needs its chain source to watch.
+///
+/// This is the same data which is provided to a [`Filter`] via [`Filter::register_tx`] and
+/// [`Filter::register_output`] as a [`ChannelMonitor`] is added and processes blocks, but may be
+/// queried at any time via [`ChannelMonitor::get_watch_items`] or
+/// [`ChainMonitor::list_watch_items`]. This allows chain sources which lose their registrations
+/// (e.g. an Electrum client after reconnecting to a server) to re-subscribe without replaying all
+/// historical [`Filter`] calls.
+///
+/// [`ChannelMonitor`]: channelmonitor::ChannelMonitor
+/// [`ChannelMonitor::get_watch_items`]: channelmonitor::ChannelMonitor::get_watch_items
+/// [`ChainMonitor::list_watch_items`]: chainmonitor::ChainMonitor::list_watch_items
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChannelWatchItems {
+	/// The channel ID of the channel the [`ChannelMonitor`] is monitoring.
+	///
+	/// [`ChannelMonitor`]: channelmonitor::ChannelMonitor
+	pub channel_id: ChannelId,
+	/// The node ID of the channel counterparty.
+	pub counterparty_node_id: PublicKey,
+	/// The funding transaction(s) of the channel, including any pending splice or RBF candidates,
+	/// along with the script of their funding output, as would be given to
+	/// [`Filter::register_tx`].
+	pub transactions: Vec<(Txid, ScriptBuf)>,
+	/// The outputs whose spends must be given to the [`ChannelMonitor`], as would be given to
+	/// [`Filter::register_output`].
+	///
+	/// Note that [`WatchedOutput::block_hash`] is always `None` here as we do not track the block
+	/// in which each output was created, except when included in a [`WatchSnapshot`], where it is
+	/// set if known.
+	///
+	/// [`ChannelMonitor`]: channelmonitor::ChannelMonitor
+	pub outputs: Vec<WatchedOutput>,
+}
+
+/// Everything a [`ChainMonitor`] needs its chain source to watch, as returned by
+/// [`ChainMonitor::watch_snapshot`].
+///
+/// This is intended to re-seed a chain source which was restarted or failed over to another
+/// backend, and thus may have lost both its [`Filter`] registrations and its view of which
+/// transactions have confirmed. Any registrations made after the snapshot was taken can be
+/// fetched via [`ChainMonitor::get_and_clear_watch_registrations`].
+///
+/// [`ChainMonitor`]: chainmonitor::ChainMonitor
+/// [`ChainMonitor::watch_snapshot`]: chainmonitor::ChainMonitor::watch_snapshot
+/// [`ChainMonitor::get_and_clear_watch_registrations`]: chainmonitor::ChainMonitor::get_and_clear_watch_registrations
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WatchSnapshot {
+	/// The lowest best block any [`ChannelMonitor`] has been synced to, or `None` if there are no
+	/// [`ChannelMonitor`]s.
+	///
+	/// A restarted chain source should ensure it hasn't missed any blocks since this one.
+	///
+	/// [`ChannelMonitor`]: channelmonitor::ChannelMonitor
+	pub best_block: Option<BestBlock>,
+	/// The transactions whose confirmation we need to be informed about if they are reorged out,
+	/// along with the height and, if known, the hash of the block they were confirmed in. See
+	/// [`Confirm::get_relevant_txids`].
+	pub relevant_txids: Vec<(Txid, u32, Option<BlockHash>)>,
+	/// The transactions and outputs to watch for each [`ChannelMonitor`], as returned by
+	/// [`ChainMonitor::list_watch_items`].
+	///
+	/// [`WatchedOutput::block_hash`] is set to the block in which the output was created if the
+	/// creating transaction is one of the [`Self::relevant_txids`] confirmed in a known block.
+	///
+	/// [`ChannelMonitor`]: channelmonitor::ChannelMonitor
+	/// [`ChainMonitor::list_watch_items`]: chainmonitor::ChainMonitor::list_watch_items
+	pub channels: Vec<ChannelWatchItems>,
+}
+
+/// A registration made with the [`Filter`] after a [`WatchSnapshot`] was taken, as returned by
+/// [`ChainMonitor::get_and_clear_watch_registrations`].
+///
+/// [`ChainMonitor::get_and_clear_watch_registrations`]: chainmonitor::ChainMonitor::get_and_clear_watch_registrations
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WatchRegistration {
+	/// A transaction to watch, as given to [`Filter::register_tx`].
+	Transaction {
+		/// The ID of the transaction.
+		txid: Txid,
+		/// The script of the transaction's output we're interested in.
+		script_pubkey: ScriptBuf,
+	},
+	/// An output to watch for spends, as given to [`Filter::register_output`].
+	Output(WatchedOutput),
+}
+
 impl<T: Listen> Listen for dyn core::ops::Deref<Target = T> {
 	fn filtered_block_connected(&self, header: &Header, txdata: &TransactionData, height: u32) {
 		(**self).filtered_block_connected(header, txdata, height);
 	}
 
+	fn filtered_blocks_connected(&self, blocks: &[(&Header, &TransactionData, u32)]) {
+		(**self).filtered_blocks_connected(blocks);
+	}
+
 	fn blocks_disconnected(&self, fork_point: BestBlock) {
 		(**self).blocks_disconnected(fork_point);
 	}
@@ -434,6 +554,11 @@ where
 		self.1.filtered_block_connected(header, txdata, height);
 	}
 
+	fn filtered_blocks_connected(&self, blocks: &[(&Header, &TransactionData, u32)]) {
+		self.0.filtered_blocks_connected(blocks);
+		self.1.filtered_blocks_connected(blocks);
+	}
+
 	fn blocks_disconnected(&self, fork_point: BestBlock) {
 		self.0.blocks_disconnected(fork_point);
 		self.1.blocks_disconnected(fork_point);
@@ -455,6 +580,13 @@ impl ClaimId {
 		}
 		ClaimId(Sha256::from_engine(engine).to_byte_array())
 	}
+	pub(crate) fn from_claim_ids<I: Iterator<Item = ClaimId>>(claim_ids: I) -> ClaimId {
+		let mut engine = Sha256::engine();
+		for claim_id in claim_ids {
+			engine.input(&claim_id.0);
+		}
+		ClaimId(Sha256::from_engine(engine).to_byte_array())
+	}
 	pub(crate) fn step_with_bytes(&self, bytes: &[u8]) -> ClaimId {
 		let mut engine = Sha256::engine();
 		engine.input(&self.0);
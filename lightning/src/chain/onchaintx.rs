@@ -23,16 +23,19 @@ use bitcoin::transaction::OutPoint as BitcoinOutPoint;
 use bitcoin::transaction::Transaction;
 
 use crate::chain::chaininterface::ConfirmationTarget;
-use crate::chain::chaininterface::{BroadcasterInterface, FeeEstimator, LowerBoundedFeeEstimator};
-use crate::chain::channelmonitor::ANTI_REORG_DELAY;
+use crate::chain::chaininterface::{
+	BroadcastContext, BroadcasterInterface, FeeEstimator, LowerBoundedFeeEstimator,
+};
+use crate::chain::channelmonitor::{PendingClaim, ANTI_REORG_DELAY};
 use crate::chain::package::{PackageSolvingData, PackageTemplate};
 use crate::chain::transaction::MaybeSignedTransaction;
 use crate::chain::ClaimId;
 use crate::ln::chan_utils::{
-	get_keyed_anchor_redeemscript, shared_anchor_script_pubkey, ChannelTransactionParameters,
-	HTLCOutputInCommitment, HolderCommitmentTransaction,
+	debug_assert_weight_estimate, get_keyed_anchor_redeemscript, shared_anchor_script_pubkey,
+	ChannelTransactionParameters, HTLCOutputInCommitment, HolderCommitmentTransaction,
 };
 use crate::ln::msgs::DecodeError;
+use crate::ln::types::ChannelId;
 use crate::sign::{ecdsa::EcdsaChannelSigner, EntropySource, HTLCDescriptor, SignerProvider};
 use crate::util::logger::Logger;
 use crate::util::ser::{
@@ -487,7 +490,7 @@ impl<ChannelSigner: EcdsaChannelSigner> OnchainTxHandler<ChannelSigner> {
 	#[rustfmt::skip]
 	pub(super) fn rebroadcast_pending_claims<B: Deref, F: Deref, L: Logger>(
 		&mut self, current_height: u32, feerate_strategy: FeerateStrategy, broadcaster: &B,
-		conf_target: ConfirmationTarget, destination_script: &Script,
+		channel_id: ChannelId, conf_target: ConfirmationTarget, destination_script: &Script,
 		fee_estimator: &LowerBoundedFeeEstimator<F>, logger: &L,
 	)
 	where
@@ -516,7 +519,12 @@ impl<ChannelSigner: EcdsaChannelSigner> OnchainTxHandler<ChannelSigner> {
 							if tx.is_fully_signed() {
 								let log_start = if feerate_was_bumped { "Broadcasting RBF-bumped" } else { "Rebroadcasting" };
 								log_info!(logger, "{} onchain {}", log_start, log_tx!(tx.0));
-								broadcaster.broadcast_transactions(&[&tx.0]);
+								let context = BroadcastContext {
+									broadcast_type: request.broadcast_type(),
+									channel_id: Some(channel_id),
+									urgency: Some(conf_target),
+								};
+								broadcaster.broadcast_transactions(&[&tx.0], context);
 							} else {
 								log_info!(logger, "Waiting for signature of unsigned onchain transaction {}", tx.0.compute_txid());
 							}
@@ -545,6 +553,33 @@ impl<ChannelSigner: EcdsaChannelSigner> OnchainTxHandler<ChannelSigner> {
 		self.pending_claim_requests.len() != 0
 	}
 
+	/// Returns the details of all pending claim requests that are not fully confirmed yet.
+	pub(super) fn get_pending_claims(&self, cur_height: u32) -> Vec<PendingClaim> {
+		let mut pending_claims = self
+			.pending_claim_requests
+			.iter()
+			.map(|(claim_id, request)| {
+				let confirmations = self
+					.onchain_events_awaiting_threshold_conf
+					.iter()
+					.find(|entry| match entry.event {
+						OnchainEvent::Claim { claim_id: id } => id == *claim_id,
+						_ => false,
+					})
+					.map(|entry| cur_height.saturating_add(1).saturating_sub(entry.height));
+				PendingClaim {
+					claim_id: *claim_id,
+					outpoints: request.outpoints().into_iter().cloned().collect(),
+					feerate_sat_per_1000_weight: request.previous_feerate(),
+					next_bump_height: request.timer(),
+					confirmations,
+				}
+			})
+			.collect::<Vec<_>>();
+		pending_claims.sort_unstable_by(|a, b| a.outpoints.cmp(&b.outpoints));
+		pending_claims
+	}
+
 	/// Lightning security model (i.e being able to redeem/timeout HTLC or penalize counterparty
 	/// onchain) lays on the assumption of claim transactions getting confirmed before timelock
 	/// expiration (CSV or CLTV following cases). In case of high-fee spikes, claim tx may get stuck
@@ -569,6 +604,8 @@ impl<ChannelSigner: EcdsaChannelSigner> OnchainTxHandler<ChannelSigner> {
 			debug_assert!(cached_request.is_malleable());
 			return None;
 		}
+		// Claims with a known deadline should pay more as it approaches.
+		let conf_target = cached_request.conf_target_for_deadline(conf_target, cur_height);
 		// If we've seen transaction inclusion in the chain for all outpoints in our request, we
 		// don't need to continue generating more claims. We'll keep tracking the request to fully
 		// remove it once it reaches the confirmation threshold, or to generate a new claim if the
@@ -630,6 +667,9 @@ impl<ChannelSigner: EcdsaChannelSigner> OnchainTxHandler<ChannelSigner> {
 					cur_height, self, Amount::from_sat(output_value), destination_script.into(), logger
 				).unwrap();
 				assert!(predicted_weight >= transaction.0.weight().to_wu());
+				if transaction.is_fully_signed() {
+					debug_assert_weight_estimate(&transaction.0, predicted_weight);
+				}
 				return Some((new_timer, new_feerate, OnchainClaim::Tx(transaction)));
 			}
 		} else {
@@ -763,8 +803,8 @@ impl<ChannelSigner: EcdsaChannelSigner> OnchainTxHandler<ChannelSigner> {
 	#[rustfmt::skip]
 	pub(super) fn update_claims_view_from_requests<B: Deref, F: Deref, L: Logger>(
 		&mut self, mut requests: Vec<PackageTemplate>, conf_height: u32, cur_height: u32,
-		broadcaster: &B, conf_target: ConfirmationTarget, destination_script: &Script,
-		fee_estimator: &LowerBoundedFeeEstimator<F>, logger: &L
+		broadcaster: &B, channel_id: ChannelId, conf_target: ConfirmationTarget,
+		destination_script: &Script, fee_estimator: &LowerBoundedFeeEstimator<F>, logger: &L
 	) where
 		B::Target: BroadcasterInterface,
 		F::Target: FeeEstimator,
@@ -863,7 +903,12 @@ impl<ChannelSigner: EcdsaChannelSigner> OnchainTxHandler<ChannelSigner> {
 					OnchainClaim::Tx(tx) => {
 						if tx.is_fully_signed() {
 							log_info!(logger, "Broadcasting onchain {}", log_tx!(tx.0));
-							broadcaster.broadcast_transactions(&[&tx.0]);
+							let context = BroadcastContext {
+								broadcast_type: req.broadcast_type(),
+								channel_id: Some(channel_id),
+								urgency: Some(conf_target),
+							};
+							broadcaster.broadcast_transactions(&[&tx.0], context);
 						} else {
 							log_info!(logger, "Waiting for signature of unsigned onchain transaction {}", tx.0.compute_txid());
 						}
@@ -914,7 +959,7 @@ impl<ChannelSigner: EcdsaChannelSigner> OnchainTxHandler<ChannelSigner> {
 	#[rustfmt::skip]
 	pub(super) fn update_claims_view_from_matched_txn<B: Deref, F: Deref, L: Logger>(
 		&mut self, txn_matched: &[&Transaction], conf_height: u32, conf_hash: BlockHash,
-		cur_height: u32, broadcaster: &B, conf_target: ConfirmationTarget,
+		cur_height: u32, broadcaster: &B, channel_id: ChannelId, conf_target: ConfirmationTarget,
 		destination_script: &Script, fee_estimator: &LowerBoundedFeeEstimator<F>, logger: &L
 	) where
 		B::Target: BroadcasterInterface,
@@ -1084,7 +1129,12 @@ impl<ChannelSigner: EcdsaChannelSigner> OnchainTxHandler<ChannelSigner> {
 					OnchainClaim::Tx(bump_tx) => {
 						if bump_tx.is_fully_signed() {
 							log_info!(logger, "Broadcasting RBF-bumped onchain {}", log_tx!(bump_tx.0));
-							broadcaster.broadcast_transactions(&[&bump_tx.0]);
+							let context = BroadcastContext {
+								broadcast_type: request.broadcast_type(),
+								channel_id: Some(channel_id),
+								urgency: Some(conf_target),
+							};
+							broadcaster.broadcast_transactions(&[&bump_tx.0], context);
 						} else {
 							log_info!(logger, "Waiting for signature of RBF-bumped unsigned onchain transaction {}",
 								bump_tx.0.compute_txid());
@@ -1114,6 +1164,7 @@ impl<ChannelSigner: EcdsaChannelSigner> OnchainTxHandler<ChannelSigner> {
 		&mut self,
 		txid: &Txid,
 		broadcaster: &B,
+		channel_id: ChannelId,
 		conf_target: ConfirmationTarget,
 		destination_script: &Script,
 		fee_estimator: &LowerBoundedFeeEstimator<F>,
@@ -1132,15 +1183,17 @@ impl<ChannelSigner: EcdsaChannelSigner> OnchainTxHandler<ChannelSigner> {
 
 		if let Some(height) = height {
 			self.blocks_disconnected(
-				height - 1, broadcaster, conf_target, destination_script, fee_estimator, logger,
+				height - 1, broadcaster, channel_id, conf_target, destination_script, fee_estimator,
+				logger,
 			);
 		}
 	}
 
 	#[rustfmt::skip]
 	pub(super) fn blocks_disconnected<B: Deref, F: Deref, L: Logger>(
-		&mut self, new_best_height: u32, broadcaster: &B, conf_target: ConfirmationTarget,
-		destination_script: &Script, fee_estimator: &LowerBoundedFeeEstimator<F>, logger: &L,
+		&mut self, new_best_height: u32, broadcaster: &B, channel_id: ChannelId,
+		conf_target: ConfirmationTarget, destination_script: &Script,
+		fee_estimator: &LowerBoundedFeeEstimator<F>, logger: &L,
 	)
 		where B::Target: BroadcasterInterface,
 			F::Target: FeeEstimator,
@@ -1187,7 +1240,12 @@ impl<ChannelSigner: EcdsaChannelSigner> OnchainTxHandler<ChannelSigner> {
 					OnchainClaim::Tx(bump_tx) => {
 						if bump_tx.is_fully_signed() {
 							log_info!(logger, "Broadcasting onchain {}", log_tx!(bump_tx.0));
-							broadcaster.broadcast_transactions(&[&bump_tx.0]);
+							let context = BroadcastContext {
+								broadcast_type: request.broadcast_type(),
+								channel_id: Some(channel_id),
+								urgency: Some(conf_target),
+							};
+							broadcaster.broadcast_transactions(&[&bump_tx.0], context);
 						} else {
 							log_info!(logger, "Waiting for signature of unsigned onchain transaction {}", bump_tx.0.compute_txid());
 						}
@@ -1281,6 +1339,7 @@ mod tests {
 	};
 	use crate::ln::channel_keys::{DelayedPaymentBasepoint, HtlcBasepoint, RevocationBasepoint};
 	use crate::ln::functional_test_utils::create_dummy_block;
+	use crate::ln::types::ChannelId;
 	use crate::sign::{ChannelDerivationParameters, ChannelSigner, HTLCDescriptor, InMemorySigner};
 	use crate::types::payment::{PaymentHash, PaymentPreimage};
 	use crate::util::test_utils::{TestBroadcaster, TestFeeEstimator, TestLogger};
@@ -1418,6 +1477,7 @@ mod tests {
 			1,
 			1,
 			&&broadcaster,
+			ChannelId([0; 32]),
 			ConfirmationTarget::UrgentOnChainSweep,
 			&destination_script,
 			&fee_estimator,
@@ -1442,6 +1502,7 @@ mod tests {
 			2,
 			2,
 			&&broadcaster,
+			ChannelId([0; 32]),
 			ConfirmationTarget::UrgentOnChainSweep,
 			&destination_script,
 			&fee_estimator,
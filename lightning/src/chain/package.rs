@@ -24,7 +24,7 @@ use bitcoin::transaction::{Transaction, TxIn, TxOut};
 use bitcoin::{Sequence, Witness};
 
 use crate::chain::chaininterface::{
-	compute_feerate_sat_per_1000_weight, ConfirmationTarget, FeeEstimator,
+	compute_feerate_sat_per_1000_weight, BroadcastType, ConfirmationTarget, FeeEstimator,
 	FEERATE_FLOOR_SATS_PER_KW, INCREMENTAL_RELAY_FEE_SAT_PER_1000_WEIGHT,
 };
 use crate::chain::channelmonitor::COUNTERPARTY_CLAIMABLE_WITHIN_BLOCKS_PINNABLE;
@@ -568,6 +568,15 @@ impl HolderHTLCOutput {
 				&htlc_sig, &htlc_descriptor.counterparty_sig, &htlc_descriptor.preimage,
 				&htlc_redeem_script, &channel_parameters.channel_type_features,
 			);
+			let channel_type_features = &channel_parameters.channel_type_features;
+			if !channel_type_features.supports_anchor_zero_fee_commitments() {
+				let predicted_weight = if htlc_descriptor.preimage.is_some() {
+					chan_utils::htlc_success_tx_weight(channel_type_features)
+				} else {
+					chan_utils::htlc_timeout_tx_weight(channel_type_features)
+				};
+				chan_utils::debug_assert_weight_estimate(&htlc_tx, predicted_weight);
+			}
 		}
 
 		Some(MaybeSignedTransaction(htlc_tx))
@@ -1251,6 +1260,19 @@ impl PackageTemplate {
 	pub(crate) fn is_malleable(&self) -> bool {
 		matches!(self.malleability, PackageMalleability::Malleable(..))
 	}
+	/// The kind of transaction claiming this package, as reported to the broadcaster. Packages only
+	/// aggregate inputs from the same transaction tree, so looking at the first one suffices.
+	pub(crate) fn broadcast_type(&self) -> BroadcastType {
+		match self.inputs.first().map(|(_, input)| input) {
+			Some(PackageSolvingData::RevokedOutput(_))
+			| Some(PackageSolvingData::RevokedHTLCOutput(_)) => BroadcastType::Justice,
+			Some(PackageSolvingData::HolderFundingOutput(_)) => BroadcastType::UnilateralClose,
+			Some(PackageSolvingData::CounterpartyOfferedHTLCOutput(_))
+			| Some(PackageSolvingData::CounterpartyReceivedHTLCOutput(_))
+			| Some(PackageSolvingData::HolderHTLCOutput(_))
+			| None => BroadcastType::HtlcClaim,
+		}
+	}
 	pub(crate) fn previous_feerate(&self) -> u64 {
 		self.feerate_previous
 	}
@@ -1428,6 +1450,78 @@ impl PackageTemplate {
 			return None;
 		} else { panic!("API Error: Package must not be inputs empty"); }
 	}
+
+	/// Gets the height by which `input` must be claimed, as after it our counterparty may be able
+	/// to claim it instead, if any.
+	fn input_claim_deadline(&self, input: &PackageSolvingData) -> Option<u32> {
+		match input {
+			PackageSolvingData::RevokedOutput(_) => {
+				// Revoked Outputs will become spendable by our counterparty at the height where
+				// the CSV expires, which is also our `counterparty_spendable_height`.
+				Some(self.counterparty_spendable_height)
+			},
+			PackageSolvingData::RevokedHTLCOutput(_) => {
+				// Revoked HTLC Outputs may be spendable by our counterparty right now, but after
+				// they spend them they still have to wait for an additional CSV delta before they
+				// can claim the full funds. Thus, there's no deadline until the HTLC output is
+				// spent, creating a `RevokedOutput`.
+				None
+			},
+			PackageSolvingData::CounterpartyOfferedHTLCOutput(outp) => {
+				// Incoming HTLCs being claimed by preimage should be claimed by the time their
+				// CLTV unlocks.
+				Some(outp.htlc.cltv_expiry)
+			},
+			PackageSolvingData::HolderHTLCOutput(outp) if outp.preimage.is_some() => {
+				// We have the same deadline here as for `CounterpartyOfferedHTLCOutput`. Note that
+				// `outp.cltv_expiry` is always 0 in this case, but `counterparty_spendable_height`
+				// holds the real HTLC expiry.
+				Some(self.counterparty_spendable_height)
+			},
+			PackageSolvingData::CounterpartyReceivedHTLCOutput(outp) => {
+				// Outgoing HTLCs being claimed through their timeout should be claimed fast enough
+				// to allow us to claim before the CLTV lock expires on the inbound edge (assuming
+				// the HTLC was forwarded).
+				Some(outp.htlc.cltv_expiry + MIN_CLTV_EXPIRY_DELTA as u32)
+			},
+			PackageSolvingData::HolderHTLCOutput(outp) => {
+				// We have the same deadline for holder timeout claims as for
+				// `CounterpartyReceivedHTLCOutput`
+				Some(outp.cltv_expiry + MIN_CLTV_EXPIRY_DELTA as u32)
+			},
+			PackageSolvingData::HolderFundingOutput(outp) => {
+				// Our commitment transaction must confirm in time for us to claim the HTLCs on it,
+				// the earliest of which must be claimed by its CLTV expiry.
+				outp.commitment_tx
+					.as_ref()
+					.and_then(|tx| tx.nondust_htlcs().iter().map(|htlc| htlc.cltv_expiry).min())
+			},
+		}
+	}
+
+	/// Gets the height by which this package must be claimed, as after it our counterparty may be
+	/// able to claim any of its inputs instead, if any.
+	pub(crate) fn claim_deadline(&self) -> Option<u32> {
+		self.inputs.iter().filter_map(|(_, input)| self.input_claim_deadline(input)).min()
+	}
+
+	/// Gets the [`ConfirmationTarget`] to use when claiming this package at `current_height`,
+	/// replacing [`ConfirmationTarget::UrgentOnChainSweep`] with its deadline-aware counterpart if
+	/// we know the deadline by which the package must be claimed.
+	pub(crate) fn conf_target_for_deadline(
+		&self, conf_target: ConfirmationTarget, current_height: u32,
+	) -> ConfirmationTarget {
+		if conf_target != ConfirmationTarget::UrgentOnChainSweep {
+			return conf_target;
+		}
+		match self.claim_deadline() {
+			Some(deadline) => ConfirmationTarget::UrgentOnChainSweepWithDeadline {
+				blocks_until_deadline: deadline.saturating_sub(current_height),
+			},
+			None => conf_target,
+		}
+	}
+
 	/// Gets the next height at which we should fee-bump this package, assuming we can do so and
 	/// the package is last fee-bumped at `current_height`.
 	///
@@ -1447,55 +1541,6 @@ impl PackageTemplate {
 		};
 		for (_, input) in self.inputs.iter() {
 			match input {
-				PackageSolvingData::RevokedOutput(_) => {
-					// Revoked Outputs will become spendable by our counterparty at the height
-					// where the CSV expires, which is also our `counterparty_spendable_height`.
-					height_timer = cmp::min(
-						height_timer,
-						timer_for_target_conf(self.counterparty_spendable_height),
-					);
-				},
-				PackageSolvingData::RevokedHTLCOutput(_) => {
-					// Revoked HTLC Outputs may be spendable by our counterparty right now, but
-					// after they spend them they still have to wait for an additional CSV delta
-					// before they can claim the full funds. Thus, we leave the timer at
-					// `LOW_FREQUENCY_BUMP_INTERVAL` until the HTLC output is spent, creating a
-					// `RevokedOutput`.
-				},
-				PackageSolvingData::CounterpartyOfferedHTLCOutput(outp) => {
-					// Incoming HTLCs being claimed by preimage should be claimed by the time their
-					// CLTV unlocks.
-					height_timer = cmp::min(
-						height_timer,
-						timer_for_target_conf(outp.htlc.cltv_expiry),
-					);
-				},
-				PackageSolvingData::HolderHTLCOutput(outp) if outp.preimage.is_some() => {
-					// We have the same deadline here as for `CounterpartyOfferedHTLCOutput`. Note
-					// that `outp.cltv_expiry` is always 0 in this case, but
-					// `counterparty_spendable_height` holds the real HTLC expiry.
-					height_timer = cmp::min(
-						height_timer,
-						timer_for_target_conf(self.counterparty_spendable_height),
-					);
-				},
-				PackageSolvingData::CounterpartyReceivedHTLCOutput(outp) => {
-					// Outgoing HTLCs being claimed through their timeout should be claimed fast
-					// enough to allow us to claim before the CLTV lock expires on the inbound
-					// edge (assuming the HTLC was forwarded).
-					height_timer = cmp::min(
-						height_timer,
-						timer_for_target_conf(outp.htlc.cltv_expiry + MIN_CLTV_EXPIRY_DELTA as u32),
-					);
-				},
-				PackageSolvingData::HolderHTLCOutput(outp) => {
-					// We have the same deadline for holder timeout claims as for
-					// `CounterpartyReceivedHTLCOutput`
-					height_timer = cmp::min(
-						height_timer,
-						timer_for_target_conf(outp.cltv_expiry + MIN_CLTV_EXPIRY_DELTA as u32),
-					);
-				},
 				PackageSolvingData::HolderFundingOutput(_) => {
 					// We should apply a smart heuristic here based on the HTLCs in the commitment
 					// transaction, but we don't currently have that information available so
@@ -1503,6 +1548,11 @@ impl PackageTemplate {
 					height_timer =
 						cmp::min(height_timer, current_height + HIGH_FREQUENCY_BUMP_INTERVAL);
 				},
+				_ => {
+					if let Some(deadline) = self.input_claim_deadline(input) {
+						height_timer = cmp::min(height_timer, timer_for_target_conf(deadline));
+					}
+				},
 			}
 		}
 		height_timer
@@ -2169,6 +2219,26 @@ mod tests {
 		assert_eq!(package.timer(), 101);
 	}
 
+	#[test]
+	#[rustfmt::skip]
+	fn test_package_deadline_conf_target() {
+		let revk_htlc_outp = dumb_revk_htlc_output!();
+		let package = PackageTemplate::build_package(fake_txid(1), 0, revk_htlc_outp, 1000);
+		assert_eq!(package.claim_deadline(), None);
+		assert_eq!(package.conf_target_for_deadline(ConfirmationTarget::UrgentOnChainSweep, 900), ConfirmationTarget::UrgentOnChainSweep);
+
+		let counterparty_outp = dumb_counterparty_received_output!(1_000_000, 1000, ChannelTypeFeatures::only_static_remote_key());
+		let package = PackageTemplate::build_package(fake_txid(1), 0, counterparty_outp, 1000);
+		let deadline = 1000 + MIN_CLTV_EXPIRY_DELTA as u32;
+		assert_eq!(package.claim_deadline(), Some(deadline));
+		assert_eq!(package.conf_target_for_deadline(ConfirmationTarget::UrgentOnChainSweep, 1000),
+			ConfirmationTarget::UrgentOnChainSweepWithDeadline { blocks_until_deadline: MIN_CLTV_EXPIRY_DELTA as u32 });
+		assert_eq!(package.conf_target_for_deadline(ConfirmationTarget::UrgentOnChainSweep, deadline + 10),
+			ConfirmationTarget::UrgentOnChainSweepWithDeadline { blocks_until_deadline: 0 });
+		// Non-urgent claims are left as-is.
+		assert_eq!(package.conf_target_for_deadline(ConfirmationTarget::OutputSpendingFee, 1000), ConfirmationTarget::OutputSpendingFee);
+	}
+
 	#[test]
 	#[rustfmt::skip]
 	fn test_package_amounts() {
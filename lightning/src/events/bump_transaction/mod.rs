@@ -14,11 +14,14 @@
 pub mod sync;
 
 use alloc::collections::BTreeMap;
+use core::cmp;
 use core::future::Future;
 use core::ops::Deref;
+use core::sync::atomic::{AtomicU32, Ordering};
 
 use crate::chain::chaininterface::{
-	compute_feerate_sat_per_1000_weight, fee_for_weight, BroadcasterInterface,
+	compute_feerate_sat_per_1000_weight, fee_for_weight, BroadcastContext, BroadcastType,
+	BroadcasterInterface, ConfirmationTarget,
 };
 use crate::chain::ClaimId;
 use crate::io_extras::sink;
@@ -255,6 +258,148 @@ pub enum BumpTransactionEvent {
 	},
 }
 
+impl BumpTransactionEvent {
+	/// Returns the unique identifier for the claim this event pertains to.
+	pub fn claim_id(&self) -> ClaimId {
+		match self {
+			BumpTransactionEvent::ChannelClose { claim_id, .. } => *claim_id,
+			BumpTransactionEvent::HTLCResolution { claim_id, .. } => *claim_id,
+		}
+	}
+
+	/// Returns the feerate, in satoshis per 1000 weight units, LDK determined the resulting
+	/// transaction (package) should target.
+	pub fn target_feerate_sat_per_1000_weight(&self) -> u32 {
+		match self {
+			BumpTransactionEvent::ChannelClose {
+				package_target_feerate_sat_per_1000_weight,
+				..
+			} => *package_target_feerate_sat_per_1000_weight,
+			BumpTransactionEvent::HTLCResolution { target_feerate_sat_per_1000_weight, .. } => {
+				*target_feerate_sat_per_1000_weight
+			},
+		}
+	}
+
+	/// Returns the lowest CLTV expiry of the HTLCs pending resolution, if any.
+	///
+	/// Once this height is reached, the counterparty may be able to claim an HTLC we're owed, or
+	/// we may be forced to close an upstream channel to claim an HTLC we've forwarded, making
+	/// timely confirmation increasingly urgent as it approaches.
+	pub fn earliest_htlc_expiry(&self) -> Option<u32> {
+		match self {
+			BumpTransactionEvent::ChannelClose { pending_htlcs, .. } => {
+				pending_htlcs.iter().map(|htlc| htlc.cltv_expiry).min()
+			},
+			BumpTransactionEvent::HTLCResolution { htlc_descriptors, .. } => {
+				htlc_descriptors.iter().map(|descriptor| descriptor.htlc.cltv_expiry).min()
+			},
+		}
+	}
+
+	/// Returns the total value of the HTLCs pending resolution.
+	pub fn htlc_value(&self) -> Amount {
+		let htlc_value_msat = match self {
+			BumpTransactionEvent::ChannelClose { pending_htlcs, .. } => {
+				pending_htlcs.iter().map(|htlc| htlc.amount_msat).sum::<u64>()
+			},
+			BumpTransactionEvent::HTLCResolution { htlc_descriptors, .. } => {
+				htlc_descriptors.iter().map(|descriptor| descriptor.htlc.amount_msat).sum()
+			},
+		};
+		Amount::from_sat(htlc_value_msat / 1000)
+	}
+}
+
+/// A policy consulted by the [`BumpTransactionEventHandler`] before it commits any funds to
+/// fee-bumping a claim, allowing users to override LDK's fee-bumping strategy.
+///
+/// LDK generates a new [`BumpTransactionEvent`] for a claim, identified by its [`ClaimId`], with an
+/// increasing feerate each time the previous attempt failed to confirm in time, with the feerate
+/// growing more aggressively as the claim's deadline approaches. Implementations can use the
+/// event's [`BumpTransactionEvent::target_feerate_sat_per_1000_weight`],
+/// [`BumpTransactionEvent::earliest_htlc_expiry`], and [`BumpTransactionEvent::htlc_value`] to
+/// implement their own bump schedule, e.g. to avoid spending more on claiming small HTLCs than
+/// they're worth.
+///
+/// Note that any feerate returned must still adhere to the [Replace-By-Fee
+/// rules](https://github.com/bitcoin/bitcoin/blob/master/doc/policy/mempool-replacements.md) with
+/// respect to prior attempts at the same claim for the resulting transaction to be accepted into
+/// the mempool.
+pub trait FeeBumpPolicy {
+	/// Returns the feerate, in satoshis per 1000 weight units, the transaction (package) resulting
+	/// from handling `event` should target, or `None` if `event` should be ignored entirely.
+	///
+	/// By default, this returns [`BumpTransactionEvent::target_feerate_sat_per_1000_weight`].
+	fn target_feerate_sat_per_1000_weight(&self, event: &BumpTransactionEvent) -> Option<u32> {
+		Some(event.target_feerate_sat_per_1000_weight())
+	}
+
+	/// Returns the maximum total fee which may be paid by the transaction(s) resulting from
+	/// handling `event`, or `None` if no limit should be applied. If the fee needed to meet the
+	/// target feerate exceeds this, no transaction will be broadcast.
+	///
+	/// Note that for [`BumpTransactionEvent::ChannelClose`] this only covers the fee paid by the
+	/// child anchor transaction, and not the fee pre-allocated to the commitment transaction. For
+	/// [`BumpTransactionEvent::HTLCResolution`] the HTLCs may be claimed across several
+	/// transactions, in which case this limits the sum of their fees.
+	///
+	/// By default, no limit is applied.
+	fn max_total_fee(&self, _event: &BumpTransactionEvent) -> Option<Amount> {
+		None
+	}
+}
+
+/// A [`FeeBumpPolicy`] which follows LDK's fee-bumping strategy without any limits.
+pub struct DefaultFeeBumpPolicy;
+
+impl FeeBumpPolicy for DefaultFeeBumpPolicy {}
+
+/// Determines how the [`BumpTransactionEventHandler`] spends the shared pay-to-anchor (P2A) output
+/// of our commitment transactions in zero-fee commitment channels.
+///
+/// Unlike the keyed anchors of `option_anchors_zero_fee_htlc_tx` channels, a P2A anchor can be
+/// spent by anyone, allowing our counterparty or an external fee-bumping service to get our
+/// commitment transaction confirmed on our behalf.
+///
+/// This has no effect on channels with keyed anchors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum P2AAnchorSpendStrategy {
+	/// Spend the P2A anchor ourselves as soon as we handle a
+	/// [`BumpTransactionEvent::ChannelClose`]. This is the default.
+	Immediate,
+	/// Give our counterparty the given number of blocks to get the commitment transaction
+	/// confirmed before spending the P2A anchor ourselves.
+	///
+	/// Blocks are counted from the height at which we first handled a
+	/// [`BumpTransactionEvent::ChannelClose`] for a claim, as last provided to
+	/// [`BumpTransactionEventHandler::best_block_updated`]. We stop waiting early if any HTLC
+	/// pending resolution would otherwise expire before we spend the anchor. Until the best block
+	/// height has been provided, we can't tell how long we've waited and thus behave as with
+	/// [`Self::Immediate`].
+	WaitForCounterparty {
+		/// The number of blocks to wait before spending the P2A anchor ourselves.
+		blocks: u32,
+	},
+	/// Never spend the P2A anchor ourselves, delegating fee-bumping to an external service.
+	///
+	/// [`BumpTransactionEvent::ChannelClose`] events for zero-fee commitment channels are ignored
+	/// by the [`BumpTransactionEventHandler`] and should instead be forwarded to the external
+	/// service, which can spend the anchor using the event's
+	/// [`AnchorDescriptor::unsigned_tx_input`].
+	Delegate,
+}
+
+impl Default for P2AAnchorSpendStrategy {
+	fn default() -> Self {
+		P2AAnchorSpendStrategy::Immediate
+	}
+}
+
+/// The number of blocks after which we stop tracking when we first saw a claim while waiting for
+/// our counterparty to spend a P2A anchor.
+const P2A_CLAIM_TRACKING_EXPIRY_BLOCKS: u32 = 2016;
+
 /// An input that must be included in a transaction when performing coin selection through
 /// [`CoinSelectionSource::select_confirmed_utxos`]. It is guaranteed to be a SegWit input, so it
 /// must have an empty [`TxIn::script_sig`] when spent.
@@ -694,32 +839,143 @@ where
 ///
 /// [`Event::BumpTransaction`]: crate::events::Event::BumpTransaction
 // Note that updates to documentation on this struct should be copied to the synchronous version.
-pub struct BumpTransactionEventHandler<B: Deref, C: Deref, SP: Deref, L: Deref>
+pub struct BumpTransactionEventHandler<B: Deref, C: Deref, SP: Deref, FP: Deref, L: Deref>
 where
 	B::Target: BroadcasterInterface,
 	C::Target: CoinSelectionSource,
 	SP::Target: SignerProvider,
+	FP::Target: FeeBumpPolicy,
 	L::Target: Logger,
 {
 	broadcaster: B,
 	utxo_source: C,
 	signer_provider: SP,
+	fee_bump_policy: FP,
 	logger: L,
 	secp: Secp256k1<secp256k1::All>,
+	p2a_anchor_spend_strategy: P2AAnchorSpendStrategy,
+	/// The best block height as last provided to [`Self::best_block_updated`], or zero if unknown.
+	best_block_height: AtomicU32,
+	/// The height at which we first handled each P2A anchor claim we're waiting to spend.
+	p2a_claims_first_seen: Mutex<HashMap<ClaimId, u32>>,
+	max_htlc_tx_weight: Option<u64>,
 }
 
-impl<B: Deref, C: Deref, SP: Deref, L: Deref> BumpTransactionEventHandler<B, C, SP, L>
+impl<B: Deref, C: Deref, SP: Deref, FP: Deref, L: Deref>
+	BumpTransactionEventHandler<B, C, SP, FP, L>
 where
 	B::Target: BroadcasterInterface,
 	C::Target: CoinSelectionSource,
 	SP::Target: SignerProvider,
+	FP::Target: FeeBumpPolicy,
 	L::Target: Logger,
 {
 	/// Returns a new instance capable of handling [`Event::BumpTransaction`] events.
 	///
+	/// The `fee_bump_policy` is consulted before fee-bumping any claim. Use
+	/// [`DefaultFeeBumpPolicy`] to follow LDK's fee-bumping strategy without any limits.
+	///
 	/// [`Event::BumpTransaction`]: crate::events::Event::BumpTransaction
-	pub fn new(broadcaster: B, utxo_source: C, signer_provider: SP, logger: L) -> Self {
-		Self { broadcaster, utxo_source, signer_provider, logger, secp: Secp256k1::new() }
+	pub fn new(
+		broadcaster: B, utxo_source: C, signer_provider: SP, fee_bump_policy: FP, logger: L,
+	) -> Self {
+		Self {
+			broadcaster,
+			utxo_source,
+			signer_provider,
+			fee_bump_policy,
+			logger,
+			secp: Secp256k1::new(),
+			p2a_anchor_spend_strategy: P2AAnchorSpendStrategy::default(),
+			best_block_height: AtomicU32::new(0),
+			p2a_claims_first_seen: Mutex::new(new_hash_map()),
+			max_htlc_tx_weight: None,
+		}
+	}
+
+	/// Sets the [`P2AAnchorSpendStrategy`] used for zero-fee commitment channels, which defaults to
+	/// [`P2AAnchorSpendStrategy::Immediate`].
+	pub fn with_p2a_anchor_spend_strategy(mut self, strategy: P2AAnchorSpendStrategy) -> Self {
+		self.p2a_anchor_spend_strategy = strategy;
+		self
+	}
+
+	/// Sets the maximum weight of each transaction claiming HTLCs, including the inputs and
+	/// change output added through coin selection. HTLCs pending resolution for a channel will be
+	/// split across as many transactions as needed to satisfy it.
+	///
+	/// Smaller transactions may confirm independently of each other when competing for block
+	/// space, while larger transactions save on fees by amortizing the fixed transaction overhead
+	/// and wallet inputs across more HTLCs. By default, and if larger, the standardness limit of
+	/// the transaction's version is used instead, i.e., [`MAX_STANDARD_TX_WEIGHT`] or, for zero-fee
+	/// commitment channels, [`TRUC_MAX_WEIGHT`].
+	///
+	/// Note that each transaction will always claim at least one HTLC, so a value too small to
+	/// accommodate a single HTLC and the wallet's inputs will result in failed claims.
+	pub fn with_max_htlc_tx_weight(mut self, max_htlc_tx_weight: u64) -> Self {
+		self.max_htlc_tx_weight = Some(max_htlc_tx_weight);
+		self
+	}
+
+	/// Notifies the handler of the current best block height.
+	///
+	/// This must be called as new blocks are connected when using
+	/// [`P2AAnchorSpendStrategy::WaitForCounterparty`], and is otherwise unnecessary. Until it is
+	/// first called, P2A anchors are spent immediately.
+	pub fn best_block_updated(&self, height: u32) {
+		self.best_block_height.store(height, Ordering::Release);
+		if let P2AAnchorSpendStrategy::WaitForCounterparty { blocks } =
+			self.p2a_anchor_spend_strategy
+		{
+			let expiry = blocks.saturating_add(P2A_CLAIM_TRACKING_EXPIRY_BLOCKS);
+			self.p2a_claims_first_seen
+				.lock()
+				.unwrap()
+				.retain(|_, first_seen| first_seen.saturating_add(expiry) > height);
+		}
+	}
+
+	/// Returns whether we should spend the anchor output to bump the fee of the commitment
+	/// transaction in a [`BumpTransactionEvent::ChannelClose`], according to our
+	/// [`P2AAnchorSpendStrategy`].
+	fn should_spend_anchor(&self, event: &BumpTransactionEvent) -> bool {
+		let (claim_id, anchor_descriptor) = match event {
+			BumpTransactionEvent::ChannelClose { claim_id, anchor_descriptor, .. } => {
+				(claim_id, anchor_descriptor)
+			},
+			BumpTransactionEvent::HTLCResolution { .. } => return true,
+		};
+		let channel_type = &anchor_descriptor
+			.channel_derivation_parameters
+			.transaction_parameters
+			.channel_type_features;
+		if !channel_type.supports_anchor_zero_fee_commitments() {
+			return true;
+		}
+		match self.p2a_anchor_spend_strategy {
+			P2AAnchorSpendStrategy::Immediate => true,
+			P2AAnchorSpendStrategy::Delegate => false,
+			P2AAnchorSpendStrategy::WaitForCounterparty { blocks } => {
+				let height = self.best_block_height.load(Ordering::Acquire);
+				if height == 0 {
+					// Rather than risk waiting forever, spend the anchor right away if we don't know
+					// how many blocks have passed since we first saw the claim.
+					log_warn!(
+						self.logger,
+						"Spending P2A anchor (claim_id = {}) immediately as the best block height is unknown",
+						log_bytes!(claim_id.0)
+					);
+					return true;
+				}
+				let mut claims_first_seen = self.p2a_claims_first_seen.lock().unwrap();
+				let first_seen = *claims_first_seen.entry(*claim_id).or_insert(height);
+				let spend_height = first_seen.saturating_add(blocks);
+				let htlc_deadline_reached = event
+					.earliest_htlc_expiry()
+					.map_or(false, |expiry| height.saturating_add(blocks) >= expiry);
+				height >= spend_height || htlc_deadline_reached
+			},
+		}
 	}
 
 	/// Updates a transaction with the result of a successful coin selection attempt.
@@ -765,7 +1021,8 @@ where
 	/// transaction spending an anchor output of the commitment transaction to bump its fee and
 	/// broadcasts them to the network as a package.
 	async fn handle_channel_close(
-		&self, claim_id: ClaimId, package_target_feerate_sat_per_1000_weight: u32,
+		&self, channel_id: ChannelId, claim_id: ClaimId,
+		package_target_feerate_sat_per_1000_weight: u32, max_total_fee: Option<Amount>,
 		commitment_tx: &Transaction, commitment_tx_fee_sat: u64,
 		anchor_descriptor: &AnchorDescriptor,
 	) -> Result<(), ()> {
@@ -788,7 +1045,12 @@ where
 			log_debug!(self.logger, "Pre-signed commitment {} already has feerate {} sat/kW above required {} sat/kW, broadcasting.",
 				commitment_tx.compute_txid(), commitment_tx_feerate_sat_per_1000_weight,
 				package_target_feerate_sat_per_1000_weight);
-			self.broadcaster.broadcast_transactions(&[&commitment_tx]);
+			let context = BroadcastContext {
+				broadcast_type: BroadcastType::UnilateralClose,
+				channel_id: Some(channel_id),
+				urgency: Some(ConfirmationTarget::UrgentOnChainSweep),
+			};
+			self.broadcaster.broadcast_transactions(&[&commitment_tx], context);
 			return Ok(());
 		}
 
@@ -902,6 +1164,15 @@ where
 				}
 			}
 
+			if let Some(max_total_fee) = max_total_fee {
+				let anchor_tx_fee = package_fee - commitment_tx_fee_sat;
+				if anchor_tx_fee > max_total_fee {
+					log_error!(self.logger, "Anchor transaction {} would pay a fee of {} exceeding the maximum of {} allowed by our fee bump policy",
+						anchor_txid, anchor_tx_fee, max_total_fee);
+					return Err(());
+				}
+			}
+
 			log_debug!(self.logger, "Signing anchor transaction {}", anchor_txid);
 			anchor_tx = self.utxo_source.sign_psbt(anchor_psbt).await?;
 
@@ -955,7 +1226,12 @@ where
 				anchor_txid,
 				commitment_tx.compute_txid()
 			);
-			self.broadcaster.broadcast_transactions(&[&commitment_tx, &anchor_tx]);
+			let context = BroadcastContext {
+				broadcast_type: BroadcastType::AnchorBump,
+				channel_id: Some(channel_id),
+				urgency: Some(ConfirmationTarget::UrgentOnChainSweep),
+			};
+			self.broadcaster.broadcast_transactions(&[&commitment_tx, &anchor_tx], context);
 			return Ok(());
 		}
 	}
@@ -963,8 +1239,8 @@ where
 	/// Handles a [`BumpTransactionEvent::HTLCResolution`] event variant by producing a
 	/// fully-signed, fee-bumped HTLC transaction that is broadcast to the network.
 	async fn handle_htlc_resolution(
-		&self, claim_id: ClaimId, target_feerate_sat_per_1000_weight: u32,
-		htlc_descriptors: &[HTLCDescriptor], tx_lock_time: LockTime,
+		&self, channel_id: ChannelId, claim_id: ClaimId, target_feerate_sat_per_1000_weight: u32,
+		max_total_fee: Option<Amount>, htlc_descriptors: &[HTLCDescriptor], tx_lock_time: LockTime,
 	) -> Result<(), ()> {
 		let channel_type = &htlc_descriptors[0]
 			.channel_derivation_parameters
@@ -999,6 +1275,7 @@ where
 			// and 483 * 705 ~= 341_000, and 341_000 < 400_000.
 			MAX_STANDARD_TX_WEIGHT as u64
 		};
+		let max_tx_weight = cmp::min(max_tx_weight, self.max_htlc_tx_weight.unwrap_or(u64::MAX));
 		// A 1-input 1-output transaction, both p2wpkh is 438 WU.
 		// This is just an initial budget, we increase it further below in case the user can't satisfy it.
 		const USER_COINS_WEIGHT_BUDGET: u64 = 1000;
@@ -1006,6 +1283,10 @@ where
 		let mut broadcasted_htlcs = 0;
 		let mut batch_size = htlc_descriptors.len() - broadcasted_htlcs;
 		let mut utxo_id = claim_id;
+		let mut total_fee = Amount::ZERO;
+		// We only broadcast once all batches have been built and signed, so that we don't end up
+		// with only some of the HTLCs claimed if a later batch exceeds our fee bump policy.
+		let mut htlc_txs = Vec::new();
 
 		while broadcasted_htlcs < htlc_descriptors.len() {
 			let mut htlc_tx = Transaction {
@@ -1028,7 +1309,9 @@ where
 				} else {
 					chan_utils::aggregated_htlc_timeout_input_output_pair_weight(channel_type)
 				};
-				if htlc_weight_sum + input_output_weight >= max_tx_weight - USER_COINS_WEIGHT_BUDGET
+				if !must_spend.is_empty()
+					&& htlc_weight_sum + input_output_weight
+						>= max_tx_weight.saturating_sub(USER_COINS_WEIGHT_BUDGET)
 				{
 					break;
 				}
@@ -1113,6 +1396,22 @@ where
 
 			self.process_coin_selection(&mut htlc_tx, &coin_selection);
 
+			if let Some(max_total_fee) = max_total_fee {
+				let batch_input_amount = selected_htlcs
+					.iter()
+					.map(|htlc_descriptor| htlc_descriptor.previous_utxo(&self.secp).value)
+					.chain(coin_selection.confirmed_utxos.iter().map(|utxo| utxo.output.value))
+					.sum::<Amount>();
+				let batch_output_amount =
+					htlc_tx.output.iter().map(|output| output.value).sum::<Amount>();
+				total_fee += batch_input_amount - batch_output_amount;
+				if total_fee > max_total_fee {
+					log_error!(self.logger, "HTLC transactions would pay a total fee of {} exceeding the maximum of {} allowed by our fee bump policy",
+						total_fee, max_total_fee);
+					return Err(());
+				}
+			}
+
 			// construct psbt
 			let mut htlc_psbt = Psbt::from_unsigned_tx(htlc_tx).unwrap();
 			// add witness_utxo to htlc inputs
@@ -1187,8 +1486,17 @@ where
 				assert!(htlc_tx.weight().to_wu() < MAX_STANDARD_TX_WEIGHT as u64);
 			}
 
+			htlc_txs.push(htlc_tx);
+		}
+
+		for htlc_tx in htlc_txs {
 			log_info!(self.logger, "Broadcasting {}", log_tx!(htlc_tx));
-			self.broadcaster.broadcast_transactions(&[&htlc_tx]);
+			let context = BroadcastContext {
+				broadcast_type: BroadcastType::HtlcClaim,
+				channel_id: Some(channel_id),
+				urgency: Some(ConfirmationTarget::UrgentOnChainSweep),
+			};
+			self.broadcaster.broadcast_transactions(&[&htlc_tx], context);
 		}
 
 		Ok(())
@@ -1196,10 +1504,31 @@ where
 
 	/// Handles all variants of [`BumpTransactionEvent`].
 	pub async fn handle_event(&self, event: &BumpTransactionEvent) {
+		let target_feerate_sat_per_1000_weight =
+			match self.fee_bump_policy.target_feerate_sat_per_1000_weight(event) {
+				Some(feerate) => feerate,
+				None => {
+					log_info!(
+						self.logger,
+						"Ignoring bump event (claim_id = {}) as instructed by our fee bump policy",
+						log_bytes!(event.claim_id().0)
+					);
+					return;
+				},
+			};
+		if !self.should_spend_anchor(event) {
+			log_info!(
+				self.logger,
+				"Not spending P2A anchor (claim_id = {}) as per our P2A anchor spend strategy",
+				log_bytes!(event.claim_id().0)
+			);
+			return;
+		}
+		let max_total_fee = self.fee_bump_policy.max_total_fee(event);
 		match event {
 			BumpTransactionEvent::ChannelClose {
+				channel_id,
 				claim_id,
-				package_target_feerate_sat_per_1000_weight,
 				commitment_tx,
 				commitment_tx_fee_satoshis,
 				anchor_descriptor,
@@ -1212,8 +1541,10 @@ where
 					commitment_tx.compute_txid()
 				);
 				self.handle_channel_close(
+					*channel_id,
 					*claim_id,
-					*package_target_feerate_sat_per_1000_weight,
+					target_feerate_sat_per_1000_weight,
+					max_total_fee,
 					commitment_tx,
 					*commitment_tx_fee_satoshis,
 					anchor_descriptor,
@@ -1228,8 +1559,8 @@ where
 				});
 			},
 			BumpTransactionEvent::HTLCResolution {
+				channel_id,
 				claim_id,
-				target_feerate_sat_per_1000_weight,
 				htlc_descriptors,
 				tx_lock_time,
 				..
@@ -1241,8 +1572,10 @@ where
 					log_iter!(htlc_descriptors.iter().map(|d| d.outpoint()))
 				);
 				self.handle_htlc_resolution(
+					*channel_id,
 					*claim_id,
-					*target_feerate_sat_per_1000_weight,
+					target_feerate_sat_per_1000_weight,
+					max_total_fee,
 					htlc_descriptors,
 					*tx_lock_time,
 				)
@@ -1368,7 +1701,13 @@ mod tests {
 		};
 		let signer = KeysManager::new(&[42; 32], 42, 42, true);
 		let logger = TestLogger::new();
-		let handler = BumpTransactionEventHandlerSync::new(&broadcaster, &source, &signer, &logger);
+		let handler = BumpTransactionEventHandlerSync::new(
+			&broadcaster,
+			&source,
+			&signer,
+			&DefaultFeeBumpPolicy,
+			&logger,
+		);
 
 		let mut transaction_parameters = ChannelTransactionParameters::test_dummy(42_000_000);
 		transaction_parameters.channel_type_features =
@@ -1393,4 +1732,209 @@ mod tests {
 			pending_htlcs: Vec::new(),
 		});
 	}
+
+	struct TestFeeBumpPolicy {
+		max_feerate_sat_per_1000_weight: Option<u32>,
+		max_total_fee: Option<Amount>,
+	}
+	impl FeeBumpPolicy for TestFeeBumpPolicy {
+		fn target_feerate_sat_per_1000_weight(&self, event: &BumpTransactionEvent) -> Option<u32> {
+			let feerate = event.target_feerate_sat_per_1000_weight();
+			match self.max_feerate_sat_per_1000_weight {
+				Some(0) => None,
+				Some(max_feerate) => Some(feerate.min(max_feerate)),
+				None => Some(feerate),
+			}
+		}
+		fn max_total_fee(&self, _event: &BumpTransactionEvent) -> Option<Amount> {
+			self.max_total_fee
+		}
+	}
+
+	#[test]
+	fn test_fee_bump_policy() {
+		// Test that the `FeeBumpPolicy` given to the handler can lower the target feerate, limit
+		// the total fee spent, or skip handling an event entirely.
+
+		// Tx 18032ad172a5f28fa6e16392d6cc57ea47895781434ce15d03766cc47a955fb9, paying a feerate of
+		// 836 sat/kW.
+		let commitment_tx_bytes = Vec::<u8>::from_hex("02000000000101cc6b0a9dd84b52c07340fff6fab002fc37b4bdccfdce9f39c5ec8391a56b652907000000009b948b80044a01000000000000220020b4182433fdfdfbf894897c98f84d92cec815cee222755ffd000ae091c9dadc2d4a01000000000000220020f83f7dbf90e2de325b5bb6bab0ae370151278c6964739242b2e7ce0cb68a5d81cb4a02000000000022002024add256b3dccee772610caef82a601045ab6f98fd6d5df608cc756b891ccfe63ffa490000000000220020894bf32b37906a643625e87131897c3714c71b3ac9b161862c9aa6c8d468b4c70400473044022060abd347bff2cca0212b660e6addff792b3356bd4a1b5b26672dc2e694c3c5f002202b40b7e346b494a7b1d048b4ec33ba99c90a09ab48eb1df64ccdc768066c865c014730440220554d8361e04dc0ee178dcb23d2d23f53ec7a1ae4312a5be76bd9e83ab8981f3d0220501f23ffb18cb81ccea72d30252f88d5e69fd28ba4992803d03c00d06fa8899e0147522102817f6ce189ab7114f89e8d5df58cdbbaf272dc8e71b92982d47456a0b6a0ceee2102c9b4d2f24aca54f65e13f4c83e2a8d8e877e12d3c71a76e81f28a5cabc652aa352ae626c7620").unwrap();
+		let commitment_tx: Transaction =
+			Readable::read(&mut Cursor::new(&commitment_tx_bytes)).unwrap();
+		let total_commitment_weight =
+			commitment_tx.weight().to_wu() + ANCHOR_INPUT_WITNESS_WEIGHT + EMPTY_SCRIPT_SIG_WEIGHT;
+		let commitment_and_anchor_fee = 930 + 330;
+
+		let mut transaction_parameters = ChannelTransactionParameters::test_dummy(42_000_000);
+		transaction_parameters.channel_type_features =
+			ChannelTypeFeatures::anchors_zero_htlc_fee_and_dependencies();
+		let event = BumpTransactionEvent::ChannelClose {
+			channel_id: ChannelId([42; 32]),
+			counterparty_node_id: PublicKey::from_slice(&[2; 33]).unwrap(),
+			claim_id: ClaimId([42; 32]),
+			package_target_feerate_sat_per_1000_weight: 2000,
+			commitment_tx_fee_satoshis: 930,
+			commitment_tx: commitment_tx.clone(),
+			anchor_descriptor: AnchorDescriptor {
+				channel_derivation_parameters: ChannelDerivationParameters {
+					value_satoshis: 42_000_000,
+					keys_id: [42; 32],
+					transaction_parameters,
+				},
+				outpoint: OutPoint { txid: commitment_tx.compute_txid(), vout: 0 },
+				value: Amount::from_sat(ANCHOR_OUTPUT_VALUE_SATOSHI),
+			},
+			pending_htlcs: Vec::new(),
+		};
+		let coin_selection = CoinSelection {
+			confirmed_utxos: vec![Utxo {
+				outpoint: OutPoint { txid: Txid::from_byte_array([44; 32]), vout: 0 },
+				output: TxOut { value: Amount::from_sat(10_000), script_pubkey: ScriptBuf::new() },
+				satisfaction_weight: 5, // Just the script_sig and witness lengths
+			}],
+			change_output: Some(TxOut {
+				value: Amount::from_sat(7_000),
+				script_pubkey: ScriptBuf::new(),
+			}),
+		};
+
+		let signer = KeysManager::new(&[42; 32], 42, 42, true);
+		let logger = TestLogger::new();
+		let broadcaster = TestBroadcaster::new(Network::Testnet);
+		let handle_event = |policy: TestFeeBumpPolicy, expected_selects| {
+			let source = TestCoinSelectionSource { expected_selects: Mutex::new(expected_selects) };
+			let handler = BumpTransactionEventHandlerSync::new(
+				&broadcaster,
+				&source,
+				&signer,
+				&policy,
+				&logger,
+			);
+			handler.handle_event(&event);
+		};
+
+		// Without any limits, we bump the commitment to the feerate requested.
+		let policy =
+			TestFeeBumpPolicy { max_feerate_sat_per_1000_weight: None, max_total_fee: None };
+		let expected_selects = vec![(
+			total_commitment_weight,
+			commitment_and_anchor_fee,
+			2000,
+			coin_selection.clone(),
+		)];
+		handle_event(policy, expected_selects);
+		assert_eq!(broadcaster.txn_broadcast().len(), 2);
+
+		// If the policy caps the feerate below that of the commitment, it's broadcast on its own.
+		let policy =
+			TestFeeBumpPolicy { max_feerate_sat_per_1000_weight: Some(800), max_total_fee: None };
+		handle_event(policy, Vec::new());
+		assert_eq!(broadcaster.txn_broadcast(), vec![commitment_tx.clone()]);
+
+		// If the anchor transaction would pay more than the policy allows, nothing is broadcast.
+		let policy = TestFeeBumpPolicy {
+			max_feerate_sat_per_1000_weight: None,
+			max_total_fee: Some(Amount::from_sat(1_000)),
+		};
+		let expected_selects =
+			vec![(total_commitment_weight, commitment_and_anchor_fee, 2000, coin_selection)];
+		handle_event(policy, expected_selects);
+		assert!(broadcaster.txn_broadcast().is_empty());
+
+		// If the policy declines to handle the event, nothing is selected nor broadcast.
+		let policy =
+			TestFeeBumpPolicy { max_feerate_sat_per_1000_weight: Some(0), max_total_fee: None };
+		handle_event(policy, Vec::new());
+		assert!(broadcaster.txn_broadcast().is_empty());
+	}
+
+	#[test]
+	fn test_p2a_anchor_spend_strategy() {
+		// Test that the `P2AAnchorSpendStrategy` given to the handler controls whether and when we
+		// spend the P2A anchor of a zero-fee commitment channel.
+
+		// Tx 18032ad172a5f28fa6e16392d6cc57ea47895781434ce15d03766cc47a955fb9, paying a feerate of
+		// 836 sat/kW. As the policy below caps the target feerate at 800 sat/kW, handling the event
+		// results in the commitment being broadcast on its own without any coin selection.
+		let commitment_tx_bytes = Vec::<u8>::from_hex("02000000000101cc6b0a9dd84b52c07340fff6fab002fc37b4bdccfdce9f39c5ec8391a56b652907000000009b948b80044a01000000000000220020b4182433fdfdfbf894897c98f84d92cec815cee222755ffd000ae091c9dadc2d4a01000000000000220020f83f7dbf90e2de325b5bb6bab0ae370151278c6964739242b2e7ce0cb68a5d81cb4a02000000000022002024add256b3dccee772610caef82a601045ab6f98fd6d5df608cc756b891ccfe63ffa490000000000220020894bf32b37906a643625e87131897c3714c71b3ac9b161862c9aa6c8d468b4c70400473044022060abd347bff2cca0212b660e6addff792b3356bd4a1b5b26672dc2e694c3c5f002202b40b7e346b494a7b1d048b4ec33ba99c90a09ab48eb1df64ccdc768066c865c014730440220554d8361e04dc0ee178dcb23d2d23f53ec7a1ae4312a5be76bd9e83ab8981f3d0220501f23ffb18cb81ccea72d30252f88d5e69fd28ba4992803d03c00d06fa8899e0147522102817f6ce189ab7114f89e8d5df58cdbbaf272dc8e71b92982d47456a0b6a0ceee2102c9b4d2f24aca54f65e13f4c83e2a8d8e877e12d3c71a76e81f28a5cabc652aa352ae626c7620").unwrap();
+		let commitment_tx: Transaction =
+			Readable::read(&mut Cursor::new(&commitment_tx_bytes)).unwrap();
+
+		let mut transaction_parameters = ChannelTransactionParameters::test_dummy(42_000_000);
+		transaction_parameters.channel_type_features =
+			ChannelTypeFeatures::anchors_zero_fee_commitments();
+		let event = BumpTransactionEvent::ChannelClose {
+			channel_id: ChannelId([42; 32]),
+			counterparty_node_id: PublicKey::from_slice(&[2; 33]).unwrap(),
+			claim_id: ClaimId([42; 32]),
+			package_target_feerate_sat_per_1000_weight: 2000,
+			commitment_tx_fee_satoshis: 930,
+			commitment_tx: commitment_tx.clone(),
+			anchor_descriptor: AnchorDescriptor {
+				channel_derivation_parameters: ChannelDerivationParameters {
+					value_satoshis: 42_000_000,
+					keys_id: [42; 32],
+					transaction_parameters,
+				},
+				outpoint: OutPoint { txid: commitment_tx.compute_txid(), vout: 0 },
+				value: Amount::ZERO,
+			},
+			pending_htlcs: Vec::new(),
+		};
+
+		let signer = KeysManager::new(&[42; 32], 42, 42, true);
+		let logger = TestLogger::new();
+		let broadcaster = TestBroadcaster::new(Network::Testnet);
+		let source = TestCoinSelectionSource { expected_selects: Mutex::new(Vec::new()) };
+		let policy =
+			TestFeeBumpPolicy { max_feerate_sat_per_1000_weight: Some(800), max_total_fee: None };
+		let new_handler = |strategy| {
+			BumpTransactionEventHandlerSync::new(&broadcaster, &source, &signer, &policy, &logger)
+				.with_p2a_anchor_spend_strategy(strategy)
+		};
+
+		// By default, we handle the event immediately.
+		let handler = new_handler(P2AAnchorSpendStrategy::default());
+		handler.handle_event(&event);
+		assert_eq!(broadcaster.txn_broadcast(), vec![commitment_tx.clone()]);
+
+		// When delegating, we never handle the event, leaving it to an external party.
+		let handler = new_handler(P2AAnchorSpendStrategy::Delegate);
+		handler.best_block_updated(100);
+		handler.handle_event(&event);
+		handler.best_block_updated(1_000);
+		handler.handle_event(&event);
+		assert!(broadcaster.txn_broadcast().is_empty());
+
+		// When waiting for the counterparty without knowing the best block height, we can't tell
+		// when to stop waiting and thus handle the event immediately.
+		let handler = new_handler(P2AAnchorSpendStrategy::WaitForCounterparty { blocks: 2 });
+		handler.handle_event(&event);
+		assert_eq!(broadcaster.txn_broadcast(), vec![commitment_tx.clone()]);
+
+		// Otherwise, we only handle the event once the configured number of blocks has passed
+		// since we first saw it.
+		let handler = new_handler(P2AAnchorSpendStrategy::WaitForCounterparty { blocks: 2 });
+		handler.best_block_updated(100);
+		handler.handle_event(&event);
+		assert!(broadcaster.txn_broadcast().is_empty());
+		handler.best_block_updated(101);
+		handler.handle_event(&event);
+		assert!(broadcaster.txn_broadcast().is_empty());
+		handler.best_block_updated(102);
+		handler.handle_event(&event);
+		assert_eq!(broadcaster.txn_broadcast(), vec![commitment_tx.clone()]);
+
+		// Non-zero-fee-commitment channels are unaffected by the strategy.
+		let mut anchors_event = event.clone();
+		if let BumpTransactionEvent::ChannelClose { ref mut anchor_descriptor, .. } = anchors_event
+		{
+			let params = &mut anchor_descriptor.channel_derivation_parameters;
+			params.transaction_parameters.channel_type_features =
+				ChannelTypeFeatures::anchors_zero_htlc_fee_and_dependencies();
+		}
+		let handler = new_handler(P2AAnchorSpendStrategy::Delegate);
+		handler.handle_event(&anchors_event);
+		assert_eq!(broadcaster.txn_broadcast(), vec![commitment_tx]);
+	}
 }
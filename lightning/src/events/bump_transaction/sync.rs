@@ -25,8 +25,8 @@ use bitcoin::{Psbt, ScriptBuf, Transaction, TxOut};
 
 use super::BumpTransactionEvent;
 use super::{
-	BumpTransactionEventHandler, CoinSelection, CoinSelectionSource, Input, Utxo, Wallet,
-	WalletSource,
+	BumpTransactionEventHandler, CoinSelection, CoinSelectionSource, FeeBumpPolicy, Input,
+	P2AAnchorSpendStrategy, Utxo, Wallet, WalletSource,
 };
 
 /// An alternative to [`CoinSelectionSourceSync`] that can be implemented and used along
@@ -267,35 +267,72 @@ where
 ///
 /// [`Event::BumpTransaction`]: crate::events::Event::BumpTransaction
 // Note that updates to documentation on this struct should be copied to the synchronous version.
-pub struct BumpTransactionEventHandlerSync<B: Deref, C: Deref, SP: Deref, L: Deref>
+pub struct BumpTransactionEventHandlerSync<B: Deref, C: Deref, SP: Deref, FP: Deref, L: Deref>
 where
 	B::Target: BroadcasterInterface,
 	C::Target: CoinSelectionSourceSync,
 	SP::Target: SignerProvider,
+	FP::Target: FeeBumpPolicy,
 	L::Target: Logger,
 {
 	bump_transaction_event_handler:
-		BumpTransactionEventHandler<B, CoinSelectionSourceSyncWrapper<C>, SP, L>,
+		BumpTransactionEventHandler<B, CoinSelectionSourceSyncWrapper<C>, SP, FP, L>,
 }
 
-impl<B: Deref, C: Deref, SP: Deref, L: Deref> BumpTransactionEventHandlerSync<B, C, SP, L>
+impl<B: Deref, C: Deref, SP: Deref, FP: Deref, L: Deref>
+	BumpTransactionEventHandlerSync<B, C, SP, FP, L>
 where
 	B::Target: BroadcasterInterface,
 	C::Target: CoinSelectionSourceSync,
 	SP::Target: SignerProvider,
+	FP::Target: FeeBumpPolicy,
 	L::Target: Logger,
 {
 	/// Constructs a new instance of [`BumpTransactionEventHandlerSync`].
-	pub fn new(broadcaster: B, utxo_source: C, signer_provider: SP, logger: L) -> Self {
+	///
+	/// The `fee_bump_policy` is consulted before fee-bumping any claim. Use
+	/// [`DefaultFeeBumpPolicy`] to follow LDK's fee-bumping strategy without any limits.
+	///
+	/// [`DefaultFeeBumpPolicy`]: super::DefaultFeeBumpPolicy
+	pub fn new(
+		broadcaster: B, utxo_source: C, signer_provider: SP, fee_bump_policy: FP, logger: L,
+	) -> Self {
 		let bump_transaction_event_handler = BumpTransactionEventHandler::new(
 			broadcaster,
 			CoinSelectionSourceSyncWrapper(utxo_source),
 			signer_provider,
+			fee_bump_policy,
 			logger,
 		);
 		Self { bump_transaction_event_handler }
 	}
 
+	/// Sets the [`P2AAnchorSpendStrategy`] used for zero-fee commitment channels, which defaults to
+	/// [`P2AAnchorSpendStrategy::Immediate`].
+	pub fn with_p2a_anchor_spend_strategy(self, strategy: P2AAnchorSpendStrategy) -> Self {
+		let bump_transaction_event_handler =
+			self.bump_transaction_event_handler.with_p2a_anchor_spend_strategy(strategy);
+		Self { bump_transaction_event_handler }
+	}
+
+	/// Sets the maximum weight of each transaction claiming HTLCs.
+	///
+	/// See [`BumpTransactionEventHandler::with_max_htlc_tx_weight`] for more details.
+	pub fn with_max_htlc_tx_weight(self, max_htlc_tx_weight: u64) -> Self {
+		let bump_transaction_event_handler =
+			self.bump_transaction_event_handler.with_max_htlc_tx_weight(max_htlc_tx_weight);
+		Self { bump_transaction_event_handler }
+	}
+
+	/// Notifies the handler of the current best block height.
+	///
+	/// This must be called as new blocks are connected when using
+	/// [`P2AAnchorSpendStrategy::WaitForCounterparty`], and is otherwise unnecessary. Until it is
+	/// first called, P2A anchors are spent immediately.
+	pub fn best_block_updated(&self, height: u32) {
+		self.bump_transaction_event_handler.best_block_updated(height);
+	}
+
 	/// Handles all variants of [`BumpTransactionEvent`].
 	pub fn handle_event(&self, event: &BumpTransactionEvent) {
 		let mut fut = pin!(self.bump_transaction_event_handler.handle_event(event));
@@ -24,7 +24,8 @@ use crate::blinded_path::payment::{
 };
 use crate::chain::transaction;
 use crate::ln::channel::FUNDING_CONF_DEADLINE_BLOCKS;
-use crate::ln::channelmanager::{InterceptId, PaymentId, RecipientOnionFields};
+use crate::ln::channel_state::ChannelLifecycleState;
+use crate::ln::channelmanager::{HTLCCorrelationId, InterceptId, PaymentId, RecipientOnionFields};
 use crate::ln::msgs;
 use crate::ln::onion_utils::LocalHTLCFailureReason;
 use crate::ln::types::ChannelId;
@@ -700,6 +701,19 @@ pub enum PaymentFailureReason {
 	///
 	/// [`HeldHtlcAvailable`]: crate::onion_message::async_payments::HeldHtlcAvailable
 	BlindedPathCreationFailed,
+	/// Retrying the payment would have exceeded its fee or CLTV budget.
+	///
+	/// The fee budget is the [`RouteParameters::max_total_routing_fee_msat`] the payment was
+	/// started with, less the fees of any parts still pending or already claimed. The CLTV budget is
+	/// [`PaymentParameters::max_total_cltv_expiry_delta`], less the number of blocks which have
+	/// elapsed since the payment was first sent.
+	///
+	/// Also used as [`RetriesExhausted`] when downgrading to versions prior to 0.3.
+	///
+	/// [`RouteParameters::max_total_routing_fee_msat`]: crate::routing::router::RouteParameters::max_total_routing_fee_msat
+	/// [`PaymentParameters::max_total_cltv_expiry_delta`]: crate::routing::router::PaymentParameters::max_total_cltv_expiry_delta
+	/// [`RetriesExhausted`]: Self::RetriesExhausted
+	BudgetExhausted,
 }
 
 impl_writeable_tlv_based_enum_upgradable!(PaymentFailureReason,
@@ -712,6 +726,7 @@ impl_writeable_tlv_based_enum_upgradable!(PaymentFailureReason,
 	(6, PaymentExpired) => {},
 	(7, BlindedPathCreationFailed) => {},
 	(8, RouteNotFound) => {},
+	(9, BudgetExhausted) => {},
 	(10, UnexpectedError) => {},
 );
 
@@ -726,7 +741,14 @@ pub enum InboundChannelFunds {
 	///
 	/// Note that these channels do not support starting with initial funds pushed from the counterparty,
 	/// who is the channel opener in this case.
-	DualFunded,
+	DualFunded {
+		/// The feerate for the funding transaction set by the channel initiator, at which we pay
+		/// for any inputs we contribute via
+		/// [`ChannelManager::accept_inbound_channel_with_contribution`].
+		///
+		/// [`ChannelManager::accept_inbound_channel_with_contribution`]: crate::ln::channelmanager::ChannelManager::accept_inbound_channel_with_contribution
+		funding_feerate_sat_per_1000_weight: u32,
+	},
 }
 
 /// An Event which you should probably take some action in response to.
@@ -852,7 +874,13 @@ pub enum Event {
 		/// The fields in the onion which were received with each HTLC. Only fields which were
 		/// identical in each HTLC involved in the payment will be included here.
 		///
+		/// This includes any custom TLVs the sender attached, e.g. via
+		/// [`ChannelManager::send_spontaneous_payment_with_custom_tlvs`], which are available via
+		/// [`RecipientOnionFields::custom_tlvs`].
+		///
 		/// Payments received on LDK versions prior to 0.0.115 will have this field unset.
+		///
+		/// [`ChannelManager::send_spontaneous_payment_with_custom_tlvs`]: crate::ln::channelmanager::ChannelManager::send_spontaneous_payment_with_custom_tlvs
 		onion_fields: Option<RecipientOnionFields>,
 		/// The value, in thousandths of a satoshi, that this payment is claimable for. May be greater
 		/// than the invoice amount.
@@ -860,11 +888,16 @@ pub enum Event {
 		/// May be less than the invoice amount if [`ChannelConfig::accept_underpaying_htlcs`] is set
 		/// and the previous hop took an extra fee.
 		///
+		/// For payments to zero-amount invoices, e.g. ones created with
+		/// [`ChannelManager::create_inbound_payment_with_amount_range`], this is the amount actually
+		/// received and should be used for accounting.
+		///
 		/// # Note
 		/// If [`ChannelConfig::accept_underpaying_htlcs`] is set and you claim without verifying this
 		/// field, you may lose money!
 		///
 		/// [`ChannelConfig::accept_underpaying_htlcs`]: crate::util::config::ChannelConfig::accept_underpaying_htlcs
+		/// [`ChannelManager::create_inbound_payment_with_amount_range`]: crate::ln::channelmanager::ChannelManager::create_inbound_payment_with_amount_range
 		amount_msat: u64,
 		/// The value, in thousands of a satoshi, that was skimmed off of this payment as an extra fee
 		/// taken by our channel counterparty.
@@ -897,6 +930,46 @@ pub enum Event {
 		/// Only filled in for payments received on LDK versions 0.1 and higher.
 		payment_id: Option<PaymentId>,
 	},
+	/// Indicates that we've received all HTLCs for a hold invoice payment registered via
+	/// [`ChannelManager::create_hold_inbound_payment_for_hash`], which are now being held pending
+	/// an explicit decision.
+	///
+	/// Unlike for [`Event::PaymentClaimable`], LDK will not surface this payment as claimable.
+	/// Instead, you should call [`ChannelManager::settle_held_payment`] once the preimage is
+	/// available, or [`ChannelManager::cancel_held_payment`] to fail the payment back to the sender.
+	///
+	/// If neither is called before [`Event::PaymentHeld::claim_deadline`], the HTLCs will be
+	/// automatically failed back to avoid a channel force-closure.
+	///
+	/// # Failure Behavior and Persistence
+	/// This event will eventually be replayed after failures-to-handle (i.e., the event handler
+	/// returning `Err(ReplayEvent ())`) and will be persisted across restarts.
+	///
+	/// [`ChannelManager::create_hold_inbound_payment_for_hash`]: crate::ln::channelmanager::ChannelManager::create_hold_inbound_payment_for_hash
+	/// [`ChannelManager::settle_held_payment`]: crate::ln::channelmanager::ChannelManager::settle_held_payment
+	/// [`ChannelManager::cancel_held_payment`]: crate::ln::channelmanager::ChannelManager::cancel_held_payment
+	PaymentHeld {
+		/// The hash of the held payment, whose preimage must be provided to
+		/// [`ChannelManager::settle_held_payment`] to claim it.
+		///
+		/// [`ChannelManager::settle_held_payment`]: crate::ln::channelmanager::ChannelManager::settle_held_payment
+		payment_hash: PaymentHash,
+		/// The value, in thousandths of a satoshi, that this payment is claimable for. May be greater
+		/// than the invoice amount.
+		amount_msat: u64,
+		/// Information about the received payment, including the payment secret.
+		purpose: PaymentPurpose,
+		/// The fields in the onion which were received with each HTLC. Only fields which were
+		/// identical in each HTLC involved in the payment will be included here.
+		onion_fields: Option<RecipientOnionFields>,
+		/// The `(channel_id, user_channel_id)` pairs over which the payment was received.
+		receiving_channel_ids: Vec<(ChannelId, Option<u128>)>,
+		/// The block height at which this payment will be automatically cancelled, failing the HTLCs
+		/// back to the sender.
+		claim_deadline: u32,
+		/// A unique ID describing this payment (derived from the list of HTLCs in the payment).
+		payment_id: PaymentId,
+	},
 	/// Indicates a payment has been claimed and we've received money!
 	///
 	/// This most likely occurs when [`ChannelManager::claim_funds`] has been called in response
@@ -1285,6 +1358,23 @@ pub enum Event {
 		/// check that whatever fee you want has been included here or subtract it as required. Further,
 		/// LDK will not stop you from forwarding more than you received.
 		expected_outbound_amount_msat: u64,
+		/// The CLTV expiry of the inbound edge of this HTLC.
+		///
+		/// This will be `None` for events serialized by LDK versions prior to 0.3.
+		inbound_cltv_expiry: Option<u32>,
+		/// The CLTV expiry the payer intended the HTLC to the next node to have. You may forward the
+		/// HTLC with a later expiry via [`ChannelManager::forward_modified_intercepted_htlc`].
+		///
+		/// This will be `None` for events serialized by LDK versions prior to 0.3.
+		///
+		/// [`ChannelManager::forward_modified_intercepted_htlc`]: crate::ln::channelmanager::ChannelManager::forward_modified_intercepted_htlc
+		expected_outbound_cltv_expiry: Option<u32>,
+		/// A stable identifier for this HTLC, allowing it to be correlated with the
+		/// [`Event::PaymentForwarded`] or [`Event::HTLCHandlingFailed`] generated once it is
+		/// resolved.
+		///
+		/// This will be `None` for events serialized by LDK versions prior to 0.3.
+		htlc_correlation_id: Option<HTLCCorrelationId>,
 	},
 	/// Used to indicate that an output which you should know how to spend was confirmed on chain
 	/// and is now spendable.
@@ -1379,6 +1469,14 @@ pub enum Event {
 		///
 		/// The caveat described above the `total_fee_earned_msat` field applies here as well.
 		outbound_amount_forwarded_msat: Option<u64>,
+		/// A stable identifier for the inbound HTLC which was forwarded, matching the one provided
+		/// in any preceding [`Event::HTLCIntercepted`] for the same HTLC.
+		///
+		/// Note that the caveat described above the `total_fee_earned_msat` field applies here as
+		/// well, i.e. duplicate events for the same HTLC will carry the same identifier.
+		///
+		/// This will be `None` for events serialized by LDK versions prior to 0.3.
+		htlc_correlation_id: Option<HTLCCorrelationId>,
 	},
 	/// Used to indicate that a channel with the given `channel_id` is being opened and pending
 	/// confirmation on-chain.
@@ -1600,12 +1698,18 @@ pub enum Event {
 	/// Note that a [`ChannelClosed`] event will _not_ be triggered if the channel is rejected.
 	///
 	/// The event is only triggered when a new open channel request is received and the
-	/// [`UserConfig::manually_accept_inbound_channels`] config flag is set to true.
+	/// [`UserConfig::manually_accept_inbound_channels`] config flag is set to true, or the
+	/// [`InboundChannelFeePolicy`] returned [`InboundChannelFeerateDecision::Defer`] for the
+	/// proposed [`ChannelParameters::commitment_feerate_sat_per_1000_weight`]. In either case,
+	/// accepting the request accepts the proposed feerate.
 	///
 	/// # Failure Behavior and Persistence
 	/// This event will eventually be replayed after failures-to-handle (i.e., the event handler
 	/// returning `Err(ReplayEvent ())`) and won't be persisted across restarts.
 	///
+	/// [`InboundChannelFeePolicy`]: crate::chain::chaininterface::InboundChannelFeePolicy
+	/// [`InboundChannelFeerateDecision::Defer`]: crate::chain::chaininterface::InboundChannelFeerateDecision::Defer
+	/// [`ChannelParameters::commitment_feerate_sat_per_1000_weight`]: msgs::ChannelParameters::commitment_feerate_sat_per_1000_weight
 	/// [`ChannelManager::accept_inbound_channel`]: crate::ln::channelmanager::ChannelManager::accept_inbound_channel
 	/// [`ChannelClosed`]: Event::ChannelClosed
 	/// [`ChannelManager::force_close_broadcasting_latest_txn`]: crate::ln::channelmanager::ChannelManager::force_close_broadcasting_latest_txn
@@ -1672,6 +1776,11 @@ pub enum Event {
 		///
 		/// This field will be `None` only for objects serialized prior to LDK 0.2.0.
 		failure_reason: Option<HTLCHandlingFailureReason>,
+		/// A stable identifier for the inbound HTLC which failed, matching the one provided in any
+		/// preceding [`Event::HTLCIntercepted`] for the same HTLC.
+		///
+		/// This will be `None` for objects serialized prior to LDK 0.3.
+		htlc_correlation_id: Option<HTLCCorrelationId>,
 	},
 	/// Indicates that a transaction originating from LDK needs to have its fee bumped. This event
 	/// requires confirmed external funds to be readily available to spend.
@@ -1863,6 +1972,280 @@ pub enum Event {
 		/// [`ChannelManager::funding_transaction_signed`]: crate::ln::channelmanager::ChannelManager::funding_transaction_signed
 		unsigned_transaction: Transaction,
 	},
+	/// Indicates that the on-chain wallet used to fee-bump anchor channel transactions (e.g. the
+	/// [`WalletSource`] backing a [`BumpTransactionEventHandler`]) no longer holds enough funds to
+	/// cover the reserves required by our open and closing anchor channels.
+	///
+	/// Without sufficient reserves, commitment and HTLC transactions may not confirm in time if
+	/// channels are force-closed, risking the loss of in-flight HTLC funds. Users should add funds
+	/// to the wallet or avoid opening or accepting additional anchor channels.
+	///
+	/// This event is generated by [`ChainMonitor::check_anchor_channel_reserves`] when the
+	/// reserves first become insufficient, and will not be generated again until they have been
+	/// replenished.
+	///
+	/// # Failure Behavior and Persistence
+	/// This event will eventually be replayed after failures-to-handle (i.e., the event handler
+	/// returning `Err(ReplayEvent ())`), but won't be persisted across restarts.
+	///
+	/// [`WalletSource`]: crate::events::bump_transaction::WalletSource
+	/// [`BumpTransactionEventHandler`]: crate::events::bump_transaction::BumpTransactionEventHandler
+	/// [`ChainMonitor::check_anchor_channel_reserves`]: crate::chain::chainmonitor::ChainMonitor::check_anchor_channel_reserves
+	InsufficientAnchorReserves {
+		/// The total reserve, in satoshis, required across all of our anchor channels.
+		///
+		/// See [`get_reserve_for_channel`] for how this is calculated for each channel.
+		///
+		/// [`get_reserve_for_channel`]: crate::util::anchor_channel_reserves::get_reserve_for_channel
+		required_reserve_satoshis: u64,
+		/// The value, in satoshis, the wallet's UTXOs can contribute towards the reserve, net of
+		/// the fees required to spend them.
+		available_reserve_satoshis: u64,
+		/// The number of anchor channels which currently require a reserve.
+		num_anchor_channels: u64,
+	},
+	/// Indicates that our counterparty set the feerate of a channel close to the minimum we accept,
+	/// as configured via [`ChannelManager::set_update_fee_policy`]. Should the feerate proposed by
+	/// our counterparty drop below the minimum, we will force-close the channel.
+	///
+	/// This may indicate that our [`FeeEstimator`] disagrees with our counterparty's, and users may
+	/// wish to investigate or close the channel cooperatively before it has to be force-closed.
+	///
+	/// This event will not be generated again for the same channel until the feerate has risen
+	/// back above [`RemoteFeerateLimits::warn_reset_above_sat_per_1000_weight`].
+	///
+	/// # Failure Behavior and Persistence
+	/// This event will eventually be replayed after failures-to-handle (i.e., the event handler
+	/// returning `Err(ReplayEvent ())`), but won't be persisted across restarts.
+	///
+	/// [`ChannelManager::set_update_fee_policy`]: crate::ln::channelmanager::ChannelManager::set_update_fee_policy
+	/// [`FeeEstimator`]: crate::chain::chaininterface::FeeEstimator
+	/// [`RemoteFeerateLimits::warn_reset_above_sat_per_1000_weight`]: crate::chain::chaininterface::RemoteFeerateLimits::warn_reset_above_sat_per_1000_weight
+	PeerFeerateNearLimit {
+		/// The `channel_id` of the channel whose feerate was updated.
+		channel_id: ChannelId,
+		/// The `node_id` of the channel counterparty.
+		counterparty_node_id: PublicKey,
+		/// The `user_channel_id` of the channel.
+		user_channel_id: u128,
+		/// The feerate proposed by our counterparty, in satoshis per 1000 weight units.
+		feerate_sat_per_1000_weight: u32,
+		/// The minimum feerate, in satoshis per 1000 weight units, we currently accept from our
+		/// counterparty.
+		min_feerate_sat_per_1000_weight: u32,
+	},
+	/// Indicates that the funding transaction of a channel which was already usable has been
+	/// un-confirmed by a chain reorganization.
+	///
+	/// This is only generated if [`UserConfig::funding_reorg_grace_period_blocks`] is non-zero.
+	/// Until the funding transaction re-confirms, the channel will not be used to send or forward
+	/// HTLCs. If it does not re-confirm before `reconfirmation_deadline_height`, the channel will be
+	/// force-closed. Otherwise, a [`Event::ChannelFundingReconfirmed`] will be generated.
+	///
+	/// # Failure Behavior and Persistence
+	/// This event will eventually be replayed after failures-to-handle (i.e., the event handler
+	/// returning `Err(ReplayEvent ())`), but won't be persisted across restarts.
+	///
+	/// [`UserConfig::funding_reorg_grace_period_blocks`]: crate::util::config::UserConfig::funding_reorg_grace_period_blocks
+	ChannelFundingUnconfirmed {
+		/// The `channel_id` of the channel whose funding transaction was un-confirmed.
+		channel_id: ChannelId,
+		/// The `node_id` of the channel counterparty.
+		counterparty_node_id: PublicKey,
+		/// The `user_channel_id` of the channel.
+		user_channel_id: u128,
+		/// The short channel id the channel had while its funding transaction was confirmed.
+		previous_short_channel_id: Option<u64>,
+		/// The height of the best block at the time the funding transaction was un-confirmed.
+		unconfirmed_at_height: u32,
+		/// The height at which the channel will be force-closed if the funding transaction has not
+		/// re-confirmed.
+		reconfirmation_deadline_height: u32,
+	},
+	/// Indicates that the funding transaction of a channel, previously reported as un-confirmed via
+	/// [`Event::ChannelFundingUnconfirmed`], has re-confirmed and the channel may be used again.
+	///
+	/// Note that the channel's short channel id may have changed if the funding transaction
+	/// re-confirmed in a different block.
+	///
+	/// # Failure Behavior and Persistence
+	/// This event will eventually be replayed after failures-to-handle (i.e., the event handler
+	/// returning `Err(ReplayEvent ())`), but won't be persisted across restarts.
+	ChannelFundingReconfirmed {
+		/// The `channel_id` of the channel whose funding transaction re-confirmed.
+		channel_id: ChannelId,
+		/// The `node_id` of the channel counterparty.
+		counterparty_node_id: PublicKey,
+		/// The `user_channel_id` of the channel.
+		user_channel_id: u128,
+		/// The new short channel id of the channel.
+		short_channel_id: Option<u64>,
+	},
+	/// Indicates that a channel moved to a different phase of its lifecycle, see
+	/// [`ChannelManager::channel_lifecycle_info`].
+	///
+	/// This is only generated if [`UserConfig::emit_channel_lifecycle_events`] is set. Note that a
+	/// channel being closed is indicated by an [`Event::ChannelClosed`] instead.
+	///
+	/// # Failure Behavior and Persistence
+	/// This event will eventually be replayed after failures-to-handle (i.e., the event handler
+	/// returning `Err(ReplayEvent ())`), but won't be persisted across restarts.
+	///
+	/// [`ChannelManager::channel_lifecycle_info`]: crate::ln::channelmanager::ChannelManager::channel_lifecycle_info
+	/// [`UserConfig::emit_channel_lifecycle_events`]: crate::util::config::UserConfig::emit_channel_lifecycle_events
+	ChannelLifecycleStateChanged {
+		/// The `channel_id` of the channel which changed state.
+		channel_id: ChannelId,
+		/// The `node_id` of the channel counterparty.
+		counterparty_node_id: PublicKey,
+		/// The `user_channel_id` of the channel.
+		user_channel_id: u128,
+		/// The state the channel was last reported in, or `None` if it was not reported before,
+		/// e.g. because the channel was just created or we restarted since.
+		previous_state: Option<ChannelLifecycleState>,
+		/// The state the channel is in now.
+		new_state: ChannelLifecycleState,
+	},
+	/// Indicates that a peer was quarantined after sending us
+	/// [`UserConfig::quarantine_after_message_failures`] consecutive messages we failed to handle.
+	///
+	/// The peer has been disconnected and will be refused reconnection, leaving its channels idle,
+	/// until [`ChannelManager::release_quarantined_peer`] is called, e.g., once the cause of the
+	/// failures has been investigated. Quarantined peers can be listed via
+	/// [`ChannelManager::list_quarantined_peers`].
+	///
+	/// # Failure Behavior and Persistence
+	/// This event will eventually be replayed after failures-to-handle (i.e., the event handler
+	/// returning `Err(ReplayEvent ())`), but won't be persisted across restarts. The quarantine
+	/// itself is persisted.
+	///
+	/// [`UserConfig::quarantine_after_message_failures`]: crate::util::config::UserConfig::quarantine_after_message_failures
+	/// [`ChannelManager::release_quarantined_peer`]: crate::ln::channelmanager::ChannelManager::release_quarantined_peer
+	/// [`ChannelManager::list_quarantined_peers`]: crate::ln::channelmanager::ChannelManager::list_quarantined_peers
+	PeerQuarantined {
+		/// The node id of the quarantined peer.
+		counterparty_node_id: PublicKey,
+		/// The channel the last failed message related to, if any.
+		channel_id: Option<ChannelId>,
+		/// The number of consecutive messages we failed to handle.
+		consecutive_failures: u8,
+		/// The error resulting from the last failed message.
+		last_error: String,
+	},
+	/// Indicates that a channel we requested to become quiescent via
+	/// [`ChannelManager::quiesce_channel`] is now quiescent, i.e., all pending updates have been
+	/// committed and neither side may propose new updates until quiescence ends.
+	///
+	/// The channel remains quiescent until [`ChannelManager::exit_quiescence`] is called or the
+	/// counterparty disconnects.
+	///
+	/// # Failure Behavior and Persistence
+	/// This event will eventually be replayed after failures-to-handle (i.e., the event handler
+	/// returning `Err(ReplayEvent ())`), but won't be persisted across restarts.
+	///
+	/// [`ChannelManager::quiesce_channel`]: crate::ln::channelmanager::ChannelManager::quiesce_channel
+	/// [`ChannelManager::exit_quiescence`]: crate::ln::channelmanager::ChannelManager::exit_quiescence
+	ChannelQuiescent {
+		/// The `channel_id` of the channel which is now quiescent.
+		channel_id: ChannelId,
+		/// The `node_id` of the channel counterparty.
+		counterparty_node_id: PublicKey,
+		/// The `user_channel_id` of the channel.
+		user_channel_id: u128,
+	},
+}
+
+/// A channel or payment an [`Event`] relates to. Events relating to a common channel or payment
+/// have to be handled in the order they were generated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum EventOrderingKey {
+	Channel(ChannelId),
+	PaymentHash(PaymentHash),
+	PaymentId(PaymentId),
+}
+
+impl Event {
+	/// Returns the channels and payments this event relates to, or `None` if it has to be handled
+	/// in order with respect to all other events.
+	pub(crate) fn ordering_keys(&self) -> Option<Vec<EventOrderingKey>> {
+		let mut keys = Vec::new();
+		match self {
+			Event::PaymentClaimable { payment_hash, receiving_channel_ids, payment_id, .. } => {
+				keys.push(EventOrderingKey::PaymentHash(*payment_hash));
+				keys.extend(payment_id.map(EventOrderingKey::PaymentId));
+				keys.extend(
+					receiving_channel_ids.iter().map(|(id, _)| EventOrderingKey::Channel(*id)),
+				);
+			},
+			Event::PaymentClaimed { payment_hash, htlcs, payment_id, .. } => {
+				keys.push(EventOrderingKey::PaymentHash(*payment_hash));
+				keys.extend(payment_id.map(EventOrderingKey::PaymentId));
+				keys.extend(htlcs.iter().map(|htlc| EventOrderingKey::Channel(htlc.channel_id)));
+			},
+			Event::PaymentSent { payment_id, payment_hash, .. }
+			| Event::PaymentPathFailed { payment_id, payment_hash, .. } => {
+				keys.push(EventOrderingKey::PaymentHash(*payment_hash));
+				keys.extend(payment_id.map(EventOrderingKey::PaymentId));
+			},
+			Event::PaymentFailed { payment_id, payment_hash, .. }
+			| Event::PaymentPathSuccessful { payment_id, payment_hash, .. } => {
+				keys.push(EventOrderingKey::PaymentId(*payment_id));
+				keys.extend(payment_hash.map(EventOrderingKey::PaymentHash));
+			},
+			Event::ProbeSuccessful { payment_id, payment_hash, .. }
+			| Event::ProbeFailed { payment_id, payment_hash, .. } => {
+				keys.push(EventOrderingKey::PaymentId(*payment_id));
+				keys.push(EventOrderingKey::PaymentHash(*payment_hash));
+			},
+			Event::InvoiceReceived { payment_id, .. } => {
+				keys.push(EventOrderingKey::PaymentId(*payment_id));
+			},
+			Event::HTLCIntercepted { payment_hash, .. } => {
+				keys.push(EventOrderingKey::PaymentHash(*payment_hash));
+			},
+			Event::PaymentForwarded { prev_channel_id, next_channel_id, .. } => {
+				keys.extend(prev_channel_id.map(EventOrderingKey::Channel));
+				keys.extend(next_channel_id.map(EventOrderingKey::Channel));
+			},
+			Event::HTLCHandlingFailed { prev_channel_id, .. } => {
+				keys.push(EventOrderingKey::Channel(*prev_channel_id));
+			},
+			Event::SpendableOutputs { channel_id, .. } => {
+				keys.extend(channel_id.map(EventOrderingKey::Channel));
+			},
+			Event::FundingGenerationReady { temporary_channel_id, .. }
+			| Event::OpenChannelRequest { temporary_channel_id, .. } => {
+				keys.push(EventOrderingKey::Channel(*temporary_channel_id));
+			},
+			Event::FundingTxBroadcastSafe { channel_id, former_temporary_channel_id, .. } => {
+				keys.push(EventOrderingKey::Channel(*channel_id));
+				keys.push(EventOrderingKey::Channel(*former_temporary_channel_id));
+			},
+			Event::ChannelPending { channel_id, former_temporary_channel_id, .. } => {
+				keys.push(EventOrderingKey::Channel(*channel_id));
+				keys.extend(former_temporary_channel_id.map(EventOrderingKey::Channel));
+			},
+			Event::ChannelReady { channel_id, .. }
+			| Event::ChannelClosed { channel_id, .. }
+			| Event::SplicePending { channel_id, .. }
+			| Event::SpliceFailed { channel_id, .. }
+			| Event::DiscardFunding { channel_id, .. }
+			| Event::FundingTransactionReadyForSigning { channel_id, .. }
+			| Event::PeerFeerateNearLimit { channel_id, .. }
+			| Event::ChannelFundingUnconfirmed { channel_id, .. }
+			| Event::ChannelFundingReconfirmed { channel_id, .. }
+			| Event::ChannelLifecycleStateChanged { channel_id, .. }
+			| Event::ChannelQuiescent { channel_id, .. } => {
+				keys.push(EventOrderingKey::Channel(*channel_id));
+			},
+			_ => {},
+		}
+		if keys.is_empty() {
+			None
+		} else {
+			Some(keys)
+		}
+	}
 }
 
 impl Writeable for Event {
@@ -2014,15 +2397,21 @@ impl Writeable for Event {
 				payment_hash,
 				inbound_amount_msat,
 				expected_outbound_amount_msat,
+				inbound_cltv_expiry,
+				expected_outbound_cltv_expiry,
 				intercept_id,
+				htlc_correlation_id,
 			} => {
 				6u8.write(writer)?;
 				let intercept_scid = InterceptNextHop::FakeScid { requested_next_hop_scid };
 				write_tlv_fields!(writer, {
 					(0, intercept_id, required),
+					(1, htlc_correlation_id, option),
 					(2, intercept_scid, required),
+					(3, inbound_cltv_expiry, option),
 					(4, payment_hash, required),
 					(6, inbound_amount_msat, required),
+					(5, expected_outbound_cltv_expiry, option),
 					(8, expected_outbound_amount_msat, required),
 				});
 			},
@@ -2037,6 +2426,7 @@ impl Writeable for Event {
 				skimmed_fee_msat,
 				claim_from_onchain_tx,
 				outbound_amount_forwarded_msat,
+				htlc_correlation_id,
 			} => {
 				7u8.write(writer)?;
 				write_tlv_fields!(writer, {
@@ -2050,6 +2440,7 @@ impl Writeable for Event {
 					(11, next_user_channel_id, option),
 					(13, prev_node_id, option),
 					(15, next_node_id, option),
+					(17, htlc_correlation_id, option),
 				});
 			},
 			&Event::ChannelClosed {
@@ -2200,12 +2591,14 @@ impl Writeable for Event {
 				ref prev_channel_id,
 				ref failure_type,
 				ref failure_reason,
+				ref htlc_correlation_id,
 			} => {
 				25u8.write(writer)?;
 				write_tlv_fields!(writer, {
 					(0, prev_channel_id, required),
 					(1, failure_reason, option),
 					(2, failure_type, required),
+					(3, htlc_correlation_id, option),
 				})
 			},
 			&Event::BumpTransaction(ref event) => {
@@ -2348,6 +2741,56 @@ impl Writeable for Event {
 					(13, *contributed_outputs, optional_vec),
 				});
 			},
+			&Event::InsufficientAnchorReserves { .. } => {
+				53u8.write(writer)?;
+				// We never write out InsufficientAnchorReserves events as they will be regenerated on
+				// the next reserve check.
+			},
+			&Event::PeerFeerateNearLimit { .. } => {
+				55u8.write(writer)?;
+				// We never write out PeerFeerateNearLimit events as they are only informational.
+			},
+			&Event::ChannelFundingUnconfirmed { .. } => {
+				57u8.write(writer)?;
+				// We never write out ChannelFundingUnconfirmed events as they are only informational.
+			},
+			&Event::ChannelFundingReconfirmed { .. } => {
+				59u8.write(writer)?;
+				// We never write out ChannelFundingReconfirmed events as they are only informational.
+			},
+			&Event::ChannelLifecycleStateChanged { .. } => {
+				61u8.write(writer)?;
+				// We never write out ChannelLifecycleStateChanged events as they are only
+				// informational.
+			},
+			&Event::PeerQuarantined { .. } => {
+				63u8.write(writer)?;
+				// We never write out PeerQuarantined events as they are only informational.
+			},
+			&Event::ChannelQuiescent { .. } => {
+				67u8.write(writer)?;
+				// We never write out ChannelQuiescent events as quiescence ends upon restart.
+			},
+			&Event::PaymentHeld {
+				ref payment_hash,
+				ref amount_msat,
+				ref purpose,
+				ref onion_fields,
+				ref receiving_channel_ids,
+				ref claim_deadline,
+				ref payment_id,
+			} => {
+				65u8.write(writer)?;
+				write_tlv_fields!(writer, {
+					(0, payment_hash, required),
+					(1, onion_fields, option),
+					(2, amount_msat, required),
+					(4, purpose, required),
+					(6, *receiving_channel_ids, required_vec),
+					(8, claim_deadline, required),
+					(10, payment_id, required),
+				});
+			},
 			// Note that, going forward, all new events must only write data inside of
 			// `write_tlv_fields`. Versions 0.0.101+ will ignore odd-numbered events that write
 			// data via `write_tlv_fields`.
@@ -2524,10 +2967,16 @@ impl MaybeReadable for Event {
 					InterceptNextHop::FakeScid { requested_next_hop_scid: 0 };
 				let mut inbound_amount_msat = 0;
 				let mut expected_outbound_amount_msat = 0;
+				let mut inbound_cltv_expiry = None;
+				let mut expected_outbound_cltv_expiry = None;
+				let mut htlc_correlation_id = None;
 				read_tlv_fields!(reader, {
 					(0, intercept_id, required),
+					(1, htlc_correlation_id, option),
 					(2, requested_next_hop_scid, required),
+					(3, inbound_cltv_expiry, option),
 					(4, payment_hash, required),
+					(5, expected_outbound_cltv_expiry, option),
 					(6, inbound_amount_msat, required),
 					(8, expected_outbound_amount_msat, required),
 				});
@@ -2539,7 +2988,10 @@ impl MaybeReadable for Event {
 					requested_next_hop_scid: next_scid,
 					inbound_amount_msat,
 					expected_outbound_amount_msat,
+					inbound_cltv_expiry,
+					expected_outbound_cltv_expiry,
 					intercept_id,
+					htlc_correlation_id,
 				}))
 			},
 			7u8 => {
@@ -2554,6 +3006,7 @@ impl MaybeReadable for Event {
 					let mut skimmed_fee_msat = None;
 					let mut claim_from_onchain_tx = false;
 					let mut outbound_amount_forwarded_msat = None;
+					let mut htlc_correlation_id = None;
 					read_tlv_fields!(reader, {
 						(0, total_fee_earned_msat, option),
 						(1, prev_channel_id, option),
@@ -2565,6 +3018,7 @@ impl MaybeReadable for Event {
 						(11, next_user_channel_id, option),
 						(13, prev_node_id, option),
 						(15, next_node_id, option),
+						(17, htlc_correlation_id, option),
 					});
 					Ok(Some(Event::PaymentForwarded {
 						prev_channel_id,
@@ -2577,6 +3031,7 @@ impl MaybeReadable for Event {
 						skimmed_fee_msat,
 						claim_from_onchain_tx,
 						outbound_amount_forwarded_msat,
+						htlc_correlation_id,
 					}))
 				};
 				f()
@@ -2764,10 +3219,12 @@ impl MaybeReadable for Event {
 					let mut prev_channel_id = ChannelId::new_zero();
 					let mut failure_reason = None;
 					let mut failure_type_opt = UpgradableRequired(None);
+					let mut htlc_correlation_id = None;
 					read_tlv_fields!(reader, {
 						(0, prev_channel_id, required),
 						(1, failure_reason, option),
 						(2, failure_type_opt, upgradable_required),
+						(3, htlc_correlation_id, option),
 					});
 
 					// If a legacy HTLCHandlingFailureType::UnknownNextHop was written, upgrade
@@ -2788,6 +3245,7 @@ impl MaybeReadable for Event {
 							upgradable_required
 						),
 						failure_reason,
+						htlc_correlation_id,
 					}))
 				};
 				f()
@@ -2979,6 +3437,43 @@ impl MaybeReadable for Event {
 				};
 				f()
 			},
+			// Note that we do not write a length-prefixed TLV for InsufficientAnchorReserves events.
+			53u8 => Ok(None),
+			// Note that we do not write a length-prefixed TLV for PeerFeerateNearLimit events.
+			55u8 => Ok(None),
+			// Note that we do not write a length-prefixed TLV for ChannelFundingUnconfirmed events.
+			57u8 => Ok(None),
+			// Note that we do not write a length-prefixed TLV for ChannelFundingReconfirmed events.
+			59u8 => Ok(None),
+			// Note that we do not write a length-prefixed TLV for ChannelLifecycleStateChanged events.
+			61u8 => Ok(None),
+			// Note that we do not write a length-prefixed TLV for PeerQuarantined events.
+			63u8 => Ok(None),
+			// Note that we do not write a length-prefixed TLV for ChannelQuiescent events.
+			67u8 => Ok(None),
+			65u8 => {
+				let mut f = || {
+					_init_and_read_len_prefixed_tlv_fields!(reader, {
+						(0, payment_hash, required),
+						(1, onion_fields, option),
+						(2, amount_msat, required),
+						(4, purpose, upgradable_required),
+						(6, receiving_channel_ids, required_vec),
+						(8, claim_deadline, required),
+						(10, payment_id, required),
+					});
+					Ok(Some(Event::PaymentHeld {
+						payment_hash: payment_hash.0.unwrap(),
+						amount_msat: amount_msat.0.unwrap(),
+						purpose: _init_tlv_based_struct_field!(purpose, upgradable_required),
+						onion_fields,
+						receiving_channel_ids,
+						claim_deadline: claim_deadline.0.unwrap(),
+						payment_id: payment_id.0.unwrap(),
+					}))
+				};
+				f()
+			},
 			// Versions prior to 0.0.100 did not ignore odd types, instead returning InvalidValue.
 			// Version 0.0.100 failed to properly ignore odd types, possibly resulting in corrupt
 			// reads.
@@ -0,0 +1,253 @@
+// This file is Copyright its original authors, visible in version control
+// history.
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Types for paying several [`Bolt11Invoice`]s as one batch via
+//! [`ChannelManager::pay_for_bolt11_invoices`], e.g., for an exchange processing batched
+//! withdrawals.
+//!
+//! Routes for all payments of a batch are computed up front, largest payment first, with each
+//! route accounting for the liquidity already claimed by the routes before it. This allows
+//! enforcing a fee budget shared by the whole batch and, optionally, not sending any payment
+//! unless all of them could be routed. The resulting [`BatchPayment`] aggregates the outcome of
+//! the individual payments as their [`Event`]s are handed to it.
+//!
+//! Note that Lightning provides no atomicity across payments. Thus, even if
+//! [`BatchPaymentParameters::all_or_nothing`] is set, some payments of a batch may fail after
+//! being sent while others succeed.
+//!
+//! [`Bolt11Invoice`]: lightning_invoice::Bolt11Invoice
+//! [`ChannelManager::pay_for_bolt11_invoices`]: crate::ln::channelmanager::ChannelManager::pay_for_bolt11_invoices
+
+use crate::events::{Event, PaymentFailureReason};
+use crate::ln::channelmanager::PaymentId;
+use crate::ln::outbound_payment::Bolt11PaymentError;
+use crate::routing::router::RouteParametersConfig;
+
+use lightning_invoice::Bolt11Invoice;
+
+use crate::prelude::*;
+
+/// A [`Bolt11Invoice`] to pay as part of a batch via
+/// [`ChannelManager::pay_for_bolt11_invoices`].
+///
+/// [`ChannelManager::pay_for_bolt11_invoices`]: crate::ln::channelmanager::ChannelManager::pay_for_bolt11_invoices
+#[derive(Clone, Debug)]
+pub struct BatchedBolt11Invoice {
+	/// The invoice to pay.
+	pub invoice: Bolt11Invoice,
+	/// The id of the payment, which must be unique within the batch.
+	pub payment_id: PaymentId,
+	/// The amount to pay, required if the invoice doesn't specify one. See
+	/// [`ChannelManager::pay_for_bolt11_invoice`] for details.
+	///
+	/// [`ChannelManager::pay_for_bolt11_invoice`]: crate::ln::channelmanager::ChannelManager::pay_for_bolt11_invoice
+	pub amount_msats: Option<u64>,
+}
+
+/// Parameters for paying a batch of invoices via [`ChannelManager::pay_for_bolt11_invoices`].
+///
+/// [`ChannelManager::pay_for_bolt11_invoices`]: crate::ln::channelmanager::ChannelManager::pay_for_bolt11_invoices
+#[derive(Clone, Copy, Debug)]
+pub struct BatchPaymentParameters {
+	/// The routing parameters applied to each payment of the batch.
+	///
+	/// Unlike for a single payment, [`RouteParametersConfig::max_total_routing_fee_msat`] limits
+	/// the total fees paid by all payments of the batch together. Once routes have been found for
+	/// all payments, any fee budget left is split evenly between them to allow for retries.
+	pub route_params_config: RouteParametersConfig,
+	/// If set, no payment is sent unless routes within the fee budget could be found for all
+	/// payments of the batch. Otherwise, payments which could not be routed are skipped and
+	/// reported as [`BatchedPaymentStatus::NotSent`].
+	///
+	/// Default value: `false`
+	pub all_or_nothing: bool,
+}
+
+impl Default for BatchPaymentParameters {
+	fn default() -> Self {
+		Self { route_params_config: RouteParametersConfig::default(), all_or_nothing: false }
+	}
+}
+
+/// An error when paying a batch of invoices via [`ChannelManager::pay_for_bolt11_invoices`]. No
+/// payment of the batch was sent if this is returned.
+///
+/// [`ChannelManager::pay_for_bolt11_invoices`]: crate::ln::channelmanager::ChannelManager::pay_for_bolt11_invoices
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BatchPaymentError {
+	/// The same [`PaymentId`] was used for more than one payment of the batch.
+	DuplicatePaymentId(PaymentId),
+	/// An invoice of the batch can't be paid, e.g., because of an invalid amount or because it is
+	/// meant for a different chain.
+	InvalidInvoice {
+		/// The id of the payment for the invalid invoice.
+		payment_id: PaymentId,
+		/// The reason the invoice can't be paid.
+		err: Bolt11PaymentError,
+	},
+	/// No route could be found for a payment of the batch while
+	/// [`BatchPaymentParameters::all_or_nothing`] was set.
+	RouteNotFound {
+		/// The id of the payment which could not be routed.
+		payment_id: PaymentId,
+	},
+	/// The routes found for the payments of the batch would exceed the fee budget given by
+	/// [`RouteParametersConfig::max_total_routing_fee_msat`].
+	FeeBudgetExceeded {
+		/// The total fees of the routes found.
+		required_fee_msat: u64,
+	},
+}
+
+/// The status of a single payment of a [`BatchPayment`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BatchedPaymentStatus {
+	/// The payment was not sent, e.g., because no route could be found for it.
+	NotSent(Bolt11PaymentError),
+	/// The payment was sent and is awaiting an [`Event::PaymentSent`] or [`Event::PaymentFailed`].
+	Pending,
+	/// The payment succeeded.
+	Succeeded {
+		/// The fees paid for the payment, see [`Event::PaymentSent::fee_paid_msat`].
+		fee_paid_msat: Option<u64>,
+	},
+	/// The payment failed.
+	Failed {
+		/// The reason the payment failed, see [`Event::PaymentFailed::reason`].
+		reason: Option<PaymentFailureReason>,
+	},
+}
+
+/// A batch of payments sent via [`ChannelManager::pay_for_bolt11_invoices`], aggregating the
+/// outcome of the individual payments.
+///
+/// Each [`Event::PaymentSent`] and [`Event::PaymentFailed`] should be passed to
+/// [`Self::handle_event`] until [`Self::is_complete`] returns `true`. The events should still be
+/// handled as usual otherwise.
+///
+/// [`ChannelManager::pay_for_bolt11_invoices`]: crate::ln::channelmanager::ChannelManager::pay_for_bolt11_invoices
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BatchPayment {
+	payments: Vec<(PaymentId, BatchedPaymentStatus)>,
+}
+
+impl BatchPayment {
+	pub(crate) fn new() -> Self {
+		Self { payments: Vec::new() }
+	}
+
+	pub(crate) fn push(&mut self, payment_id: PaymentId, status: BatchedPaymentStatus) {
+		self.payments.push((payment_id, status));
+	}
+
+	/// Updates the status of the corresponding payment if `event` is an [`Event::PaymentSent`] or
+	/// [`Event::PaymentFailed`] for a payment of this batch. Returns whether it was.
+	pub fn handle_event(&mut self, event: &Event) -> bool {
+		let (payment_id, new_status) = match event {
+			Event::PaymentSent { payment_id: Some(payment_id), fee_paid_msat, .. } => {
+				(payment_id, BatchedPaymentStatus::Succeeded { fee_paid_msat: *fee_paid_msat })
+			},
+			Event::PaymentFailed { payment_id, reason, .. } => {
+				(payment_id, BatchedPaymentStatus::Failed { reason: *reason })
+			},
+			_ => return false,
+		};
+		for (id, status) in self.payments.iter_mut() {
+			if id == payment_id && *status == BatchedPaymentStatus::Pending {
+				*status = new_status;
+				return true;
+			}
+		}
+		false
+	}
+
+	/// Returns the [`PaymentId`] and status of each payment of the batch, in the order they were
+	/// sent in.
+	pub fn payments(&self) -> &[(PaymentId, BatchedPaymentStatus)] {
+		&self.payments
+	}
+
+	/// Returns whether no payment of the batch is pending anymore.
+	pub fn is_complete(&self) -> bool {
+		self.payments.iter().all(|(_, status)| *status != BatchedPaymentStatus::Pending)
+	}
+
+	/// Returns whether all payments of the batch succeeded.
+	pub fn all_succeeded(&self) -> bool {
+		self.payments
+			.iter()
+			.all(|(_, status)| matches!(status, BatchedPaymentStatus::Succeeded { .. }))
+	}
+
+	/// Returns the total fees paid by the payments of the batch which succeeded so far.
+	pub fn total_fee_paid_msat(&self) -> u64 {
+		self.payments
+			.iter()
+			.filter_map(|(_, status)| match status {
+				BatchedPaymentStatus::Succeeded { fee_paid_msat } => *fee_paid_msat,
+				_ => None,
+			})
+			.sum()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{BatchPayment, BatchedPaymentStatus};
+
+	use crate::events::{Event, PaymentFailureReason};
+	use crate::ln::channelmanager::PaymentId;
+	use crate::ln::outbound_payment::{Bolt11PaymentError, RetryableSendFailure};
+	use crate::types::payment::{PaymentHash, PaymentPreimage};
+
+	#[test]
+	fn aggregates_payment_outcomes() {
+		let mut batch = BatchPayment::new();
+		let not_sent = Bolt11PaymentError::SendingFailed(RetryableSendFailure::RouteNotFound);
+		batch.push(PaymentId([0; 32]), BatchedPaymentStatus::Pending);
+		batch.push(PaymentId([1; 32]), BatchedPaymentStatus::Pending);
+		batch.push(PaymentId([2; 32]), BatchedPaymentStatus::NotSent(not_sent));
+		assert!(!batch.is_complete());
+
+		let sent = Event::PaymentSent {
+			payment_id: Some(PaymentId([0; 32])),
+			payment_preimage: PaymentPreimage([0; 32]),
+			payment_hash: PaymentHash([0; 32]),
+			amount_msat: Some(1_000),
+			fee_paid_msat: Some(10),
+			bolt12_invoice: None,
+		};
+		assert!(batch.handle_event(&sent));
+		// Duplicate events are ignored.
+		assert!(!batch.handle_event(&sent));
+		assert_eq!(batch.total_fee_paid_msat(), 10);
+		assert!(!batch.is_complete());
+
+		let failed = Event::PaymentFailed {
+			payment_id: PaymentId([1; 32]),
+			payment_hash: None,
+			reason: Some(PaymentFailureReason::RetriesExhausted),
+		};
+		assert!(batch.handle_event(&failed));
+		assert!(batch.is_complete());
+		assert!(!batch.all_succeeded());
+		assert_eq!(
+			batch.payments()[1].1,
+			BatchedPaymentStatus::Failed { reason: Some(PaymentFailureReason::RetriesExhausted) }
+		);
+
+		// Events for payments outside of the batch are ignored.
+		let other = Event::PaymentFailed {
+			payment_id: PaymentId([3; 32]),
+			payment_hash: None,
+			reason: None,
+		};
+		assert!(!batch.handle_event(&other));
+	}
+}
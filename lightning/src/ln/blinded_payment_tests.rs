@@ -28,6 +28,7 @@ use crate::offers::invoice::UnsignedBolt12Invoice;
 use crate::prelude::*;
 use crate::routing::router::{
 	BlindedTail, Path, Payee, PaymentParameters, Route, RouteHop, RouteParameters, TrampolineHop,
+	DEFAULT_TRAMPOLINE_FEE_BASE_MSAT, DEFAULT_TRAMPOLINE_FEE_PROPORTIONAL_MILLIONTHS,
 };
 use crate::sign::{NodeSigner, PeerStorageKey, ReceiveAuthKey, Recipient};
 use crate::types::features::{BlindedHopFeatures, ChannelFeatures, NodeFeatures};
@@ -1526,6 +1527,7 @@ fn update_add_msg(
 		skimmed_fee_msat: None,
 		blinding_point,
 		hold_htlc: None,
+		endorsed: None,
 	}
 }
 
@@ -2399,8 +2401,8 @@ fn create_blinded_tail(
 	}
 }
 
-// Creates a replacement onion that is used to produce scenarios that we don't support, specifically
-// payloads that send to unblinded receives and invalid payloads.
+// Creates a replacement onion that is used to produce scenarios that the original route doesn't
+// cover, specifically payloads that send to unblinded receives and invalid payloads.
 fn replacement_onion(
 	test_case: TrampolineTestCase, secp_ctx: &Secp256k1<All>, override_random_bytes: [u8; 32],
 	route: Route, original_amt_msat: u64, starting_htlc_offset: u32, original_trampoline_cltv: u32,
@@ -2414,7 +2416,7 @@ fn replacement_onion(
 
 	// Rebuild our trampoline packet from the original route. If we want to test Carol receiving
 	// as an unblinded trampoline hop, we switch out her inner trampoline onion with a direct
-	// receive payload because LDK doesn't support unblinded trampoline receives.
+	// receive payload as the original route pays her via a blinded path.
 	let (trampoline_packet, outer_total_msat, outer_starting_htlc_offset) = {
 		let (mut trampoline_payloads, outer_total_msat, outer_starting_htlc_offset) =
 			onion_utils::build_trampoline_onion_payloads(
@@ -2432,6 +2434,9 @@ fn replacement_onion(
 					payment_secret,
 					total_msat: original_amt_msat,
 				}),
+				payment_metadata: None,
+				keysend_preimage: None,
+				custom_tlvs: &recipient_onion_fields.custom_tlvs,
 				sender_intended_htlc_amt_msat: original_amt_msat,
 				cltv_expiry_height: original_trampoline_cltv + starting_htlc_offset,
 			}];
@@ -2799,3 +2804,194 @@ fn test_trampoline_forward_rejection() {
 		expect_payment_failed_conditions(&nodes[0], payment_hash, false, payment_failed_conditions);
 	}
 }
+
+#[test]
+fn test_trampoline_forward() {
+	do_test_trampoline_forward(true);
+	do_test_trampoline_forward(false);
+}
+
+// Alice pays Carol through Bob, who only appears as a Trampoline hop and has to find a route to
+// Carol on his own.
+fn do_test_trampoline_forward(accept_trampoline_forwards: bool) {
+	const TOTAL_NODE_COUNT: usize = 3;
+	let secp_ctx = Secp256k1::new();
+
+	let chanmon_cfgs = create_chanmon_cfgs(TOTAL_NODE_COUNT);
+	let node_cfgs = create_node_cfgs(TOTAL_NODE_COUNT, &chanmon_cfgs);
+	let mut bob_cfg = test_default_channel_config();
+	bob_cfg.accept_trampoline_forwards = accept_trampoline_forwards;
+	let node_chanmgrs =
+		create_node_chanmgrs(TOTAL_NODE_COUNT, &node_cfgs, &[None, Some(bob_cfg.clone()), None]);
+	let nodes = create_network(TOTAL_NODE_COUNT, &node_cfgs, &node_chanmgrs);
+
+	let alice_bob_chan = create_announced_chan_between_nodes_with_value(&nodes, 0, 1, 1_000_000, 0);
+	create_announced_chan_between_nodes_with_value(&nodes, 1, 2, 1_000_000, 0);
+
+	for i in 0..TOTAL_NODE_COUNT {
+		connect_blocks(
+			&nodes[i],
+			(TOTAL_NODE_COUNT as u32) * CHAN_CONFIRM_DEPTH + 1 - nodes[i].best_block_info().1,
+		);
+	}
+
+	let alice_node_id = nodes[0].node.get_our_node_id();
+	let bob_node_id = nodes[1].node.get_our_node_id();
+	let carol_node_id = nodes[2].node.get_our_node_id();
+	let alice_bob_scid = get_scid_from_channel_id(&nodes[0], alice_bob_chan.2);
+
+	let amt_msat = 1000;
+	let (payment_preimage, payment_hash, payment_secret) =
+		get_payment_preimage_hash(&nodes[2], Some(amt_msat), None);
+
+	let override_random_bytes = [42; 32];
+	*nodes[0].keys_manager.override_random_bytes.lock().unwrap() = Some(override_random_bytes);
+
+	// Bob is paid exactly his forwarding fee, and is given enough CLTV budget to cover his own
+	// delta as well as the route he finds to Carol.
+	let bob_fee_msat = bob_cfg.channel_config.forwarding_fee_base_msat as u64;
+	let mut blinded_tail = create_blinded_tail(
+		&secp_ctx,
+		override_random_bytes,
+		carol_node_id,
+		nodes[2].keys_manager.get_receive_auth_key(),
+		72,
+		amt_msat,
+		payment_secret,
+	);
+	blinded_tail.trampoline_hops.insert(
+		0,
+		TrampolineHop {
+			pubkey: bob_node_id,
+			node_features: Features::empty(),
+			fee_msat: bob_fee_msat,
+			cltv_expiry_delta: 200,
+		},
+	);
+	let route = Route {
+		paths: vec![Path {
+			hops: vec![RouteHop {
+				pubkey: bob_node_id,
+				node_features: NodeFeatures::empty(),
+				short_channel_id: alice_bob_scid,
+				channel_features: ChannelFeatures::empty(),
+				fee_msat: bob_fee_msat,
+				cltv_expiry_delta: 0,
+				maybe_announced_channel: false,
+			}],
+			blinded_tail: Some(blinded_tail),
+		}],
+		route_params: None,
+	};
+
+	nodes[0]
+		.node
+		.send_payment_with_route(
+			route,
+			payment_hash,
+			RecipientOnionFields::spontaneous_empty(),
+			PaymentId(payment_hash.0),
+		)
+		.unwrap();
+	check_added_monitors(&nodes[0], 1);
+
+	let mut events = nodes[0].node.get_and_clear_pending_msg_events();
+	assert_eq!(events.len(), 1);
+	let first_message_event = remove_first_msg_event_to_node(&bob_node_id, &mut events);
+
+	if accept_trampoline_forwards {
+		let route: &[&Node] = &[&nodes[1], &nodes[2]];
+		let args =
+			PassAlongPathArgs::new(&nodes[0], route, amt_msat, payment_hash, first_message_event)
+				.with_payment_secret(payment_secret);
+		do_pass_along_path(args);
+		claim_payment(&nodes[0], &[&nodes[1], &nodes[2]], payment_preimage);
+	} else {
+		let route: &[&Node] = &[&nodes[1]];
+		let args =
+			PassAlongPathArgs::new(&nodes[0], route, amt_msat, payment_hash, first_message_event)
+				.with_payment_preimage(payment_preimage)
+				.without_claimable_event()
+				.expect_failure(HTLCHandlingFailureType::Receive { payment_hash });
+		do_pass_along_path(args);
+
+		let node_updates = get_htlc_update_msgs(&nodes[1], &alice_node_id);
+		nodes[0].node.handle_update_fail_htlc(bob_node_id, &node_updates.update_fail_htlcs[0]);
+		do_commitment_signed_dance(
+			&nodes[0],
+			&nodes[1],
+			&node_updates.commitment_signed,
+			false,
+			false,
+		);
+
+		// `InvalidTrampolineForward` shares its failure code with `UnknownNextPeer`.
+		let payment_failed_conditions = PaymentFailedConditions::new()
+			.expected_htlc_error_data(LocalHTLCFailureReason::UnknownNextPeer, &[0; 0]);
+		expect_payment_failed_conditions(&nodes[0], payment_hash, false, payment_failed_conditions);
+	}
+}
+
+#[test]
+fn test_trampoline_send_to_unblinded_recipient() {
+	// Alice pays Carol via Bob, using the router to construct a route to Bob with Carol placed in a
+	// Trampoline onion as an unblinded recipient.
+	const TOTAL_NODE_COUNT: usize = 3;
+
+	let chanmon_cfgs = create_chanmon_cfgs(TOTAL_NODE_COUNT);
+	let node_cfgs = create_node_cfgs(TOTAL_NODE_COUNT, &chanmon_cfgs);
+	let mut bob_cfg = test_default_channel_config();
+	bob_cfg.accept_trampoline_forwards = true;
+	let node_chanmgrs =
+		create_node_chanmgrs(TOTAL_NODE_COUNT, &node_cfgs, &[None, Some(bob_cfg.clone()), None]);
+	let nodes = create_network(TOTAL_NODE_COUNT, &node_cfgs, &node_chanmgrs);
+
+	create_announced_chan_between_nodes_with_value(&nodes, 0, 1, 1_000_000, 0);
+	create_announced_chan_between_nodes_with_value(&nodes, 1, 2, 1_000_000, 0);
+
+	let bob_node_id = nodes[1].node.get_our_node_id();
+	let carol_node_id = nodes[2].node.get_our_node_id();
+
+	let amt_msat = 1000;
+	let (payment_preimage, payment_hash, payment_secret) =
+		get_payment_preimage_hash(&nodes[2], Some(amt_msat), None);
+
+	let payment_params = PaymentParameters::from_node_id(carol_node_id, TEST_FINAL_CLTV)
+		.for_trampoline(bob_node_id)
+		.unwrap();
+	let route_params = RouteParameters::from_payment_params_and_value(payment_params, amt_msat);
+	nodes[0]
+		.node
+		.send_payment(
+			payment_hash,
+			RecipientOnionFields::secret_only(payment_secret),
+			PaymentId(payment_hash.0),
+			route_params,
+			Retry::Attempts(0),
+		)
+		.unwrap();
+	check_added_monitors(&nodes[0], 1);
+
+	let mut events = nodes[0].node.get_and_clear_pending_msg_events();
+	assert_eq!(events.len(), 1);
+	let first_message_event = remove_first_msg_event_to_node(&bob_node_id, &mut events);
+
+	let route: &[&Node] = &[&nodes[1], &nodes[2]];
+	let args =
+		PassAlongPathArgs::new(&nodes[0], route, amt_msat, payment_hash, first_message_event)
+			.with_payment_secret(payment_secret);
+	do_pass_along_path(args);
+
+	// Bob keeps the full Trampoline fee, which exceeds his regular forwarding fee.
+	let trampoline_fee_msat = DEFAULT_TRAMPOLINE_FEE_BASE_MSAT
+		+ amt_msat * DEFAULT_TRAMPOLINE_FEE_PROPORTIONAL_MILLIONTHS / 1_000_000;
+	let bob_forwarding_fee_msat = bob_cfg.channel_config.forwarding_fee_base_msat as u64
+		+ amt_msat * bob_cfg.channel_config.forwarding_fee_proportional_millionths as u64
+			/ 1_000_000;
+	let overpay_msat = (trampoline_fee_msat - bob_forwarding_fee_msat) as u32;
+	let expected_route: &[&[&Node]] = &[&[&nodes[1], &nodes[2]]];
+	claim_payment_along_route(
+		ClaimAlongRouteArgs::new(&nodes[0], expected_route, payment_preimage)
+			.with_expected_min_htlc_overpay(vec![overpay_msat]),
+	);
+}
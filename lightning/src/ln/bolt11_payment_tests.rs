@@ -10,16 +10,22 @@
 //! Tests for verifying the correct end-to-end handling of BOLT11 payments, including metadata propagation.
 
 use crate::events::Event;
+use crate::ln::batch_payment::{
+	BatchPaymentError, BatchPaymentParameters, BatchedBolt11Invoice, BatchedPaymentStatus,
+};
 use crate::ln::channelmanager::{PaymentId, Retry};
 use crate::ln::functional_test_utils::*;
 use crate::ln::msgs::ChannelMessageHandler;
-use crate::ln::outbound_payment::Bolt11PaymentError;
+use crate::ln::outbound_payment::{
+	Bolt11PaymentError, RetryableSendFailure, INVOICE_PAYMENT_HASH_CUSTOM_TLV_TYPE,
+};
 use crate::routing::router::RouteParametersConfig;
 use crate::sign::{NodeSigner, Recipient};
+use crate::types::features::NodeFeatures;
 use bitcoin::hashes::sha256::Hash as Sha256;
 use bitcoin::hashes::Hash;
 use lightning_invoice::{Bolt11Invoice, Currency, InvoiceBuilder};
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
 #[test]
 fn payment_metadata_end_to_end_for_invoice_with_amount() {
@@ -37,7 +43,7 @@ fn payment_metadata_end_to_end_for_invoice_with_amount() {
 		nodes[1].node.create_inbound_payment(None, 7200, None).unwrap();
 
 	let timestamp = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap();
-	let invoice = InvoiceBuilder::new(Currency::Bitcoin)
+	let invoice = InvoiceBuilder::new(Currency::BitcoinTestnet)
 		.description("test".into())
 		.payment_hash(Sha256::from_slice(&payment_hash.0).unwrap())
 		.payment_secret(payment_secret)
@@ -106,7 +112,7 @@ fn payment_metadata_end_to_end_for_invoice_with_no_amount() {
 		nodes[1].node.create_inbound_payment(None, 7200, None).unwrap();
 
 	let timestamp = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap();
-	let invoice = InvoiceBuilder::new(Currency::Bitcoin)
+	let invoice = InvoiceBuilder::new(Currency::BitcoinTestnet)
 		.description("test".into())
 		.payment_hash(Sha256::from_slice(&payment_hash.0).unwrap())
 		.payment_secret(payment_secret)
@@ -157,3 +163,226 @@ fn payment_metadata_end_to_end_for_invoice_with_no_amount() {
 		_ => panic!("Unexpected event"),
 	}
 }
+
+#[test]
+fn keysend_fallback_for_expired_invoice() {
+	// Test that an expired invoice is paid via keysend if the caller opted into falling back and the
+	// payee supports it, with the invoice's payment hash carried in a custom TLV.
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[None, None]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+	create_announced_chan_between_nodes(&nodes, 0, 1);
+
+	let (payment_hash, payment_secret) =
+		nodes[1].node.create_inbound_payment(None, 7200, None).unwrap();
+
+	let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap();
+	let invoice = InvoiceBuilder::new(Currency::BitcoinTestnet)
+		.description("test".into())
+		.payment_hash(Sha256::from_slice(&payment_hash.0).unwrap())
+		.payment_secret(payment_secret)
+		.duration_since_epoch(now - Duration::from_secs(7200))
+		.expiry_time(Duration::from_secs(3600))
+		.min_final_cltv_expiry_delta(144)
+		.amount_milli_satoshis(50_000)
+		.build_raw()
+		.unwrap();
+	let sig = nodes[1].keys_manager.backing.sign_invoice(&invoice, Recipient::Node).unwrap();
+	let invoice = invoice.sign::<_, ()>(|_| Ok(sig)).unwrap();
+	let invoice = Bolt11Invoice::from_signed(invoice).unwrap();
+	assert!(invoice.is_expired());
+
+	// Without keysend support, we can't fall back.
+	match nodes[0].node.pay_for_bolt11_invoice_with_keysend_fallback(
+		&invoice,
+		PaymentId(payment_hash.0),
+		None,
+		RouteParametersConfig::default(),
+		Retry::Attempts(0),
+		&NodeFeatures::empty(),
+	) {
+		Err(Bolt11PaymentError::InvoiceExpired) => (),
+		_ => panic!("Unexpected result"),
+	};
+
+	let keysend_hash = nodes[0]
+		.node
+		.pay_for_bolt11_invoice_with_keysend_fallback(
+			&invoice,
+			PaymentId(payment_hash.0),
+			None,
+			RouteParametersConfig::default(),
+			Retry::Attempts(0),
+			&nodes[1].node.node_features(),
+		)
+		.unwrap();
+	assert_ne!(keysend_hash, payment_hash);
+
+	check_added_monitors(&nodes[0], 1);
+	let send_event = SendEvent::from_node(&nodes[0]);
+	nodes[1].node.handle_update_add_htlc(nodes[0].node.get_our_node_id(), &send_event.msgs[0]);
+	do_commitment_signed_dance(&nodes[1], &nodes[0], &send_event.commitment_msg, false, false);
+
+	expect_and_process_pending_htlcs(&nodes[1], false);
+
+	let expected_tlvs = vec![(
+		INVOICE_PAYMENT_HASH_CUSTOM_TLV_TYPE,
+		invoice.payment_hash().to_byte_array().to_vec(),
+	)];
+	let mut events = nodes[1].node.get_and_clear_pending_events();
+	assert_eq!(events.len(), 1);
+	let preimage = match events.pop().unwrap() {
+		Event::PaymentClaimable { payment_hash, onion_fields, amount_msat, purpose, .. } => {
+			assert_eq!(payment_hash, keysend_hash);
+			assert_eq!(amount_msat, 50_000);
+			assert_eq!(onion_fields.unwrap().custom_tlvs().clone(), expected_tlvs);
+			purpose.preimage().unwrap()
+		},
+		_ => panic!("Unexpected event"),
+	};
+	claim_payment_along_route(
+		ClaimAlongRouteArgs::new(&nodes[0], &[&[&nodes[1]]], preimage)
+			.with_custom_tlvs(expected_tlvs),
+	);
+}
+
+#[test]
+fn rejects_invoice_for_other_chain() {
+	// Test that invoices meant to be paid on a different chain are rejected before any payment
+	// attempt is made.
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[None, None]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+	create_announced_chan_between_nodes(&nodes, 0, 1);
+
+	let (payment_hash, payment_secret) =
+		nodes[1].node.create_inbound_payment(None, 7200, None).unwrap();
+
+	// Our nodes operate on testnet, so a mainnet invoice must be rejected.
+	let timestamp = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap();
+	let invoice = InvoiceBuilder::new(Currency::Bitcoin)
+		.description("test".into())
+		.payment_hash(Sha256::from_slice(&payment_hash.0).unwrap())
+		.payment_secret(payment_secret)
+		.duration_since_epoch(timestamp)
+		.min_final_cltv_expiry_delta(144)
+		.amount_milli_satoshis(50_000)
+		.build_raw()
+		.unwrap();
+	let sig = nodes[1].keys_manager.backing.sign_invoice(&invoice, Recipient::Node).unwrap();
+	let invoice = invoice.sign::<_, ()>(|_| Ok(sig)).unwrap();
+	let invoice = Bolt11Invoice::from_signed(invoice).unwrap();
+
+	match nodes[0].node.pay_for_bolt11_invoice(
+		&invoice,
+		PaymentId(payment_hash.0),
+		None,
+		RouteParametersConfig::default(),
+		Retry::Attempts(0),
+	) {
+		Err(Bolt11PaymentError::UnsupportedChain) => (),
+		_ => panic!("Unexpected result"),
+	};
+	match nodes[0].node.pay_for_bolt11_invoice_with_keysend_fallback(
+		&invoice,
+		PaymentId(payment_hash.0),
+		None,
+		RouteParametersConfig::default(),
+		Retry::Attempts(0),
+		&nodes[1].node.node_features(),
+	) {
+		Err(Bolt11PaymentError::UnsupportedChain) => (),
+		_ => panic!("Unexpected result"),
+	};
+	assert!(nodes[0].node.get_and_clear_pending_msg_events().is_empty());
+}
+
+#[test]
+fn pays_batch_of_invoices() {
+	// Test that a batch of invoices is only paid if the batch can be routed as requested.
+	let chanmon_cfgs = create_chanmon_cfgs(5);
+	let node_cfgs = create_node_cfgs(5, &chanmon_cfgs);
+	let node_chanmgrs = create_node_chanmgrs(5, &node_cfgs, &[None, None, None, None, None]);
+	let nodes = create_network(5, &node_cfgs, &node_chanmgrs);
+	create_announced_chan_between_nodes(&nodes, 0, 1);
+	create_announced_chan_between_nodes(&nodes, 0, 2);
+	create_announced_chan_between_nodes(&nodes, 2, 3);
+
+	// Nodes 1 and 3 are reachable directly and via node 2, respectively, while node 4 is not
+	// reachable at all.
+	let build_invoice = |payee: usize, amount_msat: u64| {
+		let (payment_hash, payment_secret) =
+			nodes[payee].node.create_inbound_payment(Some(amount_msat), 7200, None).unwrap();
+		let timestamp = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap();
+		let invoice = InvoiceBuilder::new(Currency::BitcoinTestnet)
+			.description("test".into())
+			.payment_hash(Sha256::from_slice(&payment_hash.0).unwrap())
+			.payment_secret(payment_secret)
+			.duration_since_epoch(timestamp)
+			.min_final_cltv_expiry_delta(144)
+			.amount_milli_satoshis(amount_msat)
+			.build_raw()
+			.unwrap();
+		let sig =
+			nodes[payee].keys_manager.backing.sign_invoice(&invoice, Recipient::Node).unwrap();
+		let invoice = invoice.sign::<_, ()>(|_| Ok(sig)).unwrap();
+		let invoice = Bolt11Invoice::from_signed(invoice).unwrap();
+		BatchedBolt11Invoice { invoice, payment_id: PaymentId(payment_hash.0), amount_msats: None }
+	};
+	let invoices =
+		vec![build_invoice(1, 20_000), build_invoice(3, 30_000), build_invoice(4, 10_000)];
+	let payment_ids = invoices.iter().map(|batched| batched.payment_id).collect::<Vec<_>>();
+
+	let mut duplicate_invoices = invoices.clone();
+	duplicate_invoices.push(invoices[0].clone());
+	match nodes[0].node.pay_for_bolt11_invoices(
+		duplicate_invoices,
+		BatchPaymentParameters::default(),
+		Retry::Attempts(0),
+	) {
+		Err(BatchPaymentError::DuplicatePaymentId(payment_id)) => {
+			assert_eq!(payment_id, payment_ids[0])
+		},
+		_ => panic!("Unexpected result"),
+	}
+
+	let params = BatchPaymentParameters { all_or_nothing: true, ..Default::default() };
+	match nodes[0].node.pay_for_bolt11_invoices(invoices.clone(), params, Retry::Attempts(0)) {
+		Err(BatchPaymentError::RouteNotFound { payment_id }) => {
+			assert_eq!(payment_id, payment_ids[2])
+		},
+		_ => panic!("Unexpected result"),
+	}
+
+	// Only the payment to node 3 pays fees, which don't fit into a zero budget.
+	let mut params = BatchPaymentParameters::default();
+	params.route_params_config.max_total_routing_fee_msat = Some(0);
+	match nodes[0].node.pay_for_bolt11_invoices(invoices.clone(), params, Retry::Attempts(0)) {
+		Err(BatchPaymentError::FeeBudgetExceeded { required_fee_msat }) => {
+			assert!(required_fee_msat > 0)
+		},
+		_ => panic!("Unexpected result"),
+	}
+	assert!(nodes[0].node.get_and_clear_pending_msg_events().is_empty());
+
+	// Without all-or-nothing, the routable payments are sent, largest first.
+	let batch = nodes[0]
+		.node
+		.pay_for_bolt11_invoices(invoices, BatchPaymentParameters::default(), Retry::Attempts(0))
+		.unwrap();
+	let not_sent = Bolt11PaymentError::SendingFailed(RetryableSendFailure::RouteNotFound);
+	assert_eq!(
+		batch.payments(),
+		&[
+			(payment_ids[1], BatchedPaymentStatus::Pending),
+			(payment_ids[0], BatchedPaymentStatus::Pending),
+			(payment_ids[2], BatchedPaymentStatus::NotSent(not_sent)),
+		]
+	);
+	assert!(!batch.is_complete());
+
+	check_added_monitors(&nodes[0], 2);
+	assert_eq!(nodes[0].node.get_and_clear_pending_msg_events().len(), 2);
+}
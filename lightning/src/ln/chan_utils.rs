@@ -29,7 +29,10 @@ use bitcoin::hashes::{Hash, HashEngine};
 use crate::chain::chaininterface::{
 	fee_for_weight, ConfirmationTarget, FeeEstimator, LowerBoundedFeeEstimator,
 };
-use crate::chain::package::WEIGHT_REVOKED_OUTPUT;
+use crate::chain::package::{
+	weight_offered_htlc, weight_received_htlc, weight_revoked_offered_htlc,
+	weight_revoked_received_htlc, WEIGHT_REVOKED_OUTPUT,
+};
 use crate::ln::msgs::DecodeError;
 use crate::sign::EntropySource;
 use crate::types::payment::{PaymentHash, PaymentPreimage};
@@ -41,6 +44,17 @@ use bitcoin::locktime::absolute::LockTime;
 use bitcoin::secp256k1::{ecdsa::Signature, Message, Secp256k1};
 use bitcoin::secp256k1::{PublicKey, Scalar, SecretKey};
 use bitcoin::{secp256k1, Sequence, Witness};
+#[cfg(feature = "ptlc_experimental")]
+use bitcoin::{
+	secp256k1::schnorr,
+	sighash::TapSighashType,
+	taproot::{LeafVersion, Signature as TaprootSignature},
+};
+#[cfg(any(taproot, feature = "ptlc_experimental"))]
+use bitcoin::{
+	secp256k1::XOnlyPublicKey,
+	taproot::{TaprootBuilder, TaprootSpendInfo},
+};
 
 use super::channel_keys::{
 	DelayedPaymentBasepoint, DelayedPaymentKey, HtlcBasepoint, HtlcKey, RevocationBasepoint,
@@ -293,6 +307,118 @@ pub(crate) fn commit_tx_fee_sat(feerate_per_kw: u32, num_htlcs: usize, channel_t
 		/ 1000
 }
 
+/// The weights LDK uses when estimating the fees of the commitment, HTLC and claim transactions of
+/// a channel of a given type, see [`ChannelTypeWeights::for_channel_type`].
+///
+/// These are the same values used internally, allowing external fee calculators and signer
+/// policies to rely on them rather than hardcoding their own. All weights are in weight units and
+/// assume maximum-size signatures where applicable.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChannelTypeWeights {
+	/// The weight of a commitment transaction without any non-dust HTLC outputs.
+	pub commitment_tx_base_weight: u64,
+	/// The weight each non-dust HTLC output adds to a commitment transaction.
+	pub commitment_tx_weight_per_htlc: u64,
+	/// The weight of a standalone HTLC-Success transaction.
+	pub htlc_success_tx_weight: u64,
+	/// The weight of a standalone HTLC-Timeout transaction.
+	pub htlc_timeout_tx_weight: u64,
+	/// The weight of a single input-output pair in an externally funded, aggregated HTLC-Success
+	/// transaction, or `None` if HTLC transactions can't be aggregated for this channel type.
+	pub aggregated_htlc_success_input_output_pair_weight: Option<u64>,
+	/// The weight of a single input-output pair in an externally funded, aggregated HTLC-Timeout
+	/// transaction, or `None` if HTLC transactions can't be aggregated for this channel type.
+	pub aggregated_htlc_timeout_input_output_pair_weight: Option<u64>,
+	/// The weight of the witness spending an anchor output, or `None` if the channel type has no
+	/// anchor outputs.
+	pub anchor_input_witness_weight: Option<u64>,
+	/// The weight of the witness spending the funding output.
+	pub funding_input_witness_weight: u64,
+	/// The weight of the witness claiming an HTLC output we received on a commitment transaction
+	/// with its preimage.
+	pub received_htlc_preimage_claim_witness_weight: u64,
+	/// The weight of the witness claiming an HTLC output we offered on a commitment transaction
+	/// once it timed out.
+	pub offered_htlc_timeout_claim_witness_weight: u64,
+	/// The weight of the witness claiming an offered HTLC output on a revoked commitment
+	/// transaction.
+	pub revoked_offered_htlc_claim_witness_weight: u64,
+	/// The weight of the witness claiming a received HTLC output on a revoked commitment
+	/// transaction.
+	pub revoked_received_htlc_claim_witness_weight: u64,
+	/// The weight of the witness claiming a `to_local` output, or the output of a second-stage
+	/// HTLC transaction, on a revoked commitment transaction.
+	pub revoked_output_claim_witness_weight: u64,
+}
+
+impl ChannelTypeWeights {
+	/// Gets the weights for the given channel type.
+	pub fn for_channel_type(channel_type_features: &ChannelTypeFeatures) -> Self {
+		let supports_htlc_aggregation = channel_type_features.supports_anchors_zero_fee_htlc_tx()
+			|| channel_type_features.supports_anchor_zero_fee_commitments();
+		let anchor_input_witness_weight =
+			if channel_type_features.supports_anchors_zero_fee_htlc_tx() {
+				Some(ANCHOR_INPUT_WITNESS_WEIGHT)
+			} else if channel_type_features.supports_anchor_zero_fee_commitments() {
+				Some(EMPTY_WITNESS_WEIGHT)
+			} else {
+				None
+			};
+		Self {
+			commitment_tx_base_weight: commitment_tx_base_weight(channel_type_features),
+			commitment_tx_weight_per_htlc: COMMITMENT_TX_WEIGHT_PER_HTLC,
+			htlc_success_tx_weight: htlc_success_tx_weight(channel_type_features),
+			htlc_timeout_tx_weight: htlc_timeout_tx_weight(channel_type_features),
+			aggregated_htlc_success_input_output_pair_weight: supports_htlc_aggregation
+				.then(|| aggregated_htlc_success_input_output_pair_weight(channel_type_features)),
+			aggregated_htlc_timeout_input_output_pair_weight: supports_htlc_aggregation
+				.then(|| aggregated_htlc_timeout_input_output_pair_weight(channel_type_features)),
+			anchor_input_witness_weight,
+			funding_input_witness_weight: FUNDING_TRANSACTION_WITNESS_WEIGHT,
+			// The witnesses below are for outputs on our counterparty's commitment transaction,
+			// i.e., HTLCs we received are offered by them and vice versa.
+			received_htlc_preimage_claim_witness_weight: weight_offered_htlc(channel_type_features),
+			offered_htlc_timeout_claim_witness_weight: weight_received_htlc(channel_type_features),
+			revoked_offered_htlc_claim_witness_weight: weight_revoked_offered_htlc(
+				channel_type_features,
+			),
+			revoked_received_htlc_claim_witness_weight: weight_revoked_received_htlc(
+				channel_type_features,
+			),
+			revoked_output_claim_witness_weight: WEIGHT_REVOKED_OUTPUT,
+		}
+	}
+}
+
+/// The amount of weight, per input, by which a fully-signed transaction may fall short of our
+/// predicted weight. Our estimates assume 73-byte signatures and 3-byte CLTV/CSV script numbers,
+/// while actual (low-R) signatures are usually a couple of bytes shorter and script numbers may be
+/// encoded in fewer bytes, and the BOLT 3 HTLC-success weight is itself off by a few units.
+pub(crate) const WEIGHT_ESTIMATE_TOLERANCE_PER_INPUT: u64 = 16;
+
+/// Checks, in debug builds only, that the weight of a fully-signed transaction matches the weight
+/// we predicted for it (and thus paid fees on). The prediction must never be below the actual
+/// weight, or we'd undershoot our target feerate, and must not exceed it by more than
+/// [`WEIGHT_ESTIMATE_TOLERANCE_PER_INPUT`] per input, which would indicate a misestimation.
+pub(crate) fn debug_assert_weight_estimate(tx: &Transaction, predicted_weight: u64) {
+	let actual_weight = tx.weight().to_wu();
+	debug_assert!(
+		actual_weight <= predicted_weight,
+		"Transaction {} weighs {} WU, more than the predicted {} WU",
+		tx.compute_txid(),
+		actual_weight,
+		predicted_weight
+	);
+	debug_assert!(
+		predicted_weight - actual_weight
+			<= WEIGHT_ESTIMATE_TOLERANCE_PER_INPUT * tx.input.len() as u64,
+		"Transaction {} weighs {} WU, much less than the predicted {} WU",
+		tx.compute_txid(),
+		actual_weight,
+		predicted_weight
+	);
+}
+
 /// Returns the fees for success and timeout second stage HTLC transactions.
 pub(crate) fn second_stage_tx_fees_sat(
 	channel_type: &ChannelTypeFeatures, feerate_sat_per_1000_weight: u32,
@@ -929,6 +1055,406 @@ pub(crate) fn build_htlc_output(
 	}
 }
 
+/// Information about a PTLC as it appears in a commitment transaction of an experimental PTLC
+/// channel (see [`ChannelTypeFeatures::supports_ptlc_experimental`]).
+///
+/// This mirrors [`HTLCOutputInCommitment`], but rather than being locked to the hash of a
+/// preimage, the output can only be claimed by revealing the discrete log of the `payment_point`,
+/// which is done by completing an [`AdaptorSignature`].
+///
+/// [`AdaptorSignature`]: crate::sign::ptlc::AdaptorSignature
+#[cfg(feature = "ptlc_experimental")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PtlcOutputInCommitment {
+	/// Whether the PTLC was "offered" (ie outbound in relation to this commitment transaction).
+	/// Note that, as with [`HTLCOutputInCommitment::offered`], this is not the same as whether it
+	/// is outbound *from us*.
+	pub offered: bool,
+	/// The value, in msat, of the PTLC. The value as it appears in the commitment transaction is
+	/// this divided by 1000.
+	pub amount_msat: u64,
+	/// The CLTV lock-time at which this PTLC expires.
+	pub cltv_expiry: u32,
+	/// The point whose discrete log unlocks this PTLC.
+	pub payment_point: PublicKey,
+	/// The position within the commitment transactions' outputs. This may be None if the value is
+	/// below the dust limit (in which case no output appears in the commitment transaction and the
+	/// value is spent to additional transaction fees).
+	pub transaction_output_index: Option<u32>,
+}
+
+#[cfg(feature = "ptlc_experimental")]
+impl PtlcOutputInCommitment {
+	/// Converts PTLC's value with millisatoshi precision into [bitcoin::Amount] with satoshi
+	/// precision.
+	pub const fn to_bitcoin_amount(&self) -> Amount {
+		Amount::from_sat(self.amount_msat / 1000)
+	}
+}
+
+#[cfg(feature = "ptlc_experimental")]
+impl_writeable_tlv_based!(PtlcOutputInCommitment, {
+	(0, offered, required),
+	(2, amount_msat, required),
+	(4, cltv_expiry, required),
+	(6, payment_point, required),
+	(8, transaction_output_index, option),
+});
+
+/// Gets the tapscript leaf of a PTLC output in a commitment transaction, requiring signatures
+/// from both the countersignatory's and the broadcaster's HTLC keys.
+///
+/// Unlike HTLC outputs, a PTLC output is never claimed directly from the commitment transaction.
+/// Both the success and timeout paths go through a pre-signed second-stage transaction (see
+/// [`build_ptlc_transaction`]), with the timeout enforced by the transaction's lock-time and the
+/// success path by the adaptor signature the countersignatory hands out for it.
+#[cfg(feature = "ptlc_experimental")]
+pub fn get_ptlc_tapscript(
+	broadcaster_htlc_key: &HtlcKey, countersignatory_htlc_key: &HtlcKey,
+) -> ScriptBuf {
+	let broadcaster_htlc_key = XOnlyPublicKey::from(broadcaster_htlc_key.to_public_key());
+	let countersignatory_htlc_key = XOnlyPublicKey::from(countersignatory_htlc_key.to_public_key());
+	Builder::new()
+		.push_x_only_key(&countersignatory_htlc_key)
+		.push_opcode(opcodes::all::OP_CHECKSIGVERIFY)
+		.push_x_only_key(&broadcaster_htlc_key)
+		.push_opcode(opcodes::all::OP_CHECKSIG)
+		.into_script()
+}
+
+/// Gets the [`TaprootSpendInfo`] of a PTLC output in a commitment transaction.
+///
+/// The revocation key is used as the internal key, allowing the countersignatory to sweep the
+/// output via the key path if the commitment transaction has been revoked. The only script leaf is
+/// the one returned by [`get_ptlc_tapscript`].
+#[cfg(feature = "ptlc_experimental")]
+pub fn get_ptlc_taproot_spend_info<C: secp256k1::Verification>(
+	secp_ctx: &Secp256k1<C>, broadcaster_htlc_key: &HtlcKey, countersignatory_htlc_key: &HtlcKey,
+	revocation_key: &RevocationKey,
+) -> TaprootSpendInfo {
+	let tapscript = get_ptlc_tapscript(broadcaster_htlc_key, countersignatory_htlc_key);
+	let internal_key = XOnlyPublicKey::from(revocation_key.to_public_key());
+	TaprootBuilder::new()
+		.add_leaf(0, tapscript)
+		.expect("A single leaf at depth 0 is always valid")
+		.finalize(secp_ctx, internal_key)
+		.expect("A tree with a single leaf is always complete")
+}
+
+/// Gets the P2TR script pubkey of a PTLC output in a commitment transaction.
+#[cfg(feature = "ptlc_experimental")]
+pub fn get_ptlc_output_script<C: secp256k1::Verification>(
+	secp_ctx: &Secp256k1<C>, keys: &TxCreationKeys,
+) -> ScriptBuf {
+	let spend_info = get_ptlc_taproot_spend_info(
+		secp_ctx,
+		&keys.broadcaster_htlc_key,
+		&keys.countersignatory_htlc_key,
+		&keys.revocation_key,
+	);
+	ScriptBuf::new_p2tr_tweaked(spend_info.output_key())
+}
+
+/// Builds an unsigned PTLC-Success or PTLC-Timeout transaction from the given channel and PTLC
+/// parameters.
+///
+/// The transaction pays to the same revokeable script as an HTLC transaction. As PTLC
+/// transactions are smaller than their HTLC counterparts, the HTLC transaction fee schedule is
+/// used.
+///
+/// Panics if ptlc.transaction_output_index.is_none() (as such PTLCs do not appear in the
+/// commitment transaction).
+#[cfg(feature = "ptlc_experimental")]
+pub fn build_ptlc_transaction(
+	commitment_txid: &Txid, feerate_per_kw: u32, contest_delay: u16, ptlc: &PtlcOutputInCommitment,
+	channel_type_features: &ChannelTypeFeatures,
+	broadcaster_delayed_payment_key: &DelayedPaymentKey, revocation_key: &RevocationKey,
+) -> Transaction {
+	let txin = TxIn {
+		previous_output: OutPoint {
+			txid: *commitment_txid,
+			vout: ptlc
+				.transaction_output_index
+				.expect("Can't build a PTLC transaction for a dust output"),
+		},
+		script_sig: ScriptBuf::new(),
+		sequence: Sequence(if channel_type_features.supports_anchors_zero_fee_htlc_tx() {
+			1
+		} else {
+			0
+		}),
+		witness: Witness::new(),
+	};
+
+	let (ptlc_success_tx_fee_sat, ptlc_timeout_tx_fee_sat) =
+		second_stage_tx_fees_sat(channel_type_features, feerate_per_kw);
+	let total_fee = if ptlc.offered { ptlc_timeout_tx_fee_sat } else { ptlc_success_tx_fee_sat };
+	let txout = TxOut {
+		script_pubkey: get_revokeable_redeemscript(
+			revocation_key,
+			contest_delay,
+			broadcaster_delayed_payment_key,
+		)
+		.to_p2wsh(),
+		value: ptlc.to_bitcoin_amount() - Amount::from_sat(total_fee),
+	};
+
+	let version = if channel_type_features.supports_anchor_zero_fee_commitments() {
+		Version::non_standard(3)
+	} else {
+		Version::TWO
+	};
+
+	Transaction {
+		version,
+		lock_time: LockTime::from_consensus(if ptlc.offered { ptlc.cltv_expiry } else { 0 }),
+		input: vec![txin],
+		output: vec![txout],
+	}
+}
+
+/// Returns the [`TapSighashType`] the countersignatory's signature on a PTLC transaction commits
+/// to. As with HTLC transactions, anchor channels allow the broadcaster to attach additional
+/// inputs and outputs to bump the transaction's fee.
+#[cfg(feature = "ptlc_experimental")]
+pub fn ptlc_countersignatory_sighash_type(
+	channel_type_features: &ChannelTypeFeatures,
+) -> TapSighashType {
+	if channel_type_features.supports_anchors_zero_fee_htlc_tx()
+		|| channel_type_features.supports_anchor_zero_fee_commitments()
+	{
+		TapSighashType::SinglePlusAnyoneCanPay
+	} else {
+		TapSighashType::Default
+	}
+}
+
+/// Returns the witness required to spend a PTLC output via its tapscript leaf.
+///
+/// For a PTLC-Success transaction, `remote_sig` is the countersignatory's adaptor signature,
+/// completed with the discrete log of the PTLC's payment point.
+#[cfg(feature = "ptlc_experimental")]
+pub fn build_ptlc_input_witness(
+	local_sig: &schnorr::Signature, remote_sig: &schnorr::Signature, spend_info: &TaprootSpendInfo,
+	tapscript: &Script, channel_type_features: &ChannelTypeFeatures,
+) -> Witness {
+	let control_block = spend_info
+		.control_block(&(tapscript.to_owned(), LeafVersion::TapScript))
+		.expect("The tapscript must be a leaf of the spend info");
+	let local_sig =
+		TaprootSignature { signature: *local_sig, sighash_type: TapSighashType::Default };
+	let remote_sig = TaprootSignature {
+		signature: *remote_sig,
+		sighash_type: ptlc_countersignatory_sighash_type(channel_type_features),
+	};
+
+	let mut witness = Witness::new();
+	// The broadcaster's key is checked last, so its signature goes at the bottom of the stack.
+	witness.push(local_sig.to_vec());
+	witness.push(remote_sig.to_vec());
+	witness.push(tapscript.to_bytes());
+	witness.push(control_block.serialize());
+	witness
+}
+
+/// The x-coordinate of the "nothing up my sleeve" point suggested by BIP 341, which has no known
+/// discrete log. Used as the internal key of taproot outputs which may only be spent via one of
+/// their script leaves.
+#[cfg(taproot)]
+const TAPROOT_NUMS_POINT: [u8; 32] = [
+	0x50, 0x92, 0x9b, 0x74, 0xc1, 0xa0, 0x49, 0x54, 0xb7, 0x8b, 0x4b, 0x60, 0x35, 0xe9, 0x7a, 0x5e,
+	0x07, 0x8a, 0x5a, 0x0f, 0x28, 0xec, 0x96, 0xd5, 0x47, 0xbf, 0xee, 0x9a, 0xce, 0x80, 0x3a, 0xc0,
+];
+
+/// Gets the delay and revocation tapscript leaves of the `to_local` output of a taproot
+/// commitment transaction, in that order.
+#[cfg(taproot)]
+pub(crate) fn get_taproot_to_local_scripts(
+	revocation_key: &RevocationKey, contest_delay: u16,
+	broadcaster_delayed_payment_key: &DelayedPaymentKey,
+) -> (ScriptBuf, ScriptBuf) {
+	let delayed_key = XOnlyPublicKey::from(broadcaster_delayed_payment_key.to_public_key());
+	let revocation_key = XOnlyPublicKey::from(revocation_key.to_public_key());
+	let delay_script = Builder::new()
+		.push_x_only_key(&delayed_key)
+		.push_opcode(opcodes::all::OP_CHECKSIGVERIFY)
+		.push_int(contest_delay as i64)
+		.push_opcode(opcodes::all::OP_CSV)
+		.into_script();
+	// The delayed payment key is committed to so that the revocation leaf's script, and thus the
+	// output, is unique to the commitment transaction.
+	let revocation_script = Builder::new()
+		.push_x_only_key(&delayed_key)
+		.push_opcode(opcodes::all::OP_DROP)
+		.push_x_only_key(&revocation_key)
+		.push_opcode(opcodes::all::OP_CHECKSIG)
+		.into_script();
+	(delay_script, revocation_script)
+}
+
+/// Gets the [`TaprootSpendInfo`] of the `to_local` output of a taproot commitment transaction.
+///
+/// As the output must not be spendable without either waiting for the `contest_delay` or knowing
+/// the revocation secret, its internal key has no known discrete log and both spend paths are
+/// script leaves, see [`get_taproot_to_local_scripts`].
+#[cfg(taproot)]
+pub(crate) fn get_taproot_to_local_spend_info<C: secp256k1::Verification>(
+	secp_ctx: &Secp256k1<C>, revocation_key: &RevocationKey, contest_delay: u16,
+	broadcaster_delayed_payment_key: &DelayedPaymentKey,
+) -> TaprootSpendInfo {
+	let (delay_script, revocation_script) = get_taproot_to_local_scripts(
+		revocation_key,
+		contest_delay,
+		broadcaster_delayed_payment_key,
+	);
+	let internal_key =
+		XOnlyPublicKey::from_slice(&TAPROOT_NUMS_POINT).expect("The NUMS point is a valid key");
+	TaprootBuilder::new()
+		.add_leaf(1, delay_script)
+		.and_then(|builder| builder.add_leaf(1, revocation_script))
+		.expect("Two leaves at depth 1 are always valid")
+		.finalize(secp_ctx, internal_key)
+		.expect("A tree with two leaves at depth 1 is always complete")
+}
+
+/// Gets the success and timeout tapscript leaves of an HTLC output in a taproot commitment
+/// transaction, in that order.
+///
+/// The leaf requiring signatures from both the broadcaster's and the countersignatory's HTLC keys
+/// is the one spent by the second-stage HTLC transaction, i.e. the timeout leaf for offered HTLCs
+/// and the success leaf for received HTLCs. The other leaf allows the countersignatory to claim
+/// the HTLC directly from the commitment transaction.
+#[cfg(taproot)]
+pub(crate) fn get_taproot_htlc_scripts(
+	htlc: &HTLCOutputInCommitment, broadcaster_htlc_key: &HtlcKey,
+	countersignatory_htlc_key: &HtlcKey,
+) -> (ScriptBuf, ScriptBuf) {
+	let broadcaster_htlc_key = XOnlyPublicKey::from(broadcaster_htlc_key.to_public_key());
+	let countersignatory_htlc_key = XOnlyPublicKey::from(countersignatory_htlc_key.to_public_key());
+	let payment_hash160 = Ripemd160::hash(&htlc.payment_hash.0[..]).to_byte_array();
+	let preimage_check = Builder::new()
+		.push_opcode(opcodes::all::OP_SIZE)
+		.push_int(32)
+		.push_opcode(opcodes::all::OP_EQUALVERIFY)
+		.push_opcode(opcodes::all::OP_HASH160)
+		.push_slice(&payment_hash160)
+		.push_opcode(opcodes::all::OP_EQUALVERIFY);
+	if htlc.offered {
+		let success_script = preimage_check
+			.push_x_only_key(&countersignatory_htlc_key)
+			.push_opcode(opcodes::all::OP_CHECKSIG)
+			.push_int(1)
+			.push_opcode(opcodes::all::OP_CSV)
+			.push_opcode(opcodes::all::OP_DROP)
+			.into_script();
+		let timeout_script = Builder::new()
+			.push_x_only_key(&broadcaster_htlc_key)
+			.push_opcode(opcodes::all::OP_CHECKSIGVERIFY)
+			.push_x_only_key(&countersignatory_htlc_key)
+			.push_opcode(opcodes::all::OP_CHECKSIG)
+			.into_script();
+		(success_script, timeout_script)
+	} else {
+		let success_script = preimage_check
+			.push_x_only_key(&broadcaster_htlc_key)
+			.push_opcode(opcodes::all::OP_CHECKSIGVERIFY)
+			.push_x_only_key(&countersignatory_htlc_key)
+			.push_opcode(opcodes::all::OP_CHECKSIG)
+			.into_script();
+		let timeout_script = Builder::new()
+			.push_x_only_key(&countersignatory_htlc_key)
+			.push_opcode(opcodes::all::OP_CHECKSIG)
+			.push_int(1)
+			.push_opcode(opcodes::all::OP_CSV)
+			.push_opcode(opcodes::all::OP_DROP)
+			.push_int(htlc.cltv_expiry as i64)
+			.push_opcode(opcodes::all::OP_CLTV)
+			.push_opcode(opcodes::all::OP_DROP)
+			.into_script();
+		(success_script, timeout_script)
+	}
+}
+
+/// Gets the [`TaprootSpendInfo`] of an HTLC output in a taproot commitment transaction.
+///
+/// The revocation key is used as the internal key, allowing the countersignatory to sweep the
+/// output via the key path if the commitment transaction has been revoked. The script leaves are
+/// the ones returned by [`get_taproot_htlc_scripts`].
+#[cfg(taproot)]
+pub(crate) fn get_taproot_htlc_spend_info<C: secp256k1::Verification>(
+	secp_ctx: &Secp256k1<C>, htlc: &HTLCOutputInCommitment, broadcaster_htlc_key: &HtlcKey,
+	countersignatory_htlc_key: &HtlcKey, revocation_key: &RevocationKey,
+) -> TaprootSpendInfo {
+	let (success_script, timeout_script) =
+		get_taproot_htlc_scripts(htlc, broadcaster_htlc_key, countersignatory_htlc_key);
+	let internal_key = XOnlyPublicKey::from(revocation_key.to_public_key());
+	TaprootBuilder::new()
+		.add_leaf(1, success_script)
+		.and_then(|builder| builder.add_leaf(1, timeout_script))
+		.expect("Two leaves at depth 1 are always valid")
+		.finalize(secp_ctx, internal_key)
+		.expect("A tree with two leaves at depth 1 is always complete")
+}
+
+/// The minimum number of HTLC signatures at which [`verify_htlc_signatures`] will spread the
+/// verification work across threads. Below this, spawning threads costs more than it saves.
+#[cfg(feature = "std")]
+pub(crate) const PARALLEL_HTLC_SIG_VERIFICATION_THRESHOLD: usize = 16;
+
+/// Verifies each of the counterparty's HTLC `signatures` against the corresponding `sighashes`
+/// using the countersignatory's HTLC `pubkey`.
+///
+/// libsecp256k1 does not offer ECDSA batch verification, so when built with `std` and given at
+/// least [`PARALLEL_HTLC_SIG_VERIFICATION_THRESHOLD`] signatures we instead split them across the
+/// available cores. On failure, the index of the first invalid signature is returned.
+pub(crate) fn verify_htlc_signatures<C: secp256k1::Verification>(
+	secp_ctx: &Secp256k1<C>, sighashes: &[Message], signatures: &[Signature], pubkey: &PublicKey,
+) -> Result<(), usize> {
+	debug_assert_eq!(sighashes.len(), signatures.len());
+	let verify_range = |start: usize, end: usize| -> Result<(), usize> {
+		for idx in start..end {
+			if secp_ctx.verify_ecdsa(&sighashes[idx], &signatures[idx], pubkey).is_err() {
+				return Err(idx);
+			}
+		}
+		Ok(())
+	};
+
+	#[cfg(feature = "std")]
+	if signatures.len() >= PARALLEL_HTLC_SIG_VERIFICATION_THRESHOLD {
+		let threads = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+		if threads > 1 {
+			let chunk_len = (signatures.len() + threads - 1) / threads;
+			return std::thread::scope(|scope| {
+				let mut handles = Vec::with_capacity(threads);
+				let mut start = 0;
+				while start < signatures.len() {
+					let end = cmp::min(start + chunk_len, signatures.len());
+					// If we fail to spawn a thread (e.g. due to resource limits), just verify the
+					// chunk on the current thread instead.
+					let handle = std::thread::Builder::new()
+						.spawn_scoped(scope, move || verify_range(start, end))
+						.map_err(|_| verify_range(start, end));
+					handles.push(handle);
+					start = end;
+				}
+				// Chunks are in order, so the first failing chunk holds the first failing index.
+				for handle in handles {
+					match handle {
+						Ok(handle) => {
+							handle.join().expect("HTLC signature verification panicked")?
+						},
+						Err(res) => res?,
+					}
+				}
+				Ok(())
+			});
+		}
+	}
+
+	verify_range(0, signatures.len())
+}
+
 /// Returns the witness required to satisfy and spend a HTLC input.
 pub fn build_htlc_input_witness(
 	local_sig: &Signature, remote_sig: &Signature, preimage: &Option<PaymentPreimage>,
@@ -1411,6 +1937,17 @@ impl HolderCommitmentTransaction {
 		}
 
 		tx.input[0].witness.push(funding_redeemscript.as_bytes().to_vec());
+
+		// Trimmed outputs mean the estimate used for the commitment fee is only an upper bound.
+		// Zero-fee commitments pay no fee, so their weight estimate doesn't matter.
+		let channel_type_features = &self.inner.channel_type_features;
+		if !channel_type_features.supports_anchor_zero_fee_commitments() {
+			debug_assert!(
+				tx.weight().to_wu()
+					<= commitment_tx_base_weight(channel_type_features)
+						+ self.inner.nondust_htlcs.len() as u64 * COMMITMENT_TX_WEIGHT_PER_HTLC
+			);
+		}
 		tx
 	}
 }
@@ -2224,6 +2761,267 @@ impl<'a> TrustedCommitmentTransaction<'a> {
 		justice_tx.output[0].value = value.checked_sub(fee).ok_or(())?;
 		Ok(justice_tx)
 	}
+
+	/// Helper method to build an unsigned justice transaction spending every revokeable output of
+	/// this commitment transaction, i.e. its `to_local` output as well as all of its non-dust HTLC
+	/// outputs, to a destination script. Fee estimation accounts for the expected revocation
+	/// witness data that will be added when signed.
+	///
+	/// Returns the justice transaction along with a [`JusticeTxInput`] for each of its inputs,
+	/// which may be used to sign them.
+	///
+	/// This method will error if the given fee rate results in an output below the dust limit of
+	/// `destination_script`, i.e., a transaction which would not be relayed, or if there exist no
+	/// revokeable outputs on this commitment transaction.
+	///
+	/// Like [`Self::build_to_local_justice_tx`], the built transaction will allow fee bumping with
+	/// RBF.
+	pub fn build_justice_tx(
+		&self, feerate_per_kw: u32, destination_script: ScriptBuf,
+	) -> Result<(Transaction, Vec<JusticeTxInput>), ()> {
+		let txid = self.txid();
+		let keys = self.keys();
+		let channel_type_features = self.channel_type_features();
+		let built_tx = &self.inner.built.transaction;
+
+		let mut justice_tx = Transaction {
+			version: Version::TWO,
+			lock_time: LockTime::ZERO,
+			input: Vec::new(),
+			output: vec![TxOut { script_pubkey: destination_script, value: Amount::ZERO }],
+		};
+		let mut inputs = Vec::new();
+		let mut witness_weight = 0;
+		let mut add_input = |vout: usize, htlc: Option<&HTLCOutputInCommitment>, weight: u64| {
+			let witness_script = match htlc {
+				Some(htlc) => get_htlc_redeemscript(htlc, channel_type_features, keys),
+				None => get_revokeable_redeemscript(
+					&keys.revocation_key,
+					self.to_broadcaster_delay.expect("Revokeable output implies a known delay"),
+					&keys.broadcaster_delayed_payment_key,
+				),
+			};
+			justice_tx.input.push(TxIn {
+				previous_output: OutPoint { txid, vout: vout as u32 },
+				script_sig: ScriptBuf::new(),
+				sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+				witness: Witness::new(),
+			});
+			inputs.push(JusticeTxInput {
+				value: built_tx.output[vout].value,
+				htlc: htlc.cloned(),
+				witness_script,
+			});
+			witness_weight += weight;
+		};
+		if let Some(output_idx) = self.revokeable_output_index() {
+			add_input(output_idx, None, WEIGHT_REVOKED_OUTPUT);
+		}
+		for htlc in self.nondust_htlcs() {
+			let output_idx = htlc.transaction_output_index.ok_or(())? as usize;
+			let weight = if htlc.offered {
+				weight_revoked_offered_htlc(channel_type_features)
+			} else {
+				weight_revoked_received_htlc(channel_type_features)
+			};
+			add_input(output_idx, Some(htlc), weight);
+		}
+		if justice_tx.input.is_empty() {
+			return Err(());
+		}
+
+		let value = inputs.iter().map(|input| input.value).sum::<Amount>();
+		let weight = justice_tx.weight().to_wu() + witness_weight;
+		let fee = Amount::from_sat(fee_for_weight(feerate_per_kw, weight));
+		let output_value = value.checked_sub(fee).ok_or(())?;
+		if output_value < justice_tx.output[0].script_pubkey.minimal_non_dust() {
+			return Err(());
+		}
+		justice_tx.output[0].value = output_value;
+		Ok((justice_tx, inputs))
+	}
+}
+
+/// An input of a justice transaction built by [`build_justice_tx`] or
+/// [`TrustedCommitmentTransaction::build_justice_tx`], describing the revoked commitment
+/// transaction output it spends.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JusticeTxInput {
+	/// The value of the revoked output being spent.
+	pub value: Amount,
+	/// The HTLC being claimed, or `None` if the revokeable `to_local` output is being spent.
+	pub htlc: Option<HTLCOutputInCommitment>,
+	/// The witness script of the revoked output being spent.
+	pub witness_script: ScriptBuf,
+}
+
+impl JusticeTxInput {
+	/// Builds the witness spending the revoked output using a signature by the revocation key.
+	pub fn witness(&self, signature: &Signature, revocation_key: &RevocationKey) -> Witness {
+		let mut witness = Witness::new();
+		witness.push_ecdsa_signature(&BitcoinSignature::sighash_all(*signature));
+		if self.htlc.is_some() {
+			witness.push(revocation_key.to_public_key().serialize());
+		} else {
+			witness.push(&[1u8]);
+		}
+		witness.push(self.witness_script.as_bytes());
+		witness
+	}
+}
+
+/// Builds an unsigned justice transaction claiming every revokeable output of the given revoked
+/// counterparty commitment transaction to `destination_script`, for use by watchtowers and
+/// recovery tools operating outside of a [`ChannelMonitor`].
+///
+/// Unlike [`TrustedCommitmentTransaction::build_justice_tx`], the commitment transaction need not
+/// be trusted: it is verified against the (fully populated) `channel_parameters`, as seen from our
+/// side of the channel, and `per_commitment_secret` must be the secret our counterparty revealed
+/// when revoking it.
+///
+/// The resulting transaction may be signed with [`sign_justice_tx`] given the revocation base
+/// secret, or with [`EcdsaChannelSigner::sign_justice_revoked_output`] and
+/// [`EcdsaChannelSigner::sign_justice_revoked_htlc`] along with [`JusticeTxInput::witness`].
+///
+/// [`ChannelMonitor`]: crate::chain::channelmonitor::ChannelMonitor
+/// [`EcdsaChannelSigner::sign_justice_revoked_output`]: crate::sign::ecdsa::EcdsaChannelSigner::sign_justice_revoked_output
+/// [`EcdsaChannelSigner::sign_justice_revoked_htlc`]: crate::sign::ecdsa::EcdsaChannelSigner::sign_justice_revoked_htlc
+pub fn build_justice_tx<T: secp256k1::Signing + secp256k1::Verification>(
+	channel_parameters: &ChannelTransactionParameters, revoked_commitment: &CommitmentTransaction,
+	per_commitment_secret: &SecretKey, destination_script: ScriptBuf, feerate_per_kw: u32,
+	secp_ctx: &Secp256k1<T>,
+) -> Result<(Transaction, Vec<JusticeTxInput>), ()> {
+	if !channel_parameters.is_populated()
+		|| PublicKey::from_secret_key(secp_ctx, per_commitment_secret)
+			!= revoked_commitment.per_commitment_point()
+	{
+		return Err(());
+	}
+	let directed_parameters = channel_parameters.as_counterparty_broadcastable();
+	let trusted_tx = revoked_commitment.verify(&directed_parameters, secp_ctx)?;
+	trusted_tx.build_justice_tx(feerate_per_kw, destination_script)
+}
+
+/// Signs each input of a justice transaction built by [`build_justice_tx`] using our
+/// `revocation_base_secret` and the `per_commitment_secret` our counterparty revealed when
+/// revoking the commitment transaction, filling in the witnesses.
+///
+/// `inputs` must be the [`JusticeTxInput`]s returned alongside `justice_tx`.
+pub fn sign_justice_tx<T: secp256k1::Signing>(
+	justice_tx: &mut Transaction, inputs: &[JusticeTxInput], revocation_base_secret: &SecretKey,
+	per_commitment_secret: &SecretKey, secp_ctx: &Secp256k1<T>,
+) -> Result<(), ()> {
+	if justice_tx.input.len() != inputs.len() {
+		return Err(());
+	}
+	let revocation_secret =
+		derive_private_revocation_key(secp_ctx, per_commitment_secret, revocation_base_secret);
+	let revocation_key = RevocationKey::from_basepoint(
+		secp_ctx,
+		&RevocationBasepoint::from(PublicKey::from_secret_key(secp_ctx, revocation_base_secret)),
+		&PublicKey::from_secret_key(secp_ctx, per_commitment_secret),
+	);
+	let mut sighash_cache = sighash::SighashCache::new(&*justice_tx);
+	let mut witnesses = Vec::with_capacity(inputs.len());
+	for (input_idx, input) in inputs.iter().enumerate() {
+		let sighash = sighash_cache
+			.p2wsh_signature_hash(
+				input_idx,
+				&input.witness_script,
+				input.value,
+				EcdsaSighashType::All,
+			)
+			.map_err(|_| ())?;
+		let signature = sign(secp_ctx, &hash_to_message!(&sighash[..]), &revocation_secret);
+		witnesses.push(input.witness(&signature, &revocation_key));
+	}
+	for (txin, witness) in justice_tx.input.iter_mut().zip(witnesses) {
+		txin.witness = witness;
+	}
+	Ok(())
+}
+
+/// Validates a signed justice transaction claiming outputs of the given revoked counterparty
+/// commitment transaction, as built by [`build_justice_tx`] and signed with [`sign_justice_tx`]
+/// or a third-party implementation.
+///
+/// Checks that the commitment transaction is valid for the (fully populated)
+/// `channel_parameters`, that every input of `justice_tx` spends a revokeable output of the
+/// commitment transaction with a valid signature by the revocation key, and that the transaction
+/// does not spend more than it claims.
+pub fn validate_justice_tx<T: secp256k1::Signing + secp256k1::Verification>(
+	channel_parameters: &ChannelTransactionParameters, revoked_commitment: &CommitmentTransaction,
+	justice_tx: &Transaction, secp_ctx: &Secp256k1<T>,
+) -> Result<(), ()> {
+	if !channel_parameters.is_populated() || justice_tx.input.is_empty() {
+		return Err(());
+	}
+	let directed_parameters = channel_parameters.as_counterparty_broadcastable();
+	let trusted_tx = revoked_commitment.verify(&directed_parameters, secp_ctx)?;
+	let txid = trusted_tx.txid();
+	let keys = trusted_tx.keys();
+	let channel_type_features = trusted_tx.channel_type_features();
+	let built_tx = &trusted_tx.built_transaction().transaction;
+	let revocation_pubkey = keys.revocation_key.to_public_key();
+
+	let mut sighash_cache = sighash::SighashCache::new(justice_tx);
+	let mut value = Amount::ZERO;
+	for (input_idx, txin) in justice_tx.input.iter().enumerate() {
+		if txin.previous_output.txid != txid {
+			return Err(());
+		}
+		let vout = txin.previous_output.vout as usize;
+		let revoked_output = built_tx.output.get(vout).ok_or(())?;
+		let htlc = trusted_tx
+			.nondust_htlcs()
+			.iter()
+			.find(|htlc| htlc.transaction_output_index == Some(vout as u32));
+		let (witness_script, second_element) = match htlc {
+			Some(htlc) => (
+				get_htlc_redeemscript(htlc, channel_type_features, keys),
+				revocation_pubkey.serialize().to_vec(),
+			),
+			None if trusted_tx.revokeable_output_index() == Some(vout) => (
+				get_revokeable_redeemscript(
+					&keys.revocation_key,
+					directed_parameters.contest_delay(),
+					&keys.broadcaster_delayed_payment_key,
+				),
+				vec![1u8],
+			),
+			None => return Err(()),
+		};
+
+		let witness = txin.witness.to_vec();
+		if witness.len() != 3
+			|| witness[1] != second_element
+			|| witness[2] != witness_script.as_bytes()
+		{
+			return Err(());
+		}
+		let signature = BitcoinSignature::from_slice(&witness[0]).map_err(|_| ())?;
+		if signature.sighash_type != EcdsaSighashType::All {
+			return Err(());
+		}
+		let sighash = sighash_cache
+			.p2wsh_signature_hash(
+				input_idx,
+				&witness_script,
+				revoked_output.value,
+				EcdsaSighashType::All,
+			)
+			.map_err(|_| ())?;
+		secp_ctx
+			.verify_ecdsa(&hash_to_message!(&sighash[..]), &signature.signature, &revocation_pubkey)
+			.map_err(|_| ())?;
+		value += revoked_output.value;
+	}
+
+	let output_value = justice_tx.output.iter().map(|output| output.value).sum::<Amount>();
+	if output_value > value {
+		return Err(());
+	}
+	Ok(())
 }
 
 /// Commitment transaction numbers which appear in the transactions themselves are XOR'd with a
@@ -2257,15 +3055,19 @@ pub fn get_commitment_transaction_number_obscure_factor(
 
 #[cfg(test)]
 mod tests {
-	use super::{ChannelPublicKeys, CounterpartyCommitmentSecrets};
+	use super::{
+		build_justice_tx, sign_justice_tx, validate_justice_tx, verify_htlc_signatures,
+		ChannelPublicKeys, CounterpartyCommitmentSecrets,
+	};
 	use crate::chain;
 	use crate::ln::chan_utils::{
 		get_htlc_redeemscript, get_keyed_anchor_redeemscript,
 		get_to_countersigner_keyed_anchor_redeemscript, shared_anchor_script_pubkey,
-		BuiltCommitmentTransaction, ChannelTransactionParameters, CommitmentTransaction,
-		CounterpartyChannelTransactionParameters, HTLCOutputInCommitment,
-		TrustedCommitmentTransaction,
+		BuiltCommitmentTransaction, ChannelTransactionParameters, ChannelTypeWeights,
+		CommitmentTransaction, CounterpartyChannelTransactionParameters, HTLCOutputInCommitment,
+		TrustedCommitmentTransaction, ANCHOR_INPUT_WITNESS_WEIGHT, EMPTY_WITNESS_WEIGHT,
 	};
+	use crate::ln::channel_keys::RevocationBasepoint;
 	use crate::sign::{ChannelSigner, SignerProvider};
 	use crate::types::features::ChannelTypeFeatures;
 	use crate::types::payment::PaymentHash;
@@ -2274,7 +3076,7 @@ mod tests {
 	use bitcoin::hex::FromHex;
 	use bitcoin::secp256k1::{self, PublicKey, Secp256k1, SecretKey};
 	use bitcoin::PublicKey as BitcoinPublicKey;
-	use bitcoin::{CompressedPublicKey, Network, ScriptBuf, Txid};
+	use bitcoin::{Amount, CompressedPublicKey, Network, ScriptBuf, Txid};
 
 	#[allow(unused_imports)]
 	use crate::prelude::*;
@@ -2446,6 +3248,48 @@ mod tests {
 				   "0020215d61bba56b19e9eadb6107f5a85d7f99c40f65992443f69229c290165bc00d");
 	}
 
+	#[test]
+	fn test_channel_type_weights() {
+		let legacy =
+			ChannelTypeWeights::for_channel_type(&ChannelTypeFeatures::only_static_remote_key());
+		assert_eq!(legacy.commitment_tx_base_weight, 724);
+		assert_eq!(legacy.htlc_success_tx_weight, 703);
+		assert_eq!(legacy.htlc_timeout_tx_weight, 663);
+		assert!(legacy.aggregated_htlc_success_input_output_pair_weight.is_none());
+		assert!(legacy.aggregated_htlc_timeout_input_output_pair_weight.is_none());
+		assert!(legacy.anchor_input_witness_weight.is_none());
+
+		let anchors = ChannelTypeWeights::for_channel_type(
+			&ChannelTypeFeatures::anchors_zero_htlc_fee_and_dependencies(),
+		);
+		assert_eq!(anchors.commitment_tx_base_weight, 1124);
+		assert_eq!(anchors.commitment_tx_weight_per_htlc, legacy.commitment_tx_weight_per_htlc);
+		assert_eq!(anchors.htlc_success_tx_weight, 706);
+		assert_eq!(anchors.htlc_timeout_tx_weight, 666);
+		assert!(anchors.aggregated_htlc_success_input_output_pair_weight.is_some());
+		assert!(anchors.aggregated_htlc_timeout_input_output_pair_weight.is_some());
+		assert_eq!(anchors.anchor_input_witness_weight, Some(ANCHOR_INPUT_WITNESS_WEIGHT));
+		// The keyed anchors HTLC scripts are three bytes longer.
+		assert_eq!(
+			anchors.revoked_offered_htlc_claim_witness_weight,
+			legacy.revoked_offered_htlc_claim_witness_weight + 3
+		);
+		assert_eq!(
+			anchors.received_htlc_preimage_claim_witness_weight,
+			legacy.received_htlc_preimage_claim_witness_weight + 3
+		);
+
+		let mut zero_fee_commitments = ChannelTypeFeatures::only_static_remote_key();
+		zero_fee_commitments.set_anchor_zero_fee_commitments_required();
+		let zero_fee_commitments = ChannelTypeWeights::for_channel_type(&zero_fee_commitments);
+		assert_eq!(zero_fee_commitments.anchor_input_witness_weight, Some(EMPTY_WITNESS_WEIGHT));
+		assert!(zero_fee_commitments.aggregated_htlc_success_input_output_pair_weight.is_some());
+		assert_eq!(
+			zero_fee_commitments.revoked_offered_htlc_claim_witness_weight,
+			legacy.revoked_offered_htlc_claim_witness_weight
+		);
+	}
+
 	#[test]
 	fn test_finding_revokeable_output_index() {
 		let builder = TestCommitmentTxBuilder::new();
@@ -2502,6 +3346,131 @@ mod tests {
 		assert_eq!(justice_tx.output[0].script_pubkey, destination_script);
 	}
 
+	#[test]
+	fn test_building_and_validating_justice_tx() {
+		let mut builder = TestCommitmentTxBuilder::new();
+		let secp_ctx = Secp256k1::new();
+		let per_commitment_secret = SecretKey::from_slice(
+			&<Vec<u8>>::from_hex(
+				"1f1e1d1c1b1a191817161514131211100f0e0d0c0b0a09080706050403020100",
+			)
+			.unwrap()[..],
+		)
+		.unwrap();
+		let revocation_base_secret = SecretKey::from_slice(&[42; 32]).unwrap();
+		builder.channel_parameters.holder_pubkeys.revocation_basepoint = RevocationBasepoint::from(
+			PublicKey::from_secret_key(&secp_ctx, &revocation_base_secret),
+		);
+		builder.channel_parameters.holder_selected_contest_delay = 144;
+		builder.channel_parameters.channel_value_satoshis = 50_000;
+
+		for channel_type_features in [
+			ChannelTypeFeatures::only_static_remote_key(),
+			ChannelTypeFeatures::anchors_zero_htlc_fee_and_dependencies(),
+		] {
+			builder.channel_parameters.channel_type_features = channel_type_features;
+			let htlc = |offered| HTLCOutputInCommitment {
+				offered,
+				amount_msat: 5_000_000,
+				cltv_expiry: 100,
+				payment_hash: PaymentHash([42; 32]),
+				transaction_output_index: None,
+			};
+			let revoked_commitment = CommitmentTransaction::new(
+				42,
+				&builder.per_commitment_point,
+				10_000,
+				20_000,
+				253,
+				vec![htlc(true), htlc(false)],
+				&builder.channel_parameters.as_counterparty_broadcastable(),
+				&secp_ctx,
+			);
+
+			// The revealed per-commitment secret must match the commitment transaction.
+			let wrong_secret = SecretKey::from_slice(&[43; 32]).unwrap();
+			assert!(build_justice_tx(
+				&builder.channel_parameters,
+				&revoked_commitment,
+				&wrong_secret,
+				ScriptBuf::new(),
+				253,
+				&secp_ctx
+			)
+			.is_err());
+
+			let (justice_tx, inputs) = build_justice_tx(
+				&builder.channel_parameters,
+				&revoked_commitment,
+				&per_commitment_secret,
+				ScriptBuf::new(),
+				253,
+				&secp_ctx,
+			)
+			.unwrap();
+			assert_eq!(justice_tx.input.len(), 3);
+			assert_eq!(inputs.len(), 3);
+			assert_eq!(inputs.iter().filter(|input| input.htlc.is_none()).count(), 1);
+			assert!(justice_tx.output[0].value.to_sat() < 20_000);
+
+			// A feerate leaving less than the dust limit to the justice output is rejected. As the
+			// fee rounds up, the fee paid at 253 sat/KW bounds the transaction's weight from below,
+			// so any feerate above `(value - dust_limit) / min_weight` pays more than
+			// `value - dust_limit` in fees, leaving a dust output.
+			let value = inputs.iter().map(|input| input.value.to_sat()).sum::<u64>();
+			let fee = value - justice_tx.output[0].value.to_sat();
+			let min_weight = (fee - 1) * 1000 / 253;
+			let dust_limit = justice_tx.output[0].script_pubkey.minimal_non_dust().to_sat();
+			let dust_feerate = ((value - dust_limit) * 1000 / min_weight + 1) as u32;
+			let build = |feerate_per_kw| {
+				build_justice_tx(
+					&builder.channel_parameters,
+					&revoked_commitment,
+					&per_commitment_secret,
+					ScriptBuf::new(),
+					feerate_per_kw,
+					&secp_ctx,
+				)
+			};
+			assert!(build(dust_feerate).is_err());
+			assert!(build(dust_feerate / 2).is_ok());
+
+			// An unsigned justice transaction is not valid.
+			let validate = |tx: &bitcoin::Transaction| {
+				validate_justice_tx(&builder.channel_parameters, &revoked_commitment, tx, &secp_ctx)
+			};
+			assert!(validate(&justice_tx).is_err());
+
+			// Nor is one signed with the wrong revocation base secret.
+			let mut wrongly_signed_tx = justice_tx.clone();
+			sign_justice_tx(
+				&mut wrongly_signed_tx,
+				&inputs,
+				&wrong_secret,
+				&per_commitment_secret,
+				&secp_ctx,
+			)
+			.unwrap();
+			assert!(validate(&wrongly_signed_tx).is_err());
+
+			let mut signed_tx = justice_tx.clone();
+			sign_justice_tx(
+				&mut signed_tx,
+				&inputs,
+				&revocation_base_secret,
+				&per_commitment_secret,
+				&secp_ctx,
+			)
+			.unwrap();
+			assert!(validate(&signed_tx).is_ok());
+
+			// Claiming more than the revoked outputs hold is not valid.
+			let mut overspending_tx = signed_tx.clone();
+			overspending_tx.output[0].value = Amount::from_sat(20_000);
+			assert!(validate(&overspending_tx).is_err());
+		}
+	}
+
 	#[test]
 	fn test_per_commitment_storage() {
 		// Test vectors from BOLT 3:
@@ -2989,4 +3958,69 @@ mod tests {
 
 		swap_htlcs!(small_htlc, big_htlc);
 	}
+
+	#[test]
+	fn test_verify_htlc_signatures() {
+		let secp_ctx = Secp256k1::new();
+		let secret_key = SecretKey::from_slice(&[42; 32]).unwrap();
+		let pubkey = PublicKey::from_secret_key(&secp_ctx, &secret_key);
+
+		// Check both the serial path and, with `std`, the threaded one.
+		for num_htlcs in [0, 1, 5, 17, 64, 483] {
+			let sighashes: Vec<_> = (0..num_htlcs)
+				.map(|i| secp256k1::Message::from_digest([(i % 255) as u8 + 1; 32]))
+				.collect();
+			let mut signatures: Vec<_> =
+				sighashes.iter().map(|sighash| secp_ctx.sign_ecdsa(sighash, &secret_key)).collect();
+			assert_eq!(verify_htlc_signatures(&secp_ctx, &sighashes, &signatures, &pubkey), Ok(()));
+
+			if num_htlcs == 0 {
+				continue;
+			}
+			// Invalidate a signature late in the set as well as an earlier one, checking that we
+			// always report the first failure.
+			let last_idx = num_htlcs - 1;
+			signatures[last_idx] = signatures[0];
+			if last_idx != 0 {
+				assert_eq!(
+					verify_htlc_signatures(&secp_ctx, &sighashes, &signatures, &pubkey),
+					Err(last_idx)
+				);
+			}
+			let first_idx = num_htlcs / 2;
+			if first_idx != 0 && first_idx != last_idx {
+				signatures[first_idx] = signatures[last_idx];
+				assert_eq!(
+					verify_htlc_signatures(&secp_ctx, &sighashes, &signatures, &pubkey),
+					Err(first_idx)
+				);
+			}
+		}
+	}
+}
+
+#[cfg(ldk_bench)]
+pub mod benches {
+	use super::verify_htlc_signatures;
+	use bitcoin::secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+
+	use criterion::Criterion;
+
+	pub fn bench_verify_htlc_signatures(bench: &mut Criterion) {
+		let secp_ctx = Secp256k1::new();
+		let secret_key = SecretKey::from_slice(&[42; 32]).unwrap();
+		let pubkey = PublicKey::from_secret_key(&secp_ctx, &secret_key);
+
+		for num_htlcs in [4, 30, 483] {
+			let sighashes: Vec<_> =
+				(0..num_htlcs).map(|i| Message::from_digest([(i % 255) as u8 + 1; 32])).collect();
+			let signatures: Vec<_> =
+				sighashes.iter().map(|sighash| secp_ctx.sign_ecdsa(sighash, &secret_key)).collect();
+			bench.bench_function(&format!("verify_{}_htlc_signatures", num_htlcs), |b| {
+				b.iter(|| {
+					verify_htlc_signatures(&secp_ctx, &sighashes, &signatures, &pubkey).unwrap()
+				})
+			});
+		}
+	}
 }
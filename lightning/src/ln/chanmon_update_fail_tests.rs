@@ -13,11 +13,14 @@
 //! here. See also the chanmon_fail_consistency fuzz test.
 
 use crate::chain::chaininterface::LowerBoundedFeeEstimator;
-use crate::chain::chainmonitor::ChainMonitor;
-use crate::chain::channelmonitor::{ChannelMonitor, MonitorEvent, ANTI_REORG_DELAY};
+use crate::chain::chainmonitor::{AsyncPersist, ChainMonitor};
+use crate::chain::channelmonitor::{
+	ChannelMonitor, ChannelMonitorUpdate, MonitorEvent, ANTI_REORG_DELAY,
+};
 use crate::chain::transaction::OutPoint;
 use crate::chain::{ChannelMonitorUpdateStatus, Listen, Watch};
 use crate::events::{ClosureReason, Event, HTLCHandlingFailureType, PaymentPurpose};
+use crate::io;
 use crate::ln::channel::AnnouncementSigsState;
 use crate::ln::channelmanager::{PaymentId, RAACommitmentOrder, RecipientOnionFields, Retry};
 use crate::ln::msgs;
@@ -27,10 +30,12 @@ use crate::ln::msgs::{
 use crate::ln::types::ChannelId;
 use crate::routing::router::{PaymentParameters, RouteParameters};
 use crate::sign::NodeSigner;
+use crate::util::async_poll::MaybeSend;
 use crate::util::native_async::FutureQueue;
 use crate::util::persist::{
-	MonitorName, MonitorUpdatingPersisterAsync, CHANNEL_MONITOR_PERSISTENCE_PRIMARY_NAMESPACE,
-	CHANNEL_MONITOR_PERSISTENCE_SECONDARY_NAMESPACE,
+	KVStore, KVStoreSync, MonitorName, MonitorUpdatingPersisterAsync,
+	CHANNEL_MONITOR_PERSISTENCE_PRIMARY_NAMESPACE, CHANNEL_MONITOR_PERSISTENCE_SECONDARY_NAMESPACE,
+	CHANNEL_MONITOR_UPDATE_JOURNAL_PRIMARY_NAMESPACE,
 	CHANNEL_MONITOR_UPDATE_PERSISTENCE_PRIMARY_NAMESPACE,
 };
 use crate::util::ser::{ReadableArgs, Writeable};
@@ -86,6 +91,7 @@ fn test_monitor_and_persister_update_fail() {
 	let persister = test_utils::TestPersister::new();
 	let tx_broadcaster = TestBroadcaster {
 		txn_broadcasted: Mutex::new(Vec::new()),
+		broadcast_contexts: Mutex::new(Vec::new()),
 		// Because we will connect a block at height 200 below, we need the TestBroadcaster to know
 		// that we are at height 200 so that it doesn't think we're violating the time lock
 		// requirements of transactions broadcasted at that point.
@@ -5057,6 +5063,254 @@ fn native_async_persist() {
 	}
 }
 
+struct KVStoreAsyncPersist(Arc<test_utils::TestStore>);
+
+impl AsyncPersist<TestChannelSigner> for KVStoreAsyncPersist {
+	fn persist_new_channel(
+		&self, monitor_name: MonitorName, monitor: &ChannelMonitor<TestChannelSigner>,
+	) -> impl core::future::Future<Output = Result<(), io::Error>> + 'static + MaybeSend {
+		let primary = CHANNEL_MONITOR_PERSISTENCE_PRIMARY_NAMESPACE;
+		let secondary = CHANNEL_MONITOR_PERSISTENCE_SECONDARY_NAMESPACE;
+		KVStore::write(&*self.0, primary, secondary, &monitor_name.to_string(), monitor.encode())
+	}
+
+	fn update_persisted_channel(
+		&self, monitor_name: MonitorName, _monitor_update: Option<&ChannelMonitorUpdate>,
+		monitor: &ChannelMonitor<TestChannelSigner>,
+	) -> impl core::future::Future<Output = Result<(), io::Error>> + 'static + MaybeSend {
+		// For simplicity, always write the full monitor.
+		self.persist_new_channel(monitor_name, monitor)
+	}
+
+	fn archive_persisted_channel(
+		&self, _monitor_name: MonitorName,
+	) -> impl core::future::Future<Output = ()> + 'static + MaybeSend {
+		async {}
+	}
+}
+
+#[test]
+fn async_persist_trait() {
+	// Test ChainMonitor::new_with_async_persist, checking that `ChannelMonitorUpdate`s are only
+	// marked complete once the futures returned by the `AsyncPersist` implementation resolve.
+	let (monitor, updates);
+	let mut chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[None, None]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+
+	let (_, _, chan_id, funding_tx) = create_announced_chan_between_nodes(&nodes, 0, 1);
+
+	monitor = get_monitor!(nodes[0], chan_id).clone();
+	send_payment(&nodes[0], &[&nodes[1]], 1_000_000);
+	let mon_updates =
+		nodes[0].chain_monitor.monitor_updates.lock().unwrap().remove(&chan_id).unwrap();
+	updates = mon_updates.into_iter().collect::<Vec<_>>();
+	assert!(updates.len() >= 2, "The test below needs at least two updates");
+
+	core::mem::drop(nodes);
+	core::mem::drop(node_chanmgrs);
+	core::mem::drop(node_cfgs);
+
+	let node_0_utils = chanmon_cfgs.remove(0);
+	let (logger, keys_manager, tx_broadcaster, fee_estimator) = (
+		node_0_utils.logger,
+		node_0_utils.keys_manager,
+		node_0_utils.tx_broadcaster,
+		node_0_utils.fee_estimator,
+	);
+	let logger = Arc::new(logger);
+	let keys_manager = Arc::new(keys_manager);
+
+	let kv_store = Arc::new(test_utils::TestStore::new(false));
+	let persister = KVStoreAsyncPersist(Arc::clone(&kv_store));
+	let persist_futures = Arc::new(FutureQueue::new());
+	let chain_source = test_utils::TestChainSource::new(Network::Testnet);
+	let async_chain_monitor = ChainMonitor::new_with_async_persist(
+		Some(&chain_source),
+		&tx_broadcaster,
+		logger,
+		&fee_estimator,
+		&persister,
+		Arc::clone(&persist_futures),
+		Arc::clone(&keys_manager),
+		keys_manager.get_peer_storage_key(),
+	);
+
+	let funding_txo = OutPoint { txid: funding_tx.compute_txid(), index: 0 };
+	let key = MonitorName::V1Channel(funding_txo).to_string();
+	let complete_monitor_writes = || {
+		kv_store.complete_async_writes_through(
+			CHANNEL_MONITOR_PERSISTENCE_PRIMARY_NAMESPACE,
+			CHANNEL_MONITOR_PERSISTENCE_SECONDARY_NAMESPACE,
+			&key,
+			usize::MAX,
+		);
+	};
+
+	// The initial write is tracked as in-flight until its future resolves and is `poll`ed.
+	let write_status = async_chain_monitor.watch_channel(chan_id, monitor).unwrap();
+	assert_eq!(write_status, ChannelMonitorUpdateStatus::InProgress);
+	assert_eq!(async_chain_monitor.in_flight_async_persists(), 1);
+	persist_futures.poll_futures();
+	assert_eq!(async_chain_monitor.in_flight_async_persists(), 1);
+	assert!(async_chain_monitor.release_pending_monitor_events().is_empty());
+
+	complete_monitor_writes();
+	persist_futures.poll_futures();
+	assert_eq!(async_chain_monitor.in_flight_async_persists(), 0);
+	let completed_persist = async_chain_monitor.release_pending_monitor_events();
+	assert_eq!(completed_persist.len(), 1);
+	assert_eq!(completed_persist[0].2.len(), 1);
+	assert!(matches!(completed_persist[0].2[0], MonitorEvent::Completed { .. }));
+
+	// With two updates in flight, no `MonitorEvent::Completed` is generated until both complete.
+	let update_status = async_chain_monitor.update_channel(chan_id, &updates[0]);
+	assert_eq!(update_status, ChannelMonitorUpdateStatus::InProgress);
+	let update_status = async_chain_monitor.update_channel(chan_id, &updates[1]);
+	assert_eq!(update_status, ChannelMonitorUpdateStatus::InProgress);
+	assert_eq!(async_chain_monitor.in_flight_async_persists(), 2);
+
+	persist_futures.poll_futures();
+	assert!(async_chain_monitor.release_pending_monitor_events().is_empty());
+
+	complete_monitor_writes();
+	persist_futures.poll_futures();
+	assert_eq!(async_chain_monitor.in_flight_async_persists(), 0);
+	let completed_persist = async_chain_monitor.release_pending_monitor_events();
+	assert_eq!(completed_persist.len(), 1);
+	assert_eq!(completed_persist[0].2.len(), 1);
+	if let MonitorEvent::Completed { monitor_update_id, .. } = &completed_persist[0].2[0] {
+		assert_eq!(*monitor_update_id, updates[1].update_id);
+	} else {
+		panic!();
+	}
+}
+
+struct UpdateKVStoreAsyncPersist(Arc<test_utils::TestStore>);
+
+impl AsyncPersist<TestChannelSigner> for UpdateKVStoreAsyncPersist {
+	fn persist_new_channel(
+		&self, monitor_name: MonitorName, monitor: &ChannelMonitor<TestChannelSigner>,
+	) -> impl core::future::Future<Output = Result<(), io::Error>> + 'static + MaybeSend {
+		KVStoreAsyncPersist(Arc::clone(&self.0)).persist_new_channel(monitor_name, monitor)
+	}
+
+	fn update_persisted_channel(
+		&self, monitor_name: MonitorName, monitor_update: Option<&ChannelMonitorUpdate>,
+		monitor: &ChannelMonitor<TestChannelSigner>,
+	) -> impl core::future::Future<Output = Result<(), io::Error>> + 'static + MaybeSend {
+		// Write each update under its own key so that their writes can be completed out-of-order.
+		let (key, data) = match monitor_update {
+			Some(update) => (update.update_id.to_string(), update.encode()),
+			None => ("full".to_owned(), monitor.encode()),
+		};
+		let primary = CHANNEL_MONITOR_UPDATE_PERSISTENCE_PRIMARY_NAMESPACE;
+		KVStore::write(&*self.0, primary, &monitor_name.to_string(), &key, data)
+	}
+
+	fn archive_persisted_channel(
+		&self, _monitor_name: MonitorName,
+	) -> impl core::future::Future<Output = ()> + 'static + MaybeSend {
+		async {}
+	}
+}
+
+#[test]
+fn async_persist_ordered_completion() {
+	// Test that when `AsyncPersist` futures for a channel resolve out-of-order, the corresponding
+	// `ChannelMonitorUpdate`s are only marked complete in the order in which they were started.
+	let (monitor, updates);
+	let mut chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[None, None]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+
+	let (_, _, chan_id, funding_tx) = create_announced_chan_between_nodes(&nodes, 0, 1);
+
+	monitor = get_monitor!(nodes[0], chan_id).clone();
+	send_payment(&nodes[0], &[&nodes[1]], 1_000_000);
+	let mon_updates =
+		nodes[0].chain_monitor.monitor_updates.lock().unwrap().remove(&chan_id).unwrap();
+	updates = mon_updates.into_iter().collect::<Vec<_>>();
+	assert!(updates.len() >= 3, "The test below needs at least three updates");
+
+	core::mem::drop(nodes);
+	core::mem::drop(node_chanmgrs);
+	core::mem::drop(node_cfgs);
+
+	let node_0_utils = chanmon_cfgs.remove(0);
+	let (logger, keys_manager, tx_broadcaster, fee_estimator) = (
+		node_0_utils.logger,
+		node_0_utils.keys_manager,
+		node_0_utils.tx_broadcaster,
+		node_0_utils.fee_estimator,
+	);
+	let logger = Arc::new(logger);
+	let keys_manager = Arc::new(keys_manager);
+
+	let kv_store = Arc::new(test_utils::TestStore::new(false));
+	let persister = UpdateKVStoreAsyncPersist(Arc::clone(&kv_store));
+	let persist_futures = Arc::new(FutureQueue::new());
+	let chain_source = test_utils::TestChainSource::new(Network::Testnet);
+	let async_chain_monitor = ChainMonitor::new_with_async_persist(
+		Some(&chain_source),
+		&tx_broadcaster,
+		logger,
+		&fee_estimator,
+		&persister,
+		Arc::clone(&persist_futures),
+		Arc::clone(&keys_manager),
+		keys_manager.get_peer_storage_key(),
+	);
+
+	let funding_txo = OutPoint { txid: funding_tx.compute_txid(), index: 0 };
+	let monitor_key = MonitorName::V1Channel(funding_txo).to_string();
+	let complete_update_write = |update_id: u64| {
+		kv_store.complete_async_writes_through(
+			CHANNEL_MONITOR_UPDATE_PERSISTENCE_PRIMARY_NAMESPACE,
+			&monitor_key,
+			&update_id.to_string(),
+			usize::MAX,
+		);
+		persist_futures.poll_futures();
+	};
+
+	let write_status = async_chain_monitor.watch_channel(chan_id, monitor).unwrap();
+	assert_eq!(write_status, ChannelMonitorUpdateStatus::InProgress);
+	kv_store.complete_all_async_writes();
+	persist_futures.poll_futures();
+	assert_eq!(async_chain_monitor.release_pending_monitor_events().len(), 1);
+
+	for update in updates.iter().take(3) {
+		let update_status = async_chain_monitor.update_channel(chan_id, update);
+		assert_eq!(update_status, ChannelMonitorUpdateStatus::InProgress);
+	}
+	assert_eq!(async_chain_monitor.in_flight_async_persists(), 3);
+
+	// Completing the later writes first doesn't mark anything complete, as the first update may
+	// not yet be durable.
+	complete_update_write(updates[2].update_id);
+	complete_update_write(updates[1].update_id);
+	assert_eq!(async_chain_monitor.in_flight_async_persists(), 1);
+	assert!(async_chain_monitor.release_pending_monitor_events().is_empty());
+	let pending_updates = async_chain_monitor.list_pending_monitor_updates();
+	assert_eq!(pending_updates.get(&chan_id).unwrap().len(), 3);
+
+	// Once the first write completes, all three updates are marked complete at once.
+	complete_update_write(updates[0].update_id);
+	assert_eq!(async_chain_monitor.in_flight_async_persists(), 0);
+	let completed_persist = async_chain_monitor.release_pending_monitor_events();
+	assert!(async_chain_monitor.list_pending_monitor_updates().get(&chan_id).unwrap().is_empty());
+	assert_eq!(completed_persist.len(), 1);
+	assert_eq!(completed_persist[0].2.len(), 1);
+	if let MonitorEvent::Completed { monitor_update_id, .. } = &completed_persist[0].2[0] {
+		assert_eq!(*monitor_update_id, updates[2].update_id);
+	} else {
+		panic!();
+	}
+}
+
 #[test]
 fn test_mpp_claim_to_holding_cell() {
 	// Previously, if an MPP payment was claimed while one channel was AwaitingRAA (causing the
@@ -5164,3 +5418,53 @@ fn test_mpp_claim_to_holding_cell() {
 	expect_payment_claimable!(nodes[3], paymnt_hash_2, payment_secret_2, 400_000);
 	claim_payment(&nodes[2], &[&nodes[3]], preimage_2);
 }
+
+#[test]
+fn monitor_update_journal_replay() {
+	// Test that with the update journal enabled, `ChannelMonitorUpdate`s are written to the journal
+	// and can be replayed against a stale copy of the `ChannelMonitor` on startup.
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[None, None]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+
+	let chan_id = create_announced_chan_between_nodes(&nodes, 0, 1).2;
+
+	let journal = Arc::new(test_utils::TestStore::new(false));
+	nodes[1].chain_monitor.chain_monitor.enable_update_journal(journal.clone());
+	let stale_monitor = get_monitor!(nodes[1], chan_id).clone();
+
+	send_payment(&nodes[0], &[&nodes[1]], 1_000_000);
+
+	let latest_update_id = get_monitor!(nodes[1], chan_id).get_latest_update_id();
+	assert!(latest_update_id > stale_monitor.get_latest_update_id());
+	let monitor_key = stale_monitor.persistence_key().to_string();
+	let journal_namespace = CHANNEL_MONITOR_UPDATE_JOURNAL_PRIMARY_NAMESPACE;
+	let journaled_updates = KVStoreSync::list(&*journal, journal_namespace, &monitor_key).unwrap();
+	let expected_updates = latest_update_id - stale_monitor.get_latest_update_id();
+	assert_eq!(journaled_updates.len() as u64, expected_updates);
+
+	// Load the stale monitor into a fresh `ChainMonitor` and replay the journal against it.
+	let persister = test_utils::TestPersister::new();
+	let chain_monitor = ChainMonitor::new(
+		None,
+		&chanmon_cfgs[1].tx_broadcaster,
+		&chanmon_cfgs[1].logger,
+		&chanmon_cfgs[1].fee_estimator,
+		&persister,
+		&chanmon_cfgs[1].keys_manager,
+		chanmon_cfgs[1].keys_manager.get_peer_storage_key(),
+	);
+	chain_monitor.enable_update_journal(journal.clone());
+	chain_monitor.load_existing_monitor(chan_id, stale_monitor).unwrap();
+	assert_eq!(chain_monitor.replay_pending_updates().unwrap(), expected_updates as usize);
+	assert_eq!(
+		chain_monitor.get_monitor(chan_id).unwrap().get_latest_update_id(),
+		latest_update_id
+	);
+
+	// Once the replayed monitor has been fully persisted, the journal is pruned.
+	let journaled_updates = KVStoreSync::list(&*journal, journal_namespace, &monitor_key).unwrap();
+	assert!(journaled_updates.is_empty());
+	assert_eq!(chain_monitor.replay_pending_updates().unwrap(), 0);
+}
@@ -29,6 +29,7 @@ use bitcoin::{secp256k1, sighash, FeeRate, Sequence, TxIn};
 use crate::blinded_path::message::BlindedMessagePath;
 use crate::chain::chaininterface::{
 	fee_for_weight, ConfirmationTarget, FeeEstimator, LowerBoundedFeeEstimator,
+	RemoteFeerateLimits, UpdateFeePolicy,
 };
 use crate::chain::channelmonitor::{
 	ChannelMonitor, ChannelMonitorUpdate, ChannelMonitorUpdateStep, CommitmentHTLCData,
@@ -43,11 +44,12 @@ use crate::ln::chan_utils::{
 	selected_commitment_sat_per_1000_weight, ChannelPublicKeys, ChannelTransactionParameters,
 	ClosingTransaction, CommitmentTransaction, CounterpartyChannelTransactionParameters,
 	CounterpartyCommitmentSecrets, HTLCOutputInCommitment, HolderCommitmentTransaction,
-	BASE_INPUT_WEIGHT, EMPTY_SCRIPT_SIG_WEIGHT, FUNDING_TRANSACTION_WITNESS_WEIGHT,
+	BASE_INPUT_WEIGHT, EMPTY_SCRIPT_SIG_WEIGHT, FUNDING_TRANSACTION_WITNESS_WEIGHT, P2A_MAX_VALUE,
 };
 use crate::ln::channel_state::{
-	ChannelShutdownState, CounterpartyForwardingInfo, InboundHTLCDetails, InboundHTLCStateDetails,
-	OutboundHTLCDetails, OutboundHTLCStateDetails,
+	ChannelLifecycleInfo, ChannelLifecycleState, ChannelShutdownState, CounterpartyForwardingInfo,
+	ExpectedChannelMessage, InboundHTLCDetails, InboundHTLCStateDetails, OutboundHTLCDetails,
+	OutboundHTLCStateDetails, ProtocolErrorRecord, MAX_PROTOCOL_ERRORS_PER_CHANNEL,
 };
 use crate::ln::channelmanager::{
 	self, ChannelReadyOrder, FundingConfirmedMessage, HTLCFailureMsg, HTLCSource,
@@ -62,18 +64,20 @@ use crate::ln::interactivetxs::{
 	InteractiveTxSigningSession, NegotiationError, SharedOwnedInput, SharedOwnedOutput,
 	TX_COMMON_FIELDS_WEIGHT,
 };
+use crate::ln::liquidity_ads::RequestFunds;
 use crate::ln::msgs;
 use crate::ln::msgs::{ClosingSigned, ClosingSignedFeeRange, DecodeError, OnionErrorPacket};
 use crate::ln::onion_utils::{
 	AttributionData, HTLCFailReason, LocalHTLCFailureReason, HOLD_TIME_UNIT_MILLIS,
 };
+use crate::ln::peer_misbehavior::{InboundHTLCLimits, MisbehaviorKind};
 use crate::ln::script::{self, ShutdownScript};
 use crate::ln::types::ChannelId;
 use crate::ln::LN_MAX_MSG_LEN;
 use crate::offers::static_invoice::StaticInvoice;
 use crate::routing::gossip::NodeId;
 use crate::sign::ecdsa::EcdsaChannelSigner;
-use crate::sign::tx_builder::{HTLCAmountDirection, NextCommitmentStats, SpecTxBuilder, TxBuilder};
+use crate::sign::tx_builder::{HTLCAmountDirection, NextCommitmentStats, TxBuilder};
 use crate::sign::{ChannelSigner, EntropySource, NodeSigner, Recipient, SignerProvider};
 use crate::types::features::{ChannelTypeFeatures, InitFeatures};
 use crate::types::payment::{PaymentHash, PaymentPreimage};
@@ -444,6 +448,8 @@ struct OutboundHTLCOutput {
 	skimmed_fee_msat: Option<u64>,
 	send_timestamp: Option<Duration>,
 	hold_htlc: Option<()>,
+	/// Whether we endorsed this HTLC, see [`msgs::UpdateAddHTLC::endorsed`].
+	endorsed: bool,
 }
 
 /// See AwaitingRemoteRevoke ChannelState for more info
@@ -462,6 +468,7 @@ enum HTLCUpdateAwaitingACK {
 		skimmed_fee_msat: Option<u64>,
 		blinding_point: Option<PublicKey>,
 		hold_htlc: Option<()>,
+		endorsed: bool,
 	},
 	ClaimHTLC {
 		payment_preimage: PaymentPreimage,
@@ -938,7 +945,9 @@ pub(super) enum ChannelError {
 	Warn(String),
 	WarnAndDisconnect(String),
 	Abort(AbortReason),
-	Close((String, ClosureReason)),
+	/// Closes the channel, recording the kind of misbehavior by our peer that caused the error if
+	/// it is known to be something other than a protocol violation.
+	Close((String, ClosureReason, Option<MisbehaviorKind>)),
 	SendError(String),
 }
 
@@ -951,7 +960,7 @@ impl fmt::Debug for ChannelError {
 				write!(f, "Disconnecting with warning: {}", e)
 			},
 			&ChannelError::Abort(ref reason) => write!(f, "Abort: {}", reason),
-			&ChannelError::Close((ref e, _)) => write!(f, "Close: {}", e),
+			&ChannelError::Close((ref e, _, _)) => write!(f, "Close: {}", e),
 			&ChannelError::SendError(ref e) => write!(f, "Not Found: {}", e),
 		}
 	}
@@ -964,7 +973,7 @@ impl fmt::Display for ChannelError {
 			&ChannelError::Warn(ref e) => write!(f, "{}", e),
 			&ChannelError::WarnAndDisconnect(ref e) => write!(f, "{}", e),
 			&ChannelError::Abort(ref reason) => write!(f, "{}", reason),
-			&ChannelError::Close((ref e, _)) => write!(f, "{}", e),
+			&ChannelError::Close((ref e, _, _)) => write!(f, "{}", e),
 			&ChannelError::SendError(ref e) => write!(f, "{}", e),
 		}
 	}
@@ -972,7 +981,11 @@ impl fmt::Display for ChannelError {
 
 impl ChannelError {
 	pub(super) fn close(err: String) -> Self {
-		ChannelError::Close((err.clone(), ClosureReason::ProcessingError { err }))
+		ChannelError::Close((err.clone(), ClosureReason::ProcessingError { err }, None))
+	}
+
+	fn close_misbehaving(kind: MisbehaviorKind, err: String) -> Self {
+		ChannelError::Close((err.clone(), ClosureReason::ProcessingError { err }, Some(kind)))
 	}
 }
 
@@ -1021,7 +1034,10 @@ macro_rules! secp_check {
 	($res: expr, $err: expr) => {
 		match $res {
 			Ok(thing) => thing,
-			Err(_) => return Err(ChannelError::close($err)),
+			Err(_) => {
+				let kind = MisbehaviorKind::InvalidSignature;
+				return Err(ChannelError::close_misbehaving(kind, $err));
+			},
 		}
 	};
 }
@@ -1092,17 +1108,6 @@ struct CommitmentData<'a> {
 	inbound_htlc_preimages: Vec<PaymentPreimage>, // preimages for successful received HTLCs since last commitment
 }
 
-/// A struct gathering stats on a commitment transaction, either local or remote.
-#[derive(Debug, PartialEq)]
-pub(crate) struct CommitmentStats {
-	/// The total fee included in the commitment transaction
-	pub commit_tx_fee_sat: u64,
-	/// The local balance before fees *not* considering dust limits
-	pub local_balance_before_fee_msat: u64,
-	/// The remote balance before fees *not* considering dust limits
-	pub remote_balance_before_fee_msat: u64,
-}
-
 /// Used when calculating whether we or the remote can afford an additional HTLC.
 struct HTLCCandidate {
 	amount_msat: u64,
@@ -1537,6 +1542,73 @@ where
 		matches!(self.phase, ChannelPhase::Funded(_))
 	}
 
+	/// Returns the position of this channel in the channel state machine.
+	pub fn lifecycle_info(&self) -> ChannelLifecycleInfo {
+		let context = self.context();
+		let state = context.lifecycle_state();
+		let channel_state = context.channel_state;
+		let expected_messages = if channel_state.is_peer_disconnected() {
+			vec![ExpectedChannelMessage::ChannelReestablish]
+		} else if state == ChannelLifecycleState::ShuttingDown
+			&& !channel_state.is_remote_shutdown_sent()
+		{
+			vec![ExpectedChannelMessage::Shutdown]
+		} else if state == ChannelLifecycleState::ShuttingDown
+			&& context.closing_negotiation_ready()
+		{
+			vec![ExpectedChannelMessage::ClosingSigned]
+		} else {
+			match (channel_state, &self.phase) {
+				(ChannelState::NegotiatingFunding(flags), _)
+					if self.funding().is_outbound() && !flags.is_their_init_sent() =>
+				{
+					vec![ExpectedChannelMessage::AcceptChannel]
+				},
+				(ChannelState::NegotiatingFunding(flags), ChannelPhase::UnfundedInboundV1(_))
+					if flags.is_our_init_sent() =>
+				{
+					vec![ExpectedChannelMessage::FundingCreated]
+				},
+				(ChannelState::NegotiatingFunding(flags), ChannelPhase::UnfundedV2(_))
+					if flags.is_our_init_sent() && flags.is_their_init_sent() =>
+				{
+					vec![ExpectedChannelMessage::InteractiveTxConstruction]
+				},
+				(ChannelState::FundingNegotiated(_), ChannelPhase::UnfundedOutboundV1(_)) => {
+					vec![ExpectedChannelMessage::FundingSigned]
+				},
+				(ChannelState::FundingNegotiated(_), _) => vec![
+					ExpectedChannelMessage::CommitmentSigned,
+					ExpectedChannelMessage::TxSignatures,
+				],
+				(ChannelState::AwaitingChannelReady(flags), _)
+					if !flags.is_their_channel_ready() =>
+				{
+					vec![ExpectedChannelMessage::ChannelReady]
+				},
+				(ChannelState::ChannelReady(flags), _) => {
+					let mut expected_messages = Vec::new();
+					if flags.is_awaiting_remote_revoke() {
+						expected_messages.push(ExpectedChannelMessage::RevokeAndAck);
+					}
+					if flags.is_local_stfu_sent() && !flags.is_remote_stfu_sent() {
+						expected_messages.push(ExpectedChannelMessage::Stfu);
+					}
+					expected_messages
+				},
+				_ => Vec::new(),
+			}
+		};
+		ChannelLifecycleInfo {
+			state,
+			allowed_transitions: state.allowed_transitions().to_vec(),
+			expected_messages,
+			is_peer_disconnected: channel_state.is_peer_disconnected(),
+			is_monitor_update_in_progress: channel_state.is_monitor_update_in_progress(),
+			is_quiescent: channel_state.is_quiescent(),
+		}
+	}
+
 	pub fn as_funded(&self) -> Option<&FundedChannel<SP>> {
 		if let ChannelPhase::Funded(channel) = &self.phase {
 			Some(channel)
@@ -1609,13 +1681,22 @@ where
 		}
 	}
 
+	pub fn as_unfunded_v2_mut(&mut self) -> Option<&mut PendingV2Channel<SP>> {
+		if let ChannelPhase::UnfundedV2(channel) = &mut self.phase {
+			Some(channel)
+		} else {
+			None
+		}
+	}
+
 	#[rustfmt::skip]
 	pub fn signer_maybe_unblocked<L: Deref, CBP>(
-		&mut self, chain_hash: ChainHash, logger: &L, path_for_release_htlc: CBP
+		&mut self, chain_hash: ChainHash, logger: &L, path_for_release_htlc: CBP,
+		tolerate_stale_state: bool,
 	) -> Result<Option<SignerResumeUpdates>, ChannelError> where L::Target: Logger, CBP: Fn(u64) -> BlindedMessagePath {
 		match &mut self.phase {
 			ChannelPhase::Undefined => unreachable!(),
-			ChannelPhase::Funded(chan) => chan.signer_maybe_unblocked(logger, path_for_release_htlc).map(|r| Some(r)),
+			ChannelPhase::Funded(chan) => chan.signer_maybe_unblocked(logger, path_for_release_htlc, tolerate_stale_state).map(|r| Some(r)),
 			ChannelPhase::UnfundedOutboundV1(chan) => {
 				let (open_channel, funding_created) = chan.signer_maybe_unblocked(chain_hash, logger);
 				Ok(Some(SignerResumeUpdates {
@@ -1682,6 +1763,7 @@ where
 					// If we were trying to get quiescent, try again after reconnection.
 					chan.context.channel_state.set_awaiting_quiescence();
 				}
+				chan.holding_quiescence = false;
 				chan.context.channel_state.clear_local_stfu_sent();
 				chan.context.channel_state.clear_remote_stfu_sent();
 				if chan.should_reset_pending_splice_state(false) {
@@ -2190,6 +2272,8 @@ where
 					holder_commitment_point,
 					pending_splice: None,
 					quiescent_action: None,
+					remote_feerate_near_limit: false,
+					holding_quiescence: false,
 				};
 				let res = funded_channel.initial_commitment_signed_v2(msg, best_block, signer_provider, logger)
 					.map(|monitor| (Some(monitor), None))
@@ -2803,6 +2887,10 @@ impl_writeable_tlv_based!(SpliceInstructions, {
 #[derive(Debug)]
 pub(crate) enum QuiescentAction {
 	Splice(SpliceInstructions),
+	/// Remain quiescent once reached, as requested via [`ChannelManager::quiesce_channel`].
+	///
+	/// [`ChannelManager::quiesce_channel`]: crate::ln::channelmanager::ChannelManager::quiesce_channel
+	Hold,
 	#[cfg(any(test, fuzzing))]
 	DoNothing,
 }
@@ -2810,15 +2898,19 @@ pub(crate) enum QuiescentAction {
 pub(crate) enum StfuResponse {
 	Stfu(msgs::Stfu),
 	SpliceInit(msgs::SpliceInit),
+	/// We initiated quiescence to hold the channel quiescent, which has now been reached.
+	Quiescent,
 }
 
 #[cfg(any(test, fuzzing))]
 impl_writeable_tlv_based_enum_upgradable!(QuiescentAction,
 	(0, DoNothing) => {},
+	(3, Hold) => {}, // Added in 0.3
 	{1, Splice} => (),
 );
 #[cfg(not(any(test, fuzzing)))]
-impl_writeable_tlv_based_enum_upgradable!(QuiescentAction,,
+impl_writeable_tlv_based_enum_upgradable!(QuiescentAction,
+	(3, Hold) => {}, // Added in 0.3
 	{1, Splice} => (),
 );
 
@@ -2887,6 +2979,9 @@ where
 	latest_monitor_update_id: u64,
 
 	holder_signer: ChannelSignerType<SP>,
+	/// The [`TxBuilder`] used to build this channel's commitment transactions, as provided by
+	/// [`SignerProvider::get_tx_builder`].
+	tx_builder: Box<dyn TxBuilder + Send + Sync>,
 	shutdown_scriptpubkey: Option<ShutdownScript>,
 	destination_script: ScriptBuf,
 
@@ -2907,6 +3002,12 @@ where
 	/// send it first.
 	resend_order: RAACommitmentOrder,
 
+	/// The [`ChannelLifecycleState`] last reported via an [`Event::ChannelLifecycleStateChanged`],
+	/// if any. This is not persisted, so the current state is reported again after a restart.
+	///
+	/// [`Event::ChannelLifecycleStateChanged`]: crate::events::Event::ChannelLifecycleStateChanged
+	pub(super) last_reported_lifecycle_state: Option<ChannelLifecycleState>,
+
 	monitor_pending_channel_ready: bool,
 	monitor_pending_revoke_and_ack: bool,
 	monitor_pending_commitment_signed: bool,
@@ -2949,6 +3050,9 @@ where
 	// indicating that our state may be stale, we set this to the received last-revoked commitment
 	// number and secret to perform the verification when the signer is ready.
 	signer_pending_stale_state_verification: Option<(u64, SecretKey)>,
+	// Set when our counterparty proved that our state is stale while we were tolerating stale
+	// state, in which case we must not broadcast our (revoked) commitment transaction on closure.
+	holder_state_stale: bool,
 
 	// pending_update_fee is filled when sending and receiving update_fee.
 	//
@@ -3082,6 +3186,11 @@ where
 	/// <https://github.com/lightningnetwork/lnd/issues/7682>.
 	sent_message_awaiting_response: Option<usize>,
 
+	/// The number of ticks which have elapsed since we last sent an `update_fee`, if we have sent
+	/// one since the channel was loaded. Used to enforce
+	/// [`ChannelConfig::min_update_fee_interval_ticks`].
+	ticks_since_update_fee: Option<u16>,
+
 	// Our counterparty can offer us SCID aliases which they will map to this channel when routing
 	// outbound payments. These can be used in invoice route hints to avoid explicitly revealing
 	// the channel's funding UTXO.
@@ -3104,6 +3213,15 @@ where
 	/// ChannelManager can look up the channel for any pending HTLCs.
 	historical_scids: Vec<u64>,
 
+	/// The height of the best block at the time our funding transaction was un-confirmed by a
+	/// reorg after the channel became ready, if we're still waiting for it to re-confirm. See
+	/// [`UserConfig::funding_reorg_grace_period_blocks`].
+	funding_unconfirmed_at_height: Option<u32>,
+
+	/// The most recent `warning` and `error` messages exchanged with our counterparty regarding
+	/// this channel, oldest first, bounded by [`MAX_PROTOCOL_ERRORS_PER_CHANNEL`].
+	protocol_error_history: Vec<ProtocolErrorRecord>,
+
 	// We track whether we already emitted a `ChannelPending` event.
 	channel_pending_event_emitted: bool,
 
@@ -3404,6 +3522,7 @@ where
 
 		let channel_keys_id = signer_provider.generate_channel_keys_id(true, user_id);
 		let holder_signer = signer_provider.derive_channel_signer(channel_keys_id);
+		let tx_builder = signer_provider.get_tx_builder(channel_keys_id);
 
 		if config.channel_handshake_config.our_to_self_delay < BREAKDOWN_TIMEOUT {
 			return Err(ChannelError::close(format!("Configured with an unreasonable our_to_self_delay ({}) putting user funds at risks. It must be greater than {}", config.channel_handshake_config.our_to_self_delay, BREAKDOWN_TIMEOUT)));
@@ -3432,7 +3551,24 @@ where
 		if open_channel_fields.htlc_minimum_msat >= full_channel_value_msat {
 			return Err(ChannelError::close(format!("Minimum htlc value ({}) was larger than full channel value ({})", open_channel_fields.htlc_minimum_msat, full_channel_value_msat)));
 		}
-		FundedChannel::<SP>::check_remote_fee(&channel_type, fee_estimator, open_channel_fields.commitment_feerate_sat_per_1000_weight, None, &&logger)?;
+		let feerate_per_kw = open_channel_fields.commitment_feerate_sat_per_1000_weight;
+		if channel_type.supports_anchor_zero_fee_commitments() {
+			if feerate_per_kw != 0 {
+				return Err(ChannelError::close("Zero Fee Channels must never attempt to use a fee".to_owned()));
+			}
+		} else {
+			// Whether the feerate is acceptable has already been decided by the `ChannelManager`'s
+			// `InboundChannelFeePolicy`, but we still note when it's lower than we'd usually like.
+			let lower_limit_conf_target = if channel_type.supports_anchors_zero_fee_htlc_tx() {
+				ConfirmationTarget::MinAllowedAnchorChannelRemoteFee
+			} else {
+				ConfirmationTarget::MinAllowedNonAnchorChannelRemoteFee
+			};
+			let estimate = fee_estimator.bounded_sat_per_1000_weight(lower_limit_conf_target);
+			if feerate_per_kw < estimate {
+				log_warn!(logger, "Accepting feerate of {} s/kW which is lower than our estimate of {} s/kW and may prevent us from closing this channel.", feerate_per_kw, estimate);
+			}
+		}
 
 		let max_counterparty_selected_contest_delay = u16::min(config.channel_handshake_limits.their_to_self_delay, MAX_LOCAL_BREAKDOWN_TIMEOUT);
 		if open_channel_fields.to_self_delay > max_counterparty_selected_contest_delay {
@@ -3499,9 +3635,9 @@ where
 		// check if the funder's amount for the initial commitment tx is sufficient
 		// for full fee payment plus a few HTLCs to ensure the channel will be useful.
 		let funders_amount_msat = open_channel_fields.funding_satoshis * 1000 - msg_push_msat;
-		let commit_tx_fee_sat = SpecTxBuilder {}.commit_tx_fee_sat(open_channel_fields.commitment_feerate_sat_per_1000_weight, MIN_AFFORDABLE_HTLC_COUNT, &channel_type);
+		let commit_tx_fee_sat = tx_builder.commit_tx_fee_sat(open_channel_fields.commitment_feerate_sat_per_1000_weight, MIN_AFFORDABLE_HTLC_COUNT, &channel_type);
 		// Subtract any non-HTLC outputs from the remote balance
-		let (_, remote_balance_before_fee_msat) = SpecTxBuilder {}.subtract_non_htlc_outputs(false, value_to_self_msat, funders_amount_msat, &channel_type);
+		let (_, remote_balance_before_fee_msat) = tx_builder.subtract_non_htlc_outputs(false, value_to_self_msat, funders_amount_msat, &channel_type);
 		if remote_balance_before_fee_msat / 1000 < commit_tx_fee_sat {
 			return Err(ChannelError::close(format!("Funding amount ({} sats) can't even pay fee for initial commitment transaction fee of {} sats.", funders_amount_msat / 1000, commit_tx_fee_sat)));
 		}
@@ -3622,6 +3758,7 @@ where
 			latest_monitor_update_id: 0,
 
 			holder_signer: ChannelSignerType::Ecdsa(holder_signer),
+			tx_builder,
 			shutdown_scriptpubkey,
 			destination_script,
 
@@ -3638,6 +3775,8 @@ where
 
 			resend_order: RAACommitmentOrder::CommitmentFirst,
 
+			last_reported_lifecycle_state: None,
+
 			monitor_pending_channel_ready: false,
 			monitor_pending_revoke_and_ack: false,
 			monitor_pending_commitment_signed: false,
@@ -3652,6 +3791,7 @@ where
 			signer_pending_closing: false,
 			signer_pending_channel_ready: false,
 			signer_pending_stale_state_verification: None,
+			holder_state_stale: false,
 
 			last_sent_closing_fee: None,
 			last_received_closing_sig: None,
@@ -3692,10 +3832,13 @@ where
 
 			workaround_lnd_bug_4006: None,
 			sent_message_awaiting_response: None,
+			ticks_since_update_fee: None,
 
 			latest_inbound_scid_alias: None,
 			outbound_scid_alias: 0,
 			historical_scids: Vec::new(),
+			funding_unconfirmed_at_height: None,
+			protocol_error_history: Vec::new(),
 
 			channel_pending_event_emitted: false,
 			funding_tx_broadcast_safe_event_emitted: false,
@@ -3742,6 +3885,7 @@ where
 	{
 		// This will be updated with the counterparty contribution if this is a dual-funded channel
 		let channel_value_satoshis = funding_satoshis;
+		let tx_builder = signer_provider.get_tx_builder(channel_keys_id);
 
 		let holder_selected_contest_delay = config.channel_handshake_config.our_to_self_delay;
 
@@ -3768,9 +3912,9 @@ where
 		);
 
 		let value_to_self_msat = channel_value_satoshis * 1000 - push_msat;
-		let commit_tx_fee_sat = SpecTxBuilder {}.commit_tx_fee_sat(commitment_feerate, MIN_AFFORDABLE_HTLC_COUNT, &channel_type);
+		let commit_tx_fee_sat = tx_builder.commit_tx_fee_sat(commitment_feerate, MIN_AFFORDABLE_HTLC_COUNT, &channel_type);
 		// Subtract any non-HTLC outputs from the local balance
-		let (local_balance_before_fee_msat, _) = SpecTxBuilder {}.subtract_non_htlc_outputs(
+		let (local_balance_before_fee_msat, _) = tx_builder.subtract_non_htlc_outputs(
 			true,
 			value_to_self_msat,
 			push_msat,
@@ -3861,6 +4005,7 @@ where
 			latest_monitor_update_id: 0,
 
 			holder_signer: ChannelSignerType::Ecdsa(holder_signer),
+			tx_builder,
 			shutdown_scriptpubkey,
 			destination_script,
 
@@ -3877,6 +4022,8 @@ where
 
 			resend_order: RAACommitmentOrder::CommitmentFirst,
 
+			last_reported_lifecycle_state: None,
+
 			monitor_pending_channel_ready: false,
 			monitor_pending_revoke_and_ack: false,
 			monitor_pending_commitment_signed: false,
@@ -3891,6 +4038,7 @@ where
 			signer_pending_closing: false,
 			signer_pending_channel_ready: false,
 			signer_pending_stale_state_verification: None,
+			holder_state_stale: false,
 
 			last_sent_closing_fee: None,
 			last_received_closing_sig: None,
@@ -3933,10 +4081,13 @@ where
 
 			workaround_lnd_bug_4006: None,
 			sent_message_awaiting_response: None,
+			ticks_since_update_fee: None,
 
 			latest_inbound_scid_alias: None,
 			outbound_scid_alias,
 			historical_scids: Vec::new(),
+			funding_unconfirmed_at_height: None,
+			protocol_error_history: Vec::new(),
 
 			channel_pending_event_emitted: false,
 			funding_tx_broadcast_safe_event_emitted: false,
@@ -3995,6 +4146,23 @@ where
 			&& !self.monitor_pending_channel_ready
 	}
 
+	/// Returns the phase of the channel's lifecycle, see [`ChannelLifecycleState`].
+	pub fn lifecycle_state(&self) -> ChannelLifecycleState {
+		match self.channel_state {
+			ChannelState::NegotiatingFunding(_) => ChannelLifecycleState::NegotiatingFunding,
+			ChannelState::FundingNegotiated(_) => ChannelLifecycleState::FundingNegotiated,
+			ChannelState::AwaitingChannelReady(_) | ChannelState::ChannelReady(_)
+				if self.channel_state.is_local_shutdown_sent()
+					|| self.channel_state.is_remote_shutdown_sent() =>
+			{
+				ChannelLifecycleState::ShuttingDown
+			},
+			ChannelState::AwaitingChannelReady(_) => ChannelLifecycleState::AwaitingChannelReady,
+			ChannelState::ChannelReady(_) => ChannelLifecycleState::ChannelReady,
+			ChannelState::ShutdownComplete => ChannelLifecycleState::ShutdownComplete,
+		}
+	}
+
 	/// shutdown state returns the state of the channel in its various stages of shutdown
 	pub fn shutdown_state(&self) -> ChannelShutdownState {
 		match self.channel_state {
@@ -4046,7 +4214,9 @@ where
 	/// is_usable() and considers things like the channel being temporarily disabled.
 	/// Allowed in any state (including after shutdown)
 	pub fn is_live(&self) -> bool {
-		self.is_usable() && !self.channel_state.is_peer_disconnected()
+		self.is_usable()
+			&& !self.channel_state.is_peer_disconnected()
+			&& self.funding_unconfirmed_at_height.is_none()
 	}
 
 	/// Returns true if the peer for this channel is currently connected and we're not waiting on
@@ -4311,6 +4481,18 @@ where
 		self.counterparty_node_id
 	}
 
+	/// Returns the identifier used to re-derive the channel's keys through
+	/// [`SignerProvider::derive_channel_signer`].
+	pub fn get_channel_keys_id(&self) -> [u8; 32] {
+		self.channel_keys_id
+	}
+
+	/// Returns true if our counterparty proved that our state is stale, in which case we did not
+	/// broadcast our commitment transaction when closing the channel.
+	pub fn is_holder_state_stale(&self) -> bool {
+		self.holder_state_stale
+	}
+
 	/// Allowed in any state (including after shutdown)
 	pub fn get_holder_htlc_minimum_msat(&self) -> u64 {
 		self.holder_htlc_minimum_msat
@@ -4642,7 +4824,7 @@ where
 		);
 		let next_value_to_self_msat = self.get_next_commitment_value_to_self_msat(true, funding);
 
-		let ret = SpecTxBuilder {}.get_next_commitment_stats(
+		let ret = self.tx_builder.get_next_commitment_stats(
 			true,
 			funding.is_outbound(),
 			funding.get_value_satoshis(),
@@ -4664,7 +4846,8 @@ where
 					predicted_fee_sat: ret.commit_tx_fee_sat,
 				};
 			} else {
-				let predicted_stats = SpecTxBuilder {}
+				let predicted_stats = self
+					.tx_builder
 					.get_next_commitment_stats(
 						true,
 						funding.is_outbound(),
@@ -4701,7 +4884,7 @@ where
 		);
 		let next_value_to_self_msat = self.get_next_commitment_value_to_self_msat(false, funding);
 
-		let ret = SpecTxBuilder {}.get_next_commitment_stats(
+		let ret = self.tx_builder.get_next_commitment_stats(
 			false,
 			funding.is_outbound(),
 			funding.get_value_satoshis(),
@@ -4723,7 +4906,8 @@ where
 					predicted_fee_sat: ret.commit_tx_fee_sat,
 				};
 			} else {
-				let predicted_stats = SpecTxBuilder {}
+				let predicted_stats = self
+					.tx_builder
 					.get_next_commitment_stats(
 						false,
 						funding.is_outbound(),
@@ -4982,7 +5166,8 @@ where
 				&msg.signature,
 				&funding.counterparty_funding_pubkey(),
 			) {
-				return Err(ChannelError::close(
+				return Err(ChannelError::close_misbehaving(
+					MisbehaviorKind::InvalidSignature,
 					"Invalid commitment tx signature from peer".to_owned(),
 				));
 			}
@@ -5006,6 +5191,8 @@ where
 		}
 
 		let holder_keys = commitment_data.tx.trust().keys();
+		let countersignatory_htlc_pubkey = holder_keys.countersignatory_htlc_key.to_public_key();
+		let mut htlc_sighashes = Vec::with_capacity(msg.htlc_signatures.len());
 		for (htlc, counterparty_sig) in
 			commitment_data.tx.nondust_htlcs().iter().zip(msg.htlc_signatures.iter())
 		{
@@ -5042,19 +5229,22 @@ where
 			);
 			log_trace!(logger, "Checking HTLC tx signature {} by key {} against tx {} (sighash {}) with redeemscript {} in channel {}.",
 				log_bytes!(counterparty_sig.serialize_compact()[..]),
-				log_bytes!(holder_keys.countersignatory_htlc_key.to_public_key().serialize()),
+				log_bytes!(countersignatory_htlc_pubkey.serialize()),
 				encode::serialize_hex(&htlc_tx),
 				log_bytes!(htlc_sighash[..]),
 				encode::serialize_hex(&htlc_redeemscript),
 				&self.channel_id(),
 			);
-			if let Err(_) = self.secp_ctx.verify_ecdsa(
-				&htlc_sighash,
-				&counterparty_sig,
-				&holder_keys.countersignatory_htlc_key.to_public_key(),
-			) {
-				return Err(ChannelError::close("Invalid HTLC tx signature from peer".to_owned()));
-			}
+			htlc_sighashes.push(htlc_sighash);
+		}
+		if let Err(_) = chan_utils::verify_htlc_signatures(
+			&self.secp_ctx,
+			&htlc_sighashes,
+			&msg.htlc_signatures,
+			&countersignatory_htlc_pubkey,
+		) {
+			let err = "Invalid HTLC tx signature from peer".to_owned();
+			return Err(ChannelError::close_misbehaving(MisbehaviorKind::InvalidSignature, err));
 		}
 
 		let holder_commitment_tx = HolderCommitmentTransaction::new(
@@ -5362,7 +5552,7 @@ where
 
 		let value_to_self_msat = (funding.value_to_self_msat + value_to_self_claimed_msat).checked_sub(value_to_remote_claimed_msat).unwrap();
 
-		let (tx, stats) = SpecTxBuilder {}.build_commitment_transaction(
+		let (tx, stats) = self.tx_builder.build_commitment_transaction(
 			local,
 			commitment_number,
 			per_commitment_point,
@@ -5372,7 +5562,7 @@ where
 			htlcs_included.iter().map(|(htlc, _source)| htlc).cloned().collect(),
 			feerate_per_kw,
 			broadcaster_dust_limit_sat,
-			logger,
+			&WithChannelContext::from(logger, self, None),
 		);
 		#[cfg(any(test, fuzzing))]
 		{
@@ -5548,10 +5738,10 @@ where
 		}
 
 		let extra_nondust_htlc_on_counterparty_tx_dust_exposure_msat = excess_feerate_opt.map(|excess_feerate| {
-			let extra_htlc_commit_tx_fee_sat = SpecTxBuilder {}.commit_tx_fee_sat(excess_feerate, on_counterparty_tx_accepted_nondust_htlcs + 1 + on_counterparty_tx_offered_nondust_htlcs, funding.get_channel_type());
+			let extra_htlc_commit_tx_fee_sat = self.tx_builder.commit_tx_fee_sat(excess_feerate, on_counterparty_tx_accepted_nondust_htlcs + 1 + on_counterparty_tx_offered_nondust_htlcs, funding.get_channel_type());
 			let extra_htlc_htlc_tx_fees_sat = chan_utils::htlc_tx_fees_sat(excess_feerate, on_counterparty_tx_accepted_nondust_htlcs + 1, on_counterparty_tx_offered_nondust_htlcs, funding.get_channel_type());
 
-			let commit_tx_fee_sat = SpecTxBuilder {}.commit_tx_fee_sat(excess_feerate, on_counterparty_tx_accepted_nondust_htlcs + on_counterparty_tx_offered_nondust_htlcs, funding.get_channel_type());
+			let commit_tx_fee_sat = self.tx_builder.commit_tx_fee_sat(excess_feerate, on_counterparty_tx_accepted_nondust_htlcs + on_counterparty_tx_offered_nondust_htlcs, funding.get_channel_type());
 			let htlc_tx_fees_sat = chan_utils::htlc_tx_fees_sat(excess_feerate, on_counterparty_tx_accepted_nondust_htlcs, on_counterparty_tx_offered_nondust_htlcs, funding.get_channel_type());
 
 			let extra_htlc_dust_exposure = on_counterparty_tx_dust_exposure_msat + (extra_htlc_commit_tx_fee_sat + extra_htlc_htlc_tx_fees_sat) * 1000;
@@ -5569,6 +5759,42 @@ where
 		}
 	}
 
+	/// Returns the value of the P2A anchor output on our next commitment transaction, or `None`
+	/// if the channel does not use zero-fee commitments.
+	///
+	/// The P2A anchor collects the value of all outputs trimmed to dust, as well as the
+	/// sub-satoshi remainders of all other outputs, up to [`P2A_MAX_VALUE`].
+	pub fn get_p2a_anchor_value_satoshis(&self, funding: &FundingScope) -> Option<u64> {
+		if !funding.get_channel_type().supports_anchor_zero_fee_commitments() {
+			return None;
+		}
+		let stats = self
+			.get_next_local_commitment_stats(
+				funding,
+				None,
+				true,
+				0,
+				self.get_feerate_sat_per_1000_weight(),
+				None,
+			)
+			.ok()?;
+		// Zero-fee commitments have no second-stage HTLC fees, so HTLCs are only trimmed if they
+		// are below our dust limit.
+		let dust_limit_sat = self.holder_dust_limit_satoshis;
+		let nondust_output_values_sat = self
+			.get_next_commitment_htlcs(true, None, true)
+			.iter()
+			.map(|htlc| htlc.amount_msat / 1000)
+			.chain([
+				stats.holder_balance_before_fee_msat / 1000,
+				stats.counterparty_balance_before_fee_msat / 1000,
+			])
+			.filter(|value_sat| *value_sat >= dust_limit_sat)
+			.sum::<u64>();
+		let trimmed_sat = funding.get_value_satoshis().saturating_sub(nondust_output_values_sat);
+		Some(cmp::min(trimmed_sat, P2A_MAX_VALUE))
+	}
+
 	/// Returns information on all pending inbound HTLCs.
 	#[rustfmt::skip]
 	pub fn get_pending_inbound_htlc_details(&self, funding: &FundingScope) -> Vec<InboundHTLCDetails> {
@@ -5679,7 +5905,7 @@ where
 		let htlc_stats = context.get_pending_htlc_stats(funding, None, dust_exposure_limiting_feerate);
 
 		// Subtract any non-HTLC outputs from the local and remote balances
-		let (local_balance_before_fee_msat, remote_balance_before_fee_msat) = SpecTxBuilder {}.subtract_non_htlc_outputs(
+		let (local_balance_before_fee_msat, remote_balance_before_fee_msat) = context.tx_builder.subtract_non_htlc_outputs(
 			funding.is_outbound(),
 			funding.value_to_self_msat.saturating_sub(htlc_stats.pending_outbound_htlcs_value_msat),
 			(funding.get_value_satoshis() * 1000).checked_sub(funding.value_to_self_msat).unwrap().saturating_sub(htlc_stats.pending_inbound_htlcs_value_msat),
@@ -5896,7 +6122,7 @@ where
 		}
 
 		let num_htlcs = included_htlcs + addl_htlcs;
-		SpecTxBuilder {}.commit_tx_fee_sat(context.feerate_per_kw, num_htlcs, funding.get_channel_type()) * 1000
+		context.tx_builder.commit_tx_fee_sat(context.feerate_per_kw, num_htlcs, funding.get_channel_type()) * 1000
 	}
 
 	/// Get the commitment tx fee for the remote's next commitment transaction based on the number of
@@ -5973,7 +6199,7 @@ where
 		}
 
 		let num_htlcs = included_htlcs + addl_htlcs;
-		SpecTxBuilder {}.commit_tx_fee_sat(context.feerate_per_kw, num_htlcs, funding.get_channel_type()) * 1000
+		context.tx_builder.commit_tx_fee_sat(context.feerate_per_kw, num_htlcs, funding.get_channel_type()) * 1000
 	}
 
 	#[rustfmt::skip]
@@ -6031,7 +6257,9 @@ where
 		// be delayed in being processed! See the docs for `ChannelManagerReadArgs` for more.
 		assert!(!matches!(self.channel_state, ChannelState::ShutdownComplete));
 
-		let broadcast = self.is_funding_broadcastable();
+		// If our counterparty proved our state is stale, broadcasting our latest commitment
+		// transaction would allow them to claim all of the channel's funds.
+		let broadcast = self.is_funding_broadcastable() && !self.holder_state_stale;
 
 		// We go ahead and "free" any holding cell HTLCs or HTLCs we haven't yet committed to and
 		// return them to fail the payment.
@@ -6107,6 +6335,7 @@ where
 					update_id: self.latest_monitor_update_id,
 					updates: vec![ChannelMonitorUpdateStep::ChannelForceClosed {
 						should_broadcast: broadcast,
+						holder_commitment_stale: self.holder_state_stale,
 					}],
 					channel_id: Some(self.channel_id()),
 				};
@@ -6156,8 +6385,14 @@ where
 		log_trace!(logger, "Initial counterparty tx for channel {} is: txid {} tx {}",
 			&self.channel_id(), counterparty_initial_bitcoin_tx.txid, encode::serialize_hex(&counterparty_initial_bitcoin_tx.transaction));
 
+		let builder_ready = self.tx_builder.counterparty_commitment_ready(
+			counterparty_initial_commitment_tx.commitment_number(),
+			&counterparty_initial_commitment_tx.per_commitment_point(),
+		).is_ok();
+
 		// We sign "counterparty" commitment transaction, allowing them to broadcast the tx if they wish.
 		let signature = match &self.holder_signer {
+			_ if !builder_ready => None,
 			// TODO (arik): move match into calling method for Taproot
 			ChannelSignerType::Ecdsa(ecdsa) => ecdsa.sign_counterparty_commitment(
 				channel_parameters, &counterparty_initial_commitment_tx, Vec::new(), Vec::new(), &self.secp_ctx
@@ -6271,6 +6506,15 @@ where
 			commitment_point = self.counterparty_current_commitment_point.unwrap();
 		}
 
+		if self
+			.tx_builder
+			.counterparty_commitment_ready(commitment_number, &commitment_point)
+			.is_err()
+		{
+			log_trace!(logger, "Initial counterparty commitment transaction is not yet ready");
+			return None;
+		}
+
 		let commitment_data = self.build_commitment_transaction(
 			funding,
 			commitment_number,
@@ -6428,6 +6672,27 @@ where
 	pub fn historical_scids(&self) -> &[u64] {
 		&self.historical_scids[..]
 	}
+
+	/// Returns the most recent `warning` and `error` messages exchanged regarding this channel,
+	/// oldest first.
+	pub fn protocol_error_history(&self) -> &[ProtocolErrorRecord] {
+		&self.protocol_error_history[..]
+	}
+
+	/// Records a `warning` or `error` message exchanged regarding this channel, dropping the oldest
+	/// record if we already have [`MAX_PROTOCOL_ERRORS_PER_CHANNEL`] of them.
+	pub fn record_protocol_error(&mut self, record: ProtocolErrorRecord) {
+		if self.protocol_error_history.len() >= MAX_PROTOCOL_ERRORS_PER_CHANNEL {
+			self.protocol_error_history.remove(0);
+		}
+		self.protocol_error_history.push(record);
+	}
+
+	/// Returns the height of the best block at the time our funding transaction was un-confirmed,
+	/// if we're currently waiting for it to re-confirm.
+	pub fn funding_unconfirmed_at_height(&self) -> Option<u32> {
+		self.funding_unconfirmed_at_height
+	}
 }
 
 // Internal utility functions for channels
@@ -6578,7 +6843,7 @@ fn estimate_v2_funding_transaction_fee(
 /// the fees of the common fields as well as the output and extra input weights.
 /// Returns estimated (partial) fees as additional information
 #[rustfmt::skip]
-fn check_v2_funding_inputs_sufficient(
+pub(super) fn check_v2_funding_inputs_sufficient(
 	contribution_amount: i64, funding_inputs: &[FundingTxInput], is_initiator: bool,
 	is_splice: bool, funding_feerate_sat_per_1000_weight: u32,
 ) -> Result<u64, String> {
@@ -6774,6 +7039,18 @@ where
 	/// initiator we may be able to merge this action into what the counterparty wanted to do (e.g.
 	/// in the case of splicing).
 	quiescent_action: Option<QuiescentAction>,
+
+	/// Whether we're quiescent as the initiator following a [`QuiescentAction::Hold`], in which
+	/// case we remain so until the user exits quiescence or the counterparty disconnects. This is
+	/// not persisted as quiescence always ends upon reconnection.
+	holding_quiescence: bool,
+
+	/// Whether we've generated an [`Event::PeerFeerateNearLimit`] for a feerate proposed by our
+	/// counterparty which hasn't since recovered past the [`UpdateFeePolicy`]'s reset threshold.
+	/// This is not persisted, so we may warn again after a restart.
+	///
+	/// [`Event::PeerFeerateNearLimit`]: crate::events::Event::PeerFeerateNearLimit
+	remote_feerate_near_limit: bool,
 }
 
 #[cfg(any(test, fuzzing))]
@@ -6977,7 +7254,6 @@ where
 							contributed_outputs: outputs,
 						})
 					},
-					#[cfg(any(test, fuzzing))]
 					Some(quiescent_action) => {
 						self.quiescent_action = Some(quiescent_action);
 						None
@@ -7113,18 +7389,25 @@ where
 		)
 	}
 
+	/// Checks a feerate proposed by our counterparty against the limits set by `update_fee_policy`,
+	/// returning the limits applied.
 	#[rustfmt::skip]
 	fn check_remote_fee<F: Deref, L: Deref>(
 		channel_type: &ChannelTypeFeatures, fee_estimator: &LowerBoundedFeeEstimator<F>,
-		feerate_per_kw: u32, cur_feerate_per_kw: Option<u32>, logger: &L
-	) -> Result<(), ChannelError> where F::Target: FeeEstimator, L::Target: Logger,
+		update_fee_policy: &dyn UpdateFeePolicy, feerate_per_kw: u32, cur_feerate_per_kw: Option<u32>,
+		logger: &L
+	) -> Result<RemoteFeerateLimits, ChannelError> where F::Target: FeeEstimator, L::Target: Logger,
 	{
 		if channel_type.supports_anchor_zero_fee_commitments() {
 			if feerate_per_kw != 0 {
 				let err = "Zero Fee Channels must never attempt to use a fee".to_owned();
-				return Err(ChannelError::close(err));
+				return Err(ChannelError::close_misbehaving(MisbehaviorKind::FeeAbuse, err));
 			} else {
-				return Ok(());
+				return Ok(RemoteFeerateLimits {
+					min_feerate_sat_per_1000_weight: 0,
+					warn_below_sat_per_1000_weight: 0,
+					warn_reset_above_sat_per_1000_weight: 0,
+				});
 			}
 		}
 
@@ -7133,14 +7416,16 @@ where
 		} else {
 			ConfirmationTarget::MinAllowedNonAnchorChannelRemoteFee
 		};
-		let lower_limit = fee_estimator.bounded_sat_per_1000_weight(lower_limit_conf_target);
+		let estimate = fee_estimator.bounded_sat_per_1000_weight(lower_limit_conf_target);
+		let limits = update_fee_policy.remote_feerate_limits(channel_type, estimate);
+		let lower_limit = limits.min_feerate_sat_per_1000_weight;
 		if feerate_per_kw < lower_limit {
 			if let Some(cur_feerate) = cur_feerate_per_kw {
 				if feerate_per_kw > cur_feerate {
 					log_warn!(logger,
 						"Accepting feerate that may prevent us from closing this channel because it's higher than what we have now. Had {} s/kW, now {} s/kW.",
 						cur_feerate, feerate_per_kw);
-					return Ok(());
+					return Ok(limits);
 				}
 			}
 			return Err(ChannelError::Close((format!(
@@ -7148,9 +7433,9 @@ where
 			), ClosureReason::PeerFeerateTooLow {
 				peer_feerate_sat_per_kw: feerate_per_kw,
 				required_feerate_sat_per_kw: lower_limit,
-			})));
+			}, None)));
 		}
-		Ok(())
+		Ok(limits)
 	}
 
 	#[inline]
@@ -7874,12 +8159,12 @@ where
 			if signing_session.has_received_tx_signatures() {
 				let msg = "Received initial commitment_signed after peer's tx_signatures received!";
 				let reason = ClosureReason::ProcessingError { err: msg.to_owned() };
-				return Err(ChannelError::Close((msg.to_owned(), reason)));
+				return Err(ChannelError::Close((msg.to_owned(), reason, None)));
 			}
 		} else {
 			let msg = "Received initial commitment_signed before funding transaction constructed!";
 			let reason = ClosureReason::ProcessingError { err: msg.to_owned() };
-			return Err(ChannelError::Close((msg.to_owned(), reason)));
+			return Err(ChannelError::Close((msg.to_owned(), reason, None)));
 		};
 
 		let holder_commitment_point = &mut self.holder_commitment_point.clone();
@@ -8384,6 +8669,7 @@ where
 						skimmed_fee_msat,
 						blinding_point,
 						hold_htlc,
+						endorsed,
 						..
 					} => {
 						match self.send_htlc(
@@ -8396,6 +8682,7 @@ where
 							skimmed_fee_msat,
 							blinding_point,
 							hold_htlc.is_some(),
+							endorsed,
 							fee_estimator,
 							logger,
 						) {
@@ -8581,7 +8868,7 @@ where
 			if PublicKey::from_secret_key(&self.context.secp_ctx, &secret)
 				!= counterparty_current_commitment_point
 			{
-				return Err(ChannelError::close("Got a revoke commitment secret which didn't correspond to their current pubkey".to_owned()));
+				return Err(ChannelError::close_misbehaving(MisbehaviorKind::InvalidSignature, "Got a revoke commitment secret which didn't correspond to their current pubkey".to_owned()));
 			}
 		}
 
@@ -8619,7 +8906,8 @@ where
 				msg.per_commitment_secret,
 			)
 			.map_err(|_| {
-				ChannelError::close("Previous secrets did not match new one".to_owned())
+				let err = "Previous secrets did not match new one".to_owned();
+				ChannelError::close_misbehaving(MisbehaviorKind::InvalidSignature, err)
 			})?;
 		self.context.latest_monitor_update_id += 1;
 		let mut monitor_update = ChannelMonitorUpdate {
@@ -9144,14 +9432,14 @@ where
 		if msg.tx_hash != signing_session.unsigned_tx().compute_txid() {
 			let msg = "The txid for the transaction does not match";
 			let reason = ClosureReason::ProcessingError { err: msg.to_owned() };
-			return Err(ChannelError::Close((msg.to_owned(), reason)));
+			return Err(ChannelError::Close((msg.to_owned(), reason, None)));
 		}
 
 		for witness in &msg.witnesses {
 			if witness.is_empty() {
 				let msg = "Unexpected empty witness in tx_signatures received";
 				let reason = ClosureReason::ProcessingError { err: msg.to_owned() };
-				return Err(ChannelError::Close((msg.to_owned(), reason)));
+				return Err(ChannelError::Close((msg.to_owned(), reason, None)));
 			}
 		}
 
@@ -9182,14 +9470,48 @@ where
 	/// Queues up an outbound update fee by placing it in the holding cell. You should call
 	/// [`Self::maybe_free_holding_cell_htlcs`] in order to actually generate and send the
 	/// commitment update.
+	///
+	/// Returns whether the update fee was queued, which it isn't if our balance cannot cover the
+	/// commitment transaction fee at the new feerate.
 	pub fn queue_update_fee<F: Deref, L: Deref>(
 		&mut self, feerate_per_kw: u32, fee_estimator: &LowerBoundedFeeEstimator<F>, logger: &L,
-	) where
+	) -> bool
+	where
 		F::Target: FeeEstimator,
 		L::Target: Logger,
 	{
 		let msg_opt = self.send_update_fee(feerate_per_kw, true, fee_estimator, logger);
 		assert!(msg_opt.is_none(), "We forced holding cell?");
+		self.context.holding_cell_update_fee == Some(feerate_per_kw)
+	}
+
+	/// Notes that a timer tick has elapsed for the purpose of
+	/// [`ChannelConfig::min_update_fee_interval_ticks`].
+	pub fn update_fee_timer_tick(&mut self) {
+		if let Some(ticks) = self.context.ticks_since_update_fee.as_mut() {
+			*ticks = ticks.saturating_add(1);
+		}
+	}
+
+	/// Returns whether we have to wait before sending another `update_fee` per
+	/// [`ChannelConfig::min_update_fee_interval_ticks`].
+	pub fn is_update_fee_rate_limited(&self) -> bool {
+		let min_interval_ticks = self.context.config().min_update_fee_interval_ticks;
+		self.context.ticks_since_update_fee.map_or(false, |ticks| ticks < min_interval_ticks)
+	}
+
+	/// Bounds the feerate we'd like to move our commitment transaction to by
+	/// [`ChannelConfig::max_update_fee_step_percent`], relative to the current feerate.
+	pub fn step_limited_update_feerate(&self, feerate_per_kw: u32) -> u32 {
+		let max_step_percent = self.context.config().max_update_fee_step_percent;
+		if max_step_percent == 0 {
+			return feerate_per_kw;
+		}
+		let current_feerate = self.context.feerate_per_kw as u64;
+		let max_step = current_feerate * max_step_percent as u64 / 100;
+		let min_feerate = current_feerate.saturating_sub(max_step);
+		let max_feerate = cmp::min(current_feerate + max_step, u32::MAX as u64);
+		(feerate_per_kw as u64).clamp(min_feerate, max_feerate) as u32
 	}
 
 	/// Adds a pending update to this channel. See the doc for send_htlc for
@@ -9234,6 +9556,7 @@ where
 			force_holding_cell = true;
 		}
 
+		self.context.ticks_since_update_fee = Some(0);
 		if force_holding_cell {
 			self.context.holding_cell_update_fee = Some(feerate_per_kw);
 			return None;
@@ -9496,12 +9819,23 @@ where
 		}
 	}
 
+	/// Handles an `update_fee` message from our counterparty, checking the new feerate against
+	/// `update_fee_policy`.
+	///
+	/// Returns the minimum feerate we'd accept if the new feerate is close enough to it that an
+	/// [`Event::PeerFeerateNearLimit`] should be generated.
+	///
+	/// [`Event::PeerFeerateNearLimit`]: crate::events::Event::PeerFeerateNearLimit
 	#[rustfmt::skip]
-	pub fn update_fee<F: Deref, L: Deref>(&mut self, fee_estimator: &LowerBoundedFeeEstimator<F>, msg: &msgs::UpdateFee, logger: &L) -> Result<(), ChannelError>
+	pub fn update_fee<F: Deref, L: Deref>(
+		&mut self, fee_estimator: &LowerBoundedFeeEstimator<F>, update_fee_policy: &dyn UpdateFeePolicy,
+		msg: &msgs::UpdateFee, logger: &L,
+	) -> Result<Option<u32>, ChannelError>
 		where F::Target: FeeEstimator, L::Target: Logger
 	{
 		if self.funding.is_outbound() {
-			return Err(ChannelError::close("Non-funding remote tried to update channel fee".to_owned()));
+			let err = "Non-funding remote tried to update channel fee".to_owned();
+			return Err(ChannelError::close_misbehaving(MisbehaviorKind::FeeAbuse, err));
 		}
 		if self.context.channel_state.is_peer_disconnected() {
 			return Err(ChannelError::close("Peer sent update_fee when we needed a channel_reestablish".to_owned()));
@@ -9513,20 +9847,35 @@ where
 			return Err(ChannelError::WarnAndDisconnect("Update fee message received for zero fee commitment channel".to_owned()));
 		}
 
-		core::iter::once(&self.funding)
-			.chain(self.pending_funding().iter())
-			.try_for_each(|funding| FundedChannel::<SP>::check_remote_fee(funding.get_channel_type(), fee_estimator, msg.feerate_per_kw, Some(self.context.feerate_per_kw), logger))?;
+		let limits = FundedChannel::<SP>::check_remote_fee(self.funding.get_channel_type(), fee_estimator, update_fee_policy, msg.feerate_per_kw, Some(self.context.feerate_per_kw), logger)?;
+		for funding in self.pending_funding().iter() {
+			FundedChannel::<SP>::check_remote_fee(funding.get_channel_type(), fee_estimator, update_fee_policy, msg.feerate_per_kw, Some(self.context.feerate_per_kw), logger)?;
+		}
+
+		let mut near_limit_warning = None;
+		if msg.feerate_per_kw < limits.warn_below_sat_per_1000_weight {
+			// Only warn once until the feerate recovers past the reset threshold, to avoid
+			// repeated warnings as the feerate oscillates around the warning threshold.
+			if !self.remote_feerate_near_limit {
+				log_warn!(logger, "Accepting feerate of {} s/kW which is close to our lower limit of {} s/kW",
+					msg.feerate_per_kw, limits.min_feerate_sat_per_1000_weight);
+				self.remote_feerate_near_limit = true;
+				near_limit_warning = Some(limits.min_feerate_sat_per_1000_weight);
+			}
+		} else if msg.feerate_per_kw >= limits.warn_reset_above_sat_per_1000_weight {
+			self.remote_feerate_near_limit = false;
+		}
 
 		self.context.pending_update_fee = Some((msg.feerate_per_kw, FeeUpdateState::RemoteAnnounced));
 		self.context.update_time_counter += 1;
-		Ok(())
+		Ok(near_limit_warning)
 	}
 
 	/// Indicates that the signer may have some signatures for us, so we should retry if we're
 	/// blocked.
 	#[rustfmt::skip]
 	pub fn signer_maybe_unblocked<L: Deref, CBP>(
-		&mut self, logger: &L, path_for_release_htlc: CBP
+		&mut self, logger: &L, path_for_release_htlc: CBP, tolerate_stale_state: bool,
 	) -> Result<SignerResumeUpdates, ChannelError> where L::Target: Logger, CBP: Fn(u64) -> BlindedMessagePath {
 		if let Some((commitment_number, commitment_secret)) = self.context.signer_pending_stale_state_verification.clone() {
 			if let Ok(expected_point) = self.context.holder_signer.as_ref()
@@ -9534,9 +9883,9 @@ where
 			{
 				self.context.signer_pending_stale_state_verification.take();
 				if expected_point != PublicKey::from_secret_key(&self.context.secp_ctx, &commitment_secret) {
-					return Err(ChannelError::close("Peer sent a channel_reestablish indicating we're stale with an invalid commitment secret".to_owned()));
+					return Err(ChannelError::close_misbehaving(MisbehaviorKind::StaleReestablish, "Peer sent a channel_reestablish indicating we're stale with an invalid commitment secret".to_owned()));
 				}
-				Self::panic_on_stale_state(logger);
+				return Err(self.on_stale_state(logger, tolerate_stale_state));
 			}
 		}
 		if !self.holder_commitment_point.can_advance() {
@@ -9719,6 +10068,7 @@ where
 					skimmed_fee_msat: htlc.skimmed_fee_msat,
 					blinding_point: htlc.blinding_point,
 					hold_htlc: htlc.hold_htlc,
+					endorsed: htlc.endorsed.then(|| 1),
 				});
 			}
 		}
@@ -9831,13 +10181,35 @@ where
 			then restart with an empty ChannelManager and the latest ChannelMonitors that you do have.");
 	}
 
+	/// Handles our counterparty proving that our state is stale. Unless `tolerate_stale_state` is
+	/// set, this panics. Otherwise, the returned error closes the channel without broadcasting our
+	/// (revoked) commitment transaction, sending our counterparty an `error` which prompts them to
+	/// broadcast their latest commitment transaction instead.
+	fn on_stale_state<L: Deref>(&mut self, logger: &L, tolerate_stale_state: bool) -> ChannelError
+	where
+		L::Target: Logger,
+	{
+		if !tolerate_stale_state {
+			Self::panic_on_stale_state(logger);
+		}
+		log_error!(
+			logger,
+			"We have fallen behind in channel {}, closing it without broadcasting to recover our funds",
+			self.context.channel_id()
+		);
+		self.context.holder_state_stale = true;
+		ChannelError::close(
+			"We have fallen behind, please broadcast your latest commitment transaction".to_owned(),
+		)
+	}
+
 	/// May panic if some calls other than message-handling calls (which will all Err immediately)
 	/// have been called between remove_uncommitted_htlcs_and_mark_paused and this call.
 	#[rustfmt::skip]
 	pub fn channel_reestablish<L: Deref, NS: Deref, CBP>(
 		&mut self, msg: &msgs::ChannelReestablish, logger: &L, node_signer: &NS,
 		chain_hash: ChainHash, user_config: &UserConfig, best_block: &BestBlock,
-		path_for_release_htlc: CBP,
+		path_for_release_htlc: CBP, tolerate_stale_state: bool,
 	) -> Result<ReestablishResponses, ChannelError>
 	where
 		L::Target: Logger,
@@ -9859,13 +10231,13 @@ where
 			|| msg.next_local_commitment_number >= INITIAL_COMMITMENT_NUMBER
 			|| msg.next_remote_commitment_number >= INITIAL_COMMITMENT_NUMBER
 		{
-			return Err(ChannelError::close("Peer sent an invalid channel_reestablish to force close in a non-standard way".to_owned()));
+			return Err(ChannelError::close_misbehaving(MisbehaviorKind::StaleReestablish, "Peer sent an invalid channel_reestablish to force close in a non-standard way".to_owned()));
 		}
 
 		let our_commitment_transaction = INITIAL_COMMITMENT_NUMBER - self.holder_commitment_point.current_transaction_number();
 		if msg.next_remote_commitment_number > 0 {
 			let given_secret = SecretKey::from_slice(&msg.your_last_per_commitment_secret)
-				.map_err(|_| ChannelError::close("Peer sent a garbage channel_reestablish with unparseable secret key".to_owned()))?;
+				.map_err(|_| ChannelError::close_misbehaving(MisbehaviorKind::StaleReestablish, "Peer sent a garbage channel_reestablish with unparseable secret key".to_owned()))?;
 			if msg.next_remote_commitment_number > our_commitment_transaction {
 				let given_commitment_number = INITIAL_COMMITMENT_NUMBER - msg.next_remote_commitment_number + 1;
 				let expected_point = self.context.holder_signer.as_ref()
@@ -9877,20 +10249,20 @@ where
 					return Err(ChannelError::WarnAndDisconnect("Channel is not ready to be reestablished yet".to_owned()));
 				}
 				if expected_point != Some(PublicKey::from_secret_key(&self.context.secp_ctx, &given_secret)) {
-					return Err(ChannelError::close("Peer sent a channel_reestablish indicating we're stale with an invalid commitment secret".to_owned()));
+					return Err(ChannelError::close_misbehaving(MisbehaviorKind::StaleReestablish, "Peer sent a channel_reestablish indicating we're stale with an invalid commitment secret".to_owned()));
 				}
-				Self::panic_on_stale_state(logger);
+				return Err(self.on_stale_state(logger, tolerate_stale_state));
 			} else if msg.next_remote_commitment_number == our_commitment_transaction {
 				let expected_point = self.holder_commitment_point.last_revoked_point()
 					.expect("The last revoked commitment point must exist when the state has advanced");
 				if expected_point != PublicKey::from_secret_key(&self.context.secp_ctx, &given_secret) {
-					return Err(ChannelError::close("Peer sent a garbage channel_reestablish with secret key not matching the commitment height provided".to_owned()));
+					return Err(ChannelError::close_misbehaving(MisbehaviorKind::StaleReestablish, "Peer sent a garbage channel_reestablish with secret key not matching the commitment height provided".to_owned()));
 				}
 			} else if msg.next_remote_commitment_number + 1 == our_commitment_transaction {
 				let expected_point = self.holder_commitment_point.previous_revoked_point()
 					.expect("The previous revoked commitment point must exist when they are one state behind");
 				if expected_point != PublicKey::from_secret_key(&self.context.secp_ctx, &given_secret) {
-					return Err(ChannelError::close("Peer sent a garbage channel_reestablish with secret key not matching the commitment height provided".to_owned()));
+					return Err(ChannelError::close_misbehaving(MisbehaviorKind::StaleReestablish, "Peer sent a garbage channel_reestablish with secret key not matching the commitment height provided".to_owned()));
 				}
 			}
 		}
@@ -9975,6 +10347,7 @@ where
 								(
 									message.clone(),
 									ClosureReason::HolderForceClosed { message, broadcasted_latest_txn: Some(false) },
+									None,
 								)
 							)
 						})?;
@@ -9987,6 +10360,7 @@ where
 								(
 									message.clone(),
 									ClosureReason::HolderForceClosed { message, broadcasted_latest_txn: Some(false) },
+									None,
 								)
 							)
 						})?;
@@ -10036,7 +10410,7 @@ where
 			if !self.context.channel_state.is_our_channel_ready() ||
 					self.context.channel_state.is_monitor_update_in_progress() {
 				if msg.next_remote_commitment_number != 0 {
-					return Err(ChannelError::close("Peer claimed they saw a revoke_and_ack but we haven't sent channel_ready yet".to_owned()));
+					return Err(ChannelError::close_misbehaving(MisbehaviorKind::StaleReestablish, "Peer claimed they saw a revoke_and_ack but we haven't sent channel_ready yet".to_owned()));
 				}
 
 				return Ok(ReestablishResponses {
@@ -10077,7 +10451,7 @@ where
 			}
 		} else {
 			debug_assert!(false, "All values should have been handled in the four cases above");
-			return Err(ChannelError::close(format!(
+			return Err(ChannelError::close_misbehaving(MisbehaviorKind::StaleReestablish, format!(
 				"Peer attempted to reestablish channel expecting a future local commitment transaction: {} (received) vs {} (expected)",
 				msg.next_remote_commitment_number,
 				our_commitment_transaction
@@ -10203,13 +10577,13 @@ where
 				})
 			}
 		} else if msg.next_local_commitment_number < next_counterparty_commitment_number {
-			Err(ChannelError::close(format!(
+			Err(ChannelError::close_misbehaving(MisbehaviorKind::StaleReestablish, format!(
 				"Peer attempted to reestablish channel with a very old remote commitment transaction: {} (received) vs {} (expected)",
 				msg.next_local_commitment_number,
 				next_counterparty_commitment_number,
 			)))
 		} else {
-			Err(ChannelError::close(format!(
+			Err(ChannelError::close_misbehaving(MisbehaviorKind::StaleReestablish, format!(
 				"Peer attempted to reestablish channel with a future remote commitment transaction: {} (received) vs {} (expected)",
 				msg.next_local_commitment_number,
 				next_counterparty_commitment_number,
@@ -10371,6 +10745,11 @@ where
 	#[allow(clippy::assertions_on_constants)]
 	#[rustfmt::skip]
 	pub fn should_disconnect_peer_awaiting_response(&mut self) -> bool {
+		if self.holding_quiescence {
+			// We're only quiescent because the user asked us to be, so there's no progress to wait
+			// on until they exit quiescence.
+			return false;
+		}
 		if let Some(ticks_elapsed) = self.context.sent_message_awaiting_response.as_mut() {
 			*ticks_elapsed += 1;
 			*ticks_elapsed >= DISCONNECT_PEER_AWAITING_RESPONSE_TICKS
@@ -10888,6 +11267,12 @@ where
 			return Err(LocalHTLCFailureReason::ChannelClosed)
 		}
 
+		// While our funding transaction awaits re-confirmation the channel is frozen, so any HTLC
+		// our counterparty still sent us is failed back rather than being received or forwarded.
+		if self.context.funding_unconfirmed_at_height.is_some() {
+			return Err(LocalHTLCFailureReason::ChannelNotReady)
+		}
+
 		let dust_exposure_limiting_feerate = self.context.get_dust_exposure_limiting_feerate(
 			&fee_estimator, self.funding.get_channel_type(),
 		);
@@ -10897,6 +11282,51 @@ where
 			.try_for_each(|funding| self.context.can_accept_incoming_htlc(funding, dust_exposure_limiting_feerate, &logger))
 	}
 
+	/// Checks the inbound HTLC with the given `htlc_id` against the given [`InboundHTLCLimits`],
+	/// which may be tighter than the limits negotiated at channel open.
+	///
+	/// Only HTLCs which are not being removed and which were offered no later than the given one
+	/// are counted, such that HTLCs in excess of the limits are failed in the order they were
+	/// offered.
+	pub fn check_inbound_htlc_limits<L: Deref>(
+		&self, htlc_id: u64, limits: &InboundHTLCLimits, logger: L,
+	) -> Result<(), LocalHTLCFailureReason>
+	where
+		L::Target: Logger,
+	{
+		let mut pending_htlcs = 0;
+		let mut pending_value_msat = 0;
+		for htlc in self.context.pending_inbound_htlcs.iter() {
+			if htlc.htlc_id > htlc_id {
+				continue;
+			}
+			if let InboundHTLCState::LocalRemoved(_) = htlc.state {
+				continue;
+			}
+			pending_htlcs += 1;
+			pending_value_msat += htlc.amount_msat;
+		}
+		if pending_htlcs > limits.max_accepted_htlcs as usize {
+			log_info!(
+				logger,
+				"Failing HTLC as the peer would have {} HTLCs pending, more than the {} its reputation allows",
+				pending_htlcs,
+				limits.max_accepted_htlcs,
+			);
+			return Err(LocalHTLCFailureReason::PeerReputationLimit);
+		}
+		if pending_value_msat > limits.max_htlc_value_in_flight_msat {
+			log_info!(
+				logger,
+				"Failing HTLC as the peer would have {} msat in flight, more than the {} msat its reputation allows",
+				pending_value_msat,
+				limits.max_htlc_value_in_flight_msat,
+			);
+			return Err(LocalHTLCFailureReason::PeerReputationLimit);
+		}
+		Ok(())
+	}
+
 	pub fn get_cur_holder_commitment_transaction_number(&self) -> u64 {
 		self.holder_commitment_point.current_transaction_number()
 	}
@@ -11271,7 +11701,7 @@ where
 			self.get_announcement_sigs(node_signer, chain_hash, user_config, block_height, logger);
 
 		if let Some(quiescent_action) = self.quiescent_action.as_ref() {
-			if matches!(quiescent_action, QuiescentAction::Splice(_)) {
+			if matches!(quiescent_action, QuiescentAction::Splice(_) | QuiescentAction::Hold) {
 				self.context.channel_state.set_awaiting_quiescence();
 			}
 		}
@@ -11397,6 +11827,7 @@ where
 		self.do_best_block_updated(
 			height,
 			highest_header_time,
+			user_config.funding_reorg_grace_period_blocks,
 			Some((chain_hash, node_signer, user_config)),
 			logger,
 		)
@@ -11404,7 +11835,7 @@ where
 
 	#[rustfmt::skip]
 	fn do_best_block_updated<NS: Deref, L: Deref>(
-		&mut self, height: u32, highest_header_time: Option<u32>,
+		&mut self, height: u32, highest_header_time: Option<u32>, funding_reorg_grace_period_blocks: u32,
 		chain_node_signer: Option<(ChainHash, &NS, &UserConfig)>, logger: &L
 	) -> Result<(Option<FundingConfirmedMessage>, Vec<(HTLCSource, PaymentHash)>, Option<msgs::AnnouncementSignatures>), ClosureReason>
 	where
@@ -11436,6 +11867,24 @@ where
 		let original_scid = self.funding.short_channel_id;
 		let was_confirmed = self.funding.funding_tx_confirmed_in.is_some();
 		let funding_tx_confirmations = self.funding.get_funding_tx_confirmations(height);
+
+		// Check whether a funding transaction we previously saw un-confirmed has re-confirmed or
+		// failed to do so within the configured grace period.
+		if let Some(unconfirmed_at_height) = self.context.funding_unconfirmed_at_height {
+			if funding_tx_confirmations > 0 {
+				log_info!(logger, "Funding transaction re-confirmed after being un-confirmed at height {}",
+					unconfirmed_at_height);
+				self.context.funding_unconfirmed_at_height = None;
+			} else if height >= unconfirmed_at_height.saturating_add(funding_reorg_grace_period_blocks) {
+				// Restore the SCID we had prior to the reorg so that we'll generate a closure
+				// `channel_update` broadcast event.
+				self.funding.short_channel_id = self.context.historical_scids.last().copied();
+				let err_reason = format!("Funding transaction was un-confirmed at height {} and did not re-confirm within {} blocks.",
+					unconfirmed_at_height, funding_reorg_grace_period_blocks);
+				return Err(ClosureReason::ProcessingError { err: err_reason });
+			}
+		}
+
 		if funding_tx_confirmations == 0 {
 			self.funding.funding_tx_confirmation_height = 0;
 			self.funding.short_channel_id = None;
@@ -11461,8 +11910,9 @@ where
 			// and are now at risk of double-spend. While its possible, even likely, that this is
 			// just a trivial reorg and we should wait to see the new block connected in the next
 			// call, its also possible we've been double-spent. To avoid further loss of funds, we
-			// need some kind of method to freeze the channel and avoid accepting further HTLCs,
-			// but absent such a method, we just force-close.
+			// need to freeze the channel and avoid accepting further HTLCs. If the user configured
+			// a `funding_reorg_grace_period_blocks`, we do so by marking the channel as not live
+			// until the funding re-confirms, otherwise we just force-close.
 			//
 			// The one exception we make is for 0-conf channels, which we decided to trust anyway,
 			// in which case we simply track the previous SCID as a `historical_scids` the same as
@@ -11474,12 +11924,21 @@ where
 					debug_assert!(false);
 				}
 				if self.context.minimum_depth(&self.funding).expect("set for a ready channel") > 0 {
-					// Reset the original short_channel_id so that we'll generate a closure
-					// `channel_update` broadcast event.
-					self.funding.short_channel_id = original_scid;
-					let err_reason = format!("Funding transaction was un-confirmed, originally locked at {} confs.",
-						self.context.minimum_depth.unwrap());
-					return Err(ClosureReason::ProcessingError { err: err_reason });
+					if funding_reorg_grace_period_blocks > 0 {
+						// The user opted to give the funding transaction a chance to re-confirm,
+						// which it will generally do in the next block for trivial reorgs. Until
+						// then, `is_live` will return false, freezing the channel.
+						log_info!(logger, "Funding transaction was un-confirmed, waiting up to {} blocks for it to re-confirm",
+							funding_reorg_grace_period_blocks);
+						self.context.funding_unconfirmed_at_height = Some(height);
+					} else {
+						// Reset the original short_channel_id so that we'll generate a closure
+						// `channel_update` broadcast event.
+						self.funding.short_channel_id = original_scid;
+						let err_reason = format!("Funding transaction was un-confirmed, originally locked at {} confs.",
+							self.context.minimum_depth.unwrap());
+						return Err(ClosureReason::ProcessingError { err: err_reason });
+					}
 				}
 			}
 		} else if !self.funding.is_outbound() && self.funding.funding_tx_confirmed_in.is_none() &&
@@ -11587,7 +12046,8 @@ where
 	/// blocks.
 	#[rustfmt::skip]
 	pub fn transaction_unconfirmed<L: Deref>(
-		&mut self, txid: &Txid, logger: &L,
+		&mut self, txid: &Txid, best_block_height: u32, funding_reorg_grace_period_blocks: u32,
+		logger: &L,
 	) -> Result<(), ClosureReason>
 	where
 		L::Target: Logger,
@@ -11603,11 +12063,19 @@ where
 				let reorg_height = funding.funding_tx_confirmation_height - 1;
 
 				let signer_config = None::<(ChainHash, &&dyn NodeSigner, &UserConfig)>;
-				match self.do_best_block_updated(reorg_height, None, signer_config, logger) {
+				let res = self.do_best_block_updated(
+					reorg_height, None, funding_reorg_grace_period_blocks, signer_config, logger,
+				);
+				match res {
 					Ok((channel_ready, timed_out_htlcs, announcement_sigs)) => {
 						assert!(channel_ready.is_none(), "We can't generate a funding with 0 confirmations?");
 						assert!(timed_out_htlcs.is_empty(), "We can't have accepted HTLCs with a timeout before our funding confirmation?");
 						assert!(announcement_sigs.is_none(), "We can't generate an announcement_sigs with 0 confirmations?");
+						// The grace period for the funding to re-confirm runs from our current best
+						// block rather than the implied reorg height.
+						if let Some(unconfirmed_at_height) = &mut self.context.funding_unconfirmed_at_height {
+							*unconfirmed_at_height = cmp::max(*unconfirmed_at_height, best_block_height);
+						}
 						Ok(())
 					},
 					Err(e) => Err(e),
@@ -12564,7 +13032,8 @@ where
 	pub fn queue_add_htlc<F: Deref, L: Deref>(
 		&mut self, amount_msat: u64, payment_hash: PaymentHash, cltv_expiry: u32,
 		source: HTLCSource, onion_routing_packet: msgs::OnionPacket, skimmed_fee_msat: Option<u64>,
-		blinding_point: Option<PublicKey>, fee_estimator: &LowerBoundedFeeEstimator<F>, logger: &L,
+		blinding_point: Option<PublicKey>, endorsed: bool,
+		fee_estimator: &LowerBoundedFeeEstimator<F>, logger: &L,
 	) -> Result<(), (LocalHTLCFailureReason, String)>
 	where
 		F::Target: FeeEstimator,
@@ -12581,6 +13050,7 @@ where
 			blinding_point,
 			// This method is only called for forwarded HTLCs, which are never held at the next hop
 			false,
+			endorsed,
 			fee_estimator,
 			logger,
 		)
@@ -12612,7 +13082,7 @@ where
 		&mut self, amount_msat: u64, payment_hash: PaymentHash, cltv_expiry: u32,
 		source: HTLCSource, onion_routing_packet: msgs::OnionPacket, mut force_holding_cell: bool,
 		skimmed_fee_msat: Option<u64>, blinding_point: Option<PublicKey>, hold_htlc: bool,
-		fee_estimator: &LowerBoundedFeeEstimator<F>, logger: &L,
+		endorsed: bool, fee_estimator: &LowerBoundedFeeEstimator<F>, logger: &L,
 	) -> Result<bool, (LocalHTLCFailureReason, String)>
 	where
 		F::Target: FeeEstimator,
@@ -12651,6 +13121,10 @@ where
 			));
 		}
 
+		if !endorsed {
+			self.check_unendorsed_htlc_capacity(amount_msat)?;
+		}
+
 		if self.context.channel_state.is_peer_disconnected() {
 			// Note that this should never really happen, if we're !is_live() on receipt of an
 			// incoming HTLC for relay will result in us rejecting the HTLC and we won't allow
@@ -12694,6 +13168,7 @@ where
 				skimmed_fee_msat,
 				blinding_point,
 				hold_htlc: hold_htlc.then(|| ()),
+				endorsed,
 			});
 			return Ok(false);
 		}
@@ -12716,12 +13191,67 @@ where
 			skimmed_fee_msat,
 			send_timestamp,
 			hold_htlc: hold_htlc.then(|| ()),
+			endorsed,
 		});
 		self.context.next_holder_htlc_id += 1;
 
 		Ok(true)
 	}
 
+	/// Checks that sending an unendorsed HTLC of the given amount neither occupies HTLC slots nor
+	/// locks up liquidity reserved for endorsed HTLCs via
+	/// [`ChannelConfig::endorsed_htlc_slots_percentage`] and
+	/// [`ChannelConfig::endorsed_liquidity_percentage`].
+	pub(super) fn check_unendorsed_htlc_capacity(
+		&self, amount_msat: u64,
+	) -> Result<(), (LocalHTLCFailureReason, String)> {
+		let config = self.context.config();
+		let reserved_slots_percentage = cmp::min(config.endorsed_htlc_slots_percentage, 100);
+		let reserved_liquidity_percentage = cmp::min(config.endorsed_liquidity_percentage, 100);
+		if reserved_slots_percentage == 0 && reserved_liquidity_percentage == 0 {
+			return Ok(());
+		}
+
+		let mut unendorsed_htlcs = 0;
+		let mut unendorsed_value_msat = 0;
+		for htlc in self.context.pending_outbound_htlcs.iter().filter(|htlc| !htlc.endorsed) {
+			unendorsed_htlcs += 1;
+			unendorsed_value_msat += htlc.amount_msat;
+		}
+		for update in self.context.holding_cell_htlc_updates.iter() {
+			if let &HTLCUpdateAwaitingACK::AddHTLC { amount_msat, endorsed: false, .. } = update {
+				unendorsed_htlcs += 1;
+				unendorsed_value_msat += amount_msat;
+			}
+		}
+
+		let max_unendorsed_htlcs = self.context.counterparty_max_accepted_htlcs as u64
+			* (100 - reserved_slots_percentage as u64)
+			/ 100;
+		if unendorsed_htlcs + 1 > max_unendorsed_htlcs {
+			return Err((
+				LocalHTLCFailureReason::UnendorsedCapacityExhausted,
+				format!(
+					"Cannot have more than {} unendorsed HTLCs pending at once",
+					max_unendorsed_htlcs
+				),
+			));
+		}
+
+		let max_unendorsed_value_msat = self.context.counterparty_max_htlc_value_in_flight_msat
+			/ 100 * (100 - reserved_liquidity_percentage as u64);
+		if unendorsed_value_msat + amount_msat > max_unendorsed_value_msat {
+			return Err((
+				LocalHTLCFailureReason::UnendorsedCapacityExhausted,
+				format!(
+					"Cannot have more than {} msat in unendorsed HTLCs in flight at once",
+					max_unendorsed_value_msat
+				),
+			));
+		}
+		Ok(())
+	}
+
 	#[rustfmt::skip]
 	pub(super) fn get_available_balances<F: Deref>(
 		&self, fee_estimator: &LowerBoundedFeeEstimator<F>,
@@ -12904,6 +13434,11 @@ where
 		#[cfg(any(test, fuzzing))]
 		self.build_commitment_no_state_update(funding, logger);
 
+		self.context.tx_builder.counterparty_commitment_ready(
+			self.context.counterparty_next_commitment_transaction_number,
+			&self.context.counterparty_next_commitment_point.unwrap(),
+		).map_err(|_| ChannelError::Ignore("Commitment transaction for new commitment_signed is not yet ready".to_owned()))?;
+
 		let commitment_data = self.context.build_commitment_transaction(
 			funding, self.context.counterparty_next_commitment_transaction_number,
 			&self.context.counterparty_next_commitment_point.unwrap(), false, true, logger,
@@ -12965,7 +13500,7 @@ where
 	pub fn send_htlc_and_commit<F: Deref, L: Deref>(
 		&mut self, amount_msat: u64, payment_hash: PaymentHash, cltv_expiry: u32,
 		source: HTLCSource, onion_routing_packet: msgs::OnionPacket, skimmed_fee_msat: Option<u64>,
-		hold_htlc: bool, fee_estimator: &LowerBoundedFeeEstimator<F>, logger: &L,
+		hold_htlc: bool, endorsed: bool, fee_estimator: &LowerBoundedFeeEstimator<F>, logger: &L,
 	) -> Result<Option<ChannelMonitorUpdate>, ChannelError>
 	where
 		F::Target: FeeEstimator,
@@ -12981,6 +13516,7 @@ where
 			skimmed_fee_msat,
 			None,
 			hold_htlc,
+			endorsed,
 			fee_estimator,
 			logger,
 		);
@@ -13333,6 +13869,12 @@ where
 					let splice_init = self.send_splice_init(instructions);
 					return Ok(Some(StfuResponse::SpliceInit(splice_init)));
 				},
+				Some(QuiescentAction::Hold) => {
+					// We stay quiescent until the user exits quiescence or our counterparty
+					// disconnects, letting the user know quiescence was reached.
+					self.holding_quiescence = true;
+					return Ok(Some(StfuResponse::Quiescent));
+				},
 				#[cfg(any(test, fuzzing))]
 				Some(QuiescentAction::DoNothing) => {
 					// In quiescence test we want to just hang out here, letting the test manually
@@ -13379,6 +13921,13 @@ where
 		Ok(None)
 	}
 
+	/// Stops holding the channel quiescent following a [`QuiescentAction::Hold`], returning whether
+	/// we were. As quiescence only ends upon reconnection, the counterparty must then be
+	/// disconnected, after which the channel will no longer be quiescent.
+	pub fn stop_holding_quiescence(&mut self) -> bool {
+		mem::replace(&mut self.holding_quiescence, false)
+	}
+
 	#[cfg(any(test, fuzzing))]
 	#[rustfmt::skip]
 	pub fn exit_quiescence(&mut self) -> bool {
@@ -13499,6 +14048,14 @@ where
 	/// Only allowed after [`FundingScope::channel_transaction_parameters`] is set.
 	#[rustfmt::skip]
 	fn get_funding_created_msg<L: Deref>(&mut self, logger: &L) -> Option<msgs::FundingCreated> where L::Target: Logger {
+		if self.context.tx_builder.counterparty_commitment_ready(
+			self.context.counterparty_next_commitment_transaction_number,
+			&self.context.counterparty_next_commitment_point.unwrap(),
+		).is_err() {
+			log_trace!(logger, "funding_created awaiting commitment transaction builder; setting signer_pending_funding");
+			self.context.signer_pending_funding = true;
+			return None;
+		}
 		let commitment_data = self.context.build_commitment_transaction(&self.funding,
 			self.context.counterparty_next_commitment_transaction_number,
 			&self.context.counterparty_next_commitment_point.unwrap(), false, false, logger);
@@ -13711,6 +14268,8 @@ where
 			holder_commitment_point,
 			pending_splice: None,
 			quiescent_action: None,
+			remote_feerate_near_limit: false,
+			holding_quiescence: false,
 		};
 
 		let need_channel_ready = channel.check_get_channel_ready(0, logger).is_some()
@@ -13999,6 +14558,8 @@ where
 			holder_commitment_point,
 			pending_splice: None,
 			quiescent_action: None,
+			remote_feerate_near_limit: false,
+			holding_quiescence: false,
 		};
 		let need_channel_ready = channel.check_get_channel_ready(0, logger).is_some()
 			|| channel.context.signer_pending_channel_ready;
@@ -14039,25 +14600,34 @@ where
 	pub funding_negotiation_context: FundingNegotiationContext,
 	/// The current interactive transaction construction session under negotiation.
 	pub interactive_tx_constructor: Option<InteractiveTxConstructor>,
+	/// The funds we requested the counterparty contribute to the channel using one of the rates
+	/// from its liquidity advertisement, if any.
+	pub requested_funds: Option<RequestFunds>,
 }
 
 impl<SP: Deref> PendingV2Channel<SP>
 where
 	SP::Target: SignerProvider,
 {
-	#[allow(dead_code)] // TODO(dual_funding): Remove once creating V2 channels is enabled.
 	#[rustfmt::skip]
 	pub fn new_outbound<ES: Deref, F: Deref, L: Deref>(
 		fee_estimator: &LowerBoundedFeeEstimator<F>, entropy_source: &ES, signer_provider: &SP,
 		counterparty_node_id: PublicKey, their_features: &InitFeatures, funding_satoshis: u64,
-		funding_inputs: Vec<FundingTxInput>, user_id: u128, config: &UserConfig,
-		current_chain_height: u32, outbound_scid_alias: u64, funding_confirmation_target: ConfirmationTarget,
-		logger: L,
+		funding_inputs: Vec<FundingTxInput>, change_script: Option<ScriptBuf>, user_id: u128,
+		config: &UserConfig, current_chain_height: u32, outbound_scid_alias: u64,
+		funding_feerate_sat_per_1000_weight: u32, requested_funds: Option<RequestFunds>, logger: L,
 	) -> Result<Self, APIError>
 	where ES::Target: EntropySource,
 	      F::Target: FeeEstimator,
 	      L::Target: Logger,
 	{
+		check_v2_funding_inputs_sufficient(
+			funding_satoshis as i64, &funding_inputs, true, false,
+			funding_feerate_sat_per_1000_weight,
+		).map_err(|err| APIError::APIMisuseError {
+			err: format!("Channel cannot be funded; {}", err),
+		})?;
+
 		let channel_keys_id = signer_provider.generate_channel_keys_id(false, user_id);
 		let holder_signer = signer_provider.derive_channel_signer(channel_keys_id);
 
@@ -14068,7 +14638,12 @@ where
 		let holder_selected_channel_reserve_satoshis = get_v2_channel_reserve_satoshis(
 			funding_satoshis, MIN_CHAN_DUST_LIMIT_SATOSHIS);
 
-		let funding_feerate_sat_per_1000_weight = fee_estimator.bounded_sat_per_1000_weight(funding_confirmation_target);
+		// The lease fee is paid to the seller out of our balance in the initial commitment.
+		let lease_fee_msat = requested_funds.map_or(0, |request| {
+			request.rate.lease_fee_sat(request.requested_sats, funding_feerate_sat_per_1000_weight)
+				.saturating_mul(1000)
+		});
+
 		let funding_tx_locktime = LockTime::from_height(current_chain_height)
 			.map_err(|_| APIError::APIMisuseError {
 				err: format!(
@@ -14082,7 +14657,7 @@ where
 			counterparty_node_id,
 			their_features,
 			funding_satoshis,
-			0,
+			lease_fee_msat,
 			user_id,
 			config,
 			current_chain_height,
@@ -14105,7 +14680,7 @@ where
 			shared_funding_input: None,
 			our_funding_inputs: funding_inputs,
 			our_funding_outputs: Vec::new(),
-			change_script: None,
+			change_script,
 		};
 		let chan = Self {
 			funding,
@@ -14113,6 +14688,7 @@ where
 			unfunded_context,
 			funding_negotiation_context,
 			interactive_tx_constructor: None,
+			requested_funds,
 		};
 		Ok(chan)
 	}
@@ -14184,31 +14760,132 @@ where
 				}),
 				channel_type: Some(self.funding.get_channel_type().clone()),
 			},
-			funding_feerate_sat_per_1000_weight: self.context.feerate_per_kw,
+			funding_feerate_sat_per_1000_weight:
+				self.funding_negotiation_context.funding_feerate_sat_per_1000_weight,
 			second_per_commitment_point,
 			locktime: self.funding_negotiation_context.funding_tx_locktime.to_consensus_u32(),
 			require_confirmed_inputs: None,
+			request_funds: self.requested_funds,
+		}
+	}
+
+	/// Handles an `accept_channel2` message from the counterparty to our `open_channel2`, starting
+	/// the interactive construction of the funding transaction.
+	///
+	/// On success, the channel's ID is updated to its final (non-temporary) value and the first
+	/// interactive transaction message we need to send, if any, is returned.
+	pub fn accept_channel_v2<ES: Deref>(
+		&mut self, msg: &msgs::AcceptChannelV2, default_limits: &ChannelHandshakeLimits,
+		their_features: &InitFeatures, signer_provider: &SP, entropy_source: &ES,
+		holder_node_id: PublicKey,
+	) -> Result<Option<InteractiveTxMessageSend>, ChannelError>
+	where
+		ES::Target: EntropySource,
+	{
+		if let Some(requested_funds) = self.requested_funds {
+			if msg.funding_satoshis < requested_funds.requested_sats {
+				return Err(ChannelError::close(format!(
+					"Counterparty contributed {} sats, less than the {} sats we requested for the lease",
+					msg.funding_satoshis, requested_funds.requested_sats,
+				)));
+			}
+		}
+
+		// The counterparty may contribute to the channel as well, increasing its total value. As
+		// reserves in V2 channels depend on the total value, they need to be updated accordingly.
+		let channel_value_satoshis =
+			self.funding.get_value_satoshis().saturating_add(msg.funding_satoshis);
+		if channel_value_satoshis > TOTAL_BITCOIN_SUPPLY_SATOSHIS {
+			return Err(ChannelError::close(format!(
+				"Counterparty's funding contribution of {} sats exceeds the total bitcoin supply",
+				msg.funding_satoshis,
+			)));
 		}
+		self.funding.channel_transaction_parameters.channel_value_satoshis = channel_value_satoshis;
+		self.funding.holder_selected_channel_reserve_satoshis =
+			get_v2_channel_reserve_satoshis(channel_value_satoshis, MIN_CHAN_DUST_LIMIT_SATOSHIS);
+		let counterparty_selected_channel_reserve_satoshis = get_v2_channel_reserve_satoshis(
+			channel_value_satoshis,
+			msg.common_fields.dust_limit_satoshis,
+		);
+
+		self.context.do_accept_channel_checks(
+			&mut self.funding,
+			default_limits,
+			their_features,
+			&msg.common_fields,
+			counterparty_selected_channel_reserve_satoshis,
+		)?;
+
+		self.context.channel_id = ChannelId::v2_from_revocation_basepoints(
+			&self.funding.get_holder_pubkeys().revocation_basepoint,
+			&self.funding.get_counterparty_pubkeys().revocation_basepoint,
+		);
+
+		// Keep our original contribution around, as negotiating the funding transaction may still
+		// fail and require us to report the inputs we contributed.
+		let funding_negotiation_context = FundingNegotiationContext {
+			is_initiator: true,
+			our_funding_contribution: self.funding_negotiation_context.our_funding_contribution,
+			funding_tx_locktime: self.funding_negotiation_context.funding_tx_locktime,
+			funding_feerate_sat_per_1000_weight: self
+				.funding_negotiation_context
+				.funding_feerate_sat_per_1000_weight,
+			shared_funding_input: None,
+			our_funding_inputs: self.funding_negotiation_context.our_funding_inputs.clone(),
+			our_funding_outputs: self.funding_negotiation_context.our_funding_outputs.clone(),
+			change_script: self.funding_negotiation_context.change_script.clone(),
+		};
+		let mut interactive_tx_constructor = funding_negotiation_context
+			.into_interactive_tx_constructor(
+				&self.context,
+				&self.funding,
+				signer_provider,
+				entropy_source,
+				holder_node_id,
+			)
+			.map_err(|err| {
+				let reason = ClosureReason::ProcessingError { err: err.reason.to_string() };
+				ChannelError::Close((err.reason.to_string(), reason, None))
+			})?;
+		let tx_msg_opt = interactive_tx_constructor.take_initiator_first_message();
+		self.interactive_tx_constructor = Some(interactive_tx_constructor);
+
+		Ok(tx_msg_opt)
+	}
+
+	/// Reverts the channel's ID to its temporary one after [`Self::accept_channel_v2`] set its
+	/// final ID, e.g. because the final ID conflicts with that of another channel.
+	pub fn unset_channel_id(&mut self) {
+		debug_assert!(self.funding.is_outbound());
+		debug_assert!(self.context.interactive_tx_signing_session.is_none());
+		self.context.channel_id = self
+			.context
+			.temporary_channel_id
+			.expect("temporary_channel_id should be set for outbound V2 channels");
 	}
 
 	/// Creates a new dual-funded channel from a remote side's request for one.
 	/// Assumes chain_hash has already been checked and corresponds with what we expect!
-	/// TODO(dual_funding): Allow contributions, pass intended amount and inputs
+	///
+	/// We contribute `our_funding_satoshis` to the channel using `our_funding_inputs`, which are
+	/// assumed to have been checked to cover the contribution plus our share of the fees at the
+	/// counterparty's funding feerate.
 	#[allow(dead_code)] // TODO(dual_funding): Remove once V2 channels is enabled.
 	#[rustfmt::skip]
 	pub fn new_inbound<ES: Deref, F: Deref, L: Deref>(
 		fee_estimator: &LowerBoundedFeeEstimator<F>, entropy_source: &ES, signer_provider: &SP,
 		holder_node_id: PublicKey, counterparty_node_id: PublicKey, our_supported_features: &ChannelTypeFeatures,
-		their_features: &InitFeatures, msg: &msgs::OpenChannelV2,
+		their_features: &InitFeatures, msg: &msgs::OpenChannelV2, our_funding_satoshis: u64,
+		our_funding_inputs: Vec<FundingTxInput>, change_script: Option<ScriptBuf>,
 		user_id: u128, config: &UserConfig, current_chain_height: u32, logger: &L,
 	) -> Result<Self, ChannelError>
 		where ES::Target: EntropySource,
 			  F::Target: FeeEstimator,
 			  L::Target: Logger,
 	{
-		// TODO(dual_funding): Take these as input once supported
-		let (our_funding_contribution, our_funding_contribution_sats) = (SignedAmount::ZERO, 0u64);
-		let our_funding_inputs = Vec::new();
+		let our_funding_contribution_sats = our_funding_satoshis;
+		let our_funding_contribution = SignedAmount::from_sat(our_funding_satoshis as i64);
 
 		let channel_value_satoshis =
 			our_funding_contribution_sats.saturating_add(msg.common_fields.funding_satoshis);
@@ -14259,30 +14936,25 @@ where
 			shared_funding_input: None,
 			our_funding_inputs: our_funding_inputs.clone(),
 			our_funding_outputs: Vec::new(),
-			change_script: None,
-		};
-		let shared_funding_output = TxOut {
-			value: Amount::from_sat(funding.get_value_satoshis()),
-			script_pubkey: funding.get_funding_redeemscript().to_p2wsh(),
+			change_script: change_script.clone(),
 		};
 
-		let interactive_tx_constructor = Some(InteractiveTxConstructor::new(
-			InteractiveTxConstructorArgs {
-				entropy_source,
-				holder_node_id,
-				counterparty_node_id,
-				channel_id: context.channel_id,
-				feerate_sat_per_kw: funding_negotiation_context.funding_feerate_sat_per_1000_weight,
-				funding_tx_locktime: funding_negotiation_context.funding_tx_locktime,
-				is_initiator: false,
-				inputs_to_contribute: our_funding_inputs,
-				shared_funding_input: None,
-				shared_funding_output: SharedOwnedOutput::new(shared_funding_output, our_funding_contribution_sats),
-				outputs_to_contribute: funding_negotiation_context.our_funding_outputs.clone(),
-			}
+		// As with the opener, our change output, if any, is only added to the copy of the context
+		// handed to the constructor.
+		let interactive_tx_constructor = Some(FundingNegotiationContext {
+			is_initiator: false,
+			our_funding_contribution,
+			funding_tx_locktime: funding_negotiation_context.funding_tx_locktime,
+			funding_feerate_sat_per_1000_weight: msg.funding_feerate_sat_per_1000_weight,
+			shared_funding_input: None,
+			our_funding_inputs,
+			our_funding_outputs: Vec::new(),
+			change_script,
+		}.into_interactive_tx_constructor(
+			&context, &funding, signer_provider, entropy_source, holder_node_id,
 		).map_err(|err| {
 			let reason = ClosureReason::ProcessingError { err: err.reason.to_string() };
-			ChannelError::Close((err.reason.to_string(), reason))
+			ChannelError::Close((err.reason.to_string(), reason, None))
 		})?);
 
 		let unfunded_context = UnfundedChannelContext {
@@ -14295,6 +14967,8 @@ where
 			funding_negotiation_context,
 			interactive_tx_constructor,
 			unfunded_context,
+			// We don't sell liquidity, so any `request_funds` from the counterparty is ignored.
+			requested_funds: None,
 		})
 	}
 
@@ -14594,6 +15268,7 @@ where
 		let mut pending_outbound_skimmed_fees: Vec<Option<u64>> = Vec::new();
 		let mut pending_outbound_blinding_points: Vec<Option<PublicKey>> = Vec::new();
 		let mut pending_outbound_held_htlc_flags: Vec<Option<()>> = Vec::new();
+		let mut pending_outbound_endorsed_flags: Vec<bool> = Vec::new();
 
 		(self.context.pending_outbound_htlcs.len() as u64).write(writer)?;
 		for htlc in self.context.pending_outbound_htlcs.iter() {
@@ -14637,6 +15312,7 @@ where
 			pending_outbound_skimmed_fees.push(htlc.skimmed_fee_msat);
 			pending_outbound_blinding_points.push(htlc.blinding_point);
 			pending_outbound_held_htlc_flags.push(htlc.hold_htlc);
+			pending_outbound_endorsed_flags.push(htlc.endorsed);
 		}
 
 		let holding_cell_htlc_update_count = self.context.holding_cell_htlc_updates.len();
@@ -14648,6 +15324,8 @@ where
 			Vec::with_capacity(holding_cell_htlc_update_count);
 		let mut holding_cell_held_htlc_flags: Vec<Option<()>> =
 			Vec::with_capacity(holding_cell_htlc_update_count);
+		let mut holding_cell_endorsed_flags: Vec<bool> =
+			Vec::with_capacity(holding_cell_htlc_update_count);
 		// Vec of (htlc_id, failure_code, sha256_of_onion)
 		let mut malformed_htlcs: Vec<(u64, u16, [u8; 32])> = Vec::new();
 		(holding_cell_htlc_update_count as u64).write(writer)?;
@@ -14662,6 +15340,7 @@ where
 					blinding_point,
 					skimmed_fee_msat,
 					hold_htlc,
+					endorsed,
 				} => {
 					0u8.write(writer)?;
 					amount_msat.write(writer)?;
@@ -14673,6 +15352,7 @@ where
 					holding_cell_skimmed_fees.push(skimmed_fee_msat);
 					holding_cell_blinding_points.push(blinding_point);
 					holding_cell_held_htlc_flags.push(hold_htlc);
+					holding_cell_endorsed_flags.push(endorsed);
 				},
 				&HTLCUpdateAwaitingACK::ClaimHTLC {
 					ref payment_preimage,
@@ -14933,6 +15613,10 @@ where
 			(69, holding_cell_held_htlc_flags, optional_vec), // Added in 0.2
 			(71, holder_commitment_point_previous_revoked, option), // Added in 0.3
 			(73, holder_commitment_point_last_revoked, option), // Added in 0.3
+			(75, self.context.funding_unconfirmed_at_height, option), // Added in 0.3
+			(77, self.context.protocol_error_history, optional_vec), // Added in 0.3
+			(79, pending_outbound_endorsed_flags, optional_vec), // Added in 0.3
+			(81, holding_cell_endorsed_flags, optional_vec), // Added in 0.3
 		});
 
 		Ok(())
@@ -15100,6 +15784,7 @@ where
 				blinding_point: None,
 				send_timestamp: None,
 				hold_htlc: None,
+				endorsed: false,
 			});
 		}
 
@@ -15119,6 +15804,7 @@ where
 					skimmed_fee_msat: None,
 					blinding_point: None,
 					hold_htlc: None,
+					endorsed: false,
 				},
 				1 => HTLCUpdateAwaitingACK::ClaimHTLC {
 					payment_preimage: Readable::read(reader)?,
@@ -15310,6 +15996,8 @@ where
 		let mut is_manual_broadcast = None;
 
 		let mut historical_scids = Some(Vec::new());
+		let mut funding_unconfirmed_at_height: Option<u32> = None;
+		let mut protocol_error_history = Some(Vec::new());
 
 		let mut interactive_tx_signing_session: Option<InteractiveTxSigningSession> = None;
 
@@ -15320,6 +16008,8 @@ where
 
 		let mut pending_outbound_held_htlc_flags_opt: Option<Vec<Option<()>>> = None;
 		let mut holding_cell_held_htlc_flags_opt: Option<Vec<Option<()>>> = None;
+		let mut pending_outbound_endorsed_flags_opt: Option<Vec<bool>> = None;
+		let mut holding_cell_endorsed_flags_opt: Option<Vec<bool>> = None;
 
 		read_tlv_fields!(reader, {
 			(0, announcement_sigs, option),
@@ -15369,6 +16059,10 @@ where
 			(69, holding_cell_held_htlc_flags_opt, optional_vec), // Added in 0.2
 			(71, holder_commitment_point_previous_revoked_opt, option), // Added in 0.3
 			(73, holder_commitment_point_last_revoked_opt, option), // Added in 0.3
+			(75, funding_unconfirmed_at_height, option), // Added in 0.3
+			(77, protocol_error_history, optional_vec), // Added in 0.3
+			(79, pending_outbound_endorsed_flags_opt, optional_vec), // Added in 0.3
+			(81, holding_cell_endorsed_flags_opt, optional_vec), // Added in 0.3
 		});
 
 		let holder_signer = signer_provider.derive_channel_signer(channel_keys_id);
@@ -15492,6 +16186,28 @@ where
 				return Err(DecodeError::InvalidValue);
 			}
 		}
+		if let Some(endorsed_htlcs) = pending_outbound_endorsed_flags_opt {
+			let mut iter = endorsed_htlcs.into_iter();
+			for htlc in pending_outbound_htlcs.iter_mut() {
+				htlc.endorsed = iter.next().ok_or(DecodeError::InvalidValue)?;
+			}
+			// We expect all endorsed HTLC flags to be consumed above
+			if iter.next().is_some() {
+				return Err(DecodeError::InvalidValue);
+			}
+		}
+		if let Some(endorsed_htlcs) = holding_cell_endorsed_flags_opt {
+			let mut iter = endorsed_htlcs.into_iter();
+			for htlc in holding_cell_htlc_updates.iter_mut() {
+				if let HTLCUpdateAwaitingACK::AddHTLC { ref mut endorsed, .. } = htlc {
+					*endorsed = iter.next().ok_or(DecodeError::InvalidValue)?;
+				}
+			}
+			// We expect all endorsed HTLC flags to be consumed above
+			if iter.next().is_some() {
+				return Err(DecodeError::InvalidValue);
+			}
+		}
 
 		if let Some(attribution_data_list) = removed_htlc_attribution_data {
 			let mut removed_htlcs = pending_inbound_htlcs.iter_mut().filter_map(|status| {
@@ -15698,6 +16414,7 @@ where
 				latest_monitor_update_id,
 
 				holder_signer: ChannelSignerType::Ecdsa(holder_signer),
+				tx_builder: signer_provider.get_tx_builder(channel_keys_id),
 				shutdown_scriptpubkey,
 				destination_script,
 
@@ -15710,6 +16427,8 @@ where
 
 				resend_order,
 
+				last_reported_lifecycle_state: None,
+
 				monitor_pending_channel_ready,
 				monitor_pending_revoke_and_ack,
 				monitor_pending_commitment_signed,
@@ -15724,6 +16443,7 @@ where
 				signer_pending_closing: false,
 				signer_pending_channel_ready: false,
 				signer_pending_stale_state_verification: None,
+				holder_state_stale: false,
 
 				pending_update_fee,
 				holding_cell_update_fee,
@@ -15769,11 +16489,14 @@ where
 
 				workaround_lnd_bug_4006: None,
 				sent_message_awaiting_response: None,
+				ticks_since_update_fee: None,
 
 				latest_inbound_scid_alias,
 				// Later in the ChannelManager deserialization phase we scan for channels and assign scid aliases if its missing
 				outbound_scid_alias,
 				historical_scids: historical_scids.unwrap(),
+				funding_unconfirmed_at_height,
+				protocol_error_history: protocol_error_history.unwrap(),
 
 				funding_tx_broadcast_safe_event_emitted: funding_tx_broadcast_safe_event_emitted
 					.unwrap_or(false),
@@ -15793,6 +16516,8 @@ where
 			holder_commitment_point,
 			pending_splice,
 			quiescent_action,
+			remote_feerate_near_limit: false,
+			holding_quiescence: false,
 		})
 	}
 }
@@ -16096,6 +16821,7 @@ mod tests {
 			blinding_point: None,
 			send_timestamp: None,
 			hold_htlc: None,
+			endorsed: false,
 		});
 
 		// Make sure when Node A calculates their local commitment transaction, none of the HTLCs pass
@@ -16551,6 +17277,7 @@ mod tests {
 			blinding_point: None,
 			send_timestamp: None,
 			hold_htlc: None,
+			endorsed: false,
 		};
 		let mut pending_outbound_htlcs = vec![dummy_outbound_output.clone(); 10];
 		for (idx, htlc) in pending_outbound_htlcs.iter_mut().enumerate() {
@@ -16577,6 +17304,7 @@ mod tests {
 			skimmed_fee_msat: None,
 			blinding_point: None,
 			hold_htlc: None,
+			endorsed: false,
 		};
 		let dummy_holding_cell_claim_htlc = |attribution_data| HTLCUpdateAwaitingACK::ClaimHTLC {
 			payment_preimage: PaymentPreimage([42; 32]),
@@ -16948,6 +17676,7 @@ mod tests {
 			blinding_point: None,
 			send_timestamp: None,
 			hold_htlc: None,
+			endorsed: false,
 		});
 
 		let payment_preimage_3 =
@@ -16963,6 +17692,7 @@ mod tests {
 			blinding_point: None,
 			send_timestamp: None,
 			hold_htlc: None,
+			endorsed: false,
 		});
 
 		let payment_preimage_4 =
@@ -17378,6 +18108,7 @@ mod tests {
 			blinding_point: None,
 			send_timestamp: None,
 			hold_htlc: None,
+			endorsed: false,
 		});
 
 		chan.context.pending_outbound_htlcs.push(OutboundHTLCOutput {
@@ -17391,6 +18122,7 @@ mod tests {
 			blinding_point: None,
 			send_timestamp: None,
 			hold_htlc: None,
+			endorsed: false,
 		});
 
 		test_commitment!("304402207d0870964530f97b62497b11153c551dca0a1e226815ef0a336651158da0f82402200f5378beee0e77759147b8a0a284decd11bfd2bc55c8fafa41c134fe996d43c8",
@@ -17632,6 +18364,7 @@ mod tests {
 				blinding_point: None,
 				send_timestamp: None,
 				hold_htlc: None,
+				endorsed: false,
 			}),
 		);
 
@@ -17695,6 +18428,7 @@ mod tests {
 				blinding_point: None,
 				send_timestamp: None,
 				hold_htlc: None,
+				endorsed: false,
 			}),
 		);
 
@@ -17777,6 +18511,7 @@ mod tests {
 					blinding_point: None,
 					send_timestamp: None,
 					hold_htlc: None,
+					endorsed: false,
 				}
 			}),
 		);
@@ -17833,6 +18568,7 @@ mod tests {
 				blinding_point: None,
 				send_timestamp: None,
 				hold_htlc: None,
+				endorsed: false,
 			}),
 		);
 
@@ -17869,6 +18605,7 @@ mod tests {
 					blinding_point: None,
 					send_timestamp: None,
 					hold_htlc: None,
+					endorsed: false,
 				},
 			),
 		);
@@ -17906,6 +18643,7 @@ mod tests {
 					blinding_point: None,
 					send_timestamp: None,
 					hold_htlc: None,
+					endorsed: false,
 				},
 			),
 		);
@@ -17943,6 +18681,7 @@ mod tests {
 					blinding_point: None,
 					send_timestamp: None,
 					hold_htlc: None,
+					endorsed: false,
 				},
 			),
 		);
@@ -18003,6 +18742,7 @@ mod tests {
 				blinding_point: None,
 				send_timestamp: None,
 				hold_htlc: None,
+				endorsed: false,
 			}),
 		);
 
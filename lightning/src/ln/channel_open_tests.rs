@@ -9,7 +9,9 @@
 
 //! Tests that test the channel open process.
 
-use crate::chain::chaininterface::LowerBoundedFeeEstimator;
+use crate::chain::chaininterface::{
+	InboundChannelFeePolicy, InboundChannelFeerateDecision, LowerBoundedFeeEstimator,
+};
 use crate::chain::channelmonitor::{self, ChannelMonitorUpdateStep};
 use crate::chain::transaction::OutPoint;
 use crate::chain::{self, ChannelMonitorUpdateStatus};
@@ -27,6 +29,7 @@ use crate::ln::msgs::{
 use crate::ln::types::ChannelId;
 use crate::ln::{functional_test_utils::*, msgs};
 use crate::sign::EntropySource;
+use crate::sync::{Arc, Mutex};
 use crate::util::config::{
 	ChannelConfigOverrides, ChannelConfigUpdate, ChannelHandshakeConfigUpdate, UserConfig,
 };
@@ -682,6 +685,90 @@ fn test_insane_zero_fee_channel_open() {
 	});
 }
 
+#[xtest(feature = "_externalize_tests")]
+pub fn test_inbound_channel_fee_policy() {
+	// Test that the commitment feerate proposed in `open_channel` is checked against the acceptor's
+	// `InboundChannelFeePolicy`, which may accept it, reject it with the range of feerates it would
+	// accept, or defer the decision to the user.
+	struct TestFeePolicy(Mutex<InboundChannelFeerateDecision>);
+	impl InboundChannelFeePolicy for Arc<TestFeePolicy> {
+		fn decide_inbound_feerate(
+			&self, _: &PublicKey, _: &ChannelTypeFeatures, _: u32, _: u32,
+		) -> InboundChannelFeerateDecision {
+			*self.0.lock().unwrap()
+		}
+	}
+
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[None, None]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+
+	let node_a_id = nodes[0].node.get_our_node_id();
+	let node_b_id = nodes[1].node.get_our_node_id();
+
+	let open_channel = || {
+		nodes[0].node.create_channel(node_b_id, 100_000, 0, 42, None, None).unwrap();
+		let open_channel = get_event_msg!(nodes[0], MessageSendEvent::SendOpenChannel, node_b_id);
+		nodes[1].node.handle_open_channel(node_a_id, &open_channel);
+		open_channel.common_fields.commitment_feerate_sat_per_1000_weight
+	};
+	let expect_rejection = |expected_err: String| {
+		assert!(nodes[1].node.get_and_clear_pending_events().is_empty());
+		let msg_events = nodes[1].node.get_and_clear_pending_msg_events();
+		assert_eq!(msg_events.len(), 1);
+		match &msg_events[0] {
+			MessageSendEvent::HandleError {
+				action: ErrorAction::SendErrorMessage { msg }, ..
+			} => assert_eq!(msg.data, expected_err),
+			_ => panic!("Unexpected event"),
+		}
+	};
+
+	// By default, feerates below our estimate are rejected, telling our counterparty which
+	// feerates we'd accept.
+	*nodes[1].fee_estimator.sat_per_kw.lock().unwrap() = 10_000;
+	let feerate = open_channel();
+	expect_rejection(format!(
+		"Unacceptable commitment feerate of {} sat/kW. We require a feerate of at least 10000 sat/kW",
+		feerate
+	));
+
+	let policy = Arc::new(TestFeePolicy(Mutex::new(InboundChannelFeerateDecision::ProposeRange {
+		min_feerate_sat_per_1000_weight: 5_000,
+		max_feerate_sat_per_1000_weight: Some(20_000),
+	})));
+	nodes[1].node.set_inbound_channel_fee_policy(Arc::clone(&policy));
+	let feerate = open_channel();
+	expect_rejection(format!(
+		"Unacceptable commitment feerate of {} sat/kW. We require a feerate between 5000 and 20000 sat/kW",
+		feerate
+	));
+
+	// If the policy defers the decision, the user is asked to accept the channel even though
+	// inbound channels are accepted automatically.
+	*policy.0.lock().unwrap() = InboundChannelFeerateDecision::Defer;
+	open_channel();
+	let events = nodes[1].node.get_and_clear_pending_events();
+	assert_eq!(events.len(), 1);
+	match &events[0] {
+		Event::OpenChannelRequest { temporary_channel_id, .. } => {
+			nodes[1]
+				.node
+				.accept_inbound_channel(temporary_channel_id, &node_a_id, 0, None)
+				.unwrap();
+		},
+		_ => panic!("Unexpected event"),
+	}
+	get_event_msg!(nodes[1], MessageSendEvent::SendAcceptChannel, node_a_id);
+
+	// Finally, the policy may accept a feerate below our estimate outright.
+	*policy.0.lock().unwrap() = InboundChannelFeerateDecision::Accept;
+	open_channel();
+	assert!(nodes[1].node.get_and_clear_pending_events().is_empty());
+	get_event_msg!(nodes[1], MessageSendEvent::SendAcceptChannel, node_a_id);
+}
+
 #[xtest(feature = "_externalize_tests")]
 pub fn test_funding_exceeds_no_wumbo_limit() {
 	// Test that if a peer does not support wumbo channels, we'll refuse to open a wumbo channel to
@@ -1006,7 +1093,7 @@ pub fn test_user_configurable_csv_delay() {
 		/*is_0conf=*/ false,
 	) {
 		match error {
-			ChannelError::Close((err, _)) => {
+			ChannelError::Close((err, _, _)) => {
 				let regex = regex::Regex::new(
 					r"Configured with an unreasonable our_to_self_delay \(\d+\) putting user funds at risks",
 				)
@@ -1066,7 +1153,7 @@ pub fn test_user_configurable_csv_delay() {
 		/*is_0conf=*/ false,
 	) {
 		match error {
-			ChannelError::Close((err, _)) => {
+			ChannelError::Close((err, _, _)) => {
 				let regex = regex::Regex::new(r"They wanted our payments to be delayed by a needlessly long period\. Upper limit: \d+\. Actual: \d+").unwrap();
 				assert!(regex.is_match(err.as_str()));
 			},
@@ -1120,6 +1207,11 @@ pub fn test_manually_accept_inbound_channel_request() {
 			max_dust_htlc_exposure_msat: None,
 			force_close_avoidance_max_fee_satoshis: None,
 			accept_underpaying_htlcs: None,
+			endorsed_htlc_slots_percentage: None,
+			endorsed_liquidity_percentage: None,
+			min_update_fee_interval_ticks: None,
+			max_update_fee_step_percent: None,
+			max_update_feerate_sat_per_1000_weight: None,
 		}),
 	};
 	let events = nodes[1].node.get_and_clear_pending_events();
@@ -9,6 +9,7 @@
 
 //! Information about the state of a channel.
 
+use alloc::string::String;
 use alloc::vec::Vec;
 
 use bitcoin::secp256k1::PublicKey;
@@ -20,9 +21,12 @@ use crate::ln::types::ChannelId;
 use crate::sign::SignerProvider;
 use crate::types::features::{ChannelTypeFeatures, InitFeatures};
 use crate::types::payment::PaymentHash;
+use crate::types::string::UntrustedString;
 use crate::util::config::ChannelConfig;
+use crate::util::export::{features_hex, json_array, versioned_bytes, JsonObject};
 
 use core::ops::Deref;
+use core::time::Duration;
 
 /// Exposes the state of pending inbound HTLCs.
 ///
@@ -269,6 +273,87 @@ impl_writeable_tlv_based!(ChannelCounterparty, {
 	(11, outbound_htlc_maximum_msat, option),
 });
 
+/// The maximum number of [`ProtocolErrorRecord`]s we keep for any one channel. Once reached, the
+/// oldest record is dropped each time a new one is added.
+pub const MAX_PROTOCOL_ERRORS_PER_CHANNEL: usize = 16;
+
+/// Whether a [`ProtocolErrorRecord`] describes a message we sent or one we received.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum ProtocolErrorDirection {
+	/// We sent the message to our counterparty.
+	Sent,
+	/// Our counterparty sent the message to us.
+	Received,
+}
+
+impl_writeable_tlv_based_enum!(ProtocolErrorDirection,
+	(0, Sent) => {},
+	(2, Received) => {},
+);
+
+/// The type of message described by a [`ProtocolErrorRecord`].
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum ProtocolErrorKind {
+	/// A `warning` message, which does not close the channel.
+	Warning,
+	/// An `error` message, which generally results in the channel being force-closed.
+	Error,
+}
+
+impl_writeable_tlv_based_enum!(ProtocolErrorKind,
+	(0, Warning) => {},
+	(2, Error) => {},
+);
+
+/// A `warning` or `error` message exchanged with our counterparty regarding a channel, as found in
+/// [`ChannelDetails::protocol_error_history`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProtocolErrorRecord {
+	/// Whether we sent or received the message.
+	pub direction: ProtocolErrorDirection,
+	/// Whether the message was a `warning` or an `error`.
+	pub kind: ProtocolErrorKind,
+	/// The time at which the message was sent or received, as a duration since the Unix epoch.
+	///
+	/// In `no-std` builds this is based on the highest block timestamp we've seen rather than the
+	/// current time.
+	pub timestamp: Duration,
+	/// The contents of the message.
+	///
+	/// If the message was received, this is provided by our counterparty and must not be trusted.
+	pub message: UntrustedString,
+}
+
+impl_writeable_tlv_based!(ProtocolErrorRecord, {
+	(0, direction, required),
+	(2, kind, required),
+	(4, timestamp, required),
+	(6, message, required),
+});
+
+impl ProtocolErrorRecord {
+	fn to_json(&self) -> String {
+		let mut obj = JsonObject::nested();
+		obj.string(
+			"direction",
+			match self.direction {
+				ProtocolErrorDirection::Sent => "sent",
+				ProtocolErrorDirection::Received => "received",
+			},
+		);
+		obj.string(
+			"kind",
+			match self.kind {
+				ProtocolErrorKind::Warning => "warning",
+				ProtocolErrorKind::Error => "error",
+			},
+		);
+		obj.number("timestamp", self.timestamp.as_secs());
+		obj.string("message", &self.message.0);
+		obj.finish()
+	}
+}
+
 /// Details of a channel, as returned by [`ChannelManager::list_channels`] and [`ChannelManager::list_usable_channels`]
 ///
 /// Balances of a channel are available through [`ChainMonitor::get_claimable_balances`] and
@@ -308,6 +393,23 @@ pub struct ChannelDetails {
 	/// state until the splice transaction reaches sufficient confirmations to be locked (and we
 	/// exchange `splice_locked` messages with our peer).
 	pub channel_type: Option<ChannelTypeFeatures>,
+	/// The format of this channel's commitment transactions, as determined by its
+	/// [`channel_type`].
+	///
+	/// `None` until negotiation completes and the channel type is finalized, or if these details
+	/// were serialized by a version of LDK prior to 0.3.
+	///
+	/// [`channel_type`]: Self::channel_type
+	pub commitment_format: Option<CommitmentFormat>,
+	/// The value of the pay-to-anchor (P2A) output on our next commitment transaction, for channels
+	/// using [`CommitmentFormat::ZeroFeeCommitments`].
+	///
+	/// As the P2A anchor collects the value of all outputs trimmed to dust (up to 240 sats), this
+	/// is the value we'd be unable to claim back if the commitment transaction were to confirm.
+	///
+	/// `None` for other commitment formats, or if these details were serialized by a version of
+	/// LDK prior to 0.3.
+	pub p2a_anchor_value_satoshis: Option<u64>,
 	/// The position of the funding transaction in the chain. None if the funding transaction has
 	/// not yet been confirmed and the channel fully opened.
 	///
@@ -484,6 +586,17 @@ pub struct ChannelDetails {
 	///
 	/// This field will be `None` for objects serialized with LDK versions prior to 0.2.0.
 	pub funding_redeem_script: Option<bitcoin::ScriptBuf>,
+	/// The most recent `warning` and `error` messages we exchanged with our counterparty regarding
+	/// this channel, oldest first.
+	///
+	/// At most [`MAX_PROTOCOL_ERRORS_PER_CHANNEL`] records are kept. They are persisted along with
+	/// the channel, but are dropped once the channel is closed. The error which led to a channel
+	/// being closed is instead available via the [`ClosureReason`].
+	///
+	/// This field is empty for objects serialized with LDK versions prior to 0.3.
+	///
+	/// [`ClosureReason`]: crate::events::ClosureReason
+	pub protocol_error_history: Vec<ProtocolErrorRecord>,
 }
 
 impl ChannelDetails {
@@ -524,6 +637,86 @@ impl ChannelDetails {
 		}
 	}
 
+	/// Exports these details as a canonical, schema-versioned JSON object.
+	///
+	/// See the [`export`] module documentation for the stability guarantees and encoding rules.
+	///
+	/// [`export`]: crate::util::export
+	pub fn to_canonical_json(&self) -> String {
+		let mut obj = JsonObject::new();
+		obj.string("channel_id", self.channel_id);
+		obj.string("counterparty_node_id", self.counterparty.node_id);
+		obj.string("counterparty_features", features_hex(self.counterparty.features.le_flags()));
+		obj.number(
+			"counterparty_unspendable_punishment_reserve",
+			self.counterparty.unspendable_punishment_reserve,
+		);
+		obj.opt_string("funding_txo", self.funding_txo);
+		obj.opt_string(
+			"channel_type",
+			self.channel_type.as_ref().map(|t| features_hex(t.le_flags())),
+		);
+		obj.opt_string(
+			"commitment_format",
+			self.commitment_format.map(|format| match format {
+				CommitmentFormat::Legacy => "legacy",
+				CommitmentFormat::StaticRemoteKey => "static_remote_key",
+				CommitmentFormat::AnchorsNonzeroFeeHtlcTx => "anchors_nonzero_fee_htlc_tx",
+				CommitmentFormat::AnchorsZeroFeeHtlcTx => "anchors_zero_fee_htlc_tx",
+				CommitmentFormat::ZeroFeeCommitments => "zero_fee_commitments",
+			}),
+		);
+		obj.opt_number("p2a_anchor_value_satoshis", self.p2a_anchor_value_satoshis);
+		obj.opt_number("short_channel_id", self.short_channel_id);
+		obj.opt_number("outbound_scid_alias", self.outbound_scid_alias);
+		obj.opt_number("inbound_scid_alias", self.inbound_scid_alias);
+		obj.number("channel_value_satoshis", self.channel_value_satoshis);
+		obj.opt_number("unspendable_punishment_reserve", self.unspendable_punishment_reserve);
+		obj.string("user_channel_id", self.user_channel_id);
+		obj.opt_number("feerate_sat_per_1000_weight", self.feerate_sat_per_1000_weight);
+		obj.number("outbound_capacity_msat", self.outbound_capacity_msat);
+		obj.number("next_outbound_htlc_limit_msat", self.next_outbound_htlc_limit_msat);
+		obj.number("next_outbound_htlc_minimum_msat", self.next_outbound_htlc_minimum_msat);
+		obj.number("inbound_capacity_msat", self.inbound_capacity_msat);
+		obj.opt_number("confirmations_required", self.confirmations_required);
+		obj.opt_number("confirmations", self.confirmations);
+		obj.opt_number("force_close_spend_delay", self.force_close_spend_delay);
+		obj.boolean("is_outbound", self.is_outbound);
+		obj.boolean("is_channel_ready", self.is_channel_ready);
+		obj.boolean("is_usable", self.is_usable);
+		obj.boolean("is_announced", self.is_announced);
+		obj.opt_string(
+			"channel_shutdown_state",
+			self.channel_shutdown_state.map(|state| match state {
+				ChannelShutdownState::NotShuttingDown => "not_shutting_down",
+				ChannelShutdownState::ShutdownInitiated => "shutdown_initiated",
+				ChannelShutdownState::ResolvingHTLCs => "resolving_htlcs",
+				ChannelShutdownState::NegotiatingClosingFee => "negotiating_closing_fee",
+				ChannelShutdownState::ShutdownComplete => "shutdown_complete",
+			}),
+		);
+		obj.opt_number("inbound_htlc_minimum_msat", self.inbound_htlc_minimum_msat);
+		obj.opt_number("inbound_htlc_maximum_msat", self.inbound_htlc_maximum_msat);
+		obj.number("pending_inbound_htlc_count", self.pending_inbound_htlcs.len() as u64);
+		obj.number("pending_outbound_htlc_count", self.pending_outbound_htlcs.len() as u64);
+		obj.raw(
+			"protocol_error_history",
+			json_array(self.protocol_error_history.iter().map(ProtocolErrorRecord::to_json)),
+		);
+		obj.finish()
+	}
+
+	/// Exports these details in a schema-versioned binary format, i.e., the
+	/// [`EXPORT_SCHEMA_VERSION`] followed by their regular serialization.
+	///
+	/// See the [`export`] module documentation for the stability guarantees.
+	///
+	/// [`EXPORT_SCHEMA_VERSION`]: crate::util::export::EXPORT_SCHEMA_VERSION
+	/// [`export`]: crate::util::export
+	pub fn to_canonical_bytes(&self) -> Vec<u8> {
+		versioned_bytes(self)
+	}
+
 	pub(super) fn from_channel<SP: Deref, F: Deref>(
 		channel: &Channel<SP>, best_block_height: u32, latest_features: InitFeatures,
 		fee_estimator: &LowerBoundedFeeEstimator<F>,
@@ -568,6 +761,12 @@ impl ChannelDetails {
 			} else {
 				None
 			},
+			commitment_format: if context.have_received_message() {
+				Some(CommitmentFormat::from_channel_type(funding.get_channel_type()))
+			} else {
+				None
+			},
+			p2a_anchor_value_satoshis: context.get_p2a_anchor_value_satoshis(funding),
 			short_channel_id: funding.get_short_channel_id(),
 			outbound_scid_alias: if context.is_usable() {
 				Some(context.outbound_scid_alias())
@@ -596,6 +795,7 @@ impl ChannelDetails {
 			channel_shutdown_state: Some(context.shutdown_state()),
 			pending_inbound_htlcs: context.get_pending_inbound_htlc_details(funding),
 			pending_outbound_htlcs: context.get_pending_outbound_htlc_details(funding),
+			protocol_error_history: context.protocol_error_history().to_vec(),
 		}
 	}
 }
@@ -636,11 +836,81 @@ impl_writeable_tlv_based!(ChannelDetails, {
 	(43, pending_inbound_htlcs, optional_vec),
 	(45, pending_outbound_htlcs, optional_vec),
 	(47, funding_redeem_script, option),
+	(49, commitment_format, option),
+	(51, p2a_anchor_value_satoshis, option),
+	(53, protocol_error_history, optional_vec),
 	(_unused, user_channel_id, (static_value,
 		_user_channel_id_low.unwrap_or(0) as u128 | ((_user_channel_id_high.unwrap_or(0) as u128) << 64)
 	)),
 });
 
+/// A read-only snapshot of our channels, as returned by [`ChannelManager::channel_snapshot`].
+///
+/// [`ChannelManager::channel_snapshot`]: crate::ln::channelmanager::ChannelManager::channel_snapshot
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChannelSnapshot {
+	/// The details of each of our channels as of when the snapshot was taken, in random order.
+	pub channels: Vec<ChannelDetails>,
+	/// The height of the best block we knew of when the snapshot was taken.
+	pub best_block_height: u32,
+}
+
+impl ChannelSnapshot {
+	/// The sum of [`ChannelDetails::outbound_capacity_msat`] across all channels.
+	pub fn total_outbound_capacity_msat(&self) -> u64 {
+		self.channels.iter().map(|chan| chan.outbound_capacity_msat).sum()
+	}
+
+	/// The sum of [`ChannelDetails::inbound_capacity_msat`] across all channels.
+	pub fn total_inbound_capacity_msat(&self) -> u64 {
+		self.channels.iter().map(|chan| chan.inbound_capacity_msat).sum()
+	}
+}
+
+/// The format of a channel's commitment transactions, as determined by its negotiated
+/// [`ChannelTypeFeatures`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CommitmentFormat {
+	/// The original commitment format, in which the counterparty's output pays to a key which is
+	/// tweaked on each commitment.
+	Legacy,
+	/// `option_static_remotekey`, in which the counterparty's output pays to a static key.
+	StaticRemoteKey,
+	/// `option_anchors_nonzero_fee_htlc_tx`, the initial version of anchor outputs, in which each
+	/// party has a 330-sat anchor output and HTLC transactions pay their own fees.
+	AnchorsNonzeroFeeHtlcTx,
+	/// `option_anchors_zero_fee_htlc_tx`, in which each party has a 330-sat anchor output used to
+	/// bump the commitment transaction's fee, and HTLC transactions are signed with zero fee.
+	AnchorsZeroFeeHtlcTx,
+	/// `option_zero_fee_commitments`, in which commitment transactions pay zero fee and have a
+	/// single pay-to-anchor (P2A) output which either party may use to bump the fee.
+	ZeroFeeCommitments,
+}
+
+impl CommitmentFormat {
+	pub(super) fn from_channel_type(channel_type: &ChannelTypeFeatures) -> Self {
+		if channel_type.supports_anchor_zero_fee_commitments() {
+			CommitmentFormat::ZeroFeeCommitments
+		} else if channel_type.supports_anchors_zero_fee_htlc_tx() {
+			CommitmentFormat::AnchorsZeroFeeHtlcTx
+		} else if channel_type.supports_anchors_nonzero_fee_htlc_tx() {
+			CommitmentFormat::AnchorsNonzeroFeeHtlcTx
+		} else if channel_type.supports_static_remote_key() {
+			CommitmentFormat::StaticRemoteKey
+		} else {
+			CommitmentFormat::Legacy
+		}
+	}
+}
+
+impl_writeable_tlv_based_enum!(CommitmentFormat,
+	(0, Legacy) => {},
+	(2, StaticRemoteKey) => {},
+	(4, AnchorsNonzeroFeeHtlcTx) => {},
+	(6, AnchorsZeroFeeHtlcTx) => {},
+	(8, ZeroFeeCommitments) => {},
+);
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 /// Further information on the details of the channel shutdown.
 /// Upon channels being forced closed (i.e. commitment transaction confirmation detected
@@ -671,11 +941,119 @@ impl_writeable_tlv_based_enum!(ChannelShutdownState,
 	(8, ShutdownComplete) => {},
 );
 
+/// The phase of a channel's lifecycle, i.e., a coarse view of the channel state machine.
+///
+/// A channel may be closed, at which point it is dropped, from any phase. This is not described
+/// by [`ChannelLifecycleState::allowed_transitions`] and is instead indicated by an
+/// [`Event::ChannelClosed`].
+///
+/// [`Event::ChannelClosed`]: crate::events::Event::ChannelClosed
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum ChannelLifecycleState {
+	/// We are negotiating the parameters of the channel and, for dual-funded channels, its funding
+	/// transaction.
+	NegotiatingFunding,
+	/// The funding transaction has been negotiated and the initial commitment transaction
+	/// signatures are being exchanged.
+	///
+	/// Inbound channels which are not dual-funded skip this phase.
+	FundingNegotiated,
+	/// The initial commitment transactions have been signed and we are waiting for the funding
+	/// transaction to confirm and `channel_ready` to be exchanged.
+	AwaitingChannelReady,
+	/// The channel is operational.
+	ChannelReady,
+	/// A `shutdown` message has been sent or received and the channel is being closed
+	/// cooperatively. See [`ChannelShutdownState`] for more details.
+	ShuttingDown,
+	/// The channel has been closed cooperatively and is about to be dropped.
+	ShutdownComplete,
+}
+
+impl ChannelLifecycleState {
+	/// Returns the phases a channel may move to directly from this one.
+	pub fn allowed_transitions(&self) -> &'static [ChannelLifecycleState] {
+		match self {
+			Self::NegotiatingFunding => &[Self::FundingNegotiated, Self::AwaitingChannelReady],
+			Self::FundingNegotiated => &[Self::AwaitingChannelReady],
+			Self::AwaitingChannelReady => &[Self::ChannelReady, Self::ShuttingDown],
+			Self::ChannelReady => &[Self::ShuttingDown],
+			Self::ShuttingDown => &[Self::ShutdownComplete],
+			Self::ShutdownComplete => &[],
+		}
+	}
+}
+
+/// A message from the channel counterparty which the channel state machine is waiting on to make
+/// progress.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum ExpectedChannelMessage {
+	/// An `accept_channel` or `accept_channel2` message.
+	AcceptChannel,
+	/// A `funding_created` message.
+	FundingCreated,
+	/// A `funding_signed` message.
+	FundingSigned,
+	/// Any of the interactive transaction construction messages, e.g. `tx_add_input` or
+	/// `tx_complete`.
+	InteractiveTxConstruction,
+	/// A `commitment_signed` message.
+	CommitmentSigned,
+	/// A `tx_signatures` message.
+	TxSignatures,
+	/// A `channel_ready` message.
+	ChannelReady,
+	/// A `channel_reestablish` message, once the peer has reconnected.
+	ChannelReestablish,
+	/// A `revoke_and_ack` message.
+	RevokeAndAck,
+	/// An `stfu` message.
+	Stfu,
+	/// A `shutdown` message.
+	Shutdown,
+	/// A `closing_signed` message.
+	ClosingSigned,
+}
+
+/// The position of a channel in the channel state machine, as returned by
+/// [`ChannelManager::channel_lifecycle_info`].
+///
+/// This is intended for introspection, e.g. by model-based testing or external verification
+/// tooling, and describes the channel as seen at the time it was generated.
+///
+/// [`ChannelManager::channel_lifecycle_info`]: crate::ln::channelmanager::ChannelManager::channel_lifecycle_info
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChannelLifecycleInfo {
+	/// The current phase of the channel.
+	pub state: ChannelLifecycleState,
+	/// The phases the channel may move to next, see [`ChannelLifecycleState::allowed_transitions`].
+	pub allowed_transitions: Vec<ChannelLifecycleState>,
+	/// The messages from the counterparty the channel is waiting on to make progress.
+	///
+	/// Note that the counterparty may send other messages where allowed by the protocol, e.g.
+	/// `update_add_htlc` or `shutdown` while the channel is operational, and that this may be
+	/// empty if we are the ones expected to make progress.
+	pub expected_messages: Vec<ExpectedChannelMessage>,
+	/// Whether the counterparty is considered disconnected and a `channel_reestablish` is
+	/// required before the channel can make progress.
+	pub is_peer_disconnected: bool,
+	/// Whether a [`ChannelMonitorUpdate`] is being persisted asynchronously, which blocks the
+	/// channel from sending messages until it completes.
+	///
+	/// [`ChannelMonitorUpdate`]: crate::chain::channelmonitor::ChannelMonitorUpdate
+	pub is_monitor_update_in_progress: bool,
+	/// Whether the channel is quiescent, i.e. no updates may be made until quiescence ends.
+	pub is_quiescent: bool,
+}
+
 #[cfg(test)]
 mod tests {
 	use bitcoin::{hashes::Hash as _, secp256k1::PublicKey};
 	use lightning_types::features::Features;
 	use types::payment::PaymentHash;
+	use types::string::UntrustedString;
+
+	use core::time::Duration;
 
 	use crate::{
 		chain::transaction::OutPoint,
@@ -693,7 +1071,10 @@ mod tests {
 		},
 	};
 
-	use super::{ChannelCounterparty, ChannelDetails, ChannelShutdownState};
+	use super::{
+		ChannelCounterparty, ChannelDetails, ChannelShutdownState, CommitmentFormat,
+		ProtocolErrorDirection, ProtocolErrorKind, ProtocolErrorRecord,
+	};
 
 	#[test]
 	fn test_channel_details_serialization() {
@@ -717,6 +1098,8 @@ mod tests {
 				&PublicKey::from_slice(&[2; 33]).unwrap(),
 			)),
 			channel_type: None,
+			commitment_format: Some(CommitmentFormat::ZeroFeeCommitments),
+			p2a_anchor_value_satoshis: Some(120),
 			short_channel_id: None,
 			outbound_scid_alias: None,
 			inbound_scid_alias: None,
@@ -756,11 +1139,37 @@ mod tests {
 				skimmed_fee_msat: Some(42),
 				is_dust: false,
 			}],
+			protocol_error_history: vec![ProtocolErrorRecord {
+				direction: ProtocolErrorDirection::Received,
+				kind: ProtocolErrorKind::Warning,
+				timestamp: Duration::from_secs(1_700_000_000),
+				message: UntrustedString("feerate-too-low".to_owned()),
+			}],
 		};
 		let mut buffer = Vec::new();
 		channel_details.write(&mut buffer).unwrap();
 		let deser_channel_details = ChannelDetails::read(&mut buffer.as_slice()).unwrap();
 
 		assert_eq!(deser_channel_details, channel_details);
+
+		let canonical_bytes = channel_details.to_canonical_bytes();
+		assert_eq!(&canonical_bytes[..2], &[0, 1]);
+		assert_eq!(&canonical_bytes[2..], &buffer[..]);
+
+		let json = channel_details.to_canonical_json();
+		assert!(json.starts_with(
+			"{\"channel_id\":\"0000000000000000000000000000000000000000000000000000000000000000\","
+		));
+		assert!(json.contains(",\"channel_shutdown_state\":\"not_shutting_down\","));
+		assert!(json.contains(",\"channel_type\":null,"));
+		assert!(json.contains(",\"commitment_format\":\"zero_fee_commitments\","));
+		assert!(json.contains(",\"p2a_anchor_value_satoshis\":120,"));
+		assert!(json.contains(",\"funding_txo\":\"0000000000000000000000000000000000000000000000000000000000000000:1\","));
+		assert!(json.contains(",\"pending_inbound_htlc_count\":1,"));
+		assert!(json.contains(
+			",\"protocol_error_history\":[{\"direction\":\"received\",\"kind\":\"warning\",\"message\":\"feerate-too-low\",\"timestamp\":1700000000}],"
+		));
+		assert!(json.contains(",\"user_channel_id\":\"18446744073709551616\"}"));
+		assert!(!json.contains(' '));
 	}
 }
@@ -30,7 +30,7 @@ use bitcoin::hashes::{Hash, HashEngine, HmacEngine};
 
 use bitcoin::secp256k1::Secp256k1;
 use bitcoin::secp256k1::{PublicKey, SecretKey};
-use bitcoin::{secp256k1, Sequence, SignedAmount};
+use bitcoin::{secp256k1, Amount, ScriptBuf, Sequence, SignedAmount, TxOut};
 
 use crate::blinded_path::message::{
 	AsyncPaymentsContext, BlindedMessagePath, MessageForwardNode, OffersContext,
@@ -39,7 +39,10 @@ use crate::blinded_path::payment::{AsyncBolt12OfferContext, Bolt12OfferContext,
 use crate::blinded_path::NodeIdLookUp;
 use crate::chain;
 use crate::chain::chaininterface::{
-	BroadcasterInterface, ConfirmationTarget, FeeEstimator, LowerBoundedFeeEstimator,
+	BroadcastContext, BroadcastType, BroadcasterInterface, ConfirmationTarget,
+	DefaultInboundChannelFeePolicy, DefaultUpdateFeePolicy, FeeEstimator, InboundChannelFeePolicy,
+	InboundChannelFeerateDecision, LowerBoundedFeeEstimator, UpdateFeePolicy,
+	FEERATE_FLOOR_SATS_PER_KW,
 };
 use crate::chain::channelmonitor::{
 	Balance, ChannelMonitor, ChannelMonitorUpdate, ChannelMonitorUpdateStep, MonitorEvent,
@@ -53,8 +56,11 @@ use crate::events::{
 	InboundChannelFunds, PaymentFailureReason, ReplayEvent,
 };
 use crate::events::{FundingInfo, PaidBolt12Invoice};
+use crate::ln::batch_payment::{
+	BatchPayment, BatchPaymentError, BatchPaymentParameters, BatchedBolt11Invoice,
+	BatchedPaymentStatus,
+};
 use crate::ln::chan_utils::selected_commitment_sat_per_1000_weight;
-#[cfg(any(test, fuzzing))]
 use crate::ln::channel::QuiescentAction;
 use crate::ln::channel::{
 	self, hold_time_since, Channel, ChannelError, ChannelUpdateStatus, DisconnectResult,
@@ -62,10 +68,23 @@ use crate::ln::channel::{
 	ReconnectionMsg, ShutdownResult, SpliceFundingFailed, StfuResponse, UpdateFulfillCommitFetch,
 	WithChannelContext,
 };
-use crate::ln::channel_state::ChannelDetails;
-use crate::ln::funding::SpliceContribution;
+use crate::ln::channel_state::{
+	ChannelDetails, ChannelLifecycleInfo, ChannelSnapshot, ProtocolErrorDirection,
+	ProtocolErrorKind, ProtocolErrorRecord,
+};
+use crate::ln::closure_scheduler::{
+	CloseDeadline, ClosureScheduler, ScheduledClosure, ScheduledClosureAction,
+	ScheduledClosureStatus,
+};
+use crate::ln::force_close_observer::{
+	ForceCloseDecision, ForceCloseObserver, ForceCloseReason, FORCE_CLOSE_VETO_TIMEOUT_BLOCKS,
+};
+use crate::ln::forwarding_policy::{ForwardCandidate, ForwardingDecision, ForwardingPolicy};
+use crate::ln::funding::{FundingContribution, SpliceContribution};
 use crate::ln::inbound_payment;
 use crate::ln::interactivetxs::InteractiveTxMessageSend;
+use crate::ln::liquidity_ads::{LiquidityAdRate, RequestFunds};
+use crate::ln::local_reputation::{LocalReputation, LocalReputationTracker};
 use crate::ln::msgs;
 use crate::ln::msgs::{
 	BaseMessageHandler, ChannelMessageHandler, CommitmentUpdate, DecodeError, LightningError,
@@ -85,9 +104,15 @@ use crate::ln::our_peer_storage::{EncryptedOurPeerStorage, PeerStorageMonitorHol
 #[cfg(test)]
 use crate::ln::outbound_payment;
 use crate::ln::outbound_payment::{
-	OutboundPayments, PendingOutboundPayment, RetryableInvoiceRequest, SendAlongPathArgs,
-	StaleExpiration,
+	bolt11_payment_amount, OutboundPayments, PendingOutboundPayment, RetryableInvoiceRequest,
+	SendAlongPathArgs, StaleExpiration,
 };
+use crate::ln::peer_misbehavior::{
+	MessageQuarantine, MisbehaviorIncident, MisbehaviorKind, MisbehaviorLedger, PeerReputation,
+	QuarantinedPeer,
+};
+use crate::ln::static_backup::{StaticChannelBackup, StaticChannelBackupEntry};
+use crate::ln::surge_pricing::{SurgePricingConfig, SurgePricingState};
 use crate::ln::types::ChannelId;
 use crate::offers::async_receive_offer_cache::AsyncReceiveOfferCache;
 use crate::offers::flow::{HeldHtlcReplyPath, InvreqResponseInstructions, OffersMessageFlow};
@@ -95,9 +120,10 @@ use crate::offers::invoice::{Bolt12Invoice, UnsignedBolt12Invoice};
 use crate::offers::invoice_error::InvoiceError;
 use crate::offers::invoice_request::{InvoiceRequest, InvoiceRequestVerifiedFromOffer};
 use crate::offers::nonce::Nonce;
-use crate::offers::offer::{Offer, OfferFromHrn};
+use crate::offers::offer::{Offer, OfferFromHrn, OfferId};
 use crate::offers::parse::Bolt12SemanticError;
-use crate::offers::refund::Refund;
+use crate::offers::recurrence::PayerRecurrence;
+use crate::offers::refund::{Refund, RefundDecision, RefundHandler};
 use crate::offers::static_invoice::StaticInvoice;
 use crate::onion_message::async_payments::{
 	AsyncPaymentsMessage, AsyncPaymentsMessageHandler, HeldHtlcAvailable, OfferPaths,
@@ -122,6 +148,7 @@ use crate::types::features::{
 };
 use crate::types::payment::{PaymentHash, PaymentPreimage, PaymentSecret};
 use crate::types::string::UntrustedString;
+use crate::util::async_poll::OrderedConcurrentPoller;
 use crate::util::config::{ChannelConfig, ChannelConfigOverrides, ChannelConfigUpdate, UserConfig};
 use crate::util::errors::APIError;
 use crate::util::logger::{Level, Logger, WithContext};
@@ -228,6 +255,11 @@ pub enum PendingHTLCRouting {
 		/// Whether this HTLC should be held by our node until we receive a corresponding
 		/// [`ReleaseHeldHtlc`] onion message.
 		hold_htlc: Option<()>,
+		/// Whether the previous hop endorsed this HTLC, see [`msgs::UpdateAddHTLC::endorsed`].
+		///
+		/// This field was added in LDK 0.3 and will be `false` for objects written by prior
+		/// versions.
+		incoming_endorsed: bool,
 	},
 	/// An HTLC which should be forwarded on to another Trampoline node.
 	TrampolineForward {
@@ -459,6 +491,10 @@ pub(super) struct PendingAddHTLCInfo {
 	prev_channel_id: ChannelId,
 	prev_funding_outpoint: OutPoint,
 	prev_user_channel_id: u128,
+
+	// The block height at which we'll fail the HTLC backwards if it is still pending interception,
+	// as set via `ChannelManager::hold_intercepted_htlc`.
+	intercept_hold_until_height: Option<u32>,
 }
 
 impl PendingAddHTLCInfo {
@@ -471,6 +507,9 @@ impl PendingAddHTLCInfo {
 			PendingHTLCRouting::Receive { trampoline_shared_secret, .. } => {
 				trampoline_shared_secret
 			},
+			PendingHTLCRouting::TrampolineForward { incoming_shared_secret, .. } => {
+				Some(incoming_shared_secret)
+			},
 			_ => None,
 		};
 
@@ -677,6 +716,50 @@ impl Readable for InterceptId {
 	}
 }
 
+/// A stable identifier for an HTLC which we received and forwarded (or attempted to forward),
+/// derived from the channel over which it was received and its HTLC ID on that channel.
+///
+/// This is included in [`Event::HTLCIntercepted`], [`Event::PaymentForwarded`] (including for
+/// HTLCs claimed on-chain), and [`Event::HTLCHandlingFailed`], allowing a single HTLC to be traced
+/// from interception through to its resolution, even across restarts.
+///
+/// This is not exported to bindings users as we just use [u8; 32] directly
+#[derive(Hash, Copy, Clone, PartialEq, Eq)]
+pub struct HTLCCorrelationId(pub [u8; 32]);
+
+impl HTLCCorrelationId {
+	pub(crate) fn from_prev_hop(prev_channel_id: &ChannelId, prev_htlc_id: u64) -> Self {
+		let mut sha = Sha256::engine();
+		sha.input(&prev_channel_id.0);
+		sha.input(&prev_htlc_id.to_be_bytes());
+		Self(Sha256::from_engine(sha).to_byte_array())
+	}
+}
+
+impl Borrow<[u8]> for HTLCCorrelationId {
+	fn borrow(&self) -> &[u8] {
+		&self.0[..]
+	}
+}
+impl_fmt_traits! {
+	impl fmt_traits for HTLCCorrelationId {
+		const LENGTH: usize = 32;
+	}
+}
+
+impl Writeable for HTLCCorrelationId {
+	fn write<W: Writer>(&self, w: &mut W) -> Result<(), io::Error> {
+		self.0.write(w)
+	}
+}
+
+impl Readable for HTLCCorrelationId {
+	fn read<R: Read>(r: &mut R) -> Result<Self, DecodeError> {
+		let buf: [u8; 32] = Readable::read(r)?;
+		Ok(HTLCCorrelationId(buf))
+	}
+}
+
 /// Optional arguments to [`ChannelManager::pay_for_offer`]
 #[cfg_attr(
 	feature = "dnssec",
@@ -914,6 +997,8 @@ struct MsgHandleErrInternal {
 	closes_channel: bool,
 	shutdown_finish: Option<(ShutdownResult, Option<(msgs::ChannelUpdate, NodeId, NodeId)>)>,
 	tx_abort: Option<msgs::TxAbort>,
+	/// The kind of misbehavior by our peer which caused the error, if known.
+	misbehavior: Option<MisbehaviorKind>,
 }
 impl MsgHandleErrInternal {
 	fn send_err_msg_no_close(err: String, channel_id: ChannelId) -> Self {
@@ -927,11 +1012,18 @@ impl MsgHandleErrInternal {
 			closes_channel: false,
 			shutdown_finish: None,
 			tx_abort: None,
+			misbehavior: None,
 		}
 	}
 
 	fn from_no_close(err: msgs::LightningError) -> Self {
-		Self { err, closes_channel: false, shutdown_finish: None, tx_abort: None }
+		Self {
+			err,
+			closes_channel: false,
+			shutdown_finish: None,
+			tx_abort: None,
+			misbehavior: None,
+		}
 	}
 
 	fn from_finish_shutdown(
@@ -952,6 +1044,7 @@ impl MsgHandleErrInternal {
 			closes_channel: true,
 			shutdown_finish: Some((shutdown_res, channel_update)),
 			tx_abort: None,
+			misbehavior: None,
 		}
 	}
 
@@ -960,6 +1053,10 @@ impl MsgHandleErrInternal {
 			&ChannelError::Abort(reason) => Some(reason.into_tx_abort_msg(channel_id)),
 			_ => None,
 		};
+		let misbehavior = match &err {
+			ChannelError::Close((_, _, misbehavior)) => *misbehavior,
+			_ => None,
+		};
 		let err = match err {
 			ChannelError::Warn(msg) => LightningError {
 				err: msg.clone(),
@@ -980,14 +1077,14 @@ impl MsgHandleErrInternal {
 			ChannelError::Abort(reason) => {
 				LightningError { err: reason.to_string(), action: msgs::ErrorAction::IgnoreError }
 			},
-			ChannelError::Close((msg, _)) | ChannelError::SendError(msg) => LightningError {
+			ChannelError::Close((msg, _, _)) | ChannelError::SendError(msg) => LightningError {
 				err: msg.clone(),
 				action: msgs::ErrorAction::SendErrorMessage {
 					msg: msgs::ErrorMessage { channel_id, data: msg },
 				},
 			},
 		};
-		Self { err, closes_channel: false, shutdown_finish: None, tx_abort }
+		Self { err, closes_channel: false, shutdown_finish: None, tx_abort, misbehavior }
 	}
 
 	fn dont_send_error_message(&mut self) {
@@ -1152,8 +1249,50 @@ struct ClaimablePayments {
 	/// are waiting on a [`ChannelMonitorUpdate`] to complete in order to be surfaced to the user
 	/// as an [`events::Event::PaymentClaimed`].
 	pending_claiming_payments: HashMap<PaymentHash, ClaimingPayment>,
+
+	/// Payment hashes registered via [`ChannelManager::create_hold_inbound_payment_for_hash`],
+	/// removed once the payment is claimed or failed back, or once the corresponding invoice has
+	/// expired without a payment being held.
+	///
+	/// Once all HTLCs for one of these are received we generate an [`events::Event::PaymentHeld`]
+	/// rather than an [`events::Event::PaymentClaimable`], and wait on an explicit call to
+	/// [`ChannelManager::settle_held_payment`] or [`ChannelManager::cancel_held_payment`].
+	held_payment_hashes: HashMap<PaymentHash, RegisteredHoldInvoice>,
+
+	/// Amount ranges registered via [`ChannelManager::create_inbound_payment_with_amount_range`]
+	/// and [`ChannelManager::create_inbound_payment_for_hash_with_amount_range`], removed once
+	/// the corresponding invoice has expired.
+	///
+	/// HTLCs which would take a payment for one of these over its maximum are failed back.
+	inbound_amount_ranges: HashMap<PaymentHash, RegisteredInboundAmountRange>,
 }
 
+/// An [`InboundAmountRange`] registered for a payment hash, along with the time after which the
+/// corresponding invoice can no longer be paid.
+struct RegisteredInboundAmountRange {
+	payment_hash: PaymentHash,
+	range: InboundAmountRange,
+	expiry_time: u64,
+}
+
+impl_writeable_tlv_based!(RegisteredInboundAmountRange, {
+	(0, payment_hash, required),
+	(2, range, required),
+	(4, expiry_time, required),
+});
+
+/// A payment hash registered as a hold invoice, along with the time after which the
+/// corresponding invoice can no longer be paid.
+struct RegisteredHoldInvoice {
+	payment_hash: PaymentHash,
+	expiry_time: u64,
+}
+
+impl_writeable_tlv_based!(RegisteredHoldInvoice, {
+	(0, payment_hash, required),
+	(2, expiry_time, required),
+});
+
 impl ClaimablePayments {
 	/// Moves a payment from [`Self::claimable_payments`] to [`Self::pending_claiming_payments`].
 	///
@@ -1647,6 +1786,48 @@ where
 		self.channel_by_id.contains_key(channel_id)
 			|| self.inbound_channel_request_by_id.contains_key(channel_id)
 	}
+
+	/// Records a `warning` or `error` message exchanged with the peer in the protocol error history
+	/// of the channel it relates to, if we have such a channel.
+	fn record_protocol_error(
+		&mut self, channel_id: &ChannelId, direction: ProtocolErrorDirection,
+		kind: ProtocolErrorKind, message: &str, timestamp: Duration,
+	) {
+		if let Some(chan) = self.channel_by_id.get_mut(channel_id) {
+			let message = UntrustedString(message.to_owned());
+			let record = ProtocolErrorRecord { direction, kind, timestamp, message };
+			chan.context_mut().record_protocol_error(record);
+		}
+	}
+
+	/// Records the `warning` and `error` messages in [`Self::pending_msg_events`] as sent to the
+	/// peer. Must be called before the events are handed to the [`PeerManager`].
+	///
+	/// [`PeerManager`]: crate::ln::peer_handler::PeerManager
+	fn record_sent_protocol_errors(&mut self, timestamp: Duration) {
+		for event in self.pending_msg_events.iter() {
+			let (kind, channel_id, message) = match event {
+				MessageSendEvent::HandleError { action, .. } => match action {
+					msgs::ErrorAction::SendErrorMessage { msg }
+					| msgs::ErrorAction::DisconnectPeer { msg: Some(msg) } => {
+						(ProtocolErrorKind::Error, msg.channel_id, &msg.data)
+					},
+					msgs::ErrorAction::SendWarningMessage { msg, .. }
+					| msgs::ErrorAction::DisconnectPeerWithWarning { msg } => {
+						(ProtocolErrorKind::Warning, msg.channel_id, &msg.data)
+					},
+					_ => continue,
+				},
+				_ => continue,
+			};
+			if let Some(chan) = self.channel_by_id.get_mut(&channel_id) {
+				let message = UntrustedString(message.clone());
+				let direction = ProtocolErrorDirection::Sent;
+				let record = ProtocolErrorRecord { direction, kind, timestamp, message };
+				chan.context_mut().record_protocol_error(record);
+			}
+		}
+	}
 }
 
 #[derive(Clone)]
@@ -1955,6 +2136,7 @@ where
 /// // Fresh start with no channels
 /// let params = ChainParameters {
 ///     network: Network::Bitcoin,
+///     custom_chain_hash: None,
 ///     best_block,
 /// };
 /// let config = UserConfig::default();
@@ -2141,6 +2323,74 @@ where
 /// # }
 /// ```
 ///
+/// ## Splicing Channels
+///
+/// Funds may be added to or removed from a channel without closing it by calling
+/// [`splice_channel`] with a [`SpliceContribution`]. When splicing in, the UTXOs to contribute are
+/// given as [`FundingTxInput`]s, optionally along with a change script. Once the funding
+/// transaction has been negotiated with the counterparty,
+/// [`Event::FundingTransactionReadyForSigning`] is generated, requiring the contributed inputs to
+/// be signed and passed back via [`funding_transaction_signed`].
+///
+/// ```
+/// # use bitcoin::{Amount, ScriptBuf, Transaction};
+/// # use bitcoin::secp256k1::PublicKey;
+/// # use lightning::ln::channelmanager::AChannelManager;
+/// # use lightning::ln::funding::{FundingTxInput, SpliceContribution};
+/// # use lightning::ln::types::ChannelId;
+/// # use lightning::events::{Event, EventsProvider};
+/// #
+/// # trait Wallet {
+/// #     fn select_utxo(&self, _amount: Amount) -> (Transaction, u32);
+/// #     fn change_script(&self) -> ScriptBuf;
+/// #     fn sign_transaction(&self, _tx: Transaction) -> Transaction;
+/// # }
+/// #
+/// # fn example<T: AChannelManager, W: Wallet>(
+/// #     channel_manager: T, wallet: W, channel_id: ChannelId, peer_id: PublicKey
+/// # ) {
+/// # let channel_manager = channel_manager.get_cm();
+/// let value = Amount::from_sat(500_000);
+/// let (prevtx, vout) = wallet.select_utxo(value);
+/// let contribution = SpliceContribution::SpliceIn {
+///     value,
+///     inputs: vec![FundingTxInput::new_p2wpkh(prevtx, vout).unwrap()],
+///     change_script: Some(wallet.change_script()),
+/// };
+/// let feerate = 1_000;
+/// match channel_manager.splice_channel(&channel_id, &peer_id, contribution, feerate, None) {
+///     Ok(()) => println!("Splicing channel {}", channel_id),
+///     Err(e) => println!("Error splicing channel {}: {:?}", channel_id, e),
+/// }
+///
+/// // On the event processing thread once the funding transaction has been negotiated
+/// channel_manager.process_pending_events(&|event| {
+///     match event {
+///         Event::FundingTransactionReadyForSigning {
+///             channel_id, counterparty_node_id, unsigned_transaction, ..
+///         } => {
+///             let signed_transaction = wallet.sign_transaction(unsigned_transaction);
+///             match channel_manager.funding_transaction_signed(
+///                 &channel_id, &counterparty_node_id, signed_transaction
+///             ) {
+///                 Ok(()) => println!("Signed splice of channel {}", channel_id),
+///                 Err(e) => println!("Error signing splice of channel {}: {:?}", channel_id, e),
+///             }
+///         },
+///         Event::SplicePending { channel_id, new_funding_txo, .. } => {
+///             println!("Channel {} now pending splice into {}", channel_id, new_funding_txo);
+///         },
+///         Event::SpliceFailed { channel_id, contributed_inputs, .. } => {
+///             println!("Splicing {} failed, freeing {:?}", channel_id, contributed_inputs);
+///         },
+///         // ...
+///     #     _ => {},
+///     }
+///     Ok(())
+/// });
+/// # }
+/// ```
+///
 /// ## Closing Channels
 ///
 /// There are two ways to close a channel: either cooperatively using [`close_channel`] or
@@ -2582,6 +2832,10 @@ where
 /// [`list_channels`]: Self::list_channels
 /// [`list_usable_channels`]: Self::list_usable_channels
 /// [`create_channel`]: Self::create_channel
+/// [`splice_channel`]: Self::splice_channel
+/// [`SpliceContribution`]: crate::ln::funding::SpliceContribution
+/// [`FundingTxInput`]: crate::ln::funding::FundingTxInput
+/// [`funding_transaction_signed`]: Self::funding_transaction_signed
 /// [`close_channel`]: Self::force_close_broadcasting_latest_txn
 /// [`force_close_broadcasting_latest_txn`]: Self::force_close_broadcasting_latest_txn
 /// [BOLT 11]: https://github.com/lightning/bolts/blob/master/11-payment-encoding.md
@@ -2671,6 +2925,62 @@ pub struct ChannelManager<
 	config: RwLock<UserConfig>,
 	chain_hash: ChainHash,
 	fee_estimator: LowerBoundedFeeEstimator<F>,
+	update_fee_policy: RwLock<Box<dyn UpdateFeePolicy + Send + Sync>>,
+	inbound_channel_fee_policy: RwLock<Box<dyn InboundChannelFeePolicy + Send + Sync>>,
+	/// Misbehavior incidents recorded per peer. Not persisted.
+	///
+	/// This is a leaf lock - no other locks may be taken while it is held.
+	misbehavior_ledger: Mutex<MisbehaviorLedger>,
+	/// The local reputation of the peers forwarding HTLCs through us, see
+	/// [`Self::list_local_reputation`]. Not persisted.
+	///
+	/// This is a leaf lock - no other locks may be taken while it is held.
+	local_reputation: Mutex<LocalReputationTracker>,
+	/// Peers quarantined after too many consecutive failures to handle their messages, see
+	/// [`UserConfig::quarantine_after_message_failures`].
+	///
+	/// This is a leaf lock - no other locks may be taken while it is held.
+	message_quarantine: Mutex<MessageQuarantine>,
+	/// Closures scheduled via [`Self::schedule_closures`]. Not persisted.
+	///
+	/// This is a leaf lock - no other locks may be taken while it is held.
+	closure_scheduler: Mutex<ClosureScheduler>,
+	/// The surge pricing state of our channels, see [`Self::set_dynamic_fee_policy`]. Not
+	/// persisted.
+	///
+	/// This is a leaf lock - no other locks may be taken while it is held.
+	dynamic_fees: Mutex<Option<SurgePricingState>>,
+	/// Channels we're recovering via [`Self::recover_from_static_backup`] or
+	/// [`Self::enter_recovery_mode`], until we've seen the counterparty's commitment transaction
+	/// confirm.
+	///
+	/// This is a leaf lock - no other locks may be taken while it is held.
+	static_backup_recovery: Mutex<Vec<StaticChannelBackupEntry>>,
+	/// Whether we tolerate our counterparties proving that our channel state is stale, see
+	/// [`Self::enter_recovery_mode`]. Not persisted.
+	recovery_mode: AtomicBool,
+	/// The periods requested by each payer of our recurring offers, keyed by the offer and the
+	/// payer's signing pubkey, used to reject out-of-window invoice requests.
+	///
+	/// This is a leaf lock - no other locks may be taken while it is held.
+	recurring_offer_payers: Mutex<HashMap<(OfferId, PublicKey), PayerRecurrence>>,
+	/// The hook consulted before responding to a [`Refund`], see [`Self::set_refund_handler`]. Not
+	/// persisted.
+	refund_handler: RwLock<Option<Box<dyn RefundHandler + Send + Sync>>>,
+	/// The hook deciding which limits apply to HTLCs offered by our peers, see
+	/// [`Self::set_peer_reputation`]. Not persisted.
+	peer_reputation: RwLock<Option<Box<dyn PeerReputation + Send + Sync>>>,
+	/// The hook consulted before we force-close channels on our own accord, see
+	/// [`Self::set_force_close_observer`]. Not persisted.
+	force_close_observer: RwLock<Option<Box<dyn ForceCloseObserver + Send + Sync>>>,
+	/// The block height at which the [`ForceCloseObserver`] was first consulted about each
+	/// channel's pending discretionary force-closure. Not persisted.
+	///
+	/// This is a leaf lock - no other locks may be taken while it is held.
+	force_close_vetoes: Mutex<HashMap<ChannelId, u32>>,
+	/// The hook consulted before we forward HTLCs, see [`Self::set_forwarding_policy`]. Not
+	/// persisted.
+	forwarding_policy: RwLock<Option<Box<dyn ForwardingPolicy + Send + Sync>>>,
 	chain_monitor: M,
 	tx_broadcaster: T,
 	router: R,
@@ -2869,9 +3179,23 @@ pub struct ChannelManager<
 	event_persist_notifier: Notifier,
 	needs_persist_flag: AtomicBool,
 
+	/// The latest snapshot handed out by [`ChannelManager::channel_snapshot`], and whether our
+	/// channels may have changed since it was taken.
+	channel_snapshot: Mutex<Arc<ChannelSnapshot>>,
+	channel_snapshot_stale: AtomicBool,
+
 	/// Tracks the message events that are to be broadcasted when we are connected to some peer.
 	pending_broadcast_messages: Mutex<Vec<MessageSendEvent>>,
 
+	/// The most recent [`msgs::ChannelUpdate`] we signed for each channel, along with the number
+	/// of [`ChannelManager::timer_tick_occurred`] calls since it was signed. As long as the
+	/// channel's policy doesn't change we can hand out the cached message rather than asking our
+	/// [`NodeSigner`] to sign it again. Entries are dropped after
+	/// [`CHANNEL_UPDATE_SIGNATURE_CACHE_TICKS`], forcing a periodic re-sign.
+	///
+	/// This is only kept in memory, as signatures can always be regenerated.
+	signed_channel_updates: Mutex<HashMap<ChannelId, (msgs::ChannelUpdate, u8)>>,
+
 	/// We only want to force-close our channels on peers based on stale feerates when we're
 	/// confident the feerate on the channel is *really* stale, not just became stale recently.
 	/// Thus, we store the fee estimates we had as of the last [`FEERATE_TRACKING_BLOCKS`] blocks
@@ -2918,12 +3242,25 @@ pub struct ChainParameters {
 	/// The network for determining the `chain_hash` in Lightning messages.
 	pub network: Network,
 
+	/// The genesis block hash of the chain we operate on, overriding the one of [`Self::network`].
+	///
+	/// Only needs to be set for chains with a custom genesis block, such as custom signets.
+	pub custom_chain_hash: Option<ChainHash>,
+
 	/// The hash and height of the latest block successfully connected.
 	///
 	/// Used to track on-chain channel funding outputs and send payments with reliable timelocks.
 	pub best_block: BestBlock,
 }
 
+impl ChainParameters {
+	/// Returns the `chain_hash` used in Lightning messages, i.e., [`Self::custom_chain_hash`] if
+	/// set or the genesis block hash of [`Self::network`] otherwise.
+	pub fn chain_hash(&self) -> ChainHash {
+		self.custom_chain_hash.unwrap_or_else(|| ChainHash::using_genesis_block(self.network))
+	}
+}
+
 #[derive(Copy, Clone, PartialEq)]
 #[must_use]
 enum NotifyOption {
@@ -2945,6 +3282,7 @@ enum NotifyOption {
 struct PersistenceNotifierGuard<'a, F: FnOnce() -> NotifyOption> {
 	event_persist_notifier: &'a Notifier,
 	needs_persist_flag: &'a AtomicBool,
+	channel_snapshot_stale: &'a AtomicBool,
 	// Always `Some` once initialized, but tracked as an `Option` to obtain the closure by value in
 	// [`PersistenceNotifierGuard::drop`].
 	should_persist: Option<F>,
@@ -2975,6 +3313,7 @@ impl<'a> PersistenceNotifierGuard<'a, fn() -> NotifyOption> {
 		PersistenceNotifierGuard {
 			event_persist_notifier: &cm.get_cm().event_persist_notifier,
 			needs_persist_flag: &cm.get_cm().needs_persist_flag,
+			channel_snapshot_stale: &cm.get_cm().channel_snapshot_stale,
 			should_persist: Some(move || {
 				// Pick the "most" action between `persist_check` and the background events
 				// processing and return that.
@@ -3006,6 +3345,7 @@ impl<'a> PersistenceNotifierGuard<'a, fn() -> NotifyOption> {
 		PersistenceNotifierGuard {
 			event_persist_notifier: &cm.get_cm().event_persist_notifier,
 			needs_persist_flag: &cm.get_cm().needs_persist_flag,
+			channel_snapshot_stale: &cm.get_cm().channel_snapshot_stale,
 			should_persist: Some(persist_check),
 			_read_guard: read_guard,
 		}
@@ -3024,9 +3364,13 @@ impl<'a, F: FnOnce() -> NotifyOption> Drop for PersistenceNotifierGuard<'a, F> {
 		match should_persist() {
 			NotifyOption::DoPersist => {
 				self.needs_persist_flag.store(true, Ordering::Release);
+				self.channel_snapshot_stale.store(true, Ordering::Release);
+				self.event_persist_notifier.notify()
+			},
+			NotifyOption::SkipPersistHandleEvents => {
+				self.channel_snapshot_stale.store(true, Ordering::Release);
 				self.event_persist_notifier.notify()
 			},
-			NotifyOption::SkipPersistHandleEvents => self.event_persist_notifier.notify(),
 			NotifyOption::SkipPersistNoEvents => {},
 		}
 	}
@@ -3112,6 +3456,10 @@ pub(crate) const DISABLE_GOSSIP_TICKS: u8 = 10;
 /// we mark the channel enabled and gossip the update.
 pub(crate) const ENABLE_GOSSIP_TICKS: u8 = 5;
 
+/// The number of ticks of [`ChannelManager::timer_tick_occurred`] for which we'll reuse a cached
+/// [`msgs::ChannelUpdate`] signature before asking the [`NodeSigner`] to sign it again.
+pub(crate) const CHANNEL_UPDATE_SIGNATURE_CACHE_TICKS: u8 = 60;
+
 /// The maximum number of unfunded channels we can have per-peer before we start rejecting new
 /// (inbound) ones. The number of peers with unfunded channels is limited separately in
 /// [`MAX_UNFUNDED_CHANNEL_PEERS`].
@@ -3587,9 +3935,12 @@ fn convert_channel_err_internal<
 		ChannelError::Abort(reason) => {
 			(false, MsgHandleErrInternal::from_chan_no_close(ChannelError::Abort(reason), chan_id))
 		},
-		ChannelError::Close((msg, reason)) => {
+		ChannelError::Close((msg, reason, misbehavior)) => {
 			let (finish, chan_update) = close(reason, &msg);
-			(true, MsgHandleErrInternal::from_finish_shutdown(msg, chan_id, finish, chan_update))
+			let mut err =
+				MsgHandleErrInternal::from_finish_shutdown(msg, chan_id, finish, chan_update);
+			err.misbehavior = misbehavior;
+			(true, err)
 		},
 		ChannelError::SendError(msg) => {
 			(false, MsgHandleErrInternal::from_chan_no_close(ChannelError::SendError(msg), chan_id))
@@ -3612,12 +3963,36 @@ where
 		let cm = cm.get_cm();
 		let logger = WithChannelContext::from(&cm.logger, &chan.context, None);
 
+		if coop_close_shutdown_res.is_none() {
+			// If the `ForceCloseObserver` was already consulted about a discretionary force-closure,
+			// it has decided to let it go ahead. Otherwise we can no longer avoid closing the
+			// channel, so we let it know but ignore its decision.
+			let force_close_reason =
+				ForceCloseReason::from_closure_reason(&reason).filter(|force_close_reason| {
+					!force_close_reason.is_discretionary()
+						|| cm.force_close_vetoes.lock().unwrap().remove(&chan_id).is_none()
+				});
+			if let Some(force_close_reason) = force_close_reason {
+				if let Some(observer) = cm.force_close_observer.read().unwrap().as_ref() {
+					let counterparty_node_id = chan.context.get_counterparty_node_id();
+					observer.before_force_close(
+						&chan_id,
+						&counterparty_node_id,
+						&force_close_reason,
+					);
+				}
+			}
+		}
 		let mut shutdown_res =
 			if let Some(res) = coop_close_shutdown_res { res } else { chan.force_shutdown(reason) };
 		let chan_update = cm.get_channel_update_for_broadcast(chan).ok();
 
 		log_error!(logger, "Closed channel due to close-required error: {}", msg);
 
+		if chan.context.is_holder_state_stale() {
+			cm.track_stale_channel_recovery(chan);
+		}
+
 		if let Some((_, funding_txo, _, update)) = shutdown_res.monitor_update.take() {
 			handle_new_monitor_update_locked_actions_handled_by_caller!(
 				cm,
@@ -3702,7 +4077,7 @@ where
 #[rustfmt::skip]
 macro_rules! convert_channel_err {
 	($self: ident, $peer_state: expr, $shutdown_result: expr, $funded_channel: expr, COOP_CLOSED) => { {
-		let reason = ChannelError::Close(("Coop Closed".to_owned(), $shutdown_result.closure_reason.clone()));
+		let reason = ChannelError::Close(("Coop Closed".to_owned(), $shutdown_result.closure_reason.clone(), None));
 		let closed_update_ids = &mut $peer_state.closed_channel_monitor_update_ids;
 		let in_flight_updates = &mut $peer_state.in_flight_monitor_updates;
 		let (close, mut err) =
@@ -3766,6 +4141,25 @@ macro_rules! try_channel_entry {
 #[rustfmt::skip]
 macro_rules! process_events_body {
 	($self: expr, $event_to_handle: expr, $handle_event: expr) => {
+		process_events_body!($self, batch events, {
+			let mut results = Vec::with_capacity(events.len());
+			for event in events {
+				log_trace!($self.logger, "Handling event {:?}...", event);
+				$event_to_handle = event;
+				let event_handling_result = $handle_event;
+				log_trace!($self.logger, "Done handling event, result: {:?}", event_handling_result);
+				let failed = event_handling_result.is_err();
+				results.push(event_handling_result);
+				if failed {
+					break;
+				}
+			}
+			results
+		})
+	};
+	// Hands all pending events to `$handle_events` at once as `$events`, which must evaluate to
+	// the handling results of (a prefix of) the events, in order.
+	($self: expr, batch $events: ident, $handle_events: expr) => {
 		let mut handling_failed = false;
 		let mut processed_all_events = false;
 		while !handling_failed && !processed_all_events {
@@ -3789,6 +4183,8 @@ macro_rules! process_events_body {
 				if $self.process_pending_monitor_events() {
 					result = NotifyOption::DoPersist;
 				}
+
+				$self.queue_channel_lifecycle_events();
 			}
 
 			let pending_events = $self.pending_events.lock().unwrap().clone();
@@ -3798,12 +4194,12 @@ macro_rules! process_events_body {
 
 			let mut post_event_actions = Vec::new();
 
+			let (events_to_handle, actions): (Vec<_>, Vec<_>) = pending_events.into_iter().unzip();
+			let $events = events_to_handle;
+			let event_handling_results: Vec<Result<(), ReplayEvent>> = $handle_events;
+
 			let mut num_handled_events = 0;
-			for (event, action_opt) in pending_events {
-				log_trace!($self.logger, "Handling event {:?}...", event);
-				$event_to_handle = event;
-				let event_handling_result = $handle_event;
-				log_trace!($self.logger, "Done handling event, result: {:?}", event_handling_result);
+			for (event_handling_result, action_opt) in event_handling_results.into_iter().zip(actions) {
 				match event_handling_result {
 					Ok(()) => {
 						if let Some(action) = action_opt {
@@ -3838,10 +4234,13 @@ macro_rules! process_events_body {
 			match result {
 				NotifyOption::DoPersist => {
 					$self.needs_persist_flag.store(true, Ordering::Release);
+					$self.channel_snapshot_stale.store(true, Ordering::Release);
+					$self.event_persist_notifier.notify();
+				},
+				NotifyOption::SkipPersistHandleEvents => {
+					$self.channel_snapshot_stale.store(true, Ordering::Release);
 					$self.event_persist_notifier.notify();
 				},
-				NotifyOption::SkipPersistHandleEvents =>
-					$self.event_persist_notifier.notify(),
 				NotifyOption::SkipPersistNoEvents => {},
 			}
 		}
@@ -3903,14 +4302,29 @@ where
 		let our_network_pubkey = node_signer.get_node_id(Recipient::Node).unwrap();
 
 		let flow = OffersMessageFlow::new(
-			ChainHash::using_genesis_block(params.network), params.best_block,
+			params.chain_hash(), params.best_block,
 			our_network_pubkey, current_timestamp, expanded_inbound_key,
 			node_signer.get_receive_auth_key(), secp_ctx.clone(), message_router, logger.clone(),
 		);
 
 		ChannelManager {
 			config: RwLock::new(config),
-			chain_hash: ChainHash::using_genesis_block(params.network),
+			update_fee_policy: RwLock::new(Box::new(DefaultUpdateFeePolicy::default())),
+			inbound_channel_fee_policy: RwLock::new(Box::new(DefaultInboundChannelFeePolicy)),
+			misbehavior_ledger: Mutex::new(MisbehaviorLedger::new()),
+			local_reputation: Mutex::new(LocalReputationTracker::new()),
+			message_quarantine: Mutex::new(MessageQuarantine::new()),
+			closure_scheduler: Mutex::new(ClosureScheduler::new()),
+			dynamic_fees: Mutex::new(None),
+			static_backup_recovery: Mutex::new(Vec::new()),
+			recovery_mode: AtomicBool::new(false),
+			recurring_offer_payers: Mutex::new(new_hash_map()),
+			refund_handler: RwLock::new(None),
+			peer_reputation: RwLock::new(None),
+			force_close_observer: RwLock::new(None),
+			force_close_vetoes: Mutex::new(new_hash_map()),
+			forwarding_policy: RwLock::new(None),
+			chain_hash: params.chain_hash(),
 			fee_estimator: LowerBoundedFeeEstimator::new(fee_est),
 			chain_monitor,
 			tx_broadcaster,
@@ -3923,7 +4337,12 @@ where
 			pending_outbound_payments: OutboundPayments::new(new_hash_map(), logger.clone()),
 			forward_htlcs: Mutex::new(new_hash_map()),
 			decode_update_add_htlcs: Mutex::new(new_hash_map()),
-			claimable_payments: Mutex::new(ClaimablePayments { claimable_payments: new_hash_map(), pending_claiming_payments: new_hash_map() }),
+			claimable_payments: Mutex::new(ClaimablePayments {
+				claimable_payments: new_hash_map(),
+				pending_claiming_payments: new_hash_map(),
+				held_payment_hashes: new_hash_map(),
+				inbound_amount_ranges: new_hash_map(),
+			}),
 			pending_intercepted_htlcs: Mutex::new(new_hash_map()),
 			short_to_chan_info: FairRwLock::new(new_hash_map()),
 
@@ -3951,9 +4370,15 @@ where
 			background_events_processed_since_startup: AtomicBool::new(false),
 			event_persist_notifier: Notifier::new(),
 			needs_persist_flag: AtomicBool::new(false),
+			channel_snapshot: Mutex::new(Arc::new(ChannelSnapshot {
+				channels: Vec::new(),
+				best_block_height: params.best_block.height,
+			})),
+			channel_snapshot_stale: AtomicBool::new(true),
 			funding_batch_states: Mutex::new(BTreeMap::new()),
 
 			pending_broadcast_messages: Mutex::new(Vec::new()),
+			signed_channel_updates: Mutex::new(new_hash_map()),
 
 			last_days_feerates: Mutex::new(VecDeque::new()),
 
@@ -4034,6 +4459,179 @@ where
 		})
 	}
 
+	/// Handles an error resulting from a message sent by `counterparty_node_id` via
+	/// [`Self::handle_error`], first recording it in our misbehavior ledger if it results in a
+	/// warning or error being sent to the peer. The incident's kind is derived from the cause of
+	/// the error, defaulting to [`MisbehaviorKind::ProtocolViolation`] if it isn't more specific.
+	///
+	/// Such failures are also counted towards quarantining the peer, see
+	/// [`UserConfig::quarantine_after_message_failures`], whereas successfully handled messages
+	/// reset the count.
+	fn handle_peer_message_error<A>(
+		&self, internal: Result<A, MsgHandleErrInternal>, counterparty_node_id: PublicKey,
+	) -> Result<A, LightningError> {
+		let mut message_failure = None;
+		if let Err(err_internal) = &internal {
+			let channel_id = match &err_internal.err.action {
+				msgs::ErrorAction::DisconnectPeer { msg: Some(msg) }
+				| msgs::ErrorAction::SendErrorMessage { msg } => Some(msg.channel_id),
+				msgs::ErrorAction::DisconnectPeerWithWarning { msg }
+				| msgs::ErrorAction::SendWarningMessage { msg, .. } => Some(msg.channel_id),
+				msgs::ErrorAction::DisconnectPeer { msg: None } => None,
+				msgs::ErrorAction::IgnoreError
+				| msgs::ErrorAction::IgnoreAndLog(_)
+				| msgs::ErrorAction::IgnoreDuplicateGossip => {
+					return self.handle_error(internal, counterparty_node_id);
+				},
+			};
+			let channel_id = err_internal
+				.shutdown_finish
+				.as_ref()
+				.map(|(shutdown_res, _)| shutdown_res.channel_id)
+				.or(channel_id.filter(|channel_id| *channel_id != ChannelId::new_zero()));
+			let kind = err_internal.misbehavior.unwrap_or(MisbehaviorKind::ProtocolViolation);
+			let description = err_internal.err.err.clone();
+			self.record_misbehavior(counterparty_node_id, kind, channel_id, description.clone());
+			message_failure = Some((channel_id, description));
+		} else {
+			self.message_quarantine.lock().unwrap().record_success(&counterparty_node_id);
+		}
+		let res = self.handle_error(internal, counterparty_node_id);
+		if let Some((channel_id, last_error)) = message_failure {
+			self.record_message_failure(counterparty_node_id, channel_id, last_error);
+		}
+		res
+	}
+
+	/// Records a failure to handle a message from the given peer, quarantining the peer if it
+	/// reached [`UserConfig::quarantine_after_message_failures`] consecutive failures.
+	fn record_message_failure(
+		&self, counterparty_node_id: PublicKey, channel_id: Option<ChannelId>, last_error: String,
+	) {
+		let threshold = self.config.read().unwrap().quarantine_after_message_failures;
+		let timestamp = self.duration_since_epoch();
+		let quarantined = self.message_quarantine.lock().unwrap().record_failure(
+			counterparty_node_id,
+			channel_id,
+			last_error,
+			timestamp,
+			threshold,
+		);
+		if let Some(peer) = quarantined {
+			let logger =
+				WithContext::from(&self.logger, Some(counterparty_node_id), channel_id, None);
+			log_error!(
+				logger,
+				"Quarantining peer {} after failing to handle {} consecutive messages from it, last with: {}",
+				log_pubkey!(counterparty_node_id),
+				peer.consecutive_failures,
+				peer.last_error
+			);
+			let per_peer_state = self.per_peer_state.read().unwrap();
+			if let Some(peer_state_mutex) = per_peer_state.get(&counterparty_node_id) {
+				let mut peer_state = peer_state_mutex.lock().unwrap();
+				peer_state.pending_msg_events.push(MessageSendEvent::HandleError {
+					node_id: counterparty_node_id,
+					action: msgs::ErrorAction::DisconnectPeer { msg: None },
+				});
+			}
+			mem::drop(per_peer_state);
+			self.pending_events.lock().unwrap().push_back((
+				events::Event::PeerQuarantined {
+					counterparty_node_id,
+					channel_id: peer.channel_id,
+					consecutive_failures: peer.consecutive_failures,
+					last_error: peer.last_error,
+				},
+				None,
+			));
+		}
+	}
+
+	fn record_misbehavior(
+		&self, counterparty_node_id: PublicKey, kind: MisbehaviorKind,
+		channel_id: Option<ChannelId>, description: String,
+	) {
+		let timestamp = self.duration_since_epoch();
+		let incident = MisbehaviorIncident { kind, channel_id, timestamp, description };
+		self.misbehavior_ledger.lock().unwrap().record(counterparty_node_id, incident);
+	}
+
+	/// Gets the [`MisbehaviorIncident`]s recorded for the given peer, oldest first.
+	///
+	/// Incidents are recorded whenever the peer sends us a message which we reject with a warning
+	/// or error, as well as when we force-close a channel because the peer did not resolve an HTLC
+	/// we offered to them in time. At most [`MAX_INCIDENTS_PER_PEER`] incidents are kept per peer
+	/// and incidents for at most [`MAX_TRACKED_PEERS`] peers are kept in total.
+	///
+	/// Incidents are not persisted and will be lost on restart.
+	///
+	/// [`MAX_INCIDENTS_PER_PEER`]: crate::ln::peer_misbehavior::MAX_INCIDENTS_PER_PEER
+	/// [`MAX_TRACKED_PEERS`]: crate::ln::peer_misbehavior::MAX_TRACKED_PEERS
+	pub fn list_peer_misbehavior(
+		&self, counterparty_node_id: &PublicKey,
+	) -> Vec<MisbehaviorIncident> {
+		self.misbehavior_ledger.lock().unwrap().incidents_for_peer(counterparty_node_id)
+	}
+
+	/// Lists all peers for which at least one [`MisbehaviorIncident`] has been recorded, along with
+	/// the number of incidents currently stored for each.
+	///
+	/// See [`Self::list_peer_misbehavior`] for more details.
+	pub fn list_misbehaving_peers(&self) -> Vec<(PublicKey, usize)> {
+		self.misbehavior_ledger.lock().unwrap().list_peers()
+	}
+
+	/// Forgets all [`MisbehaviorIncident`]s recorded for the given peer, e.g. after the operator has
+	/// reviewed them.
+	pub fn clear_peer_misbehavior(&self, counterparty_node_id: &PublicKey) {
+		self.misbehavior_ledger.lock().unwrap().clear_peer(counterparty_node_id);
+	}
+
+	/// Lists the [`LocalReputation`] of the peers which forwarded HTLCs through us, based on which
+	/// we decide whether to endorse the HTLCs they offer to us when forwarding them, see
+	/// [`HTLCEndorsementConfig`].
+	///
+	/// At most [`MAX_PEERS_WITH_REPUTATION`] peers are tracked. Reputation is not persisted and will
+	/// be lost on restart.
+	///
+	/// [`HTLCEndorsementConfig`]: crate::util::config::HTLCEndorsementConfig
+	/// [`MAX_PEERS_WITH_REPUTATION`]: crate::ln::local_reputation::MAX_PEERS_WITH_REPUTATION
+	pub fn list_local_reputation(&self) -> Vec<(PublicKey, LocalReputation)> {
+		let config = self.config.read().unwrap().htlc_endorsement_config;
+		let now = self.duration_since_epoch();
+		self.local_reputation.lock().unwrap().list_peers(now, &config)
+	}
+
+	/// Lists the peers which were quarantined after sending us
+	/// [`UserConfig::quarantine_after_message_failures`] consecutive messages we failed to handle.
+	///
+	/// Quarantined peers are refused reconnection until released via
+	/// [`Self::release_quarantined_peer`].
+	pub fn list_quarantined_peers(&self) -> Vec<QuarantinedPeer> {
+		self.message_quarantine.lock().unwrap().list_quarantined()
+	}
+
+	/// Lifts the quarantine of the given peer, e.g., after the operator investigated and resolved
+	/// the cause of the failures which led to it.
+	///
+	/// The peer is allowed to reconnect afterwards, at which point pending channel messages will
+	/// be retried as part of the usual channel reestablishment.
+	///
+	/// Returns an [`APIError::APIMisuseError`] if the peer is not quarantined.
+	pub fn release_quarantined_peer(
+		&self, counterparty_node_id: &PublicKey,
+	) -> Result<(), APIError> {
+		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
+		if self.message_quarantine.lock().unwrap().release(counterparty_node_id) {
+			Ok(())
+		} else {
+			Err(APIError::APIMisuseError {
+				err: format!("Peer {} is not quarantined", log_pubkey!(counterparty_node_id)),
+			})
+		}
+	}
+
 	/// Gets the current [`UserConfig`] which controls some global behavior and includes the
 	/// default configuration applied to all new channels.
 	pub fn get_current_config(&self) -> UserConfig {
@@ -4046,6 +4644,134 @@ where
 		*self.config.write().unwrap() = new_config;
 	}
 
+	/// Sets the [`UpdateFeePolicy`] used to decide which feerates proposed by our counterparties in
+	/// `update_fee` messages we accept, replacing the [`DefaultUpdateFeePolicy`].
+	///
+	/// The policy is not persisted and must be set again each time the [`ChannelManager`] is
+	/// deserialized.
+	pub fn set_update_fee_policy<P: UpdateFeePolicy + Send + Sync + 'static>(&self, policy: P) {
+		*self.update_fee_policy.write().unwrap() = Box::new(policy);
+	}
+
+	/// Sets the [`InboundChannelFeePolicy`] used to decide whether we accept the commitment
+	/// transaction feerates proposed by peers opening channels to us, replacing the
+	/// [`DefaultInboundChannelFeePolicy`].
+	///
+	/// The policy is not persisted and must be set again each time the [`ChannelManager`] is
+	/// deserialized.
+	pub fn set_inbound_channel_fee_policy<P: InboundChannelFeePolicy + Send + Sync + 'static>(
+		&self, policy: P,
+	) {
+		*self.inbound_channel_fee_policy.write().unwrap() = Box::new(policy);
+	}
+
+	/// Sets the [`RefundHandler`] consulted by [`Self::request_refund_payment`] before responding
+	/// to a [`Refund`] with an invoice, replacing any previously set handler.
+	///
+	/// The handler is not persisted and must be set again each time the [`ChannelManager`] is
+	/// deserialized.
+	pub fn set_refund_handler<H: RefundHandler + Send + Sync + 'static>(&self, handler: H) {
+		*self.refund_handler.write().unwrap() = Some(Box::new(handler));
+	}
+
+	/// Sets the [`PeerReputation`] deciding which [`InboundHTLCLimits`] apply to HTLCs offered to
+	/// us by each peer, replacing any previously set one.
+	///
+	/// HTLCs in excess of the limits are failed back with
+	/// [`LocalHTLCFailureReason::PeerReputationLimit`] once irrevocably committed to the channel.
+	/// As this doesn't require renegotiating the channel with the peer, the limits may change
+	/// freely, e.g., as the peer's reputation evolves.
+	///
+	/// The [`PeerReputation`] is not persisted and must be set again each time the
+	/// [`ChannelManager`] is deserialized.
+	///
+	/// [`InboundHTLCLimits`]: crate::ln::peer_misbehavior::InboundHTLCLimits
+	pub fn set_peer_reputation<P: PeerReputation + Send + Sync + 'static>(&self, reputation: P) {
+		*self.peer_reputation.write().unwrap() = Some(Box::new(reputation));
+	}
+
+	/// Sets the [`ForceCloseObserver`] consulted before and notified after we force-close a channel
+	/// on our own accord, replacing any previously set one.
+	///
+	/// The observer is not persisted and must be set again each time the [`ChannelManager`] is
+	/// deserialized.
+	pub fn set_force_close_observer<O: ForceCloseObserver + Send + Sync + 'static>(
+		&self, observer: O,
+	) {
+		*self.force_close_observer.write().unwrap() = Some(Box::new(observer));
+	}
+
+	/// Sets the [`ForwardingPolicy`] consulted before we forward HTLCs, replacing any previously
+	/// set one.
+	///
+	/// The policy is not persisted and must be set again each time the [`ChannelManager`] is
+	/// deserialized. HTLCs it deferred remain pending until they are forwarded or failed.
+	pub fn set_forwarding_policy<P: ForwardingPolicy + Send + Sync + 'static>(&self, policy: P) {
+		*self.forwarding_policy.write().unwrap() = Some(Box::new(policy));
+	}
+
+	/// Enables surge pricing of our channels' forwarding fees based on their outbound liquidity
+	/// with the given [`SurgePricingConfig`], replacing any previously set one, or disables it if
+	/// `None`.
+	///
+	/// This behaves like a [`SurgePricer`] driven by the [`ChannelManager`] itself: fees are
+	/// adjusted, and new [`ChannelUpdate`]s broadcast, on each call to
+	/// [`Self::timer_tick_occurred`]. Fees are multiples of those of the default [`ChannelConfig`]
+	/// set via [`Self::set_current_config`]. Disabling surge pricing leaves channels' fees at their
+	/// current values. See the [`surge_pricing`] module documentation for details.
+	///
+	/// The config is not persisted and must be set again each time the [`ChannelManager`] is
+	/// deserialized. Surge pricing then resumes from the channels' current fees.
+	///
+	/// [`SurgePricer`]: crate::ln::surge_pricing::SurgePricer
+	/// [`ChannelUpdate`]: msgs::ChannelUpdate
+	/// [`surge_pricing`]: crate::ln::surge_pricing
+	pub fn set_dynamic_fee_policy(&self, config: Option<SurgePricingConfig>) {
+		*self.dynamic_fees.lock().unwrap() = config.map(SurgePricingState::new);
+	}
+
+	/// Consults the [`ForceCloseObserver`], if any, before force-closing the given channel for the
+	/// given `reason`, returning whether the closure was vetoed.
+	///
+	/// Vetoes are only honored for discretionary reasons and for up to
+	/// [`FORCE_CLOSE_VETO_TIMEOUT_BLOCKS`] after the first veto.
+	fn is_force_close_vetoed(
+		&self, channel_id: &ChannelId, counterparty_node_id: &PublicKey, reason: &ForceCloseReason,
+		height: u32,
+	) -> bool {
+		let decision = match self.force_close_observer.read().unwrap().as_ref() {
+			Some(observer) => observer.before_force_close(channel_id, counterparty_node_id, reason),
+			None => return false,
+		};
+		if !reason.is_discretionary() {
+			return false;
+		}
+		// The entry is kept around if we proceed so that we don't consult the observer again once
+		// the closure is actually processed.
+		let first_height =
+			*self.force_close_vetoes.lock().unwrap().entry(*channel_id).or_insert(height);
+		match decision {
+			ForceCloseDecision::Proceed => false,
+			ForceCloseDecision::Veto => {
+				if height.saturating_sub(first_height) < FORCE_CLOSE_VETO_TIMEOUT_BLOCKS {
+					true
+				} else {
+					let logger = WithContext::from(
+						&self.logger,
+						Some(*counterparty_node_id),
+						Some(*channel_id),
+						None,
+					);
+					log_info!(
+						logger,
+						"Ignoring force-close veto in place since block {first_height}"
+					);
+					false
+				}
+			},
+		}
+	}
+
 	#[cfg(test)]
 	pub fn create_and_insert_outbound_scid_alias_for_test(&self) -> u64 {
 		self.create_and_insert_outbound_scid_alias()
@@ -4182,11 +4908,454 @@ where
 		Ok(temporary_channel_id)
 	}
 
-	fn list_funded_channels_with_filter<
-		Fn: FnMut(&(&InitFeatures, &ChannelId, &Channel<SP>)) -> bool,
-	>(
-		&self, mut f: Fn,
-	) -> Vec<ChannelDetails> {
+	/// Creates a new outbound dual-funded channel to the given remote node using the interactive
+	/// transaction construction protocol (i.e., V2 channel establishment), with the counterparty
+	/// being able to contribute to the funding transaction as well.
+	///
+	/// The `contribution` determines the amount we contribute to the channel along with the
+	/// inputs, selected from the on-chain wallet, used to fund it. The inputs must cover the
+	/// contributed amount plus our share of the funding transaction's fees at
+	/// `funding_feerate_per_kw`. As the opener, we pay for the transaction's common fields and the
+	/// shared funding output. The counterparty may contribute to the channel as well, e.g. via
+	/// [`ChannelManager::accept_inbound_channel_with_contribution`] if it runs LDK.
+	///
+	/// Note that replacing the negotiated funding transaction with one paying a higher feerate
+	/// (i.e. via `tx_init_rbf`) is not yet supported.
+	///
+	/// `user_channel_id` has no meaning inside of LDK, it is simply copied to events and
+	/// otherwise ignored.
+	///
+	/// Both our and the counterparty's node must support dual-funded channels, see
+	/// [`UserConfig::enable_dual_funded_channels`].
+	///
+	/// # Events
+	///
+	/// Once the funding transaction has been negotiated and initial commitment signatures have
+	/// been exchanged, [`Event::FundingTransactionReadyForSigning`] will be generated and
+	/// [`ChannelManager::funding_transaction_signed`] should be called with the signed funding
+	/// transaction. Until then, the contributed inputs have not been signed for and may be
+	/// re-spent if the channel is closed, as indicated by an [`Event::ChannelClosed`].
+	///
+	/// Returns the new channel's temporary `channel_id`. Once the counterparty accepts the
+	/// channel, it is swapped for one derived from both parties' revocation basepoints.
+	///
+	/// [`UserConfig::enable_dual_funded_channels`]: crate::util::config::UserConfig::enable_dual_funded_channels
+	pub fn create_dual_funded_channel(
+		&self, their_network_key: PublicKey, contribution: FundingContribution,
+		funding_feerate_per_kw: u32, user_channel_id: u128, override_config: Option<UserConfig>,
+	) -> Result<ChannelId, APIError> {
+		self.create_dual_funded_channel_internal(
+			their_network_key,
+			contribution,
+			funding_feerate_per_kw,
+			None,
+			user_channel_id,
+			override_config,
+		)
+	}
+
+	/// Creates a new outbound dual-funded channel to a node selling inbound liquidity, requesting
+	/// it contribute `requested_sats` to the channel at one of the rates from its
+	/// [`LiquidityAd`].
+	///
+	/// Sellers may be found using [`ReadOnlyNetworkGraph::liquidity_sellers`], which also provides
+	/// the `rate` to use. The resulting lease fee, computed using `funding_feerate_per_kw`, is paid
+	/// to the seller out of our balance in the channel, and thus must not exceed our
+	/// `contribution`.
+	///
+	/// If the seller contributes less than `requested_sats`, the channel is closed before any
+	/// funding transaction is signed, generating an [`Event::ChannelClosed`].
+	///
+	/// Otherwise, behaves like [`ChannelManager::create_dual_funded_channel`].
+	///
+	/// [`LiquidityAd`]: crate::ln::liquidity_ads::LiquidityAd
+	/// [`ReadOnlyNetworkGraph::liquidity_sellers`]: crate::routing::gossip::ReadOnlyNetworkGraph::liquidity_sellers
+	pub fn request_channel_with_lease(
+		&self, their_network_key: PublicKey, contribution: FundingContribution,
+		requested_sats: u64, rate: LiquidityAdRate, funding_feerate_per_kw: u32,
+		user_channel_id: u128, override_config: Option<UserConfig>,
+	) -> Result<ChannelId, APIError> {
+		if !rate.supports_amount(requested_sats) {
+			return Err(APIError::APIMisuseError {
+				err: format!(
+					"Requested {} sats, outside of the rate's range of {} to {} sats",
+					requested_sats, rate.min_funding_satoshis, rate.max_funding_satoshis,
+				),
+			});
+		}
+		let lease_fee_sat = rate.lease_fee_sat(requested_sats, funding_feerate_per_kw);
+		if lease_fee_sat > contribution.value.to_sat() {
+			return Err(APIError::APIMisuseError {
+				err: format!(
+					"Lease fee of {} sats exceeds our contribution of {} sats",
+					lease_fee_sat,
+					contribution.value.to_sat(),
+				),
+			});
+		}
+		self.create_dual_funded_channel_internal(
+			their_network_key,
+			contribution,
+			funding_feerate_per_kw,
+			Some(RequestFunds { requested_sats, rate }),
+			user_channel_id,
+			override_config,
+		)
+	}
+
+	fn create_dual_funded_channel_internal(
+		&self, their_network_key: PublicKey, contribution: FundingContribution,
+		funding_feerate_per_kw: u32, requested_funds: Option<RequestFunds>, user_channel_id: u128,
+		override_config: Option<UserConfig>,
+	) -> Result<ChannelId, APIError> {
+		let FundingContribution { value, inputs, change_script } = contribution;
+		let funding_satoshis = value.to_sat();
+		if funding_satoshis < 1000 {
+			return Err(APIError::APIMisuseError {
+				err: format!(
+					"Channel contribution must be at least 1000 satoshis. It was {}",
+					funding_satoshis
+				),
+			});
+		}
+		if !self.init_features().supports_dual_fund() {
+			return Err(APIError::APIMisuseError {
+				err: "Dual-funded channels are not enabled".to_owned(),
+			});
+		}
+
+		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
+		// We want to make sure the lock is actually acquired by PersistenceNotifierGuard.
+		debug_assert!(&self.total_consistency_lock.try_write().is_err());
+
+		let per_peer_state = self.per_peer_state.read().unwrap();
+
+		let peer_state_mutex =
+			per_peer_state.get(&their_network_key).ok_or_else(|| APIError::APIMisuseError {
+				err: format!("Not connected to node: {}", their_network_key),
+			})?;
+
+		let mut peer_state = peer_state_mutex.lock().unwrap();
+		if !peer_state.is_connected {
+			return Err(APIError::APIMisuseError {
+				err: format!("Not connected to node: {}", their_network_key),
+			});
+		}
+		if !peer_state.latest_features.supports_dual_fund() {
+			return Err(APIError::ChannelUnavailable {
+				err: "Peer does not support dual-funded channels".to_owned(),
+			});
+		}
+
+		let channel = {
+			let outbound_scid_alias = self.create_and_insert_outbound_scid_alias();
+			let their_features = &peer_state.latest_features;
+			let config = self.config.read().unwrap();
+			let config = if let Some(config) = &override_config { config } else { &*config };
+			match PendingV2Channel::new_outbound(
+				&self.fee_estimator,
+				&self.entropy_source,
+				&self.signer_provider,
+				their_network_key,
+				their_features,
+				funding_satoshis,
+				inputs,
+				change_script,
+				user_channel_id,
+				config,
+				self.best_block.read().unwrap().height,
+				outbound_scid_alias,
+				funding_feerate_per_kw,
+				requested_funds,
+				&*self.logger,
+			) {
+				Ok(res) => res,
+				Err(e) => {
+					self.outbound_scid_aliases.lock().unwrap().remove(&outbound_scid_alias);
+					return Err(e);
+				},
+			}
+		};
+		let msg = channel.get_open_channel_v2(self.chain_hash);
+
+		let temporary_channel_id = channel.context.channel_id();
+		match peer_state.channel_by_id.entry(temporary_channel_id) {
+			hash_map::Entry::Occupied(_) => {
+				if cfg!(fuzzing) {
+					return Err(APIError::APIMisuseError { err: "Fuzzy bad RNG".to_owned() });
+				} else {
+					panic!("RNG is bad???");
+				}
+			},
+			hash_map::Entry::Vacant(entry) => {
+				entry.insert(Channel::from(channel));
+			},
+		}
+
+		peer_state
+			.pending_msg_events
+			.push(MessageSendEvent::SendOpenChannelV2 { node_id: their_network_key, msg });
+		Ok(temporary_channel_id)
+	}
+
+	/// Opens, updates, and closes a loopback channel against an in-process counterparty, using
+	/// this node's own [`SignerProvider`], [`EntropySource`], and [`FeeEstimator`] on both sides
+	/// and the given [`Persist`]er for our side's [`ChannelMonitor`].
+	///
+	/// This drives the `open_channel`, `accept_channel`, `funding_created`, `funding_signed`, and
+	/// `channel_ready` handshake, a feerate update with a full round of `commitment_signed` and
+	/// `revoke_and_ack`, and a cooperative close to completion. Each step checks that each side's
+	/// signer produces signatures which the other side accepts, and that every resulting
+	/// [`ChannelMonitorUpdate`] is applied and persisted. It is intended to be run in CI of
+	/// downstream applications to validate the integration of these interfaces before going live.
+	///
+	/// The channels are never broadcast, tracked by this [`ChannelManager`], or handed to the
+	/// [`chain::Watch`]. Instead, our side's [`ChannelMonitor`] is persisted and updated via
+	/// `persister` much as a [`ChainMonitor`] would, and archived via
+	/// [`Persist::archive_persisted_channel`] once the self-test ends, whether or not it succeeded,
+	/// so that no monitor for the loopback channel is left behind. As the self-test is
+	/// driven synchronously, signers which return signatures asynchronously and persisters which
+	/// return [`ChannelMonitorUpdateStatus::InProgress`] will cause it to fail. Channels using
+	/// zero-fee commitments skip the feerate update as they don't support `update_fee`.
+	///
+	/// Returns `Err(())` if any step fails, in which case the reason is logged at
+	/// [`Level::Error`].
+	///
+	/// [`ChannelMonitor`]: crate::chain::channelmonitor::ChannelMonitor
+	/// [`Persist`]: crate::chain::chainmonitor::Persist
+	/// [`Persist::archive_persisted_channel`]: crate::chain::chainmonitor::Persist::archive_persisted_channel
+	/// [`ChainMonitor`]: crate::chain::chainmonitor::ChainMonitor
+	#[cfg(feature = "self_test")]
+	#[rustfmt::skip]
+	pub fn run_self_test<P: Deref>(&self, persister: P) -> Result<(), ()>
+	where
+		P::Target: chain::chainmonitor::Persist<<SP::Target as SignerProvider>::EcdsaSigner>,
+	{
+		const SELF_TEST_CHANNEL_VALUE_SATOSHIS: u64 = 1_000_000;
+		const SELF_TEST_MAX_CLOSING_SIGNED_ROUNDS: usize = 16;
+
+		let mut config = self.config.read().unwrap().clone();
+		// The funding transaction is never broadcast, so both sides send `channel_ready` right away.
+		config.channel_handshake_limits.trust_own_funding_0conf = true;
+		let our_features = provided_init_features(&config);
+		let best_block = *self.best_block.read().unwrap();
+		let counterparty_node_id = self.get_our_node_id();
+		let logger = WithContext::from(&self.logger, Some(counterparty_node_id), None, None);
+
+		let mut outbound = OutboundV1Channel::new(&self.fee_estimator, &self.entropy_source, &self.signer_provider,
+			counterparty_node_id, &our_features, SELF_TEST_CHANNEL_VALUE_SATOSHIS, 0, 0, &config,
+			best_block.height, 0, None, &*self.logger
+		).map_err(|e| log_error!(logger, "Self-test failed to create outbound channel: {:?}", e))?;
+		let open_channel = outbound.get_open_channel(self.chain_hash, &&logger)
+			.ok_or_else(|| log_error!(logger, "Self-test failed to get open_channel from signer"))?;
+
+		let mut inbound = InboundV1Channel::new(&self.fee_estimator, &self.entropy_source, &self.signer_provider,
+			counterparty_node_id, &self.channel_type_features(), &our_features, &open_channel, 0, &config,
+			best_block.height, &&logger, /*is_0conf=*/true
+		).map_err(|e| log_error!(logger, "Self-test failed to accept open_channel: {:?}", e))?;
+		let accept_channel = inbound.accept_inbound_channel(&&logger)
+			.ok_or_else(|| log_error!(logger, "Self-test failed to get accept_channel from signer"))?;
+		outbound.accept_channel(&accept_channel, &config.channel_handshake_limits, &our_features)
+			.map_err(|e| log_error!(logger, "Self-test failed to handle accept_channel: {:?}", e))?;
+
+		let funding_tx = Transaction {
+			version: bitcoin::transaction::Version::TWO,
+			lock_time: bitcoin::absolute::LockTime::ZERO,
+			input: Vec::new(),
+			output: vec![bitcoin::TxOut {
+				value: bitcoin::Amount::from_sat(SELF_TEST_CHANNEL_VALUE_SATOSHIS),
+				script_pubkey: outbound.funding.get_funding_redeemscript().to_p2wsh(),
+			}],
+		};
+		let funding_txo = OutPoint { txid: funding_tx.compute_txid(), index: 0 };
+		let funding_created = outbound.get_funding_created(funding_tx, funding_txo, false, &&logger)
+			.map_err(|(_, e)| log_error!(logger, "Self-test failed to fund channel: {:?}", e))?
+			.ok_or_else(|| log_error!(logger, "Self-test failed to get funding_created from signer"))?;
+
+		let (mut inbound, funding_signed, inbound_monitor) = inbound.funding_created(&funding_created, best_block, &self.signer_provider, &&logger)
+			.map_err(|(_, e)| log_error!(logger, "Self-test failed to handle funding_created: {:?}", e))?;
+		let funding_signed = funding_signed
+			.ok_or_else(|| log_error!(logger, "Self-test failed to get funding_signed from signer"))?;
+		let (mut outbound, outbound_monitor) = outbound.funding_signed(&funding_signed, best_block, &self.signer_provider, &&logger)
+			.map_err(|(_, e)| log_error!(logger, "Self-test failed to handle funding_signed: {:?}", e))?;
+
+		if inbound_monitor.get_funding_txo() != funding_txo || outbound_monitor.get_funding_txo() != funding_txo {
+			log_error!(logger, "Self-test ChannelMonitors disagree on the funding outpoint");
+			return Err(());
+		}
+
+		// Both sides' monitors share a `MonitorName`, so only ours is handed to the persister.
+		let monitor_name = outbound_monitor.persistence_key();
+		let status = persister.persist_new_channel(monitor_name, &outbound_monitor);
+		if status != ChannelMonitorUpdateStatus::Completed {
+			log_error!(logger, "Self-test persister returned {:?} for a new ChannelMonitor", status);
+			persister.archive_persisted_channel(monitor_name);
+			return Err(());
+		}
+
+		// Applies a `ChannelMonitorUpdate` to a side's monitor, persisting it if it's ours, and then
+		// resumes the channel as we would once the update completes.
+		let channel_id = outbound.context.channel_id();
+		let complete_monitor_update = |
+			chan: &mut FundedChannel<SP>, monitor: &ChannelMonitor<<SP::Target as SignerProvider>::EcdsaSigner>,
+			update: Option<ChannelMonitorUpdate>,
+		| -> Result<_, ()> {
+			if let Some(update) = update {
+				monitor.update_monitor(&update, &self.tx_broadcaster, &self.fee_estimator.0, &logger)
+					.map_err(|()| log_error!(logger, "Self-test failed to apply ChannelMonitorUpdate {}", update.update_id))?;
+				if chan.funding.is_outbound() {
+					let status = persister.update_persisted_channel(monitor_name, Some(&update), monitor);
+					if status != ChannelMonitorUpdateStatus::Completed {
+						log_error!(logger, "Self-test persister returned {:?} for ChannelMonitorUpdate {}",
+							status, update.update_id);
+						return Err(());
+					}
+				}
+			}
+			if !chan.is_awaiting_monitor_update() {
+				return Ok(None);
+			}
+			let outbound_scid_alias = chan.context.outbound_scid_alias();
+			Ok(Some(chan.monitor_updating_restored(&&logger, &self.node_signer, self.chain_hash, &config,
+				best_block.height, |htlc_id| {
+					self.path_for_release_held_htlc(htlc_id, outbound_scid_alias, &channel_id, &counterparty_node_id)
+				}
+			)))
+		};
+
+		// Everything after persisting our monitor is run in a closure so that the monitor is archived
+		// whether or not the self-test succeeds, leaving nothing behind in the persister.
+		let res = (|| -> Result<Transaction, ()> {
+			let outbound_ready = complete_monitor_update(&mut outbound, &outbound_monitor, None)?
+				.and_then(|updates| updates.channel_ready)
+				.ok_or_else(|| log_error!(logger, "Self-test failed to get channel_ready for outbound channel"))?;
+			let inbound_ready = complete_monitor_update(&mut inbound, &inbound_monitor, None)?
+				.and_then(|updates| updates.channel_ready)
+				.ok_or_else(|| log_error!(logger, "Self-test failed to get channel_ready for inbound channel"))?;
+			outbound.channel_ready(&inbound_ready, &self.node_signer, self.chain_hash, &config, &best_block, &&logger)
+				.map_err(|e| log_error!(logger, "Self-test failed to handle channel_ready: {:?}", e))?;
+			inbound.channel_ready(&outbound_ready, &self.node_signer, self.chain_hash, &config, &best_block, &&logger)
+				.map_err(|e| log_error!(logger, "Self-test failed to handle channel_ready: {:?}", e))?;
+			if !outbound.context.is_usable() || !inbound.context.is_usable() {
+				log_error!(logger, "Self-test channels are not usable after exchanging channel_ready");
+				return Err(());
+			}
+			log_info!(logger, "Self-test completed loopback channel open {}", funding_txo);
+
+			let channel_type = outbound.funding.get_channel_type().clone();
+			if channel_type.supports_anchor_zero_fee_commitments() {
+				log_info!(logger, "Self-test skipping feerate update as zero-fee commitments don't support update_fee");
+			} else {
+				// The outbound side sends `update_fee` and `commitment_signed`, receiving
+				// `revoke_and_ack` and `commitment_signed` in return, which it then revokes.
+				let feerate = selected_commitment_sat_per_1000_weight(&self.fee_estimator, &channel_type);
+				if !outbound.queue_update_fee(feerate, &self.fee_estimator, &&logger) {
+					log_error!(logger, "Self-test failed to queue update_fee to {} sat/kW", feerate);
+					return Err(());
+				}
+				let (update, _) = outbound.maybe_free_holding_cell_htlcs(&self.fee_estimator, &&logger);
+				let commitment_update = complete_monitor_update(&mut outbound, &outbound_monitor, update)?
+					.and_then(|updates| updates.commitment_update)
+					.ok_or_else(|| log_error!(logger, "Self-test failed to get update_fee from outbound channel"))?;
+				let (update_fee, commitment_signed) = match (&commitment_update.update_fee, &commitment_update.commitment_signed[..]) {
+					(Some(update_fee), [commitment_signed]) => (update_fee, commitment_signed),
+					_ => {
+						log_error!(logger, "Self-test got an unexpected commitment update from outbound channel");
+						return Err(());
+					},
+				};
+				inbound.update_fee(&self.fee_estimator, &**self.update_fee_policy.read().unwrap(), update_fee, &&logger)
+					.map_err(|e| log_error!(logger, "Self-test failed to handle update_fee: {:?}", e))?;
+				let update = inbound.commitment_signed(commitment_signed, &self.fee_estimator, &&logger)
+					.map_err(|e| log_error!(logger, "Self-test failed to handle commitment_signed: {:?}", e))?;
+				let inbound_updates = complete_monitor_update(&mut inbound, &inbound_monitor, update)?;
+				let (revoke_and_ack, commitment_update) = match inbound_updates {
+					Some(channel::MonitorRestoreUpdates { raa: Some(raa), commitment_update: Some(update), .. }) => (raa, update),
+					_ => {
+						log_error!(logger, "Self-test failed to get revoke_and_ack and commitment_signed from inbound channel");
+						return Err(());
+					},
+				};
+				let commitment_signed = match &commitment_update.commitment_signed[..] {
+					[commitment_signed] => commitment_signed,
+					_ => {
+						log_error!(logger, "Self-test got an unexpected commitment update from inbound channel");
+						return Err(());
+					},
+				};
+
+				let (_, _, update) = outbound.revoke_and_ack(&revoke_and_ack, &self.fee_estimator, &&logger, false)
+					.map_err(|e| log_error!(logger, "Self-test failed to handle revoke_and_ack: {:?}", e))?;
+				complete_monitor_update(&mut outbound, &outbound_monitor, update)?;
+				let update = outbound.commitment_signed(commitment_signed, &self.fee_estimator, &&logger)
+					.map_err(|e| log_error!(logger, "Self-test failed to handle commitment_signed: {:?}", e))?;
+				let revoke_and_ack = complete_monitor_update(&mut outbound, &outbound_monitor, update)?
+					.and_then(|updates| updates.raa)
+					.ok_or_else(|| log_error!(logger, "Self-test failed to get revoke_and_ack from outbound channel"))?;
+				let (_, _, update) = inbound.revoke_and_ack(&revoke_and_ack, &self.fee_estimator, &&logger, false)
+					.map_err(|e| log_error!(logger, "Self-test failed to handle revoke_and_ack: {:?}", e))?;
+				complete_monitor_update(&mut inbound, &inbound_monitor, update)?;
+
+				if outbound.context.get_feerate_sat_per_1000_weight() != feerate
+					|| inbound.context.get_feerate_sat_per_1000_weight() != feerate
+				{
+					log_error!(logger, "Self-test channels disagree on the feerate after update_fee");
+					return Err(());
+				}
+				log_info!(logger, "Self-test completed loopback channel update to {} sat/kW", feerate);
+			}
+
+			let (shutdown, update, _) = outbound.get_shutdown(&self.signer_provider, &our_features, None, None)
+				.map_err(|e| log_error!(logger, "Self-test failed to initiate shutdown: {:?}", e))?;
+			complete_monitor_update(&mut outbound, &outbound_monitor, update)?;
+			let (shutdown, update, _) = inbound.shutdown(&self.signer_provider, &our_features, &shutdown)
+				.map_err(|e| log_error!(logger, "Self-test failed to handle shutdown: {:?}", e))?;
+			complete_monitor_update(&mut inbound, &inbound_monitor, update)?;
+			let shutdown = shutdown
+				.ok_or_else(|| log_error!(logger, "Self-test failed to get shutdown from inbound channel"))?;
+			let (_, update, _) = outbound.shutdown(&self.signer_provider, &our_features, &shutdown)
+				.map_err(|e| log_error!(logger, "Self-test failed to handle shutdown: {:?}", e))?;
+			complete_monitor_update(&mut outbound, &outbound_monitor, update)?;
+
+			let (mut closing_signed, _) = outbound.maybe_propose_closing_signed(&self.fee_estimator, &&logger)
+				.map_err(|e| log_error!(logger, "Self-test failed to propose closing_signed: {:?}", e))?;
+			let (mut outbound_closing_tx, mut inbound_closing_tx) = (None, None);
+			for _ in 0..SELF_TEST_MAX_CLOSING_SIGNED_ROUNDS {
+				let msg = match closing_signed.take() { Some(msg) => msg, None => break };
+				let (response, closed) = inbound.closing_signed(&self.fee_estimator, &msg, &&logger)
+					.map_err(|e| log_error!(logger, "Self-test failed to handle closing_signed: {:?}", e))?;
+				inbound_closing_tx = inbound_closing_tx.or(closed.map(|(tx, _)| tx));
+				let msg = match response { Some(msg) => msg, None => break };
+				let (response, closed) = outbound.closing_signed(&self.fee_estimator, &msg, &&logger)
+					.map_err(|e| log_error!(logger, "Self-test failed to handle closing_signed: {:?}", e))?;
+				outbound_closing_tx = outbound_closing_tx.or(closed.map(|(tx, _)| tx));
+				closing_signed = response;
+			}
+			let closing_tx = match (outbound_closing_tx, inbound_closing_tx) {
+				(Some(outbound_tx), Some(inbound_tx)) if outbound_tx == inbound_tx => outbound_tx,
+				_ => {
+					log_error!(logger, "Self-test channels failed to agree on a closing transaction");
+					return Err(());
+				},
+			};
+			if closing_tx.input.iter().all(|input| input.previous_output != funding_txo.into_bitcoin_outpoint()) {
+				log_error!(logger, "Self-test closing transaction does not spend the funding outpoint");
+				return Err(());
+			}
+			Ok(closing_tx)
+		})();
+
+		persister.archive_persisted_channel(monitor_name);
+		let closing_tx = res?;
+		log_info!(logger, "Self-test completed loopback channel close {}", closing_tx.compute_txid());
+		Ok(())
+	}
+
+	fn list_funded_channels_with_filter<
+		Fn: FnMut(&(&InitFeatures, &ChannelId, &Channel<SP>)) -> bool,
+	>(
+		&self, mut f: Fn,
+	) -> Vec<ChannelDetails> {
 		// Allocate our best estimate of the number of channels we have in the `res`
 		// Vec. Sadly the `short_to_chan_info` map doesn't cover channels without
 		// a scid or a scid alias. Therefore reallocations may still occur, but is
@@ -4248,6 +5417,101 @@ where
 		res
 	}
 
+	/// Like [`Self::list_channels`], but gives up rather than waiting if any peer's state is
+	/// currently locked.
+	fn try_list_channels(&self) -> Option<Vec<ChannelDetails>> {
+		let mut res = Vec::with_capacity(self.short_to_chan_info.read().unwrap().len());
+		let best_block_height = self.best_block.read().unwrap().height;
+		let per_peer_state = self.per_peer_state.read().unwrap();
+		for (_cp_id, peer_state_mutex) in per_peer_state.iter() {
+			let peer_state = peer_state_mutex.try_lock().ok()?;
+			for (_, channel) in peer_state.channel_by_id.iter() {
+				res.push(ChannelDetails::from_channel(
+					channel,
+					best_block_height,
+					peer_state.latest_features.clone(),
+					&self.fee_estimator,
+				));
+			}
+		}
+		Some(res)
+	}
+
+	/// Gets a read-only snapshot of our channels and their balances, suitable for frequent polling
+	/// (e.g. by a monitoring dashboard).
+	///
+	/// Unlike [`Self::list_channels`], this never waits on a channel which is in the middle of
+	/// being updated. The snapshot is only rebuilt once our channels may have changed, and if any
+	/// of them are busy at the time (e.g. because we're processing HTLCs), the previous snapshot is
+	/// returned instead. Thus, the returned snapshot may be slightly out of date, but will catch up
+	/// on a later call.
+	pub fn channel_snapshot(&self) -> Arc<ChannelSnapshot> {
+		if self.channel_snapshot_stale.swap(false, Ordering::AcqRel) {
+			match self.try_list_channels() {
+				Some(channels) => {
+					let best_block_height = self.best_block.read().unwrap().height;
+					let snapshot = Arc::new(ChannelSnapshot { channels, best_block_height });
+					*self.channel_snapshot.lock().unwrap() = snapshot;
+				},
+				None => self.channel_snapshot_stale.store(true, Ordering::Release),
+			}
+		}
+		Arc::clone(&self.channel_snapshot.lock().unwrap())
+	}
+
+	/// Gets the position of the channel with the given `channel_id` and `counterparty_node_id` in
+	/// the channel state machine, i.e. its current [`ChannelLifecycleState`], the states it may
+	/// move to next and the messages it is waiting on from the counterparty.
+	///
+	/// Returns `None` if no such channel exists (anymore). Changes to a channel's
+	/// [`ChannelLifecycleState`] may also be tracked via [`Event::ChannelLifecycleStateChanged`]
+	/// by setting [`UserConfig::emit_channel_lifecycle_events`].
+	///
+	/// [`ChannelLifecycleState`]: crate::ln::channel_state::ChannelLifecycleState
+	pub fn channel_lifecycle_info(
+		&self, channel_id: &ChannelId, counterparty_node_id: &PublicKey,
+	) -> Option<ChannelLifecycleInfo> {
+		let per_peer_state = self.per_peer_state.read().unwrap();
+		let peer_state = per_peer_state.get(counterparty_node_id)?.lock().unwrap();
+		peer_state.channel_by_id.get(channel_id).map(|chan| chan.lifecycle_info())
+	}
+
+	/// Queues an [`Event::ChannelLifecycleStateChanged`] for each channel whose lifecycle state
+	/// changed since we last checked, if enabled via [`UserConfig::emit_channel_lifecycle_events`].
+	fn queue_channel_lifecycle_events(&self) {
+		if !self.config.read().unwrap().emit_channel_lifecycle_events {
+			return;
+		}
+		let mut lifecycle_events = Vec::new();
+		{
+			let per_peer_state = self.per_peer_state.read().unwrap();
+			for (counterparty_node_id, peer_state_mutex) in per_peer_state.iter() {
+				let mut peer_state_lock = peer_state_mutex.lock().unwrap();
+				let peer_state = &mut *peer_state_lock;
+				for (channel_id, chan) in peer_state.channel_by_id.iter_mut() {
+					let context = chan.context_mut();
+					let new_state = context.lifecycle_state();
+					let previous_state = context.last_reported_lifecycle_state.replace(new_state);
+					if previous_state != Some(new_state) {
+						lifecycle_events.push((
+							events::Event::ChannelLifecycleStateChanged {
+								channel_id: *channel_id,
+								counterparty_node_id: *counterparty_node_id,
+								user_channel_id: context.get_user_id(),
+								previous_state,
+								new_state,
+							},
+							None,
+						));
+					}
+				}
+			}
+		}
+		if !lifecycle_events.is_empty() {
+			self.pending_events.lock().unwrap().extend(lifecycle_events);
+		}
+	}
+
 	/// Gets the list of usable channels, in random order. Useful as an argument to
 	/// [`Router::find_route`] to ensure non-announced channels are used.
 	///
@@ -4375,7 +5639,7 @@ where
 						}
 					} else {
 						let reason = ClosureReason::LocallyCoopClosedUnfundedChannel;
-						let err = ChannelError::Close((reason.to_string(), reason));
+						let err = ChannelError::Close((reason.to_string(), reason, None));
 						let mut chan = chan_entry.remove();
 						let (_, mut e) = convert_channel_err!(self, peer_state, err, &mut chan);
 						e.dont_send_error_message();
@@ -4561,7 +5825,7 @@ where
 					let peer_state = &mut *peer_state_lock;
 					if let Some(mut chan) = peer_state.channel_by_id.remove(&channel_id) {
 						let reason = ClosureReason::FundingBatchClosure;
-						let err = ChannelError::Close((reason.to_string(), reason));
+						let err = ChannelError::Close((reason.to_string(), reason, None));
 						let (_, e) = convert_channel_err!(self, peer_state, err, &mut chan);
 						shutdown_results.push((Err(e), counterparty_node_id));
 					}
@@ -4574,6 +5838,20 @@ where
 			);
 		}
 
+		if let Some(reason) = ForceCloseReason::from_closure_reason(&shutdown_res.closure_reason) {
+			self.force_close_vetoes.lock().unwrap().remove(&shutdown_res.channel_id);
+			if let Some(observer) = self.force_close_observer.read().unwrap().as_ref() {
+				let broadcast_txid =
+					self.chain_monitor.get_broadcast_holder_commitment_txid(shutdown_res.channel_id);
+				observer.after_force_close(
+					&shutdown_res.channel_id,
+					&shutdown_res.counterparty_node_id,
+					&reason,
+					broadcast_txid,
+				);
+			}
+		}
+
 		{
 			let mut pending_events = self.pending_events.lock().unwrap();
 			pending_events.push_back((events::Event::ChannelClosed {
@@ -4637,7 +5915,7 @@ where
 
 		if let Some(mut chan) = peer_state.channel_by_id.remove(channel_id) {
 			log_error!(logger, "Force-closing channel");
-			let err = ChannelError::Close((message, reason));
+			let err = ChannelError::Close((message, reason, None));
 			let (_, mut e) = convert_channel_err!(self, peer_state, err, &mut chan);
 			mem::drop(peer_state_lock);
 			mem::drop(per_peer_state);
@@ -4684,32 +5962,417 @@ where
 		self.force_close_channel_with_peer(channel_id, &counterparty_node_id, reason)
 	}
 
-	/// Force closes a channel, immediately broadcasting the latest local transaction(s),
-	/// rejecting new HTLCs.
+	/// Force closes a channel, immediately broadcasting the latest local transaction(s),
+	/// rejecting new HTLCs.
+	///
+	/// The provided `error_message` is sent to connected peers for closing
+	/// channels and should be a human-readable description of what went wrong.
+	///
+	/// Fails if `channel_id` is unknown to the manager, or if the `counterparty_node_id`
+	/// isn't the counterparty of the corresponding channel.
+	pub fn force_close_broadcasting_latest_txn(
+		&self, channel_id: &ChannelId, counterparty_node_id: &PublicKey, error_message: String,
+	) -> Result<(), APIError> {
+		self.force_close_sending_error(channel_id, counterparty_node_id, error_message)
+	}
+
+	/// Force close all channels, immediately broadcasting the latest local commitment transaction
+	/// for each to the chain and rejecting new HTLCs on each.
+	///
+	/// The provided `error_message` is sent to connected peers for closing channels and should
+	/// be a human-readable description of what went wrong.
+	pub fn force_close_all_channels_broadcasting_latest_txn(&self, error_message: String) {
+		for chan in self.list_channels() {
+			let _ = self.force_close_broadcasting_latest_txn(
+				&chan.channel_id,
+				&chan.counterparty.node_id,
+				error_message.clone(),
+			);
+		}
+	}
+
+	/// Schedules the given channels to be closed cooperatively over time, e.g. when decommissioning
+	/// a routing node.
+	///
+	/// Rather than initiating all closures at once, we wait until the feerate our [`FeeEstimator`]
+	/// returns for [`ChannelCloseMinimum`] is low compared to the feerates seen over the last
+	/// [`CLOSE_FEERATE_WINDOW_BLOCKS`] blocks, or until the `deadline` is reached. Once a closure
+	/// with a given peer is due, all other scheduled closures with that peer are initiated at the
+	/// same time. Closures are initiated as if via [`Self::close_channel`] during
+	/// [`Self::timer_tick_occurred`].
+	///
+	/// If a channel's counterparty is not connected once the `deadline` is reached, the channel is
+	/// force-closed if [`CloseDeadline::force_close_if_unreachable`] is set, and otherwise closed
+	/// cooperatively once the counterparty reconnects.
+	///
+	/// Scheduling a channel which is already scheduled replaces its previous deadline. Fails
+	/// without scheduling any closures if any of the given channels is unknown.
+	///
+	/// Scheduled closures are not persisted and must be scheduled again after a restart. Their
+	/// progress can be monitored via [`Self::list_scheduled_closures`].
+	///
+	/// [`FeeEstimator`]: crate::chain::chaininterface::FeeEstimator
+	/// [`ChannelCloseMinimum`]: crate::chain::chaininterface::ConfirmationTarget::ChannelCloseMinimum
+	/// [`CLOSE_FEERATE_WINDOW_BLOCKS`]: crate::ln::closure_scheduler::CLOSE_FEERATE_WINDOW_BLOCKS
+	pub fn schedule_closures(
+		&self, channel_ids: Vec<ChannelId>, deadline: CloseDeadline,
+	) -> Result<(), APIError> {
+		let mut channels = Vec::with_capacity(channel_ids.len());
+		{
+			let per_peer_state = self.per_peer_state.read().unwrap();
+			for channel_id in channel_ids {
+				let counterparty_node_id =
+					per_peer_state.iter().find_map(|(node_id, peer_state_mutex)| {
+						let peer_state = peer_state_mutex.lock().unwrap();
+						peer_state.channel_by_id.contains_key(&channel_id).then_some(*node_id)
+					});
+				match counterparty_node_id {
+					Some(node_id) => channels.push((channel_id, node_id)),
+					None => {
+						return Err(APIError::ChannelUnavailable {
+							err: format!("Channel with id {} not found", channel_id),
+						})
+					},
+				}
+			}
+		}
+		self.closure_scheduler.lock().unwrap().schedule(channels, deadline);
+		Ok(())
+	}
+
+	/// Lists the closures scheduled via [`Self::schedule_closures`] along with their progress.
+	pub fn list_scheduled_closures(&self) -> Vec<ScheduledClosure> {
+		self.closure_scheduler.lock().unwrap().list()
+	}
+
+	/// Forgets all scheduled closures which have completed or failed, i.e. which are no longer
+	/// returned as [`ScheduledClosureStatus::Pending`], [`ScheduledClosureStatus::Initiated`] or
+	/// [`ScheduledClosureStatus::ForceClosed`] by [`Self::list_scheduled_closures`].
+	pub fn clear_completed_scheduled_closures(&self) {
+		self.closure_scheduler.lock().unwrap().clear_completed();
+	}
+
+	/// Initiates any closures scheduled via [`Self::schedule_closures`] which are due and notes
+	/// those which have completed.
+	fn process_scheduled_closures(&self) {
+		if !self.closure_scheduler.lock().unwrap().has_incomplete() {
+			return;
+		}
+
+		let height = self.best_block.read().unwrap().height;
+		let feerate =
+			self.fee_estimator.bounded_sat_per_1000_weight(ConfirmationTarget::ChannelCloseMinimum);
+
+		let incomplete_channels = self.closure_scheduler.lock().unwrap().incomplete_channels();
+		let mut connected_peers = new_hash_set();
+		let mut closed_channels = Vec::new();
+		{
+			let per_peer_state = self.per_peer_state.read().unwrap();
+			for (channel_id, counterparty_node_id) in incomplete_channels {
+				if let Some(peer_state_mutex) = per_peer_state.get(&counterparty_node_id) {
+					let peer_state = peer_state_mutex.lock().unwrap();
+					if peer_state.is_connected {
+						connected_peers.insert(counterparty_node_id);
+					}
+					if !peer_state.channel_by_id.contains_key(&channel_id) {
+						closed_channels.push(channel_id);
+					}
+				} else {
+					closed_channels.push(channel_id);
+				}
+			}
+		}
+
+		let actions = {
+			let mut closure_scheduler = self.closure_scheduler.lock().unwrap();
+			for channel_id in closed_channels.iter() {
+				closure_scheduler.set_status(channel_id, ScheduledClosureStatus::Closed);
+			}
+			closure_scheduler
+				.pending_actions(height, feerate, |node_id| connected_peers.contains(node_id))
+		};
+
+		for (channel_id, counterparty_node_id, action) in actions {
+			let logger =
+				WithContext::from(&self.logger, Some(counterparty_node_id), Some(channel_id), None);
+			let status = match action {
+				ScheduledClosureAction::Close => {
+					log_info!(logger, "Initiating scheduled close of channel {}", channel_id);
+					let res = self.close_channel(&channel_id, &counterparty_node_id);
+					match res {
+						Ok(()) => ScheduledClosureStatus::Initiated { height },
+						Err(e) => ScheduledClosureStatus::Failed { err: format!("{:?}", e) },
+					}
+				},
+				ScheduledClosureAction::ForceClose => {
+					log_info!(
+						logger,
+						"Force-closing channel {} as its closure deadline passed while our peer was unreachable",
+						channel_id
+					);
+					let msg = "Scheduled closure deadline reached while peer was unreachable";
+					let res = self.force_close_sending_error(
+						&channel_id,
+						&counterparty_node_id,
+						msg.to_owned(),
+					);
+					match res {
+						Ok(()) => ScheduledClosureStatus::ForceClosed { height },
+						Err(e) => ScheduledClosureStatus::Failed { err: format!("{:?}", e) },
+					}
+				},
+			};
+			self.closure_scheduler.lock().unwrap().set_status(&channel_id, status);
+		}
+	}
+
+	/// Surge prices the forwarding fees of channels whose outbound liquidity changed enough since
+	/// their last update, if enabled via [`Self::set_dynamic_fee_policy`].
+	fn apply_dynamic_fee_policy(&self) {
+		if self.dynamic_fees.lock().unwrap().is_none() {
+			return;
+		}
+
+		let channels = self.list_channels();
+		let default_config = self.config.read().unwrap().channel_config;
+		let updates = match self.dynamic_fees.lock().unwrap().as_mut() {
+			Some(state) => state.fee_updates(&channels, &default_config),
+			None => return,
+		};
+
+		for (counterparty_node_id, channel_id, multiplier_percent, config_update) in updates {
+			let logger =
+				WithContext::from(&self.logger, Some(counterparty_node_id), Some(channel_id), None);
+			log_info!(
+				logger,
+				"Surge pricing forwarding fees of channel {} at {}% of their baseline",
+				channel_id,
+				multiplier_percent
+			);
+			let res = self.update_partial_channel_config(
+				&counterparty_node_id,
+				&[channel_id],
+				&config_update,
+			);
+			match res {
+				Ok(()) => {
+					if let Some(state) = self.dynamic_fees.lock().unwrap().as_mut() {
+						state.fee_update_applied(&channel_id, multiplier_percent);
+					}
+				},
+				Err(e) => {
+					log_debug!(
+						logger,
+						"Failed to update forwarding fees of channel {}: {:?}",
+						channel_id,
+						e
+					);
+				},
+			}
+		}
+	}
+
+	/// Exports an encrypted static channel backup (SCB) of our funded channels.
+	///
+	/// The backup only changes when channels are opened or closed and allows recovering our
+	/// balance in the backed up channels via [`Self::recover_from_static_backup`] if all other
+	/// channel state was lost. It is encrypted with a key derived from
+	/// [`NodeSigner::get_peer_storage_key`] and can thus only be read by a node using the same
+	/// seed.
+	///
+	/// See the [`static_backup`] module documentation for what can and can't be recovered.
+	///
+	/// [`static_backup`]: crate::ln::static_backup
+	pub fn export_static_backup(&self) -> Vec<u8> {
+		let mut channels = Vec::new();
+		{
+			let per_peer_state = self.per_peer_state.read().unwrap();
+			for (counterparty_node_id, peer_state_mutex) in per_peer_state.iter() {
+				let peer_state = peer_state_mutex.lock().unwrap();
+				for chan in peer_state.channel_by_id.values().filter_map(Channel::as_funded) {
+					let channel_parameters = &chan.funding.channel_transaction_parameters;
+					if channel_parameters.funding_outpoint.is_none()
+						|| channel_parameters.counterparty_parameters.is_none()
+					{
+						continue;
+					}
+					channels.push(StaticChannelBackupEntry {
+						channel_id: chan.context.channel_id(),
+						counterparty_node_id: *counterparty_node_id,
+						channel_keys_id: chan.context.get_channel_keys_id(),
+						channel_parameters: channel_parameters.clone(),
+					});
+				}
+			}
+		}
+
+		// Keep any channels we're still recovering in the backup so that a new backup taken during
+		// recovery doesn't lose them.
+		for entry in self.static_backup_recovery.lock().unwrap().iter() {
+			if !channels.iter().any(|chan| chan.channel_id == entry.channel_id) {
+				channels.push(entry.clone());
+			}
+		}
+
+		let random_bytes = self.entropy_source.get_secure_random_bytes();
+		StaticChannelBackup { channels }
+			.encrypt(&self.node_signer.get_peer_storage_key(), &random_bytes)
+	}
+
+	/// Starts recovering our balance in the channels contained in the given static channel backup,
+	/// as exported via [`Self::export_static_backup`] by a node using the same seed.
+	///
+	/// For each backed up channel which we don't know about, we ask the counterparty to
+	/// force-close the channel (as described in `option_data_loss_protect`) whenever it connects,
+	/// and generate an [`Event::SpendableOutputs`] for our balance once the counterparty's
+	/// commitment transaction confirms. Thus, users need to connect to the channel counterparties
+	/// listed by [`Self::list_recovering_channels`]. If chain data is provided via a [`Filter`],
+	/// the funding outputs of the recovering channels must be registered with it as well.
+	///
+	/// Fails if the backup couldn't be decrypted.
+	///
+	/// [`Filter`]: crate::chain::Filter
+	pub fn recover_from_static_backup(&self, backup: Vec<u8>) -> Result<(), APIError> {
+		let backup = StaticChannelBackup::decrypt(backup, &self.node_signer.get_peer_storage_key())
+			.map_err(|_| APIError::APIMisuseError {
+				err: "Failed to decrypt static channel backup".to_owned(),
+			})?;
+
+		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
+		let per_peer_state = self.per_peer_state.read().unwrap();
+		for entry in backup.channels {
+			let logger = WithContext::from(
+				&self.logger,
+				Some(entry.counterparty_node_id),
+				Some(entry.channel_id),
+				None,
+			);
+			let peer_state_mutex_opt = per_peer_state.get(&entry.counterparty_node_id);
+			let mut peer_state_lock = peer_state_mutex_opt.map(|mutex| mutex.lock().unwrap());
+			if let Some(peer_state) = peer_state_lock.as_ref() {
+				if peer_state.channel_by_id.contains_key(&entry.channel_id) {
+					log_debug!(
+						logger,
+						"Not recovering channel {} as we still know about it",
+						entry.channel_id
+					);
+					continue;
+				}
+			}
+
+			{
+				let mut static_backup_recovery = self.static_backup_recovery.lock().unwrap();
+				if static_backup_recovery.iter().any(|e| e.channel_id == entry.channel_id) {
+					continue;
+				}
+				static_backup_recovery.push(entry.clone());
+			}
+			log_info!(logger, "Recovering channel {} from static channel backup", entry.channel_id);
+
+			if let Some(peer_state) = peer_state_lock.as_mut() {
+				if peer_state.is_connected {
+					peer_state.pending_msg_events.push(MessageSendEvent::SendChannelReestablish {
+						node_id: entry.counterparty_node_id,
+						msg: bogus_channel_reestablish(entry.channel_id),
+					});
+				}
+			}
+		}
+		Ok(())
+	}
+
+	/// Enters fund recovery mode, in which we tolerate our channel state being stale, e.g. after
+	/// restoring from an old backup.
 	///
-	/// The provided `error_message` is sent to connected peers for closing
-	/// channels and should be a human-readable description of what went wrong.
+	/// By default, we panic upon a counterparty proving via `option_data_loss_protect` that our
+	/// channel state is stale, as broadcasting our (revoked) commitment transaction would allow
+	/// them to claim all of the channel's funds. Once in recovery mode, we instead close such
+	/// channels without broadcasting and send the counterparty an `error`, prompting them to
+	/// broadcast their latest commitment transaction. Our balance in each such channel is then
+	/// tracked alongside the channels recovered via [`Self::recover_from_static_backup`] (and thus
+	/// listed in [`Self::list_recovering_channels`]), generating an [`Event::SpendableOutputs`]
+	/// once the counterparty's commitment transaction confirms. This works even if the
+	/// corresponding [`ChannelMonitor`]s were lost.
 	///
-	/// Fails if `channel_id` is unknown to the manager, or if the `counterparty_node_id`
-	/// isn't the counterparty of the corresponding channel.
-	pub fn force_close_broadcasting_latest_txn(
-		&self, channel_id: &ChannelId, counterparty_node_id: &PublicKey, error_message: String,
-	) -> Result<(), APIError> {
-		self.force_close_sending_error(channel_id, counterparty_node_id, error_message)
+	/// Note that any funds in HTLCs are lost, and that channels whose state isn't stale continue
+	/// operating normally.
+	///
+	/// Recovery mode is not persisted and thus needs to be entered again after the
+	/// [`ChannelManager`] is read from disk, before connecting to any peers.
+	///
+	/// [`ChannelMonitor`]: crate::chain::channelmonitor::ChannelMonitor
+	pub fn enter_recovery_mode(&self) {
+		let logger = WithContext::from(&self.logger, None, None, None);
+		log_info!(logger, "Entering recovery mode, tolerating stale channel state");
+		self.recovery_mode.store(true, Ordering::Release);
 	}
 
-	/// Force close all channels, immediately broadcasting the latest local commitment transaction
-	/// for each to the chain and rejecting new HTLCs on each.
-	///
-	/// The provided `error_message` is sent to connected peers for closing channels and should
-	/// be a human-readable description of what went wrong.
-	pub fn force_close_all_channels_broadcasting_latest_txn(&self, error_message: String) {
-		for chan in self.list_channels() {
-			let _ = self.force_close_broadcasting_latest_txn(
-				&chan.channel_id,
-				&chan.counterparty.node_id,
-				error_message.clone(),
+	/// Starts tracking our balance in a channel which was closed after our counterparty proved
+	/// that our state is stale while in [`Self::enter_recovery_mode`].
+	fn track_stale_channel_recovery(&self, chan: &FundedChannel<SP>) {
+		let channel_parameters = &chan.funding.channel_transaction_parameters;
+		if channel_parameters.funding_outpoint.is_none()
+			|| channel_parameters.counterparty_parameters.is_none()
+		{
+			return;
+		}
+		let mut static_backup_recovery = self.static_backup_recovery.lock().unwrap();
+		let channel_id = chan.context.channel_id();
+		if static_backup_recovery.iter().any(|entry| entry.channel_id == channel_id) {
+			return;
+		}
+		static_backup_recovery.push(StaticChannelBackupEntry {
+			channel_id,
+			counterparty_node_id: chan.context.get_counterparty_node_id(),
+			channel_keys_id: chan.context.get_channel_keys_id(),
+			channel_parameters: channel_parameters.clone(),
+		});
+	}
+
+	/// Lists the channels we're still recovering via [`Self::recover_from_static_backup`] or
+	/// [`Self::enter_recovery_mode`], i.e., for which we haven't yet seen the counterparty's
+	/// commitment transaction confirm.
+	pub fn list_recovering_channels(&self) -> Vec<StaticChannelBackupEntry> {
+		self.static_backup_recovery.lock().unwrap().clone()
+	}
+
+	/// Checks whether any of the given transactions spend the funding output of a channel we're
+	/// recovering via [`Self::recover_from_static_backup`], generating
+	/// [`Event::SpendableOutputs`] for our balance if so.
+	fn check_static_backup_recovery_spends(&self, txdata: &TransactionData) {
+		let mut recovered = Vec::new();
+		{
+			let mut static_backup_recovery = self.static_backup_recovery.lock().unwrap();
+			if static_backup_recovery.is_empty() {
+				return;
+			}
+			static_backup_recovery.retain(|entry| {
+				let recovered_outputs =
+					txdata.iter().find_map(|(_, tx)| entry.recovered_outputs(tx));
+				match recovered_outputs {
+					Some(outputs) => {
+						recovered.push((entry.channel_id, entry.counterparty_node_id, outputs));
+						false
+					},
+					None => true,
+				}
+			});
+		}
+
+		for (channel_id, counterparty_node_id, outputs) in recovered {
+			let logger =
+				WithContext::from(&self.logger, Some(counterparty_node_id), Some(channel_id), None);
+			log_info!(
+				logger,
+				"Counterparty commitment transaction for recovering channel {} confirmed with {} outputs to sweep",
+				channel_id,
+				outputs.len()
 			);
+			if !outputs.is_empty() {
+				let event =
+					events::Event::SpendableOutputs { outputs, channel_id: Some(channel_id) };
+				self.pending_events.lock().unwrap().push_back((event, None));
+			}
 		}
 	}
 
@@ -4762,6 +6425,32 @@ where
 		res
 	}
 
+	/// Initiate a splice which removes `amount` from the channel, paying it out on-chain to
+	/// `script_pubkey`.
+	///
+	/// This is a convenience wrapper around [`ChannelManager::splice_channel`] using a
+	/// [`SpliceContribution::SpliceOut`] with a single output. Fees for the splice transaction are
+	/// paid from our channel balance in addition to `amount`, using `funding_feerate_per_kw`.
+	///
+	/// The channel remains operational while the splice is negotiated and until it has been
+	/// locked, with payments continuing to be forwarded over both the current and the new
+	/// funding output. See [`ChannelManager::splice_channel`] for the events generated throughout
+	/// the splice.
+	pub fn splice_out(
+		&self, channel_id: &ChannelId, counterparty_node_id: &PublicKey, amount: Amount,
+		script_pubkey: ScriptBuf, funding_feerate_per_kw: u32, locktime: Option<u32>,
+	) -> Result<(), APIError> {
+		let contribution =
+			SpliceContribution::SpliceOut { outputs: vec![TxOut { value: amount, script_pubkey }] };
+		self.splice_channel(
+			channel_id,
+			counterparty_node_id,
+			contribution,
+			funding_feerate_per_kw,
+			locktime,
+		)
+	}
+
 	fn internal_splice_channel(
 		&self, channel_id: &ChannelId, counterparty_node_id: &PublicKey,
 		contribution: SpliceContribution, funding_feerate_per_kw: u32, locktime: Option<u32>,
@@ -4826,6 +6515,158 @@ where
 		}
 	}
 
+	/// Initiates quiescence on a channel, i.e., waits for all pending updates to the channel to be
+	/// irrevocably committed and then exchanges `stfu` with the counterparty, after which neither
+	/// side may propose new updates to the channel.
+	///
+	/// Quiescence is a prerequisite for protocols which change the channel fundamentally, such as
+	/// splicing or upgrading the commitment format, and requires the counterparty to support
+	/// `option_quiescence`.
+	///
+	/// # Events
+	///
+	/// Once the channel has become quiescent with us as the initiator, an
+	/// [`Event::ChannelQuiescent`] will be emitted. If the counterparty initiated quiescence at the
+	/// same time and won the tie-break, our request remains pending until their quiescence ends.
+	///
+	/// The channel then remains quiescent until [`ChannelManager::exit_quiescence`] is called or
+	/// the counterparty disconnects, while a request which has yet to complete is retried upon
+	/// reconnection. Unlike quiescence initiated for other purposes, we won't disconnect the
+	/// counterparty for failing to make progress while the channel is held quiescent, though the
+	/// counterparty may enforce its own timeout.
+	pub fn quiesce_channel(
+		&self, channel_id: &ChannelId, counterparty_node_id: &PublicKey,
+	) -> Result<(), APIError> {
+		let mut res = Ok(());
+		PersistenceNotifierGuard::optionally_notify(self, || {
+			let result = self.internal_quiesce_channel(channel_id, counterparty_node_id);
+			res = result;
+			match res {
+				Ok(_) => NotifyOption::DoPersist,
+				Err(_) => NotifyOption::SkipPersistNoEvents,
+			}
+		});
+		res
+	}
+
+	fn internal_quiesce_channel(
+		&self, channel_id: &ChannelId, counterparty_node_id: &PublicKey,
+	) -> Result<(), APIError> {
+		let per_peer_state = self.per_peer_state.read().unwrap();
+		let peer_state_mutex = per_peer_state.get(counterparty_node_id).ok_or_else(|| {
+			APIError::ChannelUnavailable {
+				err: format!(
+					"Can't find a peer matching the passed counterparty node_id {counterparty_node_id}"
+				),
+			}
+		})?;
+
+		let mut peer_state = peer_state_mutex.lock().unwrap();
+		if !peer_state.latest_features.supports_quiescence() {
+			return Err(APIError::ChannelUnavailable {
+				err: "Peer does not support quiescence".to_owned(),
+			});
+		}
+
+		match peer_state.channel_by_id.entry(*channel_id) {
+			hash_map::Entry::Occupied(mut chan_phase_entry) => {
+				if let Some(chan) = chan_phase_entry.get_mut().as_funded_mut() {
+					let logger = WithChannelContext::from(&self.logger, &chan.context, None);
+					let msg_opt = chan
+						.propose_quiescence(&&logger, QuiescentAction::Hold)
+						.map_err(|err| APIError::APIMisuseError { err: err.to_owned() })?;
+					if let Some(msg) = msg_opt {
+						peer_state.pending_msg_events.push(MessageSendEvent::SendStfu {
+							node_id: *counterparty_node_id,
+							msg,
+						});
+					}
+					Ok(())
+				} else {
+					Err(APIError::ChannelUnavailable {
+						err: format!(
+							"Channel with id {} is not funded, cannot quiesce it",
+							channel_id
+						),
+					})
+				}
+			},
+			hash_map::Entry::Vacant(_) => Err(APIError::ChannelUnavailable {
+				err: format!(
+					"Channel with id {} not found for the passed counterparty node_id {}",
+					channel_id, counterparty_node_id,
+				),
+			}),
+		}
+	}
+
+	/// Ends quiescence on a channel made quiescent via [`ChannelManager::quiesce_channel`], i.e.,
+	/// after the corresponding [`Event::ChannelQuiescent`] has been emitted.
+	///
+	/// As quiescence only ends once the update it was needed for completes, or upon reconnection,
+	/// this disconnects the counterparty. Once reconnected the channel resumes normal operation,
+	/// with any updates made while it was quiescent being sent.
+	///
+	/// Errors if the channel is not currently being held quiescent at our request.
+	pub fn exit_quiescence(
+		&self, channel_id: &ChannelId, counterparty_node_id: &PublicKey,
+	) -> Result<(), APIError> {
+		let mut res = Ok(());
+		PersistenceNotifierGuard::optionally_notify(self, || {
+			let result = self.internal_exit_quiescence(channel_id, counterparty_node_id);
+			res = result;
+			match res {
+				Ok(_) => NotifyOption::SkipPersistHandleEvents,
+				Err(_) => NotifyOption::SkipPersistNoEvents,
+			}
+		});
+		res
+	}
+
+	fn internal_exit_quiescence(
+		&self, channel_id: &ChannelId, counterparty_node_id: &PublicKey,
+	) -> Result<(), APIError> {
+		let per_peer_state = self.per_peer_state.read().unwrap();
+		let peer_state_mutex = per_peer_state.get(counterparty_node_id).ok_or_else(|| {
+			APIError::ChannelUnavailable {
+				err: format!(
+					"Can't find a peer matching the passed counterparty node_id {counterparty_node_id}"
+				),
+			}
+		})?;
+
+		let mut peer_state_lock = peer_state_mutex.lock().unwrap();
+		let peer_state = &mut *peer_state_lock;
+		let chan = peer_state
+			.channel_by_id
+			.get_mut(channel_id)
+			.and_then(|chan| chan.as_funded_mut())
+			.ok_or_else(|| APIError::ChannelUnavailable {
+				err: format!(
+					"Channel with id {} not found for the passed counterparty node_id {}",
+					channel_id, counterparty_node_id,
+				),
+			})?;
+		if !chan.stop_holding_quiescence() {
+			return Err(APIError::APIMisuseError {
+				err: format!("Channel {} is not being held quiescent", channel_id),
+			});
+		}
+
+		let logger = WithChannelContext::from(&self.logger, &chan.context, None);
+		log_debug!(logger, "Disconnecting peer to end quiescence");
+		peer_state.pending_msg_events.push(MessageSendEvent::HandleError {
+			node_id: *counterparty_node_id,
+			action: msgs::ErrorAction::DisconnectPeerWithWarning {
+				msg: msgs::WarningMessage {
+					channel_id: *channel_id,
+					data: "Disconnecting to end quiescence".to_owned(),
+				},
+			},
+		});
+		Ok(())
+	}
+
 	#[cfg(test)]
 	pub(crate) fn abandon_splice(
 		&self, channel_id: &ChannelId, counterparty_node_id: &PublicKey,
@@ -4987,7 +6828,15 @@ where
 		let outgoing_scid = match next_packet_details.outgoing_connector {
 			HopConnector::ShortChannelId(scid) => scid,
 			HopConnector::Trampoline(_) => {
-				return Err(LocalHTLCFailureReason::InvalidTrampolineForward);
+				if !self.config.read().unwrap().accept_trampoline_forwards {
+					return Err(LocalHTLCFailureReason::InvalidTrampolineForward);
+				}
+				// The route to the next Trampoline node is only computed once we process the
+				// forward, at which point the fee and CLTV budget is checked against it. For now,
+				// just make sure the CLTV budget isn't obviously insufficient.
+				let cur_height = self.best_block.read().unwrap().height + 1;
+				check_incoming_htlc_cltv(cur_height, next_packet_details.outgoing_cltv_value, msg.cltv_expiry)?;
+				return Ok(());
 			}
 		};
 		match self.do_funded_channel_callback(outgoing_scid, |chan: &mut FundedChannel<SP>| {
@@ -5167,10 +7016,24 @@ where
 	///
 	/// [`channel_update`]: msgs::ChannelUpdate
 	/// [`internal_closing_signed`]: Self::internal_closing_signed
-	#[rustfmt::skip]
 	fn get_channel_update_for_unicast(
 		&self, chan: &FundedChannel<SP>,
 	) -> Result<(msgs::ChannelUpdate, NodeId, NodeId), LightningError> {
+		let (unsigned, node_id_1, node_id_2) = self.build_unsigned_channel_update(chan)?;
+		let mut updates = self.sign_channel_updates(vec![(chan.context.channel_id(), unsigned)]);
+		debug_assert_eq!(updates.len(), 1);
+		Ok((updates.pop().unwrap(), node_id_1, node_id_2))
+	}
+
+	/// Builds the unsigned [`channel_update`] for the given channel (as well as our and our
+	/// counterparty's [`NodeId`]), returning an `Err` if the channel does not yet have an assigned
+	/// SCID. See [`Self::get_channel_update_for_unicast`] for when the result may be used.
+	///
+	/// [`channel_update`]: msgs::ChannelUpdate
+	#[rustfmt::skip]
+	fn build_unsigned_channel_update(
+		&self, chan: &FundedChannel<SP>,
+	) -> Result<(msgs::UnsignedChannelUpdate, NodeId, NodeId), LightningError> {
 		let logger = WithChannelContext::from(&self.logger, &chan.context, None);
 		log_trace!(logger, "Attempting to generate channel update");
 		let short_channel_id = match chan.funding.get_short_channel_id().or(chan.context.latest_inbound_scid_alias()) {
@@ -5198,22 +7061,64 @@ where
 			fee_proportional_millionths: chan.context.get_fee_proportional_millionths(),
 			excess_data: Vec::new(),
 		};
-		// Panic on failure to signal LDK should be restarted to retry signing the `ChannelUpdate`.
-		// If we returned an error and the `node_signer` cannot provide a signature for whatever
-		// reason`, we wouldn't be able to receive inbound payments through the corresponding
-		// channel.
-		let sig = self.node_signer.sign_gossip_message(msgs::UnsignedGossipMessage::ChannelUpdate(&unsigned)).unwrap();
 
 		Ok((
-			msgs::ChannelUpdate {
-				signature: sig,
-				contents: unsigned
-			},
+			unsigned,
 			if were_node_one { our_node_id } else { their_node_id },
 			if were_node_one { their_node_id } else { our_node_id },
 		))
 	}
 
+	/// Signs the given [`msgs::UnsignedChannelUpdate`]s, returning the signed messages in the same
+	/// order.
+	///
+	/// Updates whose contents match the last update we signed for the same channel reuse the
+	/// cached signature. All remaining updates are passed to the [`NodeSigner`] in a single
+	/// [`NodeSigner::sign_gossip_messages`] call.
+	fn sign_channel_updates(
+		&self, updates: Vec<(ChannelId, msgs::UnsignedChannelUpdate)>,
+	) -> Vec<msgs::ChannelUpdate> {
+		let mut signed = Vec::with_capacity(updates.len());
+		let mut to_sign = Vec::new();
+		{
+			let signed_channel_updates = self.signed_channel_updates.lock().unwrap();
+			for (idx, (channel_id, unsigned)) in updates.iter().enumerate() {
+				match signed_channel_updates.get(channel_id) {
+					Some((cached, _)) if cached.contents == *unsigned => {
+						signed.push(Some(cached.clone()))
+					},
+					_ => {
+						signed.push(None);
+						to_sign.push(idx);
+					},
+				}
+			}
+		}
+
+		if !to_sign.is_empty() {
+			let gossip_msgs = to_sign
+				.iter()
+				.map(|idx| msgs::UnsignedGossipMessage::ChannelUpdate(&updates[*idx].1))
+				.collect::<Vec<_>>();
+			// Panic on failure to signal LDK should be restarted to retry signing the
+			// `ChannelUpdate`. If we returned an error and the `node_signer` cannot provide a
+			// signature for whatever reason, we wouldn't be able to receive inbound payments
+			// through the corresponding channel.
+			let sigs = self.node_signer.sign_gossip_messages(&gossip_msgs).unwrap();
+			assert_eq!(sigs.len(), to_sign.len());
+
+			let mut signed_channel_updates = self.signed_channel_updates.lock().unwrap();
+			for (idx, signature) in to_sign.into_iter().zip(sigs.into_iter()) {
+				let (channel_id, contents) = &updates[idx];
+				let msg = msgs::ChannelUpdate { signature, contents: contents.clone() };
+				signed_channel_updates.insert(*channel_id, (msg.clone(), 0));
+				signed[idx] = Some(msg);
+			}
+		}
+
+		signed.into_iter().map(|msg| msg.unwrap()).collect()
+	}
+
 	#[cfg(any(test, feature = "_externalize_tests"))]
 	pub(crate) fn test_send_payment_along_path(
 		&self, path: &Path, payment_hash: &PaymentHash, recipient_onion: RecipientOnionFields,
@@ -5254,6 +7159,8 @@ where
 		debug_assert!(self.total_consistency_lock.try_write().is_err());
 		let prng_seed = self.entropy_source.get_secure_random_bytes();
 		let session_priv = SecretKey::from_slice(&session_priv_bytes[..]).expect("RNG is busted");
+		let endorse_htlc =
+			self.config.read().unwrap().htlc_endorsement_config.endorse_sent_payments;
 
 		let (onion_packet, htlc_msat, htlc_cltv) = onion_utils::create_payment_onion(
 			&self.secp_ctx,
@@ -5339,6 +7246,7 @@ where
 							onion_packet,
 							None,
 							hold_htlc_at_next_hop,
+							endorse_htlc,
 							&self.fee_estimator,
 							&&logger,
 						);
@@ -5549,6 +7457,10 @@ where
 	///
 	/// If these conditions aren’t met, the function will return [`Bolt11PaymentError::InvalidAmount`].
 	///
+	/// # Chain
+	/// If the invoice's [`Currency`] indicates it is meant to be paid on a different chain than the
+	/// one we operate on, the function will return [`Bolt11PaymentError::UnsupportedChain`].
+	///
 	/// # Custom Routing Parameters
 	/// Users can customize routing parameters via [`RouteParametersConfig`].
 	/// To use default settings, call the function with [`RouteParametersConfig::default`].
@@ -5556,6 +7468,9 @@ where
 		&self, invoice: &Bolt11Invoice, payment_id: PaymentId, amount_msats: Option<u64>,
 		route_params_config: RouteParametersConfig, retry_strategy: Retry,
 	) -> Result<(), Bolt11PaymentError> {
+		if !invoice.currency().is_compatible_with_chain_hash(self.chain_hash) {
+			return Err(Bolt11PaymentError::UnsupportedChain);
+		}
 		let best_block_height = self.best_block.read().unwrap().height;
 		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
 		self.pending_outbound_payments.pay_for_bolt11_invoice(
@@ -5575,6 +7490,175 @@ where
 		)
 	}
 
+	/// Pays a [`Bolt11Invoice`] like [`Self::pay_for_bolt11_invoice`], but falls back to a
+	/// spontaneous (keysend) payment to the invoice's payee if the invoice has expired or requires
+	/// features we don't support.
+	///
+	/// Falling back is only possible if the payee advertises support for keysend in the given
+	/// `payee_features`, which should generally be taken from the payee's latest node announcement
+	/// (e.g., via [`NetworkGraph::read_only`]). Otherwise, [`Bolt11PaymentError::InvoiceExpired`] or
+	/// [`Bolt11PaymentError::UnknownRequiredFeatures`] is returned.
+	///
+	/// Note that a keysend payment is not linked to the invoice's payment hash, and thus a payee
+	/// which isn't aware of this fallback may not credit it against the invoice. To allow payees to
+	/// match it nonetheless, the invoice's payment hash is included in the recipient onion as a
+	/// custom TLV of type [`INVOICE_PAYMENT_HASH_CUSTOM_TLV_TYPE`]. As we pick the payment preimage,
+	/// a successful keysend payment also doesn't provide a proof of payment for the invoice.
+	///
+	/// Returns the [`PaymentHash`] of the payment sent, which will differ from the invoice's
+	/// payment hash if we fell back to keysend.
+	///
+	/// [`NetworkGraph::read_only`]: crate::routing::gossip::NetworkGraph::read_only
+	/// [`INVOICE_PAYMENT_HASH_CUSTOM_TLV_TYPE`]: crate::ln::outbound_payment::INVOICE_PAYMENT_HASH_CUSTOM_TLV_TYPE
+	pub fn pay_for_bolt11_invoice_with_keysend_fallback(
+		&self, invoice: &Bolt11Invoice, payment_id: PaymentId, amount_msats: Option<u64>,
+		route_params_config: RouteParametersConfig, retry_strategy: Retry,
+		payee_features: &NodeFeatures,
+	) -> Result<PaymentHash, Bolt11PaymentError> {
+		if !invoice.currency().is_compatible_with_chain_hash(self.chain_hash) {
+			return Err(Bolt11PaymentError::UnsupportedChain);
+		}
+		let best_block_height = self.best_block.read().unwrap().height;
+		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
+		self.pending_outbound_payments.pay_for_bolt11_invoice_with_keysend_fallback(
+			invoice,
+			payment_id,
+			amount_msats,
+			route_params_config,
+			retry_strategy,
+			payee_features,
+			self.duration_since_epoch(),
+			&self.router,
+			self.list_usable_channels(),
+			|| self.compute_inflight_htlcs(),
+			&self.entropy_source,
+			&self.node_signer,
+			best_block_height,
+			&self.pending_events,
+			|args| self.send_payment_along_path(args),
+		)
+	}
+
+	/// Pays several [`Bolt11Invoice`]s as one batch, e.g., for batched withdrawals.
+	///
+	/// Routes for all payments are computed before any payment is sent, largest payment first,
+	/// with each route accounting for the liquidity claimed by the routes before it. The fee
+	/// budget given by the [`RouteParametersConfig::max_total_routing_fee_msat`] of `params` is
+	/// shared by all payments, with any budget left after routing split evenly between them for
+	/// retries. If [`BatchPaymentParameters::all_or_nothing`] is set, no payment is sent unless
+	/// all of them could be routed. See the [`batch_payment`] module documentation for details.
+	///
+	/// Each payment is then sent as if via [`Self::pay_for_bolt11_invoice`], with the outcome of
+	/// each tracked by the returned [`BatchPayment`] once it is handed the corresponding
+	/// [`Event::PaymentSent`] and [`Event::PaymentFailed`] events.
+	///
+	/// [`batch_payment`]: crate::ln::batch_payment
+	pub fn pay_for_bolt11_invoices(
+		&self, invoices: Vec<BatchedBolt11Invoice>, params: BatchPaymentParameters,
+		retry_strategy: Retry,
+	) -> Result<BatchPayment, BatchPaymentError> {
+		let mut payment_ids = new_hash_set();
+		let mut payments = Vec::with_capacity(invoices.len());
+		for batched in invoices {
+			let payment_id = batched.payment_id;
+			if !payment_ids.insert(payment_id) {
+				return Err(BatchPaymentError::DuplicatePaymentId(payment_id));
+			}
+			if !batched.invoice.currency().is_compatible_with_chain_hash(self.chain_hash) {
+				let err = Bolt11PaymentError::UnsupportedChain;
+				return Err(BatchPaymentError::InvalidInvoice { payment_id, err });
+			}
+			let amount_msat = bolt11_payment_amount(&batched.invoice, batched.amount_msats)
+				.map_err(|err| BatchPaymentError::InvalidInvoice { payment_id, err })?;
+			payments.push((batched, amount_msat));
+		}
+		// Route the largest payments first, as they are the hardest to fit.
+		payments.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+		let payer = self.get_our_node_id();
+		let usable_channels = self.list_usable_channels();
+		let first_hops = usable_channels.iter().collect::<Vec<_>>();
+		let mut inflight_htlcs = self.compute_inflight_htlcs();
+		let mut routed_payments = Vec::with_capacity(payments.len());
+		let mut total_fee_msat = 0u64;
+		for (batched, amount_msat) in payments {
+			let payment_params = PaymentParameters::from_bolt11_invoice(&batched.invoice)
+				.with_user_config_ignoring_fee_limit(params.route_params_config);
+			let route_params =
+				RouteParameters::from_payment_params_and_value(payment_params, amount_msat);
+			let route_res = self.router.find_route(
+				&payer,
+				&route_params,
+				Some(&first_hops),
+				inflight_htlcs.clone(),
+			);
+			match route_res {
+				Ok(route) => {
+					for path in route.paths.iter() {
+						inflight_htlcs.process_path(path, payer);
+					}
+					let fee_msat = route.get_total_fees();
+					total_fee_msat = total_fee_msat.saturating_add(fee_msat);
+					routed_payments.push((batched, Some(fee_msat)));
+				},
+				Err(e) => {
+					log_error!(
+						self.logger,
+						"Failed to find route for batched payment with id {}: {:?}",
+						batched.payment_id,
+						e
+					);
+					if params.all_or_nothing {
+						let payment_id = batched.payment_id;
+						return Err(BatchPaymentError::RouteNotFound { payment_id });
+					}
+					routed_payments.push((batched, None));
+				},
+			}
+		}
+
+		let routed_count = routed_payments.iter().filter(|(_, fee)| fee.is_some()).count() as u64;
+		let mut surplus_fee_per_payment_msat = None;
+		if let Some(max_fee_msat) = params.route_params_config.max_total_routing_fee_msat {
+			if total_fee_msat > max_fee_msat {
+				let required_fee_msat = total_fee_msat;
+				return Err(BatchPaymentError::FeeBudgetExceeded { required_fee_msat });
+			}
+			if routed_count != 0 {
+				surplus_fee_per_payment_msat = Some((max_fee_msat - total_fee_msat) / routed_count);
+			}
+		}
+
+		let mut batch = BatchPayment::new();
+		for (batched, fee_msat) in routed_payments {
+			let fee_msat = match fee_msat {
+				Some(fee_msat) => fee_msat,
+				None => {
+					let err =
+						Bolt11PaymentError::SendingFailed(RetryableSendFailure::RouteNotFound);
+					batch.push(batched.payment_id, BatchedPaymentStatus::NotSent(err));
+					continue;
+				},
+			};
+			let mut route_params_config = params.route_params_config;
+			if let Some(surplus_fee_msat) = surplus_fee_per_payment_msat {
+				route_params_config.max_total_routing_fee_msat = Some(fee_msat + surplus_fee_msat);
+			}
+			let status = match self.pay_for_bolt11_invoice(
+				&batched.invoice,
+				batched.payment_id,
+				batched.amount_msats,
+				route_params_config,
+				retry_strategy,
+			) {
+				Ok(()) => BatchedPaymentStatus::Pending,
+				Err(err) => BatchedPaymentStatus::NotSent(err),
+			};
+			batch.push(batched.payment_id, status);
+		}
+		Ok(batch)
+	}
+
 	/// Pays the [`Bolt12Invoice`] associated with the `payment_id` encoded in its `payer_metadata`.
 	///
 	/// The invoice's `payer_metadata` is used to authenticate that the invoice was indeed requested
@@ -5916,6 +8000,31 @@ where
 		)
 	}
 
+	/// Send a spontaneous payment carrying the given custom TLVs, e.g. podcasting 2.0 records.
+	///
+	/// The custom TLVs are validated as described in [`RecipientOnionFields::with_custom_tlvs`],
+	/// failing with [`RetryableSendFailure::InvalidCustomTlvs`] before anything is sent if they are
+	/// invalid. Otherwise, this behaves as [`send_spontaneous_payment`], with the recipient seeing
+	/// the TLVs in [`Event::PaymentClaimable::onion_fields`].
+	///
+	/// [`send_spontaneous_payment`]: Self::send_spontaneous_payment
+	/// [`Event::PaymentClaimable::onion_fields`]: crate::events::Event::PaymentClaimable::onion_fields
+	pub fn send_spontaneous_payment_with_custom_tlvs(
+		&self, payment_preimage: Option<PaymentPreimage>, custom_tlvs: Vec<(u64, Vec<u8>)>,
+		payment_id: PaymentId, route_params: RouteParameters, retry_strategy: Retry,
+	) -> Result<PaymentHash, RetryableSendFailure> {
+		let recipient_onion = RecipientOnionFields::spontaneous_empty()
+			.with_custom_tlvs(custom_tlvs)
+			.map_err(|()| RetryableSendFailure::InvalidCustomTlvs)?;
+		self.send_spontaneous_payment(
+			payment_preimage,
+			recipient_onion,
+			payment_id,
+			route_params,
+			retry_strategy,
+		)
+	}
+
 	/// Send a payment that is probing the given route for liquidity. We calculate the
 	/// [`PaymentHash`] of probes based on a static secret and a random [`PaymentId`], which allows
 	/// us to easily discern them from real payments.
@@ -5957,6 +8066,31 @@ where
 		self.send_preflight_probes(route_params, liquidity_limit_multiplier)
 	}
 
+	/// Sends payment probes for `amount_msat` towards the given `target` node, learning whether
+	/// there is sufficient liquidity along the paths of a route to it.
+	///
+	/// As with any probe, the HTLCs use a payment hash nobody knows the preimage for and are thus
+	/// failed back by the final hop without being held. Results are surfaced via
+	/// [`Event::ProbeSuccessful`] and [`Event::ProbeFailed`], which should be fed into the scorer,
+	/// e.g. by [`Prober::handle_event`].
+	///
+	/// This is equivalent to calling [`Self::send_spontaneous_preflight_probes`] with
+	/// [`MIN_FINAL_CLTV_EXPIRY_DELTA`] and the default liquidity limit multiplier. See [`Prober`]
+	/// for sending such probes subject to rate limiting and a budget.
+	///
+	/// [`Prober`]: crate::ln::probing::Prober
+	/// [`Prober::handle_event`]: crate::ln::probing::Prober::handle_event
+	pub fn probe_for_capacity(
+		&self, target: PublicKey, amount_msat: u64,
+	) -> Result<Vec<(PaymentHash, PaymentId)>, ProbeSendFailure> {
+		self.send_spontaneous_preflight_probes(
+			target,
+			amount_msat,
+			MIN_FINAL_CLTV_EXPIRY_DELTA as u32,
+			None,
+		)
+	}
+
 	/// Sends payment probes over all paths of a route that would be used to pay a route found
 	/// according to the given [`RouteParameters`].
 	///
@@ -6067,7 +8201,7 @@ where
 
 		macro_rules! abandon_chan { ($err: expr, $api_err: expr, $chan: expr) => { {
 			let counterparty;
-			let err = if let ChannelError::Close((msg, reason)) = $err {
+			let err = if let ChannelError::Close((msg, reason, _)) = $err {
 				let channel_id = $chan.context.channel_id();
 				counterparty = $chan.context.get_counterparty_node_id();
 				let shutdown_res = $chan.abandon_unfunded_chan(reason);
@@ -6413,7 +8547,7 @@ where
 						.and_then(|mut peer_state| peer_state.channel_by_id.remove(&channel_id).map(|chan| (chan, peer_state)))
 						.map(|(mut chan, mut peer_state_lock)| {
 							let reason = ClosureReason::ProcessingError { err: e.clone() };
-							let err = ChannelError::Close((e.clone(), reason));
+							let err = ChannelError::Close((e.clone(), reason, None));
 							let peer_state = &mut *peer_state_lock;
 							let (_, e) =
 								convert_channel_err!(self, peer_state, err, &mut chan);
@@ -6594,7 +8728,12 @@ where
 			"Broadcasting signed interactive funding transaction {}",
 			funding_tx.compute_txid()
 		);
-		self.tx_broadcaster.broadcast_transactions(&[funding_tx]);
+		let context = BroadcastContext {
+			broadcast_type: BroadcastType::Funding,
+			channel_id: Some(channel.context.channel_id()),
+			urgency: None,
+		};
+		self.tx_broadcaster.broadcast_transactions(&[funding_tx], context);
 		{
 			let mut pending_events = self.pending_events.lock().unwrap();
 			emit_channel_pending_event!(pending_events, channel);
@@ -6647,6 +8786,9 @@ where
 				});
 			};
 		}
+		// Collect the updated channels' `channel_update`s so that they can all be signed at once.
+		let mut unsigned_updates = Vec::new();
+		let mut update_destinations = Vec::new();
 		for channel_id in channel_ids {
 			if let Some(channel) = peer_state.channel_by_id.get_mut(channel_id) {
 				let mut config = channel.context().config();
@@ -6655,16 +8797,14 @@ where
 					continue;
 				}
 				if let Some(channel) = channel.as_funded() {
-					if let Ok((msg, node_id_1, node_id_2)) = self.get_channel_update_for_broadcast(channel) {
-						let mut pending_broadcast_messages = self.pending_broadcast_messages.lock().unwrap();
-						pending_broadcast_messages.push(MessageSendEvent::BroadcastChannelUpdate { msg, node_id_1, node_id_2 });
-					} else if peer_state.is_connected {
-						if let Ok((msg, _, _)) = self.get_channel_update_for_unicast(channel) {
-							peer_state.pending_msg_events.push(MessageSendEvent::SendChannelUpdate {
-								node_id: channel.context.get_counterparty_node_id(),
-								msg,
-							});
-						}
+					let broadcast = channel.context.should_announce()
+						&& channel.funding.get_short_channel_id().is_some();
+					if !broadcast && !peer_state.is_connected {
+						continue;
+					}
+					if let Ok((unsigned, node_id_1, node_id_2)) = self.build_unsigned_channel_update(channel) {
+						unsigned_updates.push((*channel_id, unsigned));
+						update_destinations.push((broadcast, node_id_1, node_id_2));
 					}
 				}
 				continue;
@@ -6678,6 +8818,18 @@ where
 				});
 			};
 		}
+		let signed_updates = self.sign_channel_updates(unsigned_updates);
+		for (msg, (broadcast, node_id_1, node_id_2)) in signed_updates.into_iter().zip(update_destinations) {
+			if broadcast {
+				let mut pending_broadcast_messages = self.pending_broadcast_messages.lock().unwrap();
+				pending_broadcast_messages.push(MessageSendEvent::BroadcastChannelUpdate { msg, node_id_1, node_id_2 });
+			} else {
+				peer_state.pending_msg_events.push(MessageSendEvent::SendChannelUpdate {
+					node_id: *counterparty_node_id,
+					msg,
+				});
+			}
+		}
 		Ok(())
 	}
 
@@ -6709,6 +8861,102 @@ where
 		self.update_partial_channel_config(counterparty_node_id, channel_ids, &(*config).into())
 	}
 
+	/// Updates the feerate of the given channel's commitment transaction to
+	/// `feerate_sat_per_1000_weight`, overriding the feerate we'd otherwise derive from our
+	/// [`FeeEstimator`].
+	///
+	/// The `update_fee` is sent regardless of [`ChannelConfig::min_update_fee_interval_ticks`],
+	/// [`ChannelConfig::max_update_fee_step_percent`] and
+	/// [`ChannelConfig::max_update_feerate_sat_per_1000_weight`]. Note, however, that
+	/// [`Self::timer_tick_occurred`] may move the feerate back towards our [`FeeEstimator`]'s
+	/// estimate once [`ChannelConfig::min_update_fee_interval_ticks`] have passed.
+	///
+	/// Returns [`APIMisuseError`] if we are not the channel funder, the channel uses zero-fee
+	/// commitments or the feerate is below [`FEERATE_FLOOR_SATS_PER_KW`].
+	///
+	/// Returns [`ChannelUnavailable`] if the channel is not found, cannot currently be updated
+	/// (e.g. because our peer is disconnected), or our balance cannot cover the commitment
+	/// transaction fee at the given feerate.
+	///
+	/// [`APIMisuseError`]: APIError::APIMisuseError
+	/// [`ChannelUnavailable`]: APIError::ChannelUnavailable
+	pub fn force_update_channel_fee(
+		&self, channel_id: &ChannelId, counterparty_node_id: &PublicKey,
+		feerate_sat_per_1000_weight: u32,
+	) -> Result<(), APIError> {
+		if feerate_sat_per_1000_weight < FEERATE_FLOOR_SATS_PER_KW {
+			return Err(APIError::APIMisuseError {
+				err: format!(
+					"The feerate of {} sat/kW is below the minimum of {}",
+					feerate_sat_per_1000_weight, FEERATE_FLOOR_SATS_PER_KW
+				),
+			});
+		}
+
+		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
+		let per_peer_state = self.per_peer_state.read().unwrap();
+		let peer_state_mutex = per_peer_state.get(counterparty_node_id).ok_or_else(|| {
+			APIError::ChannelUnavailable {
+				err: format!(
+					"Can't find a peer matching the passed counterparty node_id {}",
+					counterparty_node_id
+				),
+			}
+		})?;
+		let mut peer_state_lock = peer_state_mutex.lock().unwrap();
+		let peer_state = &mut *peer_state_lock;
+		let chan = peer_state
+			.channel_by_id
+			.get_mut(channel_id)
+			.and_then(Channel::as_funded_mut)
+			.ok_or_else(|| APIError::ChannelUnavailable {
+				err: format!(
+					"Channel with id {} not found for the passed counterparty node_id {}",
+					channel_id, counterparty_node_id
+				),
+			})?;
+
+		if !chan.funding.is_outbound() {
+			return Err(APIError::APIMisuseError {
+				err: format!(
+					"Cannot update the feerate of channel {} as we did not fund it",
+					channel_id
+				),
+			});
+		}
+		if chan.funding.get_channel_type().supports_anchor_zero_fee_commitments() {
+			return Err(APIError::APIMisuseError {
+				err: format!(
+					"Cannot update the feerate of channel {} as it uses zero-fee commitments",
+					channel_id
+				),
+			});
+		}
+		if !chan.context.is_usable() || !chan.context.is_live() {
+			return Err(APIError::ChannelUnavailable {
+				err: format!("Channel {} cannot currently be updated", channel_id),
+			});
+		}
+
+		let logger = WithChannelContext::from(&self.logger, &chan.context, None);
+		log_info!(
+			logger,
+			"Forcing a feerate change of channel {} from {} to {}",
+			channel_id,
+			chan.context.get_feerate_sat_per_1000_weight(),
+			feerate_sat_per_1000_weight
+		);
+		if !chan.queue_update_fee(feerate_sat_per_1000_weight, &self.fee_estimator, &&logger) {
+			return Err(APIError::ChannelUnavailable {
+				err: format!(
+					"Our balance in channel {} cannot cover the commitment transaction fee at {} sat/kW",
+					channel_id, feerate_sat_per_1000_weight
+				),
+			});
+		}
+		Ok(())
+	}
+
 	/// Attempts to forward an intercepted HTLC over the provided channel id and with the provided
 	/// amount to forward. Should only be called in response to an [`HTLCIntercepted`] event.
 	///
@@ -6726,6 +8974,9 @@ where
 	/// [`HTLCIntercepted::expected_outbound_amount_msat`] for more on forwarding a different amount
 	/// than expected.
 	///
+	/// Use [`ChannelManager::forward_modified_intercepted_htlc`] to also modify the outgoing CLTV
+	/// expiry, or [`ChannelManager::hold_intercepted_htlc`] to defer handling the HTLC.
+	///
 	/// Errors if the event was not handled in time, in which case the HTLC was automatically failed
 	/// backwards.
 	///
@@ -6737,6 +8988,52 @@ where
 	pub fn forward_intercepted_htlc(
 		&self, intercept_id: InterceptId, next_hop_channel_id: &ChannelId, next_node_id: PublicKey,
 		amt_to_forward_msat: u64,
+	) -> Result<(), APIError> {
+		self.forward_intercepted_htlc_internal(
+			intercept_id,
+			next_hop_channel_id,
+			next_node_id,
+			amt_to_forward_msat,
+			None,
+		)
+	}
+
+	/// Attempts to forward an intercepted HTLC over the provided channel id with the provided
+	/// amount and CLTV expiry. Should only be called in response to an [`HTLCIntercepted`] event.
+	///
+	/// This works like [`ChannelManager::forward_intercepted_htlc`], but allows modifying the
+	/// outgoing HTLC within the following bounds:
+	///  * `amt_to_forward_msat` may not exceed [`HTLCIntercepted::inbound_amount_msat`], i.e., we
+	///    never forward more than we received,
+	///  * `outgoing_cltv_expiry` may not be lower than
+	///    [`HTLCIntercepted::expected_outbound_cltv_expiry`], as the next hop would reject it,
+	///  * `outgoing_cltv_expiry` must leave at least [`MIN_CLTV_EXPIRY_DELTA`] blocks to
+	///    [`HTLCIntercepted::inbound_cltv_expiry`], so that we can safely claim the inbound HTLC.
+	///
+	/// If any of the bounds are violated, an [`APIMisuseError`] is returned and the HTLC remains
+	/// intercepted.
+	///
+	/// [`HTLCIntercepted`]: events::Event::HTLCIntercepted
+	/// [`HTLCIntercepted::inbound_amount_msat`]: events::Event::HTLCIntercepted::inbound_amount_msat
+	/// [`HTLCIntercepted::expected_outbound_cltv_expiry`]: events::Event::HTLCIntercepted::expected_outbound_cltv_expiry
+	/// [`HTLCIntercepted::inbound_cltv_expiry`]: events::Event::HTLCIntercepted::inbound_cltv_expiry
+	/// [`APIMisuseError`]: APIError::APIMisuseError
+	pub fn forward_modified_intercepted_htlc(
+		&self, intercept_id: InterceptId, next_hop_channel_id: &ChannelId, next_node_id: PublicKey,
+		amt_to_forward_msat: u64, outgoing_cltv_expiry: u32,
+	) -> Result<(), APIError> {
+		self.forward_intercepted_htlc_internal(
+			intercept_id,
+			next_hop_channel_id,
+			next_node_id,
+			amt_to_forward_msat,
+			Some(outgoing_cltv_expiry),
+		)
+	}
+
+	fn forward_intercepted_htlc_internal(
+		&self, intercept_id: InterceptId, next_hop_channel_id: &ChannelId, next_node_id: PublicKey,
+		amt_to_forward_msat: u64, outgoing_cltv_expiry: Option<u32>,
 	) -> Result<(), APIError> {
 		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
 
@@ -6789,14 +9086,24 @@ where
 			}
 		};
 
-		let payment = self
-			.pending_intercepted_htlcs
-			.lock()
-			.unwrap()
-			.remove(&intercept_id)
-			.ok_or_else(|| APIError::APIMisuseError {
-				err: format!("Payment with intercept id {} not found", log_bytes!(intercept_id.0)),
-			})?;
+		let payment = {
+			let mut pending_intercepts = self.pending_intercepted_htlcs.lock().unwrap();
+			let payment =
+				pending_intercepts.get(&intercept_id).ok_or_else(|| APIError::APIMisuseError {
+					err: format!(
+						"Payment with intercept id {} not found",
+						log_bytes!(intercept_id.0)
+					),
+				})?;
+			if let Some(outgoing_cltv_expiry) = outgoing_cltv_expiry {
+				Self::check_intercepted_htlc_modification(
+					payment,
+					amt_to_forward_msat,
+					outgoing_cltv_expiry,
+				)?;
+			}
+			pending_intercepts.remove(&intercept_id).expect("Checked above")
+		};
 
 		let routing = match payment.forward_info.routing {
 			PendingHTLCRouting::Forward {
@@ -6804,6 +9111,7 @@ where
 				blinded,
 				incoming_cltv_expiry,
 				hold_htlc,
+				incoming_endorsed,
 				..
 			} => {
 				debug_assert!(hold_htlc.is_none(), "Held intercept HTLCs should not be surfaced in an event until the recipient comes online");
@@ -6812,6 +9120,7 @@ where
 					blinded,
 					incoming_cltv_expiry,
 					hold_htlc,
+					incoming_endorsed,
 					short_channel_id: outbound_scid_alias,
 				}
 			},
@@ -6822,6 +9131,8 @@ where
 		let pending_htlc_info = PendingHTLCInfo {
 			skimmed_fee_msat: if skimmed_fee_msat == 0 { None } else { Some(skimmed_fee_msat) },
 			outgoing_amt_msat: amt_to_forward_msat,
+			outgoing_cltv_value: outgoing_cltv_expiry
+				.unwrap_or(payment.forward_info.outgoing_cltv_value),
 			routing,
 			..payment.forward_info
 		};
@@ -6838,6 +9149,84 @@ where
 		Ok(())
 	}
 
+	fn check_intercepted_htlc_modification(
+		payment: &PendingAddHTLCInfo, amt_to_forward_msat: u64, outgoing_cltv_expiry: u32,
+	) -> Result<(), APIError> {
+		let forward_info = &payment.forward_info;
+		if let Some(incoming_amt_msat) = forward_info.incoming_amt_msat {
+			if amt_to_forward_msat > incoming_amt_msat {
+				return Err(APIError::APIMisuseError {
+					err: format!(
+						"Cannot forward {amt_to_forward_msat} msat as only {incoming_amt_msat} msat were received"
+					),
+				});
+			}
+		}
+		if outgoing_cltv_expiry < forward_info.outgoing_cltv_value {
+			return Err(APIError::APIMisuseError {
+				err: format!(
+					"Outgoing CLTV expiry {outgoing_cltv_expiry} is below the expected {}",
+					forward_info.outgoing_cltv_value
+				),
+			});
+		}
+		let max_outgoing_cltv_expiry = forward_info
+			.routing
+			.incoming_cltv_expiry()
+			.map(|expiry| expiry.saturating_sub(MIN_CLTV_EXPIRY_DELTA as u32))
+			.unwrap_or(forward_info.outgoing_cltv_value)
+			.max(forward_info.outgoing_cltv_value);
+		if outgoing_cltv_expiry > max_outgoing_cltv_expiry {
+			return Err(APIError::APIMisuseError {
+				err: format!(
+					"Outgoing CLTV expiry {outgoing_cltv_expiry} exceeds the maximum of {max_outgoing_cltv_expiry}"
+				),
+			});
+		}
+		Ok(())
+	}
+
+	/// Holds the intercepted HTLC indicated by `intercept_id` for at most `hold_for_blocks` blocks.
+	/// Should only be called in response to an [`HTLCIntercepted`] event.
+	///
+	/// If neither [`ChannelManager::forward_intercepted_htlc`] nor
+	/// [`ChannelManager::fail_intercepted_htlc`] has been called for the HTLC by the time the hold
+	/// expires, it will be failed backwards automatically. This allows deferring the decision on
+	/// how to handle the HTLC, e.g., while waiting for a channel to be opened, without having to
+	/// track a timeout externally. Calling this again replaces any previously set hold duration.
+	///
+	/// Without a hold, intercepted HTLCs are only failed backwards automatically once they come
+	/// close to expiring. Accordingly, an [`APIMisuseError`] is returned if the hold would last
+	/// beyond that point.
+	///
+	/// [`HTLCIntercepted`]: events::Event::HTLCIntercepted
+	/// [`APIMisuseError`]: APIError::APIMisuseError
+	pub fn hold_intercepted_htlc(
+		&self, intercept_id: InterceptId, hold_for_blocks: u32,
+	) -> Result<(), APIError> {
+		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
+
+		let height = self.best_block.read().unwrap().height;
+		let mut pending_intercepts = self.pending_intercepted_htlcs.lock().unwrap();
+		let payment =
+			pending_intercepts.get_mut(&intercept_id).ok_or_else(|| APIError::APIMisuseError {
+				err: format!("Payment with intercept id {} not found", log_bytes!(intercept_id.0)),
+			})?;
+
+		let hold_until_height = height.saturating_add(hold_for_blocks);
+		let expiry_height =
+			payment.forward_info.outgoing_cltv_value.saturating_sub(HTLC_FAIL_BACK_BUFFER);
+		if hold_until_height > expiry_height {
+			return Err(APIError::APIMisuseError {
+				err: format!(
+					"Cannot hold HTLC until height {hold_until_height} as it has to be failed back by height {expiry_height}"
+				),
+			});
+		}
+		payment.intercept_hold_until_height = Some(hold_until_height);
+		Ok(())
+	}
+
 	/// Fails the intercepted HTLC indicated by intercept_id. Should only be called in response to
 	/// an [`HTLCIntercepted`] event. See [`ChannelManager::forward_intercepted_htlc`].
 	///
@@ -6928,6 +9317,15 @@ where
 				// The incoming channel no longer exists, HTLCs should be resolved onchain instead.
 				continue;
 			};
+			let inbound_htlc_limits =
+				self.peer_reputation.read().unwrap().as_ref().and_then(|reputation| {
+					let incidents = self.list_peer_misbehavior(&incoming_counterparty_node_id);
+					reputation.inbound_htlc_limits(
+						&incoming_counterparty_node_id,
+						&incoming_channel_id,
+						&incidents,
+					)
+				});
 
 			let mut htlc_forwards = Vec::new();
 			let mut htlc_fails = Vec::new();
@@ -6985,13 +9383,21 @@ where
 				// Process the HTLC on the incoming channel.
 				match self.do_funded_channel_callback(
 					incoming_scid_alias,
-					|chan: &mut FundedChannel<SP>| {
+					|chan: &mut FundedChannel<SP>| -> Result<(), LocalHTLCFailureReason> {
 						let logger = WithChannelContext::from(
 							&self.logger,
 							&chan.context,
 							Some(update_add_htlc.payment_hash),
 						);
-						chan.can_accept_incoming_htlc(&self.fee_estimator, &logger)
+						chan.can_accept_incoming_htlc(&self.fee_estimator, &logger)?;
+						if let Some(limits) = inbound_htlc_limits.as_ref() {
+							chan.check_inbound_htlc_limits(
+								update_add_htlc.htlc_id,
+								limits,
+								&logger,
+							)?;
+						}
+						Ok(())
 					},
 				) {
 					Some(Ok(_)) => {},
@@ -7066,6 +9472,10 @@ where
 			);
 			self.forward_htlcs(&mut [pending_forwards]);
 			for (htlc_fail, failure_type, failure_reason) in htlc_fails.drain(..) {
+				let failed_htlc_id = match &htlc_fail {
+					HTLCFailureMsg::Relay(fail_htlc) => fail_htlc.htlc_id,
+					HTLCFailureMsg::Malformed(fail_malformed_htlc) => fail_malformed_htlc.htlc_id,
+				};
 				let failure = match htlc_fail {
 					HTLCFailureMsg::Relay(fail_htlc) => HTLCForwardInfo::FailHTLC {
 						htlc_id: fail_htlc.htlc_id,
@@ -7090,6 +9500,10 @@ where
 						prev_channel_id: incoming_channel_id,
 						failure_type,
 						failure_reason: Some(failure_reason),
+						htlc_correlation_id: Some(HTLCCorrelationId::from_prev_hop(
+							&incoming_channel_id,
+							failed_htlc_id,
+						)),
 					},
 					None,
 				));
@@ -7152,15 +9566,33 @@ where
 		for (short_chan_id, mut pending_forwards) in forward_htlcs {
 			should_persist = NotifyOption::DoPersist;
 			if short_chan_id != 0 {
+				let mut deferred_forwards = Vec::new();
 				self.process_forward_htlcs(
 					short_chan_id,
 					&mut pending_forwards,
 					&mut failed_forwards,
 					&mut phantom_receives,
+					&mut deferred_forwards,
 				);
+				if !deferred_forwards.is_empty() {
+					// Deferred HTLCs were received before any added to the map while we were
+					// processing, so we keep them first.
+					let mut forward_htlcs = self.forward_htlcs.lock().unwrap();
+					let pending = forward_htlcs.entry(short_chan_id).or_insert_with(Vec::new);
+					pending.splice(0..0, deferred_forwards);
+				}
 			} else {
+				let (trampoline_forwards, mut pending_receives): (Vec<_>, Vec<_>) =
+					pending_forwards.into_iter().partition(|forward| match forward {
+						HTLCForwardInfo::AddHTLC(payment) => matches!(
+							payment.forward_info.routing,
+							PendingHTLCRouting::TrampolineForward { .. }
+						),
+						_ => false,
+					});
+				self.process_trampoline_forwards(trampoline_forwards, &mut failed_forwards);
 				self.process_receive_htlcs(
-					&mut pending_forwards,
+					&mut pending_receives,
 					&mut new_events,
 					&mut failed_forwards,
 				);
@@ -7236,6 +9668,7 @@ where
 								outgoing_cltv_value,
 								..
 							},
+						..
 					} = payment;
 					let logger = WithContext::from(
 						&self.logger,
@@ -7395,6 +9828,7 @@ where
 		&self, short_chan_id: u64, pending_forwards: &mut Vec<HTLCForwardInfo>,
 		failed_forwards: &mut Vec<FailedHTLCForward>,
 		phantom_receives: &mut Vec<PerSourcePendingForward>,
+		deferred_forwards: &mut Vec<HTLCForwardInfo>,
 	) {
 		let mut forwarding_counterparty = None;
 
@@ -7413,6 +9847,16 @@ where
 			},
 		};
 		forwarding_counterparty = Some(counterparty_node_id);
+		self.apply_forwarding_policy(
+			counterparty_node_id,
+			forward_chan_id,
+			pending_forwards,
+			failed_forwards,
+			deferred_forwards,
+		);
+		let height = self.best_block.read().unwrap().height;
+		let endorsement_config = self.config.read().unwrap().htlc_endorsement_config;
+		let now = self.duration_since_epoch();
 		let per_peer_state = self.per_peer_state.read().unwrap();
 		let peer_state_mutex_opt = per_peer_state.get(&counterparty_node_id);
 		if peer_state_mutex_opt.is_none() {
@@ -7434,9 +9878,13 @@ where
 					let htlc_source = HTLCSource::PreviousHopData(payment.htlc_previous_hop_data());
 					let PendingAddHTLCInfo {
 						prev_outbound_scid_alias,
+						prev_htlc_id,
+						prev_counterparty_node_id,
+						prev_channel_id,
 						forward_info:
 							PendingHTLCInfo {
 								payment_hash,
+								incoming_amt_msat,
 								outgoing_amt_msat,
 								outgoing_cltv_value,
 								routing,
@@ -7445,10 +9893,13 @@ where
 							},
 						..
 					} = payment;
-					let (onion_packet, blinded) = match routing {
-						PendingHTLCRouting::Forward { ref onion_packet, blinded, .. } => {
-							(onion_packet, blinded)
-						},
+					let (onion_packet, blinded, incoming_endorsed) = match routing {
+						PendingHTLCRouting::Forward {
+							ref onion_packet,
+							blinded,
+							incoming_endorsed,
+							..
+						} => (onion_packet, blinded, *incoming_endorsed),
 						_ => {
 							panic!("short_channel_id != 0 should imply any pending_forward entries are of type Forward");
 						},
@@ -7469,6 +9920,23 @@ where
 						})
 					});
 
+					let fee_msat = incoming_amt_msat
+						.unwrap_or(*outgoing_amt_msat)
+						.saturating_sub(*outgoing_amt_msat);
+					let endorsed = if endorsement_config.use_local_reputation {
+						incoming_endorsed
+							&& self.local_reputation.lock().unwrap().has_sufficient_reputation(
+								prev_counterparty_node_id,
+								fee_msat,
+								*outgoing_cltv_value,
+								height,
+								now,
+								&endorsement_config,
+							)
+					} else {
+						incoming_endorsed
+					};
+
 					// Forward the HTLC over the most appropriate channel with the corresponding peer,
 					// applying non-strict forwarding.
 					// The channel with the least amount of outbound liquidity will be used to maximize the
@@ -7482,7 +9950,9 @@ where
 							let is_in_range = (balances.next_outbound_htlc_minimum_msat
 								..=balances.next_outbound_htlc_limit_msat)
 								.contains(&outgoing_amt_msat);
-							if is_in_range && chan.context.is_usable() {
+							let has_capacity = endorsed
+								|| chan.check_unendorsed_htlc_capacity(*outgoing_amt_msat).is_ok();
+							if is_in_range && has_capacity && chan.context.is_usable() {
 								Some((chan, balances))
 							} else {
 								None
@@ -7533,7 +10003,7 @@ where
 						short_chan_id,
 						channel_description
 					);
-					if let Err((reason, msg)) = optimal_channel.queue_add_htlc(
+					let add_res = optimal_channel.queue_add_htlc(
 						*outgoing_amt_msat,
 						*payment_hash,
 						*outgoing_cltv_value,
@@ -7541,9 +10011,25 @@ where
 						onion_packet.clone(),
 						*skimmed_fee_msat,
 						next_blinding_point,
+						endorsed,
 						&self.fee_estimator,
 						&&logger,
-					) {
+					);
+					if add_res.is_ok() {
+						self.local_reputation.lock().unwrap().htlc_forwarded(
+							*prev_counterparty_node_id,
+							*prev_channel_id,
+							*prev_htlc_id,
+							incoming_endorsed,
+							endorsed,
+							fee_msat,
+							*outgoing_cltv_value,
+							height,
+							now,
+							&endorsement_config,
+						);
+					}
+					if let Err((reason, msg)) = add_res {
 						log_trace!(logger, "Failed to forward HTLC: {}", msg);
 
 						if let Some(chan) = peer_state
@@ -7651,6 +10137,307 @@ where
 		}
 	}
 
+	/// Consults the [`ForwardingPolicy`], if any, on the HTLCs in `pending_forwards` we're about to
+	/// forward to the given channel.
+	///
+	/// Rejected HTLCs are added to `failed_forwards` and deferred ones are moved to
+	/// `deferred_forwards`, while the remaining HTLC additions are ordered by priority after any
+	/// HTLC failures.
+	fn apply_forwarding_policy(
+		&self, next_counterparty_node_id: PublicKey, next_channel_id: ChannelId,
+		pending_forwards: &mut Vec<HTLCForwardInfo>, failed_forwards: &mut Vec<FailedHTLCForward>,
+		deferred_forwards: &mut Vec<HTLCForwardInfo>,
+	) {
+		let policy_lock = self.forwarding_policy.read().unwrap();
+		let policy = match policy_lock.as_ref() {
+			Some(policy) => policy,
+			None => return,
+		};
+		let height = self.best_block.read().unwrap().height;
+
+		let mut htlc_fails = Vec::new();
+		let mut htlc_adds = Vec::new();
+		for forward_info in pending_forwards.drain(..) {
+			let payment = match forward_info {
+				HTLCForwardInfo::AddHTLC(ref payment) => payment,
+				HTLCForwardInfo::FailHTLC { .. } | HTLCForwardInfo::FailMalformedHTLC { .. } => {
+					htlc_fails.push(forward_info);
+					continue;
+				},
+			};
+			let candidate = ForwardCandidate {
+				payment_hash: payment.forward_info.payment_hash,
+				prev_channel_id: payment.prev_channel_id,
+				prev_counterparty_node_id: payment.prev_counterparty_node_id,
+				next_channel_id,
+				next_counterparty_node_id,
+				inbound_amount_msat: payment.forward_info.incoming_amt_msat,
+				outbound_amount_msat: payment.forward_info.outgoing_amt_msat,
+				outgoing_cltv_expiry: payment.forward_info.outgoing_cltv_value,
+			};
+			let defer_deadline =
+				candidate.outgoing_cltv_expiry.saturating_sub(HTLC_FAIL_BACK_BUFFER);
+			let failure_description = match policy.evaluate_forward(&candidate) {
+				ForwardingDecision::Forward { priority } => {
+					htlc_adds.push((priority, forward_info));
+					continue;
+				},
+				ForwardingDecision::Defer if height < defer_deadline => {
+					deferred_forwards.push(forward_info);
+					continue;
+				},
+				ForwardingDecision::Defer => "deferred it until it was too close to expiring",
+				ForwardingDecision::Reject => "rejected it",
+			};
+
+			let logger = WithContext::from(
+				&self.logger,
+				Some(payment.prev_counterparty_node_id),
+				Some(payment.prev_channel_id),
+				Some(candidate.payment_hash),
+			);
+			log_info!(
+				logger,
+				"Failing HTLC to be forwarded over channel {} as our forwarding policy {}",
+				next_channel_id,
+				failure_description
+			);
+			let htlc_source = HTLCSource::PreviousHopData(payment.htlc_previous_hop_data());
+			let reason = LocalHTLCFailureReason::ForwardingPolicyRejected;
+			let data = self.get_htlc_inbound_temp_fail_data(reason);
+			let failure_type = HTLCHandlingFailureType::Forward {
+				node_id: Some(next_counterparty_node_id),
+				channel_id: next_channel_id,
+			};
+			failed_forwards.push((
+				htlc_source,
+				candidate.payment_hash,
+				HTLCFailReason::reason(reason, data),
+				failure_type,
+			));
+		}
+
+		// `sort_by` is stable, so HTLCs with the same priority remain in the order we received them.
+		htlc_adds.sort_by(|(a, _), (b, _)| b.cmp(a));
+		pending_forwards.extend(htlc_fails);
+		pending_forwards.extend(htlc_adds.into_iter().map(|(_, forward_info)| forward_info));
+	}
+
+	/// Forwards HTLCs carrying a Trampoline onion to the next Trampoline node, finding a route to it
+	/// and wrapping the inner Trampoline onion in a new outer onion.
+	fn process_trampoline_forwards(
+		&self, trampoline_forwards: Vec<HTLCForwardInfo>,
+		failed_forwards: &mut Vec<FailedHTLCForward>,
+	) {
+		for forward_info in trampoline_forwards {
+			let payment = match forward_info {
+				HTLCForwardInfo::AddHTLC(payment) => payment,
+				_ => {
+					debug_assert!(false, "Only HTLC additions are keyed by SCID 0");
+					continue;
+				},
+			};
+			let htlc_source = HTLCSource::PreviousHopData(payment.htlc_previous_hop_data());
+			if let Err((reason, failure_type)) =
+				self.forward_trampoline_htlc(&payment, htlc_source.clone())
+			{
+				let payment_hash = payment.forward_info.payment_hash;
+				let logger = WithContext::from(
+					&self.logger,
+					None,
+					Some(payment.prev_channel_id),
+					Some(payment_hash),
+				);
+				log_info!(logger, "Failed to forward Trampoline HTLC: {:?}", reason);
+				let data = if reason == LocalHTLCFailureReason::TrampolineFeeOrExpiryInsufficient {
+					let channel_config = self.config.read().unwrap().channel_config;
+					let mut data = VecWriter(Vec::with_capacity(10));
+					channel_config
+						.forwarding_fee_base_msat
+						.write(&mut data)
+						.expect("Writes cannot fail");
+					channel_config
+						.forwarding_fee_proportional_millionths
+						.write(&mut data)
+						.expect("Writes cannot fail");
+					channel_config.cltv_expiry_delta.write(&mut data).expect("Writes cannot fail");
+					data.0
+				} else {
+					Vec::new()
+				};
+				failed_forwards.push((
+					htlc_source,
+					payment_hash,
+					HTLCFailReason::reason(reason, data),
+					failure_type,
+				));
+			}
+		}
+	}
+
+	fn forward_trampoline_htlc(
+		&self, payment: &PendingAddHTLCInfo, htlc_source: HTLCSource,
+	) -> Result<(), (LocalHTLCFailureReason, HTLCHandlingFailureType)> {
+		let PendingHTLCInfo {
+			ref routing,
+			payment_hash,
+			incoming_amt_msat,
+			outgoing_amt_msat,
+			outgoing_cltv_value,
+			..
+		} = payment.forward_info;
+		let (onion_packet, next_trampoline, blinded, incoming_cltv_expiry) = match routing {
+			PendingHTLCRouting::TrampolineForward {
+				onion_packet,
+				node_id,
+				blinded,
+				incoming_cltv_expiry,
+				..
+			} => (onion_packet, *node_id, blinded, *incoming_cltv_expiry),
+			_ => {
+				debug_assert!(false, "Only Trampoline forwards should be passed here");
+				return Err((
+					LocalHTLCFailureReason::InvalidTrampolineForward,
+					HTLCHandlingFailureType::InvalidForward { requested_forward_scid: 0 },
+				));
+			},
+		};
+		let unknown_next_trampoline = || {
+			(
+				LocalHTLCFailureReason::UnknownNextTrampoline,
+				HTLCHandlingFailureType::InvalidForward { requested_forward_scid: 0 },
+			)
+		};
+		let budget_insufficient = || {
+			(
+				LocalHTLCFailureReason::TrampolineFeeOrExpiryInsufficient,
+				HTLCHandlingFailureType::InvalidForward { requested_forward_scid: 0 },
+			)
+		};
+		if !self.config.read().unwrap().accept_trampoline_forwards {
+			return Err((
+				LocalHTLCFailureReason::InvalidTrampolineForward,
+				HTLCHandlingFailureType::InvalidForward { requested_forward_scid: 0 },
+			));
+		}
+		// Relaying into a blinded path requires passing the next path key along in the outer
+		// onion, which we don't support yet.
+		if blinded.is_some() {
+			return Err(unknown_next_trampoline());
+		}
+
+		// The sender gives us the difference between the incoming HTLC and what the next
+		// Trampoline should receive as a budget, which has to cover our own fee and CLTV delta
+		// before we can spend the rest on the route.
+		let channel_config = self.config.read().unwrap().channel_config;
+		let incoming_amt_msat = incoming_amt_msat.unwrap_or(outgoing_amt_msat);
+		let our_fee_msat = (channel_config.forwarding_fee_base_msat as u64).saturating_add(
+			outgoing_amt_msat
+				.saturating_mul(channel_config.forwarding_fee_proportional_millionths as u64)
+				/ 1_000_000,
+		);
+		let fee_budget_msat = incoming_amt_msat
+			.checked_sub(outgoing_amt_msat)
+			.and_then(|budget| budget.checked_sub(our_fee_msat))
+			.ok_or_else(budget_insufficient)?;
+		let cltv_budget = incoming_cltv_expiry
+			.checked_sub(outgoing_cltv_value)
+			.and_then(|budget| budget.checked_sub(channel_config.cltv_expiry_delta as u32))
+			.ok_or_else(budget_insufficient)?;
+
+		let payment_params = PaymentParameters::from_node_id(next_trampoline, 0)
+			.with_max_total_cltv_expiry_delta(cltv_budget)
+			.with_max_path_count(1);
+		let mut route_params =
+			RouteParameters::from_payment_params_and_value(payment_params, outgoing_amt_msat);
+		route_params.max_total_routing_fee_msat = Some(fee_budget_msat);
+		let first_hops = self.list_usable_channels();
+		let route = self
+			.router
+			.find_route(
+				&self.get_our_node_id(),
+				&route_params,
+				Some(&first_hops.iter().collect::<Vec<_>>()),
+				self.compute_inflight_htlcs(),
+			)
+			.map_err(|_| unknown_next_trampoline())?;
+		let path = route.paths.first().ok_or_else(unknown_next_trampoline)?;
+		let first_hop = path.hops.first().ok_or_else(unknown_next_trampoline)?;
+
+		let session_priv_bytes = self.entropy_source.get_secure_random_bytes();
+		let session_priv = SecretKey::from_slice(&session_priv_bytes[..]).expect("RNG is busted");
+		let prng_seed = self.entropy_source.get_secure_random_bytes();
+		let (outgoing_onion, htlc_msat, htlc_cltv) = onion_utils::create_trampoline_forward_onion(
+			&self.secp_ctx,
+			path,
+			&session_priv,
+			outgoing_amt_msat,
+			outgoing_cltv_value,
+			onion_packet.clone(),
+			&payment_hash,
+			prng_seed,
+		)
+		.map_err(|_| unknown_next_trampoline())?;
+		// Double-check the route we got back actually fits in the budget.
+		if htlc_msat.saturating_add(our_fee_msat) > incoming_amt_msat
+			|| htlc_cltv.saturating_add(channel_config.cltv_expiry_delta as u32)
+				> incoming_cltv_expiry
+		{
+			return Err(budget_insufficient());
+		}
+
+		let (counterparty_node_id, channel_id) =
+			match self.short_to_chan_info.read().unwrap().get(&first_hop.short_channel_id).cloned()
+			{
+				Some(chan_info) => chan_info,
+				None => return Err(unknown_next_trampoline()),
+			};
+		let temporary_failure = || {
+			(
+				LocalHTLCFailureReason::TemporaryTrampolineFailure,
+				HTLCHandlingFailureType::Forward {
+					node_id: Some(counterparty_node_id),
+					channel_id,
+				},
+			)
+		};
+		let per_peer_state = self.per_peer_state.read().unwrap();
+		let mut peer_state_lock = match per_peer_state.get(&counterparty_node_id) {
+			Some(peer_state_mutex) => peer_state_mutex.lock().unwrap(),
+			None => return Err(temporary_failure()),
+		};
+		let peer_state = &mut *peer_state_lock;
+		let chan =
+			match peer_state.channel_by_id.get_mut(&channel_id).and_then(Channel::as_funded_mut) {
+				Some(chan) if chan.context.is_usable() => chan,
+				_ => return Err(temporary_failure()),
+			};
+		let logger = WithChannelContext::from(&self.logger, &chan.context, Some(payment_hash));
+		log_trace!(
+			logger,
+			"Forwarding Trampoline HTLC from SCID {} to {} with first hop SCID {}",
+			payment.prev_outbound_scid_alias,
+			next_trampoline,
+			first_hop.short_channel_id
+		);
+		chan.queue_add_htlc(
+			htlc_msat,
+			payment_hash,
+			htlc_cltv,
+			htlc_source,
+			outgoing_onion,
+			None,
+			None,
+			false,
+			&self.fee_estimator,
+			&&logger,
+		)
+		.map_err(|(_, msg)| {
+			log_trace!(logger, "Failed to forward Trampoline HTLC: {}", msg);
+			temporary_failure()
+		})
+	}
+
 	fn process_receive_htlcs(
 		&self, pending_forwards: &mut Vec<HTLCForwardInfo>,
 		new_events: &mut VecDeque<(Event, Option<EventCompletionAction>)>,
@@ -7813,14 +10600,40 @@ where
 							.expect("Failed to get node_id for phantom node recipient");
 					}
 
+					let fail_back_excess_mpp_htlcs =
+						self.config.read().unwrap().fail_back_excess_mpp_htlcs;
+					let excess_htlc_failure = |prev_hop: HTLCPreviousHopData| {
+						(
+							HTLCSource::PreviousHopData(prev_hop),
+							payment_hash,
+							HTLCFailReason::from_failure_code(LocalHTLCFailureReason::MPPTimeout),
+							HTLCHandlingFailureType::Receive { payment_hash },
+						)
+					};
+
 					macro_rules! check_total_value {
 						($purpose: expr) => {{
 							let mut payment_claimable_generated = false;
 							let is_keysend = $purpose.is_keysend();
 							let mut claimable_payments = self.claimable_payments.lock().unwrap();
+							let is_held = claimable_payments.held_payment_hashes.contains_key(&payment_hash);
 							if claimable_payments.pending_claiming_payments.contains_key(&payment_hash) {
 								fail_htlc!(claimable_htlc, payment_hash);
 							}
+							if let Some(registered) = claimable_payments.inbound_amount_ranges.get(&payment_hash) {
+								// Check the maximum before we (possibly) start tracking the payment, as
+								// we MUST NOT fail_htlc!() once we have.
+								let max_value_msat = registered.range.max_value_msat;
+								let received_msat: u64 = claimable_payments.claimable_payments.get(&payment_hash)
+									.map_or(0, |payment| payment.htlcs.iter().map(|htlc| htlc.sender_intended_value).sum());
+								if claimable_htlc.total_msat > max_value_msat
+									|| received_msat + claimable_htlc.sender_intended_value > max_value_msat
+								{
+									log_trace!(self.logger, "Failing HTLC with payment_hash {} as it would exceed the maximum amount of {} msat",
+										&payment_hash, max_value_msat);
+									fail_htlc!(claimable_htlc, payment_hash);
+								}
+							}
 							let ref mut claimable_payment = claimable_payments.claimable_payments
 								.entry(payment_hash)
 								// Note that if we insert here we MUST NOT fail_htlc!()
@@ -7861,12 +10674,41 @@ where
 							} else if total_value - claimable_htlc.sender_intended_value >= claimable_htlc.total_msat {
 								log_trace!(self.logger, "Failing HTLC with payment_hash {} as payment is already claimable",
 									&payment_hash);
+								if fail_back_excess_mpp_htlcs {
+									// Let the sender know the HTLC was in excess of the amount due
+									// rather than rejecting the whole payment.
+									debug_assert!(!committed_to_claimable);
+									failed_forwards.push(excess_htlc_failure(claimable_htlc.prev_hop));
+									continue 'next_forwardable_htlc;
+								}
 								fail_htlc!(claimable_htlc, payment_hash);
 							} else if total_value >= claimable_htlc.total_msat {
 								#[allow(unused_assignments)] {
 									committed_to_claimable = true;
 								}
 								claimable_payment.htlcs.push(claimable_htlc);
+								if fail_back_excess_mpp_htlcs {
+									// Fail back the parts we don't need to reach the total, trying
+									// the largest ones first.
+									let total_msat = claimable_payment.htlcs[0].total_msat;
+									let mut remaining_value = total_value;
+									claimable_payment.htlcs
+										.sort_by_key(|htlc| cmp::Reverse(htlc.sender_intended_value));
+									let mut idx = 0;
+									while idx < claimable_payment.htlcs.len() {
+										let value = claimable_payment.htlcs[idx].sender_intended_value;
+										if remaining_value - value >= total_msat {
+											remaining_value -= value;
+											let htlc = claimable_payment.htlcs.remove(idx);
+											log_trace!(self.logger, "Failing excess HTLC of {} msat with payment_hash {} as payment is already claimable without it",
+												value, &payment_hash);
+											failed_forwards.push(excess_htlc_failure(htlc.prev_hop));
+										} else {
+											idx += 1;
+										}
+									}
+									total_value = remaining_value;
+								}
 								let amount_msat =
 									claimable_payment.htlcs.iter().map(|htlc| htlc.value).sum();
 								claimable_payment.htlcs.iter_mut()
@@ -7878,17 +10720,29 @@ where
 								claimable_payment.htlcs.sort();
 								let payment_id =
 									claimable_payment.inbound_payment_id(&self.inbound_payment_id_secret);
-								new_events.push_back((events::Event::PaymentClaimable {
-									receiver_node_id: Some(receiver_node_id),
-									payment_hash,
-									purpose: $purpose,
-									amount_msat,
-									counterparty_skimmed_fee_msat,
-									receiving_channel_ids: claimable_payment.receiving_channel_ids(),
-									claim_deadline: Some(earliest_expiry - HTLC_FAIL_BACK_BUFFER),
-									onion_fields: claimable_payment.onion_fields.clone(),
-									payment_id: Some(payment_id),
-								}, None));
+								if is_held {
+									new_events.push_back((events::Event::PaymentHeld {
+										payment_hash,
+										amount_msat,
+										purpose: $purpose,
+										onion_fields: claimable_payment.onion_fields.clone(),
+										receiving_channel_ids: claimable_payment.receiving_channel_ids(),
+										claim_deadline: earliest_expiry - HTLC_FAIL_BACK_BUFFER,
+										payment_id,
+									}, None));
+								} else {
+									new_events.push_back((events::Event::PaymentClaimable {
+										receiver_node_id: Some(receiver_node_id),
+										payment_hash,
+										purpose: $purpose,
+										amount_msat,
+										counterparty_skimmed_fee_msat,
+										receiving_channel_ids: claimable_payment.receiving_channel_ids(),
+										claim_deadline: Some(earliest_expiry - HTLC_FAIL_BACK_BUFFER),
+										onion_fields: claimable_payment.onion_fields.clone(),
+										payment_id: Some(payment_id),
+									}, None));
+								}
 								payment_claimable_generated = true;
 							} else {
 								// Nothing to do - we haven't reached the total
@@ -8067,11 +10921,17 @@ where
 	}
 
 	#[rustfmt::skip]
-	fn update_channel_fee(&self, chan_id: &ChannelId, chan: &mut FundedChannel<SP>, new_feerate: u32) -> NotifyOption {
+	fn update_channel_fee(&self, chan_id: &ChannelId, chan: &mut FundedChannel<SP>, mut new_feerate: u32) -> NotifyOption {
 		if !chan.funding.is_outbound() { return NotifyOption::SkipPersistNoEvents; }
 
 		let logger = WithChannelContext::from(&self.logger, &chan.context, None);
 
+		chan.update_fee_timer_tick();
+		let max_feerate = chan.context.config().max_update_feerate_sat_per_1000_weight;
+		if max_feerate != 0 {
+			new_feerate = cmp::min(new_feerate, max_feerate);
+		}
+
 		let current_feerate = chan.context.get_feerate_sat_per_1000_weight();
 		let update_fee_required = match new_feerate.cmp(&current_feerate) {
 			cmp::Ordering::Greater => true,
@@ -8088,6 +10948,12 @@ where
 				chan_id, chan.context.get_feerate_sat_per_1000_weight(), new_feerate);
 			return NotifyOption::SkipPersistNoEvents;
 		}
+		if chan.is_update_fee_rate_limited() {
+			log_trace!(logger, "Channel {} does not qualify for a feerate change from {} to {} as we updated its feerate too recently.",
+				chan_id, chan.context.get_feerate_sat_per_1000_weight(), new_feerate);
+			return NotifyOption::SkipPersistNoEvents;
+		}
+		let new_feerate = chan.step_limited_update_feerate(new_feerate);
 		log_trace!(logger, "Channel qualifies for a feerate change from {} to {}.",
 			chan.context.get_feerate_sat_per_1000_weight(), new_feerate);
 
@@ -8143,6 +11009,8 @@ where
 	///    or those awaiting an invoice that hasn't been delivered in the necessary amount of time.
 	///    The latter is determined using the system clock in `std` and the highest seen block time
 	///    minus two hours in non-`std`.
+	///  * Initiating closures scheduled via [`Self::schedule_closures`] which have become due.
+	///  * Surge pricing forwarding fees, if enabled via [`Self::set_dynamic_fee_policy`].
 	///
 	/// Note that this may cause reentrancy through [`chain::Watch::update_channel`] calls or feerate
 	/// estimate fetches.
@@ -8150,6 +11018,12 @@ where
 	/// [`ChannelUpdate`]: msgs::ChannelUpdate
 	/// [`ChannelConfig`]: crate::util::config::ChannelConfig
 	pub fn timer_tick_occurred(&self) {
+		// Scheduled closures are initiated via our public close methods, which take their own
+		// persistence guard, so we have to process them before taking ours.
+		self.process_scheduled_closures();
+		// Similarly, dynamic fee updates go through `update_partial_channel_config`.
+		self.apply_dynamic_fee_policy();
+
 		PersistenceNotifierGuard::optionally_notify(self, || {
 			let mut should_persist = NotifyOption::SkipPersistNoEvents;
 
@@ -8254,7 +11128,7 @@ where
 										);
 									let reason = ClosureReason::FundingTimedOut;
 									let msg = "Force-closing pending channel due to timeout awaiting establishment handshake".to_owned();
-									let err = ChannelError::Close((msg, reason));
+									let err = ChannelError::Close((msg, reason, None));
 									let (_, e) = convert_channel_err!(self, peer_state, err, chan);
 									handle_errors.push((Err(e), counterparty_node_id));
 									false
@@ -8329,6 +11203,31 @@ where
 				}
 			}
 
+			self.signed_channel_updates.lock().unwrap().retain(|_, (_, ticks)| {
+				*ticks += 1;
+				*ticks < CHANNEL_UPDATE_SIGNATURE_CACHE_TICKS
+			});
+
+			// Once an invoice has expired, `inbound_payment::verify` will reject any payments to it
+			// so we no longer need to track its amount range, nor whether it's a hold invoice unless
+			// a payment to it is currently held.
+			let highest_seen_timestamp = self.highest_seen_timestamp.load(Ordering::Acquire) as u64;
+			{
+				let mut claimable_payments = self.claimable_payments.lock().unwrap();
+				let ClaimablePayments {
+					claimable_payments,
+					held_payment_hashes,
+					inbound_amount_ranges,
+					..
+				} = &mut *claimable_payments;
+				inbound_amount_ranges
+					.retain(|_, registered| registered.expiry_time >= highest_seen_timestamp);
+				held_payment_hashes.retain(|payment_hash, registered| {
+					registered.expiry_time >= highest_seen_timestamp
+						|| claimable_payments.contains_key(payment_hash)
+				});
+			}
+
 			self.claimable_payments.lock().unwrap().claimable_payments.retain(
 				|payment_hash, payment| {
 					if payment.htlcs.is_empty() {
@@ -8425,8 +11324,14 @@ where
 	) {
 		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
 
-		let removed_source =
-			self.claimable_payments.lock().unwrap().claimable_payments.remove(payment_hash);
+		let removed_source = {
+			let mut claimable_payments = self.claimable_payments.lock().unwrap();
+			let removed_source = claimable_payments.claimable_payments.remove(payment_hash);
+			if removed_source.is_some() {
+				claimable_payments.held_payment_hashes.remove(payment_hash);
+			}
+			removed_source
+		};
 		if let Some(payment) = removed_source {
 			for htlc in payment.htlcs {
 				let reason = self.get_htlc_fail_reason_from_failure_code(failure_code, &htlc);
@@ -8606,6 +11511,19 @@ where
 				// In case of trampoline + phantom we prioritize the trampoline failure over the phantom failure.
 				// TODO: Correctly wrap the error packet twice if failing back a trampoline + phantom HTLC.
 				let secondary_shared_secret = trampoline_shared_secret.or(*phantom_shared_secret);
+				// A failure relayed back from the route to the next Trampoline node is encrypted for
+				// the outer onion we built, which the sender can't decrypt, so we report a generic
+				// Trampoline failure instead.
+				let trampoline_failure;
+				let onion_error = if trampoline_shared_secret.is_some() && onion_error.is_relayed()
+				{
+					trampoline_failure = HTLCFailReason::from_failure_code(
+						LocalHTLCFailureReason::TemporaryTrampolineFailure,
+					);
+					&trampoline_failure
+				} else {
+					onion_error
+				};
 				let failure = match blinded_failure {
 					Some(BlindedFailure::FromIntroductionNode) => {
 						let blinded_onion_error = HTLCFailReason::reason(
@@ -8632,6 +11550,8 @@ where
 					},
 				};
 
+				self.resolve_forwarded_htlc(channel_id, *htlc_id, false);
+
 				let mut forward_htlcs = self.forward_htlcs.lock().unwrap();
 				match forward_htlcs.entry(*prev_outbound_scid_alias) {
 					hash_map::Entry::Occupied(mut entry) => {
@@ -8648,6 +11568,9 @@ where
 						prev_channel_id: *channel_id,
 						failure_type,
 						failure_reason: Some(onion_error.into()),
+						htlc_correlation_id: Some(HTLCCorrelationId::from_prev_hop(
+							channel_id, *htlc_id,
+						)),
 					},
 					None,
 				));
@@ -8655,6 +11578,17 @@ where
 		}
 	}
 
+	/// Updates the local reputation of the peer which offered us the given inbound HTLC once we
+	/// claim or fail it backwards, if we forwarded it.
+	fn resolve_forwarded_htlc(
+		&self, prev_channel_id: &ChannelId, prev_htlc_id: u64, settled: bool,
+	) {
+		let config = self.config.read().unwrap().htlc_endorsement_config;
+		let now = self.duration_since_epoch();
+		let mut local_reputation = self.local_reputation.lock().unwrap();
+		local_reputation.htlc_resolved(prev_channel_id, prev_htlc_id, settled, now, &config);
+	}
+
 	/// Provides a payment preimage in response to [`Event::PaymentClaimable`], generating any
 	/// [`MessageSendEvent`]s needed to claim the payment.
 	///
@@ -8702,13 +11636,19 @@ where
 		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
 
 		let (sources, claiming_payment) = {
-			let res = self.claimable_payments.lock().unwrap().begin_claiming_payment(
+			let mut claimable_payments = self.claimable_payments.lock().unwrap();
+			let res = claimable_payments.begin_claiming_payment(
 				payment_hash,
 				&self.node_signer,
 				&self.logger,
 				&self.inbound_payment_id_secret,
 				custom_tlvs_known,
 			);
+			if !matches!(&res, Err(htlcs) if htlcs.is_empty()) {
+				// The payment is either being claimed or failed back, so is no longer held.
+				claimable_payments.held_payment_hashes.remove(&payment_hash);
+			}
+			mem::drop(claimable_payments);
 
 			match res {
 				Ok((htlcs, payment_info)) => (htlcs, payment_info),
@@ -9266,6 +12206,9 @@ This indicates a bug inside LDK. Please report this error at https://github.com/
 			},
 			HTLCSource::PreviousHopData(hop_data) => {
 				let prev_channel_id = hop_data.channel_id;
+				self.resolve_forwarded_htlc(&prev_channel_id, hop_data.htlc_id, true);
+				let htlc_correlation_id =
+					HTLCCorrelationId::from_prev_hop(&prev_channel_id, hop_data.htlc_id);
 				let prev_user_channel_id = hop_data.user_channel_id;
 				let prev_node_id = hop_data.counterparty_node_id;
 				let completed_blocker =
@@ -9386,6 +12329,7 @@ This indicates a bug inside LDK. Please report this error at https://github.com/
 										skimmed_fee_msat,
 										claim_from_onchain_tx: from_onchain,
 										outbound_amount_forwarded_msat: forwarded_htlc_value_msat,
+										htlc_correlation_id: Some(htlc_correlation_id),
 									},
 									downstream_counterparty_and_funding_outpoint: chan_to_release,
 								}),
@@ -9457,7 +12401,12 @@ This indicates a bug inside LDK. Please report this error at https://github.com/
 				}
 				if let Some(tx) = batch_funding_tx {
 					log_info!(self.logger, "Broadcasting batch funding tx {}", tx.compute_txid());
-					self.tx_broadcaster.broadcast_transactions(&[&tx]);
+					let context = BroadcastContext {
+						broadcast_type: BroadcastType::Funding,
+						channel_id: None,
+						urgency: None,
+					};
+					self.tx_broadcaster.broadcast_transactions(&[&tx], context);
 				}
 			}
 		}
@@ -9761,7 +12710,12 @@ This indicates a bug inside LDK. Please report this error at https://github.com/
 				};
 			} else {
 				log_info!(logger, "Broadcasting funding transaction with txid {}", tx.compute_txid());
-				self.tx_broadcaster.broadcast_transactions(&[&tx]);
+				let context = BroadcastContext {
+					broadcast_type: BroadcastType::Funding,
+					channel_id: Some(channel.context.channel_id()),
+					urgency: None,
+				};
+				self.tx_broadcaster.broadcast_transactions(&[&tx], context);
 			}
 		}
 
@@ -9936,6 +12890,42 @@ This indicates a bug inside LDK. Please report this error at https://github.com/
 			temporary_channel_id,
 			counterparty_node_id,
 			false,
+			None,
+			user_channel_id,
+			config_overrides,
+		)
+	}
+
+	/// Accepts a request to open a dual-funded channel after a [`Event::OpenChannelRequest`],
+	/// contributing to the channel's funding transaction as well.
+	///
+	/// This may only be used for requests with a `channel_negotiation_type` of
+	/// [`InboundChannelFunds::DualFunded`], and otherwise behaves like
+	/// [`ChannelManager::accept_inbound_channel`].
+	///
+	/// The `contribution` determines the amount we contribute to the channel along with the
+	/// inputs, selected from the on-chain wallet, used to fund it. The inputs must cover the
+	/// contributed amount plus our share of the funding transaction's fees at the feerate chosen by
+	/// the counterparty, given by the request's `funding_feerate_sat_per_1000_weight`. If they
+	/// don't, an error is returned and the request may be accepted again with other inputs.
+	///
+	/// As with [`ChannelManager::create_dual_funded_channel`], once the funding transaction has
+	/// been negotiated, [`Event::FundingTransactionReadyForSigning`] will be generated and
+	/// [`ChannelManager::funding_transaction_signed`] should be called with the signed funding
+	/// transaction.
+	///
+	/// [`Event::OpenChannelRequest`]: events::Event::OpenChannelRequest
+	/// [`InboundChannelFunds::DualFunded`]: events::InboundChannelFunds::DualFunded
+	pub fn accept_inbound_channel_with_contribution(
+		&self, temporary_channel_id: &ChannelId, counterparty_node_id: &PublicKey,
+		contribution: FundingContribution, user_channel_id: u128,
+		config_overrides: Option<ChannelConfigOverrides>,
+	) -> Result<(), APIError> {
+		self.do_accept_inbound_channel(
+			temporary_channel_id,
+			counterparty_node_id,
+			false,
+			Some(contribution),
 			user_channel_id,
 			config_overrides,
 		)
@@ -9967,15 +12957,15 @@ This indicates a bug inside LDK. Please report this error at https://github.com/
 			temporary_channel_id,
 			counterparty_node_id,
 			true,
+			None,
 			user_channel_id,
 			config_overrides,
 		)
 	}
 
-	/// TODO(dual_funding): Allow contributions, pass intended amount and inputs
 	fn do_accept_inbound_channel(
 		&self, temporary_channel_id: &ChannelId, counterparty_node_id: &PublicKey,
-		accept_0conf: bool, user_channel_id: u128,
+		accept_0conf: bool, contribution: Option<FundingContribution>, user_channel_id: u128,
 		config_overrides: Option<ChannelConfigOverrides>,
 	) -> Result<(), APIError> {
 		let mut config = self.config.read().unwrap().clone();
@@ -10008,6 +12998,39 @@ This indicates a bug inside LDK. Please report this error at https://github.com/
 		let peer_state = &mut *peer_state_lock;
 		let is_only_peer_channel = peer_state.total_channel_count() == 1;
 
+		// Check our contribution before removing the request, so that it may be accepted again if
+		// the contributed inputs are insufficient.
+		if let Some(contribution) = &contribution {
+			let request = peer_state.inbound_channel_request_by_id.get(temporary_channel_id);
+			match request.map(|request| &request.open_channel_msg) {
+				Some(OpenChannelMessage::V1(_)) => {
+					return Err(APIError::APIMisuseError {
+						err: "Only dual-funded channels can be contributed to".to_owned(),
+					});
+				},
+				Some(OpenChannelMessage::V2(open_channel_msg)) => {
+					channel::check_v2_funding_inputs_sufficient(
+						contribution.value.to_sat() as i64,
+						&contribution.inputs,
+						false,
+						false,
+						open_channel_msg.funding_feerate_sat_per_1000_weight,
+					)
+					.map_err(|err| APIError::APIMisuseError {
+						err: format!("Channel cannot be funded; {}", err),
+					})?;
+				},
+				// Handled below when removing the request.
+				None => {},
+			}
+		}
+		let (our_funding_satoshis, our_funding_inputs, change_script) = match contribution {
+			Some(FundingContribution { value, inputs, change_script }) => {
+				(value.to_sat(), inputs, change_script)
+			},
+			None => (0, Vec::new(), None),
+		};
+
 		// Find (and remove) the channel in the unaccepted table. If it's not there, something weird is
 		// happening and return an error. N.B. that we create channel with an outbound SCID of zero so
 		// that we can delay allocating the SCID until after we're sure that the checks below will
@@ -10053,6 +13076,9 @@ This indicates a bug inside LDK. Please report this error at https://github.com/
 						&self.channel_type_features(),
 						&peer_state.latest_features,
 						&open_channel_msg,
+						our_funding_satoshis,
+						our_funding_inputs,
+						change_script,
 						user_channel_id,
 						&config,
 						best_block_height,
@@ -10231,6 +13257,53 @@ This indicates a bug inside LDK. Please report this error at https://github.com/
 		num_unfunded_channels + peer.inbound_channel_request_by_id.len()
 	}
 
+	/// Checks the commitment transaction feerate proposed in an `open_channel(2)` message against
+	/// our [`InboundChannelFeePolicy`], returning whether the decision was deferred to the user.
+	fn check_inbound_channel_feerate(
+		&self, counterparty_node_id: &PublicKey, common_fields: &msgs::CommonOpenChannelFields,
+		channel_type: &ChannelTypeFeatures,
+	) -> Result<bool, MsgHandleErrInternal> {
+		// Zero-fee commitment channels must never use a feerate, which is checked once we create the
+		// channel.
+		if channel_type.supports_anchor_zero_fee_commitments() {
+			return Ok(false);
+		}
+		let conf_target = if channel_type.supports_anchors_zero_fee_htlc_tx() {
+			ConfirmationTarget::MinAllowedAnchorChannelRemoteFee
+		} else {
+			ConfirmationTarget::MinAllowedNonAnchorChannelRemoteFee
+		};
+		let estimate = self.fee_estimator.bounded_sat_per_1000_weight(conf_target);
+		let feerate = common_fields.commitment_feerate_sat_per_1000_weight;
+		let decision = self.inbound_channel_fee_policy.read().unwrap().decide_inbound_feerate(
+			counterparty_node_id,
+			channel_type,
+			feerate,
+			estimate,
+		);
+		match decision {
+			InboundChannelFeerateDecision::Accept => Ok(false),
+			InboundChannelFeerateDecision::Defer => Ok(true),
+			InboundChannelFeerateDecision::ProposeRange {
+				min_feerate_sat_per_1000_weight: min,
+				max_feerate_sat_per_1000_weight: max,
+			} => {
+				let range = match max {
+					Some(max) => format!("between {} and {} sat/kW", min, max),
+					None => format!("of at least {} sat/kW", min),
+				};
+				let err = format!(
+					"Unacceptable commitment feerate of {} sat/kW. We require a feerate {}",
+					feerate, range
+				);
+				Err(MsgHandleErrInternal::send_err_msg_no_close(
+					err,
+					common_fields.temporary_channel_id,
+				))
+			},
+		}
+	}
+
 	#[rustfmt::skip]
 	fn internal_open_channel(&self, counterparty_node_id: &PublicKey, msg: OpenChannelMessageRef<'_>) -> Result<(), MsgHandleErrInternal> {
 		let common_fields = match msg {
@@ -10302,8 +13375,10 @@ This indicates a bug inside LDK. Please report this error at https://github.com/
 			common_fields, &self.channel_type_features()
 		).map_err(|e| MsgHandleErrInternal::from_chan_no_close(e, common_fields.temporary_channel_id))?;
 
+		let defer_to_user = self.check_inbound_channel_feerate(counterparty_node_id, common_fields, &channel_type)?;
+
 		// If we're doing manual acceptance checks on the channel, then defer creation until we're sure we want to accept.
-		if self.config.read().unwrap().manually_accept_inbound_channels {
+		if self.config.read().unwrap().manually_accept_inbound_channels || defer_to_user {
 			let mut pending_events = self.pending_events.lock().unwrap();
 			let is_announced = (common_fields.channel_flags & 1) == 1;
 			pending_events.push_back((events::Event::OpenChannelRequest {
@@ -10312,7 +13387,9 @@ This indicates a bug inside LDK. Please report this error at https://github.com/
 				funding_satoshis: common_fields.funding_satoshis,
 				channel_negotiation_type: match msg {
 					OpenChannelMessageRef::V1(msg) => InboundChannelFunds::PushMsat(msg.push_msat),
-					OpenChannelMessageRef::V2(_) => InboundChannelFunds::DualFunded,
+					OpenChannelMessageRef::V2(msg) => InboundChannelFunds::DualFunded {
+						funding_feerate_sat_per_1000_weight: msg.funding_feerate_sat_per_1000_weight,
+					},
 				},
 				channel_type,
 				is_announced,
@@ -10360,7 +13437,7 @@ This indicates a bug inside LDK. Please report this error at https://github.com/
 				let channel = PendingV2Channel::new_inbound(
 					&self.fee_estimator, &self.entropy_source, &self.signer_provider,
 					self.get_our_node_id(), *counterparty_node_id, &self.channel_type_features(),
-					&peer_state.latest_features, msg, user_channel_id,
+					&peer_state.latest_features, msg, 0, Vec::new(), None, user_channel_id,
 					&self.config.read().unwrap(), best_block_height, &self.logger,
 				).map_err(|e| MsgHandleErrInternal::from_chan_no_close(e, msg.common_fields.temporary_channel_id))?;
 				let message_send_event = MessageSendEvent::SendAcceptChannelV2 {
@@ -10426,6 +13503,77 @@ This indicates a bug inside LDK. Please report this error at https://github.com/
 		Ok(())
 	}
 
+	fn internal_accept_channel_v2(
+		&self, counterparty_node_id: &PublicKey, msg: &msgs::AcceptChannelV2,
+	) -> Result<(), MsgHandleErrInternal> {
+		let temporary_channel_id = msg.common_fields.temporary_channel_id;
+		let per_peer_state = self.per_peer_state.read().unwrap();
+		let peer_state_mutex = per_peer_state.get(counterparty_node_id).ok_or_else(|| {
+			debug_assert!(false);
+			MsgHandleErrInternal::send_err_msg_no_close(
+				format!("Can't find a peer matching the passed counterparty node_id {counterparty_node_id}"),
+				temporary_channel_id,
+			)
+		})?;
+		let mut peer_state_lock = peer_state_mutex.lock().unwrap();
+		let peer_state = &mut *peer_state_lock;
+		let (channel_id, tx_msg_opt) = match peer_state.channel_by_id.entry(temporary_channel_id) {
+			hash_map::Entry::Occupied(mut chan) => match chan.get_mut().as_unfunded_v2_mut() {
+				Some(unfunded_chan) if unfunded_chan.funding.is_outbound() => {
+					let res = unfunded_chan.accept_channel_v2(
+						msg,
+						&self.config.read().unwrap().channel_handshake_limits,
+						&peer_state.latest_features,
+						&self.signer_provider,
+						&self.entropy_source,
+						self.get_our_node_id(),
+					);
+					let tx_msg_opt = try_channel_entry!(self, peer_state, res, chan);
+					(unfunded_chan.context.channel_id(), tx_msg_opt)
+				},
+				_ => {
+					return Err(MsgHandleErrInternal::send_err_msg_no_close(
+						format!("Got an unexpected accept_channel2 message from peer with counterparty_node_id {}", counterparty_node_id),
+						temporary_channel_id,
+					));
+				},
+			},
+			hash_map::Entry::Vacant(_) => {
+				return Err(MsgHandleErrInternal::send_err_msg_no_close(
+					format!("Got a message for a channel from the wrong node! No such channel for the passed counterparty_node_id {}", counterparty_node_id),
+					temporary_channel_id,
+				));
+			},
+		};
+
+		// Now that both parties' revocation basepoints are known, the channel is keyed by its
+		// final ID, which is used in all further messages.
+		let mut chan =
+			peer_state.channel_by_id.remove(&temporary_channel_id).expect("Checked above");
+		match peer_state.channel_by_id.entry(channel_id) {
+			hash_map::Entry::Occupied(_) => {
+				// As in `internal_funding_created`, we must revert to the temporary channel ID before
+				// closing the channel, lest we clean up after, or report closing, the other channel.
+				if let Some(unfunded_chan) = chan.as_unfunded_v2_mut() {
+					unfunded_chan.unset_channel_id();
+				}
+				let err_msg = "Already had channel with the new channel_id".to_owned();
+				let err = ChannelError::close(err_msg);
+				let res = convert_channel_err!(self, peer_state, err, &mut chan, UNFUNDED_CHANNEL);
+				return Err(res.1);
+			},
+			hash_map::Entry::Vacant(entry) => {
+				entry.insert(chan);
+			},
+		}
+
+		if let Some(tx_msg) = tx_msg_opt {
+			let msg_send_event = tx_msg.into_msg_send_event(*counterparty_node_id);
+			peer_state.pending_msg_events.push(msg_send_event);
+		}
+		Ok(())
+	}
+
 	#[rustfmt::skip]
 	fn internal_funding_created(&self, counterparty_node_id: &PublicKey, msg: &msgs::FundingCreated) -> Result<(), MsgHandleErrInternal> {
 		let best_block = *self.best_block.read().unwrap();
@@ -10882,7 +14030,7 @@ This indicates a bug inside LDK. Please report this error at https://github.com/
 					None => {
 						let msg = "Got an unexpected tx_signatures message";
 						let reason = ClosureReason::ProcessingError { err: msg.to_owned() };
-						let err = ChannelError::Close((msg.to_owned(), reason));
+						let err = ChannelError::Close((msg.to_owned(), reason, None));
 						try_channel_entry!(self, peer_state, Err(err), chan_entry)
 					},
 				}
@@ -11086,7 +14234,7 @@ This indicates a bug inside LDK. Please report this error at https://github.com/
 						);
 						log_error!(logger, "Immediately closing unfunded channel as peer asked to cooperatively shut it down (which is unnecessary)");
 						let reason = ClosureReason::CounterpartyCoopClosedUnfundedChannel;
-						let err = ChannelError::Close((reason.to_string(), reason));
+						let err = ChannelError::Close((reason.to_string(), reason, None));
 						let mut chan = chan_entry.remove();
 						let (_, mut e) = convert_channel_err!(self, peer_state, err, &mut chan);
 						e.dont_send_error_message();
@@ -11165,7 +14313,12 @@ This indicates a bug inside LDK. Please report this error at https://github.com/
 		mem::drop(per_peer_state);
 		if let Some((broadcast_tx, err)) = tx_err {
 			log_info!(logger, "Broadcasting {}", log_tx!(broadcast_tx));
-			self.tx_broadcaster.broadcast_transactions(&[&broadcast_tx]);
+			let context = BroadcastContext {
+				broadcast_type: BroadcastType::CooperativeClose,
+				channel_id: Some(msg.channel_id),
+				urgency: None,
+			};
+			self.tx_broadcaster.broadcast_transactions(&[&broadcast_tx], context);
 			let _ = self.handle_error(err, *counterparty_node_id);
 		}
 		Ok(())
@@ -11374,7 +14527,7 @@ This indicates a bug inside LDK. Please report this error at https://github.com/
 							log_error!(logger, "Persisting initial ChannelMonitor failed, implying the channel ID was duplicated");
 							let msg = "Channel ID was a duplicate";
 							let reason = ClosureReason::ProcessingError { err: msg.to_owned() };
-							let err = ChannelError::Close((msg.to_owned(), reason));
+							let err = ChannelError::Close((msg.to_owned(), reason, None));
 							try_channel_entry!(self, peer_state, Err(err), chan_entry)
 						}
 					} else if let Some(monitor_update) = monitor_update_opt {
@@ -11473,6 +14626,7 @@ This indicates a bug inside LDK. Please report this error at https://github.com/
 						prev_htlc_id,
 						prev_user_channel_id,
 						forward_info,
+						intercept_hold_until_height: None,
 					};
 					let mut fail_intercepted_htlc = |pending_add: PendingAddHTLCInfo| {
 						let htlc_source =
@@ -11543,7 +14697,20 @@ This indicates a bug inside LDK. Please report this error at https://github.com/
 										expected_outbound_amount_msat: pending_add
 											.forward_info
 											.outgoing_amt_msat,
+										inbound_cltv_expiry: pending_add
+											.forward_info
+											.routing
+											.incoming_cltv_expiry(),
+										expected_outbound_cltv_expiry: Some(
+											pending_add.forward_info.outgoing_cltv_value,
+										),
 										intercept_id,
+										htlc_correlation_id: Some(
+											HTLCCorrelationId::from_prev_hop(
+												&pending_add.prev_channel_id,
+												pending_add.prev_htlc_id,
+											),
+										),
 									},
 									None,
 								));
@@ -11690,7 +14857,19 @@ This indicates a bug inside LDK. Please report this error at https://github.com/
 			hash_map::Entry::Occupied(mut chan_entry) => {
 				if let Some(chan) = chan_entry.get_mut().as_funded_mut() {
 					let logger = WithChannelContext::from(&self.logger, &chan.context, None);
-					try_channel_entry!(self, peer_state, chan.update_fee(&self.fee_estimator, &msg, &&logger), chan_entry);
+					let res = chan.update_fee(
+						&self.fee_estimator, &**self.update_fee_policy.read().unwrap(), &msg, &&logger
+					);
+					let near_limit = try_channel_entry!(self, peer_state, res, chan_entry);
+					if let Some(min_feerate_sat_per_1000_weight) = near_limit {
+						self.pending_events.lock().unwrap().push_back((events::Event::PeerFeerateNearLimit {
+							channel_id: msg.channel_id,
+							counterparty_node_id: *counterparty_node_id,
+							user_channel_id: chan.context.get_user_id(),
+							feerate_sat_per_1000_weight: msg.feerate_per_kw,
+							min_feerate_sat_per_1000_weight,
+						}, None));
+					}
 				} else {
 					return try_channel_entry!(self, peer_state, Err(ChannelError::close(
 						"Got an update_fee message for an unfunded channel!".into())), chan_entry);
@@ -11745,11 +14924,20 @@ This indicates a bug inside LDK. Please report this error at https://github.com/
 							});
 							Ok(true)
 						},
+						Some(StfuResponse::Quiescent) => {
+							let event = events::Event::ChannelQuiescent {
+								channel_id: msg.channel_id,
+								counterparty_node_id: *counterparty_node_id,
+								user_channel_id: chan.context.get_user_id(),
+							};
+							self.pending_events.lock().unwrap().push_back((event, None));
+							Ok(true)
+						},
 					}
 				} else {
 					let msg = "Peer sent `stfu` for an unfunded channel";
 					let err = Err(ChannelError::Close(
-						(msg.into(), ClosureReason::ProcessingError { err: msg.into() })
+						(msg.into(), ClosureReason::ProcessingError { err: msg.into() }, None)
 					));
 					return try_channel_entry!(self, peer_state, err, chan_entry);
 				}
@@ -11886,7 +15074,8 @@ This indicates a bug inside LDK. Please report this error at https://github.com/
 							self.chain_hash,
 							&self.config.read().unwrap(),
 							&*self.best_block.read().unwrap(),
-							|htlc_id| self.path_for_release_held_htlc(htlc_id, outbound_scid_alias, &msg.channel_id, counterparty_node_id)
+							|htlc_id| self.path_for_release_held_htlc(htlc_id, outbound_scid_alias, &msg.channel_id, counterparty_node_id),
+							self.recovery_mode.load(Ordering::Acquire),
 						);
 						let responses = try_channel_entry!(self, peer_state, res, chan_entry);
 						let mut channel_update = None;
@@ -11943,15 +15132,7 @@ This indicates a bug inside LDK. Please report this error at https://github.com/
 					// counterparty's to-be-broadcast latest commitment transaction.
 					peer_state.pending_msg_events.push(MessageSendEvent::SendChannelReestablish {
 						node_id: *counterparty_node_id,
-						msg: msgs::ChannelReestablish {
-							channel_id: msg.channel_id,
-							next_local_commitment_number: 0,
-							next_remote_commitment_number: 0,
-							your_last_per_commitment_secret: [1u8; 32],
-							my_current_per_commitment_point: PublicKey::from_slice(&[2u8; 33]).unwrap(),
-							next_funding: None,
-							my_current_funding_locked: None,
-						},
+						msg: bogus_channel_reestablish(msg.channel_id),
 					});
 					return Err(MsgHandleErrInternal::send_err_msg_no_close(
 						format!("Got a message for a channel from the wrong node! No such channel for the passed counterparty_node_id {}",
@@ -12242,7 +15423,15 @@ This indicates a bug inside LDK. Please report this error at https://github.com/
 										message: "Legacy ChannelMonitor closure".to_owned(),
 									}
 								};
-								let err = ChannelError::Close((reason.to_string(), reason));
+								if let ClosureReason::HTLCsTimedOut { .. } = reason {
+									self.record_misbehavior(
+										counterparty_node_id,
+										MisbehaviorKind::HTLCHold,
+										Some(channel_id),
+										reason.to_string(),
+									);
+								}
+								let err = ChannelError::Close((reason.to_string(), reason, None));
 								let mut chan = chan_entry.remove();
 								let (_, e) = convert_channel_err!(self, peer_state, err, &mut chan);
 								failed_channels.push((Err(e), counterparty_node_id));
@@ -12258,7 +15447,7 @@ This indicates a bug inside LDK. Please report this error at https://github.com/
 								peer_state.channel_by_id.entry(channel_id)
 							{
 								let reason = ClosureReason::CommitmentTxConfirmed;
-								let err = ChannelError::Close((reason.to_string(), reason));
+								let err = ChannelError::Close((reason.to_string(), reason, None));
 								let mut chan = chan_entry.remove();
 								let (_, e) = convert_channel_err!(self, peer_state, err, &mut chan);
 								failed_channels.push((Err(e), counterparty_node_id));
@@ -12365,7 +15554,8 @@ This indicates a bug inside LDK. Please report this error at https://github.com/
 			let logger = WithChannelContext::from(&self.logger, &chan.context(), None);
 			let node_id = chan.context().get_counterparty_node_id();
 			let cbp = |htlc_id| self.path_for_release_held_htlc(htlc_id, outbound_scid_alias, &channel_id, &node_id);
-			let msgs = chan.signer_maybe_unblocked(self.chain_hash, &&logger, cbp)?;
+			let tolerate_stale_state = self.recovery_mode.load(Ordering::Acquire);
+			let msgs = chan.signer_maybe_unblocked(self.chain_hash, &&logger, cbp, tolerate_stale_state)?;
 			if let Some(msgs) = msgs {
 				if chan.context().is_connected() {
 					if let Some(msg) = msgs.open_channel {
@@ -12427,7 +15617,12 @@ This indicates a bug inside LDK. Please report this error at https://github.com/
 					}
 					if let Some(broadcast_tx) = msgs.signed_closing_tx {
 						log_info!(logger, "Broadcasting closing tx {}", log_tx!(broadcast_tx));
-						self.tx_broadcaster.broadcast_transactions(&[&broadcast_tx]);
+						let context = BroadcastContext {
+							broadcast_type: BroadcastType::CooperativeClose,
+							channel_id: Some(funded_chan.context.channel_id()),
+							urgency: None,
+						};
+						self.tx_broadcaster.broadcast_transactions(&[&broadcast_tx], context);
 					}
 				} else {
 					// We don't know how to handle a channel_ready or signed_closing_tx for a
@@ -12474,7 +15669,7 @@ This indicates a bug inside LDK. Please report this error at https://github.com/
 					} else {
 						debug_assert!(false);
 						let reason = shutdown.closure_reason.clone();
-						let err = ChannelError::Close((reason.to_string(), reason));
+						let err = ChannelError::Close((reason.to_string(), reason, None));
 						convert_channel_err!(self, peer_state, err, chan, UNFUNDED_CHANNEL)
 					};
 					debug_assert!(remove);
@@ -12528,7 +15723,12 @@ This indicates a bug inside LDK. Please report this error at https://github.com/
 										handle_errors.push((*cp_id, Err(err)));
 
 										log_info!(logger, "Broadcasting {}", log_tx!(tx));
-										self.tx_broadcaster.broadcast_transactions(&[&tx]);
+										let context = BroadcastContext {
+											broadcast_type: BroadcastType::CooperativeClose,
+											channel_id: Some(funded_chan.context.channel_id()),
+											urgency: None,
+										};
+										self.tx_broadcaster.broadcast_transactions(&[&tx], context);
 										false
 									} else { true }
 								},
@@ -12643,7 +15843,7 @@ This indicates a bug inside LDK. Please report this error at https://github.com/
 
 	#[cfg(any(test, fuzzing))]
 	#[rustfmt::skip]
-	pub fn exit_quiescence(&self, counterparty_node_id: &PublicKey, channel_id: &ChannelId) -> Result<bool, APIError> {
+	pub fn exit_quiescence_locally(&self, counterparty_node_id: &PublicKey, channel_id: &ChannelId) -> Result<bool, APIError> {
 		let per_peer_state = self.per_peer_state.read().unwrap();
 		let peer_state_mutex = per_peer_state.get(counterparty_node_id)
 			.ok_or_else(|| APIError::ChannelUnavailable {
@@ -12678,11 +15878,10 @@ This indicates a bug inside LDK. Please report this error at https://github.com/
 	) -> Result<Bolt11Invoice, SignOrCreationError<()>> {
 		let Bolt11InvoiceParameters {
 			amount_msats, description, invoice_expiry_delta_secs, min_final_cltv_expiry_delta,
-			payment_hash,
+			payment_hash, amount_range_msats,
 		} = params;
 
-		let currency =
-			Network::from_chain_hash(self.chain_hash).map(Into::into).unwrap_or(Currency::Bitcoin);
+		let currency = Currency::from_chain_hash(self.chain_hash);
 
 		#[cfg(feature = "std")]
 		let duration_since_epoch = {
@@ -12704,22 +15903,39 @@ This indicates a bug inside LDK. Please report this error at https://github.com/
 			}
 		}
 
-		let (payment_hash, payment_secret) = match payment_hash {
-			Some(payment_hash) => {
+		if amount_msats.is_some() && amount_range_msats.is_some() {
+			return Err(SignOrCreationError::CreationError(CreationError::InvalidAmount));
+		}
+
+		let expiry_delta_secs = invoice_expiry_delta_secs.unwrap_or(DEFAULT_EXPIRY_TIME as u32);
+		let (payment_hash, payment_secret) = match (payment_hash, amount_range_msats) {
+			(Some(payment_hash), Some(amount_range)) => {
+				let payment_secret = self
+					.create_inbound_payment_for_hash_with_amount_range(
+						payment_hash, amount_range, expiry_delta_secs, min_final_cltv_expiry_delta,
+					)
+					.map_err(|()| SignOrCreationError::CreationError(CreationError::InvalidAmount))?;
+				(payment_hash, payment_secret)
+			},
+			(Some(payment_hash), None) => {
 				let payment_secret = self
 					.create_inbound_payment_for_hash(
-						payment_hash, amount_msats,
-						invoice_expiry_delta_secs.unwrap_or(DEFAULT_EXPIRY_TIME as u32),
-						min_final_cltv_expiry_delta,
+						payment_hash, amount_msats, expiry_delta_secs, min_final_cltv_expiry_delta,
 					)
 					.map_err(|()| SignOrCreationError::CreationError(CreationError::InvalidAmount))?;
 				(payment_hash, payment_secret)
 			},
-			None => {
+			(None, Some(amount_range)) => {
+				self
+					.create_inbound_payment_with_amount_range(
+						amount_range, expiry_delta_secs, min_final_cltv_expiry_delta,
+					)
+					.map_err(|()| SignOrCreationError::CreationError(CreationError::InvalidAmount))?
+			},
+			(None, None) => {
 				self
 					.create_inbound_payment(
-						amount_msats, invoice_expiry_delta_secs.unwrap_or(DEFAULT_EXPIRY_TIME as u32),
-						min_final_cltv_expiry_delta,
+						amount_msats, expiry_delta_secs, min_final_cltv_expiry_delta,
 					)
 					.map_err(|()| SignOrCreationError::CreationError(CreationError::InvalidAmount))?
 			},
@@ -12800,6 +16016,15 @@ pub struct Bolt11InvoiceParameters {
 	/// involving another protocol where the payment hash is also involved outside the scope of
 	/// lightning.
 	pub payment_hash: Option<PaymentHash>,
+
+	/// The range of amounts to accept payments for, if the invoice has no amount set.
+	///
+	/// If set, [`Self::amount_msats`] must not be set, and the invoice will not include an amount.
+	/// Payments of less than [`InboundAmountRange::min_value_msat`] or more than
+	/// [`InboundAmountRange::max_value_msat`] will be failed back.
+	///
+	/// See [`ChannelManager::create_inbound_payment_with_amount_range`] for more details.
+	pub amount_range_msats: Option<InboundAmountRange>,
 }
 
 impl Default for Bolt11InvoiceParameters {
@@ -12810,10 +16035,40 @@ impl Default for Bolt11InvoiceParameters {
 			invoice_expiry_delta_secs: None,
 			min_final_cltv_expiry_delta: None,
 			payment_hash: None,
+			amount_range_msats: None,
 		}
 	}
 }
 
+/// The range of amounts a zero-amount invoice will accept payments for.
+///
+/// See [`ChannelManager::create_inbound_payment_with_amount_range`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InboundAmountRange {
+	/// The minimum amount, in millisatoshis, a payment must be for.
+	pub min_value_msat: u64,
+	/// The maximum amount, in millisatoshis, a payment may be for.
+	///
+	/// HTLCs which would take the sender-intended amount of a payment over this are failed back.
+	pub max_value_msat: u64,
+}
+
+impl InboundAmountRange {
+	/// Creates a range accepting payments of at least `min_value_msat`, but rejecting overpayment
+	/// of more than `max_overpayment_multiplier` times `min_value_msat`.
+	pub fn with_max_overpayment_multiplier(
+		min_value_msat: u64, max_overpayment_multiplier: u64,
+	) -> Self {
+		let max_value_msat = min_value_msat.saturating_mul(max_overpayment_multiplier);
+		Self { min_value_msat, max_value_msat }
+	}
+}
+
+impl_writeable_tlv_based!(InboundAmountRange, {
+	(0, min_value_msat, required),
+	(2, max_value_msat, required),
+});
+
 macro_rules! create_offer_builder { ($self: ident, $builder: ty) => {
 	/// Creates an [`OfferBuilder`] such that the [`Offer`] it builds is recognized by the
 	/// [`ChannelManager`] when handling [`InvoiceRequest`] messages for the offer. The offer's
@@ -13249,7 +16504,8 @@ where
 	/// # Errors
 	///
 	/// Errors if:
-	/// - the refund is for an unsupported chain, or
+	/// - the refund is for an unsupported chain,
+	/// - the [`RefundHandler`] set using [`Self::set_refund_handler`] rejected the refund, or
 	/// - the parameterized [`Router`] is unable to create a blinded payment path or reply path for
 	///   the invoice.
 	///
@@ -13261,6 +16517,16 @@ where
 		let secp_ctx = &self.secp_ctx;
 		let entropy = &*self.entropy_source;
 
+		let decision = match self.refund_handler.read().unwrap().as_ref() {
+			Some(handler) => handler.handle_refund(refund),
+			None => RefundDecision::Approve,
+		};
+		let relative_expiry = match decision {
+			RefundDecision::Approve => None,
+			RefundDecision::ApproveWithRelativeExpiry(relative_expiry) => Some(relative_expiry),
+			RefundDecision::Reject => return Err(Bolt12SemanticError::RejectedRefund),
+		};
+
 		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
 
 		let builder = self.flow.create_invoice_builder_from_refund(
@@ -13268,6 +16534,7 @@ where
 			entropy,
 			refund,
 			self.list_usable_channels(),
+			relative_expiry,
 			|amount_msats, relative_expiry| {
 				self.create_inbound_payment(Some(amount_msats), relative_expiry, None)
 					.map_err(|()| Bolt12SemanticError::InvalidAmount)
@@ -13424,30 +16691,204 @@ where
 	/// Note that a malicious eavesdropper can intuit whether an inbound payment was created by
 	/// `create_inbound_payment` or `create_inbound_payment_for_hash` based on runtime.
 	///
-	/// # Note
+	/// # Note
+	///
+	/// If you register an inbound payment with this method, then serialize the `ChannelManager`, then
+	/// deserialize it with a node running 0.0.103 and earlier, the payment will fail to be received.
+	///
+	/// Errors if `min_value_msat` is greater than total bitcoin supply.
+	///
+	/// If `min_final_cltv_expiry_delta` is set to some value, then the payment will not be receivable
+	/// on versions of LDK prior to 0.0.114.
+	///
+	/// [`create_inbound_payment`]: Self::create_inbound_payment
+	/// [`PaymentClaimable`]: events::Event::PaymentClaimable
+	pub fn create_inbound_payment_for_hash(
+		&self, payment_hash: PaymentHash, min_value_msat: Option<u64>,
+		invoice_expiry_delta_secs: u32, min_final_cltv_expiry: Option<u16>,
+	) -> Result<PaymentSecret, ()> {
+		inbound_payment::create_from_hash(
+			&self.inbound_payment_key,
+			min_value_msat,
+			payment_hash,
+			invoice_expiry_delta_secs,
+			self.highest_seen_timestamp.load(Ordering::Acquire) as u64,
+			min_final_cltv_expiry,
+		)
+	}
+
+	/// Equivalent to [`create_inbound_payment`], but for zero-amount invoices which should only
+	/// accept payments within the given `amount_range`.
+	///
+	/// Payments of less than [`InboundAmountRange::min_value_msat`] are rejected as with
+	/// [`create_inbound_payment`]'s `min_value_msat`. Additionally, any HTLC which would take the
+	/// amount the sender intends to pay over [`InboundAmountRange::max_value_msat`] is failed
+	/// back. As the amount paid is chosen by the sender, the [`PaymentClaimable::amount_msat`] of
+	/// the resulting payment should be used for accounting.
+	///
+	/// Unlike [`create_inbound_payment`], this stores the `amount_range` in the
+	/// [`ChannelManager`] until the invoice expires. Note that the maximum will not be enforced on
+	/// versions prior to 0.3.
+	///
+	/// Errors if `amount_range`'s minimum is greater than its maximum or than the total bitcoin
+	/// supply.
+	///
+	/// [`create_inbound_payment`]: Self::create_inbound_payment
+	/// [`PaymentClaimable::amount_msat`]: events::Event::PaymentClaimable::amount_msat
+	pub fn create_inbound_payment_with_amount_range(
+		&self, amount_range: InboundAmountRange, invoice_expiry_delta_secs: u32,
+		min_final_cltv_expiry_delta: Option<u16>,
+	) -> Result<(PaymentHash, PaymentSecret), ()> {
+		if amount_range.min_value_msat > amount_range.max_value_msat {
+			return Err(());
+		}
+		let (payment_hash, payment_secret) = self.create_inbound_payment(
+			Some(amount_range.min_value_msat),
+			invoice_expiry_delta_secs,
+			min_final_cltv_expiry_delta,
+		)?;
+		self.register_inbound_amount_range(payment_hash, amount_range, invoice_expiry_delta_secs);
+		Ok((payment_hash, payment_secret))
+	}
+
+	/// Equivalent to [`create_inbound_payment_for_hash`], but for zero-amount invoices which
+	/// should only accept payments within the given `amount_range`.
+	///
+	/// See [`create_inbound_payment_with_amount_range`] for details on how `amount_range` is
+	/// enforced.
+	///
+	/// [`create_inbound_payment_for_hash`]: Self::create_inbound_payment_for_hash
+	/// [`create_inbound_payment_with_amount_range`]: Self::create_inbound_payment_with_amount_range
+	pub fn create_inbound_payment_for_hash_with_amount_range(
+		&self, payment_hash: PaymentHash, amount_range: InboundAmountRange,
+		invoice_expiry_delta_secs: u32, min_final_cltv_expiry: Option<u16>,
+	) -> Result<PaymentSecret, ()> {
+		if amount_range.min_value_msat > amount_range.max_value_msat {
+			return Err(());
+		}
+		let payment_secret = self.create_inbound_payment_for_hash(
+			payment_hash,
+			Some(amount_range.min_value_msat),
+			invoice_expiry_delta_secs,
+			min_final_cltv_expiry,
+		)?;
+		self.register_inbound_amount_range(payment_hash, amount_range, invoice_expiry_delta_secs);
+		Ok(payment_secret)
+	}
+
+	fn register_inbound_amount_range(
+		&self, payment_hash: PaymentHash, range: InboundAmountRange, invoice_expiry_delta_secs: u32,
+	) {
+		let highest_seen_timestamp = self.highest_seen_timestamp.load(Ordering::Acquire) as u64;
+		let expiry_time = inbound_payment::calculate_absolute_expiry(
+			highest_seen_timestamp,
+			invoice_expiry_delta_secs,
+		);
+		let registered = RegisteredInboundAmountRange { payment_hash, range, expiry_time };
+		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
+		let mut claimable_payments = self.claimable_payments.lock().unwrap();
+		claimable_payments.inbound_amount_ranges.insert(payment_hash, registered);
+	}
+
+	/// Creates a hold invoice payment for the given `payment_hash`, returning the payment secret to
+	/// include in the invoice.
 	///
-	/// If you register an inbound payment with this method, then serialize the `ChannelManager`, then
-	/// deserialize it with a node running 0.0.103 and earlier, the payment will fail to be received.
+	/// This behaves the same as [`create_inbound_payment_for_hash`], except that once all HTLCs
+	/// for the payment have been received, an [`Event::PaymentHeld`] is generated rather than an
+	/// [`Event::PaymentClaimable`]. The payment is then held until either
+	/// [`settle_held_payment`] or [`cancel_held_payment`] is called, allowing just-in-time
+	/// fulfillment once the preimage becomes available.
 	///
-	/// Errors if `min_value_msat` is greater than total bitcoin supply.
+	/// If neither is called before [`Event::PaymentHeld::claim_deadline`], the HTLCs will be
+	/// automatically failed back. Once a held payment has been claimed or failed back, whether via
+	/// the above methods, [`claim_funds`], [`fail_htlc_backwards`] or automatically, the payment
+	/// hash is no longer treated as a hold invoice, and any further payments to it will be
+	/// surfaced as an [`Event::PaymentClaimable`] as usual. The registration is also dropped once
+	/// the invoice has expired, unless a payment to it is being held at the time.
 	///
-	/// If `min_final_cltv_expiry_delta` is set to some value, then the payment will not be receivable
-	/// on versions of LDK prior to 0.0.114.
+	/// Note that hold invoice payments will be received as regular claimable payments on versions
+	/// prior to 0.3.
 	///
-	/// [`create_inbound_payment`]: Self::create_inbound_payment
-	/// [`PaymentClaimable`]: events::Event::PaymentClaimable
-	pub fn create_inbound_payment_for_hash(
+	/// See [`create_inbound_payment_for_hash`] for details on the parameters and errors.
+	///
+	/// [`create_inbound_payment_for_hash`]: Self::create_inbound_payment_for_hash
+	/// [`settle_held_payment`]: Self::settle_held_payment
+	/// [`cancel_held_payment`]: Self::cancel_held_payment
+	/// [`claim_funds`]: Self::claim_funds
+	/// [`fail_htlc_backwards`]: Self::fail_htlc_backwards
+	/// [`Event::PaymentHeld::claim_deadline`]: events::Event::PaymentHeld::claim_deadline
+	pub fn create_hold_inbound_payment_for_hash(
 		&self, payment_hash: PaymentHash, min_value_msat: Option<u64>,
 		invoice_expiry_delta_secs: u32, min_final_cltv_expiry: Option<u16>,
 	) -> Result<PaymentSecret, ()> {
-		inbound_payment::create_from_hash(
-			&self.inbound_payment_key,
-			min_value_msat,
+		let payment_secret = self.create_inbound_payment_for_hash(
 			payment_hash,
+			min_value_msat,
 			invoice_expiry_delta_secs,
-			self.highest_seen_timestamp.load(Ordering::Acquire) as u64,
 			min_final_cltv_expiry,
-		)
+		)?;
+		let highest_seen_timestamp = self.highest_seen_timestamp.load(Ordering::Acquire) as u64;
+		let expiry_time = inbound_payment::calculate_absolute_expiry(
+			highest_seen_timestamp,
+			invoice_expiry_delta_secs,
+		);
+		let registered = RegisteredHoldInvoice { payment_hash, expiry_time };
+		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
+		let mut claimable_payments = self.claimable_payments.lock().unwrap();
+		claimable_payments.held_payment_hashes.insert(payment_hash, registered);
+		Ok(payment_secret)
+	}
+
+	/// Settles a payment previously surfaced via [`Event::PaymentHeld`], claiming it with the given
+	/// preimage.
+	///
+	/// This is equivalent to calling [`claim_funds`] for the held payment, and an
+	/// [`Event::PaymentClaimed`] will be generated once the claim is durably persisted.
+	///
+	/// Errors if the payment hash was not registered via [`create_hold_inbound_payment_for_hash`]
+	/// or if the payment has not (yet) been held.
+	///
+	/// [`claim_funds`]: Self::claim_funds
+	/// [`create_hold_inbound_payment_for_hash`]: Self::create_hold_inbound_payment_for_hash
+	pub fn settle_held_payment(&self, payment_preimage: PaymentPreimage) -> Result<(), APIError> {
+		let payment_hash = PaymentHash(Sha256::hash(&payment_preimage.0).to_byte_array());
+		{
+			let mut claimable_payments = self.claimable_payments.lock().unwrap();
+			if !claimable_payments.held_payment_hashes.contains_key(&payment_hash) {
+				return Err(APIError::APIMisuseError {
+					err: format!("Payment with hash {payment_hash} is not a hold invoice payment"),
+				});
+			}
+			if !claimable_payments.claimable_payments.contains_key(&payment_hash) {
+				return Err(APIError::APIMisuseError {
+					err: format!("Payment with hash {payment_hash} is not currently held"),
+				});
+			}
+			claimable_payments.held_payment_hashes.remove(&payment_hash);
+		}
+		self.claim_funds(payment_preimage);
+		Ok(())
+	}
+
+	/// Cancels a hold invoice payment registered via [`create_hold_inbound_payment_for_hash`],
+	/// failing any held HTLCs back to the sender.
+	///
+	/// After this call the payment hash is no longer treated as a hold invoice, and any further
+	/// payments to it will be surfaced as an [`Event::PaymentClaimable`] as usual.
+	///
+	/// Errors if the payment hash was not registered via [`create_hold_inbound_payment_for_hash`].
+	///
+	/// [`create_hold_inbound_payment_for_hash`]: Self::create_hold_inbound_payment_for_hash
+	pub fn cancel_held_payment(&self, payment_hash: &PaymentHash) -> Result<(), APIError> {
+		let registered =
+			self.claimable_payments.lock().unwrap().held_payment_hashes.remove(payment_hash);
+		if registered.is_none() {
+			return Err(APIError::APIMisuseError {
+				err: format!("Payment with hash {payment_hash} is not a hold invoice payment"),
+			});
+		}
+		self.fail_htlc_backwards(payment_hash);
+		Ok(())
 	}
 
 	/// Gets an LDK-generated payment preimage from a payment hash and payment secret that were
@@ -13499,6 +16940,46 @@ where
 		now
 	}
 
+	/// Checks that an [`InvoiceRequest`] for a recurring [`Offer`] is for a period which the payer
+	/// may currently request, returning the payer's state to record once an invoice is sent.
+	fn check_invoice_request_recurrence(
+		&self, invoice_request: &InvoiceRequestVerifiedFromOffer,
+	) -> Result<Option<PayerRecurrence>, Bolt12SemanticError> {
+		let inner = invoice_request.inner();
+		let (recurrence, counter) = match (inner.recurrence(), inner.recurrence_counter()) {
+			(Some(recurrence), Some(counter)) => (recurrence, counter),
+			(None, None) => return Ok(None),
+			// Already checked when parsing the invoice request.
+			_ => return Err(Bolt12SemanticError::MissingRecurrenceCounter),
+		};
+
+		let offer_id = invoice_request.offer_id();
+		let payer_signing_pubkey = inner.payer_signing_pubkey();
+		let recurring_offer_payers = self.recurring_offer_payers.lock().unwrap();
+		PayerRecurrence::check_and_advance(
+			recurring_offer_payers.get(&(offer_id, payer_signing_pubkey)),
+			offer_id,
+			payer_signing_pubkey,
+			&recurrence,
+			counter,
+			self.duration_since_epoch(),
+		)
+		.map(Some)
+	}
+
+	fn record_payer_recurrence(&self, payer_recurrence: PayerRecurrence) {
+		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
+		let key = (payer_recurrence.offer_id, payer_recurrence.payer_signing_pubkey);
+		let mut recurring_offer_payers = self.recurring_offer_payers.lock().unwrap();
+		match recurring_offer_payers.get(&key) {
+			// Another request may have advanced the payer's state concurrently.
+			Some(existing) if existing.next_counter >= payer_recurrence.next_counter => {},
+			_ => {
+				recurring_offer_payers.insert(key, payer_recurrence);
+			},
+		}
+	}
+
 	fn get_peers_for_blinded_path(&self) -> Vec<MessageForwardNode> {
 		let per_peer_state = self.per_peer_state.read().unwrap();
 		per_peer_state
@@ -13831,6 +17312,42 @@ where
 		let mut ev;
 		process_events_body!(self, ev, { handler(ev).await });
 	}
+
+	/// Processes any events asynchronously using the given event handler, handling up to
+	/// `max_concurrent_handlers` events at once.
+	///
+	/// The event handler is called in the order each event was generated, however the handling of
+	/// events which do not relate to a common channel or payment may overlap. An event is only
+	/// handed to `handler` once the futures for all earlier events relating to the same channel or
+	/// payment have completed, and events which do not relate to a specific channel or payment are
+	/// only handed to `handler` once all earlier events have been handled.
+	///
+	/// If handling an event fails, no further events are handed to `handler`. Any events generated
+	/// after the failed one will be replayed on the next invocation, even if they were handled
+	/// successfully.
+	///
+	/// A `max_concurrent_handlers` of 0 is treated as 1, which is equivalent to
+	/// [`Self::process_pending_events_async`].
+	///
+	/// See the trait-level documentation of [`EventsProvider`] for requirements.
+	pub async fn process_pending_events_async_concurrently<
+		Future: core::future::Future<Output = Result<(), ReplayEvent>>,
+		H: Fn(Event) -> Future,
+	>(
+		&self, handler: H, max_concurrent_handlers: usize,
+	) {
+		let handler = |event: Event| {
+			log_trace!(self.logger, "Handling event {:?} async...", event);
+			Box::pin(handler(event))
+		};
+		process_events_body!(self, batch events, {
+			let events = events.into_iter().map(|event| (event.ordering_keys(), event)).collect();
+			let results =
+				OrderedConcurrentPoller::new(events, &handler, max_concurrent_handlers).await;
+			log_trace!(self.logger, "Done handling events async, results: {:?}", results);
+			results
+		});
+	}
 }
 
 impl<
@@ -13900,7 +17417,7 @@ where
 
 						// Clean up for removal.
 						let reason = ClosureReason::DisconnectedPeer;
-						let err = ChannelError::Close((reason.to_string(), reason));
+						let err = ChannelError::Close((reason.to_string(), reason, None));
 						let (_, e) = convert_channel_err!(self, peer_state, err, chan);
 						failed_channels.push((Err(e), counterparty_node_id));
 						false
@@ -14013,6 +17530,15 @@ where
 			return Err(());
 		}
 
+		if self.message_quarantine.lock().unwrap().is_quarantined(&counterparty_node_id) {
+			log_debug!(
+				logger,
+				"Peer {} is quarantined, disconnecting",
+				log_pubkey!(counterparty_node_id)
+			);
+			return Err(());
+		}
+
 		let mut res = Ok(());
 
 		PersistenceNotifierGuard::optionally_notify(self, || {
@@ -14108,6 +17634,20 @@ where
 						ReconnectionMsg::None => {},
 					}
 				}
+
+				for entry in self.static_backup_recovery.lock().unwrap().iter() {
+					if entry.counterparty_node_id == counterparty_node_id {
+						log_debug!(
+							logger,
+							"Requesting force-close of recovering channel {}",
+							entry.channel_id
+						);
+						pending_msg_events.push(MessageSendEvent::SendChannelReestablish {
+							node_id: counterparty_node_id,
+							msg: bogus_channel_reestablish(entry.channel_id),
+						});
+					}
+				}
 			}
 
 			return NotifyOption::SkipPersistHandleEvents;
@@ -14158,11 +17698,13 @@ where
 
 			let mut is_any_peer_connected = false;
 			let mut pending_events = Vec::new();
+			let now = self.duration_since_epoch();
 			let per_peer_state = self.per_peer_state.read().unwrap();
 			for (_cp_id, peer_state_mutex) in per_peer_state.iter() {
 				let mut peer_state_lock = peer_state_mutex.lock().unwrap();
 				let peer_state = &mut *peer_state_lock;
 				if peer_state.pending_msg_events.len() > 0 {
+					peer_state.record_sent_protocol_errors(now);
 					pending_events.append(&mut peer_state.pending_msg_events);
 				}
 				if peer_state.is_connected {
@@ -14244,16 +17786,29 @@ where
 	L::Target: Logger,
 {
 	fn filtered_block_connected(&self, header: &Header, txdata: &TransactionData, height: u32) {
-		{
-			let best_block = self.best_block.read().unwrap();
-			assert_eq!(best_block.block_hash, header.prev_blockhash,
-				"Blocks must be connected in chain-order - the connected header must build on the last connected header");
-			assert_eq!(best_block.height, height - 1,
-				"Blocks must be connected in chain-order - the connected block height must be one greater than the previous height");
-		}
+		self.filtered_blocks_connected(&[(header, txdata, height)]);
+	}
+
+	fn filtered_blocks_connected(&self, blocks: &[(&Header, &TransactionData, u32)]) {
+		// Hold a single persistence guard across the batch so that we only take the
+		// `total_consistency_lock` and request a persist once, rather than once per block.
+		let _persistence_guard =
+			PersistenceNotifierGuard::optionally_notify_skipping_background_events(
+				self,
+				|| -> NotifyOption { NotifyOption::DoPersist },
+			);
+		for (header, txdata, height) in blocks.iter() {
+			{
+				let best_block = self.best_block.read().unwrap();
+				assert_eq!(best_block.block_hash, header.prev_blockhash,
+					"Blocks must be connected in chain-order - the connected header must build on the last connected header");
+				assert_eq!(best_block.height, height - 1,
+					"Blocks must be connected in chain-order - the connected block height must be one greater than the previous height");
+			}
 
-		self.transactions_confirmed(header, txdata, height);
-		self.best_block_updated(header, height);
+			self.do_transactions_confirmed(header, txdata, *height);
+			self.do_best_block_updated(header, *height);
+		}
 	}
 
 	fn blocks_disconnected(&self, fork_point: BestBlock) {
@@ -14310,15 +17865,100 @@ where
 		// during initialization prior to the chain_monitor being fully configured in some cases.
 		// See the docs for `ChannelManagerReadArgs` for more.
 
-		let block_hash = header.block_hash();
-		log_trace!(self.logger, "{} transactions included in block {} at height {} provided", txdata.len(), block_hash, height);
+		let _persistence_guard =
+			PersistenceNotifierGuard::optionally_notify_skipping_background_events(
+				self, || -> NotifyOption { NotifyOption::DoPersist });
+		self.do_transactions_confirmed(header, txdata, height);
+	}
+
+	#[rustfmt::skip]
+	fn best_block_updated(&self, header: &Header, height: u32) {
+		// Note that we MUST NOT end up calling methods on self.chain_monitor here - we're called
+		// during initialization prior to the chain_monitor being fully configured in some cases.
+		// See the docs for `ChannelManagerReadArgs` for more.
 
 		let _persistence_guard =
 			PersistenceNotifierGuard::optionally_notify_skipping_background_events(
 				self, || -> NotifyOption { NotifyOption::DoPersist });
+		self.do_best_block_updated(header, height);
+	}
+
+	fn get_relevant_txids(&self) -> Vec<(Txid, u32, Option<BlockHash>)> {
+		let mut res = Vec::with_capacity(self.short_to_chan_info.read().unwrap().len());
+		for (_cp_id, peer_state_mutex) in self.per_peer_state.read().unwrap().iter() {
+			let mut peer_state_lock = peer_state_mutex.lock().unwrap();
+			let peer_state = &mut *peer_state_lock;
+			for chan in peer_state.channel_by_id.values().filter_map(Channel::as_funded) {
+				for (funding_txid, conf_height, block_hash) in chan.get_relevant_txids() {
+					res.push((funding_txid, conf_height, block_hash));
+				}
+			}
+		}
+		res
+	}
+
+	fn transaction_unconfirmed(&self, txid: &Txid) {
+		let _persistence_guard =
+			PersistenceNotifierGuard::optionally_notify_skipping_background_events(
+				self,
+				|| -> NotifyOption { NotifyOption::DoPersist },
+			);
+		let best_block_height = self.best_block.read().unwrap().height;
+		let funding_reorg_grace_period_blocks =
+			self.config.read().unwrap().funding_reorg_grace_period_blocks;
+		self.do_chain_event(None, |channel| {
+			let logger = WithChannelContext::from(&self.logger, &channel.context, None);
+			channel
+				.transaction_unconfirmed(
+					txid,
+					best_block_height,
+					funding_reorg_grace_period_blocks,
+					&&logger,
+				)
+				.map(|()| (None, Vec::new(), None))
+		});
+	}
+}
+
+pub(super) enum FundingConfirmedMessage {
+	Establishment(msgs::ChannelReady),
+	Splice(msgs::SpliceLocked, Option<OutPoint>, Option<ChannelMonitorUpdate>, Vec<FundingInfo>),
+}
+
+impl<
+		M: Deref,
+		T: Deref,
+		ES: Deref,
+		NS: Deref,
+		SP: Deref,
+		F: Deref,
+		R: Deref,
+		MR: Deref,
+		L: Deref,
+	> ChannelManager<M, T, ES, NS, SP, F, R, MR, L>
+where
+	M::Target: chain::Watch<<SP::Target as SignerProvider>::EcdsaSigner>,
+	T::Target: BroadcasterInterface,
+	ES::Target: EntropySource,
+	NS::Target: NodeSigner,
+	SP::Target: SignerProvider,
+	F::Target: FeeEstimator,
+	R::Target: Router,
+	MR::Target: MessageRouter,
+	L::Target: Logger,
+{
+	/// Handles [`chain::Confirm::transactions_confirmed`] without taking a persistence guard,
+	/// which callers must hold.
+	#[rustfmt::skip]
+	fn do_transactions_confirmed(&self, header: &Header, txdata: &TransactionData, height: u32) {
+		let block_hash = header.block_hash();
+		log_trace!(self.logger, "{} transactions included in block {} at height {} provided", txdata.len(), block_hash, height);
+
 		self.do_chain_event(Some(height), |channel| channel.transactions_confirmed(&block_hash, height, txdata, self.chain_hash, &self.node_signer, &self.config.read().unwrap(), &&WithChannelContext::from(&self.logger, &channel.context, None))
 			.map(|(a, b)| (a, Vec::new(), b)));
 
+		self.check_static_backup_recovery_spends(txdata);
+
 		let last_best_block_height = self.best_block.read().unwrap().height;
 		if height < last_best_block_height {
 			let timestamp = self.highest_seen_timestamp.load(Ordering::Acquire);
@@ -14336,18 +17976,13 @@ where
 		}
 	}
 
+	/// Handles [`chain::Confirm::best_block_updated`] without taking a persistence guard, which
+	/// callers must hold.
 	#[rustfmt::skip]
-	fn best_block_updated(&self, header: &Header, height: u32) {
-		// Note that we MUST NOT end up calling methods on self.chain_monitor here - we're called
-		// during initialization prior to the chain_monitor being fully configured in some cases.
-		// See the docs for `ChannelManagerReadArgs` for more.
-
+	fn do_best_block_updated(&self, header: &Header, height: u32) {
 		let block_hash = header.block_hash();
 		log_trace!(self.logger, "New best block: {} at height {}", block_hash, height);
 
-		let _persistence_guard =
-			PersistenceNotifierGuard::optionally_notify_skipping_background_events(
-				self, || -> NotifyOption { NotifyOption::DoPersist });
 		*self.best_block.write().unwrap() = BestBlock::new(block_hash, height);
 
 		let mut min_anchor_feerate = None;
@@ -14369,15 +18004,30 @@ where
 			}
 		}
 
+		if self.closure_scheduler.lock().unwrap().has_incomplete() {
+			let feerate = self.fee_estimator
+				.bounded_sat_per_1000_weight(ConfirmationTarget::ChannelCloseMinimum);
+			self.closure_scheduler.lock().unwrap().record_feerate_sample(feerate);
+		}
+
 		self.do_chain_event(Some(height), |channel| {
 			let logger = WithChannelContext::from(&self.logger, &channel.context, None);
-			if channel.funding.get_channel_type().supports_anchors_zero_fee_htlc_tx() {
-				if let Some(feerate) = min_anchor_feerate {
-					channel.check_for_stale_feerate(&logger, feerate)?;
-				}
-			} else {
-				if let Some(feerate) = min_non_anchor_feerate {
-					channel.check_for_stale_feerate(&logger, feerate)?;
+			let anchors = channel.funding.get_channel_type().supports_anchors_zero_fee_htlc_tx();
+			let min_feerate = if anchors { min_anchor_feerate } else { min_non_anchor_feerate };
+			if let Some(feerate) = min_feerate {
+				let channel_id = channel.context.channel_id();
+				let counterparty_node_id = channel.context.get_counterparty_node_id();
+				if let Err(reason) = channel.check_for_stale_feerate(&logger, feerate) {
+					let force_close_reason = ForceCloseReason::from_closure_reason(&reason)
+						.expect("Stale feerate closures always map to a ForceCloseReason");
+					if !self.is_force_close_vetoed(
+						&channel_id, &counterparty_node_id, &force_close_reason, height,
+					) {
+						return Err(reason);
+					}
+					log_info!(logger, "Delaying force-close due to stale feerate as it was vetoed");
+				} else {
+					self.force_close_vetoes.lock().unwrap().remove(&channel_id);
 				}
 			}
 
@@ -14423,60 +18073,6 @@ where
 		self.flow.best_block_updated(header, height);
 	}
 
-	fn get_relevant_txids(&self) -> Vec<(Txid, u32, Option<BlockHash>)> {
-		let mut res = Vec::with_capacity(self.short_to_chan_info.read().unwrap().len());
-		for (_cp_id, peer_state_mutex) in self.per_peer_state.read().unwrap().iter() {
-			let mut peer_state_lock = peer_state_mutex.lock().unwrap();
-			let peer_state = &mut *peer_state_lock;
-			for chan in peer_state.channel_by_id.values().filter_map(Channel::as_funded) {
-				for (funding_txid, conf_height, block_hash) in chan.get_relevant_txids() {
-					res.push((funding_txid, conf_height, block_hash));
-				}
-			}
-		}
-		res
-	}
-
-	fn transaction_unconfirmed(&self, txid: &Txid) {
-		let _persistence_guard =
-			PersistenceNotifierGuard::optionally_notify_skipping_background_events(
-				self,
-				|| -> NotifyOption { NotifyOption::DoPersist },
-			);
-		self.do_chain_event(None, |channel| {
-			let logger = WithChannelContext::from(&self.logger, &channel.context, None);
-			channel.transaction_unconfirmed(txid, &&logger).map(|()| (None, Vec::new(), None))
-		});
-	}
-}
-
-pub(super) enum FundingConfirmedMessage {
-	Establishment(msgs::ChannelReady),
-	Splice(msgs::SpliceLocked, Option<OutPoint>, Option<ChannelMonitorUpdate>, Vec<FundingInfo>),
-}
-
-impl<
-		M: Deref,
-		T: Deref,
-		ES: Deref,
-		NS: Deref,
-		SP: Deref,
-		F: Deref,
-		R: Deref,
-		MR: Deref,
-		L: Deref,
-	> ChannelManager<M, T, ES, NS, SP, F, R, MR, L>
-where
-	M::Target: chain::Watch<<SP::Target as SignerProvider>::EcdsaSigner>,
-	T::Target: BroadcasterInterface,
-	ES::Target: EntropySource,
-	NS::Target: NodeSigner,
-	SP::Target: SignerProvider,
-	F::Target: FeeEstimator,
-	R::Target: Router,
-	MR::Target: MessageRouter,
-	L::Target: Logger,
-{
 	/// Calls a function which handles an on-chain event (blocks dis/connected, transactions
 	/// un/confirmed, etc) on each channel, handling any resulting errors or messages generated by
 	/// the function.
@@ -14502,8 +18098,32 @@ where
 						// Retain unfunded channels.
 						None => true,
 						Some(funded_channel) => {
+							let previous_scid = funded_channel.funding.get_short_channel_id();
+							let was_unconfirmed = funded_channel.context.funding_unconfirmed_at_height().is_some();
 							let res = f(funded_channel);
 							if let Ok((funding_confirmed_opt, mut timed_out_pending_htlcs, announcement_sigs)) = res {
+								match (was_unconfirmed, funded_channel.context.funding_unconfirmed_at_height()) {
+									(false, Some(unconfirmed_at_height)) => {
+										let grace_period_blocks = self.config.read().unwrap().funding_reorg_grace_period_blocks;
+										self.pending_events.lock().unwrap().push_back((events::Event::ChannelFundingUnconfirmed {
+											channel_id: *channel_id,
+											counterparty_node_id: *counterparty_node_id,
+											user_channel_id: funded_channel.context.get_user_id(),
+											previous_short_channel_id: previous_scid,
+											unconfirmed_at_height,
+											reconfirmation_deadline_height: unconfirmed_at_height.saturating_add(grace_period_blocks),
+										}, None));
+									},
+									(true, None) => {
+										self.pending_events.lock().unwrap().push_back((events::Event::ChannelFundingReconfirmed {
+											channel_id: *channel_id,
+											counterparty_node_id: *counterparty_node_id,
+											user_channel_id: funded_channel.context.get_user_id(),
+											short_channel_id: funded_channel.funding.get_short_channel_id(),
+										}, None));
+									},
+									_ => {},
+								}
 								for (source, payment_hash) in timed_out_pending_htlcs.drain(..) {
 									let reason = LocalHTLCFailureReason::CLTVExpiryTooSoon;
 									let data = self.get_htlc_inbound_temp_fail_data(reason);
@@ -14629,8 +18249,8 @@ where
 										// to the short_to_chan_info map here. Note that we check whether we
 										// can relay using the real SCID at relay-time (i.e.
 										// enforce option_scid_alias then), and if the funding tx is ever
-										// un-confirmed we force-close the channel, ensuring short_to_chan_info
-										// is always consistent.
+										// un-confirmed we either force-close the channel or track the prior
+										// SCID as historical, ensuring short_to_chan_info is always consistent.
 										let mut short_to_chan_info = self.short_to_chan_info.write().unwrap();
 										let scid_insert = short_to_chan_info.insert(real_scid, (funded_channel.context.get_counterparty_node_id(), *channel_id));
 										assert!(scid_insert.is_none() || scid_insert.unwrap() == (funded_channel.context.get_counterparty_node_id(), *channel_id),
@@ -14641,7 +18261,7 @@ where
 							} else if let Err(reason) = res {
 								// It looks like our counterparty went on-chain or funding transaction was
 								// reorged out of the main chain. Close the channel.
-								let err = ChannelError::Close((reason.to_string(), reason));
+								let err = ChannelError::Close((reason.to_string(), reason, None));
 								let (_, e) = convert_channel_err!(
 									self,
 									peer_state,
@@ -14664,7 +18284,9 @@ where
 		}
 
 		if let Some(height) = height_opt {
-			self.claimable_payments.lock().unwrap().claimable_payments.retain(|payment_hash, payment| {
+			let mut claimable_payments = self.claimable_payments.lock().unwrap();
+			let ClaimablePayments { claimable_payments, held_payment_hashes, .. } = &mut *claimable_payments;
+			claimable_payments.retain(|payment_hash, payment| {
 				payment.htlcs.retain(|htlc| {
 					// If height is approaching the number of blocks we think it takes us to get
 					// our commitment transaction confirmed before the HTLC expires, plus the
@@ -14678,19 +18300,31 @@ where
 						false
 					} else { true }
 				});
+				if payment.htlcs.is_empty() {
+					// Once a held payment has been failed back it is no longer held.
+					held_payment_hashes.remove(payment_hash);
+				}
 				!payment.htlcs.is_empty() // Only retain this entry if htlcs has at least one entry.
 			});
+			mem::drop(claimable_payments);
 
 			let mut intercepted_htlcs = self.pending_intercepted_htlcs.lock().unwrap();
 			intercepted_htlcs.retain(|_, htlc| {
-				if height >= htlc.forward_info.outgoing_cltv_value - HTLC_FAIL_BACK_BUFFER {
+				let expiry_reached = height >= htlc.forward_info.outgoing_cltv_value - HTLC_FAIL_BACK_BUFFER;
+				let hold_expired = htlc.intercept_hold_until_height.map_or(false, |h| height >= h);
+				if expiry_reached || hold_expired {
 					let prev_hop_data = HTLCSource::PreviousHopData(htlc.htlc_previous_hop_data());
 					let requested_forward_scid /* intercept scid */ = match htlc.forward_info.routing {
 						PendingHTLCRouting::Forward { short_channel_id, .. } => short_channel_id,
 						_ => unreachable!(),
 					};
+					let reason = if expiry_reached {
+						LocalHTLCFailureReason::ForwardExpiryBuffer
+					} else {
+						LocalHTLCFailureReason::UnknownNextPeer
+					};
 					timed_out_htlcs.push((prev_hop_data, htlc.forward_info.payment_hash,
-							HTLCFailReason::from_failure_code(LocalHTLCFailureReason::ForwardExpiryBuffer),
+							HTLCFailReason::from_failure_code(reason),
 							HTLCHandlingFailureType::InvalidForward { requested_forward_scid }));
 					let logger = WithContext::from(
 						&self.logger, None, Some(htlc.prev_channel_id), Some(htlc.forward_info.payment_hash)
@@ -14818,7 +18452,7 @@ where
 				},
 				_ => NotifyOption::SkipPersistHandleEvents,
 			};
-			let _ = self.handle_error(res, counterparty_node_id);
+			let _ = self.handle_peer_message_error(res, counterparty_node_id);
 			persist
 		});
 	}
@@ -14843,7 +18477,7 @@ where
 				},
 				_ => NotifyOption::SkipPersistHandleEvents,
 			};
-			let _ = self.handle_error(res, counterparty_node_id);
+			let _ = self.handle_peer_message_error(res, counterparty_node_id);
 			persist
 		});
 	}
@@ -14854,7 +18488,7 @@ where
 		// change to the contents.
 		let _persistence_guard = PersistenceNotifierGuard::optionally_notify(self, || {
 			let res = self.internal_accept_channel(&counterparty_node_id, msg);
-			let _ = self.handle_error(res, counterparty_node_id);
+			let _ = self.handle_peer_message_error(res, counterparty_node_id);
 			NotifyOption::SkipPersistHandleEvents
 		});
 	}
@@ -14862,30 +18496,41 @@ where
 	fn handle_accept_channel_v2(
 		&self, counterparty_node_id: PublicKey, msg: &msgs::AcceptChannelV2,
 	) {
-		let err = Err(MsgHandleErrInternal::send_err_msg_no_close(
-			"Dual-funded channels not supported".to_owned(),
-			msg.common_fields.temporary_channel_id.clone(),
-		));
-		let _: Result<(), _> = self.handle_error(err, counterparty_node_id);
+		if !self.init_features().supports_dual_fund() {
+			let err = Err(MsgHandleErrInternal::send_err_msg_no_close(
+				"Dual-funded channels not supported".to_owned(),
+				msg.common_fields.temporary_channel_id.clone(),
+			));
+			let _: Result<(), _> = self.handle_error(err, counterparty_node_id);
+			return;
+		}
+		// Note that we never need to persist the updated ChannelManager for an inbound
+		// accept_channel2 message - pre-funded channels are never written so there should be no
+		// change to the contents.
+		let _persistence_guard = PersistenceNotifierGuard::optionally_notify(self, || {
+			let res = self.internal_accept_channel_v2(&counterparty_node_id, msg);
+			let _ = self.handle_peer_message_error(res, counterparty_node_id);
+			NotifyOption::SkipPersistHandleEvents
+		});
 	}
 
 	fn handle_funding_created(&self, counterparty_node_id: PublicKey, msg: &msgs::FundingCreated) {
 		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
 		let res = self.internal_funding_created(&counterparty_node_id, msg);
-		let _ = self.handle_error(res, counterparty_node_id);
+		let _ = self.handle_peer_message_error(res, counterparty_node_id);
 	}
 
 	fn handle_funding_signed(&self, counterparty_node_id: PublicKey, msg: &msgs::FundingSigned) {
 		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
 		let res = self.internal_funding_signed(&counterparty_node_id, msg);
-		let _ = self.handle_error(res, counterparty_node_id);
+		let _ = self.handle_peer_message_error(res, counterparty_node_id);
 	}
 
 	fn handle_peer_storage(&self, counterparty_node_id: PublicKey, msg: msgs::PeerStorage) {
 		let _persistence_guard =
 			PersistenceNotifierGuard::optionally_notify(self, || NotifyOption::SkipPersistNoEvents);
 		let res = self.internal_peer_storage(counterparty_node_id, msg);
-		let _ = self.handle_error(res, counterparty_node_id);
+		let _ = self.handle_peer_message_error(res, counterparty_node_id);
 	}
 
 	fn handle_peer_storage_retrieval(
@@ -14894,7 +18539,7 @@ where
 		let _persistence_guard =
 			PersistenceNotifierGuard::optionally_notify(self, || NotifyOption::SkipPersistNoEvents);
 		let res = self.internal_peer_storage_retrieval(counterparty_node_id, msg);
-		let _ = self.handle_error(res, counterparty_node_id);
+		let _ = self.handle_peer_message_error(res, counterparty_node_id);
 	}
 
 	fn handle_channel_ready(&self, counterparty_node_id: PublicKey, msg: &msgs::ChannelReady) {
@@ -14908,7 +18553,7 @@ where
 				Err(e) if e.closes_channel() => NotifyOption::DoPersist,
 				_ => NotifyOption::SkipPersistHandleEvents,
 			};
-			let _ = self.handle_error(res, counterparty_node_id);
+			let _ = self.handle_peer_message_error(res, counterparty_node_id);
 			persist
 		});
 	}
@@ -14927,7 +18572,7 @@ where
 					}
 				},
 			};
-			let _ = self.handle_error(res, counterparty_node_id);
+			let _ = self.handle_peer_message_error(res, counterparty_node_id);
 			persist
 		});
 	}
@@ -14940,7 +18585,7 @@ where
 				Err(_) => NotifyOption::SkipPersistHandleEvents,
 				Ok(()) => NotifyOption::SkipPersistHandleEvents,
 			};
-			let _ = self.handle_error(res, counterparty_node_id);
+			let _ = self.handle_peer_message_error(res, counterparty_node_id);
 			persist
 		});
 	}
@@ -14953,7 +18598,7 @@ where
 				Err(_) => NotifyOption::SkipPersistHandleEvents,
 				Ok(()) => NotifyOption::SkipPersistHandleEvents,
 			};
-			let _ = self.handle_error(res, counterparty_node_id);
+			let _ = self.handle_peer_message_error(res, counterparty_node_id);
 			persist
 		});
 	}
@@ -14967,7 +18612,7 @@ where
 				Err(_) => NotifyOption::SkipPersistHandleEvents,
 				Ok(()) => NotifyOption::DoPersist,
 			};
-			let _ = self.handle_error(res, counterparty_node_id);
+			let _ = self.handle_peer_message_error(res, counterparty_node_id);
 			persist
 		});
 	}
@@ -14975,27 +18620,27 @@ where
 	fn handle_shutdown(&self, counterparty_node_id: PublicKey, msg: &msgs::Shutdown) {
 		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
 		let res = self.internal_shutdown(&counterparty_node_id, msg);
-		let _ = self.handle_error(res, counterparty_node_id);
+		let _ = self.handle_peer_message_error(res, counterparty_node_id);
 	}
 
 	fn handle_closing_signed(&self, counterparty_node_id: PublicKey, msg: &msgs::ClosingSigned) {
 		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
 		let res = self.internal_closing_signed(&counterparty_node_id, msg);
-		let _ = self.handle_error(res, counterparty_node_id);
+		let _ = self.handle_peer_message_error(res, counterparty_node_id);
 	}
 
 	#[cfg(simple_close)]
 	fn handle_closing_complete(&self, counterparty_node_id: PublicKey, msg: msgs::ClosingComplete) {
 		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
 		let res = self.internal_closing_complete(counterparty_node_id, msg);
-		let _ = self.handle_error(res, counterparty_node_id);
+		let _ = self.handle_peer_message_error(res, counterparty_node_id);
 	}
 
 	#[cfg(simple_close)]
 	fn handle_closing_sig(&self, counterparty_node_id: PublicKey, msg: msgs::ClosingSig) {
 		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
 		let res = self.internal_closing_sig(counterparty_node_id, msg);
-		let _ = self.handle_error(res, counterparty_node_id);
+		let _ = self.handle_peer_message_error(res, counterparty_node_id);
 	}
 
 	fn handle_update_add_htlc(&self, counterparty_node_id: PublicKey, msg: &msgs::UpdateAddHTLC) {
@@ -15009,7 +18654,7 @@ where
 				Err(_) => NotifyOption::SkipPersistHandleEvents,
 				Ok(()) => NotifyOption::SkipPersistNoEvents,
 			};
-			let _ = self.handle_error(res, counterparty_node_id);
+			let _ = self.handle_peer_message_error(res, counterparty_node_id);
 			persist
 		});
 	}
@@ -15019,7 +18664,7 @@ where
 	) {
 		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
 		let res = self.internal_update_fulfill_htlc(&counterparty_node_id, msg);
-		let _ = self.handle_error(res, counterparty_node_id);
+		let _ = self.handle_peer_message_error(res, counterparty_node_id);
 	}
 
 	fn handle_update_fail_htlc(&self, counterparty_node_id: PublicKey, msg: &msgs::UpdateFailHTLC) {
@@ -15033,7 +18678,7 @@ where
 				Err(_) => NotifyOption::SkipPersistHandleEvents,
 				Ok(()) => NotifyOption::SkipPersistNoEvents,
 			};
-			let _ = self.handle_error(res, counterparty_node_id);
+			let _ = self.handle_peer_message_error(res, counterparty_node_id);
 			persist
 		});
 	}
@@ -15051,7 +18696,7 @@ where
 				Err(_) => NotifyOption::SkipPersistHandleEvents,
 				Ok(()) => NotifyOption::SkipPersistNoEvents,
 			};
-			let _ = self.handle_error(res, counterparty_node_id);
+			let _ = self.handle_peer_message_error(res, counterparty_node_id);
 			persist
 		});
 	}
@@ -15061,7 +18706,7 @@ where
 	) {
 		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
 		let res = self.internal_commitment_signed(&counterparty_node_id, msg);
-		let _ = self.handle_error(res, counterparty_node_id);
+		let _ = self.handle_peer_message_error(res, counterparty_node_id);
 	}
 
 	fn handle_commitment_signed_batch(
@@ -15070,13 +18715,13 @@ where
 	) {
 		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
 		let res = self.internal_commitment_signed_batch(&counterparty_node_id, channel_id, batch);
-		let _ = self.handle_error(res, counterparty_node_id);
+		let _ = self.handle_peer_message_error(res, counterparty_node_id);
 	}
 
 	fn handle_revoke_and_ack(&self, counterparty_node_id: PublicKey, msg: &msgs::RevokeAndACK) {
 		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
 		let res = self.internal_revoke_and_ack(&counterparty_node_id, msg);
-		let _ = self.handle_error(res, counterparty_node_id);
+		let _ = self.handle_peer_message_error(res, counterparty_node_id);
 	}
 
 	fn handle_update_fee(&self, counterparty_node_id: PublicKey, msg: &msgs::UpdateFee) {
@@ -15090,7 +18735,7 @@ where
 				Err(_) => NotifyOption::SkipPersistHandleEvents,
 				Ok(()) => NotifyOption::SkipPersistNoEvents,
 			};
-			let _ = self.handle_error(res, counterparty_node_id);
+			let _ = self.handle_peer_message_error(res, counterparty_node_id);
 			persist
 		});
 	}
@@ -15100,13 +18745,13 @@ where
 	) {
 		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
 		let res = self.internal_announcement_signatures(&counterparty_node_id, msg);
-		let _ = self.handle_error(res, counterparty_node_id);
+		let _ = self.handle_peer_message_error(res, counterparty_node_id);
 	}
 
 	fn handle_channel_update(&self, counterparty_node_id: PublicKey, msg: &msgs::ChannelUpdate) {
 		PersistenceNotifierGuard::optionally_notify(self, || {
 			let res = self.internal_channel_update(&counterparty_node_id, msg);
-			if let Ok(persist) = self.handle_error(res, counterparty_node_id) {
+			if let Ok(persist) = self.handle_peer_message_error(res, counterparty_node_id) {
 				persist
 			} else {
 				NotifyOption::DoPersist
@@ -15119,7 +18764,7 @@ where
 	) {
 		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
 		let res = self.internal_channel_reestablish(&counterparty_node_id, msg);
-		let _ = self.handle_error(res, counterparty_node_id);
+		let _ = self.handle_peer_message_error(res, counterparty_node_id);
 	}
 
 	#[rustfmt::skip]
@@ -15143,6 +18788,10 @@ where
 							let peer_state_mutex_opt = per_peer_state.get(&counterparty_node_id);
 							if peer_state_mutex_opt.is_none() { return NotifyOption::SkipPersistNoEvents; }
 							let mut peer_state = peer_state_mutex_opt.unwrap().lock().unwrap();
+							peer_state.record_protocol_error(
+								&msg.channel_id, ProtocolErrorDirection::Received,
+								ProtocolErrorKind::Error, &msg.data, self.duration_since_epoch(),
+							);
 							if let Some(chan) = peer_state.channel_by_id
 								.get(&msg.channel_id)
 								.and_then(Channel::as_funded)
@@ -15205,6 +18854,10 @@ where
 				if peer_state_mutex_opt.is_none() { return; }
 				let mut peer_state_lock = peer_state_mutex_opt.unwrap().lock().unwrap();
 				let peer_state = &mut *peer_state_lock;
+				peer_state.record_protocol_error(
+					&msg.channel_id, ProtocolErrorDirection::Received, ProtocolErrorKind::Error,
+					&msg.data, self.duration_since_epoch(),
+				);
 				match peer_state.channel_by_id.get_mut(&msg.channel_id) {
 					Some(chan) => match chan.maybe_handle_error_without_close(
 						self.chain_hash, &self.fee_estimator, &self.logger,
@@ -15235,6 +18888,25 @@ where
 		}
 	}
 
+	fn handle_warning(&self, counterparty_node_id: PublicKey, msg: &msgs::WarningMessage) {
+		if msg.channel_id.is_zero() {
+			return;
+		}
+		// Warnings may be sent to us in a tight loop, so we only record them in memory and let them
+		// be persisted along with the next `ChannelManager` write.
+		let per_peer_state = self.per_peer_state.read().unwrap();
+		if let Some(peer_state_mutex) = per_peer_state.get(&counterparty_node_id) {
+			let mut peer_state = peer_state_mutex.lock().unwrap();
+			peer_state.record_protocol_error(
+				&msg.channel_id,
+				ProtocolErrorDirection::Received,
+				ProtocolErrorKind::Warning,
+				&msg.data,
+				self.duration_since_epoch(),
+			);
+		}
+	}
+
 	fn get_chain_hashes(&self) -> Option<Vec<ChainHash>> {
 		Some(vec![self.chain_hash])
 	}
@@ -15436,6 +19108,16 @@ where
 					Err(_) => return None,
 				};
 
+				let recurrence_update = match self.check_invoice_request_recurrence(&invoice_request) {
+					Ok(recurrence_update) => recurrence_update,
+					Err(error) => {
+						return Some((
+							OffersMessage::InvoiceError(InvoiceError::from(error)),
+							responder.respond(),
+						));
+					},
+				};
+
 				let get_payment_info = |amount_msats, relative_expiry| {
 					self.create_inbound_payment(
 						Some(amount_msats),
@@ -15502,10 +19184,15 @@ where
 				};
 
 				Some(match result {
-					Ok(invoice) => (
-						OffersMessage::Invoice(invoice),
-						responder.respond_with_reply_path(context),
-					),
+					Ok(invoice) => {
+						if let Some(payer_recurrence) = recurrence_update {
+							self.record_payer_recurrence(payer_recurrence);
+						}
+						(
+							OffersMessage::Invoice(invoice),
+							responder.respond_with_reply_path(context),
+						)
+					},
 					Err(error) => (
 						OffersMessage::InvoiceError(error),
 						responder.respond(),
@@ -15871,6 +19558,20 @@ where
 	}
 }
 
+/// Builds an invalid [`msgs::ChannelReestablish`] with `0` commitment numbers, prompting the
+/// counterparty to force-close the channel by broadcasting its latest commitment transaction.
+fn bogus_channel_reestablish(channel_id: ChannelId) -> msgs::ChannelReestablish {
+	msgs::ChannelReestablish {
+		channel_id,
+		next_local_commitment_number: 0,
+		next_remote_commitment_number: 0,
+		your_last_per_commitment_secret: [1u8; 32],
+		my_current_per_commitment_point: PublicKey::from_slice(&[2u8; 33]).unwrap(),
+		next_funding: None,
+		my_current_funding_locked: None,
+	}
+}
+
 /// Fetches the set of [`NodeFeatures`] flags that are provided by or required by
 /// [`ChannelManager`].
 pub(crate) fn provided_node_features(config: &UserConfig) -> NodeFeatures {
@@ -15972,6 +19673,7 @@ impl_writeable_tlv_based_enum!(PendingHTLCRouting,
 		(2, short_channel_id, required),
 		(3, incoming_cltv_expiry, option),
 		(4, hold_htlc, option),
+		(5, incoming_endorsed, (default_value, false)),
 	},
 	(1, Receive) => {
 		(0, payment_data, required),
@@ -16278,6 +19980,7 @@ impl_writeable_tlv_based!(PendingAddHTLCInfo, {
 	// filled in, so we can safely unwrap it here.
 	(7, prev_channel_id, (default_value, ChannelId::v1_from_funding_outpoint(prev_funding_outpoint.0.unwrap()))),
 	(9, prev_counterparty_node_id, required),
+	(11, intercept_hold_until_height, option),
 });
 
 impl Writeable for HTLCForwardInfo {
@@ -16461,6 +20164,10 @@ where
 			htlc_purposes.push(&payment.purpose);
 			htlc_onion_fields.push(&payment.onion_fields);
 		}
+		let held_payment_hashes: Vec<&RegisteredHoldInvoice> =
+			claimable_payments.held_payment_hashes.values().collect();
+		let inbound_amount_ranges: Vec<&RegisteredInboundAmountRange> =
+			claimable_payments.inbound_amount_ranges.values().collect();
 
 		let mut monitor_update_blocked_actions_per_peer = None;
 		let mut peer_states = Vec::new();
@@ -16614,6 +20321,11 @@ where
 			}
 		}
 
+		let static_backup_recovery = self.static_backup_recovery.lock().unwrap().clone();
+		let recurring_offer_payers: Vec<PayerRecurrence> =
+			self.recurring_offer_payers.lock().unwrap().values().cloned().collect();
+		let quarantined_peers = self.message_quarantine.lock().unwrap().list_quarantined();
+
 		write_tlv_fields!(writer, {
 			(1, pending_outbound_payments_no_retry, required),
 			(2, pending_intercepted_htlcs, option),
@@ -16632,6 +20344,11 @@ where
 			(17, in_flight_monitor_updates, option),
 			(19, peer_storage_dir, optional_vec),
 			(21, WithoutLength(&self.flow.writeable_async_receive_offer_cache()), required),
+			(23, static_backup_recovery, optional_vec),
+			(25, recurring_offer_payers, optional_vec),
+			(27, quarantined_peers, optional_vec),
+			(29, held_payment_hashes, optional_vec),
+			(31, inbound_amount_ranges, optional_vec),
 		});
 
 		// Remove the SpliceFailed events added earlier.
@@ -17184,6 +20901,7 @@ where
 					update_id: monitor.get_latest_update_id().saturating_add(1),
 					updates: vec![ChannelMonitorUpdateStep::ChannelForceClosed {
 						should_broadcast: true,
+						holder_commitment_stale: false,
 					}],
 					channel_id: Some(monitor.channel_id()),
 				};
@@ -17331,6 +21049,11 @@ where
 		let mut inbound_payment_id_secret = None;
 		let mut peer_storage_dir: Option<Vec<(PublicKey, Vec<u8>)>> = None;
 		let mut async_receive_offer_cache: AsyncReceiveOfferCache = AsyncReceiveOfferCache::new();
+		let mut static_backup_recovery: Option<Vec<StaticChannelBackupEntry>> = None;
+		let mut recurring_offer_payers_vec: Option<Vec<PayerRecurrence>> = None;
+		let mut quarantined_peers: Option<Vec<QuarantinedPeer>> = None;
+		let mut held_payment_hashes: Option<Vec<RegisteredHoldInvoice>> = None;
+		let mut inbound_amount_ranges: Option<Vec<RegisteredInboundAmountRange>> = None;
 		read_tlv_fields!(reader, {
 			(1, pending_outbound_payments_no_retry, option),
 			(2, pending_intercepted_htlcs, option),
@@ -17349,7 +21072,27 @@ where
 			(17, in_flight_monitor_updates, option),
 			(19, peer_storage_dir, optional_vec),
 			(21, async_receive_offer_cache, (default_value, async_receive_offer_cache)),
+			(23, static_backup_recovery, optional_vec),
+			(25, recurring_offer_payers_vec, optional_vec),
+			(27, quarantined_peers, optional_vec),
+			(29, held_payment_hashes, optional_vec),
+			(31, inbound_amount_ranges, optional_vec),
 		});
+		let held_payment_hashes: HashMap<PaymentHash, RegisteredHoldInvoice> = held_payment_hashes
+			.unwrap_or_else(Vec::new)
+			.into_iter()
+			.map(|registered| (registered.payment_hash, registered))
+			.collect();
+		let inbound_amount_ranges: HashMap<PaymentHash, RegisteredInboundAmountRange> =
+			inbound_amount_ranges
+				.unwrap_or_else(Vec::new)
+				.into_iter()
+				.map(|registered| (registered.payment_hash, registered))
+				.collect();
+		let mut recurring_offer_payers = new_hash_map();
+		for payer in recurring_offer_payers_vec.unwrap_or_else(Vec::new) {
+			recurring_offer_payers.insert((payer.offer_id, payer.payer_signing_pubkey), payer);
+		}
 		let mut decode_update_add_htlcs = decode_update_add_htlcs.unwrap_or_else(|| new_hash_map());
 		let peer_storage_dir: Vec<(PublicKey, Vec<u8>)> = peer_storage_dir.unwrap_or_else(Vec::new);
 		if fake_scid_rand_bytes.is_none() {
@@ -18254,6 +21997,8 @@ where
 			claimable_payments: Mutex::new(ClaimablePayments {
 				claimable_payments,
 				pending_claiming_payments: pending_claiming_payments.unwrap(),
+				held_payment_hashes,
+				inbound_amount_ranges,
 			}),
 			outbound_scid_aliases: Mutex::new(outbound_scid_aliases),
 			short_to_chan_info: FairRwLock::new(short_to_chan_info),
@@ -18282,9 +22027,16 @@ where
 			event_persist_notifier: Notifier::new(),
 			needs_persist_flag: AtomicBool::new(false),
 
+			channel_snapshot: Mutex::new(Arc::new(ChannelSnapshot {
+				channels: Vec::new(),
+				best_block_height,
+			})),
+			channel_snapshot_stale: AtomicBool::new(true),
+
 			funding_batch_states: Mutex::new(BTreeMap::new()),
 
 			pending_broadcast_messages: Mutex::new(Vec::new()),
+			signed_channel_updates: Mutex::new(new_hash_map()),
 
 			entropy_source: args.entropy_source,
 			node_signer: args.node_signer,
@@ -18294,6 +22046,23 @@ where
 
 			logger: args.logger,
 			config: RwLock::new(args.config),
+			update_fee_policy: RwLock::new(Box::new(DefaultUpdateFeePolicy::default())),
+			inbound_channel_fee_policy: RwLock::new(Box::new(DefaultInboundChannelFeePolicy)),
+			misbehavior_ledger: Mutex::new(MisbehaviorLedger::new()),
+			local_reputation: Mutex::new(LocalReputationTracker::new()),
+			message_quarantine: Mutex::new(MessageQuarantine::from_quarantined(
+				quarantined_peers.unwrap_or_else(Vec::new),
+			)),
+			closure_scheduler: Mutex::new(ClosureScheduler::new()),
+			dynamic_fees: Mutex::new(None),
+			static_backup_recovery: Mutex::new(static_backup_recovery.unwrap_or_else(Vec::new)),
+			recovery_mode: AtomicBool::new(false),
+			recurring_offer_payers: Mutex::new(recurring_offer_payers),
+			refund_handler: RwLock::new(None),
+			peer_reputation: RwLock::new(None),
+			force_close_observer: RwLock::new(None),
+			force_close_vetoes: Mutex::new(new_hash_map()),
+			forwarding_policy: RwLock::new(None),
 
 			#[cfg(feature = "_test_utils")]
 			testing_dnssec_proof_offer_resolution_override: Mutex::new(new_hash_map()),
@@ -18584,19 +22353,24 @@ where
 #[cfg(test)]
 mod tests {
 	use crate::events::{ClosureReason, Event, HTLCHandlingFailureType};
+	use crate::ln::channel_state::{ChannelLifecycleState, ExpectedChannelMessage};
 	use crate::ln::channelmanager::{
 		create_recv_pending_htlc_info, inbound_payment, HTLCForwardInfo, InterceptId, PaymentId,
-		RecipientOnionFields,
+		RecipientOnionFields, CHANNEL_UPDATE_SIGNATURE_CACHE_TICKS,
 	};
+	use crate::ln::forwarding_policy::{ForwardCandidate, ForwardingDecision, ForwardingPolicy};
 	use crate::ln::functional_test_utils::*;
 	use crate::ln::msgs::{self, BaseMessageHandler, ChannelMessageHandler, MessageSendEvent};
 	use crate::ln::onion_utils::AttributionData;
 	use crate::ln::onion_utils::{self, LocalHTLCFailureReason};
 	use crate::ln::outbound_payment::Retry;
+	use crate::ln::peer_misbehavior::{InboundHTLCLimits, MisbehaviorIncident, PeerReputation};
+	use crate::ln::surge_pricing::SurgePricingConfig;
 	use crate::ln::types::ChannelId;
 	use crate::prelude::*;
 	use crate::routing::router::{find_route, PaymentParameters, RouteParameters};
 	use crate::sign::EntropySource;
+	use crate::sync::{Arc, Mutex};
 	use crate::types::payment::{PaymentHash, PaymentPreimage, PaymentSecret};
 	use crate::util::config::{ChannelConfig, ChannelConfigUpdate};
 	use crate::util::errors::APIError;
@@ -18606,6 +22380,45 @@ mod tests {
 	use bitcoin::secp256k1::{PublicKey, Secp256k1, SecretKey};
 	use core::sync::atomic::Ordering;
 
+	#[test]
+	#[cfg(feature = "self_test")]
+	fn test_run_self_test() {
+		// Check that the self-test completes a loopback channel open, update, and close without
+		// leaving any channel, monitor, or broadcast transaction behind, and that it fails without
+		// leaving its monitor in the persister if the persister fails to persist any of its monitor
+		// updates.
+		let chanmon_cfgs = create_chanmon_cfgs(2);
+		let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+		let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[None, None]);
+		let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+
+		let persister = test_utils::TestPersister::new();
+		nodes[0].node.run_self_test(&persister).unwrap();
+
+		// The monitor is archived once the channel closes.
+		assert!(persister.offchain_monitor_updates.lock().unwrap().is_empty());
+		assert!(nodes[0].node.list_channels().is_empty());
+		assert!(nodes[0].chain_monitor.added_monitors.lock().unwrap().is_empty());
+		assert!(nodes[0].tx_broadcaster.txn_broadcast().is_empty());
+		assert!(nodes[0].node.get_and_clear_pending_msg_events().is_empty());
+
+		// Have the persister complete the new monitor but fail the first update to it.
+		persister.set_update_ret(crate::chain::ChannelMonitorUpdateStatus::Completed);
+		persister.set_update_ret(crate::chain::ChannelMonitorUpdateStatus::UnrecoverableError);
+		assert!(nodes[0].node.run_self_test(&persister).is_err());
+		assert!(persister.update_rets.lock().unwrap().is_empty());
+
+		// The monitor is archived even though the self-test failed.
+		assert!(persister.offchain_monitor_updates.lock().unwrap().is_empty());
+		assert!(persister.chain_sync_monitor_persistences.lock().unwrap().is_empty());
+
+		// The same goes if the persister fails to persist the new monitor itself.
+		persister.set_update_ret(crate::chain::ChannelMonitorUpdateStatus::InProgress);
+		assert!(nodes[0].node.run_self_test(&persister).is_err());
+		assert!(persister.update_rets.lock().unwrap().is_empty());
+		assert!(persister.offchain_monitor_updates.lock().unwrap().is_empty());
+	}
+
 	#[test]
 	#[rustfmt::skip]
 	fn test_notify_limits() {
@@ -19581,6 +23394,525 @@ mod tests {
 		assert_eq!(events.len(), 0);
 	}
 
+	#[test]
+	fn test_channel_update_signature_cache() {
+		// Check that we cache the signed `channel_update`s we generate, handing out the cached
+		// message as long as the channel's policy hasn't changed, and that the cache expires.
+		let chanmon_cfg = create_chanmon_cfgs(2);
+		let node_cfg = create_node_cfgs(2, &chanmon_cfg);
+		let node_chanmgr = create_node_chanmgrs(2, &node_cfg, &[None, None]);
+		let nodes = create_network(2, &node_cfg, &node_chanmgr);
+		let node_b_id = nodes[1].node.get_our_node_id();
+		let chan_id = create_announced_chan_between_nodes(&nodes, 0, 1).2;
+
+		let mut config = nodes[0].node.list_channels()[0].config.unwrap();
+		config.forwarding_fee_base_msat += 10;
+		nodes[0].node.update_channel_config(&node_b_id, &[chan_id], &config).unwrap();
+		let events = nodes[0].node.get_and_clear_pending_msg_events();
+		let broadcast_update = match &events[..] {
+			[MessageSendEvent::BroadcastChannelUpdate { msg, .. }] => msg.clone(),
+			_ => panic!("expected BroadcastChannelUpdate event"),
+		};
+		let signed_channel_updates = nodes[0].node.signed_channel_updates.lock().unwrap();
+		assert_eq!(signed_channel_updates.get(&chan_id).unwrap().0, broadcast_update);
+		core::mem::drop(signed_channel_updates);
+
+		let unicast_update = {
+			let per_peer_state = nodes[0].node.per_peer_state.read().unwrap();
+			let peer_state = per_peer_state.get(&node_b_id).unwrap().lock().unwrap();
+			let chan = peer_state.channel_by_id.get(&chan_id).unwrap().as_funded().unwrap();
+			nodes[0].node.get_channel_update_for_unicast(chan).unwrap().0
+		};
+		assert_eq!(unicast_update, broadcast_update);
+
+		for _ in 0..CHANNEL_UPDATE_SIGNATURE_CACHE_TICKS {
+			nodes[0].node.timer_tick_occurred();
+		}
+		assert!(nodes[0].node.signed_channel_updates.lock().unwrap().get(&chan_id).is_none());
+	}
+
+	#[test]
+	fn test_dynamic_fee_policy() {
+		// Check that the surge pricing built into the `ChannelManager` raises a channel's fees
+		// according to its outbound liquidity on the next timer tick, and only broadcasts a
+		// `channel_update` if the fees actually changed.
+		let chanmon_cfg = create_chanmon_cfgs(2);
+		let node_cfg = create_node_cfgs(2, &chanmon_cfg);
+		let node_chanmgr = create_node_chanmgrs(2, &node_cfg, &[None, None]);
+		let nodes = create_network(2, &node_cfg, &node_chanmgr);
+		create_announced_chan_between_nodes(&nodes, 0, 1);
+
+		let baseline = nodes[1].node.list_channels()[0].config.unwrap();
+		nodes[0].node.set_dynamic_fee_policy(Some(SurgePricingConfig::default()));
+		nodes[1].node.set_dynamic_fee_policy(Some(SurgePricingConfig::default()));
+
+		// The channel funder holds almost all of the channel's liquidity, so its fees are unchanged.
+		nodes[0].node.timer_tick_occurred();
+		let config = nodes[0].node.list_channels()[0].config.unwrap();
+		assert_eq!(config.forwarding_fee_base_msat, baseline.forwarding_fee_base_msat);
+		assert!(nodes[0].node.get_and_clear_pending_msg_events().is_empty());
+
+		// Its counterparty holds almost none, so its fees are raised to the maximum multiplier.
+		nodes[1].node.timer_tick_occurred();
+		let config = nodes[1].node.list_channels()[0].config.unwrap();
+		let expected_base_msat = baseline.forwarding_fee_base_msat * 5;
+		assert_eq!(config.forwarding_fee_base_msat, expected_base_msat);
+		assert_eq!(
+			config.forwarding_fee_proportional_millionths,
+			baseline.forwarding_fee_proportional_millionths * 5
+		);
+		let events = nodes[1].node.get_and_clear_pending_msg_events();
+		match &events[..] {
+			[MessageSendEvent::BroadcastChannelUpdate { msg, .. }] => {
+				assert_eq!(msg.contents.fee_base_msat, expected_base_msat);
+			},
+			_ => panic!("expected BroadcastChannelUpdate event"),
+		}
+
+		nodes[1].node.timer_tick_occurred();
+		assert!(nodes[1].node.get_and_clear_pending_msg_events().is_empty());
+
+		// Disabling surge pricing leaves the fees as they are.
+		nodes[1].node.set_dynamic_fee_policy(None);
+		nodes[1].node.timer_tick_occurred();
+		let config = nodes[1].node.list_channels()[0].config.unwrap();
+		assert_eq!(config.forwarding_fee_base_msat, expected_base_msat);
+		assert!(nodes[1].node.get_and_clear_pending_msg_events().is_empty());
+
+		// Re-enabling it, as after a restart, doesn't compound the multiplier as the baseline is
+		// derived from the default config rather than the channel's current fees.
+		nodes[1].node.set_dynamic_fee_policy(Some(SurgePricingConfig::default()));
+		nodes[1].node.timer_tick_occurred();
+		let config = nodes[1].node.list_channels()[0].config.unwrap();
+		assert_eq!(config.forwarding_fee_base_msat, expected_base_msat);
+		assert!(nodes[1].node.get_and_clear_pending_msg_events().is_empty());
+	}
+
+	#[test]
+	fn test_channel_snapshot() {
+		// Check that channel snapshots are only rebuilt once our channels may have changed, and that
+		// they reflect the updated balances when they are.
+		let chanmon_cfg = create_chanmon_cfgs(2);
+		let node_cfg = create_node_cfgs(2, &chanmon_cfg);
+		let node_chanmgr = create_node_chanmgrs(2, &node_cfg, &[None, None]);
+		let nodes = create_network(2, &node_cfg, &node_chanmgr);
+		create_announced_chan_between_nodes(&nodes, 0, 1);
+
+		let snapshot = nodes[0].node.channel_snapshot();
+		assert_eq!(snapshot.channels, nodes[0].node.list_channels());
+		assert!(crate::sync::Arc::ptr_eq(&snapshot, &nodes[0].node.channel_snapshot()));
+
+		send_payment(&nodes[0], &[&nodes[1]], 1_000_000);
+		let new_snapshot = nodes[0].node.channel_snapshot();
+		assert_eq!(new_snapshot.channels, nodes[0].node.list_channels());
+		assert_eq!(
+			new_snapshot.total_outbound_capacity_msat() + 1_000_000,
+			snapshot.total_outbound_capacity_msat()
+		);
+	}
+
+	fn expect_lifecycle_event(
+		node: &Node, expected_channel_id: ChannelId,
+		expected_previous_state: Option<ChannelLifecycleState>,
+		expected_new_state: ChannelLifecycleState,
+	) {
+		let events = node.node.get_and_clear_pending_events();
+		assert_eq!(events.len(), 1);
+		match &events[0] {
+			Event::ChannelLifecycleStateChanged {
+				channel_id, previous_state, new_state, ..
+			} => {
+				assert_eq!(*channel_id, expected_channel_id);
+				assert_eq!(*previous_state, expected_previous_state);
+				assert_eq!(*new_state, expected_new_state);
+			},
+			_ => panic!("Unexpected event"),
+		}
+	}
+
+	#[test]
+	fn test_channel_lifecycle_info() {
+		// Check that the channel state machine can be introspected and that, once enabled, changes
+		// to it are reported via events.
+		let chanmon_cfg = create_chanmon_cfgs(2);
+		let node_cfg = create_node_cfgs(2, &chanmon_cfg);
+		let node_chanmgr = create_node_chanmgrs(2, &node_cfg, &[None, None]);
+		let nodes = create_network(2, &node_cfg, &node_chanmgr);
+		let node_b_id = nodes[1].node.get_our_node_id();
+		let chan_id = create_announced_chan_between_nodes(&nodes, 0, 1).2;
+
+		let info = nodes[0].node.channel_lifecycle_info(&chan_id, &node_b_id).unwrap();
+		assert_eq!(info.state, ChannelLifecycleState::ChannelReady);
+		assert_eq!(info.allowed_transitions, vec![ChannelLifecycleState::ShuttingDown]);
+		assert!(info.expected_messages.is_empty());
+		assert!(!info.is_peer_disconnected);
+		assert!(!info.is_monitor_update_in_progress);
+		assert!(!info.is_quiescent);
+		assert!(nodes[0].node.channel_lifecycle_info(&ChannelId([0; 32]), &node_b_id).is_none());
+
+		// No events are generated until enabled, at which point the current state is reported.
+		assert!(nodes[0].node.get_and_clear_pending_events().is_empty());
+		let mut config = nodes[0].node.get_current_config();
+		config.emit_channel_lifecycle_events = true;
+		nodes[0].node.set_current_config(config);
+		expect_lifecycle_event(&nodes[0], chan_id, None, ChannelLifecycleState::ChannelReady);
+		assert!(nodes[0].node.get_and_clear_pending_events().is_empty());
+
+		// Once we initiate a shutdown, we're waiting on our counterparty's `shutdown`.
+		nodes[0].node.close_channel(&chan_id, &node_b_id).unwrap();
+		get_event_msg!(nodes[0], MessageSendEvent::SendShutdown, node_b_id);
+		let info = nodes[0].node.channel_lifecycle_info(&chan_id, &node_b_id).unwrap();
+		assert_eq!(info.state, ChannelLifecycleState::ShuttingDown);
+		assert_eq!(info.allowed_transitions, vec![ChannelLifecycleState::ShutdownComplete]);
+		assert_eq!(info.expected_messages, vec![ExpectedChannelMessage::Shutdown]);
+		expect_lifecycle_event(
+			&nodes[0],
+			chan_id,
+			Some(ChannelLifecycleState::ChannelReady),
+			ChannelLifecycleState::ShuttingDown,
+		);
+
+		// A newly opened channel is waiting on our counterparty's `accept_channel`.
+		let temp_chan_id =
+			nodes[0].node.create_channel(node_b_id, 100_000, 0, 42, None, None).unwrap();
+		get_event_msg!(nodes[0], MessageSendEvent::SendOpenChannel, node_b_id);
+		let info = nodes[0].node.channel_lifecycle_info(&temp_chan_id, &node_b_id).unwrap();
+		assert_eq!(info.state, ChannelLifecycleState::NegotiatingFunding);
+		assert_eq!(
+			info.allowed_transitions,
+			vec![
+				ChannelLifecycleState::FundingNegotiated,
+				ChannelLifecycleState::AwaitingChannelReady
+			]
+		);
+		assert_eq!(info.expected_messages, vec![ExpectedChannelMessage::AcceptChannel]);
+		expect_lifecycle_event(
+			&nodes[0],
+			temp_chan_id,
+			None,
+			ChannelLifecycleState::NegotiatingFunding,
+		);
+	}
+
+	#[test]
+	fn test_peer_quarantined_after_message_failures() {
+		// Check that a peer repeatedly sending messages we fail to handle is quarantined, and can
+		// reconnect once released.
+		let chanmon_cfg = create_chanmon_cfgs(2);
+		let node_cfg = create_node_cfgs(2, &chanmon_cfg);
+		let mut config = test_default_channel_config();
+		config.quarantine_after_message_failures = 2;
+		let node_chanmgr = create_node_chanmgrs(2, &node_cfg, &[None, Some(config.clone())]);
+		let (persister, chain_monitor, node_b);
+		let mut nodes = create_network(2, &node_cfg, &node_chanmgr);
+		let node_a_id = nodes[0].node.get_our_node_id();
+		let node_b_id = nodes[1].node.get_our_node_id();
+		let chan_id = create_announced_chan_between_nodes(&nodes, 0, 1).2;
+
+		// A message for an unknown channel is rejected with an error.
+		let update_fee = msgs::UpdateFee { channel_id: ChannelId([42; 32]), feerate_per_kw: 253 };
+		nodes[1].node.handle_update_fee(node_a_id, &update_fee);
+		let msg_events = nodes[1].node.get_and_clear_pending_msg_events();
+		assert_eq!(msg_events.len(), 1);
+		assert!(nodes[1].node.get_and_clear_pending_events().is_empty());
+		assert!(nodes[1].node.list_quarantined_peers().is_empty());
+
+		// Once the threshold is reached, the peer is quarantined and disconnected.
+		nodes[1].node.handle_update_fee(node_a_id, &update_fee);
+		let msg_events = nodes[1].node.get_and_clear_pending_msg_events();
+		assert_eq!(msg_events.len(), 2);
+		match &msg_events[1] {
+			MessageSendEvent::HandleError {
+				node_id,
+				action: msgs::ErrorAction::DisconnectPeer { msg: None },
+			} => assert_eq!(*node_id, node_a_id),
+			_ => panic!("Unexpected event"),
+		}
+		let events = nodes[1].node.get_and_clear_pending_events();
+		assert_eq!(events.len(), 1);
+		match &events[0] {
+			Event::PeerQuarantined {
+				counterparty_node_id,
+				channel_id,
+				consecutive_failures,
+				..
+			} => {
+				assert_eq!(*counterparty_node_id, node_a_id);
+				assert_eq!(*channel_id, Some(ChannelId([42; 32])));
+				assert_eq!(*consecutive_failures, 2);
+			},
+			_ => panic!("Unexpected event"),
+		}
+		let quarantined = nodes[1].node.list_quarantined_peers();
+		assert_eq!(quarantined.len(), 1);
+		assert_eq!(quarantined[0].counterparty_node_id, node_a_id);
+
+		// The quarantine survives a restart.
+		nodes[0].node.peer_disconnected(node_b_id);
+		let monitor_ser = get_monitor!(nodes[1], chan_id).encode();
+		let node_b_ser = nodes[1].node.encode();
+		reload_node!(
+			nodes[1],
+			config,
+			node_b_ser,
+			&[&monitor_ser],
+			persister,
+			chain_monitor,
+			node_b
+		);
+		assert_eq!(nodes[1].node.list_quarantined_peers(), quarantined);
+
+		// Reconnection is refused until the peer is released.
+		let init_msg = msgs::Init {
+			features: nodes[0].node.init_features(),
+			networks: None,
+			remote_network_address: None,
+		};
+		assert!(nodes[1].node.peer_connected(node_a_id, &init_msg, true).is_err());
+
+		nodes[1].node.release_quarantined_peer(&node_a_id).unwrap();
+		assert!(nodes[1].node.release_quarantined_peer(&node_a_id).is_err());
+		assert!(nodes[1].node.list_quarantined_peers().is_empty());
+		reconnect_nodes(ReconnectArgs::new(&nodes[0], &nodes[1]));
+	}
+
+	struct SingleSlotReputation(PublicKey);
+
+	impl PeerReputation for SingleSlotReputation {
+		fn inbound_htlc_limits(
+			&self, counterparty_node_id: &PublicKey, _channel_id: &ChannelId,
+			_incidents: &[MisbehaviorIncident],
+		) -> Option<InboundHTLCLimits> {
+			if *counterparty_node_id == self.0 {
+				let limits = InboundHTLCLimits {
+					max_accepted_htlcs: 1,
+					max_htlc_value_in_flight_msat: u64::max_value(),
+				};
+				Some(limits)
+			} else {
+				None
+			}
+		}
+	}
+
+	#[test]
+	fn test_peer_reputation_limits_inbound_htlcs() {
+		// Check that HTLCs in excess of the limits set by a `PeerReputation` are failed back, while
+		// those within the limits are accepted as usual.
+		let chanmon_cfg = create_chanmon_cfgs(2);
+		let node_cfg = create_node_cfgs(2, &chanmon_cfg);
+		let node_chanmgr = create_node_chanmgrs(2, &node_cfg, &[None, None]);
+		let nodes = create_network(2, &node_cfg, &node_chanmgr);
+		let node_a_id = nodes[0].node.get_our_node_id();
+		let node_b_id = nodes[1].node.get_our_node_id();
+		create_announced_chan_between_nodes(&nodes, 0, 1);
+
+		nodes[1].node.set_peer_reputation(SingleSlotReputation(node_a_id));
+		let (preimage, ..) = route_payment(&nodes[0], &[&nodes[1]], 100_000);
+
+		// A second HTLC would exceed the single slot the peer is allowed.
+		let (route, hash, _, secret) = get_route_and_payment_hash!(nodes[0], nodes[1], 100_000);
+		let onion = RecipientOnionFields::secret_only(secret);
+		nodes[0].node.send_payment_with_route(route, hash, onion, PaymentId(hash.0)).unwrap();
+		check_added_monitors(&nodes[0], 1);
+		let mut events = nodes[0].node.get_and_clear_pending_msg_events();
+		assert_eq!(events.len(), 1);
+		let fail = HTLCHandlingFailureType::Receive { payment_hash: hash };
+		let args = PassAlongPathArgs::new(&nodes[0], &[&nodes[1]], 100_000, hash, events.remove(0))
+			.with_payment_secret(secret)
+			.expect_failure(fail);
+		do_pass_along_path(args);
+		let updates = get_htlc_update_msgs(&nodes[1], &node_a_id);
+		assert_eq!(updates.update_fail_htlcs.len(), 1);
+		nodes[0].node.handle_update_fail_htlc(node_b_id, &updates.update_fail_htlcs[0]);
+		do_commitment_signed_dance(&nodes[0], &nodes[1], &updates.commitment_signed, false, false);
+		let conditions = PaymentFailedConditions::new()
+			.expected_htlc_error_data(LocalHTLCFailureReason::TemporaryChannelFailure, &[0, 0]);
+		expect_payment_failed_conditions(&nodes[0], hash, false, conditions);
+
+		claim_payment(&nodes[0], &[&nodes[1]], preimage);
+	}
+
+	struct TestForwardingPolicy(Arc<Mutex<ForwardingDecision>>);
+
+	impl ForwardingPolicy for TestForwardingPolicy {
+		fn evaluate_forward(&self, _forward: &ForwardCandidate) -> ForwardingDecision {
+			*self.0.lock().unwrap()
+		}
+	}
+
+	#[test]
+	fn test_forwarding_policy_defers_and_rejects_forwards() {
+		// Check that HTLCs deferred by a `ForwardingPolicy` remain pending until it lets them
+		// through, and that rejected HTLCs are failed back.
+		let chanmon_cfgs = create_chanmon_cfgs(3);
+		let node_cfgs = create_node_cfgs(3, &chanmon_cfgs);
+		let node_chanmgrs = create_node_chanmgrs(3, &node_cfgs, &[None, None, None]);
+		let nodes = create_network(3, &node_cfgs, &node_chanmgrs);
+		let node_a_id = nodes[0].node.get_our_node_id();
+		let node_b_id = nodes[1].node.get_our_node_id();
+		let node_c_id = nodes[2].node.get_our_node_id();
+		create_announced_chan_between_nodes(&nodes, 0, 1);
+		let chan_id_2 = create_announced_chan_between_nodes(&nodes, 1, 2).2;
+
+		let decision = Arc::new(Mutex::new(ForwardingDecision::Defer));
+		nodes[1].node.set_forwarding_policy(TestForwardingPolicy(Arc::clone(&decision)));
+
+		let (route, hash, preimage, secret) =
+			get_route_and_payment_hash!(nodes[0], nodes[2], 100_000);
+		let onion = RecipientOnionFields::secret_only(secret);
+		nodes[0].node.send_payment_with_route(route, hash, onion, PaymentId(hash.0)).unwrap();
+		check_added_monitors(&nodes[0], 1);
+		let send_event = SendEvent::from_node(&nodes[0]);
+		nodes[1].node.handle_update_add_htlc(node_a_id, &send_event.msgs[0]);
+		do_commitment_signed_dance(&nodes[1], &nodes[0], &send_event.commitment_msg, false, false);
+
+		// While deferred, the HTLC is kept pending rather than forwarded.
+		nodes[1].node.process_pending_htlc_forwards();
+		assert!(nodes[1].node.get_and_clear_pending_msg_events().is_empty());
+		assert!(nodes[1].node.needs_pending_htlc_processing());
+
+		*decision.lock().unwrap() = ForwardingDecision::Forward { priority: 0 };
+		expect_and_process_pending_htlcs(&nodes[1], false);
+		check_added_monitors(&nodes[1], 1);
+		let mut events = nodes[1].node.get_and_clear_pending_msg_events();
+		assert_eq!(events.len(), 1);
+		let args = PassAlongPathArgs::new(&nodes[1], &[&nodes[2]], 100_000, hash, events.remove(0))
+			.with_payment_secret(secret);
+		do_pass_along_path(args);
+		claim_payment(&nodes[0], &[&nodes[1], &nodes[2]], preimage);
+
+		// Rejected HTLCs are failed back with a `temporary_channel_failure`.
+		*decision.lock().unwrap() = ForwardingDecision::Reject;
+		let (route, hash, _, secret) = get_route_and_payment_hash!(nodes[0], nodes[2], 100_000);
+		let onion = RecipientOnionFields::secret_only(secret);
+		nodes[0].node.send_payment_with_route(route, hash, onion, PaymentId(hash.0)).unwrap();
+		check_added_monitors(&nodes[0], 1);
+		let send_event = SendEvent::from_node(&nodes[0]);
+		nodes[1].node.handle_update_add_htlc(node_a_id, &send_event.msgs[0]);
+		do_commitment_signed_dance(&nodes[1], &nodes[0], &send_event.commitment_msg, false, false);
+		let fail =
+			HTLCHandlingFailureType::Forward { node_id: Some(node_c_id), channel_id: chan_id_2 };
+		expect_htlc_forwarding_fails(&nodes[1], &[fail]);
+		check_added_monitors(&nodes[1], 1);
+		let updates = get_htlc_update_msgs(&nodes[1], &node_a_id);
+		assert_eq!(updates.update_fail_htlcs.len(), 1);
+		nodes[0].node.handle_update_fail_htlc(node_b_id, &updates.update_fail_htlcs[0]);
+		do_commitment_signed_dance(&nodes[0], &nodes[1], &updates.commitment_signed, false, false);
+		let conditions = PaymentFailedConditions::new()
+			.expected_htlc_error_data(LocalHTLCFailureReason::TemporaryChannelFailure, &[0, 0]);
+		expect_payment_failed_conditions(&nodes[0], hash, false, conditions);
+	}
+
+	#[test]
+	fn test_htlc_endorsement_and_local_reputation() {
+		// Check that the endorsement signal is relayed unchanged by default, that forwarded HTLCs
+		// build the local reputation of the peer which offered them, and that only HTLCs from peers
+		// with sufficient reputation are endorsed once `use_local_reputation` is set.
+		let chanmon_cfgs = create_chanmon_cfgs(3);
+		let node_cfgs = create_node_cfgs(3, &chanmon_cfgs);
+		let mut sender_config = test_default_channel_config();
+		sender_config.htlc_endorsement_config.endorse_sent_payments = true;
+		let configs = [Some(sender_config), None, None];
+		let node_chanmgrs = create_node_chanmgrs(3, &node_cfgs, &configs);
+		let nodes = create_network(3, &node_cfgs, &node_chanmgrs);
+		let node_a_id = nodes[0].node.get_our_node_id();
+		create_announced_chan_between_nodes(&nodes, 0, 1);
+		create_announced_chan_between_nodes(&nodes, 1, 2);
+
+		let forward_endorsed_payment = |expect_endorsed: Option<u8>| {
+			let (route, hash, preimage, secret) =
+				get_route_and_payment_hash!(nodes[0], nodes[2], 100_000);
+			let onion = RecipientOnionFields::secret_only(secret);
+			nodes[0].node.send_payment_with_route(route, hash, onion, PaymentId(hash.0)).unwrap();
+			check_added_monitors(&nodes[0], 1);
+			let send_event = SendEvent::from_node(&nodes[0]);
+			assert_eq!(send_event.msgs[0].endorsed, Some(1));
+			nodes[1].node.handle_update_add_htlc(node_a_id, &send_event.msgs[0]);
+			let commitment = &send_event.commitment_msg;
+			do_commitment_signed_dance(&nodes[1], &nodes[0], commitment, false, false);
+
+			expect_and_process_pending_htlcs(&nodes[1], false);
+			check_added_monitors(&nodes[1], 1);
+			let mut events = nodes[1].node.get_and_clear_pending_msg_events();
+			assert_eq!(events.len(), 1);
+			match &events[0] {
+				MessageSendEvent::UpdateHTLCs { updates, .. } => {
+					assert_eq!(updates.update_add_htlcs[0].endorsed, expect_endorsed);
+				},
+				_ => panic!("Unexpected event"),
+			}
+			let args =
+				PassAlongPathArgs::new(&nodes[1], &[&nodes[2]], 100_000, hash, events.remove(0))
+					.with_payment_secret(secret);
+			do_pass_along_path(args);
+			claim_payment(&nodes[0], &[&nodes[1], &nodes[2]], preimage);
+		};
+
+		forward_endorsed_payment(Some(1));
+		let reputation = nodes[1].node.list_local_reputation();
+		assert_eq!(reputation.len(), 1);
+		assert_eq!(reputation[0].0, node_a_id);
+		assert!(reputation[0].1.reputation_msat > 0);
+		assert_eq!(reputation[0].1.in_flight_htlcs, 0);
+
+		// The fees earned from a single HTLC don't outweigh the risk of it being held until it
+		// expires, so the HTLC is no longer endorsed.
+		let mut config = nodes[1].node.get_current_config();
+		config.htlc_endorsement_config.use_local_reputation = true;
+		nodes[1].node.set_current_config(config);
+		forward_endorsed_payment(None);
+	}
+
+	#[test]
+	fn test_liquidity_reserved_for_endorsed_htlcs() {
+		// Check that unendorsed HTLCs are failed back rather than forwarded once they would lock up
+		// liquidity reserved for endorsed HTLCs, while endorsed HTLCs are still forwarded.
+		let chanmon_cfgs = create_chanmon_cfgs(3);
+		let node_cfgs = create_node_cfgs(3, &chanmon_cfgs);
+		let node_chanmgrs = create_node_chanmgrs(3, &node_cfgs, &[None, None, None]);
+		let nodes = create_network(3, &node_cfgs, &node_chanmgrs);
+		let node_a_id = nodes[0].node.get_our_node_id();
+		let node_b_id = nodes[1].node.get_our_node_id();
+		let node_c_id = nodes[2].node.get_our_node_id();
+		create_announced_chan_between_nodes(&nodes, 0, 1);
+		let chan_id_2 = create_announced_chan_between_nodes(&nodes, 1, 2).2;
+
+		let update =
+			ChannelConfigUpdate { endorsed_liquidity_percentage: Some(100), ..Default::default() };
+		nodes[1].node.update_partial_channel_config(&node_c_id, &[chan_id_2], &update).unwrap();
+		nodes[1].node.get_and_clear_pending_msg_events();
+
+		let (route, hash, _, secret) = get_route_and_payment_hash!(nodes[0], nodes[2], 100_000);
+		let onion = RecipientOnionFields::secret_only(secret);
+		nodes[0].node.send_payment_with_route(route, hash, onion, PaymentId(hash.0)).unwrap();
+		check_added_monitors(&nodes[0], 1);
+		let send_event = SendEvent::from_node(&nodes[0]);
+		assert_eq!(send_event.msgs[0].endorsed, None);
+		nodes[1].node.handle_update_add_htlc(node_a_id, &send_event.msgs[0]);
+		do_commitment_signed_dance(&nodes[1], &nodes[0], &send_event.commitment_msg, false, false);
+		let fail =
+			HTLCHandlingFailureType::Forward { node_id: Some(node_c_id), channel_id: chan_id_2 };
+		expect_htlc_forwarding_fails(&nodes[1], &[fail]);
+		check_added_monitors(&nodes[1], 1);
+		let updates = get_htlc_update_msgs(&nodes[1], &node_a_id);
+		assert_eq!(updates.update_fail_htlcs.len(), 1);
+		nodes[0].node.handle_update_fail_htlc(node_b_id, &updates.update_fail_htlcs[0]);
+		do_commitment_signed_dance(&nodes[0], &nodes[1], &updates.commitment_signed, false, false);
+		expect_payment_failed_conditions(&nodes[0], hash, false, PaymentFailedConditions::new());
+
+		let mut config = nodes[0].node.get_current_config();
+		config.htlc_endorsement_config.endorse_sent_payments = true;
+		nodes[0].node.set_current_config(config);
+		let (route, hash, preimage, secret) =
+			get_route_and_payment_hash!(nodes[0], nodes[2], 100_000);
+		let onion = RecipientOnionFields::secret_only(secret);
+		nodes[0].node.send_payment_with_route(route, hash, onion, PaymentId(hash.0)).unwrap();
+		check_added_monitors(&nodes[0], 1);
+		pass_along_route(&nodes[0], &[&[&nodes[1], &nodes[2]]], 100_000, hash, secret);
+		claim_payment(&nodes[0], &[&nodes[1], &nodes[2]], preimage);
+	}
+
 	#[test]
 	#[rustfmt::skip]
 	fn test_payment_display() {
@@ -19813,6 +24145,7 @@ pub mod bench {
 		let chain_monitor_a = ChainMonitor::new(None, &tx_broadcaster, &logger_a, &fee_estimator, &persister_a, &keys_manager_a, keys_manager_a.get_peer_storage_key());
 		let node_a = ChannelManager::new(&fee_estimator, &chain_monitor_a, &tx_broadcaster, &router, &message_router, &logger_a, &keys_manager_a, &keys_manager_a, &keys_manager_a, config.clone(), ChainParameters {
 			network,
+			custom_chain_hash: None,
 			best_block: BestBlock::from_network(network),
 		}, genesis_block.header.time);
 		let node_a_holder = ANodeHolder { node: &node_a };
@@ -19823,6 +24156,7 @@ pub mod bench {
 		let chain_monitor_b = ChainMonitor::new(None, &tx_broadcaster, &logger_a, &fee_estimator, &persister_b, &keys_manager_b, keys_manager_b.get_peer_storage_key());
 		let node_b = ChannelManager::new(&fee_estimator, &chain_monitor_b, &tx_broadcaster, &router, &message_router, &logger_b, &keys_manager_b, &keys_manager_b, &keys_manager_b, config.clone(), ChainParameters {
 			network,
+			custom_chain_hash: None,
 			best_block: BestBlock::from_network(network),
 		}, genesis_block.header.time);
 		let node_b_holder = ANodeHolder { node: &node_b };
@@ -0,0 +1,306 @@
+// This file is Copyright its original authors, visible in version control
+// history.
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Types for closing many channels cooperatively over time.
+//!
+//! Closures are scheduled via [`ChannelManager::schedule_closures`]. Rather than closing all
+//! channels at once, the [`ChannelManager`] waits for a period in which the feerate reported by
+//! the [`FeeEstimator`] for [`ConfirmationTarget::ChannelCloseMinimum`] is low compared to the
+//! recent past, or until the [`CloseDeadline`] is reached, before initiating a cooperative close.
+//! Progress can be monitored via [`ChannelManager::list_scheduled_closures`].
+//!
+//! [`ChannelManager`]: crate::ln::channelmanager::ChannelManager
+//! [`ChannelManager::schedule_closures`]: crate::ln::channelmanager::ChannelManager::schedule_closures
+//! [`ChannelManager::list_scheduled_closures`]: crate::ln::channelmanager::ChannelManager::list_scheduled_closures
+//! [`FeeEstimator`]: crate::chain::chaininterface::FeeEstimator
+//! [`ConfirmationTarget::ChannelCloseMinimum`]: crate::chain::chaininterface::ConfirmationTarget::ChannelCloseMinimum
+
+use bitcoin::secp256k1::PublicKey;
+
+use crate::ln::types::ChannelId;
+
+use crate::prelude::*;
+
+/// The number of blocks over which we track feerate samples to decide whether the current
+/// feerate is low enough to initiate scheduled closures.
+pub const CLOSE_FEERATE_WINDOW_BLOCKS: usize = 144;
+
+/// The minimum number of blocks for which we need feerate samples before we initiate any
+/// scheduled closure ahead of its [`CloseDeadline`].
+pub const MIN_CLOSE_FEERATE_SAMPLES: usize = 6;
+
+/// When a scheduled closure must be initiated at the latest.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CloseDeadline {
+	/// The block height at which we'll initiate a cooperative close regardless of the current
+	/// feerate.
+	pub height: u32,
+	/// Whether to force-close the channel if the deadline has been reached but we cannot initiate
+	/// a cooperative close because our counterparty is not connected.
+	pub force_close_if_unreachable: bool,
+}
+
+/// The progress of a closure scheduled via [`ChannelManager::schedule_closures`].
+///
+/// [`ChannelManager::schedule_closures`]: crate::ln::channelmanager::ChannelManager::schedule_closures
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ScheduledClosureStatus {
+	/// We're waiting for a low-feerate period or the deadline before initiating the closure.
+	Pending,
+	/// We've initiated a cooperative close at the given block height and are waiting for it to
+	/// complete.
+	Initiated {
+		/// The block height at which we initiated the cooperative close.
+		height: u32,
+	},
+	/// We force-closed the channel at the given block height as the deadline was reached while
+	/// our counterparty was unreachable.
+	ForceClosed {
+		/// The block height at which we force-closed the channel.
+		height: u32,
+	},
+	/// The channel has been closed and is no longer tracked by the `ChannelManager`.
+	Closed,
+	/// We failed to initiate the closure. The channel may need to be closed manually.
+	Failed {
+		/// A human-readable description of the failure.
+		err: String,
+	},
+}
+
+/// A closure scheduled via [`ChannelManager::schedule_closures`].
+///
+/// [`ChannelManager::schedule_closures`]: crate::ln::channelmanager::ChannelManager::schedule_closures
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScheduledClosure {
+	/// The channel to be closed.
+	pub channel_id: ChannelId,
+	/// The `node_id` of the channel counterparty.
+	pub counterparty_node_id: PublicKey,
+	/// The deadline by which the closure must be initiated.
+	pub deadline: CloseDeadline,
+	/// The progress of the closure.
+	pub status: ScheduledClosureStatus,
+}
+
+/// An action the `ChannelManager` should take for a scheduled closure.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ScheduledClosureAction {
+	Close,
+	ForceClose,
+}
+
+/// Tracks scheduled closures and the feerate samples used to decide when to initiate them.
+pub(crate) struct ClosureScheduler {
+	closures: Vec<ScheduledClosure>,
+	feerate_samples: VecDeque<u32>,
+}
+
+impl ClosureScheduler {
+	pub(crate) fn new() -> Self {
+		Self { closures: Vec::new(), feerate_samples: VecDeque::new() }
+	}
+
+	/// Schedules the given channels for closure, replacing any previous schedule for them.
+	pub(crate) fn schedule(
+		&mut self, channels: Vec<(ChannelId, PublicKey)>, deadline: CloseDeadline,
+	) {
+		for (channel_id, counterparty_node_id) in channels {
+			self.closures.retain(|closure| closure.channel_id != channel_id);
+			self.closures.push(ScheduledClosure {
+				channel_id,
+				counterparty_node_id,
+				deadline,
+				status: ScheduledClosureStatus::Pending,
+			});
+		}
+	}
+
+	/// Returns true if any closure has yet to complete.
+	pub(crate) fn has_incomplete(&self) -> bool {
+		self.closures.iter().any(|closure| match closure.status {
+			ScheduledClosureStatus::Pending
+			| ScheduledClosureStatus::Initiated { .. }
+			| ScheduledClosureStatus::ForceClosed { .. } => true,
+			ScheduledClosureStatus::Closed | ScheduledClosureStatus::Failed { .. } => false,
+		})
+	}
+
+	pub(crate) fn record_feerate_sample(&mut self, feerate_sat_per_1000_weight: u32) {
+		if self.feerate_samples.len() >= CLOSE_FEERATE_WINDOW_BLOCKS {
+			self.feerate_samples.pop_front();
+		}
+		self.feerate_samples.push_back(feerate_sat_per_1000_weight);
+	}
+
+	/// Returns true if the given feerate is at or below the lower quartile of the feerates sampled
+	/// over the last [`CLOSE_FEERATE_WINDOW_BLOCKS`].
+	pub(crate) fn is_low_feerate_window(&self, feerate_sat_per_1000_weight: u32) -> bool {
+		if self.feerate_samples.len() < MIN_CLOSE_FEERATE_SAMPLES {
+			return false;
+		}
+		let mut samples: Vec<u32> = self.feerate_samples.iter().copied().collect();
+		samples.sort_unstable();
+		feerate_sat_per_1000_weight <= samples[samples.len() / 4]
+	}
+
+	/// Returns the actions to take for pending closures given the current best block height and
+	/// feerate.
+	///
+	/// Once any closure with a given peer is due, we initiate all pending closures with that peer
+	/// at once so that our counterparty can process them as one batch.
+	pub(crate) fn pending_actions<C: Fn(&PublicKey) -> bool>(
+		&self, height: u32, feerate_sat_per_1000_weight: u32, is_peer_connected: C,
+	) -> Vec<(ChannelId, PublicKey, ScheduledClosureAction)> {
+		let low_feerate = self.is_low_feerate_window(feerate_sat_per_1000_weight);
+		let pending = self
+			.closures
+			.iter()
+			.filter(|closure| closure.status == ScheduledClosureStatus::Pending);
+
+		let mut due_peers = new_hash_set();
+		for closure in pending.clone() {
+			let node_id = closure.counterparty_node_id;
+			let due = low_feerate || height >= closure.deadline.height;
+			if due && is_peer_connected(&node_id) {
+				due_peers.insert(node_id);
+			}
+		}
+
+		pending
+			.filter_map(|closure| {
+				let node_id = closure.counterparty_node_id;
+				if due_peers.contains(&node_id) {
+					Some((closure.channel_id, node_id, ScheduledClosureAction::Close))
+				} else if height >= closure.deadline.height
+					&& closure.deadline.force_close_if_unreachable
+					&& !is_peer_connected(&node_id)
+				{
+					Some((closure.channel_id, node_id, ScheduledClosureAction::ForceClose))
+				} else {
+					None
+				}
+			})
+			.collect()
+	}
+
+	/// Returns the channels for which we still expect the `ChannelManager` to be tracking the
+	/// channel.
+	pub(crate) fn incomplete_channels(&self) -> Vec<(ChannelId, PublicKey)> {
+		self.closures
+			.iter()
+			.filter(|closure| match closure.status {
+				ScheduledClosureStatus::Closed | ScheduledClosureStatus::Failed { .. } => false,
+				_ => true,
+			})
+			.map(|closure| (closure.channel_id, closure.counterparty_node_id))
+			.collect()
+	}
+
+	pub(crate) fn set_status(&mut self, channel_id: &ChannelId, status: ScheduledClosureStatus) {
+		if let Some(closure) = self.closures.iter_mut().find(|c| c.channel_id == *channel_id) {
+			closure.status = status;
+		}
+	}
+
+	pub(crate) fn list(&self) -> Vec<ScheduledClosure> {
+		self.closures.clone()
+	}
+
+	/// Forgets all closures which have completed or failed.
+	pub(crate) fn clear_completed(&mut self) {
+		self.closures.retain(|closure| match closure.status {
+			ScheduledClosureStatus::Closed | ScheduledClosureStatus::Failed { .. } => false,
+			_ => true,
+		});
+		if self.closures.is_empty() {
+			self.feerate_samples.clear();
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use bitcoin::secp256k1::{Secp256k1, SecretKey};
+
+	fn node_id(idx: u8) -> PublicKey {
+		let key = SecretKey::from_slice(&[idx + 1; 32]).unwrap();
+		PublicKey::from_secret_key(&Secp256k1::new(), &key)
+	}
+
+	fn deadline(height: u32, force_close_if_unreachable: bool) -> CloseDeadline {
+		CloseDeadline { height, force_close_if_unreachable }
+	}
+
+	#[test]
+	fn low_feerate_window() {
+		let mut scheduler = ClosureScheduler::new();
+		for _ in 0..MIN_CLOSE_FEERATE_SAMPLES - 1 {
+			scheduler.record_feerate_sample(1000);
+		}
+		// We need enough samples before considering any feerate low.
+		assert!(!scheduler.is_low_feerate_window(1));
+
+		scheduler.record_feerate_sample(2000);
+		assert!(scheduler.is_low_feerate_window(1000));
+		assert!(!scheduler.is_low_feerate_window(1001));
+
+		// Old samples drop out of the window.
+		for _ in 0..CLOSE_FEERATE_WINDOW_BLOCKS {
+			scheduler.record_feerate_sample(5000);
+		}
+		assert!(scheduler.is_low_feerate_window(5000));
+		assert!(!scheduler.is_low_feerate_window(5001));
+	}
+
+	#[test]
+	fn pending_actions_batch_per_peer() {
+		let mut scheduler = ClosureScheduler::new();
+		let (peer_a, peer_b, peer_c) = (node_id(0), node_id(1), node_id(2));
+		let chan = |idx: u8| ChannelId::from_bytes([idx; 32]);
+
+		scheduler.schedule(vec![(chan(0), peer_a), (chan(1), peer_b)], deadline(100, false));
+		scheduler.schedule(vec![(chan(2), peer_a), (chan(3), peer_c)], deadline(200, true));
+		let connected = |node_id: &PublicKey| *node_id != peer_c;
+
+		// Without feerate samples nothing happens ahead of the deadline.
+		assert!(scheduler.pending_actions(99, 1000, connected).is_empty());
+
+		// Once the first deadline is reached, all closures with peer A are initiated at once.
+		let actions = scheduler.pending_actions(100, 1000, connected);
+		assert_eq!(
+			actions,
+			vec![
+				(chan(0), peer_a, ScheduledClosureAction::Close),
+				(chan(1), peer_b, ScheduledClosureAction::Close),
+				(chan(2), peer_a, ScheduledClosureAction::Close),
+			]
+		);
+
+		for (channel_id, _, _) in actions {
+			scheduler.set_status(&channel_id, ScheduledClosureStatus::Initiated { height: 100 });
+		}
+		// Peer C is unreachable, so we force-close once the deadline is reached.
+		assert!(scheduler.pending_actions(199, 1000, connected).is_empty());
+		assert_eq!(
+			scheduler.pending_actions(200, 1000, connected),
+			vec![(chan(3), peer_c, ScheduledClosureAction::ForceClose)]
+		);
+
+		assert!(scheduler.has_incomplete());
+		for idx in 0..4 {
+			scheduler.set_status(&chan(idx), ScheduledClosureStatus::Closed);
+		}
+		assert!(!scheduler.has_incomplete());
+		scheduler.clear_completed();
+		assert!(scheduler.list().is_empty());
+	}
+}
@@ -8,3 +8,432 @@
 // licenses.
 
 //! Tests that test the creation of dual-funded channels in ChannelManager.
+
+use crate::events::{ClosureReason, Event, InboundChannelFunds};
+use crate::ln::channel_keys::RevocationBasepoint;
+use crate::ln::functional_test_utils::*;
+use crate::ln::funding::{FundingContribution, FundingTxInput};
+use crate::ln::liquidity_ads::{LiquidityAdRate, RequestFunds};
+use crate::ln::msgs::{BaseMessageHandler, ChannelMessageHandler, ErrorAction, MessageSendEvent};
+use crate::ln::splicing_tests::sign_interactive_funding_tx;
+use crate::ln::types::ChannelId;
+use crate::util::errors::APIError;
+
+use bitcoin::{Amount, Transaction};
+
+#[test]
+fn test_v2_channel_establishment_with_opener_contribution() {
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let mut config = test_default_channel_config();
+	config.enable_dual_funded_channels = true;
+	let node_chanmgrs =
+		create_node_chanmgrs(2, &node_cfgs, &[Some(config.clone()), Some(config.clone())]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+	let node_id_0 = nodes[0].node.get_our_node_id();
+	let node_id_1 = nodes[1].node.get_our_node_id();
+
+	let coinbase_tx = provide_anchor_reserves(&nodes);
+	let funding_satoshis = 100_000;
+	let feerate_per_kw = 253;
+
+	// The contributed inputs must cover the contribution plus fees.
+	let contribution = FundingContribution {
+		value: Amount::ONE_BTC,
+		inputs: vec![FundingTxInput::new_p2wpkh(coinbase_tx.clone(), 0).unwrap()],
+		change_script: None,
+	};
+	let res =
+		nodes[0].node.create_dual_funded_channel(node_id_1, contribution, feerate_per_kw, 42, None);
+	assert!(matches!(res, Err(APIError::APIMisuseError { .. })));
+
+	let contribution = FundingContribution {
+		value: Amount::from_sat(funding_satoshis),
+		inputs: vec![FundingTxInput::new_p2wpkh(coinbase_tx, 0).unwrap()],
+		change_script: Some(nodes[0].wallet_source.get_change_script().unwrap()),
+	};
+	let temporary_channel_id = nodes[0]
+		.node
+		.create_dual_funded_channel(node_id_1, contribution, feerate_per_kw, 42, None)
+		.unwrap();
+
+	let open_channel = get_event_msg!(nodes[0], MessageSendEvent::SendOpenChannelV2, node_id_1);
+	assert_eq!(open_channel.common_fields.temporary_channel_id, temporary_channel_id);
+	assert_eq!(open_channel.common_fields.funding_satoshis, funding_satoshis);
+	assert_eq!(open_channel.funding_feerate_sat_per_1000_weight, feerate_per_kw);
+	nodes[1].node.handle_open_channel_v2(node_id_0, &open_channel);
+
+	let accept_channel = get_event_msg!(nodes[1], MessageSendEvent::SendAcceptChannelV2, node_id_0);
+	assert_eq!(accept_channel.funding_satoshis, 0);
+	nodes[0].node.handle_accept_channel_v2(node_id_1, &accept_channel);
+
+	// As the opener, we contribute our input along with the shared funding output and our change.
+	let tx_add_input = get_event_msg!(nodes[0], MessageSendEvent::SendTxAddInput, node_id_1);
+	let channel_id = tx_add_input.channel_id;
+	assert_ne!(channel_id, temporary_channel_id);
+	nodes[1].node.handle_tx_add_input(node_id_0, &tx_add_input);
+	let tx_complete = get_event_msg!(nodes[1], MessageSendEvent::SendTxComplete, node_id_0);
+	nodes[0].node.handle_tx_complete(node_id_1, &tx_complete);
+
+	for _ in 0..2 {
+		let tx_add_output = get_event_msg!(nodes[0], MessageSendEvent::SendTxAddOutput, node_id_1);
+		nodes[1].node.handle_tx_add_output(node_id_0, &tx_add_output);
+		let tx_complete = get_event_msg!(nodes[1], MessageSendEvent::SendTxComplete, node_id_0);
+		nodes[0].node.handle_tx_complete(node_id_1, &tx_complete);
+	}
+
+	let mut msg_events = nodes[0].node.get_and_clear_pending_msg_events();
+	assert_eq!(msg_events.len(), 2, "{msg_events:?}");
+	if let MessageSendEvent::SendTxComplete { ref msg, .. } = msg_events.remove(0) {
+		nodes[1].node.handle_tx_complete(node_id_0, msg);
+	} else {
+		panic!();
+	}
+	let initial_commit_sig_for_acceptor =
+		if let MessageSendEvent::UpdateHTLCs { mut updates, .. } = msg_events.remove(0) {
+			updates.commitment_signed.remove(0)
+		} else {
+			panic!();
+		};
+
+	let (funding_tx, _) =
+		sign_interactive_funding_tx(&nodes[0], &nodes[1], initial_commit_sig_for_acceptor, false);
+	assert!(funding_tx
+		.output
+		.iter()
+		.any(|output| output.value == Amount::from_sat(funding_satoshis)));
+
+	expect_channel_pending_event(&nodes[0], &node_id_1);
+	expect_channel_pending_event(&nodes[1], &node_id_0);
+}
+
+#[test]
+fn test_v2_channel_lease_request_closed_without_seller_contribution() {
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let mut config = test_default_channel_config();
+	config.enable_dual_funded_channels = true;
+	let node_chanmgrs =
+		create_node_chanmgrs(2, &node_cfgs, &[Some(config.clone()), Some(config.clone())]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+	let node_id_0 = nodes[0].node.get_our_node_id();
+	let node_id_1 = nodes[1].node.get_our_node_id();
+
+	let coinbase_tx = provide_anchor_reserves(&nodes);
+	let funding_satoshis = 100_000;
+	let requested_sats = 200_000;
+	let feerate_per_kw = 253;
+	let rate = LiquidityAdRate {
+		min_funding_satoshis: 100_000,
+		max_funding_satoshis: 1_000_000,
+		funding_weight: 500,
+		lease_fee_basis: 100,
+		lease_fee_base_sat: 1_000,
+	};
+
+	// The rate must cover the requested amount.
+	let contribution = FundingContribution {
+		value: Amount::from_sat(funding_satoshis),
+		inputs: vec![FundingTxInput::new_p2wpkh(coinbase_tx.clone(), 0).unwrap()],
+		change_script: None,
+	};
+	let res = nodes[0].node.request_channel_with_lease(
+		node_id_1,
+		contribution,
+		50_000,
+		rate,
+		feerate_per_kw,
+		42,
+		None,
+	);
+	assert!(matches!(res, Err(APIError::APIMisuseError { .. })));
+
+	let contribution = FundingContribution {
+		value: Amount::from_sat(funding_satoshis),
+		inputs: vec![FundingTxInput::new_p2wpkh(coinbase_tx, 0).unwrap()],
+		change_script: Some(nodes[0].wallet_source.get_change_script().unwrap()),
+	};
+	nodes[0]
+		.node
+		.request_channel_with_lease(
+			node_id_1,
+			contribution,
+			requested_sats,
+			rate,
+			feerate_per_kw,
+			42,
+			None,
+		)
+		.unwrap();
+
+	let open_channel = get_event_msg!(nodes[0], MessageSendEvent::SendOpenChannelV2, node_id_1);
+	assert_eq!(open_channel.request_funds, Some(RequestFunds { requested_sats, rate }));
+	nodes[1].node.handle_open_channel_v2(node_id_0, &open_channel);
+
+	// We don't sell liquidity, so the request is ignored and nothing is contributed.
+	let accept_channel = get_event_msg!(nodes[1], MessageSendEvent::SendAcceptChannelV2, node_id_0);
+	assert_eq!(accept_channel.funding_satoshis, 0);
+	nodes[0].node.handle_accept_channel_v2(node_id_1, &accept_channel);
+
+	let msg_events = nodes[0].node.get_and_clear_pending_msg_events();
+	assert_eq!(msg_events.len(), 1, "{msg_events:?}");
+	assert!(matches!(msg_events[0], MessageSendEvent::HandleError { .. }));
+
+	let events = nodes[0].node.get_and_clear_pending_events();
+	assert!(events.iter().any(|event| matches!(
+		event,
+		Event::ChannelClosed { reason: ClosureReason::ProcessingError { .. }, .. }
+	)));
+}
+
+#[test]
+fn test_v2_channel_closed_on_duplicate_channel_id() {
+	// Test that if the final ID of a dual-funded channel we opened conflicts with that of another
+	// channel, the new channel is closed under its temporary ID, leaving the other one untouched.
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let mut config = test_default_channel_config();
+	config.enable_dual_funded_channels = true;
+	let node_chanmgrs =
+		create_node_chanmgrs(2, &node_cfgs, &[Some(config.clone()), Some(config.clone())]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+	let node_id_0 = nodes[0].node.get_our_node_id();
+	let node_id_1 = nodes[1].node.get_our_node_id();
+
+	let coinbase_tx = provide_anchor_utxo_reserves(&nodes, 2, Amount::ONE_BTC);
+	let open_channel = |vout| {
+		let contribution = FundingContribution {
+			value: Amount::from_sat(100_000),
+			inputs: vec![FundingTxInput::new_p2wpkh(coinbase_tx.clone(), vout).unwrap()],
+			change_script: Some(nodes[0].wallet_source.get_change_script().unwrap()),
+		};
+		nodes[0].node.create_dual_funded_channel(node_id_1, contribution, 253, 42, None).unwrap();
+		let open_channel = get_event_msg!(nodes[0], MessageSendEvent::SendOpenChannelV2, node_id_1);
+		nodes[1].node.handle_open_channel_v2(node_id_0, &open_channel);
+		let accept_channel =
+			get_event_msg!(nodes[1], MessageSendEvent::SendAcceptChannelV2, node_id_0);
+		(open_channel.common_fields, accept_channel)
+	};
+	let (open_fields_a, _) = open_channel(0);
+	let (open_fields_b, accept_channel_b) = open_channel(1);
+
+	// Key the first channel by the ID the second one is about to get, as if they collided.
+	let channel_id_b = ChannelId::v2_from_revocation_basepoints(
+		&RevocationBasepoint(open_fields_b.revocation_basepoint),
+		&RevocationBasepoint(accept_channel_b.common_fields.revocation_basepoint),
+	);
+	{
+		let per_peer_state = nodes[0].node.per_peer_state.read().unwrap();
+		let mut peer_state = per_peer_state.get(&node_id_1).unwrap().lock().unwrap();
+		let channel_a =
+			peer_state.channel_by_id.remove(&open_fields_a.temporary_channel_id).unwrap();
+		peer_state.channel_by_id.insert(channel_id_b, channel_a);
+	}
+
+	nodes[0].node.handle_accept_channel_v2(node_id_1, &accept_channel_b);
+	let msg_events = nodes[0].node.get_and_clear_pending_msg_events();
+	assert_eq!(msg_events.len(), 1, "{msg_events:?}");
+	match &msg_events[0] {
+		MessageSendEvent::HandleError {
+			action: ErrorAction::SendErrorMessage { msg },
+			node_id,
+		} => {
+			assert_eq!(msg.channel_id, open_fields_b.temporary_channel_id);
+			assert_eq!(*node_id, node_id_1);
+		},
+		_ => panic!("Unexpected event {:?}", msg_events[0]),
+	}
+
+	let closed_channel_ids = nodes[0]
+		.node
+		.get_and_clear_pending_events()
+		.into_iter()
+		.filter_map(|event| match event {
+			Event::ChannelClosed {
+				channel_id,
+				reason: ClosureReason::ProcessingError { .. },
+				..
+			} => Some(channel_id),
+			_ => None,
+		})
+		.collect::<Vec<_>>();
+	assert_eq!(closed_channel_ids, vec![open_fields_b.temporary_channel_id]);
+
+	let per_peer_state = nodes[0].node.per_peer_state.read().unwrap();
+	let peer_state = per_peer_state.get(&node_id_1).unwrap().lock().unwrap();
+	assert!(peer_state.channel_by_id.contains_key(&channel_id_b));
+	assert!(!peer_state.channel_by_id.contains_key(&open_fields_b.temporary_channel_id));
+}
+
+/// Exchanges the interactive transaction construction and signing messages of a dual-funded
+/// channel open between `initiator` and `acceptor`, signing any inputs they contributed, until
+/// the funding transaction is broadcast. Returns the funding transaction.
+fn complete_interactive_funding<'a, 'b, 'c>(
+	initiator: &Node<'a, 'b, 'c>, acceptor: &Node<'a, 'b, 'c>,
+) -> Transaction {
+	let mut channel_pending_events = 0;
+	loop {
+		let mut progressed = false;
+		for (node, counterparty) in [(initiator, acceptor), (acceptor, initiator)] {
+			let node_id = node.node.get_our_node_id();
+			for event in node.node.get_and_clear_pending_events() {
+				match event {
+					Event::FundingTransactionReadyForSigning {
+						channel_id,
+						counterparty_node_id,
+						unsigned_transaction,
+						..
+					} => {
+						let signed_tx = node.wallet_source.sign_tx(unsigned_transaction).unwrap();
+						node.node
+							.funding_transaction_signed(
+								&channel_id,
+								&counterparty_node_id,
+								signed_tx,
+							)
+							.unwrap();
+						progressed = true;
+					},
+					Event::ChannelPending { .. } => channel_pending_events += 1,
+					_ => panic!("Unexpected event {event:?}"),
+				}
+			}
+			for msg_event in node.node.get_and_clear_pending_msg_events() {
+				progressed = true;
+				match msg_event {
+					MessageSendEvent::SendTxAddInput { msg, .. } => {
+						counterparty.node.handle_tx_add_input(node_id, &msg)
+					},
+					MessageSendEvent::SendTxAddOutput { msg, .. } => {
+						counterparty.node.handle_tx_add_output(node_id, &msg)
+					},
+					MessageSendEvent::SendTxComplete { msg, .. } => {
+						counterparty.node.handle_tx_complete(node_id, &msg)
+					},
+					MessageSendEvent::SendTxSignatures { msg, .. } => {
+						counterparty.node.handle_tx_signatures(node_id, &msg)
+					},
+					MessageSendEvent::UpdateHTLCs { updates, .. } => {
+						for commitment_signed in updates.commitment_signed {
+							counterparty.node.handle_commitment_signed(node_id, &commitment_signed);
+						}
+					},
+					_ => panic!("Unexpected message {msg_event:?}"),
+				}
+			}
+		}
+		if !progressed {
+			break;
+		}
+	}
+	assert_eq!(channel_pending_events, 2);
+	check_added_monitors(initiator, 1);
+	check_added_monitors(acceptor, 1);
+
+	let mut initiator_txn = initiator.tx_broadcaster.txn_broadcast();
+	assert_eq!(initiator_txn.len(), 1);
+	assert_eq!(initiator_txn, acceptor.tx_broadcaster.txn_broadcast());
+	initiator_txn.remove(0)
+}
+
+#[test]
+fn test_v2_channel_establishment_with_acceptor_contribution() {
+	// Test that the acceptor of a dual-funded channel can contribute to it as well, and that its
+	// contribution is checked to cover its share of the fees before the request is consumed.
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let mut config = test_default_channel_config();
+	config.enable_dual_funded_channels = true;
+	let mut acceptor_config = config.clone();
+	acceptor_config.manually_accept_inbound_channels = true;
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[Some(config), Some(acceptor_config)]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+	let node_id_0 = nodes[0].node.get_our_node_id();
+	let node_id_1 = nodes[1].node.get_our_node_id();
+
+	let coinbase_tx = provide_anchor_reserves(&nodes);
+	let opener_satoshis = 100_000;
+	let acceptor_satoshis = 50_000;
+	let feerate_per_kw = 253;
+
+	let contribution = FundingContribution {
+		value: Amount::from_sat(opener_satoshis),
+		inputs: vec![FundingTxInput::new_p2wpkh(coinbase_tx.clone(), 0).unwrap()],
+		change_script: Some(nodes[0].wallet_source.get_change_script().unwrap()),
+	};
+	nodes[0]
+		.node
+		.create_dual_funded_channel(node_id_1, contribution, feerate_per_kw, 42, None)
+		.unwrap();
+	let open_channel = get_event_msg!(nodes[0], MessageSendEvent::SendOpenChannelV2, node_id_1);
+	nodes[1].node.handle_open_channel_v2(node_id_0, &open_channel);
+
+	let events = nodes[1].node.get_and_clear_pending_events();
+	assert_eq!(events.len(), 1);
+	let temporary_channel_id = match &events[0] {
+		Event::OpenChannelRequest {
+			temporary_channel_id,
+			channel_negotiation_type:
+				InboundChannelFunds::DualFunded { funding_feerate_sat_per_1000_weight },
+			..
+		} => {
+			assert_eq!(*funding_feerate_sat_per_1000_weight, feerate_per_kw);
+			*temporary_channel_id
+		},
+		_ => panic!("Unexpected event {:?}", events[0]),
+	};
+
+	// The contributed inputs must cover the contribution plus fees, though the request may be
+	// accepted again with other inputs if they don't.
+	let contribution = FundingContribution {
+		value: Amount::ONE_BTC,
+		inputs: vec![FundingTxInput::new_p2wpkh(coinbase_tx.clone(), 1).unwrap()],
+		change_script: None,
+	};
+	let res = nodes[1].node.accept_inbound_channel_with_contribution(
+		&temporary_channel_id,
+		&node_id_0,
+		contribution,
+		43,
+		None,
+	);
+	assert!(matches!(res, Err(APIError::APIMisuseError { .. })));
+	assert!(nodes[1].node.get_and_clear_pending_msg_events().is_empty());
+
+	let contribution = FundingContribution {
+		value: Amount::from_sat(acceptor_satoshis),
+		inputs: vec![FundingTxInput::new_p2wpkh(coinbase_tx.clone(), 1).unwrap()],
+		change_script: Some(nodes[1].wallet_source.get_change_script().unwrap()),
+	};
+	nodes[1]
+		.node
+		.accept_inbound_channel_with_contribution(
+			&temporary_channel_id,
+			&node_id_0,
+			contribution,
+			43,
+			None,
+		)
+		.unwrap();
+
+	let accept_channel = get_event_msg!(nodes[1], MessageSendEvent::SendAcceptChannelV2, node_id_0);
+	assert_eq!(accept_channel.funding_satoshis, acceptor_satoshis);
+	nodes[0].node.handle_accept_channel_v2(node_id_1, &accept_channel);
+
+	let funding_tx = complete_interactive_funding(&nodes[0], &nodes[1]);
+	let channel_value_satoshis = opener_satoshis + acceptor_satoshis;
+	assert!(funding_tx
+		.output
+		.iter()
+		.any(|output| output.value == Amount::from_sat(channel_value_satoshis)));
+	let coinbase_txid = coinbase_tx.compute_txid();
+	for vout in 0..2 {
+		let outpoint = bitcoin::OutPoint { txid: coinbase_txid, vout };
+		assert!(funding_tx.input.iter().any(|input| input.previous_output == outpoint));
+	}
+	for node in nodes.iter() {
+		let channels = node.node.list_channels();
+		assert_eq!(channels.len(), 1);
+		assert_eq!(channels[0].channel_value_satoshis, channel_value_satoshis);
+	}
+}
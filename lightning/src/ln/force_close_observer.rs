@@ -0,0 +1,146 @@
+// This file is Copyright its original authors, visible in version control
+// history.
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Types for observing, and in some cases vetoing, force-closures initiated by the
+//! [`ChannelManager`].
+//!
+//! A [`ForceCloseObserver`] may be set via [`ChannelManager::set_force_close_observer`]. It is
+//! consulted before the [`ChannelManager`] force-closes a channel on its own accord and notified
+//! once the closure has happened.
+//!
+//! [`ChannelManager`]: crate::ln::channelmanager::ChannelManager
+//! [`ChannelManager::set_force_close_observer`]: crate::ln::channelmanager::ChannelManager::set_force_close_observer
+
+use bitcoin::secp256k1::PublicKey;
+use bitcoin::Txid;
+
+use crate::events::ClosureReason;
+use crate::ln::types::ChannelId;
+use crate::types::payment::PaymentHash;
+
+use crate::prelude::*;
+
+/// The number of blocks for which a [`ForceCloseDecision::Veto`] is honored for a given channel.
+///
+/// Once a channel's force-closure has been continuously vetoed for this many blocks, we'll go
+/// ahead with the force-closure regardless of the [`ForceCloseObserver`]'s decision.
+pub const FORCE_CLOSE_VETO_TIMEOUT_BLOCKS: u32 = 144;
+
+/// The reason we're about to force-close a channel.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ForceCloseReason {
+	/// HTLC(s) on the channel are close to expiring, requiring us to go on-chain to claim or time
+	/// them out before our counterparty can.
+	///
+	/// Note that such closures are initiated by the [`ChannelMonitor`], which will generally have
+	/// already broadcast its latest holder commitment transaction by the time the
+	/// [`ForceCloseObserver`] is consulted.
+	///
+	/// [`ChannelMonitor`]: crate::chain::channelmonitor::ChannelMonitor
+	HtlcTimeoutRisk {
+		/// The payment hash of an HTLC that timed out, if known.
+		payment_hash: Option<PaymentHash>,
+	},
+	/// Our counterparty violated the protocol such that the channel can no longer be operated.
+	PeerMisbehavior {
+		/// A developer-readable description of the violation.
+		message: String,
+	},
+	/// The feerate our counterparty set on the channel has been below the minimum we require
+	/// throughout the past day, putting the channel at risk of not confirming in a timely manner.
+	FeerateDivergence {
+		/// The feerate on our channel set by our peer.
+		peer_feerate_sat_per_kw: u32,
+		/// The required feerate we enforce, from our [`FeeEstimator`].
+		///
+		/// [`FeeEstimator`]: crate::chain::chaininterface::FeeEstimator
+		required_feerate_sat_per_kw: u32,
+	},
+}
+
+impl ForceCloseReason {
+	/// Returns the [`ForceCloseReason`] corresponding to the given [`ClosureReason`], if the
+	/// closure is one the [`ChannelManager`] initiates on its own accord.
+	///
+	/// [`ChannelManager`]: crate::ln::channelmanager::ChannelManager
+	pub(crate) fn from_closure_reason(reason: &ClosureReason) -> Option<Self> {
+		match reason {
+			ClosureReason::HTLCsTimedOut { payment_hash } => {
+				Some(Self::HtlcTimeoutRisk { payment_hash: *payment_hash })
+			},
+			ClosureReason::ProcessingError { err } => {
+				Some(Self::PeerMisbehavior { message: err.clone() })
+			},
+			ClosureReason::PeerFeerateTooLow {
+				peer_feerate_sat_per_kw,
+				required_feerate_sat_per_kw,
+			} => Some(Self::FeerateDivergence {
+				peer_feerate_sat_per_kw: *peer_feerate_sat_per_kw,
+				required_feerate_sat_per_kw: *required_feerate_sat_per_kw,
+			}),
+			_ => None,
+		}
+	}
+
+	/// Whether the force-closure is discretionary, i.e. whether a [`ForceCloseDecision::Veto`]
+	/// will be honored.
+	///
+	/// Only [`ForceCloseReason::FeerateDivergence`] is discretionary, as the channel remains
+	/// operable while the feerate is low. In all other cases, delaying the closure would put our
+	/// funds at risk.
+	pub fn is_discretionary(&self) -> bool {
+		matches!(self, Self::FeerateDivergence { .. })
+	}
+}
+
+/// The decision of a [`ForceCloseObserver`] on an impending force-closure.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ForceCloseDecision {
+	/// Go ahead with the force-closure.
+	Proceed,
+	/// Delay the force-closure.
+	///
+	/// This is only honored for [discretionary] reasons, and only for up to
+	/// [`FORCE_CLOSE_VETO_TIMEOUT_BLOCKS`] after the first veto for a given channel. The
+	/// [`ForceCloseObserver`] will be consulted again the next time the reason is detected.
+	///
+	/// [discretionary]: ForceCloseReason::is_discretionary
+	Veto,
+}
+
+/// A hook invoked before and after the [`ChannelManager`] force-closes a channel on its own
+/// accord, e.g. because our counterparty misbehaved.
+///
+/// Force-closures requested by the user or our counterparty are not reported.
+///
+/// [`ChannelManager`]: crate::ln::channelmanager::ChannelManager
+pub trait ForceCloseObserver {
+	/// Called before a channel is force-closed for the given `reason`.
+	///
+	/// The returned [`ForceCloseDecision`] is ignored unless the `reason` is
+	/// [discretionary].
+	///
+	/// [discretionary]: ForceCloseReason::is_discretionary
+	fn before_force_close(
+		&self, channel_id: &ChannelId, counterparty_node_id: &PublicKey, reason: &ForceCloseReason,
+	) -> ForceCloseDecision;
+
+	/// Called once a channel has been force-closed for the given `reason`.
+	///
+	/// `broadcast_txid` is the txid of the holder commitment transaction broadcast by the
+	/// [`ChannelMonitor`], or `None` if we did not broadcast one (e.g. because our state is stale)
+	/// or the [`chain::Watch`] implementation does not report it.
+	///
+	/// [`ChannelMonitor`]: crate::chain::channelmonitor::ChannelMonitor
+	/// [`chain::Watch`]: crate::chain::Watch
+	fn after_force_close(
+		&self, channel_id: &ChannelId, counterparty_node_id: &PublicKey, reason: &ForceCloseReason,
+		broadcast_txid: Option<Txid>,
+	);
+}
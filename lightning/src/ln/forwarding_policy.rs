@@ -0,0 +1,95 @@
+// This file is Copyright its original authors, visible in version control
+// history.
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Types for deciding, per peer and per channel, whether and when HTLCs are forwarded.
+//!
+//! A [`ForwardingPolicy`] may be set via [`ChannelManager::set_forwarding_policy`]. It is consulted
+//! for each HTLC we're about to forward in [`ChannelManager::process_pending_htlc_forwards`] and
+//! may reject the forward, defer it to a later call, or change the order in which forwards over
+//! the same channel are processed. This allows implementing, e.g., sanctions filtering,
+//! anti-jamming heuristics or per-channel-pair fee experiments without modifying LDK.
+//!
+//! [`ChannelManager::set_forwarding_policy`]: crate::ln::channelmanager::ChannelManager::set_forwarding_policy
+//! [`ChannelManager::process_pending_htlc_forwards`]: crate::ln::channelmanager::ChannelManager::process_pending_htlc_forwards
+
+use bitcoin::secp256k1::PublicKey;
+
+use crate::ln::types::ChannelId;
+use crate::types::payment::PaymentHash;
+
+/// An HTLC we're about to forward, as passed to [`ForwardingPolicy::evaluate_forward`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ForwardCandidate {
+	/// The hash of the payment the HTLC is a part of.
+	pub payment_hash: PaymentHash,
+	/// The channel over which we received the HTLC.
+	pub prev_channel_id: ChannelId,
+	/// The peer which offered the HTLC to us.
+	pub prev_counterparty_node_id: PublicKey,
+	/// The channel the sender requested the HTLC be forwarded over.
+	///
+	/// Note that we may forward the HTLC over a different channel with the same peer if it is
+	/// better suited to carry the HTLC, as permitted by non-strict forwarding.
+	pub next_channel_id: ChannelId,
+	/// The peer we're about to forward the HTLC to.
+	pub next_counterparty_node_id: PublicKey,
+	/// The amount of the HTLC we received, if known.
+	pub inbound_amount_msat: Option<u64>,
+	/// The amount of the HTLC we're about to offer to the next peer.
+	pub outbound_amount_msat: u64,
+	/// The CLTV expiry of the HTLC we're about to offer to the next peer.
+	pub outgoing_cltv_expiry: u32,
+}
+
+/// The decision of a [`ForwardingPolicy`] on a [`ForwardCandidate`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ForwardingDecision {
+	/// Forward the HTLC.
+	///
+	/// Among the HTLCs forwarded to the same channel in a single call to
+	/// [`ChannelManager::process_pending_htlc_forwards`], those with a higher `priority` are added
+	/// to the channel first, and thus are the first to be assigned the channel's liquidity and HTLC
+	/// slots. HTLCs with the same `priority` are forwarded in the order they were received.
+	///
+	/// [`ChannelManager::process_pending_htlc_forwards`]: crate::ln::channelmanager::ChannelManager::process_pending_htlc_forwards
+	Forward {
+		/// The priority of the HTLC relative to other HTLCs forwarded over the same channel.
+		priority: u16,
+	},
+	/// Keep the HTLC pending and consult the [`ForwardingPolicy`] again the next time
+	/// [`ChannelManager::process_pending_htlc_forwards`] is called.
+	///
+	/// HTLCs may only be deferred until their outgoing CLTV expiry comes within
+	/// [`HTLC_FAIL_BACK_BUFFER`] blocks of the current height, at which point they are failed
+	/// backwards.
+	///
+	/// [`ChannelManager::process_pending_htlc_forwards`]: crate::ln::channelmanager::ChannelManager::process_pending_htlc_forwards
+	/// [`HTLC_FAIL_BACK_BUFFER`]: crate::chain::channelmonitor::HTLC_FAIL_BACK_BUFFER
+	Defer,
+	/// Fail the HTLC backwards with a `temporary_channel_failure`.
+	Reject,
+}
+
+/// A hook deciding whether, when and in which order HTLCs are forwarded.
+///
+/// It is only consulted for HTLCs forwarded over one of our channels, i.e., not for HTLCs we
+/// receive or for Trampoline forwards. Intercepted HTLCs are evaluated once they are forwarded via
+/// [`ChannelManager::forward_intercepted_htlc`].
+///
+/// [`ChannelManager::forward_intercepted_htlc`]: crate::ln::channelmanager::ChannelManager::forward_intercepted_htlc
+pub trait ForwardingPolicy {
+	/// Decides how to handle the given HTLC we're about to forward.
+	///
+	/// This is called while processing pending HTLC forwards and thus must not call back into the
+	/// [`ChannelManager`]. It may be called many times for the same HTLC if it is
+	/// [`ForwardingDecision::Defer`]red, and thus should return quickly.
+	///
+	/// [`ChannelManager`]: crate::ln::channelmanager::ChannelManager
+	fn evaluate_forward(&self, forward: &ForwardCandidate) -> ForwardingDecision;
+}
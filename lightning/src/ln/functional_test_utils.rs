@@ -16,7 +16,7 @@ use crate::chain::{BestBlock, ChannelMonitorUpdateStatus, Confirm, Listen, Watch
 use crate::events::bump_transaction::sync::{
 	BumpTransactionEventHandlerSync, WalletSourceSync, WalletSync,
 };
-use crate::events::bump_transaction::BumpTransactionEvent;
+use crate::events::bump_transaction::{BumpTransactionEvent, DefaultFeeBumpPolicy};
 use crate::events::{
 	ClaimedHTLC, ClosureReason, Event, HTLCHandlingFailureType, PaidBolt12Invoice, PathFailure,
 	PaymentFailureReason, PaymentPurpose,
@@ -588,6 +588,7 @@ pub struct Node<'chan_man, 'node_cfg: 'chan_man, 'chan_mon_cfg: 'node_cfg> {
 		&'chan_mon_cfg test_utils::TestBroadcaster,
 		Arc<WalletSync<Arc<test_utils::TestWalletSource>, &'chan_mon_cfg test_utils::TestLogger>>,
 		&'chan_mon_cfg test_utils::TestKeysInterface,
+		&'static DefaultFeeBumpPolicy,
 		&'chan_mon_cfg test_utils::TestLogger,
 	>,
 }
@@ -860,6 +861,9 @@ impl<'a, 'b, 'c> Drop for Node<'a, 'b, 'c> {
 				txn_broadcasted: Mutex::new(
 					self.tx_broadcaster.txn_broadcasted.lock().unwrap().clone(),
 				),
+				broadcast_contexts: Mutex::new(
+					self.tx_broadcaster.broadcast_contexts.lock().unwrap().clone(),
+				),
 				blocks: Arc::new(Mutex::new(self.tx_broadcaster.blocks.lock().unwrap().clone())),
 			};
 
@@ -4518,7 +4522,11 @@ pub fn create_node_chanmgrs<'a, 'b>(
 	for i in 0..node_count {
 		let network = Network::Testnet;
 		let genesis_block = bitcoin::constants::genesis_block(network);
-		let params = ChainParameters { network, best_block: BestBlock::from_network(network) };
+		let params = ChainParameters {
+			network,
+			custom_chain_hash: None,
+			best_block: BestBlock::from_network(network),
+		};
 		let node = ChannelManager::new(
 			cfgs[i].fee_estimator,
 			&cfgs[i].chain_monitor,
@@ -4619,6 +4627,7 @@ pub fn create_network<'a, 'b: 'a, 'c: 'b>(
 				cfgs[i].tx_broadcaster,
 				wallet,
 				&cfgs[i].keys_manager,
+				&DefaultFeeBumpPolicy,
 				cfgs[i].logger,
 			),
 		})
@@ -72,6 +72,7 @@ use crate::sync::{Arc, Mutex, RwLock};
 use alloc::collections::BTreeSet;
 use bitcoin::hashes::Hash;
 use core::iter::repeat;
+use core::sync::atomic::Ordering;
 use lightning_macros::xtest;
 
 use crate::ln::functional_test_utils::*;
@@ -1068,6 +1069,45 @@ fn do_test_forming_justice_tx_from_monitor_updates(broadcast_initial_commitment:
 	assert_eq!(total_claimable_balance, expected_claimable_balance);
 }
 
+#[xtest(feature = "_externalize_tests")]
+pub fn test_presigned_justice_tx_claims_htlc_outputs() {
+	// Check that `ChannelMonitor::sign_justice_tx_for_commitment` signs a justice tx claiming both
+	// the `to_local` and HTLC outputs of a revoked counterparty commitment.
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let destination_script0 = chanmon_cfgs[0].keys_manager.get_destination_script([0; 32]).unwrap();
+	let destination_script1 = chanmon_cfgs[1].keys_manager.get_destination_script([0; 32]).unwrap();
+	let persisters = [
+		WatchtowerPersister::new(destination_script0),
+		WatchtowerPersister::new(destination_script1.clone()),
+	];
+	let node_cfgs = create_node_cfgs_with_persisters(2, &chanmon_cfgs, persisters.iter().collect());
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[None, None]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+
+	let (_, _, channel_id, _) = create_announced_chan_between_nodes(&nodes, 0, 1);
+	send_payment(&nodes[0], &[&nodes[1]], 5_000_000);
+
+	// Leave an HTLC pending on nodes[0]'s commitment, which we'll revoke once it's claimed.
+	let (payment_preimage, ..) = route_payment(&nodes[0], &[&nodes[1]], 3_000_000);
+	let revoked_local_txn = get_local_commitment_txn!(nodes[0], channel_id);
+	let revoked_commitment_tx = &revoked_local_txn[0];
+	assert_eq!(revoked_commitment_tx.output.len(), 3);
+	claim_payment(&nodes[0], &[&nodes[1]], payment_preimage);
+
+	let revoked_txid = revoked_commitment_tx.compute_txid();
+	let justice_tx = persisters[1].full_justice_tx(channel_id, &revoked_txid).unwrap();
+	check_spends!(justice_tx, revoked_commitment_tx);
+	// We claim nodes[0]'s `to_local` output and the HTLC output, but not our own `to_remote`.
+	assert_eq!(justice_tx.input.len(), 2);
+	assert_eq!(justice_tx.output.len(), 1);
+	assert_eq!(justice_tx.output[0].script_pubkey, destination_script1);
+
+	// The `to_local`-only justice tx formed via `sign_to_local_justice_tx` should be a subset.
+	let to_local_justice_tx = persisters[1].justice_tx(channel_id, &revoked_txid).unwrap();
+	let to_local_outpoint = to_local_justice_tx.input[0].previous_output;
+	assert!(justice_tx.input.iter().any(|input| input.previous_output == to_local_outpoint));
+}
+
 #[xtest(feature = "_externalize_tests")]
 pub fn claim_htlc_outputs() {
 	// Node revoked old state, htlcs haven't time out yet, claim them in shared justice tx
@@ -2270,6 +2310,7 @@ pub fn fail_backward_pending_htlc_upon_channel_failure() {
 			skimmed_fee_msat: None,
 			blinding_point: None,
 			hold_htlc: None,
+			endorsed: None,
 		};
 		nodes[0].node.handle_update_add_htlc(node_b_id, &update_add_htlc);
 	}
@@ -9899,3 +9940,36 @@ pub fn test_multi_post_event_actions() {
 	do_test_multi_post_event_actions(true);
 	do_test_multi_post_event_actions(false);
 }
+
+#[xtest(feature = "_externalize_tests")]
+pub fn test_tx_builder_from_signer_provider() {
+	// Test that channels build their commitment transactions using the `TxBuilder` provided by
+	// `SignerProvider::get_tx_builder`, including after being reloaded.
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let persister;
+	let new_chain_monitor;
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[None, None]);
+	let node_a_reload;
+	let mut nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+
+	let node_a_id = nodes[0].node.get_our_node_id();
+
+	let chan_id = create_announced_chan_between_nodes(&nodes, 0, 1).2;
+	let commitment_txs_built = &chanmon_cfgs[0].keys_manager.commitment_txs_built;
+	assert!(commitment_txs_built.load(Ordering::Acquire) > 0);
+
+	let builds_before_payment = commitment_txs_built.load(Ordering::Acquire);
+	send_payment(&nodes[0], &[&nodes[1]], 100_000);
+	assert!(commitment_txs_built.load(Ordering::Acquire) > builds_before_payment);
+
+	let node_ser = nodes[0].node.encode();
+	let mon_ser = get_monitor!(nodes[0], chan_id).encode();
+	reload_node!(nodes[0], node_ser, &[&mon_ser], persister, new_chain_monitor, node_a_reload);
+	nodes[1].node.peer_disconnected(node_a_id);
+	reconnect_nodes(ReconnectArgs::new(&nodes[0], &nodes[1]));
+
+	let builds_before_payment = commitment_txs_built.load(Ordering::Acquire);
+	send_payment(&nodes[0], &[&nodes[1]], 100_000);
+	assert!(commitment_txs_built.load(Ordering::Acquire) > builds_before_payment);
+}
@@ -86,6 +86,24 @@ impl SpliceContribution {
 	}
 }
 
+/// The components of a dual-funded channel's funding transaction that are contributed by one
+/// party, as selected from their on-chain wallet.
+#[derive(Debug, Clone)]
+pub struct FundingContribution {
+	/// The amount to contribute to the channel.
+	pub value: Amount,
+
+	/// The inputs included in the funding transaction to meet the contributed amount plus fees.
+	/// Any excess amount will be sent to a change output.
+	pub inputs: Vec<FundingTxInput>,
+
+	/// An optional change output script. This will be used if needed or, when not set,
+	/// generated using [`SignerProvider::get_destination_script`].
+	///
+	/// [`SignerProvider::get_destination_script`]: crate::sign::SignerProvider::get_destination_script
+	pub change_script: Option<ScriptBuf>,
+}
+
 /// An input to contribute to a channel's funding transaction either when using the v2 channel
 /// establishment protocol or when splicing.
 #[derive(Debug, Clone)]
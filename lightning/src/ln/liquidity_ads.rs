@@ -0,0 +1,202 @@
+// This file is Copyright its original authors, visible in version control
+// history.
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Data structures for liquidity advertisements, allowing nodes to sell inbound liquidity by
+//! contributing to dual-funded channels opened by buyers.
+//!
+//! Sellers advertise the rates at which they are willing to fund channels in their
+//! `node_announcement`, which are parsed into
+//! [`NodeAnnouncementInfo::liquidity_ad`]. Buyers may rank sellers using
+//! [`ReadOnlyNetworkGraph::liquidity_sellers`] and then request funds from a seller using
+//! [`ChannelManager::request_channel_with_lease`].
+//!
+//! [`NodeAnnouncementInfo::liquidity_ad`]: crate::routing::gossip::NodeAnnouncementInfo::liquidity_ad
+//! [`ReadOnlyNetworkGraph::liquidity_sellers`]: crate::routing::gossip::ReadOnlyNetworkGraph::liquidity_sellers
+//! [`ChannelManager::request_channel_with_lease`]: crate::ln::channelmanager::ChannelManager::request_channel_with_lease
+
+use crate::io;
+use crate::ln::msgs::DecodeError;
+use crate::util::ser::{Readable, Writeable, Writer};
+
+#[allow(unused_imports)]
+use crate::prelude::*;
+
+/// The TLV type used for a [`LiquidityAd`] in the excess data of a `node_announcement`.
+pub const LIQUIDITY_AD_TLV_TYPE: u64 = 1;
+
+/// The rate at which a node is willing to contribute funds to a dual-funded channel, as
+/// advertised in a [`LiquidityAd`].
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub struct LiquidityAdRate {
+	/// The minimum amount the seller is willing to contribute using this rate.
+	pub min_funding_satoshis: u32,
+	/// The maximum amount the seller is willing to contribute using this rate.
+	pub max_funding_satoshis: u32,
+	/// The weight of the seller's inputs and outputs in the funding transaction, which the buyer
+	/// pays for at the funding transaction's feerate.
+	pub funding_weight: u16,
+	/// The proportional fee charged on the contributed amount, in basis points.
+	pub lease_fee_basis: u16,
+	/// The base fee charged for the contribution, in satoshis.
+	pub lease_fee_base_sat: u32,
+}
+
+impl LiquidityAdRate {
+	/// Returns whether the rate applies to a contribution of `requested_sats`.
+	pub fn supports_amount(&self, requested_sats: u64) -> bool {
+		requested_sats >= self.min_funding_satoshis as u64
+			&& requested_sats <= self.max_funding_satoshis as u64
+	}
+
+	/// Returns the fee, in satoshis, the buyer pays the seller for a contribution of
+	/// `requested_sats` to a funding transaction with the given feerate.
+	pub fn lease_fee_sat(&self, requested_sats: u64, funding_feerate_per_kw: u32) -> u64 {
+		let proportional_fee = requested_sats.saturating_mul(self.lease_fee_basis as u64) / 10_000;
+		let funding_fee = (self.funding_weight as u64) * (funding_feerate_per_kw as u64) / 1000;
+		(self.lease_fee_base_sat as u64)
+			.saturating_add(proportional_fee)
+			.saturating_add(funding_fee)
+	}
+}
+
+impl_writeable!(LiquidityAdRate, {
+	min_funding_satoshis,
+	max_funding_satoshis,
+	funding_weight,
+	lease_fee_basis,
+	lease_fee_base_sat
+});
+
+/// The rates at which a node is willing to sell inbound liquidity, as advertised in its
+/// `node_announcement`.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct LiquidityAd {
+	/// The advertised rates, which may each apply to a different range of contributions.
+	pub rates: Vec<LiquidityAdRate>,
+}
+
+impl LiquidityAd {
+	/// Returns the cheapest rate applying to a contribution of `requested_sats`, if any, along
+	/// with the resulting lease fee in satoshis.
+	pub fn best_rate_for(
+		&self, requested_sats: u64, funding_feerate_per_kw: u32,
+	) -> Option<(LiquidityAdRate, u64)> {
+		self.rates
+			.iter()
+			.filter(|rate| rate.supports_amount(requested_sats))
+			.map(|rate| (*rate, rate.lease_fee_sat(requested_sats, funding_feerate_per_kw)))
+			.min_by_key(|(_, fee)| *fee)
+	}
+
+	/// Parses a [`LiquidityAd`] from the excess data of a `node_announcement`, returning `None`
+	/// if the node doesn't advertise one or the excess data is malformed.
+	pub fn from_node_announcement_excess_data(excess_data: &[u8]) -> Option<Self> {
+		Self::decode_excess_data(excess_data).ok().flatten()
+	}
+
+	fn decode_excess_data(excess_data: &[u8]) -> Result<Option<Self>, DecodeError> {
+		let mut reader = io::Cursor::new(excess_data);
+		let mut liquidity_ad: Option<LiquidityAd> = None;
+		decode_tlv_stream!(&mut reader, {
+			(LIQUIDITY_AD_TLV_TYPE, liquidity_ad, option),
+		});
+		Ok(liquidity_ad)
+	}
+
+	/// Encodes the [`LiquidityAd`] for use as the excess data of a `node_announcement`.
+	pub fn to_node_announcement_excess_data(&self) -> Vec<u8> {
+		let mut excess_data = Vec::new();
+		encode_tlv_stream!(&mut excess_data, {
+			(LIQUIDITY_AD_TLV_TYPE, Some(self), option),
+		});
+		excess_data
+	}
+}
+
+impl Writeable for LiquidityAd {
+	fn write<W: Writer>(&self, w: &mut W) -> Result<(), io::Error> {
+		(self.rates.len() as u16).write(w)?;
+		for rate in self.rates.iter() {
+			rate.write(w)?;
+		}
+		Ok(())
+	}
+}
+
+impl Readable for LiquidityAd {
+	fn read<R: io::Read>(r: &mut R) -> Result<Self, DecodeError> {
+		let count: u16 = Readable::read(r)?;
+		let mut rates = Vec::with_capacity(count as usize);
+		for _ in 0..count {
+			rates.push(Readable::read(r)?);
+		}
+		Ok(LiquidityAd { rates })
+	}
+}
+
+/// A request for a seller to contribute funds to a dual-funded channel, sent in `open_channel2`
+/// using one of the rates from the seller's [`LiquidityAd`].
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub struct RequestFunds {
+	/// The amount the seller is requested to contribute.
+	pub requested_sats: u64,
+	/// The advertised rate the buyer agrees to pay for the contribution.
+	pub rate: LiquidityAdRate,
+}
+
+impl_writeable!(RequestFunds, { requested_sats, rate });
+
+#[cfg(test)]
+mod tests {
+	use super::{LiquidityAd, LiquidityAdRate};
+
+	fn rate(min: u32, max: u32, basis: u16, base: u32) -> LiquidityAdRate {
+		LiquidityAdRate {
+			min_funding_satoshis: min,
+			max_funding_satoshis: max,
+			funding_weight: 500,
+			lease_fee_basis: basis,
+			lease_fee_base_sat: base,
+		}
+	}
+
+	#[test]
+	fn computes_lease_fee() {
+		let rate = rate(10_000, 1_000_000, 100, 1_000);
+		// 1_000 base + 1% of 500_000 + 500 weight at 2_000 sat/kw.
+		assert_eq!(rate.lease_fee_sat(500_000, 2_000), 1_000 + 5_000 + 1_000);
+	}
+
+	#[test]
+	fn selects_cheapest_applicable_rate() {
+		let liquidity_ad = LiquidityAd {
+			rates: vec![
+				rate(10_000, 100_000, 50, 0),
+				rate(10_000, 1_000_000, 100, 0),
+				rate(100_000, 1_000_000, 10, 5_000),
+			],
+		};
+		assert_eq!(liquidity_ad.best_rate_for(50_000, 0), Some((liquidity_ad.rates[0], 250)));
+		assert_eq!(liquidity_ad.best_rate_for(500_000, 0), Some((liquidity_ad.rates[2], 5_500)));
+		assert_eq!(liquidity_ad.best_rate_for(5_000, 0), None);
+	}
+
+	#[test]
+	fn round_trips_node_announcement_excess_data() {
+		let liquidity_ad = LiquidityAd { rates: vec![rate(10_000, 1_000_000, 100, 1_000)] };
+		let excess_data = liquidity_ad.to_node_announcement_excess_data();
+		assert_eq!(
+			LiquidityAd::from_node_announcement_excess_data(&excess_data),
+			Some(liquidity_ad)
+		);
+
+		assert_eq!(LiquidityAd::from_node_announcement_excess_data(&[]), None);
+		assert_eq!(LiquidityAd::from_node_announcement_excess_data(&[42; 3]), None);
+	}
+}
@@ -0,0 +1,366 @@
+// This file is Copyright its original authors, visible in version control
+// history.
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Types for tracking the local reputation of the peers forwarding HTLCs through us.
+//!
+//! HTLCs may carry the experimental endorsement signal described in [bLIP 4], by which the sender
+//! of an `update_add_htlc` vouches for the HTLC being resolved quickly. To decide whether to endorse
+//! the HTLCs we forward in turn, the [`ChannelManager`] keeps a [`LocalReputation`] for each peer
+//! which offers HTLCs to us, consisting of:
+//!  * the fees its HTLCs earned us, less the fees we'd have earned from an HTLC for each
+//!    [`HTLCEndorsementConfig::resolution_period_secs`] beyond the first it took to resolve an
+//!    HTLC it endorsed, decaying with [`HTLCEndorsementConfig::reputation_half_life_secs`], and
+//!  * the fees we'd lose if all HTLCs of the peer we endorsed and which are still in flight were
+//!    held until they expire.
+//!
+//! A peer has sufficient reputation for an HTLC if its reputation exceeds the fees we'd lose if
+//! the HTLC, as well as all other endorsed HTLCs it has in flight, were held until they expire.
+//! If [`HTLCEndorsementConfig::use_local_reputation`] is set, we only endorse HTLCs which the
+//! previous hop endorsed and for which it has sufficient reputation. Endorsed HTLCs may then be
+//! granted access to resources of our channels which unendorsed HTLCs can't use, limiting the
+//! damage slow HTLCs from peers with insufficient reputation can do.
+//!
+//! Reputation is not persisted and thus rebuilt from scratch after restarting.
+//!
+//! [bLIP 4]: https://github.com/lightning/blips/blob/master/blip-0004.md
+//! [`ChannelManager`]: crate::ln::channelmanager::ChannelManager
+//! [`HTLCEndorsementConfig::resolution_period_secs`]: crate::util::config::HTLCEndorsementConfig::resolution_period_secs
+//! [`HTLCEndorsementConfig::reputation_half_life_secs`]: crate::util::config::HTLCEndorsementConfig::reputation_half_life_secs
+//! [`HTLCEndorsementConfig::use_local_reputation`]: crate::util::config::HTLCEndorsementConfig::use_local_reputation
+
+use bitcoin::secp256k1::PublicKey;
+
+use crate::ln::types::ChannelId;
+use crate::util::config::HTLCEndorsementConfig;
+
+use crate::prelude::*;
+
+use core::time::Duration;
+
+/// The maximum number of peers for which we track a [`LocalReputation`]. Once reached, the peer
+/// without HTLCs in flight whose reputation was updated least recently is forgotten to make room
+/// for a new one.
+pub const MAX_PEERS_WITH_REPUTATION: usize = 1024;
+
+/// The time we expect it to take to mine a block, used to assess how long an HTLC may be held
+/// until it expires.
+const EXPECTED_BLOCK_INTERVAL_SECS: u64 = 60 * 10;
+
+/// The local reputation of a peer which offers HTLCs to us for forwarding.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LocalReputation {
+	/// The fees, in millisatoshis, the HTLCs of the peer earned us, less the cost of endorsed
+	/// HTLCs which took long to resolve, decayed over time.
+	///
+	/// This may be negative if the peer held endorsed HTLCs for long.
+	pub reputation_msat: i64,
+	/// The fees, in millisatoshis, we'd lose if all HTLCs of the peer which we endorsed and are
+	/// still in flight were held until they expire.
+	pub in_flight_risk_msat: u64,
+	/// The number of HTLCs of the peer we forwarded which are still in flight.
+	pub in_flight_htlcs: usize,
+}
+
+struct PeerReputation {
+	reputation: LocalReputation,
+	last_updated: Duration,
+}
+
+impl PeerReputation {
+	fn decay(&mut self, now: Duration, half_life_secs: u64) {
+		let elapsed_secs = now.saturating_sub(self.last_updated).as_secs();
+		self.reputation.reputation_msat =
+			decayed_reputation_msat(self.reputation.reputation_msat, elapsed_secs, half_life_secs);
+		self.last_updated = core::cmp::max(self.last_updated, now);
+	}
+}
+
+/// Decays the given reputation by halving it each `half_life_secs`, approximating the decay
+/// linearly within a half-life.
+fn decayed_reputation_msat(reputation_msat: i64, elapsed_secs: u64, half_life_secs: u64) -> i64 {
+	if half_life_secs == 0 {
+		return 0;
+	}
+	let half_lives = elapsed_secs / half_life_secs;
+	if half_lives >= 64 {
+		return 0;
+	}
+	let halved = reputation_msat as i128 / (1i128 << half_lives);
+	let remainder = (elapsed_secs % half_life_secs) as i128;
+	(halved - halved * remainder / (2 * half_life_secs as i128)) as i64
+}
+
+/// The fees we'd lose if an HTLC paying us the given fee was held until it expires, assessed as
+/// the fee for each resolution period until then.
+fn htlc_risk_msat(
+	fee_msat: u64, outgoing_cltv_expiry: u32, height: u32, config: &HTLCEndorsementConfig,
+) -> u64 {
+	let max_hold_secs =
+		(outgoing_cltv_expiry.saturating_sub(height) as u64) * EXPECTED_BLOCK_INTERVAL_SECS;
+	let resolution_period_secs = core::cmp::max(config.resolution_period_secs, 1);
+	let max_hold_periods = (max_hold_secs + resolution_period_secs - 1) / resolution_period_secs;
+	fee_msat.saturating_mul(max_hold_periods)
+}
+
+struct InFlightHTLC {
+	counterparty_node_id: PublicKey,
+	fee_msat: u64,
+	risk_msat: u64,
+	incoming_endorsed: bool,
+	forwarded_at: Duration,
+}
+
+/// Tracks the [`LocalReputation`] of peers, bounded by [`MAX_PEERS_WITH_REPUTATION`], as well as
+/// the HTLCs they have in flight.
+pub(crate) struct LocalReputationTracker {
+	peers: HashMap<PublicKey, PeerReputation>,
+	// Keyed by the channel and ID of the inbound HTLC.
+	in_flight_htlcs: HashMap<(ChannelId, u64), InFlightHTLC>,
+}
+
+impl LocalReputationTracker {
+	pub(crate) fn new() -> Self {
+		Self { peers: new_hash_map(), in_flight_htlcs: new_hash_map() }
+	}
+
+	/// Returns whether the given peer has sufficient reputation for us to endorse an HTLC it
+	/// offered to us paying us `fee_msat` and which we'd forward with the given CLTV expiry.
+	pub(crate) fn has_sufficient_reputation(
+		&self, counterparty_node_id: &PublicKey, fee_msat: u64, outgoing_cltv_expiry: u32,
+		height: u32, now: Duration, config: &HTLCEndorsementConfig,
+	) -> bool {
+		let reputation = match self.reputation(counterparty_node_id, now, config) {
+			Some(reputation) => reputation,
+			None => return false,
+		};
+		let risk_msat = htlc_risk_msat(fee_msat, outgoing_cltv_expiry, height, config);
+		let total_risk_msat = reputation.in_flight_risk_msat as i128 + risk_msat as i128;
+		reputation.reputation_msat as i128 > total_risk_msat
+	}
+
+	/// Records that we forwarded the inbound HTLC with the given ID in the given channel, which
+	/// the given peer offered to us.
+	pub(crate) fn htlc_forwarded(
+		&mut self, counterparty_node_id: PublicKey, prev_channel_id: ChannelId, prev_htlc_id: u64,
+		incoming_endorsed: bool, outgoing_endorsed: bool, fee_msat: u64, outgoing_cltv_expiry: u32,
+		height: u32, now: Duration, config: &HTLCEndorsementConfig,
+	) {
+		if !self.peers.contains_key(&counterparty_node_id)
+			&& self.peers.len() >= MAX_PEERS_WITH_REPUTATION
+		{
+			let stalest_peer = self
+				.peers
+				.iter()
+				.filter(|(_, peer)| peer.reputation.in_flight_htlcs == 0)
+				.min_by_key(|(_, peer)| peer.last_updated)
+				.map(|(node_id, _)| *node_id);
+			match stalest_peer {
+				Some(node_id) => {
+					self.peers.remove(&node_id);
+				},
+				// All peers we track have HTLCs in flight, so we can't track another one.
+				None => return,
+			}
+		}
+
+		let risk_msat = if outgoing_endorsed {
+			htlc_risk_msat(fee_msat, outgoing_cltv_expiry, height, config)
+		} else {
+			0
+		};
+		let peer = self.peers.entry(counterparty_node_id).or_insert_with(|| PeerReputation {
+			reputation: LocalReputation {
+				reputation_msat: 0,
+				in_flight_risk_msat: 0,
+				in_flight_htlcs: 0,
+			},
+			last_updated: now,
+		});
+		peer.decay(now, config.reputation_half_life_secs);
+		peer.reputation.in_flight_risk_msat =
+			peer.reputation.in_flight_risk_msat.saturating_add(risk_msat);
+		peer.reputation.in_flight_htlcs += 1;
+
+		let htlc = InFlightHTLC {
+			counterparty_node_id,
+			fee_msat,
+			risk_msat,
+			incoming_endorsed,
+			forwarded_at: now,
+		};
+		if let Some(replaced) = self.in_flight_htlcs.insert((prev_channel_id, prev_htlc_id), htlc) {
+			// We never forward the same inbound HTLC twice, but handle it gracefully regardless.
+			debug_assert!(false, "Inbound HTLC {} forwarded twice", prev_htlc_id);
+			self.release_in_flight(&replaced);
+		}
+	}
+
+	/// Records that the inbound HTLC with the given ID in the given channel was resolved, i.e.,
+	/// claimed if `settled` is set, or failed otherwise, updating the reputation of the peer which
+	/// offered it to us. Does nothing if we didn't forward the HTLC.
+	pub(crate) fn htlc_resolved(
+		&mut self, prev_channel_id: &ChannelId, prev_htlc_id: u64, settled: bool, now: Duration,
+		config: &HTLCEndorsementConfig,
+	) {
+		let htlc = match self.in_flight_htlcs.remove(&(*prev_channel_id, prev_htlc_id)) {
+			Some(htlc) => htlc,
+			None => return,
+		};
+		self.release_in_flight(&htlc);
+
+		let mut effective_fees_msat = if settled { htlc.fee_msat as i128 } else { 0 };
+		if htlc.incoming_endorsed {
+			let hold_secs = now.saturating_sub(htlc.forwarded_at).as_secs();
+			let resolution_period_secs = core::cmp::max(config.resolution_period_secs, 1);
+			let slow_periods = hold_secs.saturating_sub(1) / resolution_period_secs;
+			effective_fees_msat -= htlc.fee_msat as i128 * slow_periods as i128;
+		}
+		if let Some(peer) = self.peers.get_mut(&htlc.counterparty_node_id) {
+			peer.decay(now, config.reputation_half_life_secs);
+			let reputation_msat = peer.reputation.reputation_msat as i128 + effective_fees_msat;
+			peer.reputation.reputation_msat =
+				reputation_msat.clamp(i64::MIN as i128, i64::MAX as i128) as i64;
+		}
+	}
+
+	fn release_in_flight(&mut self, htlc: &InFlightHTLC) {
+		if let Some(peer) = self.peers.get_mut(&htlc.counterparty_node_id) {
+			peer.reputation.in_flight_risk_msat =
+				peer.reputation.in_flight_risk_msat.saturating_sub(htlc.risk_msat);
+			peer.reputation.in_flight_htlcs = peer.reputation.in_flight_htlcs.saturating_sub(1);
+		}
+	}
+
+	pub(crate) fn reputation(
+		&self, counterparty_node_id: &PublicKey, now: Duration, config: &HTLCEndorsementConfig,
+	) -> Option<LocalReputation> {
+		self.peers.get(counterparty_node_id).map(|peer| {
+			let elapsed_secs = now.saturating_sub(peer.last_updated).as_secs();
+			let reputation_msat = decayed_reputation_msat(
+				peer.reputation.reputation_msat,
+				elapsed_secs,
+				config.reputation_half_life_secs,
+			);
+			LocalReputation { reputation_msat, ..peer.reputation }
+		})
+	}
+
+	pub(crate) fn list_peers(
+		&self, now: Duration, config: &HTLCEndorsementConfig,
+	) -> Vec<(PublicKey, LocalReputation)> {
+		self.peers
+			.keys()
+			.filter_map(|node_id| Some((*node_id, self.reputation(node_id, now, config)?)))
+			.collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use bitcoin::secp256k1::{Secp256k1, SecretKey};
+
+	fn node_id(idx: u16) -> PublicKey {
+		let mut key = [42; 32];
+		key[..2].copy_from_slice(&idx.to_be_bytes());
+		PublicKey::from_secret_key(&Secp256k1::new(), &SecretKey::from_slice(&key).unwrap())
+	}
+
+	#[test]
+	fn decays_reputation() {
+		assert_eq!(decayed_reputation_msat(1_000, 0, 100), 1_000);
+		assert_eq!(decayed_reputation_msat(1_000, 50, 100), 750);
+		assert_eq!(decayed_reputation_msat(1_000, 100, 100), 500);
+		assert_eq!(decayed_reputation_msat(-1_000, 200, 100), -250);
+		assert_eq!(decayed_reputation_msat(i64::MAX, 64 * 100, 100), 0);
+		assert_eq!(decayed_reputation_msat(1_000, 1, 0), 0);
+	}
+
+	#[test]
+	fn builds_reputation_from_fees() {
+		let config = HTLCEndorsementConfig::default();
+		let mut tracker = LocalReputationTracker::new();
+		let peer = node_id(0);
+		let chan_id = ChannelId::from_bytes([1; 32]);
+		let now = Duration::from_secs(1_000_000);
+
+		// An unknown peer never has sufficient reputation.
+		assert!(!tracker.has_sufficient_reputation(&peer, 0, 100, 100, now, &config));
+		assert!(tracker.reputation(&peer, now, &config).is_none());
+
+		// Forward and settle a number of HTLCs paying a 1000 msat fee, building up reputation.
+		for htlc_id in 0..1_000 {
+			tracker
+				.htlc_forwarded(peer, chan_id, htlc_id, true, false, 1_000, 110, 100, now, &config);
+			tracker.htlc_resolved(&chan_id, htlc_id, true, now, &config);
+		}
+		let reputation = tracker.reputation(&peer, now, &config).unwrap();
+		assert_eq!(reputation.reputation_msat, 1_000_000);
+		assert_eq!(reputation.in_flight_htlcs, 0);
+
+		// Holding an HTLC for 10 blocks risks 10 * 600 / 90 = 67 times the fee.
+		assert!(tracker.has_sufficient_reputation(&peer, 10_000, 110, 100, now, &config));
+		assert!(!tracker.has_sufficient_reputation(&peer, 15_000, 110, 100, now, &config));
+
+		// In-flight endorsed HTLCs count towards the risk.
+		tracker.htlc_forwarded(peer, chan_id, 1_000, true, true, 10_000, 110, 100, now, &config);
+		let reputation = tracker.reputation(&peer, now, &config).unwrap();
+		assert_eq!(reputation.in_flight_risk_msat, 670_000);
+		assert_eq!(reputation.in_flight_htlcs, 1);
+		assert!(!tracker.has_sufficient_reputation(&peer, 10_000, 110, 100, now, &config));
+
+		// Resolving the HTLC slowly costs the peer the fee for each additional resolution period.
+		let later = now + Duration::from_secs(config.resolution_period_secs * 3);
+		tracker.htlc_resolved(&chan_id, 1_000, false, later, &config);
+		let reputation = tracker.reputation(&peer, later, &config).unwrap();
+		assert_eq!(reputation.in_flight_risk_msat, 0);
+		assert_eq!(reputation.in_flight_htlcs, 0);
+		assert!(reputation.reputation_msat < 1_000_000 - 20_000);
+
+		// Resolving an HTLC we don't know about is a no-op.
+		tracker.htlc_resolved(&chan_id, 1_000, true, later, &config);
+		assert_eq!(tracker.reputation(&peer, later, &config).unwrap(), reputation);
+	}
+
+	#[test]
+	fn bounds_tracked_peers() {
+		let config = HTLCEndorsementConfig::default();
+		let mut tracker = LocalReputationTracker::new();
+		let chan_id = ChannelId::from_bytes([1; 32]);
+		for i in 0..MAX_PEERS_WITH_REPUTATION as u16 {
+			let now = Duration::from_secs(1_000 + i as u64);
+			tracker.htlc_forwarded(
+				node_id(i),
+				chan_id,
+				i as u64,
+				false,
+				false,
+				0,
+				0,
+				0,
+				now,
+				&config,
+			);
+		}
+		// Resolve all HTLCs but that of the first peer, which thus can't be evicted, leaving the
+		// second peer as the stalest.
+		let now = Duration::from_secs(10_000);
+		for i in 1..MAX_PEERS_WITH_REPUTATION as u64 {
+			tracker.htlc_resolved(&chan_id, i, true, Duration::from_secs(1_000 + i), &config);
+		}
+
+		let new_peer = node_id(MAX_PEERS_WITH_REPUTATION as u16);
+		tracker.htlc_forwarded(new_peer, chan_id, u64::MAX, false, false, 0, 0, 0, now, &config);
+		assert_eq!(tracker.list_peers(now, &config).len(), MAX_PEERS_WITH_REPUTATION);
+		assert!(tracker.reputation(&node_id(0), now, &config).is_some());
+		assert!(tracker.reputation(&node_id(1), now, &config).is_none());
+		assert!(tracker.reputation(&new_peer, now, &config).is_some());
+	}
+}
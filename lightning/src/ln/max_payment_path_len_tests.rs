@@ -156,7 +156,14 @@ fn large_payment_metadata() {
 		.node
 		.send_payment(payment_hash_2, too_large_onion, id, route_params, Retry::Attempts(0))
 		.unwrap_err();
-	assert_eq!(err, RetryableSendFailure::OnionPacketSizeExceeded);
+	match err {
+		RetryableSendFailure::OnionPacketSizeExceeded(e) => {
+			assert!(e.excess_bytes > 0);
+			// Only custom TLVs are suggested for trimming, not payment metadata.
+			assert!(e.custom_tlvs_to_trim.is_empty());
+		},
+		_ => panic!(),
+	}
 
 	// If we remove enough payment_metadata bytes to allow for 2 hops, we're now able to send to
 	// nodes[2].
@@ -290,7 +297,13 @@ fn one_hop_blinded_path_with_custom_tlv() {
 		.node
 		.send_payment(payment_hash, too_large_custom_tlv_onion, id, route_params.clone(), no_retry)
 		.unwrap_err();
-	assert_eq!(err, RetryableSendFailure::OnionPacketSizeExceeded);
+	match err {
+		RetryableSendFailure::OnionPacketSizeExceeded(e) => {
+			assert_eq!(e.excess_bytes, 1);
+			assert_eq!(e.custom_tlvs_to_trim, vec![CUSTOM_TLV_TYPE]);
+		},
+		_ => panic!(),
+	}
 
 	// With the maximum-size custom TLV, our max path length is limited to 1, so attempting to route
 	// nodes[0] -> nodes[2] will fail.
@@ -416,7 +429,33 @@ fn blinded_path_with_custom_tlv() {
 		.node
 		.send_payment(payment_hash, too_large_onion.clone(), id, route_params.clone(), no_retry)
 		.unwrap_err();
-	assert_eq!(err, RetryableSendFailure::OnionPacketSizeExceeded);
+	match err {
+		RetryableSendFailure::OnionPacketSizeExceeded(e) => {
+			assert_eq!(e.custom_tlvs_to_trim, vec![CUSTOM_TLV_TYPE]);
+		},
+		_ => panic!(),
+	}
+
+	// Check that the onion payload budget of the route accounts for the custom TLV.
+	let cur_height = nodes[0].best_block_info().1 + DEFAULT_MAX_TOTAL_CLTV_EXPIRY_DELTA;
+	let budget = onion_utils::onion_payload_budget(
+		&route.paths[0],
+		MIN_FINAL_VALUE_ESTIMATE_WITH_OVERPAY,
+		&max_sized_onion,
+		cur_height,
+		None,
+	)
+	.unwrap();
+	assert_eq!(budget.remaining_bytes(), Some(0));
+	let budget = onion_utils::onion_payload_budget(
+		&route.paths[0],
+		MIN_FINAL_VALUE_ESTIMATE_WITH_OVERPAY,
+		&too_large_onion,
+		cur_height,
+		None,
+	)
+	.unwrap();
+	assert_eq!(budget.remaining_bytes(), None);
 
 	// Confirm that we can't construct an onion packet given this too-large custom TLV.
 	let secp_ctx = Secp256k1::signing_only();
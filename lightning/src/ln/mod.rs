@@ -13,18 +13,29 @@
 #[macro_use]
 pub mod functional_test_utils;
 
+pub mod batch_payment;
 pub mod chan_utils;
 pub mod channel_keys;
 pub mod channel_state;
 pub mod channelmanager;
+pub mod closure_scheduler;
 mod features;
+pub mod force_close_observer;
+pub mod forwarding_policy;
 pub mod funding;
 pub mod inbound_payment;
+pub mod liquidity_ads;
+pub mod local_reputation;
 pub mod msgs;
 pub mod onion_payment;
 pub mod our_peer_storage;
 pub mod peer_handler;
+pub mod peer_invoice;
+pub mod peer_misbehavior;
+pub mod probing;
 pub mod script;
+pub mod static_backup;
+pub mod surge_pricing;
 pub mod types;
 
 // TODO: These modules were moved from lightning-invoice and need to be better integrated into this
@@ -113,6 +124,8 @@ mod reorg_tests;
 mod shutdown_tests;
 #[cfg(any(feature = "_test_utils", test))]
 pub mod splicing_tests;
+#[cfg(all(test, taproot))]
+mod taproot_tests;
 #[cfg(any(test, feature = "_externalize_tests"))]
 #[allow(unused_mut)]
 pub mod update_fee_tests;
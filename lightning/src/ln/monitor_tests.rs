@@ -11,11 +11,12 @@
 
 //! Further functional tests which test blockchain reorganizations.
 
-use crate::sign::{ecdsa::EcdsaChannelSigner, OutputSpender, SignerProvider, SpendableOutputDescriptor};
+use crate::sign::{ecdsa::EcdsaChannelSigner, DelayedPaymentOutputDescriptor, OutputSpender, SignerProvider, SpendableOutputDescriptor, P2WPKH_WITNESS_WEIGHT};
 use crate::chain::Watch;
 use crate::chain::channelmonitor::{Balance, BalanceSource, ChannelMonitorUpdateStep, HolderCommitmentTransactionBalance, ANTI_REORG_DELAY, ARCHIVAL_DELAY_BLOCKS, COUNTERPARTY_CLAIMABLE_WITHIN_BLOCKS_PINNABLE, LATENCY_GRACE_PERIOD_BLOCKS};
+use crate::chain::package::weight_offered_htlc;
 use crate::chain::transaction::OutPoint;
-use crate::chain::chaininterface::{ConfirmationTarget, LowerBoundedFeeEstimator, compute_feerate_sat_per_1000_weight};
+use crate::chain::chaininterface::{BroadcastType, ConfirmationTarget, LowerBoundedFeeEstimator, compute_feerate_sat_per_1000_weight};
 use crate::events::bump_transaction::BumpTransactionEvent;
 use crate::events::{Event, ClosureReason, HTLCHandlingFailureType};
 use crate::ln::channel;
@@ -153,6 +154,16 @@ fn revoked_output_htlc_resolution_timing() {
 	}
 	assert_ne!(bs_spend_txn[0].input[0].previous_output, bs_spend_txn[1].input[0].previous_output);
 
+	// Both are handed to the broadcaster as urgent justice transactions for the channel.
+	let broadcast_contexts = nodes[1].tx_broadcaster.broadcast_contexts();
+	for tx in bs_spend_txn.iter() {
+		let (_, context) = broadcast_contexts.iter()
+			.find(|(txid, _)| *txid == tx.compute_txid()).unwrap();
+		assert_eq!(context.broadcast_type, BroadcastType::Justice);
+		assert_eq!(context.channel_id, Some(chan.2));
+		assert_eq!(context.urgency, Some(ConfirmationTarget::UrgentOnChainSweep));
+	}
+
 	// After the commitment transaction confirms, we should still wait on the HTLC spend
 	// transaction to confirm before resolving the HTLC.
 	connect_blocks(&nodes[1], ANTI_REORG_DELAY - 1);
@@ -2393,6 +2404,153 @@ fn test_restored_packages_retry() {
 	do_test_restored_packages_retry(true);
 }
 
+#[test]
+fn test_pending_claims_persist_across_reload() {
+	// Tests that pending claims, along with their `ClaimId`s and latest feerate, are retained
+	// across a restart and exposed via `ChannelMonitor::get_pending_claims`.
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let persister;
+	let new_chain_monitor;
+
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[None, None]);
+	let node_deserialized;
+
+	let mut nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+
+	let (_, _, chan_id, funding_tx) = create_announced_chan_between_nodes_with_value(&nodes, 0, 1, 100_000, 50_000_000);
+	route_payment(&nodes[0], &[&nodes[1]], 10_000_000);
+	let message = "Channel force-closed".to_owned();
+	nodes[0].node.force_close_broadcasting_latest_txn(&chan_id, &nodes[1].node.get_our_node_id(), message.clone()).unwrap();
+	check_added_monitors(&nodes[0], 1);
+	check_closed_broadcast(&nodes[0], 1, true);
+	let reason = ClosureReason::HolderForceClosed { broadcasted_latest_txn: Some(true), message };
+	check_closed_event(&nodes[0], 1, reason, &[nodes[1].node.get_our_node_id()], 100000);
+
+	let commitment_tx = {
+		let mut txn = nodes[0].tx_broadcaster.txn_broadcast();
+		assert_eq!(txn.len(), 1);
+		check_spends!(txn[0], funding_tx);
+		txn.pop().unwrap()
+	};
+
+	mine_transaction(&nodes[0], &commitment_tx);
+	if nodes[0].connect_style.borrow().updates_best_block_first() {
+		let txn = nodes[0].tx_broadcaster.txn_broadcast();
+		assert_eq!(txn.len(), 1);
+		assert_eq!(txn[0].compute_txid(), commitment_tx.compute_txid());
+	}
+
+	// Once the HTLC expires, we should start tracking a claim for its timeout.
+	connect_blocks(&nodes[0], TEST_FINAL_CLTV);
+	let htlc_timeout_tx = {
+		let mut txn = nodes[0].tx_broadcaster.txn_broadcast();
+		assert_eq!(txn.len(), 1);
+		check_spends!(txn[0], commitment_tx);
+		txn.pop().unwrap()
+	};
+	let htlc_outpoint = htlc_timeout_tx.input[0].previous_output;
+	let pending_claims = get_monitor!(nodes[0], chan_id).get_pending_claims();
+	assert_eq!(pending_claims.len(), 1);
+	assert_eq!(pending_claims[0].outpoints, vec![htlc_outpoint]);
+	assert!(pending_claims[0].feerate_sat_per_1000_weight > 0);
+	assert!(pending_claims[0].next_bump_height > nodes[0].best_block_info().1);
+	assert_eq!(pending_claims[0].confirmations, None);
+
+	// After reloading, the same claim should still be tracked with the same `ClaimId` and feerate.
+	let serialized_monitor = get_monitor!(nodes[0], chan_id).encode();
+	reload_node!(nodes[0], &nodes[0].node.encode(), &[&serialized_monitor], persister, new_chain_monitor, node_deserialized);
+	assert_eq!(get_monitor!(nodes[0], chan_id).get_pending_claims(), pending_claims);
+
+	// Once our claim confirms, its confirmations should be reported until it's considered final.
+	mine_transaction(&nodes[0], &htlc_timeout_tx);
+	let pending_claims = get_monitor!(nodes[0], chan_id).get_pending_claims();
+	assert_eq!(pending_claims.len(), 1);
+	assert_eq!(pending_claims[0].confirmations, Some(1));
+	connect_blocks(&nodes[0], 1);
+	let pending_claims = get_monitor!(nodes[0], chan_id).get_pending_claims();
+	assert_eq!(pending_claims[0].confirmations, Some(2));
+}
+
+#[test]
+fn test_anchor_spend_delegation() {
+	// Tests that we provide the data required for a third party to get our commitment transaction
+	// confirmed by spending the counterparty's anchor output, and that we detect when they do so.
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let mut anchors_config = test_default_channel_config();
+	anchors_config.channel_handshake_config.negotiate_anchors_zero_fee_htlc_tx = true;
+	anchors_config.manually_accept_inbound_channels = true;
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[Some(anchors_config.clone()), Some(anchors_config)]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+
+	let (_, _, chan_id, funding_tx) = create_announced_chan_between_nodes_with_value(
+		&nodes, 0, 1, 1_000_000, 500_000_000
+	);
+
+	// We must not sign our commitment transaction until we've decided to broadcast it.
+	assert!(get_monitor!(nodes[0], chan_id).get_anchor_spend_delegation().is_none());
+
+	let message = "Channel force-closed".to_owned();
+	nodes[0].node.force_close_broadcasting_latest_txn(&chan_id, &nodes[1].node.get_our_node_id(), message.clone()).unwrap();
+	check_added_monitors(&nodes[0], 1);
+	check_closed_broadcast(&nodes[0], 1, true);
+	let reason = ClosureReason::HolderForceClosed { broadcasted_latest_txn: Some(true), message };
+	check_closed_event(&nodes[0], 1, reason, &[nodes[1].node.get_our_node_id()], 1_000_000);
+	// We're delegating the anchor spend, so we don't handle the bump event ourselves.
+	nodes[0].chain_monitor.chain_monitor.get_and_clear_pending_events();
+
+	let delegation = get_monitor!(nodes[0], chan_id).get_anchor_spend_delegation().unwrap();
+	check_spends!(delegation.commitment_tx, funding_tx);
+	assert_eq!(delegation.anchor_outpoint.txid, delegation.commitment_tx.compute_txid());
+	let anchor_output = &delegation.commitment_tx.output[delegation.anchor_outpoint.vout as usize];
+	assert_eq!(anchor_output.value, delegation.anchor_value);
+	assert_eq!(anchor_output.script_pubkey, delegation.witness_script.to_p2wsh());
+	assert!(get_monitor!(nodes[0], chan_id).get_delegated_anchor_spend_txid().is_none());
+
+	// Confirm the commitment transaction along with a third-party child spending the anchor.
+	let anchor_spend_tx = Transaction {
+		version: Version::TWO,
+		lock_time: LockTime::ZERO,
+		input: vec![TxIn {
+			previous_output: delegation.anchor_outpoint,
+			script_sig: ScriptBuf::new(),
+			sequence: bitcoin::Sequence::ENABLE_RBF_NO_LOCKTIME,
+			witness: Witness::from_slice(&[&[][..], delegation.witness_script.as_bytes()]),
+		}],
+		output: vec![TxOut { value: Amount::ZERO, script_pubkey: ScriptBuf::new() }],
+	};
+	mine_transactions(&nodes[0], &[&delegation.commitment_tx, &anchor_spend_tx]);
+	assert_eq!(
+		get_monitor!(nodes[0], chan_id).get_delegated_anchor_spend_txid(),
+		Some(anchor_spend_tx.compute_txid())
+	);
+
+	// If the block is reorged out, so is the anchor spend.
+	disconnect_blocks(&nodes[0], 1);
+	assert!(get_monitor!(nodes[0], chan_id).get_delegated_anchor_spend_txid().is_none());
+	nodes[0].chain_monitor.chain_monitor.get_and_clear_pending_events();
+
+	// Once the commitment transaction confirms on its own, the anchor is registered with the
+	// chain source, such that a spend of it confirming in a later block is detected too.
+	mine_transaction(&nodes[0], &delegation.commitment_tx);
+	let anchor_outpoint = OutPoint {
+		txid: delegation.anchor_outpoint.txid,
+		index: delegation.anchor_outpoint.vout as u16,
+	};
+	let anchor_script_pubkey = delegation.witness_script.to_p2wsh();
+	let watched_outputs = nodes[0].chain_source.watched_outputs.lock().unwrap().clone();
+	assert!(watched_outputs.contains(&(anchor_outpoint, anchor_script_pubkey)));
+	connect_blocks(&nodes[0], 16);
+	assert!(get_monitor!(nodes[0], chan_id).get_delegated_anchor_spend_txid().is_none());
+	mine_transaction(&nodes[0], &anchor_spend_tx);
+	assert_eq!(
+		get_monitor!(nodes[0], chan_id).get_delegated_anchor_spend_txid(),
+		Some(anchor_spend_tx.compute_txid())
+	);
+	nodes[0].chain_monitor.chain_monitor.get_and_clear_pending_events();
+}
+
 fn do_test_monitor_rebroadcast_pending_claims(keyed_anchors: bool, p2a_anchor: bool) {
 	// Test that we will retry broadcasting pending claims for a force-closed channel on every
 	// `ChainMonitor::rebroadcast_pending_claims` call.
@@ -3917,3 +4075,126 @@ fn test_ladder_preimage_htlc_claims() {
 	expect_payment_sent(&nodes[0], payment_preimage2, None, true, false);
 	check_added_monitors(&nodes[0], 1);
 }
+
+#[test]
+fn test_monitor_compaction() {
+	// Tests that `ChainMonitor::compact_monitor` drops the HTLC data for old revoked counterparty
+	// commitment transactions, shrinking the serialized `ChannelMonitor`, and that we still claim
+	// the `to_local` output of a compacted revoked commitment transaction if it confirms.
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[None, None]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+
+	let chan_id = create_announced_chan_between_nodes_with_value(&nodes, 0, 1, 1_000_000, 500_000_000).2;
+
+	// Get a commitment transaction with an HTLC output which we'll revoke by claiming the HTLC.
+	let (payment_preimage, ..) = route_payment(&nodes[1], &[&nodes[0]], 1_000_000);
+	let revoked_local_txn = get_local_commitment_txn!(nodes[0], chan_id);
+	assert_eq!(revoked_local_txn.len(), 1);
+	assert_eq!(revoked_local_txn[0].output.len(), 3);
+	claim_payment(&nodes[1], &[&nodes[0]], payment_preimage);
+
+	// Route a few more payments to build up revoked states in B's `ChannelMonitor`.
+	for _ in 0..5 {
+		send_payment(&nodes[0], &[&nodes[1]], 1_000_000);
+	}
+
+	// Nothing is compacted if we retain more revoked states than we have.
+	assert_eq!(nodes[1].chain_monitor.chain_monitor.compact_monitor(chan_id, 1_000), Ok(0));
+	assert!(nodes[1].chain_monitor.chain_monitor.compact_monitor(ChannelId::from_bytes([42; 32]), 1).is_err());
+
+	let monitor_size_before = get_monitor!(nodes[1], chan_id).encode().len();
+	let compacted = nodes[1].chain_monitor.chain_monitor.compact_monitor(chan_id, 1).unwrap();
+	assert!(compacted > 0);
+	assert!(get_monitor!(nodes[1], chan_id).encode().len() < monitor_size_before);
+	// Compaction should have triggered a full persistence of the monitor.
+	assert!(chanmon_cfgs[1].persister.chain_sync_monitor_persistences.lock().unwrap()
+		.contains(&get_monitor!(nodes[1], chan_id).persistence_key()));
+
+	// Compacting again to the same depth is a no-op, while dropping the most recently revoked
+	// state as well compacts exactly one more state.
+	assert_eq!(nodes[1].chain_monitor.chain_monitor.compact_monitor(chan_id, 1), Ok(0));
+	assert_eq!(nodes[1].chain_monitor.chain_monitor.compact_monitor(chan_id, 0), Ok(1));
+
+	// The channel continues to operate as normal after compaction.
+	send_payment(&nodes[0], &[&nodes[1]], 1_000_000);
+
+	// Confirm the compacted revoked commitment transaction, closing the channel. As we no longer
+	// have the HTLC data for it, only the revoked to_self output is claimed.
+	mine_transaction(&nodes[1], &revoked_local_txn[0]);
+	check_closed_broadcast!(nodes[1], true);
+	check_added_monitors(&nodes[1], 1);
+	check_closed_event(&nodes[1], 1, ClosureReason::CommitmentTxConfirmed, &[nodes[0].node.get_our_node_id()], 1000000);
+
+	let bs_spend_txn = nodes[1].tx_broadcaster.txn_broadcasted.lock().unwrap().split_off(0);
+	assert_eq!(bs_spend_txn.len(), 1);
+	assert_eq!(bs_spend_txn[0].input.len(), 1);
+	check_spends!(bs_spend_txn[0], revoked_local_txn[0]);
+
+	// Compaction is a no-op once a commitment transaction has been seen on-chain.
+	assert_eq!(nodes[1].chain_monitor.chain_monitor.compact_monitor(chan_id, 0), Ok(0));
+}
+
+#[test]
+fn test_claim_cost_estimates() {
+	// Tests that `ChannelMonitor::estimate_claim_cost` provides the expected weights for balances
+	// both before a channel closes and on a counterparty commitment transaction.
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[None, None]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+
+	let chan_id = create_announced_chan_between_nodes_with_value(&nodes, 0, 1, 1_000_000, 500_000_000).2;
+	route_payment(&nodes[0], &[&nodes[1]], 10_000_000);
+
+	let channel_type_features = get_monitor!(nodes[0], chan_id).channel_type_features();
+	assert!(!channel_type_features.supports_anchors_zero_fee_htlc_tx());
+	let input_weight = |witness_weight: u64| {
+		chan_utils::BASE_INPUT_WEIGHT + chan_utils::EMPTY_SCRIPT_SIG_WEIGHT + witness_weight
+	};
+	let delayed_output_weight = input_weight(DelayedPaymentOutputDescriptor::MAX_WITNESS_LENGTH);
+	let feerate = 2_000;
+
+	// Before the channel closes, we expect to claim from our own commitment transaction, with our
+	// outbound HTLC requiring an HTLC-Timeout transaction.
+	{
+		let monitor = get_monitor!(nodes[0], chan_id);
+		let balances = monitor.get_claimable_balances();
+		assert_eq!(balances.len(), 2);
+		for balance in balances.iter() {
+			let estimate = monitor.estimate_claim_cost(balance, feerate);
+			let expected_weight = match balance {
+				Balance::ClaimableOnChannelClose { .. } => delayed_output_weight,
+				Balance::MaybeTimeoutClaimableHTLC { .. } =>
+					chan_utils::htlc_timeout_tx_weight(&channel_type_features) + delayed_output_weight,
+				_ => panic!("Unexpected balance {:?}", balance),
+			};
+			assert_eq!(estimate.weight, expected_weight);
+			assert_eq!(estimate.fee_satoshis, expected_weight * feerate as u64 / 1000);
+		}
+	}
+
+	// Once A's commitment transaction confirms, B claims its balances directly from it.
+	let as_commitment_tx = get_local_commitment_txn!(nodes[0], chan_id);
+	mine_transaction(&nodes[1], &as_commitment_tx[0]);
+	check_closed_broadcast!(nodes[1], true);
+	check_added_monitors(&nodes[1], 1);
+	check_closed_event(&nodes[1], 1, ClosureReason::CommitmentTxConfirmed, &[nodes[0].node.get_our_node_id()], 1000000);
+
+	let monitor = get_monitor!(nodes[1], chan_id);
+	let balances = monitor.get_claimable_balances();
+	assert_eq!(balances.len(), 2);
+	for balance in balances.iter() {
+		let estimate = monitor.estimate_claim_cost(balance, feerate);
+		let expected_weight = match balance {
+			Balance::ClaimableAwaitingConfirmations { source: BalanceSource::CounterpartyForceClosed, .. } =>
+				input_weight(P2WPKH_WITNESS_WEIGHT),
+			Balance::MaybePreimageClaimableHTLC { .. } =>
+				input_weight(weight_offered_htlc(&channel_type_features)),
+			_ => panic!("Unexpected balance {:?}", balance),
+		};
+		assert_eq!(estimate.weight, expected_weight);
+		assert_eq!(estimate.fee_satoshis, expected_weight * feerate as u64 / 1000);
+	}
+}
@@ -34,6 +34,7 @@ use bitcoin::{secp256k1, Transaction, Witness};
 use crate::blinded_path::message::BlindedMessagePath;
 use crate::blinded_path::payment::{BlindedPaymentTlvs, ForwardTlvs, ReceiveTlvs};
 use crate::blinded_path::payment::{BlindedTrampolineTlvs, TrampolineForwardTlvs};
+use crate::ln::liquidity_ads::RequestFunds;
 use crate::ln::onion_utils;
 use crate::ln::types::ChannelId;
 use crate::offers::invoice_request::InvoiceRequest;
@@ -311,6 +312,11 @@ pub struct OpenChannelV2 {
 	pub second_per_commitment_point: PublicKey,
 	/// Optionally, a requirement that only confirmed inputs can be added
 	pub require_confirmed_inputs: Option<()>,
+	/// Optionally, a request for the counterparty to contribute funds to the channel at one of the
+	/// rates from its [`LiquidityAd`].
+	///
+	/// [`LiquidityAd`]: crate::ln::liquidity_ads::LiquidityAd
+	pub request_funds: Option<RequestFunds>,
 }
 
 /// Contains fields that are both common to [`accept_channel`] and [`accept_channel2`] messages.
@@ -768,6 +774,22 @@ pub struct UpdateAddHTLC {
 	///
 	/// [`ReleaseHeldHtlc`]: crate::onion_message::async_payments::ReleaseHeldHtlc
 	pub hold_htlc: Option<()>,
+	/// The experimental endorsement signal described in [bLIP 4], set to `Some(1)` if the sender
+	/// of this message endorses the HTLC, i.e., vouches for it being resolved quickly.
+	///
+	/// Any other value, including `None`, indicates that the HTLC is not endorsed. See
+	/// [`HTLCEndorsementConfig`] for how we set this on HTLCs we send and forward.
+	///
+	/// [bLIP 4]: https://github.com/lightning/blips/blob/master/blip-0004.md
+	/// [`HTLCEndorsementConfig`]: crate::util::config::HTLCEndorsementConfig
+	pub endorsed: Option<u8>,
+}
+
+impl UpdateAddHTLC {
+	/// Returns whether the sender of this message endorsed the HTLC, see [`Self::endorsed`].
+	pub fn is_endorsed(&self) -> bool {
+		self.endorsed == Some(1)
+	}
 }
 
 /// An [`onion message`] to be sent to or received from a peer.
@@ -1352,6 +1374,7 @@ impl FromStr for SocketAddress {
 }
 
 /// Represents the set of gossip messages that require a signature from a node's identity key.
+#[derive(Clone, Copy)]
 pub enum UnsignedGossipMessage<'a> {
 	/// An unsigned channel announcement.
 	ChannelAnnouncement(&'a UnsignedChannelAnnouncement),
@@ -2159,6 +2182,8 @@ pub trait ChannelMessageHandler: BaseMessageHandler {
 	// Error:
 	/// Handle an incoming `error` message from the given peer.
 	fn handle_error(&self, their_node_id: PublicKey, msg: &ErrorMessage);
+	/// Handle an incoming `warning` message from the given peer.
+	fn handle_warning(&self, their_node_id: PublicKey, msg: &WarningMessage);
 
 	// Handler information:
 	/// Gets the chain hashes for this `ChannelMessageHandler` indicating which chains it supports.
@@ -2439,12 +2464,12 @@ mod fuzzy_internal_msgs {
 			/// The node id to which the trampoline node must find a route.
 			outgoing_node_id: PublicKey,
 		},
-		#[cfg(test)]
-		/// LDK does not support making Trampoline payments to unblinded recipients. However, for
-		/// the purpose of testing our ability to receive them, we make this variant available in a
-		/// testing environment.
+		/// This is the last Trampoline hop, used when paying an unblinded recipient via Trampoline.
 		Receive {
 			payment_data: Option<FinalOnionHopData>,
+			payment_metadata: Option<&'a Vec<u8>>,
+			keysend_preimage: Option<PaymentPreimage>,
+			custom_tlvs: &'a Vec<(u64, Vec<u8>)>,
 			sender_intended_htlc_amt_msat: u64,
 			cltv_expiry_height: u32,
 		},
@@ -3204,6 +3229,7 @@ impl Writeable for OpenChannelV2 {
 			(0, self.common_fields.shutdown_scriptpubkey.as_ref().map(|s| WithoutLength(s)), option), // Don't encode length twice.
 			(1, self.common_fields.channel_type, option),
 			(2, self.require_confirmed_inputs, option),
+			(1339, self.request_funds, option),
 		});
 		Ok(())
 	}
@@ -3234,10 +3260,12 @@ impl LengthReadable for OpenChannelV2 {
 		let mut shutdown_scriptpubkey: Option<ScriptBuf> = None;
 		let mut channel_type: Option<ChannelTypeFeatures> = None;
 		let mut require_confirmed_inputs: Option<()> = None;
+		let mut request_funds: Option<RequestFunds> = None;
 		decode_tlv_stream!(r, {
 			(0, shutdown_scriptpubkey, (option, encoding: (ScriptBuf, WithoutLength))),
 			(1, channel_type, option),
 			(2, require_confirmed_inputs, option),
+			(1339, request_funds, option),
 		});
 		Ok(OpenChannelV2 {
 			common_fields: CommonOpenChannelFields {
@@ -3264,6 +3292,7 @@ impl LengthReadable for OpenChannelV2 {
 			locktime,
 			second_per_commitment_point,
 			require_confirmed_inputs,
+			request_funds,
 		})
 	}
 }
@@ -3375,6 +3404,7 @@ impl_writeable_msg!(UpdateAddHTLC, {
 	// TODO: currently we may fail to read the `ChannelManager` if we write a new even TLV in this message
 	// and then downgrade. Once this is fixed, update the type here to match BOLTs PR 989.
 	(75537, hold_htlc, option),
+	(106823, endorsed, option),
 });
 
 impl LengthReadable for OnionMessage {
@@ -3525,17 +3555,24 @@ impl<'a> Writeable for OutboundTrampolinePayload<'a> {
 					(14, outgoing_node_id, required)
 				});
 			},
-			#[cfg(test)]
 			Self::Receive {
 				ref payment_data,
+				ref payment_metadata,
+				ref keysend_preimage,
 				sender_intended_htlc_amt_msat,
 				cltv_expiry_height,
+				ref custom_tlvs,
 			} => {
+				let keysend_tlv = keysend_preimage.map(|preimage| (5482373484, preimage.encode()));
+				let mut custom_tlvs: Vec<&(u64, Vec<u8>)> =
+					custom_tlvs.iter().chain(keysend_tlv.iter()).collect();
+				custom_tlvs.sort_unstable_by_key(|(typ, _)| *typ);
 				_encode_varint_length_prefixed_tlv!(w, {
 					(2, HighZeroBytesDroppedBigSize(*sender_intended_htlc_amt_msat), required),
 					(4, HighZeroBytesDroppedBigSize(*cltv_expiry_height), required),
-					(8, payment_data, option)
-				});
+					(8, payment_data, option),
+					(16, payment_metadata.map(|m| WithoutLength(m)), option)
+				}, custom_tlvs.iter());
 			},
 			Self::LegacyBlindedPathEntry {
 				amt_to_forward,
@@ -5062,6 +5099,7 @@ mod tests {
 			locktime: 305419896,
 			second_per_commitment_point: pubkey_7,
 			require_confirmed_inputs: if require_confirmed_inputs { Some(()) } else { None },
+			request_funds: None,
 		};
 		let encoded_value = open_channelv2.encode();
 		let mut target_value = Vec::new();
@@ -5874,6 +5912,7 @@ mod tests {
 			skimmed_fee_msat: None,
 			blinding_point: None,
 			hold_htlc: None,
+			endorsed: None,
 		};
 		let encoded_value = update_add_htlc.encode();
 		let target_value = <Vec<u8>>::from_hex("020202020202020202020202020202020202020202020202020202020202020200083a840000034d32144668701144760101010101010101010101010101010101010101010101010101010101010101000c89d4ff031b84c5567b126440995d3ed5aaba0565d71e1834604819ff9c17f5e9d5dd078f010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010202020202020202020202020202020202020202020202020202020202020202").unwrap();
@@ -60,6 +60,7 @@ use crate::offers::invoice_error::InvoiceError;
 use crate::offers::invoice_request::{InvoiceRequest, InvoiceRequestFields, InvoiceRequestVerifiedFromOffer};
 use crate::offers::nonce::Nonce;
 use crate::offers::parse::Bolt12SemanticError;
+use crate::offers::refund::{Refund, RefundDecision, RefundHandler};
 use crate::onion_message::messenger::{DefaultMessageRouter, Destination, MessageSendInstructions, NodeIdMessageRouter, NullMessageRouter, PeeledOnion, PADDED_PATH_LENGTH};
 use crate::onion_message::offers::OffersMessage;
 use crate::routing::gossip::{NodeAlias, NodeId};
@@ -1950,6 +1951,66 @@ fn fails_sending_invoice_with_unsupported_chain_for_refund() {
 	}
 }
 
+struct MaxAmountRefundHandler {
+	max_amount_msats: u64,
+	relative_expiry_secs: u32,
+}
+
+impl RefundHandler for MaxAmountRefundHandler {
+	fn handle_refund(&self, refund: &Refund) -> RefundDecision {
+		if refund.amount_msats() > self.max_amount_msats {
+			RefundDecision::Reject
+		} else {
+			RefundDecision::ApproveWithRelativeExpiry(self.relative_expiry_secs)
+		}
+	}
+}
+
+/// Checks that a `RefundHandler` can reject refunds or adjust the invoice sent in response.
+#[test]
+fn consults_refund_handler_before_sending_invoice_for_refund() {
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[None, None]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+
+	create_announced_chan_between_nodes_with_value(&nodes, 0, 1, 10_000_000, 1_000_000_000);
+
+	let alice = &nodes[0];
+	let bob = &nodes[1];
+	let bob_id = bob.node.get_our_node_id();
+
+	alice.node.set_refund_handler(MaxAmountRefundHandler {
+		max_amount_msats: 5_000_000, relative_expiry_secs: 600,
+	});
+
+	let absolute_expiry = Duration::from_secs(u64::MAX);
+	let payment_id = PaymentId([1; 32]);
+	let refund = bob.node
+		.create_refund_builder(10_000_000, absolute_expiry, payment_id, Retry::Attempts(0), RouteParametersConfig::default())
+		.unwrap()
+		.build().unwrap();
+
+	match alice.node.request_refund_payment(&refund) {
+		Ok(_) => panic!("Expected error"),
+		Err(e) => assert_eq!(e, Bolt12SemanticError::RejectedRefund),
+	}
+	assert!(alice.onion_messenger.next_onion_message_for_peer(bob_id).is_none());
+
+	let payment_id = PaymentId([2; 32]);
+	let refund = bob.node
+		.create_refund_builder(5_000_000, absolute_expiry, payment_id, Retry::Attempts(0), RouteParametersConfig::default())
+		.unwrap()
+		.build().unwrap();
+
+	let expected_invoice = alice.node.request_refund_payment(&refund).unwrap();
+	assert_eq!(expected_invoice.relative_expiry(), Duration::from_secs(600));
+
+	let onion_message = alice.onion_messenger.next_onion_message_for_peer(bob_id).unwrap();
+	let (invoice, _reply_path) = extract_invoice(bob, &onion_message);
+	assert_eq!(invoice, expected_invoice);
+}
+
 /// Fails creating an invoice request when a blinded reply path cannot be created.
 #[test]
 fn fails_creating_invoice_request_without_blinded_reply_path() {
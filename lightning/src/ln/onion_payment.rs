@@ -217,6 +217,7 @@ pub(super) fn create_fwd_pending_htlc_info(
 				short_channel_id,
 				incoming_cltv_expiry: Some(msg.cltv_expiry),
 				hold_htlc: msg.hold_htlc,
+				incoming_endorsed: msg.is_endorsed(),
 				blinded: intro_node_blinding_point.or(msg.blinding_point)
 					.map(|bp| BlindedForward {
 						inbound_blinding_point: bp,
@@ -814,6 +815,7 @@ mod tests {
 			skimmed_fee_msat: None,
 			blinding_point: None,
 			hold_htlc: None,
+			endorsed: None,
 		}
 	}
 
@@ -26,7 +26,7 @@ use crate::types::payment::{PaymentHash, PaymentPreimage};
 use crate::util::errors::APIError;
 use crate::util::logger::Logger;
 use crate::util::ser::{
-	LengthCalculatingWriter, Readable, ReadableArgs, VecWriter, Writeable, Writer,
+	BigSize, LengthCalculatingWriter, Readable, ReadableArgs, VecWriter, Writeable, Writer,
 };
 
 use bitcoin::hashes::cmp::fixed_time_eq;
@@ -275,11 +275,18 @@ impl<'a, 'b> OnionPayload<'a, 'b> for msgs::OutboundTrampolinePayload<'a> {
 		Self::Forward { outgoing_node_id, amt_to_forward, outgoing_cltv_value }
 	}
 	fn new_receive(
-		_recipient_onion: &'a RecipientOnionFields, _keysend_preimage: Option<PaymentPreimage>,
-		_sender_intended_htlc_amt_msat: u64, _total_msat: u64, _cltv_expiry_height: u32,
+		recipient_onion: &'a RecipientOnionFields, keysend_preimage: Option<PaymentPreimage>,
+		sender_intended_htlc_amt_msat: u64, total_msat: u64, cltv_expiry_height: u32,
 	) -> Result<Self::ReceiveType, APIError> {
-		Err(APIError::InvalidRoute {
-			err: "Unblinded receiving is not supported for Trampoline!".to_string(),
+		Ok(Self::Receive {
+			payment_data: recipient_onion
+				.payment_secret
+				.map(|payment_secret| msgs::FinalOnionHopData { payment_secret, total_msat }),
+			payment_metadata: recipient_onion.payment_metadata.as_ref(),
+			keysend_preimage,
+			custom_tlvs: &recipient_onion.custom_tlvs,
+			sender_intended_htlc_amt_msat,
+			cltv_expiry_height,
 		})
 	}
 	fn new_blinded_forward(
@@ -412,16 +419,21 @@ pub(super) fn build_trampoline_onion_payloads<'a>(
 ) -> Result<(Vec<msgs::OutboundTrampolinePayload<'a>>, u64, u32), APIError> {
 	let mut res: Vec<msgs::OutboundTrampolinePayload> =
 		Vec::with_capacity(blinded_tail.trampoline_hops.len() + blinded_tail.hops.len());
-	let blinded_tail_with_hop_iter = BlindedTailDetails::DirectEntry {
-		hops: blinded_tail.hops.iter(),
-		blinding_point: blinded_tail.blinding_point,
-		final_value_msat: blinded_tail.final_value_msat,
-		excess_final_cltv_expiry_delta: blinded_tail.excess_final_cltv_expiry_delta,
+	// If there are no blinded hops, the last Trampoline hop is an unblinded recipient.
+	let blinded_tail_with_hop_iter = if blinded_tail.hops.is_empty() {
+		None
+	} else {
+		Some(BlindedTailDetails::DirectEntry {
+			hops: blinded_tail.hops.iter(),
+			blinding_point: blinded_tail.blinding_point,
+			final_value_msat: blinded_tail.final_value_msat,
+			excess_final_cltv_expiry_delta: blinded_tail.excess_final_cltv_expiry_delta,
+		})
 	};
 
 	let (value_msat, cltv) = build_onion_payloads_callback(
 		blinded_tail.trampoline_hops.iter(),
-		Some(blinded_tail_with_hop_iter),
+		blinded_tail_with_hop_iter,
 		total_msat,
 		recipient_onion,
 		starting_htlc_offset,
@@ -615,11 +627,108 @@ where
 
 pub(crate) const MIN_FINAL_VALUE_ESTIMATE_WITH_OVERPAY: u64 = 100_000_000;
 
+/// Details on why a payment onion could not fit within the 1300-byte onion hop data.
+///
+/// This is returned when the [`RecipientOnionFields::payment_metadata`],
+/// [`RecipientOnionFields::custom_tlvs`], or blinded paths of a payment leave no room in the onion
+/// for even a single unblinded hop.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OnionPayloadSizeExceeded {
+	/// The number of bytes by which the hop payloads exceed the onion hop data.
+	pub excess_bytes: usize,
+	/// The estimated serialized length, including the HMAC, of each hop's payload, in path order.
+	pub hop_payload_lengths: Vec<usize>,
+	/// The types of [`RecipientOnionFields::custom_tlvs`] which, if removed, would free up at least
+	/// [`Self::excess_bytes`], largest record first.
+	///
+	/// Empty if removing all custom TLVs would not suffice.
+	pub custom_tlvs_to_trim: Vec<u64>,
+}
+
+impl OnionPayloadSizeExceeded {
+	fn new(hop_payload_lengths: Vec<usize>, custom_tlvs: &[(u64, Vec<u8>)]) -> Self {
+		let excess_bytes = hop_payload_lengths.iter().sum::<usize>().saturating_sub(ONION_DATA_LEN);
+
+		let mut records = custom_tlvs
+			.iter()
+			.map(|(typ, value)| {
+				let len = BigSize(*typ).serialized_length()
+					+ BigSize(value.len() as u64).serialized_length()
+					+ value.len();
+				(*typ, len)
+			})
+			.collect::<Vec<_>>();
+		records.sort_unstable_by(|a, b| b.1.cmp(&a.1));
+
+		let mut custom_tlvs_to_trim = Vec::new();
+		let mut trimmed_bytes = 0usize;
+		for (typ, len) in records {
+			if trimmed_bytes >= excess_bytes {
+				break;
+			}
+			trimmed_bytes = trimmed_bytes.saturating_add(len);
+			custom_tlvs_to_trim.push(typ);
+		}
+		if trimmed_bytes < excess_bytes {
+			custom_tlvs_to_trim.clear();
+		}
+
+		Self { excess_bytes, hop_payload_lengths, custom_tlvs_to_trim }
+	}
+}
+
+/// How the 1300-byte onion hop data would be used when paying over a candidate [`Path`], as
+/// returned by [`onion_payload_budget`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OnionPayloadBudget {
+	/// The serialized length, including the HMAC, of each hop's payload, in path order.
+	///
+	/// Blinded hops are included after the unblinded hops of the path.
+	pub hop_payload_lengths: Vec<usize>,
+}
+
+impl OnionPayloadBudget {
+	/// The number of onion hop data bytes left unused by the path, or `None` if the hop payloads
+	/// do not fit in the onion.
+	pub fn remaining_bytes(&self) -> Option<usize> {
+		ONION_DATA_LEN.checked_sub(self.hop_payload_lengths.iter().sum())
+	}
+}
+
+/// Computes how much of the onion hop data a payment over `path` would use, allowing callers to
+/// check how many bytes remain for [`RecipientOnionFields::custom_tlvs`] before sending.
+///
+/// `cur_block_height` should be set to the best known block height + 1.
+///
+/// Returns an error if the path's fees or CLTV deltas overflow, or if it contains trampoline hops.
+pub fn onion_payload_budget(
+	path: &Path, total_msat: u64, recipient_onion: &RecipientOnionFields, cur_block_height: u32,
+	keysend_preimage: Option<PaymentPreimage>,
+) -> Result<OnionPayloadBudget, APIError> {
+	if path.blinded_tail.as_ref().map_or(false, |tail| !tail.trampoline_hops.is_empty()) {
+		return Err(APIError::InvalidRoute {
+			err: "Trampoline paths are not supported".to_owned(),
+		});
+	}
+	let (payloads, _, _) = build_onion_payloads(
+		path,
+		total_msat,
+		recipient_onion,
+		cur_block_height,
+		&keysend_preimage,
+		None,
+		None,
+	)?;
+	let hop_payload_lengths =
+		payloads.iter().map(|payload| payload.serialized_length() + 32 /* HMAC */).collect();
+	Ok(OnionPayloadBudget { hop_payload_lengths })
+}
+
 pub(crate) fn set_max_path_length(
 	route_params: &mut RouteParameters, recipient_onion: &RecipientOnionFields,
 	keysend_preimage: Option<PaymentPreimage>, invoice_request: Option<&InvoiceRequest>,
 	best_block_height: u32,
-) -> Result<(), ()> {
+) -> Result<(), OnionPayloadSizeExceeded> {
 	const PAYLOAD_HMAC_LEN: usize = 32;
 	let unblinded_intermed_payload_len = msgs::OutboundOnionPayload::Forward {
 		short_channel_id: 42,
@@ -659,7 +768,7 @@ pub(crate) fn set_max_path_length(
 		cltv_expiry_delta,
 		maybe_announced_channel: false,
 	};
-	let mut num_reserved_bytes: usize = 0;
+	let mut hop_payload_lengths = Vec::new();
 	let build_payloads_res = build_onion_payloads_callback(
 		core::iter::once(&unblinded_route_hop),
 		blinded_tail_opt,
@@ -668,19 +777,25 @@ pub(crate) fn set_max_path_length(
 		best_block_height,
 		&keysend_preimage,
 		invoice_request,
-		|_, payload: msgs::OutboundOnionPayload| {
-			num_reserved_bytes = num_reserved_bytes
-				.saturating_add(payload.serialized_length())
-				.saturating_add(PAYLOAD_HMAC_LEN);
+		|action, payload: msgs::OutboundOnionPayload| {
+			let len = payload.serialized_length().saturating_add(PAYLOAD_HMAC_LEN);
+			match action {
+				PayloadCallbackAction::PushBack => hop_payload_lengths.push(len),
+				PayloadCallbackAction::PushFront => hop_payload_lengths.insert(0, len),
+			}
 		},
 	);
 	debug_assert!(build_payloads_res.is_ok());
+	let num_reserved_bytes =
+		hop_payload_lengths.iter().fold(0usize, |acc, l| acc.saturating_add(*l));
 
-	let max_path_length = 1300usize
+	let max_path_length = ONION_DATA_LEN
 		.checked_sub(num_reserved_bytes)
 		.map(|p| p / unblinded_intermed_payload_len)
 		.and_then(|l| u8::try_from(l.saturating_add(1)).ok())
-		.ok_or(())?;
+		.ok_or_else(|| {
+			OnionPayloadSizeExceeded::new(hop_payload_lengths, &recipient_onion.custom_tlvs)
+		})?;
 
 	route_params.payment_params.max_path_length =
 		core::cmp::min(max_path_length, route_params.payment_params.max_path_length);
@@ -1674,6 +1789,29 @@ pub enum LocalHTLCFailureReason {
 	TrampolineFeeOrExpiryInsufficient,
 	/// The specified next Trampoline node cannot be reached from our node.
 	UnknownNextTrampoline,
+	/// The HTLC was failed because accepting it would exceed the HTLC slot or in-flight value
+	/// limits the [`PeerReputation`] set via [`ChannelManager::set_peer_reputation`] imposed on
+	/// the peer which offered it to us.
+	///
+	/// [`PeerReputation`]: crate::ln::peer_misbehavior::PeerReputation
+	/// [`ChannelManager::set_peer_reputation`]: crate::ln::channelmanager::ChannelManager::set_peer_reputation
+	PeerReputationLimit,
+	/// The HTLC was failed because the [`ForwardingPolicy`] set via
+	/// [`ChannelManager::set_forwarding_policy`] rejected forwarding it, or deferred it until it was
+	/// too close to expiring to be forwarded.
+	///
+	/// [`ForwardingPolicy`]: crate::ln::forwarding_policy::ForwardingPolicy
+	/// [`ChannelManager::set_forwarding_policy`]: crate::ln::channelmanager::ChannelManager::set_forwarding_policy
+	ForwardingPolicyRejected,
+	/// The HTLC was failed because it was not endorsed and the outbound channel's HTLC slots or
+	/// liquidity not reserved for endorsed HTLCs were exhausted.
+	///
+	/// See [`ChannelConfig::endorsed_htlc_slots_percentage`] and
+	/// [`ChannelConfig::endorsed_liquidity_percentage`].
+	///
+	/// [`ChannelConfig::endorsed_htlc_slots_percentage`]: crate::util::config::ChannelConfig::endorsed_htlc_slots_percentage
+	/// [`ChannelConfig::endorsed_liquidity_percentage`]: crate::util::config::ChannelConfig::endorsed_liquidity_percentage
+	UnendorsedCapacityExhausted,
 }
 
 impl LocalHTLCFailureReason {
@@ -1694,7 +1832,10 @@ impl LocalHTLCFailureReason {
 			| Self::HTLCMinimum
 			| Self::HTLCMaximum
 			| Self::PeerOffline
-			| Self::ChannelBalanceOverdrawn => UPDATE | 7,
+			| Self::ChannelBalanceOverdrawn
+			| Self::PeerReputationLimit
+			| Self::ForwardingPolicyRejected
+			| Self::UnendorsedCapacityExhausted => UPDATE | 7,
 			Self::PermanentChannelFailure | Self::ChannelClosed | Self::OnChainTimeout => PERM | 8,
 			Self::RequiredChannelFeature => PERM | 9,
 			Self::UnknownNextPeer
@@ -1883,7 +2024,10 @@ ser_failure_reasons!(
 	(42, ChannelBalanceOverdrawn),
 	(43, TemporaryTrampolineFailure),
 	(44, TrampolineFeeOrExpiryInsufficient),
-	(45, UnknownNextTrampoline)
+	(45, UnknownNextTrampoline),
+	(46, PeerReputationLimit),
+	(47, ForwardingPolicyRejected),
+	(48, UnendorsedCapacityExhausted)
 );
 
 impl From<&HTLCFailReason> for HTLCHandlingFailureReason {
@@ -1909,6 +2053,12 @@ enum HTLCFailReasonRepr {
 }
 
 impl HTLCFailReason {
+	/// Returns whether this failure was relayed to us by a downstream node rather than generated
+	/// locally.
+	pub(super) fn is_relayed(&self) -> bool {
+		matches!(self.0, HTLCFailReasonRepr::LightningError { .. })
+	}
+
 	pub fn set_hold_time(&mut self, hold_time: u32) {
 		match self.0 {
 			HTLCFailReasonRepr::LightningError { hold_time: ref mut current_hold_time, .. } => {
@@ -2000,7 +2150,10 @@ impl HTLCFailReason {
 			| LocalHTLCFailureReason::HTLCMinimum
 			| LocalHTLCFailureReason::HTLCMaximum
 			| LocalHTLCFailureReason::PeerOffline
-			| LocalHTLCFailureReason::ChannelBalanceOverdrawn => {
+			| LocalHTLCFailureReason::ChannelBalanceOverdrawn
+			| LocalHTLCFailureReason::PeerReputationLimit
+			| LocalHTLCFailureReason::ForwardingPolicyRejected
+			| LocalHTLCFailureReason::UnendorsedCapacityExhausted => {
 				debug_assert_eq!(
 					data.len() - 2,
 					u16::from_be_bytes(data[0..2].try_into().unwrap()) as usize
@@ -2621,6 +2774,52 @@ pub(crate) fn create_payment_onion_internal<T: secp256k1::Signing>(
 	Ok((onion_packet, htlc_msat, htlc_cltv))
 }
 
+/// Builds the outer onion used to relay a Trampoline onion we received to the next Trampoline node
+/// over `path`, where the final hop of `path` is the next Trampoline node.
+///
+/// The next Trampoline node will receive `amt_to_forward` with an outer onion CLTV value of
+/// `outgoing_cltv_value`. Returns the onion as well as the first-hop amount and CLTV expiry.
+pub(super) fn create_trampoline_forward_onion<T: secp256k1::Signing>(
+	secp_ctx: &Secp256k1<T>, path: &Path, session_priv: &SecretKey, amt_to_forward: u64,
+	outgoing_cltv_value: u32, trampoline_packet: msgs::TrampolineOnionPacket,
+	payment_hash: &PaymentHash, prng_seed: [u8; 32],
+) -> Result<(msgs::OnionPacket, u64, u32), APIError> {
+	if path.blinded_tail.is_some() {
+		return Err(APIError::InvalidRoute {
+			err: "Cannot relay a Trampoline onion over a blinded path".to_owned(),
+		});
+	}
+
+	// We don't (yet) split Trampoline forwards, so the next Trampoline node is paid with a single
+	// part and no multipath data is included.
+	let recipient_onion = RecipientOnionFields::spontaneous_empty();
+	let trampoline_entry = BlindedTailDetails::<core::slice::Iter<BlindedHop>>::TrampolineEntry {
+		trampoline_packet,
+		final_value_msat: 0,
+	};
+	let mut onion_payloads = Vec::with_capacity(path.hops.len());
+	let (htlc_msat, htlc_cltv) = build_onion_payloads_callback(
+		path.hops.iter(),
+		Some(trampoline_entry),
+		amt_to_forward,
+		&recipient_onion,
+		outgoing_cltv_value,
+		&None,
+		None,
+		|action, payload| match action {
+			PayloadCallbackAction::PushBack => onion_payloads.push(payload),
+			PayloadCallbackAction::PushFront => onion_payloads.insert(0, payload),
+		},
+	)?;
+
+	let onion_keys = construct_onion_keys(&secp_ctx, &path, session_priv);
+	let onion_packet = construct_onion_packet(onion_payloads, onion_keys, prng_seed, payment_hash)
+		.map_err(|_| APIError::InvalidRoute {
+			err: "Route size too large considering onion data".to_owned(),
+		})?;
+	Ok((onion_packet, htlc_msat, htlc_cltv))
+}
+
 pub(crate) fn decode_next_untagged_hop<T, R: ReadableArgs<T>, N: NextPacketBytes>(
 	shared_secret: [u8; 32], hop_data: &[u8], hmac_bytes: [u8; 32], read_args: T,
 ) -> Result<(R, Option<([u8; 32], N)>), OnionDecodeErr> {
@@ -21,7 +21,7 @@ use crate::ln::channelmanager::{
 	EventCompletionAction, HTLCSource, PaymentCompleteUpdate, PaymentId,
 };
 use crate::ln::onion_utils;
-use crate::ln::onion_utils::{DecodedOnionFailure, HTLCFailReason};
+use crate::ln::onion_utils::{DecodedOnionFailure, HTLCFailReason, OnionPayloadSizeExceeded};
 use crate::offers::invoice::{Bolt12Invoice, DerivedSigningPubkey, InvoiceBuilder};
 use crate::offers::invoice_request::InvoiceRequest;
 use crate::offers::nonce::Nonce;
@@ -31,7 +31,7 @@ use crate::routing::router::{
 	RouteParametersConfig, Router,
 };
 use crate::sign::{EntropySource, NodeSigner, Recipient};
-use crate::types::features::Bolt12InvoiceFeatures;
+use crate::types::features::{Bolt12InvoiceFeatures, NodeFeatures};
 use crate::types::payment::{PaymentHash, PaymentPreimage, PaymentSecret};
 use crate::util::errors::APIError;
 use crate::util::logger::Logger;
@@ -248,6 +248,15 @@ impl PendingOutboundPayment {
 		}
 	}
 
+	fn pending_amt_msat_covers_total(&self) -> bool {
+		match self {
+			PendingOutboundPayment::Retryable { pending_amt_msat, total_msat, .. } => {
+				*pending_amt_msat >= *total_msat
+			},
+			_ => false,
+		}
+	}
+
 	fn total_msat(&self) -> Option<u64> {
 		match self {
 			PendingOutboundPayment::Retryable { total_msat, .. } => Some(*total_msat),
@@ -456,6 +465,21 @@ pub(super) fn has_expired(route_params: &RouteParameters) -> bool {
 	false
 }
 
+/// Determines the amount to pay for the given [`Bolt11Invoice`], given the amount the user chose to
+/// pay, if any.
+pub(super) fn bolt11_payment_amount(
+	invoice: &Bolt11Invoice, amount_msats: Option<u64>,
+) -> Result<u64, Bolt11PaymentError> {
+	match (invoice.amount_milli_satoshis(), amount_msats) {
+		(Some(amt), None) | (None, Some(amt)) => Ok(amt),
+		(Some(inv_amt), Some(user_amt)) if user_amt < inv_amt => {
+			Err(Bolt11PaymentError::InvalidAmount)
+		},
+		(Some(_), Some(user_amt)) => Ok(user_amt),
+		(None, None) => Err(Bolt11PaymentError::InvalidAmount),
+	}
+}
+
 /// Storing minimal payment attempts information required for determining if a outbound payment can
 /// be retried.
 pub(crate) struct PaymentAttempts {
@@ -541,8 +565,14 @@ pub enum RetryableSendFailure {
 	/// [`BlindedPaymentPath`]s provided are too large and caused us to exceed the maximum onion
 	/// packet size of 1300 bytes.
 	///
+	/// The contained [`OnionPayloadSizeExceeded`] describes by how much the limit was exceeded and
+	/// which custom TLVs could be removed to fit.
+	///
 	/// [`BlindedPaymentPath`]: crate::blinded_path::payment::BlindedPaymentPath
-	OnionPacketSizeExceeded,
+	OnionPacketSizeExceeded(OnionPayloadSizeExceeded),
+	/// The custom TLVs provided were rejected by [`RecipientOnionFields::with_custom_tlvs`], e.g.
+	/// because a type number was outside of the range reserved for custom types or was repeated.
+	InvalidCustomTlvs,
 }
 
 /// If a payment fails to send to a route, it can be in one of several states. This enum is returned
@@ -616,7 +646,7 @@ pub(crate) enum PaymentSendFailure {
 /// An error when attempting to pay a [`Bolt11Invoice`].
 ///
 /// [`Bolt11Invoice`]: lightning_invoice::Bolt11Invoice
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Bolt11PaymentError {
 	/// Incorrect amount was provided to [`ChannelManager::pay_for_bolt11_invoice`].
 	/// This happens when the user-provided amount is less than an amount specified in the [`Bolt11Invoice`].
@@ -624,10 +654,35 @@ pub enum Bolt11PaymentError {
 	/// [`Bolt11Invoice`]: lightning_invoice::Bolt11Invoice
 	/// [`ChannelManager::pay_for_bolt11_invoice`]: crate::ln::channelmanager::ChannelManager::pay_for_bolt11_invoice
 	InvalidAmount,
+	/// The invoice has expired and the payee doesn't support keysend, so we couldn't fall back to a
+	/// spontaneous payment in [`ChannelManager::pay_for_bolt11_invoice_with_keysend_fallback`].
+	///
+	/// [`ChannelManager::pay_for_bolt11_invoice_with_keysend_fallback`]: crate::ln::channelmanager::ChannelManager::pay_for_bolt11_invoice_with_keysend_fallback
+	InvoiceExpired,
+	/// The invoice requires features we don't support and the payee doesn't support keysend, so we
+	/// couldn't fall back to a spontaneous payment in
+	/// [`ChannelManager::pay_for_bolt11_invoice_with_keysend_fallback`].
+	///
+	/// [`ChannelManager::pay_for_bolt11_invoice_with_keysend_fallback`]: crate::ln::channelmanager::ChannelManager::pay_for_bolt11_invoice_with_keysend_fallback
+	UnknownRequiredFeatures,
+	/// The invoice's currency indicates it is meant to be paid on a different chain than the one
+	/// the [`ChannelManager`] operates on.
+	///
+	/// [`ChannelManager`]: crate::ln::channelmanager::ChannelManager
+	UnsupportedChain,
 	/// The invoice was valid for the corresponding [`PaymentId`], but sending the payment failed.
 	SendingFailed(RetryableSendFailure),
 }
 
+/// The custom TLV type used to carry the payment hash of a [`Bolt11Invoice`] in a keysend payment
+/// sent in its stead by [`ChannelManager::pay_for_bolt11_invoice_with_keysend_fallback`].
+///
+/// The type is odd, so recipients which don't understand it will simply ignore it.
+///
+/// [`Bolt11Invoice`]: lightning_invoice::Bolt11Invoice
+/// [`ChannelManager::pay_for_bolt11_invoice_with_keysend_fallback`]: crate::ln::channelmanager::ChannelManager::pay_for_bolt11_invoice_with_keysend_fallback
+pub const INVOICE_PAYMENT_HASH_CUSTOM_TLV_TYPE: u64 = 65_537;
+
 /// An error when attempting to pay a [`Bolt12Invoice`].
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Bolt12PaymentError {
@@ -935,12 +990,7 @@ where
 	{
 		let payment_hash = PaymentHash((*invoice.payment_hash()).to_byte_array());
 
-		let amount = match (invoice.amount_milli_satoshis(), amount_msats) {
-			(Some(amt), None) | (None, Some(amt)) => amt,
-			(Some(inv_amt), Some(user_amt)) if user_amt < inv_amt => return Err(Bolt11PaymentError::InvalidAmount),
-			(Some(_), Some(user_amt)) => user_amt,
-			(None, None) => return Err(Bolt11PaymentError::InvalidAmount),
-		};
+		let amount = bolt11_payment_amount(invoice, amount_msats)?;
 
 		let mut recipient_onion = RecipientOnionFields::secret_only(*invoice.payment_secret());
 		recipient_onion.payment_metadata = invoice.payment_metadata().map(|v| v.clone());
@@ -961,6 +1011,66 @@ where
 		).map_err(|err| Bolt11PaymentError::SendingFailed(err))
 	}
 
+	#[rustfmt::skip]
+	pub(super) fn pay_for_bolt11_invoice_with_keysend_fallback<R: Deref, ES: Deref, NS: Deref, IH, SP>(
+		&self, invoice: &Bolt11Invoice, payment_id: PaymentId,
+		amount_msats: Option<u64>,
+		route_params_config: RouteParametersConfig,
+		retry_strategy: Retry, payee_features: &NodeFeatures, duration_since_epoch: Duration,
+		router: &R,
+		first_hops: Vec<ChannelDetails>, compute_inflight_htlcs: IH, entropy_source: &ES,
+		node_signer: &NS, best_block_height: u32,
+		pending_events: &Mutex<VecDeque<(events::Event, Option<EventCompletionAction>)>>, send_payment_along_path: SP,
+	) -> Result<PaymentHash, Bolt11PaymentError>
+	where
+		R::Target: Router,
+		ES::Target: EntropySource,
+		NS::Target: NodeSigner,
+		IH: Fn() -> InFlightHtlcs,
+		SP: Fn(SendAlongPathArgs) -> Result<(), APIError>,
+	{
+		let invoice_expired = invoice.would_expire(duration_since_epoch);
+		let unknown_required_features =
+			invoice.features().map_or(false, |features| features.requires_unknown_bits());
+		if !invoice_expired && !unknown_required_features {
+			let payment_hash = PaymentHash((*invoice.payment_hash()).to_byte_array());
+			return self.pay_for_bolt11_invoice(invoice, payment_id, amount_msats, route_params_config,
+				retry_strategy, router, first_hops, compute_inflight_htlcs, entropy_source, node_signer,
+				best_block_height, pending_events, send_payment_along_path
+			).map(|()| payment_hash);
+		}
+
+		if !payee_features.supports_keysend() {
+			return Err(if invoice_expired {
+				Bolt11PaymentError::InvoiceExpired
+			} else {
+				Bolt11PaymentError::UnknownRequiredFeatures
+			});
+		}
+
+		let amount = bolt11_payment_amount(invoice, amount_msats)?;
+
+		let invoice_payment_hash = invoice.payment_hash().to_byte_array().to_vec();
+		let recipient_onion = RecipientOnionFields::spontaneous_empty()
+			.with_custom_tlvs(vec![(INVOICE_PAYMENT_HASH_CUSTOM_TLV_TYPE, invoice_payment_hash)])
+			.expect("INVOICE_PAYMENT_HASH_CUSTOM_TLV_TYPE is a valid custom TLV type");
+
+		let final_cltv_expiry_delta = invoice.min_final_cltv_expiry_delta().try_into().unwrap_or(u32::max_value());
+		let payment_params = PaymentParameters::for_keysend(invoice.get_payee_pub_key(), final_cltv_expiry_delta, false)
+			.with_user_config_ignoring_fee_limit(route_params_config);
+
+		let mut route_params = RouteParameters::from_payment_params_and_value(payment_params, amount);
+
+		if let Some(max_fee_msat) = route_params_config.max_total_routing_fee_msat {
+			route_params.max_total_routing_fee_msat = Some(max_fee_msat);
+		}
+
+		self.send_spontaneous_payment(None, recipient_onion, payment_id, retry_strategy, route_params,
+			router, first_hops, compute_inflight_htlcs, entropy_source, node_signer, best_block_height,
+			pending_events, send_payment_along_path
+		).map_err(|err| Bolt11PaymentError::SendingFailed(err))
+	}
+
 	#[rustfmt::skip]
 	pub(super) fn send_payment_for_bolt12_invoice<
 		R: Deref, ES: Deref, NS: Deref, NL: Deref, IH, SP
@@ -1060,7 +1170,8 @@ where
 					RetryableSendFailure::PaymentExpired => PaymentFailureReason::PaymentExpired,
 					RetryableSendFailure::RouteNotFound => PaymentFailureReason::RouteNotFound,
 					RetryableSendFailure::DuplicatePayment => PaymentFailureReason::UnexpectedError,
-					RetryableSendFailure::OnionPacketSizeExceeded => PaymentFailureReason::UnexpectedError,
+					RetryableSendFailure::OnionPacketSizeExceeded(_) => PaymentFailureReason::UnexpectedError,
+					RetryableSendFailure::InvalidCustomTlvs => PaymentFailureReason::UnexpectedError,
 				};
 				self.abandon_payment(payment_id, reason, pending_events);
 				return Err(Bolt12PaymentError::SendingFailed(e));
@@ -1189,7 +1300,7 @@ where
 					route_params.max_total_routing_fee_msat =
 						route_params_config.max_total_routing_fee_msat;
 
-					if let Err(()) = onion_utils::set_max_path_length(
+					if let Err(e) = onion_utils::set_max_path_length(
 						&mut route_params,
 						&RecipientOnionFields::spontaneous_empty(),
 						Some(keysend_preimage),
@@ -1198,7 +1309,7 @@ where
 					) {
 						abandon_with_entry!(entry, PaymentFailureReason::RouteNotFound);
 						return Err(Bolt12PaymentError::SendingFailed(
-							RetryableSendFailure::OnionPacketSizeExceeded,
+							RetryableSendFailure::OnionPacketSizeExceeded(e),
 						));
 					}
 					let absolute_expiry =
@@ -1437,16 +1548,42 @@ where
 		onion_utils::set_max_path_length(
 			route_params, recipient_onion, keysend_preimage, invoice_request, best_block_height
 		)
-			.map_err(|()| {
+			.map_err(|e| {
 				log_error!(self.logger, "Can't construct an onion packet without exceeding 1300-byte onion \
-					hop_data length for payment with id {} and hash {}", payment_id, payment_hash);
-				RetryableSendFailure::OnionPacketSizeExceeded
+					hop_data length for payment with id {} and hash {}, exceeded by {} bytes",
+					payment_id, payment_hash, e.excess_bytes);
+				RetryableSendFailure::OnionPacketSizeExceeded(e)
 			})?;
 
+		let payer = node_signer.get_node_id(Recipient::Node).unwrap();
+		let first_hops = first_hops.iter().collect::<Vec<_>>();
+
+		// With adaptive splitting, first try to send over fewer paths, leaving it to retries to
+		// re-split any part which fails. If that fails, fall back to splitting up-front.
+		let payment_params = &route_params.payment_params;
+		if let Some(initial_max_path_count) = payment_params.initial_max_path_count {
+			if initial_max_path_count < payment_params.max_path_count {
+				let mut initial_route_params = route_params.clone();
+				initial_route_params.payment_params.max_path_count = initial_max_path_count;
+				match router.find_route_with_id(
+					&payer, &initial_route_params, Some(&first_hops), inflight_htlcs(),
+					payment_hash, payment_id,
+				) {
+					Ok(mut route) => {
+						debug_assert_eq!(route.route_params.as_ref(), Some(&initial_route_params));
+						route.route_params = Some(route_params.clone());
+						return Ok(route);
+					},
+					Err(_) => log_debug!(self.logger,
+						"Failed to find route using at most {} paths for payment with id {} and hash {}, \
+						falling back to using up to {}", initial_max_path_count, payment_id, payment_hash,
+						route_params.payment_params.max_path_count),
+				}
+			}
+		}
+
 		let mut route = router.find_route_with_id(
-			&node_signer.get_node_id(Recipient::Node).unwrap(), route_params,
-			Some(&first_hops.iter().collect::<Vec<_>>()), inflight_htlcs(),
-			payment_hash, payment_id,
+			&payer, route_params, Some(&first_hops), inflight_htlcs(), payment_hash, payment_id,
 		).map_err(|_| {
 			log_error!(self.logger, "Failed to find route for payment with id {} and hash {}",
 				payment_id, payment_hash);
@@ -1463,6 +1600,61 @@ where
 		Ok(route)
 	}
 
+	/// Extends `route` by up to [`PaymentParameters::redundant_path_count`] redundant paths,
+	/// accounting for the liquidity used by its existing paths.
+	///
+	/// Returns the amount carried by the existing paths if any redundant paths were added, which is
+	/// the amount the recipient is meant to receive.
+	#[rustfmt::skip]
+	fn add_redundant_paths<R: Deref, NS: Deref, IH>(
+		&self, route: &mut Route, route_params: &RouteParameters, router: &R,
+		first_hops: &Vec<ChannelDetails>, inflight_htlcs: &IH, node_signer: &NS,
+		payment_hash: PaymentHash, payment_id: PaymentId,
+	) -> Option<u64>
+	where
+		R::Target: Router,
+		NS::Target: NodeSigner,
+		L::Target: Logger,
+		IH: Fn() -> InFlightHtlcs,
+	{
+		let redundant_path_count = route_params.payment_params.redundant_path_count;
+		if redundant_path_count == 0 || route.paths.is_empty()
+			|| !route_params.payment_params.payee.supports_basic_mpp()
+		{
+			return None;
+		}
+
+		let payer = node_signer.get_node_id(Recipient::Node).unwrap();
+		let recv_value_msat = route.get_total_amount();
+		let mut inflight_htlcs = inflight_htlcs();
+		for path in route.paths.iter() {
+			inflight_htlcs.process_path(path, payer);
+		}
+		let mut redundant_route_params = route_params.clone();
+		redundant_route_params.final_value_msat =
+			recv_value_msat / route.paths.len() as u64 * redundant_path_count as u64;
+		redundant_route_params.payment_params.max_path_count = redundant_path_count;
+		redundant_route_params.max_total_routing_fee_msat = route_params.max_total_routing_fee_msat
+			.map(|m| m.saturating_sub(route.get_total_fees()));
+
+		match router.find_route_with_id(
+			&payer, &redundant_route_params, Some(&first_hops.iter().collect::<Vec<_>>()),
+			inflight_htlcs, payment_hash, payment_id,
+		) {
+			Ok(redundant_route) => {
+				log_debug!(self.logger, "Adding {} redundant paths carrying {} msat to payment with id {} and hash {}",
+					redundant_route.paths.len(), redundant_route.get_total_amount(), payment_id, payment_hash);
+				route.paths.extend(redundant_route.paths);
+				Some(recv_value_msat)
+			},
+			Err(e) => {
+				log_debug!(self.logger, "Failed to find route for redundant paths of payment with id {} and hash {}: {}",
+					payment_id, payment_hash, e);
+				None
+			},
+		}
+	}
+
 	/// Errors immediately on [`RetryableSendFailure`] error conditions. Otherwise, further errors may
 	/// be surfaced asynchronously via [`Event::PaymentPathFailed`] and [`Event::PaymentFailed`].
 	///
@@ -1484,10 +1676,16 @@ where
 		IH: Fn() -> InFlightHtlcs,
 		SP: Fn(SendAlongPathArgs) -> Result<(), APIError>,
 	{
-		let route = self.find_initial_route(
+		let mut route = self.find_initial_route(
 			payment_id, payment_hash, &recipient_onion, keysend_preimage, None, &mut route_params, router,
 			&first_hops, &inflight_htlcs, node_signer, best_block_height,
 		)?;
+		let recv_value_msat = if recipient_onion.payment_secret.is_some() {
+			self.add_redundant_paths(
+				&mut route, &route_params, router, &first_hops, &inflight_htlcs, node_signer,
+				payment_hash, payment_id,
+			)
+		} else { None };
 
 		let onion_session_privs = self.add_new_pending_payment(payment_hash,
 			recipient_onion.clone(), payment_id, keysend_preimage, &route, Some(retry_strategy),
@@ -1497,10 +1695,17 @@ where
 					payment_id, payment_hash);
 				RetryableSendFailure::DuplicatePayment
 			})?;
+		if let Some(recv_value_msat) = recv_value_msat {
+			// Redundant paths don't count towards the amount the recipient is meant to receive.
+			let mut outbounds = self.pending_outbound_payments.lock().unwrap();
+			if let Some(PendingOutboundPayment::Retryable { total_msat, .. }) = outbounds.get_mut(&payment_id) {
+				*total_msat = recv_value_msat;
+			}
+		}
 
 		let res = self.pay_route_internal(&route, payment_hash, &recipient_onion,
-			keysend_preimage, None, None, payment_id, None, &onion_session_privs, false, node_signer,
-			best_block_height, &send_payment_along_path);
+			keysend_preimage, None, None, payment_id, recv_value_msat, &onion_session_privs, false,
+			node_signer, best_block_height, &send_payment_along_path);
 		log_info!(self.logger, "Sending payment with id {} and hash {} returned {:?}",
 			payment_id, payment_hash, res);
 		if let Err(e) = res {
@@ -1544,7 +1749,12 @@ where
 			Ok(route) => route,
 			Err(e) => {
 				log_error!(self.logger, "Failed to find a route on retry, abandoning payment {}: {:#?}", &payment_id, e);
-				self.abandon_payment(payment_id, PaymentFailureReason::RouteNotFound, pending_events);
+				let reason = if route_params.max_total_routing_fee_msat == Some(0) {
+					PaymentFailureReason::BudgetExhausted
+				} else {
+					PaymentFailureReason::RouteNotFound
+				};
+				self.abandon_payment(payment_id, reason, pending_events);
 				return
 			}
 		};
@@ -1585,7 +1795,8 @@ where
 					match payment.get() {
 						PendingOutboundPayment::Retryable {
 							total_msat, keysend_preimage, payment_secret, payment_metadata,
-							custom_tlvs, pending_amt_msat, invoice_request, ..
+							custom_tlvs, pending_amt_msat, invoice_request, payment_params,
+							starting_block_height, remaining_max_total_routing_fee_msat, ..
 						} => {
 							const RETRY_OVERFLOW_PERCENTAGE: u64 = 10;
 							let retry_amt_msat = route.get_total_amount();
@@ -1595,6 +1806,32 @@ where
 								return
 							}
 
+							// The router is expected to respect the remaining budget, but we double-check
+							// here as parts which already went out (and may yet succeed) have consumed
+							// some of it.
+							let retry_fee_msat = route.paths.iter().map(|path| path.fee_msat()).sum::<u64>();
+							if let Some(remaining_fee_msat) = remaining_max_total_routing_fee_msat {
+								if retry_fee_msat > *remaining_fee_msat {
+									log_error!(self.logger, "Retrying payment {} would pay {} msat in fees, more than its remaining fee budget of {} msat", &payment_id, retry_fee_msat, remaining_fee_msat);
+									abandon_with_entry!(payment, PaymentFailureReason::BudgetExhausted);
+									return
+								}
+							}
+
+							// Blocks which elapsed since the payment was first sent count against the CLTV
+							// budget, as our funds are locked up for at least that long already.
+							let max_total_cltv_expiry_delta = payment_params.as_ref()
+								.unwrap_or(&route_params.payment_params).max_total_cltv_expiry_delta;
+							let elapsed_blocks = best_block_height.saturating_sub(*starting_block_height);
+							let retry_cltv_expiry_delta = route.paths.iter()
+								.map(|path| path.hops.iter().map(|hop| hop.cltv_expiry_delta).sum::<u32>())
+								.max().unwrap_or(0);
+							if elapsed_blocks.saturating_add(retry_cltv_expiry_delta) > max_total_cltv_expiry_delta {
+								log_error!(self.logger, "Retrying payment {} would lock funds for {} blocks after {} blocks already elapsed, more than its CLTV budget of {} blocks", &payment_id, retry_cltv_expiry_delta, elapsed_blocks, max_total_cltv_expiry_delta);
+								abandon_with_entry!(payment, PaymentFailureReason::BudgetExhausted);
+								return
+							}
+
 							if !payment.get().is_retryable_now() {
 								log_error!(self.logger, "Retries exhausted for payment id {}", &payment_id);
 								abandon_with_entry!(payment, PaymentFailureReason::RetriesExhausted);
@@ -1711,6 +1948,11 @@ where
 					});
 				self.remove_session_privs(payment_id, failed_paths);
 				Self::push_path_failed_evs_and_scids(payment_id, payment_hash, &mut retry, route.paths, results.into_iter(), &self.logger, pending_events);
+				if retry.final_value_msat == 0 {
+					// The paths which were sent already carry the full amount, e.g., as some of
+					// the failed paths were redundant, so there's nothing left to retry.
+					return;
+				}
 				// Some paths were sent, even if we failed to send the full MPP value our recipient may
 				// misbehave and claim the funds, at which point we have to consider the payment sent, so
 				// return `Ok()` here, ignoring any retry errors.
@@ -2459,7 +2701,13 @@ where
 					}
 				}
 
-				if payment_is_probe || !is_retryable_now || payment_failed_permanently {
+				// Don't give up on the payment as long as its remaining parts, e.g., including
+				// redundant ones, may still complete it.
+				let pending_parts_suffice = payment.get().pending_amt_msat_covers_total();
+				if payment_is_probe
+					|| payment_failed_permanently
+					|| (!is_retryable_now && !pending_parts_suffice)
+				{
 					let reason = if payment_failed_permanently {
 						PaymentFailureReason::RecipientRejected
 					} else {
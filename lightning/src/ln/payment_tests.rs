@@ -38,7 +38,7 @@ use crate::ln::types::ChannelId;
 use crate::routing::gossip::{EffectiveCapacity, RoutingFees};
 use crate::routing::router::{
 	get_route, Path, PaymentParameters, Route, RouteHint, RouteHintHop, RouteHop, RouteParameters,
-	Router,
+	Router, DEFAULT_MAX_TOTAL_CLTV_EXPIRY_DELTA,
 };
 use crate::routing::scoring::ChannelUsage;
 use crate::sign::EntropySource;
@@ -201,6 +201,87 @@ fn mpp_retry() {
 	claim_payment_along_route(ClaimAlongRouteArgs::new(&nodes[0], claim_paths, preimage));
 }
 
+#[test]
+fn adaptive_mpp_retry() {
+	// Test that with adaptive splitting, the initial attempt of a payment is limited to the
+	// configured number of paths, and that only the failed part is re-routed on retry, avoiding
+	// the failed channel and allowing the full number of paths.
+	let chanmon_cfgs = create_chanmon_cfgs(4);
+	let node_cfgs = create_node_cfgs(4, &chanmon_cfgs);
+	let node_chanmgrs = create_node_chanmgrs(4, &node_cfgs, &[None, None, None, None]);
+	let nodes = create_network(4, &node_cfgs, &node_chanmgrs);
+
+	let node_a_id = nodes[0].node.get_our_node_id();
+	let node_b_id = nodes[1].node.get_our_node_id();
+	let node_c_id = nodes[2].node.get_our_node_id();
+	let node_d_id = nodes[3].node.get_our_node_id();
+
+	let (chan_1_update, _, _, _) = create_announced_chan_between_nodes(&nodes, 0, 1);
+	let (chan_2_update, _, _, _) = create_announced_chan_between_nodes(&nodes, 0, 2);
+	let (chan_3_update, _, _, _) = create_announced_chan_between_nodes(&nodes, 1, 3);
+	// As node 3 funds the channel, node 2 can't forward any payment over it.
+	let (chan_4_update, _, chan_4_id, _) = create_announced_chan_between_nodes(&nodes, 3, 2);
+
+	let amt_msat = 1_000_000;
+	let payment_params = PaymentParameters::from_node_id(node_d_id, TEST_FINAL_CLTV)
+		.with_bolt11_features(nodes[3].node.bolt11_invoice_features())
+		.unwrap()
+		.with_initial_max_path_count(1);
+	let (mut route, hash, preimage, pay_secret) =
+		get_route_and_payment_hash!(nodes[0], nodes[3], payment_params, amt_msat);
+	route.paths[0].hops[0].pubkey = node_c_id;
+	route.paths[0].hops[0].short_channel_id = chan_2_update.contents.short_channel_id;
+	route.paths[0].hops[1].short_channel_id = chan_4_update.contents.short_channel_id;
+
+	// The initial attempt is limited to a single path.
+	let mut route_params = route.route_params.clone().unwrap();
+	let mut initial_route_params = route_params.clone();
+	initial_route_params.payment_params.max_path_count = 1;
+	route.route_params = Some(initial_route_params.clone());
+	nodes[0].router.expect_find_route(initial_route_params, Ok(route.clone()));
+
+	let id = PaymentId(hash.0);
+	let onion = RecipientOnionFields::secret_only(pay_secret);
+	nodes[0].node.send_payment(hash, onion, id, route_params.clone(), Retry::Attempts(1)).unwrap();
+	check_added_monitors(&nodes[0], 1);
+	let mut events = nodes[0].node.get_and_clear_pending_msg_events();
+	assert_eq!(events.len(), 1);
+
+	// Fail the path at node 2.
+	let send_event = SendEvent::from_event(events.pop().unwrap());
+	nodes[2].node.handle_update_add_htlc(node_a_id, &send_event.msgs[0]);
+	do_commitment_signed_dance(&nodes[2], &nodes[0], &send_event.commitment_msg, false, false);
+	expect_and_process_pending_htlcs(&nodes[2], true);
+	let events = nodes[2].node.get_and_clear_pending_events();
+	let fail = HTLCHandlingFailureType::Forward { node_id: Some(node_d_id), channel_id: chan_4_id };
+	expect_htlc_failure_conditions(events, &[fail]);
+	let htlc_updates = get_htlc_update_msgs(&nodes[2], &node_a_id);
+	assert_eq!(htlc_updates.update_fail_htlcs.len(), 1);
+	check_added_monitors(&nodes[2], 1);
+	nodes[0].node.handle_update_fail_htlc(node_c_id, &htlc_updates.update_fail_htlcs[0]);
+	do_commitment_signed_dance(&nodes[0], &nodes[2], &htlc_updates.commitment_signed, false, false);
+	let events = nodes[0].node.get_and_clear_pending_events();
+	let conditions = PaymentFailedConditions::new().retry_expected();
+	expect_payment_failed_conditions_event(events, hash, false, conditions);
+
+	// The retry avoids the failed channel and may use the full number of paths.
+	let chan_4_scid = chan_4_update.contents.short_channel_id;
+	route_params.payment_params.previously_failed_channels.push(chan_4_scid);
+	route.paths[0].hops[0].pubkey = node_b_id;
+	route.paths[0].hops[0].short_channel_id = chan_1_update.contents.short_channel_id;
+	route.paths[0].hops[1].short_channel_id = chan_3_update.contents.short_channel_id;
+	route.route_params = Some(route_params.clone());
+	nodes[0].router.expect_find_route(route_params, Ok(route));
+	expect_and_process_pending_htlcs(&nodes[0], false);
+	check_added_monitors(&nodes[0], 1);
+	let mut events = nodes[0].node.get_and_clear_pending_msg_events();
+	assert_eq!(events.len(), 1);
+	let path = &[&nodes[1], &nodes[3]];
+	let event = events.pop().unwrap();
+	pass_along_path(&nodes[0], path, amt_msat, hash, Some(pay_secret), event, true, None);
+	claim_payment_along_route(ClaimAlongRouteArgs::new(&nodes[0], &[path], preimage));
+}
+
 #[test]
 fn mpp_retry_overpay() {
 	// We create an MPP scenario with two paths in which we need to overpay to reach
@@ -428,6 +509,98 @@ fn mpp_receive_timeout() {
 	do_mpp_receive_timeout(false);
 }
 
+#[test]
+fn mpp_excess_parts_failed_back() {
+	// Test that with `fail_back_excess_mpp_htlcs` set, parts received in excess of the total are
+	// failed back with an `mpp_timeout` error while the payment remains claimable.
+	let chanmon_cfgs = create_chanmon_cfgs(5);
+	let node_cfgs = create_node_cfgs(5, &chanmon_cfgs);
+	let mut config = test_default_channel_config();
+	config.fail_back_excess_mpp_htlcs = true;
+	let configs = [None, None, None, None, Some(config)];
+	let node_chanmgrs = create_node_chanmgrs(5, &node_cfgs, &configs);
+	let nodes = create_network(5, &node_cfgs, &node_chanmgrs);
+
+	let node_a_id = nodes[0].node.get_our_node_id();
+	let node_d_id = nodes[3].node.get_our_node_id();
+	let node_e_id = nodes[4].node.get_our_node_id();
+
+	let mut scids = Vec::new();
+	let mut last_chan_id = None;
+	for routing_node in 1..4 {
+		let src_chan = create_announced_chan_between_nodes(&nodes, 0, routing_node);
+		let dst_chan = create_announced_chan_between_nodes(&nodes, routing_node, 4);
+		scids.push((src_chan.0.contents.short_channel_id, dst_chan.0.contents.short_channel_id));
+		last_chan_id = Some(dst_chan.2);
+	}
+	let chan_id = last_chan_id.unwrap();
+
+	let (mut route, hash, preimage, payment_secret) =
+		get_route_and_payment_hash!(nodes[0], nodes[4], 100_000);
+	let sample_path = route.paths.pop().unwrap();
+	for (idx, (src_scid, dst_scid)) in scids.iter().enumerate() {
+		let mut path = sample_path.clone();
+		path.hops[0].pubkey = nodes[idx + 1].node.get_our_node_id();
+		path.hops[0].short_channel_id = *src_scid;
+		path.hops[1].short_channel_id = *dst_scid;
+		route.paths.push(path);
+	}
+
+	// Send three parts of 100_000 msat each for a payment of 200_000 msat.
+	let id = PaymentId(hash.0);
+	let onion = RecipientOnionFields::secret_only(payment_secret);
+	let onion_session_privs =
+		nodes[0].node.test_add_new_pending_payment(hash, onion, id, &route).unwrap();
+	let onion = RecipientOnionFields::secret_only(payment_secret);
+	let amt = Some(200_000);
+	nodes[0]
+		.node
+		.test_send_payment_internal(&route, hash, onion, None, id, amt, onion_session_privs)
+		.unwrap();
+	check_added_monitors(&nodes[0], 3);
+	let mut events = nodes[0].node.get_and_clear_pending_msg_events();
+	assert_eq!(events.len(), 3);
+
+	let paths: &[&[_]] =
+		&[&[&nodes[1], &nodes[4]], &[&nodes[2], &nodes[4]], &[&nodes[3], &nodes[4]]];
+	let payment_secret = Some(payment_secret);
+	let ev = remove_first_msg_event_to_node(&paths[0][0].node.get_our_node_id(), &mut events);
+	pass_along_path(&nodes[0], paths[0], 200_000, hash, payment_secret, ev, false, None);
+	let ev = remove_first_msg_event_to_node(&paths[1][0].node.get_our_node_id(), &mut events);
+	pass_along_path(&nodes[0], paths[1], 200_000, hash, payment_secret, ev, true, None);
+
+	// The third part isn't needed anymore, so it's failed back rather than rejected.
+	let ev = remove_first_msg_event_to_node(&node_d_id, &mut events);
+	let fail = HTLCHandlingFailureType::Receive { payment_hash: hash };
+	let args = PassAlongPathArgs::new(&nodes[0], paths[2], 200_000, hash, ev)
+		.with_payment_secret(payment_secret.unwrap())
+		.expect_failure(fail);
+	do_pass_along_path(args);
+
+	let htlc_fail_updates = get_htlc_update_msgs(&nodes[4], &node_d_id);
+	assert_eq!(htlc_fail_updates.update_fail_htlcs.len(), 1);
+	nodes[3].node.handle_update_fail_htlc(node_e_id, &htlc_fail_updates.update_fail_htlcs[0]);
+	let commitment = &htlc_fail_updates.commitment_signed;
+	do_commitment_signed_dance(&nodes[3], &nodes[4], commitment, false, false);
+
+	let fail_type =
+		HTLCHandlingFailureType::Forward { node_id: Some(node_e_id), channel_id: chan_id };
+	expect_and_process_pending_htlcs_and_htlc_handling_failed(&nodes[3], &[fail_type]);
+	let htlc_fail_updates = get_htlc_update_msgs(&nodes[3], &node_a_id);
+	assert_eq!(htlc_fail_updates.update_fail_htlcs.len(), 1);
+	nodes[0].node.handle_update_fail_htlc(node_d_id, &htlc_fail_updates.update_fail_htlcs[0]);
+	check_added_monitors(&nodes[3], 1);
+	let commitment = &htlc_fail_updates.commitment_signed;
+	do_commitment_signed_dance(&nodes[0], &nodes[3], commitment, false, false);
+
+	let conditions = PaymentFailedConditions::new()
+		.mpp_parts_remain()
+		.expected_htlc_error_data(LocalHTLCFailureReason::MPPTimeout, &[][..]);
+	expect_payment_failed_conditions(&nodes[0], hash, false, conditions);
+
+	claim_payment_along_route(ClaimAlongRouteArgs::new(&nodes[0], &paths[..2], preimage));
+}
+
 #[test]
 fn test_keysend_payments() {
 	do_test_keysend_payments(false);
@@ -2002,8 +2175,10 @@ fn abandoned_send_payment_idempotent() {
 #[derive(PartialEq)]
 enum InterceptTest {
 	Forward,
+	ModifiedForward,
 	Fail,
 	Timeout,
+	HoldTimeout,
 }
 
 #[test]
@@ -2201,6 +2376,10 @@ fn intercepted_payment() {
 	do_test_intercepted_payment(InterceptTest::Fail);
 	// Make sure that intercepted payments will be automatically failed back if too many blocks pass.
 	do_test_intercepted_payment(InterceptTest::Timeout);
+	// Make sure that the LSP may modify the outgoing CLTV expiry within bounds and hold the HTLC
+	// for a limited time.
+	do_test_intercepted_payment(InterceptTest::ModifiedForward);
+	do_test_intercepted_payment(InterceptTest::HoldTimeout);
 }
 
 fn do_test_intercepted_payment(test: InterceptTest) {
@@ -2228,12 +2407,14 @@ fn do_test_intercepted_payment(test: InterceptTest) {
 
 	let amt_msat = 100_000;
 	let intercept_scid = nodes[1].node.get_intercept_scid();
+	// Leave some room to increase the outgoing CLTV expiry when modifying the forward.
+	let extra_cltv_delta = if test == InterceptTest::ModifiedForward { 10 } else { 0 };
 	let payment_params = PaymentParameters::from_node_id(node_c_id, TEST_FINAL_CLTV)
 		.with_route_hints(vec![RouteHint(vec![RouteHintHop {
 			src_node_id: node_b_id,
 			short_channel_id: intercept_scid,
 			fees: RoutingFees { base_msat: 1000, proportional_millionths: 0 },
-			cltv_expiry_delta: MIN_CLTV_EXPIRY_DELTA,
+			cltv_expiry_delta: MIN_CLTV_EXPIRY_DELTA + extra_cltv_delta,
 			htlc_minimum_msat: None,
 			htlc_maximum_msat: None,
 		}])])
@@ -2275,18 +2456,25 @@ fn do_test_intercepted_payment(test: InterceptTest) {
 	// Check that we generate the PaymentIntercepted event when an intercept forward is detected.
 	let events = nodes[1].node.get_and_clear_pending_events();
 	assert_eq!(events.len(), 1);
-	let (intercept_id, outbound_amt) = match events[0] {
+	let (intercept_id, outbound_amt, inbound_amt, outbound_cltv) = match events[0] {
 		crate::events::Event::HTLCIntercepted {
 			intercept_id,
 			expected_outbound_amount_msat,
 			payment_hash,
 			inbound_amount_msat,
 			requested_next_hop_scid: short_channel_id,
+			inbound_cltv_expiry,
+			expected_outbound_cltv_expiry,
+			htlc_correlation_id,
 		} => {
 			assert_eq!(payment_hash, hash);
+			assert!(htlc_correlation_id.is_some());
 			assert_eq!(inbound_amount_msat, route.get_total_amount() + route.get_total_fees());
 			assert_eq!(short_channel_id, intercept_scid);
-			(intercept_id, expected_outbound_amount_msat)
+			let outbound_cltv = expected_outbound_cltv_expiry.unwrap();
+			let cltv_delta = (MIN_CLTV_EXPIRY_DELTA + extra_cltv_delta) as u32;
+			assert_eq!(inbound_cltv_expiry, Some(outbound_cltv + cltv_delta));
+			(intercept_id, expected_outbound_amount_msat, inbound_amount_msat, outbound_cltv)
 		},
 		_ => panic!(),
 	};
@@ -2322,7 +2510,7 @@ fn do_test_intercepted_payment(test: InterceptTest) {
 			.blamed_chan_closed(true)
 			.expected_htlc_error_data(LocalHTLCFailureReason::UnknownNextPeer, &[]);
 		expect_payment_failed_conditions(&nodes[0], hash, false, fail_conditions);
-	} else if test == InterceptTest::Forward {
+	} else if test == InterceptTest::Forward || test == InterceptTest::ModifiedForward {
 		// Check that we'll fail as expected when sending to a channel that isn't in `ChannelReady` yet.
 		let temp_id = nodes[1].node.create_channel(node_c_id, 100_000, 0, 42, None, None).unwrap();
 		let unusable_chan_err =
@@ -2338,10 +2526,51 @@ fn do_test_intercepted_payment(test: InterceptTest) {
 		let (_, chan_id) = open_zero_conf_channel(&nodes[1], &nodes[2], None);
 
 		// Finally, forward the intercepted payment through and claim it.
-		nodes[1]
-			.node
-			.forward_intercepted_htlc(intercept_id, &chan_id, node_c_id, outbound_amt)
-			.unwrap();
+		let expected_cltv = if test == InterceptTest::ModifiedForward {
+			let forward = |amt, cltv| {
+				nodes[1].node.forward_modified_intercepted_htlc(
+					intercept_id,
+					&chan_id,
+					node_c_id,
+					amt,
+					cltv,
+				)
+			};
+			// Check that modifications outside of the allowed bounds are rejected.
+			let err = format!(
+				"Cannot forward {} msat as only {} msat were received",
+				inbound_amt + 1,
+				inbound_amt
+			);
+			let res = forward(inbound_amt + 1, outbound_cltv);
+			assert_eq!(res, Err(APIError::APIMisuseError { err }));
+
+			let err = format!(
+				"Outgoing CLTV expiry {} is below the expected {}",
+				outbound_cltv - 1,
+				outbound_cltv
+			);
+			let res = forward(outbound_amt, outbound_cltv - 1);
+			assert_eq!(res, Err(APIError::APIMisuseError { err }));
+
+			let max_cltv = outbound_cltv + extra_cltv_delta as u32;
+			let err = format!(
+				"Outgoing CLTV expiry {} exceeds the maximum of {}",
+				max_cltv + 1,
+				max_cltv
+			);
+			let res = forward(outbound_amt, max_cltv + 1);
+			assert_eq!(res, Err(APIError::APIMisuseError { err }));
+
+			forward(outbound_amt, max_cltv).unwrap();
+			max_cltv
+		} else {
+			nodes[1]
+				.node
+				.forward_intercepted_htlc(intercept_id, &chan_id, node_c_id, outbound_amt)
+				.unwrap();
+			outbound_cltv
+		};
 		expect_and_process_pending_htlcs(&nodes[1], false);
 
 		let payment_event = {
@@ -2354,6 +2583,7 @@ fn do_test_intercepted_payment(test: InterceptTest) {
 			assert_eq!(events.len(), 1);
 			SendEvent::from_event(events.remove(0))
 		};
+		assert_eq!(payment_event.msgs[0].cltv_expiry, expected_cltv);
 		nodes[2].node.handle_update_add_htlc(node_b_id, &payment_event.msgs[0]);
 		let commitment = &payment_event.commitment_msg;
 		do_commitment_signed_dance(&nodes[2], &nodes[1], commitment, false, true);
@@ -2419,9 +2649,201 @@ fn do_test_intercepted_payment(test: InterceptTest) {
 			nodes[1].node.fail_intercepted_htlc(intercept_id).unwrap_err();
 		let err = format!("Payment with intercept id {} not found", log_bytes!(intercept_id.0));
 		assert_eq!(unknown_intercept_id_err, APIError::APIMisuseError { err });
+	} else if test == InterceptTest::HoldTimeout {
+		// We can't hold the HTLC for longer than we'd hold it anyway.
+		let height = nodes[1].best_block_info().1;
+		let expiry_height = outbound_cltv - HTLC_FAIL_BACK_BUFFER;
+		let hold_blocks = expiry_height - height + 1;
+		let err = format!(
+			"Cannot hold HTLC until height {} as it has to be failed back by height {}",
+			height + hold_blocks,
+			expiry_height
+		);
+		let res = nodes[1].node.hold_intercepted_htlc(intercept_id, hold_blocks);
+		assert_eq!(res, Err(APIError::APIMisuseError { err }));
+
+		// Once the hold expires, the HTLC is failed back.
+		nodes[1].node.hold_intercepted_htlc(intercept_id, 5).unwrap();
+		connect_blocks(&nodes[1], 4);
+		assert!(nodes[1].node.get_and_clear_pending_events().is_empty());
+		connect_blocks(&nodes[1], 1);
+		let fail_type =
+			HTLCHandlingFailureType::InvalidForward { requested_forward_scid: intercept_scid };
+		expect_and_process_pending_htlcs_and_htlc_handling_failed(&nodes[1], &[fail_type]);
+		check_added_monitors(&nodes[1], 1);
+
+		let update_fail = get_htlc_update_msgs(&nodes[1], &node_a_id);
+		assert_eq!(update_fail.update_fail_htlcs.len(), 1);
+		nodes[0].node.handle_update_fail_htlc(node_b_id, &update_fail.update_fail_htlcs[0]);
+		let commitment = &update_fail.commitment_signed;
+		do_commitment_signed_dance(&nodes[0], &nodes[1], commitment, false, false);
+
+		let fail_conditions = PaymentFailedConditions::new()
+			.blamed_scid(intercept_scid)
+			.blamed_chan_closed(true)
+			.expected_htlc_error_data(LocalHTLCFailureReason::UnknownNextPeer, &[]);
+		expect_payment_failed_conditions(&nodes[0], hash, false, fail_conditions);
 	}
 }
 
+#[test]
+fn intercepted_htlc_correlation_ids() {
+	// Test that the HTLC correlation id handed out in `HTLCIntercepted` is the same one provided in
+	// the `PaymentForwarded` or `HTLCHandlingFailed` event resolving that HTLC, and that distinct
+	// HTLCs are assigned distinct ids.
+	let chanmon_cfgs = create_chanmon_cfgs(3);
+	let node_cfgs = create_node_cfgs(3, &chanmon_cfgs);
+
+	let mut zero_conf_chan_config = test_default_channel_config();
+	zero_conf_chan_config.manually_accept_inbound_channels = true;
+	let mut intercept_forwards_config = test_default_channel_config();
+	intercept_forwards_config.accept_intercept_htlcs = true;
+
+	let configs = [None, Some(intercept_forwards_config), Some(zero_conf_chan_config)];
+	let node_chanmgrs = create_node_chanmgrs(3, &node_cfgs, &configs);
+	let nodes = create_network(3, &node_cfgs, &node_chanmgrs);
+
+	let node_a_id = nodes[0].node.get_our_node_id();
+	let node_b_id = nodes[1].node.get_our_node_id();
+	let node_c_id = nodes[2].node.get_our_node_id();
+
+	let scorer = test_utils::TestScorer::new();
+	let random_seed_bytes = chanmon_cfgs[0].keys_manager.get_secure_random_bytes();
+
+	create_announced_chan_between_nodes(&nodes, 0, 1);
+
+	let amt_msat = 100_000;
+	let intercept_scid = nodes[1].node.get_intercept_scid();
+	let payment_params = PaymentParameters::from_node_id(node_c_id, TEST_FINAL_CLTV)
+		.with_route_hints(vec![RouteHint(vec![RouteHintHop {
+			src_node_id: node_b_id,
+			short_channel_id: intercept_scid,
+			fees: RoutingFees { base_msat: 1000, proportional_millionths: 0 },
+			cltv_expiry_delta: MIN_CLTV_EXPIRY_DELTA,
+			htlc_minimum_msat: None,
+			htlc_maximum_msat: None,
+		}])])
+		.unwrap()
+		.with_bolt11_features(nodes[2].node.bolt11_invoice_features())
+		.unwrap();
+	let route_params = RouteParameters::from_payment_params_and_value(payment_params, amt_msat);
+	let route = get_route(
+		&node_a_id,
+		&route_params,
+		&nodes[0].network_graph.read_only(),
+		None,
+		nodes[0].logger,
+		&scorer,
+		&Default::default(),
+		&random_seed_bytes,
+	)
+	.unwrap();
+
+	// Send two payments through the intercept SCID, one of which we'll forward and the other fail.
+	let mut intercepted = Vec::new();
+	for _ in 0..2 {
+		let (hash, payment_secret) =
+			nodes[2].node.create_inbound_payment(Some(amt_msat), 60 * 60, None).unwrap();
+		let onion = RecipientOnionFields::secret_only(payment_secret);
+		let id = PaymentId(hash.0);
+		nodes[0].node.send_payment_with_route(route.clone(), hash, onion, id).unwrap();
+		check_added_monitors(&nodes[0], 1);
+		let payment_event = SendEvent::from_node(&nodes[0]);
+		nodes[1].node.handle_update_add_htlc(node_a_id, &payment_event.msgs[0]);
+		let commitment = &payment_event.commitment_msg;
+		do_commitment_signed_dance(&nodes[1], &nodes[0], commitment, false, true);
+		expect_and_process_pending_htlcs(&nodes[1], false);
+
+		let events = nodes[1].node.get_and_clear_pending_events();
+		assert_eq!(events.len(), 1);
+		match events[0] {
+			Event::HTLCIntercepted {
+				intercept_id,
+				payment_hash,
+				expected_outbound_amount_msat,
+				htlc_correlation_id,
+				..
+			} => {
+				assert_eq!(payment_hash, hash);
+				let correlation_id = htlc_correlation_id.unwrap();
+				let outbound_amt = expected_outbound_amount_msat;
+				intercepted.push((
+					hash,
+					payment_secret,
+					intercept_id,
+					correlation_id,
+					outbound_amt,
+				));
+			},
+			_ => panic!("Unexpected event"),
+		}
+	}
+	let (fwd_hash, fwd_secret, fwd_intercept_id, fwd_correlation_id, fwd_amt) = intercepted[0];
+	let (fail_hash, _, fail_intercept_id, fail_correlation_id, _) = intercepted[1];
+	assert_ne!(fwd_correlation_id, fail_correlation_id);
+
+	// Failing the second HTLC back surfaces its correlation id in `HTLCHandlingFailed`.
+	nodes[1].node.fail_intercepted_htlc(fail_intercept_id).unwrap();
+	let events = nodes[1].node.get_and_clear_pending_events();
+	assert_eq!(events.len(), 1);
+	match events[0] {
+		Event::HTLCHandlingFailed { htlc_correlation_id, .. } => {
+			assert_eq!(htlc_correlation_id, Some(fail_correlation_id));
+		},
+		_ => panic!("Unexpected event"),
+	}
+	let fail = HTLCHandlingFailureType::InvalidForward { requested_forward_scid: intercept_scid };
+	expect_htlc_failure_conditions(events, &[fail]);
+	nodes[1].node.process_pending_htlc_forwards();
+	let update_fail = get_htlc_update_msgs(&nodes[1], &node_a_id);
+	check_added_monitors(&nodes[1], 1);
+	nodes[0].node.handle_update_fail_htlc(node_b_id, &update_fail.update_fail_htlcs[0]);
+	let commitment = &update_fail.commitment_signed;
+	do_commitment_signed_dance(&nodes[0], &nodes[1], commitment, false, false);
+	let fail_conditions = PaymentFailedConditions::new()
+		.blamed_scid(intercept_scid)
+		.blamed_chan_closed(true)
+		.expected_htlc_error_data(LocalHTLCFailureReason::UnknownNextPeer, &[]);
+	expect_payment_failed_conditions(&nodes[0], fail_hash, false, fail_conditions);
+
+	// Forward the first HTLC over a just-in-time channel and claim it, which surfaces its
+	// correlation id in `PaymentForwarded`.
+	let (_, chan_id) = open_zero_conf_channel(&nodes[1], &nodes[2], None);
+	nodes[1].node.forward_intercepted_htlc(fwd_intercept_id, &chan_id, node_c_id, fwd_amt).unwrap();
+	expect_and_process_pending_htlcs(&nodes[1], false);
+	check_added_monitors(&nodes[1], 1);
+	let payment_event = SendEvent::from_node(&nodes[1]);
+	nodes[2].node.handle_update_add_htlc(node_b_id, &payment_event.msgs[0]);
+	let commitment = &payment_event.commitment_msg;
+	do_commitment_signed_dance(&nodes[2], &nodes[1], commitment, false, true);
+	expect_and_process_pending_htlcs(&nodes[2], false);
+
+	let preimage = nodes[2].node.get_payment_preimage(fwd_hash, fwd_secret).unwrap();
+	expect_payment_claimable!(&nodes[2], fwd_hash, fwd_secret, amt_msat, Some(preimage), node_c_id);
+	nodes[2].node.claim_funds(preimage);
+	check_added_monitors(&nodes[2], 1);
+	expect_payment_claimed!(nodes[2], fwd_hash, amt_msat);
+
+	let mut htlc_fulfill = get_htlc_update_msgs(&nodes[2], &node_b_id);
+	let fulfill_msg = htlc_fulfill.update_fulfill_htlcs.remove(0);
+	nodes[1].node.handle_update_fulfill_htlc(node_c_id, fulfill_msg);
+	check_added_monitors(&nodes[1], 1);
+	let events = nodes[1].node.get_and_clear_pending_events();
+	assert_eq!(events.len(), 1);
+	match events[0] {
+		Event::PaymentForwarded { htlc_correlation_id, .. } => {
+			assert_eq!(htlc_correlation_id, Some(fwd_correlation_id));
+		},
+		_ => panic!("Unexpected event"),
+	}
+	let mut bs_fulfill = get_htlc_update_msgs(&nodes[1], &node_a_id);
+	do_commitment_signed_dance(&nodes[1], &nodes[2], &htlc_fulfill.commitment_signed, false, false);
+
+	nodes[0].node.handle_update_fulfill_htlc(node_b_id, bs_fulfill.update_fulfill_htlcs.remove(0));
+	do_commitment_signed_dance(&nodes[0], &nodes[1], &bs_fulfill.commitment_signed, false, false);
+	expect_payment_sent(&nodes[0], preimage, None, true, true);
+}
+
 #[test]
 fn accept_underpaying_htlcs_config() {
 	do_accept_underpaying_htlcs_config(1);
@@ -2595,6 +3017,7 @@ enum AutoRetry {
 	FailTimeout,
 	FailOnRestart,
 	FailOnRetry,
+	FailCltvBudget,
 }
 
 #[test]
@@ -2605,6 +3028,7 @@ fn automatic_retries() {
 	do_automatic_retries(AutoRetry::FailTimeout);
 	do_automatic_retries(AutoRetry::FailOnRestart);
 	do_automatic_retries(AutoRetry::FailOnRetry);
+	do_automatic_retries(AutoRetry::FailCltvBudget);
 }
 fn do_automatic_retries(test: AutoRetry) {
 	// Test basic automatic payment retries in ChannelManager. See individual `test` variant comments
@@ -2853,6 +3277,36 @@ fn do_automatic_retries(test: AutoRetry) {
 			},
 			_ => panic!("Unexpected event"),
 		}
+	} else if test == AutoRetry::FailCltvBudget {
+		// Ensure ChannelManager will not retry a payment if the blocks which elapsed since it was
+		// first sent leave too little of its CLTV budget for the retry path.
+		let onion = RecipientOnionFields::secret_only(payment_secret);
+		let id = PaymentId(hash.0);
+		nodes[0].node.send_payment(hash, onion, id, route_params, Retry::Attempts(1)).unwrap();
+		pass_failed_attempt_with_retry_along_path!(channel_id_2, true);
+
+		// Open a new channel with liquidity on the second hop so we can find a route for the retry
+		// attempt, then let most of the CLTV budget elapse.
+		create_announced_chan_between_nodes(&nodes, 1, 2);
+		let elapsed_blocks = DEFAULT_MAX_TOTAL_CLTV_EXPIRY_DELTA - TEST_FINAL_CLTV;
+		for node in nodes.iter() {
+			connect_blocks(node, elapsed_blocks);
+		}
+
+		nodes[0].node.process_pending_htlc_forwards();
+		let msg_events = nodes[0].node.get_and_clear_pending_msg_events();
+		assert_eq!(msg_events.len(), 0);
+
+		let mut events = nodes[0].node.get_and_clear_pending_events();
+		assert_eq!(events.len(), 1);
+		match events[0] {
+			Event::PaymentFailed { payment_hash, payment_id, reason } => {
+				assert_eq!(Some(hash), payment_hash);
+				assert_eq!(PaymentId(hash.0), payment_id);
+				assert_eq!(PaymentFailureReason::BudgetExhausted, reason.unwrap());
+			},
+			_ => panic!("Unexpected event"),
+		}
 	}
 }
 
@@ -4455,7 +4909,24 @@ fn do_test_custom_tlvs(spontaneous: bool, even_tlvs: bool, known_tlvs: bool) {
 	if spontaneous {
 		let params = route.route_params.unwrap();
 		let retry = Retry::Attempts(0);
-		nodes[0].node.send_spontaneous_payment(Some(preimage), onion, id, params, retry).unwrap();
+
+		// Custom TLVs outside of the custom range are rejected before anything is sent.
+		let invalid_tlvs = vec![(42, vec![1, 2, 3, 4])];
+		let res = nodes[0].node.send_spontaneous_payment_with_custom_tlvs(
+			Some(preimage),
+			invalid_tlvs,
+			id,
+			params.clone(),
+			retry,
+		);
+		assert_eq!(res, Err(RetryableSendFailure::InvalidCustomTlvs));
+		assert!(nodes[0].node.get_and_clear_pending_msg_events().is_empty());
+
+		let tlvs = onion.custom_tlvs().clone();
+		nodes[0]
+			.node
+			.send_spontaneous_payment_with_custom_tlvs(Some(preimage), tlvs, id, params, retry)
+			.unwrap();
 	} else {
 		nodes[0].node.send_payment_with_route(route, hash, onion, id).unwrap();
 	}
@@ -4585,6 +5056,272 @@ fn test_retry_custom_tlvs() {
 	);
 }
 
+#[test]
+fn test_hold_invoice_settle() {
+	do_test_hold_invoice(true);
+}
+
+#[test]
+fn test_hold_invoice_cancel() {
+	do_test_hold_invoice(false);
+}
+
+fn do_test_hold_invoice(settle: bool) {
+	// Test that payments to a hold invoice generate an `Event::PaymentHeld` rather than an
+	// `Event::PaymentClaimable`, and are only claimed or failed once explicitly settled or
+	// cancelled.
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[None, None]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+
+	let node_a_id = nodes[0].node.get_our_node_id();
+	let node_b_id = nodes[1].node.get_our_node_id();
+
+	create_announced_chan_between_nodes(&nodes, 0, 1);
+
+	let amt_msat = 100_000;
+	let preimage = PaymentPreimage([42; 32]);
+	let hash = PaymentHash(Sha256::hash(&preimage.0).to_byte_array());
+	let payment_secret = nodes[1]
+		.node
+		.create_hold_inbound_payment_for_hash(hash, Some(amt_msat), 7200, None)
+		.unwrap();
+
+	// Hold invoice payments can't be settled before they're received.
+	assert!(nodes[1].node.settle_held_payment(preimage).is_err());
+
+	let (route, ..) = get_route_and_payment_hash!(&nodes[0], &nodes[1], amt_msat);
+	let onion = RecipientOnionFields::secret_only(payment_secret);
+	nodes[0].node.send_payment_with_route(route, hash, onion, PaymentId(hash.0)).unwrap();
+	check_added_monitors(&nodes[0], 1);
+
+	let mut events = nodes[0].node.get_and_clear_pending_msg_events();
+	let ev = remove_first_msg_event_to_node(&node_b_id, &mut events);
+	let path: &[&Node] = &[&nodes[1]];
+	let args = PassAlongPathArgs::new(&nodes[0], path, amt_msat, hash, ev)
+		.with_payment_secret(payment_secret)
+		.without_clearing_recipient_events();
+	do_pass_along_path(args);
+
+	let events = nodes[1].node.get_and_clear_pending_events();
+	assert_eq!(events.len(), 1);
+	match events[0] {
+		Event::PaymentHeld { payment_hash, amount_msat, ref purpose, claim_deadline, .. } => {
+			assert_eq!(payment_hash, hash);
+			assert_eq!(amount_msat, amt_msat);
+			assert_eq!(purpose.preimage(), None);
+			assert!(claim_deadline > nodes[1].best_block_info().1);
+		},
+		_ => panic!("Unexpected event"),
+	}
+
+	// Only payment hashes registered as a hold invoice can be settled or cancelled.
+	let other_hash = PaymentHash([43; 32]);
+	assert!(nodes[1].node.cancel_held_payment(&other_hash).is_err());
+
+	if settle {
+		nodes[1].node.settle_held_payment(preimage).unwrap();
+		let paths: &[&[&Node]] = &[&[&nodes[1]]];
+		pass_claimed_payment_along_route(ClaimAlongRouteArgs::new(&nodes[0], paths, preimage));
+		expect_payment_sent!(nodes[0], preimage);
+
+		// Once settled, the payment is no longer held.
+		assert!(nodes[1].node.settle_held_payment(preimage).is_err());
+	} else {
+		nodes[1].node.cancel_held_payment(&hash).unwrap();
+		let fail_type = HTLCHandlingFailureType::Receive { payment_hash: hash };
+		expect_and_process_pending_htlcs_and_htlc_handling_failed(&nodes[1], &[fail_type]);
+		check_added_monitors(&nodes[1], 1);
+
+		let htlc_fail_updates = get_htlc_update_msgs(&nodes[1], &node_a_id);
+		nodes[0].node.handle_update_fail_htlc(node_b_id, &htlc_fail_updates.update_fail_htlcs[0]);
+		let commitment = &htlc_fail_updates.commitment_signed;
+		do_commitment_signed_dance(&nodes[0], &nodes[1], commitment, false, false);
+		expect_payment_failed!(nodes[0], hash, true);
+
+		assert!(nodes[1].node.cancel_held_payment(&hash).is_err());
+	}
+}
+
+#[test]
+fn test_hold_invoice_registration_dropped() {
+	// Test that hold invoice registrations are dropped once a held payment is claimed directly via
+	// `claim_funds` or automatically failed back at its claim deadline, as well as once the invoice
+	// has expired without any payment being held.
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[None, None]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+
+	let node_a_id = nodes[0].node.get_our_node_id();
+	let node_b_id = nodes[1].node.get_our_node_id();
+
+	create_announced_chan_between_nodes(&nodes, 0, 1);
+
+	let amt_msat = 100_000;
+	let send_held_payment = |preimage: PaymentPreimage| {
+		let hash = PaymentHash(Sha256::hash(&preimage.0).to_byte_array());
+		let payment_secret = nodes[1]
+			.node
+			.create_hold_inbound_payment_for_hash(hash, Some(amt_msat), 7200, None)
+			.unwrap();
+
+		let (route, ..) = get_route_and_payment_hash!(&nodes[0], &nodes[1], amt_msat);
+		let onion = RecipientOnionFields::secret_only(payment_secret);
+		nodes[0].node.send_payment_with_route(route, hash, onion, PaymentId(hash.0)).unwrap();
+		check_added_monitors(&nodes[0], 1);
+
+		let mut events = nodes[0].node.get_and_clear_pending_msg_events();
+		let ev = remove_first_msg_event_to_node(&node_b_id, &mut events);
+		let path: &[&Node] = &[&nodes[1]];
+		let args = PassAlongPathArgs::new(&nodes[0], path, amt_msat, hash, ev)
+			.with_payment_secret(payment_secret)
+			.without_clearing_recipient_events();
+		do_pass_along_path(args);
+
+		let events = nodes[1].node.get_and_clear_pending_events();
+		assert_eq!(events.len(), 1);
+		match events[0] {
+			Event::PaymentHeld { payment_hash, claim_deadline, .. } => {
+				assert_eq!(payment_hash, hash);
+				(hash, claim_deadline)
+			},
+			_ => panic!("Unexpected event"),
+		}
+	};
+
+	// Claiming a held payment via `claim_funds` rather than `settle_held_payment` drops the
+	// registration.
+	let preimage = PaymentPreimage([42; 32]);
+	let (hash, _) = send_held_payment(preimage);
+	nodes[1].node.claim_funds(preimage);
+	let paths: &[&[&Node]] = &[&[&nodes[1]]];
+	pass_claimed_payment_along_route(ClaimAlongRouteArgs::new(&nodes[0], paths, preimage));
+	expect_payment_sent!(nodes[0], preimage);
+	assert!(nodes[1].node.cancel_held_payment(&hash).is_err());
+
+	// As does automatically failing a held payment back once its claim deadline is reached.
+	let preimage = PaymentPreimage([43; 32]);
+	let (hash, claim_deadline) = send_held_payment(preimage);
+	connect_blocks(&nodes[1], claim_deadline - nodes[1].best_block_info().1);
+	let fail_type = HTLCHandlingFailureType::Receive { payment_hash: hash };
+	expect_and_process_pending_htlcs_and_htlc_handling_failed(&nodes[1], &[fail_type]);
+	check_added_monitors(&nodes[1], 1);
+
+	let htlc_fail_updates = get_htlc_update_msgs(&nodes[1], &node_a_id);
+	nodes[0].node.handle_update_fail_htlc(node_b_id, &htlc_fail_updates.update_fail_htlcs[0]);
+	let commitment = &htlc_fail_updates.commitment_signed;
+	do_commitment_signed_dance(&nodes[0], &nodes[1], commitment, false, false);
+	expect_payment_failed!(nodes[0], hash, true);
+	assert!(nodes[1].node.cancel_held_payment(&hash).is_err());
+
+	// Finally, registrations for invoices which were never paid are pruned once they've expired.
+	let expired_hash = PaymentHash([44; 32]);
+	nodes[1].node.create_hold_inbound_payment_for_hash(expired_hash, None, 60, None).unwrap();
+	let pending_hash = PaymentHash([45; 32]);
+	nodes[1].node.create_hold_inbound_payment_for_hash(pending_hash, None, 86400, None).unwrap();
+
+	let target_time = nodes[1].node.duration_since_epoch() + Duration::from_secs(7200 + 120);
+	let block =
+		create_dummy_block(nodes[1].best_block_hash(), target_time.as_secs() as u32, Vec::new());
+	connect_block(&nodes[1], &block);
+	nodes[1].node.timer_tick_occurred();
+
+	assert!(nodes[1].node.cancel_held_payment(&expired_hash).is_err());
+	nodes[1].node.cancel_held_payment(&pending_hash).unwrap();
+}
+
+#[test]
+fn test_inbound_payment_amount_range() {
+	// Test that payments to a zero-amount invoice created with an amount range are only accepted
+	// if they're within the range, with the actual amount paid surfaced in `PaymentClaimable`.
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[None, None]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+
+	let node_a_id = nodes[0].node.get_our_node_id();
+	let node_b_id = nodes[1].node.get_our_node_id();
+
+	create_announced_chan_between_nodes(&nodes, 0, 1);
+
+	let range =
+		crate::ln::channelmanager::InboundAmountRange::with_max_overpayment_multiplier(100_000, 2);
+	assert_eq!(range.max_value_msat, 200_000);
+
+	// A range whose minimum exceeds its maximum is rejected, as is setting both an amount and a
+	// range on an invoice.
+	let bad_range = crate::ln::channelmanager::InboundAmountRange {
+		min_value_msat: 200_000,
+		max_value_msat: 100_000,
+	};
+	assert!(nodes[1].node.create_inbound_payment_with_amount_range(bad_range, 7200, None).is_err());
+	let invoice_params = crate::ln::channelmanager::Bolt11InvoiceParameters {
+		amount_msats: Some(100_000),
+		amount_range_msats: Some(range),
+		..Default::default()
+	};
+	assert!(nodes[1].node.create_bolt11_invoice(invoice_params).is_err());
+
+	let invoice_params = crate::ln::channelmanager::Bolt11InvoiceParameters {
+		amount_range_msats: Some(range),
+		..Default::default()
+	};
+	let invoice = nodes[1].node.create_bolt11_invoice(invoice_params).unwrap();
+	assert_eq!(invoice.amount_milli_satoshis(), None);
+
+	// A payment within the range is accepted, with the amount actually paid reported.
+	let hash = PaymentHash(invoice.payment_hash().to_byte_array());
+	let payment_secret = *invoice.payment_secret();
+	let (route, ..) = get_route_and_payment_hash!(&nodes[0], &nodes[1], 150_000);
+	let onion = RecipientOnionFields::secret_only(payment_secret);
+	nodes[0].node.send_payment_with_route(route, hash, onion, PaymentId(hash.0)).unwrap();
+	check_added_monitors(&nodes[0], 1);
+
+	let mut events = nodes[0].node.get_and_clear_pending_msg_events();
+	let ev = remove_first_msg_event_to_node(&node_b_id, &mut events);
+	let path: &[&Node] = &[&nodes[1]];
+	let args = PassAlongPathArgs::new(&nodes[0], path, 150_000, hash, ev)
+		.with_payment_secret(payment_secret)
+		.without_clearing_recipient_events();
+	do_pass_along_path(args);
+
+	let events = nodes[1].node.get_and_clear_pending_events();
+	assert_eq!(events.len(), 1);
+	let preimage = match events[0] {
+		Event::PaymentClaimable { payment_hash, amount_msat, ref purpose, .. } => {
+			assert_eq!(payment_hash, hash);
+			assert_eq!(amount_msat, 150_000);
+			purpose.preimage().unwrap()
+		},
+		_ => panic!("Unexpected event"),
+	};
+	claim_payment(&nodes[0], &[&nodes[1]], preimage);
+
+	// A payment above the range's maximum is failed back.
+	let (hash, payment_secret) =
+		nodes[1].node.create_inbound_payment_with_amount_range(range, 7200, None).unwrap();
+	let (route, ..) = get_route_and_payment_hash!(&nodes[0], &nodes[1], 250_000);
+	let onion = RecipientOnionFields::secret_only(payment_secret);
+	nodes[0].node.send_payment_with_route(route, hash, onion, PaymentId(hash.0)).unwrap();
+	check_added_monitors(&nodes[0], 1);
+
+	let mut events = nodes[0].node.get_and_clear_pending_msg_events();
+	let ev = remove_first_msg_event_to_node(&node_b_id, &mut events);
+	let fail = HTLCHandlingFailureType::Receive { payment_hash: hash };
+	let args = PassAlongPathArgs::new(&nodes[0], path, 250_000, hash, ev)
+		.with_payment_secret(payment_secret)
+		.expect_failure(fail);
+	do_pass_along_path(args);
+
+	let htlc_fail_updates = get_htlc_update_msgs(&nodes[1], &node_a_id);
+	nodes[0].node.handle_update_fail_htlc(node_b_id, &htlc_fail_updates.update_fail_htlcs[0]);
+	let commitment = &htlc_fail_updates.commitment_signed;
+	do_commitment_signed_dance(&nodes[0], &nodes[1], commitment, false, false);
+	expect_payment_failed!(nodes[0], hash, true);
+}
+
 #[test]
 fn test_custom_tlvs_consistency() {
 	let even_type_1 = 1 << 16;
@@ -5103,6 +5840,7 @@ fn peel_payment_onion_custom_tlvs() {
 		onion_routing_packet,
 		blinding_point: None,
 		hold_htlc: None,
+		endorsed: None,
 	};
 	let peeled_onion = crate::ln::onion_payment::peel_payment_onion(
 		&update_add,
@@ -47,18 +47,22 @@ use crate::sign::{NodeSigner, Recipient};
 use crate::types::features::{InitFeatures, NodeFeatures};
 use crate::types::string::PrintableString;
 use crate::util::atomic_counter::AtomicCounter;
+use crate::util::export::{features_hex, versioned_bytes, JsonObject};
 use crate::util::logger::{Level, Logger, WithContext};
 use crate::util::ser::{VecWriter, Writeable, Writer};
+#[cfg(feature = "std")]
+use crate::util::time::Instant;
 
 #[allow(unused_imports)]
 use crate::prelude::*;
 
 use super::wire::CustomMessageReader;
 use crate::io;
-use crate::sync::{FairRwLock, Mutex, MutexGuard};
+use crate::sync::{FairRwLock, Mutex, MutexGuard, RwLock};
 use core::convert::Infallible;
 use core::ops::Deref;
 use core::sync::atomic::{AtomicBool, AtomicI32, AtomicU32, Ordering};
+use core::time::Duration;
 use core::{cmp, fmt, hash, mem};
 
 #[cfg(not(c_bindings))]
@@ -126,6 +130,60 @@ pub trait CustomMessageHandler: wire::CustomMessageReader {
 	fn provided_init_features(&self, their_node_id: PublicKey) -> InitFeatures;
 }
 
+/// An observer which is notified of odd-typed messages which are understood by neither LDK nor the
+/// [`CustomMessageHandler`]. Set one with [`PeerManager::set_unknown_message_observer`].
+///
+/// [BOLT 1] requires that such messages be ignored, but observing them can be useful when
+/// developing new protocol extensions and debugging interoperability with other implementations.
+/// Implementations may, for example, log the messages (as [`LoggingUnknownMessageObserver`] does)
+/// or persist them for later inspection.
+///
+/// [BOLT 1]: https://github.com/lightning/bolts/blob/master/01-messaging.md
+pub trait UnknownMessageObserver {
+	/// Called with the type and payload (i.e. the message contents following the two type bytes)
+	/// of an unknown odd message received from `their_node_id`.
+	///
+	/// This is called while processing reads from the peer, so implementations should avoid
+	/// blocking for extended periods.
+	fn observe_unknown_message(&self, their_node_id: PublicKey, message_type: u16, payload: &[u8]);
+}
+
+/// An [`UnknownMessageObserver`] which logs each unknown message, including its hex-encoded
+/// payload, at a configurable [`Level`].
+pub struct LoggingUnknownMessageObserver<L: Deref>
+where
+	L::Target: Logger,
+{
+	logger: L,
+	level: Level,
+}
+
+impl<L: Deref> LoggingUnknownMessageObserver<L>
+where
+	L::Target: Logger,
+{
+	/// Constructs a new `LoggingUnknownMessageObserver` which logs to `logger` at the given `level`.
+	pub fn new(logger: L, level: Level) -> Self {
+		Self { logger, level }
+	}
+}
+
+impl<L: Deref> UnknownMessageObserver for LoggingUnknownMessageObserver<L>
+where
+	L::Target: Logger,
+{
+	fn observe_unknown_message(&self, their_node_id: PublicKey, message_type: u16, payload: &[u8]) {
+		let logger = WithContext::from(&self.logger, Some(their_node_id), None, None);
+		log_given_level!(
+			logger,
+			self.level,
+			"Received unknown odd message of type {} with payload {}",
+			message_type,
+			log_bytes!(payload)
+		);
+	}
+}
+
 /// A dummy struct which implements `RoutingMessageHandler` without storing any routing information
 /// or doing any processing. You can provide one of these as the route_handler in a MessageHandler.
 pub struct IgnoringMessageHandler {}
@@ -506,6 +564,8 @@ impl ChannelMessageHandler for ErroringMessageHandler {
 
 	fn handle_error(&self, _their_node_id: PublicKey, _msg: &msgs::ErrorMessage) {}
 
+	fn handle_warning(&self, _their_node_id: PublicKey, _msg: &msgs::WarningMessage) {}
+
 	fn get_chain_hashes(&self) -> Option<Vec<ChainHash>> {
 		// We don't enforce any chains upon peer connection for `ErroringMessageHandler` and leave it up
 		// to users of `ErroringMessageHandler` to make decisions on network compatiblility.
@@ -681,8 +741,87 @@ pub struct PeerDetails {
 	///
 	/// Will be `true` for inbound connections, and `false` for outbound connections.
 	pub is_inbound_connection: bool,
+	/// Timing information about the connection and the peer's responsiveness.
+	pub timings: PeerTimings,
+}
+
+/// Timing information about a peer connection, as reported in [`PeerDetails::timings`].
+///
+/// Timings are only tracked if the `std` feature is enabled, and will always be `None` otherwise.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PeerTimings {
+	/// The time it took to complete the Noise handshake after the connection was registered via
+	/// [`PeerManager::new_outbound_connection`] or [`PeerManager::new_inbound_connection`].
+	pub handshake_duration: Option<Duration>,
+	/// The time between completing the Noise handshake and receiving the peer's `init` message.
+	pub init_exchange_duration: Option<Duration>,
+	/// The time between receiving the peer's `init` message and the first gossip announcement or
+	/// update it sent us.
+	///
+	/// Will be `None` if the peer hasn't sent us any gossip yet.
+	pub first_gossip_latency: Option<Duration>,
+	/// A moving average of the time the peer took to respond to our `commitment_signed` messages
+	/// with a `revoke_and_ack`.
+	///
+	/// Will be `None` if the peer hasn't acknowledged any channel updates yet.
+	pub commitment_ack_latency: Option<Duration>,
+}
+
+impl PeerTimings {
+	fn to_canonical_json(&self) -> String {
+		let millis = |duration: Option<Duration>| duration.map(|d| d.as_millis() as u64);
+		let mut obj = JsonObject::nested();
+		obj.opt_number("handshake_duration_ms", millis(self.handshake_duration));
+		obj.opt_number("init_exchange_duration_ms", millis(self.init_exchange_duration));
+		obj.opt_number("first_gossip_latency_ms", millis(self.first_gossip_latency));
+		obj.opt_number("commitment_ack_latency_ms", millis(self.commitment_ack_latency));
+		obj.finish()
+	}
 }
 
+impl_writeable_tlv_based!(PeerTimings, {
+	(1, handshake_duration, option),
+	(3, init_exchange_duration, option),
+	(5, first_gossip_latency, option),
+	(7, commitment_ack_latency, option),
+});
+
+impl PeerDetails {
+	/// Exports these details as a canonical, schema-versioned JSON object.
+	///
+	/// See the [`export`] module documentation for the stability guarantees and encoding rules.
+	///
+	/// [`export`]: crate::util::export
+	pub fn to_canonical_json(&self) -> String {
+		let mut obj = JsonObject::new();
+		obj.string("counterparty_node_id", self.counterparty_node_id);
+		obj.opt_string("socket_address", self.socket_address.as_ref());
+		obj.string("init_features", features_hex(self.init_features.le_flags()));
+		obj.boolean("is_inbound_connection", self.is_inbound_connection);
+		obj.raw("timings", self.timings.to_canonical_json());
+		obj.finish()
+	}
+
+	/// Exports these details in a schema-versioned binary format, i.e., the
+	/// [`EXPORT_SCHEMA_VERSION`] followed by their regular serialization.
+	///
+	/// See the [`export`] module documentation for the stability guarantees.
+	///
+	/// [`EXPORT_SCHEMA_VERSION`]: crate::util::export::EXPORT_SCHEMA_VERSION
+	/// [`export`]: crate::util::export
+	pub fn to_canonical_bytes(&self) -> Vec<u8> {
+		versioned_bytes(self)
+	}
+}
+
+impl_writeable_tlv_based!(PeerDetails, {
+	(0, counterparty_node_id, required),
+	(2, socket_address, option),
+	(4, init_features, required),
+	(6, is_inbound_connection, required),
+	(7, timings, (default_value, PeerTimings::default())),
+});
+
 /// Error for PeerManager errors. If you get one of these, you must disconnect the socket and
 /// generate no further read_event/write_buffer_space_avail/socket_disconnected calls for the
 /// descriptor.
@@ -774,6 +913,93 @@ const BUFFER_DRAIN_MSGS_PER_TICK: usize = 32;
 /// the equivalent maximum buffer size for gossip backfill is zero.
 const OUTBOUND_BUFFER_SIZE_LIMIT_DROP_GOSSIP: usize = 64 * 1024 * 2;
 
+/// The latency above which we consider a peer's `revoke_and_ack` in response to our
+/// `commitment_signed` to be slow.
+#[cfg(feature = "std")]
+const SLOW_COMMITMENT_ACK_LATENCY: Duration = Duration::from_secs(10);
+
+/// The number of consecutive slow `revoke_and_ack`s after which we warn about a peer. Peers which
+/// are consistently slow to acknowledge channel updates often end up with stuck HTLCs.
+#[cfg(feature = "std")]
+const SLOW_COMMITMENT_ACK_WARN_THRESHOLD: usize = 3;
+
+/// The maximum number of unacknowledged `commitment_signed`s per channel we track the send time
+/// of.
+#[cfg(feature = "std")]
+const MAX_PENDING_COMMITMENT_ACKS_PER_CHANNEL: usize = 16;
+
+/// Tracks when a peer connection reached its various stages in order to populate
+/// [`PeerTimings`].
+#[cfg(feature = "std")]
+struct PeerTimingTracker {
+	connected_at: Instant,
+	noise_complete_at: Option<Instant>,
+	init_received_at: Option<Instant>,
+	first_gossip_at: Option<Instant>,
+	/// The times at which we sent `commitment_signed`s which have yet to be acknowledged via
+	/// `revoke_and_ack`, per channel.
+	pending_commitment_acks: HashMap<ChannelId, VecDeque<Instant>>,
+	commitment_ack_latency: Option<Duration>,
+	consecutive_slow_commitment_acks: usize,
+}
+
+#[cfg(feature = "std")]
+impl PeerTimingTracker {
+	fn new() -> Self {
+		Self {
+			connected_at: Instant::now(),
+			noise_complete_at: None,
+			init_received_at: None,
+			first_gossip_at: None,
+			pending_commitment_acks: new_hash_map(),
+			commitment_ack_latency: None,
+			consecutive_slow_commitment_acks: 0,
+		}
+	}
+
+	fn timings(&self) -> PeerTimings {
+		let between = |start: Option<Instant>, end: Option<Instant>| {
+			start.zip(end).map(|(start, end)| end.duration_since(start))
+		};
+		PeerTimings {
+			handshake_duration: between(Some(self.connected_at), self.noise_complete_at),
+			init_exchange_duration: between(self.noise_complete_at, self.init_received_at),
+			first_gossip_latency: between(self.init_received_at, self.first_gossip_at),
+			commitment_ack_latency: self.commitment_ack_latency,
+		}
+	}
+
+	/// Records the receipt of a `revoke_and_ack` for the given channel, returning whether the peer
+	/// just became consistently slow to acknowledge our channel updates.
+	fn revoke_and_ack_received(&mut self, channel_id: &ChannelId) -> bool {
+		let sent_at = match self.pending_commitment_acks.get_mut(channel_id) {
+			Some(pending_acks) => {
+				let sent_at = pending_acks.pop_front();
+				if pending_acks.is_empty() {
+					self.pending_commitment_acks.remove(channel_id);
+				}
+				sent_at
+			},
+			None => None,
+		};
+		let latency = match sent_at {
+			Some(sent_at) => Instant::now().duration_since(sent_at),
+			None => return false,
+		};
+
+		self.commitment_ack_latency = Some(match self.commitment_ack_latency {
+			Some(avg_latency) => avg_latency * 3 / 4 + latency / 4,
+			None => latency,
+		});
+		if latency > SLOW_COMMITMENT_ACK_LATENCY {
+			self.consecutive_slow_commitment_acks += 1;
+		} else {
+			self.consecutive_slow_commitment_acks = 0;
+		}
+		self.consecutive_slow_commitment_acks == SLOW_COMMITMENT_ACK_WARN_THRESHOLD
+	}
+}
+
 struct Peer {
 	channel_encryptor: PeerChannelEncryptor,
 	/// We cache a `NodeId` here to avoid serializing peers' keys every time we forward gossip
@@ -817,9 +1043,16 @@ struct Peer {
 	/// check if we're gossip-processing-backlogged).
 	received_channel_announce_since_backlogged: bool,
 
+	/// The number of unknown odd messages from this peer passed to the [`UnknownMessageObserver`]
+	/// since the last timer tick.
+	unknown_messages_observed_since_timer_tick: usize,
+
 	inbound_connection: bool,
 
 	message_batch: Option<MessageBatch>,
+
+	#[cfg(feature = "std")]
+	timings: PeerTimingTracker,
 }
 
 impl Peer {
@@ -830,6 +1063,67 @@ impl Peer {
 		self.their_features.is_some()
 	}
 
+	fn timings(&self) -> PeerTimings {
+		#[cfg(feature = "std")]
+		{
+			self.timings.timings()
+		}
+		#[cfg(not(feature = "std"))]
+		{
+			PeerTimings::default()
+		}
+	}
+
+	fn record_noise_complete(&mut self) {
+		#[cfg(feature = "std")]
+		{
+			self.timings.noise_complete_at = Some(Instant::now());
+		}
+	}
+
+	fn record_init_received(&mut self) {
+		#[cfg(feature = "std")]
+		{
+			self.timings.init_received_at = Some(Instant::now());
+		}
+	}
+
+	fn record_gossip_received(&mut self) {
+		#[cfg(feature = "std")]
+		{
+			if self.timings.first_gossip_at.is_none() {
+				self.timings.first_gossip_at = Some(Instant::now());
+			}
+		}
+	}
+
+	fn record_commitment_signed_sent(&mut self, _channel_id: ChannelId) {
+		#[cfg(feature = "std")]
+		{
+			let pending_acks = self
+				.timings
+				.pending_commitment_acks
+				.entry(_channel_id)
+				.or_insert_with(VecDeque::new);
+			if pending_acks.len() < MAX_PENDING_COMMITMENT_ACKS_PER_CHANNEL {
+				pending_acks.push_back(Instant::now());
+			}
+		}
+	}
+
+	/// Records the receipt of a `revoke_and_ack` for the given channel, returning whether the peer
+	/// just became consistently slow to acknowledge our channel updates.
+	fn record_revoke_and_ack_received(&mut self, _channel_id: &ChannelId) -> bool {
+		#[cfg(feature = "std")]
+		{
+			self.timings.revoke_and_ack_received(_channel_id)
+		}
+		#[cfg(not(feature = "std"))]
+		{
+			false
+		}
+	}
+
 	/// Returns true if the channel announcements/updates for the given channel should be
 	/// forwarded to this peer.
 	/// If we are sending our routing table to this peer and we have not yet sent channel
@@ -1118,6 +1412,11 @@ pub struct PeerManager<
 	node_signer: NS,
 	our_node_id: NodeId,
 
+	/// The observer notified of unknown odd messages, if any, along with the maximum number of
+	/// messages from a single peer it is notified of per timer tick.
+	unknown_message_observer:
+		RwLock<Option<(Box<dyn UnknownMessageObserver + Send + Sync>, usize)>>,
+
 	logger: L,
 	secp_ctx: Secp256k1<secp256k1::SignOnly>,
 }
@@ -1343,6 +1642,7 @@ where
 			gossip_processing_backlogged: AtomicBool::new(false),
 			gossip_processing_backlog_lifted: AtomicBool::new(false),
 			last_node_announcement_serial: AtomicU32::new(current_time),
+			unknown_message_observer: RwLock::new(None),
 			logger,
 			our_node_id: NodeId::from_pubkey(&our_node_pubkey),
 			node_signer,
@@ -1350,6 +1650,28 @@ where
 		}
 	}
 
+	/// Sets the [`UnknownMessageObserver`] to notify of unknown odd messages received from our
+	/// peers, replacing any previously-set observer.
+	///
+	/// To bound the work done on behalf of any one peer, at most `max_messages_per_peer_per_tick`
+	/// messages from each peer are passed to the observer between calls to
+	/// [`Self::timer_tick_occurred`]. Any further messages are ignored without being observed.
+	///
+	/// The observer is not notified of unknown even messages, which always cause us to disconnect
+	/// the peer.
+	pub fn set_unknown_message_observer<O: UnknownMessageObserver + Send + Sync + 'static>(
+		&self, observer: O, max_messages_per_peer_per_tick: usize,
+	) {
+		*self.unknown_message_observer.write().unwrap() =
+			Some((Box::new(observer), max_messages_per_peer_per_tick));
+	}
+
+	/// Removes the [`UnknownMessageObserver`] set with [`Self::set_unknown_message_observer`], if
+	/// any.
+	pub fn clear_unknown_message_observer(&self) {
+		*self.unknown_message_observer.write().unwrap() = None;
+	}
+
 	/// Returns a list of [`PeerDetails`] for connected peers that have completed the initial
 	/// handshake.
 	pub fn list_peers(&self) -> Vec<PeerDetails> {
@@ -1368,6 +1690,7 @@ where
 				// completed.
 				init_features: p.their_features.clone().unwrap(),
 				is_inbound_connection: p.inbound_connection,
+				timings: p.timings(),
 			};
 			Some(details)
 		};
@@ -1400,6 +1723,7 @@ where
 				// completed.
 				init_features: p.their_features.clone().unwrap(),
 				is_inbound_connection: p.inbound_connection,
+				timings: p.timings(),
 			};
 			Some(details)
 		})
@@ -1476,9 +1800,13 @@ where
 					sent_gossip_timestamp_filter: false,
 
 					received_channel_announce_since_backlogged: false,
+					unknown_messages_observed_since_timer_tick: 0,
 					inbound_connection: false,
 
 					message_batch: None,
+
+					#[cfg(feature = "std")]
+					timings: PeerTimingTracker::new(),
 				}));
 				Ok(res)
 			},
@@ -1537,9 +1865,13 @@ where
 					sent_gossip_timestamp_filter: false,
 
 					received_channel_announce_since_backlogged: false,
+					unknown_messages_observed_since_timer_tick: 0,
 					inbound_connection: true,
 
 					message_batch: None,
+
+					#[cfg(feature = "std")]
+					timings: PeerTimingTracker::new(),
 				}));
 				Ok(())
 			},
@@ -1822,6 +2154,7 @@ where
 				let mut peer_lock = peer_mutex.lock().unwrap();
 				let peer = &mut *peer_lock;
 				let mut msg_to_handle = None;
+				let mut unknown_message_to_observe = None;
 				if peer_node_id.is_none() {
 					peer_node_id.clone_from(&peer.their_node_id);
 				}
@@ -1893,6 +2226,7 @@ where
 							peer.pending_outbound_buffer.push_back(act_three.to_vec());
 							peer.pending_read_buffer = [0; 18].to_vec(); // Message length header is 18 bytes
 							peer.pending_read_is_header = true;
+							peer.record_noise_complete();
 
 							peer.set_their_node_id(their_node_id);
 							insert_node_id!();
@@ -1915,6 +2249,7 @@ where
 							let their_node_id = try_potential_handleerror!(peer, res);
 							peer.pending_read_buffer = [0; 18].to_vec(); // Message length header is 18 bytes
 							peer.pending_read_is_header = true;
+							peer.record_noise_complete();
 							peer.set_their_node_id(their_node_id);
 							insert_node_id!();
 							let features = self.init_features(their_node_id);
@@ -1957,6 +2292,13 @@ where
 									&*self.message_handler.custom_message_handler,
 								);
 
+								if let Ok(Message::Unknown(message_type)) = &message_result {
+									if message_type % 2 == 1 {
+										unknown_message_to_observe =
+											self.unknown_message_to_observe(peer, *message_type);
+									}
+								}
+
 								// Reset read buffer
 								if peer.pending_read_buffer.capacity() > 8192 {
 									peer.pending_read_buffer = Vec::new();
@@ -2056,6 +2398,12 @@ where
 						Ok(None) => {},
 					}
 				}
+
+				if let Some((their_node_id, message_type, payload)) = unknown_message_to_observe {
+					if let Some((observer, _)) = &*self.unknown_message_observer.read().unwrap() {
+						observer.observe_unknown_message(their_node_id, message_type, &payload);
+					}
+				}
 			}
 		} else {
 			// This is most likely a simple race condition where the user read some bytes
@@ -2076,6 +2424,25 @@ where
 		Ok(())
 	}
 
+	/// Checks whether an unknown odd message of the given type, which must be in the peer's
+	/// decrypted read buffer, should be passed to our [`UnknownMessageObserver`], returning a copy of
+	/// its payload if so.
+	fn unknown_message_to_observe(
+		&self, peer: &mut Peer, message_type: u16,
+	) -> Option<(PublicKey, u16, Vec<u8>)> {
+		let their_node_id = peer.their_node_id?.0;
+		let max_messages_per_tick = match &*self.unknown_message_observer.read().unwrap() {
+			Some((_, max_messages_per_tick)) => *max_messages_per_tick,
+			None => return None,
+		};
+		if peer.unknown_messages_observed_since_timer_tick >= max_messages_per_tick {
+			return None;
+		}
+		peer.unknown_messages_observed_since_timer_tick += 1;
+		let payload = peer.pending_read_buffer[2..peer.pending_read_buffer.len() - 16].to_vec();
+		Some((their_node_id, message_type, payload))
+	}
+
 	/// Process an incoming message and return a decision (ok, lightning error, peer handling error) regarding the next action with the peer
 	///
 	/// Returns the message back if it needs to be broadcasted to all other peers.
@@ -2234,12 +2601,29 @@ where
 
 			peer_lock.awaiting_pong_timer_tick_intervals = 0;
 			peer_lock.their_features = Some(msg.features);
+			peer_lock.record_init_received();
 			return Ok(None);
 		} else if peer_lock.their_features.is_none() {
 			log_debug!(logger, "Peer sent non-Init first message");
 			return Err(PeerHandleError {}.into());
 		}
 
+		match &message {
+			Message::ChannelAnnouncement(_)
+			| Message::NodeAnnouncement(_)
+			| Message::ChannelUpdate(_) => peer_lock.record_gossip_received(),
+			Message::RevokeAndACK(msg) => {
+				if peer_lock.record_revoke_and_ack_received(&msg.channel_id) {
+					log_warn!(
+						logger,
+						"Peer {} has been consistently slow to acknowledge channel updates, which may lead to stuck HTLCs",
+						their_node_id
+					);
+				}
+			},
+			_ => {},
+		}
+
 		// During splicing, commitment_signed messages need to be collected into a single batch
 		// before they are handled.
 		if let Message::StartBatch(msg) = message {
@@ -2435,6 +2819,7 @@ where
 			},
 			Message::Warning(msg) => {
 				log_debug!(logger, "Got warning message: {}", PrintableString(&msg.data));
+				self.message_handler.chan_handler.handle_warning(their_node_id, &msg);
 			},
 
 			Message::Ping(msg) => {
@@ -3120,6 +3505,9 @@ where
 								let msg = Message::StartBatch(msg);
 								self.enqueue_message(&mut *peer, msg);
 							}
+							if !commitment_signed.is_empty() {
+								peer.record_commitment_signed_sent(*channel_id);
+							}
 							for msg in commitment_signed {
 								let msg = Message::CommitmentSigned(msg);
 								self.enqueue_message(&mut *peer, msg);
@@ -3602,6 +3990,7 @@ where
 				if flush_read_disabled {
 					peer.received_channel_announce_since_backlogged = false;
 				}
+				peer.unknown_messages_observed_since_timer_tick = 0;
 
 				if !peer.handshake_complete() {
 					// The peer needs to complete its handshake before we can exchange messages. We
@@ -4303,6 +4692,108 @@ mod tests {
 		peers[1].read_event(&mut fd_b, &a_data).unwrap();
 	}
 
+	#[test]
+	#[cfg(feature = "std")]
+	fn test_peer_timings() {
+		use crate::util::time::Instant;
+
+		let cfgs = create_peermgr_cfgs(2);
+		let peers = create_network(2, &cfgs);
+		establish_connection(&peers[0], &peers[1]);
+		let their_id = peers[1].node_signer.get_node_id(Recipient::Node).unwrap();
+		let timings = peers[0].peer_by_node_id(&their_id).unwrap().timings;
+		assert!(timings.handshake_duration.is_some());
+		assert!(timings.init_exchange_duration.is_some());
+		assert_eq!(timings.first_gossip_latency, None);
+		assert_eq!(timings.commitment_ack_latency, None);
+
+		let mut tracker = PeerTimingTracker::new();
+		Instant::advance(Duration::from_millis(100));
+		tracker.noise_complete_at = Some(Instant::now());
+		Instant::advance(Duration::from_millis(50));
+		tracker.init_received_at = Some(Instant::now());
+		Instant::advance(Duration::from_secs(1));
+		tracker.first_gossip_at = Some(Instant::now());
+		let timings = tracker.timings();
+		assert_eq!(timings.handshake_duration, Some(Duration::from_millis(100)));
+		assert_eq!(timings.init_exchange_duration, Some(Duration::from_millis(50)));
+		assert_eq!(timings.first_gossip_latency, Some(Duration::from_secs(1)));
+
+		// A `revoke_and_ack` we aren't awaiting is ignored.
+		let channel_id = ChannelId::from_bytes([42; 32]);
+		assert!(!tracker.revoke_and_ack_received(&channel_id));
+		assert_eq!(tracker.timings().commitment_ack_latency, None);
+
+		let ack_after = |tracker: &mut PeerTimingTracker, latency: Duration| {
+			let pending_acks = tracker.pending_commitment_acks.entry(channel_id).or_default();
+			pending_acks.push_back(Instant::now());
+			Instant::advance(latency);
+			tracker.revoke_and_ack_received(&channel_id)
+		};
+		assert!(!ack_after(&mut tracker, Duration::from_secs(2)));
+		assert_eq!(tracker.timings().commitment_ack_latency, Some(Duration::from_secs(2)));
+
+		// We only consider the peer slow once it was slow to acknowledge several updates in a row.
+		let slow_latency = SLOW_COMMITMENT_ACK_LATENCY + Duration::from_secs(2);
+		for _ in 1..SLOW_COMMITMENT_ACK_WARN_THRESHOLD {
+			assert!(!ack_after(&mut tracker, slow_latency));
+		}
+		assert!(ack_after(&mut tracker, slow_latency));
+		assert!(tracker.timings().commitment_ack_latency.unwrap() > Duration::from_secs(2));
+		assert!(tracker.pending_commitment_acks.is_empty());
+	}
+
+	struct RecordingUnknownMessageObserver(Arc<Mutex<Vec<(PublicKey, u16, Vec<u8>)>>>);
+
+	impl UnknownMessageObserver for RecordingUnknownMessageObserver {
+		fn observe_unknown_message(
+			&self, their_node_id: PublicKey, message_type: u16, payload: &[u8],
+		) {
+			self.0.lock().unwrap().push((their_node_id, message_type, payload.to_vec()));
+		}
+	}
+
+	#[test]
+	fn test_unknown_message_observer() {
+		// Test that unknown odd messages are passed to the `UnknownMessageObserver`, subject to the
+		// per-peer rate cap which is reset on each timer tick.
+		let cfgs = create_peermgr_cfgs(2);
+		let peers = create_network(2, &cfgs);
+		let (fd_a, mut fd_b) = establish_connection(&peers[0], &peers[1]);
+		let id_a = peers[0].node_signer.get_node_id(Recipient::Node).unwrap();
+
+		let observed = Arc::new(Mutex::new(Vec::new()));
+		peers[1].set_unknown_message_observer(
+			RecordingUnknownMessageObserver(Arc::clone(&observed)),
+			2,
+		);
+
+		let send_unknown_message = |fd_b: &mut FileDescriptor, message_type: u16| {
+			let mut encoded_msg = message_type.to_be_bytes().to_vec();
+			encoded_msg.extend_from_slice(&[42; 3]);
+			let msg_bytes = {
+				let peers_lock = peers[0].peers.read().unwrap();
+				let mut peer = peers_lock.get(&fd_a).unwrap().lock().unwrap();
+				peer.channel_encryptor.encrypt_buffer(MessageBuf::from_encoded(&encoded_msg))
+			};
+			peers[1].read_event(fd_b, &msg_bytes)
+		};
+
+		for _ in 0..3 {
+			send_unknown_message(&mut fd_b, 65001).unwrap();
+		}
+		assert_eq!(*observed.lock().unwrap(), vec![(id_a, 65001, vec![42; 3]); 2]);
+
+		peers[1].timer_tick_occurred();
+		send_unknown_message(&mut fd_b, 65003).unwrap();
+		assert_eq!(observed.lock().unwrap().len(), 3);
+		assert_eq!(observed.lock().unwrap()[2], (id_a, 65003, vec![42; 3]));
+
+		// Unknown even messages are never observed and cause a disconnect.
+		assert!(send_unknown_message(&mut fd_b, 65002).is_err());
+		assert_eq!(observed.lock().unwrap().len(), 3);
+	}
+
 	#[test]
 	fn test_non_init_first_msg() {
 		// Simple test of the first message received over a connection being something other than
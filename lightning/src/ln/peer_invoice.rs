@@ -0,0 +1,640 @@
+// This file is Copyright its original authors, visible in version control
+// history.
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! An optional protocol for requesting BOLT 11 invoices directly from a connected peer using
+//! custom messages sent over the existing BOLT 8 connection.
+//!
+//! This is useful when both parties are already connected, such as a point-of-sale terminal and
+//! the wallet paying it or an LSP billing its clients, as no web server or other out-of-band
+//! channel is needed to exchange the invoice.
+//!
+//! To use it, set a [`PeerInvoiceMessageHandler`] as the [`CustomMessageHandler`] of your
+//! [`PeerManager`] (possibly composed with other handlers, e.g., using the
+//! `lightning-custom-message` crate). Invoices may then be requested from peers with
+//! [`PeerInvoiceMessageHandler::request_invoice`], with the outcome of each request returned by
+//! [`PeerInvoiceMessageHandler::get_and_clear_pending_results`]. Requests received from peers are
+//! answered using the provided [`PeerInvoiceRequestHandler`].
+//!
+//! All message types are odd, so peers not supporting the protocol will simply ignore requests,
+//! which will then time out.
+//!
+//! [`PeerManager`]: crate::ln::peer_handler::PeerManager
+
+use bitcoin::secp256k1::PublicKey;
+
+use lightning_invoice::Bolt11Invoice;
+
+use crate::io;
+use crate::ln::msgs::{DecodeError, ErrorAction, Init, LightningError};
+use crate::ln::peer_handler::{CustomMessageHandler, IgnoringMessageHandler};
+use crate::ln::wire::{CustomMessageReader, Type};
+use crate::sync::Mutex;
+use crate::types::features::{InitFeatures, NodeFeatures};
+use crate::types::string::UntrustedString;
+use crate::util::logger::Level;
+use crate::util::ser::{LengthLimitedRead, LengthReadable, Writeable, Writer};
+
+use core::ops::Deref;
+
+use crate::prelude::*;
+
+/// The message type of a [`PeerInvoiceRequest`].
+pub const PEER_INVOICE_REQUEST_TYPE: u16 = 44_001;
+/// The message type of a [`PeerInvoice`].
+pub const PEER_INVOICE_TYPE: u16 = 44_003;
+/// The message type of a [`PeerInvoiceError`].
+pub const PEER_INVOICE_ERROR_TYPE: u16 = 44_005;
+
+/// A request for the recipient to respond with a BOLT 11 invoice.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct PeerInvoiceRequest {
+	/// An identifier chosen by the sender, echoed back in the response.
+	pub request_id: u64,
+	/// The amount the invoice is requested for, if any.
+	pub amount_msats: Option<u64>,
+	/// A description of what the invoice is for, to be included in the invoice.
+	pub description: UntrustedString,
+}
+
+impl_writeable_msg!(PeerInvoiceRequest, {
+	request_id,
+	description,
+}, {
+	(1, amount_msats, option),
+});
+
+/// A successful response to a [`PeerInvoiceRequest`].
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct PeerInvoice {
+	/// The `request_id` of the [`PeerInvoiceRequest`] being responded to.
+	pub request_id: u64,
+	/// The requested BOLT 11 invoice, encoded as a string.
+	pub invoice: String,
+}
+
+impl_writeable_msg!(PeerInvoice, {
+	request_id,
+	invoice,
+}, {});
+
+/// An unsuccessful response to a [`PeerInvoiceRequest`].
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct PeerInvoiceError {
+	/// The `request_id` of the [`PeerInvoiceRequest`] being responded to.
+	pub request_id: u64,
+	/// A description of why no invoice was provided.
+	pub error: UntrustedString,
+}
+
+impl_writeable_msg!(PeerInvoiceError, {
+	request_id,
+	error,
+}, {});
+
+/// A message of the peer invoice protocol.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub enum PeerInvoiceMessage {
+	/// A request for an invoice.
+	Request(PeerInvoiceRequest),
+	/// A response containing the requested invoice.
+	Invoice(PeerInvoice),
+	/// A response indicating no invoice will be provided.
+	Error(PeerInvoiceError),
+}
+
+impl Type for PeerInvoiceMessage {
+	fn type_id(&self) -> u16 {
+		match self {
+			PeerInvoiceMessage::Request(_) => PEER_INVOICE_REQUEST_TYPE,
+			PeerInvoiceMessage::Invoice(_) => PEER_INVOICE_TYPE,
+			PeerInvoiceMessage::Error(_) => PEER_INVOICE_ERROR_TYPE,
+		}
+	}
+}
+
+impl Writeable for PeerInvoiceMessage {
+	fn write<W: Writer>(&self, w: &mut W) -> Result<(), io::Error> {
+		match self {
+			PeerInvoiceMessage::Request(msg) => msg.write(w),
+			PeerInvoiceMessage::Invoice(msg) => msg.write(w),
+			PeerInvoiceMessage::Error(msg) => msg.write(w),
+		}
+	}
+}
+
+/// Handles [`PeerInvoiceRequest`]s received from peers, creating the requested invoices.
+pub trait PeerInvoiceRequestHandler {
+	/// Returns an invoice for the request received from `counterparty_node_id`, or an error
+	/// describing why the request was rejected, which is sent back to the peer.
+	///
+	/// The invoice will typically be created using
+	/// [`ChannelManager::create_bolt11_invoice`], and must be signed by our node's key, as
+	/// requesters check its payee matches the node they requested it from. If `amount_msats` is
+	/// set, the invoice must be for exactly that amount.
+	///
+	/// [`ChannelManager::create_bolt11_invoice`]: crate::ln::channelmanager::ChannelManager::create_bolt11_invoice
+	fn handle_invoice_request(
+		&self, counterparty_node_id: PublicKey, amount_msats: Option<u64>,
+		description: &UntrustedString,
+	) -> Result<Bolt11Invoice, String>;
+}
+
+impl PeerInvoiceRequestHandler for IgnoringMessageHandler {
+	fn handle_invoice_request(
+		&self, _counterparty_node_id: PublicKey, _amount_msats: Option<u64>,
+		_description: &UntrustedString,
+	) -> Result<Bolt11Invoice, String> {
+		Err("Invoice requests are not supported".to_owned())
+	}
+}
+
+/// The outcome of a request made with [`PeerInvoiceMessageHandler::request_invoice`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PeerInvoiceResult {
+	/// The peer responded with a valid invoice.
+	Received {
+		/// The peer the invoice was requested from.
+		counterparty_node_id: PublicKey,
+		/// The identifier returned by [`PeerInvoiceMessageHandler::request_invoice`].
+		request_id: u64,
+		/// The received invoice, whose payee and amount have been checked to match the request.
+		invoice: Bolt11Invoice,
+	},
+	/// No valid invoice was received.
+	Failed {
+		/// The peer the invoice was requested from.
+		counterparty_node_id: PublicKey,
+		/// The identifier returned by [`PeerInvoiceMessageHandler::request_invoice`].
+		request_id: u64,
+		/// Why no invoice was received.
+		reason: PeerInvoiceFailureReason,
+	},
+}
+
+/// Why a request made with [`PeerInvoiceMessageHandler::request_invoice`] failed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PeerInvoiceFailureReason {
+	/// The peer rejected the request with the given error.
+	Rejected(UntrustedString),
+	/// The peer responded with an invoice which could not be parsed, isn't payable to the peer,
+	/// or is for a different amount than requested.
+	InvalidInvoice,
+	/// The peer didn't respond before the request timed out.
+	TimedOut,
+	/// The peer disconnected before responding.
+	PeerDisconnected,
+}
+
+struct PendingRequest {
+	counterparty_node_id: PublicKey,
+	amount_msats: Option<u64>,
+	remaining_ticks: u32,
+}
+
+struct PeerInvoiceState {
+	next_request_id: u64,
+	connected_peers: HashSet<PublicKey>,
+	pending_requests: HashMap<u64, PendingRequest>,
+	pending_messages: Vec<(PublicKey, PeerInvoiceMessage)>,
+	pending_results: Vec<PeerInvoiceResult>,
+}
+
+/// A [`CustomMessageHandler`] implementing the peer invoice protocol, both requesting invoices
+/// from peers and responding to their requests using a [`PeerInvoiceRequestHandler`].
+///
+/// See the [module-level documentation] for usage.
+///
+/// [module-level documentation]: crate::ln::peer_invoice
+pub struct PeerInvoiceMessageHandler<H: Deref>
+where
+	H::Target: PeerInvoiceRequestHandler,
+{
+	request_handler: H,
+	state: Mutex<PeerInvoiceState>,
+}
+
+impl<H: Deref> PeerInvoiceMessageHandler<H>
+where
+	H::Target: PeerInvoiceRequestHandler,
+{
+	/// Constructs a new handler answering requests from peers using `request_handler`.
+	///
+	/// Nodes which only request invoices may use an [`IgnoringMessageHandler`], which rejects all
+	/// requests.
+	pub fn new(request_handler: H) -> Self {
+		Self {
+			request_handler,
+			state: Mutex::new(PeerInvoiceState {
+				next_request_id: 0,
+				connected_peers: new_hash_set(),
+				pending_requests: new_hash_map(),
+				pending_messages: Vec::new(),
+				pending_results: Vec::new(),
+			}),
+		}
+	}
+
+	/// Requests an invoice from the connected peer `counterparty_node_id`, returning an identifier
+	/// for the request.
+	///
+	/// The outcome of the request will be returned by [`Self::get_and_clear_pending_results`],
+	/// either once the peer responds, it disconnects, or `timeout_ticks` calls to
+	/// [`Self::timer_tick_occurred`] have passed, whichever comes first.
+	///
+	/// Returns `Err(())` if we are not connected to the peer.
+	pub fn request_invoice(
+		&self, counterparty_node_id: PublicKey, amount_msats: Option<u64>, description: String,
+		timeout_ticks: u32,
+	) -> Result<u64, ()> {
+		let mut state = self.state.lock().unwrap();
+		if !state.connected_peers.contains(&counterparty_node_id) {
+			return Err(());
+		}
+
+		let request_id = state.next_request_id;
+		state.next_request_id += 1;
+		state.pending_requests.insert(
+			request_id,
+			PendingRequest { counterparty_node_id, amount_msats, remaining_ticks: timeout_ticks },
+		);
+
+		let request = PeerInvoiceRequest {
+			request_id,
+			amount_msats,
+			description: UntrustedString(description),
+		};
+		state.pending_messages.push((counterparty_node_id, PeerInvoiceMessage::Request(request)));
+		Ok(request_id)
+	}
+
+	/// Returns the outcome of any requests made with [`Self::request_invoice`] which have
+	/// completed since the last call, clearing them in the process.
+	pub fn get_and_clear_pending_results(&self) -> Vec<PeerInvoiceResult> {
+		core::mem::take(&mut self.state.lock().unwrap().pending_results)
+	}
+
+	/// Times out pending requests. Should be called roughly once per minute.
+	pub fn timer_tick_occurred(&self) {
+		let state = &mut *self.state.lock().unwrap();
+		let pending_results = &mut state.pending_results;
+		state.pending_requests.retain(|request_id, request| {
+			if request.remaining_ticks > 0 {
+				request.remaining_ticks -= 1;
+				return true;
+			}
+			pending_results.push(PeerInvoiceResult::Failed {
+				counterparty_node_id: request.counterparty_node_id,
+				request_id: *request_id,
+				reason: PeerInvoiceFailureReason::TimedOut,
+			});
+			false
+		});
+	}
+
+	fn handle_invoice_response(
+		&self, state: &mut PeerInvoiceState, sender_node_id: PublicKey, request_id: u64,
+		result: Result<String, UntrustedString>,
+	) -> Result<(), LightningError> {
+		match state.pending_requests.get(&request_id) {
+			Some(request) if request.counterparty_node_id == sender_node_id => {},
+			_ => {
+				return Err(LightningError {
+					err: format!(
+						"Received a response to an unknown invoice request {}",
+						request_id
+					),
+					action: ErrorAction::IgnoreAndLog(Level::Debug),
+				});
+			},
+		}
+		let request = state.pending_requests.remove(&request_id).expect("Checked above");
+
+		let result = match result {
+			Ok(invoice) => match invoice.parse::<Bolt11Invoice>() {
+				Ok(invoice)
+					if invoice.get_payee_pub_key() == sender_node_id
+						&& (request.amount_msats.is_none()
+							|| invoice.amount_milli_satoshis() == request.amount_msats) =>
+				{
+					PeerInvoiceResult::Received {
+						counterparty_node_id: sender_node_id,
+						request_id,
+						invoice,
+					}
+				},
+				_ => PeerInvoiceResult::Failed {
+					counterparty_node_id: sender_node_id,
+					request_id,
+					reason: PeerInvoiceFailureReason::InvalidInvoice,
+				},
+			},
+			Err(error) => PeerInvoiceResult::Failed {
+				counterparty_node_id: sender_node_id,
+				request_id,
+				reason: PeerInvoiceFailureReason::Rejected(error),
+			},
+		};
+		state.pending_results.push(result);
+		Ok(())
+	}
+}
+
+impl<H: Deref> CustomMessageReader for PeerInvoiceMessageHandler<H>
+where
+	H::Target: PeerInvoiceRequestHandler,
+{
+	type CustomMessage = PeerInvoiceMessage;
+
+	fn read<R: LengthLimitedRead>(
+		&self, message_type: u16, buffer: &mut R,
+	) -> Result<Option<PeerInvoiceMessage>, DecodeError> {
+		match message_type {
+			PEER_INVOICE_REQUEST_TYPE => Ok(Some(PeerInvoiceMessage::Request(
+				LengthReadable::read_from_fixed_length_buffer(buffer)?,
+			))),
+			PEER_INVOICE_TYPE => Ok(Some(PeerInvoiceMessage::Invoice(
+				LengthReadable::read_from_fixed_length_buffer(buffer)?,
+			))),
+			PEER_INVOICE_ERROR_TYPE => Ok(Some(PeerInvoiceMessage::Error(
+				LengthReadable::read_from_fixed_length_buffer(buffer)?,
+			))),
+			_ => Ok(None),
+		}
+	}
+}
+
+impl<H: Deref> CustomMessageHandler for PeerInvoiceMessageHandler<H>
+where
+	H::Target: PeerInvoiceRequestHandler,
+{
+	fn handle_custom_message(
+		&self, msg: PeerInvoiceMessage, sender_node_id: PublicKey,
+	) -> Result<(), LightningError> {
+		match msg {
+			PeerInvoiceMessage::Request(request) => {
+				let response = match self.request_handler.handle_invoice_request(
+					sender_node_id,
+					request.amount_msats,
+					&request.description,
+				) {
+					Ok(invoice) => PeerInvoiceMessage::Invoice(PeerInvoice {
+						request_id: request.request_id,
+						invoice: invoice.to_string(),
+					}),
+					Err(error) => PeerInvoiceMessage::Error(PeerInvoiceError {
+						request_id: request.request_id,
+						error: UntrustedString(error),
+					}),
+				};
+				self.state.lock().unwrap().pending_messages.push((sender_node_id, response));
+				Ok(())
+			},
+			PeerInvoiceMessage::Invoice(response) => {
+				let mut state = self.state.lock().unwrap();
+				let result = Ok(response.invoice);
+				self.handle_invoice_response(
+					&mut state,
+					sender_node_id,
+					response.request_id,
+					result,
+				)
+			},
+			PeerInvoiceMessage::Error(response) => {
+				let mut state = self.state.lock().unwrap();
+				let result = Err(response.error);
+				self.handle_invoice_response(
+					&mut state,
+					sender_node_id,
+					response.request_id,
+					result,
+				)
+			},
+		}
+	}
+
+	fn get_and_clear_pending_msg(&self) -> Vec<(PublicKey, PeerInvoiceMessage)> {
+		core::mem::take(&mut self.state.lock().unwrap().pending_messages)
+	}
+
+	fn peer_disconnected(&self, their_node_id: PublicKey) {
+		let state = &mut *self.state.lock().unwrap();
+		state.connected_peers.remove(&their_node_id);
+		let pending_results = &mut state.pending_results;
+		state.pending_requests.retain(|request_id, request| {
+			if request.counterparty_node_id != their_node_id {
+				return true;
+			}
+			pending_results.push(PeerInvoiceResult::Failed {
+				counterparty_node_id: their_node_id,
+				request_id: *request_id,
+				reason: PeerInvoiceFailureReason::PeerDisconnected,
+			});
+			false
+		});
+	}
+
+	fn peer_connected(
+		&self, their_node_id: PublicKey, _msg: &Init, _inbound: bool,
+	) -> Result<(), ()> {
+		self.state.lock().unwrap().connected_peers.insert(their_node_id);
+		Ok(())
+	}
+
+	fn provided_node_features(&self) -> NodeFeatures {
+		NodeFeatures::empty()
+	}
+
+	fn provided_init_features(&self, _their_node_id: PublicKey) -> InitFeatures {
+		InitFeatures::empty()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use bitcoin::hashes::sha256::Hash as Sha256;
+	use bitcoin::hashes::Hash;
+	use bitcoin::secp256k1::{Secp256k1, SecretKey};
+
+	use lightning_invoice::{Currency, InvoiceBuilder};
+
+	use crate::types::payment::PaymentSecret;
+
+	use core::time::Duration;
+
+	struct TestRequestHandler {
+		node_secret: SecretKey,
+	}
+
+	impl PeerInvoiceRequestHandler for TestRequestHandler {
+		fn handle_invoice_request(
+			&self, _counterparty_node_id: PublicKey, amount_msats: Option<u64>,
+			description: &UntrustedString,
+		) -> Result<Bolt11Invoice, String> {
+			if amount_msats == Some(0) {
+				return Err("Zero-amount invoices are not supported".to_owned());
+			}
+			let secp_ctx = Secp256k1::new();
+			let mut builder = InvoiceBuilder::new(Currency::Regtest)
+				.description(description.0.clone())
+				.payment_hash(Sha256::hash(&[42; 32]))
+				.payment_secret(PaymentSecret([43; 32]))
+				.duration_since_epoch(Duration::from_secs(1_700_000_000))
+				.min_final_cltv_expiry_delta(144);
+			// Respond with an invoice for twice the requested amount when asked for 1 sat.
+			let amount_msats = amount_msats.map(|amt| if amt == 1_000 { 2_000 } else { amt });
+			if let Some(amount_msats) = amount_msats {
+				builder = builder.amount_milli_satoshis(amount_msats);
+			}
+			Ok(builder
+				.build_signed(|hash| secp_ctx.sign_ecdsa_recoverable(hash, &self.node_secret))
+				.unwrap())
+		}
+	}
+
+	fn pubkey(secret: &SecretKey) -> PublicKey {
+		PublicKey::from_secret_key(&Secp256k1::new(), secret)
+	}
+
+	fn init() -> Init {
+		Init { features: InitFeatures::empty(), networks: None, remote_network_address: None }
+	}
+
+	/// Delivers all pending messages from `from` to `to`, encoding and decoding each one.
+	fn deliver<A: Deref, B: Deref>(
+		from: &PeerInvoiceMessageHandler<A>, from_node_id: PublicKey,
+		to: &PeerInvoiceMessageHandler<B>,
+	) -> Vec<Result<(), LightningError>>
+	where
+		A::Target: PeerInvoiceRequestHandler,
+		B::Target: PeerInvoiceRequestHandler,
+	{
+		from.get_and_clear_pending_msg()
+			.into_iter()
+			.map(|(_, msg)| {
+				let encoded = msg.encode();
+				let decoded = to.read(msg.type_id(), &mut &encoded[..]).unwrap().unwrap();
+				assert_eq!(decoded, msg);
+				to.handle_custom_message(decoded, from_node_id)
+			})
+			.collect()
+	}
+
+	#[test]
+	fn requests_invoice_from_peer() {
+		let payer_secret = SecretKey::from_slice(&[1; 32]).unwrap();
+		let payee_secret = SecretKey::from_slice(&[2; 32]).unwrap();
+		let (payer_id, payee_id) = (pubkey(&payer_secret), pubkey(&payee_secret));
+
+		let payer = PeerInvoiceMessageHandler::new(&IgnoringMessageHandler {});
+		let request_handler = TestRequestHandler { node_secret: payee_secret };
+		let payee = PeerInvoiceMessageHandler::new(&request_handler);
+
+		// Requests can only be made to connected peers.
+		assert_eq!(payer.request_invoice(payee_id, Some(50_000), "coffee".to_owned(), 1), Err(()));
+		payer.peer_connected(payee_id, &init(), false).unwrap();
+		payee.peer_connected(payer_id, &init(), true).unwrap();
+
+		let request_id =
+			payer.request_invoice(payee_id, Some(50_000), "coffee".to_owned(), 1).unwrap();
+		assert!(deliver(&payer, payer_id, &payee).iter().all(|res| res.is_ok()));
+		assert!(deliver(&payee, payee_id, &payer).iter().all(|res| res.is_ok()));
+
+		match &payer.get_and_clear_pending_results()[..] {
+			[PeerInvoiceResult::Received { counterparty_node_id, request_id: id, invoice }] => {
+				assert_eq!(*counterparty_node_id, payee_id);
+				assert_eq!(*id, request_id);
+				assert_eq!(invoice.amount_milli_satoshis(), Some(50_000));
+			},
+			results => panic!("Unexpected results: {:?}", results),
+		}
+
+		// A duplicate response is ignored.
+		let request_id = payer.request_invoice(payee_id, None, "tip".to_owned(), 1).unwrap();
+		deliver(&payer, payer_id, &payee);
+		let responses = payee.get_and_clear_pending_msg();
+		for (_, response) in responses.iter().chain(responses.iter()) {
+			payee.state.lock().unwrap().pending_messages.push((payer_id, response.clone()));
+		}
+		let results = deliver(&payee, payee_id, &payer);
+		assert!(results[0].is_ok() && results[1].is_err());
+		assert!(matches!(
+			&payer.get_and_clear_pending_results()[..],
+			[PeerInvoiceResult::Received { request_id: id, .. }] if *id == request_id
+		));
+	}
+
+	#[test]
+	fn fails_invalid_or_unanswered_requests() {
+		let payer_secret = SecretKey::from_slice(&[1; 32]).unwrap();
+		let payee_secret = SecretKey::from_slice(&[2; 32]).unwrap();
+		let (payer_id, payee_id) = (pubkey(&payer_secret), pubkey(&payee_secret));
+
+		let payer = PeerInvoiceMessageHandler::new(&IgnoringMessageHandler {});
+		// The payee signs invoices with a key other than its node key.
+		let request_handler = TestRequestHandler { node_secret: payer_secret };
+		let payee = PeerInvoiceMessageHandler::new(&request_handler);
+		payer.peer_connected(payee_id, &init(), false).unwrap();
+		payee.peer_connected(payer_id, &init(), true).unwrap();
+
+		let check_failure = |request_id, reason| {
+			let expected =
+				PeerInvoiceResult::Failed { counterparty_node_id: payee_id, request_id, reason };
+			assert_eq!(payer.get_and_clear_pending_results(), vec![expected]);
+		};
+
+		let request_id = payer.request_invoice(payee_id, Some(0), "zero".to_owned(), 1).unwrap();
+		deliver(&payer, payer_id, &payee);
+		deliver(&payee, payee_id, &payer);
+		let error = UntrustedString("Zero-amount invoices are not supported".to_owned());
+		check_failure(request_id, PeerInvoiceFailureReason::Rejected(error));
+
+		let request_id =
+			payer.request_invoice(payee_id, None, "wrong payee".to_owned(), 1).unwrap();
+		deliver(&payer, payer_id, &payee);
+		deliver(&payee, payee_id, &payer);
+		check_failure(request_id, PeerInvoiceFailureReason::InvalidInvoice);
+
+		// Requests time out once the given number of ticks have passed.
+		let request_id = payer.request_invoice(payee_id, None, "lost".to_owned(), 1).unwrap();
+		payer.get_and_clear_pending_msg();
+		payer.timer_tick_occurred();
+		assert!(payer.get_and_clear_pending_results().is_empty());
+		payer.timer_tick_occurred();
+		check_failure(request_id, PeerInvoiceFailureReason::TimedOut);
+
+		let request_id = payer.request_invoice(payee_id, None, "offline".to_owned(), 1).unwrap();
+		payer.peer_disconnected(payee_id);
+		check_failure(request_id, PeerInvoiceFailureReason::PeerDisconnected);
+	}
+
+	#[test]
+	fn checks_requested_amount() {
+		let payer_secret = SecretKey::from_slice(&[1; 32]).unwrap();
+		let payee_secret = SecretKey::from_slice(&[2; 32]).unwrap();
+		let (payer_id, payee_id) = (pubkey(&payer_secret), pubkey(&payee_secret));
+
+		let payer = PeerInvoiceMessageHandler::new(&IgnoringMessageHandler {});
+		let request_handler = TestRequestHandler { node_secret: payee_secret };
+		let payee = PeerInvoiceMessageHandler::new(&request_handler);
+		payer.peer_connected(payee_id, &init(), false).unwrap();
+		payee.peer_connected(payer_id, &init(), true).unwrap();
+
+		let request_id = payer.request_invoice(payee_id, Some(1_000), "sat".to_owned(), 1).unwrap();
+		deliver(&payer, payer_id, &payee);
+		deliver(&payee, payee_id, &payer);
+		let expected = PeerInvoiceResult::Failed {
+			counterparty_node_id: payee_id,
+			request_id,
+			reason: PeerInvoiceFailureReason::InvalidInvoice,
+		};
+		assert_eq!(payer.get_and_clear_pending_results(), vec![expected]);
+	}
+}
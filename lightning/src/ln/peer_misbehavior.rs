@@ -0,0 +1,371 @@
+// This file is Copyright its original authors, visible in version control
+// history.
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Types for tracking misbehavior by our counterparties.
+//!
+//! The [`ChannelManager`] records a [`MisbehaviorIncident`] each time a peer sends us something we
+//! reject with a warning or error, as well as when a channel has to be force-closed because the
+//! peer failed to resolve an HTLC in time. The recorded incidents can be fetched with
+//! [`ChannelManager::list_peer_misbehavior`] and used to decide whether to close channels with, or
+//! stop accepting connections from, a given peer.
+//!
+//! Additionally, if [`UserConfig::quarantine_after_message_failures`] is set, a peer which sends
+//! us that many consecutive messages we fail to handle is quarantined, i.e., disconnected and
+//! refused reconnection until [`ChannelManager::release_quarantined_peer`] is called. This
+//! isolates the peer (and its channels) if handling its messages fails persistently, e.g., due to
+//! a local persistence bug, rather than repeating the failure on every reconnection.
+//!
+//! Finally, a [`PeerReputation`] set via [`ChannelManager::set_peer_reputation`] may tighten the
+//! number of HTLC slots and the value in flight a peer may use towards us based on its history,
+//! e.g., to limit the damage a peer which previously held HTLCs for long can do by jamming our
+//! channels.
+//!
+//! [`ChannelManager`]: crate::ln::channelmanager::ChannelManager
+//! [`ChannelManager::list_peer_misbehavior`]: crate::ln::channelmanager::ChannelManager::list_peer_misbehavior
+//! [`ChannelManager::release_quarantined_peer`]: crate::ln::channelmanager::ChannelManager::release_quarantined_peer
+//! [`ChannelManager::set_peer_reputation`]: crate::ln::channelmanager::ChannelManager::set_peer_reputation
+//! [`UserConfig::quarantine_after_message_failures`]: crate::util::config::UserConfig::quarantine_after_message_failures
+
+use bitcoin::secp256k1::PublicKey;
+
+use crate::ln::types::ChannelId;
+
+use crate::prelude::*;
+
+use core::time::Duration;
+
+/// The maximum number of [`MisbehaviorIncident`]s we store for any one peer. Once reached, the
+/// oldest incident is dropped each time a new one is recorded.
+pub const MAX_INCIDENTS_PER_PEER: usize = 64;
+
+/// The maximum number of peers for which we store [`MisbehaviorIncident`]s. Once reached, the
+/// peer whose most recent incident is the oldest is forgotten to make room for a new one.
+pub const MAX_TRACKED_PEERS: usize = 1024;
+
+/// The kind of misbehavior recorded in a [`MisbehaviorIncident`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MisbehaviorKind {
+	/// The peer sent an invalid signature or per-commitment secret, e.g. in a `funding_created`,
+	/// `commitment_signed` or `revoke_and_ack`.
+	InvalidSignature,
+	/// The peer sent a `channel_reestablish` which refers to a commitment state which is
+	/// inconsistent with our own, or an invalid per-commitment secret for it.
+	StaleReestablish,
+	/// The peer tried to set a feerate it is not allowed to set, e.g. because it is not the
+	/// channel's funder or the channel uses zero-fee commitments.
+	///
+	/// Note that a feerate we reject merely because it is below our fee estimates is not
+	/// considered misbehavior, as it may be due to an honest disagreement between fee estimators.
+	FeeAbuse,
+	/// A channel was force-closed because the peer did not resolve an HTLC we offered to them
+	/// before it expired.
+	HTLCHold,
+	/// The peer sent some other message which we rejected with a warning or error, including
+	/// messages for unknown channels.
+	ProtocolViolation,
+}
+
+/// A single instance of misbehavior by a peer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MisbehaviorIncident {
+	/// The kind of misbehavior.
+	pub kind: MisbehaviorKind,
+	/// The channel the misbehavior relates to, if any.
+	pub channel_id: Option<ChannelId>,
+	/// The time at which the incident was recorded, as a duration since the Unix epoch.
+	///
+	/// In `no-std` builds this is based on the highest block timestamp we've seen rather than the
+	/// current time.
+	pub timestamp: Duration,
+	/// A human-readable description of the incident, generally the error we sent to the peer.
+	pub description: String,
+}
+
+/// Tracks [`MisbehaviorIncident`]s per peer, bounded by [`MAX_INCIDENTS_PER_PEER`] and
+/// [`MAX_TRACKED_PEERS`].
+pub(crate) struct MisbehaviorLedger {
+	incidents: HashMap<PublicKey, VecDeque<MisbehaviorIncident>>,
+}
+
+impl MisbehaviorLedger {
+	pub(crate) fn new() -> Self {
+		Self { incidents: new_hash_map() }
+	}
+
+	pub(crate) fn record(
+		&mut self, counterparty_node_id: PublicKey, incident: MisbehaviorIncident,
+	) {
+		if !self.incidents.contains_key(&counterparty_node_id)
+			&& self.incidents.len() >= MAX_TRACKED_PEERS
+		{
+			let stalest_peer = self
+				.incidents
+				.iter()
+				.min_by_key(|(_, incidents)| incidents.back().map(|incident| incident.timestamp))
+				.map(|(node_id, _)| *node_id);
+			if let Some(node_id) = stalest_peer {
+				self.incidents.remove(&node_id);
+			}
+		}
+
+		let incidents = self.incidents.entry(counterparty_node_id).or_insert_with(VecDeque::new);
+		if incidents.len() >= MAX_INCIDENTS_PER_PEER {
+			incidents.pop_front();
+		}
+		incidents.push_back(incident);
+	}
+
+	pub(crate) fn incidents_for_peer(
+		&self, counterparty_node_id: &PublicKey,
+	) -> Vec<MisbehaviorIncident> {
+		self.incidents
+			.get(counterparty_node_id)
+			.map(|incidents| incidents.iter().cloned().collect())
+			.unwrap_or_default()
+	}
+
+	pub(crate) fn list_peers(&self) -> Vec<(PublicKey, usize)> {
+		self.incidents.iter().map(|(node_id, incidents)| (*node_id, incidents.len())).collect()
+	}
+
+	pub(crate) fn clear_peer(&mut self, counterparty_node_id: &PublicKey) {
+		self.incidents.remove(counterparty_node_id);
+	}
+}
+
+/// A peer which was quarantined after sending us too many consecutive messages we failed to
+/// handle.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QuarantinedPeer {
+	/// The node id of the quarantined peer.
+	pub counterparty_node_id: PublicKey,
+	/// The channel the last failed message related to, if any.
+	pub channel_id: Option<ChannelId>,
+	/// The number of consecutive messages we failed to handle before quarantining the peer.
+	pub consecutive_failures: u8,
+	/// The error resulting from the last failed message.
+	pub last_error: String,
+	/// The time at which the peer was quarantined, as a duration since the Unix epoch.
+	///
+	/// In `no-std` builds this is based on the highest block timestamp we've seen rather than the
+	/// current time.
+	pub timestamp: Duration,
+}
+
+impl_writeable_tlv_based!(QuarantinedPeer, {
+	(0, counterparty_node_id, required),
+	(1, channel_id, option),
+	(2, consecutive_failures, required),
+	(4, last_error, required),
+	(6, timestamp, required),
+});
+
+/// Tracks consecutive message handling failures per peer and the peers quarantined as a result,
+/// bounded by [`MAX_TRACKED_PEERS`].
+pub(crate) struct MessageQuarantine {
+	consecutive_failures: HashMap<PublicKey, u8>,
+	quarantined: HashMap<PublicKey, QuarantinedPeer>,
+}
+
+impl MessageQuarantine {
+	pub(crate) fn new() -> Self {
+		Self::from_quarantined(Vec::new())
+	}
+
+	pub(crate) fn from_quarantined(quarantined_peers: Vec<QuarantinedPeer>) -> Self {
+		let mut quarantined = new_hash_map();
+		for peer in quarantined_peers {
+			quarantined.insert(peer.counterparty_node_id, peer);
+		}
+		Self { consecutive_failures: new_hash_map(), quarantined }
+	}
+
+	/// Records a failure to handle a message from the given peer, returning the resulting
+	/// [`QuarantinedPeer`] if the peer reached `threshold` consecutive failures.
+	pub(crate) fn record_failure(
+		&mut self, counterparty_node_id: PublicKey, channel_id: Option<ChannelId>,
+		last_error: String, timestamp: Duration, threshold: u8,
+	) -> Option<QuarantinedPeer> {
+		if threshold == 0 || self.quarantined.contains_key(&counterparty_node_id) {
+			return None;
+		}
+		if !self.consecutive_failures.contains_key(&counterparty_node_id)
+			&& self.consecutive_failures.len() >= MAX_TRACKED_PEERS
+		{
+			let evicted_peer = self.consecutive_failures.keys().next().copied();
+			if let Some(node_id) = evicted_peer {
+				self.consecutive_failures.remove(&node_id);
+			}
+		}
+
+		let failures = self.consecutive_failures.entry(counterparty_node_id).or_insert(0);
+		*failures = failures.saturating_add(1);
+		if *failures < threshold {
+			return None;
+		}
+
+		let consecutive_failures = *failures;
+		self.consecutive_failures.remove(&counterparty_node_id);
+		let peer = QuarantinedPeer {
+			counterparty_node_id,
+			channel_id,
+			consecutive_failures,
+			last_error,
+			timestamp,
+		};
+		self.quarantined.insert(counterparty_node_id, peer.clone());
+		Some(peer)
+	}
+
+	/// Records that a message from the given peer was handled successfully.
+	pub(crate) fn record_success(&mut self, counterparty_node_id: &PublicKey) {
+		self.consecutive_failures.remove(counterparty_node_id);
+	}
+
+	pub(crate) fn is_quarantined(&self, counterparty_node_id: &PublicKey) -> bool {
+		self.quarantined.contains_key(counterparty_node_id)
+	}
+
+	pub(crate) fn list_quarantined(&self) -> Vec<QuarantinedPeer> {
+		self.quarantined.values().cloned().collect()
+	}
+
+	/// Lifts the quarantine of the given peer, returning whether it was quarantined.
+	pub(crate) fn release(&mut self, counterparty_node_id: &PublicKey) -> bool {
+		self.quarantined.remove(counterparty_node_id).is_some()
+	}
+}
+
+/// Limits on the HTLCs a peer may have pending towards us in a channel, in addition to those
+/// negotiated when the channel was opened.
+///
+/// The limits are enforced locally by failing back HTLCs in excess of them once they've been
+/// irrevocably committed to the channel, rather than by renegotiating the channel parameters
+/// with the peer. Thus, they may be changed at any time and only take effect for HTLCs received
+/// after the change.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InboundHTLCLimits {
+	/// The maximum number of HTLCs the peer may have pending towards us, including the one being
+	/// accepted.
+	///
+	/// Limits above the `max_accepted_htlcs` negotiated at channel open have no effect.
+	pub max_accepted_htlcs: u16,
+	/// The maximum total value, in millisatoshis, of the HTLCs the peer may have pending towards
+	/// us, including the one being accepted.
+	///
+	/// Limits above the `max_htlc_value_in_flight_msat` negotiated at channel open have no
+	/// effect.
+	pub max_htlc_value_in_flight_msat: u64,
+}
+
+/// Decides which [`InboundHTLCLimits`] apply to a peer based on its reputation.
+///
+/// This is consulted by the [`ChannelManager`] each time it processes HTLCs a peer offered to us
+/// in a channel, allowing the limits to be tightened for peers with a history of misbehavior,
+/// e.g., holding HTLCs for long, and relaxed again as their reputation recovers.
+///
+/// [`ChannelManager`]: crate::ln::channelmanager::ChannelManager
+pub trait PeerReputation {
+	/// Returns the [`InboundHTLCLimits`] to apply to HTLCs offered by `counterparty_node_id` in the
+	/// channel with the given `channel_id`, or `None` if only the limits negotiated at channel open
+	/// should apply.
+	///
+	/// `incidents` are the [`MisbehaviorIncident`]s currently recorded for the peer, oldest first.
+	fn inbound_htlc_limits(
+		&self, counterparty_node_id: &PublicKey, channel_id: &ChannelId,
+		incidents: &[MisbehaviorIncident],
+	) -> Option<InboundHTLCLimits>;
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use bitcoin::secp256k1::{Secp256k1, SecretKey};
+
+	fn incident(kind: MisbehaviorKind, secs: u64) -> MisbehaviorIncident {
+		MisbehaviorIncident {
+			kind,
+			channel_id: None,
+			timestamp: Duration::from_secs(secs),
+			description: String::new(),
+		}
+	}
+
+	fn node_id(idx: u16) -> PublicKey {
+		let mut key = [42; 32];
+		key[..2].copy_from_slice(&idx.to_be_bytes());
+		PublicKey::from_secret_key(&Secp256k1::new(), &SecretKey::from_slice(&key).unwrap())
+	}
+
+	#[test]
+	fn ledger_bounds_incidents_per_peer() {
+		let mut ledger = MisbehaviorLedger::new();
+		let peer = node_id(0);
+		for i in 0..MAX_INCIDENTS_PER_PEER as u64 + 1 {
+			ledger.record(peer, incident(MisbehaviorKind::ProtocolViolation, i));
+		}
+		let incidents = ledger.incidents_for_peer(&peer);
+		assert_eq!(incidents.len(), MAX_INCIDENTS_PER_PEER);
+		assert_eq!(incidents[0].timestamp, Duration::from_secs(1));
+
+		ledger.clear_peer(&peer);
+		assert!(ledger.incidents_for_peer(&peer).is_empty());
+	}
+
+	#[test]
+	fn ledger_evicts_stalest_peer() {
+		let mut ledger = MisbehaviorLedger::new();
+		for i in 0..MAX_TRACKED_PEERS as u16 {
+			ledger.record(node_id(i), incident(MisbehaviorKind::FeeAbuse, 1_000 + i as u64));
+		}
+		// Refresh the first peer so that the second becomes the stalest.
+		ledger.record(node_id(0), incident(MisbehaviorKind::FeeAbuse, 10_000));
+
+		let new_peer = node_id(MAX_TRACKED_PEERS as u16);
+		ledger.record(new_peer, incident(MisbehaviorKind::HTLCHold, 10_001));
+		assert_eq!(ledger.list_peers().len(), MAX_TRACKED_PEERS);
+		assert!(ledger.incidents_for_peer(&node_id(1)).is_empty());
+		assert_eq!(ledger.incidents_for_peer(&node_id(0)).len(), 2);
+		assert_eq!(ledger.incidents_for_peer(&new_peer).len(), 1);
+	}
+
+	#[test]
+	fn quarantines_after_consecutive_failures() {
+		let mut quarantine = MessageQuarantine::new();
+		let peer = node_id(0);
+		let fail = |quarantine: &mut MessageQuarantine, threshold| {
+			quarantine.record_failure(peer, None, "err".to_owned(), Duration::ZERO, threshold)
+		};
+
+		// A threshold of zero disables quarantining.
+		for _ in 0..10 {
+			assert!(fail(&mut quarantine, 0).is_none());
+		}
+
+		// Successfully handled messages reset the failure count.
+		assert!(fail(&mut quarantine, 3).is_none());
+		assert!(fail(&mut quarantine, 3).is_none());
+		quarantine.record_success(&peer);
+		assert!(fail(&mut quarantine, 3).is_none());
+		assert!(fail(&mut quarantine, 3).is_none());
+		assert!(!quarantine.is_quarantined(&peer));
+
+		let quarantined = fail(&mut quarantine, 3).unwrap();
+		assert_eq!(quarantined.consecutive_failures, 3);
+		assert!(quarantine.is_quarantined(&peer));
+		assert_eq!(quarantine.list_quarantined(), vec![quarantined]);
+		assert!(fail(&mut quarantine, 3).is_none());
+
+		assert!(quarantine.release(&peer));
+		assert!(!quarantine.release(&peer));
+		assert!(!quarantine.is_quarantined(&peer));
+		assert!(fail(&mut quarantine, 3).is_none());
+	}
+}
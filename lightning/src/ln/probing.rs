@@ -0,0 +1,356 @@
+// This file is Copyright its original authors, visible in version control
+// history.
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! An optional module actively probing the network for liquidity.
+//!
+//! A [`Prober`] sends probes via [`ChannelManager::probe_for_capacity`], i.e., HTLCs with a
+//! payment hash nobody knows the preimage for, which are failed back by the final hop without
+//! being held. The results are fed into a [`ScoreUpdate`] via [`Prober::handle_event`], training
+//! the scorer ahead of any actual payments.
+//!
+//! As probes lock up liquidity while in flight and consume resources of the nodes along their
+//! paths, probing is limited in two ways:
+//!  * Probes are rate limited by a token bucket holding up to [`ProbingConfig::bucket_capacity`]
+//!    tokens, which is refilled with one token every [`ProbingConfig::refill_ticks`] calls to
+//!    [`Prober::timer_tick_occurred`].
+//!  * The total amount of all probes in flight may not exceed
+//!    [`ProbingConfig::max_in_flight_msat`].
+//!
+//! [`ChannelManager::probe_for_capacity`]: crate::ln::channelmanager::ChannelManager::probe_for_capacity
+
+use crate::events::Event;
+use crate::ln::channelmanager::{AChannelManager, PaymentId, ProbeSendFailure};
+use crate::routing::scoring::ScoreUpdate;
+use crate::sync::Mutex;
+use crate::types::payment::PaymentHash;
+
+use bitcoin::secp256k1::PublicKey;
+
+use core::ops::Deref;
+use core::time::Duration;
+
+use crate::prelude::*;
+
+/// Configuration for a [`Prober`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProbingConfig {
+	/// The maximum number of probes the [`Prober`]'s token bucket may hold, i.e., the number of
+	/// calls to [`Prober::probe`] which may be made in quick succession.
+	///
+	/// Default value: `5`
+	pub bucket_capacity: u16,
+	/// The number of calls to [`Prober::timer_tick_occurred`] after which a token is added to the
+	/// [`Prober`]'s bucket, if not full.
+	///
+	/// Default value: `1`, i.e., roughly one probe per minute
+	pub refill_ticks: u16,
+	/// The maximum total amount of all probes sent by the [`Prober`] which may be in flight at
+	/// once.
+	///
+	/// Default value: 100,000,000 msat
+	pub max_in_flight_msat: u64,
+}
+
+impl Default for ProbingConfig {
+	fn default() -> Self {
+		Self { bucket_capacity: 5, refill_ticks: 1, max_in_flight_msat: 100_000_000 }
+	}
+}
+
+/// An error when sending probes via a [`Prober`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProbingError {
+	/// No token was available in the [`Prober`]'s bucket. Probing may be retried after further
+	/// calls to [`Prober::timer_tick_occurred`].
+	RateLimited,
+	/// Sending the probes would exceed [`ProbingConfig::max_in_flight_msat`]. Probing may be
+	/// retried once probes currently in flight have resolved.
+	BudgetExceeded,
+	/// Sending the probes failed.
+	SendFailure(ProbeSendFailure),
+}
+
+/// The rate limiting and budget state of a [`Prober`].
+struct ProbingState {
+	tokens: u16,
+	ticks_until_refill: u16,
+	in_flight_msat: u64,
+	/// The amount reserved for each probe in flight.
+	in_flight_probes: HashMap<PaymentId, u64>,
+}
+
+impl ProbingState {
+	fn new(config: &ProbingConfig) -> Self {
+		Self {
+			tokens: config.bucket_capacity,
+			ticks_until_refill: config.refill_ticks,
+			in_flight_msat: 0,
+			in_flight_probes: new_hash_map(),
+		}
+	}
+
+	fn refill(&mut self, config: &ProbingConfig) {
+		if self.tokens >= config.bucket_capacity {
+			self.ticks_until_refill = config.refill_ticks;
+			return;
+		}
+		self.ticks_until_refill = self.ticks_until_refill.saturating_sub(1);
+		if self.ticks_until_refill == 0 {
+			self.tokens += 1;
+			self.ticks_until_refill = config.refill_ticks;
+		}
+	}
+
+	/// Reserves a token and `amount_msat` of the budget, if available.
+	fn reserve(&mut self, config: &ProbingConfig, amount_msat: u64) -> Result<(), ProbingError> {
+		if self.tokens == 0 {
+			return Err(ProbingError::RateLimited);
+		}
+		if self.in_flight_msat.saturating_add(amount_msat) > config.max_in_flight_msat {
+			return Err(ProbingError::BudgetExceeded);
+		}
+		self.tokens -= 1;
+		self.in_flight_msat += amount_msat;
+		Ok(())
+	}
+
+	/// Tracks the given probes as in flight, splitting the `amount_msat` reserved for them evenly,
+	/// and releases the reservation if no probes were sent.
+	fn track(&mut self, amount_msat: u64, probes: &[(PaymentHash, PaymentId)]) {
+		if probes.is_empty() {
+			self.in_flight_msat -= amount_msat;
+			return;
+		}
+		let share_msat = amount_msat / probes.len() as u64;
+		let remainder_msat = amount_msat % probes.len() as u64;
+		for (idx, (_, payment_id)) in probes.iter().enumerate() {
+			let reserved_msat = if idx == 0 { share_msat + remainder_msat } else { share_msat };
+			self.in_flight_probes.insert(*payment_id, reserved_msat);
+		}
+	}
+
+	/// Releases the budget reserved for the given probe, returning whether it was sent by us.
+	fn release(&mut self, payment_id: &PaymentId) -> bool {
+		match self.in_flight_probes.remove(payment_id) {
+			Some(reserved_msat) => {
+				self.in_flight_msat -= reserved_msat;
+				true
+			},
+			None => false,
+		}
+	}
+}
+
+/// Actively probes the network for liquidity via a [`ChannelManager`].
+///
+/// See the [module-level documentation] for details.
+///
+/// The [`Prober`]'s state is not persisted. Thus, any probes in flight on restart no longer count
+/// towards [`ProbingConfig::max_in_flight_msat`].
+///
+/// [`ChannelManager`]: crate::ln::channelmanager::ChannelManager
+/// [module-level documentation]: crate::ln::probing
+pub struct Prober<CM: Deref>
+where
+	CM::Target: AChannelManager,
+{
+	channel_manager: CM,
+	config: ProbingConfig,
+	state: Mutex<ProbingState>,
+}
+
+impl<CM: Deref> Prober<CM>
+where
+	CM::Target: AChannelManager,
+{
+	/// Constructs a new [`Prober`] sending probes via the given [`ChannelManager`].
+	///
+	/// [`ChannelManager`]: crate::ln::channelmanager::ChannelManager
+	pub fn new(channel_manager: CM, config: ProbingConfig) -> Self {
+		let state = Mutex::new(ProbingState::new(&config));
+		Self { channel_manager, config, state }
+	}
+
+	/// Probes whether `amount_msat` can be sent to the given `target` node, if allowed by the
+	/// configured rate limit and budget.
+	///
+	/// See [`ChannelManager::probe_for_capacity`] for details.
+	///
+	/// [`ChannelManager::probe_for_capacity`]: crate::ln::channelmanager::ChannelManager::probe_for_capacity
+	pub fn probe(
+		&self, target: PublicKey, amount_msat: u64,
+	) -> Result<Vec<(PaymentHash, PaymentId)>, ProbingError> {
+		let mut state = self.state.lock().unwrap();
+		state.reserve(&self.config, amount_msat)?;
+		match self.channel_manager.get_cm().probe_for_capacity(target, amount_msat) {
+			Ok(probes) => {
+				state.track(amount_msat, &probes);
+				Ok(probes)
+			},
+			Err(e) => {
+				// Without knowing the target's features we only ever probe a single path, so nothing
+				// was sent and we refund the token along with the budget.
+				state.tokens += 1;
+				state.in_flight_msat -= amount_msat;
+				Err(ProbingError::SendFailure(e))
+			},
+		}
+	}
+
+	/// Returns the total amount of all probes sent by this [`Prober`] which are still in flight.
+	pub fn in_flight_msat(&self) -> u64 {
+		self.state.lock().unwrap().in_flight_msat
+	}
+
+	/// Releases the budget reserved for a probe sent by this [`Prober`] once it resolved, without
+	/// updating any scorer. Returns whether `event` concerned such a probe.
+	///
+	/// This should be used rather than [`Self::handle_event`] if the scorer is already updated for
+	/// all probe events elsewhere, e.g., by `lightning-background-processor`.
+	pub fn probe_resolved(&self, event: &Event) -> bool {
+		match event {
+			Event::ProbeSuccessful { payment_id, .. } | Event::ProbeFailed { payment_id, .. } => {
+				self.state.lock().unwrap().release(payment_id)
+			},
+			_ => false,
+		}
+	}
+
+	/// Feeds the result of a probe sent by this [`Prober`] into the given `scorer` and releases
+	/// the budget reserved for it. Returns whether `event` concerned such a probe.
+	///
+	/// Should be called for every [`Event::ProbeSuccessful`] and [`Event::ProbeFailed`].
+	pub fn handle_event<S: ScoreUpdate>(
+		&self, event: &Event, scorer: &mut S, duration_since_epoch: Duration,
+	) -> bool {
+		if !self.probe_resolved(event) {
+			return false;
+		}
+		match event {
+			Event::ProbeSuccessful { path, .. } => {
+				scorer.probe_successful(path, duration_since_epoch);
+			},
+			Event::ProbeFailed { path, short_channel_id: Some(scid), .. } => {
+				scorer.probe_failed(path, *scid, duration_since_epoch);
+			},
+			_ => {},
+		}
+		true
+	}
+
+	/// Refills the [`Prober`]'s token bucket. Should be called roughly once per minute.
+	pub fn timer_tick_occurred(&self) {
+		self.state.lock().unwrap().refill(&self.config);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{Prober, ProbingConfig, ProbingError, ProbingState};
+
+	use crate::events::Event;
+	use crate::ln::channelmanager::PaymentId;
+	use crate::ln::functional_test_utils::*;
+	use crate::routing::router::Path;
+	use crate::routing::scoring::ScoreUpdate;
+	use crate::types::payment::PaymentHash;
+
+	use core::time::Duration;
+
+	#[derive(Default)]
+	struct RecordingScorer {
+		successful_probes: usize,
+		failed_probes: usize,
+	}
+
+	impl ScoreUpdate for RecordingScorer {
+		fn payment_path_failed(&mut self, _: &Path, _: u64, _: Duration) {}
+		fn payment_path_successful(&mut self, _: &Path, _: Duration) {}
+		fn probe_failed(&mut self, _: &Path, _: u64, _: Duration) {
+			self.failed_probes += 1;
+		}
+		fn probe_successful(&mut self, _: &Path, _: Duration) {
+			self.successful_probes += 1;
+		}
+		fn time_passed(&mut self, _: Duration) {}
+	}
+
+	#[test]
+	fn limits_probe_rate_and_budget() {
+		let config =
+			ProbingConfig { bucket_capacity: 2, refill_ticks: 2, max_in_flight_msat: 1_000 };
+		let mut state = ProbingState::new(&config);
+
+		// The budget is split between the probes sent and released as they resolve.
+		let probes = [
+			(PaymentHash([0; 32]), PaymentId([0; 32])),
+			(PaymentHash([1; 32]), PaymentId([1; 32])),
+		];
+		state.reserve(&config, 601).unwrap();
+		state.track(601, &probes);
+		assert_eq!(state.reserve(&config, 400), Err(ProbingError::BudgetExceeded));
+		assert!(state.release(&PaymentId([0; 32])));
+		assert!(!state.release(&PaymentId([0; 32])));
+		assert_eq!(state.in_flight_msat, 300);
+
+		// If no probes were sent, the budget is released right away.
+		state.reserve(&config, 700).unwrap();
+		state.track(700, &[]);
+		assert_eq!(state.in_flight_msat, 300);
+
+		// The bucket holds two tokens, so a third probe must wait for a refill.
+		assert_eq!(state.reserve(&config, 1), Err(ProbingError::RateLimited));
+		state.refill(&config);
+		assert_eq!(state.reserve(&config, 1), Err(ProbingError::RateLimited));
+		state.refill(&config);
+		assert_eq!(state.reserve(&config, 1), Ok(()));
+	}
+
+	#[test]
+	fn probes_for_capacity() {
+		let chanmon_cfgs = create_chanmon_cfgs(3);
+		let node_cfgs = create_node_cfgs(3, &chanmon_cfgs);
+		let node_chanmgrs = create_node_chanmgrs(3, &node_cfgs, &[None, None, None]);
+		let nodes = create_network(3, &node_cfgs, &node_chanmgrs);
+		create_announced_chan_between_nodes(&nodes, 0, 1);
+		create_announced_chan_between_nodes(&nodes, 1, 2);
+
+		let config =
+			ProbingConfig { bucket_capacity: 1, refill_ticks: 1, max_in_flight_msat: 1_000_000 };
+		let prober = Prober::new(nodes[0].node, config);
+		let node_c_id = nodes[2].node.get_our_node_id();
+
+		let probes = prober.probe(node_c_id, 100_000).unwrap();
+		assert_eq!(probes.len(), 1);
+		assert_eq!(prober.in_flight_msat(), 100_000);
+		assert_eq!(prober.probe(node_c_id, 100_000), Err(ProbingError::RateLimited));
+
+		send_probe_along_route(&nodes[0], &[(&[&nodes[1], &nodes[2]], probes[0].0)]);
+
+		let mut scorer = RecordingScorer::default();
+		let events = nodes[0].node.get_and_clear_pending_events();
+		assert_eq!(events.len(), 1);
+		match events[0] {
+			Event::ProbeSuccessful { payment_id, .. } => assert_eq!(payment_id, probes[0].1),
+			_ => panic!("Unexpected event"),
+		}
+		assert!(prober.handle_event(&events[0], &mut scorer, Duration::ZERO));
+		assert_eq!(scorer.successful_probes, 1);
+		assert_eq!(prober.in_flight_msat(), 0);
+
+		// Events for probes we don't know about are ignored.
+		assert!(!prober.handle_event(&events[0], &mut scorer, Duration::ZERO));
+		assert_eq!(scorer.successful_probes, 1);
+
+		// Once refilled, probes exceeding the budget are rejected.
+		prober.timer_tick_occurred();
+		assert_eq!(prober.probe(node_c_id, 1_000_001), Err(ProbingError::BudgetExceeded));
+		assert_eq!(scorer.failed_probes, 0);
+	}
+}
@@ -33,8 +33,14 @@ fn test_quiescence_tie() {
 
 	assert!(stfu_node_0.initiator && stfu_node_1.initiator);
 
-	assert!(nodes[0].node.exit_quiescence(&nodes[1].node.get_our_node_id(), &chan_id).unwrap());
-	assert!(nodes[1].node.exit_quiescence(&nodes[0].node.get_our_node_id(), &chan_id).unwrap());
+	assert!(nodes[0]
+		.node
+		.exit_quiescence_locally(&nodes[1].node.get_our_node_id(), &chan_id)
+		.unwrap());
+	assert!(nodes[1]
+		.node
+		.exit_quiescence_locally(&nodes[0].node.get_our_node_id(), &chan_id)
+		.unwrap());
 }
 
 #[test]
@@ -175,7 +181,7 @@ fn allow_shutdown_while_awaiting_quiescence(local_shutdown: bool) {
 	// Now that the state machine is no longer pending, and `closing_signed` is ready to be sent,
 	// make sure we're still not waiting for the quiescence handshake to complete.
 	// Note that we never actually reached full quiescence here.
-	assert!(!local_node.node.exit_quiescence(&remote_node_id, &chan_id).unwrap());
+	assert!(!local_node.node.exit_quiescence_locally(&remote_node_id, &chan_id).unwrap());
 
 	let _ = get_event_msg!(local_node, MessageSendEvent::SendClosingSigned, remote_node_id);
 	check_added_monitors(local_node, 2); // One for the last revoke_and_ack, another for closing_signed
@@ -281,8 +287,8 @@ fn test_quiescence_waits_for_async_signer_and_monitor_update() {
 	let stfu = get_event_msg!(&nodes[0], MessageSendEvent::SendStfu, node_id_1);
 	nodes[1].node.handle_stfu(node_id_0, &stfu);
 
-	assert!(nodes[0].node.exit_quiescence(&node_id_1, &chan_id).unwrap());
-	assert!(nodes[1].node.exit_quiescence(&node_id_0, &chan_id).unwrap());
+	assert!(nodes[0].node.exit_quiescence_locally(&node_id_1, &chan_id).unwrap());
+	assert!(nodes[1].node.exit_quiescence_locally(&node_id_0, &chan_id).unwrap());
 
 	// After exiting quiescence, we should be able to resume payments from nodes[0].
 	send_payment(&nodes[0], &[&nodes[1]], payment_amount);
@@ -338,8 +344,8 @@ fn test_quiescence_on_final_revoke_and_ack_pending_monitor_update() {
 		panic!();
 	}
 
-	assert!(nodes[0].node.exit_quiescence(&node_id_1, &chan_id).unwrap());
-	assert!(nodes[1].node.exit_quiescence(&node_id_0, &chan_id).unwrap());
+	assert!(nodes[0].node.exit_quiescence_locally(&node_id_1, &chan_id).unwrap());
+	assert!(nodes[1].node.exit_quiescence_locally(&node_id_0, &chan_id).unwrap());
 }
 
 #[test]
@@ -408,8 +414,8 @@ fn quiescence_updates_go_to_holding_cell(fail_htlc: bool) {
 	let stfu = get_event_msg!(&nodes[0], MessageSendEvent::SendStfu, node_id_1);
 	nodes[1].node.handle_stfu(node_id_0, &stfu);
 
-	assert!(nodes[0].node.exit_quiescence(&node_id_1, &chan_id).unwrap());
-	assert!(nodes[1].node.exit_quiescence(&node_id_0, &chan_id).unwrap());
+	assert!(nodes[0].node.exit_quiescence_locally(&node_id_1, &chan_id).unwrap());
+	assert!(nodes[1].node.exit_quiescence_locally(&node_id_0, &chan_id).unwrap());
 
 	// Now that quiescence is over, nodes are allowed to make updates again. nodes[1] will have its
 	// outbound HTLC finally go out, along with the fail/claim of nodes[0]'s payment.
@@ -689,8 +695,8 @@ fn do_test_quiescence_during_disconnection(with_pending_claim: bool, propose_dis
 	let stfu_resp = get_event_msg!(nodes[0], MessageSendEvent::SendStfu, node_b_id);
 	nodes[1].node.handle_stfu(node_a_id, &stfu_resp);
 
-	assert!(nodes[0].node.exit_quiescence(&node_b_id, &chan_id).unwrap());
-	assert!(nodes[1].node.exit_quiescence(&node_a_id, &chan_id).unwrap());
+	assert!(nodes[0].node.exit_quiescence_locally(&node_b_id, &chan_id).unwrap());
+	assert!(nodes[1].node.exit_quiescence_locally(&node_a_id, &chan_id).unwrap());
 }
 
 #[test]
@@ -760,3 +766,87 @@ fn do_test_quiescence_termination_on_disconnect(reload: bool) {
 
 	send_payment(&nodes[0], &[&nodes[1]], 1_000_000);
 }
+
+#[test]
+fn test_quiesce_channel() {
+	// Test that `ChannelManager::quiesce_channel` exchanges `stfu`, generates an
+	// `Event::ChannelQuiescent` once quiescent, and holds the channel quiescent without timing out
+	// until `ChannelManager::exit_quiescence` disconnects the counterparty.
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[None, None]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+	let chan_id = create_announced_chan_between_nodes(&nodes, 0, 1).2;
+
+	let node_id_0 = nodes[0].node.get_our_node_id();
+	let node_id_1 = nodes[1].node.get_our_node_id();
+
+	nodes[0].node.quiesce_channel(&chan_id, &node_id_1).unwrap();
+	match nodes[0].node.quiesce_channel(&chan_id, &node_id_1) {
+		Err(APIError::APIMisuseError { err }) => assert_eq!(
+			err,
+			"Channel already has a pending quiescent action and cannot start another"
+		),
+		res => panic!("{res:?}"),
+	}
+
+	// We can't exit quiescence before it's been reached.
+	assert!(nodes[0].node.exit_quiescence(&chan_id, &node_id_1).is_err());
+
+	let stfu_initiator = get_event_msg!(nodes[0], MessageSendEvent::SendStfu, node_id_1);
+	nodes[1].node.handle_stfu(node_id_0, &stfu_initiator);
+	let stfu_responder = get_event_msg!(nodes[1], MessageSendEvent::SendStfu, node_id_0);
+	nodes[0].node.handle_stfu(node_id_1, &stfu_responder);
+	assert!(stfu_initiator.initiator && !stfu_responder.initiator);
+
+	let events = nodes[0].node.get_and_clear_pending_events();
+	assert_eq!(events.len(), 1);
+	match &events[0] {
+		Event::ChannelQuiescent { channel_id, counterparty_node_id, user_channel_id } => {
+			assert_eq!(*channel_id, chan_id);
+			assert_eq!(*counterparty_node_id, node_id_1);
+			assert_eq!(*user_channel_id, 42);
+		},
+		_ => panic!("{events:?}"),
+	}
+	assert!(nodes[1].node.get_and_clear_pending_events().is_empty());
+	assert!(nodes[0].node.get_and_clear_pending_msg_events().is_empty());
+
+	// While held quiescent the channel can't be updated, but unlike other quiescence we won't
+	// disconnect the counterparty for failing to make progress.
+	assert!(nodes[0].node.channel_lifecycle_info(&chan_id, &node_id_1).unwrap().is_quiescent);
+	assert_eq!(
+		nodes[0].node.close_channel(&chan_id, &node_id_1),
+		Err(APIError::APIMisuseError { err: "Cannot begin shutdown while quiescent".to_owned() })
+	);
+	for _ in 0..DISCONNECT_PEER_AWAITING_RESPONSE_TICKS * 2 {
+		nodes[0].node.timer_tick_occurred();
+	}
+	let msg_events = nodes[0].node.get_and_clear_pending_msg_events();
+	assert!(!msg_events.iter().any(|ev| matches!(ev, MessageSendEvent::HandleError { .. })));
+
+	// Exiting quiescence disconnects the counterparty, as that's the only way to end it.
+	nodes[0].node.exit_quiescence(&chan_id, &node_id_1).unwrap();
+	let msg_events = nodes[0].node.get_and_clear_pending_msg_events();
+	assert_eq!(msg_events.len(), 1);
+	match &msg_events[0] {
+		MessageSendEvent::HandleError {
+			node_id,
+			action: ErrorAction::DisconnectPeerWithWarning { .. },
+		} => assert_eq!(*node_id, node_id_1),
+		_ => panic!("{msg_events:?}"),
+	}
+	assert!(nodes[0].node.exit_quiescence(&chan_id, &node_id_1).is_err());
+
+	// Once the peers disconnect, quiescence ends and isn't attempted again upon reconnection.
+	nodes[0].node.peer_disconnected(node_id_1);
+	nodes[1].node.peer_disconnected(node_id_0);
+
+	let mut reconnect_args = ReconnectArgs::new(&nodes[0], &nodes[1]);
+	reconnect_args.send_channel_ready = (true, true);
+	reconnect_args.send_announcement_sigs = (true, true);
+	reconnect_nodes(reconnect_args);
+	assert!(!nodes[0].node.channel_lifecycle_info(&chan_id, &node_id_1).unwrap().is_quiescent);
+
+	send_payment(&nodes[0], &[&nodes[1]], 1_000_000);
+}
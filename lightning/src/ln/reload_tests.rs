@@ -714,6 +714,107 @@ fn test_data_loss_protect() {
 	do_test_data_loss_protect(false, false, false);
 }
 
+#[test]
+fn test_data_loss_protect_recovery_mode() {
+	// Tests that once in recovery mode, proof that we've fallen behind doesn't cause a panic.
+	// Instead, the channel is closed without broadcasting our revoked commitment transaction, our
+	// counterparty is prompted to broadcast theirs, and our balance therein is recovered. Our
+	// revoked commitment transaction is never broadcast, even once an HTLC pending in it times out.
+	use crate::chain::channelmonitor::LATENCY_GRACE_PERIOD_BLOCKS;
+	use crate::types::string::UntrustedString;
+	use bitcoin::opcodes;
+	use bitcoin::script::Builder;
+	use bitcoin::secp256k1::Secp256k1;
+
+	let mut chanmon_cfgs = create_chanmon_cfgs(2);
+	// We sign our revoked commitment transaction during `Drop` as the `ChannelMonitor` is stale.
+	chanmon_cfgs[0].keys_manager.disable_revocation_policy_check = true;
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let persister;
+	let new_chain_monitor;
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[None, None]);
+	let nodes_0_deserialized;
+	let mut nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+	let node_a_id = nodes[0].node.get_our_node_id();
+	let node_b_id = nodes[1].node.get_our_node_id();
+
+	let chan = create_announced_chan_between_nodes_with_value(&nodes, 0, 1, 1000000, 1000000);
+
+	// Leave an HTLC pending in the state we'll later restore.
+	let htlc_expiry = nodes[0].best_block_info().1 + TEST_FINAL_CLTV;
+	route_payment(&nodes[0], &[&nodes[1]], 1000000);
+
+	let previous_node_state = nodes[0].node.encode();
+	let previous_chain_monitor_state = get_monitor!(nodes[0], chan.2).encode();
+
+	send_payment(&nodes[0], &[&nodes[1]], 8000000);
+	send_payment(&nodes[0], &[&nodes[1]], 8000000);
+
+	nodes[0].node.peer_disconnected(node_b_id);
+	nodes[1].node.peer_disconnected(node_a_id);
+
+	reload_node!(nodes[0], previous_node_state, &[&previous_chain_monitor_state], persister, new_chain_monitor, nodes_0_deserialized);
+	nodes[0].node.enter_recovery_mode();
+
+	nodes[0].node.peer_connected(node_b_id, &msgs::Init {
+		features: nodes[1].node.init_features(), networks: None, remote_network_address: None
+	}, true).unwrap();
+	nodes[1].node.peer_connected(node_a_id, &msgs::Init {
+		features: nodes[0].node.init_features(), networks: None, remote_network_address: None
+	}, false).unwrap();
+
+	let reestablish_a = get_chan_reestablish_msgs!(nodes[0], nodes[1]);
+	nodes[1].node.handle_channel_reestablish(node_a_id, &reestablish_a[0]);
+	let msg_events = nodes[1].node.get_and_clear_pending_msg_events();
+	assert_eq!(msg_events.len(), 2);
+	let reestablish_b = match &msg_events[0] {
+		MessageSendEvent::SendChannelReestablish { msg, .. } => msg.clone(),
+		_ => panic!("Unexpected events: {:?}", msg_events),
+	};
+
+	// Rather than panicking, A closes the channel without broadcasting.
+	nodes[0].node.handle_channel_reestablish(node_b_id, &reestablish_b);
+	check_added_monitors(&nodes[0], 1);
+	let err = "We have fallen behind, please broadcast your latest commitment transaction";
+	let reason = ClosureReason::ProcessingError { err: err.to_owned() };
+	check_closed_event(&nodes[0], 1, reason, &[node_b_id], 1000000);
+	let err_msg = check_closed_broadcast!(nodes[0], true).unwrap();
+	assert!(nodes[0].tx_broadcaster.txn_broadcasted.lock().unwrap().is_empty());
+	assert_eq!(nodes[0].node.list_recovering_channels().len(), 1);
+
+	// Even once the pending HTLC times out, A's `ChannelMonitor` must not broadcast the revoked
+	// commitment transaction.
+	let height = nodes[0].best_block_info().1;
+	connect_blocks(&nodes[0], htlc_expiry + LATENCY_GRACE_PERIOD_BLOCKS - height + 1);
+	assert!(nodes[0].tx_broadcaster.txn_broadcasted.lock().unwrap().is_empty());
+
+	// The error prompts B to broadcast its latest commitment transaction.
+	nodes[1].node.handle_error(node_a_id, &err_msg);
+	check_added_monitors(&nodes[1], 1);
+	check_closed_broadcast!(nodes[1], false);
+	let reason = ClosureReason::CounterpartyForceClosed { peer_msg: UntrustedString(err.to_owned()) };
+	check_closed_event(&nodes[1], 1, reason, &[node_a_id], 1000000);
+	let node_txn = nodes[1].tx_broadcaster.txn_broadcasted.lock().unwrap().split_off(0);
+	assert_eq!(node_txn.len(), 1);
+	check_spends!(node_txn[0], chan.3);
+
+	// Once it confirms, A can sweep its balance.
+	mine_transaction(&nodes[0], &node_txn[0]);
+	assert!(nodes[0].node.list_recovering_channels().is_empty());
+	let events = nodes[0].node.get_and_clear_pending_events();
+	assert_eq!(events.len(), 1);
+	if let Event::SpendableOutputs { outputs, channel_id } = &events[0] {
+		assert_eq!(*channel_id, Some(chan.2));
+		assert_eq!(outputs.len(), 1);
+		let spend_tx = nodes[0].keys_manager.backing.spend_spendable_outputs(&[&outputs[0]],
+			Vec::new(), Builder::new().push_opcode(opcodes::all::OP_RETURN).into_script(), 253,
+			None, &Secp256k1::new()).unwrap();
+		check_spends!(spend_tx, node_txn[0]);
+	} else {
+		panic!("Unexpected event: {:?}", events[0]);
+	}
+}
+
 fn do_test_partial_claim_before_restart(persist_both_monitors: bool, double_restart: bool) {
 	// Test what happens if a node receives an MPP payment, claims it, but crashes before
 	// persisting the ChannelManager. If `persist_both_monitors` is false, also crash after only
@@ -15,8 +15,10 @@ use crate::chain::chaininterface::LowerBoundedFeeEstimator;
 use crate::chain::channelmonitor::{ANTI_REORG_DELAY, Balance, LATENCY_GRACE_PERIOD_BLOCKS};
 use crate::chain::transaction::OutPoint;
 use crate::chain::Confirm;
-use crate::events::{Event, ClosureReason, HTLCHandlingFailureType};
+use crate::events::{Event, ClosureReason, HTLCHandlingFailureReason, HTLCHandlingFailureType};
+use crate::ln::channelmanager::{PaymentId, RecipientOnionFields};
 use crate::ln::msgs::{BaseMessageHandler, ChannelMessageHandler, Init, MessageSendEvent};
+use crate::ln::onion_utils::LocalHTLCFailureReason;
 use crate::ln::types::ChannelId;
 use crate::sign::OutputSpender;
 use crate::types::payment::PaymentHash;
@@ -448,6 +450,170 @@ fn test_unconf_chan_via_funding_unconfirmed() {
 	do_test_unconf_chan(false, false, true, ConnectStyle::FullBlockViaListen);
 }
 
+fn do_test_funding_reorg_grace_period(use_funding_unconfirmed: bool, connect_style: ConnectStyle) {
+	// Test that with a `funding_reorg_grace_period_blocks` configured, a channel whose funding
+	// transaction is un-confirmed is frozen rather than force-closed, becomes usable again once the
+	// funding re-confirms, and is only force-closed once the grace period expires.
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let mut config = test_default_channel_config();
+	config.funding_reorg_grace_period_blocks = 6;
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[Some(config), None]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+	*nodes[0].connect_style.borrow_mut() = connect_style;
+
+	let node_b_id = nodes[1].node.get_our_node_id();
+	let chan = create_announced_chan_between_nodes(&nodes, 0, 1);
+	let original_scid = nodes[0].node.list_channels()[0].short_channel_id;
+	assert!(original_scid.is_some());
+
+	let unconfirm_funding = || {
+		if use_funding_unconfirmed {
+			nodes[0].node.transaction_unconfirmed(&chan.3.compute_txid());
+		} else {
+			let confirmations = nodes[0].node.list_channels()[0].confirmations.unwrap();
+			disconnect_blocks(&nodes[0], confirmations);
+		}
+	};
+
+	unconfirm_funding();
+	let unconfirmed_height = nodes[0].best_block_info().1;
+	let events = nodes[0].node.get_and_clear_pending_events();
+	assert_eq!(events.len(), 1);
+	match &events[0] {
+		Event::ChannelFundingUnconfirmed {
+			channel_id, counterparty_node_id, previous_short_channel_id, unconfirmed_at_height,
+			reconfirmation_deadline_height, ..
+		} => {
+			assert_eq!(*channel_id, chan.2);
+			assert_eq!(*counterparty_node_id, node_b_id);
+			assert_eq!(*previous_short_channel_id, original_scid);
+			assert_eq!(*unconfirmed_at_height, unconfirmed_height);
+			assert_eq!(*reconfirmation_deadline_height, unconfirmed_height + 6);
+		},
+		_ => panic!("Unexpected event"),
+	}
+	assert_eq!(nodes[0].node.list_channels().len(), 1);
+	assert!(nodes[0].node.list_usable_channels().is_empty());
+	assert!(nodes[0].node.get_and_clear_pending_msg_events().is_empty());
+	check_added_monitors(&nodes[0], 0);
+
+	// Once the funding transaction re-confirms, the channel is usable again.
+	connect_blocks(&nodes[0], 2);
+	mine_transaction(&nodes[0], &chan.3);
+	let events = nodes[0].node.get_and_clear_pending_events();
+	assert_eq!(events.len(), 1);
+	let new_scid = match &events[0] {
+		Event::ChannelFundingReconfirmed { channel_id, counterparty_node_id, short_channel_id, .. } => {
+			assert_eq!(*channel_id, chan.2);
+			assert_eq!(*counterparty_node_id, node_b_id);
+			assert!(short_channel_id.is_some());
+			assert_ne!(*short_channel_id, original_scid);
+			*short_channel_id
+		},
+		_ => panic!("Unexpected event"),
+	};
+	assert_eq!(nodes[0].node.list_usable_channels().len(), 1);
+	assert_eq!(nodes[0].node.list_usable_channels()[0].short_channel_id, new_scid);
+
+	let (node_a_height, node_b_height) = (nodes[0].best_block_info().1, nodes[1].best_block_info().1);
+	if node_a_height < node_b_height {
+		connect_blocks(&nodes[0], node_b_height - node_a_height);
+	} else {
+		connect_blocks(&nodes[1], node_a_height - node_b_height);
+	}
+	send_payment(&nodes[0], &[&nodes[1]], 1_000_000);
+
+	// If the funding transaction is un-confirmed again and doesn't re-confirm within the grace
+	// period, the channel is force-closed.
+	unconfirm_funding();
+	let unconfirmed_height = nodes[0].best_block_info().1;
+	let events = nodes[0].node.get_and_clear_pending_events();
+	assert_eq!(events.len(), 1);
+	assert!(matches!(events[0], Event::ChannelFundingUnconfirmed { .. }));
+
+	connect_blocks(&nodes[0], 5);
+	assert_eq!(nodes[0].node.list_channels().len(), 1);
+	assert!(nodes[0].node.get_and_clear_pending_events().is_empty());
+
+	*nodes[0].chain_monitor.expect_channel_force_closed.lock().unwrap() = Some((chan.2, true));
+	connect_blocks(&nodes[0], 1);
+	check_added_monitors(&nodes[0], 1);
+	check_closed_broadcast(&nodes[0], 1, true);
+	let expected_err = format!(
+		"Funding transaction was un-confirmed at height {} and did not re-confirm within 6 blocks.",
+		unconfirmed_height
+	);
+	check_closed_event(&nodes[0], 1, ClosureReason::ProcessingError { err: expected_err }, &[node_b_id], 100000);
+	assert!(nodes[0].node.list_channels().is_empty());
+}
+
+#[test]
+fn test_funding_reorg_grace_period() {
+	do_test_funding_reorg_grace_period(false, ConnectStyle::BestBlockFirstSkippingBlocks);
+	do_test_funding_reorg_grace_period(false, ConnectStyle::BestBlockFirstReorgsOnlyTip);
+	do_test_funding_reorg_grace_period(false, ConnectStyle::FullBlockViaListen);
+	do_test_funding_reorg_grace_period(true, ConnectStyle::BestBlockFirstSkippingBlocks);
+	do_test_funding_reorg_grace_period(true, ConnectStyle::FullBlockViaListen);
+}
+
+#[test]
+fn test_htlcs_failed_while_funding_unconfirmed() {
+	// Test that while a channel is frozen awaiting the re-confirmation of its funding transaction,
+	// HTLCs our counterparty (which may not yet have seen the reorg) sends us are failed back
+	// rather than received.
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let mut config = test_default_channel_config();
+	config.funding_reorg_grace_period_blocks = 6;
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[Some(config), None]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+
+	let node_a_id = nodes[0].node.get_our_node_id();
+	let node_b_id = nodes[1].node.get_our_node_id();
+	let chan = create_announced_chan_between_nodes(&nodes, 0, 1);
+
+	nodes[0].node.transaction_unconfirmed(&chan.3.compute_txid());
+	let events = nodes[0].node.get_and_clear_pending_events();
+	assert_eq!(events.len(), 1);
+	assert!(matches!(events[0], Event::ChannelFundingUnconfirmed { .. }));
+	assert!(nodes[0].node.list_usable_channels().is_empty());
+
+	let (route, payment_hash, _, payment_secret) = get_route_and_payment_hash!(nodes[1], nodes[0], 1_000_000);
+	let onion = RecipientOnionFields::secret_only(payment_secret);
+	let payment_id = PaymentId(payment_hash.0);
+	nodes[1].node.send_payment_with_route(route, payment_hash, onion, payment_id).unwrap();
+	check_added_monitors(&nodes[1], 1);
+	let updates = get_htlc_update_msgs(&nodes[1], &node_a_id);
+	nodes[0].node.handle_update_add_htlc(node_b_id, &updates.update_add_htlcs[0]);
+	do_commitment_signed_dance(&nodes[0], &nodes[1], &updates.commitment_signed, false, false);
+
+	nodes[0].node.process_pending_htlc_forwards();
+	let events = nodes[0].node.get_and_clear_pending_events();
+	assert_eq!(events.len(), 1);
+	match &events[0] {
+		Event::HTLCHandlingFailed { failure_type, failure_reason, .. } => {
+			assert_eq!(*failure_type, HTLCHandlingFailureType::Receive { payment_hash });
+			assert_eq!(*failure_reason, Some(HTLCHandlingFailureReason::Local {
+				reason: LocalHTLCFailureReason::ChannelNotReady,
+			}));
+		},
+		_ => panic!("Unexpected event"),
+	}
+	expect_and_process_pending_htlcs(&nodes[0], false);
+	check_added_monitors(&nodes[0], 1);
+
+	let updates = get_htlc_update_msgs(&nodes[0], &node_b_id);
+	assert_eq!(updates.update_fail_htlcs.len(), 1);
+	nodes[1].node.handle_update_fail_htlc(node_a_id, &updates.update_fail_htlcs[0]);
+	do_commitment_signed_dance(&nodes[1], &nodes[0], &updates.commitment_signed, false, false);
+	let events = nodes[1].node.get_and_clear_pending_events();
+	assert_eq!(events.len(), 2);
+	assert!(matches!(events[0], Event::PaymentPathFailed { payment_hash: hash, .. } if hash == payment_hash));
+	assert!(matches!(events[1], Event::PaymentFailed { payment_hash: Some(hash), .. } if hash == payment_hash));
+	assert!(nodes[0].node.get_and_clear_pending_events().is_empty());
+}
+
 #[test]
 fn test_set_outpoints_partial_claiming() {
 	// - remote party claim tx, new bump tx
@@ -13,8 +13,16 @@
 use crate::chain::transaction::OutPoint;
 use crate::chain::ChannelMonitorUpdateStatus;
 use crate::events::{ClosureReason, Event, HTLCHandlingFailureReason, HTLCHandlingFailureType};
-use crate::ln::channel_state::{ChannelDetails, ChannelShutdownState};
+use crate::ln::channel_state::{
+	ChannelDetails, ChannelShutdownState, ProtocolErrorDirection, ProtocolErrorKind,
+};
 use crate::ln::channelmanager::{self, PaymentId, RecipientOnionFields, Retry};
+use crate::ln::closure_scheduler::{
+	CloseDeadline, ScheduledClosureStatus, MIN_CLOSE_FEERATE_SAMPLES,
+};
+use crate::ln::force_close_observer::{
+	ForceCloseDecision, ForceCloseObserver, ForceCloseReason, FORCE_CLOSE_VETO_TIMEOUT_BLOCKS,
+};
 use crate::ln::msgs;
 use crate::ln::msgs::{BaseMessageHandler, ChannelMessageHandler, ErrorAction, MessageSendEvent};
 use crate::ln::onion_utils::LocalHTLCFailureReason;
@@ -23,6 +31,7 @@ use crate::ln::types::ChannelId;
 use crate::prelude::*;
 use crate::routing::router::{get_route, PaymentParameters, RouteParameters};
 use crate::sign::{EntropySource, SignerProvider};
+use crate::sync::{Arc, Mutex};
 use crate::types::string::UntrustedString;
 use crate::util::config::UserConfig;
 use crate::util::errors::APIError;
@@ -34,8 +43,9 @@ use bitcoin::locktime::absolute::LockTime;
 use bitcoin::network::Network;
 use bitcoin::opcodes;
 use bitcoin::script::Builder;
+use bitcoin::secp256k1::PublicKey;
 use bitcoin::transaction::Version;
-use bitcoin::{Transaction, TxOut, WitnessProgram, WitnessVersion};
+use bitcoin::{Transaction, TxOut, Txid, WitnessProgram, WitnessVersion};
 
 use crate::ln::functional_test_utils::*;
 
@@ -83,6 +93,107 @@ fn pre_funding_lock_shutdown_test() {
 	check_closed_event(&nodes[1], 1, reason_b, &[node_a_id], 8000000);
 }
 
+#[test]
+fn test_scheduled_closures() {
+	// Test that closures scheduled via `ChannelManager::schedule_closures` are initiated once the
+	// feerate is low compared to recent blocks, and that channels with unreachable peers are
+	// force-closed once their deadline is reached.
+	let chanmon_cfgs = create_chanmon_cfgs(3);
+	let node_cfgs = create_node_cfgs(3, &chanmon_cfgs);
+	let node_chanmgrs = create_node_chanmgrs(3, &node_cfgs, &[None, None, None]);
+	let nodes = create_network(3, &node_cfgs, &node_chanmgrs);
+	let node_a_id = nodes[0].node.get_our_node_id();
+	let node_b_id = nodes[1].node.get_our_node_id();
+	let node_c_id = nodes[2].node.get_our_node_id();
+
+	let chan_a = create_announced_chan_between_nodes(&nodes, 0, 1).2;
+	let chan_b = create_announced_chan_between_nodes(&nodes, 0, 1).2;
+	let chan_c = create_announced_chan_between_nodes(&nodes, 0, 2).2;
+
+	let height = nodes[0].best_block_info().1;
+	let far_deadline = CloseDeadline { height: height + 1000, force_close_if_unreachable: false };
+	let near_deadline = CloseDeadline { height: height + 2, force_close_if_unreachable: true };
+	nodes[0].node.schedule_closures(vec![chan_a], far_deadline).unwrap();
+	nodes[0].node.schedule_closures(vec![chan_c], near_deadline).unwrap();
+	let unknown_chan = ChannelId::from_bytes([42; 32]);
+	assert!(nodes[0].node.schedule_closures(vec![unknown_chan], far_deadline).is_err());
+
+	nodes[0].node.peer_disconnected(node_c_id);
+	nodes[2].node.peer_disconnected(node_a_id);
+
+	let count_shutdowns = |events: Vec<MessageSendEvent>| {
+		events.iter().filter(|ev| matches!(ev, MessageSendEvent::SendShutdown { .. })).count()
+	};
+
+	// Without enough feerate samples and ahead of the deadlines, nothing happens.
+	nodes[0].node.timer_tick_occurred();
+	assert_eq!(count_shutdowns(nodes[0].node.get_and_clear_pending_msg_events()), 0);
+	assert!(nodes[0]
+		.node
+		.list_scheduled_closures()
+		.iter()
+		.all(|closure| closure.status == ScheduledClosureStatus::Pending));
+
+	// Sample the feerate over a few blocks, then raise it so that it isn't considered low. Once
+	// the deadline for the channel with our unreachable peer passes, it is force-closed.
+	connect_blocks(&nodes[0], MIN_CLOSE_FEERATE_SAMPLES as u32);
+	let height = nodes[0].best_block_info().1;
+	*chanmon_cfgs[0].fee_estimator.sat_per_kw.lock().unwrap() *= 2;
+	nodes[0].node.timer_tick_occurred();
+	check_added_monitors(&nodes[0], 1);
+	let message = "Scheduled closure deadline reached while peer was unreachable".to_owned();
+	let reason = ClosureReason::HolderForceClosed { broadcasted_latest_txn: Some(true), message };
+	check_closed_event(&nodes[0], 1, reason, &[node_c_id], 100000);
+	assert_eq!(count_shutdowns(nodes[0].node.get_and_clear_pending_msg_events()), 0);
+	nodes[0].tx_broadcaster.txn_broadcasted.lock().unwrap().clear();
+
+	let closures = nodes[0].node.list_scheduled_closures();
+	assert_eq!(closures.len(), 2);
+	assert_eq!(closures[0].channel_id, chan_a);
+	assert_eq!(closures[0].status, ScheduledClosureStatus::Pending);
+	assert_eq!(closures[1].channel_id, chan_c);
+	assert_eq!(closures[1].status, ScheduledClosureStatus::ForceClosed { height });
+
+	// Once the feerate drops again, we initiate the cooperative close of the scheduled channel,
+	// leaving the other channel with the same peer alone.
+	*chanmon_cfgs[0].fee_estimator.sat_per_kw.lock().unwrap() /= 2;
+	nodes[0].node.timer_tick_occurred();
+	let closures = nodes[0].node.list_scheduled_closures();
+	assert_eq!(closures[0].status, ScheduledClosureStatus::Initiated { height });
+	assert_eq!(closures[1].status, ScheduledClosureStatus::Closed);
+	expect_channel_shutdown_state!(nodes[0], chan_a, ChannelShutdownState::ShutdownInitiated);
+	expect_channel_shutdown_state!(nodes[0], chan_b, ChannelShutdownState::NotShuttingDown);
+
+	let node_0_shutdown = get_event_msg!(nodes[0], MessageSendEvent::SendShutdown, node_b_id);
+	nodes[1].node.handle_shutdown(node_a_id, &node_0_shutdown);
+	let node_1_shutdown = get_event_msg!(nodes[1], MessageSendEvent::SendShutdown, node_a_id);
+	nodes[0].node.handle_shutdown(node_b_id, &node_1_shutdown);
+
+	let node_0_closing_signed =
+		get_event_msg!(nodes[0], MessageSendEvent::SendClosingSigned, node_b_id);
+	nodes[1].node.handle_closing_signed(node_a_id, &node_0_closing_signed);
+	let node_1_closing_signed =
+		get_event_msg!(nodes[1], MessageSendEvent::SendClosingSigned, node_a_id);
+	nodes[0].node.handle_closing_signed(node_b_id, &node_1_closing_signed);
+	let (_, node_0_2nd_closing_signed) = get_closing_signed_broadcast!(nodes[0].node, node_b_id);
+	nodes[1].node.handle_closing_signed(node_a_id, &node_0_2nd_closing_signed.unwrap());
+	let (_, node_1_none) = get_closing_signed_broadcast!(nodes[1].node, node_a_id);
+	assert!(node_1_none.is_none());
+
+	let reason_a = ClosureReason::LocallyInitiatedCooperativeClosure;
+	check_closed_event(&nodes[0], 1, reason_a, &[node_b_id], 100000);
+	let reason_b = ClosureReason::CounterpartyInitiatedCooperativeClosure;
+	check_closed_event(&nodes[1], 1, reason_b, &[node_a_id], 100000);
+
+	// The next timer tick notices the closure completed.
+	nodes[0].node.timer_tick_occurred();
+	let closures = nodes[0].node.list_scheduled_closures();
+	assert_eq!(closures[0].status, ScheduledClosureStatus::Closed);
+	nodes[0].node.clear_completed_scheduled_closures();
+	assert!(nodes[0].node.list_scheduled_closures().is_empty());
+	assert_eq!(nodes[0].node.list_channels().len(), 1);
+}
+
 #[test]
 fn expect_channel_shutdown_state() {
 	// Test sending a shutdown prior to channel_ready after funding generation
@@ -266,6 +377,27 @@ fn test_lnd_bug_6039() {
 		panic!();
 	}
 
+	// Both the error we received and the warning we sent in response should be recorded in the
+	// channel's protocol error history, as should any warnings we receive.
+	let warn_msg = msgs::WarningMessage { channel_id: chan.2, data: "still waiting".to_string() };
+	nodes[0].node.handle_warning(node_b_id, &warn_msg);
+	let history = nodes[0].node.list_channels()[0].protocol_error_history.clone();
+	let summary = history
+		.iter()
+		.map(|record| (record.direction, record.kind, record.message.0.as_str()))
+		.collect::<Vec<_>>();
+	assert_eq!(summary.len(), 3);
+	assert_eq!(
+		summary[0],
+		(ProtocolErrorDirection::Received, ProtocolErrorKind::Error, "link failed to shutdown")
+	);
+	assert_eq!(summary[1].0, ProtocolErrorDirection::Sent);
+	assert_eq!(summary[1].1, ProtocolErrorKind::Warning);
+	assert_eq!(
+		summary[2],
+		(ProtocolErrorDirection::Received, ProtocolErrorKind::Warning, "still waiting")
+	);
+
 	let node_1_shutdown = get_event_msg!(nodes[1], MessageSendEvent::SendShutdown, node_a_id);
 
 	assert!(nodes[0].node.get_and_clear_pending_msg_events().is_empty());
@@ -1828,6 +1960,108 @@ fn test_force_closure_on_low_stale_fee() {
 	check_closed_events(&nodes[1], &[ExpectedCloseEvent::from_id_reason(chan_id, false, reason)]);
 }
 
+struct TestForceCloseObserver {
+	decision: Mutex<ForceCloseDecision>,
+	before_calls: Mutex<Vec<(ChannelId, ForceCloseReason)>>,
+	after_calls: Mutex<Vec<(ChannelId, ForceCloseReason, Option<Txid>)>>,
+}
+
+impl ForceCloseObserver for Arc<TestForceCloseObserver> {
+	fn before_force_close(
+		&self, channel_id: &ChannelId, _counterparty_node_id: &PublicKey, reason: &ForceCloseReason,
+	) -> ForceCloseDecision {
+		self.before_calls.lock().unwrap().push((*channel_id, reason.clone()));
+		*self.decision.lock().unwrap()
+	}
+
+	fn after_force_close(
+		&self, channel_id: &ChannelId, _counterparty_node_id: &PublicKey,
+		reason: &ForceCloseReason, broadcast_txid: Option<Txid>,
+	) {
+		self.after_calls.lock().unwrap().push((*channel_id, reason.clone(), broadcast_txid));
+	}
+}
+
+#[test]
+fn test_force_close_observer_veto_on_low_stale_fee() {
+	// Check that a `ForceCloseObserver` is consulted before we force-close a channel due to a
+	// stale low feerate, that its veto delays the closure for up to
+	// `FORCE_CLOSE_VETO_TIMEOUT_BLOCKS`, and that it is notified of the broadcast commitment
+	// transaction once the channel is closed.
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[None, None]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+
+	let (_, _, chan_id, funding_tx) = create_announced_chan_between_nodes(&nodes, 0, 1);
+
+	let observer = Arc::new(TestForceCloseObserver {
+		decision: Mutex::new(ForceCloseDecision::Veto),
+		before_calls: Mutex::new(Vec::new()),
+		after_calls: Mutex::new(Vec::new()),
+	});
+	nodes[1].node.set_force_close_observer(Arc::clone(&observer));
+
+	for _ in 0..channelmanager::FEERATE_TRACKING_BLOCKS * 2 {
+		connect_blocks(&nodes[1], 1);
+	}
+	{
+		let mut feerate_lock = chanmon_cfgs[1].fee_estimator.sat_per_kw.lock().unwrap();
+		*feerate_lock *= 2;
+	}
+	for _ in 0..channelmanager::FEERATE_TRACKING_BLOCKS - 1 {
+		connect_blocks(&nodes[1], 1);
+	}
+	assert!(observer.before_calls.lock().unwrap().is_empty());
+
+	// The next block would force-close the channel, but the observer vetoes it, as it does for
+	// every block until the veto times out.
+	let reason = ForceCloseReason::FeerateDivergence {
+		peer_feerate_sat_per_kw: 253,
+		required_feerate_sat_per_kw: 253 * 2,
+	};
+	for _ in 0..FORCE_CLOSE_VETO_TIMEOUT_BLOCKS {
+		connect_blocks(&nodes[1], 1);
+	}
+	assert!(nodes[1].node.get_and_clear_pending_events().is_empty());
+	assert!(nodes[1].node.get_and_clear_pending_msg_events().is_empty());
+	assert_eq!(nodes[1].node.list_channels().len(), 1);
+	{
+		let before_calls = observer.before_calls.lock().unwrap();
+		assert_eq!(before_calls.len(), FORCE_CLOSE_VETO_TIMEOUT_BLOCKS as usize);
+		assert!(before_calls.iter().all(|call| *call == (chan_id, reason.clone())));
+	}
+	assert!(observer.after_calls.lock().unwrap().is_empty());
+
+	// Once the veto has been in place for `FORCE_CLOSE_VETO_TIMEOUT_BLOCKS`, we close anyway.
+	connect_blocks(&nodes[1], 1);
+	check_added_monitors(&nodes[1], 1);
+	check_closed_broadcast(&nodes[1], 1, true);
+	let closure_reason = ClosureReason::PeerFeerateTooLow {
+		peer_feerate_sat_per_kw: 253,
+		required_feerate_sat_per_kw: 253 * 2,
+	};
+	check_closed_events(
+		&nodes[1],
+		&[ExpectedCloseEvent::from_id_reason(chan_id, false, closure_reason)],
+	);
+
+	// The observer was consulted only once more for the actual closure, and was then told which
+	// commitment transaction we broadcast.
+	assert_eq!(
+		observer.before_calls.lock().unwrap().len(),
+		FORCE_CLOSE_VETO_TIMEOUT_BLOCKS as usize + 1
+	);
+	let commitment_tx = {
+		let txn = nodes[1].tx_broadcaster.txn_broadcasted.lock().unwrap();
+		assert_eq!(txn.len(), 1);
+		txn[0].clone()
+	};
+	check_spends!(commitment_tx, funding_tx);
+	let after_calls = observer.after_calls.lock().unwrap();
+	assert_eq!(*after_calls, vec![(chan_id, reason, Some(commitment_tx.compute_txid()))]);
+}
+
 #[test]
 fn test_pending_htlcs_arent_lost_on_mon_delay() {
 	// Test that HTLCs which were queued to be sent to peers but which never made it out due to a
@@ -835,6 +835,43 @@ fn test_splice_in() {
 	let _ = send_payment(&nodes[0], &[&nodes[1]], htlc_limit_msat);
 }
 
+#[test]
+fn test_monitor_compaction_during_splice() {
+	// Tests that compacting a `ChannelMonitor` while a splice is pending counts each revoked state
+	// once, even though it is tracked by both the current and the pending `FundingScope`.
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[None, None]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+
+	let initial_channel_value_sat = 100_000;
+	let (_, _, channel_id, _) =
+		create_announced_chan_between_nodes_with_value(&nodes, 0, 1, initial_channel_value_sat, 0);
+
+	let coinbase_tx = provide_anchor_reserves(&nodes);
+	let initiator_contribution = SpliceContribution::SpliceIn {
+		value: Amount::from_sat(initial_channel_value_sat),
+		inputs: vec![FundingTxInput::new_p2wpkh(coinbase_tx, 0).unwrap()],
+		change_script: Some(nodes[0].wallet_source.get_change_script().unwrap()),
+	};
+	let splice_tx = splice_channel(&nodes[0], &nodes[1], channel_id, initiator_contribution);
+	mine_transaction(&nodes[0], &splice_tx);
+	mine_transaction(&nodes[1], &splice_tx);
+
+	// Build up revoked states which are tracked by both `FundingScope`s.
+	for _ in 0..3 {
+		send_payment(&nodes[0], &[&nodes[1]], 1_000_000);
+	}
+
+	let chain_monitor = &nodes[1].chain_monitor.chain_monitor;
+	assert!(chain_monitor.compact_monitor(channel_id, 1).unwrap() > 0);
+	assert_eq!(chain_monitor.compact_monitor(channel_id, 0), Ok(1));
+
+	// The splice still locks and the channel continues to operate as normal after compaction.
+	lock_splice_after_blocks(&nodes[0], &nodes[1], ANTI_REORG_DELAY - 1);
+	send_payment(&nodes[0], &[&nodes[1]], 1_000_000);
+}
+
 #[test]
 fn test_splice_out() {
 	let chanmon_cfgs = create_chanmon_cfgs(2);
@@ -878,6 +915,47 @@ fn test_splice_out() {
 	let _ = send_payment(&nodes[0], &[&nodes[1]], htlc_limit_msat);
 }
 
+#[test]
+fn test_splice_out_to_address() {
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[None, None]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+
+	let initial_channel_value_sat = 100_000;
+	let (_, _, channel_id, _) =
+		create_announced_chan_between_nodes_with_value(&nodes, 0, 1, initial_channel_value_sat, 0);
+
+	let node_id_1 = nodes[1].node.get_our_node_id();
+	let script_pubkey = nodes[0].wallet_source.get_change_script().unwrap();
+
+	// We can't splice out more than our balance.
+	let res = nodes[0].node.splice_out(
+		&channel_id,
+		&node_id_1,
+		Amount::from_sat(initial_channel_value_sat),
+		script_pubkey.clone(),
+		FEERATE_FLOOR_SATS_PER_KW,
+		None,
+	);
+	assert!(matches!(res, Err(APIError::APIMisuseError { .. })));
+	assert!(nodes[0].node.get_and_clear_pending_msg_events().is_empty());
+
+	// Otherwise, splicing out starts the quiescence handshake, just like `splice_channel`.
+	nodes[0]
+		.node
+		.splice_out(
+			&channel_id,
+			&node_id_1,
+			Amount::from_sat(initial_channel_value_sat / 4),
+			script_pubkey,
+			FEERATE_FLOOR_SATS_PER_KW,
+			None,
+		)
+		.unwrap();
+	let _ = get_event_msg!(nodes[0], MessageSendEvent::SendStfu, node_id_1);
+}
+
 #[cfg(test)]
 #[derive(PartialEq)]
 enum SpliceStatus {
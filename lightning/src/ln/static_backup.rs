@@ -0,0 +1,189 @@
+// This file is Copyright its original authors, visible in version control
+// history.
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Static channel backups (SCBs) allow recovering the funds in our channels after all channel
+//! state has been lost, as long as the node's seed is still available.
+//!
+//! A static channel backup contains only the information needed to identify each channel and our
+//! output in the counterparty's commitment transaction, and thus only needs to be updated when
+//! channels are opened or closed. On recovery, we ask each counterparty to force-close the channel
+//! (as described in `option_data_loss_protect`) and sweep our balance once their commitment
+//! transaction confirms. Note that any funds in HTLCs and, as we can't claim our `to_self` output
+//! without knowing the revocation state, any funds in a commitment transaction we broadcast
+//! ourselves are lost.
+//!
+//! See [`ChannelManager::export_static_backup`] and
+//! [`ChannelManager::recover_from_static_backup`] for the API.
+//!
+//! [`ChannelManager::export_static_backup`]: crate::ln::channelmanager::ChannelManager::export_static_backup
+//! [`ChannelManager::recover_from_static_backup`]: crate::ln::channelmanager::ChannelManager::recover_from_static_backup
+
+use bitcoin::hashes::sha256::Hash as Sha256;
+use bitcoin::hashes::{Hash, HashEngine, Hmac, HmacEngine};
+use bitcoin::secp256k1::PublicKey;
+use bitcoin::{ScriptBuf, Transaction};
+
+use crate::chain::transaction::OutPoint;
+use crate::ln::chan_utils::{self, ChannelTransactionParameters};
+use crate::ln::msgs::DecodeError;
+use crate::ln::our_peer_storage::{DecryptedOurPeerStorage, EncryptedOurPeerStorage};
+use crate::ln::types::ChannelId;
+use crate::sign::{PeerStorageKey, SpendableOutputDescriptor, StaticPaymentOutputDescriptor};
+use crate::util::ser::{Readable, Writeable};
+
+use crate::prelude::*;
+
+// The label used to derive the backup encryption key from the `NodeSigner`'s peer storage key,
+// ensuring backups are never encrypted with the same key as peer storage.
+const BACKUP_KEY_LABEL: &[u8] = b"LDK static channel backup key";
+
+/// The information about a single channel contained in a [`StaticChannelBackup`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StaticChannelBackupEntry {
+	/// The channel's id.
+	pub channel_id: ChannelId,
+	/// The node id of the channel's counterparty.
+	pub counterparty_node_id: PublicKey,
+	/// The identifier used to re-derive the channel's keys through
+	/// [`SignerProvider::derive_channel_signer`].
+	///
+	/// [`SignerProvider::derive_channel_signer`]: crate::sign::SignerProvider::derive_channel_signer
+	pub channel_keys_id: [u8; 32],
+	/// The channel's parameters, including its funding outpoint.
+	pub channel_parameters: ChannelTransactionParameters,
+}
+
+impl StaticChannelBackupEntry {
+	/// Returns the channel's funding outpoint.
+	pub fn funding_outpoint(&self) -> Option<OutPoint> {
+		self.channel_parameters.funding_outpoint
+	}
+
+	/// Returns the `script_pubkey` of the channel's funding output, which chain sources relying on
+	/// a [`Filter`] need to watch for spends during recovery.
+	///
+	/// [`Filter`]: crate::chain::Filter
+	pub fn funding_script_pubkey(&self) -> Option<ScriptBuf> {
+		self.channel_parameters.make_funding_redeemscript_opt().map(|script| script.to_p2wsh())
+	}
+
+	/// Returns the descriptors of our outputs in the given transaction if it spends the channel's
+	/// funding output, i.e., if it is the counterparty's commitment transaction, and `None`
+	/// otherwise.
+	pub(crate) fn recovered_outputs(
+		&self, tx: &Transaction,
+	) -> Option<Vec<SpendableOutputDescriptor>> {
+		let funding_outpoint = self.funding_outpoint()?.into_bitcoin_outpoint();
+		if !tx.input.iter().any(|input| input.previous_output == funding_outpoint) {
+			return None;
+		}
+
+		let payment_script = chan_utils::get_countersigner_payment_script(
+			&self.channel_parameters.channel_type_features,
+			&self.channel_parameters.holder_pubkeys.payment_point,
+		);
+		let txid = tx.compute_txid();
+		let outputs = tx
+			.output
+			.iter()
+			.enumerate()
+			.filter(|(_, output)| output.script_pubkey == payment_script)
+			.map(|(idx, output)| {
+				SpendableOutputDescriptor::StaticPaymentOutput(StaticPaymentOutputDescriptor {
+					outpoint: OutPoint { txid, index: idx as u16 },
+					output: output.clone(),
+					channel_keys_id: self.channel_keys_id,
+					channel_value_satoshis: self.channel_parameters.channel_value_satoshis,
+					channel_transaction_parameters: Some(self.channel_parameters.clone()),
+				})
+			})
+			.collect();
+		Some(outputs)
+	}
+}
+
+impl_writeable_tlv_based!(StaticChannelBackupEntry, {
+	(0, channel_id, required),
+	(2, counterparty_node_id, required),
+	(4, channel_keys_id, required),
+	(6, channel_parameters, (required: ReadableArgs, None)),
+});
+
+/// A static channel backup, listing the information needed to recover our balance in each of our
+/// funded channels.
+///
+/// It is exported in encrypted form via [`ChannelManager::export_static_backup`].
+///
+/// [`ChannelManager::export_static_backup`]: crate::ln::channelmanager::ChannelManager::export_static_backup
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StaticChannelBackup {
+	/// The backed up channels.
+	pub channels: Vec<StaticChannelBackupEntry>,
+}
+
+impl_writeable_tlv_based!(StaticChannelBackup, {
+	(0, channels, required_vec),
+});
+
+impl StaticChannelBackup {
+	/// Encrypts the backup with a key derived from the given `peer_storage_key`, using the given
+	/// `random_bytes` to derive the nonce.
+	pub(crate) fn encrypt(
+		&self, peer_storage_key: &PeerStorageKey, random_bytes: &[u8; 32],
+	) -> Vec<u8> {
+		let key = derive_backup_key(peer_storage_key);
+		DecryptedOurPeerStorage::new(self.encode()).encrypt(&key, random_bytes).into_vec()
+	}
+
+	/// Decrypts a backup previously returned by [`Self::encrypt`] using a key derived from the
+	/// given `peer_storage_key`.
+	pub(crate) fn decrypt(
+		encrypted: Vec<u8>, peer_storage_key: &PeerStorageKey,
+	) -> Result<Self, DecodeError> {
+		let key = derive_backup_key(peer_storage_key);
+		let decrypted = EncryptedOurPeerStorage::new(encrypted)
+			.and_then(|encrypted| encrypted.decrypt(&key))
+			.map_err(|()| DecodeError::InvalidValue)?;
+		Readable::read(&mut &decrypted.into_vec()[..])
+	}
+}
+
+fn derive_backup_key(peer_storage_key: &PeerStorageKey) -> PeerStorageKey {
+	let mut hmac = HmacEngine::<Sha256>::new(&peer_storage_key.inner);
+	hmac.input(BACKUP_KEY_LABEL);
+	PeerStorageKey { inner: Hmac::from_engine(hmac).to_byte_array() }
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn static_backup_encryption_round_trip() {
+		let secp_ctx = bitcoin::secp256k1::Secp256k1::new();
+		let secret_key = bitcoin::secp256k1::SecretKey::from_slice(&[42; 32]).unwrap();
+		let backup = StaticChannelBackup {
+			channels: vec![StaticChannelBackupEntry {
+				channel_id: ChannelId([1; 32]),
+				counterparty_node_id: PublicKey::from_secret_key(&secp_ctx, &secret_key),
+				channel_keys_id: [2; 32],
+				channel_parameters: ChannelTransactionParameters::test_dummy(100_000),
+			}],
+		};
+
+		let key = PeerStorageKey { inner: [3; 32] };
+		let encrypted = backup.encrypt(&key, &[4; 32]);
+		assert_eq!(StaticChannelBackup::decrypt(encrypted.clone(), &key).unwrap(), backup);
+
+		// Backups can't be decrypted with a different key, nor can they be peer storage.
+		let other_key = PeerStorageKey { inner: [5; 32] };
+		assert!(StaticChannelBackup::decrypt(encrypted.clone(), &other_key).is_err());
+		assert!(EncryptedOurPeerStorage::new(encrypted).unwrap().decrypt(&key).is_err());
+	}
+}
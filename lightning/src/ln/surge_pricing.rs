@@ -0,0 +1,495 @@
+// This file is Copyright its original authors, visible in version control
+// history.
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! An optional module raising the forwarding fees of channels as their outbound liquidity
+//! depletes, i.e., surge pricing.
+//!
+//! A [`SurgePricer`] scales each channel's forwarding fees by a multiplier given by a
+//! [`SurgePricingConfig::curve`] over the fraction of the channel's value available as outbound
+//! liquidity. Updated fees are applied via [`ChannelManager::update_partial_channel_config`],
+//! which broadcasts a new `channel_update` for the channel.
+//!
+//! To avoid flooding the network with `channel_update`s, updates are damped: changes smaller than
+//! [`SurgePricingConfig::min_fee_change_percent`] are ignored, and each channel may only be
+//! updated when a token is available in its token bucket, which holds up to
+//! [`SurgePricingConfig::bucket_capacity`] tokens and is refilled with one token every
+//! [`SurgePricingConfig::refill_ticks`] calls to [`SurgePricer::timer_tick_occurred`].
+//!
+//! Multipliers apply to each channel's baseline fees, which are those of the
+//! [`ChannelManager`]'s current default [`ChannelConfig`] unless overridden via
+//! [`SurgePricer::set_baseline_fees`]. As baselines are re-derived on every tick, and a channel's
+//! fees are updated whenever they don't match the multiplier last applied to its baseline, surge
+//! pricing picks up where it left off after a restart.
+//!
+//! [`ChannelManager`]: crate::ln::channelmanager::ChannelManager
+//! [`ChannelManager::update_partial_channel_config`]: crate::ln::channelmanager::ChannelManager::update_partial_channel_config
+//! [`ChannelConfig`]: crate::util::config::ChannelConfig
+
+use bitcoin::secp256k1::PublicKey;
+
+use crate::ln::channel_state::ChannelDetails;
+use crate::ln::channelmanager::AChannelManager;
+use crate::ln::types::ChannelId;
+use crate::sync::Mutex;
+use crate::util::config::{ChannelConfig, ChannelConfigUpdate};
+
+use core::ops::Deref;
+
+use crate::prelude::*;
+
+/// A point on a [`SurgePricingConfig::curve`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SurgeCurvePoint {
+	/// The percentage of the channel's value available as outbound liquidity, from 0 to 100.
+	pub outbound_liquidity_percent: u8,
+	/// The percentage by which the channel's baseline fees are multiplied at this point, where
+	/// 100 leaves the fees unchanged.
+	pub fee_multiplier_percent: u16,
+}
+
+/// Configuration for a [`SurgePricer`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SurgePricingConfig {
+	/// The fee multiplier as a function of a channel's outbound liquidity.
+	///
+	/// Points must be sorted by [`SurgeCurvePoint::outbound_liquidity_percent`]. The multiplier is
+	/// linearly interpolated between points, and is that of the first or last point outside of
+	/// the curve's range.
+	///
+	/// Default value: 100% at or above 50% outbound liquidity, 200% at 20%, and 500% at 0%.
+	pub curve: Vec<SurgeCurvePoint>,
+	/// The minimum change, as a percentage of the currently applied multiplier, for a channel's
+	/// fees to be updated.
+	///
+	/// Default value: `10`
+	pub min_fee_change_percent: u8,
+	/// The maximum number of fee updates a channel's token bucket may hold, i.e., the number of
+	/// updates which may be made in quick succession.
+	///
+	/// Default value: `2`
+	pub bucket_capacity: u8,
+	/// The number of calls to [`SurgePricer::timer_tick_occurred`] after which a token is added
+	/// to a channel's bucket, if not full.
+	///
+	/// Default value: `10`, i.e., roughly 10 minutes
+	pub refill_ticks: u16,
+}
+
+impl Default for SurgePricingConfig {
+	fn default() -> Self {
+		let point = |outbound_liquidity_percent, fee_multiplier_percent| SurgeCurvePoint {
+			outbound_liquidity_percent,
+			fee_multiplier_percent,
+		};
+		Self {
+			curve: vec![point(0, 500), point(20, 200), point(50, 100)],
+			min_fee_change_percent: 10,
+			bucket_capacity: 2,
+			refill_ticks: 10,
+		}
+	}
+}
+
+impl SurgePricingConfig {
+	/// Returns the fee multiplier percentage for a channel with the given percentage of its value
+	/// available as outbound liquidity.
+	pub fn fee_multiplier_percent(&self, outbound_liquidity_percent: u8) -> u16 {
+		let first = match self.curve.first() {
+			Some(first) => first,
+			None => return 100,
+		};
+		if outbound_liquidity_percent <= first.outbound_liquidity_percent {
+			return first.fee_multiplier_percent;
+		}
+		for window in self.curve.windows(2) {
+			let (low, high) = (window[0], window[1]);
+			if outbound_liquidity_percent > high.outbound_liquidity_percent {
+				continue;
+			}
+			let span = (high.outbound_liquidity_percent - low.outbound_liquidity_percent) as i64;
+			if span == 0 {
+				return high.fee_multiplier_percent;
+			}
+			let offset = (outbound_liquidity_percent - low.outbound_liquidity_percent) as i64;
+			let delta = high.fee_multiplier_percent as i64 - low.fee_multiplier_percent as i64;
+			return (low.fee_multiplier_percent as i64 + delta * offset / span) as u16;
+		}
+		self.curve.last().map_or(100, |last| last.fee_multiplier_percent)
+	}
+}
+
+/// The fee state of a channel tracked by a [`SurgePricer`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct ChannelSurgeState {
+	/// The fees to which multipliers apply, as of the last tick.
+	baseline_base_msat: u32,
+	baseline_proportional_millionths: u32,
+	/// The baseline fees set via [`SurgePricer::set_baseline_fees`], if any, used instead of those
+	/// of the default [`ChannelConfig`].
+	baseline_override: Option<(u32, u32)>,
+	/// The multiplier currently applied to the channel's fees.
+	applied_multiplier_percent: u16,
+	tokens: u8,
+	ticks_until_refill: u16,
+}
+
+impl ChannelSurgeState {
+	fn new(
+		config: &SurgePricingConfig, baseline_base_msat: u32, baseline_proportional_millionths: u32,
+	) -> Self {
+		Self {
+			baseline_base_msat,
+			baseline_proportional_millionths,
+			baseline_override: None,
+			applied_multiplier_percent: 100,
+			tokens: config.bucket_capacity,
+			ticks_until_refill: config.refill_ticks,
+		}
+	}
+
+	fn refill(&mut self, config: &SurgePricingConfig) {
+		if self.tokens >= config.bucket_capacity {
+			self.ticks_until_refill = config.refill_ticks;
+			return;
+		}
+		self.ticks_until_refill = self.ticks_until_refill.saturating_sub(1);
+		if self.ticks_until_refill == 0 {
+			self.tokens += 1;
+			self.ticks_until_refill = config.refill_ticks;
+		}
+	}
+
+	/// Returns the multiplier to apply to the channel's fees, if it should be updated, consuming
+	/// a token in the process.
+	fn take_update(
+		&mut self, config: &SurgePricingConfig, target_multiplier_percent: u16,
+	) -> Option<u16> {
+		let applied = self.applied_multiplier_percent as u32;
+		let change = (target_multiplier_percent as u32).abs_diff(applied);
+		if change == 0 || change * 100 < config.min_fee_change_percent as u32 * applied {
+			return None;
+		}
+		if self.tokens == 0 {
+			return None;
+		}
+		self.tokens -= 1;
+		Some(target_multiplier_percent)
+	}
+
+	fn fees(&self, multiplier_percent: u16) -> (u32, u32) {
+		let scale = |fee: u32| {
+			let scaled = fee as u64 * multiplier_percent as u64 / 100;
+			core::cmp::min(scaled, u32::MAX as u64) as u32
+		};
+		(scale(self.baseline_base_msat), scale(self.baseline_proportional_millionths))
+	}
+}
+
+/// The surge pricing state of a node's channels, shared by [`SurgePricer`] and the surge pricing
+/// built into the [`ChannelManager`] via [`ChannelManager::set_dynamic_fee_policy`].
+///
+/// [`ChannelManager`]: crate::ln::channelmanager::ChannelManager
+/// [`ChannelManager::set_dynamic_fee_policy`]: crate::ln::channelmanager::ChannelManager::set_dynamic_fee_policy
+pub(crate) struct SurgePricingState {
+	config: SurgePricingConfig,
+	channels: HashMap<ChannelId, ChannelSurgeState>,
+}
+
+impl SurgePricingState {
+	pub(crate) fn new(config: SurgePricingConfig) -> Self {
+		Self { config, channels: new_hash_map() }
+	}
+
+	/// Overrides the fees to which multipliers are applied for the given channel, which are
+	/// otherwise those of the default [`ChannelConfig`] passed to [`Self::fee_updates`].
+	pub(crate) fn set_baseline_fees(
+		&mut self, channel_id: ChannelId, base_msat: u32, proportional_millionths: u32,
+	) {
+		let config = &self.config;
+		let state = self
+			.channels
+			.entry(channel_id)
+			.or_insert_with(|| ChannelSurgeState::new(config, base_msat, proportional_millionths));
+		state.baseline_override = Some((base_msat, proportional_millionths));
+	}
+
+	/// Refills the channels' token buckets and returns the channels whose fees should be updated
+	/// given their outbound liquidity, along with the multiplier to record via
+	/// [`Self::fee_update_applied`] once the update was applied.
+	///
+	/// Channels' baseline fees are those of `default_config` unless overridden. Channels whose
+	/// current fees match neither the last applied nor the target multiplier, e.g. because their
+	/// baseline changed or we restarted, are updated regardless of damping as soon as a token is
+	/// available.
+	pub(crate) fn fee_updates(
+		&mut self, channel_details: &[ChannelDetails], default_config: &ChannelConfig,
+	) -> Vec<(PublicKey, ChannelId, u16, ChannelConfigUpdate)> {
+		self.channels.retain(|channel_id, _| {
+			channel_details.iter().any(|details| details.channel_id == *channel_id)
+		});
+
+		let mut updates = Vec::new();
+		for details in channel_details {
+			let channel_config = match details.config {
+				Some(config) if details.is_channel_ready => config,
+				_ => continue,
+			};
+			let config = &self.config;
+			let state = self.channels.entry(details.channel_id).or_insert_with(|| {
+				ChannelSurgeState::new(
+					config,
+					default_config.forwarding_fee_base_msat,
+					default_config.forwarding_fee_proportional_millionths,
+				)
+			});
+			state.refill(config);
+
+			let (base_msat, proportional_millionths) = state.baseline_override.unwrap_or((
+				default_config.forwarding_fee_base_msat,
+				default_config.forwarding_fee_proportional_millionths,
+			));
+			state.baseline_base_msat = base_msat;
+			state.baseline_proportional_millionths = proportional_millionths;
+
+			let channel_value_msat = details.channel_value_satoshis.saturating_mul(1000);
+			if channel_value_msat == 0 {
+				continue;
+			}
+			let outbound_liquidity_percent =
+				core::cmp::min(details.outbound_capacity_msat * 100 / channel_value_msat, 100);
+			let target = config.fee_multiplier_percent(outbound_liquidity_percent as u8);
+
+			let current_fees = (
+				channel_config.forwarding_fee_base_msat,
+				channel_config.forwarding_fee_proportional_millionths,
+			);
+			if state.fees(state.applied_multiplier_percent) != current_fees {
+				if state.fees(target) == current_fees {
+					state.applied_multiplier_percent = target;
+				} else {
+					// Force an update to resync the channel's fees regardless of damping.
+					state.applied_multiplier_percent = 0;
+				}
+			}
+
+			if let Some(multiplier_percent) = state.take_update(config, target) {
+				let (base_msat, proportional_millionths) = state.fees(multiplier_percent);
+				let config_update = ChannelConfigUpdate {
+					forwarding_fee_base_msat: Some(base_msat),
+					forwarding_fee_proportional_millionths: Some(proportional_millionths),
+					..Default::default()
+				};
+				let counterparty_node_id = details.counterparty.node_id;
+				updates.push((
+					counterparty_node_id,
+					details.channel_id,
+					multiplier_percent,
+					config_update,
+				));
+			}
+		}
+		updates
+	}
+
+	/// Records that the fees of the given channel were updated with the given multiplier.
+	pub(crate) fn fee_update_applied(&mut self, channel_id: &ChannelId, multiplier_percent: u16) {
+		if let Some(state) = self.channels.get_mut(channel_id) {
+			state.applied_multiplier_percent = multiplier_percent;
+		}
+	}
+}
+
+/// Adjusts the forwarding fees of a [`ChannelManager`]'s channels based on their outbound
+/// liquidity.
+///
+/// See the [module-level documentation] for details. The same surge pricing may also be enabled
+/// within the [`ChannelManager`] itself via [`ChannelManager::set_dynamic_fee_policy`], in which
+/// case no [`SurgePricer`] should be used for it.
+///
+/// Channels' baseline fees are those of the [`ChannelManager`]'s current default
+/// [`ChannelConfig`], as set via [`ChannelManager::set_current_config`], unless overridden via
+/// [`SurgePricer::set_baseline_fees`]. Fees should not otherwise be updated for channels while
+/// they're being surge priced, as they will be reset on the next tick. Overrides are not
+/// persisted, so they must be set again on restart.
+///
+/// [`ChannelManager::set_current_config`]: crate::ln::channelmanager::ChannelManager::set_current_config
+/// [`ChannelManager`]: crate::ln::channelmanager::ChannelManager
+/// [`ChannelManager::set_dynamic_fee_policy`]: crate::ln::channelmanager::ChannelManager::set_dynamic_fee_policy
+/// [module-level documentation]: crate::ln::surge_pricing
+pub struct SurgePricer<CM: Deref>
+where
+	CM::Target: AChannelManager,
+{
+	channel_manager: CM,
+	state: Mutex<SurgePricingState>,
+}
+
+impl<CM: Deref> SurgePricer<CM>
+where
+	CM::Target: AChannelManager,
+{
+	/// Constructs a new [`SurgePricer`] for the channels of the given [`ChannelManager`].
+	///
+	/// [`ChannelManager`]: crate::ln::channelmanager::ChannelManager
+	pub fn new(channel_manager: CM, config: SurgePricingConfig) -> Self {
+		Self { channel_manager, state: Mutex::new(SurgePricingState::new(config)) }
+	}
+
+	/// Overrides the fees to which multipliers are applied for the given channel, which are
+	/// otherwise those of the default [`ChannelConfig`], updating the channel's fees on the next
+	/// call to [`Self::timer_tick_occurred`].
+	pub fn set_baseline_fees(
+		&self, channel_id: ChannelId, base_msat: u32, proportional_millionths: u32,
+	) {
+		self.state.lock().unwrap().set_baseline_fees(
+			channel_id,
+			base_msat,
+			proportional_millionths,
+		);
+	}
+
+	/// Updates the fees of channels whose outbound liquidity changed enough since their last
+	/// update. Should be called roughly once per minute.
+	pub fn timer_tick_occurred(&self) {
+		let channel_manager = self.channel_manager.get_cm();
+		let channel_details = channel_manager.list_channels();
+		let default_config = channel_manager.get_current_config().channel_config;
+
+		let mut state = self.state.lock().unwrap();
+		for (counterparty_node_id, channel_id, multiplier_percent, config_update) in
+			state.fee_updates(&channel_details, &default_config)
+		{
+			let res = channel_manager.update_partial_channel_config(
+				&counterparty_node_id,
+				&[channel_id],
+				&config_update,
+			);
+			if res.is_ok() {
+				state.fee_update_applied(&channel_id, multiplier_percent);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{ChannelSurgeState, SurgePricer, SurgePricingConfig};
+
+	use crate::ln::functional_test_utils::*;
+	use crate::ln::msgs::{BaseMessageHandler, MessageSendEvent};
+
+	#[test]
+	fn interpolates_fee_multiplier() {
+		let config = SurgePricingConfig::default();
+		assert_eq!(config.fee_multiplier_percent(0), 500);
+		assert_eq!(config.fee_multiplier_percent(10), 350);
+		assert_eq!(config.fee_multiplier_percent(20), 200);
+		assert_eq!(config.fee_multiplier_percent(35), 150);
+		assert_eq!(config.fee_multiplier_percent(50), 100);
+		assert_eq!(config.fee_multiplier_percent(100), 100);
+
+		let empty = SurgePricingConfig { curve: Vec::new(), ..SurgePricingConfig::default() };
+		assert_eq!(empty.fee_multiplier_percent(0), 100);
+	}
+
+	#[test]
+	fn damps_fee_updates() {
+		let config = SurgePricingConfig {
+			min_fee_change_percent: 10,
+			bucket_capacity: 2,
+			refill_ticks: 3,
+			..SurgePricingConfig::default()
+		};
+		let mut state = ChannelSurgeState::new(&config, 1_000, 100);
+
+		// Small changes are ignored.
+		assert_eq!(state.take_update(&config, 105), None);
+		assert_eq!(state.take_update(&config, 110), Some(110));
+		state.applied_multiplier_percent = 110;
+		assert_eq!(state.fees(110), (1_100, 110));
+
+		// The bucket holds two tokens, so a third update must wait for a refill.
+		assert_eq!(state.take_update(&config, 200), Some(200));
+		state.applied_multiplier_percent = 200;
+		assert_eq!(state.take_update(&config, 500), None);
+		state.refill(&config);
+		state.refill(&config);
+		assert_eq!(state.take_update(&config, 500), None);
+		state.refill(&config);
+		assert_eq!(state.take_update(&config, 500), Some(500));
+	}
+
+	#[test]
+	fn raises_fees_of_depleted_channels() {
+		let chanmon_cfgs = create_chanmon_cfgs(2);
+		let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+		let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[None, None]);
+		let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+		create_announced_chan_between_nodes(&nodes, 0, 1);
+
+		let baseline = nodes[1].node.list_channels()[0].config.unwrap();
+
+		// Nearly all of the channel's liquidity is on node 0's side, so its fees are unchanged.
+		let pricer_0 = SurgePricer::new(nodes[0].node, SurgePricingConfig::default());
+		pricer_0.timer_tick_occurred();
+		assert!(nodes[0].node.get_and_clear_pending_msg_events().is_empty());
+
+		// Node 1 has almost no outbound liquidity, so its fees are raised to the maximum.
+		let pricer_1 = SurgePricer::new(nodes[1].node, SurgePricingConfig::default());
+		pricer_1.timer_tick_occurred();
+		let config = nodes[1].node.list_channels()[0].config.unwrap();
+		assert_eq!(config.forwarding_fee_base_msat, baseline.forwarding_fee_base_msat * 5);
+		assert_eq!(
+			config.forwarding_fee_proportional_millionths,
+			baseline.forwarding_fee_proportional_millionths * 5
+		);
+		let events = nodes[1].node.get_and_clear_pending_msg_events();
+		assert_eq!(events.len(), 1);
+		assert!(matches!(events[0], MessageSendEvent::BroadcastChannelUpdate { .. }));
+
+		// Nothing changed, so no further updates are made.
+		pricer_1.timer_tick_occurred();
+		assert!(nodes[1].node.get_and_clear_pending_msg_events().is_empty());
+
+		// Setting new baseline fees applies them with the current multiplier.
+		let channel_id = nodes[1].node.list_channels()[0].channel_id;
+		pricer_1.set_baseline_fees(channel_id, 1_000, 10);
+		pricer_1.timer_tick_occurred();
+		let config = nodes[1].node.list_channels()[0].config.unwrap();
+		assert_eq!(config.forwarding_fee_base_msat, 5_000);
+		assert_eq!(config.forwarding_fee_proportional_millionths, 50);
+		assert_eq!(nodes[1].node.get_and_clear_pending_msg_events().len(), 1);
+
+		// Overrides aren't persisted, so after a restart baselines are derived from the default
+		// config again rather than from the surged fees.
+		let pricer_1 = SurgePricer::new(nodes[1].node, SurgePricingConfig::default());
+		pricer_1.timer_tick_occurred();
+		let config = nodes[1].node.list_channels()[0].config.unwrap();
+		assert_eq!(config.forwarding_fee_base_msat, baseline.forwarding_fee_base_msat * 5);
+		assert_eq!(
+			config.forwarding_fee_proportional_millionths,
+			baseline.forwarding_fee_proportional_millionths * 5
+		);
+		assert_eq!(nodes[1].node.get_and_clear_pending_msg_events().len(), 1);
+
+		// Once in sync, restarting doesn't update the fees again.
+		let pricer_1 = SurgePricer::new(nodes[1].node, SurgePricingConfig::default());
+		pricer_1.timer_tick_occurred();
+		assert!(nodes[1].node.get_and_clear_pending_msg_events().is_empty());
+
+		// Changing the default config changes the baseline.
+		let mut user_config = nodes[1].node.get_current_config();
+		user_config.channel_config.forwarding_fee_base_msat = 2_000;
+		nodes[1].node.set_current_config(user_config);
+		pricer_1.timer_tick_occurred();
+		let config = nodes[1].node.list_channels()[0].config.unwrap();
+		assert_eq!(config.forwarding_fee_base_msat, 10_000);
+		assert_eq!(nodes[1].node.get_and_clear_pending_msg_events().len(), 1);
+	}
+}
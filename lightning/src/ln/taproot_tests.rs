@@ -0,0 +1,466 @@
+// This file is Copyright its original authors, visible in version control
+// history.
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Tests of the signing flow of taproot channels. As taproot channels are not yet supported by the
+//! channel state machine, two [`InMemorySigner`]s play both sides of a channel here, exchanging
+//! nonces and signatures as peers would.
+
+use crate::chain::transaction::OutPoint;
+use crate::ln::chan_utils::{
+	self, ChannelTransactionParameters, CommitmentTransaction,
+	CounterpartyChannelTransactionParameters, HTLCOutputInCommitment, HolderCommitmentTransaction,
+};
+use crate::ln::msgs::PartialSignatureWithNonce;
+use crate::sign::musig::{KeyAggContext, SecretNonce, SigningSession};
+use crate::sign::taproot::TaprootChannelSigner;
+use crate::sign::{ChannelDerivationParameters, ChannelSigner, HTLCDescriptor, InMemorySigner};
+use crate::types::features::ChannelTypeFeatures;
+use crate::types::payment::{PaymentHash, PaymentPreimage};
+
+use bitcoin::hashes::sha256::Hash as Sha256;
+use bitcoin::hashes::Hash;
+use bitcoin::locktime::absolute::LockTime;
+use bitcoin::secp256k1::{self, Message, Secp256k1, SecretKey, XOnlyPublicKey};
+use bitcoin::sighash::{Prevouts, SighashCache, TapSighashType};
+use bitcoin::taproot::{LeafVersion, TapLeafHash};
+use bitcoin::transaction::Version;
+use bitcoin::{Amount, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid, Witness};
+
+use musig2::types::PartialSignature;
+
+use crate::prelude::*;
+
+const CHANNEL_VALUE_SATOSHIS: u64 = 100_000;
+const CONTEST_DELAY: u16 = 144;
+
+fn signer(seed: u8) -> InMemorySigner {
+	let key = |i: u8| SecretKey::from_slice(&[seed + i; 32]).unwrap();
+	InMemorySigner::new(
+		key(0),
+		key(1),
+		key(2),
+		key(2),
+		true,
+		key(3),
+		key(4),
+		[seed + 5; 32],
+		[seed + 6; 32],
+		[seed + 7; 32],
+	)
+}
+
+fn channel_parameters(
+	signer: &InMemorySigner, counterparty_signer: &InMemorySigner, is_outbound: bool,
+	secp_ctx: &Secp256k1<secp256k1::All>,
+) -> ChannelTransactionParameters {
+	ChannelTransactionParameters {
+		holder_pubkeys: signer.pubkeys(secp_ctx),
+		holder_selected_contest_delay: CONTEST_DELAY,
+		is_outbound_from_holder: is_outbound,
+		counterparty_parameters: Some(CounterpartyChannelTransactionParameters {
+			pubkeys: counterparty_signer.pubkeys(secp_ctx),
+			selected_contest_delay: CONTEST_DELAY,
+		}),
+		funding_outpoint: Some(OutPoint { txid: Txid::all_zeros(), index: 0 }),
+		splice_parent_funding_txid: None,
+		channel_type_features: ChannelTypeFeatures::anchors_zero_htlc_fee_and_dependencies(),
+		channel_value_satoshis: CHANNEL_VALUE_SATOSHIS,
+	}
+}
+
+/// Builds a transaction spending `outpoint` to a dummy output, as would be done to claim it.
+fn spending_tx(txid: Txid, vout: u32, value: Amount) -> Transaction {
+	Transaction {
+		version: Version::TWO,
+		lock_time: LockTime::ZERO,
+		input: vec![TxIn {
+			previous_output: bitcoin::OutPoint { txid, vout },
+			script_sig: ScriptBuf::new(),
+			sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+			witness: Witness::new(),
+		}],
+		output: vec![TxOut { script_pubkey: ScriptBuf::new_op_return(&[]), value }],
+	}
+}
+
+fn tapscript_sighash(
+	tx: &Transaction, prevout: &TxOut, tapscript: &ScriptBuf, sighash_type: TapSighashType,
+) -> Message {
+	let leaf_hash = TapLeafHash::from_script(tapscript, LeafVersion::TapScript);
+	let sighash = SighashCache::new(tx)
+		.taproot_script_spend_signature_hash(0, &Prevouts::One(0, prevout), leaf_hash, sighash_type)
+		.unwrap();
+	Message::from_digest(sighash.to_byte_array())
+}
+
+#[test]
+fn test_taproot_commitment_signing_flow() {
+	let secp_ctx = Secp256k1::new();
+	let signer_a = signer(1);
+	let signer_b = signer(11);
+	let params_a = channel_parameters(&signer_a, &signer_b, true, &secp_ctx);
+	let params_b = channel_parameters(&signer_b, &signer_a, false, &secp_ctx);
+
+	let preimage = PaymentPreimage([42; 32]);
+	let payment_hash = PaymentHash(Sha256::hash(&preimage.0).to_byte_array());
+	let htlcs = vec![
+		HTLCOutputInCommitment {
+			offered: true,
+			amount_msat: 10_000_000,
+			cltv_expiry: 500,
+			payment_hash,
+			transaction_output_index: None,
+		},
+		HTLCOutputInCommitment {
+			offered: false,
+			amount_msat: 20_000_000,
+			cltv_expiry: 600,
+			payment_hash: PaymentHash([43; 32]),
+			transaction_output_index: None,
+		},
+	];
+
+	// B signs A's commitment transaction, using the nonce A committed to for it.
+	let commitment_number = (1 << 48) - 1;
+	let per_commitment_point = signer_a.get_per_commitment_point(commitment_number, &secp_ctx);
+	let per_commitment_point = per_commitment_point.unwrap();
+	let commitment_tx = CommitmentTransaction::new(
+		commitment_number,
+		&per_commitment_point,
+		50_000,
+		20_000,
+		253,
+		htlcs,
+		&params_a.as_holder_broadcastable(),
+		&secp_ctx,
+	);
+	let nonce_a = signer_a.generate_local_nonce_pair(commitment_number, &secp_ctx);
+	let (partial_sig_b, htlc_sigs_b) = signer_b
+		.partially_sign_counterparty_commitment(
+			&params_b,
+			nonce_a.clone(),
+			&commitment_tx,
+			Vec::new(),
+			Vec::new(),
+			&secp_ctx,
+		)
+		.unwrap();
+	assert_eq!(htlc_sigs_b.len(), 2);
+
+	// A finalizes its commitment transaction, rejecting a partial signature that is not B's.
+	let dummy_sig =
+		secp_ctx.sign_ecdsa(&Message::from_digest([42; 32]), &signer_a.funding_key(None));
+	let holder_commitment_tx = HolderCommitmentTransaction::new(
+		commitment_tx.clone(),
+		dummy_sig,
+		vec![dummy_sig; 2],
+		&params_a.holder_pubkeys.funding_pubkey,
+		&params_b.holder_pubkeys.funding_pubkey,
+	);
+	let PartialSignatureWithNonce(sig_b, nonce_b) = partial_sig_b.clone();
+	let bogus_sig = PartialSignature::from_slice(&[1; 32]).unwrap();
+	let bogus_partial_sig = PartialSignatureWithNonce(bogus_sig, nonce_b.clone());
+	assert!(signer_a
+		.finalize_holder_commitment(&params_a, &holder_commitment_tx, bogus_partial_sig, &secp_ctx)
+		.is_err());
+	let sig_a = signer_a
+		.finalize_holder_commitment(
+			&params_a,
+			&holder_commitment_tx,
+			partial_sig_b.clone(),
+			&secp_ctx,
+		)
+		.unwrap();
+
+	// Finalizing the same transaction again returns the same signature, while signing a different
+	// transaction with the same commitment number is refused, as it would reuse A's nonce.
+	let sig_a_again = signer_a
+		.finalize_holder_commitment(&params_a, &holder_commitment_tx, partial_sig_b, &secp_ctx)
+		.unwrap();
+	assert_eq!(sig_a.serialize(), sig_a_again.serialize());
+	let other_commitment_tx = CommitmentTransaction::new(
+		commitment_number,
+		&per_commitment_point,
+		50_000,
+		20_000,
+		254,
+		Vec::new(),
+		&params_a.as_holder_broadcastable(),
+		&secp_ctx,
+	);
+	let (other_partial_sig_b, _) = signer_b
+		.partially_sign_counterparty_commitment(
+			&params_b,
+			nonce_a.clone(),
+			&other_commitment_tx,
+			Vec::new(),
+			Vec::new(),
+			&secp_ctx,
+		)
+		.unwrap();
+	let other_holder_commitment_tx = HolderCommitmentTransaction::new(
+		other_commitment_tx,
+		dummy_sig,
+		Vec::new(),
+		&params_a.holder_pubkeys.funding_pubkey,
+		&params_b.holder_pubkeys.funding_pubkey,
+	);
+	assert!(signer_a
+		.finalize_holder_commitment(
+			&params_a,
+			&other_holder_commitment_tx,
+			other_partial_sig_b,
+			&secp_ctx
+		)
+		.is_err());
+
+	// The aggregate signature is valid for the funding output.
+	let funding_pubkeys =
+		[params_a.holder_pubkeys.funding_pubkey, params_b.holder_pubkeys.funding_pubkey];
+	let key_agg_ctx = KeyAggContext::new(&secp_ctx, &funding_pubkeys).unwrap();
+	let funding_output = TxOut {
+		value: Amount::from_sat(CHANNEL_VALUE_SATOSHIS),
+		script_pubkey: key_agg_ctx.script_pubkey(),
+	};
+	let trusted_tx = commitment_tx.trust();
+	let built_tx = trusted_tx.built_transaction();
+	let sighash = SighashCache::new(&built_tx.transaction)
+		.taproot_key_spend_signature_hash(
+			0,
+			&Prevouts::All(&[&funding_output]),
+			TapSighashType::Default,
+		)
+		.unwrap();
+	let sighash = Message::from_digest(sighash.to_byte_array());
+	let session =
+		SigningSession::new(&secp_ctx, &key_agg_ctx, &[&nonce_a, &nonce_b], &sighash).unwrap();
+	let sig = session.aggregate(&[&sig_a, &sig_b]).unwrap();
+	assert!(secp_ctx.verify_schnorr(&sig, &sighash, &key_agg_ctx.output_key()).is_ok());
+
+	// B's HTLC signatures, and A's own signatures on its HTLC transactions, are valid for the
+	// leaves of the HTLC outputs requiring both signatures.
+	let keys = trusted_tx.keys();
+	let htlc_key_a = XOnlyPublicKey::from(keys.broadcaster_htlc_key.to_public_key());
+	let htlc_key_b = XOnlyPublicKey::from(keys.countersignatory_htlc_key.to_public_key());
+	for (htlc, htlc_sig_b) in commitment_tx.nondust_htlcs().iter().zip(htlc_sigs_b.iter()) {
+		let htlc_tx = chan_utils::build_htlc_transaction(
+			&built_tx.txid,
+			commitment_tx.negotiated_feerate_per_kw(),
+			CONTEST_DELAY,
+			htlc,
+			&params_a.channel_type_features,
+			&keys.broadcaster_delayed_payment_key,
+			&keys.revocation_key,
+		);
+		let spend_info = chan_utils::get_taproot_htlc_spend_info(
+			&secp_ctx,
+			htlc,
+			&keys.broadcaster_htlc_key,
+			&keys.countersignatory_htlc_key,
+			&keys.revocation_key,
+		);
+		let prevout = TxOut {
+			value: htlc.to_bitcoin_amount(),
+			script_pubkey: ScriptBuf::new_p2tr_tweaked(spend_info.output_key()),
+		};
+		let (success_script, timeout_script) = chan_utils::get_taproot_htlc_scripts(
+			htlc,
+			&keys.broadcaster_htlc_key,
+			&keys.countersignatory_htlc_key,
+		);
+		let tapscript = if htlc.offered { &timeout_script } else { &success_script };
+		let sighash = tapscript_sighash(
+			&htlc_tx,
+			&prevout,
+			tapscript,
+			TapSighashType::SinglePlusAnyoneCanPay,
+		);
+		assert!(secp_ctx.verify_schnorr(htlc_sig_b, &sighash, &htlc_key_b).is_ok());
+
+		let htlc_descriptor = HTLCDescriptor {
+			channel_derivation_parameters: ChannelDerivationParameters {
+				value_satoshis: CHANNEL_VALUE_SATOSHIS,
+				keys_id: signer_a.channel_keys_id(),
+				transaction_parameters: params_a.clone(),
+			},
+			commitment_txid: built_tx.txid,
+			per_commitment_number: commitment_number,
+			per_commitment_point,
+			feerate_per_kw: commitment_tx.negotiated_feerate_per_kw(),
+			htlc: htlc.clone(),
+			preimage: if htlc.offered { None } else { Some(PaymentPreimage([43; 32])) },
+			counterparty_sig: dummy_sig,
+		};
+		let htlc_sig_a = signer_a
+			.sign_holder_htlc_transaction(&htlc_tx, 0, &htlc_descriptor, &secp_ctx)
+			.unwrap();
+		let sighash = tapscript_sighash(&htlc_tx, &prevout, tapscript, TapSighashType::Default);
+		assert!(secp_ctx.verify_schnorr(&htlc_sig_a, &sighash, &htlc_key_a).is_ok());
+
+		// B can claim the HTLC A offered with the preimage, without A's signature.
+		if htlc.offered {
+			let claim_tx = spending_tx(
+				built_tx.txid,
+				htlc.transaction_output_index.unwrap(),
+				htlc.to_bitcoin_amount(),
+			);
+			let claim_sig = signer_b
+				.sign_counterparty_htlc_transaction(
+					&params_b,
+					&claim_tx,
+					0,
+					htlc.to_bitcoin_amount().to_sat(),
+					&per_commitment_point,
+					htlc,
+					&secp_ctx,
+				)
+				.unwrap();
+			let sighash = tapscript_sighash(
+				&claim_tx,
+				&prevout,
+				&success_script,
+				TapSighashType::AllPlusAnyoneCanPay,
+			);
+			assert!(secp_ctx.verify_schnorr(&claim_sig, &sighash, &htlc_key_b).is_ok());
+		}
+	}
+
+	// Once A revokes the commitment transaction, B can claim all of its outputs.
+	let per_commitment_secret = signer_a.release_commitment_secret(commitment_number).unwrap();
+	let per_commitment_key = SecretKey::from_slice(&per_commitment_secret).unwrap();
+	let revocation_key = XOnlyPublicKey::from(keys.revocation_key.to_public_key());
+
+	let to_local_index = trusted_tx.revokeable_output_index().unwrap();
+	let to_local_value = built_tx.transaction.output[to_local_index].value;
+	let justice_tx = spending_tx(built_tx.txid, to_local_index as u32, to_local_value);
+	let justice_sig = signer_b
+		.sign_justice_revoked_output(
+			&params_b,
+			&justice_tx,
+			0,
+			to_local_value.to_sat(),
+			&per_commitment_key,
+			&secp_ctx,
+		)
+		.unwrap();
+	let spend_info = chan_utils::get_taproot_to_local_spend_info(
+		&secp_ctx,
+		&keys.revocation_key,
+		CONTEST_DELAY,
+		&keys.broadcaster_delayed_payment_key,
+	);
+	let (_, revocation_script) = chan_utils::get_taproot_to_local_scripts(
+		&keys.revocation_key,
+		CONTEST_DELAY,
+		&keys.broadcaster_delayed_payment_key,
+	);
+	let prevout = TxOut {
+		value: to_local_value,
+		script_pubkey: ScriptBuf::new_p2tr_tweaked(spend_info.output_key()),
+	};
+	let sighash = tapscript_sighash(
+		&justice_tx,
+		&prevout,
+		&revocation_script,
+		TapSighashType::AllPlusAnyoneCanPay,
+	);
+	assert!(secp_ctx.verify_schnorr(&justice_sig, &sighash, &revocation_key).is_ok());
+
+	for htlc in commitment_tx.nondust_htlcs() {
+		let justice_tx = spending_tx(
+			built_tx.txid,
+			htlc.transaction_output_index.unwrap(),
+			htlc.to_bitcoin_amount(),
+		);
+		let justice_sig = signer_b
+			.sign_justice_revoked_htlc(
+				&params_b,
+				&justice_tx,
+				0,
+				htlc.to_bitcoin_amount().to_sat(),
+				&per_commitment_key,
+				htlc,
+				&secp_ctx,
+			)
+			.unwrap();
+		let spend_info = chan_utils::get_taproot_htlc_spend_info(
+			&secp_ctx,
+			htlc,
+			&keys.broadcaster_htlc_key,
+			&keys.countersignatory_htlc_key,
+			&keys.revocation_key,
+		);
+		let prevout = TxOut {
+			value: htlc.to_bitcoin_amount(),
+			script_pubkey: ScriptBuf::new_p2tr_tweaked(spend_info.output_key()),
+		};
+		let sighash = SighashCache::new(&justice_tx)
+			.taproot_key_spend_signature_hash(
+				0,
+				&Prevouts::One(0, &prevout),
+				TapSighashType::AllPlusAnyoneCanPay,
+			)
+			.unwrap();
+		let sighash = Message::from_digest(sighash.to_byte_array());
+		let output_key = spend_info.output_key().to_inner();
+		assert!(secp_ctx.verify_schnorr(&justice_sig, &sighash, &output_key).is_ok());
+	}
+}
+
+#[test]
+fn test_taproot_closing_signing_flow() {
+	let secp_ctx = Secp256k1::new();
+	let signer_a = signer(1);
+	let signer_b = signer(11);
+	let params_a = channel_parameters(&signer_a, &signer_b, true, &secp_ctx);
+	let params_b = channel_parameters(&signer_b, &signer_a, false, &secp_ctx);
+
+	let funding_outpoint = bitcoin::OutPoint { txid: Txid::all_zeros(), vout: 0 };
+	let closing_tx = chan_utils::ClosingTransaction::new(
+		60_000,
+		39_000,
+		ScriptBuf::new_op_return(&[1]),
+		ScriptBuf::new_op_return(&[2]),
+		funding_outpoint,
+	);
+	// B proposes the closing transaction, signing it with the nonce A committed to.
+	let secret_nonce_a = SecretNonce::from_random_bytes([42; 32], [43; 32]).unwrap();
+	let nonce_a = secret_nonce_a.public_nonce(&secp_ctx);
+	let PartialSignatureWithNonce(sig_b, nonce_b) = signer_b
+		.partially_sign_closing_transaction(&params_b, nonce_a.clone(), &closing_tx, &secp_ctx)
+		.unwrap();
+
+	let funding_pubkeys =
+		[params_a.holder_pubkeys.funding_pubkey, params_b.holder_pubkeys.funding_pubkey];
+	let key_agg_ctx = KeyAggContext::new(&secp_ctx, &funding_pubkeys).unwrap();
+	let funding_output = TxOut {
+		value: Amount::from_sat(CHANNEL_VALUE_SATOSHIS),
+		script_pubkey: key_agg_ctx.script_pubkey(),
+	};
+	let sighash = SighashCache::new(closing_tx.trust().built_transaction())
+		.taproot_key_spend_signature_hash(
+			0,
+			&Prevouts::All(&[&funding_output]),
+			TapSighashType::Default,
+		)
+		.unwrap();
+	let sighash = Message::from_digest(sighash.to_byte_array());
+	let nonces = [&nonce_a, &nonce_b];
+	let session = SigningSession::new(&secp_ctx, &key_agg_ctx, &nonces, &sighash).unwrap();
+	let funding_pubkey_b = &params_b.holder_pubkeys.funding_pubkey;
+	assert!(session
+		.verify_partial_signature(&secp_ctx, &sig_b, &nonce_b, funding_pubkey_b)
+		.is_ok());
+
+	// A completes the signature with its own partial signature.
+	let funding_key_a = signer_a.funding_key(None);
+	let sig_a = session.partial_sign(&secp_ctx, secret_nonce_a, &funding_key_a).unwrap();
+	let sig = session.aggregate(&[&sig_a, &sig_b]).unwrap();
+	assert!(secp_ctx.verify_schnorr(&sig, &sighash, &key_agg_ctx.output_key()).is_ok());
+}
@@ -1,5 +1,6 @@
 //! Functional tests testing channel feerate handling.
 
+use crate::chain::chaininterface::{DefaultUpdateFeePolicy, UpdateFeeThresholds};
 use crate::events::{ClosureReason, Event};
 use crate::ln::chan_utils::{
 	self, commitment_tx_base_weight, CommitmentTransaction, HTLCOutputInCommitment,
@@ -15,14 +16,16 @@ use crate::ln::msgs::{
 	self, BaseMessageHandler, ChannelMessageHandler, ErrorAction, MessageSendEvent,
 };
 use crate::ln::outbound_payment::RecipientOnionFields;
+use crate::ln::peer_misbehavior::MisbehaviorKind;
+use crate::ln::types::ChannelId;
 use crate::sign::ecdsa::EcdsaChannelSigner;
 use crate::types::features::ChannelTypeFeatures;
-use crate::util::config::UserConfig;
+use crate::util::config::{ChannelConfigUpdate, UserConfig};
 use crate::util::errors::APIError;
 
 use lightning_macros::xtest;
 
-use bitcoin::secp256k1::Secp256k1;
+use bitcoin::secp256k1::{Message, Secp256k1, SecretKey};
 
 #[xtest(feature = "_externalize_tests")]
 pub fn test_async_inbound_update_fee() {
@@ -1003,6 +1006,238 @@ pub fn accept_busted_but_better_fee() {
 	};
 }
 
+#[xtest(feature = "_externalize_tests")]
+pub fn test_rejected_update_fee_recorded_as_misbehavior() {
+	// Test that when we reject a peer's `update_fee`, the rejection is recorded in the
+	// `ChannelManager`'s misbehavior ledger, with the kind of misbehavior depending on the cause of
+	// the rejection rather than the message which was rejected.
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[None, None]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+
+	let node_a_id = nodes[0].node.get_our_node_id();
+	let node_b_id = nodes[1].node.get_our_node_id();
+
+	let chan_id = create_chan_between_nodes(&nodes[0], &nodes[1]).3;
+	assert!(nodes[1].node.list_misbehaving_peers().is_empty());
+
+	// Messages for unknown channels are protocol violations, even if they carry a signature.
+	let unknown_chan_id = ChannelId([42; 32]);
+	let update_fee = msgs::UpdateFee { channel_id: unknown_chan_id, feerate_per_kw: 253 };
+	nodes[1].node.handle_update_fee(node_a_id, &update_fee);
+	let secp_ctx = Secp256k1::new();
+	let secret_key = SecretKey::from_slice(&[42; 32]).unwrap();
+	let commitment_signed = msgs::CommitmentSigned {
+		channel_id: unknown_chan_id,
+		signature: secp_ctx.sign_ecdsa(&Message::from_digest([42; 32]), &secret_key),
+		htlc_signatures: Vec::new(),
+		funding_txid: None,
+		#[cfg(taproot)]
+		partial_signature_with_nonce: None,
+	};
+	nodes[1].node.handle_commitment_signed(node_a_id, &commitment_signed);
+	assert_eq!(nodes[1].node.get_and_clear_pending_msg_events().len(), 2);
+
+	let incidents = nodes[1].node.list_peer_misbehavior(&node_a_id);
+	assert_eq!(incidents.len(), 2);
+	assert!(incidents.iter().all(|incident| incident.kind == MisbehaviorKind::ProtocolViolation));
+	nodes[1].node.clear_peer_misbehavior(&node_a_id);
+
+	// nodes[1] expects 253 sat/kW, so a decrease below that is rejected and the channel closed.
+	// This may well be due to an honest disagreement between fee estimators though, so it's not
+	// considered fee abuse.
+	let update_fee = msgs::UpdateFee { channel_id: chan_id, feerate_per_kw: 200 };
+	nodes[1].node.handle_update_fee(node_a_id, &update_fee);
+	let reason = ClosureReason::PeerFeerateTooLow {
+		peer_feerate_sat_per_kw: 200,
+		required_feerate_sat_per_kw: 253,
+	};
+	check_closed_event(&nodes[1], 1, reason, &[node_a_id], 100000);
+	check_closed_broadcast!(nodes[1], true);
+	check_added_monitors(&nodes[1], 1);
+
+	assert_eq!(nodes[1].node.list_misbehaving_peers(), vec![(node_a_id, 1)]);
+	let incidents = nodes[1].node.list_peer_misbehavior(&node_a_id);
+	assert_eq!(incidents.len(), 1);
+	assert_eq!(incidents[0].kind, MisbehaviorKind::ProtocolViolation);
+	assert_eq!(incidents[0].channel_id, Some(chan_id));
+
+	nodes[1].node.clear_peer_misbehavior(&node_a_id);
+	assert!(nodes[1].node.list_peer_misbehavior(&node_a_id).is_empty());
+
+	// An `update_fee` from the peer which isn't the channel's funder is fee abuse.
+	assert!(nodes[0].node.list_misbehaving_peers().is_empty());
+	nodes[0].node.handle_update_fee(node_b_id, &update_fee);
+	let err = "Non-funding remote tried to update channel fee".to_owned();
+	check_closed_event(&nodes[0], 1, ClosureReason::ProcessingError { err }, &[node_b_id], 100000);
+	check_closed_broadcast!(nodes[0], true);
+	check_added_monitors(&nodes[0], 1);
+
+	let incidents = nodes[0].node.list_peer_misbehavior(&node_b_id);
+	assert_eq!(incidents.len(), 1);
+	assert_eq!(incidents[0].kind, MisbehaviorKind::FeeAbuse);
+	assert_eq!(incidents[0].channel_id, Some(chan_id));
+}
+
+#[xtest(feature = "_externalize_tests")]
+pub fn test_update_fee_policy_near_limit_events() {
+	// Test that a configured `UpdateFeePolicy` generates `Event::PeerFeerateNearLimit` when our
+	// counterparty sets a feerate close to our lower limit, only warning again once the feerate
+	// has recovered past the policy's hysteresis threshold.
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[None, None]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+
+	let node_a_id = nodes[0].node.get_our_node_id();
+
+	let chan_id = create_announced_chan_between_nodes(&nodes, 0, 1).2;
+
+	// With an estimate of 1,000 sat/kW, nodes[1] will reject feerates below 1,000 sat/kW, warn
+	// about feerates below 1,500 sat/kW, and warn again once the feerate reached 2,000 sat/kW.
+	*chanmon_cfgs[1].fee_estimator.sat_per_kw.lock().unwrap() = 1000;
+	nodes[1].node.set_update_fee_policy(DefaultUpdateFeePolicy {
+		anchor_channels: UpdateFeeThresholds::default(),
+		non_anchor_channels: UpdateFeeThresholds {
+			min_feerate_percent: 100,
+			warn_below_percent: 150,
+			hysteresis_percent: 50,
+		},
+	});
+
+	let update_fee = |feerate| {
+		*chanmon_cfgs[0].fee_estimator.sat_per_kw.lock().unwrap() = feerate;
+		nodes[0].node.timer_tick_occurred();
+		check_added_monitors(&nodes[0], 1);
+
+		let events = nodes[0].node.get_and_clear_pending_msg_events();
+		assert_eq!(events.len(), 1);
+		match events[0] {
+			MessageSendEvent::UpdateHTLCs {
+				updates: msgs::CommitmentUpdate { ref update_fee, ref commitment_signed, .. },
+				..
+			} => {
+				assert_eq!(update_fee.as_ref().unwrap().feerate_per_kw, feerate);
+				nodes[1].node.handle_update_fee(node_a_id, update_fee.as_ref().unwrap());
+				do_commitment_signed_dance(&nodes[1], &nodes[0], &commitment_signed, false, false);
+			},
+			_ => panic!("Unexpected event"),
+		}
+	};
+	let expect_near_limit_event = |feerate| {
+		let events = nodes[1].node.get_and_clear_pending_events();
+		assert_eq!(events.len(), 1);
+		match events[0] {
+			Event::PeerFeerateNearLimit {
+				channel_id,
+				counterparty_node_id,
+				feerate_sat_per_1000_weight,
+				min_feerate_sat_per_1000_weight,
+				..
+			} => {
+				assert_eq!(channel_id, chan_id);
+				assert_eq!(counterparty_node_id, node_a_id);
+				assert_eq!(feerate_sat_per_1000_weight, feerate);
+				assert_eq!(min_feerate_sat_per_1000_weight, 1000);
+			},
+			_ => panic!("Unexpected event"),
+		}
+	};
+
+	update_fee(1200);
+	expect_near_limit_event(1200);
+
+	// We've already warned, so no new event is generated until the feerate recovers.
+	update_fee(1300);
+	assert!(nodes[1].node.get_and_clear_pending_events().is_empty());
+
+	update_fee(2500);
+	assert!(nodes[1].node.get_and_clear_pending_events().is_empty());
+
+	update_fee(1250);
+	expect_near_limit_event(1250);
+}
+
+#[xtest(feature = "_externalize_tests")]
+pub fn test_update_fee_limits_and_forced_update() {
+	// Test that the feerate updates we send are rate limited, stepped and capped per our
+	// `ChannelConfig`, and that `force_update_channel_fee` bypasses these limits.
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[None, None]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+
+	let node_a_id = nodes[0].node.get_our_node_id();
+	let node_b_id = nodes[1].node.get_our_node_id();
+
+	let chan_id = create_announced_chan_between_nodes(&nodes, 0, 1).2;
+	let config_update = ChannelConfigUpdate {
+		min_update_fee_interval_ticks: Some(2),
+		max_update_fee_step_percent: Some(50),
+		max_update_feerate_sat_per_1000_weight: Some(1000),
+		..Default::default()
+	};
+	nodes[0].node.update_partial_channel_config(&node_b_id, &[chan_id], &config_update).unwrap();
+	assert_eq!(nodes[0].node.get_and_clear_pending_msg_events().len(), 0);
+
+	let expect_update_fee = |feerate| {
+		let events = nodes[0].node.get_and_clear_pending_msg_events();
+		assert_eq!(events.len(), 1);
+		match events[0] {
+			MessageSendEvent::UpdateHTLCs {
+				updates: msgs::CommitmentUpdate { ref update_fee, ref commitment_signed, .. },
+				..
+			} => {
+				assert_eq!(update_fee.as_ref().unwrap().feerate_per_kw, feerate);
+				nodes[1].node.handle_update_fee(node_a_id, update_fee.as_ref().unwrap());
+				do_commitment_signed_dance(&nodes[1], &nodes[0], &commitment_signed, false, false);
+			},
+			_ => panic!("Unexpected event"),
+		}
+	};
+
+	// The estimate is capped at 1,000 sat/kW, and we only move halfway from the current feerate of
+	// 253 sat/kW towards it.
+	*chanmon_cfgs[0].fee_estimator.sat_per_kw.lock().unwrap() = 5000;
+	nodes[0].node.timer_tick_occurred();
+	check_added_monitors(&nodes[0], 1);
+	expect_update_fee(379);
+
+	// We have to wait two ticks before updating the feerate again.
+	nodes[0].node.timer_tick_occurred();
+	assert!(nodes[0].node.get_and_clear_pending_msg_events().is_empty());
+	check_added_monitors(&nodes[0], 0);
+	nodes[0].node.timer_tick_occurred();
+	check_added_monitors(&nodes[0], 1);
+	expect_update_fee(568);
+
+	// Forced updates may only be sent by the funder and bypass all of the limits.
+	let res = nodes[1].node.force_update_channel_fee(&chan_id, &node_a_id, 2000);
+	assert!(matches!(res, Err(APIError::APIMisuseError { .. })));
+	let res = nodes[0].node.force_update_channel_fee(&chan_id, &node_b_id, 100);
+	assert!(matches!(res, Err(APIError::APIMisuseError { .. })));
+	nodes[0].node.force_update_channel_fee(&chan_id, &node_b_id, 2000).unwrap();
+	let events = nodes[0].node.get_and_clear_pending_msg_events();
+	check_added_monitors(&nodes[0], 1);
+	match events[..] {
+		[MessageSendEvent::UpdateHTLCs {
+			updates: msgs::CommitmentUpdate { ref update_fee, ref commitment_signed, .. },
+			..
+		}] => {
+			assert_eq!(update_fee.as_ref().unwrap().feerate_per_kw, 2000);
+			nodes[1].node.handle_update_fee(node_a_id, update_fee.as_ref().unwrap());
+			do_commitment_signed_dance(&nodes[1], &nodes[0], &commitment_signed, false, false);
+		},
+		_ => panic!("Unexpected event"),
+	}
+
+	// The forced update counts as our last one, so we don't immediately move back.
+	nodes[0].node.timer_tick_occurred();
+	assert!(nodes[0].node.get_and_clear_pending_msg_events().is_empty());
+	check_added_monitors(&nodes[0], 0);
+}
+
 #[xtest(feature = "_externalize_tests")]
 pub fn cannot_afford_on_holding_cell_release() {
 	do_cannot_afford_on_holding_cell_release(ChannelTypeFeatures::only_static_remote_key(), true);
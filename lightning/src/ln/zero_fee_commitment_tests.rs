@@ -1,14 +1,19 @@
+use crate::events::bump_transaction::sync::{BumpTransactionEventHandlerSync, WalletSync};
+use crate::events::bump_transaction::{BumpTransactionEvent, DefaultFeeBumpPolicy, FeeBumpPolicy};
 use crate::events::{ClosureReason, Event};
 use crate::ln::chan_utils;
 use crate::ln::chan_utils::{
 	BASE_INPUT_WEIGHT, BASE_TX_SIZE, EMPTY_SCRIPT_SIG_WEIGHT, EMPTY_WITNESS_WEIGHT,
 	P2WSH_TXOUT_WEIGHT, SEGWIT_MARKER_FLAG_WEIGHT, TRUC_CHILD_MAX_WEIGHT,
 };
+use crate::ln::channel_state::CommitmentFormat;
 use crate::ln::functional_test_utils::*;
 use crate::ln::msgs::BaseMessageHandler;
 use crate::prelude::*;
+use crate::sync::Arc;
 
 use bitcoin::constants::WITNESS_SCALE_FACTOR;
+use bitcoin::transaction::Version;
 use bitcoin::Amount;
 
 #[test]
@@ -39,6 +44,10 @@ fn test_p2a_anchor_values_under_trims_and_rounds() {
 	)
 	.2;
 
+	let details = nodes[0].node.list_channels().pop().unwrap();
+	assert_eq!(details.commitment_format, Some(CommitmentFormat::ZeroFeeCommitments));
+	assert_eq!(details.p2a_anchor_value_satoshis, Some(0));
+
 	macro_rules! p2a_value_test {
 		([$($node_0_1_amt_msat:expr),*], $expected_p2a_value_sat:expr) => {
 			p2a_value_test!([$($node_0_1_amt_msat),*], [], $expected_p2a_value_sat)
@@ -60,6 +69,8 @@ fn test_p2a_anchor_values_under_trims_and_rounds() {
 			let txn = get_local_commitment_txn!(nodes[1], chan_id);
 			assert_eq!(txn.len(), 1);
 			assert_eq!(txn[0].output.iter().find(|output| output.script_pubkey == chan_utils::shared_anchor_script_pubkey()).unwrap().value.to_sat(), $expected_p2a_value_sat);
+			assert_eq!(nodes[0].node.list_channels()[0].p2a_anchor_value_satoshis, Some($expected_p2a_value_sat));
+			assert_eq!(nodes[1].node.list_channels()[0].p2a_anchor_value_satoshis, Some($expected_p2a_value_sat));
 			for hash in node_0_1_hashes {
 				fail_payment(&nodes[0], &[&nodes[1]], hash);
 			}
@@ -296,6 +307,132 @@ fn test_htlc_claim_chunking() {
 	);
 }
 
+#[test]
+fn test_htlc_claim_max_tx_weight() {
+	// Assert that HTLC claims are split into transactions no heavier than the maximum weight
+	// configured on the `BumpTransactionEventHandler`, rather than the TRUC_MAX_WEIGHT default.
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let mut user_cfg = test_default_channel_config();
+	user_cfg.channel_handshake_config.negotiate_anchor_zero_fee_commitments = true;
+	user_cfg.manually_accept_inbound_channels = true;
+
+	let configs = [Some(user_cfg.clone()), Some(user_cfg)];
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &configs);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+
+	let coinbase_tx = provide_anchor_utxo_reserves(&nodes, 50, Amount::from_sat(500));
+
+	const CHAN_CAPACITY: u64 = 10_000_000;
+	let (_, _, chan_id, _funding_tx) = create_announced_chan_between_nodes_with_value(
+		&nodes,
+		0,
+		1,
+		CHAN_CAPACITY,
+		(CHAN_CAPACITY / 2) * 1000,
+	);
+
+	let channel_type = nodes[1].node.list_channels()[0].channel_type.clone().unwrap();
+
+	const NUM_HTLCS: usize = 10;
+	let mut node_1_preimages = Vec::new();
+	for _ in 0..NUM_HTLCS {
+		let (preimage, payment_hash, _, _) = route_payment(&nodes[0], &[&nodes[1]], 1_000_000);
+		node_1_preimages.push((preimage, payment_hash));
+	}
+	let node_1_commit_tx = get_local_commitment_txn!(nodes[1], chan_id);
+	assert_eq!(node_1_commit_tx.len(), 1);
+
+	for (preimage, payment_hash) in node_1_preimages {
+		nodes[1].node.claim_funds(preimage);
+		check_added_monitors(&nodes[1], 1);
+		expect_payment_claimed!(nodes[1], payment_hash, 1_000_000);
+	}
+	nodes[0].node.get_and_clear_pending_msg_events();
+	nodes[1].node.get_and_clear_pending_msg_events();
+
+	mine_transaction(&nodes[0], &node_1_commit_tx[0]);
+	mine_transaction(&nodes[1], &node_1_commit_tx[0]);
+
+	// Allow for roughly five HTLCs per transaction, with room left for the wallet's inputs.
+	let htlc_weight = chan_utils::aggregated_htlc_success_input_output_pair_weight(&channel_type);
+	let max_htlc_tx_weight = htlc_weight * 5 + 2000;
+	let wallet = WalletSync::new(Arc::clone(&nodes[1].wallet_source), nodes[1].logger);
+	let bump_tx_handler = BumpTransactionEventHandlerSync::new(
+		nodes[1].tx_broadcaster,
+		&wallet,
+		nodes[1].keys_manager,
+		&DefaultFeeBumpPolicy,
+		nodes[1].logger,
+	)
+	.with_max_htlc_tx_weight(max_htlc_tx_weight);
+
+	let mut events = nodes[1].chain_monitor.chain_monitor.get_and_clear_pending_events();
+	assert_eq!(events.len(), 1);
+	let bump_event = match events.pop().unwrap() {
+		Event::BumpTransaction(bump_event) => bump_event,
+		_ => panic!("Unexpected event"),
+	};
+	bump_tx_handler.handle_event(&bump_event);
+
+	let htlc_claims = nodes[1].tx_broadcaster.txn_broadcast();
+	assert!(htlc_claims.len() >= 2);
+	let mut num_htlcs_claimed = 0;
+	let mut total_fee = Amount::ZERO;
+	for htlc_claim in htlc_claims.iter() {
+		check_spends!(htlc_claim, node_1_commit_tx[0], coinbase_tx);
+		assert!(htlc_claim.weight().to_wu() <= max_htlc_tx_weight);
+		num_htlcs_claimed += htlc_claim
+			.input
+			.iter()
+			.filter(|input| input.previous_output.txid == node_1_commit_tx[0].compute_txid())
+			.count();
+		let input_amount = htlc_claim
+			.input
+			.iter()
+			.map(|input| {
+				let vout = input.previous_output.vout as usize;
+				if input.previous_output.txid == coinbase_tx.compute_txid() {
+					coinbase_tx.output[vout].value
+				} else {
+					node_1_commit_tx[0].output[vout].value
+				}
+			})
+			.sum::<Amount>();
+		total_fee += input_amount - htlc_claim.output.iter().map(|o| o.value).sum::<Amount>();
+	}
+	assert_eq!(num_htlcs_claimed, NUM_HTLCS);
+
+	// If our fee bump policy can't afford to claim all of the HTLCs, none of the transactions
+	// should be broadcast, even though the first ones fit within the limit on their own.
+	struct MaxTotalFeePolicy(Amount);
+	impl FeeBumpPolicy for MaxTotalFeePolicy {
+		fn max_total_fee(&self, _event: &BumpTransactionEvent) -> Option<Amount> {
+			Some(self.0)
+		}
+	}
+	let policy = MaxTotalFeePolicy(total_fee - Amount::from_sat(1));
+	let bump_tx_handler = BumpTransactionEventHandlerSync::new(
+		nodes[1].tx_broadcaster,
+		&wallet,
+		nodes[1].keys_manager,
+		&policy,
+		nodes[1].logger,
+	)
+	.with_max_htlc_tx_weight(max_htlc_tx_weight);
+	bump_tx_handler.handle_event(&bump_event);
+	assert!(nodes[1].tx_broadcaster.txn_broadcast().is_empty());
+
+	check_closed_broadcast!(nodes[0], true);
+	check_added_monitors(&nodes[0], 1);
+	let reason = ClosureReason::CommitmentTxConfirmed;
+	check_closed_event(&nodes[0], 1, reason, &[nodes[1].node.get_our_node_id()], CHAN_CAPACITY);
+	check_closed_broadcast!(nodes[1], true);
+	check_added_monitors(&nodes[1], 1);
+	let reason = ClosureReason::CommitmentTxConfirmed;
+	check_closed_event(&nodes[1], 1, reason, &[nodes[0].node.get_our_node_id()], CHAN_CAPACITY);
+}
+
 #[test]
 fn test_anchor_tx_too_big() {
 	// Assert all V3 anchor tx transactions are below TRUC_CHILD_MAX_WEIGHT.
@@ -400,6 +537,9 @@ fn test_anchor_tx_too_big() {
 	assert_eq!(txns.len(), 2);
 	check_spends!(txns[1], txns[0], coinbase_tx_b);
 	assert!(txns[1].weight().to_wu() < TRUC_CHILD_MAX_WEIGHT);
+	// Both the commitment and its anchor spend are TRUC transactions, broadcast as a package.
+	assert_eq!(txns[0].version, Version::non_standard(3));
+	assert_eq!(txns[1].version, Version::non_standard(3));
 
 	assert_eq!(txns[0].compute_txid(), commitment_txid);
 	assert_eq!(txns[1].input.len(), 2);
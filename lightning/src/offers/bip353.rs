@@ -0,0 +1,239 @@
+// This file is Copyright its original authors, visible in version control
+// history.
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Utilities for publishing [`Offer`]s under [BIP 353] Human Readable Names.
+//!
+//! A [`Bip353Record`] links a [`HumanReadableName`] to an [`Offer`] via a DNS `TXT` record
+//! containing a `bitcoin:` URI, which payers resolve, e.g., using an `OMNameResolver`. A
+//! [`Bip353Zone`] collects such records for any number of users and renders them in the standard
+//! zone file format, ready to be included in the zone of the corresponding domain.
+//!
+//! Note that BIP 353 requires records to be served with DNSSEC. Thus, the rendered zone must be
+//! signed, e.g., using `ldns-signzone` or the DNS server's online signing, before it is published.
+//!
+//! [BIP 353]: https://github.com/bitcoin/bips/blob/master/bip-0353.mediawiki
+
+use crate::offers::offer::Offer;
+use crate::onion_message::dns_resolution::HumanReadableName;
+
+use crate::prelude::*;
+
+use core::fmt::Write;
+
+/// The default TTL, in seconds, of records rendered by [`Bip353Zone`].
+pub const DEFAULT_RECORD_TTL: u32 = 3600;
+
+/// The maximum length of a single character-string within a DNS `TXT` record.
+const MAX_TXT_STRING_LEN: usize = 255;
+
+/// A [BIP 353] DNS `TXT` record linking a [`HumanReadableName`] to an [`Offer`].
+///
+/// [BIP 353]: https://github.com/bitcoin/bips/blob/master/bip-0353.mediawiki
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Bip353Record {
+	name: HumanReadableName,
+	uri: String,
+}
+
+impl Bip353Record {
+	/// Creates a record for paying `offer` when resolving `name`.
+	pub fn for_offer(name: HumanReadableName, offer: &Offer) -> Self {
+		Self { name, uri: format!("bitcoin:?lno={}", offer) }
+	}
+
+	/// Gets the [`HumanReadableName`] the record is published for.
+	pub fn name(&self) -> &HumanReadableName {
+		&self.name
+	}
+
+	/// Gets the fully-qualified DNS name the record must be published under, i.e.,
+	/// `user.user._bitcoin-payment.domain.`.
+	pub fn dns_name(&self) -> String {
+		format!("{}.user._bitcoin-payment.{}.", self.name.user(), self.name.domain())
+	}
+
+	/// Gets the `bitcoin:` URI contained in the record.
+	pub fn uri(&self) -> &str {
+		&self.uri
+	}
+
+	/// Gets the URI split into the character-strings making up the `TXT` record's data, as each
+	/// may be at most 255 bytes long. Resolvers concatenate them to recover the URI.
+	pub fn txt_strings(&self) -> Vec<&str> {
+		// The URI only contains ASCII characters, so any byte offset is a character boundary.
+		debug_assert!(self.uri.is_ascii());
+		let mut strings = Vec::with_capacity(self.uri.len() / MAX_TXT_STRING_LEN + 1);
+		let mut remaining = self.uri.as_str();
+		while !remaining.is_empty() {
+			let (string, rest) = remaining.split_at(remaining.len().min(MAX_TXT_STRING_LEN));
+			strings.push(string);
+			remaining = rest;
+		}
+		strings
+	}
+
+	/// Renders the record as a single zone file line with the given TTL, in seconds.
+	pub fn to_zone_entry(&self, ttl: u32) -> String {
+		let mut entry = format!("{} {} IN TXT", self.dns_name(), ttl);
+		for string in self.txt_strings() {
+			let _ = write!(entry, " \"{}\"", string);
+		}
+		entry
+	}
+}
+
+/// A set of [`Bip353Record`]s to be published in a DNS zone.
+///
+/// See the [module-level documentation](self) for details.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Bip353Zone {
+	ttl: u32,
+	records: Vec<Bip353Record>,
+}
+
+impl Default for Bip353Zone {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Bip353Zone {
+	/// Creates an empty zone whose records are rendered with the [`DEFAULT_RECORD_TTL`].
+	pub fn new() -> Self {
+		Self { ttl: DEFAULT_RECORD_TTL, records: Vec::new() }
+	}
+
+	/// Sets the TTL, in seconds, the records are rendered with.
+	///
+	/// Payers cache resolved offers for up to this long, so it bounds how quickly an offer can be
+	/// replaced.
+	pub fn with_ttl(mut self, ttl: u32) -> Self {
+		self.ttl = ttl;
+		self
+	}
+
+	/// Adds a record for paying `offer` when resolving `name`.
+	///
+	/// Errors if a record for `name` was already added, as BIP 353 requires exactly one `bitcoin:`
+	/// URI per name. DNS names are compared case-insensitively.
+	pub fn add_offer(&mut self, name: HumanReadableName, offer: &Offer) -> Result<(), ()> {
+		let record = Bip353Record::for_offer(name, offer);
+		let dns_name = record.dns_name();
+		if self.records.iter().any(|r| r.dns_name().eq_ignore_ascii_case(&dns_name)) {
+			return Err(());
+		}
+		self.records.push(record);
+		Ok(())
+	}
+
+	/// Removes the record for `name`, if any, returning whether one was removed.
+	pub fn remove(&mut self, name: &HumanReadableName) -> bool {
+		let dns_name = Bip353Record { name: *name, uri: String::new() }.dns_name();
+		let records_len = self.records.len();
+		self.records.retain(|r| !r.dns_name().eq_ignore_ascii_case(&dns_name));
+		self.records.len() != records_len
+	}
+
+	/// Gets the records of the zone, in the order they were added.
+	pub fn records(&self) -> &[Bip353Record] {
+		&self.records
+	}
+
+	/// Renders all records in the zone file format, one per line and sorted by DNS name, such
+	/// that the same set of records always renders identically.
+	///
+	/// The output must be signed with DNSSEC before being published.
+	pub fn to_zone_string(&self) -> String {
+		let mut entries = self
+			.records
+			.iter()
+			.map(|record| (record.dns_name().to_ascii_lowercase(), record.to_zone_entry(self.ttl)))
+			.collect::<Vec<_>>();
+		entries.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+		let mut zone = String::new();
+		for (_, entry) in entries {
+			zone.push_str(&entry);
+			zone.push('\n');
+		}
+		zone
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{Bip353Record, Bip353Zone, DEFAULT_RECORD_TTL};
+
+	use crate::offers::offer::{Offer, OfferBuilder};
+	use crate::offers::test_utils::pubkey;
+	use crate::onion_message::dns_resolution::HumanReadableName;
+
+	use core::str::FromStr;
+
+	#[test]
+	fn builds_records_for_offers() {
+		let offer = OfferBuilder::new(pubkey(42)).build().unwrap();
+		let name = HumanReadableName::new("alice", "example.com").unwrap();
+		let record = Bip353Record::for_offer(name, &offer);
+		assert_eq!(record.dns_name(), "alice.user._bitcoin-payment.example.com.");
+		assert_eq!(record.uri(), format!("bitcoin:?lno={}", offer));
+		assert_eq!(
+			record.to_zone_entry(300),
+			format!(
+				"alice.user._bitcoin-payment.example.com. 300 IN TXT \"bitcoin:?lno={}\"",
+				offer
+			)
+		);
+
+		// Long offers are split across several character-strings.
+		let long_offer =
+			OfferBuilder::new(pubkey(42)).description("a".repeat(500)).build().unwrap();
+		let record = Bip353Record::for_offer(name, &long_offer);
+		let strings = record.txt_strings();
+		assert!(strings.len() > 2);
+		assert!(strings.iter().all(|string| string.len() <= 255));
+		let uri = strings.concat();
+		let lno = uri.strip_prefix("bitcoin:?lno=").unwrap();
+		assert_eq!(Offer::from_str(lno).unwrap(), long_offer);
+	}
+
+	#[test]
+	fn renders_zone() {
+		let offer = OfferBuilder::new(pubkey(42)).build().unwrap();
+		let other_offer = OfferBuilder::new(pubkey(43)).build().unwrap();
+		let bob = HumanReadableName::new("bob", "example.com").unwrap();
+		let alice = HumanReadableName::new("alice", "example.com").unwrap();
+
+		let mut zone = Bip353Zone::new();
+		zone.add_offer(bob, &offer).unwrap();
+		zone.add_offer(alice, &other_offer).unwrap();
+		assert_eq!(
+			zone.add_offer(HumanReadableName::new("BOB", "Example.com").unwrap(), &offer),
+			Err(())
+		);
+		assert_eq!(zone.records().len(), 2);
+
+		// Records are sorted by name regardless of the order they were added in.
+		assert_eq!(
+			zone.to_zone_string(),
+			format!(
+				"{}\n{}\n",
+				Bip353Record::for_offer(alice, &other_offer).to_zone_entry(DEFAULT_RECORD_TTL),
+				Bip353Record::for_offer(bob, &offer).to_zone_entry(DEFAULT_RECORD_TTL),
+			)
+		);
+
+		assert!(zone.remove(&bob));
+		assert!(!zone.remove(&bob));
+		let zone = zone.with_ttl(60);
+		assert_eq!(
+			zone.to_zone_string(),
+			format!("{}\n", Bip353Record::for_offer(alice, &other_offer).to_zone_entry(60))
+		);
+	}
+}
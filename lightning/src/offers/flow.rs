@@ -887,6 +887,7 @@ where
 	/// - Blinded payment paths created using the parameterized [`Router`], with the provided
 	///   `payment_secret` included in the path payloads.
 	/// - The given `payment_hash` and `payment_secret`, enabling secure claim verification.
+	/// - The given `relative_expiry` in seconds, if any, or otherwise a default relative expiry.
 	///
 	/// Returns an error if the refund targets a different chain or if no valid
 	/// blinded path can be constructed.
@@ -894,7 +895,7 @@ where
 	/// This is not exported to bindings users as builder patterns don't map outside of move semantics.
 	pub fn create_invoice_builder_from_refund<'a, ES: Deref, R: Deref, F>(
 		&'a self, router: &R, entropy_source: ES, refund: &'a Refund,
-		usable_channels: Vec<ChannelDetails>, get_payment_info: F,
+		usable_channels: Vec<ChannelDetails>, relative_expiry: Option<u32>, get_payment_info: F,
 	) -> Result<InvoiceBuilder<'a, DerivedSigningPubkey>, Bolt12SemanticError>
 	where
 		ES::Target: EntropySource,
@@ -909,7 +910,9 @@ where
 		let entropy = &*entropy_source;
 
 		let amount_msats = refund.amount_msats();
-		let relative_expiry = DEFAULT_RELATIVE_EXPIRY.as_secs() as u32;
+		let custom_relative_expiry = relative_expiry;
+		let relative_expiry =
+			custom_relative_expiry.unwrap_or(DEFAULT_RELATIVE_EXPIRY.as_secs() as u32);
 
 		let (payment_hash, payment_secret) = get_payment_info(amount_msats, relative_expiry)?;
 
@@ -944,7 +947,11 @@ where
 			entropy,
 		)?;
 
-		Ok(builder.into())
+		let builder: InvoiceBuilder<'a, DerivedSigningPubkey> = builder.into();
+		match custom_relative_expiry {
+			Some(relative_expiry) => Ok(builder.relative_expiry(relative_expiry)),
+			None => Ok(builder),
+		}
 	}
 
 	/// Creates an [`InvoiceBuilder<DerivedSigningPubkey>`] for the
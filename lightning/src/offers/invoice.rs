@@ -2013,8 +2013,16 @@ mod tests {
 					message_paths: None,
 				},
 				SignatureTlvStreamRef { signature: Some(&invoice.signature()) },
-				ExperimentalOfferTlvStreamRef { experimental_foo: None },
-				ExperimentalInvoiceRequestTlvStreamRef { experimental_bar: None },
+				ExperimentalOfferTlvStreamRef {
+					recurrence_period: None,
+					recurrence_start: None,
+					recurrence_limit: None,
+					experimental_foo: None,
+				},
+				ExperimentalInvoiceRequestTlvStreamRef {
+					recurrence_counter: None,
+					experimental_bar: None,
+				},
 				ExperimentalInvoiceTlvStreamRef { experimental_baz: None },
 			),
 		);
@@ -2116,8 +2124,16 @@ mod tests {
 					message_paths: None,
 				},
 				SignatureTlvStreamRef { signature: Some(&invoice.signature()) },
-				ExperimentalOfferTlvStreamRef { experimental_foo: None },
-				ExperimentalInvoiceRequestTlvStreamRef { experimental_bar: None },
+				ExperimentalOfferTlvStreamRef {
+					recurrence_period: None,
+					recurrence_start: None,
+					recurrence_limit: None,
+					experimental_foo: None,
+				},
+				ExperimentalInvoiceRequestTlvStreamRef {
+					recurrence_counter: None,
+					experimental_bar: None,
+				},
 				ExperimentalInvoiceTlvStreamRef { experimental_baz: None },
 			),
 		);
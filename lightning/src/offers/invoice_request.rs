@@ -186,7 +186,7 @@ macro_rules! invoice_request_builder_methods { (
 		InvoiceRequestContentsWithoutPayerSigningPubkey {
 			payer: PayerContents(metadata), offer, chain: None, amount_msats: None,
 			features: InvoiceRequestFeatures::empty(), quantity: None, payer_note: None,
-			offer_from_hrn: None,
+			offer_from_hrn: None, recurrence_counter: None,
 			#[cfg(test)]
 			experimental_bar: None,
 		}
@@ -239,6 +239,17 @@ macro_rules! invoice_request_builder_methods { (
 		Ok($return_value)
 	}
 
+	/// Sets the [`InvoiceRequest::recurrence_counter`] identifying which period of the
+	/// [`Offer::recurrence`] is being requested. Required if and only if the offer is recurring.
+	/// Errors if the offer is not recurring or if `counter` exceeds the recurrence limit.
+	///
+	/// Successive calls to this method will override the previous setting.
+	pub fn recurrence_counter($($self_mut)* $self: $self_type, counter: u32) -> Result<$return_type, Bolt12SemanticError> {
+		$self.invoice_request.offer.check_recurrence_counter(Some(counter))?;
+		$self.invoice_request.recurrence_counter = Some(counter);
+		Ok($return_value)
+	}
+
 	/// Sets the [`InvoiceRequest::payer_note`].
 	///
 	/// Successive calls to this method will override the previous setting.
@@ -282,6 +293,7 @@ macro_rules! invoice_request_builder_methods { (
 		$self.invoice_request.offer.check_amount_msats_for_quantity(
 			$self.invoice_request.amount_msats, $self.invoice_request.quantity
 		)?;
+		$self.invoice_request.offer.check_recurrence_counter($self.invoice_request.recurrence_counter)?;
 
 		Ok($self.build_without_checks())
 	}
@@ -366,6 +378,12 @@ macro_rules! invoice_request_builder_test_methods { (
 		$return_value
 	}
 
+	#[cfg_attr(c_bindings, allow(dead_code))]
+	fn recurrence_counter_unchecked($($self_mut)* $self: $self_type, counter: u32) -> $return_type {
+		$self.invoice_request.recurrence_counter = Some(counter);
+		$return_value
+	}
+
 	#[cfg_attr(c_bindings, allow(dead_code))]
 	pub(super) fn payer_signing_pubkey($($self_mut)* $self: $self_type, signing_pubkey: PublicKey) -> $return_type {
 		$self.payer_signing_pubkey = Some(signing_pubkey);
@@ -500,7 +518,7 @@ impl UnsignedInvoiceRequest {
 
 		invoice_request_tlv_stream.write(&mut bytes).unwrap();
 
-		const EXPERIMENTAL_TLV_ALLOCATION_SIZE: usize = 0;
+		const EXPERIMENTAL_TLV_ALLOCATION_SIZE: usize = 48;
 		let mut experimental_bytes = Vec::with_capacity(EXPERIMENTAL_TLV_ALLOCATION_SIZE);
 
 		let experimental_tlv_stream =
@@ -686,6 +704,7 @@ pub(super) struct InvoiceRequestContentsWithoutPayerSigningPubkey {
 	quantity: Option<u64>,
 	payer_note: Option<String>,
 	offer_from_hrn: Option<HumanReadableName>,
+	recurrence_counter: Option<u32>,
 	#[cfg(test)]
 	experimental_bar: Option<u64>,
 }
@@ -747,6 +766,12 @@ macro_rules! invoice_request_accessors { ($self: ident, $contents: expr) => {
 	pub fn offer_from_hrn(&$self) -> &Option<HumanReadableName> {
 		$contents.offer_from_hrn()
 	}
+
+	/// The period of the [`Offer::recurrence`] being requested, starting at `0`. Only set for
+	/// recurring offers.
+	pub fn recurrence_counter(&$self) -> Option<u32> {
+		$contents.recurrence_counter()
+	}
 } }
 
 impl UnsignedInvoiceRequest {
@@ -1179,6 +1204,10 @@ impl InvoiceRequestContents {
 		&self.inner.offer_from_hrn
 	}
 
+	pub(super) fn recurrence_counter(&self) -> Option<u32> {
+		self.inner.recurrence_counter
+	}
+
 	pub(super) fn as_tlv_stream(&self) -> PartialInvoiceRequestTlvStreamRef<'_> {
 		let (payer, offer, mut invoice_request, experimental_offer, experimental_invoice_request) =
 			self.inner.as_tlv_stream();
@@ -1225,6 +1254,7 @@ impl InvoiceRequestContentsWithoutPayerSigningPubkey {
 		};
 
 		let experimental_invoice_request = ExperimentalInvoiceRequestTlvStreamRef {
+			recurrence_counter: self.recurrence_counter,
 			#[cfg(test)]
 			experimental_bar: self.experimental_bar,
 		};
@@ -1296,6 +1326,7 @@ tlv_stream!(
 	{
 		// When adding experimental TLVs, update EXPERIMENTAL_TLV_ALLOCATION_SIZE accordingly in
 		// UnsignedInvoiceRequest::new to avoid unnecessary allocations.
+		(2_000_000_024, recurrence_counter: (u32, HighZeroBytesDroppedBigSize)),
 	}
 );
 
@@ -1303,6 +1334,7 @@ tlv_stream!(
 tlv_stream!(
 	ExperimentalInvoiceRequestTlvStream, ExperimentalInvoiceRequestTlvStreamRef,
 	EXPERIMENTAL_INVOICE_REQUEST_TYPES, {
+		(2_000_000_024, recurrence_counter: (u32, HighZeroBytesDroppedBigSize)),
 		(2_999_999_999, experimental_bar: (u64, HighZeroBytesDroppedBigSize)),
 	}
 );
@@ -1437,6 +1469,7 @@ impl TryFrom<PartialInvoiceRequestTlvStream> for InvoiceRequestContents {
 			},
 			experimental_offer_tlv_stream,
 			ExperimentalInvoiceRequestTlvStream {
+				recurrence_counter,
 				#[cfg(test)]
 				experimental_bar,
 			},
@@ -1458,6 +1491,7 @@ impl TryFrom<PartialInvoiceRequestTlvStream> for InvoiceRequestContents {
 
 		offer.check_quantity(quantity)?;
 		offer.check_amount_msats_for_quantity(amount, quantity)?;
+		offer.check_recurrence_counter(recurrence_counter)?;
 
 		let features = features.unwrap_or_else(InvoiceRequestFeatures::empty);
 
@@ -1480,6 +1514,7 @@ impl TryFrom<PartialInvoiceRequestTlvStream> for InvoiceRequestContents {
 				quantity,
 				payer_note,
 				offer_from_hrn,
+				recurrence_counter,
 				#[cfg(test)]
 				experimental_bar,
 			},
@@ -1566,6 +1601,7 @@ mod tests {
 	use crate::offers::offer::OfferWithExplicitMetadataBuilder as OfferBuilder;
 	use crate::offers::offer::{
 		Amount, CurrencyCode, ExperimentalOfferTlvStreamRef, OfferTlvStreamRef, Quantity,
+		Recurrence,
 	};
 	use crate::offers::parse::{Bolt12ParseError, Bolt12SemanticError};
 	use crate::offers::payer::PayerTlvStreamRef;
@@ -1577,7 +1613,6 @@ mod tests {
 	use bitcoin::network::Network;
 	use bitcoin::secp256k1::{self, Keypair, Secp256k1, SecretKey};
 	use core::num::NonZeroU64;
-	#[cfg(feature = "std")]
 	use core::time::Duration;
 
 	#[test]
@@ -1659,8 +1694,16 @@ mod tests {
 					offer_from_hrn: None,
 				},
 				SignatureTlvStreamRef { signature: Some(&invoice_request.signature()) },
-				ExperimentalOfferTlvStreamRef { experimental_foo: None },
-				ExperimentalInvoiceRequestTlvStreamRef { experimental_bar: None },
+				ExperimentalOfferTlvStreamRef {
+					recurrence_period: None,
+					recurrence_start: None,
+					recurrence_limit: None,
+					experimental_foo: None,
+				},
+				ExperimentalInvoiceRequestTlvStreamRef {
+					recurrence_counter: None,
+					experimental_bar: None,
+				},
 			),
 		);
 
@@ -2259,6 +2302,72 @@ mod tests {
 		}
 	}
 
+	#[test]
+	fn builds_invoice_request_with_recurrence_counter() {
+		let expanded_key = ExpandedKey::new([42; 32]);
+		let entropy = FixedEntropy {};
+		let nonce = Nonce::from_entropy_source(&entropy);
+		let secp_ctx = Secp256k1::new();
+		let payment_id = PaymentId([1; 32]);
+
+		let recurrence =
+			Recurrence { period: Duration::from_secs(86_400), start: None, limit: Some(12) };
+
+		let invoice_request = OfferBuilder::new(recipient_pubkey())
+			.amount_msats(1000)
+			.recurrence(recurrence)
+			.build()
+			.unwrap()
+			.request_invoice(&expanded_key, nonce, &secp_ctx, payment_id)
+			.unwrap()
+			.recurrence_counter(11)
+			.unwrap()
+			.build_and_sign()
+			.unwrap();
+		let (_, _, _, _, _, experimental_tlv_stream) = invoice_request.as_tlv_stream();
+		assert_eq!(invoice_request.recurrence(), Some(recurrence));
+		assert_eq!(invoice_request.recurrence_counter(), Some(11));
+		assert_eq!(experimental_tlv_stream.recurrence_counter, Some(11));
+
+		match OfferBuilder::new(recipient_pubkey())
+			.amount_msats(1000)
+			.recurrence(recurrence)
+			.build()
+			.unwrap()
+			.request_invoice(&expanded_key, nonce, &secp_ctx, payment_id)
+			.unwrap()
+			.recurrence_counter(12)
+		{
+			Ok(_) => panic!("expected error"),
+			Err(e) => assert_eq!(e, Bolt12SemanticError::RecurrenceOutOfWindow),
+		}
+
+		match OfferBuilder::new(recipient_pubkey())
+			.amount_msats(1000)
+			.recurrence(recurrence)
+			.build()
+			.unwrap()
+			.request_invoice(&expanded_key, nonce, &secp_ctx, payment_id)
+			.unwrap()
+			.build_and_sign()
+		{
+			Ok(_) => panic!("expected error"),
+			Err(e) => assert_eq!(e, Bolt12SemanticError::MissingRecurrenceCounter),
+		}
+
+		match OfferBuilder::new(recipient_pubkey())
+			.amount_msats(1000)
+			.build()
+			.unwrap()
+			.request_invoice(&expanded_key, nonce, &secp_ctx, payment_id)
+			.unwrap()
+			.recurrence_counter(0)
+		{
+			Ok(_) => panic!("expected error"),
+			Err(e) => assert_eq!(e, Bolt12SemanticError::UnexpectedRecurrence),
+		}
+	}
+
 	#[test]
 	fn builds_invoice_request_with_payer_note() {
 		let expanded_key = ExpandedKey::new([42; 32]);
@@ -2676,6 +2785,99 @@ mod tests {
 		}
 	}
 
+	#[test]
+	fn parses_invoice_request_with_recurrence_counter() {
+		let expanded_key = ExpandedKey::new([42; 32]);
+		let entropy = FixedEntropy {};
+		let nonce = Nonce::from_entropy_source(&entropy);
+		let secp_ctx = Secp256k1::new();
+		let payment_id = PaymentId([1; 32]);
+
+		let recurrence =
+			Recurrence { period: Duration::from_secs(86_400), start: None, limit: Some(12) };
+
+		let invoice_request = OfferBuilder::new(recipient_pubkey())
+			.amount_msats(1000)
+			.recurrence(recurrence)
+			.build()
+			.unwrap()
+			.request_invoice(&expanded_key, nonce, &secp_ctx, payment_id)
+			.unwrap()
+			.recurrence_counter(0)
+			.unwrap()
+			.build_and_sign()
+			.unwrap();
+
+		let mut buffer = Vec::new();
+		invoice_request.write(&mut buffer).unwrap();
+
+		match InvoiceRequest::try_from(buffer) {
+			Ok(invoice_request) => assert_eq!(invoice_request.recurrence_counter(), Some(0)),
+			Err(e) => panic!("error parsing invoice_request: {:?}", e),
+		}
+
+		let invoice_request = OfferBuilder::new(recipient_pubkey())
+			.amount_msats(1000)
+			.recurrence(recurrence)
+			.build()
+			.unwrap()
+			.request_invoice(&expanded_key, nonce, &secp_ctx, payment_id)
+			.unwrap()
+			.build_unchecked_and_sign();
+
+		let mut buffer = Vec::new();
+		invoice_request.write(&mut buffer).unwrap();
+
+		match InvoiceRequest::try_from(buffer) {
+			Ok(_) => panic!("expected error"),
+			Err(e) => assert_eq!(
+				e,
+				Bolt12ParseError::InvalidSemantics(Bolt12SemanticError::MissingRecurrenceCounter)
+			),
+		}
+
+		let invoice_request = OfferBuilder::new(recipient_pubkey())
+			.amount_msats(1000)
+			.recurrence(recurrence)
+			.build()
+			.unwrap()
+			.request_invoice(&expanded_key, nonce, &secp_ctx, payment_id)
+			.unwrap()
+			.recurrence_counter_unchecked(12)
+			.build_unchecked_and_sign();
+
+		let mut buffer = Vec::new();
+		invoice_request.write(&mut buffer).unwrap();
+
+		match InvoiceRequest::try_from(buffer) {
+			Ok(_) => panic!("expected error"),
+			Err(e) => assert_eq!(
+				e,
+				Bolt12ParseError::InvalidSemantics(Bolt12SemanticError::RecurrenceOutOfWindow)
+			),
+		}
+
+		let invoice_request = OfferBuilder::new(recipient_pubkey())
+			.amount_msats(1000)
+			.build()
+			.unwrap()
+			.request_invoice(&expanded_key, nonce, &secp_ctx, payment_id)
+			.unwrap()
+			.recurrence_counter_unchecked(0)
+			.build_unchecked_and_sign();
+
+		let mut buffer = Vec::new();
+		invoice_request.write(&mut buffer).unwrap();
+
+		match InvoiceRequest::try_from(buffer) {
+			Ok(_) => panic!("expected error"),
+			Err(e) => assert_eq!(
+				e,
+				Bolt12ParseError::InvalidSemantics(Bolt12SemanticError::UnexpectedRecurrence)
+			),
+		}
+	}
+
 	#[test]
 	fn fails_parsing_invoice_request_without_metadata() {
 		let expanded_key = ExpandedKey::new([42; 32]);
@@ -17,6 +17,7 @@ pub mod offer;
 pub mod flow;
 
 pub mod async_receive_offer_cache;
+pub mod bip353;
 pub mod invoice;
 pub mod invoice_error;
 mod invoice_macros;
@@ -25,6 +26,7 @@ pub mod merkle;
 pub mod nonce;
 pub mod parse;
 mod payer;
+pub(crate) mod recurrence;
 pub mod refund;
 pub(crate) mod signer;
 pub mod static_invoice;
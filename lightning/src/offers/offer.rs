@@ -89,6 +89,7 @@ use crate::offers::signer::{self, Metadata, MetadataMaterial};
 use crate::onion_message::dns_resolution::HumanReadableName;
 use crate::types::features::OfferFeatures;
 use crate::types::string::PrintableString;
+use crate::util::qr::{QrBudget, QrEncoding};
 use crate::util::ser::{
 	CursorReadable, HighZeroBytesDroppedBigSize, LengthLimitedRead, LengthReadable, Readable,
 	WithoutLength, Writeable, Writer,
@@ -118,7 +119,7 @@ pub(super) const IV_BYTES_WITH_METADATA: &[u8; IV_LEN] = b"LDK Offer ~~~~~~";
 pub(super) const IV_BYTES_WITHOUT_METADATA: &[u8; IV_LEN] = b"LDK Offer v2~~~~";
 
 /// An identifier for an [`Offer`] built using [`DerivedMetadata`].
-#[derive(Clone, Copy, Eq, PartialEq)]
+#[derive(Clone, Copy, Eq, Hash, PartialEq)]
 pub struct OfferId(pub [u8; 32]);
 
 impl OfferId {
@@ -247,6 +248,7 @@ macro_rules! offer_explicit_metadata_builder_methods {
 					paths: None,
 					supported_quantity: Quantity::One,
 					issuer_signing_pubkey: Some(signing_pubkey),
+					recurrence: None,
 					#[cfg(test)]
 					experimental_foo: None,
 				},
@@ -301,6 +303,7 @@ macro_rules! offer_derived_metadata_builder_methods {
 					paths: None,
 					supported_quantity: Quantity::One,
 					issuer_signing_pubkey: Some(node_id),
+					recurrence: None,
 					#[cfg(test)]
 					experimental_foo: None,
 				},
@@ -398,6 +401,15 @@ macro_rules! offer_builder_methods { (
 		$return_value
 	}
 
+	/// Sets the [`Offer::recurrence`], allowing the offer to be paid once per period (e.g., for a
+	/// subscription). If not called, the offer is not recurring.
+	///
+	/// Successive calls to this method will override the previous setting.
+	pub fn recurrence($($self_mut)* $self: $self_type, recurrence: Recurrence) -> $return_type {
+		$self.offer.recurrence = Some(recurrence);
+		$return_value
+	}
+
 	/// Builds an [`Offer`] from the builder's settings.
 	pub fn build($($self_mut)* $self: $self_type) -> Result<Offer, Bolt12SemanticError> {
 		match $self.offer.amount {
@@ -410,6 +422,12 @@ macro_rules! offer_builder_methods { (
 			None => {},
 		}
 
+		if let Some(recurrence) = &$self.offer.recurrence {
+			if !recurrence.is_valid() {
+				return Err(Bolt12SemanticError::InvalidRecurrence);
+			}
+		}
+
 		if $self.offer.amount.is_some() && $self.offer.description.is_none() {
 			$self.offer.description = Some(String::new());
 		}
@@ -423,6 +441,47 @@ macro_rules! offer_builder_methods { (
 		Ok($self.build_without_checks())
 	}
 
+	/// Builds an [`Offer`] from the builder's settings, trimming it as needed such that its
+	/// uppercase bech32 encoding fits in a QR code within the given `budget`.
+	///
+	/// The offer is trimmed by first dropping all but the first of [`Offer::paths`], then by
+	/// truncating [`Offer::description`]. Other fields are left untouched, so this fails with
+	/// [`Bolt12SemanticError::ExceedsQrBudget`] if the offer doesn't fit once there is nothing left
+	/// to trim.
+	///
+	/// Returns the built offer along with its [`QrEncoding`], which is what should be displayed.
+	pub fn build_for_qr(
+		$($self_mut)* $self: $self_type, budget: &QrBudget,
+	) -> Result<(Offer, QrEncoding), Bolt12SemanticError> {
+		loop {
+			let offer = Self {
+				offer: $self.offer.clone(),
+				metadata_strategy: core::marker::PhantomData,
+				secp_ctx: $self.secp_ctx,
+			}.build()?;
+			let excess_len = match budget.encode_offer(&offer) {
+				Ok(encoding) => return Ok((offer, encoding)),
+				Err(e) => e.data_len - e.max_data_len,
+			};
+
+			let paths = $self.offer.paths.as_mut().filter(|paths| paths.len() > 1);
+			let description = $self.offer.description.as_mut().filter(|d| !d.is_empty());
+			if let Some(paths) = paths {
+				paths.pop();
+			} else if let Some(description) = description {
+				// Each byte takes 8/5 bech32 characters to encode.
+				let excess_bytes = (excess_len * 5).div_ceil(8);
+				let mut len = description.len().saturating_sub(excess_bytes);
+				while !description.is_char_boundary(len) {
+					len -= 1;
+				}
+				description.truncate(len);
+			} else {
+				return Err(Bolt12SemanticError::ExceedsQrBudget);
+			}
+		}
+	}
+
 	fn build_without_checks($($self_mut)* $self: $self_type) -> Offer {
 		if let Some(mut metadata) = $self.offer.metadata.take() {
 			// Create the metadata for stateless verification of an InvoiceRequest.
@@ -632,6 +691,7 @@ pub(super) struct OfferContents {
 	paths: Option<Vec<BlindedMessagePath>>,
 	supported_quantity: Quantity,
 	issuer_signing_pubkey: Option<PublicKey>,
+	recurrence: Option<Recurrence>,
 	#[cfg(test)]
 	experimental_foo: Option<u64>,
 }
@@ -708,6 +768,12 @@ macro_rules! offer_accessors { ($self: ident, $contents: expr) => {
 	pub fn issuer_signing_pubkey(&$self) -> Option<bitcoin::secp256k1::PublicKey> {
 		$contents.issuer_signing_pubkey()
 	}
+
+	/// The recurrence of payments supported by the offer, if any. Each period must be requested
+	/// using a separate `InvoiceRequest` with an increasing `recurrence_counter`.
+	pub fn recurrence(&$self) -> Option<$crate::offers::offer::Recurrence> {
+		$contents.recurrence()
+	}
 } }
 
 impl Offer {
@@ -993,6 +1059,24 @@ impl OfferContents {
 		self.issuer_signing_pubkey
 	}
 
+	pub fn recurrence(&self) -> Option<Recurrence> {
+		self.recurrence
+	}
+
+	pub(super) fn check_recurrence_counter(
+		&self, recurrence_counter: Option<u32>,
+	) -> Result<(), Bolt12SemanticError> {
+		match (self.recurrence, recurrence_counter) {
+			(None, None) => Ok(()),
+			(None, Some(_)) => Err(Bolt12SemanticError::UnexpectedRecurrence),
+			(Some(_), None) => Err(Bolt12SemanticError::MissingRecurrenceCounter),
+			(Some(Recurrence { limit: Some(limit), .. }), Some(counter)) if counter >= limit => {
+				Err(Bolt12SemanticError::RecurrenceOutOfWindow)
+			},
+			(Some(_), Some(_)) => Ok(()),
+		}
+	}
+
 	pub(super) fn verify_using_metadata<T: secp256k1::Signing>(
 		&self, bytes: &[u8], key: &ExpandedKey, secp_ctx: &Secp256k1<T>,
 	) -> Result<(OfferId, Option<Keypair>), ()> {
@@ -1075,6 +1159,12 @@ impl OfferContents {
 		};
 
 		let experimental_offer = ExperimentalOfferTlvStreamRef {
+			recurrence_period: self.recurrence.map(|recurrence| recurrence.period.as_secs()),
+			recurrence_start: self
+				.recurrence
+				.and_then(|recurrence| recurrence.start)
+				.map(|start| start.as_secs()),
+			recurrence_limit: self.recurrence.and_then(|recurrence| recurrence.limit),
 			#[cfg(test)]
 			experimental_foo: self.experimental_foo,
 		};
@@ -1205,6 +1295,47 @@ impl Quantity {
 	}
 }
 
+/// Recurrence of payments supported by an [`Offer`], allowing it to be used for subscriptions.
+///
+/// Each period is paid for using a separate [`InvoiceRequest`] whose
+/// [`InvoiceRequest::recurrence_counter`] identifies the period, starting at `0`. Payers must use
+/// the same [`InvoiceRequest::payer_signing_pubkey`] for each period so that the recipient can
+/// track which periods have been requested.
+///
+/// [`InvoiceRequest`]: crate::offers::invoice_request::InvoiceRequest
+/// [`InvoiceRequest::recurrence_counter`]: crate::offers::invoice_request::InvoiceRequest::recurrence_counter
+/// [`InvoiceRequest::payer_signing_pubkey`]: crate::offers::invoice_request::InvoiceRequest::payer_signing_pubkey
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Recurrence {
+	/// The length of each period, truncated to whole seconds. Must be at least one second.
+	pub period: Duration,
+	/// Duration since the Unix epoch when the first period begins. If `None`, the first period
+	/// begins when the recipient receives the payer's first [`InvoiceRequest`].
+	///
+	/// [`InvoiceRequest`]: crate::offers::invoice_request::InvoiceRequest
+	pub start: Option<Duration>,
+	/// The maximum number of periods which may be paid for. If `None`, the recurrence continues
+	/// until the offer expires. Must be non-zero if set.
+	pub limit: Option<u32>,
+}
+
+impl Recurrence {
+	fn is_valid(&self) -> bool {
+		self.period.as_secs() > 0 && self.limit != Some(0)
+	}
+
+	/// Returns the start and end of the period identified by `counter`, given the start of the
+	/// first period, or `None` on overflow.
+	pub(crate) fn period_bounds(
+		&self, base: Duration, counter: u32,
+	) -> Option<(Duration, Duration)> {
+		let period = self.period.as_secs();
+		let start = base.as_secs().checked_add(period.checked_mul(counter as u64)?)?;
+		let end = start.checked_add(period)?;
+		Some((Duration::from_secs(start), Duration::from_secs(end)))
+	}
+}
+
 /// Valid type range for offer TLV records.
 pub(super) const OFFER_TYPES: core::ops::Range<u64> = 1..80;
 
@@ -1233,10 +1364,16 @@ pub(super) const EXPERIMENTAL_OFFER_TYPES: core::ops::Range<u64> = 1_000_000_000
 
 #[cfg(not(test))]
 tlv_stream!(ExperimentalOfferTlvStream, ExperimentalOfferTlvStreamRef, EXPERIMENTAL_OFFER_TYPES, {
+	(1_000_000_024, recurrence_period: (u64, HighZeroBytesDroppedBigSize)),
+	(1_000_000_026, recurrence_start: (u64, HighZeroBytesDroppedBigSize)),
+	(1_000_000_028, recurrence_limit: (u32, HighZeroBytesDroppedBigSize)),
 });
 
 #[cfg(test)]
 tlv_stream!(ExperimentalOfferTlvStream, ExperimentalOfferTlvStreamRef, EXPERIMENTAL_OFFER_TYPES, {
+	(1_000_000_024, recurrence_period: (u64, HighZeroBytesDroppedBigSize)),
+	(1_000_000_026, recurrence_start: (u64, HighZeroBytesDroppedBigSize)),
+	(1_000_000_028, recurrence_limit: (u32, HighZeroBytesDroppedBigSize)),
 	(1_999_999_999, experimental_foo: (u64, HighZeroBytesDroppedBigSize)),
 });
 
@@ -1297,6 +1434,9 @@ impl TryFrom<FullOfferTlvStream> for OfferContents {
 				issuer_id,
 			},
 			ExperimentalOfferTlvStream {
+				recurrence_period,
+				recurrence_start,
+				recurrence_limit,
 				#[cfg(test)]
 				experimental_foo,
 			},
@@ -1333,6 +1473,22 @@ impl TryFrom<FullOfferTlvStream> for OfferContents {
 			Some(n) => Quantity::Bounded(NonZeroU64::new(n).unwrap()),
 		};
 
+		let recurrence = match (recurrence_period, recurrence_start, recurrence_limit) {
+			(None, None, None) => None,
+			(None, _, _) => return Err(Bolt12SemanticError::InvalidRecurrence),
+			(Some(period), start, limit) => {
+				let recurrence = Recurrence {
+					period: Duration::from_secs(period),
+					start: start.map(Duration::from_secs),
+					limit,
+				};
+				if !recurrence.is_valid() {
+					return Err(Bolt12SemanticError::InvalidRecurrence);
+				}
+				Some(recurrence)
+			},
+		};
+
 		let (issuer_signing_pubkey, paths) = match (issuer_id, paths) {
 			(None, None) => return Err(Bolt12SemanticError::MissingIssuerSigningPubkey),
 			(None, Some(paths)) if paths.is_empty() => {
@@ -1352,6 +1508,7 @@ impl TryFrom<FullOfferTlvStream> for OfferContents {
 			paths,
 			supported_quantity,
 			issuer_signing_pubkey,
+			recurrence,
 			#[cfg(test)]
 			experimental_foo,
 		})
@@ -1385,7 +1542,7 @@ mod tests {
 	#[cfg(c_bindings)]
 	use super::OfferWithExplicitMetadataBuilder as OfferBuilder;
 	use super::{
-		Amount, ExperimentalOfferTlvStreamRef, Offer, OfferTlvStreamRef, Quantity,
+		Amount, ExperimentalOfferTlvStreamRef, Offer, OfferTlvStreamRef, Quantity, Recurrence,
 		EXPERIMENTAL_OFFER_TYPES, OFFER_TYPES,
 	};
 
@@ -1400,6 +1557,7 @@ mod tests {
 	use crate::offers::test_utils::*;
 	use crate::types::features::OfferFeatures;
 	use crate::types::string::PrintableString;
+	use crate::util::qr::{QrBudget, QrErrorCorrection, QrMode};
 	use crate::util::ser::{BigSize, Writeable};
 	use bitcoin::constants::ChainHash;
 	use bitcoin::network::Network;
@@ -1446,7 +1604,12 @@ mod tests {
 					quantity_max: None,
 					issuer_id: Some(&pubkey(42)),
 				},
-				ExperimentalOfferTlvStreamRef { experimental_foo: None },
+				ExperimentalOfferTlvStreamRef {
+					recurrence_period: None,
+					recurrence_start: None,
+					recurrence_limit: None,
+					experimental_foo: None,
+				},
 			),
 		);
 
@@ -1800,6 +1963,57 @@ mod tests {
 		assert_eq!(tlv_stream.0.issuer_id, Some(&pubkey(42)));
 	}
 
+	#[test]
+	fn builds_offer_for_qr() {
+		let paths = vec![
+			BlindedMessagePath::from_blinded_path(
+				pubkey(40),
+				pubkey(41),
+				vec![
+					BlindedHop { blinded_node_id: pubkey(43), encrypted_payload: vec![0; 43] },
+					BlindedHop { blinded_node_id: pubkey(44), encrypted_payload: vec![0; 44] },
+				],
+			),
+			BlindedMessagePath::from_blinded_path(
+				pubkey(40),
+				pubkey(41),
+				vec![
+					BlindedHop { blinded_node_id: pubkey(45), encrypted_payload: vec![0; 45] },
+					BlindedHop { blinded_node_id: pubkey(46), encrypted_payload: vec![0; 46] },
+				],
+			),
+		];
+		let description = "Une description très longue ".repeat(20);
+
+		let budget = QrBudget::new(QrErrorCorrection::Low, 15);
+		let (offer, encoding) = OfferBuilder::new(pubkey(42))
+			.description(description.clone())
+			.path(paths[0].clone())
+			.path(paths[1].clone())
+			.build_for_qr(&budget)
+			.unwrap();
+		assert_eq!(offer.paths(), &paths[..1]);
+		let trimmed_description = offer.description().unwrap().0;
+		assert!(!trimmed_description.is_empty());
+		assert!(trimmed_description.len() < description.len());
+		assert!(description.starts_with(trimmed_description));
+		assert_eq!(encoding.data, offer.to_string().to_ascii_uppercase());
+		assert_eq!(encoding.mode, QrMode::Alphanumeric);
+		assert!(encoding.version <= 15);
+		assert_eq!(encoding.data.parse::<Offer>().unwrap(), offer);
+
+		// Offers which already fit are left untouched.
+		let (offer, _) =
+			OfferBuilder::new(pubkey(42)).description("foo".into()).build_for_qr(&budget).unwrap();
+		assert_eq!(offer.description(), Some(PrintableString("foo")));
+
+		let budget = QrBudget::new(QrErrorCorrection::High, 1);
+		match OfferBuilder::new(pubkey(42)).path(paths[0].clone()).build_for_qr(&budget) {
+			Ok(_) => panic!("expected error"),
+			Err(e) => assert_eq!(e, Bolt12SemanticError::ExceedsQrBudget),
+		}
+	}
+
 	#[test]
 	fn builds_offer_with_issuer() {
 		let offer = OfferBuilder::new(pubkey(42)).issuer("foo".into()).build().unwrap();
@@ -1863,6 +2077,41 @@ mod tests {
 		assert_eq!(tlv_stream.0.quantity_max, None);
 	}
 
+	#[test]
+	fn builds_offer_with_recurrence() {
+		let recurrence = Recurrence {
+			period: Duration::from_secs(86_400),
+			start: Some(Duration::from_secs(1_700_000_000)),
+			limit: Some(12),
+		};
+
+		let offer = OfferBuilder::new(pubkey(42)).build().unwrap();
+		let tlv_stream = offer.as_tlv_stream();
+		assert_eq!(offer.recurrence(), None);
+		assert_eq!(tlv_stream.1.recurrence_period, None);
+		assert_eq!(tlv_stream.1.recurrence_start, None);
+		assert_eq!(tlv_stream.1.recurrence_limit, None);
+
+		let offer = OfferBuilder::new(pubkey(42)).recurrence(recurrence).build().unwrap();
+		let tlv_stream = offer.as_tlv_stream();
+		assert_eq!(offer.recurrence(), Some(recurrence));
+		assert_eq!(tlv_stream.1.recurrence_period, Some(86_400));
+		assert_eq!(tlv_stream.1.recurrence_start, Some(1_700_000_000));
+		assert_eq!(tlv_stream.1.recurrence_limit, Some(12));
+
+		let invalid_period = Recurrence { period: Duration::from_millis(999), ..recurrence };
+		match OfferBuilder::new(pubkey(42)).recurrence(invalid_period).build() {
+			Ok(_) => panic!("expected error"),
+			Err(e) => assert_eq!(e, Bolt12SemanticError::InvalidRecurrence),
+		}
+
+		let invalid_limit = Recurrence { limit: Some(0), ..recurrence };
+		match OfferBuilder::new(pubkey(42)).recurrence(invalid_limit).build() {
+			Ok(_) => panic!("expected error"),
+			Err(e) => assert_eq!(e, Bolt12SemanticError::InvalidRecurrence),
+		}
+	}
+
 	#[test]
 	fn fails_requesting_invoice_with_unknown_required_features() {
 		let expanded_key = ExpandedKey::new([42; 32]);
@@ -2097,6 +2346,45 @@ mod tests {
 		}
 	}
 
+	#[test]
+	fn parses_offer_with_recurrence() {
+		let recurrence =
+			Recurrence { period: Duration::from_secs(3_600), start: None, limit: None };
+		let offer = OfferBuilder::new(pubkey(42)).recurrence(recurrence).build().unwrap();
+		match offer.to_string().parse::<Offer>() {
+			Ok(offer) => assert_eq!(offer.recurrence(), Some(recurrence)),
+			Err(e) => panic!("error parsing offer: {:?}", e),
+		}
+
+		let mut tlv_stream = offer.as_tlv_stream();
+		tlv_stream.1.recurrence_period = Some(0);
+
+		let mut encoded_offer = Vec::new();
+		tlv_stream.write(&mut encoded_offer).unwrap();
+
+		match Offer::try_from(encoded_offer) {
+			Ok(_) => panic!("expected error"),
+			Err(e) => assert_eq!(
+				e,
+				Bolt12ParseError::InvalidSemantics(Bolt12SemanticError::InvalidRecurrence)
+			),
+		}
+
+		let mut tlv_stream = OfferBuilder::new(pubkey(42)).build().unwrap().as_tlv_stream();
+		tlv_stream.1.recurrence_limit = Some(5);
+
+		let mut encoded_offer = Vec::new();
+		tlv_stream.write(&mut encoded_offer).unwrap();
+
+		match Offer::try_from(encoded_offer) {
+			Ok(_) => panic!("expected error"),
+			Err(e) => assert_eq!(
+				e,
+				Bolt12ParseError::InvalidSemantics(Bolt12SemanticError::InvalidRecurrence)
+			),
+		}
+	}
+
 	#[test]
 	fn parses_offer_with_issuer_id() {
 		let offer = OfferBuilder::new(pubkey(42)).build().unwrap();
@@ -191,6 +191,15 @@ pub enum Bolt12SemanticError {
 	InvalidQuantity,
 	/// A quantity or quantity bounds was provided but was not expected.
 	UnexpectedQuantity,
+	/// A recurrence was provided with a zero period or limit, or without a period.
+	InvalidRecurrence,
+	/// A recurrence or recurrence counter was provided but was not expected.
+	UnexpectedRecurrence,
+	/// A recurrence counter was expected but was missing.
+	MissingRecurrenceCounter,
+	/// A recurrence counter was provided for a period which is beyond the recurrence limit or
+	/// cannot be requested at the current time.
+	RecurrenceOutOfWindow,
 	/// Metadata could not be used to verify the offers message.
 	InvalidMetadata,
 	/// Metadata was provided but was not expected.
@@ -224,6 +233,16 @@ pub enum Bolt12SemanticError {
 	///
 	/// [`Refund`]: super::refund::Refund
 	UnexpectedHumanReadableName,
+	/// A [`Refund`] was rejected by the [`RefundHandler`].
+	///
+	/// [`Refund`]: super::refund::Refund
+	/// [`RefundHandler`]: super::refund::RefundHandler
+	RejectedRefund,
+	/// An [`Offer`] could not be trimmed to fit in the requested [`QrBudget`].
+	///
+	/// [`Offer`]: super::offer::Offer
+	/// [`QrBudget`]: crate::util::qr::QrBudget
+	ExceedsQrBudget,
 }
 
 impl From<CheckedHrpstringError> for Bolt12ParseError {
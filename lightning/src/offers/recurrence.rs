@@ -0,0 +1,175 @@
+// This file is Copyright its original authors, visible in version control
+// history.
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Per-payer state for recurring [`Offer`]s, used when responding to [`InvoiceRequest`]s.
+//!
+//! [`Offer`]: crate::offers::offer::Offer
+//! [`InvoiceRequest`]: crate::offers::invoice_request::InvoiceRequest
+
+use bitcoin::secp256k1::PublicKey;
+
+use core::time::Duration;
+
+use crate::offers::offer::{OfferId, Recurrence};
+use crate::offers::parse::Bolt12SemanticError;
+
+/// The recurrence periods requested by a payer, identified by their payer signing pubkey, for a
+/// recurring [`Offer`].
+///
+/// [`Offer`]: crate::offers::offer::Offer
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct PayerRecurrence {
+	pub(crate) offer_id: OfferId,
+	pub(crate) payer_signing_pubkey: PublicKey,
+	/// The counter of the next period the payer is expected to request.
+	pub(crate) next_counter: u32,
+	/// The start of the period with counter `0`.
+	pub(crate) period_base: Duration,
+}
+
+impl PayerRecurrence {
+	/// Checks that the period identified by `counter` may be requested at time `now`, given the
+	/// payer's state from any previous requests, returning the payer's updated state to record
+	/// once an invoice has been sent for the period.
+	///
+	/// Besides the next period, a payer may re-request the previous period (e.g., if the invoice
+	/// was lost), but only while it is still within that period's window. A payer's first request
+	/// must be for period `0` unless the [`Recurrence::start`] is fixed, in which case it may join
+	/// at the current period.
+	pub(crate) fn check_and_advance(
+		state: Option<&PayerRecurrence>, offer_id: OfferId, payer_signing_pubkey: PublicKey,
+		recurrence: &Recurrence, counter: u32, now: Duration,
+	) -> Result<PayerRecurrence, Bolt12SemanticError> {
+		let (period_base, next_counter) = match state {
+			None => match recurrence.start {
+				Some(start) => (start, 0),
+				None if counter == 0 => (now, 0),
+				None => return Err(Bolt12SemanticError::RecurrenceOutOfWindow),
+			},
+			Some(state) => {
+				let is_next = counter == state.next_counter;
+				let is_previous = counter.checked_add(1) == Some(state.next_counter);
+				if !is_next && !is_previous {
+					return Err(Bolt12SemanticError::RecurrenceOutOfWindow);
+				}
+				(state.period_base, state.next_counter)
+			},
+		};
+
+		if let Some(limit) = recurrence.limit {
+			if counter >= limit {
+				return Err(Bolt12SemanticError::RecurrenceOutOfWindow);
+			}
+		}
+
+		let (period_start, period_end) = recurrence
+			.period_bounds(period_base, counter)
+			.ok_or(Bolt12SemanticError::RecurrenceOutOfWindow)?;
+		if now.as_secs() < period_start.as_secs() || now >= period_end {
+			return Err(Bolt12SemanticError::RecurrenceOutOfWindow);
+		}
+
+		Ok(PayerRecurrence {
+			offer_id,
+			payer_signing_pubkey,
+			next_counter: core::cmp::max(next_counter, counter.saturating_add(1)),
+			period_base,
+		})
+	}
+}
+
+impl_writeable_tlv_based!(PayerRecurrence, {
+	(0, offer_id, required),
+	(2, payer_signing_pubkey, required),
+	(4, next_counter, required),
+	(6, period_base, required),
+});
+
+#[cfg(test)]
+mod tests {
+	use super::PayerRecurrence;
+
+	use bitcoin::secp256k1::{PublicKey, Secp256k1, SecretKey};
+
+	use core::time::Duration;
+
+	use crate::offers::offer::{OfferId, Recurrence};
+	use crate::offers::parse::Bolt12SemanticError;
+
+	fn check(
+		state: Option<&PayerRecurrence>, recurrence: &Recurrence, counter: u32, now_secs: u64,
+	) -> Result<PayerRecurrence, Bolt12SemanticError> {
+		let secp_ctx = Secp256k1::new();
+		let payer_signing_pubkey =
+			PublicKey::from_secret_key(&secp_ctx, &SecretKey::from_slice(&[42; 32]).unwrap());
+		let now = Duration::from_secs(now_secs);
+		let offer_id = OfferId([1; 32]);
+		PayerRecurrence::check_and_advance(
+			state,
+			offer_id,
+			payer_signing_pubkey,
+			recurrence,
+			counter,
+			now,
+		)
+	}
+
+	#[test]
+	fn advances_through_periods_without_fixed_start() {
+		let recurrence =
+			Recurrence { period: Duration::from_secs(100), start: None, limit: Some(3) };
+		let out_of_window = Err(Bolt12SemanticError::RecurrenceOutOfWindow);
+
+		// The first period must be requested first.
+		assert_eq!(check(None, &recurrence, 1, 1_000), out_of_window);
+
+		let state = check(None, &recurrence, 0, 1_000).unwrap();
+		assert_eq!(state.next_counter, 1);
+		assert_eq!(state.period_base, Duration::from_secs(1_000));
+
+		// Too early for the next period.
+		assert_eq!(check(Some(&state), &recurrence, 1, 1_099), out_of_window);
+
+		// Re-requesting the current period doesn't advance the counter.
+		let state = check(Some(&state), &recurrence, 0, 1_099).unwrap();
+		assert_eq!(state.next_counter, 1);
+
+		let state = check(Some(&state), &recurrence, 1, 1_100).unwrap();
+		assert_eq!(state.next_counter, 2);
+
+		// Skipping a period isn't allowed.
+		assert_eq!(check(Some(&state), &recurrence, 3, 1_300), out_of_window);
+
+		let state = check(Some(&state), &recurrence, 2, 1_250).unwrap();
+		assert_eq!(state.next_counter, 3);
+
+		// The limit has been reached.
+		assert_eq!(check(Some(&state), &recurrence, 3, 1_300), out_of_window);
+	}
+
+	#[test]
+	fn joins_current_period_with_fixed_start() {
+		let recurrence = Recurrence {
+			period: Duration::from_secs(100),
+			start: Some(Duration::from_secs(1_000)),
+			limit: None,
+		};
+		let out_of_window = Err(Bolt12SemanticError::RecurrenceOutOfWindow);
+
+		// Before the first period has started.
+		assert_eq!(check(None, &recurrence, 0, 999), out_of_window);
+
+		// Requesting a period which has already passed.
+		assert_eq!(check(None, &recurrence, 0, 1_150), out_of_window);
+
+		let state = check(None, &recurrence, 1, 1_150).unwrap();
+		assert_eq!(state.next_counter, 2);
+		assert_eq!(state.period_base, Duration::from_secs(1_000));
+	}
+}
@@ -811,11 +811,15 @@ impl RefundContents {
 		};
 
 		let experimental_offer = ExperimentalOfferTlvStreamRef {
+			recurrence_period: None,
+			recurrence_start: None,
+			recurrence_limit: None,
 			#[cfg(test)]
 			experimental_foo: self.experimental_foo,
 		};
 
 		let experimental_invoice_request = ExperimentalInvoiceRequestTlvStreamRef {
+			recurrence_counter: None,
 			#[cfg(test)]
 			experimental_bar: self.experimental_bar,
 		};
@@ -930,10 +934,14 @@ impl TryFrom<RefundTlvStream> for RefundContents {
 				offer_from_hrn,
 			},
 			ExperimentalOfferTlvStream {
+				recurrence_period,
+				recurrence_start,
+				recurrence_limit,
 				#[cfg(test)]
 				experimental_foo,
 			},
 			ExperimentalInvoiceRequestTlvStream {
+				recurrence_counter,
 				#[cfg(test)]
 				experimental_bar,
 			},
@@ -984,6 +992,14 @@ impl TryFrom<RefundTlvStream> for RefundContents {
 			return Err(Bolt12SemanticError::UnexpectedHumanReadableName);
 		}
 
+		if recurrence_period.is_some()
+			|| recurrence_start.is_some()
+			|| recurrence_limit.is_some()
+			|| recurrence_counter.is_some()
+		{
+			return Err(Bolt12SemanticError::UnexpectedRecurrence);
+		}
+
 		let amount_msats = match amount {
 			None => return Err(Bolt12SemanticError::MissingAmount),
 			Some(amount_msats) if amount_msats > MAX_VALUE_MSAT => {
@@ -1025,6 +1041,37 @@ impl core::fmt::Display for Refund {
 	}
 }
 
+/// The decision of a [`RefundHandler`] on how to respond to a [`Refund`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RefundDecision {
+	/// Respond to the refund with a [`Bolt12Invoice`] using the default settings.
+	///
+	/// [`Bolt12Invoice`]: crate::offers::invoice::Bolt12Invoice
+	Approve,
+	/// Respond to the refund with a [`Bolt12Invoice`] which expires the given number of seconds
+	/// after its creation rather than after the default relative expiry.
+	///
+	/// [`Bolt12Invoice`]: crate::offers::invoice::Bolt12Invoice
+	ApproveWithRelativeExpiry(u32),
+	/// Don't respond to the refund.
+	Reject,
+}
+
+/// A hook consulted by [`ChannelManager::request_refund_payment`] before responding to a
+/// [`Refund`] with a [`Bolt12Invoice`], allowing merchant business rules (e.g., limits on
+/// [`Refund::amount_msats`] or on refunds per [`Refund::payer_signing_pubkey`]) to be applied.
+///
+/// Set using [`ChannelManager::set_refund_handler`]. If unset, all refunds for a supported chain
+/// are approved.
+///
+/// [`ChannelManager::request_refund_payment`]: crate::ln::channelmanager::ChannelManager::request_refund_payment
+/// [`ChannelManager::set_refund_handler`]: crate::ln::channelmanager::ChannelManager::set_refund_handler
+/// [`Bolt12Invoice`]: crate::offers::invoice::Bolt12Invoice
+pub trait RefundHandler {
+	/// Decides whether and how to respond to the given `refund`.
+	fn handle_refund(&self, refund: &Refund) -> RefundDecision;
+}
+
 #[cfg(test)]
 mod tests {
 	#[cfg(not(c_bindings))]
@@ -1119,8 +1166,16 @@ mod tests {
 					paths: None,
 					offer_from_hrn: None,
 				},
-				ExperimentalOfferTlvStreamRef { experimental_foo: None },
-				ExperimentalInvoiceRequestTlvStreamRef { experimental_bar: None },
+				ExperimentalOfferTlvStreamRef {
+					recurrence_period: None,
+					recurrence_start: None,
+					recurrence_limit: None,
+					experimental_foo: None,
+				},
+				ExperimentalInvoiceRequestTlvStreamRef {
+					recurrence_counter: None,
+					experimental_bar: None,
+				},
 			),
 		);
 
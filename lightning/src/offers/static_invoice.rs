@@ -924,7 +924,12 @@ mod tests {
 					message_paths: Some(&paths),
 				},
 				SignatureTlvStreamRef { signature: Some(&invoice.signature()) },
-				ExperimentalOfferTlvStreamRef { experimental_foo: None },
+				ExperimentalOfferTlvStreamRef {
+					recurrence_period: None,
+					recurrence_start: None,
+					recurrence_limit: None,
+					experimental_foo: None,
+				},
 				ExperimentalInvoiceTlvStreamRef { experimental_baz: None },
 			)
 		);
@@ -19,8 +19,10 @@ use bitcoin::secp256k1::{PublicKey, Verification};
 
 use bitcoin::hashes::sha256d::Hash as Sha256dHash;
 use bitcoin::hashes::Hash;
+use bitcoin::hex::DisplayHex;
 use bitcoin::network::Network;
 
+use crate::ln::liquidity_ads::{LiquidityAd, LiquidityAdRate};
 use crate::ln::msgs;
 use crate::ln::msgs::{
 	BaseMessageHandler, ChannelAnnouncement, ChannelUpdate, GossipTimestampFilter, NodeAnnouncement,
@@ -37,6 +39,7 @@ use crate::ln::types::ChannelId;
 use crate::routing::utxo::{self, UtxoLookup, UtxoResolver};
 use crate::types::features::{ChannelFeatures, InitFeatures, NodeFeatures};
 use crate::types::string::PrintableString;
+use crate::util::export::{features_hex, json_array, json_string, versioned_bytes, JsonObject};
 use crate::util::indexed_map::{
 	Entry as IndexedMapEntry, IndexedMap, OccupiedEntry as IndexedMapOccupiedEntry,
 };
@@ -1375,6 +1378,9 @@ pub struct NodeAnnouncementDetails {
 
 	/// Internet-level addresses via which one can connect to the node
 	pub addresses: Vec<SocketAddress>,
+
+	/// The rates at which the node advertised it is willing to sell inbound liquidity, if any.
+	pub liquidity_ad: Option<LiquidityAd>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -1434,6 +1440,16 @@ impl NodeAnnouncementInfo {
 		}
 	}
 
+	/// The rates at which the node advertised it is willing to sell inbound liquidity, if any.
+	pub fn liquidity_ad(&self) -> Option<LiquidityAd> {
+		match self {
+			NodeAnnouncementInfo::Relayed(relayed) => {
+				LiquidityAd::from_node_announcement_excess_data(&relayed.contents.excess_data)
+			},
+			NodeAnnouncementInfo::Local(local) => local.liquidity_ad.clone(),
+		}
+	}
+
 	/// An initial announcement of the node
 	///
 	/// Not stored if contains excess data to prevent DoS.
@@ -1453,6 +1469,10 @@ impl Writeable for NodeAnnouncementInfo {
 		let alias = self.alias();
 		let addresses = self.addresses();
 		let announcement_message = self.announcement_message();
+		let liquidity_ad = match self {
+			NodeAnnouncementInfo::Relayed(_) => None,
+			NodeAnnouncementInfo::Local(local) => local.liquidity_ad.as_ref(),
+		};
 
 		write_tlv_fields!(writer, {
 			(0, features, required),
@@ -1461,6 +1481,7 @@ impl Writeable for NodeAnnouncementInfo {
 			(6, alias, required),
 			(8, announcement_message, option),
 			(10, *addresses, required_vec), // Versions 0.0.115 through 0.0.123 only serialized an empty vec
+			(11, liquidity_ad, option),
 		});
 		Ok(())
 	}
@@ -1475,6 +1496,7 @@ impl Readable for NodeAnnouncementInfo {
 			(6, alias, required),
 			(8, announcement_message, option),
 			(10, addresses, required_vec),
+			(11, liquidity_ad, option),
 		});
 		if let Some(announcement) = announcement_message {
 			Ok(Self::Relayed(announcement))
@@ -1485,6 +1507,7 @@ impl Readable for NodeAnnouncementInfo {
 				rgb: rgb.0.unwrap(),
 				alias: alias.0.unwrap(),
 				addresses,
+				liquidity_ad,
 			}))
 		}
 	}
@@ -1562,6 +1585,40 @@ impl NodeInfo {
 			.map(|addresses| addresses.iter().all(|address| address.is_tor()))
 			.unwrap_or(false)
 	}
+
+	/// Exports this node's information as a canonical, schema-versioned JSON object.
+	///
+	/// See the [`export`] module documentation for the stability guarantees and encoding rules.
+	/// Note that the node alias is sanitized, replacing any control characters.
+	///
+	/// [`export`]: crate::util::export
+	pub fn to_canonical_json(&self) -> String {
+		let mut obj = JsonObject::new();
+		obj.raw("channels", json_array(self.channels.iter().map(|scid| scid.to_string())));
+		let announcement_info = self.announcement_info.as_ref().map(|info| {
+			let mut announcement = JsonObject::nested();
+			announcement.string("features", features_hex(info.features().le_flags()));
+			announcement.number("last_update", info.last_update());
+			announcement.string("rgb", info.rgb().as_hex());
+			announcement.string("alias", info.alias());
+			let addresses = info.addresses().iter().map(|addr| json_string(&addr.to_string()));
+			announcement.raw("addresses", json_array(addresses));
+			announcement.finish()
+		});
+		obj.raw("announcement_info", announcement_info.unwrap_or("null".to_owned()));
+		obj.finish()
+	}
+
+	/// Exports this node's information in a schema-versioned binary format, i.e., the
+	/// [`EXPORT_SCHEMA_VERSION`] followed by its regular serialization.
+	///
+	/// See the [`export`] module documentation for the stability guarantees.
+	///
+	/// [`EXPORT_SCHEMA_VERSION`]: crate::util::export::EXPORT_SCHEMA_VERSION
+	/// [`export`]: crate::util::export
+	pub fn to_canonical_bytes(&self) -> Vec<u8> {
+		versioned_bytes(self)
+	}
 }
 
 impl fmt::Display for NodeInfo {
@@ -1775,9 +1832,17 @@ where
 {
 	/// Creates a new, empty, network graph.
 	pub fn new(network: Network, logger: L) -> NetworkGraph<L> {
+		Self::new_with_chain_hash(ChainHash::using_genesis_block(network), logger)
+	}
+
+	/// Creates a new, empty, network graph for the chain with the given genesis block hash, e.g.,
+	/// a custom signet.
+	///
+	/// Gossip for any other chain is rejected.
+	pub fn new_with_chain_hash(chain_hash: ChainHash, logger: L) -> NetworkGraph<L> {
 		Self {
 			secp_ctx: Secp256k1::verification_only(),
-			chain_hash: ChainHash::using_genesis_block(network),
+			chain_hash,
 			logger,
 			channels: RwLock::new(IndexedMap::with_capacity(CHAN_COUNT_ESTIMATE)),
 			nodes: RwLock::new(IndexedMap::with_capacity(NODE_COUNT_ESTIMATE)),
@@ -1948,6 +2013,9 @@ where
 							rgb: msg.rgb,
 							alias: msg.alias,
 							addresses: msg.addresses.clone(),
+							liquidity_ad: LiquidityAd::from_node_announcement_excess_data(
+								&msg.excess_data,
+							),
 						}))
 					};
 
@@ -2683,6 +2751,20 @@ where
 	}
 }
 
+/// A node advertising inbound liquidity for sale, as returned by
+/// [`ReadOnlyNetworkGraph::liquidity_sellers`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LiquiditySeller {
+	/// The seller's node id.
+	pub node_id: NodeId,
+	/// The cheapest of the seller's advertised rates applying to the requested amount.
+	pub rate: LiquidityAdRate,
+	/// The fee, in satoshis, the seller would charge for the requested amount using `rate`.
+	pub lease_fee_sat: u64,
+	/// The total capacity of the seller's known channels, in satoshis.
+	pub capacity_sats: u64,
+}
+
 impl ReadOnlyNetworkGraph<'_> {
 	/// Returns all known valid channels' short ids along with announced channel info.
 	///
@@ -2729,6 +2811,45 @@ impl ReadOnlyNetworkGraph<'_> {
 			.and_then(|node| node.announcement_info.as_ref().map(|ann| ann.addresses().to_vec()))
 	}
 
+	/// Returns the nodes advertising inbound liquidity for sale which can contribute
+	/// `requested_sats` to a dual-funded channel, ranked by the lease fee they would charge at the
+	/// given funding feerate and then by their total channel capacity.
+	///
+	/// For each node, only the cheapest of its advertised rates applying to `requested_sats` is
+	/// considered. Nodes with more capacity are ranked first among those charging the same fee, as
+	/// they are more likely to be well-connected.
+	pub fn liquidity_sellers(
+		&self, requested_sats: u64, funding_feerate_per_kw: u32,
+	) -> Vec<LiquiditySeller> {
+		let mut sellers = Vec::new();
+		for (node_id, node) in self.nodes.unordered_iter() {
+			let liquidity_ad = match node.announcement_info.as_ref().and_then(|a| a.liquidity_ad())
+			{
+				Some(liquidity_ad) => liquidity_ad,
+				None => continue,
+			};
+			let (rate, lease_fee_sat) =
+				match liquidity_ad.best_rate_for(requested_sats, funding_feerate_per_kw) {
+					Some(best_rate) => best_rate,
+					None => continue,
+				};
+			let capacity_sats = node
+				.channels
+				.iter()
+				.filter_map(|scid| self.channels.get(scid))
+				.filter_map(|channel| channel.capacity_sats)
+				.fold(0u64, |total, capacity| total.saturating_add(capacity));
+			sellers.push(LiquiditySeller { node_id: *node_id, rate, lease_fee_sat, capacity_sats });
+		}
+		sellers.sort_unstable_by(|a, b| {
+			a.lease_fee_sat
+				.cmp(&b.lease_fee_sat)
+				.then_with(|| b.capacity_sats.cmp(&a.capacity_sats))
+				.then_with(|| a.node_id.cmp(&b.node_id))
+		});
+		sellers
+	}
+
 	/// Gets the maximum possible node_counter for a node in this graph
 	pub(crate) fn max_node_counter(&self) -> u32 {
 		self.max_node_counter
@@ -2739,6 +2860,7 @@ impl ReadOnlyNetworkGraph<'_> {
 pub(crate) mod tests {
 	use crate::ln::chan_utils::make_funding_redeemscript;
 	use crate::ln::channelmanager;
+	use crate::ln::liquidity_ads::{LiquidityAd, LiquidityAdRate};
 	use crate::ln::msgs::{BaseMessageHandler, MessageSendEvent, SocketAddress};
 	use crate::ln::msgs::{
 		ChannelAnnouncement, ChannelUpdate, NodeAnnouncement, QueryChannelRange,
@@ -2977,6 +3099,86 @@ pub(crate) mod tests {
 		};
 	}
 
+	#[test]
+	fn ranks_liquidity_sellers_from_node_announcements() {
+		let network_graph = create_network_graph();
+		let (secp_ctx, gossip_sync) = create_gossip_sync(&network_graph);
+
+		let rate = |max_funding_satoshis, lease_fee_base_sat| LiquidityAdRate {
+			min_funding_satoshis: 10_000,
+			max_funding_satoshis,
+			funding_weight: 0,
+			lease_fee_basis: 100,
+			lease_fee_base_sat,
+		};
+		let node_privkeys = [
+			SecretKey::from_slice(&[42; 32]).unwrap(),
+			SecretKey::from_slice(&[41; 32]).unwrap(),
+			SecretKey::from_slice(&[43; 32]).unwrap(),
+			SecretKey::from_slice(&[44; 32]).unwrap(),
+		];
+		let liquidity_ads = [
+			Some(LiquidityAd { rates: vec![rate(1_000_000, 2_000)] }),
+			Some(LiquidityAd { rates: vec![rate(100_000, 0), rate(1_000_000, 1_000)] }),
+			Some(LiquidityAd { rates: vec![rate(100_000, 0)] }),
+			None,
+		];
+
+		for (i, (node_privkey, liquidity_ad)) in
+			node_privkeys.iter().zip(&liquidity_ads).enumerate()
+		{
+			let node_pubkey = PublicKey::from_secret_key(&secp_ctx, node_privkey);
+			let counterparty_privkey = &node_privkeys[(i + 1) % node_privkeys.len()];
+			let channel_announcement = get_signed_channel_announcement(
+				|unsigned_announcement| unsigned_announcement.short_channel_id = i as u64,
+				node_privkey,
+				counterparty_privkey,
+				&secp_ctx,
+			);
+			assert!(gossip_sync
+				.handle_channel_announcement(Some(node_pubkey), &channel_announcement)
+				.unwrap());
+
+			let node_announcement = get_signed_node_announcement(
+				|unsigned_announcement| {
+					if let Some(liquidity_ad) = liquidity_ad {
+						unsigned_announcement.excess_data =
+							liquidity_ad.to_node_announcement_excess_data();
+					}
+				},
+				node_privkey,
+				&secp_ctx,
+			);
+			assert!(gossip_sync
+				.handle_node_announcement(Some(node_pubkey), &node_announcement)
+				.unwrap());
+		}
+
+		let read_only_graph = network_graph.read_only();
+		for (node_privkey, liquidity_ad) in node_privkeys.iter().zip(&liquidity_ads) {
+			let node_id = NodeId::from_pubkey(&PublicKey::from_secret_key(&secp_ctx, node_privkey));
+			let node = read_only_graph.node(&node_id).unwrap();
+			assert_eq!(&node.announcement_info.as_ref().unwrap().liquidity_ad(), liquidity_ad);
+		}
+
+		let node_id = |i: usize| {
+			NodeId::from_pubkey(&PublicKey::from_secret_key(&secp_ctx, &node_privkeys[i]))
+		};
+
+		// The cheapest applicable rate of each seller is used.
+		let sellers = read_only_graph.liquidity_sellers(50_000, 253);
+		let ranking: Vec<_> = sellers.iter().map(|s| (s.node_id, s.lease_fee_sat)).collect();
+		let mut expected = vec![(node_id(1), 500), (node_id(2), 500), (node_id(0), 2_500)];
+		expected[..2].sort_unstable_by(|a, b| a.0.cmp(&b.0));
+		assert_eq!(ranking, expected);
+		assert_eq!(sellers[2].rate, rate(1_000_000, 2_000));
+
+		// Sellers without a rate covering the requested amount are excluded.
+		let sellers = read_only_graph.liquidity_sellers(500_000, 253);
+		let ranking: Vec<_> = sellers.iter().map(|s| (s.node_id, s.lease_fee_sat)).collect();
+		assert_eq!(ranking, vec![(node_id(1), 6_000), (node_id(0), 7_000)]);
+	}
+
 	#[test]
 	fn handling_channel_announcements() {
 		let secp_ctx = Secp256k1::new();
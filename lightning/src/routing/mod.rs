@@ -11,6 +11,7 @@
 
 pub mod gossip;
 mod log_approx;
+pub mod route_server;
 pub mod router;
 pub mod scoring;
 #[cfg(test)]
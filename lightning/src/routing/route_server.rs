@@ -0,0 +1,233 @@
+// This file is Copyright its original authors, visible in version control
+// history.
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! A [`RouteServer`] answers route queries on behalf of other nodes, e.g., for a fleet of mobile
+//! clients, using the same pathfinding logic as the [`DefaultRouter`] but without requiring a
+//! [`ChannelManager`].
+//!
+//! The server operates on snapshots of a [`NetworkGraph`] and a [`ProbabilisticScorer`] in their
+//! regular serialization, e.g., as persisted by a routing node's background processor, and can be
+//! handed fresh snapshots at any time via [`RouteServer::load_snapshot`] without interrupting
+//! queries in progress.
+//!
+//! [`DefaultRouter`]: crate::routing::router::DefaultRouter
+//! [`ChannelManager`]: crate::ln::channelmanager::ChannelManager
+
+use bitcoin::secp256k1::PublicKey;
+
+use crate::io::Cursor;
+use crate::ln::channel_state::ChannelDetails;
+use crate::ln::msgs::DecodeError;
+use crate::routing::gossip::NetworkGraph;
+use crate::routing::router::{
+	find_route, InFlightHtlcs, Route, RouteParameters, ScorerAccountingForInFlightHtlcs,
+};
+use crate::routing::scoring::{
+	ProbabilisticScorer, ProbabilisticScoringDecayParameters, ProbabilisticScoringFeeParameters,
+};
+use crate::sign::EntropySource;
+use crate::sync::{Arc, Mutex};
+use crate::util::logger::Logger;
+use crate::util::ser::ReadableArgs;
+
+use core::ops::Deref;
+
+/// A loaded pair of [`NetworkGraph`] and [`ProbabilisticScorer`] snapshots.
+struct RouteServerSnapshot<L: Deref + Clone>
+where
+	L::Target: Logger,
+{
+	network_graph: Arc<NetworkGraph<L>>,
+	scorer: ProbabilisticScorer<Arc<NetworkGraph<L>>, L>,
+}
+
+/// Answers route queries from serialized [`NetworkGraph`] and [`ProbabilisticScorer`] snapshots.
+///
+/// See the [module-level documentation](self) for details.
+pub struct RouteServer<L: Deref + Clone, ES: Deref>
+where
+	L::Target: Logger,
+	ES::Target: EntropySource,
+{
+	// Queries clone the `Arc` and release the lock before routing, so that loading a new snapshot
+	// never blocks on queries in progress.
+	snapshot: Mutex<Arc<RouteServerSnapshot<L>>>,
+	decay_params: ProbabilisticScoringDecayParameters,
+	score_params: ProbabilisticScoringFeeParameters,
+	logger: L,
+	entropy_source: ES,
+}
+
+impl<L: Deref + Clone, ES: Deref> RouteServer<L, ES>
+where
+	L::Target: Logger,
+	ES::Target: EntropySource,
+{
+	/// Creates a new server from a serialized [`NetworkGraph`] and, optionally, a serialized
+	/// [`ProbabilisticScorer`]. If no scorer snapshot is given, routes are found without any
+	/// liquidity information.
+	pub fn new(
+		graph_snapshot: &[u8], scorer_snapshot: Option<&[u8]>,
+		decay_params: ProbabilisticScoringDecayParameters,
+		score_params: ProbabilisticScoringFeeParameters, logger: L, entropy_source: ES,
+	) -> Result<Self, DecodeError> {
+		let snapshot =
+			Self::read_snapshot(graph_snapshot, scorer_snapshot, decay_params, logger.clone())?;
+		Ok(Self {
+			snapshot: Mutex::new(Arc::new(snapshot)),
+			decay_params,
+			score_params,
+			logger,
+			entropy_source,
+		})
+	}
+
+	fn read_snapshot(
+		graph_snapshot: &[u8], scorer_snapshot: Option<&[u8]>,
+		decay_params: ProbabilisticScoringDecayParameters, logger: L,
+	) -> Result<RouteServerSnapshot<L>, DecodeError> {
+		let network_graph =
+			Arc::new(NetworkGraph::read(&mut Cursor::new(graph_snapshot), logger.clone())?);
+		let scorer = match scorer_snapshot {
+			Some(scorer_snapshot) => ProbabilisticScorer::read(
+				&mut Cursor::new(scorer_snapshot),
+				(decay_params, Arc::clone(&network_graph), logger),
+			)?,
+			None => ProbabilisticScorer::new(decay_params, Arc::clone(&network_graph), logger),
+		};
+		Ok(RouteServerSnapshot { network_graph, scorer })
+	}
+
+	/// Replaces the current snapshots with the given ones, as in [`Self::new`].
+	///
+	/// Queries in progress complete using the previous snapshots. If the snapshots fail to
+	/// deserialize, the previous ones are kept.
+	pub fn load_snapshot(
+		&self, graph_snapshot: &[u8], scorer_snapshot: Option<&[u8]>,
+	) -> Result<(), DecodeError> {
+		let snapshot = Self::read_snapshot(
+			graph_snapshot,
+			scorer_snapshot,
+			self.decay_params,
+			self.logger.clone(),
+		)?;
+		*self.snapshot.lock().unwrap() = Arc::new(snapshot);
+		Ok(())
+	}
+
+	/// Returns the [`NetworkGraph`] of the current snapshot.
+	pub fn network_graph(&self) -> Arc<NetworkGraph<L>> {
+		Arc::clone(&self.snapshot.lock().unwrap().network_graph)
+	}
+
+	/// Finds a [`Route`] for a payment from the given `payer`, as [`Router::find_route`] would
+	/// for a node using a [`DefaultRouter`] with the current snapshots.
+	///
+	/// As the server has no knowledge of the payer's channels, `first_hops` should be provided by
+	/// the payer if any of them are unannounced, as should any HTLCs it has in flight.
+	///
+	/// [`Router::find_route`]: crate::routing::router::Router::find_route
+	/// [`DefaultRouter`]: crate::routing::router::DefaultRouter
+	pub fn find_route(
+		&self, payer: &PublicKey, route_params: &RouteParameters,
+		first_hops: Option<&[&ChannelDetails]>, inflight_htlcs: InFlightHtlcs,
+	) -> Result<Route, &'static str> {
+		let snapshot = Arc::clone(&*self.snapshot.lock().unwrap());
+		let random_seed_bytes = self.entropy_source.get_secure_random_bytes();
+		find_route(
+			payer,
+			route_params,
+			&snapshot.network_graph,
+			first_hops,
+			&*self.logger,
+			&ScorerAccountingForInFlightHtlcs::new(&snapshot.scorer, &inflight_htlcs),
+			&self.score_params,
+			&random_seed_bytes,
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::RouteServer;
+
+	use crate::routing::gossip::NetworkGraph;
+	use crate::routing::router::{
+		find_route, InFlightHtlcs, PaymentParameters, Route, RouteParameters,
+	};
+	use crate::routing::scoring::{
+		ProbabilisticScorer, ProbabilisticScoringDecayParameters, ProbabilisticScoringFeeParameters,
+	};
+	use crate::routing::test_utils::{build_graph, get_nodes};
+	use crate::util::ser::Writeable;
+	use crate::util::test_utils::TestKeysInterface;
+
+	use bitcoin::network::Network;
+
+	use crate::sync::Arc;
+
+	#[test]
+	fn finds_routes_from_snapshots() {
+		let (secp_ctx, network_graph, _, _, logger) = build_graph();
+		let (_, our_id, _, nodes) = get_nodes(&secp_ctx);
+		let decay_params = ProbabilisticScoringDecayParameters::default();
+		let score_params = ProbabilisticScoringFeeParameters::default();
+		let scorer =
+			ProbabilisticScorer::new(decay_params, Arc::clone(&network_graph), Arc::clone(&logger));
+		let graph_snapshot = network_graph.encode();
+		let scorer_snapshot = scorer.encode();
+
+		let keys_manager = TestKeysInterface::new(&[0; 32], Network::Testnet);
+		let server = RouteServer::new(
+			&graph_snapshot,
+			Some(&scorer_snapshot),
+			decay_params,
+			score_params.clone(),
+			Arc::clone(&logger),
+			&keys_manager,
+		)
+		.unwrap();
+		assert!(*server.network_graph() == *network_graph);
+
+		// The server finds the same route as the payer would on its own.
+		let payment_params = PaymentParameters::from_node_id(nodes[2], 42);
+		let route_params = RouteParameters::from_payment_params_and_value(payment_params, 100);
+		let route = server.find_route(&our_id, &route_params, None, InFlightHtlcs::new()).unwrap();
+		let expected_route = find_route(
+			&our_id,
+			&route_params,
+			&network_graph,
+			None,
+			Arc::clone(&logger),
+			&scorer,
+			&score_params,
+			&[42; 32],
+		)
+		.unwrap();
+		// Paths only differ in their randomized CLTV expiry deltas.
+		let hops = |route: &Route| {
+			route.paths[0]
+				.hops
+				.iter()
+				.map(|hop| (hop.pubkey, hop.short_channel_id, hop.fee_msat))
+				.collect::<Vec<_>>()
+		};
+		assert_eq!(hops(&route), hops(&expected_route));
+
+		// Invalid snapshots are rejected, keeping the previous ones.
+		assert!(server.load_snapshot(&graph_snapshot[..graph_snapshot.len() / 2], None).is_err());
+		assert!(server.find_route(&our_id, &route_params, None, InFlightHtlcs::new()).is_ok());
+
+		// Once a graph without a path to the payee is loaded, no route is found anymore.
+		let empty_graph = NetworkGraph::new(Network::Testnet, Arc::clone(&logger));
+		server.load_snapshot(&empty_graph.encode(), None).unwrap();
+		assert!(*server.network_graph() == empty_graph);
+		assert!(server.find_route(&our_id, &route_params, None, InFlightHtlcs::new()).is_err());
+	}
+}
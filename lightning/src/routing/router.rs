@@ -21,7 +21,7 @@ use crate::crypto::chacha20::ChaCha20;
 use crate::ln::channel_state::ChannelDetails;
 use crate::ln::channelmanager::{PaymentId, RecipientOnionFields, MIN_FINAL_CLTV_EXPIRY_DELTA};
 use crate::ln::msgs::{DecodeError, MAX_VALUE_MSAT};
-use crate::ln::onion_utils;
+use crate::ln::onion_utils::{self, OnionPayloadSizeExceeded};
 use crate::offers::invoice::Bolt12Invoice;
 use crate::offers::static_invoice::StaticInvoice;
 use crate::routing::gossip::{
@@ -771,10 +771,13 @@ impl RouteParameters {
 
 	/// Sets the maximum number of hops that can be included in a payment path, based on the provided
 	/// [`RecipientOnionFields`] and blinded paths.
+	///
+	/// Fails if the onion would be too large even for a single-hop path, describing which custom
+	/// TLVs may be removed to fit.
 	#[rustfmt::skip]
 	pub fn set_max_path_length(
 		&mut self, recipient_onion: &RecipientOnionFields, is_keysend: bool, best_block_height: u32
-	) -> Result<(), ()> {
+	) -> Result<(), OnionPayloadSizeExceeded> {
 		let keysend_preimage_opt = is_keysend.then(|| PaymentPreimage([42; 32]));
 		// TODO: no way to account for the invoice request here yet
 		onion_utils::set_max_path_length(
@@ -829,6 +832,20 @@ pub const DEFAULT_MAX_PATH_COUNT: u8 = 10;
 
 const DEFAULT_MAX_CHANNEL_SATURATION_POW_HALF: u8 = 2;
 
+/// Maximum number of Trampoline nodes a payment may be routed through. See
+/// [`PaymentParameters::for_trampoline`].
+pub const MAX_TRAMPOLINE_NODES: usize = 2;
+
+/// The base fee, in millisatoshi, we offer each Trampoline node a payment is routed through.
+pub const DEFAULT_TRAMPOLINE_FEE_BASE_MSAT: u64 = 4_000;
+
+/// The proportional fee, in millionths of the amount forwarded, we offer each Trampoline node a
+/// payment is routed through.
+pub const DEFAULT_TRAMPOLINE_FEE_PROPORTIONAL_MILLIONTHS: u64 = 4_000;
+
+/// The CLTV expiry delta we offer each Trampoline node a payment is routed through.
+pub const DEFAULT_TRAMPOLINE_CLTV_EXPIRY_DELTA: u32 = 288;
+
 // The median hop CLTV expiry delta currently seen in the network.
 const MEDIAN_HOP_CLTV_EXPIRY_DELTA: u32 = 40;
 
@@ -864,7 +881,49 @@ pub struct PaymentParameters {
 	/// Defaults to [`DEFAULT_MAX_PATH_COUNT`].
 	pub max_path_count: u8,
 
+	/// If set, enables adaptive splitting, limiting the initial attempt of a payment to at most
+	/// this many paths rather than [`Self::max_path_count`].
+	///
+	/// Rather than splitting a payment into many parts up-front, this tries to send it over few,
+	/// large paths. When a path then fails, e.g., with a `temporary_channel_failure`, the failed
+	/// channel is avoided and only the failed part is re-split across alternative channels on
+	/// retry, using up to [`Self::max_path_count`] paths in total. Thus, this only has an effect if
+	/// the payment is sent with a [`Retry`] strategy allowing for retries.
+	///
+	/// If no route is found using this many paths, the initial attempt falls back to using up to
+	/// [`Self::max_path_count`] paths.
+	///
+	/// Defaults to `None`.
+	///
+	/// [`Retry`]: crate::ln::channelmanager::Retry
+	pub initial_max_path_count: Option<u8>,
+
+	/// The number of redundant paths to send in addition to the ones carrying the payment amount,
+	/// each carrying about as much as an average non-redundant path.
+	///
+	/// The payment then succeeds as soon as enough of its paths reach the recipient, rather than
+	/// having to wait for failed paths to be retried, at the cost of temporarily locking up
+	/// additional liquidity. Note that only paths which are needed to reach the payment amount
+	/// may be claimed by the recipient, whereas the others have to be failed back. This requires
+	/// the recipient's cooperation, e.g., LDK recipients only do so with
+	/// [`UserConfig::fail_back_excess_mpp_htlcs`] set. Otherwise, the recipient may claim all
+	/// paths, overpaying it by the amount carried by the redundant paths. Thus, this should only
+	/// be set when paying recipients known to cooperate.
+	///
+	/// Redundant paths are only sent on the initial attempt of a payment and are skipped if no
+	/// route could be found for them. They are not sent for payments to BOLT 12 invoices.
+	///
+	/// Defaults to `0`.
+	///
+	/// [`UserConfig::fail_back_excess_mpp_htlcs`]: crate::util::config::UserConfig::fail_back_excess_mpp_htlcs
+	pub redundant_path_count: u8,
+
 	/// The maximum number of [`Path::hops`] in any returned path.
+	///
+	/// Note that hops in a [`BlindedTail`] are not counted here. However, as they need to fit in
+	/// the onion alongside the unblinded hops, paths whose unblinded and blinded hops together
+	/// would exceed [`MAX_PATH_LENGTH_ESTIMATE`] hops are never returned.
+	///
 	/// Defaults to [`MAX_PATH_LENGTH_ESTIMATE`].
 	pub max_path_length: u8,
 
@@ -892,6 +951,11 @@ pub struct PaymentParameters {
 	/// payment was previously attempted over and which caused the payment to fail. Future attempts
 	/// for the same payment shouldn't be relayed through any of these blinded paths.
 	pub previously_failed_blinded_path_idxs: Vec<u64>,
+
+	/// The Trampoline nodes, in order, the payment should be routed through. If non-empty, we only
+	/// find a route to the first Trampoline node and leave finding a route to the payee to the
+	/// Trampoline nodes. See [`PaymentParameters::for_trampoline`].
+	pub trampoline_node_ids: Vec<PublicKey>,
 }
 
 impl Writeable for PaymentParameters {
@@ -919,6 +983,9 @@ impl Writeable for PaymentParameters {
 			(9, self.payee.final_cltv_expiry_delta(), option),
 			(11, self.previously_failed_blinded_path_idxs, required_vec),
 			(13, self.max_path_length, required),
+			(15, self.trampoline_node_ids, optional_vec),
+			(17, self.initial_max_path_count, option),
+			(19, self.redundant_path_count, required),
 		});
 		Ok(())
 	}
@@ -940,6 +1007,9 @@ impl ReadableArgs<u32> for PaymentParameters {
 			(9, final_cltv_expiry_delta, (default_value, default_final_cltv_expiry_delta)),
 			(11, previously_failed_blinded_path_idxs, optional_vec),
 			(13, max_path_length, (default_value, MAX_PATH_LENGTH_ESTIMATE)),
+			(15, trampoline_node_ids, optional_vec),
+			(17, initial_max_path_count, option),
+			(19, redundant_path_count, (default_value, 0u8)),
 		});
 		let blinded_route_hints = blinded_route_hints.unwrap_or(vec![]);
 		let payee = if blinded_route_hints.len() != 0 {
@@ -962,12 +1032,15 @@ impl ReadableArgs<u32> for PaymentParameters {
 		Ok(Self {
 			max_total_cltv_expiry_delta: _init_tlv_based_struct_field!(max_total_cltv_expiry_delta, (default_value, unused)),
 			max_path_count: _init_tlv_based_struct_field!(max_path_count, (default_value, unused)),
+			initial_max_path_count,
+			redundant_path_count: _init_tlv_based_struct_field!(redundant_path_count, (default_value, unused)),
 			payee,
 			max_channel_saturation_power_of_half: _init_tlv_based_struct_field!(max_channel_saturation_power_of_half, (default_value, unused)),
 			expiry_time,
 			previously_failed_channels: previously_failed_channels.unwrap_or(Vec::new()),
 			previously_failed_blinded_path_idxs: previously_failed_blinded_path_idxs.unwrap_or(Vec::new()),
 			max_path_length: _init_tlv_based_struct_field!(max_path_length, (default_value, unused)),
+			trampoline_node_ids: trampoline_node_ids.unwrap_or(Vec::new()),
 		})
 	}
 }
@@ -984,10 +1057,13 @@ impl PaymentParameters {
 			expiry_time: None,
 			max_total_cltv_expiry_delta: DEFAULT_MAX_TOTAL_CLTV_EXPIRY_DELTA,
 			max_path_count: DEFAULT_MAX_PATH_COUNT,
+			initial_max_path_count: None,
+			redundant_path_count: 0,
 			max_path_length: MAX_PATH_LENGTH_ESTIMATE,
 			max_channel_saturation_power_of_half: DEFAULT_MAX_CHANNEL_SATURATION_POW_HALF,
 			previously_failed_channels: Vec::new(),
 			previously_failed_blinded_path_idxs: Vec::new(),
+			trampoline_node_ids: Vec::new(),
 		}
 	}
 
@@ -1057,10 +1133,13 @@ impl PaymentParameters {
 			expiry_time: None,
 			max_total_cltv_expiry_delta: DEFAULT_MAX_TOTAL_CLTV_EXPIRY_DELTA,
 			max_path_count: DEFAULT_MAX_PATH_COUNT,
+			initial_max_path_count: None,
+			redundant_path_count: 0,
 			max_path_length: MAX_PATH_LENGTH_ESTIMATE,
 			max_channel_saturation_power_of_half: DEFAULT_MAX_CHANNEL_SATURATION_POW_HALF,
 			previously_failed_channels: Vec::new(),
 			previously_failed_blinded_path_idxs: Vec::new(),
+			trampoline_node_ids: Vec::new(),
 		}
 	}
 
@@ -1075,6 +1154,8 @@ impl PaymentParameters {
 		Self {
 			max_total_cltv_expiry_delta: params_config.max_total_cltv_expiry_delta,
 			max_path_count: params_config.max_path_count,
+			initial_max_path_count: params_config.initial_max_path_count,
+			max_path_length: core::cmp::min(self.max_path_length, params_config.max_path_length),
 			max_channel_saturation_power_of_half: params_config.max_channel_saturation_power_of_half,
 			..self
 		}
@@ -1148,6 +1229,30 @@ impl PaymentParameters {
 		Self { max_path_count, ..self }
 	}
 
+	/// Enables adaptive splitting, limiting the initial attempt to at most `initial_max_path_count`
+	/// paths. See [`PaymentParameters::initial_max_path_count`].
+	///
+	/// This is not exported to bindings users since bindings don't support move semantics
+	pub fn with_initial_max_path_count(self, initial_max_path_count: u8) -> Self {
+		Self { initial_max_path_count: Some(initial_max_path_count), ..self }
+	}
+
+	/// Includes the number of redundant paths to send. See
+	/// [`PaymentParameters::redundant_path_count`].
+	///
+	/// This is not exported to bindings users since bindings don't support move semantics
+	pub fn with_redundant_path_count(self, redundant_path_count: u8) -> Self {
+		Self { redundant_path_count, ..self }
+	}
+
+	/// Includes a limit for the maximum number of [`Path::hops`] in any payment path. See
+	/// [`PaymentParameters::max_path_length`].
+	///
+	/// This is not exported to bindings users since bindings don't support move semantics
+	pub fn with_max_path_length(self, max_path_length: u8) -> Self {
+		Self { max_path_length, ..self }
+	}
+
 	/// Includes a limit for the maximum share of a channel's total capacity that can be sent over, as
 	/// a power of 1/2. See [`PaymentParameters::max_channel_saturation_power_of_half`].
 	///
@@ -1158,6 +1263,34 @@ impl PaymentParameters {
 		Self { max_channel_saturation_power_of_half, ..self }
 	}
 
+	/// Routes the payment through the Trampoline node with the given `trampoline_node_id`, which
+	/// will find a route to the payee (or the next Trampoline node) on our behalf. This allows
+	/// paying recipients which are not (or not reachably) present in our network graph, e.g. for
+	/// light clients which only keep track of their own channels.
+	///
+	/// May be called up to [`MAX_TRAMPOLINE_NODES`] times, in which case the payment is routed
+	/// through the Trampoline nodes in the order they were added. Each Trampoline node is offered
+	/// [`DEFAULT_TRAMPOLINE_FEE_BASE_MSAT`] and [`DEFAULT_TRAMPOLINE_FEE_PROPORTIONAL_MILLIONTHS`]
+	/// as fee as well as [`DEFAULT_TRAMPOLINE_CLTV_EXPIRY_DELTA`], which count towards
+	/// [`RouteParameters::max_total_routing_fee_msat`] and
+	/// [`PaymentParameters::max_total_cltv_expiry_delta`], respectively.
+	///
+	/// Trampoline payments are only supported to unblinded payees without route hints and are
+	/// always sent over a single path. Errors if the parameters were initialized with
+	/// [`PaymentParameters::blinded`] or if [`MAX_TRAMPOLINE_NODES`] were already added.
+	///
+	/// This is not exported to bindings users since bindings don't support move semantics
+	pub fn for_trampoline(mut self, trampoline_node_id: PublicKey) -> Result<Self, ()> {
+		if let Payee::Blinded { .. } = self.payee {
+			return Err(());
+		}
+		if self.trampoline_node_ids.len() >= MAX_TRAMPOLINE_NODES {
+			return Err(());
+		}
+		self.trampoline_node_ids.push(trampoline_node_id);
+		Ok(self)
+	}
+
 	#[rustfmt::skip]
 	pub(crate) fn insert_previously_failed_blinded_path(&mut self, failed_blinded_tail: &BlindedTail) {
 		let mut found_blinded_tail = false;
@@ -1194,6 +1327,12 @@ pub struct RouteParametersConfig {
 	/// Defaults to [`DEFAULT_MAX_PATH_COUNT`].
 	pub max_path_count: u8,
 
+	/// If set, enables adaptive splitting, limiting the initial attempt of a payment to at most
+	/// this many paths. See [`PaymentParameters::initial_max_path_count`].
+	///
+	/// Defaults to `None`.
+	pub initial_max_path_count: Option<u8>,
+
 	/// Selects the maximum share of a channel's total capacity which will be sent over a channel,
 	/// as a power of 1/2. A higher value prefers to send the payment using more MPP parts whereas
 	/// a lower value prefers to send larger MPP parts, potentially saturating channels and
@@ -1208,6 +1347,15 @@ pub struct RouteParametersConfig {
 	///
 	/// Default value: 2
 	pub max_channel_saturation_power_of_half: u8,
+
+	/// The maximum number of [`Path::hops`] in any payment path. See
+	/// [`PaymentParameters::max_path_length`].
+	///
+	/// Note that this may be further lowered when sending the payment, to make sure the path fits
+	/// in the onion alongside the recipient's blinded path and any custom TLVs.
+	///
+	/// Defaults to [`MAX_PATH_LENGTH_ESTIMATE`].
+	pub max_path_length: u8,
 }
 
 impl_writeable_tlv_based!(RouteParametersConfig, {
@@ -1215,6 +1363,8 @@ impl_writeable_tlv_based!(RouteParametersConfig, {
 	(3, max_total_cltv_expiry_delta, required),
 	(5, max_path_count, required),
 	(7, max_channel_saturation_power_of_half, required),
+	(9, max_path_length, (default_value, MAX_PATH_LENGTH_ESTIMATE)),
+	(11, initial_max_path_count, option),
 });
 
 impl RouteParametersConfig {
@@ -1239,6 +1389,22 @@ impl RouteParametersConfig {
 		Self { max_path_count, ..self }
 	}
 
+	/// Enables adaptive splitting, limiting the initial attempt to at most `initial_max_path_count`
+	/// paths. See [`PaymentParameters::initial_max_path_count`].
+	///
+	/// This is not exported to bindings users since bindings don't support move semantics
+	pub fn with_initial_max_path_count(self, initial_max_path_count: u8) -> Self {
+		Self { initial_max_path_count: Some(initial_max_path_count), ..self }
+	}
+
+	/// Includes a limit for the maximum number of [`Path::hops`] in any payment path. See
+	/// [`PaymentParameters::max_path_length`].
+	///
+	/// This is not exported to bindings users since bindings don't support move semantics
+	pub fn with_max_path_length(self, max_path_length: u8) -> Self {
+		Self { max_path_length, ..self }
+	}
+
 	/// Includes a limit for the maximum share of a channel's total capacity that can be sent over, as
 	/// a power of 1/2. See [`PaymentParameters::max_channel_saturation_power_of_half`].
 	///
@@ -1257,7 +1423,9 @@ impl Default for RouteParametersConfig {
 			max_total_routing_fee_msat: None,
 			max_total_cltv_expiry_delta: DEFAULT_MAX_TOTAL_CLTV_EXPIRY_DELTA,
 			max_path_count: DEFAULT_MAX_PATH_COUNT,
+			initial_max_path_count: None,
 			max_channel_saturation_power_of_half: DEFAULT_MAX_CHANNEL_SATURATION_POW_HALF,
+			max_path_length: MAX_PATH_LENGTH_ESTIMATE,
 		}
 	}
 }
@@ -1310,7 +1478,7 @@ impl Payee {
 		}
 	}
 	#[rustfmt::skip]
-	fn supports_basic_mpp(&self) -> bool {
+	pub(crate) fn supports_basic_mpp(&self) -> bool {
 		match self {
 			Self::Clear { features, .. } => features.as_ref().is_some_and(|f| f.supports_basic_mpp()),
 			Self::Blinded { features, .. } => features.as_ref().is_some_and(|f| f.supports_basic_mpp()),
@@ -2458,12 +2626,119 @@ pub fn find_route<L: Deref, GL: Deref, S: ScoreLookUp>(
 ) -> Result<Route, &'static str>
 where L::Target: Logger, GL::Target: Logger {
 	let graph_lock = network_graph.read_only();
+	if !route_params.payment_params.trampoline_node_ids.is_empty() {
+		return get_trampoline_route(our_node_pubkey, route_params, &graph_lock, first_hops, logger,
+			scorer, score_params, random_seed_bytes);
+	}
 	let mut route = get_route(our_node_pubkey, &route_params, &graph_lock, first_hops, logger,
 		scorer, score_params, random_seed_bytes)?;
 	add_random_cltv_offset(&mut route, &route_params.payment_params, &graph_lock, random_seed_bytes);
 	Ok(route)
 }
 
+/// Finds a route through the [`PaymentParameters::trampoline_node_ids`]. We only route to the
+/// first Trampoline node ourselves, the remaining Trampoline hops and the payee are placed in the
+/// resulting [`Path`]'s [`BlindedTail::trampoline_hops`], with no blinded hops following them.
+#[rustfmt::skip]
+fn get_trampoline_route<L: Deref, S: ScoreLookUp>(
+	our_node_pubkey: &PublicKey, route_params: &RouteParameters, network_graph: &ReadOnlyNetworkGraph,
+	first_hops: Option<&[&ChannelDetails]>, logger: L, scorer: &S, score_params: &S::ScoreParams,
+	random_seed_bytes: &[u8; 32]
+) -> Result<Route, &'static str>
+where L::Target: Logger {
+	let payment_params = &route_params.payment_params;
+	let (payee_pubkey, final_cltv_expiry_delta) = match &payment_params.payee {
+		Payee::Clear { node_id, route_hints, final_cltv_expiry_delta, .. } => {
+			if !route_hints.is_empty() {
+				return Err("Route hints cannot be used when paying via Trampoline");
+			}
+			(*node_id, *final_cltv_expiry_delta)
+		},
+		Payee::Blinded { .. } => return Err("Cannot pay a blinded payee via Trampoline"),
+	};
+	let trampoline_node_ids = &payment_params.trampoline_node_ids;
+	if trampoline_node_ids.len() > MAX_TRAMPOLINE_NODES {
+		return Err("Too many Trampoline nodes provided");
+	}
+	if trampoline_node_ids.iter().any(|pk| *pk == payee_pubkey || pk == our_node_pubkey) {
+		return Err("Cannot route via Trampoline through ourselves or the payee");
+	}
+
+	let node_features = |pubkey: &PublicKey| {
+		network_graph.node(&NodeId::from_pubkey(pubkey))
+			.and_then(|node| node.announcement_info.as_ref().map(|info| info.features().clone()))
+			.unwrap_or_else(NodeFeatures::empty)
+	};
+
+	// Build the Trampoline hops backwards from the payee, with each Trampoline node being paid a
+	// fee based on the amount it has to forward.
+	let final_value_msat = route_params.final_value_msat;
+	let mut trampoline_hops = Vec::with_capacity(trampoline_node_ids.len() + 1);
+	trampoline_hops.push(TrampolineHop {
+		pubkey: payee_pubkey,
+		node_features: payment_params.payee.node_features().unwrap_or_else(|| node_features(&payee_pubkey)),
+		fee_msat: final_value_msat,
+		cltv_expiry_delta: final_cltv_expiry_delta,
+	});
+	let mut trampoline_fee_msat = 0u64;
+	let mut trampoline_cltv_expiry_delta = final_cltv_expiry_delta;
+	for trampoline_node_id in trampoline_node_ids.iter().rev() {
+		let amt_to_forward_msat = final_value_msat.saturating_add(trampoline_fee_msat);
+		let fee_msat = DEFAULT_TRAMPOLINE_FEE_BASE_MSAT.saturating_add(
+			amt_to_forward_msat.saturating_mul(DEFAULT_TRAMPOLINE_FEE_PROPORTIONAL_MILLIONTHS) / 1_000_000);
+		trampoline_fee_msat = trampoline_fee_msat.saturating_add(fee_msat);
+		trampoline_cltv_expiry_delta = trampoline_cltv_expiry_delta
+			.saturating_add(DEFAULT_TRAMPOLINE_CLTV_EXPIRY_DELTA);
+		trampoline_hops.insert(0, TrampolineHop {
+			pubkey: *trampoline_node_id,
+			node_features: node_features(trampoline_node_id),
+			fee_msat,
+			cltv_expiry_delta: DEFAULT_TRAMPOLINE_CLTV_EXPIRY_DELTA,
+		});
+	}
+
+	let max_cltv_expiry_delta = payment_params.max_total_cltv_expiry_delta
+		.checked_sub(trampoline_cltv_expiry_delta)
+		.ok_or("Can't find a route that adheres to the maximum total CLTV expiry delta")?;
+	let max_total_routing_fee_msat = match route_params.max_total_routing_fee_msat {
+		Some(max_fee_msat) => Some(max_fee_msat.checked_sub(trampoline_fee_msat)
+			.ok_or("Failed to find route that adheres to the maximum total fee limit")?),
+		None => None,
+	};
+
+	// Trampoline payments are (currently) sent over a single path to the first Trampoline node.
+	let mut first_trampoline_params = PaymentParameters::from_node_id(trampoline_node_ids[0], 0)
+		.with_max_total_cltv_expiry_delta(max_cltv_expiry_delta)
+		.with_max_path_count(1)
+		.with_max_path_length(payment_params.max_path_length)
+		.with_max_channel_saturation_power_of_half(payment_params.max_channel_saturation_power_of_half);
+	first_trampoline_params.previously_failed_channels = payment_params.previously_failed_channels.clone();
+	let first_trampoline_route_params = RouteParameters {
+		payment_params: first_trampoline_params,
+		final_value_msat: final_value_msat.saturating_add(trampoline_fee_msat),
+		max_total_routing_fee_msat,
+	};
+	let mut route = get_route(our_node_pubkey, &first_trampoline_route_params, network_graph,
+		first_hops, logger, scorer, score_params, random_seed_bytes)?;
+	add_random_cltv_offset(&mut route, &first_trampoline_route_params.payment_params, network_graph,
+		random_seed_bytes);
+
+	for path in route.paths.iter_mut() {
+		// The last hop's fee is the fee paid to the Trampoline nodes, see `BlindedTail`.
+		path.hops.last_mut().ok_or("Route to Trampoline node has no hops")?.fee_msat = trampoline_fee_msat;
+		path.blinded_tail = Some(BlindedTail {
+			trampoline_hops: trampoline_hops.clone(),
+			hops: Vec::new(),
+			// There are no blinded hops, so the blinding point is never used.
+			blinding_point: payee_pubkey,
+			excess_final_cltv_expiry_delta: 0,
+			final_value_msat,
+		});
+	}
+	route.route_params = Some(route_params.clone());
+	Ok(route)
+}
+
 #[rustfmt::skip]
 pub(crate) fn get_route<L: Deref, S: ScoreLookUp>(
 	our_node_pubkey: &PublicKey, route_params: &RouteParameters, network_graph: &ReadOnlyNetworkGraph,
@@ -2836,8 +3111,20 @@ where L::Target: Logger {
 						});
 
 					// Do not consider candidate hops that would exceed the maximum path length.
+					// Blinded hops aren't counted towards `max_path_length`, but each of them
+					// still takes up an onion payload. Thus, for blinded candidates we reserve
+					// as much of the remaining length as needed to keep the unblinded and blinded
+					// hops together within `MAX_PATH_LENGTH_ESTIMATE`, rather than only failing
+					// once the onion turns out too large at send time.
 					let path_length_to_node = $next_hops_path_length
-						+ if $candidate.blinded_hint_idx().is_some() { 0 } else { 1 };
+						+ match $candidate.blinded_path() {
+							Some(path) => {
+								let max_unblinded_hops = (MAX_PATH_LENGTH_ESTIMATE as usize + 1)
+									.saturating_sub(path.blinded_hops().len());
+								(max_path_length as usize).saturating_sub(max_unblinded_hops) as u8
+							},
+							None => 1,
+						};
 					let exceeds_max_path_length = path_length_to_node > max_path_length;
 
 					// Do not consider candidates that exceed the maximum total cltv expiry limit.
@@ -3933,11 +4220,13 @@ mod tests {
 	use crate::ln::types::ChannelId;
 	use crate::routing::gossip::{EffectiveCapacity, NetworkGraph, NodeId, P2PGossipSync};
 	use crate::routing::router::{
-		add_random_cltv_offset, build_route_from_hops_internal, default_node_features, get_route,
-		BlindedPathCandidate, BlindedTail, CandidateRouteHop, InFlightHtlcs, Path,
+		add_random_cltv_offset, build_route_from_hops_internal, default_node_features, find_route,
+		get_route, BlindedPathCandidate, BlindedTail, CandidateRouteHop, InFlightHtlcs, Path,
 		PaymentParameters, PublicHopCandidate, Route, RouteHint, RouteHintHop, RouteHop,
-		RouteParameters, RoutingFees, ScorerAccountingForInFlightHtlcs,
-		DEFAULT_MAX_TOTAL_CLTV_EXPIRY_DELTA, MAX_PATH_LENGTH_ESTIMATE,
+		RouteParameters, RouteParametersConfig, RoutingFees, ScorerAccountingForInFlightHtlcs,
+		DEFAULT_MAX_TOTAL_CLTV_EXPIRY_DELTA, DEFAULT_TRAMPOLINE_CLTV_EXPIRY_DELTA,
+		DEFAULT_TRAMPOLINE_FEE_BASE_MSAT, DEFAULT_TRAMPOLINE_FEE_PROPORTIONAL_MILLIONTHS,
+		MAX_PATH_LENGTH_ESTIMATE,
 	};
 	use crate::routing::scoring::{
 		ChannelUsage, FixedPenaltyScorer, ProbabilisticScorer, ProbabilisticScoringDecayParameters,
@@ -3990,6 +4279,8 @@ mod tests {
 				&PublicKey::from_slice(&[2; 33]).unwrap(),
 			)),
 			channel_type: None,
+			commitment_format: None,
+			p2a_anchor_value_satoshis: None,
 			short_channel_id,
 			outbound_scid_alias: None,
 			inbound_scid_alias: None,
@@ -4012,6 +4303,7 @@ mod tests {
 			channel_shutdown_state: Some(ChannelShutdownState::NotShuttingDown),
 			pending_inbound_htlcs: Vec::new(),
 			pending_outbound_htlcs: Vec::new(),
+			protocol_error_history: Vec::new(),
 		}
 	}
 
@@ -7500,6 +7792,112 @@ mod tests {
 		}
 	}
 
+	#[test]
+	fn limits_path_length_with_blinded_tail() {
+		let (secp_ctx, network, _, _, logger) = build_line_graph();
+		let (_, our_id, _, nodes) = get_nodes(&secp_ctx);
+		let network_graph = network.read_only();
+
+		let scorer = ln_test_utils::TestScorer::new();
+		let random_seed_bytes = [42; 32];
+		let blinded_payinfo = BlindedPayInfo {
+			fee_base_msat: 0,
+			fee_proportional_millionths: 0,
+			htlc_minimum_msat: 0,
+			htlc_maximum_msat: MAX_VALUE_MSAT,
+			cltv_expiry_delta: 0,
+			features: BlindedHopFeatures::empty(),
+		};
+
+		// The two blinded hops take up one more onion payload than the unblinded hop to the
+		// introduction node would, so the introduction node may be at most 18 hops away.
+		let blinded_path = dummy_blinded_path(nodes[17], blinded_payinfo.clone());
+		let payment_params = PaymentParameters::blinded(vec![blinded_path]);
+		let route_params = RouteParameters::from_payment_params_and_value(payment_params, 100);
+		let route = get_route(
+			&our_id,
+			&route_params,
+			&network_graph,
+			None,
+			Arc::clone(&logger),
+			&scorer,
+			&Default::default(),
+			&random_seed_bytes,
+		)
+		.unwrap();
+		assert_eq!(route.paths[0].hops.len(), usize::from(MAX_PATH_LENGTH_ESTIMATE) - 1);
+		assert_eq!(route.paths[0].blinded_tail.as_ref().unwrap().hops.len(), 2);
+
+		// Even though the unblinded hops alone would fit, the route is rejected at routing time
+		// once the blinded hops are accounted for.
+		let blinded_path = dummy_blinded_path(nodes[18], blinded_payinfo.clone());
+		let payment_params = PaymentParameters::blinded(vec![blinded_path]);
+		let route_params = RouteParameters::from_payment_params_and_value(payment_params, 100);
+		match get_route(
+			&our_id,
+			&route_params,
+			&network_graph,
+			None,
+			Arc::clone(&logger),
+			&scorer,
+			&Default::default(),
+			&random_seed_bytes,
+		) {
+			Err(err) => {
+				assert_eq!(err, "Failed to find a path to the given destination");
+			},
+			Ok(_) => panic!("Expected error"),
+		}
+
+		// A one-hop blinded path is budgeted exactly like an unblinded recipient.
+		let blinded_path = dummy_one_hop_blinded_path(nodes[18], blinded_payinfo);
+		let payment_params = PaymentParameters::blinded(vec![blinded_path]);
+		let route_params = RouteParameters::from_payment_params_and_value(payment_params, 100);
+		let route = get_route(
+			&our_id,
+			&route_params,
+			&network_graph,
+			None,
+			Arc::clone(&logger),
+			&scorer,
+			&Default::default(),
+			&random_seed_bytes,
+		)
+		.unwrap();
+		assert_eq!(route.paths[0].hops.len(), usize::from(MAX_PATH_LENGTH_ESTIMATE));
+
+		// Lower user-provided limits are respected on top of that.
+		let config = RouteParametersConfig::default().with_max_path_length(5);
+		let payment_params = PaymentParameters::from_node_id(nodes[4], 0)
+			.with_user_config_ignoring_fee_limit(config);
+		assert_eq!(payment_params.max_path_length, 5);
+		let route_params = RouteParameters::from_payment_params_and_value(payment_params, 100);
+		assert!(get_route(
+			&our_id,
+			&route_params,
+			&network_graph,
+			None,
+			Arc::clone(&logger),
+			&scorer,
+			&Default::default(),
+			&random_seed_bytes,
+		)
+		.is_ok());
+		let payment_params = PaymentParameters::from_node_id(nodes[5], 0).with_max_path_length(5);
+		let route_params = RouteParameters::from_payment_params_and_value(payment_params, 100);
+		assert!(get_route(
+			&our_id,
+			&route_params,
+			&network_graph,
+			None,
+			Arc::clone(&logger),
+			&scorer,
+			&Default::default(),
+			&random_seed_bytes,
+		)
+		.is_err());
+	}
+
 	#[test]
 	#[rustfmt::skip]
 	fn adds_and_limits_cltv_offset() {
@@ -9378,6 +9776,116 @@ mod tests {
 		assert_eq!(route.paths[0].hops.len(), 2);
 		assert_eq!(route.paths[0].hops[1].short_channel_id, 16);
 	}
+
+	#[test]
+	fn routes_via_trampoline_nodes() {
+		let (secp_ctx, network_graph, _, _, logger) = build_graph();
+		let (_, our_id, _, nodes) = get_nodes(&secp_ctx);
+		let scorer = ln_test_utils::TestScorer::new();
+		let random_seed_bytes = [42; 32];
+		let payee = ln_test_utils::pubkey(42);
+		let amt_msat = 100_000;
+
+		// With a single Trampoline node, we only route to it and leave the rest to the Trampoline.
+		let payment_params =
+			PaymentParameters::from_node_id(payee, 42).for_trampoline(nodes[2]).unwrap();
+		let route_params = RouteParameters::from_payment_params_and_value(payment_params, amt_msat);
+		let route = find_route(
+			&our_id,
+			&route_params,
+			&network_graph,
+			None,
+			Arc::clone(&logger),
+			&scorer,
+			&Default::default(),
+			&random_seed_bytes,
+		)
+		.unwrap();
+		let trampoline_fee_msat = DEFAULT_TRAMPOLINE_FEE_BASE_MSAT
+			+ amt_msat * DEFAULT_TRAMPOLINE_FEE_PROPORTIONAL_MILLIONTHS / 1_000_000;
+		assert_eq!(route.paths.len(), 1);
+		let path = &route.paths[0];
+		assert_eq!(path.hops.last().unwrap().pubkey, nodes[2]);
+		assert_eq!(path.hops.last().unwrap().fee_msat, trampoline_fee_msat);
+		assert_eq!(path.final_value_msat(), amt_msat);
+		assert!(path.fee_msat() >= trampoline_fee_msat);
+
+		let blinded_tail = path.blinded_tail.as_ref().unwrap();
+		assert!(blinded_tail.hops.is_empty());
+		assert_eq!(blinded_tail.trampoline_hops.len(), 2);
+		assert_eq!(blinded_tail.trampoline_hops[0].pubkey, nodes[2]);
+		assert_eq!(blinded_tail.trampoline_hops[0].fee_msat, trampoline_fee_msat);
+		assert_eq!(
+			blinded_tail.trampoline_hops[0].cltv_expiry_delta,
+			DEFAULT_TRAMPOLINE_CLTV_EXPIRY_DELTA
+		);
+		assert_eq!(blinded_tail.trampoline_hops[1].pubkey, payee);
+		assert_eq!(blinded_tail.trampoline_hops[1].fee_msat, amt_msat);
+		assert_eq!(blinded_tail.trampoline_hops[1].cltv_expiry_delta, 42);
+		assert_eq!(route.route_params, Some(route_params));
+
+		// With two Trampoline nodes, the first one is also paid for the second one's fee.
+		let payment_params = PaymentParameters::from_node_id(payee, 42)
+			.for_trampoline(nodes[2])
+			.unwrap()
+			.for_trampoline(nodes[3])
+			.unwrap();
+		let route_params = RouteParameters::from_payment_params_and_value(payment_params, amt_msat);
+		let route = find_route(
+			&our_id,
+			&route_params,
+			&network_graph,
+			None,
+			Arc::clone(&logger),
+			&scorer,
+			&Default::default(),
+			&random_seed_bytes,
+		)
+		.unwrap();
+		let second_fee_msat = trampoline_fee_msat;
+		let first_fee_msat = DEFAULT_TRAMPOLINE_FEE_BASE_MSAT
+			+ (amt_msat + second_fee_msat) * DEFAULT_TRAMPOLINE_FEE_PROPORTIONAL_MILLIONTHS
+				/ 1_000_000;
+		let path = &route.paths[0];
+		assert_eq!(path.hops.last().unwrap().pubkey, nodes[2]);
+		assert_eq!(path.hops.last().unwrap().fee_msat, first_fee_msat + second_fee_msat);
+		let trampoline_hops = &path.blinded_tail.as_ref().unwrap().trampoline_hops;
+		assert_eq!(trampoline_hops.len(), 3);
+		assert_eq!(trampoline_hops[0].pubkey, nodes[2]);
+		assert_eq!(trampoline_hops[0].fee_msat, first_fee_msat);
+		assert_eq!(trampoline_hops[1].pubkey, nodes[3]);
+		assert_eq!(trampoline_hops[1].fee_msat, second_fee_msat);
+		assert_eq!(trampoline_hops[2].pubkey, payee);
+
+		// The Trampoline fees count towards the fee limit.
+		let mut route_params = route_params;
+		route_params.max_total_routing_fee_msat = Some(first_fee_msat + second_fee_msat - 1);
+		let err = find_route(
+			&our_id,
+			&route_params,
+			&network_graph,
+			None,
+			Arc::clone(&logger),
+			&scorer,
+			&Default::default(),
+			&random_seed_bytes,
+		)
+		.unwrap_err();
+		assert_eq!(err, "Failed to find route that adheres to the maximum total fee limit");
+
+		// We only support up to `MAX_TRAMPOLINE_NODES` Trampoline nodes and unblinded payees.
+		assert!(route_params.payment_params.for_trampoline(nodes[4]).is_err());
+		let blinded_payinfo = BlindedPayInfo {
+			fee_base_msat: 0,
+			fee_proportional_millionths: 0,
+			htlc_minimum_msat: 0,
+			htlc_maximum_msat: MAX_VALUE_MSAT,
+			cltv_expiry_delta: 0,
+			features: BlindedHopFeatures::empty(),
+		};
+		let blinded_path = dummy_blinded_path(nodes[2], blinded_payinfo);
+		assert!(PaymentParameters::blinded(vec![blinded_path]).for_trampoline(nodes[2]).is_err());
+	}
 }
 
 #[cfg(any(test, ldk_bench))]
@@ -9490,6 +9998,8 @@ pub(crate) mod bench_utils {
 				&PublicKey::from_slice(&[2; 33]).unwrap(),
 			)),
 			channel_type: None,
+			commitment_format: None,
+			p2a_anchor_value_satoshis: None,
 			short_channel_id: Some(1),
 			inbound_scid_alias: None,
 			outbound_scid_alias: None,
@@ -9514,6 +10024,7 @@ pub(crate) mod bench_utils {
 			channel_shutdown_state: Some(ChannelShutdownState::NotShuttingDown),
 			pending_inbound_htlcs: Vec::new(),
 			pending_outbound_htlcs: Vec::new(),
+			protocol_error_history: Vec::new(),
 		}
 	}
 
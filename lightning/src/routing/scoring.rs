@@ -59,8 +59,10 @@ use crate::routing::gossip::{DirectedChannelInfo, EffectiveCapacity, NetworkGrap
 use crate::routing::log_approx;
 use crate::routing::router::{BlindedPathCandidate, CandidateRouteHop, Path, PublicHopCandidate};
 use crate::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use crate::util::export::{versioned_bytes, EXPORT_SCHEMA_VERSION};
 use crate::util::logger::Logger;
 use crate::util::ser::{Readable, ReadableArgs, Writeable, Writer};
+use alloc::collections::BTreeMap;
 use bucketed_history::{
 	DirectedHistoricalLiquidityTracker, HistoricalBucketRangeTracker, HistoricalLiquidityTracker,
 	LegacyHistoricalBucketRangeTracker,
@@ -575,6 +577,43 @@ impl Writeable for ChannelLiquidities {
 	}
 }
 
+/// The historical liquidity buckets of a single channel, as exported by
+/// [`ProbabilisticScorer::export_history`].
+struct ExportedChannelHistory {
+	min_liquidity_offset_history: HistoricalBucketRangeTracker,
+	max_liquidity_offset_history: HistoricalBucketRangeTracker,
+	offset_history_last_updated: Duration,
+}
+
+impl_writeable_tlv_based!(ExportedChannelHistory, {
+	(0, min_liquidity_offset_history, required),
+	(2, max_liquidity_offset_history, required),
+	(4, offset_history_last_updated, required),
+});
+
+/// The historical liquidity buckets of all channels, keyed by short channel id. A [`BTreeMap`] is
+/// used so that the same scorer state is always exported as the same bytes.
+struct ExportedHistory(BTreeMap<u64, ExportedChannelHistory>);
+
+impl Writeable for ExportedHistory {
+	fn write<W: Writer>(&self, w: &mut W) -> Result<(), io::Error> {
+		write_tlv_fields!(w, {
+			(0, self.0, required),
+		});
+		Ok(())
+	}
+}
+
+impl Readable for ExportedHistory {
+	fn read<R: Read>(r: &mut R) -> Result<Self, DecodeError> {
+		let mut history = BTreeMap::new();
+		read_tlv_fields!(r, {
+			(0, history, required),
+		});
+		Ok(ExportedHistory(history))
+	}
+}
+
 /// Parameters for configuring [`ProbabilisticScorer`].
 ///
 /// Used to configure base, liquidity, and amount penalties, the sum of which comprises the channel
@@ -1207,6 +1246,94 @@ where
 	pub fn scores(&self) -> &ChannelLiquidities {
 		&self.channel_liquidities
 	}
+
+	/// Exports the historical liquidity data of all channels we have datapoints for.
+	///
+	/// Unlike [`Self::scores`], only the historical liquidity buckets are included, which are
+	/// independent of our own position in the network and can thus be shared between nodes, e.g.,
+	/// to aggregate observations across a fleet of nodes or to bootstrap the scorer of a new node
+	/// via [`Self::merge_history`].
+	///
+	/// The returned bytes consist of the [`EXPORT_SCHEMA_VERSION`] followed by a TLV stream and are
+	/// kept stable across LDK releases as described in the [`export`] module documentation.
+	///
+	/// [`EXPORT_SCHEMA_VERSION`]: crate::util::export::EXPORT_SCHEMA_VERSION
+	/// [`export`]: crate::util::export
+	pub fn export_history(&self) -> Vec<u8> {
+		let mut history = BTreeMap::new();
+		for (scid, liquidity) in self.channel_liquidities.iter() {
+			if !liquidity.liquidity_history.has_datapoints() {
+				continue;
+			}
+			let channel_history = ExportedChannelHistory {
+				min_liquidity_offset_history: *liquidity
+					.liquidity_history
+					.writeable_min_offset_history(),
+				max_liquidity_offset_history: *liquidity
+					.liquidity_history
+					.writeable_max_offset_history(),
+				offset_history_last_updated: liquidity.offset_history_last_updated,
+			};
+			history.insert(*scid, channel_history);
+		}
+		versioned_bytes(&ExportedHistory(history))
+	}
+
+	/// Merges historical liquidity data previously exported via [`Self::export_history`] (possibly
+	/// by another node) into this scorer.
+	///
+	/// Imported data is first decayed to `duration_since_epoch` as configured by
+	/// [`ProbabilisticScoringDecayParameters::historical_no_updates_half_life`]. For channels we
+	/// already have historical data for, the buckets are then averaged with our own, otherwise the
+	/// imported buckets are used as-is. Our live liquidity bounds are left untouched.
+	///
+	/// Returns [`DecodeError::UnknownVersion`] if `history` was exported with an unsupported
+	/// schema version, in which case the scorer is not modified.
+	pub fn merge_history(
+		&mut self, history: &[u8], duration_since_epoch: Duration,
+	) -> Result<(), DecodeError> {
+		let mut reader = io::Cursor::new(history);
+		let schema_version: u16 = Readable::read(&mut reader)?;
+		if schema_version != EXPORT_SCHEMA_VERSION {
+			return Err(DecodeError::UnknownVersion);
+		}
+		let history: ExportedHistory = Readable::read(&mut reader)?;
+
+		let half_life = self.decay_params.historical_no_updates_half_life.as_secs_f64();
+		for (scid, channel_history) in history.0 {
+			let mut imported = HistoricalLiquidityTracker::from_min_max(
+				channel_history.min_liquidity_offset_history,
+				channel_history.max_liquidity_offset_history,
+			);
+			let elapsed_time =
+				duration_since_epoch.saturating_sub(channel_history.offset_history_last_updated);
+			if elapsed_time > self.decay_params.historical_no_updates_half_life && half_life != 0.0
+			{
+				imported.decay_buckets(elapsed_time.as_secs_f64() / half_life);
+			}
+			if !imported.has_datapoints() {
+				continue;
+			}
+
+			match self.channel_liquidities.entry(scid) {
+				Entry::Occupied(mut entry) => {
+					let liquidity = entry.get_mut();
+					if liquidity.liquidity_history.has_datapoints() {
+						liquidity.liquidity_history.merge(&imported);
+					} else {
+						liquidity.liquidity_history = imported;
+					}
+					liquidity.offset_history_last_updated = duration_since_epoch;
+				},
+				Entry::Vacant(entry) => {
+					let mut liquidity = ChannelLiquidity::new(duration_since_epoch);
+					liquidity.liquidity_history = imported;
+					entry.insert(liquidity);
+				},
+			}
+		}
+		Ok(())
+	}
 }
 
 impl ChannelLiquidity {
@@ -2637,7 +2764,8 @@ mod tests {
 
 	use crate::ln::channelmanager;
 	use crate::ln::msgs::{
-		ChannelAnnouncement, ChannelUpdate, UnsignedChannelAnnouncement, UnsignedChannelUpdate,
+		ChannelAnnouncement, ChannelUpdate, DecodeError, UnsignedChannelAnnouncement,
+		UnsignedChannelUpdate,
 	};
 	use crate::routing::gossip::{EffectiveCapacity, NetworkGraph, NodeId};
 	use crate::routing::router::{
@@ -4283,6 +4411,67 @@ mod tests {
 		assert_eq!(liquidity_range.unwrap(), (0, 0));
 	}
 
+	#[test]
+	fn exports_and_merges_history() {
+		let logger = TestLogger::new();
+		let network_graph = network_graph(&logger);
+		let decay_params = ProbabilisticScoringDecayParameters::default();
+		let target = target_node_id();
+
+		let mut scorer = ProbabilisticScorer::new(decay_params, &network_graph, &logger);
+		scorer.payment_path_failed(&payment_path_for_amount(600), 42, Duration::ZERO);
+		let buckets = scorer.historical_estimated_channel_liquidity_probabilities(42, &target);
+		assert!(buckets.is_some());
+
+		// Exporting the same state always yields the same bytes.
+		let exported = scorer.export_history();
+		assert_eq!(exported, scorer.export_history());
+		assert_eq!(exported[..2], [0, 1]);
+
+		// A fresh scorer takes the imported buckets as-is.
+		let mut new_scorer = ProbabilisticScorer::new(decay_params, &network_graph, &logger);
+		new_scorer.merge_history(&exported, Duration::ZERO).unwrap();
+		assert_eq!(
+			new_scorer.historical_estimated_channel_liquidity_probabilities(42, &target),
+			buckets
+		);
+
+		// A scorer with its own data averages the buckets.
+		let mut other_scorer = ProbabilisticScorer::new(decay_params, &network_graph, &logger);
+		other_scorer.payment_path_successful(&payment_path_for_amount(600), Duration::ZERO);
+		let other_buckets =
+			other_scorer.historical_estimated_channel_liquidity_probabilities(42, &target);
+		other_scorer.merge_history(&exported, Duration::ZERO).unwrap();
+		let merged_buckets =
+			other_scorer.historical_estimated_channel_liquidity_probabilities(42, &target);
+		assert_ne!(merged_buckets, buckets);
+		assert_ne!(merged_buckets, other_buckets);
+
+		// Data exported long ago is decayed before being merged.
+		let mut decayed_scorer = ProbabilisticScorer::new(decay_params, &network_graph, &logger);
+		let now = decay_params.historical_no_updates_half_life * 2;
+		decayed_scorer.merge_history(&exported, now).unwrap();
+		let (min_buckets, max_buckets) = decayed_scorer
+			.historical_estimated_channel_liquidity_probabilities(42, &target)
+			.unwrap();
+		let (orig_min_buckets, orig_max_buckets) = buckets.unwrap();
+		assert!(min_buckets.iter().sum::<u16>() < orig_min_buckets.iter().sum::<u16>());
+		assert!(max_buckets.iter().sum::<u16>() < orig_max_buckets.iter().sum::<u16>());
+
+		// Unknown schema versions are rejected without modifying the scorer.
+		let mut bad_version = exported.clone();
+		bad_version[1] = 2;
+		let mut rejecting_scorer = ProbabilisticScorer::new(decay_params, &network_graph, &logger);
+		assert_eq!(
+			rejecting_scorer.merge_history(&bad_version, Duration::ZERO),
+			Err(DecodeError::UnknownVersion)
+		);
+		assert_eq!(
+			rejecting_scorer.historical_estimated_channel_liquidity_probabilities(42, &target),
+			None
+		);
+	}
+
 	#[test]
 	#[rustfmt::skip]
 	fn probes_for_diversity() {
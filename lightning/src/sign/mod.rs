@@ -15,12 +15,20 @@
 use bitcoin::amount::Amount;
 use bitcoin::bip32::{ChildNumber, Xpriv, Xpub};
 use bitcoin::ecdsa::Signature as EcdsaSignature;
+#[cfg(taproot)]
+use bitcoin::key::TapTweak;
 use bitcoin::locktime::absolute::LockTime;
 use bitcoin::network::Network;
 use bitcoin::opcodes;
 use bitcoin::script::{Builder, Script, ScriptBuf};
 use bitcoin::sighash;
 use bitcoin::sighash::EcdsaSighashType;
+#[cfg(taproot)]
+use bitcoin::sighash::Prevouts;
+#[cfg(any(taproot, feature = "ptlc_experimental"))]
+use bitcoin::sighash::TapSighashType;
+#[cfg(taproot)]
+use bitcoin::taproot::{LeafVersion, TapLeafHash, TaprootSpendInfo};
 use bitcoin::transaction::Version;
 use bitcoin::transaction::{Transaction, TxIn, TxOut};
 
@@ -40,6 +48,10 @@ use lightning_invoice::RawBolt11Invoice;
 use crate::chain::transaction::OutPoint;
 use crate::crypto::utils::{hkdf_extract_expand_twice, sign, sign_with_aux_rand};
 use crate::ln::chan_utils;
+#[cfg(feature = "ptlc_experimental")]
+use crate::ln::chan_utils::PtlcOutputInCommitment;
+#[cfg(taproot)]
+use crate::ln::chan_utils::TxCreationKeys;
 use crate::ln::chan_utils::{
 	get_countersigner_payment_script, get_revokeable_redeemscript, make_funding_redeemscript,
 	ChannelPublicKeys, ChannelTransactionParameters, ClosingTransaction, CommitmentTransaction,
@@ -66,7 +78,14 @@ use crate::crypto::chacha20::ChaCha20;
 use crate::prelude::*;
 use crate::sign::ecdsa::EcdsaChannelSigner;
 #[cfg(taproot)]
+use crate::sign::musig::{KeyAggContext, SecretNonce, SigningSession};
+#[cfg(feature = "ptlc_experimental")]
+use crate::sign::ptlc::{self, AdaptorSignature, PtlcChannelSigner};
+#[cfg(taproot)]
 use crate::sign::taproot::TaprootChannelSigner;
+use crate::sign::tx_builder::{SpecTxBuilder, TxBuilder};
+#[cfg(taproot)]
+use crate::sync::Mutex;
 use crate::util::atomic_counter::AtomicCounter;
 
 use core::convert::TryInto;
@@ -80,6 +99,10 @@ pub(crate) mod type_resolver;
 
 pub mod ecdsa;
 #[cfg(taproot)]
+pub(crate) mod musig;
+#[cfg(feature = "ptlc_experimental")]
+pub mod ptlc;
+#[cfg(taproot)]
 pub mod taproot;
 pub mod tx_builder;
 
@@ -206,21 +229,24 @@ impl StaticPaymentOutputDescriptor {
 	/// shorter.
 	pub fn max_witness_length(&self) -> u64 {
 		if self.needs_csv_1_for_spend() {
-			let witness_script_weight = 1 /* pubkey push */
-				+ COMPRESSED_PUBLIC_KEY_SIZE
-				+ 1 /* OP_CHECKSIGVERIFY */
-				+ 1 /* OP_1 */
-				+ 1 /* OP_CHECKSEQUENCEVERIFY */;
-			(1 /* num witness items */
-				+ 1 /* sig push */
-				+ MAX_STANDARD_SIGNATURE_SIZE
-				+ 1 /* witness script push */
-				+ witness_script_weight) as u64
+			Self::CSV_1_MAX_WITNESS_LENGTH
 		} else {
 			P2WPKH_WITNESS_WEIGHT
 		}
 	}
 
+	/// The maximum length a well-formed witness spending one of these should have when it
+	/// originated from an anchor outputs channel, i.e. when [`Self::needs_csv_1_for_spend`].
+	pub(crate) const CSV_1_MAX_WITNESS_LENGTH: u64 = (1 /* num witness items */
+		+ 1 /* sig push */
+		+ MAX_STANDARD_SIGNATURE_SIZE
+		+ 1 /* witness script push */
+		+ 1 /* OP_CHECKSIGVERIFY */
+		+ 1 /* OP_1 */
+		+ 1 /* OP_CHECKSEQUENCEVERIFY */
+		+ 1 /* pubkey push */
+		+ COMPRESSED_PUBLIC_KEY_SIZE) as u64;
+
 	/// Returns true if spending this output requires a transaction with a CheckSequenceVerify
 	/// value of at least 1.
 	pub fn needs_csv_1_for_spend(&self) -> bool {
@@ -980,6 +1006,18 @@ pub trait NodeSigner {
 	/// corresponding channel.
 	fn sign_gossip_message(&self, msg: UnsignedGossipMessage) -> Result<Signature, ()>;
 
+	/// Sign a batch of gossip messages, returning the signatures in the same order as `msgs`.
+	///
+	/// This is used when many gossip messages need to be (re-)signed at once, e.g. when updating
+	/// the forwarding policy of many channels, allowing signers which are expensive to reach
+	/// (e.g. remote HSMs) to handle them in a single round trip. By default, this simply calls
+	/// [`NodeSigner::sign_gossip_message`] for each message.
+	///
+	/// The same failure semantics as for [`NodeSigner::sign_gossip_message`] apply.
+	fn sign_gossip_messages(&self, msgs: &[UnsignedGossipMessage]) -> Result<Vec<Signature>, ()> {
+		msgs.iter().map(|msg| self.sign_gossip_message(*msg)).collect()
+	}
+
 	/// Sign an arbitrary message with the node's secret key.
 	///
 	/// Creates a digital signature of a message given the node's secret. The message is prefixed
@@ -1074,6 +1112,19 @@ pub trait SignerProvider {
 	/// This method should return a different value each time it is called, to avoid linking
 	/// on-chain funds across channels as controlled to the same user.
 	fn get_shutdown_scriptpubkey(&self) -> Result<ShutdownScript, ()>;
+
+	/// Gets the [`TxBuilder`] which will be used to build the commitment transactions of the
+	/// channel with the given `channel_keys_id`.
+	///
+	/// This is called both when a channel is opened and each time it is read from disk, so the
+	/// same builder must be returned for a given `channel_keys_id` across restarts.
+	///
+	/// By default, this returns a [`SpecTxBuilder`], which builds commitment transactions as
+	/// specified in BOLT 3. Custom builders are only useful for experimenting with alternative
+	/// commitment transaction layouts, and must match whatever the counterparty builds.
+	fn get_tx_builder(&self, _channel_keys_id: [u8; 32]) -> Box<dyn TxBuilder + Send + Sync> {
+		Box::new(SpecTxBuilder {})
+	}
 }
 
 /// A helper trait that describes an on-chain wallet capable of returning a (change) destination
@@ -1219,6 +1270,28 @@ pub struct InMemorySigner {
 	channel_keys_id: [u8; 32],
 	/// A source of random bytes.
 	entropy_source: RandomBytes,
+	/// The MuSig2 nonces handed out for our commitment transactions, by commitment number.
+	///
+	/// These are never persisted nor cloned, so that a secret nonce can only be used once.
+	#[cfg(taproot)]
+	holder_commitment_nonces: Mutex<HashMap<u64, HolderCommitmentNonce>>,
+}
+
+/// The state of a MuSig2 nonce handed out via
+/// [`TaprootChannelSigner::generate_local_nonce_pair`] for one of our commitment transactions.
+#[cfg(taproot)]
+enum HolderCommitmentNonce {
+	/// The nonce has yet to be used to sign.
+	Unused(SecretNonce),
+	/// The nonce has been used to sign the given sighash with the counterparty's nonce. Only the
+	/// same partial signature may be handed out again, as signing anything else would reuse the
+	/// nonce.
+	Used {
+		public_nonce: PublicNonce,
+		sighash: secp256k1::Message,
+		counterparty_nonce: PublicNonce,
+		partial_signature: PartialSignature,
+	},
 }
 
 impl PartialEq for InMemorySigner {
@@ -1248,6 +1321,8 @@ impl Clone for InMemorySigner {
 			commitment_seed: self.commitment_seed.clone(),
 			channel_keys_id: self.channel_keys_id,
 			entropy_source: RandomBytes::new(self.get_secure_random_bytes()),
+			#[cfg(taproot)]
+			holder_commitment_nonces: Mutex::new(new_hash_map()),
 		}
 	}
 }
@@ -1271,6 +1346,8 @@ impl InMemorySigner {
 			commitment_seed,
 			channel_keys_id,
 			entropy_source: RandomBytes::new(rand_bytes_unique_start),
+			#[cfg(taproot)]
+			holder_commitment_nonces: Mutex::new(new_hash_map()),
 		}
 	}
 
@@ -1292,6 +1369,8 @@ impl InMemorySigner {
 			commitment_seed,
 			channel_keys_id,
 			entropy_source: RandomBytes::new(rand_bytes_unique_start),
+			#[cfg(taproot)]
+			holder_commitment_nonces: Mutex::new(new_hash_map()),
 		}
 	}
 
@@ -1888,62 +1967,561 @@ impl EcdsaChannelSigner for InMemorySigner {
 	}
 }
 
+#[cfg(feature = "ptlc_experimental")]
+impl InMemorySigner {
+	/// Returns our HTLC private key along with the PTLC output being spent and the tapscript leaf
+	/// to sign for.
+	fn ptlc_signing_data(
+		&self, channel_parameters: &ChannelTransactionParameters, holder_commitment: bool,
+		per_commitment_point: &PublicKey, ptlc: &PtlcOutputInCommitment,
+		secp_ctx: &Secp256k1<secp256k1::All>,
+	) -> (SecretKey, TxOut, ScriptBuf) {
+		assert!(channel_parameters.is_populated(), "Channel parameters must be fully populated");
+
+		let counterparty_keys =
+			channel_parameters.counterparty_pubkeys().expect(MISSING_PARAMS_ERR);
+		let holder_htlc_key = HtlcKey::from_basepoint(
+			&secp_ctx,
+			&channel_parameters.holder_pubkeys.htlc_basepoint,
+			&per_commitment_point,
+		);
+		let counterparty_htlc_key = HtlcKey::from_basepoint(
+			&secp_ctx,
+			&counterparty_keys.htlc_basepoint,
+			&per_commitment_point,
+		);
+		let (broadcaster_htlc_key, countersignatory_htlc_key, revocation_basepoint) =
+			if holder_commitment {
+				(holder_htlc_key, counterparty_htlc_key, &counterparty_keys.revocation_basepoint)
+			} else {
+				(
+					counterparty_htlc_key,
+					holder_htlc_key,
+					&channel_parameters.holder_pubkeys.revocation_basepoint,
+				)
+			};
+		let revocation_key =
+			RevocationKey::from_basepoint(&secp_ctx, revocation_basepoint, &per_commitment_point);
+		let spend_info = chan_utils::get_ptlc_taproot_spend_info(
+			secp_ctx,
+			&broadcaster_htlc_key,
+			&countersignatory_htlc_key,
+			&revocation_key,
+		);
+		let prevout = TxOut {
+			value: ptlc.to_bitcoin_amount(),
+			script_pubkey: ScriptBuf::new_p2tr_tweaked(spend_info.output_key()),
+		};
+		let tapscript =
+			chan_utils::get_ptlc_tapscript(&broadcaster_htlc_key, &countersignatory_htlc_key);
+		let htlc_key =
+			chan_utils::derive_private_key(&secp_ctx, &per_commitment_point, &self.htlc_base_key);
+		(htlc_key, prevout, tapscript)
+	}
+}
+
+#[cfg(feature = "ptlc_experimental")]
+impl PtlcChannelSigner for InMemorySigner {
+	fn sign_counterparty_ptlc_timeout_transaction(
+		&self, channel_parameters: &ChannelTransactionParameters, ptlc_tx: &Transaction,
+		input: usize, per_commitment_point: &PublicKey, ptlc: &PtlcOutputInCommitment,
+		secp_ctx: &Secp256k1<secp256k1::All>,
+	) -> Result<schnorr::Signature, ()> {
+		if !ptlc.offered {
+			return Err(());
+		}
+		let (htlc_key, prevout, tapscript) =
+			self.ptlc_signing_data(channel_parameters, false, per_commitment_point, ptlc, secp_ctx);
+		let sighash_type = chan_utils::ptlc_countersignatory_sighash_type(
+			&channel_parameters.channel_type_features,
+		);
+		let sighash =
+			ptlc::ptlc_tapscript_sighash(ptlc_tx, input, &prevout, &tapscript, sighash_type)?;
+		let keypair = Keypair::from_secret_key(secp_ctx, &htlc_key);
+		Ok(secp_ctx.sign_schnorr_with_aux_rand(&sighash, &keypair, &self.get_secure_random_bytes()))
+	}
+
+	fn adaptor_sign_counterparty_ptlc_success_transaction(
+		&self, channel_parameters: &ChannelTransactionParameters, ptlc_tx: &Transaction,
+		input: usize, per_commitment_point: &PublicKey, ptlc: &PtlcOutputInCommitment,
+		secp_ctx: &Secp256k1<secp256k1::All>,
+	) -> Result<AdaptorSignature, ()> {
+		if ptlc.offered {
+			return Err(());
+		}
+		let (htlc_key, prevout, tapscript) =
+			self.ptlc_signing_data(channel_parameters, false, per_commitment_point, ptlc, secp_ctx);
+		let sighash_type = chan_utils::ptlc_countersignatory_sighash_type(
+			&channel_parameters.channel_type_features,
+		);
+		let sighash =
+			ptlc::ptlc_tapscript_sighash(ptlc_tx, input, &prevout, &tapscript, sighash_type)?;
+		AdaptorSignature::sign(
+			secp_ctx,
+			&sighash,
+			&htlc_key,
+			&ptlc.payment_point,
+			&self.get_secure_random_bytes(),
+		)
+	}
+
+	fn sign_holder_ptlc_transaction(
+		&self, channel_parameters: &ChannelTransactionParameters, ptlc_tx: &Transaction,
+		input: usize, per_commitment_point: &PublicKey, ptlc: &PtlcOutputInCommitment,
+		secp_ctx: &Secp256k1<secp256k1::All>,
+	) -> Result<schnorr::Signature, ()> {
+		let (htlc_key, prevout, tapscript) =
+			self.ptlc_signing_data(channel_parameters, true, per_commitment_point, ptlc, secp_ctx);
+		let sighash = ptlc::ptlc_tapscript_sighash(
+			ptlc_tx,
+			input,
+			&prevout,
+			&tapscript,
+			TapSighashType::Default,
+		)?;
+		let keypair = Keypair::from_secret_key(secp_ctx, &htlc_key);
+		Ok(secp_ctx.sign_schnorr_with_aux_rand(&sighash, &keypair, &self.get_secure_random_bytes()))
+	}
+}
+
+/// Computes the BIP 341 sighash of the input at index `input` of `tx`, spending `prevout` either
+/// via the key path or, if `tapscript` is set, via the given script leaf.
+///
+/// Unless `sighash_type` has the `ANYONECANPAY` flag set, the signature commits to the values of
+/// all inputs, so `tx` must not spend anything but `prevout`.
+#[cfg(taproot)]
+fn taproot_sighash(
+	tx: &Transaction, input: usize, prevout: &TxOut, tapscript: Option<&Script>,
+	sighash_type: TapSighashType,
+) -> Result<secp256k1::Message, ()> {
+	let all_prevouts = [prevout];
+	let prevouts = match sighash_type {
+		TapSighashType::AllPlusAnyoneCanPay
+		| TapSighashType::NonePlusAnyoneCanPay
+		| TapSighashType::SinglePlusAnyoneCanPay => Prevouts::One(input, prevout),
+		_ if tx.input.len() == 1 => Prevouts::All(&all_prevouts),
+		_ => return Err(()),
+	};
+	let mut sighash_cache = sighash::SighashCache::new(tx);
+	let sighash = match tapscript {
+		Some(tapscript) => {
+			let leaf_hash = TapLeafHash::from_script(tapscript, LeafVersion::TapScript);
+			sighash_cache.taproot_script_spend_signature_hash(
+				input,
+				&prevouts,
+				leaf_hash,
+				sighash_type,
+			)
+		},
+		None => sighash_cache.taproot_key_spend_signature_hash(input, &prevouts, sighash_type),
+	}
+	.map_err(|_| ())?;
+	Ok(hash_to_message!(sighash.as_byte_array()))
+}
+
+#[cfg(taproot)]
+impl InMemorySigner {
+	/// Returns the MuSig2 key aggregation context of the channel's funding output, along with our
+	/// funding key and the funding output itself.
+	fn taproot_funding_signing_data(
+		&self, channel_parameters: &ChannelTransactionParameters,
+		secp_ctx: &Secp256k1<secp256k1::All>,
+	) -> Result<(KeyAggContext, SecretKey, TxOut), ()> {
+		assert!(channel_parameters.is_populated(), "Channel parameters must be fully populated");
+
+		let funding_key = self.funding_key(channel_parameters.splice_parent_funding_txid);
+		let counterparty_keys =
+			channel_parameters.counterparty_pubkeys().expect(MISSING_PARAMS_ERR);
+		let key_agg_ctx = KeyAggContext::new(
+			secp_ctx,
+			&[funding_key.public_key(secp_ctx), counterparty_keys.funding_pubkey],
+		)?;
+		let funding_output = TxOut {
+			value: Amount::from_sat(channel_parameters.channel_value_satoshis),
+			script_pubkey: key_agg_ctx.script_pubkey(),
+		};
+		Ok((key_agg_ctx, funding_key, funding_output))
+	}
+
+	/// Generates a fresh, random secret nonce.
+	fn generate_secret_nonce(&self) -> SecretNonce {
+		loop {
+			let (k1, k2) = (self.get_secure_random_bytes(), self.get_secure_random_bytes());
+			if let Ok(secret_nonce) = SecretNonce::from_random_bytes(k1, k2) {
+				return secret_nonce;
+			}
+		}
+	}
+
+	/// Creates our MuSig2 partial signature for `tx`, which spends the funding output, using a
+	/// fresh nonce.
+	fn partially_sign_funding_spend(
+		&self, channel_parameters: &ChannelTransactionParameters, counterparty_nonce: PublicNonce,
+		tx: &Transaction, secp_ctx: &Secp256k1<secp256k1::All>,
+	) -> Result<PartialSignatureWithNonce, ()> {
+		let (key_agg_ctx, funding_key, funding_output) =
+			self.taproot_funding_signing_data(channel_parameters, secp_ctx)?;
+		let sighash = taproot_sighash(tx, 0, &funding_output, None, TapSighashType::Default)?;
+		let secret_nonce = self.generate_secret_nonce();
+		let public_nonce = secret_nonce.public_nonce(secp_ctx);
+		let nonces = [&public_nonce, &counterparty_nonce];
+		let session = SigningSession::new(secp_ctx, &key_agg_ctx, &nonces, &sighash)?;
+		let partial_sig = session.partial_sign(secp_ctx, secret_nonce, &funding_key)?;
+		Ok(PartialSignatureWithNonce(partial_sig, public_nonce))
+	}
+
+	/// Returns the output of `htlc` in a taproot commitment transaction built with `keys`, along
+	/// with its [`TaprootSpendInfo`].
+	fn taproot_htlc_output(
+		&self, keys: &TxCreationKeys, htlc: &HTLCOutputInCommitment,
+		secp_ctx: &Secp256k1<secp256k1::All>,
+	) -> (TxOut, TaprootSpendInfo) {
+		let spend_info = chan_utils::get_taproot_htlc_spend_info(
+			secp_ctx,
+			htlc,
+			&keys.broadcaster_htlc_key,
+			&keys.countersignatory_htlc_key,
+			&keys.revocation_key,
+		);
+		let output = TxOut {
+			value: htlc.to_bitcoin_amount(),
+			script_pubkey: ScriptBuf::new_p2tr_tweaked(spend_info.output_key()),
+		};
+		(output, spend_info)
+	}
+
+	/// Returns the tapscript leaf of `htlc` which is spent by its second-stage HTLC transaction and
+	/// thus requires both parties' signatures.
+	fn taproot_second_stage_htlc_script(
+		&self, keys: &TxCreationKeys, htlc: &HTLCOutputInCommitment,
+	) -> ScriptBuf {
+		let (success_script, timeout_script) = chan_utils::get_taproot_htlc_scripts(
+			htlc,
+			&keys.broadcaster_htlc_key,
+			&keys.countersignatory_htlc_key,
+		);
+		if htlc.offered {
+			timeout_script
+		} else {
+			success_script
+		}
+	}
+}
+
 #[cfg(taproot)]
-#[allow(unused)]
 impl TaprootChannelSigner for InMemorySigner {
 	fn generate_local_nonce_pair(
 		&self, commitment_number: u64, secp_ctx: &Secp256k1<All>,
 	) -> PublicNonce {
-		todo!()
+		let mut nonces = self.holder_commitment_nonces.lock().unwrap();
+		// Commitment numbers count down, so we can forget about the nonces of commitment
+		// transactions older than the current one.
+		nonces.retain(|number, _| *number <= commitment_number.saturating_add(1));
+		let nonce = nonces
+			.entry(commitment_number)
+			.or_insert_with(|| HolderCommitmentNonce::Unused(self.generate_secret_nonce()));
+		match nonce {
+			HolderCommitmentNonce::Unused(secret_nonce) => secret_nonce.public_nonce(secp_ctx),
+			HolderCommitmentNonce::Used { public_nonce, .. } => public_nonce.clone(),
+		}
 	}
 
 	fn partially_sign_counterparty_commitment(
-		&self, counterparty_nonce: PublicNonce, commitment_tx: &CommitmentTransaction,
-		inbound_htlc_preimages: Vec<PaymentPreimage>,
-		outbound_htlc_preimages: Vec<PaymentPreimage>, secp_ctx: &Secp256k1<All>,
+		&self, channel_parameters: &ChannelTransactionParameters, counterparty_nonce: PublicNonce,
+		commitment_tx: &CommitmentTransaction, _inbound_htlc_preimages: Vec<PaymentPreimage>,
+		_outbound_htlc_preimages: Vec<PaymentPreimage>, secp_ctx: &Secp256k1<All>,
 	) -> Result<(PartialSignatureWithNonce, Vec<schnorr::Signature>), ()> {
-		todo!()
+		let trusted_tx = commitment_tx.trust();
+		let keys = trusted_tx.keys();
+		let built_tx = trusted_tx.built_transaction();
+		let commitment_sig = self.partially_sign_funding_spend(
+			channel_parameters,
+			counterparty_nonce,
+			&built_tx.transaction,
+			secp_ctx,
+		)?;
+
+		let chan_type = &channel_parameters.channel_type_features;
+		let htlc_sighash_type = if chan_type.supports_anchors_zero_fee_htlc_tx()
+			|| chan_type.supports_anchor_zero_fee_commitments()
+		{
+			TapSighashType::SinglePlusAnyoneCanPay
+		} else {
+			TapSighashType::Default
+		};
+		let holder_htlc_key = chan_utils::derive_private_key(
+			&secp_ctx,
+			&keys.per_commitment_point,
+			&self.htlc_base_key,
+		);
+		let holder_htlc_keypair = Keypair::from_secret_key(secp_ctx, &holder_htlc_key);
+
+		let mut htlc_sigs = Vec::with_capacity(commitment_tx.nondust_htlcs().len());
+		for htlc in commitment_tx.nondust_htlcs() {
+			let htlc_tx = chan_utils::build_htlc_transaction(
+				&built_tx.txid,
+				commitment_tx.negotiated_feerate_per_kw(),
+				channel_parameters.holder_selected_contest_delay,
+				htlc,
+				chan_type,
+				&keys.broadcaster_delayed_payment_key,
+				&keys.revocation_key,
+			);
+			let (prevout, _) = self.taproot_htlc_output(keys, htlc, secp_ctx);
+			let tapscript = self.taproot_second_stage_htlc_script(keys, htlc);
+			let sighash =
+				taproot_sighash(&htlc_tx, 0, &prevout, Some(&tapscript), htlc_sighash_type)?;
+			let aux_rand = self.get_secure_random_bytes();
+			htlc_sigs.push(secp_ctx.sign_schnorr_with_aux_rand(
+				&sighash,
+				&holder_htlc_keypair,
+				&aux_rand,
+			));
+		}
+
+		Ok((commitment_sig, htlc_sigs))
 	}
 
 	fn finalize_holder_commitment(
-		&self, commitment_tx: &HolderCommitmentTransaction,
+		&self, channel_parameters: &ChannelTransactionParameters,
+		commitment_tx: &HolderCommitmentTransaction,
 		counterparty_partial_signature: PartialSignatureWithNonce, secp_ctx: &Secp256k1<All>,
 	) -> Result<PartialSignature, ()> {
-		todo!()
+		let (key_agg_ctx, funding_key, funding_output) =
+			self.taproot_funding_signing_data(channel_parameters, secp_ctx)?;
+		let counterparty_keys =
+			channel_parameters.counterparty_pubkeys().expect(MISSING_PARAMS_ERR);
+		let trusted_tx = commitment_tx.trust();
+		let sighash = taproot_sighash(
+			&trusted_tx.built_transaction().transaction,
+			0,
+			&funding_output,
+			None,
+			TapSighashType::Default,
+		)?;
+
+		let PartialSignatureWithNonce(counterparty_sig, counterparty_nonce) =
+			counterparty_partial_signature;
+		let commitment_number = commitment_tx.commitment_number();
+		let mut holder_nonces = self.holder_commitment_nonces.lock().unwrap();
+		let public_nonce = match holder_nonces.get(&commitment_number) {
+			Some(HolderCommitmentNonce::Unused(secret_nonce)) => {
+				secret_nonce.public_nonce(secp_ctx)
+			},
+			Some(HolderCommitmentNonce::Used { public_nonce, .. }) => public_nonce.clone(),
+			None => return Err(()),
+		};
+		let nonces = [&public_nonce, &counterparty_nonce];
+		let session = SigningSession::new(secp_ctx, &key_agg_ctx, &nonces, &sighash)?;
+		session.verify_partial_signature(
+			secp_ctx,
+			&counterparty_sig,
+			&counterparty_nonce,
+			&counterparty_keys.funding_pubkey,
+		)?;
+
+		match holder_nonces.remove(&commitment_number) {
+			Some(HolderCommitmentNonce::Unused(secret_nonce)) => {
+				let partial_signature =
+					session.partial_sign(secp_ctx, secret_nonce, &funding_key)?;
+				let used_nonce = HolderCommitmentNonce::Used {
+					public_nonce,
+					sighash,
+					counterparty_nonce,
+					partial_signature: partial_signature.clone(),
+				};
+				holder_nonces.insert(commitment_number, used_nonce);
+				Ok(partial_signature)
+			},
+			Some(used_nonce) => {
+				// We may only hand out the signature we already made, as signing a different
+				// message, or with a different counterparty nonce, would leak our funding key.
+				let res = match &used_nonce {
+					HolderCommitmentNonce::Used {
+						sighash: signed_sighash,
+						counterparty_nonce: signed_counterparty_nonce,
+						partial_signature,
+						..
+					} if *signed_sighash == sighash
+						&& *signed_counterparty_nonce == counterparty_nonce =>
+					{
+						Ok(partial_signature.clone())
+					},
+					_ => Err(()),
+				};
+				holder_nonces.insert(commitment_number, used_nonce);
+				res
+			},
+			None => Err(()),
+		}
 	}
 
 	fn sign_justice_revoked_output(
-		&self, justice_tx: &Transaction, input: usize, amount: u64, per_commitment_key: &SecretKey,
-		secp_ctx: &Secp256k1<All>,
+		&self, channel_parameters: &ChannelTransactionParameters, justice_tx: &Transaction,
+		input: usize, amount: u64, per_commitment_key: &SecretKey, secp_ctx: &Secp256k1<All>,
 	) -> Result<schnorr::Signature, ()> {
-		todo!()
+		assert!(channel_parameters.is_populated(), "Channel parameters must be fully populated");
+
+		let revocation_key = chan_utils::derive_private_revocation_key(
+			&secp_ctx,
+			&per_commitment_key,
+			&self.revocation_base_key,
+		);
+		let per_commitment_point = PublicKey::from_secret_key(secp_ctx, &per_commitment_key);
+		let revocation_pubkey = RevocationKey::from_basepoint(
+			&secp_ctx,
+			&channel_parameters.holder_pubkeys.revocation_basepoint,
+			&per_commitment_point,
+		);
+		let counterparty_keys =
+			channel_parameters.counterparty_pubkeys().expect(MISSING_PARAMS_ERR);
+		let counterparty_delayedpubkey = DelayedPaymentKey::from_basepoint(
+			&secp_ctx,
+			&counterparty_keys.delayed_payment_basepoint,
+			&per_commitment_point,
+		);
+		let contest_delay = channel_parameters.holder_selected_contest_delay;
+		let spend_info = chan_utils::get_taproot_to_local_spend_info(
+			secp_ctx,
+			&revocation_pubkey,
+			contest_delay,
+			&counterparty_delayedpubkey,
+		);
+		let (_, revocation_script) = chan_utils::get_taproot_to_local_scripts(
+			&revocation_pubkey,
+			contest_delay,
+			&counterparty_delayedpubkey,
+		);
+		let prevout = TxOut {
+			value: Amount::from_sat(amount),
+			script_pubkey: ScriptBuf::new_p2tr_tweaked(spend_info.output_key()),
+		};
+		let sighash = taproot_sighash(
+			justice_tx,
+			input,
+			&prevout,
+			Some(&revocation_script),
+			TapSighashType::AllPlusAnyoneCanPay,
+		)?;
+		let keypair = Keypair::from_secret_key(secp_ctx, &revocation_key);
+		Ok(secp_ctx.sign_schnorr_with_aux_rand(&sighash, &keypair, &self.get_secure_random_bytes()))
 	}
 
 	fn sign_justice_revoked_htlc(
-		&self, justice_tx: &Transaction, input: usize, amount: u64, per_commitment_key: &SecretKey,
-		htlc: &HTLCOutputInCommitment, secp_ctx: &Secp256k1<All>,
+		&self, channel_parameters: &ChannelTransactionParameters, justice_tx: &Transaction,
+		input: usize, amount: u64, per_commitment_key: &SecretKey, htlc: &HTLCOutputInCommitment,
+		secp_ctx: &Secp256k1<All>,
 	) -> Result<schnorr::Signature, ()> {
-		todo!()
+		assert!(channel_parameters.is_populated(), "Channel parameters must be fully populated");
+
+		let revocation_key = chan_utils::derive_private_revocation_key(
+			&secp_ctx,
+			&per_commitment_key,
+			&self.revocation_base_key,
+		);
+		let per_commitment_point = PublicKey::from_secret_key(secp_ctx, &per_commitment_key);
+		let counterparty_keys =
+			channel_parameters.counterparty_pubkeys().expect(MISSING_PARAMS_ERR);
+		let keys = TxCreationKeys::from_channel_static_keys(
+			&per_commitment_point,
+			counterparty_keys,
+			&channel_parameters.holder_pubkeys,
+			secp_ctx,
+		);
+		let (mut prevout, spend_info) = self.taproot_htlc_output(&keys, htlc, secp_ctx);
+		prevout.value = Amount::from_sat(amount);
+		// The revocation key is the HTLC output's internal key, so we spend it via the key path.
+		let sighash = taproot_sighash(
+			justice_tx,
+			input,
+			&prevout,
+			None,
+			TapSighashType::AllPlusAnyoneCanPay,
+		)?;
+		let keypair = Keypair::from_secret_key(secp_ctx, &revocation_key)
+			.tap_tweak(secp_ctx, spend_info.merkle_root())
+			.to_inner();
+		Ok(secp_ctx.sign_schnorr_with_aux_rand(&sighash, &keypair, &self.get_secure_random_bytes()))
 	}
 
 	fn sign_holder_htlc_transaction(
 		&self, htlc_tx: &Transaction, input: usize, htlc_descriptor: &HTLCDescriptor,
 		secp_ctx: &Secp256k1<All>,
 	) -> Result<schnorr::Signature, ()> {
-		todo!()
+		let channel_parameters =
+			&htlc_descriptor.channel_derivation_parameters.transaction_parameters;
+		assert!(channel_parameters.is_populated(), "Channel parameters must be fully populated");
+
+		let counterparty_keys =
+			channel_parameters.counterparty_pubkeys().expect(MISSING_PARAMS_ERR);
+		let keys = TxCreationKeys::from_channel_static_keys(
+			&htlc_descriptor.per_commitment_point,
+			&channel_parameters.holder_pubkeys,
+			counterparty_keys,
+			secp_ctx,
+		);
+		let htlc = &htlc_descriptor.htlc;
+		let (prevout, _) = self.taproot_htlc_output(&keys, htlc, secp_ctx);
+		let tapscript = self.taproot_second_stage_htlc_script(&keys, htlc);
+		let sighash =
+			taproot_sighash(htlc_tx, input, &prevout, Some(&tapscript), TapSighashType::Default)?;
+		let our_htlc_private_key = chan_utils::derive_private_key(
+			&secp_ctx,
+			&htlc_descriptor.per_commitment_point,
+			&self.htlc_base_key,
+		);
+		let keypair = Keypair::from_secret_key(secp_ctx, &our_htlc_private_key);
+		Ok(secp_ctx.sign_schnorr_with_aux_rand(&sighash, &keypair, &self.get_secure_random_bytes()))
 	}
 
 	fn sign_counterparty_htlc_transaction(
-		&self, htlc_tx: &Transaction, input: usize, amount: u64, per_commitment_point: &PublicKey,
-		htlc: &HTLCOutputInCommitment, secp_ctx: &Secp256k1<All>,
+		&self, channel_parameters: &ChannelTransactionParameters, htlc_tx: &Transaction,
+		input: usize, amount: u64, per_commitment_point: &PublicKey, htlc: &HTLCOutputInCommitment,
+		secp_ctx: &Secp256k1<All>,
 	) -> Result<schnorr::Signature, ()> {
-		todo!()
+		assert!(channel_parameters.is_populated(), "Channel parameters must be fully populated");
+
+		let counterparty_keys =
+			channel_parameters.counterparty_pubkeys().expect(MISSING_PARAMS_ERR);
+		let keys = TxCreationKeys::from_channel_static_keys(
+			per_commitment_point,
+			counterparty_keys,
+			&channel_parameters.holder_pubkeys,
+			secp_ctx,
+		);
+		let (mut prevout, _) = self.taproot_htlc_output(&keys, htlc, secp_ctx);
+		prevout.value = Amount::from_sat(amount);
+		// We claim HTLCs offered by our counterparty with the preimage, and those they received
+		// once they time out, via the leaf only requiring our signature.
+		let (success_script, timeout_script) = chan_utils::get_taproot_htlc_scripts(
+			htlc,
+			&keys.broadcaster_htlc_key,
+			&keys.countersignatory_htlc_key,
+		);
+		let tapscript = if htlc.offered { success_script } else { timeout_script };
+		let sighash = taproot_sighash(
+			htlc_tx,
+			input,
+			&prevout,
+			Some(&tapscript),
+			TapSighashType::AllPlusAnyoneCanPay,
+		)?;
+		let htlc_key =
+			chan_utils::derive_private_key(&secp_ctx, &per_commitment_point, &self.htlc_base_key);
+		let keypair = Keypair::from_secret_key(secp_ctx, &htlc_key);
+		Ok(secp_ctx.sign_schnorr_with_aux_rand(&sighash, &keypair, &self.get_secure_random_bytes()))
 	}
 
 	fn partially_sign_closing_transaction(
-		&self, closing_tx: &ClosingTransaction, secp_ctx: &Secp256k1<All>,
-	) -> Result<PartialSignature, ()> {
-		todo!()
+		&self, channel_parameters: &ChannelTransactionParameters, counterparty_nonce: PublicNonce,
+		closing_tx: &ClosingTransaction, secp_ctx: &Secp256k1<All>,
+	) -> Result<PartialSignatureWithNonce, ()> {
+		self.partially_sign_funding_spend(
+			channel_parameters,
+			counterparty_nonce,
+			closing_tx.trust().built_transaction(),
+			secp_ctx,
+		)
 	}
 }
 
@@ -0,0 +1,377 @@
+// This file is Copyright its original authors, visible in version control
+// history.
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! A minimal implementation of the two-round MuSig2 multi-signature scheme as specified in
+//! [BIP 327], used to sign for the funding output of taproot channels.
+//!
+//! The funding output of a taproot channel is a key-path-only P2TR output whose internal key is
+//! the MuSig2 aggregate of both parties' funding keys, tweaked as per [BIP 86]. Only this
+//! configuration is supported here.
+//!
+//! [BIP 327]: https://github.com/bitcoin/bips/blob/master/bip-0327.mediawiki
+//! [BIP 86]: https://github.com/bitcoin/bips/blob/master/bip-0086.mediawiki
+
+use bitcoin::hashes::sha256::Hash as Sha256;
+use bitcoin::hashes::{Hash, HashEngine};
+use bitcoin::key::{Parity, TweakedPublicKey};
+use bitcoin::script::ScriptBuf;
+use bitcoin::secp256k1::{
+	Message, PublicKey, Scalar, Secp256k1, SecretKey, Signing, Verification, XOnlyPublicKey,
+};
+use bitcoin::taproot::TapTweakHash;
+
+use musig2::types::{PartialSignature, PublicNonce};
+
+use crate::prelude::*;
+
+/// Computes a BIP 340 tagged hash of `data` with the given `tag`.
+fn tagged_hash(tag: &[u8], data: &[&[u8]]) -> [u8; 32] {
+	let tag_hash = Sha256::hash(tag);
+	let mut engine = Sha256::engine();
+	engine.input(tag_hash.as_byte_array());
+	engine.input(tag_hash.as_byte_array());
+	for d in data {
+		engine.input(d);
+	}
+	Sha256::from_engine(engine).to_byte_array()
+}
+
+/// Interprets a hash as a scalar. BIP 327 reduces hashes modulo the curve order, but the chance
+/// of one overflowing is negligible so we simply fail instead.
+fn hash_to_scalar(hash: [u8; 32]) -> Result<Scalar, ()> {
+	Scalar::from_be_bytes(hash).map_err(|_| ())
+}
+
+fn scalar_mul(a: &Scalar, b: &Scalar) -> Result<Scalar, ()> {
+	let a = SecretKey::from_slice(&a.to_be_bytes()).map_err(|_| ())?;
+	a.mul_tweak(b).map(Scalar::from).map_err(|_| ())
+}
+
+/// Splits a [`PublicNonce`] into the two points it consists of.
+fn parse_public_nonce(nonce: &PublicNonce) -> Result<(PublicKey, PublicKey), ()> {
+	let bytes = nonce.serialize();
+	let r1 = PublicKey::from_slice(&bytes[..33]).map_err(|_| ())?;
+	let r2 = PublicKey::from_slice(&bytes[33..]).map_err(|_| ())?;
+	Ok((r1, r2))
+}
+
+fn serialize_nonce_points(r1: &PublicKey, r2: &PublicKey) -> [u8; 66] {
+	let mut res = [0; 66];
+	res[..33].copy_from_slice(&r1.serialize());
+	res[33..].copy_from_slice(&r2.serialize());
+	res
+}
+
+/// The result of aggregating the participants' funding keys and applying the BIP 86 taproot
+/// tweak to the aggregate key.
+pub(crate) struct KeyAggContext {
+	/// The participants' keys, sorted as per BIP 327's `KeySort`.
+	pubkeys: Vec<PublicKey>,
+	/// The hash of all participants' keys, committed to in each key's aggregation coefficient.
+	keys_hash: [u8; 32],
+	/// The output key, i.e. the tweaked aggregate key.
+	output_key: PublicKey,
+	/// Whether the untweaked aggregate key had to be negated before tweaking it, i.e. whether
+	/// BIP 327's `gacc` is `-1`.
+	negated_before_tweak: bool,
+	/// The BIP 86 tweak applied to the aggregate key, i.e. BIP 327's `tacc`.
+	#[cfg(test)]
+	tweak: Scalar,
+}
+
+/// Returns the aggregation coefficient of `pubkey`, which must be one of the sorted `pubkeys`.
+fn key_coefficient(
+	pubkeys: &[PublicKey], keys_hash: &[u8; 32], pubkey: &PublicKey,
+) -> Result<Scalar, ()> {
+	if !pubkeys.contains(pubkey) {
+		return Err(());
+	}
+	// As an optimization, the first key differing from the first participant's key has a
+	// coefficient of one.
+	let second_key = pubkeys.iter().find(|pk| *pk != &pubkeys[0]);
+	if second_key == Some(pubkey) {
+		return Ok(Scalar::ONE);
+	}
+	hash_to_scalar(tagged_hash(b"KeyAgg coefficient", &[keys_hash, &pubkey.serialize()]))
+}
+
+impl KeyAggContext {
+	/// Aggregates the given keys, which are sorted first so their order does not matter.
+	pub(crate) fn new<C: Verification>(
+		secp_ctx: &Secp256k1<C>, pubkeys: &[PublicKey],
+	) -> Result<Self, ()> {
+		let mut pubkeys = pubkeys.to_vec();
+		pubkeys.sort_unstable_by_key(|pubkey| pubkey.serialize());
+		let serialized_keys: Vec<[u8; 33]> = pubkeys.iter().map(|pk| pk.serialize()).collect();
+		let keys_data: Vec<&[u8]> = serialized_keys.iter().map(|pk| &pk[..]).collect();
+		let keys_hash = tagged_hash(b"KeyAgg list", &keys_data);
+
+		let mut weighted_keys = Vec::with_capacity(pubkeys.len());
+		for pubkey in pubkeys.iter() {
+			let coefficient = key_coefficient(&pubkeys, &keys_hash, pubkey)?;
+			weighted_keys.push(pubkey.mul_tweak(secp_ctx, &coefficient).map_err(|_| ())?);
+		}
+		let weighted_keys: Vec<&PublicKey> = weighted_keys.iter().collect();
+		let aggregate_key = PublicKey::combine_keys(&weighted_keys).map_err(|_| ())?;
+
+		let (internal_key, parity) = aggregate_key.x_only_public_key();
+		let tweak = TapTweakHash::from_key_and_tweak(internal_key, None).to_scalar();
+		let negated_before_tweak = parity == Parity::Odd;
+		let even_aggregate_key =
+			if negated_before_tweak { aggregate_key.negate(secp_ctx) } else { aggregate_key };
+		let output_key = even_aggregate_key.add_exp_tweak(secp_ctx, &tweak).map_err(|_| ())?;
+		Ok(Self {
+			pubkeys,
+			keys_hash,
+			output_key,
+			negated_before_tweak,
+			#[cfg(test)]
+			tweak,
+		})
+	}
+
+	fn coefficient(&self, pubkey: &PublicKey) -> Result<Scalar, ()> {
+		key_coefficient(&self.pubkeys, &self.keys_hash, pubkey)
+	}
+
+	/// Returns the x-only output key that the aggregate signature will be valid for.
+	pub(crate) fn output_key(&self) -> XOnlyPublicKey {
+		self.output_key.x_only_public_key().0
+	}
+
+	/// Returns the P2TR script pubkey paying to the output key.
+	pub(crate) fn script_pubkey(&self) -> ScriptBuf {
+		ScriptBuf::new_p2tr_tweaked(TweakedPublicKey::dangerous_assume_tweaked(self.output_key()))
+	}
+
+	/// Returns whether a participant's secret key (or public key) has to be negated when signing
+	/// (or verifying partial signatures), i.e. whether BIP 327's `g * gacc` is `-1`.
+	fn negate_participant_keys(&self) -> bool {
+		let output_key_odd = self.output_key.x_only_public_key().1 == Parity::Odd;
+		output_key_odd != self.negated_before_tweak
+	}
+}
+
+/// A secret nonce pair, from which a [`PublicNonce`] is derived.
+///
+/// A secret nonce must never be used to sign more than one message, otherwise the secret key it
+/// was used with can be recovered. Thus, it is neither `Clone` nor derived deterministically, and
+/// is consumed by [`SigningSession::partial_sign`].
+pub(crate) struct SecretNonce {
+	k1: SecretKey,
+	k2: SecretKey,
+}
+
+impl SecretNonce {
+	/// Creates a secret nonce from 64 bytes of fresh randomness, which must never be reused.
+	pub(crate) fn from_random_bytes(k1: [u8; 32], k2: [u8; 32]) -> Result<Self, ()> {
+		let k1 = SecretKey::from_slice(&k1).map_err(|_| ())?;
+		let k2 = SecretKey::from_slice(&k2).map_err(|_| ())?;
+		Ok(Self { k1, k2 })
+	}
+
+	pub(crate) fn public_nonce<C: Signing>(&self, secp_ctx: &Secp256k1<C>) -> PublicNonce {
+		let r1 = PublicKey::from_secret_key(secp_ctx, &self.k1);
+		let r2 = PublicKey::from_secret_key(secp_ctx, &self.k2);
+		PublicNonce::from_slice(&serialize_nonce_points(&r1, &r2))
+			.expect("Two valid points always make a valid public nonce")
+	}
+}
+
+/// The state required to create, verify and aggregate partial signatures once all participants'
+/// public nonces are known.
+pub(crate) struct SigningSession<'a> {
+	key_agg_ctx: &'a KeyAggContext,
+	/// BIP 327's nonce coefficient `b`.
+	nonce_coefficient: Scalar,
+	/// The x-only final nonce `R`, committed to in the aggregate signature.
+	#[cfg(test)]
+	final_nonce: XOnlyPublicKey,
+	/// Whether the final nonce had an odd y-coordinate, requiring each participant's nonce to be
+	/// negated.
+	negate_nonces: bool,
+	/// The BIP 340 challenge `e`.
+	challenge: Scalar,
+}
+
+impl<'a> SigningSession<'a> {
+	/// Starts a session for signing `msg` with the given public nonces of all participants.
+	pub(crate) fn new<C: Verification>(
+		secp_ctx: &Secp256k1<C>, key_agg_ctx: &'a KeyAggContext, public_nonces: &[&PublicNonce],
+		msg: &Message,
+	) -> Result<Self, ()> {
+		let mut r1s = Vec::with_capacity(public_nonces.len());
+		let mut r2s = Vec::with_capacity(public_nonces.len());
+		for nonce in public_nonces {
+			let (r1, r2) = parse_public_nonce(nonce)?;
+			r1s.push(r1);
+			r2s.push(r2);
+		}
+		// BIP 327 allows the aggregate nonce points to be infinity, which is only possible if a
+		// participant chose their nonce maliciously, so we simply fail instead.
+		let r1 = PublicKey::combine_keys(&r1s.iter().collect::<Vec<_>>()).map_err(|_| ())?;
+		let r2 = PublicKey::combine_keys(&r2s.iter().collect::<Vec<_>>()).map_err(|_| ())?;
+
+		let output_key = key_agg_ctx.output_key().serialize();
+		let nonce_coefficient = hash_to_scalar(tagged_hash(
+			b"MuSig/noncecoef",
+			&[&serialize_nonce_points(&r1, &r2), &output_key, &msg[..]],
+		))?;
+		let final_nonce = r1.combine(&r2.mul_tweak(secp_ctx, &nonce_coefficient).map_err(|_| ())?);
+		let (final_nonce, parity) = final_nonce.map_err(|_| ())?.x_only_public_key();
+		let challenge = hash_to_scalar(tagged_hash(
+			b"BIP0340/challenge",
+			&[&final_nonce.serialize(), &output_key, &msg[..]],
+		))?;
+		Ok(Self {
+			key_agg_ctx,
+			nonce_coefficient,
+			#[cfg(test)]
+			final_nonce,
+			negate_nonces: parity == Parity::Odd,
+			challenge,
+		})
+	}
+
+	/// Creates our partial signature with `secret_key`, consuming the `secret_nonce` we handed
+	/// out the [`PublicNonce`] of.
+	pub(crate) fn partial_sign<C: Signing>(
+		&self, secp_ctx: &Secp256k1<C>, secret_nonce: SecretNonce, secret_key: &SecretKey,
+	) -> Result<PartialSignature, ()> {
+		let pubkey = PublicKey::from_secret_key(secp_ctx, secret_key);
+		let coefficient = self.key_agg_ctx.coefficient(&pubkey)?;
+		let (k1, k2) = if self.negate_nonces {
+			(secret_nonce.k1.negate(), secret_nonce.k2.negate())
+		} else {
+			(secret_nonce.k1, secret_nonce.k2)
+		};
+		let secret_key = if self.key_agg_ctx.negate_participant_keys() {
+			secret_key.negate()
+		} else {
+			*secret_key
+		};
+
+		// s = k1 + b * k2 + e * a * d
+		let bk2 = k2.mul_tweak(&self.nonce_coefficient).map_err(|_| ())?;
+		let ead =
+			secret_key.mul_tweak(&scalar_mul(&self.challenge, &coefficient)?).map_err(|_| ())?;
+		let s = k1
+			.add_tweak(&Scalar::from(bk2))
+			.and_then(|s| s.add_tweak(&Scalar::from(ead)))
+			.map_err(|_| ())?;
+		PartialSignature::from_slice(&s.secret_bytes()).map_err(|_| ())
+	}
+
+	/// Verifies the partial signature of the participant with the given `pubkey` and
+	/// `public_nonce`.
+	pub(crate) fn verify_partial_signature<C: Signing + Verification>(
+		&self, secp_ctx: &Secp256k1<C>, partial_signature: &PartialSignature,
+		public_nonce: &PublicNonce, pubkey: &PublicKey,
+	) -> Result<(), ()> {
+		let s = SecretKey::from_slice(&partial_signature.serialize()).map_err(|_| ())?;
+		let coefficient = self.key_agg_ctx.coefficient(pubkey)?;
+		let (r1, r2) = parse_public_nonce(public_nonce)?;
+		let nonce = r1
+			.combine(&r2.mul_tweak(secp_ctx, &self.nonce_coefficient).map_err(|_| ())?)
+			.map_err(|_| ())?;
+		let nonce = if self.negate_nonces { nonce.negate(secp_ctx) } else { nonce };
+		let pubkey = if self.key_agg_ctx.negate_participant_keys() {
+			pubkey.negate(secp_ctx)
+		} else {
+			*pubkey
+		};
+
+		// s * G == R + e * a * P
+		let eap = pubkey
+			.mul_tweak(secp_ctx, &scalar_mul(&self.challenge, &coefficient)?)
+			.map_err(|_| ())?;
+		let expected = nonce.combine(&eap).map_err(|_| ())?;
+		if PublicKey::from_secret_key(secp_ctx, &s) == expected {
+			Ok(())
+		} else {
+			Err(())
+		}
+	}
+
+	/// Aggregates all participants' partial signatures into a BIP 340 signature valid for the
+	/// output key.
+	#[cfg(test)]
+	pub(crate) fn aggregate(
+		&self, partial_signatures: &[&PartialSignature],
+	) -> Result<bitcoin::secp256k1::schnorr::Signature, ()> {
+		// s = sum(s_i) + e * g * tacc
+		let mut tweak = SecretKey::from_slice(&self.tweak_term()?.to_be_bytes()).map_err(|_| ())?;
+		for partial_signature in partial_signatures {
+			let s = Scalar::from_be_bytes(partial_signature.serialize()).map_err(|_| ())?;
+			tweak = tweak.add_tweak(&s).map_err(|_| ())?;
+		}
+		let mut sig = [0; 64];
+		sig[..32].copy_from_slice(&self.final_nonce.serialize());
+		sig[32..].copy_from_slice(&tweak.secret_bytes());
+		bitcoin::secp256k1::schnorr::Signature::from_slice(&sig).map_err(|_| ())
+	}
+
+	#[cfg(test)]
+	fn tweak_term(&self) -> Result<Scalar, ()> {
+		let term = scalar_mul(&self.challenge, &self.key_agg_ctx.tweak)?;
+		if self.key_agg_ctx.output_key.x_only_public_key().1 == Parity::Odd {
+			let term = SecretKey::from_slice(&term.to_be_bytes()).map_err(|_| ())?;
+			Ok(Scalar::from(term.negate()))
+		} else {
+			Ok(term)
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{KeyAggContext, SecretNonce, SigningSession};
+
+	use bitcoin::secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+
+	#[test]
+	fn musig2_round_trip() {
+		let secp_ctx = Secp256k1::new();
+		for i in 1..16u8 {
+			let msg = Message::from_digest([i; 32]);
+			let alice_key = SecretKey::from_slice(&[i; 32]).unwrap();
+			let bob_key = SecretKey::from_slice(&[i + 16; 32]).unwrap();
+			let pubkeys = [
+				PublicKey::from_secret_key(&secp_ctx, &alice_key),
+				PublicKey::from_secret_key(&secp_ctx, &bob_key),
+			];
+			let key_agg_ctx = KeyAggContext::new(&secp_ctx, &pubkeys).unwrap();
+			let reversed = [pubkeys[1], pubkeys[0]];
+			let reversed_ctx = KeyAggContext::new(&secp_ctx, &reversed).unwrap();
+			assert_eq!(key_agg_ctx.output_key(), reversed_ctx.output_key());
+
+			let alice_nonce = SecretNonce::from_random_bytes([i; 32], [i + 32; 32]).unwrap();
+			let bob_nonce = SecretNonce::from_random_bytes([i + 64; 32], [i + 96; 32]).unwrap();
+			let alice_public_nonce = alice_nonce.public_nonce(&secp_ctx);
+			let bob_public_nonce = bob_nonce.public_nonce(&secp_ctx);
+			let nonces = [&alice_public_nonce, &bob_public_nonce];
+			let session = SigningSession::new(&secp_ctx, &key_agg_ctx, &nonces, &msg).unwrap();
+
+			let alice_sig = session.partial_sign(&secp_ctx, alice_nonce, &alice_key).unwrap();
+			let bob_sig = session.partial_sign(&secp_ctx, bob_nonce, &bob_key).unwrap();
+			assert!(session
+				.verify_partial_signature(&secp_ctx, &alice_sig, &alice_public_nonce, &pubkeys[0])
+				.is_ok());
+			assert!(session
+				.verify_partial_signature(&secp_ctx, &bob_sig, &bob_public_nonce, &pubkeys[1])
+				.is_ok());
+			assert!(session
+				.verify_partial_signature(&secp_ctx, &alice_sig, &bob_public_nonce, &pubkeys[1])
+				.is_err());
+
+			let sig = session.aggregate(&[&alice_sig, &bob_sig]).unwrap();
+			assert!(secp_ctx.verify_schnorr(&sig, &msg, &key_agg_ctx.output_key()).is_ok());
+		}
+	}
+}
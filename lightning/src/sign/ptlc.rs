@@ -0,0 +1,289 @@
+// This file is Copyright its original authors, visible in version control
+// history.
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Defines an experimental signer type and adaptor signature primitives for prototyping PTLC
+//! channels.
+//!
+//! Nothing in here is part of the Lightning protocol yet, and none of it is used by LDK's channel
+//! state machine. It is only available when LDK is built with the `ptlc_experimental` feature.
+
+use bitcoin::hashes::sha256::Hash as Sha256;
+use bitcoin::hashes::{Hash, HashEngine};
+use bitcoin::key::Parity;
+use bitcoin::secp256k1;
+use bitcoin::secp256k1::{
+	schnorr, Message, PublicKey, Scalar, Secp256k1, SecretKey, Signing, Verification,
+	XOnlyPublicKey,
+};
+use bitcoin::sighash::{Prevouts, SighashCache, TapSighashType};
+use bitcoin::taproot::{LeafVersion, TapLeafHash};
+use bitcoin::transaction::{Transaction, TxOut};
+
+use crate::ln::chan_utils::{ChannelTransactionParameters, PtlcOutputInCommitment};
+use crate::sign::ChannelSigner;
+
+/// A BIP 340 Schnorr signature which has been "encrypted" to an adaptor point `T`.
+///
+/// An adaptor signature can be verified against the signer's public key, the signed message and
+/// the adaptor point, but only becomes a valid Schnorr signature once completed with the discrete
+/// log `t` of the adaptor point (see [`AdaptorSignature::adapt`]). Conversely, anyone holding both
+/// the adaptor signature and the completed signature can recover `t` (see
+/// [`AdaptorSignature::extract_secret`]).
+///
+/// This is what makes PTLCs work: the countersignatory hands out an adaptor signature for the
+/// PTLC-Success transaction under the PTLC's payment point, and learns the payment secret once the
+/// completed signature appears on-chain.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AdaptorSignature {
+	/// The x-coordinate of `R + T`, which will be the nonce of the completed signature.
+	nonce_point: XOnlyPublicKey,
+	/// The adaptor signature scalar `s' = k + ex`.
+	presig: SecretKey,
+}
+
+/// Computes a BIP 340 tagged hash of `data` with the given `tag`.
+fn tagged_hash(tag: &[u8], data: &[&[u8]]) -> [u8; 32] {
+	let tag_hash = Sha256::hash(tag);
+	let mut engine = Sha256::engine();
+	engine.input(tag_hash.as_byte_array());
+	engine.input(tag_hash.as_byte_array());
+	for d in data {
+		engine.input(d);
+	}
+	Sha256::from_engine(engine).to_byte_array()
+}
+
+/// Computes the BIP 340 challenge `e` for the given nonce, public key and message.
+fn challenge(
+	nonce_point: &XOnlyPublicKey, pubkey: &XOnlyPublicKey, msg: &Message,
+) -> Result<Scalar, ()> {
+	let e = tagged_hash(
+		b"BIP0340/challenge",
+		&[&nonce_point.serialize(), &pubkey.serialize(), &msg[..]],
+	);
+	// BIP 340 reduces the challenge modulo the curve order, but the chance of it overflowing is
+	// negligible so we simply fail instead.
+	Scalar::from_be_bytes(e).map_err(|_| ())
+}
+
+impl AdaptorSignature {
+	/// Creates an adaptor signature for `msg` with `secret_key`, encrypted to `adaptor_point`.
+	///
+	/// `aux_rand` should be fresh randomness, which is mixed into the nonce as in BIP 340.
+	pub fn sign<C: Signing>(
+		secp_ctx: &Secp256k1<C>, msg: &Message, secret_key: &SecretKey, adaptor_point: &PublicKey,
+		aux_rand: &[u8; 32],
+	) -> Result<Self, ()> {
+		let (pubkey, parity) = secret_key.x_only_public_key(secp_ctx);
+		let secret_key = if parity == Parity::Odd { secret_key.negate() } else { *secret_key };
+
+		// The completed signature's nonce is `R + T`, which BIP 340 requires to have an even
+		// y-coordinate. We can't fix that up by negating `k` without also changing `R + T`, so we
+		// just try successive nonces until we find one that works, which takes two tries on
+		// average.
+		for attempt in 0..=u8::MAX {
+			let nonce = SecretKey::from_slice(&tagged_hash(
+				b"LDK PTLC/adaptor nonce",
+				&[
+					&secret_key.secret_bytes(),
+					&adaptor_point.serialize(),
+					&msg[..],
+					aux_rand,
+					&[attempt],
+				],
+			));
+			let nonce = match nonce {
+				Ok(nonce) => nonce,
+				Err(_) => continue,
+			};
+			let adapted_nonce_point =
+				match PublicKey::from_secret_key(secp_ctx, &nonce).combine(adaptor_point) {
+					Ok(point) => point,
+					Err(_) => continue,
+				};
+			let (nonce_point, nonce_parity) = adapted_nonce_point.x_only_public_key();
+			if nonce_parity == Parity::Odd {
+				continue;
+			}
+
+			let e = challenge(&nonce_point, &pubkey, msg)?;
+			let ex = secret_key.mul_tweak(&e).map_err(|_| ())?;
+			let presig = nonce.add_tweak(&Scalar::from(ex)).map_err(|_| ())?;
+			return Ok(Self { nonce_point, presig });
+		}
+		Err(())
+	}
+
+	/// Verifies that this is a valid adaptor signature for `msg` by `pubkey`, encrypted to
+	/// `adaptor_point`.
+	///
+	/// If this succeeds, completing the signature with the discrete log of `adaptor_point` will
+	/// result in a valid BIP 340 signature.
+	pub fn verify<C: Verification>(
+		&self, secp_ctx: &Secp256k1<C>, msg: &Message, pubkey: &XOnlyPublicKey,
+		adaptor_point: &PublicKey,
+	) -> Result<(), ()> {
+		let e = challenge(&self.nonce_point, pubkey, msg)?;
+		let expected = self
+			.nonce_point
+			.public_key(Parity::Even)
+			.combine(&adaptor_point.negate(secp_ctx))
+			.and_then(|nonce_point| {
+				let e_pubkey = pubkey.public_key(Parity::Even).mul_tweak(secp_ctx, &e)?;
+				nonce_point.combine(&e_pubkey)
+			})
+			.map_err(|_| ())?;
+		if PublicKey::from_secret_key(secp_ctx, &self.presig) == expected {
+			Ok(())
+		} else {
+			Err(())
+		}
+	}
+
+	/// Completes this adaptor signature into a BIP 340 signature using `adaptor_secret`, the
+	/// discrete log of the adaptor point it was created for.
+	pub fn adapt(&self, adaptor_secret: &SecretKey) -> Result<schnorr::Signature, ()> {
+		let s = self.presig.add_tweak(&Scalar::from(*adaptor_secret)).map_err(|_| ())?;
+		let mut sig = [0; 64];
+		sig[..32].copy_from_slice(&self.nonce_point.serialize());
+		sig[32..].copy_from_slice(&s.secret_bytes());
+		schnorr::Signature::from_slice(&sig).map_err(|_| ())
+	}
+
+	/// Recovers the discrete log of the adaptor point from the `signature` this adaptor signature
+	/// was completed into.
+	///
+	/// Fails if `signature` was not completed from this adaptor signature.
+	pub fn extract_secret(&self, signature: &schnorr::Signature) -> Result<SecretKey, ()> {
+		let sig = signature.serialize();
+		if sig[..32] != self.nonce_point.serialize() {
+			return Err(());
+		}
+		let s = SecretKey::from_slice(&sig[32..]).map_err(|_| ())?;
+		s.add_tweak(&Scalar::from(self.presig.negate())).map_err(|_| ())
+	}
+
+	/// Serializes the adaptor signature as the x-only nonce point followed by the scalar.
+	pub fn serialize(&self) -> [u8; 64] {
+		let mut res = [0; 64];
+		res[..32].copy_from_slice(&self.nonce_point.serialize());
+		res[32..].copy_from_slice(&self.presig.secret_bytes());
+		res
+	}
+
+	/// Deserializes an adaptor signature serialized with [`AdaptorSignature::serialize`].
+	pub fn from_slice(data: &[u8]) -> Result<Self, ()> {
+		if data.len() != 64 {
+			return Err(());
+		}
+		let nonce_point = XOnlyPublicKey::from_slice(&data[..32]).map_err(|_| ())?;
+		let presig = SecretKey::from_slice(&data[32..]).map_err(|_| ())?;
+		Ok(Self { nonce_point, presig })
+	}
+}
+
+/// Computes the sighash of the PTLC transaction input spending `prevout` via the PTLC `tapscript`
+/// leaf.
+pub(crate) fn ptlc_tapscript_sighash(
+	ptlc_tx: &Transaction, input: usize, prevout: &TxOut, tapscript: &bitcoin::Script,
+	sighash_type: TapSighashType,
+) -> Result<Message, ()> {
+	let leaf_hash = TapLeafHash::from_script(tapscript, LeafVersion::TapScript);
+	let prevouts = [prevout];
+	let prevouts = if sighash_type == TapSighashType::SinglePlusAnyoneCanPay {
+		Prevouts::One(input, prevout)
+	} else {
+		Prevouts::All(&prevouts)
+	};
+	let sighash = SighashCache::new(ptlc_tx)
+		.taproot_script_spend_signature_hash(input, &prevouts, leaf_hash, sighash_type)
+		.map_err(|_| ())?;
+	Ok(Message::from_digest(sighash.to_byte_array()))
+}
+
+/// An experimental signer type that defines the signing methods required to operate PTLC
+/// channels, see [`ChannelTypeFeatures::supports_ptlc_experimental`].
+///
+/// All signatures are over the tapscript leaf returned by
+/// [`chan_utils::get_ptlc_tapscript`], spending the output returned by
+/// [`chan_utils::get_ptlc_output_script`].
+///
+/// [`ChannelTypeFeatures::supports_ptlc_experimental`]: crate::types::features::ChannelTypeFeatures::supports_ptlc_experimental
+/// [`chan_utils::get_ptlc_tapscript`]: crate::ln::chan_utils::get_ptlc_tapscript
+/// [`chan_utils::get_ptlc_output_script`]: crate::ln::chan_utils::get_ptlc_output_script
+pub trait PtlcChannelSigner: ChannelSigner {
+	/// Creates a signature for a PTLC-Timeout transaction spending a PTLC offered by our
+	/// counterparty on their commitment transaction.
+	///
+	/// The signature must commit to
+	/// [`chan_utils::ptlc_countersignatory_sighash_type`].
+	///
+	/// [`chan_utils::ptlc_countersignatory_sighash_type`]: crate::ln::chan_utils::ptlc_countersignatory_sighash_type
+	fn sign_counterparty_ptlc_timeout_transaction(
+		&self, channel_parameters: &ChannelTransactionParameters, ptlc_tx: &Transaction,
+		input: usize, per_commitment_point: &PublicKey, ptlc: &PtlcOutputInCommitment,
+		secp_ctx: &Secp256k1<secp256k1::All>,
+	) -> Result<schnorr::Signature, ()>;
+
+	/// Creates an adaptor signature, encrypted to the PTLC's payment point, for a PTLC-Success
+	/// transaction spending a PTLC received by our counterparty on their commitment transaction.
+	///
+	/// Our counterparty can only complete the signature, and thus claim the PTLC, by learning the
+	/// payment point's discrete log, which we can then extract from the completed signature once
+	/// it appears on-chain (see [`AdaptorSignature::extract_secret`]).
+	fn adaptor_sign_counterparty_ptlc_success_transaction(
+		&self, channel_parameters: &ChannelTransactionParameters, ptlc_tx: &Transaction,
+		input: usize, per_commitment_point: &PublicKey, ptlc: &PtlcOutputInCommitment,
+		secp_ctx: &Secp256k1<secp256k1::All>,
+	) -> Result<AdaptorSignature, ()>;
+
+	/// Creates a signature for a PTLC-Success or PTLC-Timeout transaction spending a PTLC on our
+	/// own commitment transaction.
+	///
+	/// This may be called multiple times for the same transaction.
+	fn sign_holder_ptlc_transaction(
+		&self, channel_parameters: &ChannelTransactionParameters, ptlc_tx: &Transaction,
+		input: usize, per_commitment_point: &PublicKey, ptlc: &PtlcOutputInCommitment,
+		secp_ctx: &Secp256k1<secp256k1::All>,
+	) -> Result<schnorr::Signature, ()>;
+}
+
+#[cfg(test)]
+mod tests {
+	use super::AdaptorSignature;
+
+	use bitcoin::secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+
+	#[test]
+	fn adaptor_signature_round_trip() {
+		let secp_ctx = Secp256k1::new();
+		let msg = Message::from_digest([42; 32]);
+		for i in 1..16u8 {
+			let secret_key = SecretKey::from_slice(&[i; 32]).unwrap();
+			let (pubkey, _) = secret_key.x_only_public_key(&secp_ctx);
+			let adaptor_secret = SecretKey::from_slice(&[i + 16; 32]).unwrap();
+			let adaptor_point = PublicKey::from_secret_key(&secp_ctx, &adaptor_secret);
+
+			let adaptor_sig =
+				AdaptorSignature::sign(&secp_ctx, &msg, &secret_key, &adaptor_point, &[i; 32])
+					.unwrap();
+			assert!(adaptor_sig.verify(&secp_ctx, &msg, &pubkey, &adaptor_point).is_ok());
+			let other_point = PublicKey::from_secret_key(&secp_ctx, &secret_key);
+			assert!(adaptor_sig.verify(&secp_ctx, &msg, &pubkey, &other_point).is_err());
+			assert_eq!(
+				AdaptorSignature::from_slice(&adaptor_sig.serialize()).unwrap(),
+				adaptor_sig
+			);
+
+			let sig = adaptor_sig.adapt(&adaptor_secret).unwrap();
+			assert!(secp_ctx.verify_schnorr(&sig, &msg, &pubkey).is_ok());
+			assert_eq!(adaptor_sig.extract_secret(&sig).unwrap(), adaptor_secret);
+		}
+	}
+}
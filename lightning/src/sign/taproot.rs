@@ -8,7 +8,8 @@ use bitcoin::transaction::Transaction;
 use musig2::types::{PartialSignature, PublicNonce};
 
 use crate::ln::chan_utils::{
-	ClosingTransaction, CommitmentTransaction, HTLCOutputInCommitment, HolderCommitmentTransaction,
+	ChannelTransactionParameters, ClosingTransaction, CommitmentTransaction,
+	HTLCOutputInCommitment, HolderCommitmentTransaction,
 };
 use crate::ln::msgs::PartialSignatureWithNonce;
 use crate::sign::{ChannelSigner, HTLCDescriptor};
@@ -20,12 +21,21 @@ use crate::types::payment::PaymentPreimage;
 pub trait TaprootChannelSigner: ChannelSigner {
 	/// Generate a local nonce pair, which requires committing to ahead of time.
 	/// The counterparty needs the public nonce generated herein to compute a partial signature.
+	///
+	/// The nonce generated for a given `commitment_number` is the one used by
+	/// [`Self::finalize_holder_commitment`] to sign our commitment transaction with that number.
+	/// Nonces must be random and single-use: calling this again for the same `commitment_number`
+	/// returns the same nonce, which must never be used to sign more than one message.
 	fn generate_local_nonce_pair(
 		&self, commitment_number: u64, secp_ctx: &Secp256k1<secp256k1::All>,
 	) -> PublicNonce;
 
 	/// Create a signature for a counterparty's commitment transaction and associated HTLC transactions.
 	///
+	/// The partial signature is created with a fresh nonce, which is returned alongside it and
+	/// must be sent to the counterparty together with the signature. The HTLC signatures are
+	/// over the tapscript leaf of each HTLC output requiring both parties' signatures.
+	///
 	/// Note that if signing fails or is rejected, the channel will be force-closed.
 	///
 	/// Policy checks should be implemented in this function, including checking the amount
@@ -41,8 +51,8 @@ pub trait TaprootChannelSigner: ChannelSigner {
 	//
 	// TODO: Document the things someone using this interface should enforce before signing.
 	fn partially_sign_counterparty_commitment(
-		&self, counterparty_nonce: PublicNonce, commitment_tx: &CommitmentTransaction,
-		inbound_htlc_preimages: Vec<PaymentPreimage>,
+		&self, channel_parameters: &ChannelTransactionParameters, counterparty_nonce: PublicNonce,
+		commitment_tx: &CommitmentTransaction, inbound_htlc_preimages: Vec<PaymentPreimage>,
 		outbound_htlc_preimages: Vec<PaymentPreimage>, secp_ctx: &Secp256k1<secp256k1::All>,
 	) -> Result<(PartialSignatureWithNonce, Vec<Signature>), ()>;
 
@@ -52,13 +62,22 @@ pub trait TaprootChannelSigner: ChannelSigner {
 	/// - with a non-revoked `commitment_tx`.
 	/// - with the latest `commitment_tx` when we initiate a force-close.
 	///
-	/// This may be called multiple times for the same transaction.
+	/// This may be called multiple times for the same transaction and counterparty nonce, in
+	/// which case the same partial signature must be returned. Any attempt to sign a different
+	/// transaction, or with a different counterparty nonce, with the same commitment number must
+	/// fail, as it would reuse our nonce and thus leak our funding key.
 	///
 	/// An external signer implementation should check that the commitment has not been revoked.
 	///
+	/// Returns our partial signature, created with the nonce returned by
+	/// [`Self::generate_local_nonce_pair`] for the commitment's number, after verifying the
+	/// counterparty's partial signature. Both are then aggregated into the final signature. Fails
+	/// if no nonce was generated for the commitment's number.
+	///
 	// TODO: Document the things someone using this interface should enforce before signing.
 	fn finalize_holder_commitment(
-		&self, commitment_tx: &HolderCommitmentTransaction,
+		&self, channel_parameters: &ChannelTransactionParameters,
+		commitment_tx: &HolderCommitmentTransaction,
 		counterparty_partial_signature: PartialSignatureWithNonce,
 		secp_ctx: &Secp256k1<secp256k1::All>,
 	) -> Result<PartialSignature, ()>;
@@ -72,13 +91,18 @@ pub trait TaprootChannelSigner: ChannelSigner {
 	/// to an upcoming timelock expiration.
 	///
 	/// Amount is value of the output spent by this input, committed to in the BIP 341 signature.
+	/// As the values of the other inputs are not known, the signature is computed using
+	/// [`TapSighashType::AllPlusAnyoneCanPay`].
 	///
 	/// `per_commitment_key` is revocation secret which was provided by our counterparty when they
 	/// revoked the state which they eventually broadcast. It's not a _holder_ secret key and does
 	/// not allow the spending of any funds by itself (you need our holder `revocation_secret` to do
 	/// so).
+	///
+	/// [`TapSighashType::AllPlusAnyoneCanPay`]: bitcoin::sighash::TapSighashType::AllPlusAnyoneCanPay
 	fn sign_justice_revoked_output(
-		&self, justice_tx: &Transaction, input: usize, amount: u64, per_commitment_key: &SecretKey,
+		&self, channel_parameters: &ChannelTransactionParameters, justice_tx: &Transaction,
+		input: usize, amount: u64, per_commitment_key: &SecretKey,
 		secp_ctx: &Secp256k1<secp256k1::All>,
 	) -> Result<Signature, ()>;
 
@@ -91,7 +115,8 @@ pub trait TaprootChannelSigner: ChannelSigner {
 	/// to an upcoming timelock expiration.
 	///
 	/// `amount` is the value of the output spent by this input, committed to in the BIP 341
-	/// signature.
+	/// signature. As the values of the other inputs are not known, the signature is computed using
+	/// [`TapSighashType::AllPlusAnyoneCanPay`].
 	///
 	/// `per_commitment_key` is revocation secret which was provided by our counterparty when they
 	/// revoked the state which they eventually broadcast. It's not a _holder_ secret key and does
@@ -100,14 +125,18 @@ pub trait TaprootChannelSigner: ChannelSigner {
 	///
 	/// `htlc` holds HTLC elements (hash, timelock), thus changing the format of the witness script
 	/// (which is committed to in the BIP 341 signatures).
+	///
+	/// [`TapSighashType::AllPlusAnyoneCanPay`]: bitcoin::sighash::TapSighashType::AllPlusAnyoneCanPay
 	fn sign_justice_revoked_htlc(
-		&self, justice_tx: &Transaction, input: usize, amount: u64, per_commitment_key: &SecretKey,
-		htlc: &HTLCOutputInCommitment, secp_ctx: &Secp256k1<secp256k1::All>,
+		&self, channel_parameters: &ChannelTransactionParameters, justice_tx: &Transaction,
+		input: usize, amount: u64, per_commitment_key: &SecretKey, htlc: &HTLCOutputInCommitment,
+		secp_ctx: &Secp256k1<secp256k1::All>,
 	) -> Result<Signature, ()>;
 
 	/// Computes the signature for a commitment transaction's HTLC output used as an input within
 	/// `htlc_tx`, which spends the commitment transaction at index `input`. The signature returned
-	/// must be be computed using [`TapSighashType::Default`].
+	/// must be be computed using [`TapSighashType::Default`], and thus `htlc_tx` must not have
+	/// any inputs other than the one at index `input`.
 	///
 	/// Note that this may be called for HTLCs in the penultimate commitment transaction if a
 	/// [`ChannelMonitor`] [replica](https://github.com/lightningdevkit/rust-lightning/blob/main/GLOSSARY.md#monitor-replicas)
@@ -133,23 +162,32 @@ pub trait TaprootChannelSigner: ChannelSigner {
 	/// outputs.
 	///
 	/// `amount` is value of the output spent by this input, committed to in the BIP 341 signature.
+	/// As the values of the other inputs are not known, the signature is computed using
+	/// [`TapSighashType::AllPlusAnyoneCanPay`].
 	///
 	/// `per_commitment_point` is the dynamic point corresponding to the channel state
 	/// detected onchain. It has been generated by our counterparty and is used to derive
 	/// channel state keys, which are then included in the witness script and committed to in the
 	/// BIP 341 signature.
+	///
+	/// [`TapSighashType::AllPlusAnyoneCanPay`]: bitcoin::sighash::TapSighashType::AllPlusAnyoneCanPay
 	fn sign_counterparty_htlc_transaction(
-		&self, htlc_tx: &Transaction, input: usize, amount: u64, per_commitment_point: &PublicKey,
-		htlc: &HTLCOutputInCommitment, secp_ctx: &Secp256k1<secp256k1::All>,
+		&self, channel_parameters: &ChannelTransactionParameters, htlc_tx: &Transaction,
+		input: usize, amount: u64, per_commitment_point: &PublicKey, htlc: &HTLCOutputInCommitment,
+		secp_ctx: &Secp256k1<secp256k1::All>,
 	) -> Result<Signature, ()>;
 
 	/// Create a signature for a (proposed) closing transaction.
 	///
+	/// As with [`Self::partially_sign_counterparty_commitment`], the partial signature is created
+	/// with a fresh nonce, which is returned alongside it.
+	///
 	/// Note that, due to rounding, there may be one "missing" satoshi, and either party may have
 	/// chosen to forgo their output as dust.
 	fn partially_sign_closing_transaction(
-		&self, closing_tx: &ClosingTransaction, secp_ctx: &Secp256k1<secp256k1::All>,
-	) -> Result<PartialSignature, ()>;
+		&self, channel_parameters: &ChannelTransactionParameters, counterparty_nonce: PublicNonce,
+		closing_tx: &ClosingTransaction, secp_ctx: &Secp256k1<secp256k1::All>,
+	) -> Result<PartialSignatureWithNonce, ()>;
 
 	// TODO: sign channel announcement
 }
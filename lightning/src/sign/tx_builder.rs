@@ -1,8 +1,6 @@
 //! Defines the `TxBuilder` trait, and the `SpecTxBuilder` type
-#![allow(dead_code)]
 
 use core::cmp;
-use core::ops::Deref;
 
 use bitcoin::secp256k1::{self, PublicKey, Secp256k1};
 
@@ -11,13 +9,17 @@ use crate::ln::chan_utils::{
 	second_stage_tx_fees_sat, ChannelTransactionParameters, CommitmentTransaction,
 	HTLCOutputInCommitment,
 };
-use crate::ln::channel::{CommitmentStats, ANCHOR_OUTPUT_VALUE_SATOSHI};
+use crate::ln::channel::ANCHOR_OUTPUT_VALUE_SATOSHI;
 use crate::prelude::*;
 use crate::types::features::ChannelTypeFeatures;
 use crate::util::logger::Logger;
 
-pub(crate) struct HTLCAmountDirection {
+/// An HTLC which will be included in the next commitment transaction, from the point of view of
+/// the holder.
+pub struct HTLCAmountDirection {
+	/// Whether the HTLC was offered by the holder.
 	pub outbound: bool,
+	/// The value of the HTLC, in millisatoshis.
 	pub amount_msat: u64,
 }
 
@@ -34,15 +36,30 @@ impl HTLCAmountDirection {
 	}
 }
 
-pub(crate) struct NextCommitmentStats {
+/// Stats on the next commitment transaction of either party, as returned by
+/// [`TxBuilder::get_next_commitment_stats`].
+pub struct NextCommitmentStats {
+	/// Whether the holder is the funder of the channel.
 	pub is_outbound_from_holder: bool,
+	/// The number of HTLCs offered by the counterparty.
 	pub inbound_htlcs_count: usize,
+	/// The total value of the HTLCs offered by the counterparty, in millisatoshis.
 	pub inbound_htlcs_value_msat: u64,
+	/// The holder's balance after HTLCs and any non-HTLC outputs, but before the commitment
+	/// transaction fee is paid.
 	pub holder_balance_before_fee_msat: u64,
+	/// The counterparty's balance after HTLCs and any non-HTLC outputs, but before the commitment
+	/// transaction fee is paid.
 	pub counterparty_balance_before_fee_msat: u64,
+	/// The number of HTLCs which are not trimmed to dust on the commitment transaction.
 	pub nondust_htlc_count: usize,
+	/// The fee paid by the commitment transaction, in satoshis.
 	pub commit_tx_fee_sat: u64,
+	/// The total value of the HTLCs and fees we would lose to dust if the commitment transaction
+	/// were to confirm, in millisatoshis.
 	pub dust_exposure_msat: u64,
+	/// The additional dust exposure we would incur by accepting one more non-dust HTLC, in
+	/// millisatoshis.
 	pub extra_accepted_htlc_dust_exposure_msat: u64,
 }
 
@@ -154,7 +171,31 @@ fn get_dust_buffer_feerate(feerate_per_kw: u32) -> u32 {
 	cmp::max(feerate_per_kw.saturating_add(2530), feerate_plus_quarter.unwrap_or(u32::MAX))
 }
 
-pub(crate) trait TxBuilder {
+/// A struct gathering stats on a commitment transaction, either local or remote.
+#[derive(Debug, PartialEq)]
+pub struct CommitmentStats {
+	/// The total fee included in the commitment transaction
+	pub commit_tx_fee_sat: u64,
+	/// The local balance before fees *not* considering dust limits
+	pub local_balance_before_fee_msat: u64,
+	/// The remote balance before fees *not* considering dust limits
+	pub remote_balance_before_fee_msat: u64,
+}
+
+/// Builds the commitment transactions of a channel, and computes the balances and fees of the
+/// next commitment transactions when deciding whether to accept or send new updates.
+///
+/// The builder used for a given channel is provided by [`SignerProvider::get_tx_builder`].
+/// Unless you are experimenting with custom commitment transaction layouts, [`SpecTxBuilder`]
+/// should be used, which builds commitment transactions as specified in BOLT 3.
+///
+/// [`SignerProvider::get_tx_builder`]: crate::sign::SignerProvider::get_tx_builder
+pub trait TxBuilder {
+	/// Computes the stats of the next commitment transaction of either the holder (if `local`) or
+	/// the counterparty, given the HTLCs which will be included in it, plus
+	/// `addl_nondust_htlc_count` additional non-dust HTLCs.
+	///
+	/// Returns `Err(())` if either party's balance cannot cover the given HTLCs.
 	fn get_next_commitment_stats(
 		&self, local: bool, is_outbound_from_holder: bool, channel_value_satoshis: u64,
 		value_to_holder_msat: u64, next_commitment_htlcs: &[HTLCAmountDirection],
@@ -162,24 +203,52 @@ pub(crate) trait TxBuilder {
 		dust_exposure_limiting_feerate: Option<u32>, broadcaster_dust_limit_satoshis: u64,
 		channel_type: &ChannelTypeFeatures,
 	) -> Result<NextCommitmentStats, ()>;
+	/// Returns the fee paid by a commitment transaction at the given feerate with the given number
+	/// of non-dust HTLCs, in satoshis.
 	fn commit_tx_fee_sat(
 		&self, feerate_per_kw: u32, nondust_htlc_count: usize, channel_type: &ChannelTypeFeatures,
 	) -> u64;
+	/// Subtracts the value of any non-HTLC outputs (e.g. anchors) from the funder's balance,
+	/// returning the holder's and counterparty's balances, in millisatoshis.
 	fn subtract_non_htlc_outputs(
 		&self, is_outbound_from_holder: bool, value_to_self_after_htlcs: u64,
 		value_to_remote_after_htlcs: u64, channel_type: &ChannelTypeFeatures,
 	) -> (u64, u64);
-	fn build_commitment_transaction<L: Deref>(
+	/// Builds the commitment transaction of either the holder (if `local`) or the counterparty
+	/// with the given `commitment_number`, trimming any dust HTLCs out of `htlcs_in_tx`.
+	fn build_commitment_transaction(
 		&self, local: bool, commitment_number: u64, per_commitment_point: &PublicKey,
 		channel_parameters: &ChannelTransactionParameters, secp_ctx: &Secp256k1<secp256k1::All>,
 		value_to_self_msat: u64, htlcs_in_tx: Vec<HTLCOutputInCommitment>, feerate_per_kw: u32,
-		broadcaster_dust_limit_satoshis: u64, logger: &L,
-	) -> (CommitmentTransaction, CommitmentStats)
-	where
-		L::Target: Logger;
+		broadcaster_dust_limit_satoshis: u64, logger: &dyn Logger,
+	) -> (CommitmentTransaction, CommitmentStats);
+	/// Polls whether the counterparty commitment transaction with the given `commitment_number`
+	/// and `per_commitment_point` can be built and handed to the signer now.
+	///
+	/// Builders which depend on a remote party (e.g. a remote signer which builds the commitment
+	/// transaction alongside signing it) may return `Err(())` while that round-trip is still
+	/// outstanding. In that case, just like when an async [`ChannelSigner`] is unable to provide
+	/// a signature, the channel will hold off on sending the corresponding `funding_created`,
+	/// `funding_signed`, or `commitment_signed` message until
+	/// [`ChannelManager::signer_unblocked`] is called, at which point this will be polled again.
+	///
+	/// [`ChannelSigner`]: crate::sign::ChannelSigner
+	/// [`ChannelManager::signer_unblocked`]: crate::ln::channelmanager::ChannelManager::signer_unblocked
+	fn counterparty_commitment_ready(
+		&self, _commitment_number: u64, _per_commitment_point: &PublicKey,
+	) -> Result<(), ()> {
+		Ok(())
+	}
+}
+
+impl core::fmt::Debug for dyn TxBuilder + Send + Sync {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> Result<(), core::fmt::Error> {
+		f.write_str("TxBuilder")
+	}
 }
 
-pub(crate) struct SpecTxBuilder {}
+/// A [`TxBuilder`] which builds commitment transactions as specified in BOLT 3.
+pub struct SpecTxBuilder {}
 
 impl TxBuilder for SpecTxBuilder {
 	fn get_next_commitment_stats(
@@ -322,15 +391,12 @@ impl TxBuilder for SpecTxBuilder {
 
 		(local_balance_before_fee_msat, remote_balance_before_fee_msat)
 	}
-	fn build_commitment_transaction<L: Deref>(
+	fn build_commitment_transaction(
 		&self, local: bool, commitment_number: u64, per_commitment_point: &PublicKey,
 		channel_parameters: &ChannelTransactionParameters, secp_ctx: &Secp256k1<secp256k1::All>,
 		value_to_self_msat: u64, mut htlcs_in_tx: Vec<HTLCOutputInCommitment>, feerate_per_kw: u32,
-		broadcaster_dust_limit_satoshis: u64, logger: &L,
-	) -> (CommitmentTransaction, CommitmentStats)
-	where
-		L::Target: Logger,
-	{
+		broadcaster_dust_limit_satoshis: u64, logger: &dyn Logger,
+	) -> (CommitmentTransaction, CommitmentStats) {
 		let mut local_htlc_total_msat = 0;
 		let mut remote_htlc_total_msat = 0;
 		let channel_type = &channel_parameters.channel_type_features;
@@ -41,6 +41,13 @@ impl<T> Mutex<T> {
 		Ok(MutexGuard { lock: self.inner.borrow_mut() })
 	}
 
+	pub fn try_lock<'a>(&'a self) -> LockResult<MutexGuard<'a, T>> {
+		match self.inner.try_borrow_mut() {
+			Ok(lock) => Ok(MutexGuard { lock }),
+			Err(_) => Err(()),
+		}
+	}
+
 	pub fn into_inner(self) -> LockResult<T> {
 		Ok(self.inner.into_inner())
 	}
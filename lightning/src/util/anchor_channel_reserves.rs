@@ -23,6 +23,7 @@ use crate::chain::chaininterface::BroadcasterInterface;
 use crate::chain::chaininterface::FeeEstimator;
 use crate::chain::chainmonitor::ChainMonitor;
 use crate::chain::chainmonitor::Persist;
+use crate::chain::channelmonitor::{Balance, ChannelMonitor};
 use crate::chain::Filter;
 use crate::events::bump_transaction::Utxo;
 use crate::ln::chan_utils::max_htlcs;
@@ -36,7 +37,7 @@ use bitcoin::constants::WITNESS_SCALE_FACTOR;
 use bitcoin::Amount;
 use bitcoin::FeeRate;
 use bitcoin::Weight;
-use core::cmp::min;
+use core::cmp::{max, min};
 use core::ops::Deref;
 
 // Transaction weights based on:
@@ -182,22 +183,42 @@ fn get_reserve_per_channel_with_input(
 ) -> Amount {
 	let max_max_htlcs = max_htlcs(&ChannelTypeFeatures::only_static_remote_key());
 	let expected_accepted_htlcs = min(context.expected_accepted_htlcs, max_max_htlcs) as u64;
+	// Reserves are calculated in terms of accepted HTLCs, as their timeout defines the urgency of
+	// on-chain resolution. Each accepted HTLC is assumed to be forwarded to calculate an upper
+	// bound for the reserve, resulting in `expected_accepted_htlcs` inbound HTLCs and
+	// `expected_accepted_htlcs` outbound HTLCs per channel in aggregate.
+	get_reserve_for_htlcs_with_input(
+		context,
+		expected_accepted_htlcs,
+		expected_accepted_htlcs,
+		initial_input_weight,
+	)
+}
+
+fn get_reserve_for_htlcs_with_input(
+	context: &AnchorChannelReserveContext, inbound_htlcs: u64, outbound_htlcs: u64,
+	initial_input_weight: Weight,
+) -> Amount {
 	let weight = Weight::from_wu(
 		COMMITMENT_TRANSACTION_BASE_WEIGHT +
-		// Reserves are calculated in terms of accepted HTLCs, as their timeout defines the urgency of
-		// on-chain resolution. Each accepted HTLC is assumed to be forwarded to calculate an upper
-		// bound for the reserve, resulting in `expected_accepted_htlcs` inbound HTLCs and
-		// `expected_accepted_htlcs` outbound HTLCs per channel in aggregate.
-		2 * expected_accepted_htlcs * COMMITMENT_TRANSACTION_PER_HTLC_WEIGHT +
+		(inbound_htlcs + outbound_htlcs) * COMMITMENT_TRANSACTION_PER_HTLC_WEIGHT +
 		anchor_output_spend_transaction_weight(context, initial_input_weight) +
 		// As an upper bound, it is assumed that each HTLC is resolved in a separate transaction.
 		// However, they might be aggregated when possible depending on timelocks and expiries.
-		htlc_success_transaction_weight(context) * expected_accepted_htlcs +
-		htlc_timeout_transaction_weight(context) * expected_accepted_htlcs,
+		htlc_success_transaction_weight(context) * inbound_htlcs +
+		htlc_timeout_transaction_weight(context) * outbound_htlcs,
 	);
 	context.upper_bound_fee_rate.fee_wu(weight).unwrap_or(Amount::MAX)
 }
 
+fn wallet_input_weight(context: &AnchorChannelReserveContext) -> Weight {
+	if context.taproot_wallet {
+		Weight::from_wu(P2TR_KEYPATH_INPUT_WEIGHT)
+	} else {
+		Weight::from_wu(P2WPKH_INPUT_WEIGHT)
+	}
+}
+
 /// Returns the amount that needs to be maintained as a reserve per anchor channel.
 ///
 /// This reserve currently needs to be allocated as a disjoint set of at least 1 UTXO per channel,
@@ -219,16 +240,59 @@ fn get_reserve_per_channel_with_input(
 ///
 /// [ConfirmationTarget::UrgentOnChainSweep]: crate::chain::chaininterface::ConfirmationTarget::UrgentOnChainSweep
 pub fn get_reserve_per_channel(context: &AnchorChannelReserveContext) -> Amount {
-	get_reserve_per_channel_with_input(
+	get_reserve_per_channel_with_input(context, wallet_input_weight(context))
+}
+
+/// Returns the amount that needs to be maintained as a reserve for the anchor channel tracked by
+/// the given [`ChannelMonitor`], or zero if it is not an anchor channel or has nothing left to
+/// claim on-chain.
+///
+/// Unlike [`get_reserve_per_channel`], this accounts for the HTLCs currently pending in the
+/// channel, using the larger of the actual number of pending inbound and outbound HTLCs and
+/// [`AnchorChannelReserveContext::expected_accepted_htlcs`] for each direction.
+pub fn get_reserve_for_channel<ChannelSigner: EcdsaChannelSigner>(
+	context: &AnchorChannelReserveContext, channel_monitor: &ChannelMonitor<ChannelSigner>,
+) -> Amount {
+	if !channel_monitor.channel_type_features().supports_anchors_zero_fee_htlc_tx() {
+		return Amount::ZERO;
+	}
+	let balances = channel_monitor.get_claimable_balances();
+	if balances.is_empty() {
+		return Amount::ZERO;
+	}
+	let (mut inbound_htlcs, mut outbound_htlcs) = (0, 0);
+	for balance in balances {
+		match balance {
+			Balance::MaybePreimageClaimableHTLC { .. } => inbound_htlcs += 1,
+			Balance::MaybeTimeoutClaimableHTLC { .. } => outbound_htlcs += 1,
+			_ => {},
+		}
+	}
+	let max_max_htlcs = max_htlcs(&ChannelTypeFeatures::only_static_remote_key());
+	let expected_accepted_htlcs = min(context.expected_accepted_htlcs, max_max_htlcs) as u64;
+	get_reserve_for_htlcs_with_input(
 		context,
-		if context.taproot_wallet {
-			Weight::from_wu(P2TR_KEYPATH_INPUT_WEIGHT)
-		} else {
-			Weight::from_wu(P2WPKH_INPUT_WEIGHT)
-		},
+		max(inbound_htlcs, expected_accepted_htlcs),
+		max(outbound_htlcs, expected_accepted_htlcs),
+		wallet_input_weight(context),
 	)
 }
 
+/// Returns the total value `utxos` can contribute towards anchor channel reserves, i.e. their
+/// value less the fee to spend them at [`AnchorChannelReserveContext::upper_bound_fee_rate`].
+pub fn get_available_reserve(context: &AnchorChannelReserveContext, utxos: &[Utxo]) -> Amount {
+	let mut total = Amount::ZERO;
+	for utxo in utxos {
+		let satisfaction_fee = context
+			.upper_bound_fee_rate
+			.fee_wu(Weight::from_wu(utxo.satisfaction_weight))
+			.unwrap_or(Amount::MAX);
+		let amount = utxo.output.value.checked_sub(satisfaction_fee).unwrap_or(Amount::ZERO);
+		total = total.checked_add(amount).unwrap_or(Amount::MAX);
+	}
+	total
+}
+
 /// Calculates the number of anchor channels that can be supported by the reserve provided
 /// by `utxos`.
 pub fn get_supportable_anchor_channels(
@@ -376,6 +440,26 @@ mod test {
 		assert_eq!(get_supportable_anchor_channels(&context, utxos.as_slice()), 3);
 	}
 
+	#[test]
+	fn test_get_available_reserve() {
+		let context = AnchorChannelReserveContext::default();
+		let satisfaction_fee = context
+			.upper_bound_fee_rate
+			.fee_wu(Weight::from_wu(make_p2wpkh_utxo(Amount::ZERO).satisfaction_weight))
+			.unwrap();
+		let utxos = vec![
+			make_p2wpkh_utxo(Amount::from_sat(100_000)),
+			make_p2wpkh_utxo(Amount::from_sat(50_000)),
+			// UTXOs which cost more to spend than they're worth don't count against the others.
+			make_p2wpkh_utxo(satisfaction_fee / 2),
+		];
+		assert_eq!(
+			get_available_reserve(&context, &utxos),
+			Amount::from_sat(150_000) - satisfaction_fee * 2
+		);
+		assert_eq!(get_available_reserve(&context, &[]), Amount::ZERO);
+	}
+
 	#[test]
 	fn test_anchor_output_spend_transaction_weight() {
 		// Example with smaller signatures:
@@ -9,7 +9,9 @@
 
 //! Some utilities to make working with the standard library's [`Future`]s easier
 
+use alloc::collections::VecDeque;
 use alloc::vec::Vec;
+use core::cmp;
 use core::future::Future;
 use core::marker::Unpin;
 use core::pin::Pin;
@@ -70,6 +72,121 @@ impl<F: Future<Output = Result<(), E>> + Unpin, E: Unpin> Future for MultiResult
 	}
 }
 
+/// Polls the futures returned by `handler` for a series of items, allowing up to a fixed number
+/// of them to be pending at once.
+///
+/// Items are handed to `handler` in order. An item is only handed to `handler` once the futures
+/// for all earlier items sharing one of its keys have completed. Items without keys are only
+/// handed to `handler` once the futures for all earlier items have completed, and no later item is
+/// handed to `handler` until their own future has completed.
+///
+/// Once any future fails, no further items are handed to `handler`. The output holds the results
+/// for all items which were handed to `handler`, in order.
+pub(crate) struct OrderedConcurrentPoller<'a, K, T, F, E, H>
+where
+	K: PartialEq + Unpin,
+	T: Unpin,
+	F: Future<Output = Result<(), E>> + Unpin,
+	E: Unpin,
+	H: Fn(T) -> F,
+{
+	pending_items: VecDeque<(Option<Vec<K>>, T)>,
+	in_flight: Vec<(usize, Option<Vec<K>>, F)>,
+	results: Vec<Option<Result<(), E>>>,
+	next_item_idx: usize,
+	handler: &'a H,
+	max_in_flight: usize,
+	failed: bool,
+}
+
+impl<'a, K, T, F, E, H> OrderedConcurrentPoller<'a, K, T, F, E, H>
+where
+	K: PartialEq + Unpin,
+	T: Unpin,
+	F: Future<Output = Result<(), E>> + Unpin,
+	E: Unpin,
+	H: Fn(T) -> F,
+{
+	/// Creates a new poller for the given items and their keys. A `max_in_flight` of 0 is treated
+	/// as 1.
+	pub fn new(items: Vec<(Option<Vec<K>>, T)>, handler: &'a H, max_in_flight: usize) -> Self {
+		let results = items.iter().map(|_| None).collect();
+		Self {
+			pending_items: items.into_iter().collect(),
+			in_flight: Vec::new(),
+			results,
+			next_item_idx: 0,
+			handler,
+			max_in_flight: cmp::max(max_in_flight, 1),
+			failed: false,
+		}
+	}
+
+	fn can_start(&self, keys: &Option<Vec<K>>) -> bool {
+		if self.in_flight.len() >= self.max_in_flight {
+			return false;
+		}
+		match keys {
+			None => self.in_flight.is_empty(),
+			Some(keys) => self.in_flight.iter().all(|(_, in_flight_keys, _)| {
+				in_flight_keys.as_ref().map_or(false, |in_flight_keys| {
+					in_flight_keys.iter().all(|key| !keys.contains(key))
+				})
+			}),
+		}
+	}
+}
+
+impl<'a, K, T, F, E, H> Future for OrderedConcurrentPoller<'a, K, T, F, E, H>
+where
+	K: PartialEq + Unpin,
+	T: Unpin,
+	F: Future<Output = Result<(), E>> + Unpin,
+	E: Unpin,
+	H: Fn(T) -> F,
+{
+	type Output = Vec<Result<(), E>>;
+	fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Vec<Result<(), E>>> {
+		let this = self.get_mut();
+		loop {
+			let mut idx = 0;
+			while idx < this.in_flight.len() {
+				match Pin::new(&mut this.in_flight[idx].2).poll(cx) {
+					Poll::Ready(res) => {
+						let (item_idx, _, _) = this.in_flight.swap_remove(idx);
+						this.failed |= res.is_err();
+						this.results[item_idx] = Some(res);
+					},
+					Poll::Pending => idx += 1,
+				}
+			}
+
+			// Hand out as many further items as we can. Any new futures have to be polled before
+			// we return to ensure we get woken up once they make progress.
+			let mut started_any = false;
+			while !this.failed {
+				match this.pending_items.front() {
+					Some((keys, _)) if this.can_start(keys) => {},
+					_ => break,
+				}
+				let (keys, item) = this.pending_items.pop_front().expect("Checked above");
+				this.in_flight.push((this.next_item_idx, keys, (this.handler)(item)));
+				this.next_item_idx += 1;
+				started_any = true;
+			}
+			if !started_any {
+				break;
+			}
+		}
+
+		if this.in_flight.is_empty() && (this.failed || this.pending_items.is_empty()) {
+			Poll::Ready(this.results.drain(..).map_while(|res| res).collect())
+		} else {
+			Poll::Pending
+		}
+	}
+}
+
 // If we want to poll a future without an async context to figure out if it has completed or
 // not without awaiting, we need a Waker, which needs a vtable...we fill it with dummy values
 // but sadly there's a good bit of boilerplate here.
@@ -118,3 +235,99 @@ pub use core::marker::Send as MaybeSend;
 pub trait MaybeSend {}
 #[cfg(not(feature = "std"))]
 impl<T> MaybeSend for T where T: ?Sized {}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use core::cell::RefCell;
+
+	struct TestFuture<'a> {
+		item: usize,
+		outcomes: &'a RefCell<Vec<Option<Result<(), ()>>>>,
+	}
+
+	impl<'a> Future for TestFuture<'a> {
+		type Output = Result<(), ()>;
+		fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), ()>> {
+			match self.outcomes.borrow()[self.item] {
+				Some(res) => Poll::Ready(res),
+				None => Poll::Pending,
+			}
+		}
+	}
+
+	#[test]
+	fn ordered_concurrent_poller_respects_keys_and_limit() {
+		let outcomes = RefCell::new(vec![None; 6]);
+		let started = RefCell::new(Vec::new());
+		let handler = |item: usize| {
+			started.borrow_mut().push(item);
+			TestFuture { item, outcomes: &outcomes }
+		};
+		let items = vec![
+			(Some(vec![1]), 0),
+			(Some(vec![1, 2]), 1),
+			(Some(vec![3]), 2),
+			(Some(vec![4]), 3),
+			(None, 4),
+			(Some(vec![2]), 5),
+		];
+		let mut poller = OrderedConcurrentPoller::new(items, &handler, 2);
+		let waker = dummy_waker();
+		let mut cx = Context::from_waker(&waker);
+		let mut poll = || Pin::new(&mut poller).poll(&mut cx);
+
+		// Item 1 shares a key with item 0, so has to wait for it to complete.
+		assert!(poll().is_pending());
+		assert_eq!(*started.borrow(), vec![0]);
+
+		outcomes.borrow_mut()[0] = Some(Ok(()));
+		assert!(poll().is_pending());
+		assert_eq!(*started.borrow(), vec![0, 1, 2]);
+
+		// Only two items may be in flight at once.
+		outcomes.borrow_mut()[2] = Some(Ok(()));
+		assert!(poll().is_pending());
+		assert_eq!(*started.borrow(), vec![0, 1, 2, 3]);
+
+		// Item 4 has no keys, so has to wait for all earlier items, and blocks all later ones.
+		outcomes.borrow_mut()[1] = Some(Ok(()));
+		assert!(poll().is_pending());
+		assert_eq!(started.borrow().len(), 4);
+		outcomes.borrow_mut()[3] = Some(Ok(()));
+		assert!(poll().is_pending());
+		assert_eq!(*started.borrow(), vec![0, 1, 2, 3, 4]);
+		outcomes.borrow_mut()[4] = Some(Ok(()));
+		assert!(poll().is_pending());
+		assert_eq!(*started.borrow(), vec![0, 1, 2, 3, 4, 5]);
+
+		outcomes.borrow_mut()[5] = Some(Ok(()));
+		assert_eq!(poll(), Poll::Ready(vec![Ok(()); 6]));
+	}
+
+	#[test]
+	fn ordered_concurrent_poller_stops_on_failure() {
+		let outcomes = RefCell::new(vec![None; 3]);
+		let started = RefCell::new(Vec::new());
+		let handler = |item: usize| {
+			started.borrow_mut().push(item);
+			TestFuture { item, outcomes: &outcomes }
+		};
+		let items = vec![(Some(vec![1]), 0), (Some(vec![2]), 1), (Some(vec![3]), 2)];
+		let mut poller = OrderedConcurrentPoller::new(items, &handler, 2);
+		let waker = dummy_waker();
+		let mut cx = Context::from_waker(&waker);
+		let mut poll = || Pin::new(&mut poller).poll(&mut cx);
+
+		assert!(poll().is_pending());
+		assert_eq!(*started.borrow(), vec![0, 1]);
+
+		// Once item 0 fails, item 2 is never started, but we still wait for item 1.
+		outcomes.borrow_mut()[0] = Some(Err(()));
+		assert!(poll().is_pending());
+		outcomes.borrow_mut()[1] = Some(Ok(()));
+		assert_eq!(poll(), Poll::Ready(vec![Err(()), Ok(())]));
+		assert_eq!(*started.borrow(), vec![0, 1]);
+	}
+}
@@ -626,6 +626,68 @@ pub struct ChannelConfig {
 	/// [`PaymentClaimable::counterparty_skimmed_fee_msat`]: crate::events::Event::PaymentClaimable::counterparty_skimmed_fee_msat
 	//  TODO: link to bLIP when it's merged
 	pub accept_underpaying_htlcs: bool,
+	/// The percentage of the HTLC slots our counterparty allows us to use in this channel which we
+	/// reserve for endorsed HTLCs.
+	///
+	/// HTLCs we forward or send over this channel which are not endorsed may only occupy the
+	/// remaining slots, bounding the number of slots an attacker can jam with slow HTLCs. Note
+	/// that if [`HTLCEndorsementConfig::use_local_reputation`] is set, we only endorse forwarded
+	/// HTLCs if the peer which offered them to us has sufficient local reputation.
+	///
+	/// Values above `100` are treated as `100`.
+	///
+	/// Default value: `0`
+	pub endorsed_htlc_slots_percentage: u8,
+	/// The percentage of the total value of outbound HTLCs our counterparty allows us to have in
+	/// flight in this channel which we reserve for endorsed HTLCs.
+	///
+	/// HTLCs we forward or send over this channel which are not endorsed may only lock up the
+	/// remaining liquidity. See [`Self::endorsed_htlc_slots_percentage`] for when HTLCs are
+	/// endorsed.
+	///
+	/// Values above `100` are treated as `100`.
+	///
+	/// Default value: `0`
+	pub endorsed_liquidity_percentage: u8,
+	/// The minimum number of calls to [`ChannelManager::timer_tick_occurred`] between two
+	/// `update_fee` messages we send to update the feerate of this channel's commitment
+	/// transaction, if we are the channel funder.
+	///
+	/// Limiting the frequency of feerate updates avoids following short-lived spikes in our
+	/// [`FeeEstimator`]'s estimates, which may otherwise cost us balance to cover commitment
+	/// transaction fees we'll never pay. Updates made via
+	/// [`ChannelManager::force_update_channel_fee`] are not limited, but do count as the last
+	/// update.
+	///
+	/// Default value: `0` (no limit)
+	///
+	/// [`ChannelManager::timer_tick_occurred`]: crate::ln::channelmanager::ChannelManager::timer_tick_occurred
+	/// [`ChannelManager::force_update_channel_fee`]: crate::ln::channelmanager::ChannelManager::force_update_channel_fee
+	/// [`FeeEstimator`]: crate::chain::chaininterface::FeeEstimator
+	pub min_update_fee_interval_ticks: u16,
+	/// The maximum percentage by which a single `update_fee` we send may change the feerate of
+	/// this channel's commitment transaction, if we are the channel funder.
+	///
+	/// If our [`FeeEstimator`]'s estimate moved further, we move the feerate towards it over
+	/// several updates, subject to [`Self::min_update_fee_interval_ticks`]. Updates made via
+	/// [`ChannelManager::force_update_channel_fee`] are not limited.
+	///
+	/// Default value: `0` (no limit)
+	///
+	/// [`ChannelManager::force_update_channel_fee`]: crate::ln::channelmanager::ChannelManager::force_update_channel_fee
+	/// [`FeeEstimator`]: crate::chain::chaininterface::FeeEstimator
+	pub max_update_fee_step_percent: u16,
+	/// The maximum feerate, in satoshis per 1000 weight units, we'll automatically update this
+	/// channel's commitment transaction to, if we are the channel funder.
+	///
+	/// Note that capping the feerate may delay the confirmation of our commitment transaction
+	/// should we have to force-close while feerates are high. Updates made via
+	/// [`ChannelManager::force_update_channel_fee`] are not limited.
+	///
+	/// Default value: `0` (no limit)
+	///
+	/// [`ChannelManager::force_update_channel_fee`]: crate::ln::channelmanager::ChannelManager::force_update_channel_fee
+	pub max_update_feerate_sat_per_1000_weight: u32,
 }
 
 impl ChannelConfig {
@@ -653,6 +715,23 @@ impl ChannelConfig {
 		if let Some(accept_underpaying_htlcs) = update.accept_underpaying_htlcs {
 			self.accept_underpaying_htlcs = accept_underpaying_htlcs;
 		}
+		if let Some(endorsed_htlc_slots_percentage) = update.endorsed_htlc_slots_percentage {
+			self.endorsed_htlc_slots_percentage = endorsed_htlc_slots_percentage;
+		}
+		if let Some(endorsed_liquidity_percentage) = update.endorsed_liquidity_percentage {
+			self.endorsed_liquidity_percentage = endorsed_liquidity_percentage;
+		}
+		if let Some(min_update_fee_interval_ticks) = update.min_update_fee_interval_ticks {
+			self.min_update_fee_interval_ticks = min_update_fee_interval_ticks;
+		}
+		if let Some(max_update_fee_step_percent) = update.max_update_fee_step_percent {
+			self.max_update_fee_step_percent = max_update_fee_step_percent;
+		}
+		if let Some(max_update_feerate_sat_per_1000_weight) =
+			update.max_update_feerate_sat_per_1000_weight
+		{
+			self.max_update_feerate_sat_per_1000_weight = max_update_feerate_sat_per_1000_weight;
+		}
 	}
 }
 
@@ -666,6 +745,11 @@ impl Default for ChannelConfig {
 			max_dust_htlc_exposure: MaxDustHTLCExposure::FeeRateMultiplier(10000),
 			force_close_avoidance_max_fee_satoshis: 1000,
 			accept_underpaying_htlcs: false,
+			endorsed_htlc_slots_percentage: 0,
+			endorsed_liquidity_percentage: 0,
+			min_update_fee_interval_ticks: 0,
+			max_update_fee_step_percent: 0,
+			max_update_feerate_sat_per_1000_weight: 0,
 		}
 	}
 }
@@ -682,11 +766,16 @@ impl crate::util::ser::Writeable for ChannelConfig {
 			(2, self.forwarding_fee_base_msat, required),
 			(3, self.max_dust_htlc_exposure, required),
 			(4, self.cltv_expiry_delta, required),
+			(5, self.endorsed_htlc_slots_percentage, (default_value, 0)), // Added in 0.3
 			(6, max_dust_htlc_exposure_msat_fixed_limit, required),
+			(7, self.endorsed_liquidity_percentage, (default_value, 0)), // Added in 0.3
+			(9, self.min_update_fee_interval_ticks, (default_value, 0)), // Added in 0.3
 			// ChannelConfig serialized this field with a required type of 8 prior to the introduction of
 			// LegacyChannelConfig. To make sure that serialization is not compatible with this one, we use
 			// the next required type of 10, which if seen by the old serialization will always fail.
 			(10, self.force_close_avoidance_max_fee_satoshis, required),
+			(11, self.max_update_fee_step_percent, (default_value, 0)), // Added in 0.3
+			(13, self.max_update_feerate_sat_per_1000_weight, (default_value, 0)), // Added in 0.3
 		});
 		Ok(())
 	}
@@ -701,15 +790,25 @@ impl crate::util::ser::Readable for ChannelConfig {
 		let mut max_dust_htlc_exposure_msat = None;
 		let mut max_dust_htlc_exposure_enum = None;
 		let mut force_close_avoidance_max_fee_satoshis = 1000;
+		let mut endorsed_htlc_slots_percentage = 0;
+		let mut endorsed_liquidity_percentage = 0;
+		let mut min_update_fee_interval_ticks = 0;
+		let mut max_update_fee_step_percent = 0;
+		let mut max_update_feerate_sat_per_1000_weight = 0;
 		read_tlv_fields!(reader, {
 			(0, forwarding_fee_proportional_millionths, required),
 			(1, accept_underpaying_htlcs, (default_value, false)),
 			(2, forwarding_fee_base_msat, required),
 			(3, max_dust_htlc_exposure_enum, option),
 			(4, cltv_expiry_delta, required),
+			(5, endorsed_htlc_slots_percentage, (default_value, 0u8)),
 			// Has always been written, but became optionally read in 0.0.116
 			(6, max_dust_htlc_exposure_msat, option),
+			(7, endorsed_liquidity_percentage, (default_value, 0u8)),
+			(9, min_update_fee_interval_ticks, (default_value, 0u16)),
 			(10, force_close_avoidance_max_fee_satoshis, required),
+			(11, max_update_fee_step_percent, (default_value, 0u16)),
+			(13, max_update_feerate_sat_per_1000_weight, (default_value, 0u32)),
 		});
 		let max_dust_htlc_fixed_limit = max_dust_htlc_exposure_msat.unwrap_or(5_000_000);
 		let max_dust_htlc_exposure_msat = max_dust_htlc_exposure_enum
@@ -721,6 +820,11 @@ impl crate::util::ser::Readable for ChannelConfig {
 			cltv_expiry_delta,
 			max_dust_htlc_exposure: max_dust_htlc_exposure_msat,
 			force_close_avoidance_max_fee_satoshis,
+			endorsed_htlc_slots_percentage,
+			endorsed_liquidity_percentage,
+			min_update_fee_interval_ticks,
+			max_update_fee_step_percent,
+			max_update_feerate_sat_per_1000_weight,
 		})
 	}
 }
@@ -750,6 +854,26 @@ pub struct ChannelConfigUpdate {
 	/// If set, allows this channel's counterparty to skim an additional fee off this node's inbound HTLCs. See
 	/// [`ChannelConfig::accept_underpaying_htlcs`].
 	pub accept_underpaying_htlcs: Option<bool>,
+
+	/// The percentage of HTLC slots reserved for endorsed HTLCs. See
+	/// [`ChannelConfig::endorsed_htlc_slots_percentage`].
+	pub endorsed_htlc_slots_percentage: Option<u8>,
+
+	/// The percentage of outbound liquidity reserved for endorsed HTLCs. See
+	/// [`ChannelConfig::endorsed_liquidity_percentage`].
+	pub endorsed_liquidity_percentage: Option<u8>,
+
+	/// The minimum number of timer ticks between two `update_fee` messages we send. See
+	/// [`ChannelConfig::min_update_fee_interval_ticks`].
+	pub min_update_fee_interval_ticks: Option<u16>,
+
+	/// The maximum percentage by which a single `update_fee` we send may change the feerate. See
+	/// [`ChannelConfig::max_update_fee_step_percent`].
+	pub max_update_fee_step_percent: Option<u16>,
+
+	/// The maximum feerate we'll automatically update the commitment transaction to. See
+	/// [`ChannelConfig::max_update_feerate_sat_per_1000_weight`].
+	pub max_update_feerate_sat_per_1000_weight: Option<u32>,
 }
 
 impl From<ChannelConfig> for ChannelConfigUpdate {
@@ -765,6 +889,13 @@ impl From<ChannelConfig> for ChannelConfigUpdate {
 				config.force_close_avoidance_max_fee_satoshis,
 			),
 			accept_underpaying_htlcs: Some(config.accept_underpaying_htlcs),
+			endorsed_htlc_slots_percentage: Some(config.endorsed_htlc_slots_percentage),
+			endorsed_liquidity_percentage: Some(config.endorsed_liquidity_percentage),
+			min_update_fee_interval_ticks: Some(config.min_update_fee_interval_ticks),
+			max_update_fee_step_percent: Some(config.max_update_fee_step_percent),
+			max_update_feerate_sat_per_1000_weight: Some(
+				config.max_update_feerate_sat_per_1000_weight,
+			),
 		}
 	}
 }
@@ -808,7 +939,12 @@ impl crate::util::ser::Writeable for LegacyChannelConfig {
 			(4, self.announce_for_forwarding, required),
 			(5, self.options.max_dust_htlc_exposure, required),
 			(6, self.commit_upfront_shutdown_pubkey, required),
+			(7, self.options.endorsed_htlc_slots_percentage, (default_value, 0)), // Added in 0.3
 			(8, self.options.forwarding_fee_base_msat, required),
+			(9, self.options.endorsed_liquidity_percentage, (default_value, 0)), // Added in 0.3
+			(11, self.options.min_update_fee_interval_ticks, (default_value, 0)), // Added in 0.3
+			(13, self.options.max_update_fee_step_percent, (default_value, 0)), // Added in 0.3
+			(15, self.options.max_update_feerate_sat_per_1000_weight, (default_value, 0)), // Added in 0.3
 		});
 		Ok(())
 	}
@@ -824,6 +960,11 @@ impl crate::util::ser::Readable for LegacyChannelConfig {
 		let mut commit_upfront_shutdown_pubkey = false;
 		let mut forwarding_fee_base_msat = 0;
 		let mut max_dust_htlc_exposure_enum = None;
+		let mut endorsed_htlc_slots_percentage = 0;
+		let mut endorsed_liquidity_percentage = 0;
+		let mut min_update_fee_interval_ticks = 0;
+		let mut max_update_fee_step_percent = 0;
+		let mut max_update_feerate_sat_per_1000_weight = 0;
 		read_tlv_fields!(reader, {
 			(0, forwarding_fee_proportional_millionths, required),
 			// Has always been written, but became optionally read in 0.0.116
@@ -833,7 +974,12 @@ impl crate::util::ser::Readable for LegacyChannelConfig {
 			(4, announce_for_forwarding, required),
 			(5, max_dust_htlc_exposure_enum, option),
 			(6, commit_upfront_shutdown_pubkey, required),
+			(7, endorsed_htlc_slots_percentage, (default_value, 0u8)),
 			(8, forwarding_fee_base_msat, required),
+			(9, endorsed_liquidity_percentage, (default_value, 0u8)),
+			(11, min_update_fee_interval_ticks, (default_value, 0u16)),
+			(13, max_update_fee_step_percent, (default_value, 0u16)),
+			(15, max_update_feerate_sat_per_1000_weight, (default_value, 0u32)),
 		});
 		let max_dust_htlc_exposure_msat_fixed_limit =
 			max_dust_htlc_exposure_msat_fixed_limit.unwrap_or(5_000_000);
@@ -848,6 +994,11 @@ impl crate::util::ser::Readable for LegacyChannelConfig {
 				force_close_avoidance_max_fee_satoshis,
 				forwarding_fee_base_msat,
 				accept_underpaying_htlcs: false,
+				endorsed_htlc_slots_percentage,
+				endorsed_liquidity_percentage,
+				min_update_fee_interval_ticks,
+				max_update_fee_step_percent,
+				max_update_feerate_sat_per_1000_weight,
 			},
 			announce_for_forwarding,
 			commit_upfront_shutdown_pubkey,
@@ -855,6 +1006,67 @@ impl crate::util::ser::Readable for LegacyChannelConfig {
 	}
 }
 
+/// Options for the experimental HTLC endorsement signal described in [bLIP 4] and the local
+/// reputation we track for the peers forwarding HTLCs through us, which together help mitigate
+/// channel jamming.
+///
+/// See the [`local_reputation`] module documentation for how reputation is tracked.
+///
+/// [bLIP 4]: https://github.com/lightning/blips/blob/master/blip-0004.md
+/// [`local_reputation`]: crate::ln::local_reputation
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct HTLCEndorsementConfig {
+	/// If this is set to `true`, the HTLCs of payments we send are endorsed.
+	///
+	/// Default value: `false`
+	pub endorse_sent_payments: bool,
+	/// If this is set to `true`, we only endorse an HTLC we forward if the previous hop endorsed
+	/// it and has sufficient local reputation for it. Otherwise, the endorsement signal of the
+	/// previous hop is relayed unchanged.
+	///
+	/// Default value: `false`
+	pub use_local_reputation: bool,
+	/// The time, in seconds, within which we expect HTLCs we forward to be resolved.
+	///
+	/// Each additional period an HTLC endorsed by the previous hop takes to resolve costs it as
+	/// much reputation as the fee we'd have earned from the HTLC. Further, the risk of forwarding
+	/// an HTLC is assessed as the fees we'd lose if it was held for this period for each block
+	/// until it expires.
+	///
+	/// Default value: `90`
+	pub resolution_period_secs: u64,
+	/// The time, in seconds, after which a peer's reputation has decayed to half its value.
+	///
+	/// Default value: `1209600` (two weeks)
+	pub reputation_half_life_secs: u64,
+}
+
+impl Default for HTLCEndorsementConfig {
+	fn default() -> Self {
+		HTLCEndorsementConfig {
+			endorse_sent_payments: false,
+			use_local_reputation: false,
+			resolution_period_secs: 90,
+			reputation_half_life_secs: 60 * 60 * 24 * 14,
+		}
+	}
+}
+
+// When fuzzing, we want to allow the fuzzer to pick any configuration parameters. Thus, we
+// implement Readable here in a naive way (which is a bit easier for the fuzzer to handle). We
+// don't really want to ever expose this to users (if we did we'd want to use TLVs).
+#[cfg(fuzzing)]
+impl Readable for HTLCEndorsementConfig {
+	fn read<R: crate::io::Read>(reader: &mut R) -> Result<Self, crate::ln::msgs::DecodeError> {
+		Ok(Self {
+			endorse_sent_payments: Readable::read(reader)?,
+			use_local_reputation: Readable::read(reader)?,
+			resolution_period_secs: Readable::read(reader)?,
+			reputation_half_life_secs: Readable::read(reader)?,
+		})
+	}
+}
+
 /// Top-level config which holds ChannelHandshakeLimits and ChannelConfig.
 ///
 /// `Default::default()` provides sane defaults for most configurations
@@ -918,6 +1130,20 @@ pub struct UserConfig {
 	/// [`ChannelManager::get_intercept_scid`]: crate::ln::channelmanager::ChannelManager::get_intercept_scid
 	/// [`Event::HTLCIntercepted`]: crate::events::Event::HTLCIntercepted
 	pub accept_intercept_htlcs: bool,
+	/// If this is set to `true`, we will forward HTLCs carrying a Trampoline onion which instructs
+	/// us to relay the payment to another Trampoline node or the final recipient. We find a route to
+	/// the next node using our own [`Router`] and re-wrap the inner Trampoline onion in a fresh
+	/// outer onion.
+	///
+	/// The difference between the incoming HTLC and the amount and CLTV expiry the sender asked us
+	/// to deliver is the budget we have for the route. It must cover the fees and CLTV deltas of the
+	/// route we find as well as our own fee and CLTV delta as configured in
+	/// [`Self::channel_config`], otherwise the HTLC is failed back.
+	///
+	/// Default value: `false`
+	///
+	/// [`Router`]: crate::routing::router::Router
+	pub accept_trampoline_forwards: bool,
 	/// If this is set to `true`, the user needs to manually pay [`Bolt12Invoice`]s when received.
 	///
 	/// When set to `true`, [`Event::InvoiceReceived`] will be generated for each received
@@ -973,6 +1199,66 @@ pub struct UserConfig {
 	///
 	/// [`ChannelManager::splice_channel`]: crate::ln::channelmanager::ChannelManager::splice_channel
 	pub reject_inbound_splices: bool,
+	/// The number of blocks we'll wait for the funding transaction of a channel to re-confirm if it
+	/// is un-confirmed by a chain reorganization after the channel became usable.
+	///
+	/// While waiting, the channel is not used to send or forward HTLCs and an
+	/// [`Event::ChannelFundingUnconfirmed`] is generated. If the funding transaction re-confirms
+	/// within this many blocks an [`Event::ChannelFundingReconfirmed`] is generated and the channel
+	/// is usable again, otherwise it is force-closed.
+	///
+	/// If this is set to `0`, the channel is force-closed as soon as its funding transaction is
+	/// un-confirmed. Note that this does not apply to 0-conf channels, which are never closed due
+	/// to their funding transaction being un-confirmed.
+	///
+	/// Default value: `0`
+	///
+	/// [`Event::ChannelFundingUnconfirmed`]: crate::events::Event::ChannelFundingUnconfirmed
+	/// [`Event::ChannelFundingReconfirmed`]: crate::events::Event::ChannelFundingReconfirmed
+	pub funding_reorg_grace_period_blocks: u32,
+	/// If this is set to `true`, an [`Event::ChannelLifecycleStateChanged`] is generated whenever
+	/// a channel moves to a different [`ChannelLifecycleState`], allowing external tooling to
+	/// follow the channel state machine. See also [`ChannelManager::channel_lifecycle_info`].
+	///
+	/// Note that changes are detected when events are processed, so a channel which moves through
+	/// several states in between will only have its latest state reported.
+	///
+	/// Default value: `false`
+	///
+	/// [`Event::ChannelLifecycleStateChanged`]: crate::events::Event::ChannelLifecycleStateChanged
+	/// [`ChannelLifecycleState`]: crate::ln::channel_state::ChannelLifecycleState
+	/// [`ChannelManager::channel_lifecycle_info`]: crate::ln::channelmanager::ChannelManager::channel_lifecycle_info
+	pub emit_channel_lifecycle_events: bool,
+	/// The number of consecutive messages from a peer which we fail to handle (i.e., reject with a
+	/// warning or error) after which we quarantine the peer, or `0` to never do so.
+	///
+	/// A quarantined peer is disconnected and refused reconnection, isolating it and its channels
+	/// if handling its messages fails persistently, e.g., due to a local persistence bug. An
+	/// [`Event::PeerQuarantined`] is generated when a peer is quarantined, after which the
+	/// operator may investigate and call [`ChannelManager::release_quarantined_peer`] to let the
+	/// peer reconnect and retry.
+	///
+	/// Default value: `0`
+	///
+	/// [`Event::PeerQuarantined`]: crate::events::Event::PeerQuarantined
+	/// [`ChannelManager::release_quarantined_peer`]: crate::ln::channelmanager::ChannelManager::release_quarantined_peer
+	pub quarantine_after_message_failures: u8,
+	/// If this is set to `true`, once the parts of an inbound multi-part payment add up to the
+	/// amount the sender intends to pay, any parts not needed to reach it are failed back rather
+	/// than claimed along with the others.
+	///
+	/// This allows senders to speed up payments by sending redundant parts in excess of the
+	/// amount due, see [`PaymentParameters::redundant_path_count`], without overpaying us. Excess
+	/// parts are failed back with an `mpp_timeout` error, indicating to the sender that the
+	/// failure is not permanent.
+	///
+	/// Default value: `false`
+	///
+	/// [`PaymentParameters::redundant_path_count`]: crate::routing::router::PaymentParameters::redundant_path_count
+	pub fail_back_excess_mpp_htlcs: bool,
+	/// Options for the experimental HTLC endorsement signal and the local reputation we track for
+	/// our peers to decide which HTLCs we endorse.
+	pub htlc_endorsement_config: HTLCEndorsementConfig,
 }
 
 impl Default for UserConfig {
@@ -985,11 +1271,17 @@ impl Default for UserConfig {
 			accept_inbound_channels: true,
 			manually_accept_inbound_channels: false,
 			accept_intercept_htlcs: false,
+			accept_trampoline_forwards: false,
 			manually_handle_bolt12_invoices: false,
 			enable_dual_funded_channels: false,
 			enable_htlc_hold: false,
 			hold_outbound_htlcs_at_next_hop: false,
 			reject_inbound_splices: true,
+			funding_reorg_grace_period_blocks: 0,
+			emit_channel_lifecycle_events: false,
+			quarantine_after_message_failures: 0,
+			fail_back_excess_mpp_htlcs: false,
+			htlc_endorsement_config: HTLCEndorsementConfig::default(),
 		}
 	}
 }
@@ -1008,11 +1300,17 @@ impl Readable for UserConfig {
 			accept_inbound_channels: Readable::read(reader)?,
 			manually_accept_inbound_channels: Readable::read(reader)?,
 			accept_intercept_htlcs: Readable::read(reader)?,
+			accept_trampoline_forwards: Readable::read(reader)?,
 			manually_handle_bolt12_invoices: Readable::read(reader)?,
 			enable_dual_funded_channels: Readable::read(reader)?,
 			hold_outbound_htlcs_at_next_hop: Readable::read(reader)?,
 			enable_htlc_hold: Readable::read(reader)?,
 			reject_inbound_splices: Readable::read(reader)?,
+			funding_reorg_grace_period_blocks: Readable::read(reader)?,
+			emit_channel_lifecycle_events: Readable::read(reader)?,
+			quarantine_after_message_failures: Readable::read(reader)?,
+			fail_back_excess_mpp_htlcs: Readable::read(reader)?,
+			htlc_endorsement_config: Readable::read(reader)?,
 		})
 	}
 }
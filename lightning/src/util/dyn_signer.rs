@@ -61,65 +61,36 @@ impl DynSigner {
 }
 
 #[cfg(taproot)]
-#[allow(unused_variables)]
-impl TaprootChannelSigner for DynSigner {
-	fn generate_local_nonce_pair(
-		&self, commitment_number: u64, secp_ctx: &Secp256k1<All>,
-	) -> PublicNonce {
-		todo!()
-	}
-
-	fn partially_sign_counterparty_commitment(
-		&self, counterparty_nonce: PublicNonce, commitment_tx: &CommitmentTransaction,
-		inbound_htlc_preimages: Vec<PaymentPreimage>,
-		outbound_htlc_preimages: Vec<PaymentPreimage>, secp_ctx: &Secp256k1<All>,
-	) -> Result<(crate::ln::msgs::PartialSignatureWithNonce, Vec<secp256k1::schnorr::Signature>), ()>
-	{
-		todo!();
-	}
-
-	fn finalize_holder_commitment(
-		&self, commitment_tx: &HolderCommitmentTransaction,
+delegate!(DynSigner, TaprootChannelSigner, inner,
+	fn generate_local_nonce_pair(, commitment_number: u64,
+		secp_ctx: &Secp256k1<All>) -> PublicNonce,
+	fn partially_sign_counterparty_commitment(, channel_parameters: &ChannelTransactionParameters,
+		counterparty_nonce: PublicNonce,
+		commitment_tx: &CommitmentTransaction, inbound_htlc_preimages: Vec<PaymentPreimage>,
+		outbound_htlc_preimages: Vec<PaymentPreimage>, secp_ctx: &Secp256k1<All>
+	) -> Result<(crate::ln::msgs::PartialSignatureWithNonce, Vec<secp256k1::schnorr::Signature>), ()>,
+	fn finalize_holder_commitment(, channel_parameters: &ChannelTransactionParameters,
+		commitment_tx: &HolderCommitmentTransaction,
 		counterparty_partial_signature: crate::ln::msgs::PartialSignatureWithNonce,
-		secp_ctx: &Secp256k1<All>,
-	) -> Result<PartialSignature, ()> {
-		todo!();
-	}
-
-	fn sign_justice_revoked_output(
-		&self, justice_tx: &Transaction, input: usize, amount: u64, per_commitment_key: &SecretKey,
-		secp_ctx: &Secp256k1<All>,
-	) -> Result<secp256k1::schnorr::Signature, ()> {
-		todo!();
-	}
-
-	fn sign_justice_revoked_htlc(
-		&self, justice_tx: &Transaction, input: usize, amount: u64, per_commitment_key: &SecretKey,
-		htlc: &HTLCOutputInCommitment, secp_ctx: &Secp256k1<All>,
-	) -> Result<secp256k1::schnorr::Signature, ()> {
-		todo!();
-	}
-
-	fn sign_holder_htlc_transaction(
-		&self, htlc_tx: &Transaction, input: usize, htlc_descriptor: &HTLCDescriptor,
-		secp_ctx: &Secp256k1<All>,
-	) -> Result<secp256k1::schnorr::Signature, ()> {
-		todo!();
-	}
-
-	fn sign_counterparty_htlc_transaction(
-		&self, htlc_tx: &Transaction, input: usize, amount: u64, per_commitment_point: &PublicKey,
-		htlc: &HTLCOutputInCommitment, secp_ctx: &Secp256k1<All>,
-	) -> Result<secp256k1::schnorr::Signature, ()> {
-		todo!();
-	}
-
-	fn partially_sign_closing_transaction(
-		&self, closing_tx: &ClosingTransaction, secp_ctx: &Secp256k1<All>,
-	) -> Result<PartialSignature, ()> {
-		todo!();
-	}
-}
+		secp_ctx: &Secp256k1<All>) -> Result<PartialSignature, ()>,
+	fn sign_justice_revoked_output(, channel_parameters: &ChannelTransactionParameters,
+		justice_tx: &Transaction, input: usize, amount: u64, per_commitment_key: &SecretKey,
+		secp_ctx: &Secp256k1<All>) -> Result<secp256k1::schnorr::Signature, ()>,
+	fn sign_justice_revoked_htlc(, channel_parameters: &ChannelTransactionParameters,
+		justice_tx: &Transaction, input: usize, amount: u64,
+		per_commitment_key: &SecretKey, htlc: &HTLCOutputInCommitment,
+		secp_ctx: &Secp256k1<All>) -> Result<secp256k1::schnorr::Signature, ()>,
+	fn sign_holder_htlc_transaction(, htlc_tx: &Transaction, input: usize,
+		htlc_descriptor: &HTLCDescriptor,
+		secp_ctx: &Secp256k1<All>) -> Result<secp256k1::schnorr::Signature, ()>,
+	fn sign_counterparty_htlc_transaction(, channel_parameters: &ChannelTransactionParameters,
+		htlc_tx: &Transaction, input: usize, amount: u64,
+		per_commitment_point: &PublicKey, htlc: &HTLCOutputInCommitment,
+		secp_ctx: &Secp256k1<All>) -> Result<secp256k1::schnorr::Signature, ()>,
+	fn partially_sign_closing_transaction(, channel_parameters: &ChannelTransactionParameters,
+		counterparty_nonce: PublicNonce, closing_tx: &ClosingTransaction,
+		secp_ctx: &Secp256k1<All>) -> Result<crate::ln::msgs::PartialSignatureWithNonce, ()>
+);
 
 impl Clone for DynSigner {
 	fn clone(&self) -> Self {
@@ -213,6 +184,7 @@ delegate!(DynKeysInterface, NodeSigner,
 inner,
 	fn get_node_id(, recipient: Recipient) -> Result<PublicKey, ()>,
 	fn sign_gossip_message(, msg: UnsignedGossipMessage) -> Result<Signature, ()>,
+	fn sign_gossip_messages(, msgs: &[UnsignedGossipMessage]) -> Result<Vec<Signature>, ()>,
 	fn sign_message(, msg: &[u8]) -> Result<String, ()>,
 	fn ecdh(, recipient: Recipient, other_key: &PublicKey, tweak: Option<&Scalar>) -> Result<SharedSecret, ()>,
 	fn sign_invoice(, invoice: &RawBolt11Invoice, recipient: Recipient) -> Result<RecoverableSignature, ()>,
@@ -281,6 +253,7 @@ delegate!(DynPhantomKeysInterface, NodeSigner,
 	inner,
 	fn get_node_id(, recipient: Recipient) -> Result<PublicKey, ()>,
 	fn sign_gossip_message(, msg: UnsignedGossipMessage) -> Result<Signature, ()>,
+	fn sign_gossip_messages(, msgs: &[UnsignedGossipMessage]) -> Result<Vec<Signature>, ()>,
 	fn sign_message(, msg: &[u8]) -> Result<String, ()>,
 	fn ecdh(, recipient: Recipient, other_key: &PublicKey, tweak: Option<&Scalar>) -> Result<SharedSecret, ()>,
 	fn sign_invoice(, invoice: &RawBolt11Invoice, recipient: Recipient) -> Result<RecoverableSignature, ()>,
@@ -0,0 +1,330 @@
+// This file is Copyright its original authors, visible in version control
+// history.
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Contains [`EncryptedKVStore`], a wrapper which encrypts all values written to an inner
+//! [`KVStore`] or [`KVStoreSync`].
+
+use bitcoin::hashes::sha256::Hash as Sha256;
+use bitcoin::hashes::{Hash, HashEngine, Hmac, HmacEngine};
+
+use core::future::Future;
+use core::ops::Deref;
+
+use crate::crypto::chacha20poly1305rfc::ChaCha20Poly1305RFC;
+use crate::io;
+use crate::prelude::*;
+use crate::sign::{EntropySource, NodeSigner};
+use crate::util::async_poll::MaybeSend;
+use crate::util::persist::{KVStore, KVStoreSync, MigratableKVStore};
+
+// The label used to derive the storage encryption key from the `NodeSigner`'s peer storage key,
+// ensuring we never use the same key for peer storage and local storage.
+const ENCRYPTION_KEY_LABEL: &[u8] = b"LDK KVStore encryption key";
+
+// Values are stored as random_bytes(32 bytes) + encrypted_data + tag(16 bytes).
+const RANDOM_BYTES_LEN: usize = 32;
+const TAG_LEN: usize = 16;
+
+/// A [`KVStore`] and [`KVStoreSync`] wrapper which transparently encrypts all values before
+/// writing them to the inner store and decrypts them again on read.
+///
+/// Values are encrypted using ChaCha20-Poly1305 with a key derived from the [`NodeSigner`]'s
+/// [`NodeSigner::get_peer_storage_key`] (or a key provided via [`EncryptedKVStore::new_with_key`]).
+/// Each write uses a fresh nonce derived from random bytes stored alongside the ciphertext and the
+/// namespaces and key the value is written under. The namespaces and key are further
+/// authenticated, so a value which was moved to a different key by the storage provider will fail
+/// to decrypt rather than being silently accepted.
+///
+/// Note that only values are encrypted - namespaces and keys (which may include channel funding
+/// outpoints or channel ids) as well as the size of each value remain visible to the inner store.
+///
+/// Data written through an [`EncryptedKVStore`] can only be read back through an
+/// [`EncryptedKVStore`] using the same key, i.e., the same node secret. Existing plaintext data
+/// can be migrated by reading it from the inner store and writing it through the wrapper, e.g., via
+/// [`migrate_kv_store_data`].
+///
+/// [`migrate_kv_store_data`]: crate::util::persist::migrate_kv_store_data
+pub struct EncryptedKVStore<K, ES: Deref>
+where
+	ES::Target: EntropySource,
+{
+	inner: K,
+	encryption_key: [u8; 32],
+	entropy_source: ES,
+}
+
+impl<K, ES: Deref> EncryptedKVStore<K, ES>
+where
+	ES::Target: EntropySource,
+{
+	/// Wraps the given `inner` store, encrypting values with a key derived from the given
+	/// `node_signer`.
+	///
+	/// `entropy_source` is used to generate the random bytes from which each write's nonce is
+	/// derived.
+	pub fn new<NS: Deref>(inner: K, node_signer: NS, entropy_source: ES) -> Self
+	where
+		NS::Target: NodeSigner,
+	{
+		let peer_storage_key = node_signer.get_peer_storage_key();
+		let mut hmac = HmacEngine::<Sha256>::new(&peer_storage_key.inner);
+		hmac.input(ENCRYPTION_KEY_LABEL);
+		let encryption_key = Hmac::from_engine(hmac).to_byte_array();
+		Self::new_with_key(inner, encryption_key, entropy_source)
+	}
+
+	/// Wraps the given `inner` store, encrypting values with the given `encryption_key`.
+	///
+	/// The `encryption_key` must be kept secret and must be the same every time the store is
+	/// constructed, otherwise previously written data cannot be read.
+	pub fn new_with_key(inner: K, encryption_key: [u8; 32], entropy_source: ES) -> Self {
+		Self { inner, encryption_key, entropy_source }
+	}
+
+	/// Returns a reference to the inner store.
+	pub fn inner(&self) -> &K {
+		&self.inner
+	}
+
+	fn encrypt(
+		&self, primary_namespace: &str, secondary_namespace: &str, key: &str, mut buf: Vec<u8>,
+	) -> Vec<u8> {
+		let random_bytes = self.entropy_source.get_secure_random_bytes();
+		let nonce = derive_nonce(
+			&self.encryption_key,
+			primary_namespace,
+			secondary_namespace,
+			key,
+			&random_bytes,
+		);
+		let aad = location_aad(primary_namespace, secondary_namespace, key);
+
+		let mut chacha = ChaCha20Poly1305RFC::new(&self.encryption_key, &nonce, &aad);
+		let mut tag = [0; TAG_LEN];
+		chacha.encrypt_full_message_in_place(&mut buf[..], &mut tag);
+
+		let mut res = Vec::with_capacity(RANDOM_BYTES_LEN + buf.len() + TAG_LEN);
+		res.extend_from_slice(&random_bytes);
+		res.extend_from_slice(&buf);
+		res.extend_from_slice(&tag);
+		res
+	}
+}
+
+fn decrypt(
+	encryption_key: &[u8; 32], primary_namespace: &str, secondary_namespace: &str, key: &str,
+	mut buf: Vec<u8>,
+) -> Result<Vec<u8>, io::Error> {
+	if buf.len() < RANDOM_BYTES_LEN + TAG_LEN {
+		return Err(io::Error::new(
+			io::ErrorKind::InvalidData,
+			"Encrypted value is too short to be valid",
+		));
+	}
+
+	let buf_len = buf.len();
+	let (random_bytes, data_mut) = buf.split_at_mut(RANDOM_BYTES_LEN);
+	let (encrypted_data, tag) = data_mut.split_at_mut(buf_len - RANDOM_BYTES_LEN - TAG_LEN);
+	let nonce =
+		derive_nonce(encryption_key, primary_namespace, secondary_namespace, key, random_bytes);
+	let aad = location_aad(primary_namespace, secondary_namespace, key);
+
+	let mut chacha = ChaCha20Poly1305RFC::new(encryption_key, &nonce, &aad);
+	if chacha.check_decrypt_in_place(encrypted_data, tag).is_err() {
+		return Err(io::Error::new(io::ErrorKind::InvalidData, "Failed to decrypt stored value"));
+	}
+
+	buf.truncate(buf_len - TAG_LEN);
+	buf.drain(..RANDOM_BYTES_LEN);
+	Ok(buf)
+}
+
+/// Returns the additional authenticated data committing to the location a value is stored at.
+///
+/// Namespaces and keys can't contain `/`, so the concatenation is unambiguous.
+fn location_aad(primary_namespace: &str, secondary_namespace: &str, key: &str) -> Vec<u8> {
+	let mut aad =
+		Vec::with_capacity(primary_namespace.len() + secondary_namespace.len() + key.len() + 2);
+	aad.extend_from_slice(primary_namespace.as_bytes());
+	aad.push(b'/');
+	aad.extend_from_slice(secondary_namespace.as_bytes());
+	aad.push(b'/');
+	aad.extend_from_slice(key.as_bytes());
+	aad
+}
+
+/// Nonce for encryption and decryption: Hmac(Sha256(key), location + random_bytes).
+fn derive_nonce(
+	encryption_key: &[u8; 32], primary_namespace: &str, secondary_namespace: &str, key: &str,
+	random_bytes: &[u8],
+) -> [u8; 12] {
+	let key_hash = Sha256::hash(encryption_key);
+
+	let mut hmac = HmacEngine::<Sha256>::new(key_hash.as_byte_array());
+	hmac.input(&location_aad(primary_namespace, secondary_namespace, key));
+	hmac.input(random_bytes);
+	let mut nonce = [0u8; 12];
+	// First 4 bytes of the nonce should be 0.
+	nonce[4..].copy_from_slice(&Hmac::from_engine(hmac).to_byte_array()[0..8]);
+
+	nonce
+}
+
+impl<K: KVStoreSync, ES: Deref> KVStoreSync for EncryptedKVStore<K, ES>
+where
+	ES::Target: EntropySource,
+{
+	fn read(
+		&self, primary_namespace: &str, secondary_namespace: &str, key: &str,
+	) -> Result<Vec<u8>, io::Error> {
+		let encrypted = self.inner.read(primary_namespace, secondary_namespace, key)?;
+		decrypt(&self.encryption_key, primary_namespace, secondary_namespace, key, encrypted)
+	}
+
+	fn write(
+		&self, primary_namespace: &str, secondary_namespace: &str, key: &str, buf: Vec<u8>,
+	) -> Result<(), io::Error> {
+		let encrypted = self.encrypt(primary_namespace, secondary_namespace, key, buf);
+		self.inner.write(primary_namespace, secondary_namespace, key, encrypted)
+	}
+
+	fn remove(
+		&self, primary_namespace: &str, secondary_namespace: &str, key: &str, lazy: bool,
+	) -> Result<(), io::Error> {
+		self.inner.remove(primary_namespace, secondary_namespace, key, lazy)
+	}
+
+	fn list(
+		&self, primary_namespace: &str, secondary_namespace: &str,
+	) -> Result<Vec<String>, io::Error> {
+		self.inner.list(primary_namespace, secondary_namespace)
+	}
+}
+
+/// This is not exported to bindings users as async is only supported in Rust.
+impl<K: KVStore, ES: Deref> KVStore for EncryptedKVStore<K, ES>
+where
+	ES::Target: EntropySource,
+{
+	fn read(
+		&self, primary_namespace: &str, secondary_namespace: &str, key: &str,
+	) -> impl Future<Output = Result<Vec<u8>, io::Error>> + 'static + MaybeSend {
+		let fut = self.inner.read(primary_namespace, secondary_namespace, key);
+		let encryption_key = self.encryption_key;
+		let primary_namespace = primary_namespace.to_string();
+		let secondary_namespace = secondary_namespace.to_string();
+		let key = key.to_string();
+
+		async move {
+			let encrypted = fut.await?;
+			decrypt(&encryption_key, &primary_namespace, &secondary_namespace, &key, encrypted)
+		}
+	}
+
+	fn write(
+		&self, primary_namespace: &str, secondary_namespace: &str, key: &str, buf: Vec<u8>,
+	) -> impl Future<Output = Result<(), io::Error>> + 'static + MaybeSend {
+		// Encrypt synchronously so that the inner store sees writes in the order they were made.
+		let encrypted = self.encrypt(primary_namespace, secondary_namespace, key, buf);
+		self.inner.write(primary_namespace, secondary_namespace, key, encrypted)
+	}
+
+	fn remove(
+		&self, primary_namespace: &str, secondary_namespace: &str, key: &str, lazy: bool,
+	) -> impl Future<Output = Result<(), io::Error>> + 'static + MaybeSend {
+		self.inner.remove(primary_namespace, secondary_namespace, key, lazy)
+	}
+
+	fn list(
+		&self, primary_namespace: &str, secondary_namespace: &str,
+	) -> impl Future<Output = Result<Vec<String>, io::Error>> + 'static + MaybeSend {
+		self.inner.list(primary_namespace, secondary_namespace)
+	}
+}
+
+impl<K: MigratableKVStore, ES: Deref> MigratableKVStore for EncryptedKVStore<K, ES>
+where
+	ES::Target: EntropySource,
+{
+	fn list_all_keys(&self) -> Result<Vec<(String, String, String)>, io::Error> {
+		self.inner.list_all_keys()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::sign::{KeysManager, RandomBytes};
+	use crate::util::test_utils::TestStore;
+
+	#[test]
+	fn encrypts_and_authenticates_values() {
+		let keys_manager = KeysManager::new(&[42; 32], 42, 42, true);
+		let entropy_source = RandomBytes::new([43; 32]);
+		let store = EncryptedKVStore::new(TestStore::new(false), &keys_manager, &entropy_source);
+
+		let data = vec![42u8; 32];
+		KVStoreSync::write(&store, "testspace", "testsubspace", "testkey", data.clone()).unwrap();
+		assert_eq!(
+			KVStoreSync::read(&store, "testspace", "testsubspace", "testkey").unwrap(),
+			data
+		);
+		assert_eq!(
+			KVStoreSync::list(&store, "testspace", "testsubspace").unwrap(),
+			vec!["testkey".to_string()]
+		);
+
+		// The inner store only ever sees the ciphertext.
+		let encrypted =
+			KVStoreSync::read(store.inner(), "testspace", "testsubspace", "testkey").unwrap();
+		assert_eq!(encrypted.len(), data.len() + RANDOM_BYTES_LEN + TAG_LEN);
+		assert!(!encrypted.windows(data.len()).any(|w| w == &data[..]));
+
+		// Writing the same value again uses a fresh nonce.
+		KVStoreSync::write(&store, "testspace", "testsubspace", "testkey", data.clone()).unwrap();
+		let reencrypted =
+			KVStoreSync::read(store.inner(), "testspace", "testsubspace", "testkey").unwrap();
+		assert_ne!(encrypted, reencrypted);
+
+		// A value moved to a different key fails to decrypt.
+		KVStoreSync::write(store.inner(), "testspace", "testsubspace", "otherkey", encrypted)
+			.unwrap();
+		assert!(KVStoreSync::read(&store, "testspace", "testsubspace", "otherkey").is_err());
+
+		// As does a tampered value.
+		let mut tampered = reencrypted.clone();
+		tampered[RANDOM_BYTES_LEN] ^= 1;
+		KVStoreSync::write(store.inner(), "testspace", "testsubspace", "testkey", tampered)
+			.unwrap();
+		assert!(KVStoreSync::read(&store, "testspace", "testsubspace", "testkey").is_err());
+
+		// As does a truncated value.
+		KVStoreSync::write(store.inner(), "testspace", "testsubspace", "testkey", vec![0; 47])
+			.unwrap();
+		assert!(KVStoreSync::read(&store, "testspace", "testsubspace", "testkey").is_err());
+
+		// And a value written with a different node's key.
+		let other_keys_manager = KeysManager::new(&[44; 32], 42, 42, true);
+		let other_store =
+			EncryptedKVStore::new(TestStore::new(false), &other_keys_manager, &entropy_source);
+		KVStoreSync::write(&other_store, "testspace", "", "testkey", data.clone()).unwrap();
+		let other_encrypted = KVStoreSync::read(other_store.inner(), "testspace", "", "testkey");
+		KVStoreSync::write(store.inner(), "testspace", "", "testkey", other_encrypted.unwrap())
+			.unwrap();
+		assert!(KVStoreSync::read(&store, "testspace", "", "testkey").is_err());
+	}
+
+	#[test]
+	fn empty_values_round_trip() {
+		let entropy_source = RandomBytes::new([43; 32]);
+		let store = EncryptedKVStore::new_with_key(TestStore::new(false), [1; 32], &entropy_source);
+		KVStoreSync::write(&store, "", "", "testkey", Vec::new()).unwrap();
+		assert_eq!(KVStoreSync::read(&store, "", "", "testkey").unwrap(), Vec::<u8>::new());
+	}
+}
@@ -0,0 +1,186 @@
+// This file is Copyright its original authors, visible in version control
+// history.
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Utilities for exporting LDK objects in a schema-versioned, machine-readable format.
+//!
+//! [`ChannelDetails`], [`PeerDetails`], and [`NodeInfo`] can be exported via their
+//! `to_canonical_json` and `to_canonical_bytes` methods. Both formats are intended for consumption
+//! by external control planes (e.g., RPC daemons or dashboards) and are kept stable across LDK
+//! releases as follows:
+//!  * Every exported object carries the [`EXPORT_SCHEMA_VERSION`] it was generated with, as the
+//!    `schema_version` field in JSON and as a big-endian `u16` prefix in the binary format.
+//!  * Within a schema version, fields are only ever added, never removed, renamed, or changed in
+//!    meaning. Consumers should thus ignore any fields they don't know.
+//!  * Any other change to the schema bumps [`EXPORT_SCHEMA_VERSION`].
+//!
+//! The JSON encoding is canonical, i.e., exporting the same object always yields byte-identical
+//! output:
+//!  * Objects contain no insignificant whitespace and their keys are sorted lexicographically.
+//!  * Unset fields are present with a `null` value rather than omitted.
+//!  * Integers of up to 64 bits are encoded as JSON numbers, whereas 128-bit integers (i.e.,
+//!    `user_channel_id`) are encoded as decimal strings.
+//!  * Public keys, channel ids, and colors are encoded as lowercase hex strings. Feature sets are
+//!    encoded as lowercase hex strings of their big-endian BOLT 9 encoding.
+//!  * Outpoints are encoded as `txid:vout` strings and socket addresses as `host:port` strings.
+//!
+//! The binary encoding consists of the [`EXPORT_SCHEMA_VERSION`] followed by the object's regular
+//! LDK serialization, which is a TLV stream following the same "it's OK to be odd" rules for
+//! forwards compatibility as the Lightning protocol.
+//!
+//! [`ChannelDetails`]: crate::ln::channel_state::ChannelDetails
+//! [`PeerDetails`]: crate::ln::peer_handler::PeerDetails
+//! [`NodeInfo`]: crate::routing::gossip::NodeInfo
+
+use crate::prelude::*;
+use crate::util::ser::Writeable;
+
+use bitcoin::hex::DisplayHex;
+
+use core::fmt::{Display, Write};
+
+/// The version of the schema used by the canonical JSON and binary exports.
+///
+/// See the [module-level documentation](self) for details on when this is bumped.
+pub const EXPORT_SCHEMA_VERSION: u16 = 1;
+
+/// A builder for a canonical JSON object as described in the [module-level documentation](self).
+pub(crate) struct JsonObject {
+	fields: Vec<(&'static str, String)>,
+}
+
+impl JsonObject {
+	/// Creates a new object which already contains the `schema_version` field.
+	pub(crate) fn new() -> Self {
+		let mut obj = Self { fields: Vec::new() };
+		obj.number("schema_version", EXPORT_SCHEMA_VERSION);
+		obj
+	}
+
+	/// Creates a new object meant to be nested in another one, and thus lacking the
+	/// `schema_version` field.
+	pub(crate) fn nested() -> Self {
+		Self { fields: Vec::new() }
+	}
+
+	pub(crate) fn number<N: Into<u64>>(&mut self, key: &'static str, value: N) {
+		self.fields.push((key, value.into().to_string()));
+	}
+
+	pub(crate) fn opt_number<N: Into<u64>>(&mut self, key: &'static str, value: Option<N>) {
+		self.fields.push((key, value.map_or("null".to_owned(), |v| v.into().to_string())));
+	}
+
+	pub(crate) fn boolean(&mut self, key: &'static str, value: bool) {
+		self.fields.push((key, value.to_string()));
+	}
+
+	/// Adds a string field holding the [`Display`] representation of `value`.
+	pub(crate) fn string<D: Display>(&mut self, key: &'static str, value: D) {
+		self.fields.push((key, json_string(&value.to_string())));
+	}
+
+	pub(crate) fn opt_string<D: Display>(&mut self, key: &'static str, value: Option<D>) {
+		self.fields.push((key, value.map_or("null".to_owned(), |v| json_string(&v.to_string()))));
+	}
+
+	/// Adds a field holding an already-encoded JSON value, e.g., a nested object or an array.
+	pub(crate) fn raw(&mut self, key: &'static str, value: String) {
+		self.fields.push((key, value));
+	}
+
+	pub(crate) fn finish(mut self) -> String {
+		self.fields.sort_unstable_by_key(|(key, _)| *key);
+		debug_assert!(self.fields.windows(2).all(|w| w[0].0 != w[1].0), "Duplicate JSON key");
+		let mut res = String::from("{");
+		for (idx, (key, value)) in self.fields.iter().enumerate() {
+			if idx != 0 {
+				res.push(',');
+			}
+			res.push_str(&json_string(key));
+			res.push(':');
+			res.push_str(value);
+		}
+		res.push('}');
+		res
+	}
+}
+
+/// Encodes the given already-encoded JSON values as a JSON array.
+pub(crate) fn json_array<I: Iterator<Item = String>>(values: I) -> String {
+	let mut res = String::from("[");
+	for (idx, value) in values.enumerate() {
+		if idx != 0 {
+			res.push(',');
+		}
+		res.push_str(&value);
+	}
+	res.push(']');
+	res
+}
+
+/// Encodes the given string as a JSON string, escaping it as required.
+pub(crate) fn json_string(value: &str) -> String {
+	let mut res = String::with_capacity(value.len() + 2);
+	res.push('"');
+	for c in value.chars() {
+		match c {
+			'"' => res.push_str("\\\""),
+			'\\' => res.push_str("\\\\"),
+			'\n' => res.push_str("\\n"),
+			'\r' => res.push_str("\\r"),
+			'\t' => res.push_str("\\t"),
+			c if (c as u32) < 0x20 => {
+				let _ = write!(res, "\\u{:04x}", c as u32);
+			},
+			c => res.push(c),
+		}
+	}
+	res.push('"');
+	res
+}
+
+/// Encodes the given little-endian feature flags as a hex string of their big-endian encoding.
+pub(crate) fn features_hex(le_flags: &[u8]) -> String {
+	let be_flags: Vec<u8> = le_flags.iter().rev().copied().collect();
+	be_flags.as_hex().to_string()
+}
+
+/// Serializes `value` prefixed with the [`EXPORT_SCHEMA_VERSION`].
+pub(crate) fn versioned_bytes<W: Writeable>(value: &W) -> Vec<u8> {
+	let mut res = EXPORT_SCHEMA_VERSION.encode();
+	value.write(&mut res).expect("In-memory writes can't fail");
+	res
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{features_hex, json_array, json_string, versioned_bytes, JsonObject};
+
+	#[test]
+	fn json_object_is_canonical() {
+		let mut obj = JsonObject::new();
+		obj.string("b", "quote\" backslash\\ newline\n bell\u{7}");
+		obj.opt_number::<u64>("a", None);
+		obj.raw("c", json_array([1u8, 2].iter().map(|n| n.to_string())));
+		obj.boolean("_d", true);
+		obj.opt_string("e", Some(u64::max_value()));
+		assert_eq!(
+			obj.finish(),
+			"{\"_d\":true,\"a\":null,\"b\":\"quote\\\" backslash\\\\ newline\\n bell\\u0007\",\"c\":[1,2],\"e\":\"18446744073709551615\",\"schema_version\":1}"
+		);
+		assert_eq!(json_string(""), "\"\"");
+		assert_eq!(json_array(core::iter::empty()), "[]");
+	}
+
+	#[test]
+	fn exports_are_versioned() {
+		assert_eq!(features_hex(&[0x01, 0x82]), "8201");
+		assert_eq!(versioned_bytes(&42u8), vec![0, 1, 42]);
+	}
+}
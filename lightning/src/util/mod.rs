@@ -25,10 +25,14 @@ pub mod async_poll;
 pub mod base32;
 #[cfg(not(fuzzing))]
 pub(crate) mod base32;
+pub mod encrypted_store;
 pub mod errors;
+pub mod export;
 pub mod message_signing;
 pub mod native_async;
+pub mod node_assembler;
 pub mod persist;
+pub mod qr;
 pub mod scid_utils;
 pub mod ser;
 pub mod sweep;
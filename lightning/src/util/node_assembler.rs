@@ -0,0 +1,438 @@
+// This file is Copyright its original authors, visible in version control
+// history.
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! A sequencer enforcing the order in which a node's components are started.
+//!
+//! On startup, a node has to:
+//!  1. read its [`ChannelMonitor`]s, e.g., via [`read_channel_monitors`],
+//!  2. read its [`ChannelManager`] via [`ChannelManagerReadArgs`], which requires the
+//!     [`ChannelMonitor`]s,
+//!  3. replay any blocks connected or disconnected while it was offline against both the
+//!     [`ChannelMonitor`]s and the [`ChannelManager`], e.g., via
+//!     `lightning_block_sync::init::synchronize_listeners`, and only then hand the
+//!     [`ChannelMonitor`]s to its [`ChainMonitor`] via [`ChainMonitor::load_existing_monitor`],
+//!  4. construct its [`PeerManager`] and start connecting to peers, and finally
+//!  5. start processing events and persisting the [`ChannelManager`], e.g., via
+//!     `lightning_background_processor::BackgroundProcessor`.
+//!
+//! Doing any of these out of order, e.g., connecting to peers before the chain has been replayed,
+//! may lead to force-closures or loss of funds. A [`NodeAssembler`] takes each step as a closure
+//! and only allows the next step to run once the previous one completed, returning a
+//! [`NodeAssemblyError`] identifying the failed [`StartupPhase`] otherwise. Hooks registered via
+//! [`NodeAssembler::with_hook`] are called once each phase completed, e.g., to report progress or
+//! to abort startup.
+//!
+//! [`ChannelMonitor`]: crate::chain::channelmonitor::ChannelMonitor
+//! [`read_channel_monitors`]: crate::util::persist::read_channel_monitors
+//! [`ChannelManager`]: crate::ln::channelmanager::ChannelManager
+//! [`ChannelManagerReadArgs`]: crate::ln::channelmanager::ChannelManagerReadArgs
+//! [`ChainMonitor`]: crate::chain::chainmonitor::ChainMonitor
+//! [`ChainMonitor::load_existing_monitor`]: crate::chain::chainmonitor::ChainMonitor::load_existing_monitor
+//! [`PeerManager`]: crate::ln::peer_handler::PeerManager
+
+#[allow(unused_imports)]
+use crate::prelude::*;
+
+use core::fmt;
+
+/// A phase of a node's startup, in the order in which they are run by a [`NodeAssembler`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum StartupPhase {
+	/// Reading the [`ChannelMonitor`]s.
+	///
+	/// [`ChannelMonitor`]: crate::chain::channelmonitor::ChannelMonitor
+	ReadMonitors,
+	/// Reading the [`ChannelManager`].
+	///
+	/// [`ChannelManager`]: crate::ln::channelmanager::ChannelManager
+	ReadChannelManager,
+	/// Replaying the chain against the [`ChannelMonitor`]s and the [`ChannelManager`], and handing
+	/// the [`ChannelMonitor`]s to the [`ChainMonitor`].
+	///
+	/// [`ChannelMonitor`]: crate::chain::channelmonitor::ChannelMonitor
+	/// [`ChannelManager`]: crate::ln::channelmanager::ChannelManager
+	/// [`ChainMonitor`]: crate::chain::chainmonitor::ChainMonitor
+	ReplayChain,
+	/// Constructing the [`PeerManager`] and connecting to peers.
+	///
+	/// [`PeerManager`]: crate::ln::peer_handler::PeerManager
+	StartPeerManager,
+	/// Starting the background processor.
+	StartBackgroundProcessor,
+}
+
+impl fmt::Display for StartupPhase {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			StartupPhase::ReadMonitors => f.write_str("reading channel monitors"),
+			StartupPhase::ReadChannelManager => f.write_str("reading channel manager"),
+			StartupPhase::ReplayChain => f.write_str("replaying chain"),
+			StartupPhase::StartPeerManager => f.write_str("starting peer manager"),
+			StartupPhase::StartBackgroundProcessor => f.write_str("starting background processor"),
+		}
+	}
+}
+
+/// An error returned by a [`NodeAssembler`], identifying the phase in which startup failed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NodeAssemblyError<E> {
+	/// Reading the [`ChannelMonitor`]s failed.
+	///
+	/// [`ChannelMonitor`]: crate::chain::channelmonitor::ChannelMonitor
+	ReadMonitors(E),
+	/// Reading the [`ChannelManager`] failed.
+	///
+	/// [`ChannelManager`]: crate::ln::channelmanager::ChannelManager
+	ReadChannelManager(E),
+	/// Replaying the chain failed.
+	ReplayChain(E),
+	/// Starting the [`PeerManager`] failed.
+	///
+	/// [`PeerManager`]: crate::ln::peer_handler::PeerManager
+	StartPeerManager(E),
+	/// Starting the background processor failed.
+	StartBackgroundProcessor(E),
+	/// A hook registered via [`NodeAssembler::with_hook`] aborted startup after `phase` completed.
+	Hook {
+		/// The phase which completed before the hook was called.
+		phase: StartupPhase,
+		/// The error returned by the hook.
+		error: E,
+	},
+}
+
+impl<E> NodeAssemblyError<E> {
+	/// Returns the phase in which startup failed. For [`NodeAssemblyError::Hook`], this is the
+	/// phase which completed before the hook aborted startup.
+	pub fn phase(&self) -> StartupPhase {
+		match self {
+			NodeAssemblyError::ReadMonitors(_) => StartupPhase::ReadMonitors,
+			NodeAssemblyError::ReadChannelManager(_) => StartupPhase::ReadChannelManager,
+			NodeAssemblyError::ReplayChain(_) => StartupPhase::ReplayChain,
+			NodeAssemblyError::StartPeerManager(_) => StartupPhase::StartPeerManager,
+			NodeAssemblyError::StartBackgroundProcessor(_) => {
+				StartupPhase::StartBackgroundProcessor
+			},
+			NodeAssemblyError::Hook { phase, .. } => *phase,
+		}
+	}
+}
+
+impl<E: fmt::Display> fmt::Display for NodeAssemblyError<E> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			NodeAssemblyError::Hook { phase, error } => {
+				write!(f, "Startup aborted after {}: {}", phase, error)
+			},
+			NodeAssemblyError::ReadMonitors(e)
+			| NodeAssemblyError::ReadChannelManager(e)
+			| NodeAssemblyError::ReplayChain(e)
+			| NodeAssemblyError::StartPeerManager(e)
+			| NodeAssemblyError::StartBackgroundProcessor(e) => {
+				write!(f, "Startup failed while {}: {}", self.phase(), e)
+			},
+		}
+	}
+}
+
+struct PhaseHooks<'a, E> {
+	hooks: Vec<Box<dyn FnMut(StartupPhase) -> Result<(), E> + 'a>>,
+}
+
+impl<'a, E> PhaseHooks<'a, E> {
+	fn phase_completed(&mut self, phase: StartupPhase) -> Result<(), NodeAssemblyError<E>> {
+		for hook in self.hooks.iter_mut() {
+			hook(phase).map_err(|error| NodeAssemblyError::Hook { phase, error })?;
+		}
+		Ok(())
+	}
+}
+
+/// Sequences a node's startup, only allowing each [`StartupPhase`] to run once the previous one
+/// completed.
+///
+/// Each phase is run by passing a closure to the respective method, which returns the state
+/// required to run the next phase. See the [module-level documentation] for the steps each phase
+/// is expected to perform.
+///
+/// [module-level documentation]: crate::util::node_assembler
+pub struct NodeAssembler<'a, E> {
+	hooks: PhaseHooks<'a, E>,
+}
+
+impl<'a, E> NodeAssembler<'a, E> {
+	/// Constructs a new [`NodeAssembler`] without any hooks.
+	pub fn new() -> Self {
+		Self { hooks: PhaseHooks { hooks: Vec::new() } }
+	}
+
+	/// Registers a hook which is called with each [`StartupPhase`] once it completed, before the
+	/// next phase is run. Returning an error aborts startup with a
+	/// [`NodeAssemblyError::Hook`].
+	///
+	/// Hooks are called in the order in which they were registered.
+	pub fn with_hook<H: FnMut(StartupPhase) -> Result<(), E> + 'a>(mut self, hook: H) -> Self {
+		self.hooks.hooks.push(Box::new(hook));
+		self
+	}
+
+	/// Runs [`StartupPhase::ReadMonitors`], with `read_monitors` returning the node's
+	/// [`ChannelMonitor`]s.
+	///
+	/// [`ChannelMonitor`]: crate::chain::channelmonitor::ChannelMonitor
+	pub fn read_monitors<M, F: FnOnce() -> Result<M, E>>(
+		mut self, read_monitors: F,
+	) -> Result<MonitorsRead<'a, E, M>, NodeAssemblyError<E>> {
+		let monitors = read_monitors().map_err(NodeAssemblyError::ReadMonitors)?;
+		self.hooks.phase_completed(StartupPhase::ReadMonitors)?;
+		Ok(MonitorsRead { hooks: self.hooks, monitors })
+	}
+}
+
+/// A [`NodeAssembler`] which completed [`StartupPhase::ReadMonitors`].
+pub struct MonitorsRead<'a, E, M> {
+	hooks: PhaseHooks<'a, E>,
+	monitors: M,
+}
+
+impl<'a, E, M> MonitorsRead<'a, E, M> {
+	/// Returns the [`ChannelMonitor`]s read in [`StartupPhase::ReadMonitors`].
+	///
+	/// [`ChannelMonitor`]: crate::chain::channelmonitor::ChannelMonitor
+	pub fn monitors(&self) -> &M {
+		&self.monitors
+	}
+
+	/// Runs [`StartupPhase::ReadChannelManager`], with `read_channel_manager` returning the node's
+	/// [`ChannelManager`] given the [`ChannelMonitor`]s.
+	///
+	/// [`ChannelManager`]: crate::ln::channelmanager::ChannelManager
+	/// [`ChannelMonitor`]: crate::chain::channelmonitor::ChannelMonitor
+	pub fn read_channel_manager<CM, F: FnOnce(&mut M) -> Result<CM, E>>(
+		mut self, read_channel_manager: F,
+	) -> Result<ChannelManagerRead<'a, E, M, CM>, NodeAssemblyError<E>> {
+		let channel_manager = read_channel_manager(&mut self.monitors)
+			.map_err(NodeAssemblyError::ReadChannelManager)?;
+		self.hooks.phase_completed(StartupPhase::ReadChannelManager)?;
+		Ok(ChannelManagerRead { hooks: self.hooks, monitors: self.monitors, channel_manager })
+	}
+}
+
+/// A [`NodeAssembler`] which completed [`StartupPhase::ReadChannelManager`].
+pub struct ChannelManagerRead<'a, E, M, CM> {
+	hooks: PhaseHooks<'a, E>,
+	monitors: M,
+	channel_manager: CM,
+}
+
+impl<'a, E, M, CM> ChannelManagerRead<'a, E, M, CM> {
+	/// Returns the [`ChannelMonitor`]s read in [`StartupPhase::ReadMonitors`].
+	///
+	/// [`ChannelMonitor`]: crate::chain::channelmonitor::ChannelMonitor
+	pub fn monitors(&self) -> &M {
+		&self.monitors
+	}
+
+	/// Returns the [`ChannelManager`] read in [`StartupPhase::ReadChannelManager`].
+	///
+	/// [`ChannelManager`]: crate::ln::channelmanager::ChannelManager
+	pub fn channel_manager(&self) -> &CM {
+		&self.channel_manager
+	}
+
+	/// Runs [`StartupPhase::ReplayChain`], with `replay_chain` syncing the [`ChannelMonitor`]s and
+	/// the [`ChannelManager`] to the chain tip and then handing the [`ChannelMonitor`]s to the
+	/// [`ChainMonitor`].
+	///
+	/// [`ChannelMonitor`]: crate::chain::channelmonitor::ChannelMonitor
+	/// [`ChannelManager`]: crate::ln::channelmanager::ChannelManager
+	/// [`ChainMonitor`]: crate::chain::chainmonitor::ChainMonitor
+	pub fn replay_chain<F: FnOnce(M, &CM) -> Result<(), E>>(
+		mut self, replay_chain: F,
+	) -> Result<ChainReplayed<'a, E, CM>, NodeAssemblyError<E>> {
+		replay_chain(self.monitors, &self.channel_manager)
+			.map_err(NodeAssemblyError::ReplayChain)?;
+		self.hooks.phase_completed(StartupPhase::ReplayChain)?;
+		Ok(ChainReplayed { hooks: self.hooks, channel_manager: self.channel_manager })
+	}
+}
+
+/// A [`NodeAssembler`] which completed [`StartupPhase::ReplayChain`].
+pub struct ChainReplayed<'a, E, CM> {
+	hooks: PhaseHooks<'a, E>,
+	channel_manager: CM,
+}
+
+impl<'a, E, CM> ChainReplayed<'a, E, CM> {
+	/// Returns the [`ChannelManager`] read in [`StartupPhase::ReadChannelManager`].
+	///
+	/// [`ChannelManager`]: crate::ln::channelmanager::ChannelManager
+	pub fn channel_manager(&self) -> &CM {
+		&self.channel_manager
+	}
+
+	/// Runs [`StartupPhase::StartPeerManager`], with `start_peer_manager` returning the node's
+	/// [`PeerManager`].
+	///
+	/// [`PeerManager`]: crate::ln::peer_handler::PeerManager
+	pub fn start_peer_manager<PM, F: FnOnce(&CM) -> Result<PM, E>>(
+		mut self, start_peer_manager: F,
+	) -> Result<PeerManagerStarted<'a, E, CM, PM>, NodeAssemblyError<E>> {
+		let peer_manager = start_peer_manager(&self.channel_manager)
+			.map_err(NodeAssemblyError::StartPeerManager)?;
+		self.hooks.phase_completed(StartupPhase::StartPeerManager)?;
+		Ok(PeerManagerStarted {
+			hooks: self.hooks,
+			channel_manager: self.channel_manager,
+			peer_manager,
+		})
+	}
+}
+
+/// A [`NodeAssembler`] which completed [`StartupPhase::StartPeerManager`].
+pub struct PeerManagerStarted<'a, E, CM, PM> {
+	hooks: PhaseHooks<'a, E>,
+	channel_manager: CM,
+	peer_manager: PM,
+}
+
+impl<'a, E, CM, PM> PeerManagerStarted<'a, E, CM, PM> {
+	/// Returns the [`ChannelManager`] read in [`StartupPhase::ReadChannelManager`].
+	///
+	/// [`ChannelManager`]: crate::ln::channelmanager::ChannelManager
+	pub fn channel_manager(&self) -> &CM {
+		&self.channel_manager
+	}
+
+	/// Returns the [`PeerManager`] started in [`StartupPhase::StartPeerManager`].
+	///
+	/// [`PeerManager`]: crate::ln::peer_handler::PeerManager
+	pub fn peer_manager(&self) -> &PM {
+		&self.peer_manager
+	}
+
+	/// Runs [`StartupPhase::StartBackgroundProcessor`], with `start_background_processor`
+	/// returning the node's running background processor, completing startup.
+	///
+	/// If a hook aborts startup after this phase, the background processor is dropped, which
+	/// should stop it.
+	pub fn start_background_processor<BP, F: FnOnce(&CM, &PM) -> Result<BP, E>>(
+		mut self, start_background_processor: F,
+	) -> Result<AssembledNode<CM, PM, BP>, NodeAssemblyError<E>> {
+		let background_processor =
+			start_background_processor(&self.channel_manager, &self.peer_manager)
+				.map_err(NodeAssemblyError::StartBackgroundProcessor)?;
+		self.hooks.phase_completed(StartupPhase::StartBackgroundProcessor)?;
+		Ok(AssembledNode {
+			channel_manager: self.channel_manager,
+			peer_manager: self.peer_manager,
+			background_processor,
+		})
+	}
+}
+
+/// The components of a node which completed all [`StartupPhase`]s.
+pub struct AssembledNode<CM, PM, BP> {
+	/// The node's [`ChannelManager`].
+	///
+	/// [`ChannelManager`]: crate::ln::channelmanager::ChannelManager
+	pub channel_manager: CM,
+	/// The node's [`PeerManager`].
+	///
+	/// [`PeerManager`]: crate::ln::peer_handler::PeerManager
+	pub peer_manager: PM,
+	/// The node's background processor.
+	pub background_processor: BP,
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use core::cell::RefCell;
+
+	#[test]
+	fn runs_phases_in_order() {
+		let completed = RefCell::new(Vec::new());
+		let node = NodeAssembler::<()>::new()
+			.with_hook(|phase| {
+				completed.borrow_mut().push(phase);
+				Ok(())
+			})
+			.read_monitors(|| Ok(vec![1u8, 2]))
+			.unwrap()
+			.read_channel_manager(|monitors| Ok(monitors.len()))
+			.unwrap()
+			.replay_chain(|monitors, channel_manager| {
+				assert_eq!(monitors.len(), *channel_manager);
+				Ok(())
+			})
+			.unwrap()
+			.start_peer_manager(|channel_manager| Ok(*channel_manager + 1))
+			.unwrap()
+			.start_background_processor(|channel_manager, peer_manager| {
+				Ok(channel_manager + peer_manager)
+			})
+			.unwrap();
+
+		assert_eq!(node.channel_manager, 2);
+		assert_eq!(node.peer_manager, 3);
+		assert_eq!(node.background_processor, 5);
+		assert_eq!(
+			*completed.borrow(),
+			vec![
+				StartupPhase::ReadMonitors,
+				StartupPhase::ReadChannelManager,
+				StartupPhase::ReplayChain,
+				StartupPhase::StartPeerManager,
+				StartupPhase::StartBackgroundProcessor,
+			]
+		);
+	}
+
+	#[test]
+	fn fails_in_phase() {
+		let res = NodeAssembler::<&str>::new()
+			.read_monitors(|| Ok(()))
+			.unwrap()
+			.read_channel_manager(|_| Ok(()))
+			.unwrap()
+			.replay_chain(|_, _| Err("chain source unavailable"));
+		let err = res.err().unwrap();
+		assert_eq!(err, NodeAssemblyError::ReplayChain("chain source unavailable"));
+		assert_eq!(err.phase(), StartupPhase::ReplayChain);
+		assert_eq!(
+			err.to_string(),
+			"Startup failed while replaying chain: chain source unavailable"
+		);
+	}
+
+	#[test]
+	fn hook_aborts_startup() {
+		let mut read_manager = false;
+		let res = NodeAssembler::new()
+			.with_hook(|_| Ok(()))
+			.with_hook(
+				|phase| if phase == StartupPhase::ReadMonitors { Err("abort") } else { Ok(()) },
+			)
+			.read_monitors(|| Ok(()))
+			.and_then(|assembler| {
+				assembler.read_channel_manager(|_| {
+					read_manager = true;
+					Ok(())
+				})
+			});
+		let err = res.err().unwrap();
+		assert_eq!(
+			err,
+			NodeAssemblyError::Hook { phase: StartupPhase::ReadMonitors, error: "abort" }
+		);
+		assert!(!read_manager);
+	}
+}
@@ -66,6 +66,12 @@ pub const CHANNEL_MONITOR_PERSISTENCE_PRIMARY_NAMESPACE: &str = "monitors";
 pub const CHANNEL_MONITOR_PERSISTENCE_SECONDARY_NAMESPACE: &str = "";
 /// The primary namespace under which [`ChannelMonitorUpdate`]s will be persisted.
 pub const CHANNEL_MONITOR_UPDATE_PERSISTENCE_PRIMARY_NAMESPACE: &str = "monitor_updates";
+/// The primary namespace under which [`ChannelMonitorUpdate`]s are written by the
+/// [`ChainMonitor`]'s update journal, see [`ChainMonitor::enable_update_journal`].
+///
+/// [`ChainMonitor`]: crate::chain::chainmonitor::ChainMonitor
+/// [`ChainMonitor::enable_update_journal`]: crate::chain::chainmonitor::ChainMonitor::enable_update_journal
+pub const CHANNEL_MONITOR_UPDATE_JOURNAL_PRIMARY_NAMESPACE: &str = "monitor_update_journal";
 
 /// The primary namespace under which archived [`ChannelMonitor`]s will be persisted.
 pub const ARCHIVED_CHANNEL_MONITOR_PERSISTENCE_PRIMARY_NAMESPACE: &str = "archived_monitors";
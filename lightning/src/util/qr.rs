@@ -0,0 +1,305 @@
+// This file is Copyright its original authors, visible in version control
+// history.
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Utilities for encoding invoices and offers such that they fit in a scannable QR code.
+//!
+//! QR codes can encode uppercase alphanumeric strings far more compactly than arbitrary bytes.
+//! As bech32 strings may be written in all uppercase, encoding an [`Offer`], [`Refund`] or
+//! [`Bolt11Invoice`] in uppercase allows for a QR code roughly 30% smaller than its lowercase
+//! [`Display`] encoding.
+//!
+//! A [`QrBudget`] bounds the size of the QR code a wallet is willing to display, and reports the
+//! size of the resulting code through a [`QrEncoding`]. To fit an [`Offer`] in a given budget,
+//! see [`OfferBuilder::build_for_qr`].
+//!
+//! [`Offer`]: crate::offers::offer::Offer
+//! [`Refund`]: crate::offers::refund::Refund
+//! [`Bolt11Invoice`]: crate::bolt11_invoice::Bolt11Invoice
+//! [`Display`]: core::fmt::Display
+//! [`OfferBuilder::build_for_qr`]: crate::offers::offer::OfferBuilder::build_for_qr
+
+use crate::bolt11_invoice::Bolt11Invoice;
+use crate::offers::offer::Offer;
+use crate::offers::refund::Refund;
+
+#[allow(unused_imports)]
+use crate::prelude::*;
+
+/// The highest QR code version, i.e. the one with the most modules.
+pub const MAX_QR_VERSION: u8 = 40;
+
+/// The number of data codewords of each QR code version, indexed by version and then by
+/// [`QrErrorCorrection`] level, as specified in ISO/IEC 18004 Table 7.
+const QR_DATA_CODEWORDS: [[u16; 4]; MAX_QR_VERSION as usize] = [
+	[19, 16, 13, 9],
+	[34, 28, 22, 16],
+	[55, 44, 34, 26],
+	[80, 64, 48, 36],
+	[108, 86, 62, 46],
+	[136, 108, 76, 60],
+	[156, 124, 88, 66],
+	[194, 154, 110, 86],
+	[232, 182, 132, 100],
+	[274, 216, 154, 122],
+	[324, 254, 180, 140],
+	[370, 290, 206, 158],
+	[428, 334, 244, 180],
+	[461, 365, 261, 197],
+	[523, 415, 295, 223],
+	[589, 453, 325, 253],
+	[647, 507, 367, 283],
+	[721, 563, 397, 313],
+	[795, 627, 445, 341],
+	[861, 669, 485, 385],
+	[932, 714, 512, 406],
+	[1006, 782, 568, 442],
+	[1094, 860, 614, 464],
+	[1174, 914, 664, 514],
+	[1276, 1000, 718, 538],
+	[1370, 1062, 754, 596],
+	[1468, 1128, 808, 628],
+	[1531, 1193, 871, 661],
+	[1631, 1267, 911, 701],
+	[1735, 1373, 985, 745],
+	[1843, 1455, 1033, 793],
+	[1955, 1541, 1115, 845],
+	[2071, 1631, 1171, 901],
+	[2191, 1725, 1231, 961],
+	[2306, 1812, 1286, 986],
+	[2434, 1914, 1354, 1054],
+	[2566, 1992, 1426, 1096],
+	[2702, 2102, 1502, 1142],
+	[2812, 2216, 1582, 1222],
+	[2956, 2334, 1666, 1276],
+];
+
+/// The error correction level of a QR code, trading capacity for resilience to damage.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QrErrorCorrection {
+	/// Recovers from roughly 7% of the code being unreadable.
+	Low,
+	/// Recovers from roughly 15% of the code being unreadable.
+	Medium,
+	/// Recovers from roughly 25% of the code being unreadable.
+	Quartile,
+	/// Recovers from roughly 30% of the code being unreadable.
+	High,
+}
+
+/// The QR code encoding mode used for a [`QrEncoding`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QrMode {
+	/// Digits, uppercase letters and a handful of symbols, packed two characters per 11 bits.
+	Alphanumeric,
+	/// Arbitrary bytes, packed one per 8 bits.
+	Byte,
+}
+
+impl QrMode {
+	fn for_data(data: &str) -> Self {
+		let is_alphanumeric =
+			|c: char| c.is_ascii_digit() || c.is_ascii_uppercase() || " $%*+-./:".contains(c);
+		if data.chars().all(is_alphanumeric) {
+			QrMode::Alphanumeric
+		} else {
+			QrMode::Byte
+		}
+	}
+}
+
+/// The largest QR code a wallet is willing to display.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QrBudget {
+	error_correction: QrErrorCorrection,
+	max_version: u8,
+}
+
+/// The size of the QR code for some encoded data.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QrEncoding {
+	/// The string to encode in the QR code.
+	pub data: String,
+	/// The mode the QR code must be encoded in to achieve the reported size.
+	pub mode: QrMode,
+	/// The smallest QR code version that fits [`Self::data`].
+	pub version: u8,
+	/// The number of modules along each side of a QR code of [`Self::version`].
+	pub modules: u16,
+	/// The number of characters a QR code of [`Self::version`] can hold in [`Self::mode`], of
+	/// which [`Self::data`] uses [`Self::data_len`].
+	pub capacity: usize,
+	/// The length of [`Self::data`] in bytes.
+	pub data_len: usize,
+}
+
+/// Returned when data does not fit in a [`QrBudget`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QrBudgetExceeded {
+	/// The length of the data in bytes.
+	pub data_len: usize,
+	/// The most bytes that fit in the [`QrBudget`] in the data's [`QrMode`].
+	pub max_data_len: usize,
+}
+
+impl QrBudget {
+	/// Creates a budget for QR codes of at most `max_version`, which is capped to
+	/// [`MAX_QR_VERSION`].
+	///
+	/// A QR code of version `v` is `17 + 4 * v` modules wide.
+	pub fn new(error_correction: QrErrorCorrection, max_version: u8) -> Self {
+		Self { error_correction, max_version: max_version.clamp(1, MAX_QR_VERSION) }
+	}
+
+	/// Creates a budget for QR codes at most `max_modules` modules wide.
+	pub fn with_max_modules(error_correction: QrErrorCorrection, max_modules: u16) -> Self {
+		let max_version = (max_modules.saturating_sub(17) / 4).min(MAX_QR_VERSION as u16);
+		Self::new(error_correction, max_version as u8)
+	}
+
+	/// The error correction level of the budget.
+	pub fn error_correction(&self) -> QrErrorCorrection {
+		self.error_correction
+	}
+
+	/// The largest QR code version in the budget.
+	pub fn max_version(&self) -> u8 {
+		self.max_version
+	}
+
+	/// The number of modules along each side of the largest QR code in the budget.
+	pub fn max_modules(&self) -> u16 {
+		modules_for_version(self.max_version)
+	}
+
+	/// The number of characters that fit in the budget when encoded in `mode`.
+	pub fn max_data_len(&self, mode: QrMode) -> usize {
+		capacity(self.max_version, self.error_correction, mode)
+	}
+
+	/// Reports the size of the QR code for `data`, failing if it doesn't fit in the budget.
+	///
+	/// [`QrMode::Alphanumeric`] is used if `data` only contains characters it supports, e.g. if it
+	/// is an uppercase bech32 string.
+	pub fn encode(&self, data: String) -> Result<QrEncoding, QrBudgetExceeded> {
+		let mode = QrMode::for_data(&data);
+		let data_len = data.len();
+		for version in 1..=self.max_version {
+			let capacity = capacity(version, self.error_correction, mode);
+			if data_len <= capacity {
+				let modules = modules_for_version(version);
+				return Ok(QrEncoding { data, mode, version, modules, capacity, data_len });
+			}
+		}
+		Err(QrBudgetExceeded { data_len, max_data_len: self.max_data_len(mode) })
+	}
+
+	/// Encodes a bech32 string in uppercase, allowing for the more compact
+	/// [`QrMode::Alphanumeric`], and reports the size of the QR code for it.
+	pub fn encode_bech32(&self, bech32: &str) -> Result<QrEncoding, QrBudgetExceeded> {
+		self.encode(bech32.to_ascii_uppercase())
+	}
+
+	/// Encodes an [`Offer`] in uppercase bech32 and reports the size of the QR code for it.
+	///
+	/// Use [`OfferBuilder::build_for_qr`] to build an offer that fits in the budget.
+	///
+	/// [`OfferBuilder::build_for_qr`]: crate::offers::offer::OfferBuilder::build_for_qr
+	pub fn encode_offer(&self, offer: &Offer) -> Result<QrEncoding, QrBudgetExceeded> {
+		self.encode_bech32(&offer.to_string())
+	}
+
+	/// Encodes a [`Refund`] in uppercase bech32 and reports the size of the QR code for it.
+	pub fn encode_refund(&self, refund: &Refund) -> Result<QrEncoding, QrBudgetExceeded> {
+		self.encode_bech32(&refund.to_string())
+	}
+
+	/// Encodes a [`Bolt11Invoice`] in uppercase bech32 and reports the size of the QR code for it.
+	///
+	/// As the invoice is signed, it cannot be trimmed to fit the budget. If this fails, consider
+	/// creating the invoice with a shorter (or hashed) description or fewer route hints.
+	pub fn encode_bolt11_invoice(
+		&self, invoice: &Bolt11Invoice,
+	) -> Result<QrEncoding, QrBudgetExceeded> {
+		self.encode_bech32(&invoice.to_string())
+	}
+}
+
+fn modules_for_version(version: u8) -> u16 {
+	17 + 4 * version as u16
+}
+
+/// The number of characters a QR code of the given `version` can hold in `mode`.
+fn capacity(version: u8, error_correction: QrErrorCorrection, mode: QrMode) -> usize {
+	debug_assert!(version >= 1 && version <= MAX_QR_VERSION);
+	let ecc_index = match error_correction {
+		QrErrorCorrection::Low => 0,
+		QrErrorCorrection::Medium => 1,
+		QrErrorCorrection::Quartile => 2,
+		QrErrorCorrection::High => 3,
+	};
+	let data_bits = QR_DATA_CODEWORDS[version as usize - 1][ecc_index] as usize * 8;
+	// Each segment starts with a 4-bit mode indicator followed by a character count whose length
+	// depends on the version.
+	match mode {
+		QrMode::Alphanumeric => {
+			let count_bits = match version {
+				1..=9 => 9,
+				10..=26 => 11,
+				_ => 13,
+			};
+			let bits = data_bits - 4 - count_bits;
+			// Pairs of characters take 11 bits, with a trailing odd character taking 6.
+			bits / 11 * 2 + if bits % 11 >= 6 { 1 } else { 0 }
+		},
+		QrMode::Byte => {
+			let count_bits = if version <= 9 { 8 } else { 16 };
+			(data_bits - 4 - count_bits) / 8
+		},
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{capacity, QrBudget, QrBudgetExceeded, QrErrorCorrection, QrMode};
+
+	#[test]
+	fn computes_capacity() {
+		assert_eq!(capacity(1, QrErrorCorrection::Low, QrMode::Alphanumeric), 25);
+		assert_eq!(capacity(1, QrErrorCorrection::Low, QrMode::Byte), 17);
+		assert_eq!(capacity(10, QrErrorCorrection::Medium, QrMode::Alphanumeric), 311);
+		assert_eq!(capacity(40, QrErrorCorrection::Low, QrMode::Alphanumeric), 4296);
+		assert_eq!(capacity(40, QrErrorCorrection::Medium, QrMode::Alphanumeric), 3391);
+		assert_eq!(capacity(40, QrErrorCorrection::Quartile, QrMode::Alphanumeric), 2420);
+		assert_eq!(capacity(40, QrErrorCorrection::High, QrMode::Alphanumeric), 1852);
+		assert_eq!(capacity(40, QrErrorCorrection::Low, QrMode::Byte), 2953);
+	}
+
+	#[test]
+	fn encodes_in_smallest_version() {
+		let budget = QrBudget::new(QrErrorCorrection::Low, 2);
+		assert_eq!(budget.max_modules(), 25);
+
+		let encoding = budget.encode_bech32("lno1qgsqvgnwgcg35z6ee2h3yczraddm72xrfua9").unwrap();
+		assert_eq!(encoding.mode, QrMode::Alphanumeric);
+		assert_eq!(encoding.version, 2);
+		assert_eq!(encoding.modules, 25);
+		assert_eq!(encoding.capacity, 47);
+		assert!(encoding.data.starts_with("LNO1"));
+
+		let encoding = budget.encode("lno1".to_string()).unwrap();
+		assert_eq!(encoding.mode, QrMode::Byte);
+		assert_eq!(encoding.version, 1);
+
+		let data = "A".repeat(48);
+		assert_eq!(budget.encode(data), Err(QrBudgetExceeded { data_len: 48, max_data_len: 47 }));
+
+		assert_eq!(QrBudget::with_max_modules(QrErrorCorrection::Medium, 100).max_version(), 20);
+		assert_eq!(QrBudget::with_max_modules(QrErrorCorrection::Medium, 1000).max_version(), 40);
+	}
+}
@@ -1100,6 +1100,7 @@ impl_for_vec!(crate::routing::router::TrampolineHop);
 impl_for_vec_with_element_length_prefix!(crate::ln::msgs::UpdateAddHTLC);
 impl_writeable_for_vec_with_element_length_prefix!(&crate::ln::msgs::UpdateAddHTLC);
 impl_for_vec!(u32);
+impl_for_vec!(PublicKey);
 
 impl Writeable for Vec<Witness> {
 	#[inline]
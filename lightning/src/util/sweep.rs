@@ -8,11 +8,16 @@
 //! [`SpendableOutputDescriptor`]s, i.e., persists them in a given [`KVStoreSync`] and regularly retries
 //! sweeping them.
 
-use crate::chain::chaininterface::{BroadcasterInterface, ConfirmationTarget, FeeEstimator};
+use crate::chain::chaininterface::{
+	BroadcastContext, BroadcastType, BroadcasterInterface, ConfirmationTarget, FeeEstimator,
+	INCREMENTAL_RELAY_FEE_SAT_PER_1000_WEIGHT,
+};
 use crate::chain::channelmonitor::{ANTI_REORG_DELAY, ARCHIVAL_DELAY_BLOCKS};
+use crate::chain::transaction::OutPoint;
 use crate::chain::{self, BestBlock, Confirm, Filter, Listen, WatchedOutput};
 use crate::io;
 use crate::ln::msgs::DecodeError;
+use crate::ln::script::ShutdownScript;
 use crate::ln::types::ChannelId;
 use crate::prelude::*;
 use crate::sign::{
@@ -20,6 +25,7 @@ use crate::sign::{
 	OutputSpender, SpendableOutputDescriptor,
 };
 use crate::sync::Mutex;
+use crate::util::async_poll::{MaybeSend, MaybeSync};
 use crate::util::logger::Logger;
 use crate::util::persist::{
 	KVStore, KVStoreSync, KVStoreSyncWrapper, OUTPUT_SWEEPER_PERSISTENCE_KEY,
@@ -28,11 +34,14 @@ use crate::util::persist::{
 use crate::util::ser::{Readable, ReadableArgs, Writeable};
 use crate::{impl_writeable_tlv_based, log_debug, log_error};
 
+use bitcoin::bip32::{ChildNumber, Xpub};
 use bitcoin::block::Header;
+use bitcoin::hashes::Hash;
 use bitcoin::locktime::absolute::LockTime;
 use bitcoin::secp256k1::Secp256k1;
-use bitcoin::{BlockHash, ScriptBuf, Transaction, Txid};
+use bitcoin::{Amount, BlockHash, ScriptBuf, Transaction, TxOut, Txid, WPubkeyHash};
 
+use core::cmp;
 use core::future::Future;
 use core::ops::Deref;
 use core::pin::{pin, Pin};
@@ -55,9 +64,30 @@ pub struct TrackedSpendableOutput {
 	pub channel_id: Option<ChannelId>,
 	/// The current status of the output spend.
 	pub status: OutputSpendStatus,
+	/// The timelock encumbering the output, if it was tracked via
+	/// [`OutputSweeper::track_timelocked_outputs`] before becoming spendable.
+	pub timelock: Option<OutputTimelock>,
 }
 
 impl TrackedSpendableOutput {
+	/// Returns the height of the first block in which a transaction spending this output may be
+	/// confirmed.
+	///
+	/// Returns `Some(0)` for outputs which aren't timelocked and `None` if the transaction creating
+	/// the output hasn't been confirmed yet and hence the height can't be determined.
+	pub fn spendable_at_height(&self) -> Option<u32> {
+		match &self.timelock {
+			Some(timelock) => timelock.spendable_at_height(&self.descriptor),
+			None => Some(0),
+		}
+	}
+
+	fn is_mature(&self, cur_height: u32) -> bool {
+		// Our spends use the current height as locktime, so they may be confirmed in the next
+		// block at the earliest.
+		self.spendable_at_height().is_some_and(|height| cur_height + 1 >= height)
+	}
+
 	fn to_watched_output(&self, cur_hash: BlockHash) -> WatchedOutput {
 		let block_hash = self.status.first_broadcast_hash().or(Some(cur_hash));
 		match &self.descriptor {
@@ -92,8 +122,74 @@ impl_writeable_tlv_based!(TrackedSpendableOutput, {
 	(0, descriptor, required),
 	(2, channel_id, option),
 	(4, status, required),
+	(5, timelock, option),
 });
 
+/// The timelock encumbering an output tracked via [`OutputSweeper::track_timelocked_outputs`].
+///
+/// The relative (CSV) delay is determined from the output's [`SpendableOutputDescriptor`], while
+/// any absolute (CLTV) delay needs to be given when tracking the output.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutputTimelock {
+	/// The block in which the transaction creating the output was confirmed, if it has been
+	/// confirmed yet.
+	pub parent_confirmation: Option<BestBlock>,
+	/// The block height the output is locked until via `OP_CHECKLOCKTIMEVERIFY`, if any.
+	pub absolute_height_lock: Option<u32>,
+}
+
+impl OutputTimelock {
+	fn spendable_at_height(&self, descriptor: &SpendableOutputDescriptor) -> Option<u32> {
+		let parent_confirmation_height = self.parent_confirmation.as_ref()?.height;
+		let relative_delay = match descriptor {
+			SpendableOutputDescriptor::StaticOutput { .. } => 0,
+			SpendableOutputDescriptor::DelayedPaymentOutput(output) => output.to_self_delay as u32,
+			SpendableOutputDescriptor::StaticPaymentOutput(output) => {
+				// Outputs of anchor channels are encumbered by an additional `1 OP_CSV`.
+				if output.witness_script().is_some() {
+					1
+				} else {
+					0
+				}
+			},
+		};
+		let csv_height = parent_confirmation_height + relative_delay;
+		// Spends of CLTV-locked outputs need a locktime of at least the locked height, and hence
+		// may only be confirmed in the following block.
+		let cltv_height = self.absolute_height_lock.map_or(0, |height| height + 1);
+		Some(cmp::max(csv_height, cltv_height))
+	}
+}
+
+fn descriptor_output(descriptor: &SpendableOutputDescriptor) -> &TxOut {
+	match descriptor {
+		SpendableOutputDescriptor::StaticOutput { output, .. } => output,
+		SpendableOutputDescriptor::DelayedPaymentOutput(output) => &output.output,
+		SpendableOutputDescriptor::StaticPaymentOutput(output) => &output.output,
+	}
+}
+
+impl_writeable_tlv_based!(OutputTimelock, {
+	(0, parent_confirmation, option),
+	(2, absolute_height_lock, option),
+});
+
+/// An entry of the calendar returned by [`OutputSweeper::spendability_calendar`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpendabilityCalendarEntry {
+	/// The outpoint of the timelocked output.
+	pub outpoint: OutPoint,
+	/// The channel this output belongs to, if known.
+	pub channel_id: Option<ChannelId>,
+	/// The value of the output.
+	pub value: Amount,
+	/// The height of the first block in which a transaction spending the output may be
+	/// confirmed.
+	///
+	/// Will be `None` if the transaction creating the output hasn't been confirmed yet.
+	pub spendable_at_height: Option<u32>,
+}
+
 /// The current status of the output spend.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum OutputSpendStatus {
@@ -111,6 +207,18 @@ pub enum OutputSpendStatus {
 		latest_broadcast_height: u32,
 		/// The transaction spending this output we last broadcasted.
 		latest_spending_tx: Transaction,
+		/// The feerate, in satoshis per 1000 weight units, of the transaction spending this output
+		/// we last broadcasted.
+		///
+		/// Will be `None` if the transaction was broadcast by LDK 0.2 or prior, or if a previously
+		/// confirmed spend was reorganized out of the chain.
+		latest_feerate_sat_per_1000_weight: Option<u32>,
+		/// The best height when we last bumped the feerate of the transaction spending this output,
+		/// or first broadcast it if we haven't bumped it yet.
+		///
+		/// Will be `None` if the transaction was broadcast by LDK 0.2 or prior, or if a previously
+		/// confirmed spend was reorganized out of the chain.
+		latest_fee_bump_height: Option<u32>,
 	},
 	/// A transaction spending the output has been confirmed on-chain but will be tracked until it
 	/// reaches at least [`PRUNE_DELAY_BLOCKS`] confirmations to ensure [`Event::SpendableOutputs`]
@@ -133,7 +241,10 @@ pub enum OutputSpendStatus {
 }
 
 impl OutputSpendStatus {
-	fn broadcast(&mut self, cur_hash: BlockHash, cur_height: u32, latest_spending_tx: Transaction) {
+	fn broadcast(
+		&mut self, cur_hash: BlockHash, cur_height: u32, latest_spending_tx: Transaction,
+		feerate_sat_per_1000_weight: u32, fee_bump_height: u32,
+	) {
 		match self {
 			Self::PendingInitialBroadcast { delayed_until_height } => {
 				if let Some(delayed_until_height) = delayed_until_height {
@@ -146,6 +257,8 @@ impl OutputSpendStatus {
 					first_broadcast_hash: cur_hash,
 					latest_broadcast_height: cur_height,
 					latest_spending_tx,
+					latest_feerate_sat_per_1000_weight: Some(feerate_sat_per_1000_weight),
+					latest_fee_bump_height: Some(fee_bump_height),
 				};
 			},
 			Self::PendingFirstConfirmation { first_broadcast_hash, .. } => {
@@ -153,6 +266,8 @@ impl OutputSpendStatus {
 					first_broadcast_hash: *first_broadcast_hash,
 					latest_broadcast_height: cur_height,
 					latest_spending_tx,
+					latest_feerate_sat_per_1000_weight: Some(feerate_sat_per_1000_weight),
+					latest_fee_bump_height: Some(fee_bump_height),
 				};
 			},
 			Self::PendingThresholdConfirmations { .. } => {
@@ -231,6 +346,8 @@ impl OutputSpendStatus {
 					first_broadcast_hash: *first_broadcast_hash,
 					latest_broadcast_height: *latest_broadcast_height,
 					latest_spending_tx: latest_spending_tx.clone(),
+					latest_feerate_sat_per_1000_weight: None,
+					latest_fee_bump_height: None,
 				};
 			},
 		}
@@ -290,6 +407,19 @@ impl OutputSpendStatus {
 		}
 	}
 
+	fn latest_fee_bump_state(&self) -> Option<(u32, u32)> {
+		match self {
+			Self::PendingInitialBroadcast { .. } => None,
+			Self::PendingFirstConfirmation {
+				latest_feerate_sat_per_1000_weight: Some(feerate),
+				latest_fee_bump_height: Some(height),
+				..
+			} => Some((*feerate, *height)),
+			Self::PendingFirstConfirmation { .. } => None,
+			Self::PendingThresholdConfirmations { .. } => None,
+		}
+	}
+
 	fn is_confirmed(&self) -> bool {
 		match self {
 			Self::PendingInitialBroadcast { .. } => false,
@@ -307,6 +437,8 @@ impl_writeable_tlv_based_enum!(OutputSpendStatus,
 		(0, first_broadcast_hash, required),
 		(2, latest_broadcast_height, required),
 		(4, latest_spending_tx, required),
+		(5, latest_feerate_sat_per_1000_weight, option),
+		(7, latest_fee_bump_height, option),
 	},
 	(4, PendingThresholdConfirmations) => {
 		(0, first_broadcast_hash, required),
@@ -317,6 +449,194 @@ impl_writeable_tlv_based_enum!(OutputSpendStatus,
 	},
 );
 
+/// Configures how aggressively an [`OutputSweeper`] bumps the feerate of a sweeping transaction
+/// which fails to confirm in a timely manner.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SweepFeeBumpConfig {
+	/// The number of blocks a sweeping transaction may remain unconfirmed before we replace it
+	/// with one paying a higher feerate.
+	pub blocks_between_bumps: u32,
+	/// The percentage by which we increase the feerate of the previously broadcast sweeping
+	/// transaction upon each bump.
+	///
+	/// Regardless of this value, each bump increases the feerate by at least the incremental relay
+	/// fee, as otherwise the replacement would not be relayed.
+	pub feerate_increase_percent: u32,
+	/// The maximum feerate, in satoshis per 1000 weight units, we will bump to.
+	///
+	/// Note that if our [`FeeEstimator`] returns a higher feerate for
+	/// [`ConfirmationTarget::OutputSpendingFee`] we will still use it.
+	pub max_feerate_sat_per_1000_weight: u32,
+}
+
+/// A policy determining if and how an [`OutputSweeper`] bumps the feerate of sweeping
+/// transactions which fail to confirm, configurable per [`SpendableOutputDescriptor`] type.
+///
+/// By default, the sweeper regenerates its sweeping transaction once per block, paying the current
+/// [`ConfirmationTarget::OutputSpendingFee`] estimate, which may not suffice to replace the
+/// previously broadcast transaction. For output types with a [`SweepFeeBumpConfig`] set, the
+/// sweeper instead tracks the feerate it last broadcast at, never pays less than that, and
+/// increases it every [`SweepFeeBumpConfig::blocks_between_bumps`] blocks until the sweep
+/// confirms.
+///
+/// If outputs with different configurations are swept in the same transaction, the most
+/// aggressive applicable configuration determines its feerate.
+///
+/// Note that we bump fees via replacement rather than by attaching a child spend, as the sweeping
+/// transaction pays to a destination whose keys we generally don't control.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SweepFeeBumpPolicy {
+	/// The configuration for [`SpendableOutputDescriptor::StaticOutput`]s.
+	pub static_output: Option<SweepFeeBumpConfig>,
+	/// The configuration for [`SpendableOutputDescriptor::DelayedPaymentOutput`]s, i.e., our
+	/// `to_local` outputs.
+	pub delayed_payment_output: Option<SweepFeeBumpConfig>,
+	/// The configuration for [`SpendableOutputDescriptor::StaticPaymentOutput`]s, i.e., our
+	/// `to_remote` outputs.
+	pub static_payment_output: Option<SweepFeeBumpConfig>,
+}
+
+impl SweepFeeBumpPolicy {
+	fn config_for(&self, descriptor: &SpendableOutputDescriptor) -> Option<&SweepFeeBumpConfig> {
+		match descriptor {
+			SpendableOutputDescriptor::StaticOutput { .. } => self.static_output.as_ref(),
+			SpendableOutputDescriptor::DelayedPaymentOutput(_) => {
+				self.delayed_payment_output.as_ref()
+			},
+			SpendableOutputDescriptor::StaticPaymentOutput(_) => {
+				self.static_payment_output.as_ref()
+			},
+		}
+	}
+}
+
+/// Determines the feerate at which to (re-)spend the given outputs at `cur_height`, as well as the
+/// height to record as their latest fee bump.
+fn sweep_feerate<'a>(
+	policy: Option<&SweepFeeBumpPolicy>, outputs: impl Iterator<Item = &'a TrackedSpendableOutput>,
+	estimated_feerate: u32, cur_height: u32,
+) -> (u32, u32) {
+	let policy = match policy {
+		Some(policy) => policy,
+		None => return (estimated_feerate, cur_height),
+	};
+
+	let mut feerate = estimated_feerate;
+	let mut fee_bump_height = cur_height;
+	let mut bumped = false;
+	for output in outputs {
+		let config = match policy.config_for(&output.descriptor) {
+			Some(config) => config,
+			None => continue,
+		};
+		let (prev_feerate, prev_bump_height) = match output.status.latest_fee_bump_state() {
+			Some(state) => state,
+			None => continue,
+		};
+
+		let mut target_feerate = prev_feerate;
+		if cur_height >= prev_bump_height.saturating_add(config.blocks_between_bumps) {
+			let increased_feerate =
+				(prev_feerate as u64) * (100 + config.feerate_increase_percent as u64) / 100;
+			let min_replacement_feerate =
+				prev_feerate as u64 + INCREMENTAL_RELAY_FEE_SAT_PER_1000_WEIGHT;
+			let bumped_feerate = cmp::max(increased_feerate, min_replacement_feerate)
+				.min(config.max_feerate_sat_per_1000_weight as u64) as u32;
+			if bumped_feerate > prev_feerate {
+				target_feerate = bumped_feerate;
+				bumped = true;
+			}
+		}
+		feerate = cmp::max(feerate, target_feerate);
+		fee_bump_height = cmp::min(fee_bump_height, prev_bump_height);
+	}
+
+	if bumped {
+		fee_bump_height = cur_height;
+	}
+	(feerate, fee_bump_height)
+}
+
+/// A policy for deriving a fresh destination script for each sweep and cooperative close from a
+/// caller-provided extended public key, avoiding the address reuse encouraged by static
+/// [`ChangeDestinationSource`] or [`SignerProvider::get_shutdown_scriptpubkey`] implementations.
+///
+/// Destinations are derived as P2WPKH scripts at the non-hardened child `xpub/i`, where `i` is a
+/// monotonically increasing derivation index persisted as part of the [`OutputSweeper`]'s state.
+/// Note that, as a new destination is derived each time a sweeping transaction is regenerated,
+/// wallets scanning the `xpub` should use a gap limit large enough to cover sweeps which are
+/// rebroadcast over several blocks.
+///
+/// [`SignerProvider::get_shutdown_scriptpubkey`]: crate::sign::SignerProvider::get_shutdown_scriptpubkey
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DestinationRotationPolicy {
+	xpub: Xpub,
+}
+
+impl DestinationRotationPolicy {
+	/// Constructs a new [`DestinationRotationPolicy`] deriving destinations from the given `xpub`.
+	pub fn new(xpub: Xpub) -> Self {
+		Self { xpub }
+	}
+
+	/// Derives the P2WPKH destination script at the given derivation index.
+	///
+	/// Returns `Err` if `index` is not a valid non-hardened child number.
+	pub fn destination_script(&self, index: u32) -> Result<ScriptBuf, ()> {
+		self.derive_pubkey_hash(index).map(|hash| ScriptBuf::new_p2wpkh(&hash))
+	}
+
+	/// Derives the P2WPKH [`ShutdownScript`] at the given derivation index.
+	///
+	/// Returns `Err` if `index` is not a valid non-hardened child number.
+	pub fn shutdown_script(&self, index: u32) -> Result<ShutdownScript, ()> {
+		self.derive_pubkey_hash(index).map(|hash| ShutdownScript::new_p2wpkh(&hash))
+	}
+
+	fn derive_pubkey_hash(&self, index: u32) -> Result<WPubkeyHash, ()> {
+		let child_number = ChildNumber::from_normal_idx(index).map_err(|_| ())?;
+		let child = self.xpub.derive_pub(&Secp256k1::verification_only(), &[child_number]);
+		let pubkey = child.map_err(|_| ())?.public_key;
+		Ok(WPubkeyHash::hash(&pubkey.serialize()))
+	}
+}
+
+/// The decision of a [`SweepCoinControl`] on a proposed sweep.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SweepDecision {
+	/// Generate and broadcast the sweeping transaction.
+	Sweep {
+		/// The script the swept funds are sent to.
+		///
+		/// If `None`, the destination is determined as usual, i.e., via the configured
+		/// [`DestinationRotationPolicy`] or the [`ChangeDestinationSource`].
+		destination_script: Option<ScriptBuf>,
+		/// The maximum feerate, in satoshis per 1000 weight units, the sweeping transaction may
+		/// pay.
+		///
+		/// Note that capping the feerate below that of a previously broadcast sweep of the same
+		/// outputs will prevent the replacement from propagating.
+		max_feerate_sat_per_1000_weight: Option<u32>,
+	},
+	/// Don't sweep for now. The sweep will be proposed again on the next call to
+	/// [`OutputSweeper::regenerate_and_broadcast_spend_if_necessary`].
+	Defer,
+}
+
+/// A hook consulted before each sweeping transaction is constructed, allowing users with
+/// coin-control or labeling requirements to manage swept funds like the rest of their wallet.
+pub trait SweepCoinControl: MaybeSend + MaybeSync {
+	/// Decides whether and how the given `outputs` should be swept at the given feerate, in
+	/// satoshis per 1000 weight units, at the given best block height.
+	///
+	/// Note that this is called while the sweeper's state is locked and hence must not call back
+	/// into the [`OutputSweeper`].
+	fn decide_sweep(
+		&self, outputs: &[&TrackedSpendableOutput], feerate_sat_per_1000_weight: u32,
+		best_block_height: u32,
+	) -> SweepDecision;
+}
+
 /// A utility that keeps track of [`SpendableOutputDescriptor`]s, persists them in a given
 /// [`KVStore`] and regularly retries sweeping them based on a callback given to the constructor
 /// methods.
@@ -354,6 +674,9 @@ where
 	chain_data_source: Option<F>,
 	output_spender: O,
 	change_destination_source: D,
+	destination_rotation: Option<DestinationRotationPolicy>,
+	fee_bump_policy: Option<SweepFeeBumpPolicy>,
+	coin_control: Option<Box<dyn SweepCoinControl>>,
 	kv_store: K,
 	logger: L,
 }
@@ -378,7 +701,12 @@ where
 		output_spender: O, change_destination_source: D, kv_store: K, logger: L,
 	) -> Self {
 		let outputs = Vec::new();
-		let sweeper_state = Mutex::new(SweeperState { outputs, best_block, dirty: false });
+		let sweeper_state = Mutex::new(SweeperState {
+			outputs,
+			best_block,
+			next_destination_index: 0,
+			dirty: false,
+		});
 		Self {
 			sweeper_state,
 			pending_sweep: AtomicBool::new(false),
@@ -387,11 +715,64 @@ where
 			chain_data_source,
 			output_spender,
 			change_destination_source,
+			destination_rotation: None,
+			fee_bump_policy: None,
+			coin_control: None,
 			kv_store,
 			logger,
 		}
 	}
 
+	/// Sets a [`DestinationRotationPolicy`] to derive the destination of each sweeping
+	/// transaction from, rather than requesting it from the [`ChangeDestinationSource`].
+	///
+	/// This is not persisted and thus needs to be set again after the sweeper is read from disk.
+	/// The derivation index, however, is persisted, so that destinations are never reused across
+	/// restarts.
+	pub fn with_destination_rotation(mut self, policy: DestinationRotationPolicy) -> Self {
+		self.destination_rotation = Some(policy);
+		self
+	}
+
+	/// Sets a [`SweepFeeBumpPolicy`] determining how we bump the feerate of sweeping transactions
+	/// which fail to confirm.
+	///
+	/// This is not persisted and thus needs to be set again after the sweeper is read from disk.
+	pub fn with_fee_bump_policy(mut self, policy: SweepFeeBumpPolicy) -> Self {
+		self.fee_bump_policy = Some(policy);
+		self
+	}
+
+	/// Sets a [`SweepCoinControl`] which is consulted before each sweeping transaction is
+	/// constructed.
+	///
+	/// This is not persisted and thus needs to be set again after the sweeper is read from disk.
+	pub fn with_coin_control<C: SweepCoinControl + 'static>(mut self, coin_control: C) -> Self {
+		self.coin_control = Some(Box::new(coin_control));
+		self
+	}
+
+	/// Derives a fresh [`ShutdownScript`] from the configured [`DestinationRotationPolicy`],
+	/// persisting the advanced derivation index before returning it.
+	///
+	/// This is intended to be used by [`SignerProvider::get_shutdown_scriptpubkey`]
+	/// implementations, such that each cooperative close pays to a fresh destination.
+	///
+	/// Returns `Err` if no [`DestinationRotationPolicy`] was set or on persistence failure.
+	///
+	/// [`SignerProvider::get_shutdown_scriptpubkey`]: crate::sign::SignerProvider::get_shutdown_scriptpubkey
+	pub async fn next_shutdown_script(&self) -> Result<ShutdownScript, ()> {
+		let policy = self.destination_rotation.as_ref().ok_or(())?;
+		self.update_state(|sweeper_state| -> Result<(ShutdownScript, bool), ()> {
+			let index = sweeper_state.next_destination_index;
+			let shutdown_script = policy.shutdown_script(index)?;
+			sweeper_state.next_destination_index = index + 1;
+			sweeper_state.dirty = true;
+			Ok((shutdown_script, false))
+		})
+		.await
+	}
+
 	/// Tells the sweeper to track the given outputs descriptors.
 	///
 	/// Usually, this should be called based on the values emitted by the
@@ -431,6 +812,7 @@ where
 					status: OutputSpendStatus::PendingInitialBroadcast {
 						delayed_until_height: delay_until_height,
 					},
+					timelock: None,
 				};
 
 				if state_lock
@@ -451,11 +833,91 @@ where
 		.await
 	}
 
+	/// Tells the sweeper to track the given outputs descriptors before they become spendable,
+	/// e.g., the `to_self` output of a commitment transaction after a force close.
+	///
+	/// The sweeper will wait for the transaction creating the outputs to confirm (unless
+	/// `parent_confirmation` is given) and automatically include the outputs in a sweep once their
+	/// relative timelock and, if set, their `absolute_height_lock` expired. When the outputs are
+	/// later also emitted via [`Event::SpendableOutputs`], they do not need to be re-submitted.
+	///
+	/// Use [`Self::spendability_calendar`] to learn when the outputs become spendable.
+	///
+	/// If chain data is provided via the [`Confirm`] interface or via filtered blocks, a [`Filter`]
+	/// needs to be given on construction to learn about the confirmation of the transaction
+	/// creating the outputs. Note that a [`Filter`] generally doesn't learn about confirmations
+	/// which happened before the outputs are tracked, so `parent_confirmation` should be given in
+	/// that case.
+	///
+	/// Returns `Err` on persistence failure, in which case the call may be safely retried.
+	///
+	/// [`Event::SpendableOutputs`]: crate::events::Event::SpendableOutputs
+	pub async fn track_timelocked_outputs(
+		&self, output_descriptors: Vec<SpendableOutputDescriptor>, channel_id: Option<ChannelId>,
+		parent_confirmation: Option<BestBlock>, absolute_height_lock: Option<u32>,
+	) -> Result<(), ()> {
+		if output_descriptors.is_empty() {
+			return Ok(());
+		}
+
+		self.update_state(|state_lock| -> Result<((), bool), ()> {
+			for descriptor in output_descriptors {
+				if state_lock.outputs.iter().any(|o| o.descriptor == descriptor) {
+					continue;
+				}
+
+				if parent_confirmation.is_none() {
+					if let Some(filter) = self.chain_data_source.as_ref() {
+						let outpoint = descriptor.spendable_outpoint();
+						let script_pubkey = &descriptor_output(&descriptor).script_pubkey;
+						filter.register_tx(&outpoint.txid, script_pubkey);
+					}
+				}
+
+				let output_info = TrackedSpendableOutput {
+					descriptor,
+					channel_id,
+					status: OutputSpendStatus::PendingInitialBroadcast {
+						delayed_until_height: None,
+					},
+					timelock: Some(OutputTimelock { parent_confirmation, absolute_height_lock }),
+				};
+				state_lock.outputs.push(output_info);
+				state_lock.dirty = true;
+			}
+
+			Ok(((), false))
+		})
+		.await
+	}
+
 	/// Returns a list of the currently tracked spendable outputs.
 	pub fn tracked_spendable_outputs(&self) -> Vec<TrackedSpendableOutput> {
 		self.sweeper_state.lock().unwrap().outputs.clone()
 	}
 
+	/// Returns when each output tracked via [`Self::track_timelocked_outputs`] which we haven't
+	/// started sweeping yet becomes spendable, ordered by the height at which they do.
+	///
+	/// Outputs whose creating transaction hasn't been confirmed yet are listed last.
+	pub fn spendability_calendar(&self) -> Vec<SpendabilityCalendarEntry> {
+		let state_lock = self.sweeper_state.lock().unwrap();
+		let mut calendar = state_lock
+			.outputs
+			.iter()
+			.filter(|o| o.timelock.is_some())
+			.filter(|o| matches!(o.status, OutputSpendStatus::PendingInitialBroadcast { .. }))
+			.map(|o| SpendabilityCalendarEntry {
+				outpoint: o.descriptor.spendable_outpoint(),
+				channel_id: o.channel_id,
+				value: descriptor_output(&o.descriptor).value,
+				spendable_at_height: o.spendable_at_height(),
+			})
+			.collect::<Vec<_>>();
+		calendar.sort_unstable_by_key(|entry| entry.spendable_at_height.unwrap_or(u32::MAX));
+		calendar
+	}
+
 	/// Gets the latest best block which was connected either via the [`Listen`] or
 	/// [`Confirm`] interfaces.
 	pub fn current_best_block(&self) -> BestBlock {
@@ -495,6 +957,11 @@ where
 				return false;
 			}
 
+			if !o.is_mature(cur_height) {
+				// Don't generate and broadcast if still timelocked
+				return false;
+			}
+
 			if o.status.latest_broadcast_height() >= Some(cur_height) {
 				// Only broadcast once per block height.
 				return false;
@@ -518,11 +985,16 @@ where
 			return Ok(());
 		}
 
-		// Request a new change address outside of the mutex to avoid the mutex crossing await.
-		let change_destination_script =
-			self.change_destination_source.get_change_destination_script().await?;
+		// Request a new change address outside of the mutex to avoid the mutex crossing await,
+		// unless we derive it ourselves while holding the mutex below.
+		let change_destination_script = if self.destination_rotation.is_none() {
+			Some(self.change_destination_source.get_change_destination_script().await?)
+		} else {
+			None
+		};
 
 		// Sweep the outputs.
+		let mut sweep_channel_id = None;
 		let spending_tx = self
 			.update_state(|sweeper_state| -> Result<(Option<Transaction>, bool), ()> {
 				let cur_height = sweeper_state.best_block.height;
@@ -543,11 +1015,56 @@ where
 
 				// Generate the spending transaction and broadcast it.
 				if !respend_descriptors.is_empty() {
+					let estimated_feerate = self
+						.fee_estimator
+						.get_est_sat_per_1000_weight(ConfirmationTarget::OutputSpendingFee);
+					let (mut tx_feerate, fee_bump_height) = sweep_feerate(
+						self.fee_bump_policy.as_ref(),
+						sweeper_state.outputs.iter().filter(|o| filter_fn(*o, cur_height)),
+						estimated_feerate,
+						cur_height,
+					);
+
+					let mut destination_override = None;
+					if let Some(coin_control) = self.coin_control.as_ref() {
+						let outputs = sweeper_state
+							.outputs
+							.iter()
+							.filter(|o| filter_fn(*o, cur_height))
+							.collect::<Vec<_>>();
+						match coin_control.decide_sweep(&outputs, tx_feerate, cur_height) {
+							SweepDecision::Sweep {
+								destination_script,
+								max_feerate_sat_per_1000_weight,
+							} => {
+								if let Some(max_feerate) = max_feerate_sat_per_1000_weight {
+									tx_feerate = cmp::min(tx_feerate, max_feerate);
+								}
+								destination_override = destination_script;
+							},
+							SweepDecision::Defer => {
+								log_debug!(
+									self.logger,
+									"Deferring sweep of {} outputs as requested by coin control",
+									outputs.len()
+								);
+								return Ok((None, false));
+							},
+						}
+					}
+
+					let change_destination_script =
+						match (destination_override, change_destination_script) {
+							(Some(script), _) => script,
+							(None, Some(script)) => script,
+							(None, None) => self.next_rotated_destination_script(sweeper_state)?,
+						};
 					let spending_tx = self
 						.spend_outputs(
 							&sweeper_state,
 							&respend_descriptors,
 							change_destination_script,
+							tx_feerate,
 						)
 						.map_err(|e| {
 							log_error!(self.logger, "Error spending outputs: {:?}", e);
@@ -555,12 +1072,23 @@ where
 
 					log_debug!(
 						self.logger,
-						"Generating and broadcasting sweeping transaction {}",
-						spending_tx.compute_txid()
+						"Generating and broadcasting sweeping transaction {} at {} sat/kW",
+						spending_tx.compute_txid(),
+						tx_feerate
 					);
 
 					// As we didn't modify the state so far, the same filter_fn yields the same elements as
 					// above.
+					let mut channel_ids = sweeper_state
+						.outputs
+						.iter()
+						.filter(|o| filter_fn(*o, cur_height))
+						.map(|o| o.channel_id);
+					let first_channel_id = channel_ids.next().flatten();
+					if channel_ids.all(|id| id == first_channel_id) {
+						sweep_channel_id = first_channel_id;
+					}
+
 					let respend_outputs =
 						sweeper_state.outputs.iter_mut().filter(|o| filter_fn(&**o, cur_height));
 					for output_info in respend_outputs {
@@ -569,7 +1097,13 @@ where
 							filter.register_output(watched_output);
 						}
 
-						output_info.status.broadcast(cur_hash, cur_height, spending_tx.clone());
+						output_info.status.broadcast(
+							cur_hash,
+							cur_height,
+							spending_tx.clone(),
+							tx_feerate,
+							fee_bump_height,
+						);
 						sweeper_state.dirty = true;
 					}
 
@@ -582,12 +1116,30 @@ where
 
 		// Persistence completely successfully. If we have a spending transaction, we broadcast it.
 		if let Some(spending_tx) = spending_tx {
-			self.broadcaster.broadcast_transactions(&[&spending_tx]);
+			let context = BroadcastContext {
+				broadcast_type: BroadcastType::Sweep,
+				channel_id: sweep_channel_id,
+				urgency: Some(ConfirmationTarget::OutputSpendingFee),
+			};
+			self.broadcaster.broadcast_transactions(&[&spending_tx], context);
 		}
 
 		Ok(())
 	}
 
+	fn next_rotated_destination_script(
+		&self, sweeper_state: &mut SweeperState,
+	) -> Result<ScriptBuf, ()> {
+		let policy = self.destination_rotation.as_ref().ok_or(())?;
+		let index = sweeper_state.next_destination_index;
+		let script = policy.destination_script(index).map_err(|()| {
+			log_error!(self.logger, "Failed to derive sweep destination at index {}", index);
+		})?;
+		sweeper_state.next_destination_index = index + 1;
+		sweeper_state.dirty = true;
+		Ok(script)
+	}
+
 	fn prune_confirmed_outputs(&self, sweeper_state: &mut SweeperState) {
 		let cur_height = sweeper_state.best_block.height;
 
@@ -668,10 +1220,8 @@ where
 
 	fn spend_outputs(
 		&self, sweeper_state: &SweeperState, descriptors: &[&SpendableOutputDescriptor],
-		change_destination_script: ScriptBuf,
+		change_destination_script: ScriptBuf, tx_feerate: u32,
 	) -> Result<Transaction, ()> {
-		let tx_feerate =
-			self.fee_estimator.get_est_sat_per_1000_weight(ConfirmationTarget::OutputSpendingFee);
 		let cur_height = sweeper_state.best_block.height;
 		let locktime = Some(LockTime::from_height(cur_height).unwrap_or(LockTime::ZERO));
 		self.output_spender.spend_spendable_outputs(
@@ -690,10 +1240,17 @@ where
 	) {
 		let confirmation_hash = header.block_hash();
 		for (_, tx) in txdata {
+			let txid = tx.compute_txid();
 			for output_info in sweeper_state.outputs.iter_mut() {
 				if output_info.is_spent_in(*tx) {
 					output_info.status.confirmed(confirmation_hash, height, (*tx).clone())
 				}
+				if let Some(timelock) = output_info.timelock.as_mut() {
+					if output_info.descriptor.spendable_outpoint().txid == txid {
+						timelock.parent_confirmation =
+							Some(BestBlock::new(confirmation_hash, height));
+					}
+				}
 			}
 		}
 
@@ -745,6 +1302,12 @@ where
 			if output_info.status.confirmation_height() > Some(fork_point.height) {
 				output_info.status.unconfirmed();
 			}
+			if let Some(timelock) = output_info.timelock.as_mut() {
+				if timelock.parent_confirmation.as_ref().map(|b| b.height) > Some(fork_point.height)
+				{
+					timelock.parent_confirmation = None;
+				}
+			}
 		}
 
 		state_lock.dirty = true;
@@ -789,6 +1352,20 @@ where
 
 			state_lock.dirty = true;
 		}
+
+		// Timelocked outputs created by the unconfirmed transaction are no longer maturing.
+		let mut parent_unconfirmed = false;
+		for output_info in state_lock.outputs.iter_mut() {
+			if output_info.descriptor.spendable_outpoint().txid != *txid {
+				continue;
+			}
+			if let Some(timelock) = output_info.timelock.as_mut() {
+				parent_unconfirmed |= timelock.parent_confirmation.take().is_some();
+			}
+		}
+		if parent_unconfirmed {
+			state_lock.dirty = true;
+		}
 	}
 
 	fn best_block_updated(&self, header: &Header, height: u32) {
@@ -812,6 +1389,15 @@ where
 					confirmation_height,
 					Some(confirmation_hash),
 				)),
+				OutputSpendStatus::PendingInitialBroadcast { .. } => {
+					// Track the confirmation of transactions creating timelocked outputs.
+					let parent_confirmation = o.timelock.as_ref()?.parent_confirmation.as_ref()?;
+					Some((
+						o.descriptor.spendable_outpoint().txid,
+						parent_confirmation.height,
+						Some(parent_confirmation.block_hash),
+					))
+				},
 				_ => None,
 			})
 			.collect::<Vec<_>>()
@@ -822,11 +1408,13 @@ where
 struct SweeperState {
 	outputs: Vec<TrackedSpendableOutput>,
 	best_block: BestBlock,
+	next_destination_index: u32,
 	dirty: bool,
 }
 
 impl_writeable_tlv_based!(SweeperState, {
 	(0, outputs, required_vec),
+	(1, next_destination_index, (default_value, 0)),
 	(2, best_block, required),
 	(_unused, dirty, (static_value, false)),
 });
@@ -893,6 +1481,8 @@ where
 				chain_data_source,
 				output_spender,
 				change_destination_source,
+				destination_rotation: None,
+				fee_bump_policy: None,
 				kv_store,
 				logger,
 			},
@@ -969,6 +1559,49 @@ where
 		Self { sweeper }
 	}
 
+	/// Sets a [`DestinationRotationPolicy`] to derive the destination of each sweeping
+	/// transaction from, rather than requesting it from the [`ChangeDestinationSourceSync`].
+	///
+	/// Wraps [`OutputSweeper::with_destination_rotation`].
+	pub fn with_destination_rotation(self, policy: DestinationRotationPolicy) -> Self {
+		Self { sweeper: self.sweeper.with_destination_rotation(policy) }
+	}
+
+	/// Sets a [`SweepFeeBumpPolicy`] determining how we bump the feerate of sweeping transactions
+	/// which fail to confirm.
+	///
+	/// Wraps [`OutputSweeper::with_fee_bump_policy`].
+	pub fn with_fee_bump_policy(self, policy: SweepFeeBumpPolicy) -> Self {
+		Self { sweeper: self.sweeper.with_fee_bump_policy(policy) }
+	}
+
+	/// Sets a [`SweepCoinControl`] which is consulted before each sweeping transaction is
+	/// constructed.
+	///
+	/// Wraps [`OutputSweeper::with_coin_control`].
+	pub fn with_coin_control<C: SweepCoinControl + 'static>(self, coin_control: C) -> Self {
+		Self { sweeper: self.sweeper.with_coin_control(coin_control) }
+	}
+
+	/// Derives a fresh [`ShutdownScript`] from the configured [`DestinationRotationPolicy`],
+	/// persisting the advanced derivation index before returning it.
+	///
+	/// Wraps [`OutputSweeper::next_shutdown_script`].
+	pub fn next_shutdown_script(&self) -> Result<ShutdownScript, ()> {
+		let mut fut = pin!(self.sweeper.next_shutdown_script());
+		let mut waker = dummy_waker();
+		let mut ctx = task::Context::from_waker(&mut waker);
+		match fut.as_mut().poll(&mut ctx) {
+			task::Poll::Ready(result) => result,
+			task::Poll::Pending => {
+				// In a sync context, we can't wait for the future to complete.
+				unreachable!(
+					"OutputSweeper::next_shutdown_script should not be pending in a sync context"
+				);
+			},
+		}
+	}
+
 	/// Tells the sweeper to track the given outputs descriptors.
 	///
 	/// Usually, this should be called based on the values emitted by the
@@ -1005,6 +1638,30 @@ where
 		}
 	}
 
+	/// Tells the sweeper to track the given outputs descriptors before they become spendable.
+	///
+	/// Wraps [`OutputSweeper::track_timelocked_outputs`].
+	pub fn track_timelocked_outputs(
+		&self, output_descriptors: Vec<SpendableOutputDescriptor>, channel_id: Option<ChannelId>,
+		parent_confirmation: Option<BestBlock>, absolute_height_lock: Option<u32>,
+	) -> Result<(), ()> {
+		let mut fut = pin!(self.sweeper.track_timelocked_outputs(
+			output_descriptors,
+			channel_id,
+			parent_confirmation,
+			absolute_height_lock,
+		));
+		let mut waker = dummy_waker();
+		let mut ctx = task::Context::from_waker(&mut waker);
+		match fut.as_mut().poll(&mut ctx) {
+			task::Poll::Ready(result) => result,
+			task::Poll::Pending => {
+				// In a sync context, we can't wait for the future to complete.
+				unreachable!("OutputSweeper::track_timelocked_outputs should not be pending in a sync context");
+			},
+		}
+	}
+
 	/// Returns a list of the currently tracked spendable outputs.
 	///
 	/// Wraps [`OutputSweeper::tracked_spendable_outputs`].
@@ -1012,6 +1669,13 @@ where
 		self.sweeper.tracked_spendable_outputs()
 	}
 
+	/// Returns when each tracked timelocked output becomes spendable.
+	///
+	/// Wraps [`OutputSweeper::spendability_calendar`].
+	pub fn spendability_calendar(&self) -> Vec<SpendabilityCalendarEntry> {
+		self.sweeper.spendability_calendar()
+	}
+
 	/// Gets the latest best block which was connected either via [`Listen`] or [`Confirm`]
 	/// interfaces.
 	pub fn current_best_block(&self) -> BestBlock {
@@ -1129,3 +1793,153 @@ where
 		Ok((best_block, OutputSweeperSync { sweeper }))
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::{
+		sweep_feerate, DestinationRotationPolicy, OutputSpendStatus, OutputTimelock,
+		SweepFeeBumpConfig, SweepFeeBumpPolicy, TrackedSpendableOutput,
+	};
+	use crate::chain::transaction::OutPoint;
+	use crate::chain::BestBlock;
+	use crate::ln::channel_keys::RevocationKey;
+	use crate::sign::{DelayedPaymentOutputDescriptor, SpendableOutputDescriptor};
+
+	use bitcoin::bip32::{Xpriv, Xpub};
+	use bitcoin::hashes::Hash;
+	use bitcoin::locktime::absolute::LockTime;
+	use bitcoin::secp256k1::{PublicKey, Secp256k1, SecretKey};
+	use bitcoin::transaction::Version;
+	use bitcoin::{Amount, BlockHash, Network, ScriptBuf, Transaction, TxOut, Txid};
+
+	#[test]
+	fn destination_rotation_derives_fresh_scripts() {
+		let secp_ctx = Secp256k1::new();
+		let xpriv = Xpriv::new_master(Network::Testnet, &[42; 32]).unwrap();
+		let policy = DestinationRotationPolicy::new(Xpub::from_priv(&secp_ctx, &xpriv));
+
+		let first = policy.destination_script(0).unwrap();
+		let second = policy.destination_script(1).unwrap();
+		assert!(first.is_p2wpkh());
+		assert_ne!(first, second);
+		assert_eq!(policy.destination_script(0).unwrap(), first);
+		assert_eq!(policy.shutdown_script(1).unwrap().into_inner(), second);
+
+		// Hardened indices cannot be derived from an `Xpub`.
+		assert!(policy.destination_script(1 << 31).is_err());
+	}
+
+	fn tracked_static_output(
+		idx: u16, latest_fee_bump_state: Option<(u32, u32)>,
+	) -> TrackedSpendableOutput {
+		let descriptor = SpendableOutputDescriptor::StaticOutput {
+			outpoint: OutPoint { txid: Txid::all_zeros(), index: idx },
+			output: TxOut { value: Amount::from_sat(10_000), script_pubkey: ScriptBuf::new() },
+			channel_keys_id: None,
+		};
+		let status = match latest_fee_bump_state {
+			Some((feerate, height)) => OutputSpendStatus::PendingFirstConfirmation {
+				first_broadcast_hash: BlockHash::all_zeros(),
+				latest_broadcast_height: height,
+				latest_spending_tx: Transaction {
+					version: Version::TWO,
+					lock_time: LockTime::ZERO,
+					input: Vec::new(),
+					output: Vec::new(),
+				},
+				latest_feerate_sat_per_1000_weight: Some(feerate),
+				latest_fee_bump_height: Some(height),
+			},
+			None => OutputSpendStatus::PendingInitialBroadcast { delayed_until_height: None },
+		};
+		TrackedSpendableOutput { descriptor, channel_id: None, status, timelock: None }
+	}
+
+	#[test]
+	fn sweep_feerate_bumps_unconfirmed_sweeps() {
+		let config = SweepFeeBumpConfig {
+			blocks_between_bumps: 6,
+			feerate_increase_percent: 25,
+			max_feerate_sat_per_1000_weight: 5000,
+		};
+		let policy = SweepFeeBumpPolicy { static_output: Some(config), ..Default::default() };
+
+		// Without a policy, we always use the current estimate.
+		let outputs = [tracked_static_output(0, Some((2000, 100)))];
+		assert_eq!(sweep_feerate(None, outputs.iter(), 1000, 110), (1000, 110));
+
+		// Outputs which haven't been broadcast yet use the current estimate.
+		let outputs = [tracked_static_output(0, None)];
+		assert_eq!(sweep_feerate(Some(&policy), outputs.iter(), 1000, 110), (1000, 110));
+
+		// Before the bump is due, we never pay less than we did previously.
+		let outputs = [tracked_static_output(0, Some((2000, 100)))];
+		assert_eq!(sweep_feerate(Some(&policy), outputs.iter(), 1000, 105), (2000, 100));
+		assert_eq!(sweep_feerate(Some(&policy), outputs.iter(), 3000, 105), (3000, 100));
+
+		// Once due, we bump by the configured percentage, or at least the incremental relay fee.
+		assert_eq!(sweep_feerate(Some(&policy), outputs.iter(), 1000, 106), (2500, 106));
+		let outputs = [tracked_static_output(0, Some((300, 100)))];
+		assert_eq!(sweep_feerate(Some(&policy), outputs.iter(), 253, 106), (553, 106));
+
+		// We never bump beyond the configured maximum.
+		let outputs = [tracked_static_output(0, Some((4500, 100)))];
+		assert_eq!(sweep_feerate(Some(&policy), outputs.iter(), 1000, 106), (5000, 106));
+		let outputs = [tracked_static_output(0, Some((5000, 100)))];
+		assert_eq!(sweep_feerate(Some(&policy), outputs.iter(), 1000, 106), (5000, 100));
+
+		// Outputs joining an already broadcast sweep don't reset its bump schedule.
+		let outputs = [tracked_static_output(0, Some((2000, 100))), tracked_static_output(1, None)];
+		assert_eq!(sweep_feerate(Some(&policy), outputs.iter(), 1000, 104), (2000, 100));
+	}
+
+	#[test]
+	fn timelocked_outputs_mature_at_expected_height() {
+		let secp_ctx = Secp256k1::new();
+		let pubkey =
+			PublicKey::from_secret_key(&secp_ctx, &SecretKey::from_slice(&[42; 32]).unwrap());
+		let descriptor =
+			SpendableOutputDescriptor::DelayedPaymentOutput(DelayedPaymentOutputDescriptor {
+				outpoint: OutPoint { txid: Txid::all_zeros(), index: 0 },
+				per_commitment_point: pubkey,
+				to_self_delay: 144,
+				output: TxOut { value: Amount::from_sat(10_000), script_pubkey: ScriptBuf::new() },
+				revocation_pubkey: RevocationKey(pubkey),
+				channel_keys_id: [0; 32],
+				channel_value_satoshis: 100_000,
+				channel_transaction_parameters: None,
+			});
+		let mut output = TrackedSpendableOutput {
+			descriptor,
+			channel_id: None,
+			status: OutputSpendStatus::PendingInitialBroadcast { delayed_until_height: None },
+			timelock: Some(OutputTimelock {
+				parent_confirmation: None,
+				absolute_height_lock: None,
+			}),
+		};
+
+		// Until the parent confirms, we can't tell when the output becomes spendable.
+		assert_eq!(output.spendable_at_height(), None);
+		assert!(!output.is_mature(u32::MAX - 1));
+
+		// Once it does, the output may be spent in the block `to_self_delay` blocks later, so we
+		// broadcast a spend one block before.
+		let parent_confirmation = Some(BestBlock::new(BlockHash::all_zeros(), 100));
+		output.timelock.as_mut().unwrap().parent_confirmation = parent_confirmation;
+		assert_eq!(output.spendable_at_height(), Some(244));
+		assert!(!output.is_mature(242));
+		assert!(output.is_mature(243));
+
+		// An absolute lock beyond the relative one delays spending further.
+		output.timelock.as_mut().unwrap().absolute_height_lock = Some(300);
+		assert_eq!(output.spendable_at_height(), Some(301));
+		assert!(!output.is_mature(299));
+		assert!(output.is_mature(300));
+
+		// Outputs which aren't timelocked are always mature.
+		let static_output = tracked_static_output(0, None);
+		assert_eq!(static_output.spendable_at_height(), Some(0));
+		assert!(static_output.is_mature(0));
+	}
+}
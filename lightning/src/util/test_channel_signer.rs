@@ -527,61 +527,144 @@ impl EcdsaChannelSigner for TestChannelSigner {
 }
 
 #[cfg(taproot)]
-#[allow(unused)]
 impl TaprootChannelSigner for TestChannelSigner {
 	fn generate_local_nonce_pair(
 		&self, commitment_number: u64, secp_ctx: &Secp256k1<All>,
 	) -> PublicNonce {
-		todo!()
+		TaprootChannelSigner::generate_local_nonce_pair(&self.inner, commitment_number, secp_ctx)
 	}
 
 	fn partially_sign_counterparty_commitment(
-		&self, counterparty_nonce: PublicNonce, commitment_tx: &CommitmentTransaction,
-		inbound_htlc_preimages: Vec<PaymentPreimage>,
+		&self, channel_parameters: &ChannelTransactionParameters, counterparty_nonce: PublicNonce,
+		commitment_tx: &CommitmentTransaction, inbound_htlc_preimages: Vec<PaymentPreimage>,
 		outbound_htlc_preimages: Vec<PaymentPreimage>, secp_ctx: &Secp256k1<All>,
 	) -> Result<(PartialSignatureWithNonce, Vec<secp256k1::schnorr::Signature>), ()> {
-		todo!()
+		#[cfg(test)]
+		if !self.is_signer_available(SignerOp::SignCounterpartyCommitment) {
+			return Err(());
+		}
+		TaprootChannelSigner::partially_sign_counterparty_commitment(
+			&self.inner,
+			channel_parameters,
+			counterparty_nonce,
+			commitment_tx,
+			inbound_htlc_preimages,
+			outbound_htlc_preimages,
+			secp_ctx,
+		)
 	}
 
 	fn finalize_holder_commitment(
-		&self, commitment_tx: &HolderCommitmentTransaction,
+		&self, channel_parameters: &ChannelTransactionParameters,
+		commitment_tx: &HolderCommitmentTransaction,
 		counterparty_partial_signature: PartialSignatureWithNonce, secp_ctx: &Secp256k1<All>,
 	) -> Result<PartialSignature, ()> {
-		todo!()
+		#[cfg(test)]
+		if !self.is_signer_available(SignerOp::SignHolderCommitment) {
+			return Err(());
+		}
+		TaprootChannelSigner::finalize_holder_commitment(
+			&self.inner,
+			channel_parameters,
+			commitment_tx,
+			counterparty_partial_signature,
+			secp_ctx,
+		)
 	}
 
 	fn sign_justice_revoked_output(
-		&self, justice_tx: &Transaction, input: usize, amount: u64, per_commitment_key: &SecretKey,
-		secp_ctx: &Secp256k1<All>,
+		&self, channel_parameters: &ChannelTransactionParameters, justice_tx: &Transaction,
+		input: usize, amount: u64, per_commitment_key: &SecretKey, secp_ctx: &Secp256k1<All>,
 	) -> Result<secp256k1::schnorr::Signature, ()> {
-		todo!()
+		#[cfg(test)]
+		if !self.is_signer_available(SignerOp::SignJusticeRevokedOutput) {
+			return Err(());
+		}
+		TaprootChannelSigner::sign_justice_revoked_output(
+			&self.inner,
+			channel_parameters,
+			justice_tx,
+			input,
+			amount,
+			per_commitment_key,
+			secp_ctx,
+		)
 	}
 
 	fn sign_justice_revoked_htlc(
-		&self, justice_tx: &Transaction, input: usize, amount: u64, per_commitment_key: &SecretKey,
-		htlc: &HTLCOutputInCommitment, secp_ctx: &Secp256k1<All>,
+		&self, channel_parameters: &ChannelTransactionParameters, justice_tx: &Transaction,
+		input: usize, amount: u64, per_commitment_key: &SecretKey, htlc: &HTLCOutputInCommitment,
+		secp_ctx: &Secp256k1<All>,
 	) -> Result<secp256k1::schnorr::Signature, ()> {
-		todo!()
+		#[cfg(test)]
+		if !self.is_signer_available(SignerOp::SignJusticeRevokedHtlc) {
+			return Err(());
+		}
+		TaprootChannelSigner::sign_justice_revoked_htlc(
+			&self.inner,
+			channel_parameters,
+			justice_tx,
+			input,
+			amount,
+			per_commitment_key,
+			htlc,
+			secp_ctx,
+		)
 	}
 
 	fn sign_holder_htlc_transaction(
 		&self, htlc_tx: &Transaction, input: usize, htlc_descriptor: &HTLCDescriptor,
 		secp_ctx: &Secp256k1<All>,
 	) -> Result<secp256k1::schnorr::Signature, ()> {
-		todo!()
+		#[cfg(test)]
+		if !self.is_signer_available(SignerOp::SignHolderHtlcTransaction) {
+			return Err(());
+		}
+		TaprootChannelSigner::sign_holder_htlc_transaction(
+			&self.inner,
+			htlc_tx,
+			input,
+			htlc_descriptor,
+			secp_ctx,
+		)
 	}
 
 	fn sign_counterparty_htlc_transaction(
-		&self, htlc_tx: &Transaction, input: usize, amount: u64, per_commitment_point: &PublicKey,
-		htlc: &HTLCOutputInCommitment, secp_ctx: &Secp256k1<All>,
+		&self, channel_parameters: &ChannelTransactionParameters, htlc_tx: &Transaction,
+		input: usize, amount: u64, per_commitment_point: &PublicKey, htlc: &HTLCOutputInCommitment,
+		secp_ctx: &Secp256k1<All>,
 	) -> Result<secp256k1::schnorr::Signature, ()> {
-		todo!()
+		#[cfg(test)]
+		if !self.is_signer_available(SignerOp::SignCounterpartyHtlcTransaction) {
+			return Err(());
+		}
+		TaprootChannelSigner::sign_counterparty_htlc_transaction(
+			&self.inner,
+			channel_parameters,
+			htlc_tx,
+			input,
+			amount,
+			per_commitment_point,
+			htlc,
+			secp_ctx,
+		)
 	}
 
 	fn partially_sign_closing_transaction(
-		&self, closing_tx: &ClosingTransaction, secp_ctx: &Secp256k1<All>,
-	) -> Result<PartialSignature, ()> {
-		todo!()
+		&self, channel_parameters: &ChannelTransactionParameters, counterparty_nonce: PublicNonce,
+		closing_tx: &ClosingTransaction, secp_ctx: &Secp256k1<All>,
+	) -> Result<PartialSignatureWithNonce, ()> {
+		#[cfg(test)]
+		if !self.is_signer_available(SignerOp::SignClosingTransaction) {
+			return Err(());
+		}
+		TaprootChannelSigner::partially_sign_closing_transaction(
+			&self.inner,
+			channel_parameters,
+			counterparty_nonce,
+			closing_tx,
+			secp_ctx,
+		)
 	}
 }
 
@@ -12,6 +12,7 @@ use crate::blinded_path::message::{BlindedMessagePath, MessageForwardNode};
 use crate::blinded_path::payment::{BlindedPaymentPath, ReceiveTlvs};
 use crate::chain;
 use crate::chain::chaininterface;
+use crate::chain::chaininterface::BroadcastContext;
 use crate::chain::chaininterface::ConfirmationTarget;
 #[cfg(any(test, feature = "_externalize_tests"))]
 use crate::chain::chaininterface::FEERATE_FLOOR_SATS_PER_KW;
@@ -23,8 +24,9 @@ use crate::chain::transaction::OutPoint;
 use crate::chain::WatchedOutput;
 use crate::events::bump_transaction::sync::WalletSourceSync;
 use crate::events::bump_transaction::Utxo;
-#[cfg(any(test, feature = "_externalize_tests"))]
-use crate::ln::chan_utils::CommitmentTransaction;
+use crate::ln::chan_utils::{
+	ChannelTransactionParameters, CommitmentTransaction, HTLCOutputInCommitment,
+};
 use crate::ln::channel_state::ChannelDetails;
 use crate::ln::channelmanager;
 use crate::ln::inbound_payment::ExpandedKey;
@@ -46,10 +48,13 @@ use crate::routing::router::{
 };
 use crate::routing::scoring::{ChannelUsage, ScoreLookUp, ScoreUpdate};
 use crate::routing::utxo::{UtxoLookup, UtxoLookupError, UtxoResult};
+use crate::sign::tx_builder::{
+	CommitmentStats, HTLCAmountDirection, NextCommitmentStats, SpecTxBuilder, TxBuilder,
+};
 use crate::sign::{self, ReceiveAuthKey};
 use crate::sign::{ChannelSigner, PeerStorageKey};
 use crate::sync::RwLock;
-use crate::types::features::{ChannelFeatures, InitFeatures, NodeFeatures};
+use crate::types::features::{ChannelFeatures, ChannelTypeFeatures, InitFeatures, NodeFeatures};
 use crate::util::async_poll::MaybeSend;
 use crate::util::config::UserConfig;
 use crate::util::dyn_signer::{
@@ -156,12 +161,13 @@ impl TestFeeEstimator {
 }
 impl chaininterface::FeeEstimator for TestFeeEstimator {
 	fn get_est_sat_per_1000_weight(&self, conf_target: ConfirmationTarget) -> u32 {
-		*self
-			.target_override
-			.lock()
-			.unwrap()
-			.get(&conf_target)
-			.unwrap_or(&*self.sat_per_kw.lock().unwrap())
+		let target_override = self.target_override.lock().unwrap();
+		let mut feerate = target_override.get(&conf_target);
+		if let ConfirmationTarget::UrgentOnChainSweepWithDeadline { .. } = conf_target {
+			// Unless overridden, deadline-aware claims use the same feerate as other urgent ones.
+			feerate = feerate.or(target_override.get(&ConfirmationTarget::UrgentOnChainSweep));
+		}
+		*feerate.unwrap_or(&*self.sat_per_kw.lock().unwrap())
 	}
 }
 
@@ -634,7 +640,7 @@ impl<'a> chain::Watch<TestChannelSigner> for TestChainMonitor<'a> {
 			assert_eq!(channel_id, exp.0);
 			assert_eq!(update.updates.len(), 1);
 			let update = &update.updates[0];
-			if let ChannelMonitorUpdateStep::ChannelForceClosed { should_broadcast } = update {
+			if let ChannelMonitorUpdateStep::ChannelForceClosed { should_broadcast, .. } = update {
 				assert_eq!(*should_broadcast, exp.1);
 			} else {
 				panic!();
@@ -672,6 +678,10 @@ impl<'a> chain::Watch<TestChannelSigner> for TestChainMonitor<'a> {
 	) -> Vec<(OutPoint, ChannelId, Vec<MonitorEvent>, PublicKey)> {
 		return self.chain_monitor.release_pending_monitor_events();
 	}
+
+	fn get_broadcast_holder_commitment_txid(&self, channel_id: ChannelId) -> Option<Txid> {
+		self.chain_monitor.get_broadcast_holder_commitment_txid(channel_id)
+	}
 }
 
 #[cfg(any(test, feature = "_externalize_tests"))]
@@ -692,6 +702,12 @@ pub(crate) struct WatchtowerPersister {
 	/// After receiving a revoke_and_ack for a commitment number, we'll form and store the justice
 	/// tx which would be used to provide a watchtower with the data it needs.
 	watchtower_state: Mutex<HashMap<ChannelId, HashMap<Txid, Transaction>>>,
+	/// Counterparty commitment transactions with revokeable outputs which have not yet been
+	/// revoked, in the order we received them.
+	unrevoked_commitment_txs: Mutex<HashMap<ChannelId, VecDeque<CommitmentTransaction>>>,
+	/// Justice txs claiming all revokeable outputs of a revoked commitment, as signed by
+	/// [`ChannelMonitor::sign_justice_tx_for_commitment`].
+	full_justice_txs: Mutex<HashMap<ChannelId, HashMap<Txid, Transaction>>>,
 	destination_script: ScriptBuf,
 }
 
@@ -704,10 +720,47 @@ impl WatchtowerPersister {
 			persister: TestPersister::new(),
 			unsigned_justice_tx_data,
 			watchtower_state,
+			unrevoked_commitment_txs: Mutex::new(new_hash_map()),
+			full_justice_txs: Mutex::new(new_hash_map()),
 			destination_script,
 		}
 	}
 
+	pub(crate) fn full_justice_tx(
+		&self, channel_id: ChannelId, commitment_txid: &Txid,
+	) -> Option<Transaction> {
+		self.full_justice_txs.lock().unwrap().get(&channel_id)?.get(commitment_txid).cloned()
+	}
+
+	fn track_commitment_txs<Signer: sign::ecdsa::EcdsaChannelSigner>(
+		&self, commitment_txs: Vec<CommitmentTransaction>, data: &ChannelMonitor<Signer>,
+	) {
+		let mut unrevoked_commitment_txs = self.unrevoked_commitment_txs.lock().unwrap();
+		let channel_state = unrevoked_commitment_txs.entry(data.channel_id()).or_default();
+		channel_state.extend(commitment_txs.into_iter().filter(|commitment_tx| {
+			commitment_tx.trust().revokeable_output_index().is_some()
+				|| !commitment_tx.nondust_htlcs().is_empty()
+		}));
+
+		while let Some(commitment_tx) = channel_state.front() {
+			if commitment_tx.commitment_number() < data.get_min_seen_secret() {
+				break;
+			}
+			let justice_tx = data
+				.sign_justice_tx_for_commitment(
+					commitment_tx,
+					FEERATE_FLOOR_SATS_PER_KW,
+					self.destination_script.clone(),
+				)
+				.unwrap();
+			let commitment_txid = commitment_tx.trust().txid();
+			let mut full_justice_txs = self.full_justice_txs.lock().unwrap();
+			let channel_justice_txs = full_justice_txs.entry(data.channel_id()).or_default();
+			assert!(channel_justice_txs.insert(commitment_txid, justice_tx).is_none());
+			channel_state.pop_front();
+		}
+	}
+
 	pub(crate) fn justice_tx(
 		&self, channel_id: ChannelId, commitment_txid: &Txid,
 	) -> Option<Transaction> {
@@ -760,6 +813,7 @@ impl<Signer: sign::ecdsa::EcdsaChannelSigner> Persist<Signer> for WatchtowerPers
 
 		let initial_counterparty_commitment_tx =
 			data.initial_counterparty_commitment_tx().expect("First and only call expects Some");
+		self.track_commitment_txs(vec![initial_counterparty_commitment_tx.clone()], data);
 		if let Some(justice_data) =
 			self.form_justice_data_from_commitment(&initial_counterparty_commitment_tx)
 		{
@@ -781,6 +835,7 @@ impl<Signer: sign::ecdsa::EcdsaChannelSigner> Persist<Signer> for WatchtowerPers
 
 		if let Some(update) = update {
 			let commitment_txs = data.counterparty_commitment_txs_from_update(update);
+			self.track_commitment_txs(commitment_txs.clone(), data);
 			let justice_datas = commitment_txs
 				.into_iter()
 				.filter_map(|commitment_tx| self.form_justice_data_from_commitment(&commitment_tx));
@@ -1125,19 +1180,27 @@ unsafe impl Send for TestStore {}
 
 pub struct TestBroadcaster {
 	pub txn_broadcasted: Mutex<Vec<Transaction>>,
+	pub broadcast_contexts: Mutex<Vec<(Txid, BroadcastContext)>>,
 	pub blocks: Arc<Mutex<Vec<(Block, u32)>>>,
 }
 
 impl TestBroadcaster {
 	pub fn new(network: Network) -> Self {
 		let txn_broadcasted = Mutex::new(Vec::new());
+		let broadcast_contexts = Mutex::new(Vec::new());
 		let blocks = Arc::new(Mutex::new(vec![(genesis_block(network), 0)]));
-		Self { txn_broadcasted, blocks }
+		Self { txn_broadcasted, broadcast_contexts, blocks }
 	}
 
 	pub fn with_blocks(blocks: Arc<Mutex<Vec<(Block, u32)>>>) -> Self {
 		let txn_broadcasted = Mutex::new(Vec::new());
-		Self { txn_broadcasted, blocks }
+		let broadcast_contexts = Mutex::new(Vec::new());
+		Self { txn_broadcasted, broadcast_contexts, blocks }
+	}
+
+	/// Returns the context each transaction was last broadcast with, keyed by txid.
+	pub fn broadcast_contexts(&self) -> Vec<(Txid, BroadcastContext)> {
+		self.broadcast_contexts.lock().unwrap().split_off(0)
 	}
 
 	pub fn txn_broadcast(&self) -> Vec<Transaction> {
@@ -1153,7 +1216,7 @@ impl TestBroadcaster {
 }
 
 impl chaininterface::BroadcasterInterface for TestBroadcaster {
-	fn broadcast_transactions(&self, txs: &[&Transaction]) {
+	fn broadcast_transactions(&self, txs: &[&Transaction], context: BroadcastContext) {
 		// Assert that any batch of transactions of length greater than 1 is sorted
 		// topologically, and is a `child-with-parents` package as defined in
 		// <https://github.com/bitcoin/bitcoin/blob/master/doc/policy/packages.md>.
@@ -1194,6 +1257,8 @@ impl chaininterface::BroadcasterInterface for TestBroadcaster {
 				}
 			}
 		}
+		let mut broadcast_contexts = self.broadcast_contexts.lock().unwrap();
+		broadcast_contexts.extend(txs.iter().map(|tx| (tx.compute_txid(), context)));
 		let owned_txs: Vec<Transaction> = txs.iter().map(|tx| (*tx).clone()).collect();
 		self.txn_broadcasted.lock().unwrap().extend(owned_txs);
 	}
@@ -1377,6 +1442,9 @@ impl msgs::ChannelMessageHandler for TestChannelMessageHandler {
 	fn handle_error(&self, _their_node_id: PublicKey, msg: &msgs::ErrorMessage) {
 		self.received_msg(wire::Message::Error(msg.clone()));
 	}
+	fn handle_warning(&self, _their_node_id: PublicKey, msg: &msgs::WarningMessage) {
+		self.received_msg(wire::Message::Warning(msg.clone()));
+	}
 
 	fn get_chain_hashes(&self) -> Option<Vec<ChainHash>> {
 		Some(vec![self.chain_hash])
@@ -1823,6 +1891,70 @@ impl NodeSigner for TestNodeSigner {
 	}
 }
 
+/// A [`TxBuilder`] which defers to [`SpecTxBuilder`], counting the commitment transactions built.
+pub struct TestTxBuilder {
+	pub commitment_txs_built: Arc<AtomicUsize>,
+}
+
+impl TxBuilder for TestTxBuilder {
+	fn get_next_commitment_stats(
+		&self, local: bool, is_outbound_from_holder: bool, channel_value_satoshis: u64,
+		value_to_holder_msat: u64, next_commitment_htlcs: &[HTLCAmountDirection],
+		addl_nondust_htlc_count: usize, feerate_per_kw: u32,
+		dust_exposure_limiting_feerate: Option<u32>, broadcaster_dust_limit_satoshis: u64,
+		channel_type: &ChannelTypeFeatures,
+	) -> Result<NextCommitmentStats, ()> {
+		SpecTxBuilder {}.get_next_commitment_stats(
+			local,
+			is_outbound_from_holder,
+			channel_value_satoshis,
+			value_to_holder_msat,
+			next_commitment_htlcs,
+			addl_nondust_htlc_count,
+			feerate_per_kw,
+			dust_exposure_limiting_feerate,
+			broadcaster_dust_limit_satoshis,
+			channel_type,
+		)
+	}
+	fn commit_tx_fee_sat(
+		&self, feerate_per_kw: u32, nondust_htlc_count: usize, channel_type: &ChannelTypeFeatures,
+	) -> u64 {
+		SpecTxBuilder {}.commit_tx_fee_sat(feerate_per_kw, nondust_htlc_count, channel_type)
+	}
+	fn subtract_non_htlc_outputs(
+		&self, is_outbound_from_holder: bool, value_to_self_after_htlcs: u64,
+		value_to_remote_after_htlcs: u64, channel_type: &ChannelTypeFeatures,
+	) -> (u64, u64) {
+		SpecTxBuilder {}.subtract_non_htlc_outputs(
+			is_outbound_from_holder,
+			value_to_self_after_htlcs,
+			value_to_remote_after_htlcs,
+			channel_type,
+		)
+	}
+	fn build_commitment_transaction(
+		&self, local: bool, commitment_number: u64, per_commitment_point: &PublicKey,
+		channel_parameters: &ChannelTransactionParameters, secp_ctx: &Secp256k1<secp256k1::All>,
+		value_to_self_msat: u64, htlcs_in_tx: Vec<HTLCOutputInCommitment>, feerate_per_kw: u32,
+		broadcaster_dust_limit_satoshis: u64, logger: &dyn Logger,
+	) -> (CommitmentTransaction, CommitmentStats) {
+		self.commitment_txs_built.fetch_add(1, Ordering::AcqRel);
+		SpecTxBuilder {}.build_commitment_transaction(
+			local,
+			commitment_number,
+			per_commitment_point,
+			channel_parameters,
+			secp_ctx,
+			value_to_self_msat,
+			htlcs_in_tx,
+			feerate_per_kw,
+			broadcaster_dust_limit_satoshis,
+			logger,
+		)
+	}
+}
+
 pub struct TestKeysInterface {
 	pub backing: DynKeysInterface,
 	pub override_random_bytes: Mutex<Option<[u8; 32]>>,
@@ -1833,6 +1965,7 @@ pub struct TestKeysInterface {
 	pub unavailable_signers_ops: Mutex<HashMap<[u8; 32], HashSet<SignerOp>>>,
 	pub next_signer_disabled_ops: Mutex<HashSet<SignerOp>>,
 	pub override_next_keys_id: Mutex<Option<[u8; 32]>>,
+	pub commitment_txs_built: Arc<AtomicUsize>,
 }
 
 impl std::fmt::Debug for TestKeysInterface {
@@ -1943,6 +2076,11 @@ impl SignerProvider for TestKeysInterface {
 			},
 		}
 	}
+
+	fn get_tx_builder(&self, _channel_keys_id: [u8; 32]) -> Box<dyn TxBuilder + Send + Sync> {
+		let commitment_txs_built = Arc::clone(&self.commitment_txs_built);
+		Box::new(TestTxBuilder { commitment_txs_built })
+	}
 }
 
 #[cfg(feature = "std")]
@@ -1988,6 +2126,7 @@ impl TestKeysInterface {
 			unavailable_signers_ops: Mutex::new(new_hash_map()),
 			next_signer_disabled_ops: Mutex::new(new_hash_set()),
 			override_next_keys_id: Mutex::new(None),
+			commitment_txs_built: Arc::new(AtomicUsize::new(0)),
 		}
 	}
 